@@ -1,5 +1,5 @@
 use crate::db::entity::{chat_file_uploads, chats, messages};
-use crate::models::share_link;
+use crate::models::{notification, share_grant, share_link};
 use chrono::{Duration, Utc};
 use ractor::{Actor, ActorProcessingErr, ActorRef};
 use sea_orm::{
@@ -15,6 +15,8 @@ pub enum CleanupWorkerMessage {
 pub struct CleanupWorkerArgs {
     pub db: DatabaseConnection,
     pub cleanup_archived_max_age_days: u32,
+    pub cleanup_expired_share_grants_max_age_days: u32,
+    pub cleanup_read_notifications_max_age_days: u32,
 }
 
 pub struct CleanupWorker;
@@ -108,6 +110,122 @@ pub async fn cleanup_archived_chats(
     Ok(())
 }
 
+/// Age after which a chat that is still provisional belongs to a submission that failed before
+/// its first message was saved.
+const PROVISIONAL_CHAT_MAX_AGE_HOURS: i64 = 1;
+
+pub async fn cleanup_provisional_chats(db: &DatabaseConnection) -> Result<(), ActorProcessingErr> {
+    let cutoff_date = Utc::now() - Duration::hours(PROVISIONAL_CHAT_MAX_AGE_HOURS);
+    tracing::info!(
+        "Cleaning up provisional chats created before {}",
+        cutoff_date
+    );
+
+    let stale_condition = Condition::all()
+        .add(chats::Column::Provisional.eq(true))
+        .add(chats::Column::CreatedAt.lt(cutoff_date));
+    let chat_ids: Vec<sea_orm::prelude::Uuid> = chats::Entity::find()
+        .filter(stale_condition.clone())
+        .all(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to query provisional chats for cleanup: {}", e);
+            ActorProcessingErr::from(e)
+        })?
+        .into_iter()
+        .map(|chat| chat.id)
+        .collect();
+
+    if chat_ids.is_empty() {
+        tracing::info!("No stale provisional chats to delete.");
+        return Ok(());
+    }
+
+    let txn = db.begin().await.map_err(|e| {
+        tracing::error!("Failed to begin transaction for cleanup: {}", e);
+        ActorProcessingErr::from(e)
+    })?;
+
+    // Files may have been uploaded to the chat before the submission failed. As for archived
+    // chats, only the relations are deleted, and the file uploads are preserved.
+    chat_file_uploads::Entity::delete_many()
+        .filter(chat_file_uploads::Column::ChatId.is_in(chat_ids.clone()))
+        .exec(&txn)
+        .await
+        .map_err(|e| {
+            tracing::error!(
+                "Failed to delete chat-file upload relations for cleanup: {}",
+                e
+            );
+            ActorProcessingErr::from(e)
+        })?;
+
+    // The chat stops being provisional with its first message, which may have been saved in the
+    // meantime.
+    let delete_result = chats::Entity::delete_many()
+        .filter(chats::Column::Id.is_in(chat_ids))
+        .filter(stale_condition)
+        .exec(&txn)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to delete provisional chats for cleanup: {}", e);
+            ActorProcessingErr::from(e)
+        })?;
+
+    txn.commit().await.map_err(|e| {
+        tracing::error!("Failed to commit transaction for cleanup: {}", e);
+        ActorProcessingErr::from(e)
+    })?;
+
+    tracing::info!(
+        "Deleted {} stale provisional chats.",
+        delete_result.rows_affected
+    );
+    Ok(())
+}
+
+pub async fn cleanup_expired_share_grants(
+    db: &DatabaseConnection,
+    max_age_days: u32,
+) -> Result<(), ActorProcessingErr> {
+    let cutoff_date = Utc::now() - Duration::days(max_age_days as i64);
+    tracing::info!(
+        "Cleaning up share grants that expired before {}",
+        cutoff_date
+    );
+
+    let deleted_count = share_grant::delete_expired_share_grants(db, cutoff_date.into())
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to delete expired share grants for cleanup: {}", e);
+            ActorProcessingErr::from(e.to_string())
+        })?;
+
+    tracing::info!("Deleted {} expired share grants.", deleted_count);
+    Ok(())
+}
+
+pub async fn cleanup_read_notifications(
+    db: &DatabaseConnection,
+    max_age_days: u32,
+) -> Result<(), ActorProcessingErr> {
+    let cutoff_date = Utc::now() - Duration::days(max_age_days as i64);
+    tracing::info!(
+        "Cleaning up notifications that were read before {}",
+        cutoff_date
+    );
+
+    let deleted_count = notification::delete_read_notifications(db, cutoff_date)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to delete read notifications for cleanup: {}", e);
+            ActorProcessingErr::from(e.to_string())
+        })?;
+
+    tracing::info!("Deleted {} read notifications.", deleted_count);
+    Ok(())
+}
+
 impl Actor for CleanupWorker {
     type Msg = CleanupWorkerMessage;
     type State = CleanupWorkerArgs;
@@ -130,6 +248,17 @@ impl Actor for CleanupWorker {
         match message {
             CleanupWorkerMessage::Tick => {
                 cleanup_archived_chats(&state.db, state.cleanup_archived_max_age_days).await?;
+                cleanup_provisional_chats(&state.db).await?;
+                cleanup_expired_share_grants(
+                    &state.db,
+                    state.cleanup_expired_share_grants_max_age_days,
+                )
+                .await?;
+                cleanup_read_notifications(
+                    &state.db,
+                    state.cleanup_read_notifications_max_age_days,
+                )
+                .await?;
             }
         }
         Ok(())
@@ -1,34 +1,343 @@
 use crate::actors::supervisor::WorkerSupervisor;
 use crate::config::AppConfig;
+use crate::state::AppState;
+use async_trait::async_trait;
+use eyre::{Report, eyre};
+use futures::future::BoxFuture;
 use ractor::Actor;
-use sea_orm::DatabaseConnection;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Interval in which unready dependencies are checked again.
+const DEPENDENCY_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// When an actor is started, relative to the startup of the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StartupOrder {
+    /// Started during [`ActorManager::startup`], which waits for it.
+    Immediate,
+    /// Started in the background once the database is reachable.
+    AfterHealthCheck,
+    /// Started in the background once the server has received its first request.
+    AfterFirstRequest,
+}
+
+/// A condition that has to hold before an actor is started.
+#[async_trait]
+pub trait ActorDependency<S = AppState>: Send + Sync {
+    /// Name of the dependency, used in log messages.
+    fn name(&self) -> String;
+
+    async fn is_ready(&self, app_state: &S) -> bool;
+}
+
+/// Ready once the database answers a ping.
+pub struct DatabaseHealthCheck;
+
+#[async_trait]
+impl ActorDependency for DatabaseHealthCheck {
+    fn name(&self) -> String {
+        "database".to_string()
+    }
+
+    async fn is_ready(&self, app_state: &AppState) -> bool {
+        app_state.db.ping().await.is_ok()
+    }
+}
+
+/// Ready once the server has received its first request.
+pub struct FirstRequestReceived(Arc<AtomicBool>);
+
+#[async_trait]
+impl ActorDependency for FirstRequestReceived {
+    fn name(&self) -> String {
+        "first_request".to_string()
+    }
+
+    async fn is_ready(&self, _app_state: &AppState) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+type StartFn = Arc<dyn Fn(AppState) -> BoxFuture<'static, Result<(), Report>> + Send + Sync>;
+
+/// An actor managed by the [`ActorManager`], together with what it needs before it can start.
+#[derive(Clone)]
+pub struct ActorDefinition {
+    pub name: String,
+    pub startup_order: StartupOrder,
+    pub dependencies: Vec<Arc<dyn ActorDependency>>,
+    start: StartFn,
+}
+
+impl ActorDefinition {
+    pub fn new<F>(name: impl Into<String>, startup_order: StartupOrder, start: F) -> Self
+    where
+        F: Fn(AppState) -> BoxFuture<'static, Result<(), Report>> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            startup_order,
+            dependencies: Vec::new(),
+            start: Arc::new(start),
+        }
+    }
+
+    pub fn with_dependency(mut self, dependency: impl ActorDependency + 'static) -> Self {
+        self.dependencies.push(Arc::new(dependency));
+        self
+    }
+}
+
+impl fmt::Debug for ActorDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ActorDefinition")
+            .field("name", &self.name)
+            .field("startup_order", &self.startup_order)
+            .field(
+                "dependencies",
+                &self
+                    .dependencies
+                    .iter()
+                    .map(|dependency| dependency.name())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
 
 #[derive(Clone, Debug)]
-pub struct ActorManager;
+pub struct ActorManager {
+    definitions: Arc<Vec<ActorDefinition>>,
+    startup_timeout: Duration,
+    first_request: Arc<AtomicBool>,
+}
 
 impl ActorManager {
-    pub async fn new(db: DatabaseConnection, config: AppConfig) -> Self {
-        Self::new_with_name(db, config, Some("worker_supervisor".to_string())).await
-    }
-
-    pub async fn new_with_name(
-        db: DatabaseConnection,
-        config: AppConfig,
-        supervisor_name: Option<String>,
-    ) -> Self {
-        let args = (db, config);
-        // Spawn the top-level supervisor
-        let (_supervisor, supervisor_handle) =
-            Actor::spawn(supervisor_name, WorkerSupervisor, args)
-                .await
-                .expect("Failed to spawn WorkerSupervisor");
-
-        // We'll spawn the supervisor handle in a background task to ensure it's not dropped
-        // and the actor system keeps running.
+    pub fn new(config: &AppConfig) -> Self {
+        Self::new_with_name(config, Some("worker_supervisor".to_string()))
+    }
+
+    pub fn new_with_name(config: &AppConfig, supervisor_name: Option<String>) -> Self {
+        let worker_supervisor = ActorDefinition::new(
+            "worker_supervisor",
+            StartupOrder::Immediate,
+            move |app_state| {
+                let supervisor_name = supervisor_name.clone();
+                Box::pin(async move {
+                    let args = (app_state.db.clone(), app_state.config.clone());
+                    // Spawn the top-level supervisor
+                    let (_supervisor, supervisor_handle) =
+                        Actor::spawn(supervisor_name, WorkerSupervisor, args)
+                            .await
+                            .map_err(|e| eyre!("Failed to spawn WorkerSupervisor: {e}"))?;
+
+                    // We'll spawn the supervisor handle in a background task to ensure it's not dropped
+                    // and the actor system keeps running.
+                    tokio::spawn(async move {
+                        supervisor_handle.await.unwrap();
+                    });
+                    Ok(())
+                })
+            },
+        )
+        .with_dependency(DatabaseHealthCheck);
+
+        Self {
+            definitions: Arc::new(vec![worker_supervisor]),
+            startup_timeout: Duration::from_secs(config.actor_startup_timeout_seconds),
+            first_request: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Record that the server has received a request, which releases actors
+    /// started [`StartupOrder::AfterFirstRequest`].
+    pub fn mark_first_request(&self) {
+        self.first_request.store(true, Ordering::Relaxed);
+    }
+
+    /// Start all managed actors.
+    ///
+    /// `Immediate` actors are started one after another in definition order, and this
+    /// waits until each of them has started. All other actors are started in the
+    /// background once their startup order allows it. An actor that does not become
+    /// ready within the configured timeout, or fails to start, is logged and skipped.
+    pub async fn startup(app_state: &AppState) {
+        let manager = &app_state.actor_manager;
+        let (immediate, deferred) = startup_plan(&manager.definitions);
+
+        for index in immediate {
+            manager
+                .start_actor(&manager.definitions[index], app_state)
+                .await;
+        }
+
+        if deferred.is_empty() {
+            return;
+        }
+        let app_state = app_state.clone();
         tokio::spawn(async move {
-            supervisor_handle.await.unwrap();
+            let manager = &app_state.actor_manager;
+            for index in deferred {
+                let definition = &manager.definitions[index];
+                if let Some(gate) = manager.order_gate(definition.startup_order) {
+                    wait_until_ready(&[gate], &app_state).await;
+                }
+                manager.start_actor(definition, &app_state).await;
+            }
         });
+    }
+
+    /// The dependency implied by the startup order of an actor.
+    fn order_gate(&self, startup_order: StartupOrder) -> Option<Arc<dyn ActorDependency>> {
+        match startup_order {
+            StartupOrder::Immediate => None,
+            StartupOrder::AfterHealthCheck => Some(Arc::new(DatabaseHealthCheck)),
+            StartupOrder::AfterFirstRequest => {
+                Some(Arc::new(FirstRequestReceived(self.first_request.clone())))
+            }
+        }
+    }
+
+    async fn start_actor(&self, definition: &ActorDefinition, app_state: &AppState) {
+        let started = tokio::time::timeout(self.startup_timeout, async {
+            wait_until_ready(&definition.dependencies, app_state).await;
+            (definition.start)(app_state.clone()).await
+        })
+        .await;
+
+        match started {
+            Ok(Ok(())) => {
+                tracing::info!(actor = %definition.name, "Started actor");
+            }
+            Ok(Err(error)) => {
+                tracing::warn!(actor = %definition.name, %error, "Failed to start actor");
+            }
+            Err(_) => {
+                let pending_dependency = first_unready(&definition.dependencies, app_state).await;
+                tracing::warn!(
+                    actor = %definition.name,
+                    pending_dependency = ?pending_dependency,
+                    timeout_secs = self.startup_timeout.as_secs(),
+                    "Actor did not start within the startup timeout"
+                );
+            }
+        }
+    }
+}
+
+/// Split actor definitions into the indices of the `Immediate` ones, in definition
+/// order, and the indices of all others, ordered by startup order.
+fn startup_plan(definitions: &[ActorDefinition]) -> (Vec<usize>, Vec<usize>) {
+    let (immediate, mut deferred): (Vec<usize>, Vec<usize>) = (0..definitions.len())
+        .partition(|&index| definitions[index].startup_order == StartupOrder::Immediate);
+    deferred.sort_by_key(|&index| definitions[index].startup_order);
+    (immediate, deferred)
+}
+
+/// Name of the first dependency that is not ready yet, if any.
+async fn first_unready<S: Sync>(
+    dependencies: &[Arc<dyn ActorDependency<S>>],
+    app_state: &S,
+) -> Option<String> {
+    for dependency in dependencies {
+        if !dependency.is_ready(app_state).await {
+            return Some(dependency.name());
+        }
+    }
+    None
+}
+
+async fn wait_until_ready<S: Sync>(dependencies: &[Arc<dyn ActorDependency<S>>], app_state: &S) {
+    while first_unready(dependencies, app_state).await.is_some() {
+        tokio::time::sleep(DEPENDENCY_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn definition(name: &str, startup_order: StartupOrder) -> ActorDefinition {
+        ActorDefinition::new(name, startup_order, |_| Box::pin(async { Ok(()) }))
+    }
+
+    /// Becomes ready after it has been checked `checks_until_ready` times.
+    struct EventuallyReady {
+        checks: AtomicUsize,
+        checks_until_ready: usize,
+    }
+
+    impl EventuallyReady {
+        fn new(checks_until_ready: usize) -> Arc<Self> {
+            Arc::new(Self {
+                checks: AtomicUsize::new(0),
+                checks_until_ready,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl ActorDependency<()> for EventuallyReady {
+        fn name(&self) -> String {
+            format!("ready_after_{}", self.checks_until_ready)
+        }
+
+        async fn is_ready(&self, _app_state: &()) -> bool {
+            self.checks.fetch_add(1, Ordering::Relaxed) >= self.checks_until_ready
+        }
+    }
+
+    #[test]
+    fn plans_immediate_actors_first_in_definition_order() {
+        let definitions = vec![
+            definition("a", StartupOrder::AfterFirstRequest),
+            definition("b", StartupOrder::Immediate),
+            definition("c", StartupOrder::AfterHealthCheck),
+            definition("d", StartupOrder::Immediate),
+            definition("e", StartupOrder::AfterHealthCheck),
+        ];
+
+        let (immediate, deferred) = startup_plan(&definitions);
+        assert_eq!(immediate, vec![1, 3]);
+        assert_eq!(deferred, vec![2, 4, 0]);
+    }
+
+    #[tokio::test]
+    async fn reports_first_unready_dependency() {
+        let dependencies: Vec<Arc<dyn ActorDependency<()>>> =
+            vec![EventuallyReady::new(0), EventuallyReady::new(5)];
+        assert_eq!(
+            first_unready(&dependencies, &()).await,
+            Some("ready_after_5".to_string())
+        );
+        assert_eq!(first_unready(&[], &()).await, None);
+    }
+
+    #[tokio::test]
+    async fn waits_until_dependencies_are_ready() {
+        let dependency = EventuallyReady::new(2);
+        let dependencies: Vec<Arc<dyn ActorDependency<()>>> = vec![dependency.clone()];
+
+        tokio::time::timeout(Duration::from_secs(5), wait_until_ready(&dependencies, &()))
+            .await
+            .expect("Dependency should become ready");
+        assert_eq!(dependency.checks.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn times_out_on_dependency_that_never_becomes_ready() {
+        let dependencies: Vec<Arc<dyn ActorDependency<()>>> =
+            vec![EventuallyReady::new(usize::MAX)];
 
-        Self
+        let result = tokio::time::timeout(
+            Duration::from_millis(100),
+            wait_until_ready(&dependencies, &()),
+        )
+        .await;
+        assert!(result.is_err());
     }
 }
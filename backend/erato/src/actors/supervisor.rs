@@ -29,6 +29,9 @@ impl Actor for WorkerSupervisor {
         let args = CleanupWorkerArgs {
             db: db.clone(),
             cleanup_archived_max_age_days: config.cleanup_archived_max_age_days,
+            cleanup_expired_share_grants_max_age_days: config
+                .cleanup_expired_share_grants_max_age_days,
+            cleanup_read_notifications_max_age_days: config.cleanup_read_notifications_max_age_days,
         };
 
         // Start the cron manager
@@ -5,11 +5,13 @@ use config::builder::DefaultState;
 use config::{Config, ConfigBuilder, ConfigError, Environment};
 use eyre::{OptionExt, Report, eyre};
 use facet::Facet;
+use ipnet::IpNet;
 use regex::Regex;
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize, de};
 use std::collections::HashMap;
 use std::fmt;
+use std::net::IpAddr;
 use std::ops::Deref;
 use utoipa::ToSchema;
 
@@ -159,11 +161,16 @@ pub enum PromptSourceSpecification {
     },
 }
 
-#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Facet)]
+#[derive(Debug, Default, Deserialize, PartialEq, Clone, Facet)]
 #[facet(untagged)]
 pub struct I18nConfig {
     #[serde(default)]
     pub language: I18nLanguageConfig,
+
+    // Detection of the language a user message is written in, used as the
+    // response-language hint for the generation.
+    #[serde(default)]
+    pub message_language_detection: MessageLanguageDetectionConfig,
 }
 
 impl I18nConfig {
@@ -172,10 +179,71 @@ impl I18nConfig {
             return Err(eyre!("i18n.language.default_language cannot be empty"));
         }
 
+        let min_confidence = self.message_language_detection.min_confidence;
+        if !(0.0..=1.0).contains(&min_confidence) {
+            return Err(eyre!(
+                "i18n.message_language_detection.min_confidence must be between 0.0 and 1.0, got: {}",
+                min_confidence
+            ));
+        }
+
         Ok(())
     }
 }
 
+#[derive(Debug, Deserialize, PartialEq, Clone, Facet)]
+pub struct MessageLanguageDetectionConfig {
+    // If true, the language of submitted user messages is detected and takes
+    // precedence over the profile language when answering.
+    // Defaults to `true`.
+    #[serde(default = "default_message_language_detection_enabled")]
+    pub enabled: bool,
+
+    // Messages with fewer characters (after trimming) are not analyzed, as
+    // detection on short texts is unreliable.
+    // Defaults to `20`.
+    #[serde(default = "default_message_language_detection_min_chars")]
+    pub min_chars: usize,
+
+    // Only the first `max_bytes` bytes of a message are analyzed.
+    // Defaults to `4096`.
+    #[serde(default = "default_message_language_detection_max_bytes")]
+    pub max_bytes: usize,
+
+    // The confidence (between 0.0 and 1.0) a detected language must exceed
+    // to be used as the response language.
+    // Defaults to `0.5`.
+    #[serde(default = "default_message_language_detection_min_confidence")]
+    pub min_confidence: f64,
+}
+
+impl Default for MessageLanguageDetectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_message_language_detection_enabled(),
+            min_chars: default_message_language_detection_min_chars(),
+            max_bytes: default_message_language_detection_max_bytes(),
+            min_confidence: default_message_language_detection_min_confidence(),
+        }
+    }
+}
+
+fn default_message_language_detection_enabled() -> bool {
+    true
+}
+
+fn default_message_language_detection_min_chars() -> usize {
+    20
+}
+
+fn default_message_language_detection_max_bytes() -> usize {
+    4096
+}
+
+fn default_message_language_detection_min_confidence() -> f64 {
+    0.5
+}
+
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
 pub struct I18nLanguageConfig {
     // Ordered list of language detection sources to try.
@@ -345,8 +413,9 @@ fn validate_prompt_injection_filter_config(
 
 #[derive(Debug, Default, Deserialize, PartialEq, Clone, Facet)]
 pub struct AppConfig {
-    // A opaque marker to signify the environment. This may be forwarded to diagnostic/observability tools to signify the environment,
-    // but is never parsed/interpreted by the application to trigger environment-specific behavior.
+    // A marker to signify the environment. This may be forwarded to diagnostic/observability tools to signify the environment.
+    // The environment-specific behaviors are that seeding of demo data (`erato seed`, `POST /admin/seed`) is refused in `production`,
+    // and that the OpenAPI spec advertises the listener address as server in `development` if no `http.public_base_url` is configured.
     #[facet(erato_config::hide_in_docs(hidden = true))]
     pub environment: String,
     // The HTTP host to listen on.
@@ -357,9 +426,35 @@ pub struct AppConfig {
     // Defaults to `3130`.
     #[facet(erato_config::hide_in_docs(hidden = true))]
     pub http_port: i32,
+    // The HTTP host of the internal listener.
+    // Defaults to `http_host`. Only used if `internal_http_port` is set.
+    pub internal_http_host: Option<String>,
+    // The HTTP port of the internal listener.
+    //
+    // If set, operational routes (health check, admin API, profiling endpoints) are only served
+    // on a second listener bound to this port, and respond with 404 on the public listener.
+    // This allows restricting their exposure to the cluster network.
+    // If unset, all routes are served on the public listener.
+    pub internal_http_port: Option<i32>,
     #[facet(sensitive)]
     pub database_url: SecretConfigString,
     pub chat_providers: Option<ChatProvidersConfig>,
+    // Aliases of chat provider IDs, mapping a provider ID that is no longer configured to the ID
+    // of a configured provider, e.g. `{ "old-primary" = "primary" }`.
+    //
+    // Chats and messages that reference an aliased ID are transparently served by the provider it
+    // maps to, e.g. when regenerating a message or showing the model of a chat.
+    #[serde(default)]
+    pub chat_provider_aliases: HashMap<String, String>,
+    // Groups of chat providers that serve the same model, e.g. deployments of a model in several
+    // regions, to spread the load across them.
+    //
+    // The keys of the map act as the IDs of the groups. A group is offered to users as a single
+    // model under its ID, and can be used wherever the ID of a chat provider is accepted, e.g. in
+    // `chat_providers.priority_order` and `model_permissions`. Every generation is served by one
+    // member of the group, selected according to the group's `strategy`.
+    #[serde(default)]
+    pub chat_provider_groups: HashMap<String, ChatProviderGroupConfig>,
     // A list of file storage providers to use.
     //
     // The keys of the map act as the IDs for the providers.
@@ -368,6 +463,9 @@ pub struct AppConfig {
     pub file_storage_providers: HashMap<String, FileStorageProviderConfig>,
     // The default file storage provider to use.
     pub default_file_storage_provider: Option<String>,
+    // The ways users can add files, i.e. uploading them or linking them from other sources.
+    #[serde(default)]
+    pub file_uploads: FileUploadsConfig,
 
     // A list of MCP servers that may be used in conjunction with the LLM providers.
     #[serde(default)]
@@ -379,6 +477,10 @@ pub struct AppConfig {
     #[serde(default)]
     pub server: ServerConfig,
 
+    // Settings of the public HTTP listener.
+    #[serde(default)]
+    pub http: HttpConfig,
+
     #[serde(default)]
     pub frontend: FrontendConfig,
 
@@ -398,6 +500,22 @@ pub struct AppConfig {
     #[serde(default)]
     pub guardrails: GuardrailsConfig,
 
+    // Protections against prompt injection through file contents and tool outputs.
+    #[serde(default)]
+    pub security: SecurityConfig,
+
+    // Limits for exporting chat transcripts (e.g. as PDF).
+    #[serde(default)]
+    pub chat_export: ChatExportConfig,
+
+    // Moderation of user messages before they are passed to the LLM.
+    #[serde(default)]
+    pub moderation: ModerationConfig,
+
+    // In-memory data of the policy engine.
+    #[serde(default)]
+    pub policy_engine: PolicyEngineConfig,
+
     // Model permissions configuration for controlling access to chat providers based on user attributes.
     #[serde(default)]
     pub model_permissions: ModelPermissionsConfig,
@@ -426,6 +544,10 @@ pub struct AppConfig {
     #[serde(default)]
     pub prompt_optimizer: PromptOptimizerConfig,
 
+    // OpenAI-compatible chat completions API under `/compat/v1`.
+    #[serde(default)]
+    pub compat_api: CompatApiConfig,
+
     // User preferences feature configuration.
     #[serde(default)]
     pub user_preferences: UserPreferencesConfig,
@@ -453,6 +575,29 @@ pub struct AppConfig {
     #[serde(default)]
     pub chat_sharing: ChatSharingConfig,
 
+    // Claim of the ID token that holds the ID of the organization (tenant) of the user.
+    // If set, users, chats, assistants, file uploads and share grants are scoped to the
+    // organization, and users can't access any data of other organizations.
+    // Defaults to no claim, in which case all users are part of the same organization.
+    #[serde(default)]
+    pub organization_claim: Option<String>,
+
+    // Settings per organization ID, as found in the `organization_claim`.
+    #[serde(default)]
+    pub organizations: HashMap<String, OrganizationConfig>,
+
+    // Administration configuration.
+    #[serde(default)]
+    pub admin: AdminConfig,
+
+    // How the user of a request is authenticated.
+    #[serde(default)]
+    pub auth: AuthConfig,
+
+    // Debugging aids for QA and prompt engineering.
+    #[serde(default)]
+    pub debug: DebugConfig,
+
     // Caches configuration for file contents and token counts.
     #[serde(default)]
     pub caches: CachesConfig,
@@ -461,11 +606,27 @@ pub struct AppConfig {
     #[serde(default)]
     pub file_processor: FileProcessorConfig,
 
+    // Configuration for how attached files are presented to the model.
+    #[serde(default)]
+    pub chat: ChatConfig,
+
     // Generation status tracking (persisted per-chat generation state surfaced
     // in the chat list).
     #[serde(default)]
     pub generation_status: GenerationStatusConfig,
 
+    // Streaming of generations over a WebSocket, as an alternative to server-sent events.
+    #[serde(default)]
+    pub websocket_streaming: WebSocketStreamingConfig,
+
+    // Messages that users schedule to be submitted at a later time.
+    #[serde(default)]
+    pub scheduled_messages: ScheduledMessagesConfig,
+
+    // Anonymous reporting of aggregate usage numbers.
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
     // Audio transcription feature configuration.
     #[serde(default)]
     pub audio_transcription: AudioTranscriptionConfig,
@@ -487,6 +648,23 @@ pub struct AppConfig {
     // Defaults to 30.
     #[facet(erato_config::needs_scoped_replacement(enabled = true))]
     pub cleanup_archived_max_age_days: u32,
+    // Number of days after their expiry after which expired share grants should be deleted by the
+    // cleanup worker. Expired share grants no longer grant access regardless of this setting.
+    // Only has an effect if `cleanup_enabled` is `true`.
+    // Defaults to 30.
+    #[facet(erato_config::needs_scoped_replacement(enabled = true))]
+    pub cleanup_expired_share_grants_max_age_days: u32,
+    // Number of days after they were read after which notifications should be deleted by the
+    // cleanup worker. Unread notifications are kept.
+    // Only has an effect if `cleanup_enabled` is `true`.
+    // Defaults to 30.
+    #[facet(erato_config::needs_scoped_replacement(enabled = true))]
+    pub cleanup_read_notifications_max_age_days: u32,
+
+    // Maximum number of seconds to wait for each background actor to become ready and start
+    // during server startup. Actors that take longer are skipped with a warning.
+    // Defaults to 30.
+    pub actor_startup_timeout_seconds: u64,
 
     // **Deprecated**: Please use `chat_providers` instead for multiple provider support and better flexibility.
     #[facet(erato_config::hide_in_docs(hidden = true))]
@@ -593,6 +771,9 @@ impl AppConfig {
             .set_default("frontend.web_frontend_bundle_path", "./public")?
             .set_default("cleanup_enabled", false)?
             .set_default("cleanup_archived_max_age_days", 30)?
+            .set_default("cleanup_expired_share_grants_max_age_days", 30)?
+            .set_default("cleanup_read_notifications_max_age_days", 30)?
+            .set_default("actor_startup_timeout_seconds", 30)?
             .set_default("logging.format", "plain")?
             .set_default("audio_transcription.enabled", false)?
             .set_default("audio_transcription.max_recording_duration_seconds", 1200)?
@@ -763,6 +944,10 @@ impl AppConfig {
             panic!("Invalid Microsoft Office integration configuration: {}", e);
         }
 
+        if let Err(e) = config.validate_internal_listener() {
+            panic!("Invalid internal listener configuration: {}", e);
+        }
+
         // Validate Prometheus configuration
         if let Err(e) = config.integrations.prometheus.validate(config.http_port) {
             panic!("Invalid Prometheus configuration: {}", e);
@@ -776,6 +961,10 @@ impl AppConfig {
             panic!("Invalid server configuration: {}", e);
         }
 
+        if let Err(e) = config.http.validate() {
+            panic!("Invalid HTTP configuration: {}", e);
+        }
+
         // Migrate single chat_provider to new chat_providers structure and handle Azure OpenAI migration
         config = config.migrate_chat_providers();
         config.action_facets.inject_builtin_ms_office_addin_facets();
@@ -805,10 +994,67 @@ impl AppConfig {
             panic!("Invalid facet permissions configuration: {}", e);
         }
 
+        if let Err(e) = config.security.validate() {
+            panic!("Invalid security configuration: {}", e);
+        }
+
+        if let Err(e) = config.guardrails.validate() {
+            panic!("Invalid guardrails configuration: {}", e);
+        }
+
+        if let Err(e) = config.chat_export.validate() {
+            panic!("Invalid chat export configuration: {}", e);
+        }
+
+        if let Err(e) = config.moderation.validate() {
+            panic!("Invalid moderation configuration: {}", e);
+        }
+
+        if let Err(e) = config.chat.validate() {
+            panic!("Invalid chat configuration: {}", e);
+        }
+
+        if let Err(e) = config.telemetry.usage_reporting.validate() {
+            panic!("Invalid usage reporting configuration: {}", e);
+        }
+
+        if let Err(e) = config.file_uploads.validate(&config.file_storage_providers) {
+            panic!("Invalid file uploads configuration: {}", e);
+        }
+
+        if let Err(e) = config
+            .compat_api
+            .validate(&config.chat_provider_and_group_ids())
+        {
+            panic!("Invalid compat API configuration: {}", e);
+        }
+
+        if let Err(e) = config.debug.validate(config.is_production_environment()) {
+            panic!("Invalid debug configuration: {}", e);
+        }
+
+        if let Err(e) = config.validate_organizations() {
+            panic!("Invalid organizations configuration: {}", e);
+        }
+
+        if let Err(e) = config.auth.validate() {
+            panic!("Invalid auth configuration: {}", e);
+        }
+
         // Validate budget configuration
         if let Err(e) = config.budget.validate() {
             panic!("Invalid budget configuration: {}", e);
         }
+        if let Some(chat_providers) = &config.chat_providers
+            && let Err(e) = config.budget.validate_pricing_currencies(
+                chat_providers
+                    .providers
+                    .values()
+                    .filter_map(|provider| provider.model_capabilities.cost_currency.as_ref()),
+            )
+        {
+            panic!("Invalid budget configuration: {}", e);
+        }
 
         // Validate assistants configuration
         if let Err(e) = config.assistants.validate() {
@@ -1010,6 +1256,7 @@ impl AppConfig {
                     all_providers: AllChatProvidersConfig::default(),
                     providers,
                     summary: SummaryConfig::default(),
+                    user_group_defaults: HashMap::new(),
                 });
 
                 // Clear the old chat_provider after migration
@@ -1073,7 +1320,9 @@ impl AppConfig {
 
             // Warn if a provider_id in priority order is not configured
             for provider_id in &chat_providers.priority_order {
-                if !chat_providers.providers.contains_key(provider_id) {
+                if !chat_providers.providers.contains_key(provider_id)
+                    && !self.chat_provider_groups.contains_key(provider_id)
+                {
                     tracing::warn!(
                         "Provider '{}' in priority order is not configured in providers",
                         provider_id
@@ -1081,9 +1330,15 @@ impl AppConfig {
                 }
             }
 
-            // Warn if a configured provider is not in priority order
+            // Warn if a configured provider is not in priority order. Members of groups are
+            // usually only offered through their group.
             for provider_id in chat_providers.providers.keys() {
-                if !chat_providers.priority_order.contains(provider_id) {
+                if !chat_providers.priority_order.contains(provider_id)
+                    && !self
+                        .chat_provider_groups
+                        .values()
+                        .any(|group| group.members.contains(provider_id))
+                {
                     tracing::warn!(
                         "Provider '{}' is configured but not in priority order",
                         provider_id
@@ -1091,6 +1346,82 @@ impl AppConfig {
                 }
             }
 
+            for (alias, provider_id) in &self.chat_provider_aliases {
+                if chat_providers.providers.contains_key(alias) {
+                    return Err(eyre!(
+                        "Chat provider alias '{}' must not be the ID of a configured provider",
+                        alias
+                    ));
+                }
+                if !chat_providers.providers.contains_key(provider_id) {
+                    return Err(eyre!(
+                        "Chat provider alias '{}' refers to provider '{}', which is not configured",
+                        alias,
+                        provider_id
+                    ));
+                }
+            }
+
+            for (group_id, group) in &self.chat_provider_groups {
+                if chat_providers.providers.contains_key(group_id)
+                    || self.chat_provider_aliases.contains_key(group_id)
+                {
+                    return Err(eyre!(
+                        "Chat provider group '{}' must not use the ID of a configured provider or alias",
+                        group_id
+                    ));
+                }
+                if group.members.is_empty() {
+                    return Err(eyre!(
+                        "Chat provider group '{}' must have at least one member",
+                        group_id
+                    ));
+                }
+                for member_id in &group.members {
+                    if !chat_providers.providers.contains_key(member_id) {
+                        return Err(eyre!(
+                            "Chat provider group '{}' has member '{}', which is not a configured provider",
+                            group_id,
+                            member_id
+                        ));
+                    }
+                }
+                for member_id in group.weights.keys() {
+                    if !group.members.contains(member_id) {
+                        return Err(eyre!(
+                            "Chat provider group '{}' has a weight for '{}', which is not a member of the group",
+                            group_id,
+                            member_id
+                        ));
+                    }
+                }
+                if group.strategy == ChatProviderGroupStrategy::Weighted
+                    && group
+                        .members
+                        .iter()
+                        .all(|member_id| group.weight(member_id) == 0)
+                {
+                    return Err(eyre!(
+                        "Chat provider group '{}' uses the `weighted` strategy, but all of its members have a weight of 0",
+                        group_id
+                    ));
+                }
+            }
+
+            // Warn if a group default is not configured, as it can never be recommended
+            for (user_group, provider_id) in &chat_providers.user_group_defaults {
+                if !chat_providers.providers.contains_key(provider_id)
+                    && !self.chat_provider_aliases.contains_key(provider_id)
+                    && !self.chat_provider_groups.contains_key(provider_id)
+                {
+                    tracing::warn!(
+                        "Provider '{}', the default of user group '{}', is not configured",
+                        provider_id,
+                        user_group
+                    );
+                }
+            }
+
             // Validate individual provider configurations
             for (provider_id, provider_config) in &chat_providers.providers {
                 if let Err(e) = provider_config.validate() {
@@ -1150,6 +1481,66 @@ impl AppConfig {
         chat_providers.all_providers.guardrails.clone()
     }
 
+    /// Validates that the per-organization settings only refer to configured resources.
+    pub fn validate_organizations(&self) -> Result<(), eyre::Report> {
+        if !self.organizations.is_empty() && self.organization_claim.is_none() {
+            return Err(eyre!(
+                "`organizations` are configured, but no `organization_claim` is set"
+            ));
+        }
+        for (organization_id, organization) in &self.organizations {
+            for chat_provider_id in organization.chat_providers.iter().flatten() {
+                let is_configured = self
+                    .chat_provider_and_group_ids()
+                    .contains(&chat_provider_id.as_str());
+                if !is_configured {
+                    return Err(eyre!(
+                        "Organization '{}' refers to chat provider '{}', which is not configured",
+                        organization_id,
+                        chat_provider_id
+                    ));
+                }
+            }
+            for facet_id in organization.facets.iter().flatten() {
+                if !self.experimental_facets.facets.contains_key(facet_id) {
+                    return Err(eyre!(
+                        "Organization '{}' refers to facet '{}', which is not configured",
+                        organization_id,
+                        facet_id
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a chat provider is available to the users of an organization.
+    ///
+    /// Users without an organization, and organizations without `chat_providers`, can use all
+    /// chat providers.
+    pub fn organization_allows_chat_provider(
+        &self,
+        organization_id: Option<&str>,
+        chat_provider_id: &str,
+    ) -> bool {
+        organization_id
+            .and_then(|organization_id| self.organizations.get(organization_id))
+            .and_then(|organization| organization.chat_providers.as_ref())
+            .is_none_or(|chat_provider_ids| {
+                chat_provider_ids.iter().any(|id| id == chat_provider_id)
+            })
+    }
+
+    /// Whether a facet is available to the users of an organization.
+    ///
+    /// Users without an organization, and organizations without `facets`, can use all facets.
+    pub fn organization_allows_facet(&self, organization_id: Option<&str>, facet_id: &str) -> bool {
+        organization_id
+            .and_then(|organization_id| self.organizations.get(organization_id))
+            .and_then(|organization| organization.facets.as_ref())
+            .is_none_or(|facet_ids| facet_ids.iter().any(|id| id == facet_id))
+    }
+
     pub fn validate_prompt_optimizer(&self) -> Result<(), eyre::Report> {
         if !self.prompt_optimizer.enabled {
             return Ok(());
@@ -1190,6 +1581,52 @@ impl AppConfig {
         Ok(())
     }
 
+    pub fn validate_internal_listener(&self) -> Result<(), eyre::Report> {
+        let Some(internal_http_port) = self.internal_http_port else {
+            if self.internal_http_host.is_some() {
+                return Err(eyre!(
+                    "internal_http_host is set, but internal_http_port is not. Configure internal_http_port to enable the internal listener."
+                ));
+            }
+            return Ok(());
+        };
+
+        if internal_http_port <= 0 || internal_http_port > u16::MAX as i32 {
+            return Err(eyre!(
+                "internal_http_port must be between 1 and {}, got {}",
+                u16::MAX,
+                internal_http_port
+            ));
+        }
+        if internal_http_port == self.http_port {
+            return Err(eyre!(
+                "internal_http_port ({}) must not match http_port",
+                internal_http_port
+            ));
+        }
+        if self.integrations.prometheus.enabled
+            && internal_http_port == self.integrations.prometheus.port
+        {
+            return Err(eyre!(
+                "internal_http_port ({}) must not match integrations.prometheus.port",
+                internal_http_port
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the address of the internal listener, if one is configured.
+    pub fn internal_http_addr(&self) -> Option<String> {
+        self.internal_http_port.map(|port| {
+            let host = self
+                .internal_http_host
+                .as_deref()
+                .unwrap_or(&self.http_host);
+            format!("{}:{}", host, port)
+        })
+    }
+
     /// Returns the maximum configured file upload size in bytes, if any.
     pub fn max_upload_size_bytes(&self) -> Option<u64> {
         self.file_storage_providers
@@ -1199,6 +1636,25 @@ impl AppConfig {
             .map(|kb| kb * 1024)
     }
 
+    /// Returns the sources users can add files from: `local` if they can upload files from
+    /// their device, followed by the enabled link sources whose integration is enabled and
+    /// usable with the auth mode.
+    pub fn available_file_sources(&self) -> Vec<&str> {
+        let sharepoint = &self.integrations.experimental_sharepoint;
+        let mut sources = Vec::new();
+        if self.file_uploads.local_upload_enabled {
+            sources.push(LOCAL_UPLOAD_FILE_SOURCE);
+        }
+        if self.file_uploads.link_source_enabled("sharepoint")
+            && sharepoint.enabled
+            && sharepoint.file_upload_enabled
+            && self.auth.mode.provides_access_token()
+        {
+            sources.push("sharepoint");
+        }
+        sources
+    }
+
     pub fn additional_frontend_environment(&self) -> HashMap<String, serde_json::Value> {
         self.frontend.additional_environment.clone()
     }
@@ -1208,11 +1664,7 @@ impl AppConfig {
     pub fn available_chat_providers(&self, chat_provider_allowlist: Option<&[&str]>) -> Vec<&str> {
         let (all_providers, priority_order): (Vec<&str>, Vec<&str>) =
             if let Some(chat_providers) = &self.chat_providers {
-                let all: Vec<&str> = chat_providers
-                    .providers
-                    .keys()
-                    .map(|s| s.as_str())
-                    .collect();
+                let all: Vec<&str> = self.chat_provider_and_group_ids();
                 let priority: Vec<&str> = chat_providers
                     .priority_order
                     .iter()
@@ -1265,11 +1717,7 @@ impl AppConfig {
     ) -> Result<&'a str, eyre::Report> {
         let (all_providers, precedence_order): (Vec<&str>, Vec<&str>) =
             if let Some(chat_providers) = &self.chat_providers {
-                let all: Vec<&str> = chat_providers
-                    .providers
-                    .keys()
-                    .map(|s| s.as_str())
-                    .collect();
+                let all: Vec<&str> = self.chat_provider_and_group_ids();
                 let precedence: Vec<&str> = chat_providers
                     .priority_order
                     .iter()
@@ -1299,6 +1747,7 @@ impl AppConfig {
         tracing::debug!(?allowed, "Allowed chat providers after allowlist filter");
 
         if let Some(requested) = requested_chat_provider {
+            let requested = self.resolve_chat_provider_alias(requested);
             tracing::debug!(requested, "Requested chat provider");
             // Return the matching &str from allowed (which is from self), not the input reference
             if let Some(&matching) = allowed.iter().find(|&&p| p == requested) {
@@ -1332,12 +1781,116 @@ impl AppConfig {
         }
     }
 
-    /// Returns the ChatProviderConfig for the given provider id.
+    /// Determines the chat provider like [`Self::determine_chat_provider`], but falls back if the
+    /// requested chat provider is no longer configured, e.g. because it was removed while chats
+    /// still refer to it.
+    ///
+    /// The fallback is `fallback_chat_provider` (e.g. the default chat provider of the
+    /// assistant) if it is configured and allowed, and otherwise the allowed chat provider with
+    /// the highest priority.
+    pub fn determine_chat_provider_with_fallback<'a>(
+        &'a self,
+        chat_provider_allowlist: Option<&[&str]>,
+        requested_chat_provider: Option<&str>,
+        fallback_chat_provider: Option<&str>,
+    ) -> Result<&'a str, eyre::Report> {
+        let Some(requested) =
+            requested_chat_provider.filter(|requested| !self.is_known_chat_provider_id(requested))
+        else {
+            return self.determine_chat_provider(chat_provider_allowlist, requested_chat_provider);
+        };
+
+        let fallback = fallback_chat_provider
+            .filter(|fallback| self.is_known_chat_provider_id(fallback))
+            .and_then(|fallback| {
+                self.determine_chat_provider(chat_provider_allowlist, Some(fallback))
+                    .ok()
+            });
+        let chat_provider_id = match fallback {
+            Some(chat_provider_id) => chat_provider_id,
+            None => self.determine_chat_provider(chat_provider_allowlist, None)?,
+        };
+        tracing::info!(
+            requested,
+            chat_provider_id,
+            "Requested chat provider is not configured, falling back to another chat provider"
+        );
+        Ok(chat_provider_id)
+    }
+
+    /// Resolves a chat provider ID through `chat_provider_aliases`.
+    ///
+    /// Returns the ID unchanged if it is not an alias.
+    pub fn resolve_chat_provider_alias<'a>(&'a self, provider_id: &'a str) -> &'a str {
+        self.chat_provider_aliases
+            .get(provider_id)
+            .map(String::as_str)
+            .unwrap_or(provider_id)
+    }
+
+    /// IDs of the configured chat providers and chat provider groups.
+    ///
+    /// Empty in legacy single-provider mode.
+    pub fn chat_provider_and_group_ids(&self) -> Vec<&str> {
+        let Some(chat_providers) = &self.chat_providers else {
+            return vec![];
+        };
+        chat_providers
+            .providers
+            .keys()
+            .chain(self.chat_provider_groups.keys())
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Whether any chat provider limits the usage of its users with a `user_quota`.
+    pub fn any_chat_provider_has_user_quota(&self) -> bool {
+        let has_limits = |provider: &ChatProviderConfig| {
+            provider
+                .user_quota
+                .as_ref()
+                .is_some_and(ChatProviderUserQuotaConfig::has_limits)
+        };
+        match &self.chat_providers {
+            Some(chat_providers) => chat_providers.providers.values().any(has_limits),
+            None => self.chat_provider.as_ref().is_some_and(has_limits),
+        }
+    }
+
+    /// Returns the chat provider group with the given ID, if it is one.
+    pub fn chat_provider_group(&self, group_id: &str) -> Option<&ChatProviderGroupConfig> {
+        self.chat_provider_groups.get(group_id)
+    }
+
+    /// Returns true if the given provider ID is configured, is a chat provider group, or is an
+    /// alias of a configured provider.
+    pub fn is_known_chat_provider_id(&self, provider_id: &str) -> bool {
+        match &self.chat_providers {
+            Some(chat_providers) => {
+                chat_providers
+                    .providers
+                    .contains_key(self.resolve_chat_provider_alias(provider_id))
+                    || self.chat_provider_groups.contains_key(provider_id)
+            }
+            // In legacy mode, any provider ID maps to the single provider
+            None => self.chat_provider.is_some(),
+        }
+    }
+
+    /// Returns the ChatProviderConfig for the given provider id, resolving aliases.
+    ///
+    /// Chat provider groups resolve to their first member, as all members serve the same model.
     pub fn get_chat_provider(&self, provider_id: &str) -> &ChatProviderConfig {
         if let Some(chat_providers) = &self.chat_providers {
+            let provider_id = self
+                .chat_provider_groups
+                .get(provider_id)
+                .and_then(|group| group.members.first())
+                .map(String::as_str)
+                .unwrap_or(provider_id);
             chat_providers
                 .providers
-                .get(provider_id)
+                .get(self.resolve_chat_provider_alias(provider_id))
                 .unwrap_or_else(|| {
                     panic!("Chat provider '{}' not found in configuration", provider_id)
                 })
@@ -1411,6 +1964,11 @@ impl AppConfig {
             .as_ref()
             .or(self.sentry_dsn.as_ref())
     }
+
+    /// Whether the configured environment is `production`.
+    pub fn is_production_environment(&self) -> bool {
+        self.environment == "production"
+    }
 }
 
 fn validate_audio_feature_config(
@@ -1553,23 +2111,63 @@ impl ServerConfig {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy, Default, Facet)]
-#[facet(rename_all = "lowercase")]
-#[serde(rename_all = "lowercase")]
-#[repr(C)]
-pub enum LoggingFormat {
-    #[default]
-    Plain,
-    Json,
-}
-
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
-pub struct LoggingConfig {
+pub struct HttpConfig {
+    // The URLs under which clients reach the server, e.g. behind a reverse proxy or TLS
+    // terminator. They are advertised as the servers of the OpenAPI spec.
+    // If empty, the address of the listener is advertised in the `development` environment, and
+    // no servers are advertised otherwise.
     #[serde(default)]
-    pub format: LoggingFormat,
+    pub public_base_url: Vec<PublicBaseUrlConfig>,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Clone, Facet)]
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct PublicBaseUrlConfig {
+    // The absolute URL, e.g. `https://chat.example.com`.
+    pub url: String,
+    // A description of the URL shown in the API docs, e.g. `Production`.
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl HttpConfig {
+    pub fn validate(&self) -> Result<(), Report> {
+        for public_base_url in &self.public_base_url {
+            let url = url::Url::parse(&public_base_url.url).map_err(|error| {
+                eyre!(
+                    "http.public_base_url `{}` must be an absolute URL: {}",
+                    public_base_url.url,
+                    error
+                )
+            })?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(eyre!(
+                    "http.public_base_url `{}` must be an http or https URL",
+                    public_base_url.url
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy, Default, Facet)]
+#[facet(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+#[repr(C)]
+pub enum LoggingFormat {
+    #[default]
+    Plain,
+    Json,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
+pub struct LoggingConfig {
+    #[serde(default)]
+    pub format: LoggingFormat,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Facet)]
 pub struct ChatProvidersConfig {
     // Priority order of chat providers to use.
     // Each string should match a key in the providers map.
@@ -1582,6 +2180,10 @@ pub struct ChatProvidersConfig {
     // Configuration for summary generation.
     #[serde(default)]
     pub summary: SummaryConfig,
+    // Map of user group to the chat provider that is recommended to its members by
+    // `/me/models` and `/me/models/recommend`.
+    #[serde(default)]
+    pub user_group_defaults: HashMap<String, String>,
 }
 
 #[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Facet)]
@@ -1594,6 +2196,127 @@ pub struct AllChatProvidersConfig {
 pub struct GuardrailsConfig {
     #[serde(default)]
     pub prompt_patterns: HashMap<String, PromptPatternConfig>,
+    // Phrases that must not appear in generated answers.
+    #[serde(default)]
+    pub output_compliance: OutputComplianceConfig,
+}
+
+impl GuardrailsConfig {
+    /// Validates the guardrails configuration.
+    pub fn validate(&self) -> Result<(), Report> {
+        self.output_compliance.validate()
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct OutputComplianceConfig {
+    // Rules applied to the answers of all assistants and to chats without an assistant, by rule ID.
+    //
+    // Matches are recorded on the generated message (without the matched text), logged and counted
+    // in the `erato_output_compliance_matches_total` metric.
+    #[serde(default)]
+    pub rules: HashMap<String, OutputComplianceRuleConfig>,
+    // Additional rules applied to the answers of individual assistants, by assistant ID.
+    #[serde(default)]
+    pub assistants: HashMap<String, AssistantOutputComplianceConfig>,
+    // Maximum number of characters of streamed text that are held back, so that matches split
+    // across chunks are caught before they reach the client. Fixed phrases only hold back one
+    // character less than the longest phrase, while a regex rule holds back this many characters,
+    // which also limits the length of the regex matches that are caught across chunks.
+    // Defaults to 200.
+    #[serde(default = "default_output_compliance_max_holdback_chars")]
+    pub max_holdback_chars: usize,
+}
+
+fn default_output_compliance_max_holdback_chars() -> usize {
+    200
+}
+
+impl Default for OutputComplianceConfig {
+    fn default() -> Self {
+        Self {
+            rules: HashMap::new(),
+            assistants: HashMap::new(),
+            max_holdback_chars: default_output_compliance_max_holdback_chars(),
+        }
+    }
+}
+
+impl OutputComplianceConfig {
+    /// Validates the output compliance configuration.
+    pub fn validate(&self) -> Result<(), Report> {
+        validate_output_compliance_rules("guardrails.output_compliance.rules", &self.rules)?;
+        for (assistant_id, assistant_config) in &self.assistants {
+            validate_output_compliance_rules(
+                &format!("guardrails.output_compliance.assistants.{assistant_id}.rules"),
+                &assistant_config.rules,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Whether any rules are configured, for any assistant.
+    pub fn has_rules(&self) -> bool {
+        !self.rules.is_empty()
+            || self
+                .assistants
+                .values()
+                .any(|assistant| !assistant.rules.is_empty())
+    }
+}
+
+fn validate_output_compliance_rules(
+    config_key: &str,
+    rules: &HashMap<String, OutputComplianceRuleConfig>,
+) -> Result<(), Report> {
+    for (rule_id, rule) in rules {
+        if rule.pattern.is_empty() {
+            return Err(eyre!("{config_key}.{rule_id}.pattern must not be empty"));
+        }
+
+        if matches!(rule.r#type, PromptPatternType::Regex) {
+            Regex::new(&rule.pattern).map_err(|err| {
+                eyre!("{config_key}.{rule_id}.pattern is not a valid regex: {err}")
+            })?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct AssistantOutputComplianceConfig {
+    // Rules applied to the answers of the assistant, in addition to the global rules, by rule ID.
+    #[serde(default)]
+    pub rules: HashMap<String, OutputComplianceRuleConfig>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct OutputComplianceRuleConfig {
+    // Whether `pattern` is a fixed phrase or a regex.
+    pub r#type: PromptPatternType,
+    pub pattern: String,
+    // Whether the pattern matches regardless of case.
+    // Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub case_insensitive: bool,
+    // What happens to answers that match the rule.
+    // Defaults to `mask`.
+    #[serde(default)]
+    pub action: OutputComplianceAction,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Facet)]
+#[serde(rename_all = "snake_case")]
+#[facet(rename_all = "snake_case")]
+#[repr(C)]
+pub enum OutputComplianceAction {
+    // The matched text is replaced with `[redacted]`, both in the streamed text and in the saved
+    // message.
+    #[default]
+    Mask,
+    // The generation is aborted with a `compliance_policy` error before the matched text is
+    // streamed.
+    Abort,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
@@ -1614,6 +2337,47 @@ pub struct PromptPatternConfig {
     pub tags: Vec<String>,
 }
 
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct ChatProviderGroupConfig {
+    // IDs of the chat providers that serve the generations of the group.
+    // The first member also provides the model display name, description, icon and capabilities
+    // of the group, unless `model_display_name` is set.
+    pub members: Vec<String>,
+    // How the member that serves a generation is selected.
+    // Defaults to `round_robin`.
+    #[serde(default)]
+    pub strategy: ChatProviderGroupStrategy,
+    // Relative weights of the members for the `weighted` strategy, by member ID.
+    // Members without a weight have a weight of 1, and members with a weight of 0 are not used.
+    #[serde(default)]
+    pub weights: HashMap<String, u32>,
+    // The name of the model of the group, shown to users.
+    // Defaults to the display name of the first member.
+    pub model_display_name: Option<String>,
+}
+
+impl ChatProviderGroupConfig {
+    /// Weight of a member for the `weighted` strategy.
+    pub fn weight(&self, member_id: &str) -> u32 {
+        self.weights.get(member_id).copied().unwrap_or(1)
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Copy, Facet)]
+#[serde(rename_all = "snake_case")]
+#[facet(rename_all = "snake_case")]
+#[repr(C)]
+pub enum ChatProviderGroupStrategy {
+    // The members take turns.
+    #[default]
+    RoundRobin,
+    // A random member is selected, in proportion to the `weights` of the members.
+    Weighted,
+    // The member with the fewest errors in the last minutes is selected, skipping members that
+    // respond much slower than the others. Equally healthy members take turns.
+    LeastErrors,
+}
+
 #[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Facet)]
 pub struct ChatProviderGuardrailsConfig {
     #[serde(default)]
@@ -1639,6 +2403,138 @@ pub struct PromptInjectionFilterConfig {
     pub exclude_pattern_ids: Vec<String>,
 }
 
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct SecurityConfig {
+    // Whether the contents of attached files and the outputs of MCP tools are wrapped in blocks
+    // delimited by a random boundary token (new for every generation), together with an
+    // instruction for the model to treat them as data and not as instructions.
+    // Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub wrap_untrusted_content: bool,
+    // Patterns that the contents of attached files and the outputs of MCP tools are scanned for.
+    //
+    // Matches don't block the generation (see `guardrails` for that). Instead, they are recorded on
+    // the generated message, reported to the client with a `prompt_injection_warning` event and
+    // counted in the `erato_prompt_injection_warnings_total` metric.
+    #[serde(default)]
+    pub injection_patterns: HashMap<String, PromptPatternConfig>,
+    // Whether matches of `injection_patterns` are neutralized by quoting them before the content
+    // is sent to the model.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub neutralize_injection_matches: bool,
+    // Whether text copied from the system prompt is redacted from the arguments of MCP tool calls,
+    // to prevent the system prompt from being exfiltrated through a tool.
+    // Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub redact_system_prompt_from_tool_arguments: bool,
+    // Minimum number of consecutive characters that a tool call argument has to share with the
+    // system prompt to be redacted.
+    // Defaults to 40.
+    #[serde(default = "default_system_prompt_redaction_min_chars")]
+    pub system_prompt_redaction_min_chars: usize,
+}
+
+fn default_system_prompt_redaction_min_chars() -> usize {
+    40
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            wrap_untrusted_content: true,
+            injection_patterns: HashMap::new(),
+            neutralize_injection_matches: false,
+            redact_system_prompt_from_tool_arguments: true,
+            system_prompt_redaction_min_chars: default_system_prompt_redaction_min_chars(),
+        }
+    }
+}
+
+impl SecurityConfig {
+    /// Validates the security configuration.
+    pub fn validate(&self) -> Result<(), Report> {
+        for (pattern_id, pattern_config) in &self.injection_patterns {
+            if pattern_config.pattern.is_empty() {
+                return Err(eyre!(
+                    "security.injection_patterns.{pattern_id}.pattern must not be empty"
+                ));
+            }
+
+            if matches!(pattern_config.r#type, PromptPatternType::Regex) {
+                Regex::new(&pattern_config.pattern).map_err(|err| {
+                    eyre!(
+                        "security.injection_patterns.{pattern_id}.pattern is not a valid regex: {err}"
+                    )
+                })?;
+            }
+        }
+
+        if self.system_prompt_redaction_min_chars == 0 {
+            return Err(eyre!(
+                "security.system_prompt_redaction_min_chars must be greater than 0"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct ChatExportConfig {
+    // Maximum number of pages of a PDF export. Exports that would be longer are rejected, and
+    // have to be narrowed down with `from_message_id`/`to_message_id`.
+    // Defaults to 200.
+    #[serde(default = "default_chat_export_max_pdf_pages")]
+    pub max_pdf_pages: usize,
+    // Images are scaled down to at most this width (in pixels) before they are embedded.
+    // Defaults to 1200.
+    #[serde(default = "default_chat_export_max_image_width_px")]
+    pub max_image_width_px: u32,
+    // Maximum total size of the images embedded in one export, in bytes. Images beyond the budget
+    // are replaced by a placeholder.
+    // Defaults to 20 MiB.
+    #[serde(default = "default_chat_export_max_total_image_bytes")]
+    pub max_total_image_bytes: usize,
+}
+
+fn default_chat_export_max_pdf_pages() -> usize {
+    200
+}
+
+fn default_chat_export_max_image_width_px() -> u32 {
+    1200
+}
+
+fn default_chat_export_max_total_image_bytes() -> usize {
+    20 * 1024 * 1024
+}
+
+impl Default for ChatExportConfig {
+    fn default() -> Self {
+        Self {
+            max_pdf_pages: default_chat_export_max_pdf_pages(),
+            max_image_width_px: default_chat_export_max_image_width_px(),
+            max_total_image_bytes: default_chat_export_max_total_image_bytes(),
+        }
+    }
+}
+
+impl ChatExportConfig {
+    /// Validates the chat export configuration.
+    pub fn validate(&self) -> Result<(), Report> {
+        if self.max_pdf_pages == 0 {
+            return Err(eyre!("chat_export.max_pdf_pages must be greater than 0"));
+        }
+        if self.max_image_width_px == 0 {
+            return Err(eyre!(
+                "chat_export.max_image_width_px must be greater than 0"
+            ));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Facet)]
 pub struct SummaryConfig {
     // The chat provider ID to use for summary generation.
@@ -1661,104 +2557,371 @@ impl SummaryConfig {
     }
 }
 
-#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Facet)]
-pub struct PromptOptimizerConfig {
-    // Whether the prompt optimizer is enabled.
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct ModerationConfig {
+    // Whether user messages are moderated before they are passed to the LLM.
     // Defaults to `false`.
     #[serde(default)]
     pub enabled: bool,
-    // The chat provider ID to use for prompt optimization.
-    pub chat_provider_id: Option<String>,
-    // The system prompt to use for prompt optimization.
-    pub prompt: Option<PromptSourceSpecification>,
-}
-
-#[derive(Debug, Default, Deserialize, PartialEq, Clone, Facet)]
-pub struct ChatProviderConfig {
-    // May be one of:
-    // - "openai" (OpenAI-compatible Chat Completions API)
-    // - "openai_responses" (OpenAI Responses API)
-    // - "azure_openai" (will be automatically converted to "openai" format during config loading)
-    // - "azure_openai_responses" (Azure OpenAI Responses API endpoint settings)
-    // - "ollama"
-    // - "gemini"
-    // - "vertex_ai" (will be automatically converted to "gemini" during config loading)
-    pub provider_kind: String,
-    // The model name to use for the chat provider.
-    //
-    // E.g. `gpt-4o`
-    pub model_name: String,
-    // The display name for the model shown to users.
-    // Falls back to model_name if not provided.
-    pub model_display_name: Option<String>,
-    // Optional description for the model shown to users in the frontend.
-    pub model_description: Option<String>,
-    // Optional icon identifier for the model shown to users in the frontend.
-    pub model_icon: Option<String>,
-    // The model name to report to Langfuse for observability and tracing.
-    // Falls back to model_name if not provided.
-    // This is useful when the provider's model name differs from the standardized name
-    // used in Langfuse (e.g., Azure deployment names vs. OpenAI model names).
-    pub model_name_langfuse: Option<String>,
-    // The base URL for OpenAI compatible API endpoints.
-    // If not provided, will use the default for the provider.
-    //
-    // Should likely end with `/v1/`
-    // E.g. 'http://localhost:11434/v1/'
-    //
-    // For Azure OpenAI, this should be the deployment endpoint URL ending with
-    // either `.api.cognitive.microsoft.com`, `.openai.azure.com`,
-    // `.cognitiveservices.azure.com`, or `.services.ai.azure.com`
-    // E.g. 'https://germanywestcentral.api.cognitive.microsoft.com'
-    pub base_url: Option<String>,
-    // Optional Vertex AI region (e.g. "europe-west3").
-    // If provider_kind is `vertex_ai` and base_url is not provided, this will be used
-    // to construct a regional Vertex AI endpoint.
-    pub region: Option<String>,
+    // The kind of moderation service.
+    // Defaults to `openai_moderation`.
+    #[serde(default)]
+    pub provider: ModerationProviderKind,
+    // URL of the moderation service. For `openai_moderation` this is the full URL of the
+    // moderations endpoint, e.g. `https://api.openai.com/v1/moderations`.
+    pub endpoint: Option<String>,
+    // API key sent as bearer token to the moderation service.
     #[facet(sensitive)]
     pub api_key: Option<SecretConfigString>,
-    // For Azure OpenAI, the API version to use (e.g. "2024-10-21").
-    // This will be automatically converted to additional_request_parameters during config loading.
-    pub api_version: Option<String>,
-    // Additional request parameters to be added to API requests.
-    // E.g. 'api-version=2024-10-21'
-    pub additional_request_parameters: Option<Vec<String>>,
-    // Additional request headers to be added to API requests.
-    // E.g. 'api-key=XYZ'
-    #[facet(sensitive)]
-    pub additional_request_headers: Option<Vec<SecretConfigString>>,
-    // Optional system prompt to use with the chat provider.
-    pub system_prompt: Option<PromptSourceSpecification>,
-    // Optional Langfuse system prompt configuration.
-    // Mutually exclusive with system_prompt.
-    pub system_prompt_langfuse: Option<LangfuseSystemPromptConfig>,
-    // Model capabilities configuration for this chat provider.
+    // The model passed to the OpenAI moderations endpoint.
+    // Only has an effect for `openai_moderation`.
+    pub model: Option<String>,
+    // What happens to messages that are flagged by the moderation service.
+    // Defaults to `block`.
     #[serde(default)]
-    pub model_capabilities: ModelCapabilities,
-    // Model settings configuration for this chat provider.
+    pub action: ModerationAction,
+    // Only flags for these categories are acted upon. If empty, any flagged message is acted upon.
     #[serde(default)]
-    pub model_settings: ModelSettings,
-    // Hallucination suppression configuration for this chat provider.
-    #[serde(default, alias = "hallucination_supression")]
-    pub hallucination_suppression: HallucinationSuppressionConfig,
-    // Provider-level guardrail configuration. If set, this completely overrides
-    // `chat_providers.all_providers.guardrails`.
-    pub guardrails: Option<ChatProviderGuardrailsConfig>,
-}
-
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
-pub struct HallucinationSuppressionConfig {
-    // Whether hallucination suppression is enabled.
+    pub categories_blocklist: Vec<String>,
+    // Whether the synopses of files attached to the message are moderated together with its text.
     // Defaults to `false`.
     #[serde(default)]
-    pub enabled: bool,
-    // Abort the generation after this many successive whitespace-only text deltas.
-    #[serde(default = "default_hallucination_suppression_whitespace_delta_threshold")]
-    pub whitespace_delta_threshold: usize,
+    pub include_file_synopses: bool,
+    // Whether messages are passed to the LLM if the moderation service fails or can't be reached.
+    // If `false`, such messages are blocked.
+    // Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub fail_open: bool,
+    // Timeout for requests to the moderation service, in seconds.
+    // Defaults to 10.
+    #[serde(default = "default_moderation_timeout_seconds")]
+    pub timeout_seconds: u64,
 }
 
-impl Default for HallucinationSuppressionConfig {
-    fn default() -> Self {
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Copy, Facet)]
+#[serde(rename_all = "snake_case")]
+#[facet(rename_all = "snake_case")]
+#[repr(C)]
+pub enum ModerationProviderKind {
+    // The OpenAI moderations API (`POST /v1/moderations`), or a compatible endpoint.
+    #[default]
+    OpenaiModeration,
+    // A custom webhook receiving `{"input": ...}` and returning `{"flagged": ..., "categories": [...]}`.
+    Webhook,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Copy, Facet)]
+#[serde(rename_all = "snake_case")]
+#[facet(rename_all = "snake_case")]
+#[repr(C)]
+pub enum ModerationAction {
+    // Flagged messages are not passed to the LLM; the assistant message is saved with an error.
+    #[default]
+    Block,
+    // Flagged messages are annotated, and generation continues.
+    Flag,
+}
+
+fn default_moderation_timeout_seconds() -> u64 {
+    10
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct PolicyEngineConfig {
+    // Leaving old resources out of the in-memory policy data.
+    #[serde(default)]
+    pub tiering: PolicyDataTieringConfig,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct PolicyDataTieringConfig {
+    // Whether cold resources are left out of the in-memory policy data and looked up in the
+    // database when they are authorized. Small deployments can disable this to keep all
+    // resources in memory.
+    // Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    // Chats archived more than this many days ago, and the files linked to them, are cold.
+    // Defaults to 30.
+    #[serde(default = "default_policy_data_archived_after_days")]
+    pub archived_after_days: u64,
+    // Chats that are not archived, but weren't updated for this many days, are cold as well.
+    // Not set by default.
+    pub inactive_after_days: Option<u64>,
+    // Number of recently authorized cold resources that are kept in memory until the next
+    // rebuild of the policy data.
+    // Defaults to 1000.
+    #[serde(default = "default_policy_data_promoted_capacity")]
+    pub promoted_capacity: u64,
+}
+
+fn default_policy_data_archived_after_days() -> u64 {
+    30
+}
+
+fn default_policy_data_promoted_capacity() -> u64 {
+    1000
+}
+
+impl Default for PolicyDataTieringConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            archived_after_days: default_policy_data_archived_after_days(),
+            inactive_after_days: None,
+            promoted_capacity: default_policy_data_promoted_capacity(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct CompatApiConfig {
+    // Whether the OpenAI-compatible API under `/compat/v1` is served.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+    // Model names accepted by the API, mapped to the chat provider (or chat provider group) that
+    // serves them. Model names that are not mapped are used as chat provider IDs.
+    #[serde(default)]
+    pub models: HashMap<String, String>,
+    // Where the usage of the API is recorded.
+    // Defaults to `ephemeral`.
+    #[serde(default)]
+    pub usage_recording: CompatApiUsageRecording,
+    // Title of the chat the exchanges are stored in with `usage_recording = "chat"`.
+    // Defaults to `API`.
+    pub chat_title: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Copy, Facet)]
+#[serde(rename_all = "snake_case")]
+#[facet(rename_all = "snake_case")]
+#[repr(C)]
+pub enum CompatApiUsageRecording {
+    // Nothing is stored; the token usage only counts against the quotas of the chat providers.
+    #[default]
+    Ephemeral,
+    // The last user message and the answer of each request are stored in a chat of the user.
+    Chat,
+}
+
+impl CompatApiConfig {
+    /// The title of the chat the exchanges are stored in.
+    pub fn chat_title(&self) -> &str {
+        self.chat_title.as_deref().unwrap_or("API")
+    }
+
+    /// Validates the compat API configuration against the configured chat providers.
+    pub fn validate(&self, chat_provider_ids: &[&str]) -> Result<(), Report> {
+        if !self.enabled || chat_provider_ids.is_empty() {
+            return Ok(());
+        }
+        for (model, chat_provider_id) in &self.models {
+            if !chat_provider_ids.contains(&chat_provider_id.as_str()) {
+                return Err(eyre!(
+                    "Model '{}' is mapped to unknown chat provider '{}'",
+                    model,
+                    chat_provider_id
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for ModerationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: ModerationProviderKind::default(),
+            endpoint: None,
+            api_key: None,
+            model: None,
+            action: ModerationAction::default(),
+            categories_blocklist: Vec::new(),
+            include_file_synopses: false,
+            fail_open: true,
+            timeout_seconds: default_moderation_timeout_seconds(),
+        }
+    }
+}
+
+impl ModerationConfig {
+    /// Validates the moderation configuration.
+    pub fn validate(&self) -> Result<(), Report> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self
+            .endpoint
+            .as_deref()
+            .is_none_or(|endpoint| endpoint.trim().is_empty())
+        {
+            return Err(eyre!(
+                "Moderation enabled but `moderation.endpoint` is not set"
+            ));
+        }
+        if self.timeout_seconds == 0 {
+            return Err(eyre!("moderation.timeout_seconds must be greater than 0"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct PromptOptimizerConfig {
+    // Whether the prompt optimizer is enabled.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+    // The chat provider ID to use for prompt optimization.
+    pub chat_provider_id: Option<String>,
+    // The system prompt to use for prompt optimization.
+    pub prompt: Option<PromptSourceSpecification>,
+    // Maximum number of tokens of recent chat messages that are included as
+    // context when a chat is provided with the prompt to optimize.
+    // Defaults to `2000`.
+    #[serde(default = "default_prompt_optimizer_context_max_tokens")]
+    pub context_max_tokens: usize,
+}
+
+impl Default for PromptOptimizerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            chat_provider_id: None,
+            prompt: None,
+            context_max_tokens: default_prompt_optimizer_context_max_tokens(),
+        }
+    }
+}
+
+fn default_prompt_optimizer_context_max_tokens() -> usize {
+    2000
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Clone, Facet)]
+pub struct ChatProviderConfig {
+    // May be one of:
+    // - "openai" (OpenAI-compatible Chat Completions API)
+    // - "openai_responses" (OpenAI Responses API)
+    // - "azure_openai" (will be automatically converted to "openai" format during config loading)
+    // - "azure_openai_responses" (Azure OpenAI Responses API endpoint settings)
+    // - "ollama"
+    // - "gemini"
+    // - "vertex_ai" (will be automatically converted to "gemini" during config loading)
+    pub provider_kind: String,
+    // The model name to use for the chat provider.
+    //
+    // E.g. `gpt-4o`
+    pub model_name: String,
+    // The display name for the model shown to users.
+    // Falls back to model_name if not provided.
+    pub model_display_name: Option<String>,
+    // Optional description for the model shown to users in the frontend.
+    pub model_description: Option<String>,
+    // Optional icon identifier for the model shown to users in the frontend.
+    pub model_icon: Option<String>,
+    // The model name to report to Langfuse for observability and tracing.
+    // Falls back to model_name if not provided.
+    // This is useful when the provider's model name differs from the standardized name
+    // used in Langfuse (e.g., Azure deployment names vs. OpenAI model names).
+    pub model_name_langfuse: Option<String>,
+    // The base URL for OpenAI compatible API endpoints.
+    // If not provided, will use the default for the provider.
+    //
+    // Should likely end with `/v1/`
+    // E.g. 'http://localhost:11434/v1/'
+    //
+    // For Azure OpenAI, this should be the deployment endpoint URL ending with
+    // either `.api.cognitive.microsoft.com`, `.openai.azure.com`,
+    // `.cognitiveservices.azure.com`, or `.services.ai.azure.com`
+    // E.g. 'https://germanywestcentral.api.cognitive.microsoft.com'
+    pub base_url: Option<String>,
+    // Optional Vertex AI region (e.g. "europe-west3").
+    // If provider_kind is `vertex_ai` and base_url is not provided, this will be used
+    // to construct a regional Vertex AI endpoint.
+    pub region: Option<String>,
+    #[facet(sensitive)]
+    pub api_key: Option<SecretConfigString>,
+    // For Azure OpenAI, the API version to use (e.g. "2024-10-21").
+    // This will be automatically converted to additional_request_parameters during config loading.
+    pub api_version: Option<String>,
+    // Additional request parameters to be added to API requests.
+    // E.g. 'api-version=2024-10-21'
+    pub additional_request_parameters: Option<Vec<String>>,
+    // Additional request headers to be added to API requests.
+    // E.g. 'api-key=XYZ'
+    #[facet(sensitive)]
+    pub additional_request_headers: Option<Vec<SecretConfigString>>,
+    // Optional system prompt to use with the chat provider.
+    pub system_prompt: Option<PromptSourceSpecification>,
+    // Optional Langfuse system prompt configuration.
+    // Mutually exclusive with system_prompt.
+    pub system_prompt_langfuse: Option<LangfuseSystemPromptConfig>,
+    // Model capabilities configuration for this chat provider.
+    #[serde(default)]
+    pub model_capabilities: ModelCapabilities,
+    // Model settings configuration for this chat provider.
+    #[serde(default)]
+    pub model_settings: ModelSettings,
+    // Hallucination suppression configuration for this chat provider.
+    #[serde(default, alias = "hallucination_supression")]
+    pub hallucination_suppression: HallucinationSuppressionConfig,
+    // Provider-level guardrail configuration. If set, this completely overrides
+    // `chat_providers.all_providers.guardrails`.
+    pub guardrails: Option<ChatProviderGuardrailsConfig>,
+    // Per-user limits of the messages and tokens of this chat provider.
+    // Chat provider groups are limited by the quota of their first member.
+    pub user_quota: Option<ChatProviderUserQuotaConfig>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct ChatProviderUserQuotaConfig {
+    // Maximum number of generations per user and day (UTC).
+    pub messages_per_day: Option<u64>,
+    // Maximum number of tokens per user and day (UTC).
+    pub tokens_per_day: Option<u64>,
+    // Maximum number of generations per user and week (starting Monday, UTC).
+    pub messages_per_week: Option<u64>,
+    // Maximum number of tokens per user and week (starting Monday, UTC).
+    pub tokens_per_week: Option<u64>,
+    // Users in any of these groups are not limited.
+    // Defaults to `[]`.
+    #[serde(default)]
+    pub exempt_groups: Vec<String>,
+}
+
+impl ChatProviderUserQuotaConfig {
+    /// Whether any limit is configured.
+    pub fn has_limits(&self) -> bool {
+        self.messages_per_day.is_some()
+            || self.tokens_per_day.is_some()
+            || self.messages_per_week.is_some()
+            || self.tokens_per_week.is_some()
+    }
+
+    /// Whether a user with the given groups is exempt from the quota.
+    pub fn is_exempt(&self, user_groups: &[String]) -> bool {
+        self.exempt_groups
+            .iter()
+            .any(|group| user_groups.contains(group))
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct HallucinationSuppressionConfig {
+    // Whether hallucination suppression is enabled.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+    // Abort the generation after this many successive whitespace-only text deltas.
+    #[serde(default = "default_hallucination_suppression_whitespace_delta_threshold")]
+    pub whitespace_delta_threshold: usize,
+}
+
+impl Default for HallucinationSuppressionConfig {
+    fn default() -> Self {
         Self {
             enabled: false,
             whitespace_delta_threshold:
@@ -1875,6 +3038,7 @@ impl ChatProviderConfig {
             model_settings: self.model_settings,
             hallucination_suppression: self.hallucination_suppression,
             guardrails: self.guardrails,
+            user_quota: self.user_quota,
         })
     }
 
@@ -1965,6 +3129,12 @@ pub struct FileStorageProviderConfig {
     // The maximum file size that may be uploaded in kilobytes.
     #[serde(default)]
     pub max_upload_size_kb: Option<u64>,
+    // Whether the backend fails to start if the self-test of the provider fails, instead of only
+    // logging a warning.
+    //
+    // Defaults to `false`.
+    #[serde(default)]
+    pub required: bool,
 }
 
 impl FileStorageProviderConfig {
@@ -1989,6 +3159,125 @@ impl FileStorageProviderConfig {
     }
 }
 
+/// Source of the files that users upload from their device, see
+/// `FileUploadsConfig::local_upload_enabled`.
+pub const LOCAL_UPLOAD_FILE_SOURCE: &str = "local";
+
+/// Valid values for `FileUploadsConfig::link_enabled_sources`.
+pub const LINK_FILE_SOURCES: [&str; 1] = ["sharepoint"];
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct FileUploadsConfig {
+    // If true, users can upload files from their device with `POST /me/files`, which stores them
+    // in the default file storage provider.
+    //
+    // If false, the endpoint responds with `403 Forbidden`, except for the uses enabled in
+    // `storage_carve_outs`, and files can only be added by linking them from one of the
+    // `link_enabled_sources`, e.g. for deployments where all documents must stay in SharePoint.
+    // Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub local_upload_enabled: bool,
+
+    // The sources that files can be linked from with `POST /me/files/link`.
+    //
+    // May contain:
+    // - "sharepoint" - Also requires `integrations.experimental_sharepoint.enabled` and
+    //   `integrations.experimental_sharepoint.file_upload_enabled`.
+    // Defaults to `["sharepoint"]`.
+    #[serde(default = "default_link_enabled_sources")]
+    pub link_enabled_sources: Vec<String>,
+
+    // If true, uploads and links of files that none of the models available to the user can use,
+    // e.g. archives or images without a model with image understanding, are rejected with
+    // `422 Unprocessable Entity`. Otherwise, they are accepted with a warning in the response.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub reject_unsupported: bool,
+
+    // Uses of the default file storage provider that stay available when `local_upload_enabled`
+    // is false.
+    #[serde(default)]
+    pub storage_carve_outs: FileStorageCarveOutsConfig,
+}
+
+fn default_link_enabled_sources() -> Vec<String> {
+    vec!["sharepoint".to_string()]
+}
+
+impl Default for FileUploadsConfig {
+    fn default() -> Self {
+        Self {
+            local_upload_enabled: true,
+            link_enabled_sources: default_link_enabled_sources(),
+            reject_unsupported: false,
+            storage_carve_outs: FileStorageCarveOutsConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
+pub struct FileStorageCarveOutsConfig {
+    // If true, files for the knowledge of assistants can still be uploaded with `POST /me/files`
+    // without a `chat_id` when `local_upload_enabled` is false.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub assistant_files: bool,
+
+    // If true, images returned by MCP tools are still stored when `local_upload_enabled` is
+    // false. Otherwise, tool calls that return images fail.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub generated_images: bool,
+}
+
+impl FileStorageCarveOutsConfig {
+    /// Whether any of the carve-outs is enabled.
+    pub fn any_enabled(&self) -> bool {
+        self.assistant_files || self.generated_images
+    }
+}
+
+impl FileUploadsConfig {
+    /// Whether files can be uploaded for the knowledge of assistants, i.e. without a chat.
+    pub fn assistant_file_upload_allowed(&self) -> bool {
+        self.local_upload_enabled || self.storage_carve_outs.assistant_files
+    }
+
+    /// Whether images returned by MCP tools can be stored.
+    pub fn generated_image_storage_allowed(&self) -> bool {
+        self.local_upload_enabled || self.storage_carve_outs.generated_images
+    }
+
+    /// Whether files can be linked from the given source.
+    pub fn link_source_enabled(&self, source: &str) -> bool {
+        self.link_enabled_sources
+            .iter()
+            .any(|enabled| enabled == source)
+    }
+
+    /// Validates the file uploads configuration against the configured file storage providers.
+    pub fn validate(
+        &self,
+        file_storage_providers: &HashMap<String, FileStorageProviderConfig>,
+    ) -> Result<(), Report> {
+        for source in &self.link_enabled_sources {
+            if !LINK_FILE_SOURCES.contains(&source.as_str()) {
+                return Err(eyre!(
+                    "Unknown source '{}' in `file_uploads.link_enabled_sources`. Valid values: {}",
+                    source,
+                    LINK_FILE_SOURCES.join(", ")
+                ));
+            }
+        }
+        if self.storage_carve_outs.any_enabled() && file_storage_providers.is_empty() {
+            return Err(eyre!(
+                "`file_uploads.storage_carve_outs` requires a configured file storage provider"
+            ));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
 #[repr(C)]
 pub enum StorageProviderSpecificConfig {
@@ -2090,10 +3379,29 @@ pub struct McpServerConfig {
     // When omitted, the global `mcp_servers_global.max_session_idle_seconds` is used.
     #[serde(default)]
     pub max_session_idle_seconds: Option<u64>,
+    // What to do with structured tool results that don't match the output schema declared by
+    // the tool.
+    #[serde(default)]
+    pub on_schema_violation: McpSchemaViolationMode,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet, Default)]
-#[serde(tag = "mode", rename_all = "snake_case")]
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Copy, Facet)]
+#[serde(rename_all = "snake_case")]
+#[facet(rename_all = "snake_case")]
+#[repr(C)]
+pub enum McpSchemaViolationMode {
+    // The result is passed to the model with a note listing the violations.
+    #[default]
+    Warn,
+    // The tool call fails, and the model gets the violations as the error.
+    Error,
+    // Fields that don't match the schema are dropped. Falls back to `error` if the result still
+    // doesn't match.
+    Coerce,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet, Default)]
+#[serde(tag = "mode", rename_all = "snake_case")]
 #[repr(C)]
 pub enum McpServerAuthenticationConfig {
     #[default]
@@ -2181,6 +3489,20 @@ pub struct McpServersGlobalConfig {
     #[serde(default)]
     pub max_session_idle_seconds: Option<u64>,
 
+    // Interval (in seconds) of the keep-alive pings that are sent on MCP sessions without
+    // activity, so that the servers don't expire them between generations.
+    // Sessions that fail a ping are evicted and re-established on their next use.
+    // Defaults to 120 seconds when not set. Set to `0` to disable keep-alive pings.
+    #[serde(default)]
+    pub session_keep_alive_seconds: Option<u64>,
+
+    // How long (in seconds) the tool list of an MCP session is reused before it is fetched again.
+    // For servers that send `notifications/tools/list_changed`, the tool list is only fetched again
+    // after such a notification.
+    // Defaults to 300 seconds when not set.
+    #[serde(default)]
+    pub tool_list_ttl_seconds: Option<u64>,
+
     // Whether the MCP servers tab should be shown in the frontend preferences dialog.
     // Defaults to `false`.
     #[serde(default)]
@@ -2265,6 +3587,17 @@ pub struct FrontendConfig {
     #[serde(default)]
     pub message_feedback_edit_time_limit_seconds: Option<u64>,
 
+    // Whether to enable emoji reactions on messages in the UI.
+    // Reactions are visible to everyone that can read the chat.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub enable_message_reactions: bool,
+
+    // The emojis users can react to messages with. Reactions with other emojis are rejected.
+    // Defaults to `["👍", "👎", "🎉", "❤️", "😄", "❓"]`.
+    #[serde(default = "default_message_reaction_emojis")]
+    pub message_reaction_emojis: Vec<String>,
+
     // Sidebar collapsed behavior: "hidden" (default) or "slim" (icon-only)
     // Defaults to `"hidden"`.
     #[serde(default = "default_sidebar_collapsed_mode")]
@@ -2382,6 +3715,13 @@ Error:
         .to_string()
 }
 
+fn default_message_reaction_emojis() -> Vec<String> {
+    ["👍", "👎", "🎉", "❤️", "😄", "❓"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
 fn default_sidebar_collapsed_mode() -> String {
     "hidden".to_string()
 }
@@ -2433,6 +3773,20 @@ pub struct AssistantsConfig {
     // back to its built-in default of 5000 characters for UI validation.
     #[serde(default)]
     pub max_system_prompt_length: Option<usize>,
+
+    // Categories that assistants can be assigned to, for browsing the assistants of an
+    // organization.
+    //
+    // The map key is the category ID used by the backend.
+    #[serde(default)]
+    pub categories: HashMap<String, AssistantHubCategoryConfig>,
+
+    // Whether chats based on an assistant keep using the configuration of the assistant as it
+    // was when the chat was created (prompt, default model, tools and files), instead of its
+    // current configuration. The configuration is snapshotted into every new chat either way.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub freeze_assistant_config: bool,
 }
 
 impl Default for AssistantsConfig {
@@ -2445,6 +3799,8 @@ impl Default for AssistantsConfig {
             context_file_contributor_threshold:
                 default_assistant_context_file_contributor_threshold(),
             max_system_prompt_length: None,
+            categories: HashMap::new(),
+            freeze_assistant_config: false,
         }
     }
 }
@@ -2473,6 +3829,26 @@ impl AssistantsConfig {
             ));
         }
 
+        for (category_id, category) in &self.categories {
+            if category_id.trim().is_empty() {
+                return Err(eyre!("assistants category IDs must not be empty"));
+            }
+
+            if category.display_name.trim().is_empty() {
+                return Err(eyre!(
+                    "assistants category '{}' must have a non-empty display_name",
+                    category_id
+                ));
+            }
+
+            if category.icon.trim().is_empty() {
+                return Err(eyre!(
+                    "assistants category '{}' must have a non-empty icon",
+                    category_id
+                ));
+            }
+        }
+
         Ok(())
     }
 }
@@ -2791,282 +4167,1515 @@ pub struct ChatSharingConfig {
     pub enabled: bool,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Clone, Default, Facet)]
-pub struct FacetConfig {
-    // Human readable name for the facet.
-    pub display_name: String,
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
+pub struct OrganizationConfig {
+    // IDs of the chat providers available to the users of the organization.
+    // Defaults to all chat providers.
+    #[serde(default)]
+    pub chat_providers: Option<Vec<String>>,
+    // IDs of the facets available to the users of the organization.
+    // Defaults to all facets.
+    #[serde(default)]
+    pub facets: Option<Vec<String>>,
+}
 
-    // Optional icon identifier (e.g. "iconoir-lightbulb").
-    pub icon: Option<String>,
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
+pub struct AdminConfig {
+    // List of group identifiers whose members can use the admin API.
+    // Defaults to no groups, in which case nobody can use the admin API.
+    #[serde(default)]
+    pub groups: Vec<String>,
+    // Migration of file contents stored inline in the generation inputs of older messages to
+    // file pointers.
+    #[serde(default)]
+    pub file_pointer_migration: FilePointerMigrationConfig,
+    // Spillover of the content of messages that were stored before `chat.content_spillover`
+    // was enabled, or with a higher threshold.
+    #[serde(default)]
+    pub content_spillover_migration: ContentSpilloverMigrationConfig,
+    // Compression of the generation inputs of old messages in archived chats.
+    #[serde(default)]
+    pub generation_input_archival: GenerationInputArchivalConfig,
+    // Periodic scan of a sample of chats for corruptions of their thread of messages, reported
+    // as metrics.
+    #[serde(default)]
+    pub thread_integrity_scan: ThreadIntegrityScanConfig,
+    // Manifest of the prompts each assistant message was generated with, which can be
+    // retrieved through the admin API.
+    #[serde(default)]
+    pub prompt_manifest: PromptManifestConfig,
+}
 
-    // Additional system prompt to inject when this facet is selected.
-    pub additional_system_prompt: Option<PromptSourceSpecification>,
+impl AdminConfig {
+    pub fn is_admin(&self, user_groups: &[String]) -> bool {
+        user_groups
+            .iter()
+            .any(|user_group| self.groups.contains(user_group))
+    }
+}
 
-    // Allowlist of tools for this facet.
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
+pub struct AuthConfig {
+    // How the user of a request is determined.
+    //
+    // May be:
+    // - "jwt" - From the ID token in the `Authorization` header, as forwarded by oauth2-proxy.
+    //   An access token of the user for external APIs like MS Graph is read from the
+    //   `X-Forwarded-Access-Token` header.
+    // - "trusted_headers" - From the identity headers set by a proxy that already authenticated
+    //   the user, see `auth.trusted_headers`. Features that need an access token of the user,
+    //   like the SharePoint integration, are unavailable.
+    // Defaults to "jwt".
     #[serde(default)]
-    pub tool_call_allowlist: Vec<String>,
-
-    // Optional model settings overrides for this facet.
+    pub mode: AuthMode,
+    // Identity headers of the "trusted_headers" mode.
     #[serde(default)]
-    pub model_settings: ModelSettings,
+    pub trusted_headers: TrustedHeadersConfig,
+}
 
-    // Disable the global facet prompt template for this facet.
-    // Defaults to `false`.
-    #[serde(default)]
-    pub disable_facet_prompt_template: bool,
+impl AuthConfig {
+    pub fn validate(&self) -> Result<(), Report> {
+        if self.mode != AuthMode::TrustedHeaders {
+            return Ok(());
+        }
+        self.trusted_proxy_networks()?;
+        let trusted_headers = &self.trusted_headers;
+        if trusted_headers.user_id_header.trim().is_empty() {
+            return Err(eyre!("auth.trusted_headers.user_id_header cannot be empty"));
+        }
+        if trusted_headers.groups_separator.is_empty() {
+            return Err(eyre!("auth.trusted_headers.groups_separator cannot be empty"));
+        }
+        if trusted_headers.issuer.trim().is_empty() {
+            return Err(eyre!("auth.trusted_headers.issuer cannot be empty"));
+        }
+        Ok(())
+    }
 
-    // Hidden baseline facet: never shown in the facet pool and not
-    // user-selectable. Instead of being toggled by the user, it is injected as
-    // an always-on baseline system prompt at conversation start (scoped by
-    // `hidden_always_active_for_platform`). Its `model_settings` and
-    // `tool_call_allowlist` still apply, per request, like any active facet.
-    // Defaults to `false`.
-    #[serde(default)]
-    pub hidden: bool,
+    /// Parses the networks of the proxies that may set the identity headers, which must not be
+    /// empty in the "trusted_headers" mode. Other modes don't read identity headers, so no
+    /// networks are returned for them.
+    pub fn trusted_proxy_networks(&self) -> Result<Vec<IpNet>, Report> {
+        if self.mode != AuthMode::TrustedHeaders {
+            return Ok(Vec::new());
+        }
+        if self.trusted_headers.trusted_proxies.is_empty() {
+            return Err(eyre!(
+                "auth.trusted_headers.trusted_proxies must not be empty in the trusted_headers mode"
+            ));
+        }
+        self.trusted_headers.trusted_proxy_networks()
+    }
+}
 
-    // Restricts auto-activation of a hidden facet to requests on this platform
-    // (matched against the `X-Erato-Platform` header, e.g. "outlook"). `None`
-    // applies on all platforms. Only meaningful together with `hidden = true`.
-    #[serde(default)]
-    pub hidden_always_active_for_platform: Option<String>,
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy, Default, Facet)]
+#[serde(rename_all = "snake_case")]
+#[facet(rename_all = "snake_case")]
+#[repr(C)]
+pub enum AuthMode {
+    #[default]
+    Jwt,
+    TrustedHeaders,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
-pub struct ActionFacetConfig {
-    // Human readable name for the action facet.
-    pub display_name: String,
+impl AuthMode {
+    /// Whether requests can carry an access token of the user for external APIs.
+    pub fn provides_access_token(&self) -> bool {
+        *self == AuthMode::Jwt
+    }
+}
 
-    // Optional platform identifier (e.g. "slack", "teams").
-    pub platform: Option<String>,
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct TrustedHeadersConfig {
+    // Networks of the proxies that may set the identity headers, in CIDR notation (e.g.
+    // "10.0.0.0/8") or as single addresses. Requests from any other address are rejected with
+    // `401 Unauthorized`, regardless of their headers.
+    // Defaults to no networks, and must be set in the "trusted_headers" mode.
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+    // Header with the unique ID of the user. Requests without it are rejected with
+    // `401 Unauthorized`.
+    // Defaults to "X-Forwarded-User".
+    #[serde(default = "default_trusted_headers_user_id_header")]
+    pub user_id_header: String,
+    // Header with the email address of the user.
+    // Defaults to "X-Forwarded-Email".
+    #[serde(default = "default_trusted_headers_email_header")]
+    pub email_header: String,
+    // Header with the display name of the user.
+    // Defaults to "X-Forwarded-Preferred-Username".
+    #[serde(default = "default_trusted_headers_name_header")]
+    pub name_header: String,
+    // Header with the groups of the user, separated by `groups_separator`. The groups are used
+    // like the `groups` claim of an ID token, e.g. for `admin.groups` and share grants.
+    // Defaults to "X-Forwarded-Groups".
+    #[serde(default = "default_trusted_headers_groups_header")]
+    pub groups_header: String,
+    // Separator of the groups in `groups_header`. Whitespace around the groups is trimmed.
+    // Defaults to ",".
+    #[serde(default = "default_trusted_headers_groups_separator")]
+    pub groups_separator: String,
+    // Header with the preferred language of the user, which is used like the `xms_pl` claim of
+    // an ID token in `i18n.language.language_detection_priority`.
+    // Defaults to "X-Forwarded-Preferred-Language".
+    #[serde(default = "default_trusted_headers_preferred_language_header")]
+    pub preferred_language_header: String,
+    // Issuer the users are stored with, in place of the `iss` claim of an ID token. To keep the
+    // users of the "jwt" mode, set it to the issuer of the ID tokens and `user_id_header` to a
+    // header with their `sub` claim.
+    // Defaults to "trusted-headers".
+    #[serde(default = "default_trusted_headers_issuer")]
+    pub issuer: String,
+}
+
+impl Default for TrustedHeadersConfig {
+    fn default() -> Self {
+        Self {
+            trusted_proxies: Vec::new(),
+            user_id_header: default_trusted_headers_user_id_header(),
+            email_header: default_trusted_headers_email_header(),
+            name_header: default_trusted_headers_name_header(),
+            groups_header: default_trusted_headers_groups_header(),
+            groups_separator: default_trusted_headers_groups_separator(),
+            preferred_language_header: default_trusted_headers_preferred_language_header(),
+            issuer: default_trusted_headers_issuer(),
+        }
+    }
+}
 
-    // Template string for the action facet.
-    pub template: String,
+impl TrustedHeadersConfig {
+    /// Parses `trusted_proxies`, treating single addresses as networks of one address.
+    pub fn trusted_proxy_networks(&self) -> Result<Vec<IpNet>, Report> {
+        self.trusted_proxies
+            .iter()
+            .map(|proxy| {
+                proxy
+                    .parse::<IpNet>()
+                    .or_else(|_| proxy.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| {
+                        eyre!(
+                            "auth.trusted_headers.trusted_proxies contains '{}', which is neither a CIDR network nor an IP address",
+                            proxy
+                        )
+                    })
+            })
+            .collect()
+    }
+}
 
-    // List of allowed argument names for the template.
-    #[serde(default)]
-    pub allowed_args: Vec<String>,
+fn default_trusted_headers_user_id_header() -> String {
+    "X-Forwarded-User".to_string()
+}
 
-    // Fixed identifiers of client-side actions (e.g. "outlook.reply") the
-    // model may propose via the `propose_client_action` tool when this facet
-    // is active. The client application validates the proposal and performs
-    // the action only after user confirmation.
-    #[serde(default)]
-    pub client_actions: Vec<String>,
+fn default_trusted_headers_email_header() -> String {
+    "X-Forwarded-Email".to_string()
+}
 
-    // How the client should surface a proposed client action:
-    // - "render_buttons" (default): show action buttons; the user clicks.
-    // - "auto_prompt": the client may immediately surface the proposed action
-    //   after a fresh assistant completion, subject to the user's local
-    //   approval preferences.
-    // Only meaningful when `client_actions` is non-empty.
-    pub presentation: Option<String>,
+fn default_trusted_headers_name_header() -> String {
+    "X-Forwarded-Preferred-Username".to_string()
+}
 
-    // Subset of `client_actions` for which the deployment ENFORCES a
-    // per-use confirmation: the client must always ask and must not offer
-    // (or honor) a persistent "always allow" for these actions. Users may
-    // still deny them entirely.
-    #[serde(default)]
-    pub client_actions_always_ask: Vec<String>,
+fn default_trusted_headers_groups_header() -> String {
+    "X-Forwarded-Groups".to_string()
+}
 
-    // Allowlist of tools the model may call when this action facet is active,
-    // using the same `namespace/*` / `server/tool` pattern syntax as regular
-    // facets' `tool_call_allowlist`. Selects both top-level `client_tools` (by
-    // their `namespace/name`, e.g. `outlook/*`) and MCP tools. Empty = none.
-    #[serde(default)]
-    pub tool_call_allowlist: Vec<String>,
+fn default_trusted_headers_groups_separator() -> String {
+    ",".to_string()
 }
 
-/// Valid values for `ActionFacetConfig::presentation`.
-pub const ACTION_FACET_PRESENTATIONS: [&str; 2] = ["render_buttons", "auto_prompt"];
+fn default_trusted_headers_preferred_language_header() -> String {
+    "X-Forwarded-Preferred-Language".to_string()
+}
 
-/// Top-level `[client_tools]` configuration. Tools live in a map keyed by a
-/// config tool id (`[client_tools.tools.<id>]`) rather than an array, so they
-/// are overrideable / composable across multiple config files (like
-/// `chat_providers`). The intermediate `tools` level leaves room for future
-/// `[client_tools]` sibling keys that set global settings for all client tools.
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
-pub struct ClientToolsConfig {
-    /// Client tools keyed by config tool id. The id is the override handle; the
-    /// model-facing name is each tool's `name` field.
+fn default_trusted_headers_issuer() -> String {
+    "trusted-headers".to_string()
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct FilePointerMigrationConfig {
+    // If true, the migration is started in the background on startup, resuming where it stopped
+    // before. It can also be started through the admin API.
+    // Defaults to `false`.
     #[serde(default)]
-    pub tools: HashMap<String, ClientToolConfig>,
+    pub run_on_startup: bool,
+
+    // Number of messages processed per batch.
+    // Defaults to 100.
+    #[serde(default = "default_file_pointer_migration_batch_size")]
+    pub batch_size: u64,
+
+    // Time in milliseconds to wait between two batches, limiting the load on the database.
+    // Defaults to 1000.
+    #[serde(default = "default_file_pointer_migration_batch_interval_ms")]
+    pub batch_interval_ms: u64,
 }
 
-/// A client-executed tool, declared top-level in `[client_tools.tools.<id>]` and
-/// selected into a context by an action facet's `tool_call_allowlist` (via its
-/// `namespace/name`). The model may CALL it mid-turn; the client (add-in / web
-/// app) executes it and POSTs the result back, which the backend feeds into the
-/// same agentic loop as a tool response — like an MCP tool, but executed on the
-/// client. Distinct from `client_actions`, which are terminal, one-way,
-/// user-confirmed mutations.
-///
-/// Returning client tools MUST be read-only / idempotent: a backend restart
-/// drops the parked turn, so a client may re-execute on recovery. Mutations
-/// must use the terminal `client_actions` (`propose_client_action`) path.
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
-pub struct ClientToolConfig {
-    /// Tool name exposed to the model (e.g. "fetch_availability", selected in
-    /// allowlists as "outlook/fetch_availability" via its namespace). Must be
-    /// non-empty and must not collide with the reserved
-    /// `propose_client_action` name; the `namespace/name` pair must be unique.
-    /// Portable names match `^[a-zA-Z0-9_-]{1,64}$` — that is the
-    /// cross-provider intersection (OpenAI/Azure and Anthropic reject
-    /// anything else at request time; Gemini additionally accepts dots,
-    /// colons, and up to 128 chars). Names outside the portable set load with
-    /// a startup warning, not an error.
-    pub name: String,
+fn default_file_pointer_migration_batch_size() -> u64 {
+    100
+}
 
-    /// Namespace used for `tool_call_allowlist` selection (e.g. `outlook` →
-    /// selectable as `outlook/<name>` and in bulk via `outlook/*`). Defaults to
-    /// `client`. Purely a selection identity — the model still sees `name`.
+fn default_file_pointer_migration_batch_interval_ms() -> u64 {
+    1000
+}
+
+impl Default for FilePointerMigrationConfig {
+    fn default() -> Self {
+        Self {
+            run_on_startup: false,
+            batch_size: default_file_pointer_migration_batch_size(),
+            batch_interval_ms: default_file_pointer_migration_batch_interval_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct ContentSpilloverMigrationConfig {
+    // If true, the migration is started in the background on startup, resuming where it stopped
+    // before. It can also be started through the admin API.
+    // Defaults to `false`.
     #[serde(default)]
-    pub namespace: Option<String>,
+    pub run_on_startup: bool,
 
-    /// Human-readable description surfaced to the model as the tool description.
-    pub description: String,
+    // Number of oversized messages processed per batch.
+    // Defaults to 100.
+    #[serde(default = "default_file_pointer_migration_batch_size")]
+    pub batch_size: u64,
 
-    /// JSON Schema (as a JSON string) for the tool's input parameters. Parsed
-    /// and validated at config load; surfaced to the model as the tool schema.
-    /// Kept as a string (not a structured value) so the config can keep
-    /// deriving `Eq` (`serde_json::Value` is not `Eq`).
-    pub parameters: String,
+    // Time in milliseconds to wait between two batches, limiting the load on the database and
+    // the file storage.
+    // Defaults to 1000.
+    #[serde(default = "default_file_pointer_migration_batch_interval_ms")]
+    pub batch_interval_ms: u64,
+}
 
-    /// Optional per-tool park timeout in milliseconds. The agentic loop holds
-    /// open at most this long awaiting the client's result before injecting an
-    /// error tool response and continuing.
+impl Default for ContentSpilloverMigrationConfig {
+    fn default() -> Self {
+        Self {
+            run_on_startup: false,
+            batch_size: default_file_pointer_migration_batch_size(),
+            batch_interval_ms: default_file_pointer_migration_batch_interval_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct GenerationInputArchivalConfig {
+    // If true, the archival is started in the background on startup, resuming where it stopped
+    // before. It can also be started through the admin API, which can also estimate the savings
+    // without changing anything.
+    // Defaults to `false`.
     #[serde(default)]
-    pub timeout_ms: Option<u64>,
+    pub run_on_startup: bool,
+
+    // Minimum age in days of the assistant messages in archived chats whose generation inputs
+    // are archived.
+    // Defaults to 90.
+    #[serde(default = "default_generation_input_archival_min_age_days")]
+    pub min_age_days: u64,
+
+    // Where the compressed generation inputs are stored.
+    // Defaults to "database".
+    #[serde(default)]
+    pub storage: GenerationInputArchiveStorage,
+
+    // zstd compression level, from 1 (fastest) to 22 (smallest).
+    // Defaults to 3.
+    #[serde(default = "default_generation_input_archival_compression_level")]
+    pub compression_level: i32,
+
+    // Number of restored generation inputs kept in memory, so that generating repeatedly in a
+    // chat that was archived before doesn't decompress them every time.
+    // Defaults to 100.
+    #[serde(default = "default_generation_input_archival_cache_entries")]
+    pub cache_entries: u64,
+
+    // Number of messages processed per batch.
+    // Defaults to 100.
+    #[serde(default = "default_file_pointer_migration_batch_size")]
+    pub batch_size: u64,
+
+    // Time in milliseconds to wait between two batches, limiting the load on the database and
+    // the file storage.
+    // Defaults to 1000.
+    #[serde(default = "default_file_pointer_migration_batch_interval_ms")]
+    pub batch_interval_ms: u64,
 }
 
-/// Default namespace for a client tool when none is configured.
-pub const DEFAULT_CLIENT_TOOL_NAMESPACE: &str = "client";
+fn default_generation_input_archival_min_age_days() -> u64 {
+    90
+}
+
+fn default_generation_input_archival_compression_level() -> i32 {
+    3
+}
+
+fn default_generation_input_archival_cache_entries() -> u64 {
+    100
+}
+
+impl Default for GenerationInputArchivalConfig {
+    fn default() -> Self {
+        Self {
+            run_on_startup: false,
+            min_age_days: default_generation_input_archival_min_age_days(),
+            storage: GenerationInputArchiveStorage::default(),
+            compression_level: default_generation_input_archival_compression_level(),
+            cache_entries: default_generation_input_archival_cache_entries(),
+            batch_size: default_file_pointer_migration_batch_size(),
+            batch_interval_ms: default_file_pointer_migration_batch_interval_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy, Default, Facet)]
+#[serde(rename_all = "snake_case")]
+#[facet(rename_all = "snake_case")]
+#[repr(C)]
+pub enum GenerationInputArchiveStorage {
+    // In the `archived_generation_inputs` table of the database.
+    #[default]
+    Database,
+    // In the default file storage provider.
+    FileStorage,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct ThreadIntegrityScanConfig {
+    // If true, a sample of the chats is checked in the background at every interval, and the
+    // number of corrupted chats is reported in the `erato_thread_integrity_*` metrics.
+    // Nothing is repaired; see the thread repair of the admin API.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+
+    // Time in seconds between two scans.
+    // Defaults to 604800 (one week).
+    #[serde(default = "default_thread_integrity_scan_interval_secs")]
+    pub interval_secs: u64,
+
+    // Number of randomly selected chats checked per scan.
+    // Defaults to 1000.
+    #[serde(default = "default_thread_integrity_scan_sample_size")]
+    pub sample_size: u64,
+}
+
+fn default_thread_integrity_scan_interval_secs() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_thread_integrity_scan_sample_size() -> u64 {
+    1000
+}
+
+impl Default for ThreadIntegrityScanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: default_thread_integrity_scan_interval_secs(),
+            sample_size: default_thread_integrity_scan_sample_size(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
+pub struct PromptManifestConfig {
+    // If true, the full text of the prompts is stored in the manifest, in addition to their
+    // hashes and lengths. The text of the prompts may contain user preferences of the user, like
+    // their nickname or custom instructions.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub store_full_text: bool,
+
+    // If true, the owner of a chat can retrieve the prompt manifests of its messages through the
+    // admin API, in addition to admins.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub visible_to_chat_owner: bool,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Default, Facet)]
+pub struct DebugConfig {
+    // Whether message submissions can be sent with `dry_run: true`, which returns the
+    // composed LLM request instead of generating a response.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub allow_dry_run: bool,
+    // Capture of the traffic to the chat providers, for reproducing provider issues.
+    #[serde(default)]
+    pub capture_provider_traffic: ProviderTrafficCaptureConfig,
+}
+
+impl DebugConfig {
+    /// Validates the debug configuration.
+    pub fn validate(&self, is_production_environment: bool) -> Result<(), Report> {
+        self.capture_provider_traffic
+            .validate(is_production_environment)
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Facet)]
+pub struct ProviderTrafficCaptureConfig {
+    // Whether the requests to the chat providers are captured for sampled generations.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+    // Fraction of generations that are captured, between 0 and 1.
+    // Defaults to 1.
+    #[serde(default = "default_provider_traffic_capture_sample_rate")]
+    pub sample_rate: f64,
+    // Where captures are written to.
+    // Defaults to `storage`.
+    #[serde(default)]
+    pub target: ProviderTrafficCaptureTarget,
+    // Directory that captures are written to with the `file` target.
+    // Defaults to the temporary directory of the system.
+    pub directory: Option<String>,
+    // Hours after which captures are deleted.
+    // Defaults to 24.
+    #[serde(default = "default_provider_traffic_capture_retention_hours")]
+    pub retention_hours: u64,
+    // Whether the responses of successful requests are captured as well.
+    // Errors returned by the provider are always captured.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub include_responses: bool,
+    // Regular expressions whose matches are redacted from captures.
+    // Defaults to patterns for email addresses and common API key formats.
+    #[serde(default = "default_provider_traffic_capture_redact_patterns")]
+    pub redact_patterns: Vec<String>,
+    // Whether the capture may be enabled in the `production` environment.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub allow_in_production: bool,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Copy, Facet)]
+#[serde(rename_all = "snake_case")]
+#[facet(rename_all = "snake_case")]
+#[repr(C)]
+pub enum ProviderTrafficCaptureTarget {
+    // A local directory (see `directory`).
+    File,
+    // The default file storage provider.
+    #[default]
+    Storage,
+}
+
+fn default_provider_traffic_capture_sample_rate() -> f64 {
+    1.0
+}
+
+fn default_provider_traffic_capture_retention_hours() -> u64 {
+    24
+}
+
+fn default_provider_traffic_capture_redact_patterns() -> Vec<String> {
+    vec![
+        r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}".to_string(),
+        r"\b(?:sk|pk|rk)-[A-Za-z0-9_-]{16,}".to_string(),
+        r"(?i)\bbearer\s+[A-Za-z0-9._~+/=-]+".to_string(),
+    ]
+}
+
+impl Default for ProviderTrafficCaptureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sample_rate: default_provider_traffic_capture_sample_rate(),
+            target: ProviderTrafficCaptureTarget::default(),
+            directory: None,
+            retention_hours: default_provider_traffic_capture_retention_hours(),
+            include_responses: false,
+            redact_patterns: default_provider_traffic_capture_redact_patterns(),
+            allow_in_production: false,
+        }
+    }
+}
+
+impl ProviderTrafficCaptureConfig {
+    /// Validates the provider traffic capture configuration.
+    pub fn validate(&self, is_production_environment: bool) -> Result<(), Report> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if is_production_environment && !self.allow_in_production {
+            return Err(eyre!(
+                "debug.capture_provider_traffic can't be enabled in the `production` environment unless `debug.capture_provider_traffic.allow_in_production` is set"
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.sample_rate) {
+            return Err(eyre!(
+                "debug.capture_provider_traffic.sample_rate must be between 0 and 1"
+            ));
+        }
+        if self.retention_hours == 0 {
+            return Err(eyre!(
+                "debug.capture_provider_traffic.retention_hours must be greater than 0"
+            ));
+        }
+        for pattern in &self.redact_patterns {
+            Regex::new(pattern).map_err(|err| {
+                eyre!(
+                    "debug.capture_provider_traffic.redact_patterns contains an invalid regex `{pattern}`: {err}"
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Default, Facet)]
+pub struct FacetConfig {
+    // Human readable name for the facet.
+    pub display_name: String,
+
+    // Optional icon identifier (e.g. "iconoir-lightbulb").
+    pub icon: Option<String>,
+
+    // Additional system prompt to inject when this facet is selected.
+    pub additional_system_prompt: Option<PromptSourceSpecification>,
+
+    // Allowlist of tools for this facet.
+    #[serde(default)]
+    pub tool_call_allowlist: Vec<String>,
+
+    // Optional model settings overrides for this facet.
+    #[serde(default)]
+    pub model_settings: ModelSettings,
+
+    // Disable the global facet prompt template for this facet.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub disable_facet_prompt_template: bool,
+
+    // Hidden baseline facet: never shown in the facet pool and not
+    // user-selectable. Instead of being toggled by the user, it is injected as
+    // an always-on baseline system prompt at conversation start (scoped by
+    // `hidden_always_active_for_platform`). Its `model_settings` and
+    // `tool_call_allowlist` still apply, per request, like any active facet.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub hidden: bool,
+
+    // Restricts auto-activation of a hidden facet to requests on this platform
+    // (matched against the `X-Erato-Platform` header, e.g. "outlook"). `None`
+    // applies on all platforms. Only meaningful together with `hidden = true`.
+    #[serde(default)]
+    pub hidden_always_active_for_platform: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
+pub struct ActionFacetConfig {
+    // Human readable name for the action facet.
+    pub display_name: String,
+
+    // Optional platform identifier (e.g. "slack", "teams").
+    pub platform: Option<String>,
+
+    // Template string for the action facet.
+    pub template: String,
+
+    // List of allowed argument names for the template.
+    #[serde(default)]
+    pub allowed_args: Vec<String>,
+
+    // Fixed identifiers of client-side actions (e.g. "outlook.reply") the
+    // model may propose via the `propose_client_action` tool when this facet
+    // is active. The client application validates the proposal and performs
+    // the action only after user confirmation.
+    #[serde(default)]
+    pub client_actions: Vec<String>,
+
+    // How the client should surface a proposed client action:
+    // - "render_buttons" (default): show action buttons; the user clicks.
+    // - "auto_prompt": the client may immediately surface the proposed action
+    //   after a fresh assistant completion, subject to the user's local
+    //   approval preferences.
+    // Only meaningful when `client_actions` is non-empty.
+    pub presentation: Option<String>,
+
+    // Subset of `client_actions` for which the deployment ENFORCES a
+    // per-use confirmation: the client must always ask and must not offer
+    // (or honor) a persistent "always allow" for these actions. Users may
+    // still deny them entirely.
+    #[serde(default)]
+    pub client_actions_always_ask: Vec<String>,
+
+    // Allowlist of tools the model may call when this action facet is active,
+    // using the same `namespace/*` / `server/tool` pattern syntax as regular
+    // facets' `tool_call_allowlist`. Selects both top-level `client_tools` (by
+    // their `namespace/name`, e.g. `outlook/*`) and MCP tools. Empty = none.
+    #[serde(default)]
+    pub tool_call_allowlist: Vec<String>,
+}
+
+/// Valid values for `ActionFacetConfig::presentation`.
+pub const ACTION_FACET_PRESENTATIONS: [&str; 2] = ["render_buttons", "auto_prompt"];
+
+/// Top-level `[client_tools]` configuration. Tools live in a map keyed by a
+/// config tool id (`[client_tools.tools.<id>]`) rather than an array, so they
+/// are overrideable / composable across multiple config files (like
+/// `chat_providers`). The intermediate `tools` level leaves room for future
+/// `[client_tools]` sibling keys that set global settings for all client tools.
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
+pub struct ClientToolsConfig {
+    /// Client tools keyed by config tool id. The id is the override handle; the
+    /// model-facing name is each tool's `name` field.
+    #[serde(default)]
+    pub tools: HashMap<String, ClientToolConfig>,
+}
+
+/// A client-executed tool, declared top-level in `[client_tools.tools.<id>]` and
+/// selected into a context by an action facet's `tool_call_allowlist` (via its
+/// `namespace/name`). The model may CALL it mid-turn; the client (add-in / web
+/// app) executes it and POSTs the result back, which the backend feeds into the
+/// same agentic loop as a tool response — like an MCP tool, but executed on the
+/// client. Distinct from `client_actions`, which are terminal, one-way,
+/// user-confirmed mutations.
+///
+/// Returning client tools MUST be read-only / idempotent: a backend restart
+/// drops the parked turn, so a client may re-execute on recovery. Mutations
+/// must use the terminal `client_actions` (`propose_client_action`) path.
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
+pub struct ClientToolConfig {
+    /// Tool name exposed to the model (e.g. "fetch_availability", selected in
+    /// allowlists as "outlook/fetch_availability" via its namespace). Must be
+    /// non-empty and must not collide with the reserved
+    /// `propose_client_action` name; the `namespace/name` pair must be unique.
+    /// Portable names match `^[a-zA-Z0-9_-]{1,64}$` — that is the
+    /// cross-provider intersection (OpenAI/Azure and Anthropic reject
+    /// anything else at request time; Gemini additionally accepts dots,
+    /// colons, and up to 128 chars). Names outside the portable set load with
+    /// a startup warning, not an error.
+    pub name: String,
+
+    /// Namespace used for `tool_call_allowlist` selection (e.g. `outlook` →
+    /// selectable as `outlook/<name>` and in bulk via `outlook/*`). Defaults to
+    /// `client`. Purely a selection identity — the model still sees `name`.
+    #[serde(default)]
+    pub namespace: Option<String>,
+
+    /// Human-readable description surfaced to the model as the tool description.
+    pub description: String,
+
+    /// JSON Schema (as a JSON string) for the tool's input parameters. Parsed
+    /// and validated at config load; surfaced to the model as the tool schema.
+    /// Kept as a string (not a structured value) so the config can keep
+    /// deriving `Eq` (`serde_json::Value` is not `Eq`).
+    pub parameters: String,
+
+    /// Optional per-tool park timeout in milliseconds. The agentic loop holds
+    /// open at most this long awaiting the client's result before injecting an
+    /// error tool response and continuing.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+/// Default namespace for a client tool when none is configured.
+pub const DEFAULT_CLIENT_TOOL_NAMESPACE: &str = "client";
+
+impl ClientToolConfig {
+    /// The tool's namespace, or the `client` default.
+    pub fn namespace_or_default(&self) -> &str {
+        self.namespace
+            .as_deref()
+            .unwrap_or(DEFAULT_CLIENT_TOOL_NAMESPACE)
+    }
+
+    /// The tool's `namespace/name` selection identity (matched against
+    /// `tool_call_allowlist` patterns).
+    pub fn qualified_name(&self) -> String {
+        format!("{}/{}", self.namespace_or_default(), self.name)
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct CachesConfig {
+    // Maximum size in MB for file bytes cache (raw file bytes for both text and images)
+    // Defaults to 100MB
+    #[serde(default = "default_file_bytes_cache_mb")]
+    pub file_bytes_cache_mb: u64,
+
+    // Maximum size in MB for file contents cache (parsed text files only)
+    // Defaults to 100MB
+    #[serde(default = "default_file_contents_cache_mb")]
+    pub file_contents_cache_mb: u64,
+
+    // Maximum size in MB for token count cache
+    // Defaults to 100MB
+    #[serde(default = "default_token_count_cache_mb")]
+    pub token_count_cache_mb: u64,
+
+    // Global concurrency limit for file processing work on cache misses.
+    // Applies to file downloads, text extraction, and token estimation.
+    // Defaults to 4.
+    #[serde(default = "default_file_processing_parallelism")]
+    pub file_processing_parallelism: usize,
+}
+
+fn default_file_bytes_cache_mb() -> u64 {
+    100
+}
+
+fn default_file_contents_cache_mb() -> u64 {
+    100
+}
+
+fn default_token_count_cache_mb() -> u64 {
+    100
+}
+
+fn default_file_processing_parallelism() -> usize {
+    4
+}
+
+impl Default for CachesConfig {
+    fn default() -> Self {
+        Self {
+            file_bytes_cache_mb: default_file_bytes_cache_mb(),
+            file_contents_cache_mb: default_file_contents_cache_mb(),
+            token_count_cache_mb: default_token_count_cache_mb(),
+            file_processing_parallelism: default_file_processing_parallelism(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct GenerationStatusConfig {
+    // Interval in seconds at which in-flight generations refresh their
+    // heartbeat on the chats row.
+    // Defaults to 10.
+    #[serde(default = "default_generation_status_heartbeat_interval_secs")]
+    pub heartbeat_interval_secs: u64,
+
+    // Seconds after the last heartbeat before a running generation is
+    // considered stale. Stale generations are marked as errored and no longer
+    // reported as running.
+    // Defaults to 30.
+    #[serde(default = "default_generation_status_stale_after_secs")]
+    pub stale_after_secs: u64,
+
+    // Seconds a completed or errored generation remains visible in the
+    // generating-chats endpoint after it ended.
+    // Defaults to 60.
+    #[serde(default = "default_generation_status_terminal_retention_secs")]
+    pub terminal_retention_secs: u64,
+
+    // Interval in seconds at which the content streamed so far is saved as a draft of the
+    // assistant message, so that a crash of the process loses at most this interval of content.
+    // Set to 0 to disable saving drafts.
+    // Defaults to 5.
+    #[serde(default = "default_generation_status_content_draft_flush_interval_secs")]
+    pub content_draft_flush_interval_secs: u64,
+
+    // Number of streamed content deltas after which the draft is saved, even if the flush
+    // interval hasn't passed yet. Set to 0 to only save drafts based on the interval.
+    // Defaults to 50.
+    #[serde(default = "default_generation_status_content_draft_flush_deltas")]
+    pub content_draft_flush_deltas: u64,
+
+    // Minimum time in milliseconds between two saves of the draft of a message, capping the
+    // write rate of fast streams.
+    // Defaults to 1000.
+    #[serde(default = "default_generation_status_content_draft_min_flush_interval_ms")]
+    pub content_draft_min_flush_interval_ms: u64,
+
+    // Identifier of this backend replica. It is stored on the chats row with the lease of every
+    // generation the replica runs, and reported to clients that try to start another generation
+    // in the chat on another replica. Must be unique per replica, e.g. the name of the pod.
+    // Defaults to a random ID that is generated at startup.
+    #[serde(default)]
+    pub replica_id: Option<String>,
+}
+
+fn default_generation_status_heartbeat_interval_secs() -> u64 {
+    10
+}
+
+fn default_generation_status_stale_after_secs() -> u64 {
+    30
+}
+
+fn default_generation_status_terminal_retention_secs() -> u64 {
+    60
+}
+
+fn default_generation_status_content_draft_flush_interval_secs() -> u64 {
+    5
+}
+
+fn default_generation_status_content_draft_flush_deltas() -> u64 {
+    50
+}
+
+fn default_generation_status_content_draft_min_flush_interval_ms() -> u64 {
+    1000
+}
+
+impl Default for GenerationStatusConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval_secs: default_generation_status_heartbeat_interval_secs(),
+            stale_after_secs: default_generation_status_stale_after_secs(),
+            terminal_retention_secs: default_generation_status_terminal_retention_secs(),
+            content_draft_flush_interval_secs:
+                default_generation_status_content_draft_flush_interval_secs(),
+            content_draft_flush_deltas: default_generation_status_content_draft_flush_deltas(),
+            content_draft_min_flush_interval_ms:
+                default_generation_status_content_draft_min_flush_interval_ms(),
+            replica_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct ScheduledMessagesConfig {
+    // How far in the future a message can be scheduled, in seconds.
+    // Defaults to 30 days.
+    #[serde(default = "default_scheduled_messages_max_horizon_secs")]
+    pub max_horizon_secs: u64,
+
+    // Interval in seconds at which the scheduler checks for due messages.
+    // Defaults to 30.
+    #[serde(default = "default_scheduled_messages_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+
+    // Number of attempts to submit a scheduled message before it is marked as failed.
+    // Defaults to 3.
+    #[serde(default = "default_scheduled_messages_max_attempts")]
+    pub max_attempts: u32,
+
+    // Seconds to wait before retrying a failed attempt.
+    // Defaults to 300.
+    #[serde(default = "default_scheduled_messages_retry_delay_secs")]
+    pub retry_delay_secs: u64,
+}
+
+fn default_scheduled_messages_max_horizon_secs() -> u64 {
+    30 * 24 * 60 * 60
+}
+
+fn default_scheduled_messages_poll_interval_secs() -> u64 {
+    30
+}
+
+fn default_scheduled_messages_max_attempts() -> u32 {
+    3
+}
+
+fn default_scheduled_messages_retry_delay_secs() -> u64 {
+    300
+}
+
+impl Default for ScheduledMessagesConfig {
+    fn default() -> Self {
+        Self {
+            max_horizon_secs: default_scheduled_messages_max_horizon_secs(),
+            poll_interval_secs: default_scheduled_messages_poll_interval_secs(),
+            max_attempts: default_scheduled_messages_max_attempts(),
+            retry_delay_secs: default_scheduled_messages_retry_delay_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
+pub struct TelemetryConfig {
+    // Anonymous usage reports, see `UsageReportingConfig`.
+    #[serde(default)]
+    pub usage_reporting: UsageReportingConfig,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct UsageReportingConfig {
+    // If true, a report of aggregate usage numbers is sent to `endpoint` at every interval.
+    //
+    // The report only consists of the enabled features, counts that are bucketed into ranges,
+    // and the version of Erato. It never contains any content, names or IDs of users, chats or
+    // other data. The report that would be sent can be inspected with
+    // `GET /admin/telemetry/preview` before enabling it.
+    //
+    // Reports are signed with a random ID of the installation, which is created when usage
+    // reporting is enabled, and deleted when it is disabled again.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+
+    // URL the reports are sent to with a POST request. Required if `enabled` is true.
+    pub endpoint: Option<String>,
+
+    // Time in hours between two reports.
+    // Defaults to 24.
+    #[serde(default = "default_usage_reporting_interval_hours")]
+    pub interval_hours: u64,
+}
+
+fn default_usage_reporting_interval_hours() -> u64 {
+    24
+}
+
+impl Default for UsageReportingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            interval_hours: default_usage_reporting_interval_hours(),
+        }
+    }
+}
+
+impl UsageReportingConfig {
+    /// Validates the usage reporting configuration.
+    pub fn validate(&self) -> Result<(), Report> {
+        if self.interval_hours == 0 {
+            return Err(eyre!(
+                "telemetry.usage_reporting.interval_hours must be greater than 0"
+            ));
+        }
+        if self.enabled
+            && self
+                .endpoint
+                .as_deref()
+                .is_none_or(|endpoint| endpoint.trim().is_empty())
+        {
+            return Err(eyre!(
+                "Usage reporting enabled but `telemetry.usage_reporting.endpoint` is not set"
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct WebSocketStreamingConfig {
+    // Whether the `/me/ws` endpoint accepts connections.
+    // Defaults to false.
+    #[serde(default)]
+    pub enabled: bool,
+
+    // Maximum number of submit and resume operations that stream events over one connection at
+    // the same time.
+    // Defaults to 4.
+    #[serde(default = "default_websocket_streaming_max_operations_per_connection")]
+    pub max_operations_per_connection: usize,
+
+    // Seconds without any frame from the client and without a streaming operation after which
+    // the connection is closed.
+    // Defaults to 300.
+    #[serde(default = "default_websocket_streaming_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+}
+
+fn default_websocket_streaming_max_operations_per_connection() -> usize {
+    4
+}
+
+fn default_websocket_streaming_idle_timeout_secs() -> u64 {
+    300
+}
+
+impl Default for WebSocketStreamingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_operations_per_connection:
+                default_websocket_streaming_max_operations_per_connection(),
+            idle_timeout_secs: default_websocket_streaming_idle_timeout_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct FileProcessorConfig {
+    /// File processor to use (currently only "kreuzberg" is supported)
+    /// parser-core has been removed as it was unmaintained and yanked
+    #[serde(default = "default_processor")]
+    pub processor: String,
+    /// Maximum size in MB of a file whose contents are extracted. Larger files are reported
+    /// as too large instead of being parsed.
+    /// Defaults to no limit.
+    #[serde(default)]
+    pub max_file_size_mb: Option<u64>,
+    /// Maximum time in seconds for extracting the contents of a file.
+    /// Defaults to 120.
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+}
+
+fn default_processor() -> String {
+    "kreuzberg".to_string()
+}
+
+impl Default for FileProcessorConfig {
+    fn default() -> Self {
+        Self {
+            processor: default_processor(),
+            max_file_size_mb: None,
+            timeout_seconds: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct ChatConfig {
+    // Whether to add a manifest listing all files attached in a turn, together with their
+    // synopses, before the file contents. Only added if more than one file is attached.
+    // Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub file_manifest: bool,
+
+    // Number of sentences at the start of a file that are included in its synopsis.
+    // The synopsis is placed before the contents of each attached file. Set to `0` to only
+    // include the detected language and page or sheet count.
+    // Defaults to 3.
+    #[serde(default = "default_file_synopsis_sentences")]
+    pub file_synopsis_sentences: usize,
+
+    // Caching of generations for identical requests.
+    #[serde(default)]
+    pub generation_cache: GenerationCacheConfig,
+
+    // Minimum time in milliseconds between two `tool_call_arguments_delta` events of the same
+    // tool call. Argument fragments streamed in between are coalesced into the next event.
+    // Set to 0 to send an event for every fragment.
+    // Defaults to 100.
+    #[serde(default = "default_tool_call_arguments_delta_interval_ms")]
+    pub tool_call_arguments_delta_interval_ms: u64,
+
+    // Spillover of the content of oversized messages to the default file storage.
+    #[serde(default)]
+    pub content_spillover: ContentSpilloverConfig,
+
+    // Handling of long messages, like log files that are pasted into the message box.
+    #[serde(default)]
+    pub long_messages: LongMessagesConfig,
+
+    // Whether generations are aborted when their client disconnects, instead of continuing in
+    // the background where they can be resumed. The content generated until then is kept.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub abort_on_client_disconnect: bool,
+
+    // Users in any of these groups can submit messages with the `system` role, e.g. for
+    // automations that steer a chat. Nobody can by default.
+    // Defaults to `[]`.
+    #[serde(default)]
+    pub allow_client_system_messages_groups: Vec<String>,
+
+    // Maximum number of generations that run at the same time on each replica of the backend.
+    // Further generations are rejected with HTTP 429, or queued with `queue_when_limited`.
+    // Unlimited by default.
+    #[serde(default)]
+    pub max_concurrent_generations: Option<usize>,
+
+    // Whether generations over `max_concurrent_generations` wait in a queue until they can
+    // start, instead of being rejected.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub queue_when_limited: bool,
+
+    // Whether the markdown of generated answers is normalized before they are stored. Code fences
+    // and emphasis left open at the end of an answer that was cut off are closed, and a partial
+    // escape sequence at its end is removed. Streamed text deltas are not normalized, so clients
+    // should render the content of the `assistant_message_completed` event.
+    // Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub normalize_markdown: bool,
+
+    // Replay of files from earlier messages next to the newest user message.
+    #[serde(default)]
+    pub file_recency: FileRecencyConfig,
+
+    // Webhooks that post-process generated answers before they are stored and shown, applied in
+    // the order they are listed.
+    // Defaults to `[]`.
+    #[serde(default)]
+    pub output_transformers: Vec<OutputTransformerConfig>,
+
+    // Incognito chats, whose messages are only held in memory and never stored in the database.
+    #[serde(default)]
+    pub ephemeral_chats: EphemeralChatsConfig,
+
+    // Text that is displayed in place of the text of a user message that was submitted without
+    // text, but with attached files. It is not passed to the model.
+    // Defaults to `[file attached]`.
+    #[serde(default = "default_file_only_message_placeholder")]
+    pub file_only_message_placeholder: String,
+}
+
+impl ChatConfig {
+    /// Whether a user with the given groups can submit system messages.
+    pub fn allows_client_system_messages(&self, user_groups: &[String]) -> bool {
+        self.allow_client_system_messages_groups
+            .iter()
+            .any(|group| user_groups.contains(group))
+    }
+
+    /// Validates the chat configuration.
+    pub fn validate(&self) -> Result<(), Report> {
+        let mut names = std::collections::HashSet::new();
+        for transformer in &self.output_transformers {
+            if transformer.name.trim().is_empty() {
+                return Err(eyre!("chat.output_transformers: `name` must not be empty"));
+            }
+            if !names.insert(transformer.name.as_str()) {
+                return Err(eyre!(
+                    "chat.output_transformers: duplicate transformer name '{}'",
+                    transformer.name
+                ));
+            }
+            if transformer.url.trim().is_empty() {
+                return Err(eyre!(
+                    "chat.output_transformers: `url` of transformer '{}' must not be empty",
+                    transformer.name
+                ));
+            }
+            if transformer.timeout_ms == 0 {
+                return Err(eyre!(
+                    "chat.output_transformers: `timeout_ms` of transformer '{}' must be greater than 0",
+                    transformer.name
+                ));
+            }
+            if transformer.buffer_chars == 0 {
+                return Err(eyre!(
+                    "chat.output_transformers: `buffer_chars` of transformer '{}' must be greater than 0",
+                    transformer.name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct OutputTransformerConfig {
+    // Name of the transformer, recorded in the generation metadata of the answers it was applied
+    // to.
+    pub name: String,
+
+    // URL the content is posted to.
+    pub url: String,
+
+    // Secret the requests are signed with. The `X-Erato-Signature` header carries
+    // `sha256=<hex>`, the HMAC-SHA256 of `<timestamp>.<body>`, with the timestamp from the
+    // `X-Erato-Timestamp` header.
+    #[facet(sensitive)]
+    pub secret: SecretConfigString,
+
+    // When the transformer is applied to the answer.
+    // Defaults to `final`.
+    #[serde(default)]
+    pub mode: OutputTransformerMode,
+
+    // Number of characters of streamed text that are passed to the transformer at once.
+    // Only has an effect for `streaming_buffered`.
+    // Defaults to 200.
+    #[serde(default = "default_output_transformer_buffer_chars")]
+    pub buffer_chars: usize,
+
+    // Timeout of each request to the transformer, in milliseconds.
+    // Defaults to 5000.
+    #[serde(default = "default_output_transformer_timeout_ms")]
+    pub timeout_ms: u64,
+
+    // What happens if the transformer fails or can't be reached.
+    // Defaults to `open`.
+    #[serde(default)]
+    pub fail_mode: OutputTransformerFailMode,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Copy, Facet)]
+#[serde(rename_all = "snake_case")]
+#[facet(rename_all = "snake_case")]
+#[repr(C)]
+pub enum OutputTransformerMode {
+    // The content parts of the completed answer are transformed before they are stored.
+    #[default]
+    Final,
+    // The streamed text is transformed in chunks of `buffer_chars` characters.
+    StreamingBuffered,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Clone, Copy, Facet)]
+#[serde(rename_all = "snake_case")]
+#[facet(rename_all = "snake_case")]
+#[repr(C)]
+pub enum OutputTransformerFailMode {
+    // The untransformed content is passed on.
+    #[default]
+    Open,
+    // The generation ends with a `transformer_failed` error.
+    Closed,
+}
+
+fn default_output_transformer_buffer_chars() -> usize {
+    200
+}
+
+fn default_output_transformer_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_file_synopsis_sentences() -> usize {
+    3
+}
+
+fn default_tool_call_arguments_delta_interval_ms() -> u64 {
+    100
+}
+
+fn default_file_only_message_placeholder() -> String {
+    "[file attached]".to_string()
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            file_manifest: true,
+            file_synopsis_sentences: default_file_synopsis_sentences(),
+            generation_cache: GenerationCacheConfig::default(),
+            tool_call_arguments_delta_interval_ms: default_tool_call_arguments_delta_interval_ms(),
+            content_spillover: ContentSpilloverConfig::default(),
+            long_messages: LongMessagesConfig::default(),
+            abort_on_client_disconnect: false,
+            allow_client_system_messages_groups: Vec::new(),
+            max_concurrent_generations: None,
+            queue_when_limited: false,
+            normalize_markdown: true,
+            file_recency: FileRecencyConfig::default(),
+            output_transformers: Vec::new(),
+            ephemeral_chats: EphemeralChatsConfig::default(),
+            file_only_message_placeholder: default_file_only_message_placeholder(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct EphemeralChatsConfig {
+    // Whether users can create ephemeral chats. Their messages are only held in the memory of the
+    // backend replica the chat was created on, and are lost when it expires or the replica
+    // restarts. Can be overridden by the `chat.ephemeral_chats.enabled` feature flag.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+
+    // Seconds after its creation an ephemeral chat expires. Its messages are dropped, and the
+    // files uploaded to it are deleted.
+    // Defaults to 3600 (1 hour).
+    #[serde(default = "default_ephemeral_chats_ttl_secs")]
+    pub ttl_secs: u64,
+
+    // Maximum number of ephemeral chats a user can have at the same time. Further chats are
+    // rejected until one of them expires.
+    // Defaults to 3.
+    #[serde(default = "default_ephemeral_chats_max_chats_per_user")]
+    pub max_chats_per_user: usize,
+}
+
+fn default_ephemeral_chats_ttl_secs() -> u64 {
+    60 * 60
+}
 
-impl ClientToolConfig {
-    /// The tool's namespace, or the `client` default.
-    pub fn namespace_or_default(&self) -> &str {
-        self.namespace
-            .as_deref()
-            .unwrap_or(DEFAULT_CLIENT_TOOL_NAMESPACE)
-    }
+fn default_ephemeral_chats_max_chats_per_user() -> usize {
+    3
+}
 
-    /// The tool's `namespace/name` selection identity (matched against
-    /// `tool_call_allowlist` patterns).
-    pub fn qualified_name(&self) -> String {
-        format!("{}/{}", self.namespace_or_default(), self.name)
+impl Default for EphemeralChatsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: default_ephemeral_chats_ttl_secs(),
+            max_chats_per_user: default_ephemeral_chats_max_chats_per_user(),
+        }
     }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
-pub struct CachesConfig {
-    // Maximum size in MB for file bytes cache (raw file bytes for both text and images)
-    // Defaults to 100MB
-    #[serde(default = "default_file_bytes_cache_mb")]
-    pub file_bytes_cache_mb: u64,
-
-    // Maximum size in MB for file contents cache (parsed text files only)
-    // Defaults to 100MB
-    #[serde(default = "default_file_contents_cache_mb")]
-    pub file_contents_cache_mb: u64,
+pub struct FileRecencyConfig {
+    // Whether files of earlier messages that the newest user message refers to are moved next to
+    // it in the composed prompt, instead of only being included at the position they were first
+    // attached at. A message refers to a file by attaching it again, by mentioning its
+    // `erato_file_id:`, or by listing it in `refresh_file_ids` of the submit request. Only files
+    // that are part of the chat history can be moved.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
 
-    // Maximum size in MB for token count cache
-    // Defaults to 100MB
-    #[serde(default = "default_token_count_cache_mb")]
-    pub token_count_cache_mb: u64,
+    // Maximum number of files that are moved next to the newest user message per generation.
+    // Files in `refresh_file_ids` take precedence over attached and mentioned files.
+    // Defaults to 3.
+    #[serde(default = "default_file_recency_max_files")]
+    pub max_files: usize,
+}
 
-    // Global concurrency limit for file processing work on cache misses.
-    // Applies to file downloads, text extraction, and token estimation.
-    // Defaults to 4.
-    #[serde(default = "default_file_processing_parallelism")]
-    pub file_processing_parallelism: usize,
+fn default_file_recency_max_files() -> usize {
+    3
 }
 
-fn default_file_bytes_cache_mb() -> u64 {
-    100
+impl Default for FileRecencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_files: default_file_recency_max_files(),
+        }
+    }
 }
 
-fn default_file_contents_cache_mb() -> u64 {
-    100
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct ContentSpilloverConfig {
+    // Whether the largest text and tool use parts of a message whose serialized content exceeds
+    // `threshold_bytes` are moved to the default file storage when the message is stored. The
+    // stored message keeps a preview of each moved part.
+    // Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    // Size in bytes of the serialized content of a message above which parts are moved to the
+    // file storage.
+    // Defaults to 262144 (256 KB).
+    #[serde(default = "default_content_spillover_threshold_bytes")]
+    pub threshold_bytes: u64,
+
+    // Number of characters of a moved part that are kept in the message as a preview.
+    // Defaults to 1000.
+    #[serde(default = "default_content_spillover_preview_chars")]
+    pub preview_chars: usize,
 }
 
-fn default_token_count_cache_mb() -> u64 {
-    100
+fn default_content_spillover_threshold_bytes() -> u64 {
+    256 * 1024
 }
 
-fn default_file_processing_parallelism() -> usize {
-    4
+fn default_content_spillover_preview_chars() -> usize {
+    1000
 }
 
-impl Default for CachesConfig {
+impl Default for ContentSpilloverConfig {
     fn default() -> Self {
         Self {
-            file_bytes_cache_mb: default_file_bytes_cache_mb(),
-            file_contents_cache_mb: default_file_contents_cache_mb(),
-            token_count_cache_mb: default_token_count_cache_mb(),
-            file_processing_parallelism: default_file_processing_parallelism(),
+            enabled: true,
+            threshold_bytes: default_content_spillover_threshold_bytes(),
+            preview_chars: default_content_spillover_preview_chars(),
         }
     }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
-pub struct GenerationStatusConfig {
-    // Interval in seconds at which in-flight generations refresh their
-    // heartbeat on the chats row.
-    // Defaults to 10.
-    #[serde(default = "default_generation_status_heartbeat_interval_secs")]
-    pub heartbeat_interval_secs: u64,
+pub struct LongMessagesConfig {
+    // Whether the text of a submitted message that exceeds `max_inline_bytes` is stored as a text
+    // file that is attached to the message, like an uploaded file. The message only keeps a
+    // preview of the text.
+    // Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub auto_attach: bool,
 
-    // Seconds after the last heartbeat before a running generation is
-    // considered stale. Stale generations are marked as errored and no longer
-    // reported as running.
-    // Defaults to 30.
-    #[serde(default = "default_generation_status_stale_after_secs")]
-    pub stale_after_secs: u64,
+    // Size in bytes of the text of a submitted message above which it is attached as a file.
+    // Defaults to 102400 (100 KB).
+    #[serde(default = "default_long_messages_max_inline_bytes")]
+    pub max_inline_bytes: u64,
 
-    // Seconds a completed or errored generation remains visible in the
-    // generating-chats endpoint after it ended.
-    // Defaults to 60.
-    #[serde(default = "default_generation_status_terminal_retention_secs")]
-    pub terminal_retention_secs: u64,
+    // Number of characters of an attached text that are kept in the message as a preview.
+    // Defaults to 2000.
+    #[serde(default = "default_long_messages_preview_chars")]
+    pub preview_chars: usize,
+
+    // Whether text parts that exceed `max_text_part_bytes` are split into several text parts
+    // when a message is stored, so that no single text of a message exceeds it.
+    // Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub split_text_parts: bool,
+
+    // Size in bytes above which a text part is split.
+    // Defaults to 65536 (64 KB).
+    #[serde(default = "default_long_messages_max_text_part_bytes")]
+    pub max_text_part_bytes: u64,
 }
 
-fn default_generation_status_heartbeat_interval_secs() -> u64 {
-    10
+fn default_long_messages_max_inline_bytes() -> u64 {
+    100 * 1024
 }
 
-fn default_generation_status_stale_after_secs() -> u64 {
-    30
+fn default_long_messages_preview_chars() -> usize {
+    2000
 }
 
-fn default_generation_status_terminal_retention_secs() -> u64 {
-    60
+fn default_long_messages_max_text_part_bytes() -> u64 {
+    64 * 1024
 }
 
-impl Default for GenerationStatusConfig {
+impl Default for LongMessagesConfig {
     fn default() -> Self {
         Self {
-            heartbeat_interval_secs: default_generation_status_heartbeat_interval_secs(),
-            stale_after_secs: default_generation_status_stale_after_secs(),
-            terminal_retention_secs: default_generation_status_terminal_retention_secs(),
+            auto_attach: true,
+            max_inline_bytes: default_long_messages_max_inline_bytes(),
+            preview_chars: default_long_messages_preview_chars(),
+            split_text_parts: true,
+            max_text_part_bytes: default_long_messages_max_text_part_bytes(),
         }
     }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
-pub struct FileProcessorConfig {
-    /// File processor to use (currently only "kreuzberg" is supported)
-    /// parser-core has been removed as it was unmaintained and yanked
-    #[serde(default = "default_processor")]
-    pub processor: String,
+pub struct GenerationCacheConfig {
+    // Whether generations are replayed from the cache if the exact same request was sent to the
+    // same chat provider before. The cache is shared by all users.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+
+    // Seconds after which a cached generation is no longer replayed and evicted.
+    // Defaults to 86400 (1 day).
+    #[serde(default = "default_generation_cache_ttl_secs")]
+    pub ttl_secs: u64,
+
+    // Maximum number of cached generations. The oldest ones are evicted beyond that.
+    // Defaults to 10000.
+    #[serde(default = "default_generation_cache_max_entries")]
+    pub max_entries: u64,
+
+    // Whether generations are also cached if tools are offered to the model. Tool calls may have
+    // side effects, so their results are never cached, only answers that did not call a tool.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub cache_with_tools: bool,
+
+    // If set, only generations of chats with one of these assistants are cached.
+    #[serde(default)]
+    pub assistant_ids: Option<Vec<String>>,
+
+    // Milliseconds to wait between the chunks of a replayed generation, to imitate the pacing of
+    // the chat provider. Replayed without delay if `0`.
+    // Defaults to 0.
+    #[serde(default)]
+    pub replay_chunk_delay_ms: u64,
+
+    // Interval in seconds at which expired and surplus cached generations are evicted.
+    // Defaults to 600.
+    #[serde(default = "default_generation_cache_eviction_interval_secs")]
+    pub eviction_interval_secs: u64,
 }
 
-fn default_processor() -> String {
-    "kreuzberg".to_string()
+fn default_generation_cache_ttl_secs() -> u64 {
+    24 * 60 * 60
 }
 
-impl Default for FileProcessorConfig {
+fn default_generation_cache_max_entries() -> u64 {
+    10_000
+}
+
+fn default_generation_cache_eviction_interval_secs() -> u64 {
+    600
+}
+
+impl Default for GenerationCacheConfig {
     fn default() -> Self {
         Self {
-            processor: default_processor(),
+            enabled: false,
+            ttl_secs: default_generation_cache_ttl_secs(),
+            max_entries: default_generation_cache_max_entries(),
+            cache_with_tools: false,
+            assistant_ids: None,
+            replay_chunk_delay_ms: 0,
+            eviction_interval_secs: default_generation_cache_eviction_interval_secs(),
         }
     }
 }
@@ -3290,6 +5899,26 @@ pub struct ExperimentalEntraIdConfig {
     // Defaults to `true`.
     #[serde(default = "default_true")]
     pub auth_via_access_token: bool,
+
+    // Base URL of the Microsoft Graph API, e.g. for national clouds.
+    // Defaults to `https://graph.microsoft.com/v1.0`.
+    #[serde(default = "default_graph_api_base_url")]
+    pub graph_api_base_url: String,
+
+    // Maximum number of Microsoft Graph requests a single request to the organization users or
+    // groups endpoints may make, including retries after throttling.
+    // Must be greater than 0.
+    // Defaults to `10`.
+    #[serde(default = "default_max_graph_calls_per_request")]
+    pub max_graph_calls_per_request: usize,
+}
+
+fn default_graph_api_base_url() -> String {
+    "https://graph.microsoft.com/v1.0".to_string()
+}
+
+fn default_max_graph_calls_per_request() -> usize {
+    10
 }
 
 impl Default for ExperimentalEntraIdConfig {
@@ -3297,6 +5926,8 @@ impl Default for ExperimentalEntraIdConfig {
         Self {
             enabled: false,
             auth_via_access_token: true,
+            graph_api_base_url: default_graph_api_base_url(),
+            max_graph_calls_per_request: default_max_graph_calls_per_request(),
         }
     }
 }
@@ -3310,6 +5941,11 @@ impl ExperimentalEntraIdConfig {
                  Currently, auth_via_access_token must be true when Entra ID is enabled."
             ));
         }
+        if self.max_graph_calls_per_request == 0 {
+            return Err(eyre!(
+                "integrations.experimental_entra_id.max_graph_calls_per_request must be greater than 0"
+            ));
+        }
         Ok(())
     }
 }
@@ -3706,6 +6342,52 @@ pub struct LangfuseConfig {
     // the legacy ingestion endpoint. Defaults to `false`.
     #[serde(default)]
     pub use_otel: bool,
+
+    // Queue for ingestion batches that could not be delivered to Langfuse.
+    #[serde(default)]
+    pub dead_letter_queue: LangfuseDeadLetterQueueConfig,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct LangfuseDeadLetterQueueConfig {
+    // Maximum number of undelivered ingestion batches that are kept for a retry. When the queue
+    // is full, the oldest batches are dropped. `0` disables the queue.
+    // Defaults to `10000`.
+    #[serde(default = "default_langfuse_dead_letter_max_batches")]
+    pub max_batches: u64,
+
+    // Delay in seconds before the queued batches are retried after a failure. The delay doubles
+    // with every failure in a row.
+    // Defaults to `5`.
+    #[serde(default = "default_langfuse_dead_letter_retry_initial_delay_seconds")]
+    pub retry_initial_delay_seconds: u64,
+
+    // Maximum delay in seconds between two retries.
+    // Defaults to `300`.
+    #[serde(default = "default_langfuse_dead_letter_retry_max_delay_seconds")]
+    pub retry_max_delay_seconds: u64,
+}
+
+impl Default for LangfuseDeadLetterQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_batches: default_langfuse_dead_letter_max_batches(),
+            retry_initial_delay_seconds: default_langfuse_dead_letter_retry_initial_delay_seconds(),
+            retry_max_delay_seconds: default_langfuse_dead_letter_retry_max_delay_seconds(),
+        }
+    }
+}
+
+fn default_langfuse_dead_letter_max_batches() -> u64 {
+    10_000
+}
+
+fn default_langfuse_dead_letter_retry_initial_delay_seconds() -> u64 {
+    5
+}
+
+fn default_langfuse_dead_letter_retry_max_delay_seconds() -> u64 {
+    300
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
@@ -3737,12 +6419,66 @@ pub struct ModelCapabilities {
     // Whether the model supports providing a verbosity parameter (for future support of GPT-5-type models)
     #[serde(default)]
     pub supports_verbosity: bool,
+    // Whether the model supports tool calls. Models without it are not recommended for chats
+    // that use tools. Defaults to true.
+    #[serde(default = "default_supports_tools")]
+    pub supports_tools: bool,
+    // Whether the provider caches prompt prefixes that are marked as cacheable (e.g. Anthropic
+    // models). With it, the stable start of the prompt (system prompt, assistant prompt and
+    // assistant files) is marked as cacheable. Defaults to false.
+    #[serde(default)]
+    pub supports_prompt_caching: bool,
     // Price per 1 million input tokens (unit-less)
     #[serde(default)]
     pub cost_input_tokens_per_1m: f64,
     // Price per 1 million output tokens (unit-less)
     #[serde(default)]
     pub cost_output_tokens_per_1m: f64,
+    // Price per 1 million input tokens that were read from the prompt cache of the provider
+    // (unit-less). If not set, they are priced like other input tokens.
+    #[serde(default)]
+    pub cost_cache_read_tokens_per_1m: Option<f64>,
+    // Price per 1 million input tokens that were written to the prompt cache of the provider
+    // (unit-less). If not set, they are priced like other input tokens.
+    #[serde(default)]
+    pub cost_cache_write_tokens_per_1m: Option<f64>,
+    // Currency of the prices above. Costs are converted to the currency of a budget with
+    // `budget.conversion_rates`. If not set, the prices are taken as they are for every budget.
+    #[serde(default)]
+    pub cost_currency: Option<BudgetCurrency>,
+    // Maximum number of files that may be attached to a single message. Unlimited if not set.
+    #[serde(default)]
+    pub max_input_files: Option<usize>,
+    // Kinds of attachments the model accepts. If not set, text is accepted, and images and audio
+    // are accepted according to `supports_image_understanding` and `supports_audio_input`.
+    #[serde(default)]
+    pub supported_input_modalities: Option<Vec<InputModality>>,
+}
+
+impl ModelCapabilities {
+    /// Whether the model accepts attachments of the given modality.
+    pub fn supports_input_modality(&self, modality: InputModality) -> bool {
+        match &self.supported_input_modalities {
+            Some(modalities) => modalities.contains(&modality),
+            None => match modality {
+                InputModality::Text => true,
+                InputModality::Image => self.supports_image_understanding,
+                InputModality::Audio => self.supports_audio_input,
+            },
+        }
+    }
+
+    /// The kinds of attachments the model accepts.
+    pub fn input_modalities(&self) -> Vec<InputModality> {
+        [
+            InputModality::Text,
+            InputModality::Image,
+            InputModality::Audio,
+        ]
+        .into_iter()
+        .filter(|modality| self.supports_input_modality(*modality))
+        .collect()
+    }
 }
 
 fn default_context_size_tokens() -> usize {
@@ -3757,6 +6493,10 @@ fn default_supports_encrypted_reasoning_content() -> bool {
     true
 }
 
+fn default_supports_tools() -> bool {
+    true
+}
+
 impl Default for ModelCapabilities {
     fn default() -> Self {
         Self {
@@ -3767,13 +6507,31 @@ impl Default for ModelCapabilities {
             supports_reasoning_summary: default_supports_reasoning_summary(),
             supports_encrypted_reasoning_content: default_supports_encrypted_reasoning_content(),
             supports_verbosity: false,
+            supports_tools: default_supports_tools(),
+            supports_prompt_caching: false,
             cost_input_tokens_per_1m: 0.0,
             cost_output_tokens_per_1m: 0.0,
+            cost_cache_read_tokens_per_1m: None,
+            cost_cache_write_tokens_per_1m: None,
+            cost_currency: None,
+            max_input_files: None,
+            supported_input_modalities: None,
         }
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy, Facet)]
+/// A kind of attachment a model can be provided with
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, ToSchema, Facet)]
+#[facet(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+#[repr(C)]
+pub enum InputModality {
+    Text,
+    Image,
+    Audio,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, ToSchema, Facet)]
 #[facet(rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 #[repr(C)]
@@ -3785,7 +6543,7 @@ pub enum ModelReasoningEffort {
     High,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy, Facet)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, ToSchema, Facet)]
 #[facet(rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 #[repr(C)]
@@ -3813,6 +6571,56 @@ pub struct ModelSettings {
     pub reasoning_effort: Option<ModelReasoningEffort>,
     // Optional verbosity setting for supported models.
     pub verbosity: Option<ModelVerbosity>,
+    // Optional pacing of the streamed text, for providers that return answers in a few large
+    // chunks.
+    pub output_pacing: Option<OutputPacingConfig>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Facet)]
+pub struct OutputPacingConfig {
+    // Whether large chunks of generated text are split into smaller deltas that are streamed at
+    // `target_chars_per_second`. Only the stream sent to the client is paced; the message is
+    // stored, and resumed streams are replayed, without delay.
+    // Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+    // The rate at which the text is streamed to the client, in characters per second. Text that
+    // is generated more slowly is streamed as it arrives.
+    // Defaults to `400`.
+    #[serde(default = "default_output_pacing_target_chars_per_second")]
+    pub target_chars_per_second: u32,
+    // The maximum number of characters of a paced text delta.
+    // Defaults to `24`.
+    #[serde(default = "default_output_pacing_max_chunk_chars")]
+    pub max_chunk_chars: usize,
+    // The maximum time in milliseconds that the pacing may delay the events following the text,
+    // like the completion of the message. The remaining text is sent at once when it is reached.
+    // Defaults to `2000`.
+    #[serde(default = "default_output_pacing_max_delay_ms")]
+    pub max_delay_ms: u64,
+}
+
+fn default_output_pacing_target_chars_per_second() -> u32 {
+    400
+}
+
+fn default_output_pacing_max_chunk_chars() -> usize {
+    24
+}
+
+fn default_output_pacing_max_delay_ms() -> u64 {
+    2000
+}
+
+impl Default for OutputPacingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_chars_per_second: default_output_pacing_target_chars_per_second(),
+            max_chunk_chars: default_output_pacing_max_chunk_chars(),
+            max_delay_ms: default_output_pacing_max_delay_ms(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone, Default, Facet)]
@@ -3850,6 +6658,69 @@ pub struct BudgetConfig {
     // Defaults to `30`.
     #[serde(default = "default_budget_period_days")]
     pub budget_period_days: u32,
+
+    // Additional budgets with their own scope, limit, currency and period, e.g. per department.
+    // Each budget limits the spending of every single user it applies to.
+    // Only has an effect if `enabled` is `true`.
+    #[serde(default)]
+    pub budgets: Vec<ScopedBudgetConfig>,
+
+    // Static conversion rates between currencies. Used when the pricing currency of a chat
+    // provider (`model_capabilities.cost_currency`) differs from the currency of a budget.
+    #[serde(default)]
+    pub conversion_rates: Vec<CurrencyConversionRate>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Facet)]
+pub struct ScopedBudgetConfig {
+    // Who the budget applies to: `global` for all users, or `group:<group-id>` for the members of
+    // a group (from the `groups` claim of the ID token).
+    pub scope: String,
+
+    // The maximum budget amount per period, in `currency`.
+    pub limit: f64,
+
+    // The currency of the limit.
+    // Defaults to `USD`.
+    #[serde(default)]
+    pub currency: BudgetCurrency,
+
+    // The calendar period after which the spending is reset.
+    pub period: BudgetPeriod,
+}
+
+impl ScopedBudgetConfig {
+    /// The group the budget applies to, or `None` for a global budget.
+    pub fn group(&self) -> Option<&str> {
+        self.scope.strip_prefix("group:")
+    }
+
+    /// Whether the budget applies to a user in the given groups.
+    pub fn applies_to(&self, groups: &[String]) -> bool {
+        match self.group() {
+            Some(group) => groups.iter().any(|user_group| user_group == group),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy, Facet)]
+#[facet(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+#[repr(C)]
+pub enum BudgetPeriod {
+    // Calendar months (UTC).
+    Monthly,
+    // Calendar quarters, starting in January, April, July and October (UTC).
+    Quarterly,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Facet)]
+pub struct CurrencyConversionRate {
+    pub from: BudgetCurrency,
+    pub to: BudgetCurrency,
+    // Amount in `to` per 1 unit of `from`. The inverse is used to convert in the other direction.
+    pub rate: f64,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, ToSchema, Default, Facet)]
@@ -3874,8 +6745,10 @@ impl BudgetConfig {
     /// Validates the budget configuration.
     pub fn validate(&self) -> Result<(), Report> {
         if self.enabled {
-            if self.max_budget.is_none() {
-                return Err(eyre!("Budget is enabled but max_budget is not set"));
+            if self.max_budget.is_none() && self.budgets.is_empty() {
+                return Err(eyre!(
+                    "Budget is enabled but neither max_budget nor any budgets are set"
+                ));
             }
 
             if let Some(max_budget) = self.max_budget
@@ -3900,9 +6773,81 @@ impl BudgetConfig {
                     self.budget_period_days
                 ));
             }
+
+            for budget in &self.budgets {
+                if budget.scope != "global" && budget.group().is_none_or(str::is_empty) {
+                    return Err(eyre!(
+                        "Budget scope must be `global` or `group:<group-id>`, got: {}",
+                        budget.scope
+                    ));
+                }
+                if budget.limit <= 0.0 {
+                    return Err(eyre!(
+                        "Limit of budget `{}` must be greater than 0, got: {}",
+                        budget.scope,
+                        budget.limit
+                    ));
+                }
+            }
+        }
+
+        for conversion_rate in &self.conversion_rates {
+            if conversion_rate.rate <= 0.0 || !conversion_rate.rate.is_finite() {
+                return Err(eyre!(
+                    "Conversion rate from {:?} to {:?} must be greater than 0, got: {}",
+                    conversion_rate.from,
+                    conversion_rate.to,
+                    conversion_rate.rate
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates that costs in each of the given pricing currencies can be converted to the
+    /// currencies of all budgets.
+    pub fn validate_pricing_currencies<'a>(
+        &self,
+        pricing_currencies: impl IntoIterator<Item = &'a BudgetCurrency>,
+    ) -> Result<(), Report> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let budget_currencies: Vec<&BudgetCurrency> = std::iter::once(&self.budget_currency)
+            .chain(self.budgets.iter().map(|budget| &budget.currency))
+            .collect();
+        for pricing_currency in pricing_currencies {
+            for budget_currency in &budget_currencies {
+                if self
+                    .conversion_rate(pricing_currency, budget_currency)
+                    .is_none()
+                {
+                    return Err(eyre!(
+                        "No conversion rate from {:?} to {:?} is configured in budget.conversion_rates",
+                        pricing_currency,
+                        budget_currency
+                    ));
+                }
+            }
         }
         Ok(())
     }
+
+    /// The rate to convert amounts from one currency to another, if it is known.
+    pub fn conversion_rate(&self, from: &BudgetCurrency, to: &BudgetCurrency) -> Option<f64> {
+        if from == to {
+            return Some(1.0);
+        }
+        self.conversion_rates.iter().find_map(|conversion_rate| {
+            if &conversion_rate.from == from && &conversion_rate.to == to {
+                Some(conversion_rate.rate)
+            } else if &conversion_rate.from == to && &conversion_rate.to == from {
+                Some(1.0 / conversion_rate.rate)
+            } else {
+                None
+            }
+        })
+    }
 }
 
 impl LangfuseConfig {
@@ -273,10 +273,13 @@ fn ensure_entry(entries: &mut BTreeMap<String, Value>, key: String) {
 
 fn map_key_placeholder(parent_segment: Option<&str>) -> String {
     match parent_segment {
+        Some("assistants") => "<assistant-id>".to_string(),
+        Some("chat_provider_groups") => "<group-id>".to_string(),
         Some("facets") => "<facet-id>".to_string(),
         Some("file_storage_providers") => "<provider-id>".to_string(),
         Some("localized_prompts") => "<locale>".to_string(),
         Some("mcp_servers") => "<server-id>".to_string(),
+        Some("organizations") => "<organization-id>".to_string(),
         Some("prompt_patterns") => "<pattern-id>".to_string(),
         Some("prompts") => "<prompt-id>".to_string(),
         Some("providers") => "<provider-id>".to_string(),
@@ -22,6 +22,17 @@ pub struct Model {
     pub updated_at: DateTimeWithTimeZone,
     pub facet_ids: Option<Vec<String>>,
     pub enforce_facet_settings: bool,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub welcome_message: Option<String>,
+    pub pinned_facet_ids: Option<Vec<String>>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub organization_id: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    #[sea_orm(column_type = "Text")]
+    pub visibility: String,
+    pub featured: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -25,6 +25,18 @@ pub struct Model {
     pub generation_started_at: Option<DateTimeWithTimeZone>,
     pub generation_heartbeat_at: Option<DateTimeWithTimeZone>,
     pub generation_ended_at: Option<DateTimeWithTimeZone>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub organization_id: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub generation_replica_id: Option<String>,
+    pub generation_message_id: Option<Uuid>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub assistant_snapshot: Option<Json>,
+    pub provisional: bool,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub custom_instruction: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub response_language: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -39,8 +51,14 @@ pub enum Relation {
     Assistants,
     #[sea_orm(has_many = "super::chat_file_uploads::Entity")]
     ChatFileUploads,
+    #[sea_orm(has_many = "super::chat_labels::Entity")]
+    ChatLabels,
+    #[sea_orm(has_many = "super::chat_read_states::Entity")]
+    ChatReadStates,
     #[sea_orm(has_many = "super::messages::Entity")]
     Messages,
+    #[sea_orm(has_many = "super::scheduled_messages::Entity")]
+    ScheduledMessages,
 }
 
 impl Related<super::assistants::Entity> for Entity {
@@ -55,12 +73,30 @@ impl Related<super::chat_file_uploads::Entity> for Entity {
     }
 }
 
+impl Related<super::chat_labels::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ChatLabels.def()
+    }
+}
+
+impl Related<super::chat_read_states::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ChatReadStates.def()
+    }
+}
+
 impl Related<super::messages::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Messages.def()
     }
 }
 
+impl Related<super::scheduled_messages::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ScheduledMessages.def()
+    }
+}
+
 impl Related<super::file_uploads::Entity> for Entity {
     fn to() -> RelationDef {
         super::chat_file_uploads::Relation::FileUploads.def()
@@ -70,4 +106,13 @@ impl Related<super::file_uploads::Entity> for Entity {
     }
 }
 
+impl Related<super::labels::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::chat_labels::Relation::Labels.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(super::chat_labels::Relation::Chats.def().rev())
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
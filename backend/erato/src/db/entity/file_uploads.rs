@@ -19,6 +19,10 @@ pub struct Model {
     pub owner_user_id: String,
     #[sea_orm(column_type = "Text", nullable)]
     pub audio_transcription: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub organization_id: Option<String>,
+    #[sea_orm(column_type = "Text")]
+    pub storage_status: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -27,6 +31,8 @@ pub enum Relation {
     AssistantFileUploads,
     #[sea_orm(has_many = "super::chat_file_uploads::Entity")]
     ChatFileUploads,
+    #[sea_orm(has_many = "super::message_redactions::Entity")]
+    MessageRedactions,
 }
 
 impl Related<super::assistant_file_uploads::Entity> for Entity {
@@ -41,6 +47,12 @@ impl Related<super::chat_file_uploads::Entity> for Entity {
     }
 }
 
+impl Related<super::message_redactions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MessageRedactions.def()
+    }
+}
+
 impl Related<super::assistants::Entity> for Entity {
     fn to() -> RelationDef {
         super::assistant_file_uploads::Relation::Assistants.def()
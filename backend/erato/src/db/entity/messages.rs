@@ -24,10 +24,16 @@ pub struct Model {
     pub generation_metadata: Option<Json>,
     #[sea_orm(column_type = "JsonBinary", nullable)]
     pub input_parameters: Option<Json>,
+    pub is_welcome_message: bool,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub content_draft: Option<Json>,
+    pub edit_of_message_id: Option<Uuid>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_one = "super::archived_generation_inputs::Entity")]
+    ArchivedGenerationInputs,
     #[sea_orm(
         belongs_to = "super::chats::Entity",
         from = "Column::ChatId",
@@ -36,8 +42,16 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Chats,
+    #[sea_orm(has_many = "super::chat_read_states::Entity")]
+    ChatReadStates,
     #[sea_orm(has_one = "super::message_feedbacks::Entity")]
     MessageFeedbacks,
+    #[sea_orm(has_many = "super::message_reactions::Entity")]
+    MessageReactions,
+    #[sea_orm(has_many = "super::message_redactions::Entity")]
+    MessageRedactions,
+    #[sea_orm(has_many = "super::scheduled_messages::Entity")]
+    ScheduledMessages,
     #[sea_orm(
         belongs_to = "Entity",
         from = "Column::PreviousMessageId",
@@ -54,6 +68,20 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     SelfRef1,
+    #[sea_orm(
+        belongs_to = "Entity",
+        from = "Column::EditOfMessageId",
+        to = "Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    SelfRef3,
+}
+
+impl Related<super::archived_generation_inputs::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ArchivedGenerationInputs.def()
+    }
 }
 
 impl Related<super::chats::Entity> for Entity {
@@ -62,10 +90,34 @@ impl Related<super::chats::Entity> for Entity {
     }
 }
 
+impl Related<super::chat_read_states::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ChatReadStates.def()
+    }
+}
+
 impl Related<super::message_feedbacks::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::MessageFeedbacks.def()
     }
 }
 
+impl Related<super::message_reactions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MessageReactions.def()
+    }
+}
+
+impl Related<super::message_redactions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MessageRedactions.def()
+    }
+}
+
+impl Related<super::scheduled_messages::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ScheduledMessages.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -2,20 +2,36 @@
 
 pub mod prelude;
 
+pub mod archived_generation_inputs;
 pub mod assistant_file_uploads;
 pub mod assistant_hub_assistant_versions;
 pub mod assistant_hub_assistants;
 pub mod assistant_hub_reviews;
 pub mod assistants;
 pub mod chat_file_uploads;
+pub mod chat_labels;
+pub mod chat_read_states;
 pub mod chats;
+pub mod feature_flags;
+pub mod file_deletions;
 pub mod file_uploads;
+pub mod generation_cache_entries;
+pub mod labels;
+pub mod langfuse_dead_letters;
 pub mod mcp_server_oauth_authorization_states;
 pub mod mcp_server_oauth_clients;
 pub mod mcp_server_oauth_credentials;
 pub mod message_feedbacks;
+pub mod message_reactions;
+pub mod message_redactions;
 pub mod messages;
+pub mod notifications;
+pub mod ownership_transfers;
+pub mod policy_invalidations;
+pub mod provider_usage_counters;
+pub mod scheduled_messages;
 pub mod share_grants;
 pub mod share_links;
+pub mod usage_reporting_installation;
 pub mod user_preferences;
 pub mod users;
@@ -1,19 +1,35 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.4
 
+pub use super::archived_generation_inputs::Entity as ArchivedGenerationInputs;
 pub use super::assistant_file_uploads::Entity as AssistantFileUploads;
 pub use super::assistant_hub_assistant_versions::Entity as AssistantHubAssistantVersions;
 pub use super::assistant_hub_assistants::Entity as AssistantHubAssistants;
 pub use super::assistant_hub_reviews::Entity as AssistantHubReviews;
 pub use super::assistants::Entity as Assistants;
 pub use super::chat_file_uploads::Entity as ChatFileUploads;
+pub use super::chat_labels::Entity as ChatLabels;
+pub use super::chat_read_states::Entity as ChatReadStates;
 pub use super::chats::Entity as Chats;
+pub use super::feature_flags::Entity as FeatureFlags;
+pub use super::file_deletions::Entity as FileDeletions;
 pub use super::file_uploads::Entity as FileUploads;
+pub use super::generation_cache_entries::Entity as GenerationCacheEntries;
+pub use super::labels::Entity as Labels;
+pub use super::langfuse_dead_letters::Entity as LangfuseDeadLetters;
 pub use super::mcp_server_oauth_authorization_states::Entity as McpServerOauthAuthorizationStates;
 pub use super::mcp_server_oauth_clients::Entity as McpServerOauthClients;
 pub use super::mcp_server_oauth_credentials::Entity as McpServerOauthCredentials;
 pub use super::message_feedbacks::Entity as MessageFeedbacks;
+pub use super::message_reactions::Entity as MessageReactions;
+pub use super::message_redactions::Entity as MessageRedactions;
 pub use super::messages::Entity as Messages;
+pub use super::notifications::Entity as Notifications;
+pub use super::ownership_transfers::Entity as OwnershipTransfers;
+pub use super::policy_invalidations::Entity as PolicyInvalidations;
+pub use super::provider_usage_counters::Entity as ProviderUsageCounters;
+pub use super::scheduled_messages::Entity as ScheduledMessages;
 pub use super::share_grants::Entity as ShareGrants;
 pub use super::share_links::Entity as ShareLinks;
+pub use super::usage_reporting_installation::Entity as UsageReportingInstallation;
 pub use super::user_preferences::Entity as UserPreferences;
 pub use super::users::Entity as Users;
@@ -15,6 +15,8 @@ pub struct Model {
     pub email: Option<String>,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub organization_id: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -25,10 +27,28 @@ pub enum Relation {
     AssistantHubReviews,
     #[sea_orm(has_many = "super::assistants::Entity")]
     Assistants,
+    #[sea_orm(has_many = "super::chat_read_states::Entity")]
+    ChatReadStates,
+    #[sea_orm(has_many = "super::file_deletions::Entity")]
+    FileDeletions,
+    #[sea_orm(has_many = "super::labels::Entity")]
+    Labels,
     #[sea_orm(has_many = "super::mcp_server_oauth_authorization_states::Entity")]
     McpServerOauthAuthorizationStates,
     #[sea_orm(has_many = "super::mcp_server_oauth_credentials::Entity")]
     McpServerOauthCredentials,
+    #[sea_orm(has_many = "super::message_reactions::Entity")]
+    MessageReactions,
+    #[sea_orm(has_many = "super::message_redactions::Entity")]
+    MessageRedactions,
+    #[sea_orm(has_many = "super::notifications::Entity")]
+    Notifications,
+    #[sea_orm(has_many = "super::ownership_transfers::Entity")]
+    OwnershipTransfers,
+    #[sea_orm(has_many = "super::provider_usage_counters::Entity")]
+    ProviderUsageCounters,
+    #[sea_orm(has_many = "super::scheduled_messages::Entity")]
+    ScheduledMessages,
     #[sea_orm(has_one = "super::user_preferences::Entity")]
     UserPreferences,
 }
@@ -51,6 +71,24 @@ impl Related<super::assistants::Entity> for Entity {
     }
 }
 
+impl Related<super::chat_read_states::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ChatReadStates.def()
+    }
+}
+
+impl Related<super::file_deletions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FileDeletions.def()
+    }
+}
+
+impl Related<super::labels::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Labels.def()
+    }
+}
+
 impl Related<super::mcp_server_oauth_authorization_states::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::McpServerOauthAuthorizationStates.def()
@@ -63,6 +101,42 @@ impl Related<super::mcp_server_oauth_credentials::Entity> for Entity {
     }
 }
 
+impl Related<super::message_reactions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MessageReactions.def()
+    }
+}
+
+impl Related<super::message_redactions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MessageRedactions.def()
+    }
+}
+
+impl Related<super::notifications::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Notifications.def()
+    }
+}
+
+impl Related<super::ownership_transfers::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OwnershipTransfers.def()
+    }
+}
+
+impl Related<super::provider_usage_counters::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ProviderUsageCounters.def()
+    }
+}
+
+impl Related<super::scheduled_messages::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ScheduledMessages.def()
+    }
+}
+
 impl Related<super::user_preferences::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::UserPreferences.def()
@@ -0,0 +1,37 @@
+use chrono::{DateTime, FixedOffset, Utc};
+
+pub use super::super::entity::messages::*;
+
+impl Model {
+    /// When the message was created, normalized to UTC.
+    pub fn created_at_utc(&self) -> DateTime<Utc> {
+        self.created_at.with_timezone(&Utc)
+    }
+
+    /// When the message was last updated, normalized to UTC.
+    pub fn updated_at_utc(&self) -> DateTime<Utc> {
+        self.updated_at.with_timezone(&Utc)
+    }
+}
+
+/// Convert a UTC timestamp to the representation used in the API, which is RFC3339 with an
+/// explicit `+00:00` offset.
+pub fn api_timestamp(timestamp: DateTime<Utc>) -> DateTime<FixedOffset> {
+    timestamp.fixed_offset()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_timestamp_is_serialized_with_utc_offset() {
+        let skewed = DateTime::parse_from_rfc3339("2025-03-01T14:30:00.123456+05:30").unwrap();
+
+        let serialized = serde_json::to_string(&api_timestamp(skewed.with_timezone(&Utc))).unwrap();
+
+        assert_eq!(serialized, "\"2025-03-01T09:00:00.123456+00:00\"");
+        let parsed: DateTime<FixedOffset> = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(parsed, skewed);
+    }
+}
@@ -1,8 +1,8 @@
 pub mod prelude;
 
 // Unchanged models
-pub use super::entity::messages;
 pub use super::entity::users;
 // Views / extended models
 pub mod chats;
 pub mod chats_latest_message;
+pub mod messages;
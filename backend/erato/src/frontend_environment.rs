@@ -1,6 +1,6 @@
 //! Inlined version of the frontend-environment crate (to simplify dependency version alignment)
 pub use self::axum::serve_files_with_script;
-use crate::config::{AppConfig, TranslationPoCompilationMode};
+use crate::config::{AppConfig, LOCAL_UPLOAD_FILE_SOURCE, TranslationPoCompilationMode};
 use crate::translation_po::TranslationPoCache;
 use ::axum::http::HeaderValue;
 use lol_html::html_content::ContentType;
@@ -25,6 +25,7 @@ const FRONTEND_ENV_KEY_FRONTEND_PUBLIC_BASE_PATH: &str = "FRONTEND_PUBLIC_BASE_P
 const FRONTEND_ENV_KEY_COMMON_PUBLIC_BASE_PATH: &str = "COMMON_PUBLIC_BASE_PATH";
 const FRONTEND_ENV_KEY_THEME_CUSTOMER_NAME: &str = "THEME_CUSTOMER_NAME";
 const FRONTEND_ENV_KEY_DISABLE_UPLOAD: &str = "DISABLE_UPLOAD";
+const FRONTEND_ENV_KEY_LOCAL_UPLOAD_ENABLED: &str = "LOCAL_UPLOAD_ENABLED";
 const FRONTEND_ENV_KEY_DISABLE_CHAT_INPUT_AUTOFOCUS: &str = "DISABLE_CHAT_INPUT_AUTOFOCUS";
 const FRONTEND_ENV_KEY_CHAT_INPUT_EMPTY_STATE_LAYOUT: &str = "CHAT_INPUT_EMPTY_STATE_LAYOUT";
 const FRONTEND_ENV_KEY_DISABLE_LOGOUT: &str = "DISABLE_LOGOUT";
@@ -47,11 +48,14 @@ const FRONTEND_ENV_KEY_MCP_SERVERS_TAB_ENABLED: &str = "MCP_SERVERS_TAB_ENABLED"
 const FRONTEND_ENV_KEY_SHAREPOINT_ENABLED: &str = "SHAREPOINT_ENABLED";
 const FRONTEND_ENV_KEY_SHAREPOINT_SHOW_DISCLAIMER: &str = "SHAREPOINT_SHOW_DISCLAIMER";
 const FRONTEND_ENV_KEY_CHAT_SHARING_ENABLED: &str = "CHAT_SHARING_ENABLED";
+const FRONTEND_ENV_KEY_EPHEMERAL_CHATS_ENABLED: &str = "EPHEMERAL_CHATS_ENABLED";
 const FRONTEND_ENV_KEY_MESSAGE_FEEDBACK_ENABLED: &str = "MESSAGE_FEEDBACK_ENABLED";
 const FRONTEND_ENV_KEY_MESSAGE_FEEDBACK_COMMENTS_ENABLED: &str =
     "MESSAGE_FEEDBACK_COMMENTS_ENABLED";
 const FRONTEND_ENV_KEY_MESSAGE_FEEDBACK_EDIT_TIME_LIMIT_SECONDS: &str =
     "MESSAGE_FEEDBACK_EDIT_TIME_LIMIT_SECONDS";
+const FRONTEND_ENV_KEY_MESSAGE_REACTIONS_ENABLED: &str = "MESSAGE_REACTIONS_ENABLED";
+const FRONTEND_ENV_KEY_MESSAGE_REACTION_EMOJIS: &str = "MESSAGE_REACTION_EMOJIS";
 const FRONTEND_ENV_KEY_SHOW_VERBOSE_ASSISTANT_ERRORS: &str = "SHOW_VERBOSE_ASSISTANT_ERRORS";
 const FRONTEND_ENV_KEY_SHOW_COPY_ERROR_REPORT: &str = "SHOW_COPY_ERROR_REPORT";
 const FRONTEND_ENV_KEY_ERROR_REPORT_TEMPLATE: &str = "ERROR_REPORT_TEMPLATE";
@@ -568,9 +572,14 @@ fn build_frontend_environment(
     }
 
     // Inject frontend configuration flags
+    let file_sources = config.available_file_sources();
     env.additional_environment.insert(
         FRONTEND_ENV_KEY_DISABLE_UPLOAD.to_string(),
-        Value::Bool(config.frontend.disable_upload),
+        Value::Bool(config.frontend.disable_upload || file_sources.is_empty()),
+    );
+    env.additional_environment.insert(
+        FRONTEND_ENV_KEY_LOCAL_UPLOAD_ENABLED.to_string(),
+        Value::Bool(file_sources.contains(&LOCAL_UPLOAD_FILE_SOURCE)),
     );
     env.additional_environment.insert(
         FRONTEND_ENV_KEY_DISABLE_CHAT_INPUT_AUTOFOCUS.to_string(),
@@ -632,13 +641,7 @@ fn build_frontend_environment(
     );
     env.additional_environment.insert(
         FRONTEND_ENV_KEY_SHAREPOINT_ENABLED.to_string(),
-        Value::Bool(
-            config.integrations.experimental_sharepoint.enabled
-                && config
-                    .integrations
-                    .experimental_sharepoint
-                    .file_upload_enabled,
-        ),
+        Value::Bool(file_sources.contains(&"sharepoint")),
     );
     env.additional_environment.insert(
         FRONTEND_ENV_KEY_SHAREPOINT_SHOW_DISCLAIMER.to_string(),
@@ -648,6 +651,10 @@ fn build_frontend_environment(
         FRONTEND_ENV_KEY_CHAT_SHARING_ENABLED.to_string(),
         Value::Bool(config.chat_sharing.enabled),
     );
+    env.additional_environment.insert(
+        FRONTEND_ENV_KEY_EPHEMERAL_CHATS_ENABLED.to_string(),
+        Value::Bool(config.chat.ephemeral_chats.enabled),
+    );
     env.additional_environment.insert(
         FRONTEND_ENV_KEY_MESSAGE_FEEDBACK_ENABLED.to_string(),
         Value::Bool(config.frontend.enable_message_feedback),
@@ -662,6 +669,14 @@ fn build_frontend_environment(
             Value::Number(limit.into()),
         );
     }
+    env.additional_environment.insert(
+        FRONTEND_ENV_KEY_MESSAGE_REACTIONS_ENABLED.to_string(),
+        Value::Bool(config.frontend.enable_message_reactions),
+    );
+    env.additional_environment.insert(
+        FRONTEND_ENV_KEY_MESSAGE_REACTION_EMOJIS.to_string(),
+        Value::from(config.frontend.message_reaction_emojis.clone()),
+    );
     env.additional_environment.insert(
         FRONTEND_ENV_KEY_SHOW_VERBOSE_ASSISTANT_ERRORS.to_string(),
         Value::Bool(config.frontend.error_report.show_verbose_assistant_errors),
@@ -784,6 +799,11 @@ impl DeploymentVersion {
         Self(version, random_hex_string())
     }
 
+    /// The deployment version, or the version of the backend if none is set.
+    pub fn version(&self) -> &str {
+        self.0.as_deref().unwrap_or(env!("CARGO_PKG_VERSION"))
+    }
+
     fn etag_value_for_path(
         &self,
         request_path: &str,
@@ -1431,6 +1451,46 @@ mod tests {
         }));
     }
 
+    #[test]
+    fn file_upload_flags_follow_available_file_sources() {
+        let environment_value = |config: &AppConfig, key: &str| {
+            build_frontend_environment(config, FrontendKind::Web)
+                .additional_environment
+                .get(key)
+                .cloned()
+        };
+        let mut config = AppConfig::default();
+        config.integrations.experimental_sharepoint.enabled = true;
+        config
+            .integrations
+            .experimental_sharepoint
+            .file_upload_enabled = true;
+        config.file_uploads.local_upload_enabled = false;
+
+        assert_eq!(
+            environment_value(&config, FRONTEND_ENV_KEY_LOCAL_UPLOAD_ENABLED),
+            Some(Value::Bool(false))
+        );
+        assert_eq!(
+            environment_value(&config, FRONTEND_ENV_KEY_SHAREPOINT_ENABLED),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(
+            environment_value(&config, FRONTEND_ENV_KEY_DISABLE_UPLOAD),
+            Some(Value::Bool(false))
+        );
+
+        config.file_uploads.link_enabled_sources.clear();
+        assert_eq!(
+            environment_value(&config, FRONTEND_ENV_KEY_SHAREPOINT_ENABLED),
+            Some(Value::Bool(false))
+        );
+        assert_eq!(
+            environment_value(&config, FRONTEND_ENV_KEY_DISABLE_UPLOAD),
+            Some(Value::Bool(true))
+        );
+    }
+
     #[test]
     fn specific_mount_path_matches_before_root() {
         let registry = FrontendRegistry {
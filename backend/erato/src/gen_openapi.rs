@@ -1,15 +1,31 @@
 //! Binary to generate the OpenAPI spec
+//!
+//! Usage: `gen-openapi [--check] [--out <path>]`
+//!
+//! Writes to `./generated/openapi.json` by default. `--out -` prints the spec to stdout
+//! instead, e.g. for client generation in CI.
 use std::fs;
 use std::process;
 
 use erato::ApiDoc;
 
+const DEFAULT_OUTPUT_PATH: &str = "./generated/openapi.json";
+
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     let check_mode = args.iter().any(|arg| arg == "--check");
+    let output_path = match args.iter().position(|arg| arg == "--out") {
+        Some(index) => match args.get(index + 1) {
+            Some(path) => path.as_str(),
+            None => {
+                eprintln!("Error: --out requires a path, or `-` for stdout.");
+                process::exit(2);
+            }
+        },
+        None => DEFAULT_OUTPUT_PATH,
+    };
 
-    let generated_doc = ApiDoc::build_openapi_full().to_pretty_json().unwrap();
-    let output_path = "./generated/openapi.json";
+    let generated_doc = ApiDoc::build_openapi_json();
 
     if check_mode {
         // Check if the file exists
@@ -22,6 +38,9 @@ fn main() {
                     println!(
                         "OpenAPI documentation is out of date. Run without --check to update."
                     );
+                    println!(
+                        "Run `cargo test --test integration_tests openapi` to see the changes."
+                    );
                     process::exit(1);
                 }
             }
@@ -31,6 +50,8 @@ fn main() {
                 process::exit(1);
             }
         }
+    } else if output_path == "-" {
+        println!("{}", generated_doc);
     } else {
         // Normal mode: write the generated doc to file
         fs::write(output_path, generated_doc).unwrap();
@@ -1,8 +1,12 @@
+use std::net::SocketAddr;
 use utoipa::OpenApi;
-use utoipa::openapi::{Info, OpenApiBuilder};
+use utoipa::openapi::extensions::ExtensionsBuilder;
+use utoipa::openapi::{Info, OpenApiBuilder, Server, ServerBuilder};
 
 use server::router::MainRouterApiDoc;
 
+use crate::config::AppConfig;
+use crate::frontend_environment::DeploymentVersion;
 use crate::server::router::MAIN_ROUTER_DOC;
 
 pub mod actors;
@@ -44,4 +48,55 @@ impl ApiDoc {
             .info(Info::builder().description(Some(MAIN_ROUTER_DOC)))
             .build()
     }
+
+    /// The OpenAPI spec served by a running server.
+    ///
+    /// Advertises the servers configured in `http.public_base_url`, falling back to the address
+    /// of the listener in the `development` environment, and includes the deployment version as
+    /// `x-erato-version` in the info block.
+    pub fn build_openapi_for_deployment(
+        config: &AppConfig,
+        local_addr: Option<SocketAddr>,
+        deployment_version: &DeploymentVersion,
+    ) -> utoipa::openapi::OpenApi {
+        let mut spec = Self::build_openapi_full();
+
+        let servers: Vec<Server> = if !config.http.public_base_url.is_empty() {
+            config
+                .http
+                .public_base_url
+                .iter()
+                .map(|public_base_url| {
+                    ServerBuilder::new()
+                        .url(&public_base_url.url)
+                        .description(public_base_url.description.clone())
+                        .build()
+                })
+                .collect()
+        } else if let Some(local_addr) = local_addr
+            && config.environment == "development"
+        {
+            vec![Server::new(format!("http://{}", local_addr))]
+        } else {
+            Vec::new()
+        };
+        spec.servers = (!servers.is_empty()).then_some(servers);
+
+        spec.info.extensions = Some(
+            ExtensionsBuilder::new()
+                .add("x-erato-version", deployment_version.version())
+                .build(),
+        );
+        spec
+    }
+
+    /// The full OpenAPI spec as pretty-printed JSON, as committed to `generated/openapi.json`.
+    ///
+    /// Only depends on the route and schema annotations, so it can be generated without a
+    /// database or an [`state::AppState`].
+    pub fn build_openapi_json() -> String {
+        Self::build_openapi_full()
+            .to_pretty_json()
+            .expect("OpenAPI spec serializes to JSON")
+    }
 }
@@ -1,18 +1,23 @@
 use axum::Extension;
 use axum::handler::HandlerWithoutStateExt;
 use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, OtelInResponseLayer};
-use eyre::{Report, WrapErr};
-use utoipa_scalar::{Scalar, Servable as ScalarServable};
+use eyre::{Report, WrapErr, eyre};
 
 use erato::config::AppConfig;
 use erato::frontend_environment::{
     DeploymentVersion, build_frontend_registry, serve_files_with_script,
 };
 use erato::models;
+use erato::services::admin_commands::{AdminCommand, AdminCommandOptions, run_admin_command};
+use erato::services::file_storage_self_test::run_file_storage_self_tests;
+use erato::services::seed::{DEFAULT_SEED, SeedOptions, SeedProfile, seed_database};
 use erato::services::sentry::{extend_with_sentry_layers, setup_sentry};
 use erato::startup_log;
 use erato::state::AppState;
 use erato::{ApiDoc, server};
+use sea_orm::prelude::Uuid;
+use std::net::SocketAddr;
+use tokio_util::sync::CancellationToken;
 use tower_http::cors::CorsLayer;
 
 #[cfg(all(feature = "profiling", target_os = "linux"))]
@@ -56,6 +61,97 @@ fn configured_tokio_worker_threads() -> usize {
     }
 }
 
+const SEED_USAGE: &str = "Usage: erato seed [--profile minimal|demo] [--seed <number>]";
+
+/// Parse the arguments of the `seed` subcommand.
+///
+/// Returns `None` if the server should be started instead.
+fn parse_seed_command() -> Result<Option<SeedOptions>, Report> {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some("seed") {
+        return Ok(None);
+    }
+
+    let mut options = SeedOptions {
+        profile: SeedProfile::default(),
+        seed: DEFAULT_SEED,
+    };
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or_else(|| eyre!(SEED_USAGE))?;
+        match arg.as_str() {
+            "--profile" => {
+                options.profile = SeedProfile::from_name(&value)
+                    .ok_or_else(|| eyre!("Unknown seed profile `{value}`. {SEED_USAGE}"))?;
+            }
+            "--seed" => {
+                options.seed = value
+                    .parse()
+                    .map_err(|_| eyre!("Invalid seed `{value}`. {SEED_USAGE}"))?;
+            }
+            _ => return Err(eyre!(SEED_USAGE)),
+        }
+    }
+    Ok(Some(options))
+}
+
+const ADMIN_USAGE: &str = "Usage: erato admin <command> [--json] [--dry-run] [--yes]
+
+Commands:
+  user-stats <user_id>
+  chat-inspect <chat_id>
+  chat-transfer <chat_id> <new_owner_user_id>
+  file-verify <file_id>
+  policy-rebuild
+  orphan-report";
+
+/// Parse the arguments of the `admin` subcommand.
+///
+/// Returns `None` if the server should be started instead.
+fn parse_admin_command() -> Result<Option<(AdminCommand, AdminCommandOptions)>, Report> {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some("admin") {
+        return Ok(None);
+    }
+
+    let mut options = AdminCommandOptions::default();
+    let mut positional = vec![];
+    for arg in args {
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "--dry-run" => options.dry_run = true,
+            "--yes" => options.yes = true,
+            _ if arg.starts_with("--") => {
+                return Err(eyre!("Unknown option `{arg}`.\n{ADMIN_USAGE}"));
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let parse_id = |value: &String| {
+        Uuid::parse_str(value).map_err(|_| eyre!("Invalid ID `{value}`.\n{ADMIN_USAGE}"))
+    };
+    let command = match positional.as_slice() {
+        [command, user_id] if command == "user-stats" => AdminCommand::UserStats {
+            user_id: parse_id(user_id)?,
+        },
+        [command, chat_id] if command == "chat-inspect" => AdminCommand::ChatInspect {
+            chat_id: parse_id(chat_id)?,
+        },
+        [command, chat_id, new_owner_user_id] if command == "chat-transfer" => {
+            AdminCommand::ChatTransfer {
+                chat_id: parse_id(chat_id)?,
+                new_owner_user_id: parse_id(new_owner_user_id)?,
+            }
+        }
+        [command, file_id] if command == "file-verify" => AdminCommand::FileVerify {
+            file_id: parse_id(file_id)?,
+        },
+        [command] if command == "policy-rebuild" => AdminCommand::PolicyRebuild,
+        [command] if command == "orphan-report" => AdminCommand::OrphanReport,
+        _ => return Err(eyre!(ADMIN_USAGE)),
+    };
+    Ok(Some((command, options)))
+}
+
 fn main() -> Result<(), Report> {
     let worker_threads = configured_tokio_worker_threads();
     let runtime = tokio::runtime::Builder::new_multi_thread()
@@ -76,6 +172,8 @@ async fn async_main(worker_threads: usize) -> Result<(), Report> {
         }
     }
 
+    let seed_command = parse_seed_command()?;
+    let admin_command = parse_admin_command()?;
     let config = AppConfig::new_for_app(None)?;
 
     // initialize tracing
@@ -96,31 +194,113 @@ async fn async_main(worker_threads: usize) -> Result<(), Report> {
     // Verify that the database has been migrated to the latest version
     models::ensure_latest_migration(&state.db).await?;
 
-    let (router, _api) = server::router::router(state.clone()).split_for_parts();
+    if let Some((command, options)) = admin_command {
+        let output = run_admin_command(&state, &command, &options).await?;
+        println!("{output}");
+        return Ok(());
+    }
+
+    if let Some(seed_options) = seed_command {
+        if config.is_production_environment() {
+            return Err(eyre!(
+                "Refusing to seed demo data in the `production` environment"
+            ));
+        }
+        let summary = seed_database(&state, &seed_options).await?;
+        tracing::info!(?summary, "Seeded database");
+        return Ok(());
+    }
+
+    // Verify that the file storage providers can be written to and read from
+    run_file_storage_self_tests(&state).await?;
+
+    // Report chat provider IDs of stored messages that are neither configured nor aliased
+    match models::message::find_unknown_generation_chat_provider_ids(&state.db, &config).await {
+        Ok(unknown_provider_ids) => {
+            for provider_id in unknown_provider_ids {
+                tracing::warn!(
+                    provider_id,
+                    "Stored messages reference chat provider '{}', which is neither configured nor aliased. Add it to `chat_provider_aliases` to keep regenerating these messages working.",
+                    provider_id
+                );
+            }
+        }
+        Err(error) => {
+            tracing::warn!(
+                ?error,
+                "Failed to check chat provider IDs of stored messages"
+            );
+        }
+    }
+
+    let (router, internal_router) = server::router::listener_routers(state.clone());
+    let (router, _api) = router.split_for_parts();
 
     let listener =
         tokio::net::TcpListener::bind(format!("{}:{}", config.http_host, config.http_port)).await?;
     let local_addr = listener.local_addr()?;
 
     // Create OpenAPI spec with server information
-    let mut spec = ApiDoc::build_openapi_full();
-    spec.servers = Some(vec![utoipa::openapi::Server::new(format!(
-        "http://{}",
-        local_addr
-    ))]);
+    let deployment_version = DeploymentVersion::from_env();
+    let spec = ApiDoc::build_openapi_for_deployment(&config, Some(local_addr), &deployment_version);
 
     let app = extend_with_sentry_layers(router)
-        .merge(Scalar::with_url("/scalar", spec.clone()))
-        .route(
-            "/openapi.json",
-            axum::routing::get(move || async move { axum::Json(spec.clone()) }),
-        )
+        .merge(server::router::api_docs_routes(spec))
         .fallback_service(serve_files_with_script.into_service())
         .layer(Extension(build_frontend_registry(&config)))
-        .layer(Extension(DeploymentVersion::from_env()))
+        .layer(Extension(deployment_version.clone()))
         .layer(CorsLayer::very_permissive());
 
-    let app = if config.integrations.otel.enabled {
+    let app = with_otel_layers(app, &config).with_state(state.clone());
+
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            shutdown.cancel();
+        }
+    });
+
+    let internal_server = match (internal_router, config.internal_http_addr()) {
+        (Some(internal_router), Some(internal_addr)) => {
+            let internal_listener = tokio::net::TcpListener::bind(&internal_addr)
+                .await
+                .wrap_err_with(|| format!("Failed to bind internal listener to {internal_addr}"))?;
+            let internal_app = with_otel_layers(
+                extend_with_sentry_layers(internal_router.split_for_parts().0)
+                    .layer(Extension(deployment_version.clone())),
+                &config,
+            )
+            .with_state(state.clone());
+            tracing::info!(listen_addr = %internal_listener.local_addr()?, "Internal server listening");
+            Some(serve_until_shutdown(
+                internal_listener,
+                internal_app,
+                shutdown.clone(),
+            ))
+        }
+        _ => None,
+    };
+
+    tracing::info!(api_docs_url = %format!("http://{}/scalar", local_addr), "API docs available");
+    tracing::info!(frontend_url = %format!("http://{}", local_addr), "Frontend available");
+    tracing::info!(listen_addr = %local_addr, worker_threads, "Server listening");
+    let public_server = serve_until_shutdown(listener, app, shutdown);
+    let result = match internal_server {
+        Some(internal_server) => {
+            let (public_result, internal_result) = tokio::join!(public_server, internal_server);
+            public_result.and(internal_result)
+        }
+        None => public_server.await,
+    };
+    state.shutdown().await;
+
+    result
+}
+
+fn with_otel_layers(app: axum::Router<AppState>, config: &AppConfig) -> axum::Router<AppState> {
+    if config.integrations.otel.enabled {
         app
             // include trace context as header into the response
             .layer(OtelInResponseLayer)
@@ -129,14 +309,49 @@ async fn async_main(worker_threads: usize) -> Result<(), Report> {
     } else {
         app
     }
-    .with_state(state);
+}
 
-    tracing::info!(api_docs_url = %format!("http://{}/scalar", local_addr), "API docs available");
-    tracing::info!(frontend_url = %format!("http://{}", local_addr), "Frontend available");
-    tracing::info!(listen_addr = %local_addr, worker_threads, "Server listening");
-    axum::serve(listener, app.into_make_service()).await?;
+/// Serves the app until shutdown is requested.
+///
+/// If the server stops on its own, shutdown is requested as well, so the other listener is
+/// shut down along with it.
+async fn serve_until_shutdown(
+    listener: tokio::net::TcpListener,
+    app: axum::Router,
+    shutdown: CancellationToken,
+) -> Result<(), Report> {
+    // The address of the peer is needed to check for trusted proxies, see `auth.trusted_headers`
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    let result = axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+        .await;
+    shutdown.cancel();
+    Ok(result?)
+}
 
-    Ok(())
+/// Resolves once the process is asked to stop, via Ctrl+C or (on Unix) SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Shutdown signal received, waiting for in-flight requests");
 }
 
 #[cfg(test)]
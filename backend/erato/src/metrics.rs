@@ -10,17 +10,52 @@ use metrics_exporter_prometheus::PrometheusBuilder;
 use moka::future::Cache;
 use tokio_metrics::RuntimeMetricsReporterBuilder;
 
-use crate::config::AppConfig;
-use crate::models::message::GenerationErrorType;
+use crate::config::{AppConfig, McpSchemaViolationMode, OutputComplianceAction};
+use crate::models::message::{
+    GenerationErrorType, PromptInjectionWarning, RenderableBlock, RenderableBlockType,
+    UntrustedContentSource,
+};
+use crate::models::message_thread_integrity::ThreadIntegrityIssueKind;
 use crate::query_metrics::{POSTGRES_QUERY_DURATION_METRIC, init_known_postgres_query_metrics};
+use crate::services::file_processor::FileProcessingErrorKind;
 use crate::state::AppState;
 
 const MCP_ACTIVE_SESSIONS_METRIC: &str = "erato_mcp_active_sessions";
+const MCP_OLDEST_SESSION_AGE_METRIC: &str = "erato_mcp_oldest_session_age_seconds";
 const CHAT_PROVIDER_TIME_TO_FIRST_TOKEN_METRIC: &str =
     "erato_chat_provider_time_to_first_token_seconds";
 const CHAT_PROVIDER_TIME_TO_LAST_TOKEN_METRIC: &str =
     "erato_chat_provider_time_to_last_token_seconds";
+const MESSAGE_TIME_TO_FIRST_TOKEN_METRIC: &str = "erato_message_time_to_first_token_seconds";
+const MESSAGE_GENERATION_DURATION_METRIC: &str = "erato_message_generation_duration_seconds";
 const CHAT_PROVIDER_GENERATION_ERRORS_METRIC: &str = "erato_chat_provider_generation_errors_total";
+const RENDERABLE_BLOCKS_METRIC: &str = "erato_renderable_blocks_total";
+const PROMPT_INJECTION_WARNINGS_METRIC: &str = "erato_prompt_injection_warnings_total";
+const INLINE_FILE_CONTENTS_WRITES_METRIC: &str = "erato_inline_file_contents_writes_total";
+const GENERATION_CACHE_LOOKUPS_METRIC: &str = "erato_generation_cache_lookups_total";
+const THREAD_INTEGRITY_SCANNED_CHATS_METRIC: &str = "erato_thread_integrity_scanned_chats";
+const THREAD_INTEGRITY_CORRUPTED_CHATS_METRIC: &str = "erato_thread_integrity_corrupted_chats";
+const MCP_TOOL_SCHEMA_VIOLATIONS_METRIC: &str = "erato_mcp_tool_schema_violations_total";
+const MESSAGE_CONTENT_SPILLED_PARTS_METRIC: &str = "erato_message_content_spilled_parts_total";
+const MESSAGE_CONTENT_SPILLED_BYTES_METRIC: &str = "erato_message_content_spilled_bytes_total";
+const DETACHED_GENERATIONS_COMPLETED_METRIC: &str = "erato_detached_generations_completed_total";
+const POLICY_DATASET_SIZE_METRIC: &str = "erato_policy_dataset_size_bytes";
+const POLICY_DATASET_ENGINES_METRIC: &str = "erato_policy_dataset_engines";
+const POLICY_COLD_LOOKUPS_METRIC: &str = "erato_policy_cold_lookups_total";
+const FILE_PROCESSING_ERRORS_METRIC: &str = "erato_file_processing_errors_total";
+const GENERATION_QUEUE_DEPTH_METRIC: &str = "erato_generation_queue_depth";
+const GENERATION_QUEUE_WAIT_METRIC: &str = "erato_generation_queue_wait_seconds";
+const OUTPUT_COMPLIANCE_MATCHES_METRIC: &str = "erato_output_compliance_matches_total";
+const FILE_STORAGE_HEALTHY_METRIC: &str = "erato_file_storage_healthy";
+const POLICY_INVALIDATION_PROPAGATION_METRIC: &str =
+    "erato_policy_invalidation_propagation_seconds";
+const LANGFUSE_DEAD_LETTER_QUEUE_DEPTH_METRIC: &str = "erato_langfuse_dead_letter_queue_depth";
+const LANGFUSE_DEAD_LETTER_EVICTIONS_METRIC: &str = "erato_langfuse_dead_letter_evictions_total";
+const LANGFUSE_CONSECUTIVE_FAILURES_METRIC: &str = "erato_langfuse_consecutive_failures";
+const GENERATION_INPUTS_ARCHIVED_METRIC: &str = "erato_generation_inputs_archived_total";
+const GENERATION_INPUT_ARCHIVAL_BYTES_SAVED_METRIC: &str =
+    "erato_generation_input_archival_bytes_saved_total";
+const GENERATION_INPUT_REHYDRATIONS_METRIC: &str = "erato_generation_input_rehydrations_total";
 
 pub fn init_prometheus_metrics(config: &AppConfig) -> Result<()> {
     if !config.integrations.prometheus.enabled {
@@ -186,6 +221,7 @@ fn report_mcp_active_session_metrics(config: &AppConfig) {
 
     for server_id in server_ids {
         report_mcp_active_sessions_for_server(server_id, 0);
+        report_mcp_oldest_session_age(server_id, None);
     }
 }
 
@@ -193,6 +229,12 @@ pub fn report_mcp_active_sessions_for_server(server_id: &str, count: usize) {
     gauge!(MCP_ACTIVE_SESSIONS_METRIC, "server_id" => server_id.to_string()).set(count as f64);
 }
 
+/// Report the age of the oldest session of an MCP server, or `0` if it has no sessions.
+pub fn report_mcp_oldest_session_age(server_id: &str, age: Option<Duration>) {
+    gauge!(MCP_OLDEST_SESSION_AGE_METRIC, "server_id" => server_id.to_string())
+        .set(age.map_or(0.0, |age| age.as_secs_f64()));
+}
+
 pub fn report_chat_provider_time_to_first_token(chat_provider_id: &str, duration: Duration) {
     histogram!(
         CHAT_PROVIDER_TIME_TO_FIRST_TOKEN_METRIC,
@@ -209,6 +251,22 @@ pub fn report_chat_provider_time_to_last_token(chat_provider_id: &str, duration:
     .record(duration_seconds_with_millisecond_precision(duration));
 }
 
+pub fn report_message_time_to_first_token(chat_provider_id: &str, duration: Duration) {
+    histogram!(
+        MESSAGE_TIME_TO_FIRST_TOKEN_METRIC,
+        "chat_provider_id" => chat_provider_id.to_string()
+    )
+    .record(duration_seconds_with_millisecond_precision(duration));
+}
+
+pub fn report_message_generation_duration(chat_provider_id: &str, duration: Duration) {
+    histogram!(
+        MESSAGE_GENERATION_DURATION_METRIC,
+        "chat_provider_id" => chat_provider_id.to_string()
+    )
+    .record(duration_seconds_with_millisecond_precision(duration));
+}
+
 pub fn report_chat_provider_generation_error(chat_provider_id: &str, error: &GenerationErrorType) {
     counter!(
         CHAT_PROVIDER_GENERATION_ERRORS_METRIC,
@@ -221,15 +279,208 @@ pub fn report_chat_provider_generation_error(chat_provider_id: &str, error: &Gen
 fn generation_error_type_label(error: &GenerationErrorType) -> &'static str {
     match error {
         GenerationErrorType::ContentFilter { .. } => "content_filter",
-        GenerationErrorType::RateLimit { .. } => "rate_limit",
+        GenerationErrorType::RateLimited { .. } => "rate_limit",
         GenerationErrorType::ModelUnavailable { .. } => "model_unavailable",
         GenerationErrorType::InvalidRequest { .. } => "invalid_request",
         GenerationErrorType::ProviderError { .. } => "provider_error",
         GenerationErrorType::HallucinationLoop { .. } => "hallucination_loop",
+        GenerationErrorType::ModerationBlocked { .. } => "moderation_blocked",
+        GenerationErrorType::CompliancePolicy { .. } => "compliance_policy",
+        GenerationErrorType::TransformerFailed { .. } => "transformer_failed",
         GenerationErrorType::InternalError { .. } => "internal_error",
     }
 }
 
+pub fn report_renderable_block(block: &RenderableBlock) {
+    counter!(
+        RENDERABLE_BLOCKS_METRIC,
+        "block_type" => renderable_block_type_label(block.block_type).to_string(),
+        "valid" => block.valid.to_string()
+    )
+    .increment(1);
+}
+
+fn renderable_block_type_label(block_type: RenderableBlockType) -> &'static str {
+    match block_type {
+        RenderableBlockType::Mermaid => "mermaid",
+        RenderableBlockType::Math => "math",
+    }
+}
+
+pub fn report_prompt_injection_warning(warning: &PromptInjectionWarning) {
+    counter!(
+        PROMPT_INJECTION_WARNINGS_METRIC,
+        "source" => untrusted_content_source_label(warning.source).to_string(),
+        "pattern_id" => warning.pattern_id.clone()
+    )
+    .increment(1);
+}
+
+fn untrusted_content_source_label(source: UntrustedContentSource) -> &'static str {
+    match source {
+        UntrustedContentSource::File => "file",
+        UntrustedContentSource::ToolOutput => "tool_output",
+    }
+}
+
+pub fn report_inline_file_contents_write(file_count: u64) {
+    counter!(INLINE_FILE_CONTENTS_WRITES_METRIC).increment(file_count);
+}
+
+pub fn report_generation_cache_lookup(chat_provider_id: &str, hit: bool) {
+    counter!(
+        GENERATION_CACHE_LOOKUPS_METRIC,
+        "chat_provider_id" => chat_provider_id.to_string(),
+        "result" => if hit { "hit" } else { "miss" }
+    )
+    .increment(1);
+}
+
+pub fn report_mcp_tool_schema_violation(
+    mcp_server_id: &str,
+    tool_name: &str,
+    mode: McpSchemaViolationMode,
+) {
+    counter!(
+        MCP_TOOL_SCHEMA_VIOLATIONS_METRIC,
+        "mcp_server_id" => mcp_server_id.to_string(),
+        "tool_name" => tool_name.to_string(),
+        "mode" => mcp_schema_violation_mode_label(mode)
+    )
+    .increment(1);
+}
+
+/// Report content parts of an oversized message that were moved to the file storage.
+///
+/// `origin` is `write` for messages that are being stored, and `migration` for messages that
+/// were stored before.
+pub fn report_message_content_spill(origin: &'static str, parts: usize, bytes: u64) {
+    counter!(MESSAGE_CONTENT_SPILLED_PARTS_METRIC, "origin" => origin).increment(parts as u64);
+    counter!(MESSAGE_CONTENT_SPILLED_BYTES_METRIC, "origin" => origin).increment(bytes);
+}
+
+/// Report generation inputs that were compressed into the archive, and the bytes that saved.
+pub fn report_generation_inputs_archived(storage: &'static str, rows: u64, bytes_saved: i64) {
+    counter!(GENERATION_INPUTS_ARCHIVED_METRIC, "storage" => storage).increment(rows);
+    counter!(GENERATION_INPUT_ARCHIVAL_BYTES_SAVED_METRIC, "storage" => storage)
+        .increment(bytes_saved.max(0) as u64);
+}
+
+/// Report an archived generation input that was restored for a generation.
+pub fn report_generation_input_rehydration(cache_hit: bool) {
+    counter!(
+        GENERATION_INPUT_REHYDRATIONS_METRIC,
+        "result" => if cache_hit { "hit" } else { "miss" }
+    )
+    .increment(1);
+}
+
+/// Report a generation that completed in the background after its client disconnected.
+pub fn report_detached_generation_completed() {
+    counter!(DETACHED_GENERATIONS_COMPLETED_METRIC).increment(1);
+}
+
+/// Report the size of the in-memory policy data after a rebuild.
+///
+/// `size_bytes` is the size of the JSON the policy engines of all organizations were loaded with.
+pub fn report_policy_dataset_size(size_bytes: usize, engines: usize) {
+    gauge!(POLICY_DATASET_SIZE_METRIC).set(size_bytes as f64);
+    gauge!(POLICY_DATASET_ENGINES_METRIC).set(engines as f64);
+}
+
+/// Report a lookup of a resource that is not part of the in-memory policy data.
+///
+/// `source` is `request_cache`, `promoted` or `database`.
+pub fn report_policy_cold_lookup(source: &'static str) {
+    counter!(POLICY_COLD_LOOKUPS_METRIC, "source" => source).increment(1);
+}
+
+/// Report a file whose contents could not be extracted.
+pub fn report_file_processing_error(kind: FileProcessingErrorKind) {
+    counter!(FILE_PROCESSING_ERRORS_METRIC, "error_type" => kind.as_str()).increment(1);
+}
+
+/// Report the number of generations that wait for capacity to start.
+pub fn report_generation_queue_depth(depth: usize) {
+    gauge!(GENERATION_QUEUE_DEPTH_METRIC).set(depth as f64);
+}
+
+/// Report how long a queued generation waited until it was started.
+pub fn report_generation_queue_wait(duration: Duration) {
+    histogram!(GENERATION_QUEUE_WAIT_METRIC)
+        .record(duration_seconds_with_millisecond_precision(duration));
+}
+
+/// Report a match of an output compliance rule in a generated answer.
+pub fn report_output_compliance_match(rule_id: &str, action: OutputComplianceAction) {
+    counter!(
+        OUTPUT_COMPLIANCE_MATCHES_METRIC,
+        "rule_id" => rule_id.to_string(),
+        "action" => output_compliance_action_label(action)
+    )
+    .increment(1);
+}
+
+/// Report the result of the last self-test of a file storage provider.
+pub fn report_file_storage_self_test(provider_id: &str, healthy: bool) {
+    gauge!(FILE_STORAGE_HEALTHY_METRIC, "provider_id" => provider_id.to_string())
+        .set(if healthy { 1.0 } else { 0.0 });
+}
+
+/// Report the time from the commit of a policy invalidation until it was observed by this
+/// instance.
+pub fn report_policy_invalidation_propagation(lag: Duration, source: &'static str) {
+    histogram!(POLICY_INVALIDATION_PROPAGATION_METRIC, "source" => source)
+        .record(duration_seconds_with_millisecond_precision(lag));
+}
+
+/// Report the number of Langfuse ingestion batches that wait for a retry.
+pub fn report_langfuse_dead_letter_queue_depth(depth: usize) {
+    gauge!(LANGFUSE_DEAD_LETTER_QUEUE_DEPTH_METRIC).set(depth as f64);
+}
+
+/// Report Langfuse ingestion batches that were dropped because the dead-letter queue was full.
+pub fn report_langfuse_dead_letter_evictions(count: u64) {
+    counter!(LANGFUSE_DEAD_LETTER_EVICTIONS_METRIC).increment(count);
+}
+
+/// Report the number of requests to Langfuse that failed since the last successful one.
+pub fn report_langfuse_consecutive_failures(count: u64) {
+    gauge!(LANGFUSE_CONSECUTIVE_FAILURES_METRIC).set(count as f64);
+}
+
+fn output_compliance_action_label(action: OutputComplianceAction) -> &'static str {
+    match action {
+        OutputComplianceAction::Mask => "mask",
+        OutputComplianceAction::Abort => "abort",
+    }
+}
+
+fn mcp_schema_violation_mode_label(mode: McpSchemaViolationMode) -> &'static str {
+    match mode {
+        McpSchemaViolationMode::Warn => "warn",
+        McpSchemaViolationMode::Error => "error",
+        McpSchemaViolationMode::Coerce => "coerce",
+    }
+}
+
+/// Report the result of a scan of the threads of a sample of chats.
+///
+/// `corrupted_chats` holds the number of scanned chats with at least one issue of each kind.
+pub fn report_thread_integrity_scan(
+    scanned_chats: usize,
+    corrupted_chats: &[(ThreadIntegrityIssueKind, usize)],
+) {
+    gauge!(THREAD_INTEGRITY_SCANNED_CHATS_METRIC).set(scanned_chats as f64);
+    for (kind, count) in corrupted_chats {
+        gauge!(
+            THREAD_INTEGRITY_CORRUPTED_CHATS_METRIC,
+            "issue" => kind.as_str()
+        )
+        .set(*count as f64);
+    }
+}
+
 pub(crate) fn duration_seconds_with_millisecond_precision(duration: Duration) -> f64 {
     duration.as_millis() as f64 / 1_000.0
 }
@@ -250,6 +501,16 @@ fn describe_application_metrics() {
         Unit::Seconds,
         "Time from dispatching a chat-provider generation request until the last streamed token, recorded with millisecond precision."
     );
+    describe_histogram!(
+        MESSAGE_TIME_TO_FIRST_TOKEN_METRIC,
+        Unit::Seconds,
+        "Time from the start of the final model turn of an assistant message until its first streamed text chunk, excluding earlier tool-call turns, recorded with millisecond precision."
+    );
+    describe_histogram!(
+        MESSAGE_GENERATION_DURATION_METRIC,
+        Unit::Seconds,
+        "Time from the start of the generation of an assistant message until its completion, including tool-call turns, recorded with millisecond precision."
+    );
     describe_histogram!(
         POSTGRES_QUERY_DURATION_METRIC,
         Unit::Seconds,
@@ -260,11 +521,141 @@ fn describe_application_metrics() {
         Unit::Count,
         "Total number of chat-provider generation failures segmented by provider and normalized error type."
     );
+    describe_counter!(
+        RENDERABLE_BLOCKS_METRIC,
+        Unit::Count,
+        "Total number of mermaid diagrams and math blocks in generated messages segmented by block type and validation result."
+    );
+    describe_counter!(
+        PROMPT_INJECTION_WARNINGS_METRIC,
+        Unit::Count,
+        "Total number of matches of the configured prompt injection patterns in file contents and tool outputs segmented by content source and pattern ID."
+    );
+    describe_counter!(
+        INLINE_FILE_CONTENTS_WRITES_METRIC,
+        Unit::Count,
+        "Total number of files whose contents were stored inline in a generation input instead of as a file pointer. Expected to stay at 0."
+    );
+    describe_counter!(
+        GENERATION_CACHE_LOOKUPS_METRIC,
+        Unit::Count,
+        "Total number of lookups in the generation cache segmented by chat provider and hit or miss. The hit rate is the share of hits."
+    );
+    describe_counter!(
+        MCP_TOOL_SCHEMA_VIOLATIONS_METRIC,
+        Unit::Count,
+        "Total number of MCP tool results that didn't match the output schema of their tool segmented by MCP server, tool and configured handling mode."
+    );
+    describe_counter!(
+        MESSAGE_CONTENT_SPILLED_PARTS_METRIC,
+        Unit::Count,
+        "Total number of content parts of oversized messages that were moved to the file storage segmented by origin (write or migration)."
+    );
+    describe_counter!(
+        MESSAGE_CONTENT_SPILLED_BYTES_METRIC,
+        Unit::Bytes,
+        "Total size of the content parts of oversized messages that were moved to the file storage segmented by origin (write or migration)."
+    );
+    describe_counter!(
+        GENERATION_INPUTS_ARCHIVED_METRIC,
+        Unit::Count,
+        "Total number of generation inputs of old messages in archived chats that were compressed into the archive segmented by storage (database or file_storage)."
+    );
+    describe_counter!(
+        GENERATION_INPUT_ARCHIVAL_BYTES_SAVED_METRIC,
+        Unit::Bytes,
+        "Total number of bytes saved by compressing generation inputs into the archive segmented by storage (database or file_storage)."
+    );
+    describe_counter!(
+        GENERATION_INPUT_REHYDRATIONS_METRIC,
+        Unit::Count,
+        "Total number of archived generation inputs that were restored for a generation segmented by hit or miss of the in-memory cache."
+    );
+    describe_counter!(
+        DETACHED_GENERATIONS_COMPLETED_METRIC,
+        Unit::Count,
+        "Total number of generations that completed in the background after their client disconnected."
+    );
+    describe_gauge!(
+        POLICY_DATASET_SIZE_METRIC,
+        Unit::Bytes,
+        "Size of the JSON data the policy engines were loaded with in the last rebuild of the policy data."
+    );
+    describe_gauge!(
+        POLICY_DATASET_ENGINES_METRIC,
+        Unit::Count,
+        "Number of policy engines with the data of an organization, including the one for organizations without resources."
+    );
+    describe_counter!(
+        POLICY_COLD_LOOKUPS_METRIC,
+        Unit::Count,
+        "Total number of lookups of resources left out of the in-memory policy data segmented by the source they were found in."
+    );
+    describe_counter!(
+        FILE_PROCESSING_ERRORS_METRIC,
+        Unit::Count,
+        "Total number of files whose contents could not be extracted segmented by error type."
+    );
+    describe_gauge!(
+        GENERATION_QUEUE_DEPTH_METRIC,
+        Unit::Count,
+        "Current number of generations that wait for capacity to start."
+    );
+    describe_histogram!(
+        GENERATION_QUEUE_WAIT_METRIC,
+        Unit::Seconds,
+        "Time queued generations waited until they were started, recorded with millisecond precision."
+    );
+    describe_counter!(
+        OUTPUT_COMPLIANCE_MATCHES_METRIC,
+        Unit::Count,
+        "Total number of matches of the output compliance rules in generated answers segmented by rule ID and action."
+    );
+    describe_gauge!(
+        FILE_STORAGE_HEALTHY_METRIC,
+        Unit::Count,
+        "Whether the last self-test of a file storage provider succeeded (1) or failed (0). Not reported for providers that can't be verified."
+    );
+    describe_histogram!(
+        POLICY_INVALIDATION_PROPAGATION_METRIC,
+        Unit::Seconds,
+        "Time from a policy invalidation until it was observed by the instance segmented by whether it was notified or polled, recorded with millisecond precision."
+    );
+    describe_gauge!(
+        LANGFUSE_DEAD_LETTER_QUEUE_DEPTH_METRIC,
+        Unit::Count,
+        "Current number of Langfuse ingestion batches that wait for a retry, including the batches of other instances as of the last check."
+    );
+    describe_counter!(
+        LANGFUSE_DEAD_LETTER_EVICTIONS_METRIC,
+        Unit::Count,
+        "Total number of undelivered Langfuse ingestion batches that were dropped because the dead-letter queue was full."
+    );
+    describe_gauge!(
+        LANGFUSE_CONSECUTIVE_FAILURES_METRIC,
+        Unit::Count,
+        "Number of requests to Langfuse that failed since the last successful one, 0 while Langfuse is reachable."
+    );
+    describe_gauge!(
+        THREAD_INTEGRITY_SCANNED_CHATS_METRIC,
+        Unit::Count,
+        "Number of chats checked by the last thread integrity scan."
+    );
+    describe_gauge!(
+        THREAD_INTEGRITY_CORRUPTED_CHATS_METRIC,
+        Unit::Count,
+        "Number of chats of the last thread integrity scan with a corrupted thread segmented by issue kind."
+    );
     describe_gauge!(
         MCP_ACTIVE_SESSIONS_METRIC,
         Unit::Count,
         "Current number of active MCP sessions for each configured MCP server."
     );
+    describe_gauge!(
+        MCP_OLDEST_SESSION_AGE_METRIC,
+        Unit::Seconds,
+        "Age of the oldest active MCP session for each configured MCP server, 0 without sessions."
+    );
     describe_gauge!(
         "erato_cache_max_size_bytes",
         Unit::Bytes,
@@ -301,9 +692,9 @@ fn describe_application_metrics() {
 mod tests {
     use super::{
         calculate_fill_ratio, duration_seconds_with_millisecond_precision,
-        generation_error_type_label,
+        generation_error_type_label, renderable_block_type_label,
     };
-    use crate::models::message::GenerationErrorType;
+    use crate::models::message::{GenerationErrorType, RateLimitScope, RenderableBlockType};
     use std::time::Duration;
 
     #[test]
@@ -326,8 +717,10 @@ mod tests {
             "content_filter"
         );
         assert_eq!(
-            generation_error_type_label(&GenerationErrorType::RateLimit {
+            generation_error_type_label(&GenerationErrorType::RateLimited {
                 error_description: "x".to_string(),
+                retry_after_seconds: Some(20),
+                scope: Some(RateLimitScope::Tokens),
             }),
             "rate_limit"
         );
@@ -356,6 +749,27 @@ mod tests {
             }),
             "hallucination_loop"
         );
+        assert_eq!(
+            generation_error_type_label(&GenerationErrorType::ModerationBlocked {
+                error_description: "x".to_string(),
+                categories: vec![],
+            }),
+            "moderation_blocked"
+        );
+        assert_eq!(
+            generation_error_type_label(&GenerationErrorType::CompliancePolicy {
+                error_description: "x".to_string(),
+                matched_rule_id: "x".to_string(),
+            }),
+            "compliance_policy"
+        );
+        assert_eq!(
+            generation_error_type_label(&GenerationErrorType::TransformerFailed {
+                error_description: "x".to_string(),
+                transformer: "x".to_string(),
+            }),
+            "transformer_failed"
+        );
         assert_eq!(
             generation_error_type_label(&GenerationErrorType::InternalError {
                 error_description: "x".to_string(),
@@ -364,6 +778,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn renderable_block_type_label_matches_serialized_names() {
+        for block_type in [RenderableBlockType::Mermaid, RenderableBlockType::Math] {
+            assert_eq!(
+                serde_json::to_value(block_type).unwrap(),
+                renderable_block_type_label(block_type)
+            );
+        }
+    }
+
     #[test]
     fn duration_seconds_with_millisecond_precision_truncates_sub_millisecond_precision() {
         assert_eq!(
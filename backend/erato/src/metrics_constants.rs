@@ -2,24 +2,108 @@ pub const POSTGRES_QUERY_VERIFY_LATEST_MIGRATION: &str = "verify_latest_migratio
 pub const POSTGRES_QUERY_LIST_RECENT_CHATS: &str = "list_recent_chats";
 pub const POSTGRES_QUERY_COUNT_RECENT_CHATS: &str = "count_recent_chats";
 pub const POSTGRES_QUERY_FREQUENT_ASSISTANTS: &str = "frequent_assistants";
+pub const POSTGRES_QUERY_ASSISTANT_POPULARITY: &str = "assistant_popularity";
 pub const POSTGRES_QUERY_USER_SPENDING_BY_PROVIDER: &str = "user_spending_by_provider";
+pub const POSTGRES_QUERY_USAGE_BY_ASSISTANT: &str = "usage_by_assistant";
 pub const POSTGRES_QUERY_GENERATION_START: &str = "generation_start";
 pub const POSTGRES_QUERY_GENERATION_FINISH: &str = "generation_finish";
 pub const POSTGRES_QUERY_GENERATION_HEARTBEAT: &str = "generation_heartbeat";
 pub const POSTGRES_QUERY_GENERATION_REAP: &str = "generation_reap";
 pub const POSTGRES_QUERY_GENERATION_CLEANUP: &str = "generation_cleanup";
+pub const POSTGRES_QUERY_GENERATION_MESSAGE: &str = "generation_message";
 pub const POSTGRES_QUERY_LIST_GENERATING_CHATS: &str = "list_generating_chats";
+pub const POSTGRES_QUERY_DISTINCT_GENERATION_CHAT_PROVIDER_IDS: &str =
+    "distinct_generation_chat_provider_ids";
+pub const POSTGRES_QUERY_COUNT_UNREAD_CHAT_MESSAGES: &str = "count_unread_chat_messages";
+pub const POSTGRES_QUERY_CROSS_CHAT_MESSAGE_LINKS: &str = "cross_chat_message_links";
+pub const POSTGRES_QUERY_EXPIRED_PROVIDER_CAPTURES: &str = "expired_provider_captures";
+pub const POSTGRES_QUERY_CLEAR_PROVIDER_CAPTURES: &str = "clear_provider_captures";
+pub const POSTGRES_QUERY_BACKFILL_SHARE_GRANT_ORGANIZATION: &str =
+    "backfill_share_grant_organization";
+pub const POSTGRES_QUERY_SAVE_MESSAGE_CONTENT_DRAFT: &str = "save_message_content_draft";
+pub const POSTGRES_QUERY_FINALIZE_MESSAGE_CONTENT_DRAFTS: &str = "finalize_message_content_drafts";
+pub const POSTGRES_QUERY_GET_RECENT_CHAT: &str = "get_recent_chat";
+pub const POSTGRES_QUERY_SEARCH_CHAT_MESSAGES: &str = "search_chat_messages";
+pub const POSTGRES_QUERY_GET_MAINTENANCE_TASK_PROGRESS: &str = "get_maintenance_task_progress";
+pub const POSTGRES_QUERY_LOCK_MAINTENANCE_TASK_PROGRESS: &str = "lock_maintenance_task_progress";
+pub const POSTGRES_QUERY_START_MAINTENANCE_TASK: &str = "start_maintenance_task";
+pub const POSTGRES_QUERY_RESET_MAINTENANCE_TASK_PROGRESS: &str = "reset_maintenance_task_progress";
+pub const POSTGRES_QUERY_UPDATE_MAINTENANCE_TASK_PROGRESS: &str =
+    "update_maintenance_task_progress";
+pub const POSTGRES_QUERY_SET_MAINTENANCE_TASK_ERROR: &str = "set_maintenance_task_error";
+pub const POSTGRES_QUERY_FILE_POINTER_MIGRATION_BATCH: &str = "file_pointer_migration_batch";
+pub const POSTGRES_QUERY_RECORD_FILE_POINTER_MIGRATION: &str = "record_file_pointer_migration";
+pub const POSTGRES_QUERY_CONTENT_SPILLOVER_MIGRATION_BATCH: &str =
+    "content_spillover_migration_batch";
+pub const POSTGRES_QUERY_GENERATION_INPUT_ARCHIVAL_BATCH: &str = "generation_input_archival_batch";
+pub const POSTGRES_QUERY_CLAIM_DUE_SCHEDULED_MESSAGES: &str = "claim_due_scheduled_messages";
+pub const POSTGRES_QUERY_HIT_GENERATION_CACHE: &str = "hit_generation_cache";
+pub const POSTGRES_QUERY_STORE_GENERATION_CACHE: &str = "store_generation_cache";
+pub const POSTGRES_QUERY_EVICT_GENERATION_CACHE: &str = "evict_generation_cache";
+pub const POSTGRES_QUERY_FILE_UPLOAD_REFERENCES: &str = "file_upload_references";
+pub const POSTGRES_QUERY_RECORD_PROVIDER_USAGE: &str = "record_provider_usage";
+pub const POSTGRES_QUERY_SAMPLE_CHAT_IDS: &str = "sample_chat_ids";
+pub const POSTGRES_QUERY_UNREFERENCED_FILE_UPLOADS: &str = "unreferenced_file_uploads";
+pub const POSTGRES_QUERY_SHARE_GRANTS_OF_MISSING_RESOURCES: &str =
+    "share_grants_of_missing_resources";
+pub const POSTGRES_QUERY_CHATS_OF_MISSING_OWNERS: &str = "chats_of_missing_owners";
+pub const POSTGRES_QUERY_REWRITE_MESSAGE_ATTRIBUTION: &str = "rewrite_message_attribution";
+pub const POSTGRES_QUERY_USAGE_REPORT_COUNTS: &str = "usage_report_counts";
+pub const POSTGRES_QUERY_CREATE_USAGE_REPORTING_INSTALLATION: &str =
+    "create_usage_reporting_installation";
+pub const POSTGRES_QUERY_CLAIM_USAGE_REPORT: &str = "claim_usage_report";
+pub const POSTGRES_QUERY_CLAIM_LANGFUSE_DEAD_LETTER: &str = "claim_langfuse_dead_letter";
+pub const POSTGRES_QUERY_EVICT_LANGFUSE_DEAD_LETTERS: &str = "evict_langfuse_dead_letters";
 
 pub const KNOWN_POSTGRES_QUERY_IDS: &[&str] = &[
     POSTGRES_QUERY_VERIFY_LATEST_MIGRATION,
     POSTGRES_QUERY_LIST_RECENT_CHATS,
     POSTGRES_QUERY_COUNT_RECENT_CHATS,
     POSTGRES_QUERY_FREQUENT_ASSISTANTS,
+    POSTGRES_QUERY_ASSISTANT_POPULARITY,
     POSTGRES_QUERY_USER_SPENDING_BY_PROVIDER,
+    POSTGRES_QUERY_USAGE_BY_ASSISTANT,
     POSTGRES_QUERY_GENERATION_START,
     POSTGRES_QUERY_GENERATION_FINISH,
     POSTGRES_QUERY_GENERATION_HEARTBEAT,
     POSTGRES_QUERY_GENERATION_REAP,
     POSTGRES_QUERY_GENERATION_CLEANUP,
+    POSTGRES_QUERY_GENERATION_MESSAGE,
     POSTGRES_QUERY_LIST_GENERATING_CHATS,
+    POSTGRES_QUERY_DISTINCT_GENERATION_CHAT_PROVIDER_IDS,
+    POSTGRES_QUERY_COUNT_UNREAD_CHAT_MESSAGES,
+    POSTGRES_QUERY_CROSS_CHAT_MESSAGE_LINKS,
+    POSTGRES_QUERY_EXPIRED_PROVIDER_CAPTURES,
+    POSTGRES_QUERY_CLEAR_PROVIDER_CAPTURES,
+    POSTGRES_QUERY_BACKFILL_SHARE_GRANT_ORGANIZATION,
+    POSTGRES_QUERY_SAVE_MESSAGE_CONTENT_DRAFT,
+    POSTGRES_QUERY_FINALIZE_MESSAGE_CONTENT_DRAFTS,
+    POSTGRES_QUERY_GET_RECENT_CHAT,
+    POSTGRES_QUERY_SEARCH_CHAT_MESSAGES,
+    POSTGRES_QUERY_GET_MAINTENANCE_TASK_PROGRESS,
+    POSTGRES_QUERY_LOCK_MAINTENANCE_TASK_PROGRESS,
+    POSTGRES_QUERY_START_MAINTENANCE_TASK,
+    POSTGRES_QUERY_RESET_MAINTENANCE_TASK_PROGRESS,
+    POSTGRES_QUERY_UPDATE_MAINTENANCE_TASK_PROGRESS,
+    POSTGRES_QUERY_SET_MAINTENANCE_TASK_ERROR,
+    POSTGRES_QUERY_FILE_POINTER_MIGRATION_BATCH,
+    POSTGRES_QUERY_RECORD_FILE_POINTER_MIGRATION,
+    POSTGRES_QUERY_CONTENT_SPILLOVER_MIGRATION_BATCH,
+    POSTGRES_QUERY_GENERATION_INPUT_ARCHIVAL_BATCH,
+    POSTGRES_QUERY_CLAIM_DUE_SCHEDULED_MESSAGES,
+    POSTGRES_QUERY_HIT_GENERATION_CACHE,
+    POSTGRES_QUERY_STORE_GENERATION_CACHE,
+    POSTGRES_QUERY_EVICT_GENERATION_CACHE,
+    POSTGRES_QUERY_FILE_UPLOAD_REFERENCES,
+    POSTGRES_QUERY_RECORD_PROVIDER_USAGE,
+    POSTGRES_QUERY_SAMPLE_CHAT_IDS,
+    POSTGRES_QUERY_UNREFERENCED_FILE_UPLOADS,
+    POSTGRES_QUERY_SHARE_GRANTS_OF_MISSING_RESOURCES,
+    POSTGRES_QUERY_CHATS_OF_MISSING_OWNERS,
+    POSTGRES_QUERY_REWRITE_MESSAGE_ATTRIBUTION,
+    POSTGRES_QUERY_USAGE_REPORT_COUNTS,
+    POSTGRES_QUERY_CREATE_USAGE_REPORTING_INSTALLATION,
+    POSTGRES_QUERY_CLAIM_USAGE_REPORT,
+    POSTGRES_QUERY_CLAIM_LANGFUSE_DEAD_LETTER,
+    POSTGRES_QUERY_EVICT_LANGFUSE_DEAD_LETTERS,
 ];
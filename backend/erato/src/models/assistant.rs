@@ -4,6 +4,7 @@ use crate::db::entity::{
 };
 use crate::models::assistant_hub;
 use crate::models::file_upload;
+use crate::models::organization_condition;
 use crate::models::share_grant;
 use crate::policy::prelude::*;
 use crate::services::file_storage::FileStorage;
@@ -17,6 +18,12 @@ use sea_orm::{
 use serde::Serialize;
 use sqlx::types::Uuid;
 
+/// Visibility of assistants that are only readable by their owner and the users they are shared
+/// with
+pub const VISIBILITY_PRIVATE: &str = "private";
+/// Visibility of assistants that are readable by all users of their organization
+pub const VISIBILITY_ORGANIZATION: &str = "organization";
+
 /// Serializable file information for API responses
 #[derive(Debug, Clone, Serialize)]
 pub struct FileInfo {
@@ -55,6 +62,12 @@ pub struct AssistantWithFiles {
     pub facet_ids: Option<Vec<String>>,
     pub default_chat_provider: Option<String>,
     pub enforce_facet_settings: bool,
+    pub welcome_message: Option<String>,
+    pub pinned_facet_ids: Option<Vec<String>>,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    pub visibility: String,
+    pub featured: bool,
     pub archived_at: Option<DateTimeWithTimeZone>,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
@@ -74,6 +87,20 @@ fn normalize_assistant_facet_ids(facet_ids: Option<Vec<String>>) -> Option<Vec<S
     (!deduped.is_empty()).then_some(deduped)
 }
 
+/// Trim the tags of an assistant, and drop empty and duplicate ones.
+fn normalize_assistant_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+
+    for tag in tags {
+        let tag = tag.trim();
+        if !tag.is_empty() && !normalized.iter().any(|existing| existing == tag) {
+            normalized.push(tag.to_string());
+        }
+    }
+
+    normalized
+}
+
 /// Create a new assistant
 #[allow(clippy::too_many_arguments)]
 pub async fn create_assistant(
@@ -87,6 +114,11 @@ pub async fn create_assistant(
     facet_ids: Option<Vec<String>>,
     default_chat_provider: Option<String>,
     enforce_facet_settings: bool,
+    welcome_message: Option<String>,
+    pinned_facet_ids: Option<Vec<String>>,
+    category: Option<String>,
+    tags: Vec<String>,
+    visibility: &str,
 ) -> Result<assistants::Model, Report> {
     // Get the user ID from subject (subject contains the user UUID)
     let user_id_str = subject.user_id();
@@ -107,9 +139,16 @@ pub async fn create_assistant(
         facet_ids: Set(normalize_assistant_facet_ids(facet_ids)),
         default_chat_provider: Set(default_chat_provider),
         enforce_facet_settings: Set(enforce_facet_settings),
+        welcome_message: Set(welcome_message),
+        pinned_facet_ids: Set(normalize_assistant_facet_ids(pinned_facet_ids)),
         archived_at: Set(None),
         created_at: Set(Utc::now().into()),
         updated_at: Set(Utc::now().into()),
+        organization_id: Set(user.organization_id),
+        category: Set(category),
+        tags: Set(normalize_assistant_tags(tags)),
+        visibility: Set(visibility.to_string()),
+        featured: Set(false),
     };
 
     let created_assistant = Assistants::insert(new_assistant)
@@ -121,6 +160,8 @@ pub async fn create_assistant(
 
 /// Get all assistants available to the user (owner's assistants + shared assistants)
 ///
+/// Assistants that are visible to the organization of the user count as shared with them.
+///
 /// The `sharing_relation` parameter filters the results:
 /// - `"all"` (default): Returns all assistants (owned + shared)
 /// - `"owned_by_user"`: Returns only assistants owned by the user
@@ -170,19 +211,25 @@ pub async fn get_user_assistants(
             .filter_map(|grant| Uuid::parse_str(&grant.resource_id).ok())
             .collect();
 
-        // Query all non-archived shared assistants
+        // Query all non-archived shared assistants, and the ones visible to the organization
+        let mut shared_or_visible =
+            Condition::any().add(assistants::Column::Visibility.eq(VISIBILITY_ORGANIZATION));
         if !shared_assistant_ids.is_empty() {
-            Assistants::find()
-                .filter(
-                    Condition::all()
-                        .add(assistants::Column::Id.is_in(shared_assistant_ids))
-                        .add(assistants::Column::ArchivedAt.is_null()),
-                )
-                .all(conn)
-                .await?
-        } else {
-            vec![]
+            shared_or_visible =
+                shared_or_visible.add(assistants::Column::Id.is_in(shared_assistant_ids));
         }
+        Assistants::find()
+            .filter(
+                Condition::all()
+                    .add(shared_or_visible)
+                    .add(assistants::Column::ArchivedAt.is_null())
+                    .add(organization_condition(
+                        assistants::Column::OrganizationId,
+                        subject,
+                    )),
+            )
+            .all(conn)
+            .await?
     } else {
         vec![]
     };
@@ -245,7 +292,10 @@ async fn get_assistant_by_id_internal(
     allow_archived: bool,
 ) -> Result<assistants::Model, Report> {
     // Build query
-    let mut query = Assistants::find_by_id(assistant_id);
+    let mut query = Assistants::find_by_id(assistant_id).filter(organization_condition(
+        assistants::Column::OrganizationId,
+        subject,
+    ));
 
     // Only filter out archived assistants if not allowed
     if !allow_archived {
@@ -279,6 +329,15 @@ async fn get_assistant_by_id_internal(
         ));
     }
 
+    // Assistants that are visible to the organization are readable without a share grant. The
+    // query above is already restricted to the organization of the user.
+    if assistant.visibility == VISIBILITY_ORGANIZATION
+        && assistant_hub::hub_version_allows_generic_assistant_read(conn, subject, assistant_id)
+            .await?
+    {
+        return Ok(assistant);
+    }
+
     // If not the owner, check if the assistant is shared with the user (including organization group grants)
     let share_grants = share_grant::get_resources_shared_with_subject_and_groups(
         conn,
@@ -319,9 +378,23 @@ pub async fn get_assistant_by_id(
     get_assistant_by_id_internal(conn, subject, assistant_id, false).await
 }
 
-/// Get an assistant by ID for update/delete operations (user must be the owner)
+/// Get an assistant by ID, including archived ones (user must be the owner or have viewer access)
 ///
-/// This is stricter than get_assistant_by_id - it requires ownership, not just viewer access.
+/// For user-facing endpoints about existing chats, which may have been created with an
+/// assistant that has been archived since.
+pub async fn get_assistant_by_id_allow_archived(
+    conn: &DatabaseConnection,
+    subject: &Subject,
+    assistant_id: Uuid,
+) -> Result<assistants::Model, Report> {
+    get_assistant_by_id_internal(conn, subject, assistant_id, true).await
+}
+
+/// Get an assistant by ID for update/archive operations (user must be the owner or have
+/// an edit share grant)
+///
+/// This is stricter than get_assistant_by_id - it requires ownership or `edit` permission,
+/// not just viewer access.
 async fn get_assistant_by_id_for_modification(
     conn: &DatabaseConnection,
     subject: &Subject,
@@ -329,7 +402,10 @@ async fn get_assistant_by_id_for_modification(
     allow_archived: bool,
 ) -> Result<assistants::Model, Report> {
     // Build query
-    let mut query = Assistants::find_by_id(assistant_id);
+    let mut query = Assistants::find_by_id(assistant_id).filter(organization_condition(
+        assistants::Column::OrganizationId,
+        subject,
+    ));
 
     // Only filter out archived assistants if not allowed
     if !allow_archived {
@@ -356,11 +432,16 @@ async fn get_assistant_by_id_for_modification(
         .await?
         .wrap_err("User not found")?;
 
-    // Check if the user is the owner of the assistant (no viewer access for modifications)
+    // Check if the user is the owner of the assistant or was granted edit permission
+    // (no viewer access for modifications)
     if assistant.owner_user_id != user.id {
-        return Err(eyre::eyre!(
-            "Access denied: Only the owner can modify this assistant"
-        ));
+        let edit_granted_ids =
+            share_grant::get_edit_granted_resource_ids(conn, subject, "assistant").await?;
+        if !edit_granted_ids.contains(&assistant_id.to_string()) {
+            return Err(eyre::eyre!(
+                "Access denied: Only the owner or users with edit permission can modify this assistant"
+            ));
+        }
     }
 
     Ok(assistant)
@@ -402,6 +483,12 @@ pub async fn get_assistant_with_files(
         facet_ids: assistant.facet_ids,
         default_chat_provider: assistant.default_chat_provider,
         enforce_facet_settings: assistant.enforce_facet_settings,
+        welcome_message: assistant.welcome_message,
+        pinned_facet_ids: assistant.pinned_facet_ids,
+        category: assistant.category,
+        tags: assistant.tags,
+        visibility: assistant.visibility,
+        featured: assistant.featured,
         archived_at: assistant.archived_at,
         created_at: assistant.created_at,
         updated_at: assistant.updated_at,
@@ -423,9 +510,14 @@ pub async fn update_assistant(
     facet_ids: Option<Option<Vec<String>>>,
     default_chat_provider: Option<Option<String>>,
     enforce_facet_settings: Option<bool>,
+    welcome_message: Option<Option<String>>,
+    pinned_facet_ids: Option<Option<Vec<String>>>,
+    category: Option<Option<String>>,
+    tags: Option<Vec<String>>,
+    visibility: Option<&str>,
 ) -> Result<assistants::Model, Report> {
     let _ = policy; // Unused but kept for API consistency
-    // Get the assistant (includes ownership check - viewers cannot update)
+    // Get the assistant (includes ownership check - only owners and edit grantees can update)
     let assistant =
         get_assistant_by_id_for_modification(conn, subject, assistant_id, false).await?;
 
@@ -460,6 +552,27 @@ pub async fn update_assistant(
         active_assistant.enforce_facet_settings = Set(new_enforce_facet_settings);
     }
 
+    if let Some(new_welcome_message) = welcome_message {
+        active_assistant.welcome_message = Set(new_welcome_message);
+    }
+
+    if let Some(new_pinned_facet_ids) = pinned_facet_ids {
+        active_assistant.pinned_facet_ids =
+            Set(normalize_assistant_facet_ids(new_pinned_facet_ids));
+    }
+
+    if let Some(new_category) = category {
+        active_assistant.category = Set(new_category);
+    }
+
+    if let Some(new_tags) = tags {
+        active_assistant.tags = Set(normalize_assistant_tags(new_tags));
+    }
+
+    if let Some(new_visibility) = visibility {
+        active_assistant.visibility = Set(new_visibility.to_string());
+    }
+
     active_assistant.updated_at = Set(Utc::now().into());
 
     let updated_assistant = active_assistant.update(conn).await?;
@@ -475,7 +588,7 @@ pub async fn archive_assistant(
     assistant_id: Uuid,
 ) -> Result<assistants::Model, Report> {
     let _ = policy; // Unused but kept for API consistency
-    // Get the assistant (includes ownership check - viewers cannot archive)
+    // Get the assistant (includes ownership check - only owners and edit grantees can archive)
     let assistant =
         get_assistant_by_id_for_modification(conn, subject, assistant_id, false).await?;
 
@@ -489,6 +602,40 @@ pub async fn archive_assistant(
     Ok(archived_assistant)
 }
 
+/// Set whether an assistant is featured in the assistant listing.
+///
+/// Featuring is curated by admins, so it is not restricted to the owner of the assistant. Admins
+/// of an organization can only feature the assistants of their organization, which
+/// `organization_id` restricts the lookup to; platform admins pass `None`.
+pub async fn set_assistant_featured(
+    conn: &DatabaseConnection,
+    organization_id: Option<&str>,
+    assistant_id: Uuid,
+    featured: bool,
+) -> Result<assistants::Model, Report> {
+    let mut query =
+        Assistants::find_by_id(assistant_id).filter(assistants::Column::ArchivedAt.is_null());
+    if let Some(organization_id) = organization_id {
+        query = query.filter(assistants::Column::OrganizationId.eq(organization_id));
+    }
+    let assistant = query
+        .one(conn)
+        .await?
+        .wrap_err("Assistant not found or archived")?;
+
+    if assistant_hub::is_hub_version_assistant(conn, assistant_id).await? {
+        return Err(eyre::eyre!(
+            "Assistant not found: Assistant hub versions are featured through the hub"
+        ));
+    }
+
+    // Featuring doesn't change the assistant itself, so `updated_at` is kept
+    let mut active_assistant = assistant.into_active_model();
+    active_assistant.featured = Set(featured);
+
+    Ok(active_assistant.update(conn).await?)
+}
+
 /// Associate a file upload with an assistant
 pub async fn add_file_to_assistant(
     conn: &DatabaseConnection,
@@ -569,6 +716,8 @@ pub async fn create_standalone_file_upload(
         audio_transcription: Set(None),
         created_at: Set(Utc::now().into()),
         updated_at: Set(Utc::now().into()),
+        organization_id: Set(subject.organization_id().map(str::to_string)),
+        storage_status: Set(file_upload::FileStorageStatus::Ok.as_str().to_string()),
     };
 
     let created_file_upload = file_uploads::Entity::insert(new_file_upload)
@@ -4,7 +4,7 @@ use crate::db::entity::{
     assistant_file_uploads, assistant_hub_assistant_versions, assistant_hub_assistants,
     assistant_hub_reviews, assistants, users,
 };
-use crate::models::share_grant;
+use crate::models::{assistant, share_grant};
 use crate::policy::engine::PolicyEngine;
 use crate::policy::prelude::*;
 use chrono::Utc;
@@ -268,9 +268,17 @@ async fn clone_source_assistant(
         facet_ids: Set(source.facet_ids),
         default_chat_provider: Set(source.default_chat_provider),
         enforce_facet_settings: Set(source.enforce_facet_settings),
+        welcome_message: Set(source.welcome_message),
+        pinned_facet_ids: Set(source.pinned_facet_ids),
         archived_at: Set(None),
         created_at: Set(now),
         updated_at: Set(now),
+        organization_id: Set(source.organization_id),
+        category: Set(source.category),
+        tags: Set(source.tags),
+        // Hub versions are only readable through the hub
+        visibility: Set(assistant::VISIBILITY_PRIVATE.to_string()),
+        featured: Set(false),
     };
 
     let cloned = Assistants::insert(cloned).exec_with_returning(conn).await?;
@@ -430,6 +438,8 @@ pub async fn build_submission_diff(
             diff_field("facet_ids", baseline_assistant.as_ref().map(|a| a.facet_ids.clone().unwrap_or_default()), source.facet_ids.unwrap_or_default()),
             diff_field("default_chat_provider", baseline_assistant.as_ref().map(|a| a.default_chat_provider.clone()), source.default_chat_provider),
             diff_field("enforce_facet_settings", baseline_assistant.as_ref().map(|a| a.enforce_facet_settings), source.enforce_facet_settings),
+            diff_field("welcome_message", baseline_assistant.as_ref().map(|a| a.welcome_message.clone()), source.welcome_message),
+            diff_field("pinned_facet_ids", baseline_assistant.as_ref().map(|a| a.pinned_facet_ids.clone().unwrap_or_default()), source.pinned_facet_ids.unwrap_or_default()),
             diff_field("files", baseline_files, source_files),
             diff_field("long_description", baseline_version.as_ref().map(|v| v.long_description.clone()), profile.long_description.clone()),
             diff_field("category_ids", baseline_version.as_ref().map(|v| v.category_ids.clone().unwrap_or_default()), category_ids),
@@ -1,23 +1,33 @@
-use crate::db::entity::messages;
-use crate::db::entity_ext::chats;
+use crate::db::entity::{chat_labels, file_uploads, labels, share_grants};
 use crate::db::entity_ext::prelude::*;
+use crate::db::entity_ext::{chats, messages};
 use crate::metrics_constants::{
+    POSTGRES_QUERY_ASSISTANT_POPULARITY, POSTGRES_QUERY_CHATS_OF_MISSING_OWNERS,
     POSTGRES_QUERY_COUNT_RECENT_CHATS, POSTGRES_QUERY_FREQUENT_ASSISTANTS,
-    POSTGRES_QUERY_LIST_GENERATING_CHATS, POSTGRES_QUERY_LIST_RECENT_CHATS,
+    POSTGRES_QUERY_GET_RECENT_CHAT, POSTGRES_QUERY_LIST_GENERATING_CHATS,
+    POSTGRES_QUERY_LIST_RECENT_CHATS, POSTGRES_QUERY_REWRITE_MESSAGE_ATTRIBUTION,
 };
-use crate::models::message::GenerationParameters;
-use crate::models::pagination;
+use crate::models::assistant::{AssistantWithFiles, FileInfo, VISIBILITY_PRIVATE};
+use crate::models::chat_read_state::{UNREAD_MESSAGE_COUNT_SQL, read_state_joins_sql};
+use crate::models::message::{GenerationParameters, check_previous_message_for_chat};
+use crate::models::ownership_transfer::{
+    OwnershipTransferOptions, find_new_owner, grant_previous_owner_read_access,
+    record_ownership_transfer, user_share_grant_ids,
+};
+use crate::models::{organization_condition, pagination};
 use crate::policy::prelude::*;
 use crate::query_metrics::named_statement_from_sql_and_values;
+use crate::services::policy_invalidations::{PolicyInvalidationReason, record_policy_invalidation};
 use eyre::{Report, eyre};
 use sea_orm::prelude::*;
 use sea_orm::{
     ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, FromQueryResult, QueryFilter,
-    QueryOrder,
+    QueryOrder, QuerySelect, TransactionTrait,
 };
 use serde::{Deserialize, Serialize};
 use sqlx::types::chrono::Utc;
 use tracing::instrument;
+use utoipa::ToSchema;
 
 /// Configuration for a chat that is based on an assistant
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -45,6 +55,107 @@ impl AssistantConfiguration {
     }
 }
 
+/// Copy of the configuration of the assistant of a chat, as it was when the snapshot was taken.
+///
+/// Every chat created from an assistant gets a snapshot. It is only used in place of the live
+/// assistant if `assistants.freeze_assistant_config` is enabled.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct AssistantSnapshot {
+    /// The ID of the assistant the snapshot was taken of
+    pub assistant_id: Uuid,
+    /// The ID of the owner of the assistant
+    pub owner_user_id: Uuid,
+    /// Name of the assistant
+    pub name: String,
+    /// Description of the assistant
+    pub description: Option<String>,
+    /// System prompt of the assistant
+    pub prompt: String,
+    /// Default chat provider of the assistant
+    pub default_chat_provider: Option<String>,
+    /// MCP servers that were enabled for the assistant
+    pub mcp_server_ids: Option<Vec<String>>,
+    /// Facets that were enabled for the assistant
+    pub facet_ids: Option<Vec<String>>,
+    /// Whether the facet settings of the assistant were enforced
+    pub enforce_facet_settings: bool,
+    /// Facets that were pinned in the assistant
+    pub pinned_facet_ids: Option<Vec<String>>,
+    /// Welcome message of the assistant
+    pub welcome_message: Option<String>,
+    /// The IDs of the files of the assistant
+    pub file_ids: Vec<Uuid>,
+    /// When the assistant was last updated before the snapshot was taken
+    pub assistant_updated_at: DateTimeWithTimeZone,
+    /// When the snapshot was taken
+    pub taken_at: DateTimeWithTimeZone,
+}
+
+impl AssistantSnapshot {
+    /// Take a snapshot of the current configuration of an assistant.
+    pub fn of(assistant: &AssistantWithFiles) -> Self {
+        Self {
+            assistant_id: assistant.id,
+            owner_user_id: assistant.owner_user_id,
+            name: assistant.name.clone(),
+            description: assistant.description.clone(),
+            prompt: assistant.prompt.clone(),
+            default_chat_provider: assistant.default_chat_provider.clone(),
+            mcp_server_ids: assistant.mcp_server_ids.clone(),
+            facet_ids: assistant.facet_ids.clone(),
+            enforce_facet_settings: assistant.enforce_facet_settings,
+            pinned_facet_ids: assistant.pinned_facet_ids.clone(),
+            welcome_message: assistant.welcome_message.clone(),
+            file_ids: assistant.files.iter().map(|file| file.id).collect(),
+            assistant_updated_at: assistant.updated_at,
+            taken_at: Utc::now().into(),
+        }
+    }
+
+    /// Parse an assistant snapshot from a JSONB value
+    pub fn from_json(json: &serde_json::Value) -> Result<Self, Report> {
+        serde_json::from_value(json.clone())
+            .map_err(|e| eyre!("Failed to parse assistant snapshot: {}", e))
+    }
+
+    /// Convert to a JSONB value for storage
+    pub fn to_json(&self) -> Result<serde_json::Value, Report> {
+        serde_json::to_value(self)
+            .map_err(|e| eyre!("Failed to serialize assistant snapshot: {}", e))
+    }
+
+    /// The assistant as it was configured in the snapshot. Files that were deleted since the
+    /// snapshot was taken are left out.
+    async fn into_assistant(self, conn: &DatabaseConnection) -> Result<AssistantWithFiles, Report> {
+        let files = FileUploads::find()
+            .filter(file_uploads::Column::Id.is_in(self.file_ids))
+            .all(conn)
+            .await?;
+
+        Ok(AssistantWithFiles {
+            id: self.assistant_id,
+            owner_user_id: self.owner_user_id,
+            name: self.name,
+            description: self.description,
+            prompt: self.prompt,
+            mcp_server_ids: self.mcp_server_ids,
+            facet_ids: self.facet_ids,
+            default_chat_provider: self.default_chat_provider,
+            enforce_facet_settings: self.enforce_facet_settings,
+            welcome_message: self.welcome_message,
+            pinned_facet_ids: self.pinned_facet_ids,
+            category: None,
+            tags: Vec::new(),
+            visibility: VISIBILITY_PRIVATE.to_string(),
+            featured: false,
+            archived_at: None,
+            created_at: self.assistant_updated_at,
+            updated_at: self.assistant_updated_at,
+            files: files.into_iter().map(FileInfo::from).collect(),
+        })
+    }
+}
+
 /// Indicates whether a chat was newly created or already existed
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ChatCreationStatus {
@@ -63,7 +174,8 @@ impl From<&chats::Model> for Resource {
 /// If `existing_chat_id` is provided, try to load the chat from the database.
 /// If the chat is not found, an error is returned.
 /// If `existing_chat_id` is not provided, create a new chat, with `owner_user_id` as the owner.
-/// If `assistant_id` is provided when creating a new chat, the assistant configuration will be stored.
+/// If `assistant_id` is provided when creating a new chat, the assistant configuration will be stored,
+/// together with a snapshot of the assistant.
 ///
 /// Returns a tuple of (chat model, creation status) where the status indicates whether
 /// the chat was newly created or already existed.
@@ -77,35 +189,128 @@ pub async fn get_or_create_chat(
     title_by_user_provided: Option<String>,
 ) -> Result<(chats::Model, ChatCreationStatus), Report> {
     if let Some(existing_chat_id) = existing_chat_id {
-        let existing_chat: Option<chats::Model> =
-            Chats::find_by_id(*existing_chat_id).one(conn).await?;
+        let existing_chat: Option<chats::Model> = Chats::find_by_id(*existing_chat_id)
+            .filter(organization_condition(
+                chats::Column::OrganizationId,
+                subject,
+            ))
+            .one(conn)
+            .await?;
         // Return with error if the chat is not found
         let existing_chat = existing_chat.ok_or(eyre!("Chat {existing_chat_id} not found"))?;
         // Authorize the user to access the chat
         authorize!(policy, subject, &existing_chat, Action::Read)?;
         Ok((existing_chat, ChatCreationStatus::Existing))
     } else {
-        // Authorize that user is allowed to create a chat
-        authorize!(policy, subject, &Resource::ChatSingleton, Action::Create)?;
+        create_chat(
+            conn,
+            policy,
+            subject,
+            owner_user_id,
+            assistant_id,
+            title_by_user_provided,
+            false,
+        )
+        .await
+    }
+}
 
-        // Build assistant_configuration JSON if assistant_id is provided
-        let assistant_configuration = if let Some(aid) = assistant_id {
-            Some(AssistantConfiguration::new(*aid).to_json()?)
-        } else {
-            None
-        };
+/// Create a new chat, with `owner_user_id` as the owner.
+///
+/// A `provisional` chat is created together with its first message, and stays provisional until
+/// that message is saved (see [`submit_message`](crate::models::message::submit_message)).
+/// Provisional chats are not listed, and are deleted by the cleanup worker if the message is never
+/// saved.
+async fn create_chat(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    owner_user_id: &str,
+    assistant_id: Option<&Uuid>,
+    title_by_user_provided: Option<String>,
+    provisional: bool,
+) -> Result<(chats::Model, ChatCreationStatus), Report> {
+    // Authorize that user is allowed to create a chat
+    authorize!(policy, subject, &Resource::ChatSingleton, Action::Create)?;
+
+    let (assistant_configuration, assistant_snapshot) =
+        assistant_columns(conn, policy, subject, assistant_id).await?;
+
+    let new_chat = chats::ActiveModel {
+        owner_user_id: ActiveValue::Set(owner_user_id.to_owned()),
+        assistant_configuration: ActiveValue::Set(assistant_configuration),
+        assistant_snapshot: ActiveValue::Set(assistant_snapshot),
+        title_by_user_provided: ActiveValue::Set(title_by_user_provided),
+        organization_id: ActiveValue::Set(subject.organization_id().map(str::to_string)),
+        provisional: ActiveValue::Set(provisional),
+        ..Default::default()
+    };
+    let txn = conn.begin().await?;
+    let created_chat = chats::Entity::insert(new_chat)
+        .exec_with_returning(&txn)
+        .await?;
+    record_policy_invalidation(&txn, PolicyInvalidationReason::ChatCreated).await?;
+    txn.commit().await?;
+    Ok((created_chat, ChatCreationStatus::Created))
+}
 
-        let new_chat = chats::ActiveModel {
-            owner_user_id: ActiveValue::Set(owner_user_id.to_owned()),
-            assistant_configuration: ActiveValue::Set(assistant_configuration),
-            title_by_user_provided: ActiveValue::Set(title_by_user_provided),
-            ..Default::default()
-        };
-        let created_chat = chats::Entity::insert(new_chat)
-            .exec_with_returning(conn)
+/// Build an ephemeral chat, with `owner_user_id` as the owner, which is only held in memory and
+/// never saved (see [`EphemeralChatStore`](crate::services::ephemeral_chats::EphemeralChatStore)).
+pub async fn new_ephemeral_chat(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    owner_user_id: &str,
+    assistant_id: Option<&Uuid>,
+    title_by_user_provided: Option<String>,
+) -> Result<chats::Model, Report> {
+    authorize!(policy, subject, &Resource::ChatSingleton, Action::Create)?;
+
+    let (assistant_configuration, assistant_snapshot) =
+        assistant_columns(conn, policy, subject, assistant_id).await?;
+    let now = Utc::now().fixed_offset();
+    Ok(chats::Model {
+        id: Uuid::new_v4(),
+        owner_user_id: owner_user_id.to_owned(),
+        created_at: now,
+        updated_at: now,
+        title_by_summary: None,
+        archived_at: None,
+        assistant_configuration,
+        assistant_id: assistant_id.copied(),
+        title_by_user_provided,
+        active_generation_id: None,
+        generation_state: None,
+        generation_started_at: None,
+        generation_heartbeat_at: None,
+        generation_ended_at: None,
+        organization_id: subject.organization_id().map(str::to_string),
+        generation_replica_id: None,
+        generation_message_id: None,
+        assistant_snapshot,
+        provisional: false,
+        custom_instruction: None,
+        response_language: None,
+    })
+}
+
+/// The `assistant_configuration` and `assistant_snapshot` of a new chat with the given assistant.
+async fn assistant_columns(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    assistant_id: Option<&Uuid>,
+) -> Result<(Option<serde_json::Value>, Option<serde_json::Value>), Report> {
+    let Some(aid) = assistant_id else {
+        return Ok((None, None));
+    };
+    let assistant =
+        crate::models::assistant::get_assistant_with_files(conn, policy, subject, *aid, false)
             .await?;
-        Ok((created_chat, ChatCreationStatus::Created))
-    }
+    Ok((
+        Some(AssistantConfiguration::new(*aid).to_json()?),
+        Some(AssistantSnapshot::of(&assistant).to_json()?),
+    ))
 }
 
 /// Get all chats from the database.
@@ -116,7 +321,9 @@ pub async fn get_all_chats(conn: &DatabaseConnection) -> Result<Vec<chats::Model
 /// Convenience method to get or create a chat based on a previous message ID.
 ///
 /// If the message ID is provided, it will find the chat that the message belongs to.
-/// If the message ID is not provided, it will create a new chat.
+/// The message must be part of the active thread of that chat.
+/// If the message ID is not provided, it will create a new provisional chat, for the message that
+/// is submitted next.
 ///
 /// Returns a tuple of (chat model, creation status) where the status indicates whether
 /// the chat was newly created or already existed.
@@ -135,6 +342,9 @@ pub async fn get_or_create_chat_by_previous_message_id(
             .one(conn)
             .await?
             .ok_or_else(|| eyre!("Message with ID {} not found", message_id))?;
+        // The chat is derived from the message, so only the active thread needs checking here.
+        // Read access to that chat is authorized by get_or_create_chat below.
+        check_previous_message_for_chat(&message, &message.chat_id, false)?;
 
         // Use the chat_id from the message with get_or_create_chat
         // Note: We pass None for assistant_id here because we're referencing an existing chat
@@ -152,16 +362,17 @@ pub async fn get_or_create_chat_by_previous_message_id(
         )
         .await
     } else {
-        // No previous message ID, so create a new chat using get_or_create_chat
+        // No previous message ID, so create a new chat, which stays provisional until the first
+        // message is saved.
         // Pass assistant_id here so it gets stored in the new chat's assistant_configuration
-        get_or_create_chat(
+        create_chat(
             conn,
             policy,
             subject,
-            None,
             owner_user_id,
             assistant_id,
             title_by_user_provided,
+            true,
         )
         .await
     }
@@ -178,7 +389,7 @@ pub fn resolve_chat_display_name(
         .to_string()
 }
 
-#[derive(Debug, FromQueryResult)]
+#[derive(Debug)]
 pub struct RecentChat {
     pub id: String,
     /// Title of the chat as generated by summary automation.
@@ -200,9 +411,20 @@ pub struct RecentChat {
     pub assistant_id: Option<Uuid>,
     /// The name of the assistant if this chat is based on an assistant
     pub assistant_name: Option<String>,
+    /// Instruction of the user that is added to the prompt of every generation of the chat
+    pub custom_instruction: Option<String>,
+    /// Language the assistant should respond in, unless the request or the detected language of
+    /// the latest user message asks for another one
+    pub response_language: Option<String>,
     /// Start time of the chat's generation, present only while it is running
     /// with a fresh heartbeat.
     pub active_generation_started_at: Option<DateTimeWithTimeZone>,
+    /// Labels assigned to the chat, ordered by name.
+    pub labels: Vec<labels::Model>,
+    /// The last message the requesting user has marked as read in this chat.
+    pub last_read_message_id: Option<Uuid>,
+    /// Number of assistant messages in the active thread that are newer than the last read message.
+    pub unread_count: i64,
 }
 
 /// Statistics for a list of chats
@@ -237,9 +459,14 @@ struct ChatWithLatestMessage {
     title_by_user_provided: Option<String>,
     archived_at: Option<DateTimeWithTimeZone>,
     assistant_id: Option<Uuid>,
+    custom_instruction: Option<String>,
+    response_language: Option<String>,
     active_generation_started_at: Option<DateTimeWithTimeZone>,
     // Latest message fields
     latest_message_at: DateTimeWithTimeZone,
+    // Read state fields of the requesting user
+    last_read_message_id: Option<Uuid>,
+    unread_count: i64,
 }
 
 /// Filtering and pagination options for recent chat listing.
@@ -253,6 +480,10 @@ pub struct RecentChatsFilter<'a> {
     pub include_archived: bool,
     /// Optional full-text search query for chat titles.
     pub search_query: Option<&'a str>,
+    /// Only include chats that have this label assigned.
+    pub label_id: Option<Uuid>,
+    /// Only include chats that were created with this assistant.
+    pub assistant_id: Option<Uuid>,
 }
 
 /// Get the most recent chats for a user.
@@ -269,10 +500,6 @@ pub async fn get_recent_chats(
     filter: RecentChatsFilter<'_>,
     generation_stale_after_secs: u64,
 ) -> Result<(Vec<RecentChat>, ChatListStats), Report> {
-    use crate::db::entity::assistants;
-    use crate::db::entity::prelude::Assistants;
-    use std::collections::HashMap;
-
     // Build the WHERE clause conditions
     let archived_condition = if !filter.include_archived {
         "AND \"chats\".\"archived_at\" IS NULL"
@@ -300,6 +527,30 @@ pub async fn get_recent_chats(
             String::new()
         }
     };
+    let label_condition = |param_index: u8| {
+        if filter.label_id.is_some() {
+            format!(
+                r#"AND EXISTS (
+                SELECT 1
+                FROM chat_labels cl
+                WHERE cl.chat_id = chats.id
+                    AND cl.label_id = ${param_index}
+            )"#
+            )
+        } else {
+            String::new()
+        }
+    };
+    let assistant_condition = |param_index: u8| {
+        if filter.assistant_id.is_some() {
+            format!(r#"AND "chats"."assistant_id" = ${param_index}"#)
+        } else {
+            String::new()
+        }
+    };
+    // Optional parameters are appended after the fixed ones, in this order.
+    let search_param_count = u8::from(search_query.is_some());
+    let label_param_count = u8::from(filter.label_id.is_some());
 
     // Query using INNER JOIN LATERAL for better performance
     // This ensures the database does all filtering, sorting, and pagination
@@ -312,29 +563,40 @@ pub async fn get_recent_chats(
             "chats"."title_by_user_provided",
             "chats"."archived_at",
             "chats"."assistant_id",
+            "chats"."custom_instruction",
+            "chats"."response_language",
             CASE
                 WHEN "chats"."generation_state" = 'running'
                     AND "chats"."generation_heartbeat_at" > now() - make_interval(secs => {generation_stale_after_secs})
                 THEN "chats"."generation_started_at"
             END AS "active_generation_started_at",
-            "latest_msg"."created_at" AS "latest_message_at"
+            "latest_msg"."created_at" AS "latest_message_at",
+            "read_state"."last_read_message_id" AS "last_read_message_id",
+            {UNREAD_MESSAGE_COUNT_SQL} AS "unread_count"
         FROM "chats"
         INNER JOIN LATERAL (
             SELECT m.chat_id, m.id, m.created_at
             FROM messages m
             WHERE m.chat_id = chats.id
-            ORDER BY m.created_at DESC
+            ORDER BY m.created_at DESC, m.id DESC
             LIMIT 1
         ) latest_msg ON true
+        {}
         WHERE "chats"."owner_user_id" = $1
+            AND NOT "chats"."provisional"
             {}
             {}
-        ORDER BY latest_msg.created_at DESC
+            {}
+            {}
+        ORDER BY latest_msg.created_at DESC, chats.id DESC
         LIMIT $2
         OFFSET $3
         "#,
+        read_state_joins_sql(1),
         archived_condition,
-        search_condition(4)
+        search_condition(4),
+        label_condition(4 + search_param_count),
+        assistant_condition(4 + search_param_count + label_param_count)
     );
 
     let mut query_values = vec![
@@ -345,6 +607,12 @@ pub async fn get_recent_chats(
     if let Some(search_query) = search_query {
         query_values.push(search_query.into());
     }
+    if let Some(label_id) = filter.label_id {
+        query_values.push(label_id.into());
+    }
+    if let Some(assistant_id) = filter.assistant_id {
+        query_values.push(assistant_id.into());
+    }
 
     let chats_with_messages: Vec<ChatWithLatestMessage> =
         ChatWithLatestMessage::find_by_statement(named_statement_from_sql_and_values(
@@ -376,12 +644,17 @@ pub async fn get_recent_chats(
                         LIMIT 1
                     ) latest_msg ON true
                     WHERE "chats"."owner_user_id" = $1
+                        AND NOT "chats"."provisional"
+                        {}
+                        {}
                         {}
                         {}
                 ) AS sub_query
                 "#,
                 archived_condition,
-                search_condition(2)
+                search_condition(2),
+                label_condition(2 + search_param_count),
+                assistant_condition(2 + search_param_count + label_param_count)
             );
 
             #[derive(Debug, FromQueryResult)]
@@ -393,6 +666,12 @@ pub async fn get_recent_chats(
             if let Some(search_query) = search_query {
                 count_values.push(search_query.into());
             }
+            if let Some(label_id) = filter.label_id {
+                count_values.push(label_id.into());
+            }
+            if let Some(assistant_id) = filter.assistant_id {
+                count_values.push(assistant_id.into());
+            }
 
             let count_result: CountResult =
                 CountResult::find_by_statement(named_statement_from_sql_and_values(
@@ -426,6 +705,108 @@ pub async fn get_recent_chats(
         }
     }
 
+    let recent_chats = assemble_recent_chats(conn, &authorized_chats).await?;
+
+    // Create the statistics object
+    let stats = ChatListStats {
+        total_count: pagination::u64_to_i64_count(total_count),
+        current_offset: filter.offset,
+        returned_count: recent_chats.len(),
+        has_more,
+    };
+
+    Ok((recent_chats, stats))
+}
+
+/// Get a single chat in the shape of the recent chats listing.
+///
+/// Unlike the listing, the chat doesn't have to be owned by the user, so archived chats and chats
+/// shared with the user are returned as well. Chats without messages report their creation time
+/// as the time of the last message.
+///
+/// Returns `None` if the chat doesn't exist or the subject is not allowed to read it.
+#[instrument(skip_all)]
+pub async fn get_recent_chat_by_id(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    user_id: &str,
+    chat_id: &Uuid,
+    generation_stale_after_secs: u64,
+) -> Result<Option<RecentChat>, Report> {
+    if authorize!(
+        policy,
+        subject,
+        &Resource::Chat(chat_id.to_string()),
+        Action::Read
+    )
+    .is_err()
+    {
+        return Ok(None);
+    }
+
+    let sql = format!(
+        r#"
+        SELECT
+            "chats"."id",
+            "chats"."owner_user_id",
+            "chats"."title_by_summary",
+            "chats"."title_by_user_provided",
+            "chats"."archived_at",
+            "chats"."assistant_id",
+            "chats"."custom_instruction",
+            "chats"."response_language",
+            CASE
+                WHEN "chats"."generation_state" = 'running'
+                    AND "chats"."generation_heartbeat_at" > now() - make_interval(secs => {generation_stale_after_secs})
+                THEN "chats"."generation_started_at"
+            END AS "active_generation_started_at",
+            COALESCE("latest_msg"."created_at", "chats"."created_at") AS "latest_message_at",
+            "read_state"."last_read_message_id" AS "last_read_message_id",
+            {UNREAD_MESSAGE_COUNT_SQL} AS "unread_count"
+        FROM "chats"
+        LEFT JOIN LATERAL (
+            SELECT m.created_at
+            FROM messages m
+            WHERE m.chat_id = chats.id
+            ORDER BY m.created_at DESC, m.id DESC
+            LIMIT 1
+        ) latest_msg ON true
+        {}
+        WHERE "chats"."id" = $2
+        "#,
+        read_state_joins_sql(1),
+    );
+
+    let Some(chat_with_msg) =
+        ChatWithLatestMessage::find_by_statement(named_statement_from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            POSTGRES_QUERY_GET_RECENT_CHAT,
+            sql,
+            [user_id.into(), (*chat_id).into()],
+        ))
+        .one(conn)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    Ok(assemble_recent_chats(conn, &[&chat_with_msg])
+        .await?
+        .into_iter()
+        .next())
+}
+
+/// Assemble [`RecentChat`]s from authorized chat rows, batch-fetching the assistant names, the
+/// last generation parameters and the labels of all chats.
+async fn assemble_recent_chats(
+    conn: &DatabaseConnection,
+    authorized_chats: &[&ChatWithLatestMessage],
+) -> Result<Vec<RecentChat>, Report> {
+    use crate::db::entity::assistants;
+    use crate::db::entity::prelude::Assistants;
+    use std::collections::HashMap;
+
     // Collect all chat IDs for batch queries
     let authorized_chat_ids: Vec<Uuid> = authorized_chats.iter().map(|c| c.id).collect();
 
@@ -464,6 +845,7 @@ pub async fn get_recent_chats(
             .filter(messages::Column::IsMessageInActiveThread.eq(true))
             .filter(messages::Column::GenerationParameters.is_not_null())
             .order_by_desc(messages::Column::CreatedAt)
+            .order_by_desc(messages::Column::Id)
             .all(conn)
             .await?;
 
@@ -476,13 +858,14 @@ pub async fn get_recent_chats(
                     .generation_parameters
                     .and_then(|params| serde_json::from_value::<GenerationParameters>(params).ok())
                     .map(|gp| {
+                        let provider_id = gp.selected_chat_provider_id().map(String::from);
                         let selected_facets: Vec<String> = gp
                             .selected_facets
                             .into_iter()
                             .filter_map(|(id, enabled)| enabled.then_some(id))
                             .collect();
                         LastGenerationInfo {
-                            provider_id: gp.generation_chat_provider_id,
+                            provider_id,
                             selected_facets: Some(selected_facets),
                         }
                     })
@@ -498,6 +881,10 @@ pub async fn get_recent_chats(
         HashMap::new()
     };
 
+    // Batch query: Get the labels of all chats in a single query
+    let mut labels_map =
+        crate::models::label::get_labels_for_chats(conn, &authorized_chat_ids).await?;
+
     // Assemble the final results using the pre-fetched data
     let recent_chats: Vec<RecentChat> = authorized_chats
         .iter()
@@ -520,27 +907,26 @@ pub async fn get_recent_chats(
                     chat_with_msg.title_by_user_provided.as_deref(),
                     chat_with_msg.title_by_summary.as_deref(),
                 ),
-                last_message_at: chat_with_msg.latest_message_at,
+                last_message_at: messages::api_timestamp(
+                    chat_with_msg.latest_message_at.with_timezone(&chrono::Utc),
+                ),
                 archived_at: chat_with_msg.archived_at,
                 owner_user_id: chat_with_msg.owner_user_id.clone(),
                 last_chat_provider_id,
                 last_selected_facets,
                 assistant_id: chat_with_msg.assistant_id,
                 assistant_name,
+                custom_instruction: chat_with_msg.custom_instruction.clone(),
+                response_language: chat_with_msg.response_language.clone(),
                 active_generation_started_at: chat_with_msg.active_generation_started_at,
+                labels: labels_map.remove(&chat_with_msg.id).unwrap_or_default(),
+                last_read_message_id: chat_with_msg.last_read_message_id,
+                unread_count: chat_with_msg.unread_count,
             }
         })
         .collect();
 
-    // Create the statistics object
-    let stats = ChatListStats {
-        total_count: pagination::u64_to_i64_count(total_count),
-        current_offset: filter.offset,
-        returned_count: recent_chats.len(),
-        has_more,
-    };
-
-    Ok((recent_chats, stats))
+    Ok(recent_chats)
 }
 
 /// A chat with a running or recently ended generation.
@@ -574,6 +960,7 @@ pub async fn get_generating_chats(
         FROM "chats"
         WHERE "chats"."owner_user_id" = $1
             AND "chats"."archived_at" IS NULL
+            AND NOT "chats"."provisional"
             AND "chats"."generation_started_at" IS NOT NULL
             AND (
                 (
@@ -648,6 +1035,7 @@ pub async fn get_frequent_assistants(
                     COUNT(*) as usage_count
                 FROM chats
                 WHERE owner_user_id = $1
+                    AND NOT provisional
                     AND assistant_configuration IS NOT NULL
                     AND assistant_configuration->>'assistant_id' IS NOT NULL
                     AND created_at >= $2
@@ -698,6 +1086,54 @@ pub async fn get_frequent_assistants(
     Ok(frequent_assistants)
 }
 
+/// Count the chats created with each assistant in the organization of the subject in the last
+/// `days` days.
+///
+/// Unlike [`get_frequent_assistants`], this counts the chats of all users, and is used to rank
+/// assistants by popularity.
+pub async fn get_assistant_usage_counts(
+    conn: &DatabaseConnection,
+    subject: &Subject,
+    days: u32,
+) -> Result<std::collections::HashMap<Uuid, i64>, Report> {
+    #[derive(Debug, FromQueryResult)]
+    struct AssistantUsageCount {
+        assistant_id: Uuid,
+        usage_count: i64,
+    }
+
+    let cutoff_date = Utc::now() - chrono::Duration::days(days as i64);
+
+    let usage_counts: Vec<AssistantUsageCount> =
+        AssistantUsageCount::find_by_statement(named_statement_from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            POSTGRES_QUERY_ASSISTANT_POPULARITY,
+            r#"
+                SELECT
+                    (assistant_configuration->>'assistant_id')::uuid as assistant_id,
+                    COUNT(*) as usage_count
+                FROM chats
+                WHERE organization_id IS NOT DISTINCT FROM $1
+                    AND NOT provisional
+                    AND assistant_configuration IS NOT NULL
+                    AND assistant_configuration->>'assistant_id' IS NOT NULL
+                    AND created_at >= $2
+                GROUP BY assistant_configuration->>'assistant_id'
+            "#,
+            vec![
+                subject.organization_id().map(str::to_string).into(),
+                cutoff_date.into(),
+            ],
+        ))
+        .all(conn)
+        .await?;
+
+    Ok(usage_counts
+        .into_iter()
+        .map(|usage| (usage.assistant_id, usage.usage_count))
+        .collect())
+}
+
 pub async fn get_chat_by_message_id(
     conn: &DatabaseConnection,
     policy: &PolicyEngine,
@@ -753,7 +1189,10 @@ pub async fn update_chat_summary(
     let mut chat_active: chats::ActiveModel = chat.clone().into();
     chat_active.title_by_summary = ActiveValue::Set(Some(summary));
 
-    let updated_chat = chat_active.update(conn).await?;
+    let txn = conn.begin().await?;
+    let updated_chat = chat_active.update(&txn).await?;
+    record_policy_invalidation(&txn, PolicyInvalidationReason::ChatArchived).await?;
+    txn.commit().await?;
 
     Ok(updated_chat)
 }
@@ -792,6 +1231,115 @@ pub async fn update_chat_title_by_user_provided(
     Ok(updated_chat)
 }
 
+/// Maximum length of the custom instruction of a chat in characters. Also enforced by the
+/// database.
+pub const MAX_CHAT_CUSTOM_INSTRUCTION_CHARS: usize = 2000;
+
+/// Set the custom instruction of a chat, which is added to the prompt of its next generations.
+/// `None` removes a previously set instruction.
+pub async fn update_chat_custom_instruction(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    chat_id: &Uuid,
+    custom_instruction: Option<String>,
+) -> Result<chats::Model, Report> {
+    if let Some(custom_instruction) = &custom_instruction
+        && custom_instruction.chars().count() > MAX_CHAT_CUSTOM_INSTRUCTION_CHARS
+    {
+        return Err(eyre!(
+            "Custom instruction exceeds the maximum length of {} characters",
+            MAX_CHAT_CUSTOM_INSTRUCTION_CHARS
+        ));
+    }
+
+    let chat = Chats::find_by_id(*chat_id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| eyre!("Chat with ID {} not found", chat_id))?;
+
+    // Like the title, the instruction may be changed by everyone who can edit the chat
+    authorize!(
+        policy,
+        subject,
+        &Resource::Chat(chat.id.to_string()),
+        Action::Update
+    )?;
+
+    let mut chat_active: chats::ActiveModel = chat.into();
+    chat_active.custom_instruction = ActiveValue::Set(custom_instruction);
+    Ok(chat_active.update(conn).await?)
+}
+
+/// Set the language the assistant should respond in for the next generations of a chat, as an
+/// ISO 639-1 code like `de`. `None` removes a previously set language, so the preferred language
+/// of the user is used again.
+pub async fn update_chat_response_language(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    chat_id: &Uuid,
+    response_language: Option<String>,
+) -> Result<chats::Model, Report> {
+    let chat = Chats::find_by_id(*chat_id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| eyre!("Chat with ID {} not found", chat_id))?;
+
+    authorize!(
+        policy,
+        subject,
+        &Resource::Chat(chat.id.to_string()),
+        Action::Update
+    )?;
+
+    let mut chat_active: chats::ActiveModel = chat.into();
+    chat_active.response_language = ActiveValue::Set(response_language);
+    Ok(chat_active.update(conn).await?)
+}
+
+/// Replace the assistant snapshot of a chat with one of the current configuration of its
+/// assistant. Only the owner of the chat may refresh it.
+pub async fn refresh_assistant_snapshot(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    chat_id: &Uuid,
+) -> Result<chats::Model, Report> {
+    let chat = Chats::find_by_id(*chat_id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| eyre!("Chat with ID {} not found", chat_id))?;
+
+    authorize!(
+        policy,
+        subject,
+        &Resource::Chat(chat.id.to_string()),
+        Action::Update
+    )?;
+    if chat.owner_user_id != subject.user_id() {
+        return Err(eyre!(
+            "Access denied: only the owner can refresh the assistant snapshot"
+        ));
+    }
+
+    let config = parse_assistant_configuration(&chat)?
+        .ok_or_else(|| eyre!("Chat with ID {} has no assistant", chat_id))?;
+    let assistant = crate::models::assistant::get_assistant_with_files(
+        conn,
+        policy,
+        subject,
+        config.assistant_id,
+        false,
+    )
+    .await?;
+
+    let mut chat_active: chats::ActiveModel = chat.into();
+    chat_active.assistant_snapshot =
+        ActiveValue::Set(Some(AssistantSnapshot::of(&assistant).to_json()?));
+    Ok(chat_active.update(conn).await?)
+}
+
 /// Archive a chat by setting its archived_at timestamp
 pub async fn archive_chat(
     conn: &DatabaseConnection,
@@ -830,6 +1378,7 @@ pub async fn archive_all_unarchived_chats_for_owner(
     owner_user_id: &str,
 ) -> Result<u64, Report> {
     let now: DateTimeWithTimeZone = Utc::now().into();
+    let txn = conn.begin().await?;
     let result = Chats::update_many()
         .set(chats::ActiveModel {
             archived_at: ActiveValue::Set(Some(now)),
@@ -837,12 +1386,267 @@ pub async fn archive_all_unarchived_chats_for_owner(
         })
         .filter(chats::Column::OwnerUserId.eq(owner_user_id))
         .filter(chats::Column::ArchivedAt.is_null())
-        .exec(conn)
+        .exec(&txn)
         .await?;
+    if result.rows_affected > 0 {
+        record_policy_invalidation(&txn, PolicyInvalidationReason::ChatArchived).await?;
+    }
+    txn.commit().await?;
 
     Ok(result.rows_affected)
 }
 
+/// Result of the transfer of a chat to another owner
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ChatOwnershipTransfer {
+    pub chat_id: Uuid,
+    pub previous_owner_user_id: String,
+    pub new_owner_user_id: String,
+    /// Share grants of the chat to the new owner, which are removed as the owner can access the
+    /// chat anyway
+    pub removed_share_grant_ids: Vec<Uuid>,
+    /// Labels of the previous owner, which are removed from the chat
+    pub removed_label_ids: Vec<Uuid>,
+    /// Whether the chat was shared with the previous owner for reading
+    pub previous_owner_access_kept: bool,
+    /// Number of messages of the previous owner that are attributed to the new owner
+    pub rewritten_attribution_count: u64,
+}
+
+/// Transfer a chat to another user, e.g. when its owner leaves the organization.
+///
+/// The new owner has to belong to the organization of the chat, and `organization_id` restricts
+/// the lookup of the chat to an organization (`None` for platform admins and the CLI). Other
+/// share grants of the chat are kept, and the policy data of all instances is invalidated, so
+/// that only the new owner and the grantees can access the chat afterwards. The transfer is
+/// recorded with `actor_user_id`, see [`OwnershipTransferOptions`] for the other options. With
+/// `dry_run`, the changes are returned without applying them.
+pub async fn transfer_chat_ownership(
+    conn: &DatabaseConnection,
+    organization_id: Option<&str>,
+    chat_id: &Uuid,
+    new_owner_user_id: &Uuid,
+    actor_user_id: Option<&Uuid>,
+    options: &OwnershipTransferOptions,
+) -> Result<ChatOwnershipTransfer, Report> {
+    let txn = conn.begin().await?;
+    let transfer = transfer_chat_ownership_in_transaction(
+        &txn,
+        organization_id,
+        chat_id,
+        new_owner_user_id,
+        actor_user_id,
+        options,
+    )
+    .await?;
+    record_policy_invalidation(&txn, PolicyInvalidationReason::ChatTransferred).await?;
+    if options.dry_run {
+        txn.rollback().await?;
+    } else {
+        txn.commit().await?;
+    }
+
+    Ok(transfer)
+}
+
+/// Transfer a chat within the transaction of the caller, which records the invalidation of the
+/// policy data and commits the transaction.
+async fn transfer_chat_ownership_in_transaction<C: ConnectionTrait>(
+    txn: &C,
+    organization_id: Option<&str>,
+    chat_id: &Uuid,
+    new_owner_user_id: &Uuid,
+    actor_user_id: Option<&Uuid>,
+    options: &OwnershipTransferOptions,
+) -> Result<ChatOwnershipTransfer, Report> {
+    let mut query = Chats::find_by_id(*chat_id);
+    if let Some(organization_id) = organization_id {
+        query = query.filter(chats::Column::OrganizationId.eq(organization_id));
+    }
+    let chat = query
+        .lock_exclusive()
+        .one(txn)
+        .await?
+        .ok_or_else(|| eyre!("Chat with ID {} not found", chat_id))?;
+    let new_owner = find_new_owner(
+        txn,
+        new_owner_user_id,
+        "chat",
+        chat_id,
+        chat.organization_id.as_deref(),
+    )
+    .await?;
+    if chat.owner_user_id == new_owner.id.to_string() {
+        return Err(eyre!("Chat {} is already owned by user {}", chat_id, new_owner.id));
+    }
+
+    let removed_share_grant_ids = user_share_grant_ids(txn, "chat", chat_id, &new_owner.id).await?;
+    let previous_owner_label_ids: Vec<Uuid> = match Uuid::parse_str(&chat.owner_user_id) {
+        Ok(previous_owner_id) => Labels::find()
+            .filter(labels::Column::OwnerUserId.eq(previous_owner_id))
+            .all(txn)
+            .await?
+            .into_iter()
+            .map(|label| label.id)
+            .collect(),
+        Err(_) => vec![],
+    };
+    let removed_label_ids: Vec<Uuid> = ChatLabels::find()
+        .filter(chat_labels::Column::ChatId.eq(*chat_id))
+        .filter(chat_labels::Column::LabelId.is_in(previous_owner_label_ids))
+        .all(txn)
+        .await?
+        .into_iter()
+        .map(|chat_label| chat_label.label_id)
+        .collect();
+    let mut transfer = ChatOwnershipTransfer {
+        chat_id: *chat_id,
+        previous_owner_user_id: chat.owner_user_id.clone(),
+        new_owner_user_id: new_owner.id.to_string(),
+        removed_share_grant_ids,
+        removed_label_ids,
+        previous_owner_access_kept: options.keep_previous_owner_access,
+        rewritten_attribution_count: 0,
+    };
+
+    let organization_id = chat.organization_id.clone();
+    let mut chat_active: chats::ActiveModel = chat.into();
+    chat_active.owner_user_id = ActiveValue::Set(transfer.new_owner_user_id.clone());
+    chat_active.update(txn).await?;
+    if !transfer.removed_share_grant_ids.is_empty() {
+        ShareGrants::delete_many()
+            .filter(share_grants::Column::Id.is_in(transfer.removed_share_grant_ids.clone()))
+            .exec(txn)
+            .await?;
+    }
+    if !transfer.removed_label_ids.is_empty() {
+        ChatLabels::delete_many()
+            .filter(chat_labels::Column::ChatId.eq(*chat_id))
+            .filter(chat_labels::Column::LabelId.is_in(transfer.removed_label_ids.clone()))
+            .exec(txn)
+            .await?;
+    }
+    if options.keep_previous_owner_access {
+        grant_previous_owner_read_access(
+            txn,
+            "chat",
+            chat_id,
+            &transfer.previous_owner_user_id,
+            organization_id,
+        )
+        .await?;
+    }
+    if options.rewrite_attribution {
+        // Messages sent by grantees with edit access keep their attribution
+        let statement = named_statement_from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            POSTGRES_QUERY_REWRITE_MESSAGE_ATTRIBUTION,
+            r#"
+            UPDATE messages
+            SET raw_message = jsonb_set(raw_message, '{name}', to_jsonb($3::text))
+            WHERE chat_id = $1
+              AND raw_message->>'role' = 'user'
+              AND raw_message->>'name' = $2
+            "#,
+            vec![
+                (*chat_id).into(),
+                transfer.previous_owner_user_id.clone().into(),
+                transfer.new_owner_user_id.clone().into(),
+            ],
+        );
+        transfer.rewritten_attribution_count = txn.execute_raw(statement).await?.rows_affected();
+    }
+    record_ownership_transfer(
+        txn,
+        "chat",
+        chat_id,
+        &transfer.previous_owner_user_id,
+        &transfer.new_owner_user_id,
+        actor_user_id,
+        options,
+    )
+    .await?;
+
+    Ok(transfer)
+}
+
+/// Transfer all chats of a user to another user, oldest first, see [`transfer_chat_ownership`].
+///
+/// All chats are transferred in a single transaction, so if the transfer of a chat fails, none of
+/// the chats are transferred.
+pub async fn transfer_chats_of_owner(
+    conn: &DatabaseConnection,
+    organization_id: Option<&str>,
+    previous_owner_user_id: &Uuid,
+    new_owner_user_id: &Uuid,
+    actor_user_id: Option<&Uuid>,
+    options: &OwnershipTransferOptions,
+) -> Result<Vec<ChatOwnershipTransfer>, Report> {
+    if previous_owner_user_id == new_owner_user_id {
+        return Err(eyre!(
+            "Can't transfer the chats of user {} to the same user",
+            previous_owner_user_id
+        ));
+    }
+    let txn = conn.begin().await?;
+    let mut query = Chats::find()
+        .filter(chats::Column::OwnerUserId.eq(previous_owner_user_id.to_string()));
+    if let Some(organization_id) = organization_id {
+        query = query.filter(chats::Column::OrganizationId.eq(organization_id));
+    }
+    let chat_ids: Vec<Uuid> = query
+        .order_by_asc(chats::Column::CreatedAt)
+        .order_by_asc(chats::Column::Id)
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|chat| chat.id)
+        .collect();
+
+    let mut transfers = Vec::with_capacity(chat_ids.len());
+    for chat_id in chat_ids {
+        transfers.push(
+            transfer_chat_ownership_in_transaction(
+                &txn,
+                organization_id,
+                &chat_id,
+                new_owner_user_id,
+                actor_user_id,
+                options,
+            )
+            .await?,
+        );
+    }
+    if !transfers.is_empty() {
+        record_policy_invalidation(&txn, PolicyInvalidationReason::ChatTransferred).await?;
+    }
+    if options.dry_run {
+        txn.rollback().await?;
+    } else {
+        txn.commit().await?;
+    }
+
+    Ok(transfers)
+}
+
+/// All chats whose owner doesn't exist as a user, oldest first.
+pub async fn get_chats_of_missing_owners(
+    conn: &DatabaseConnection,
+) -> Result<Vec<chats::Model>, Report> {
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_CHATS_OF_MISSING_OWNERS,
+        r#"
+        SELECT c.*
+        FROM chats c
+        WHERE NOT EXISTS (SELECT 1 FROM users u WHERE u.id::text = c.owner_user_id)
+        ORDER BY c.created_at, c.id
+        "#,
+        vec![],
+    );
+    Ok(Chats::find().from_raw_sql(statement).all(conn).await?)
+}
+
 /// Get the chat provider ID from the most recent active message in a chat.
 /// Returns None if no active messages found or if the message doesn't have generation parameters.
 pub async fn get_last_chat_provider_id(
@@ -855,6 +1659,7 @@ pub async fn get_last_chat_provider_id(
         .filter(messages::Column::IsMessageInActiveThread.eq(true))
         .filter(messages::Column::GenerationParameters.is_not_null())
         .order_by_desc(messages::Column::CreatedAt)
+        .order_by_desc(messages::Column::Id)
         .one(conn)
         .await?;
 
@@ -871,7 +1676,9 @@ pub async fn get_last_chat_provider_id(
                 )
             })?;
 
-        return Ok(generation_params.generation_chat_provider_id);
+        return Ok(generation_params
+            .selected_chat_provider_id()
+            .map(String::from));
     }
 
     Ok(None)
@@ -890,16 +1697,39 @@ pub fn parse_assistant_configuration(
     }
 }
 
+/// Parse the assistant snapshot from a chat model
+///
+/// Returns None for chats without an assistant and chats created before snapshots were taken.
+pub fn parse_assistant_snapshot(chat: &chats::Model) -> Result<Option<AssistantSnapshot>, Report> {
+    chat.assistant_snapshot
+        .as_ref()
+        .map(AssistantSnapshot::from_json)
+        .transpose()
+}
+
+/// Whether the assistant of a chat is read from its snapshot instead of the live assistant.
+pub fn is_assistant_config_frozen(chat: &chats::Model, freeze_assistant_config: bool) -> bool {
+    freeze_assistant_config && chat.assistant_snapshot.is_some()
+}
+
 /// Get the assistant configuration for a chat if one is associated
 ///
 /// Returns the full assistant details with files if an assistant is configured for the chat.
-/// Returns None if no assistant is configured.
+/// Returns None if no assistant is configured. With `freeze_assistant_config`, the assistant is
+/// read from the snapshot of the chat, if it has one.
 pub async fn get_chat_assistant_configuration(
     conn: &DatabaseConnection,
     policy: &PolicyEngine,
     subject: &Subject,
     chat: &chats::Model,
+    freeze_assistant_config: bool,
 ) -> Result<Option<crate::models::assistant::AssistantWithFiles>, Report> {
+    if is_assistant_config_frozen(chat, freeze_assistant_config)
+        && let Some(snapshot) = parse_assistant_snapshot(chat)?
+    {
+        return Ok(Some(snapshot.into_assistant(conn).await?));
+    }
+
     // Parse assistant configuration from chat
     if let Some(config) = parse_assistant_configuration(chat)? {
         // Get the full assistant details including files
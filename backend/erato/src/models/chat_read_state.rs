@@ -0,0 +1,116 @@
+use crate::db::entity::chat_read_states;
+use crate::db::entity::prelude::*;
+use crate::metrics_constants::POSTGRES_QUERY_COUNT_UNREAD_CHAT_MESSAGES;
+use crate::models::message::get_message_by_id;
+use crate::policy::prelude::*;
+use crate::query_metrics::named_statement_from_sql_and_values;
+use eyre::{Report, eyre};
+use sea_orm::prelude::*;
+use sea_orm::{DatabaseConnection, EntityTrait, FromQueryResult, Set};
+
+/// SQL expression counting the unread assistant messages of `chats` for the read state joined as
+/// `read_state_msg` (the user's last read message).
+///
+/// Only assistant messages in the active thread count as unread; without a read state all of them
+/// do. Shared with the recent chats listing so both report the same number.
+pub(crate) const UNREAD_MESSAGE_COUNT_SQL: &str = r#"(
+            SELECT COUNT(*)
+            FROM messages um
+            WHERE um.chat_id = chats.id
+                AND um.is_message_in_active_thread = true
+                AND um.raw_message->>'role' = 'assistant'
+                AND (
+                    read_state_msg.created_at IS NULL
+                    OR um.created_at > read_state_msg.created_at
+                )
+        )"#;
+
+/// SQL joins providing `read_state` and `read_state_msg` for [`UNREAD_MESSAGE_COUNT_SQL`], for the
+/// user ID bound to the given parameter.
+pub(crate) fn read_state_joins_sql(user_id_param_index: u8) -> String {
+    format!(
+        r#"LEFT JOIN chat_read_states read_state
+            ON read_state.chat_id = chats.id
+            AND read_state.user_id = ${user_id_param_index}::uuid
+        LEFT JOIN messages read_state_msg
+            ON read_state_msg.id = read_state.last_read_message_id"#
+    )
+}
+
+/// Set the last message a user has read in a chat.
+///
+/// Any user that can read the chat may track their own read state, so shared chats have an
+/// independent read state per user. The message must belong to the chat.
+pub async fn set_chat_read_state(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    user_id: &Uuid,
+    chat_id: &Uuid,
+    last_read_message_id: &Uuid,
+) -> Result<chat_read_states::Model, Report> {
+    // Checks that the subject can read the chat of the message
+    let message = get_message_by_id(conn, policy, subject, last_read_message_id).await?;
+    if message.chat_id != *chat_id {
+        return Err(eyre!(
+            "Message with ID {} not found in chat {}",
+            last_read_message_id,
+            chat_id
+        ));
+    }
+
+    let existing = ChatReadStates::find_by_id((*user_id, *chat_id))
+        .one(conn)
+        .await?;
+
+    let read_state = if let Some(existing) = existing {
+        let mut active_read_state: chat_read_states::ActiveModel = existing.into();
+        active_read_state.last_read_message_id = Set(*last_read_message_id);
+        active_read_state.update(conn).await?
+    } else {
+        chat_read_states::ActiveModel {
+            user_id: Set(*user_id),
+            chat_id: Set(*chat_id),
+            last_read_message_id: Set(*last_read_message_id),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await?
+    };
+
+    Ok(read_state)
+}
+
+/// Count the assistant messages of a chat the user has not read yet.
+pub async fn count_unread_messages(
+    conn: &DatabaseConnection,
+    user_id: &Uuid,
+    chat_id: &Uuid,
+) -> Result<i64, Report> {
+    #[derive(Debug, FromQueryResult)]
+    struct UnreadCount {
+        unread_count: i64,
+    }
+
+    let sql = format!(
+        r#"
+        SELECT {UNREAD_MESSAGE_COUNT_SQL} AS "unread_count"
+        FROM "chats"
+        {}
+        WHERE "chats"."id" = $2
+        "#,
+        read_state_joins_sql(1)
+    );
+
+    let result = UnreadCount::find_by_statement(named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_COUNT_UNREAD_CHAT_MESSAGES,
+        sql,
+        vec![user_id.to_string().into(), (*chat_id).into()],
+    ))
+    .one(conn)
+    .await?
+    .ok_or_else(|| eyre!("Chat with ID {} not found", chat_id))?;
+
+    Ok(result.unread_count)
+}
@@ -1,3 +1,7 @@
+use crate::config::{AppConfig, InputModality};
+use crate::policy::prelude::*;
+use crate::state::AppState;
+use eyre::Report;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
@@ -17,6 +21,12 @@ pub struct FileCapability {
 
     /// Operations that can be performed on matching files
     pub operations: Vec<FileOperation>,
+
+    /// Sources matching files can be added from: `local` for uploads from the device of the user,
+    /// and the enabled link sources, e.g. `sharepoint`. Only set by the file capabilities
+    /// endpoint.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
 }
 
 /// Operations that can be performed on files
@@ -43,6 +53,7 @@ impl FileCapability {
             extensions,
             mime_types,
             operations,
+            sources: Vec::new(),
         }
     }
 
@@ -89,6 +100,94 @@ impl FileCapability {
 
         false
     }
+
+    /// Whether any model can use matching files, by extracting their text or analyzing them.
+    pub fn is_supported(&self) -> bool {
+        !self.operations.is_empty()
+    }
+
+    /// A human-readable reason why no model can use matching files, or `None` if they are
+    /// supported.
+    pub fn unsupported_reason(&self) -> Option<String> {
+        if self.is_supported() {
+            return None;
+        }
+        let reason = match input_modality_of_capability(self) {
+            _ if self.id == "other" => "Files of this type can't be processed",
+            InputModality::Image => "None of the available models can analyze images",
+            InputModality::Audio => "None of the available models accept audio files",
+            InputModality::Text => "None of the available models accept the text of files",
+        };
+        Some(reason.to_string())
+    }
+}
+
+/// Whom, and for which model, file capabilities are evaluated.
+#[derive(Debug, Clone, Copy)]
+pub struct FileCapabilityContext<'a> {
+    pub subject: &'a Subject,
+    /// Groups of the user, which determine the models available to them
+    pub user_groups: &'a [String],
+    /// Evaluate the capabilities for this model only, instead of all models available to the user
+    pub model_id: Option<&'a str>,
+}
+
+/// Evaluates the file capabilities in the given context.
+///
+/// Without a `model_id`, a file is supported if any of the models available to the user can use
+/// it. The caller has to check that the `model_id` is available to the user.
+pub async fn evaluate_file_capabilities(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    context: FileCapabilityContext<'_>,
+) -> Result<Vec<FileCapability>, Report> {
+    if let Some(model_id) = context.model_id {
+        return Ok(file_capabilities_for_chat_providers(
+            &app_state.config,
+            [model_id],
+        ));
+    }
+
+    let available_models = app_state
+        .available_models(policy, context.subject, context.user_groups)
+        .await?;
+    Ok(file_capabilities_for_chat_providers(
+        &app_state.config,
+        available_models
+            .iter()
+            .map(|model| model.chat_provider_id.as_str()),
+    ))
+}
+
+/// Builds the file capabilities of files that are used with any of the given chat providers,
+/// based on the attachment modalities they support
+/// (`model_capabilities.supported_input_modalities`).
+pub fn file_capabilities_for_chat_providers<'a>(
+    config: &AppConfig,
+    chat_provider_ids: impl IntoIterator<Item = &'a str>,
+) -> Vec<FileCapability> {
+    let (mut supports_text_input, mut supports_image_understanding, mut supports_audio_input) =
+        (false, false, false);
+    for chat_provider_id in chat_provider_ids {
+        let provider_config = config.get_chat_provider(chat_provider_id);
+        let capabilities = &provider_config.model_capabilities;
+        supports_text_input |= capabilities.supports_input_modality(InputModality::Text);
+        supports_image_understanding |= capabilities.supports_input_modality(InputModality::Image);
+        supports_audio_input |= capabilities.supports_input_modality(InputModality::Audio);
+    }
+
+    let mut capabilities =
+        get_file_capabilities(supports_image_understanding, supports_audio_input);
+    // Models that don't accept text attachments can't use the extracted text of documents either
+    if !supports_text_input {
+        for capability in capabilities
+            .iter_mut()
+            .filter(|capability| input_modality_of_capability(capability) == InputModality::Text)
+        {
+            capability.operations.clear();
+        }
+    }
+    capabilities
 }
 
 /// Builds the list of available file capabilities based on the file processor and model capabilities
@@ -292,6 +391,23 @@ pub fn find_file_capability_by_filename(
         })
 }
 
+/// The modality the files of a capability are provided to the model as
+pub fn input_modality_of_capability(capability: &FileCapability) -> InputModality {
+    match capability.id.as_str() {
+        "image" => InputModality::Image,
+        "audio" => InputModality::Audio,
+        _ => InputModality::Text,
+    }
+}
+
+/// The modality a file is provided to the model as, based on its filename
+pub fn input_modality_for_filename(filename: &str) -> InputModality {
+    input_modality_of_capability(&find_file_capability_by_filename(
+        &get_file_capabilities(true, true),
+        filename,
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -395,4 +511,46 @@ mod tests {
         assert_eq!(cap.id, "pdf");
         assert_ne!(cap.id, "other");
     }
+
+    #[test]
+    fn test_unsupported_reason() {
+        let caps = get_file_capabilities(false, true);
+
+        let cap = find_file_capability_by_filename(&caps, "report.pdf");
+        assert!(cap.is_supported());
+        assert_eq!(cap.unsupported_reason(), None);
+
+        let cap = find_file_capability_by_filename(&caps, "photo.png");
+        assert!(!cap.is_supported());
+        assert_eq!(
+            cap.unsupported_reason().as_deref(),
+            Some("None of the available models can analyze images")
+        );
+
+        let cap = find_file_capability_by_filename(&caps, "archive.zip");
+        assert_eq!(
+            cap.unsupported_reason().as_deref(),
+            Some("Files of this type can't be processed")
+        );
+    }
+
+    #[test]
+    fn test_input_modality_for_filename() {
+        assert_eq!(
+            input_modality_for_filename("photo.PNG"),
+            InputModality::Image
+        );
+        assert_eq!(
+            input_modality_for_filename("memo.m4a"),
+            InputModality::Audio
+        );
+        assert_eq!(
+            input_modality_for_filename("report.pdf"),
+            InputModality::Text
+        );
+        assert_eq!(
+            input_modality_for_filename("archive.zip"),
+            InputModality::Text
+        );
+    }
 }
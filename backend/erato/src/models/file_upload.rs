@@ -1,10 +1,21 @@
 use crate::db::entity::prelude::*;
-use crate::db::entity::{chat_file_uploads, file_uploads};
+use crate::db::entity::{chat_file_uploads, file_deletions, file_uploads};
+use crate::metrics_constants::{
+    POSTGRES_QUERY_FILE_UPLOAD_REFERENCES, POSTGRES_QUERY_UNREFERENCED_FILE_UPLOADS,
+};
+use crate::models::organization_condition;
 use crate::policy::prelude::*;
-use crate::services::file_storage::{FileStorage, SHAREPOINT_PROVIDER_ID, SharepointContext};
+use crate::query_metrics::named_statement_from_sql_and_values;
+use crate::services::file_storage::{
+    FileStorage, SHAREPOINT_PROVIDER_ID, SharepointContext, is_missing_permissions_error,
+};
+use crate::services::policy_invalidations::{PolicyInvalidationReason, record_policy_invalidation};
 use eyre::{ContextCompat, OptionExt, Report, WrapErr};
 use sea_orm::prelude::*;
-use sea_orm::{ActiveValue, DatabaseConnection, JoinType, QuerySelect};
+use sea_orm::{
+    ActiveValue, DatabaseConnection, FromQueryResult, JoinType, QueryOrder, QuerySelect,
+    TransactionTrait,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::to_string;
 use sqlx::types::Uuid;
@@ -16,6 +27,10 @@ pub fn proxied_preview_url_for_file(file_id: &Uuid) -> String {
     format!("/api/v1beta/files/{file_id}/preview")
 }
 
+pub fn api_url_for_file(file_id: &Uuid) -> String {
+    format!("/api/v1beta/files/{file_id}")
+}
+
 fn subject_user_id(subject: &Subject) -> String {
     subject.user_id().to_string()
 }
@@ -235,6 +250,234 @@ pub fn get_audio_transcript_if_ready(file_upload: &file_uploads::Model) -> Optio
         .and_then(|metadata| metadata.aggregate_transcript())
 }
 
+/// Whether the object of a file upload is available in its storage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStorageStatus {
+    #[default]
+    Ok,
+    /// The object was not found in the storage when it was last read.
+    Missing,
+    /// The owner deleted the contents of the file, while it was still referenced.
+    Deleted,
+}
+
+impl FileStorageStatus {
+    /// Value as stored in the `storage_status` column.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileStorageStatus::Ok => "ok",
+            FileStorageStatus::Missing => "missing",
+            FileStorageStatus::Deleted => "deleted",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ok" => Some(FileStorageStatus::Ok),
+            "missing" => Some(FileStorageStatus::Missing),
+            "deleted" => Some(FileStorageStatus::Deleted),
+            _ => None,
+        }
+    }
+
+    /// The storage status of a file upload row.
+    pub fn of(file_upload: &file_uploads::Model) -> Self {
+        Self::parse(&file_upload.storage_status).unwrap_or_default()
+    }
+}
+
+/// A file whose contents could not be included in a generation, because its object is missing in
+/// the storage or its contents were deleted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UnavailableFile {
+    /// The ID of the file upload
+    pub id: Uuid,
+    /// The original filename of the file
+    pub filename: String,
+}
+
+/// Record whether the object of a file upload is available in its storage.
+///
+/// Returns whether the status changed.
+pub async fn set_file_upload_storage_status<C: ConnectionTrait>(
+    conn: &C,
+    file_upload_id: &Uuid,
+    status: FileStorageStatus,
+) -> Result<bool, Report> {
+    let result = FileUploads::update_many()
+        .col_expr(
+            file_uploads::Column::StorageStatus,
+            Expr::value(status.as_str()),
+        )
+        .filter(file_uploads::Column::Id.eq(*file_upload_id))
+        .filter(file_uploads::Column::StorageStatus.ne(status.as_str()))
+        .exec(conn)
+        .await
+        .wrap_err("Failed to update storage status of file upload")?;
+
+    Ok(result.rows_affected > 0)
+}
+
+/// The kind of a resource that references a file upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileUploadReferenceKind {
+    /// A message the file was attached to
+    Message,
+    /// A message whose stored generation inputs include the file
+    GenerationInput,
+    /// An assistant the file is attached to
+    Assistant,
+}
+
+/// A resource that references a file upload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct FileUploadReference {
+    pub kind: FileUploadReferenceKind,
+    /// The ID of the message or assistant
+    pub id: Uuid,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct FileUploadReferenceRow {
+    kind: String,
+    id: Uuid,
+}
+
+/// All resources that reference a file upload, regardless of whether the subject can read them.
+///
+/// A message that has the file attached is only listed once, even though its generation inputs
+/// include the file as well.
+pub async fn get_file_upload_references(
+    conn: &DatabaseConnection,
+    file_upload_id: &Uuid,
+) -> Result<Vec<FileUploadReference>, Report> {
+    let sql = r#"
+        SELECT 'message' AS kind, id
+        FROM messages
+        WHERE $1 = ANY(input_file_uploads)
+        UNION ALL
+        SELECT 'generation_input' AS kind, id
+        FROM messages
+        WHERE strpos(generation_input_messages::text, $2) > 0
+          AND NOT ($1 = ANY(COALESCE(input_file_uploads, '{}')))
+        UNION ALL
+        SELECT 'assistant' AS kind, assistant_id AS id
+        FROM assistant_file_uploads
+        WHERE file_upload_id = $1
+        ORDER BY kind, id
+    "#;
+    let rows = FileUploadReferenceRow::find_by_statement(named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_FILE_UPLOAD_REFERENCES,
+        sql,
+        [(*file_upload_id).into(), file_upload_id.to_string().into()],
+    ))
+    .all(conn)
+    .await
+    .wrap_err("Failed to get references of file upload")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| FileUploadReference {
+            kind: match row.kind.as_str() {
+                "message" => FileUploadReferenceKind::Message,
+                "generation_input" => FileUploadReferenceKind::GenerationInput,
+                _ => FileUploadReferenceKind::Assistant,
+            },
+            id: row.id,
+        })
+        .collect())
+}
+
+/// Delete a file upload on behalf of `actor_user_id`, and record the deletion in
+/// `file_deletions`.
+///
+/// A file that is still referenced keeps its row for the references, but is flagged as deleted
+/// and loses its audio transcription. Otherwise, the row is removed. Removing the object from the
+/// storage is up to the caller.
+pub async fn delete_file_upload(
+    conn: &DatabaseConnection,
+    actor_user_id: &Uuid,
+    file_upload: &file_uploads::Model,
+    references: &[FileUploadReference],
+) -> Result<(), Report> {
+    let txn = conn.begin().await?;
+    if references.is_empty() {
+        FileUploads::delete_by_id(file_upload.id).exec(&txn).await?;
+    } else {
+        file_uploads::ActiveModel {
+            id: ActiveValue::Set(file_upload.id),
+            storage_status: ActiveValue::Set(FileStorageStatus::Deleted.as_str().to_string()),
+            audio_transcription: ActiveValue::Set(None),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await?;
+    }
+    file_deletions::ActiveModel {
+        file_upload_id: ActiveValue::Set(file_upload.id),
+        filename: ActiveValue::Set(file_upload.filename.clone()),
+        actor_user_id: ActiveValue::Set(Some(*actor_user_id)),
+        forced: ActiveValue::Set(!references.is_empty()),
+        referencing_resources: ActiveValue::Set(serde_json::to_value(references)?),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await
+    .wrap_err("Failed to record file deletion")?;
+    txn.commit().await?;
+
+    Ok(())
+}
+
+/// All file uploads whose object is missing in the storage, most recently uploaded first.
+///
+/// If `organization_id` is given, only the file uploads of that organization are returned.
+pub async fn get_missing_file_uploads(
+    conn: &DatabaseConnection,
+    organization_id: Option<&str>,
+) -> Result<Vec<file_uploads::Model>, Report> {
+    let mut query = FileUploads::find()
+        .filter(file_uploads::Column::StorageStatus.eq(FileStorageStatus::Missing.as_str()));
+    if let Some(organization_id) = organization_id {
+        query = query.filter(file_uploads::Column::OrganizationId.eq(organization_id));
+    }
+    query
+        .order_by_desc(file_uploads::Column::CreatedAt)
+        .all(conn)
+        .await
+        .wrap_err("Failed to get missing file uploads")
+}
+
+/// All file uploads that are neither linked to a chat or an assistant, nor attached to a message,
+/// oldest first.
+///
+/// Such files are left behind e.g. by uploads whose chat was never created.
+pub async fn get_unreferenced_file_uploads(
+    conn: &DatabaseConnection,
+) -> Result<Vec<file_uploads::Model>, Report> {
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_UNREFERENCED_FILE_UPLOADS,
+        r#"
+        SELECT f.*
+        FROM file_uploads f
+        WHERE NOT EXISTS (SELECT 1 FROM chat_file_uploads c WHERE c.file_upload_id = f.id)
+          AND NOT EXISTS (SELECT 1 FROM assistant_file_uploads a WHERE a.file_upload_id = f.id)
+          AND NOT EXISTS (SELECT 1 FROM messages m WHERE f.id = ANY(m.input_file_uploads))
+        ORDER BY f.created_at, f.id
+        "#,
+        vec![],
+    );
+    FileUploads::find()
+        .from_raw_sql(statement)
+        .all(conn)
+        .await
+        .wrap_err("Failed to get unreferenced file uploads")
+}
+
 /// Lookup capability used by code paths that need a file_upload row but should not be
 /// coupled to `AppState`/`DatabaseConnection` directly — namely so they can be unit-tested
 /// against an in-memory stub. Implemented for `DatabaseConnection` below; production callers
@@ -282,11 +525,13 @@ pub async fn create_file_upload(
         filename: ActiveValue::Set(filename),
         file_storage_provider_id: ActiveValue::Set(file_storage_provider_id),
         file_storage_path: ActiveValue::Set(file_storage_path),
+        organization_id: ActiveValue::Set(subject.organization_id().map(str::to_string)),
         ..Default::default()
     };
 
+    let txn = conn.begin().await?;
     let created_file_upload = file_uploads::Entity::insert(new_file_upload)
-        .exec_with_returning(conn)
+        .exec_with_returning(&txn)
         .await?;
 
     // Create the relation in the join table
@@ -297,8 +542,10 @@ pub async fn create_file_upload(
     };
 
     chat_file_uploads::Entity::insert(new_chat_file_upload)
-        .exec(conn)
+        .exec(&txn)
         .await?;
+    record_policy_invalidation(&txn, PolicyInvalidationReason::FileLinked).await?;
+    txn.commit().await?;
 
     Ok(created_file_upload)
 }
@@ -340,11 +587,13 @@ pub async fn create_sharepoint_file_upload(
         filename: ActiveValue::Set(filename),
         file_storage_provider_id: ActiveValue::Set(SHAREPOINT_PROVIDER_ID.to_string()),
         file_storage_path: ActiveValue::Set(file_storage_path),
+        organization_id: ActiveValue::Set(subject.organization_id().map(str::to_string)),
         ..Default::default()
     };
 
+    let txn = conn.begin().await?;
     let created_file_upload = file_uploads::Entity::insert(new_file_upload)
-        .exec_with_returning(conn)
+        .exec_with_returning(&txn)
         .await?;
 
     // If chat_id provided, create the relation in the join table
@@ -356,9 +605,11 @@ pub async fn create_sharepoint_file_upload(
         };
 
         chat_file_uploads::Entity::insert(new_chat_file_upload)
-            .exec(conn)
+            .exec(&txn)
             .await?;
+        record_policy_invalidation(&txn, PolicyInvalidationReason::FileLinked).await?;
     }
+    txn.commit().await?;
 
     Ok(created_file_upload)
 }
@@ -401,6 +652,10 @@ pub async fn get_file_upload_by_id(
 ) -> Result<file_uploads::Model, Report> {
     // Find the file upload
     let file_upload = FileUploads::find_by_id(*file_upload_id)
+        .filter(organization_condition(
+            file_uploads::Column::OrganizationId,
+            subject,
+        ))
         .one(conn)
         .await?
         .wrap_err("File upload not found")?;
@@ -428,101 +683,159 @@ pub struct FileUploadWithUrl {
     pub file_storage_provider_id: String,
     pub file_storage_path: String,
     pub download_url: String,
+    pub url_kind: FileUrlKind,
     pub preview_url: Option<String>,
     pub file_contents_unavailable_missing_permissions: bool,
     pub audio_transcription: Option<AudioTranscriptionMetadata>,
+    pub storage_status: FileStorageStatus,
 }
 
-/// Get a specific file upload by ID, including a pre-signed download URL.
+/// How clients can use the download URL of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileUrlKind {
+    /// A pre-signed storage URL or a short-lived MS Graph download URL, which can be fetched
+    /// without further authentication
+    Direct,
+    /// The API route of the file, which has to be requested with the credentials of the user
+    RequiresAuthProxy,
+}
+
+/// The download and preview URLs of a file, resolved for the current user
+#[derive(Debug, Clone)]
+pub struct ResolvedFileUrls {
+    pub download_url: String,
+    pub url_kind: FileUrlKind,
+    pub preview_url: Option<String>,
+    pub file_contents_unavailable_missing_permissions: bool,
+}
+
+impl ResolvedFileUrls {
+    fn requires_auth_proxy(
+        file_id: &Uuid,
+        preview_url: Option<String>,
+        file_contents_unavailable_missing_permissions: bool,
+    ) -> Self {
+        Self {
+            download_url: api_url_for_file(file_id),
+            url_kind: FileUrlKind::RequiresAuthProxy,
+            preview_url,
+            file_contents_unavailable_missing_permissions,
+        }
+    }
+}
+
+/// Resolve the download and preview URLs of a file for the current user.
 ///
-/// For Sharepoint files, the access token is used to generate the download URL.
-pub async fn get_file_upload_with_url_and_token(
-    conn: &DatabaseConnection,
-    policy: &PolicyEngine,
-    subject: &Subject,
-    file_upload_id: &Uuid,
+/// Files in an OpenDAL storage get a pre-signed storage URL, and Sharepoint files get a
+/// short-lived MS Graph download URL, which requires the access token of the user. If neither
+/// can be generated, the API route of the file is returned as [`FileUrlKind::RequiresAuthProxy`].
+pub async fn resolve_file_urls(
+    file_id: &Uuid,
+    filename: &str,
+    file_storage_provider_id: &str,
+    file_storage_path: &str,
     file_storage_providers: &HashMap<String, FileStorage>,
     access_token: Option<&str>,
-) -> Result<FileUploadWithUrl, Report> {
-    // Find the file upload
-    let file_upload = get_file_upload_by_id(conn, policy, subject, file_upload_id).await?;
-
-    // Build the context for Sharepoint (will be ignored by other providers)
-    let sharepoint_ctx = access_token.map(|token| SharepointContext {
-        access_token: token,
-    });
-
-    // Get the file storage provider
+) -> Result<ResolvedFileUrls, Report> {
     let file_storage = file_storage_providers
-        .get(&file_upload.file_storage_provider_id)
+        .get(file_storage_provider_id)
         .ok_or_eyre(format!(
             "File storage provider not found: {}",
-            file_upload.file_storage_provider_id
+            file_storage_provider_id
         ))?;
 
-    let file_storage_path = file_upload.file_storage_path.clone();
-    let file_storage_provider_id = file_upload.file_storage_provider_id.clone();
-    let filename = file_upload.filename.clone();
-    let audio_transcription = get_audio_transcription_metadata(&file_upload);
-
-    let (download_url, preview_url, file_contents_unavailable_missing_permissions) = if file_storage
-        .is_sharepoint()
-    {
-        match file_storage
-            .get_sharepoint_file_metadata_with_context(&file_storage_path, sharepoint_ctx.as_ref())
-            .await
-        {
-            Ok(metadata) => (
-                metadata.download_url,
-                Some(format!("/api/v1beta/files/{}/preview", file_upload.id)),
-                false,
-            ),
-            Err(err) => {
-                tracing::warn!(
-                    file_id = %file_upload.id,
-                    provider = %file_storage_provider_id,
-                    error = %err,
-                    "Failed to generate Sharepoint file metadata, returning placeholder"
-                );
-                (format!("/api/v1beta/files/{}", file_upload.id), None, true)
-            }
-        }
-    } else {
-        let download_url = match file_storage
-            .generate_presigned_download_url_with_context(
-                &file_storage_path,
-                None,
-                Some(&filename),
-                sharepoint_ctx.as_ref(),
-            )
+    if file_storage.is_sharepoint() {
+        let Some(access_token) = access_token else {
+            return Ok(ResolvedFileUrls::requires_auth_proxy(file_id, None, false));
+        };
+        let sharepoint_ctx = SharepointContext { access_token };
+        return match file_storage
+            .get_sharepoint_file_metadata_with_context(file_storage_path, Some(&sharepoint_ctx))
             .await
         {
-            Ok(url) => url,
+            Ok(metadata) => Ok(ResolvedFileUrls {
+                download_url: metadata.download_url,
+                url_kind: FileUrlKind::Direct,
+                preview_url: Some(proxied_preview_url_for_file(file_id)),
+                file_contents_unavailable_missing_permissions: false,
+            }),
             Err(err) => {
                 tracing::warn!(
-                    file_id = %file_upload.id,
+                    file_id = %file_id,
                     provider = %file_storage_provider_id,
                     error = %err,
-                    "Failed to generate download URL, returning placeholder"
+                    "Failed to generate Sharepoint file metadata, returning the API route"
                 );
-                format!("/api/v1beta/files/{}", file_upload.id)
+                Ok(ResolvedFileUrls::requires_auth_proxy(file_id, None, true))
             }
         };
+    }
 
-        let preview_url = Some(proxied_preview_url_for_file(&file_upload.id));
+    match file_storage
+        .generate_presigned_download_url(file_storage_path, None, Some(filename))
+        .await
+    {
+        Ok(download_url) => Ok(ResolvedFileUrls {
+            download_url,
+            url_kind: FileUrlKind::Direct,
+            preview_url: Some(proxied_preview_url_for_file(file_id)),
+            file_contents_unavailable_missing_permissions: false,
+        }),
+        Err(err) => {
+            tracing::warn!(
+                file_id = %file_id,
+                provider = %file_storage_provider_id,
+                error = %err,
+                "Failed to generate download URL, returning the API route"
+            );
+            let missing_permissions = is_missing_permissions_error(&err);
+            let preview_url = (!missing_permissions).then(|| proxied_preview_url_for_file(file_id));
+            Ok(ResolvedFileUrls::requires_auth_proxy(
+                file_id,
+                preview_url,
+                missing_permissions,
+            ))
+        }
+    }
+}
 
-        (download_url, preview_url, false)
-    };
+/// Get a specific file upload by ID, including a pre-signed download URL.
+///
+/// For Sharepoint files, the access token is used to generate the download URL.
+pub async fn get_file_upload_with_url_and_token(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    file_upload_id: &Uuid,
+    file_storage_providers: &HashMap<String, FileStorage>,
+    access_token: Option<&str>,
+) -> Result<FileUploadWithUrl, Report> {
+    // Find the file upload
+    let file_upload = get_file_upload_by_id(conn, policy, subject, file_upload_id).await?;
+
+    let urls = resolve_file_urls(
+        &file_upload.id,
+        &file_upload.filename,
+        &file_upload.file_storage_provider_id,
+        &file_upload.file_storage_path,
+        file_storage_providers,
+        access_token,
+    )
+    .await?;
 
     Ok(FileUploadWithUrl {
         id: file_upload.id,
-        filename,
-        file_storage_provider_id,
-        file_storage_path,
-        download_url,
-        preview_url,
-        file_contents_unavailable_missing_permissions,
-        audio_transcription,
+        audio_transcription: get_audio_transcription_metadata(&file_upload),
+        storage_status: FileStorageStatus::of(&file_upload),
+        filename: file_upload.filename,
+        file_storage_provider_id: file_upload.file_storage_provider_id,
+        file_storage_path: file_upload.file_storage_path,
+        download_url: urls.download_url,
+        url_kind: urls.url_kind,
+        preview_url: urls.preview_url,
+        file_contents_unavailable_missing_permissions: urls
+            .file_contents_unavailable_missing_permissions,
     })
 }
 
@@ -563,88 +876,32 @@ pub async fn get_chat_file_uploads_with_urls_and_token(
     // Get all file uploads for the chat
     let file_uploads = get_chat_file_uploads(conn, policy, subject, chat_id).await?;
 
-    // Build the context for Sharepoint (will be ignored by other providers)
-    let sharepoint_ctx = access_token.map(|token| SharepointContext {
-        access_token: token,
-    });
-
-    // For each file upload, generate a pre-signed download URL
+    // For each file upload, resolve its download URL
     let mut result = Vec::with_capacity(file_uploads.len());
 
     for upload in file_uploads {
-        let file_storage_path = upload.file_storage_path.clone();
-        let filename = upload.filename.clone();
-        let file_storage_provider_id = upload.file_storage_provider_id.clone();
-        let audio_transcription = get_audio_transcription_metadata(&upload);
-
-        // Get the file storage provider
-        let file_storage = file_storage_providers
-            .get(&file_storage_provider_id)
-            .ok_or_eyre(format!(
-                "File storage provider not found: {}",
-                file_storage_provider_id
-            ))?;
-
-        let (download_url, preview_url, file_contents_unavailable_missing_permissions) =
-            if file_storage.is_sharepoint() {
-                match file_storage
-                    .get_sharepoint_file_metadata_with_context(
-                        &file_storage_path,
-                        sharepoint_ctx.as_ref(),
-                    )
-                    .await
-                {
-                    Ok(metadata) => (
-                        metadata.download_url,
-                        Some(format!("/api/v1beta/files/{}/preview", upload.id)),
-                        false,
-                    ),
-                    Err(err) => {
-                        tracing::warn!(
-                            file_id = %upload.id,
-                            provider = %file_storage_provider_id,
-                            error = %err,
-                            "Failed to generate Sharepoint file metadata, returning placeholder"
-                        );
-                        (format!("/api/v1beta/files/{}", upload.id), None, true)
-                    }
-                }
-            } else {
-                let download_url = match file_storage
-                    .generate_presigned_download_url_with_context(
-                        &file_storage_path,
-                        None,
-                        Some(&filename),
-                        sharepoint_ctx.as_ref(),
-                    )
-                    .await
-                {
-                    Ok(url) => url,
-                    Err(err) => {
-                        tracing::warn!(
-                            file_id = %upload.id,
-                            provider = %file_storage_provider_id,
-                            error = %err,
-                            "Failed to generate download URL, returning placeholder"
-                        );
-                        format!("/api/v1beta/files/{}", upload.id)
-                    }
-                };
-
-                let preview_url = Some(proxied_preview_url_for_file(&upload.id));
-
-                (download_url, preview_url, false)
-            };
+        let urls = resolve_file_urls(
+            &upload.id,
+            &upload.filename,
+            &upload.file_storage_provider_id,
+            &upload.file_storage_path,
+            file_storage_providers,
+            access_token,
+        )
+        .await?;
 
         result.push(FileUploadWithUrl {
             id: upload.id,
-            filename,
-            file_storage_provider_id,
-            file_storage_path,
-            download_url,
-            preview_url,
-            file_contents_unavailable_missing_permissions,
-            audio_transcription,
+            audio_transcription: get_audio_transcription_metadata(&upload),
+            storage_status: FileStorageStatus::of(&upload),
+            filename: upload.filename,
+            file_storage_provider_id: upload.file_storage_provider_id,
+            file_storage_path: upload.file_storage_path,
+            download_url: urls.download_url,
+            url_kind: urls.url_kind,
+            preview_url: urls.preview_url,
+            file_contents_unavailable_missing_permissions: urls
+                .file_contents_unavailable_missing_permissions,
         });
     }
 
@@ -0,0 +1,326 @@
+use crate::db::entity::prelude::*;
+use crate::db::entity::{chat_labels, labels};
+use crate::policy::prelude::*;
+use eyre::{Report, eyre};
+use sea_orm::prelude::*;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ConnectionTrait, DatabaseConnection, EntityTrait,
+    IntoActiveModel, QueryFilter, QueryOrder, QuerySelect, TransactionTrait,
+};
+use std::collections::HashMap;
+
+/// Maximum number of labels a single user can own.
+pub const MAX_LABELS_PER_USER: u64 = 50;
+/// Maximum number of labels that can be assigned to a single chat.
+pub const MAX_LABELS_PER_CHAT: u64 = 10;
+
+/// Fields of a label that can be changed after creation.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateLabelInput {
+    pub name: Option<String>,
+    pub color: Option<Option<String>>,
+}
+
+/// List all labels owned by a user, ordered by name.
+pub async fn list_labels(
+    conn: &DatabaseConnection,
+    owner_user_id: &Uuid,
+) -> Result<Vec<labels::Model>, Report> {
+    Ok(Labels::find()
+        .filter(labels::Column::OwnerUserId.eq(*owner_user_id))
+        .order_by_asc(labels::Column::Name)
+        .all(conn)
+        .await?)
+}
+
+/// Get a label by ID, making sure it is owned by the given user.
+///
+/// Labels of other users are reported as not found, so their existence is not leaked.
+pub async fn get_owned_label(
+    conn: &DatabaseConnection,
+    owner_user_id: &Uuid,
+    label_id: &Uuid,
+) -> Result<labels::Model, Report> {
+    Labels::find_by_id(*label_id)
+        .filter(labels::Column::OwnerUserId.eq(*owner_user_id))
+        .one(conn)
+        .await?
+        .ok_or_else(|| eyre!("Label with ID {} not found", label_id))
+}
+
+/// Create a new label for a user.
+pub async fn create_label(
+    conn: &DatabaseConnection,
+    owner_user_id: &Uuid,
+    name: String,
+    color: Option<String>,
+) -> Result<labels::Model, Report> {
+    let name = normalize_name(&name)?;
+    let color = normalize_color(color)?;
+
+    // Locking the owner serializes concurrent creations, so they can't exceed the limit together
+    let txn = conn.begin().await?;
+    Users::find_by_id(*owner_user_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or_else(|| eyre!("User with ID {} not found", owner_user_id))?;
+
+    let existing_count = Labels::find()
+        .filter(labels::Column::OwnerUserId.eq(*owner_user_id))
+        .count(&txn)
+        .await?;
+    if existing_count >= MAX_LABELS_PER_USER {
+        return Err(eyre!(
+            "Label limit reached: a user can have at most {} labels",
+            MAX_LABELS_PER_USER
+        ));
+    }
+    ensure_name_available(&txn, owner_user_id, &name, None).await?;
+
+    let label = labels::ActiveModel {
+        owner_user_id: ActiveValue::Set(*owner_user_id),
+        name: ActiveValue::Set(name),
+        color: ActiveValue::Set(color),
+        ..Default::default()
+    };
+    let label = Labels::insert(label).exec_with_returning(&txn).await?;
+
+    txn.commit().await?;
+    Ok(label)
+}
+
+/// Update the name and/or color of a label owned by the user.
+pub async fn update_label(
+    conn: &DatabaseConnection,
+    owner_user_id: &Uuid,
+    label_id: &Uuid,
+    input: UpdateLabelInput,
+) -> Result<labels::Model, Report> {
+    let label = get_owned_label(conn, owner_user_id, label_id).await?;
+    let mut model = label.into_active_model();
+
+    if let Some(name) = input.name {
+        let name = normalize_name(&name)?;
+        ensure_name_available(conn, owner_user_id, &name, Some(label_id)).await?;
+        model.name = ActiveValue::Set(name);
+    }
+    if let Some(color) = input.color {
+        model.color = ActiveValue::Set(normalize_color(color)?);
+    }
+
+    Ok(model.update(conn).await?)
+}
+
+/// Delete a label owned by the user. Its chat assignments are removed by the
+/// database via `ON DELETE CASCADE`.
+pub async fn delete_label(
+    conn: &DatabaseConnection,
+    owner_user_id: &Uuid,
+    label_id: &Uuid,
+) -> Result<(), Report> {
+    let label = get_owned_label(conn, owner_user_id, label_id).await?;
+    Labels::delete_by_id(label.id).exec(conn).await?;
+    Ok(())
+}
+
+/// Assign a label to a chat.
+///
+/// The subject needs update permission on the chat and must own the label.
+/// Assigning a label that is already assigned is a no-op.
+pub async fn add_label_to_chat(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    chat_id: &Uuid,
+    label_id: &Uuid,
+) -> Result<(), Report> {
+    authorize_chat_update(conn, policy, subject, chat_id).await?;
+    let owner_user_id = Uuid::parse_str(subject.user_id())?;
+    let label = get_owned_label(conn, &owner_user_id, label_id).await?;
+
+    // Locking the chat serializes concurrent assignments, so they can't exceed the limit together
+    let txn = conn.begin().await?;
+    Chats::find_by_id(*chat_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or_else(|| eyre!("Chat with ID {} not found", chat_id))?;
+
+    let already_assigned = ChatLabels::find_by_id((*chat_id, label.id))
+        .one(&txn)
+        .await?
+        .is_some();
+    if already_assigned {
+        return Ok(());
+    }
+
+    let assigned_count = ChatLabels::find()
+        .filter(chat_labels::Column::ChatId.eq(*chat_id))
+        .count(&txn)
+        .await?;
+    if assigned_count >= MAX_LABELS_PER_CHAT {
+        return Err(eyre!(
+            "Label limit reached: a chat can have at most {} labels",
+            MAX_LABELS_PER_CHAT
+        ));
+    }
+
+    let chat_label = chat_labels::ActiveModel {
+        chat_id: ActiveValue::Set(*chat_id),
+        label_id: ActiveValue::Set(label.id),
+        ..Default::default()
+    };
+    ChatLabels::insert(chat_label)
+        .exec_without_returning(&txn)
+        .await?;
+
+    txn.commit().await?;
+    Ok(())
+}
+
+/// Remove a label from a chat.
+///
+/// Removing a label that is not assigned is a no-op.
+pub async fn remove_label_from_chat(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    chat_id: &Uuid,
+    label_id: &Uuid,
+) -> Result<(), Report> {
+    authorize_chat_update(conn, policy, subject, chat_id).await?;
+    let owner_user_id = Uuid::parse_str(subject.user_id())?;
+    let label = get_owned_label(conn, &owner_user_id, label_id).await?;
+
+    ChatLabels::delete_by_id((*chat_id, label.id))
+        .exec(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Get the labels assigned to each of the given chats, ordered by name.
+pub async fn get_labels_for_chats(
+    conn: &DatabaseConnection,
+    chat_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<labels::Model>>, Report> {
+    if chat_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let rows = ChatLabels::find()
+        .filter(chat_labels::Column::ChatId.is_in(chat_ids.to_vec()))
+        .find_also_related(Labels)
+        .order_by_asc(labels::Column::Name)
+        .all(conn)
+        .await?;
+
+    let mut labels_by_chat: HashMap<Uuid, Vec<labels::Model>> = HashMap::new();
+    for (chat_label, label) in rows {
+        if let Some(label) = label {
+            labels_by_chat
+                .entry(chat_label.chat_id)
+                .or_default()
+                .push(label);
+        }
+    }
+
+    Ok(labels_by_chat)
+}
+
+async fn authorize_chat_update(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    chat_id: &Uuid,
+) -> Result<(), Report> {
+    let chat = Chats::find_by_id(*chat_id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| eyre!("Chat with ID {} not found", chat_id))?;
+
+    authorize!(
+        policy,
+        subject,
+        &Resource::Chat(chat.id.to_string()),
+        Action::Update
+    )?;
+
+    Ok(())
+}
+
+async fn ensure_name_available<C: ConnectionTrait>(
+    conn: &C,
+    owner_user_id: &Uuid,
+    name: &str,
+    exclude_label_id: Option<&Uuid>,
+) -> Result<(), Report> {
+    let mut query = Labels::find()
+        .filter(labels::Column::OwnerUserId.eq(*owner_user_id))
+        .filter(labels::Column::Name.eq(name));
+    if let Some(exclude_label_id) = exclude_label_id {
+        query = query.filter(labels::Column::Id.ne(*exclude_label_id));
+    }
+
+    if query.one(conn).await?.is_some() {
+        return Err(eyre!("Label with name '{}' already exists", name));
+    }
+    Ok(())
+}
+
+fn normalize_name(name: &str) -> Result<String, Report> {
+    let trimmed = name.trim();
+    if trimmed.is_empty() {
+        return Err(eyre!("Invalid label name: must not be empty"));
+    }
+    Ok(trimmed.to_string())
+}
+
+/// Colors are optional `#rrggbb` hex strings; empty values clear the color.
+fn normalize_color(color: Option<String>) -> Result<Option<String>, Report> {
+    let Some(color) = color
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+    else {
+        return Ok(None);
+    };
+    let is_hex_color = color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+    if !is_hex_color {
+        return Err(eyre!(
+            "Invalid label color '{}': expected a hex color like #1a2b3c",
+            color
+        ));
+    }
+    Ok(Some(color.to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_color_accepts_hex_colors() {
+        assert_eq!(
+            normalize_color(Some("#A1b2C3".to_string())).unwrap(),
+            Some("#a1b2c3".to_string())
+        );
+        assert_eq!(normalize_color(Some("  ".to_string())).unwrap(), None);
+        assert_eq!(normalize_color(None).unwrap(), None);
+    }
+
+    #[test]
+    fn normalize_color_rejects_other_values() {
+        assert!(normalize_color(Some("red".to_string())).is_err());
+        assert!(normalize_color(Some("#12345".to_string())).is_err());
+        assert!(normalize_color(Some("#12345g".to_string())).is_err());
+    }
+
+    #[test]
+    fn normalize_name_trims_and_rejects_empty() {
+        assert_eq!(normalize_name("  Work ").unwrap(), "Work");
+        assert!(normalize_name("   ").is_err());
+    }
+}
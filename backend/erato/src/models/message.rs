@@ -1,14 +1,30 @@
-use crate::db::entity::messages;
+use crate::config::{AppConfig, OutputComplianceAction};
+use crate::db::entity::chats;
 use crate::db::entity::prelude::*;
-use crate::models::file_upload::proxied_preview_url_for_file;
+use crate::db::entity_ext::messages;
+use crate::metrics_constants::{
+    POSTGRES_QUERY_CROSS_CHAT_MESSAGE_LINKS, POSTGRES_QUERY_DISTINCT_GENERATION_CHAT_PROVIDER_IDS,
+    POSTGRES_QUERY_FINALIZE_MESSAGE_CONTENT_DRAFTS, POSTGRES_QUERY_SAVE_MESSAGE_CONTENT_DRAFT,
+};
+use crate::models::file_upload::{proxied_preview_url_for_file, resolve_file_urls};
+use crate::models::message_thread_integrity::{
+    ThreadIntegrityReport, ThreadRepairAction, ThreadRepairChange, analyze_thread_integrity,
+    plan_thread_repair,
+};
 use crate::models::pagination;
 use crate::policy::prelude::*;
+use crate::query_metrics::named_statement_from_sql_and_values;
 use crate::server::api::v1beta::message_streaming::FileContentsForGeneration;
-use eyre::{Report, eyre};
+use crate::services::file_pointer_migration::check_no_inline_file_contents;
+use crate::services::prompt_composition::manifest::PromptManifest;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL_SAFE};
+use chrono::Utc;
+use eyre::{Report, WrapErr, eyre};
 use genai::chat::ReasoningItem;
 use sea_orm::prelude::*;
 use sea_orm::{
-    ActiveValue, DatabaseConnection, EntityTrait, QueryOrder, QuerySelect, TransactionTrait,
+    ActiveValue, Condition, ConnectionTrait, DatabaseConnection, EntityTrait, Order, QueryOrder,
+    QuerySelect, TransactionTrait,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{Value as JsonValue, to_value};
@@ -22,6 +38,18 @@ pub struct GenerationParameters {
     /// The chat provider ID that was used to generate the message
     #[serde(skip_serializing_if = "Option::is_none")]
     pub generation_chat_provider_id: Option<String>,
+    /// The chat provider ID that was requested, if it was an alias (see `chat_provider_aliases`)
+    /// that resolved to `generation_chat_provider_id`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested_chat_provider_id: Option<String>,
+    /// The chat provider ID that was requested, if it is no longer configured and the
+    /// generation fell back to `generation_chat_provider_id`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_from_chat_provider_id: Option<String>,
+    /// The chat provider group (see `chat_provider_groups`) that was selected, if
+    /// `generation_chat_provider_id` served the generation as one of its members.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat_provider_group_id: Option<String>,
     /// Request-scoped context for this generation.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub request_context: Option<GenerationRequestContext>,
@@ -34,6 +62,19 @@ pub struct GenerationParameters {
     /// The arguments of the action facet used for this generation, if any.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub action_facet_args: Option<HashMap<String, String>>,
+    /// The language the assistant was asked to respond in, and why.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_language: Option<ResponseLanguage>,
+}
+
+impl GenerationParameters {
+    /// The chat provider that was selected for the generation, which is the chat provider group
+    /// if the generation was served by one of its members.
+    pub fn selected_chat_provider_id(&self) -> Option<&str> {
+        self.chat_provider_group_id
+            .as_deref()
+            .or(self.generation_chat_provider_id.as_deref())
+    }
 }
 
 /// User-provided input context stored on user messages.
@@ -42,7 +83,7 @@ pub struct GenerationParameters {
 /// such as action facet payloads (e.g., selected text from Outlook compose).
 /// Semantically distinct from `GenerationParameters` which records how the
 /// assistant response was produced.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct InputParameters {
     /// The action facet ID supplied with this message, if any.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -50,6 +91,47 @@ pub struct InputParameters {
     /// The action facet arguments supplied with this message, if any.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub action_facet_args: Option<HashMap<String, String>>,
+    /// The language the message text was detected to be written in, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detected_language: Option<DetectedLanguage>,
+    /// Text displayed in place of the empty text of a message that only carries files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub placeholder_text: Option<String>,
+}
+
+/// A language detected in the text of a user message.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DetectedLanguage {
+    /// ISO 639-1 code of the detected language (e.g. `de`).
+    pub code: String,
+    /// Confidence of the detection, between 0.0 and 1.0.
+    pub confidence: f64,
+}
+
+/// Where the response language of a generation was taken from.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseLanguageSource {
+    /// Explicitly requested with the generation request.
+    RequestOverride,
+    /// Detected from the text of the latest user message.
+    DetectedMessageLanguage,
+    /// Configured on the chat.
+    ChatSetting,
+    /// The preferred language of the user's profile.
+    ProfilePreference,
+}
+
+/// The language the assistant was asked to respond in.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResponseLanguage {
+    /// ISO 639-1 code of the response language (e.g. `de`).
+    pub code: String,
+    /// Where the language was taken from.
+    pub source: ResponseLanguageSource,
+    /// Detection confidence, if the language was detected from the message text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
 }
 
 /// Request-scoped context captured for a generation request.
@@ -73,12 +155,17 @@ pub enum GenerationErrorType {
         #[serde(skip_serializing_if = "Option::is_none")]
         filter_details: Option<JsonValue>,
     },
-    /// Rate limit was exceeded.
-    #[allow(dead_code)]
+    /// Rate limit of the model provider was exceeded.
     #[serde(rename = "rate_limit")]
-    RateLimit {
+    RateLimited {
         /// Description of the rate limit error.
         error_description: String,
+        /// Number of seconds after which the provider accepts requests again, if it reported one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        retry_after_seconds: Option<u64>,
+        /// Which limit was exceeded, if the provider reported it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scope: Option<RateLimitScope>,
     },
     /// Model or provider is unavailable.
     #[allow(dead_code)]
@@ -109,6 +196,30 @@ pub enum GenerationErrorType {
         /// Description of why generation was aborted.
         error_description: String,
     },
+    /// The user message was blocked by the content moderation before it was passed to the model.
+    #[serde(rename = "moderation_blocked")]
+    ModerationBlocked {
+        /// Description of why the message was blocked.
+        error_description: String,
+        /// The moderation categories the message was flagged for.
+        categories: Vec<String>,
+    },
+    /// Generation was aborted because the answer matched an output compliance rule.
+    #[serde(rename = "compliance_policy")]
+    CompliancePolicy {
+        /// Description of why generation was aborted.
+        error_description: String,
+        /// ID of the rule the answer matched.
+        matched_rule_id: String,
+    },
+    /// Generation was aborted because an output transformer with `fail_mode = "closed"` failed.
+    #[serde(rename = "transformer_failed")]
+    TransformerFailed {
+        /// Description of why generation was aborted.
+        error_description: String,
+        /// Name of the transformer that failed.
+        transformer: String,
+    },
     /// Internal server error.
     #[serde(rename = "internal_error")]
     InternalError {
@@ -117,8 +228,18 @@ pub enum GenerationErrorType {
     },
 }
 
+/// The limit of a model provider that was exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitScope {
+    /// The limit on the number of requests
+    Requests,
+    /// The limit on the number of tokens
+    Tokens,
+}
+
 /// Metadata about the generation process, including usage statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GenerationMetadata {
     /// Number of prompt tokens used during generation
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -132,6 +253,12 @@ pub struct GenerationMetadata {
     /// Number of reasoning tokens used during generation (e.g., for o1 models)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub used_reasoning_tokens: Option<u32>,
+    /// Number of prompt tokens that were read from the prompt cache of the provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub used_cache_read_tokens: Option<u32>,
+    /// Number of prompt tokens that were written to the prompt cache of the provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub used_cache_write_tokens: Option<u32>,
     /// Reasoning summary emitted by the model, persisted separately from assistant text.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reasoning_summary: Option<String>,
@@ -144,6 +271,10 @@ pub struct GenerationMetadata {
     /// Langfuse trace ID for this generation (if Langfuse tracing was enabled)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub langfuse_trace_id: Option<String>,
+    /// OpenTelemetry trace ID of the generation (if OTel was enabled), matching the `trace_id` of
+    /// the `assistant_message_started` and `assistant_message_completed` events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub otel_trace_id: Option<String>,
     /// Whether this generation was stopped before natural completion
     #[serde(skip_serializing_if = "Option::is_none")]
     pub was_aborted: Option<bool>,
@@ -153,6 +284,281 @@ pub struct GenerationMetadata {
     /// MCP server IDs that were unavailable while preparing this generation.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mcp_servers_unavailable: Option<Vec<String>>,
+    /// Estimated prompt tokens per part of the prompt.
+    /// Not present on messages generated before the breakdown was recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_breakdown: Option<TokenBreakdown>,
+    /// The prompts the generation was composed of.
+    /// Not present on messages generated before the manifest was recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_manifest: Option<PromptManifest>,
+    /// Mermaid diagrams and math blocks found in the text of the generated message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub renderable_blocks: Option<Vec<RenderableBlock>>,
+    /// Possible prompt injections found in file contents and tool outputs used for the generation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_injection_warnings: Option<Vec<PromptInjectionWarning>>,
+    /// Matches of the output compliance rules in the generated text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_compliance_matches: Option<Vec<OutputComplianceMatch>>,
+    /// The output transformers (see `chat.output_transformers`) applied to the generated content.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_transformations: Option<Vec<OutputTransformation>>,
+    /// Why the generation ended.
+    /// Not present on messages generated before the stop reason was recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<StopReason>,
+    /// Milliseconds from dispatching the request of the final model turn until its first streamed
+    /// text chunk. Earlier turns that ended in tool calls and the execution of those tool calls
+    /// are excluded. Not present if no text was streamed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_to_first_token_ms: Option<u64>,
+    /// Milliseconds from the start of the generation until its completion, including all
+    /// tool-call turns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation_duration_ms: Option<u64>,
+    /// Result of the content moderation of a user message.
+    /// Only present on user messages that were moderated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moderation: Option<ModerationResult>,
+    /// ID of the capture of the traffic to the chat provider (see
+    /// `debug.capture_provider_traffic`). Removed once the capture has expired.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_capture_id: Option<String>,
+    /// Whether the generation was replayed from the generation cache (see
+    /// `chat.generation_cache`) instead of being requested from the chat provider.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_hit: Option<bool>,
+    /// Whether the markdown of the generated text was changed by the normalization (see
+    /// `chat.normalize_markdown`). Not present if the normalization was disabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub markdown_normalized: Option<bool>,
+    /// The tools that were offered to the model.
+    /// Not present on messages generated before the offered tools were recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offered_tools: Option<OfferedTools>,
+    /// The options the chat provider was called with.
+    /// Not present on messages generated before the chat options were recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat_options: Option<GenerationChatOptions>,
+}
+
+/// Maximum number of offered tools that are recorded for a generation. Tools beyond it are only
+/// counted.
+pub const MAX_RECORDED_OFFERED_TOOLS: usize = 200;
+
+/// A tool that was offered to the model for a generation.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct OfferedTool {
+    /// ID of the MCP server that provides the tool. Not present for tools that are handled by the
+    /// backend or the client, like client tools.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub server_id: Option<String>,
+    /// Name of the tool, as offered to the model.
+    pub name: String,
+}
+
+/// The tools that were offered to the model for a generation.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct OfferedTools {
+    /// Number of offered tools, including the ones that were cut off from `tools`.
+    pub count: usize,
+    /// The offered tools, in the order they were offered. At most `MAX_RECORDED_OFFERED_TOOLS`
+    /// are recorded.
+    pub tools: Vec<OfferedTool>,
+    /// Whether `tools` was cut off, because more tools were offered than are recorded.
+    pub truncated: bool,
+}
+
+impl OfferedTools {
+    pub fn new(mut tools: Vec<OfferedTool>) -> Self {
+        let count = tools.len();
+        let truncated = count > MAX_RECORDED_OFFERED_TOOLS;
+        tools.truncate(MAX_RECORDED_OFFERED_TOOLS);
+        Self {
+            count,
+            tools,
+            truncated,
+        }
+    }
+}
+
+/// The options the chat provider was called with for a generation.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct GenerationChatOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub max_tokens: Option<u32>,
+    /// The reasoning effort, as named by the chat provider client (e.g. `High`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub reasoning_effort: Option<String>,
+    /// The verbosity, as named by the chat provider client (e.g. `Low`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub verbosity: Option<String>,
+}
+
+impl GenerationChatOptions {
+    pub fn from_genai(chat_options: &genai::chat::ChatOptions) -> Self {
+        Self {
+            temperature: chat_options.temperature,
+            top_p: chat_options.top_p,
+            max_tokens: chat_options.max_tokens,
+            reasoning_effort: chat_options
+                .reasoning_effort
+                .as_ref()
+                .map(|effort| format!("{effort:?}")),
+            verbosity: chat_options
+                .verbosity
+                .as_ref()
+                .map(|verbosity| format!("{verbosity:?}")),
+        }
+    }
+}
+
+/// Result of the content moderation of a user message (see `moderation` in the config).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct ModerationResult {
+    /// Whether the message was flagged in one of the moderated categories.
+    pub flagged: bool,
+    /// The moderated categories the message was flagged for.
+    pub categories: Vec<String>,
+    /// Whether the message was blocked from being passed to the model.
+    pub blocked: bool,
+}
+
+/// Why the generation of an assistant message ended.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// The model finished its response naturally.
+    Completed,
+    /// The response was cut off because the maximum number of output tokens was reached.
+    /// The generation can be continued.
+    MaxTokens,
+    /// The response was cut off by a content filter of the provider.
+    ContentFilter,
+    /// The model stopped to wait for the result of a tool call.
+    ToolCalls,
+    /// The generation was aborted by the user.
+    Cancelled,
+    /// The generation failed.
+    Error,
+}
+
+impl StopReason {
+    /// Map the stop reason reported by the provider to a stop reason.
+    pub fn from_genai(stop_reason: &genai::chat::StopReason) -> Self {
+        match stop_reason {
+            genai::chat::StopReason::MaxTokens(_) => StopReason::MaxTokens,
+            genai::chat::StopReason::ContentFilter(_) => StopReason::ContentFilter,
+            genai::chat::StopReason::ToolCall(_) => StopReason::ToolCalls,
+            // Stop sequences and unknown provider reasons are treated as a natural end
+            _ => StopReason::Completed,
+        }
+    }
+}
+
+/// Kind of a block that the frontend renders specially.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderableBlockType {
+    /// A fenced `mermaid` code block.
+    Mermaid,
+    /// A fenced `math`/`latex` code block, or display math delimited by `$$` or `\[ \]`.
+    Math,
+}
+
+/// A block in the text of a message that the frontend renders specially.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct RenderableBlock {
+    #[serde(rename = "type")]
+    pub block_type: RenderableBlockType,
+    /// Index of the text content part that contains the block.
+    pub content_index: usize,
+    /// Byte offset in the text at which the block, including its delimiters, starts.
+    pub start: usize,
+    /// Byte offset in the text at which the block ends (exclusive).
+    pub end: usize,
+    /// Whether the block passed validation. Invalid blocks should be rendered as plain code.
+    pub valid: bool,
+    /// Why the block failed validation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub error: Option<String>,
+}
+
+/// Where untrusted content that was passed to the model came from.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UntrustedContentSource {
+    /// The contents of an attached file.
+    File,
+    /// The output of an MCP tool call.
+    ToolOutput,
+}
+
+/// A match of one of the configured `guardrails.output_compliance` rules in a generated answer.
+/// The matched text is not recorded.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OutputComplianceMatch {
+    /// ID of the rule that matched.
+    pub rule_id: String,
+    /// What was done about the match.
+    pub action: OutputComplianceAction,
+}
+
+/// An output transformer (see `chat.output_transformers`) that was applied to a generated answer.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OutputTransformation {
+    /// Name of the transformer.
+    pub transformer: String,
+    /// Number of bytes of the content that were changed by the transformer.
+    pub changed_bytes: usize,
+}
+
+/// A match of one of the configured `security.injection_patterns` in untrusted content.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct PromptInjectionWarning {
+    pub source: UntrustedContentSource,
+    /// ID of the file the content was read from, for `file` sources.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub file_id: Option<Uuid>,
+    /// ID of the tool call that produced the content, for `tool_output` sources.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub tool_call_id: Option<String>,
+    /// ID of the pattern that matched.
+    pub pattern_id: String,
+    /// The first text that matched the pattern.
+    pub matched_text: String,
+    /// Whether the matches were quoted before the content was passed to the model.
+    pub neutralized: bool,
+}
+
+/// Estimated number of prompt tokens per part of the prompt of a generation.
+///
+/// Counted with the `o200k_base` tokenizer on the prompt of the first generation turn,
+/// so the sum may differ from the prompt tokens reported by the provider.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenBreakdown {
+    /// Tokens of the system prompt, assistant prompt and facet prompts
+    pub system_prompt_tokens: u32,
+    /// Tokens of previous messages in the chat
+    pub chat_history_tokens: u32,
+    /// Tokens of file contents, both of attached files and assistant files
+    pub file_content_tokens: u32,
+    /// Tokens of the user message the generation responds to
+    pub user_message_tokens: u32,
 }
 
 /// Role of the message author (as defined by the LLM providers)
@@ -218,6 +624,11 @@ pub enum ContentPart {
     /// of *prior-turn* history, the resolver drops it; for the *current
     /// turn* it renders the template against the current config + args.
     ActionFacetMarker(ContentPartActionFacetMarker),
+    /// Content part that was moved to the file storage because the message was too large to be
+    /// stored inline (see `chat.content_spillover`). Only the beginning of its content is kept
+    /// as a preview; the full part is served by `GET /messages/{message_id}/content/{index}`
+    /// and resolved like a file pointer when generating.
+    BlobPointer(ContentPartBlobPointer),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
@@ -277,6 +688,16 @@ pub struct ContentPartActionFacetMarker {
     pub args: HashMap<String, String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct ContentPartBlobPointer {
+    /// Path of the blob in the default file storage, which holds the serialized content part
+    pub blob_path: String,
+    /// Size of the blob in bytes
+    pub size: u64,
+    /// The beginning of the text of the content part
+    pub preview: String,
+}
+
 /// Statistics for a list of messages
 #[derive(Debug, Clone)]
 pub struct MessageListStats {
@@ -288,6 +709,123 @@ pub struct MessageListStats {
     pub returned_count: usize,
     /// Whether there are more messages available
     pub has_more: bool,
+    /// Cursor for the page following this one, if there is one
+    pub next_cursor: Option<String>,
+    /// Cursor for the page preceding this one, if there is one
+    pub prev_cursor: Option<String>,
+}
+
+/// Order in which the messages of a chat are listed, by creation time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl MessageOrder {
+    fn reversed(self) -> Self {
+        match self {
+            MessageOrder::Asc => MessageOrder::Desc,
+            MessageOrder::Desc => MessageOrder::Asc,
+        }
+    }
+}
+
+impl From<MessageOrder> for Order {
+    fn from(order: MessageOrder) -> Self {
+        match order {
+            MessageOrder::Asc => Order::Asc,
+            MessageOrder::Desc => Order::Desc,
+        }
+    }
+}
+
+impl std::str::FromStr for MessageOrder {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asc" => Ok(MessageOrder::Asc),
+            "desc" => Ok(MessageOrder::Desc),
+            other => Err(eyre!("Invalid message order '{}'", other)),
+        }
+    }
+}
+
+/// Keyset position of a message within a chat's `(created_at, id)` ordering.
+///
+/// Cursors are handed to clients as opaque strings via [`MessageCursor::encode`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageCursor {
+    /// Creation time of the message the cursor points at
+    pub created_at: DateTimeWithTimeZone,
+    /// ID of the message the cursor points at, used as a tie-breaker
+    pub id: Uuid,
+    /// Whether the cursor walks towards the start of the listing (a `prev_cursor`)
+    #[serde(default)]
+    pub backward: bool,
+}
+
+impl MessageCursor {
+    fn for_message(message: &messages::Model, backward: bool) -> Self {
+        Self {
+            created_at: messages::api_timestamp(message.created_at_utc()),
+            id: message.id,
+            backward,
+        }
+    }
+
+    /// Encode the cursor as an opaque, URL-safe string.
+    pub fn encode(&self) -> Result<String, Report> {
+        let json = serde_json::to_vec(self).wrap_err("Failed to serialize message cursor")?;
+        Ok(BASE64_URL_SAFE.encode(json))
+    }
+
+    /// Decode a cursor previously produced by [`MessageCursor::encode`].
+    pub fn decode(encoded: &str) -> Result<Self, Report> {
+        let json = BASE64_URL_SAFE
+            .decode(encoded)
+            .wrap_err("Invalid message cursor encoding")?;
+        serde_json::from_slice(&json).wrap_err("Invalid message cursor")
+    }
+
+    /// Condition matching the messages strictly after the cursor when scanning in `order`.
+    fn keyset_condition(&self, order: MessageOrder) -> Condition {
+        match order {
+            MessageOrder::Asc => Condition::any()
+                .add(messages::Column::CreatedAt.gt(self.created_at))
+                .add(
+                    Condition::all()
+                        .add(messages::Column::CreatedAt.eq(self.created_at))
+                        .add(messages::Column::Id.gt(self.id)),
+                ),
+            MessageOrder::Desc => Condition::any()
+                .add(messages::Column::CreatedAt.lt(self.created_at))
+                .add(
+                    Condition::all()
+                        .add(messages::Column::CreatedAt.eq(self.created_at))
+                        .add(messages::Column::Id.lt(self.id)),
+                ),
+        }
+    }
+}
+
+/// Options for listing the messages of a chat.
+#[derive(Debug, Clone, Default)]
+pub struct GetChatMessagesOptions {
+    /// Maximum number of messages to return (defaults to 100)
+    pub limit: Option<u64>,
+    /// Number of messages to skip; ignored when a `cursor` is provided
+    pub offset: Option<u64>,
+    /// Order in which the messages are paged through
+    pub order: MessageOrder,
+    /// Order of the messages within the returned page (defaults to `order`). Lets clients that
+    /// page from the newest messages backwards still render each page oldest first.
+    pub page_order: Option<MessageOrder>,
+    /// Keyset cursor to continue from, as returned in a previous page
+    pub cursor: Option<MessageCursor>,
 }
 
 /// Schema for validating message structure
@@ -327,6 +865,21 @@ impl MessageSchema {
         serde_json::to_value(self).map_err(|e| eyre!("Failed to serialize message: {}", e))
     }
 
+    /// The beginning of the text of the message, including the previews of spilled parts.
+    pub fn text_preview(&self, max_chars: usize) -> String {
+        let text = self
+            .content
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text(text) => Some(text.text.as_str()),
+                ContentPart::BlobPointer(pointer) => Some(pointer.preview.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<&str>>()
+            .join(" ");
+        text.chars().take(max_chars).collect()
+    }
+
     pub fn full_text(&self) -> String {
         self.content
             .iter()
@@ -340,6 +893,8 @@ impl MessageSchema {
                 // Markers are placeholders for the action-facet resolver and
                 // do not contribute to a message's full text representation.
                 ContentPart::ActionFacetMarker(_) => None,
+                // Spilled content is only available from the file storage.
+                ContentPart::BlobPointer(_) => None,
             })
             .collect::<Vec<&str>>()
             .join(" ")
@@ -358,6 +913,14 @@ impl From<&messages::Model> for Resource {
 /// and the order_index of the new message will be set to the previous message's order_index + 1.
 ///
 /// If `previous_message_id` is not specified, the order_index will be set to 0.
+///
+/// Messages with `is_welcome_message` set are shown in the chat, but never sent to the LLM.
+///
+/// `edit_of_message_id` is the message the new message is an edited version of, which is only
+/// set for edits of user messages, unlike `sibling_message_id`.
+///
+/// If the chat is provisional, it stops being provisional in the same transaction that inserts
+/// the message.
 #[allow(clippy::too_many_arguments)]
 pub async fn submit_message(
     conn: &DatabaseConnection,
@@ -367,14 +930,19 @@ pub async fn submit_message(
     raw_message: JsonValue,
     previous_message_id: Option<&Uuid>,
     sibling_message_id: Option<&Uuid>,
+    edit_of_message_id: Option<&Uuid>,
     generation_input_messages: Option<GenerationInputMessages>,
     input_files_ids: &[Uuid],
     generation_parameters: Option<GenerationParameters>,
     generation_metadata: Option<GenerationMetadata>,
     input_parameters: Option<InputParameters>,
+    is_welcome_message: bool,
 ) -> Result<messages::Model, Report> {
     // Validate the message format
     MessageSchema::validate(&raw_message)?;
+    if let Some(generation_input_messages) = &generation_input_messages {
+        check_no_inline_file_contents(conn, generation_input_messages).await?;
+    }
     let generation_input_messages: Option<JsonValue> =
         generation_input_messages.map(to_value).transpose()?;
     let generation_parameters_json: Option<JsonValue> =
@@ -474,6 +1042,7 @@ pub async fn submit_message(
         raw_message: ActiveValue::Set(raw_message),
         previous_message_id: ActiveValue::Set(previous_message_id.copied()),
         sibling_message_id: ActiveValue::Set(sibling_message_id.copied()),
+        edit_of_message_id: ActiveValue::Set(edit_of_message_id.copied()),
         is_message_in_active_thread: ActiveValue::Set(true), // New messages are active by default
         generation_input_messages: ActiveValue::Set(generation_input_messages),
         input_file_uploads: ActiveValue::Set(if input_files_ids.is_empty() {
@@ -484,6 +1053,7 @@ pub async fn submit_message(
         generation_parameters: ActiveValue::Set(generation_parameters_json),
         generation_metadata: ActiveValue::Set(generation_metadata_json),
         input_parameters: ActiveValue::Set(input_parameters_json),
+        is_welcome_message: ActiveValue::Set(is_welcome_message),
         ..Default::default()
     };
 
@@ -509,6 +1079,20 @@ pub async fn submit_message(
             .map_err(|e| eyre!("Failed to reactivate target active thread messages: {}", e))?;
     }
 
+    // Step 5: A provisional chat becomes durable with its first message.
+    let durable_chat_update = chats::ActiveModel {
+        provisional: ActiveValue::Set(false),
+        ..Default::default()
+    };
+
+    Chats::update_many()
+        .set(durable_chat_update)
+        .filter(chats::Column::Id.eq(*chat_id))
+        .filter(chats::Column::Provisional.eq(true))
+        .exec(&txn)
+        .await
+        .map_err(|e| eyre!("Failed to mark chat as no longer provisional: {}", e))?;
+
     // Commit the transaction
     txn.commit()
         .await
@@ -517,22 +1101,97 @@ pub async fn submit_message(
     Ok(created_message)
 }
 
+/// Build a message of an ephemeral chat, which is only held in memory and never saved.
+///
+/// Unlike [`submit_message`], the chat is not looked up, so it's up to the caller to check that
+/// the user owns the chat and that the previous message belongs to it.
+#[allow(clippy::too_many_arguments)]
+pub fn new_ephemeral_message(
+    chat_id: &Uuid,
+    raw_message: JsonValue,
+    previous_message_id: Option<&Uuid>,
+    generation_input_messages: Option<GenerationInputMessages>,
+    input_files_ids: &[Uuid],
+    generation_parameters: Option<GenerationParameters>,
+    generation_metadata: Option<GenerationMetadata>,
+    input_parameters: Option<InputParameters>,
+) -> Result<messages::Model, Report> {
+    MessageSchema::validate(&raw_message)?;
+    let now = Utc::now().fixed_offset();
+    Ok(messages::Model {
+        id: Uuid::new_v4(),
+        chat_id: *chat_id,
+        raw_message,
+        created_at: now,
+        updated_at: now,
+        previous_message_id: previous_message_id.copied(),
+        sibling_message_id: None,
+        is_message_in_active_thread: true,
+        generation_input_messages: generation_input_messages.map(to_value).transpose()?,
+        input_file_uploads: if input_files_ids.is_empty() {
+            None
+        } else {
+            Some(input_files_ids.to_vec())
+        },
+        generation_parameters: generation_parameters.map(to_value).transpose()?,
+        generation_metadata: generation_metadata.map(to_value).transpose()?,
+        input_parameters: input_parameters.map(to_value).transpose()?,
+        is_welcome_message: false,
+        content_draft: None,
+        edit_of_message_id: None,
+    })
+}
+
+/// Submit the welcome message of an assistant as the first message of a chat.
+pub async fn submit_welcome_message(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    chat_id: &Uuid,
+    welcome_message: &str,
+) -> Result<messages::Model, Report> {
+    let raw_message = serde_json::json!({
+        "role": "assistant",
+        "content": [{
+            "content_type": "text",
+            "text": welcome_message,
+        }],
+    });
+
+    submit_message(
+        conn,
+        policy,
+        subject,
+        chat_id,
+        raw_message,
+        None,
+        None,
+        None,
+        None,
+        &[],
+        None,
+        None,
+        None,
+        true,
+    )
+    .await
+}
+
 /// Get messages for a chat with pagination support.
 ///
 /// This function retrieves messages for a given chat ID, after checking that
-/// the subject has read permission for the chat. It supports pagination with
-/// limit and offset parameters.
+/// the subject has read permission for the chat. It supports both limit/offset
+/// pagination and keyset pagination over `(created_at, id)` via a cursor.
 ///
 /// Returns a tuple of (messages, stats) where:
-/// - messages: Vec<messages::Model> - The list of messages
+/// - messages: Vec<messages::Model> - The list of messages, in the requested page order
 /// - stats: MessageListStats - Statistics about the message list
 pub async fn get_chat_messages(
     conn: &DatabaseConnection,
     policy: &PolicyEngine,
     subject: &Subject,
     chat_id: &Uuid,
-    limit: Option<u64>,
-    offset: Option<u64>,
+    options: GetChatMessagesOptions,
 ) -> Result<(Vec<messages::Model>, MessageListStats), Report> {
     // Authorize that the subject can read this chat
     authorize!(
@@ -543,36 +1202,119 @@ pub async fn get_chat_messages(
     )?;
 
     // Set default pagination values
-    let limit = limit.unwrap_or(100);
-    let offset = offset.unwrap_or(0);
+    let limit = options.limit.unwrap_or(100);
+    let order = options.order;
+    // Cursors are computed in paging order, so the page is only flipped at the very end.
+    let flip_page = options
+        .page_order
+        .is_some_and(|page_order| page_order != order);
+
+    let Some(cursor) = options.cursor else {
+        let offset = options.offset.unwrap_or(0);
+
+        // Query messages for this chat with pagination, ordered by creation time
+        let mut messages = Messages::find()
+            .filter(messages::Column::ChatId.eq(*chat_id))
+            .order_by(messages::Column::CreatedAt, order.into())
+            .order_by(messages::Column::Id, order.into())
+            .limit(limit)
+            .offset(offset)
+            .all(conn)
+            .await?;
+
+        // Use our pagination utility to efficiently calculate the total count
+        let (total_count, has_more) =
+            pagination::calculate_total_count(offset, limit, messages.len(), || async {
+                Messages::find()
+                    .filter(messages::Column::ChatId.eq(*chat_id))
+                    .count(conn)
+                    .await
+            })
+            .await?;
+
+        let next_cursor = match messages.last() {
+            Some(last) if has_more => Some(MessageCursor::for_message(last, false).encode()?),
+            _ => None,
+        };
+        let prev_cursor = match messages.first() {
+            Some(first) if offset > 0 => Some(MessageCursor::for_message(first, true).encode()?),
+            _ => None,
+        };
+
+        // Create the statistics object
+        let stats = MessageListStats {
+            total_count: pagination::u64_to_i64_count(total_count),
+            current_offset: offset,
+            returned_count: messages.len(),
+            has_more,
+            next_cursor,
+            prev_cursor,
+        };
+
+        if flip_page {
+            messages.reverse();
+        }
 
-    // Query messages for this chat with pagination, ordered by creation time
-    let messages = Messages::find()
+        return Ok((messages, stats));
+    };
+
+    // Walking backward scans in the opposite direction; the page is flipped
+    // afterwards so it is always returned in the requested order.
+    let scan_order = if cursor.backward {
+        order.reversed()
+    } else {
+        order
+    };
+
+    // Fetch one extra row to learn whether the scan continues past this page.
+    let mut messages = Messages::find()
         .filter(messages::Column::ChatId.eq(*chat_id))
-        .order_by_desc(messages::Column::CreatedAt)
-        .limit(limit)
-        .offset(offset)
+        .filter(cursor.keyset_condition(scan_order))
+        .order_by(messages::Column::CreatedAt, scan_order.into())
+        .order_by(messages::Column::Id, scan_order.into())
+        .limit(limit + 1)
         .all(conn)
         .await?;
+    let scan_has_more = messages.len() as u64 > limit;
+    messages.truncate(limit as usize);
+    if cursor.backward {
+        messages.reverse();
+    }
 
-    // Use our pagination utility to efficiently calculate the total count
-    let (total_count, has_more) =
-        pagination::calculate_total_count(offset, limit, messages.len(), || async {
-            Messages::find()
-                .filter(messages::Column::ChatId.eq(*chat_id))
-                .count(conn)
-                .await
-        })
+    // The side we came from always has messages (at least the cursor's own).
+    let (has_next, has_prev) = if cursor.backward {
+        (true, scan_has_more)
+    } else {
+        (scan_has_more, true)
+    };
+
+    let total_count = Messages::find()
+        .filter(messages::Column::ChatId.eq(*chat_id))
+        .count(conn)
         .await?;
 
-    // Create the statistics object
+    let next_cursor = match messages.last() {
+        Some(last) if has_next => Some(MessageCursor::for_message(last, false).encode()?),
+        _ => None,
+    };
+    let prev_cursor = match messages.first() {
+        Some(first) if has_prev => Some(MessageCursor::for_message(first, true).encode()?),
+        _ => None,
+    };
+
     let stats = MessageListStats {
         total_count: pagination::u64_to_i64_count(total_count),
-        current_offset: offset,
+        current_offset: 0,
         returned_count: messages.len(),
-        has_more,
+        has_more: has_next,
+        next_cursor,
+        prev_cursor,
     };
 
+    if flip_page {
+        messages.reverse();
+    }
+
     Ok((messages, stats))
 }
 
@@ -599,6 +1341,271 @@ pub async fn get_message_by_id(
     Ok(message)
 }
 
+/// Get the previous versions of an edited message, starting with the original message.
+///
+/// The versions are followed back via `edit_of_message_id`, so regenerations of a message are not
+/// included. Returns no versions for messages that are not edits.
+pub async fn get_message_edit_history(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    message_id: &Uuid,
+) -> Result<Vec<messages::Model>, Report> {
+    // Checks that the subject can read the chat of the message
+    let message = get_message_by_id(conn, policy, subject, message_id).await?;
+
+    let mut versions = Vec::new();
+    let mut visited_ids = std::collections::HashSet::from([message.id]);
+    let mut edit_of_message_id = message.edit_of_message_id;
+    while let Some(version_id) = edit_of_message_id {
+        if !visited_ids.insert(version_id) {
+            break;
+        }
+        let Some(version) = Messages::find_by_id(version_id)
+            .filter(messages::Column::ChatId.eq(message.chat_id))
+            .one(conn)
+            .await?
+        else {
+            break;
+        };
+        edit_of_message_id = version.edit_of_message_id;
+        versions.push(version);
+    }
+    versions.reverse();
+
+    Ok(versions)
+}
+
+/// Check that a message can be used as the `previous_message_id` of a new message in a chat.
+///
+/// The message must belong to the chat, as a link across chats corrupts both threads and pulls
+/// the other chat's content into the prompt. Unless `allow_branching` is set (e.g. for edits),
+/// the message must also be part of the chat's active thread.
+pub fn check_previous_message_for_chat(
+    previous_message: &messages::Model,
+    chat_id: &Uuid,
+    allow_branching: bool,
+) -> Result<(), Report> {
+    if previous_message.chat_id != *chat_id {
+        return Err(eyre!(
+            "Invalid previous_message_id: message {} belongs to a different chat than {}",
+            previous_message.id,
+            chat_id
+        ));
+    }
+    if !allow_branching && !previous_message.is_message_in_active_thread {
+        return Err(eyre!(
+            "Invalid previous_message_id: message {} is not part of the active thread of chat {}",
+            previous_message.id,
+            chat_id
+        ));
+    }
+    Ok(())
+}
+
+/// Get the most recent message of the active thread of a chat, if the chat has any messages.
+pub async fn get_latest_active_thread_message(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    chat_id: &Uuid,
+) -> Result<Option<messages::Model>, Report> {
+    // Authorize that the subject can read this chat
+    authorize!(
+        policy,
+        subject,
+        &Resource::Chat(chat_id.as_hyphenated().to_string()),
+        Action::Read
+    )?;
+
+    Ok(Messages::find()
+        .filter(messages::Column::ChatId.eq(*chat_id))
+        .filter(messages::Column::IsMessageInActiveThread.eq(true))
+        .order_by_desc(messages::Column::CreatedAt)
+        .order_by_desc(messages::Column::Id)
+        .one(conn)
+        .await?)
+}
+
+/// Chat provider IDs referenced by the generation parameters of stored messages that are
+/// neither configured nor aliased (see `chat_provider_aliases`).
+///
+/// Regenerating such messages without explicitly choosing a model fails, so these are
+/// reported at startup.
+pub async fn find_unknown_generation_chat_provider_ids(
+    conn: &DatabaseConnection,
+    config: &AppConfig,
+) -> Result<Vec<String>, Report> {
+    let stmt = named_statement_from_sql_and_values(
+        conn.get_database_backend(),
+        POSTGRES_QUERY_DISTINCT_GENERATION_CHAT_PROVIDER_IDS,
+        "SELECT DISTINCT generation_parameters->>'generation_chat_provider_id' AS chat_provider_id \
+         FROM messages \
+         WHERE generation_parameters->>'generation_chat_provider_id' IS NOT NULL",
+        vec![],
+    );
+    let rows = conn.query_all_raw(stmt).await?;
+
+    let mut unknown_provider_ids = rows
+        .iter()
+        .map(|row| row.try_get::<String>("", "chat_provider_id"))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|provider_id| !config.is_known_chat_provider_id(provider_id))
+        .collect::<Vec<_>>();
+    unknown_provider_ids.sort();
+    Ok(unknown_provider_ids)
+}
+
+/// A link from a message to a message of another chat, via its `previous_message_id` or
+/// `sibling_message_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CrossChatMessageLink {
+    pub message_id: Uuid,
+    pub chat_id: Uuid,
+    /// Either `previous` or `sibling`, depending on the column the link is stored in.
+    pub link_type: String,
+    pub linked_message_id: Uuid,
+    pub linked_chat_id: Uuid,
+}
+
+/// Find all messages that are linked to a message of another chat.
+///
+/// Such links corrupt the threads of both chats. They are rejected when submitting messages,
+/// but may exist from before that validation was added.
+pub async fn find_cross_chat_message_links(
+    conn: &DatabaseConnection,
+) -> Result<Vec<CrossChatMessageLink>, Report> {
+    let stmt = named_statement_from_sql_and_values(
+        conn.get_database_backend(),
+        POSTGRES_QUERY_CROSS_CHAT_MESSAGE_LINKS,
+        r#"
+        SELECT m.id AS message_id, m.chat_id, 'previous' AS link_type,
+            linked.id AS linked_message_id, linked.chat_id AS linked_chat_id
+        FROM messages m
+        INNER JOIN messages linked ON linked.id = m.previous_message_id
+        WHERE linked.chat_id <> m.chat_id
+        UNION ALL
+        SELECT m.id AS message_id, m.chat_id, 'sibling' AS link_type,
+            linked.id AS linked_message_id, linked.chat_id AS linked_chat_id
+        FROM messages m
+        INNER JOIN messages linked ON linked.id = m.sibling_message_id
+        WHERE linked.chat_id <> m.chat_id
+        ORDER BY message_id, link_type
+        "#,
+        vec![],
+    );
+    let rows = conn.query_all_raw(stmt).await?;
+
+    rows.iter()
+        .map(|row| -> Result<CrossChatMessageLink, Report> {
+            Ok(CrossChatMessageLink {
+                message_id: row.try_get("", "message_id")?,
+                chat_id: row.try_get("", "chat_id")?,
+                link_type: row.try_get("", "link_type")?,
+                linked_message_id: row.try_get("", "linked_message_id")?,
+                linked_chat_id: row.try_get("", "linked_chat_id")?,
+            })
+        })
+        .collect()
+}
+
+/// Load the messages of a chat, failing if the chat does not exist.
+async fn find_chat_messages_for_integrity<C: ConnectionTrait>(
+    conn: &C,
+    chat_id: &Uuid,
+    lock: bool,
+) -> Result<Vec<messages::Model>, Report> {
+    if Chats::find_by_id(*chat_id).one(conn).await?.is_none() {
+        return Err(eyre!("Chat {} not found", chat_id));
+    }
+    let mut query = Messages::find()
+        .filter(messages::Column::ChatId.eq(*chat_id))
+        .order_by_asc(messages::Column::CreatedAt)
+        .order_by_asc(messages::Column::Id);
+    if lock {
+        query = query.lock_exclusive();
+    }
+    Ok(query.all(conn).await?)
+}
+
+/// All messages of a chat, oldest first, regardless of who may read them. Meant for operators
+/// inspecting a chat.
+pub async fn get_all_chat_messages(
+    conn: &DatabaseConnection,
+    chat_id: &Uuid,
+) -> Result<Vec<messages::Model>, Report> {
+    find_chat_messages_for_integrity(conn, chat_id, false).await
+}
+
+/// Check the thread of a chat for corruptions, see [`analyze_thread_integrity`].
+pub async fn check_thread_integrity(
+    conn: &DatabaseConnection,
+    chat_id: &Uuid,
+) -> Result<ThreadIntegrityReport, Report> {
+    let chat_messages = find_chat_messages_for_integrity(conn, chat_id, false).await?;
+    Ok(analyze_thread_integrity(*chat_id, &chat_messages))
+}
+
+/// Result of the repair of the thread of a chat
+#[derive(Debug, Clone)]
+pub struct ThreadRepairResult {
+    /// The integrity of the thread before the repair
+    pub report: ThreadIntegrityReport,
+    /// The changes that repair the thread, in the order they are applied
+    pub changes: Vec<ThreadRepairChange>,
+}
+
+/// Repair the thread of a chat, see [`plan_thread_repair`].
+///
+/// The messages of the chat are locked while the repair is planned and applied, so that it does
+/// not interleave with new messages. With `dry_run`, the planned changes are returned without
+/// applying them.
+pub async fn repair_thread_integrity(
+    conn: &DatabaseConnection,
+    chat_id: &Uuid,
+    dry_run: bool,
+) -> Result<ThreadRepairResult, Report> {
+    let txn = conn.begin().await?;
+    let chat_messages = find_chat_messages_for_integrity(&txn, chat_id, true).await?;
+    let report = analyze_thread_integrity(*chat_id, &chat_messages);
+    let changes = plan_thread_repair(&chat_messages);
+    if dry_run || changes.is_empty() {
+        txn.rollback().await?;
+        return Ok(ThreadRepairResult { report, changes });
+    }
+
+    for change in &changes {
+        let update = match change.action {
+            ThreadRepairAction::DetachPreviousMessage => messages::ActiveModel {
+                previous_message_id: ActiveValue::Set(None),
+                ..Default::default()
+            },
+            ThreadRepairAction::DetachSiblingMessage => messages::ActiveModel {
+                sibling_message_id: ActiveValue::Set(None),
+                ..Default::default()
+            },
+            ThreadRepairAction::Activate => messages::ActiveModel {
+                is_message_in_active_thread: ActiveValue::Set(true),
+                ..Default::default()
+            },
+            ThreadRepairAction::Deactivate => messages::ActiveModel {
+                is_message_in_active_thread: ActiveValue::Set(false),
+                ..Default::default()
+            },
+        };
+        messages::Entity::update_many()
+            .set(update)
+            .filter(messages::Column::Id.eq(change.message_id))
+            .exec(&txn)
+            .await
+            .wrap_err_with(|| format!("Failed to repair message {}", change.message_id))?;
+    }
+    txn.commit().await?;
+
+    Ok(ThreadRepairResult { report, changes })
+}
+
 pub fn get_generation_chat_provider_id_from_message(
     message: &messages::Model,
 ) -> Result<Option<String>, Report> {
@@ -616,6 +1623,29 @@ pub fn get_generation_chat_provider_id_from_message(
     Ok(None)
 }
 
+/// The chat provider that was selected for the generation of a message, see
+/// [`GenerationParameters::selected_chat_provider_id`].
+///
+/// Used to generate again with the same model, e.g. when regenerating a message.
+pub fn get_selected_chat_provider_id_from_message(
+    message: &messages::Model,
+) -> Result<Option<String>, Report> {
+    if let Some(generation_params_json) = &message.generation_parameters {
+        let generation_params: GenerationParameters =
+            serde_json::from_value(generation_params_json.clone()).map_err(|e| {
+                eyre!(
+                    "Failed to parse generation parameters for message {}: {}",
+                    message.id,
+                    e
+                )
+            })?;
+        return Ok(generation_params
+            .selected_chat_provider_id()
+            .map(String::from));
+    }
+    Ok(None)
+}
+
 /// An action facet (id + args) as stored in generation parameters.
 pub type StoredActionFacet = (String, std::collections::HashMap<String, String>);
 
@@ -663,6 +1693,25 @@ pub fn get_input_action_facet_from_message(
     Ok(None)
 }
 
+/// The language detected in the text of a user message, as stored in its input
+/// parameters, if any.
+pub fn get_input_detected_language_from_message(
+    message: &messages::Model,
+) -> Result<Option<DetectedLanguage>, Report> {
+    if let Some(input_params_json) = &message.input_parameters {
+        let input_params: InputParameters = serde_json::from_value(input_params_json.clone())
+            .map_err(|e| {
+                eyre!(
+                    "Failed to parse input parameters for message {}: {}",
+                    message.id,
+                    e
+                )
+            })?;
+        return Ok(input_params.detected_language);
+    }
+    Ok(None)
+}
+
 /// Resolve a provider for a user message branch by checking the assistant response that follows
 /// the user message. Prefer the active-thread assistant sibling; fall back to newest sibling.
 pub async fn get_generation_chat_provider_id_for_replaced_user_message(
@@ -674,22 +1723,24 @@ pub async fn get_generation_chat_provider_id_for_replaced_user_message(
         .filter(messages::Column::GenerationParameters.is_not_null())
         .filter(messages::Column::IsMessageInActiveThread.eq(true))
         .order_by_desc(messages::Column::CreatedAt)
+        .order_by_desc(messages::Column::Id)
         .one(conn)
         .await?;
 
     if let Some(message) = active_sibling {
-        return get_generation_chat_provider_id_from_message(&message);
+        return get_selected_chat_provider_id_from_message(&message);
     }
 
     let newest_sibling = Messages::find()
         .filter(messages::Column::PreviousMessageId.eq(*user_message_id))
         .filter(messages::Column::GenerationParameters.is_not_null())
         .order_by_desc(messages::Column::CreatedAt)
+        .order_by_desc(messages::Column::Id)
         .one(conn)
         .await?;
 
     if let Some(message) = newest_sibling {
-        return get_generation_chat_provider_id_from_message(&message);
+        return get_selected_chat_provider_id_from_message(&message);
     }
 
     Ok(None)
@@ -730,6 +1781,8 @@ pub async fn update_message_content(
     let active_model = messages::ActiveModel {
         id: ActiveValue::Set(*message_id),
         raw_message: ActiveValue::Set(updated_raw_message),
+        // The final content supersedes the draft flushed during the generation
+        content_draft: ActiveValue::Set(None),
         ..Default::default() // Only update raw_message, preserve other fields
     };
 
@@ -747,6 +1800,63 @@ pub async fn update_message_content(
     Ok(updated_message_model)
 }
 
+/// Save the content streamed so far for an assistant message that is still being generated.
+///
+/// Only writes the `content_draft` column, leaving the message itself untouched, so that the
+/// message isn't reported as complete. The draft is cleared by [`update_message_content`] once the
+/// generation completes, or merged into the message by [`finalize_stale_message_content_drafts`]
+/// if it never does.
+///
+/// Not authorized, as it is only called by the generation that created the message.
+pub async fn save_message_content_draft(
+    conn: &DatabaseConnection,
+    message_id: &Uuid,
+    content: &[ContentPart],
+) -> Result<(), Report> {
+    let content_draft = to_value(strip_image_urls_from_content(content.to_vec()))?;
+    let statement = named_statement_from_sql_and_values(
+        conn.get_database_backend(),
+        POSTGRES_QUERY_SAVE_MESSAGE_CONTENT_DRAFT,
+        "UPDATE messages SET content_draft = $1 WHERE id = $2",
+        [content_draft.into(), (*message_id).into()],
+    );
+    conn.execute_raw(statement)
+        .await
+        .wrap_err_with(|| format!("Failed to save content draft of message {message_id}"))?;
+    Ok(())
+}
+
+/// Merge the content drafts of generations that ended without completing into their messages.
+///
+/// A draft is considered abandoned if the chat isn't generating anymore and the draft wasn't
+/// updated for `stale_after_secs`. Returns the number of finalized messages.
+pub async fn finalize_stale_message_content_drafts(
+    conn: &DatabaseConnection,
+    stale_after_secs: u64,
+) -> Result<u64, Report> {
+    // The scan is supported by the partial index `idx_messages_content_draft_updated_at`.
+    let statement = named_statement_from_sql_and_values(
+        conn.get_database_backend(),
+        POSTGRES_QUERY_FINALIZE_MESSAGE_CONTENT_DRAFTS,
+        r#"
+        UPDATE messages
+        SET raw_message = jsonb_set(messages.raw_message, '{content}', messages.content_draft),
+            content_draft = NULL
+        FROM chats
+        WHERE messages.chat_id = chats.id
+          AND messages.content_draft IS NOT NULL
+          AND messages.updated_at < now() - make_interval(secs => $1::double precision)
+          AND chats.generation_state IS DISTINCT FROM 'running'
+        "#,
+        [(stale_after_secs as f64).into()],
+    );
+    let result = conn
+        .execute_raw(statement)
+        .await
+        .wrap_err("Failed to finalize stale message content drafts")?;
+    Ok(result.rows_affected())
+}
+
 /// Update the generation metadata for a message.
 pub async fn update_message_generation_metadata(
     conn: &DatabaseConnection,
@@ -791,6 +1901,53 @@ pub async fn update_message_generation_metadata(
     Ok(updated_message_model)
 }
 
+/// Records the chat provider that served a generation in its generation parameters, when a
+/// chat provider group switched to another member during the generation.
+pub async fn update_message_generation_chat_provider_id(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    message_id: &Uuid,
+    chat_provider_id: &str,
+) -> Result<(), Report> {
+    let message = Messages::find_by_id(*message_id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| eyre!("Message with ID {} not found for update", message_id))?;
+
+    authorize!(
+        policy,
+        subject,
+        &Resource::Chat(message.chat_id.to_string()),
+        Action::SubmitMessage
+    )?;
+
+    let Some(generation_params_json) = message.generation_parameters else {
+        return Ok(());
+    };
+    let mut generation_params: GenerationParameters =
+        serde_json::from_value(generation_params_json).map_err(|e| {
+            eyre!(
+                "Failed to parse generation parameters for message {}: {}",
+                message.id,
+                e
+            )
+        })?;
+    generation_params.generation_chat_provider_id = Some(chat_provider_id.to_string());
+
+    let active_model = messages::ActiveModel {
+        id: ActiveValue::Set(*message_id),
+        generation_parameters: ActiveValue::Set(Some(to_value(generation_params)?)),
+        ..Default::default()
+    };
+    messages::Entity::update(active_model)
+        .exec(conn)
+        .await
+        .map_err(|e| eyre!("Failed to update message generation parameters: {}", e))?;
+
+    Ok(())
+}
+
 /// One input message for an LLM generation.
 /// In contrast to the `Message` model, which bundles multiple individual LLM messages, this is closer
 /// to the native format of the LLM.
@@ -811,6 +1968,7 @@ impl InputMessage {
             ContentPart::Image(_) => String::new(),
             // Marker is metadata-only; rendering happens in the resolver.
             ContentPart::ActionFacetMarker(_) => String::new(),
+            ContentPart::BlobPointer(_) => String::new(),
         }
     }
 }
@@ -1024,43 +2182,30 @@ pub async fn regenerate_image_urls_in_content(
                     .await?;
 
                 if let Some(file) = file_upload {
-                    // Get the file storage provider
-                    let file_storage = file_storage_providers.get(&file.file_storage_provider_id);
-
-                    if let Some(storage) = file_storage {
-                        let download_url = match storage
-                            .generate_presigned_download_url_with_context(
-                                &file.file_storage_path,
-                                None,
-                                Some(&file.filename),
-                                None, // No Sharepoint context for now (image generation uses default provider)
-                            )
-                            .await
-                        {
-                            Ok(url) => url,
-                            Err(err) => {
-                                tracing::warn!(
-                                    file_id = %file.id,
-                                    provider = %file.file_storage_provider_id,
-                                    error = %err,
-                                    "Failed to regenerate download URL, using placeholder"
-                                );
-                                // Return placeholder URL that the frontend can use to fetch via API
-                                format!("/api/v1beta/files/{}", file.id)
-                            }
-                        };
-                        ContentPart::ImageFilePointer(ContentPartImageFilePointer {
+                    match resolve_file_urls(
+                        &file.id,
+                        &file.filename,
+                        &file.file_storage_provider_id,
+                        &file.file_storage_path,
+                        file_storage_providers,
+                        None, // No Sharepoint context for now (image generation uses default provider)
+                    )
+                    .await
+                    {
+                        Ok(urls) => ContentPart::ImageFilePointer(ContentPartImageFilePointer {
                             file_upload_id: pointer.file_upload_id,
-                            download_url: Some(download_url),
+                            download_url: Some(urls.download_url),
                             preview_url: Some(proxied_preview_url_for_file(&file.id)),
-                        })
-                    } else {
-                        tracing::warn!(
-                            file_id = %file.id,
-                            provider = %file.file_storage_provider_id,
-                            "File storage provider not found, keeping original URL"
-                        );
-                        part
+                        }),
+                        Err(err) => {
+                            tracing::warn!(
+                                file_id = %file.id,
+                                provider = %file.file_storage_provider_id,
+                                error = %err,
+                                "Failed to resolve download URL, keeping original URL"
+                            );
+                            part
+                        }
                     }
                 } else {
                     tracing::warn!(
@@ -1164,3 +2309,36 @@ mod action_facet_filter_tests {
         );
     }
 }
+
+#[cfg(test)]
+mod offered_tools_tests {
+    use super::{MAX_RECORDED_OFFERED_TOOLS, OfferedTool, OfferedTools};
+
+    fn tools(count: usize) -> Vec<OfferedTool> {
+        (0..count)
+            .map(|index| OfferedTool {
+                server_id: Some("files".to_string()),
+                name: format!("tool_{index}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn records_all_tools_up_to_the_limit() {
+        let offered_tools = OfferedTools::new(tools(MAX_RECORDED_OFFERED_TOOLS));
+
+        assert_eq!(offered_tools.count, MAX_RECORDED_OFFERED_TOOLS);
+        assert_eq!(offered_tools.tools.len(), MAX_RECORDED_OFFERED_TOOLS);
+        assert!(!offered_tools.truncated);
+    }
+
+    #[test]
+    fn truncates_tools_beyond_the_limit() {
+        let offered_tools = OfferedTools::new(tools(MAX_RECORDED_OFFERED_TOOLS + 5));
+
+        assert_eq!(offered_tools.count, MAX_RECORDED_OFFERED_TOOLS + 5);
+        assert_eq!(offered_tools.tools.len(), MAX_RECORDED_OFFERED_TOOLS);
+        assert_eq!(offered_tools.tools[0].name, "tool_0");
+        assert!(offered_tools.truncated);
+    }
+}
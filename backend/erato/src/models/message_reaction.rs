@@ -0,0 +1,150 @@
+//! Emoji reactions of users on messages.
+//!
+//! Reactions are visible to everyone that can read the chat of the message, and anyone that can
+//! read it may react. Unlike feedback, reactions are not forwarded to Langfuse.
+
+use crate::db::entity::message_reactions;
+use crate::db::entity::prelude::*;
+use crate::models::message::get_message_by_id;
+use crate::policy::prelude::*;
+use eyre::{Report, eyre};
+use sea_orm::prelude::*;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    DatabaseConnection, EntityTrait, FromQueryResult, Order, QueryOrder, QuerySelect, Set,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// The reactions with one emoji on a message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct MessageReaction {
+    /// The emoji
+    pub emoji: String,
+    /// Number of users that reacted with the emoji
+    pub count: i64,
+    /// Whether the current user reacted with the emoji
+    pub reacted_by_me: bool,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct MessageReactionCount {
+    message_id: Uuid,
+    emoji: String,
+    count: i64,
+    reacted_by_me: bool,
+}
+
+/// Add a reaction of a user to a message.
+///
+/// The user must be able to read the chat of the message, and the emoji must be one of
+/// `allowed_emojis`. Adding a reaction the user already has is a no-op.
+pub async fn add_reaction(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    user_id: &Uuid,
+    message_id: &Uuid,
+    emoji: &str,
+    allowed_emojis: &[String],
+) -> Result<(), Report> {
+    // Checks that the subject can read the chat of the message
+    get_message_by_id(conn, policy, subject, message_id).await?;
+
+    if !allowed_emojis.iter().any(|allowed| allowed == emoji) {
+        return Err(eyre!("Reactions with emoji '{}' are not allowed", emoji));
+    }
+
+    let existing = MessageReactions::find()
+        .filter(message_reactions::Column::MessageId.eq(*message_id))
+        .filter(message_reactions::Column::UserId.eq(*user_id))
+        .filter(message_reactions::Column::Emoji.eq(emoji))
+        .one(conn)
+        .await?;
+    if existing.is_none() {
+        message_reactions::ActiveModel {
+            message_id: Set(*message_id),
+            user_id: Set(*user_id),
+            emoji: Set(emoji.to_string()),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Remove a reaction of a user from a message.
+///
+/// The user must be able to read the chat of the message. Removing a reaction the user doesn't
+/// have is a no-op, so that reactions with emojis that were removed from the allowlist can still
+/// be taken back.
+pub async fn remove_reaction(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    user_id: &Uuid,
+    message_id: &Uuid,
+    emoji: &str,
+) -> Result<(), Report> {
+    // Checks that the subject can read the chat of the message
+    get_message_by_id(conn, policy, subject, message_id).await?;
+
+    MessageReactions::delete_many()
+        .filter(message_reactions::Column::MessageId.eq(*message_id))
+        .filter(message_reactions::Column::UserId.eq(*user_id))
+        .filter(message_reactions::Column::Emoji.eq(emoji))
+        .exec(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Get the aggregated reactions of multiple messages with a single query.
+///
+/// The reactions of each message are ordered by their first use on the message. Messages without
+/// reactions are not included in the returned map.
+pub async fn get_reactions_for_messages(
+    conn: &DatabaseConnection,
+    user_id: &Uuid,
+    message_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<MessageReaction>>, Report> {
+    if message_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let counts = MessageReactions::find()
+        .select_only()
+        .column(message_reactions::Column::MessageId)
+        .column(message_reactions::Column::Emoji)
+        .column_as(Expr::cust("COUNT(*)"), "count")
+        .column_as(
+            Expr::cust_with_values(r#"BOOL_OR("user_id" = ?)"#, [*user_id]),
+            "reacted_by_me",
+        )
+        .filter(message_reactions::Column::MessageId.is_in(message_ids.iter().copied()))
+        .group_by(message_reactions::Column::MessageId)
+        .group_by(message_reactions::Column::Emoji)
+        .order_by_asc(message_reactions::Column::MessageId)
+        .order_by(Expr::cust(r#"MIN("created_at")"#), Order::Asc)
+        .order_by_asc(message_reactions::Column::Emoji)
+        .into_model::<MessageReactionCount>()
+        .all(conn)
+        .await?;
+
+    let mut reactions: HashMap<Uuid, Vec<MessageReaction>> = HashMap::new();
+    for count in counts {
+        reactions
+            .entry(count.message_id)
+            .or_default()
+            .push(MessageReaction {
+                emoji: count.emoji,
+                count: count.count,
+                reacted_by_me: count.reacted_by_me,
+            });
+    }
+
+    Ok(reactions)
+}
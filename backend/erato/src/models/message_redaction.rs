@@ -0,0 +1,453 @@
+//! Redaction of sensitive content from stored messages by admins.
+//!
+//! Redacting a message rewrites the matching text in its stored content, as well as in the
+//! copies of that text in the generation inputs of the messages of the chat, so the redacted
+//! text can't reach the model again via the stored history. Archived generation inputs that
+//! contain the text are restored before they are rewritten. Every redaction is recorded in
+//! `message_redactions` with the redacted locations, but without the redacted text.
+
+use crate::db::entity::prelude::*;
+use crate::db::entity::{file_uploads, message_redactions, messages};
+use crate::models::message::{
+    ContentPart, ContentPartText, GenerationInputMessages, MessageRole, MessageSchema,
+};
+use crate::services::generation_input_archive::{ArchivedGenerationInput, GenerationInputArchive};
+use chrono::Utc;
+use eyre::{Report, WrapErr, eyre};
+use regex::Regex;
+use sea_orm::prelude::*;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, Set, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+/// The text redacted content is replaced with, if no other replacement is requested.
+pub const DEFAULT_REDACTION_REPLACEMENT: &str = "[REDACTED]";
+
+/// A range of characters in one content part of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RedactionSpan {
+    /// Index of the content part in the message. For a file, always `0` (its transcript).
+    pub content_index: usize,
+    /// Offset of the first redacted character (inclusive)
+    pub start: usize,
+    /// Offset after the last redacted character (exclusive)
+    pub end: usize,
+}
+
+/// What to redact in a message.
+pub struct RedactionRequest<'a> {
+    /// Redact a file attached to the message instead of the text of the message.
+    pub file_id: Option<Uuid>,
+    /// Redact all matches of this pattern.
+    pub pattern: Option<&'a Regex>,
+    /// Redact these ranges.
+    pub ranges: &'a [RedactionSpan],
+    pub replacement: &'a str,
+    /// Only find the spans to redact, without modifying anything.
+    pub dry_run: bool,
+}
+
+/// Result of redacting a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedactionResult {
+    /// The redacted spans, in the content before the redaction.
+    pub spans: Vec<RedactionSpan>,
+    /// Number of messages whose stored generation inputs were rewritten.
+    pub updated_generation_inputs: u64,
+}
+
+/// Redact the content of a message, or of a file attached to it.
+///
+/// Not subject to the policy engine, as it is only available to admins. The audit entry is
+/// attributed to `actor_user_id`.
+pub async fn redact_message(
+    conn: &DatabaseConnection,
+    generation_input_archive: &GenerationInputArchive,
+    actor_user_id: &Uuid,
+    message_id: &Uuid,
+    request: &RedactionRequest<'_>,
+) -> Result<RedactionResult, Report> {
+    let message = Messages::find_by_id(*message_id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| eyre!("Message with ID {} not found", message_id))?;
+
+    if let Some(file_id) = request.file_id {
+        return redact_file(conn, actor_user_id, &message, &file_id, request).await;
+    }
+
+    let mut schema = MessageSchema::validate(&message.raw_message)?;
+    let texts: Vec<(usize, &str)> = schema
+        .content
+        .iter()
+        .enumerate()
+        .filter_map(|(index, part)| match part {
+            ContentPart::Text(text) => Some((index, text.text.as_str())),
+            _ => None,
+        })
+        .collect();
+    let spans = find_redaction_spans(&texts, request.pattern, request.ranges)?;
+    if request.dry_run || spans.is_empty() {
+        return Ok(RedactionResult {
+            spans,
+            updated_generation_inputs: 0,
+        });
+    }
+
+    // Original text -> redacted text of every redacted content part
+    let mut redacted_texts: Vec<(String, String)> = Vec::new();
+    for (content_index, part_spans) in spans_by_content_index(&spans) {
+        if let Some(ContentPart::Text(text)) = schema.content.get_mut(content_index) {
+            let redacted = apply_redactions(&text.text, &part_spans, request.replacement);
+            redacted_texts.push((
+                std::mem::replace(&mut text.text, redacted.clone()),
+                redacted,
+            ));
+        }
+    }
+
+    let txn = conn.begin().await?;
+    let mut active_message: messages::ActiveModel = message.clone().into();
+    active_message.raw_message = Set(schema.to_json()?);
+    active_message.updated_at = Set(Utc::now().into());
+    active_message.update(&txn).await?;
+
+    // Rewrite the copies of the redacted text parts in the stored history of the chat
+    let mut updated_generation_inputs = 0;
+    let mut restored_archives = Vec::new();
+    let chat_messages = Messages::find()
+        .filter(messages::Column::ChatId.eq(message.chat_id))
+        .filter(messages::Column::GenerationInputMessages.is_not_null())
+        .all(&txn)
+        .await?;
+    for chat_message in chat_messages {
+        let Some(generation_input_json) = &chat_message.generation_input_messages else {
+            continue;
+        };
+        let archived = ArchivedGenerationInput::from_generation_input(generation_input_json);
+        let mut generation_input = match &archived {
+            Some(archived) => GenerationInputMessages::validate(
+                &generation_input_archive
+                    .load(&txn, chat_message.id, archived)
+                    .await?,
+            )?,
+            None => GenerationInputMessages::validate(generation_input_json)?,
+        };
+        let changed = redact_generation_input(
+            &mut generation_input,
+            &schema.role,
+            &redacted_texts,
+            request.pattern,
+            request.replacement,
+        );
+        if changed {
+            if let Some(archived) = archived {
+                restored_archives.push((chat_message.id, archived));
+            }
+            let mut active_chat_message: messages::ActiveModel = chat_message.into();
+            active_chat_message.generation_input_messages =
+                Set(Some(serde_json::to_value(&generation_input)?));
+            active_chat_message.update(&txn).await?;
+            updated_generation_inputs += 1;
+        }
+    }
+
+    record_redaction(&txn, actor_user_id, message_id, None, &spans, request).await?;
+    txn.commit().await?;
+
+    // The archived copies still contain the redacted text
+    for (chat_message_id, archived) in restored_archives {
+        if let Err(err) = generation_input_archive
+            .delete(conn, chat_message_id, &archived)
+            .await
+        {
+            tracing::warn!(
+                error = %err,
+                message_id = %chat_message_id,
+                "Failed to delete the archived generation input of a redacted message"
+            );
+        }
+    }
+
+    Ok(RedactionResult {
+        spans,
+        updated_generation_inputs,
+    })
+}
+
+/// Redact the transcript of an audio file attached to a message.
+///
+/// The parsed content of other files is not stored, and is re-read from the file storage. The
+/// cached parsed content has to be purged separately.
+async fn redact_file(
+    conn: &DatabaseConnection,
+    actor_user_id: &Uuid,
+    message: &messages::Model,
+    file_id: &Uuid,
+    request: &RedactionRequest<'_>,
+) -> Result<RedactionResult, Report> {
+    if !message
+        .input_file_uploads
+        .as_ref()
+        .is_some_and(|file_ids| file_ids.contains(file_id))
+    {
+        return Err(eyre!(
+            "File with ID {} not found on message {}",
+            file_id,
+            message.id
+        ));
+    }
+    let file = FileUploads::find_by_id(*file_id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| eyre!("File with ID {} not found", file_id))?;
+
+    let spans = match &file.audio_transcription {
+        Some(transcription) => find_redaction_spans(
+            &[(0, transcription.as_str())],
+            request.pattern,
+            request.ranges,
+        )?,
+        None if request.ranges.is_empty() => Vec::new(),
+        None => {
+            return Err(eyre!(
+                "Invalid redaction range: the file has no text content"
+            ));
+        }
+    };
+    if request.dry_run {
+        return Ok(RedactionResult {
+            spans,
+            updated_generation_inputs: 0,
+        });
+    }
+
+    let txn = conn.begin().await?;
+    if let Some(transcription) = &file.audio_transcription
+        && !spans.is_empty()
+    {
+        let redacted = apply_redactions(transcription, &spans, request.replacement);
+        let mut active_file: file_uploads::ActiveModel = file.clone().into();
+        active_file.audio_transcription = Set(Some(redacted));
+        active_file.update(&txn).await?;
+    }
+    let mut active_message: messages::ActiveModel = message.clone().into();
+    active_message.updated_at = Set(Utc::now().into());
+    active_message.update(&txn).await?;
+    record_redaction(
+        &txn,
+        actor_user_id,
+        &message.id,
+        Some(file_id),
+        &spans,
+        request,
+    )
+    .await?;
+    txn.commit().await?;
+
+    Ok(RedactionResult {
+        spans,
+        updated_generation_inputs: 0,
+    })
+}
+
+async fn record_redaction(
+    conn: &impl sea_orm::ConnectionTrait,
+    actor_user_id: &Uuid,
+    message_id: &Uuid,
+    file_id: Option<&Uuid>,
+    spans: &[RedactionSpan],
+    request: &RedactionRequest<'_>,
+) -> Result<message_redactions::Model, Report> {
+    message_redactions::ActiveModel {
+        message_id: Set(*message_id),
+        file_upload_id: Set(file_id.copied()),
+        actor_user_id: Set(Some(*actor_user_id)),
+        spans: Set(serde_json::to_value(spans)?),
+        replacement: Set(request.replacement.to_string()),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await
+    .wrap_err("Failed to record message redaction")
+}
+
+/// Rewrite the text parts with the given role in stored generation inputs.
+///
+/// Parts that are exact copies of a redacted content part are replaced with the redacted text.
+/// If a pattern is given, its matches are redacted in the other parts as well, to also cover
+/// copies that were modified while composing the prompt. Returns whether anything changed.
+fn redact_generation_input(
+    generation_input: &mut GenerationInputMessages,
+    role: &MessageRole,
+    redacted_texts: &[(String, String)],
+    pattern: Option<&Regex>,
+    replacement: &str,
+) -> bool {
+    let mut changed = false;
+    for input_message in &mut generation_input.messages {
+        if &input_message.role != role {
+            continue;
+        }
+        let ContentPart::Text(ContentPartText { text }) = &mut input_message.content else {
+            continue;
+        };
+        if let Some((_, redacted)) = redacted_texts.iter().find(|(original, _)| original == text) {
+            *text = redacted.clone();
+            changed = true;
+        } else if let Some(pattern) = pattern
+            && pattern.is_match(text)
+        {
+            *text = pattern
+                .replace_all(text, regex::NoExpand(replacement))
+                .into_owned();
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Find the spans to redact in the given texts (by content index), merging overlapping spans.
+///
+/// Fails if a range refers to a content index that is not in `texts`, or lies outside of the
+/// text.
+pub(crate) fn find_redaction_spans(
+    texts: &[(usize, &str)],
+    pattern: Option<&Regex>,
+    ranges: &[RedactionSpan],
+) -> Result<Vec<RedactionSpan>, Report> {
+    let mut spans = Vec::new();
+    for range in ranges {
+        let Some((_, text)) = texts
+            .iter()
+            .find(|(index, _)| *index == range.content_index)
+        else {
+            return Err(eyre!(
+                "Invalid redaction range: content part {} is not a text part",
+                range.content_index
+            ));
+        };
+        if range.start >= range.end || range.end > text.chars().count() {
+            return Err(eyre!(
+                "Invalid redaction range: {}..{} in content part {}",
+                range.start,
+                range.end,
+                range.content_index
+            ));
+        }
+        spans.push(*range);
+    }
+    if let Some(pattern) = pattern {
+        for (content_index, text) in texts {
+            for found in pattern.find_iter(text) {
+                if found.is_empty() {
+                    continue;
+                }
+                let start = text[..found.start()].chars().count();
+                spans.push(RedactionSpan {
+                    content_index: *content_index,
+                    start,
+                    end: start + found.as_str().chars().count(),
+                });
+            }
+        }
+    }
+
+    spans.sort_by_key(|span| (span.content_index, span.start, span.end));
+    let mut merged: Vec<RedactionSpan> = Vec::with_capacity(spans.len());
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if last.content_index == span.content_index && span.start <= last.end => {
+                last.end = last.end.max(span.end);
+            }
+            _ => merged.push(span),
+        }
+    }
+    Ok(merged)
+}
+
+fn spans_by_content_index(spans: &[RedactionSpan]) -> BTreeMap<usize, Vec<RedactionSpan>> {
+    let mut by_index: BTreeMap<usize, Vec<RedactionSpan>> = BTreeMap::new();
+    for span in spans {
+        by_index.entry(span.content_index).or_default().push(*span);
+    }
+    by_index
+}
+
+/// Replace the given (sorted, non-overlapping) character spans of a text.
+pub(crate) fn apply_redactions(text: &str, spans: &[RedactionSpan], replacement: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut spans = spans.iter().peekable();
+    let mut in_span = false;
+    for (offset, character) in text.chars().enumerate() {
+        while spans.peek().is_some_and(|span| offset >= span.end) {
+            spans.next();
+            in_span = false;
+        }
+        match spans.peek() {
+            Some(span) if offset >= span.start => {
+                if !in_span {
+                    redacted.push_str(replacement);
+                    in_span = true;
+                }
+            }
+            _ => redacted.push(character),
+        }
+    }
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(content_index: usize, start: usize, end: usize) -> RedactionSpan {
+        RedactionSpan {
+            content_index,
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn finds_pattern_matches_as_character_offsets() {
+        let pattern = Regex::new(r"sk-[a-z0-9]+").unwrap();
+        let texts = [(0, "Schlüssel: sk-abc123"), (2, "kein Schlüssel")];
+        assert_eq!(
+            find_redaction_spans(&texts, Some(&pattern), &[]).unwrap(),
+            vec![span(0, 11, 20)]
+        );
+    }
+
+    #[test]
+    fn merges_overlapping_ranges_and_matches() {
+        let pattern = Regex::new(r"secret").unwrap();
+        let texts = [(0, "my secret value")];
+        assert_eq!(
+            find_redaction_spans(&texts, Some(&pattern), &[span(0, 6, 15), span(0, 0, 2)]).unwrap(),
+            vec![span(0, 0, 2), span(0, 3, 15)]
+        );
+    }
+
+    #[test]
+    fn rejects_ranges_outside_of_text_parts() {
+        let texts = [(0, "short")];
+        assert!(find_redaction_spans(&texts, None, &[span(0, 2, 6)]).is_err());
+        assert!(find_redaction_spans(&texts, None, &[span(1, 0, 1)]).is_err());
+        assert!(find_redaction_spans(&texts, None, &[span(0, 3, 3)]).is_err());
+    }
+
+    #[test]
+    fn replaces_each_span_once() {
+        assert_eq!(
+            apply_redactions(
+                "Schlüssel sk-1 und sk-2",
+                &[span(0, 10, 14), span(0, 19, 23)],
+                DEFAULT_REDACTION_REPLACEMENT
+            ),
+            "Schlüssel [REDACTED] und [REDACTED]"
+        );
+        assert_eq!(apply_redactions("abc", &[span(0, 0, 3)], "*"), "*");
+    }
+}
@@ -0,0 +1,269 @@
+//! Search for text in the messages of a single chat (find-in-conversation).
+//!
+//! The database narrows the messages of the chat down to those with a text content part that
+//! contains the query, and the matches are then located in the text to build snippets. All
+//! offsets count characters (Unicode scalar values), not bytes.
+
+use crate::db::entity_ext::messages::api_timestamp;
+use crate::metrics_constants::POSTGRES_QUERY_SEARCH_CHAT_MESSAGES;
+use crate::models::message::{ContentPart, MessageSchema};
+use crate::policy::prelude::*;
+use crate::query_metrics::named_statement_from_sql_and_values;
+use chrono::Utc;
+use eyre::Report;
+use sea_orm::prelude::*;
+use sea_orm::{DatabaseConnection, FromQueryResult};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Minimum number of characters of a search query.
+pub const MIN_SEARCH_QUERY_CHARS: usize = 2;
+
+/// Text content parts up to this number of characters are returned as a whole as the snippet.
+const MAX_FULL_SNIPPET_CHARS: usize = 200;
+/// Number of characters of context around the first match in snippets of longer text.
+const SNIPPET_CONTEXT_CHARS: usize = 60;
+
+/// A range of characters in a snippet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct SearchMatchOffset {
+    /// Offset of the first matched character (inclusive)
+    pub start: usize,
+    /// Offset after the last matched character (exclusive)
+    pub end: usize,
+}
+
+/// A text content part of a message that matches a search query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageSearchMatch {
+    pub message_id: Uuid,
+    /// Index of the content part in the message.
+    pub content_index: usize,
+    /// Excerpt of the text of the content part around the first match.
+    pub snippet: String,
+    /// The matches within the snippet.
+    pub match_offsets: Vec<SearchMatchOffset>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+/// Options for searching the messages of a chat.
+#[derive(Debug, Clone, Copy)]
+pub struct MessageSearchOptions {
+    /// Also search messages that are not part of the active thread.
+    pub include_inactive: bool,
+    /// Maximum number of matches to return.
+    pub limit: u64,
+    /// Number of matches to skip.
+    pub offset: u64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct MatchingMessage {
+    id: Uuid,
+    created_at: DateTimeWithTimeZone,
+    raw_message: Json,
+}
+
+/// Search the text content parts of the messages of a chat, case-insensitively.
+///
+/// Returns one match per matching content part, ordered by the position of the message in the
+/// chat and the position of the content part in the message, together with the total number of
+/// matches.
+pub async fn search_chat_messages(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    chat_id: &Uuid,
+    query: &str,
+    options: MessageSearchOptions,
+) -> Result<(Vec<MessageSearchMatch>, u64), Report> {
+    authorize!(
+        policy,
+        subject,
+        &Resource::Chat(chat_id.as_hyphenated().to_string()),
+        Action::Read
+    )?;
+
+    let sql = r#"
+        SELECT m.id, m.created_at, m.raw_message
+        FROM messages m
+        WHERE m.chat_id = $1
+            AND ($2 OR m.is_message_in_active_thread)
+            AND EXISTS (
+                SELECT 1
+                FROM jsonb_array_elements(
+                    CASE
+                        WHEN jsonb_typeof(m.raw_message->'content') = 'array'
+                        THEN m.raw_message->'content'
+                        ELSE '[]'::jsonb
+                    END
+                ) AS part
+                WHERE part->>'content_type' = 'text'
+                    AND part->>'text' ILIKE $3 ESCAPE '\'
+            )
+        ORDER BY m.created_at, m.id
+    "#;
+    let matching_messages =
+        MatchingMessage::find_by_statement(named_statement_from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            POSTGRES_QUERY_SEARCH_CHAT_MESSAGES,
+            sql,
+            [
+                (*chat_id).into(),
+                options.include_inactive.into(),
+                format!("%{}%", escape_like_pattern(query)).into(),
+            ],
+        ))
+        .all(conn)
+        .await?;
+
+    let query: Vec<char> = query.chars().map(fold_char).collect();
+    let mut matches = Vec::new();
+    for message in matching_messages {
+        // Messages that fail to parse can't be displayed either
+        let Ok(schema) = MessageSchema::validate(&message.raw_message) else {
+            continue;
+        };
+        for (content_index, part) in schema.content.iter().enumerate() {
+            let ContentPart::Text(text) = part else {
+                continue;
+            };
+            if let Some((snippet, match_offsets)) = build_snippet(&text.text, &query) {
+                matches.push(MessageSearchMatch {
+                    message_id: message.id,
+                    content_index,
+                    snippet,
+                    match_offsets,
+                    created_at: api_timestamp(message.created_at.with_timezone(&Utc)),
+                });
+            }
+        }
+    }
+
+    let total_count = matches.len() as u64;
+    let matches = matches
+        .into_iter()
+        .skip(options.offset as usize)
+        .take(options.limit as usize)
+        .collect();
+    Ok((matches, total_count))
+}
+
+/// Escape the wildcards of a `LIKE` pattern.
+fn escape_like_pattern(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Lowercase a character for case-insensitive matching.
+///
+/// Keeps exactly one character per character, so that offsets in the folded text are offsets in
+/// the original text.
+fn fold_char(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Find the non-overlapping occurrences of the folded `query` in `text`, as character ranges.
+fn find_matches(text: &[char], query: &[char]) -> Vec<SearchMatchOffset> {
+    let mut matches = Vec::new();
+    if query.is_empty() || query.len() > text.len() {
+        return matches;
+    }
+    let folded: Vec<char> = text.iter().copied().map(fold_char).collect();
+    let mut start = 0;
+    while start + query.len() <= folded.len() {
+        if folded[start..start + query.len()] == *query {
+            matches.push(SearchMatchOffset {
+                start,
+                end: start + query.len(),
+            });
+            start += query.len();
+        } else {
+            start += 1;
+        }
+    }
+    matches
+}
+
+/// Build the snippet of a text around its first match of the folded `query`, with the offsets of
+/// the matches within the snippet.
+///
+/// Returns `None` if the text doesn't contain the query.
+fn build_snippet(text: &str, query: &[char]) -> Option<(String, Vec<SearchMatchOffset>)> {
+    let chars: Vec<char> = text.chars().collect();
+    let matches = find_matches(&chars, query);
+    let first_match = *matches.first()?;
+
+    let (snippet_start, snippet_end) = if chars.len() <= MAX_FULL_SNIPPET_CHARS {
+        (0, chars.len())
+    } else {
+        (
+            first_match.start.saturating_sub(SNIPPET_CONTEXT_CHARS),
+            (first_match.end + SNIPPET_CONTEXT_CHARS).min(chars.len()),
+        )
+    };
+    let snippet = chars[snippet_start..snippet_end].iter().collect();
+    let match_offsets = matches
+        .into_iter()
+        .filter(|m| m.start >= snippet_start && m.end <= snippet_end)
+        .map(|m| SearchMatchOffset {
+            start: m.start - snippet_start,
+            end: m.end - snippet_start,
+        })
+        .collect();
+    Some((snippet, match_offsets))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folded(query: &str) -> Vec<char> {
+        query.chars().map(fold_char).collect()
+    }
+
+    #[test]
+    fn test_escape_like_pattern() {
+        assert_eq!(escape_like_pattern("100%_a\\b"), "100\\%\\_a\\\\b");
+    }
+
+    #[test]
+    fn test_build_snippet_returns_all_matches_case_insensitively() {
+        let (snippet, offsets) = build_snippet("Apple, apple and APPLE", &folded("apple")).unwrap();
+        assert_eq!(snippet, "Apple, apple and APPLE");
+        assert_eq!(
+            offsets,
+            vec![
+                SearchMatchOffset { start: 0, end: 5 },
+                SearchMatchOffset { start: 7, end: 12 },
+                SearchMatchOffset { start: 17, end: 22 },
+            ]
+        );
+        assert_eq!(build_snippet("Banana", &folded("apple")), None);
+    }
+
+    #[test]
+    fn test_build_snippet_uses_character_offsets() {
+        let (snippet, offsets) = build_snippet("Grüße 👋 aus Köln", &folded("KÖLN")).unwrap();
+        assert_eq!(offsets, vec![SearchMatchOffset { start: 12, end: 16 }]);
+        let matched: String = snippet.chars().skip(12).take(4).collect();
+        assert_eq!(matched, "Köln");
+    }
+
+    #[test]
+    fn test_build_snippet_excerpts_long_text() {
+        let text = format!("{}needle{}needle", "a".repeat(300), "b".repeat(300));
+        let (snippet, offsets) = build_snippet(&text, &folded("needle")).unwrap();
+        assert_eq!(
+            snippet,
+            format!("{}needle{}", "a".repeat(60), "b".repeat(60))
+        );
+        assert_eq!(offsets, vec![SearchMatchOffset { start: 60, end: 66 }]);
+    }
+}
@@ -0,0 +1,761 @@
+//! Integrity of the thread of messages of a chat.
+//!
+//! The messages of a chat form a tree through their `previous_message_id`, and the answers that
+//! replace each other through edits and regenerations are grouped through their
+//! `sibling_message_id`. The active thread is the path from the first message of the chat to the
+//! message the user continues from, and is marked with `is_message_in_active_thread`.
+//!
+//! Crashes in the middle of an edit and links across chats from older versions left some chats
+//! with threads that don't form such a tree. The functions here detect these corruptions, and
+//! plan the changes that repair them.
+
+use crate::db::entity::messages;
+use sea_orm::prelude::Uuid;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use utoipa::ToSchema;
+
+/// A class of corruption of the thread of a chat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadIntegrityIssueKind {
+    /// More than one message of the active thread is not followed by another message of the
+    /// active thread
+    MultipleActiveLeaves,
+    /// A message of the active thread follows a message that is not part of the active thread,
+    /// or the chat has messages but none of them is part of the active thread
+    BrokenActiveThread,
+    /// The previous message of a message does not exist in the chat, e.g. because it belongs to
+    /// another chat
+    DanglingPreviousMessage,
+    /// The sibling message of a message does not exist in the chat
+    DanglingSiblingMessage,
+    /// Following the previous messages of a message leads back to the message
+    PreviousMessageCycle,
+    /// Following the sibling messages of a message leads back to the message
+    SiblingMessageCycle,
+}
+
+impl ThreadIntegrityIssueKind {
+    pub const ALL: [ThreadIntegrityIssueKind; 6] = [
+        ThreadIntegrityIssueKind::MultipleActiveLeaves,
+        ThreadIntegrityIssueKind::BrokenActiveThread,
+        ThreadIntegrityIssueKind::DanglingPreviousMessage,
+        ThreadIntegrityIssueKind::DanglingSiblingMessage,
+        ThreadIntegrityIssueKind::PreviousMessageCycle,
+        ThreadIntegrityIssueKind::SiblingMessageCycle,
+    ];
+
+    /// The name of the kind, as serialized.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThreadIntegrityIssueKind::MultipleActiveLeaves => "multiple_active_leaves",
+            ThreadIntegrityIssueKind::BrokenActiveThread => "broken_active_thread",
+            ThreadIntegrityIssueKind::DanglingPreviousMessage => "dangling_previous_message",
+            ThreadIntegrityIssueKind::DanglingSiblingMessage => "dangling_sibling_message",
+            ThreadIntegrityIssueKind::PreviousMessageCycle => "previous_message_cycle",
+            ThreadIntegrityIssueKind::SiblingMessageCycle => "sibling_message_cycle",
+        }
+    }
+}
+
+/// A corruption found in the thread of a chat
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ThreadIntegrityIssue {
+    pub kind: ThreadIntegrityIssueKind,
+    /// The messages involved, oldest first: the message holding a dangling link, the messages
+    /// of a cycle, the leaves of the active thread, or the messages of the active thread that
+    /// follow a message outside of it
+    pub message_ids: Vec<Uuid>,
+    /// The message a dangling link points to
+    #[schema(nullable = false)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linked_message_id: Option<Uuid>,
+}
+
+/// Result of the integrity check of the thread of a chat
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ThreadIntegrityReport {
+    pub chat_id: Uuid,
+    /// Number of messages in the chat
+    pub message_count: usize,
+    /// The corruptions found, empty if the thread is intact
+    pub issues: Vec<ThreadIntegrityIssue>,
+}
+
+impl ThreadIntegrityReport {
+    /// Whether no corruption was found.
+    pub fn is_intact(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// A change to a message that repairs the thread of its chat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadRepairAction {
+    /// Set the previous message to `null`, making the message the start of a thread
+    DetachPreviousMessage,
+    /// Set the sibling message to `null`, making the message the first of its siblings
+    DetachSiblingMessage,
+    /// Mark the message as part of the active thread
+    Activate,
+    /// Mark the message as not part of the active thread
+    Deactivate,
+}
+
+/// A planned or applied change of the repair of a thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ThreadRepairChange {
+    pub message_id: Uuid,
+    pub action: ThreadRepairAction,
+}
+
+/// The links between the messages of a chat.
+struct ThreadLinks {
+    /// Message IDs, oldest first
+    order: Vec<Uuid>,
+    previous: HashMap<Uuid, Option<Uuid>>,
+    sibling: HashMap<Uuid, Option<Uuid>>,
+    active: HashMap<Uuid, bool>,
+}
+
+#[derive(Clone, Copy)]
+enum LinkType {
+    Previous,
+    Sibling,
+}
+
+impl ThreadLinks {
+    fn new(messages: &[messages::Model]) -> Self {
+        let mut sorted: Vec<&messages::Model> = messages.iter().collect();
+        sorted.sort_by_key(|message| (message.created_at, message.id));
+        Self {
+            order: sorted.iter().map(|message| message.id).collect(),
+            previous: sorted
+                .iter()
+                .map(|message| (message.id, message.previous_message_id))
+                .collect(),
+            sibling: sorted
+                .iter()
+                .map(|message| (message.id, message.sibling_message_id))
+                .collect(),
+            active: sorted
+                .iter()
+                .map(|message| (message.id, message.is_message_in_active_thread))
+                .collect(),
+        }
+    }
+
+    fn links(&self, link_type: LinkType) -> &HashMap<Uuid, Option<Uuid>> {
+        match link_type {
+            LinkType::Previous => &self.previous,
+            LinkType::Sibling => &self.sibling,
+        }
+    }
+
+    fn detach(&mut self, link_type: LinkType, message_id: Uuid) {
+        match link_type {
+            LinkType::Previous => self.previous.insert(message_id, None),
+            LinkType::Sibling => self.sibling.insert(message_id, None),
+        };
+    }
+
+    /// The linked message, if it exists in the chat.
+    fn linked(&self, link_type: LinkType, message_id: Uuid) -> Option<Uuid> {
+        self.links(link_type)
+            .get(&message_id)
+            .copied()
+            .flatten()
+            .filter(|linked_id| self.active.contains_key(linked_id))
+    }
+
+    /// Messages linking to a message that does not exist in the chat, with that message.
+    fn dangling(&self, link_type: LinkType) -> Vec<(Uuid, Uuid)> {
+        self.order
+            .iter()
+            .filter_map(|message_id| {
+                let linked_id = self.links(link_type)[message_id]?;
+                (!self.active.contains_key(&linked_id)).then_some((*message_id, linked_id))
+            })
+            .collect()
+    }
+
+    /// The cycles formed by following the links, each with its messages oldest first.
+    fn cycles(&self, link_type: LinkType) -> Vec<Vec<Uuid>> {
+        let position: HashMap<Uuid, usize> = self
+            .order
+            .iter()
+            .enumerate()
+            .map(|(position, message_id)| (*message_id, position))
+            .collect();
+        let mut visited = HashSet::new();
+        let mut cycles = vec![];
+        for start in &self.order {
+            let mut path: Vec<Uuid> = vec![];
+            let mut current = Some(*start);
+            while let Some(message_id) = current {
+                if let Some(index) = path.iter().position(|id| *id == message_id) {
+                    let mut cycle = path[index..].to_vec();
+                    cycle.sort_by_key(|id| position[id]);
+                    cycles.push(cycle);
+                    break;
+                }
+                if !visited.insert(message_id) {
+                    break;
+                }
+                path.push(message_id);
+                current = self.linked(link_type, message_id);
+            }
+        }
+        cycles
+    }
+
+    /// Messages of the active thread that are not followed by another message of it, oldest
+    /// first.
+    fn active_leaves(&self) -> Vec<Uuid> {
+        let active_predecessors: HashSet<Uuid> = self
+            .order
+            .iter()
+            .filter(|message_id| self.active[*message_id])
+            .filter_map(|message_id| self.linked(LinkType::Previous, *message_id))
+            .collect();
+        self.order
+            .iter()
+            .filter(|message_id| {
+                self.active[*message_id] && !active_predecessors.contains(*message_id)
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Messages that are not followed by another message, oldest first.
+    fn leaves(&self) -> Vec<Uuid> {
+        let predecessors: HashSet<Uuid> = self
+            .order
+            .iter()
+            .filter_map(|message_id| self.linked(LinkType::Previous, *message_id))
+            .collect();
+        self.order
+            .iter()
+            .filter(|message_id| !predecessors.contains(*message_id))
+            .copied()
+            .collect()
+    }
+}
+
+const DANGLING_ISSUES: [(LinkType, ThreadIntegrityIssueKind); 2] = [
+    (
+        LinkType::Previous,
+        ThreadIntegrityIssueKind::DanglingPreviousMessage,
+    ),
+    (
+        LinkType::Sibling,
+        ThreadIntegrityIssueKind::DanglingSiblingMessage,
+    ),
+];
+
+const CYCLE_ISSUES: [(LinkType, ThreadIntegrityIssueKind); 2] = [
+    (
+        LinkType::Previous,
+        ThreadIntegrityIssueKind::PreviousMessageCycle,
+    ),
+    (
+        LinkType::Sibling,
+        ThreadIntegrityIssueKind::SiblingMessageCycle,
+    ),
+];
+
+/// Check the messages of a chat for corruptions of its thread.
+pub fn analyze_thread_integrity(
+    chat_id: Uuid,
+    messages: &[messages::Model],
+) -> ThreadIntegrityReport {
+    let links = ThreadLinks::new(messages);
+    let mut issues = vec![];
+
+    for (link_type, kind) in DANGLING_ISSUES {
+        issues.extend(
+            links
+                .dangling(link_type)
+                .into_iter()
+                .map(|(message_id, linked_id)| ThreadIntegrityIssue {
+                    kind,
+                    message_ids: vec![message_id],
+                    linked_message_id: Some(linked_id),
+                }),
+        );
+    }
+    for (link_type, kind) in CYCLE_ISSUES {
+        issues.extend(
+            links
+                .cycles(link_type)
+                .into_iter()
+                .map(|cycle| ThreadIntegrityIssue {
+                    kind,
+                    message_ids: cycle,
+                    linked_message_id: None,
+                }),
+        );
+    }
+
+    let active_leaves = links.active_leaves();
+    if active_leaves.len() > 1 {
+        issues.push(ThreadIntegrityIssue {
+            kind: ThreadIntegrityIssueKind::MultipleActiveLeaves,
+            message_ids: active_leaves,
+            linked_message_id: None,
+        });
+    }
+    let following_inactive: Vec<Uuid> = links
+        .order
+        .iter()
+        .filter(|message_id| links.active[*message_id])
+        .filter(|message_id| {
+            links
+                .linked(LinkType::Previous, **message_id)
+                .is_some_and(|previous_id| !links.active[&previous_id])
+        })
+        .copied()
+        .collect();
+    let has_active_thread = links.active.values().any(|active| *active);
+    if !following_inactive.is_empty() || (!links.order.is_empty() && !has_active_thread) {
+        issues.push(ThreadIntegrityIssue {
+            kind: ThreadIntegrityIssueKind::BrokenActiveThread,
+            message_ids: following_inactive,
+            linked_message_id: None,
+        });
+    }
+
+    ThreadIntegrityReport {
+        chat_id,
+        message_count: messages.len(),
+        issues,
+    }
+}
+
+/// Plan the changes that repair the thread of a chat, in the order they are applied.
+///
+/// - Links to messages that don't exist in the chat are detached.
+/// - Cycles are broken at their oldest message, whose link is detached.
+/// - The most recent leaf of the active thread (or of the chat, if no message is active) becomes
+///   the end of the active thread, and exactly the messages on the path to it are marked as
+///   active.
+///
+/// Returns no changes for an intact thread.
+pub fn plan_thread_repair(messages: &[messages::Model]) -> Vec<ThreadRepairChange> {
+    let mut links = ThreadLinks::new(messages);
+    let mut changes = vec![];
+
+    for (link_type, action) in [
+        (
+            LinkType::Previous,
+            ThreadRepairAction::DetachPreviousMessage,
+        ),
+        (LinkType::Sibling, ThreadRepairAction::DetachSiblingMessage),
+    ] {
+        let mut detached: Vec<Uuid> = links
+            .dangling(link_type)
+            .into_iter()
+            .map(|(message_id, _)| message_id)
+            .collect();
+        detached.extend(links.cycles(link_type).into_iter().map(|cycle| cycle[0]));
+        for message_id in detached {
+            links.detach(link_type, message_id);
+            changes.push(ThreadRepairChange { message_id, action });
+        }
+    }
+
+    let active_leaves = links.active_leaves();
+    let candidates = if active_leaves.is_empty() {
+        links.leaves()
+    } else {
+        active_leaves
+    };
+    // Leaves are ordered oldest first
+    let Some(leaf_id) = candidates.last().copied() else {
+        return changes;
+    };
+    let mut active_thread = HashSet::new();
+    let mut current = Some(leaf_id);
+    while let Some(message_id) = current {
+        if !active_thread.insert(message_id) {
+            break;
+        }
+        current = links.linked(LinkType::Previous, message_id);
+    }
+    for message_id in &links.order {
+        let should_be_active = active_thread.contains(message_id);
+        if links.active[message_id] != should_be_active {
+            changes.push(ThreadRepairChange {
+                message_id: *message_id,
+                action: if should_be_active {
+                    ThreadRepairAction::Activate
+                } else {
+                    ThreadRepairAction::Deactivate
+                },
+            });
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone, Utc};
+    use serde_json::json;
+
+    /// Messages of a chat, created one minute apart in the order they are added.
+    struct ChatFixture {
+        chat_id: Uuid,
+        messages: Vec<messages::Model>,
+    }
+
+    impl ChatFixture {
+        fn new() -> Self {
+            Self {
+                chat_id: Uuid::new_v4(),
+                messages: vec![],
+            }
+        }
+
+        fn add(&mut self, previous: Option<Uuid>, sibling: Option<Uuid>, active: bool) -> Uuid {
+            let id = Uuid::new_v4();
+            let created_at = (Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()
+                + Duration::minutes(self.messages.len() as i64))
+            .into();
+            self.messages.push(messages::Model {
+                id,
+                chat_id: self.chat_id,
+                raw_message: json!({ "role": "user", "content": [] }),
+                created_at,
+                updated_at: created_at,
+                previous_message_id: previous,
+                sibling_message_id: sibling,
+                is_message_in_active_thread: active,
+                generation_input_messages: None,
+                input_file_uploads: None,
+                generation_parameters: None,
+                generation_metadata: None,
+                input_parameters: None,
+                is_welcome_message: false,
+                content_draft: None,
+            });
+            id
+        }
+
+        fn message_mut(&mut self, id: Uuid) -> &mut messages::Model {
+            self.messages
+                .iter_mut()
+                .find(|message| message.id == id)
+                .unwrap()
+        }
+
+        fn report(&self) -> ThreadIntegrityReport {
+            analyze_thread_integrity(self.chat_id, &self.messages)
+        }
+
+        fn kinds(&self) -> Vec<ThreadIntegrityIssueKind> {
+            self.report()
+                .issues
+                .into_iter()
+                .map(|issue| issue.kind)
+                .collect()
+        }
+
+        /// Apply the planned repair, like `repair_thread_integrity` does in the database.
+        fn repair(&mut self) -> Vec<ThreadRepairChange> {
+            let changes = plan_thread_repair(&self.messages);
+            for change in &changes {
+                let message = self.message_mut(change.message_id);
+                match change.action {
+                    ThreadRepairAction::DetachPreviousMessage => message.previous_message_id = None,
+                    ThreadRepairAction::DetachSiblingMessage => message.sibling_message_id = None,
+                    ThreadRepairAction::Activate => message.is_message_in_active_thread = true,
+                    ThreadRepairAction::Deactivate => message.is_message_in_active_thread = false,
+                }
+            }
+            changes
+        }
+
+        fn active_ids(&self) -> Vec<Uuid> {
+            self.messages
+                .iter()
+                .filter(|message| message.is_message_in_active_thread)
+                .map(|message| message.id)
+                .collect()
+        }
+
+        /// Assert that the thread is a valid tree: the active thread is a single path ending in
+        /// a message without a previous message, and at most one message of every sibling group
+        /// is active.
+        fn assert_valid_tree(&self) {
+            assert!(self.report().is_intact(), "{:?}", self.report());
+            let by_id: HashMap<Uuid, &messages::Model> = self
+                .messages
+                .iter()
+                .map(|message| (message.id, message))
+                .collect();
+            let active = self.active_ids();
+            let leaves: Vec<&Uuid> = active
+                .iter()
+                .filter(|id| {
+                    !active
+                        .iter()
+                        .any(|other| by_id[other].previous_message_id == Some(**id))
+                })
+                .collect();
+            assert_eq!(leaves.len(), 1);
+            let mut path = vec![];
+            let mut current = Some(*leaves[0]);
+            while let Some(id) = current {
+                assert!(!path.contains(&id), "The active thread loops");
+                path.push(id);
+                current = by_id[&id].previous_message_id;
+            }
+            assert_eq!(path.len(), active.len());
+            let mut active_groups = HashSet::new();
+            for id in &active {
+                let group = by_id[id].sibling_message_id.unwrap_or(*id);
+                assert!(active_groups.insert(group), "Two active siblings");
+            }
+        }
+    }
+
+    /// A user message, a regenerated answer and its replaced original, and a follow-up.
+    fn edited_chat() -> (ChatFixture, [Uuid; 4]) {
+        let mut chat = ChatFixture::new();
+        let question = chat.add(None, None, true);
+        let original_answer = chat.add(Some(question), None, false);
+        let answer = chat.add(Some(question), Some(original_answer), true);
+        let follow_up = chat.add(Some(answer), None, true);
+        (chat, [question, original_answer, answer, follow_up])
+    }
+
+    #[test]
+    fn intact_thread_has_no_issues_and_needs_no_repair() {
+        let (chat, _) = edited_chat();
+        let report = chat.report();
+        assert!(report.is_intact());
+        assert_eq!(report.message_count, 4);
+        assert!(plan_thread_repair(&chat.messages).is_empty());
+        chat.assert_valid_tree();
+    }
+
+    #[test]
+    fn empty_chat_is_intact() {
+        let chat = ChatFixture::new();
+        assert!(chat.report().is_intact());
+        assert!(plan_thread_repair(&chat.messages).is_empty());
+    }
+
+    #[test]
+    fn multiple_active_leaves_keep_the_most_recent_one() {
+        let (mut chat, [question, original_answer, answer, follow_up]) = edited_chat();
+        // A crash in the middle of the regeneration left both answers active
+        chat.message_mut(original_answer)
+            .is_message_in_active_thread = true;
+        assert_eq!(
+            chat.report().issues,
+            vec![ThreadIntegrityIssue {
+                kind: ThreadIntegrityIssueKind::MultipleActiveLeaves,
+                message_ids: vec![original_answer, follow_up],
+                linked_message_id: None,
+            }]
+        );
+
+        assert_eq!(
+            chat.repair(),
+            vec![ThreadRepairChange {
+                message_id: original_answer,
+                action: ThreadRepairAction::Deactivate,
+            }]
+        );
+        chat.assert_valid_tree();
+        assert_eq!(chat.active_ids(), vec![question, answer, follow_up]);
+    }
+
+    #[test]
+    fn active_message_after_inactive_one_is_reconnected() {
+        let (mut chat, [question, _, answer, follow_up]) = edited_chat();
+        chat.message_mut(answer).is_message_in_active_thread = false;
+        assert_eq!(
+            chat.kinds(),
+            vec![
+                ThreadIntegrityIssueKind::MultipleActiveLeaves,
+                ThreadIntegrityIssueKind::BrokenActiveThread,
+            ]
+        );
+        assert_eq!(chat.report().issues[1].message_ids, vec![follow_up]);
+
+        chat.repair();
+        chat.assert_valid_tree();
+        assert_eq!(chat.active_ids(), vec![question, answer, follow_up]);
+    }
+
+    #[test]
+    fn chat_without_active_thread_activates_the_most_recent_leaf() {
+        let (mut chat, [question, _, answer, follow_up]) = edited_chat();
+        for message in &mut chat.messages {
+            message.is_message_in_active_thread = false;
+        }
+        assert_eq!(
+            chat.kinds(),
+            vec![ThreadIntegrityIssueKind::BrokenActiveThread]
+        );
+
+        chat.repair();
+        chat.assert_valid_tree();
+        assert_eq!(chat.active_ids(), vec![question, answer, follow_up]);
+    }
+
+    #[test]
+    fn dangling_previous_message_is_detached() {
+        let (mut chat, [question, ..]) = edited_chat();
+        let other_chat_message = Uuid::new_v4();
+        chat.message_mut(question).previous_message_id = Some(other_chat_message);
+        assert_eq!(
+            chat.report().issues,
+            vec![ThreadIntegrityIssue {
+                kind: ThreadIntegrityIssueKind::DanglingPreviousMessage,
+                message_ids: vec![question],
+                linked_message_id: Some(other_chat_message),
+            }]
+        );
+
+        assert_eq!(
+            chat.repair(),
+            vec![ThreadRepairChange {
+                message_id: question,
+                action: ThreadRepairAction::DetachPreviousMessage,
+            }]
+        );
+        chat.assert_valid_tree();
+    }
+
+    #[test]
+    fn dangling_previous_message_in_the_middle_splits_the_thread() {
+        let (mut chat, [_, _, answer, follow_up]) = edited_chat();
+        chat.message_mut(answer).previous_message_id = Some(Uuid::new_v4());
+        assert_eq!(
+            chat.kinds(),
+            vec![
+                ThreadIntegrityIssueKind::DanglingPreviousMessage,
+                ThreadIntegrityIssueKind::MultipleActiveLeaves,
+            ]
+        );
+
+        chat.repair();
+        chat.assert_valid_tree();
+        // The question is cut off from the most recent part of the thread
+        assert_eq!(chat.active_ids(), vec![answer, follow_up]);
+    }
+
+    #[test]
+    fn dangling_sibling_message_is_detached() {
+        let (mut chat, [_, _, answer, _]) = edited_chat();
+        let missing = Uuid::new_v4();
+        chat.message_mut(answer).sibling_message_id = Some(missing);
+        assert_eq!(
+            chat.report().issues,
+            vec![ThreadIntegrityIssue {
+                kind: ThreadIntegrityIssueKind::DanglingSiblingMessage,
+                message_ids: vec![answer],
+                linked_message_id: Some(missing),
+            }]
+        );
+
+        assert_eq!(
+            chat.repair(),
+            vec![ThreadRepairChange {
+                message_id: answer,
+                action: ThreadRepairAction::DetachSiblingMessage,
+            }]
+        );
+        chat.assert_valid_tree();
+    }
+
+    #[test]
+    fn previous_message_cycle_is_broken_at_the_oldest_message() {
+        let (mut chat, [question, _, answer, follow_up]) = edited_chat();
+        chat.message_mut(question).previous_message_id = Some(follow_up);
+        assert_eq!(
+            chat.report().issues,
+            vec![ThreadIntegrityIssue {
+                kind: ThreadIntegrityIssueKind::PreviousMessageCycle,
+                message_ids: vec![question, answer, follow_up],
+                linked_message_id: None,
+            }]
+        );
+
+        assert_eq!(
+            chat.repair(),
+            vec![ThreadRepairChange {
+                message_id: question,
+                action: ThreadRepairAction::DetachPreviousMessage,
+            }]
+        );
+        chat.assert_valid_tree();
+        assert_eq!(chat.active_ids(), vec![question, answer, follow_up]);
+    }
+
+    #[test]
+    fn sibling_message_cycle_is_broken_at_the_oldest_message() {
+        let (mut chat, [_, original_answer, answer, _]) = edited_chat();
+        chat.message_mut(original_answer).sibling_message_id = Some(answer);
+        assert_eq!(
+            chat.report().issues,
+            vec![ThreadIntegrityIssue {
+                kind: ThreadIntegrityIssueKind::SiblingMessageCycle,
+                message_ids: vec![original_answer, answer],
+                linked_message_id: None,
+            }]
+        );
+
+        assert_eq!(
+            chat.repair(),
+            vec![ThreadRepairChange {
+                message_id: original_answer,
+                action: ThreadRepairAction::DetachSiblingMessage,
+            }]
+        );
+        chat.assert_valid_tree();
+    }
+
+    #[test]
+    fn self_referencing_message_is_a_cycle() {
+        let mut chat = ChatFixture::new();
+        let message = chat.add(None, None, true);
+        chat.message_mut(message).previous_message_id = Some(message);
+        chat.message_mut(message).sibling_message_id = Some(message);
+        assert_eq!(
+            chat.kinds(),
+            vec![
+                ThreadIntegrityIssueKind::PreviousMessageCycle,
+                ThreadIntegrityIssueKind::SiblingMessageCycle,
+            ]
+        );
+
+        chat.repair();
+        chat.assert_valid_tree();
+    }
+
+    #[test]
+    fn combined_corruptions_converge_in_one_repair() {
+        let (mut chat, [question, original_answer, answer, follow_up]) = edited_chat();
+        let stray = chat.add(Some(Uuid::new_v4()), None, true);
+        chat.message_mut(original_answer)
+            .is_message_in_active_thread = true;
+        chat.message_mut(original_answer).sibling_message_id = Some(answer);
+        chat.message_mut(question).previous_message_id = Some(follow_up);
+        assert!(chat.kinds().len() >= 4, "{:?}", chat.kinds());
+
+        assert!(!chat.repair().is_empty());
+        chat.assert_valid_tree();
+        assert!(plan_thread_repair(&chat.messages).is_empty());
+        // The stray message is the most recent leaf of the active thread
+        assert_eq!(chat.active_ids(), vec![stray]);
+    }
+}
@@ -1,12 +1,21 @@
 pub mod assistant;
 pub mod assistant_hub;
 pub mod chat;
+pub mod chat_read_state;
 pub mod file_capability;
 pub mod file_upload;
+pub mod label;
 pub mod mcp_oauth;
 pub mod message;
 pub mod message_feedback;
+pub mod message_reaction;
+pub mod message_redaction;
+pub mod message_search;
+pub mod message_thread_integrity;
+pub mod notification;
+pub mod ownership_transfer;
 pub mod permissions;
+pub mod scheduled_message;
 pub mod share_grant;
 pub mod share_link;
 pub mod user;
@@ -65,9 +74,10 @@ pub mod pagination {
 }
 
 use eyre::{Result, eyre};
-use sea_orm::{ConnectionTrait, DatabaseConnection};
+use sea_orm::{ColumnTrait, Condition, ConnectionTrait, DatabaseConnection};
 
 use crate::metrics_constants::POSTGRES_QUERY_VERIFY_LATEST_MIGRATION;
+use crate::policy::types::Subject;
 use crate::query_metrics::named_statement_from_sql_and_values;
 
 /// The latest migration change hash from the sqitch deployment
@@ -112,6 +122,17 @@ pub async fn ensure_latest_migration(conn: &DatabaseConnection) -> Result<()> {
     Ok(())
 }
 
+/// Condition restricting a query to the rows of the organization of the subject.
+///
+/// Rows of other organizations are filtered out at the query level, so that they are
+/// indistinguishable from rows that don't exist.
+pub fn organization_condition(column: impl ColumnTrait, subject: &Subject) -> Condition {
+    match subject.organization_id() {
+        Some(organization_id) => Condition::all().add(column.eq(organization_id)),
+        None => Condition::all().add(column.is_null()),
+    }
+}
+
 /// Test module for pagination utilities
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,303 @@
+//! In-app notifications of users.
+//!
+//! Notifications are produced for events that happen while the user is not necessarily looking,
+//! like a resource being shared with them, or a scheduled message being answered. The kind of a
+//! notification is stored next to its payload, so that the payload can be deserialized into the
+//! matching type.
+
+use crate::db::entity::prelude::*;
+use crate::db::entity::{notifications, share_grants};
+use crate::models::share_grant::ShareGrantPermission;
+use chrono::{DateTime, Utc};
+use eyre::{Report, eyre};
+use sea_orm::prelude::*;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ConnectionTrait, DatabaseConnection, Order, QueryOrder,
+    QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::pagination;
+
+/// Kind of a notification, which determines the type of its payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// A chat or assistant was shared with the user. Payload: `ShareGrantCreatedPayload`.
+    ShareGrantCreated,
+    /// A scheduled message of the user was submitted and answered. Payload:
+    /// `ScheduledMessageSucceededPayload`.
+    ScheduledMessageSucceeded,
+    /// All attempts to submit a scheduled message of the user failed. Payload:
+    /// `ScheduledMessageFailedPayload`.
+    ScheduledMessageFailed,
+    /// The generation of an answer in a chat of the user failed while nobody was watching it.
+    /// Payload: `GenerationFailedPayload`.
+    GenerationFailed,
+}
+
+impl NotificationKind {
+    pub fn as_db_str(self) -> &'static str {
+        match self {
+            Self::ShareGrantCreated => "share_grant_created",
+            Self::ScheduledMessageSucceeded => "scheduled_message_succeeded",
+            Self::ScheduledMessageFailed => "scheduled_message_failed",
+            Self::GenerationFailed => "generation_failed",
+        }
+    }
+
+    pub fn from_db_str(kind: &str) -> Result<Self, Report> {
+        match kind {
+            "share_grant_created" => Ok(Self::ShareGrantCreated),
+            "scheduled_message_succeeded" => Ok(Self::ScheduledMessageSucceeded),
+            "scheduled_message_failed" => Ok(Self::ScheduledMessageFailed),
+            "generation_failed" => Ok(Self::GenerationFailed),
+            _ => Err(eyre!("Unknown notification kind '{}'", kind)),
+        }
+    }
+}
+
+/// Payload of a `share_grant_created` notification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ShareGrantCreatedPayload {
+    /// The ID of the share grant
+    pub share_grant_id: String,
+    /// The type of the shared resource (`chat` or `assistant`)
+    pub resource_type: String,
+    /// The ID of the shared resource
+    pub resource_id: String,
+    /// The ID of the user that shared the resource
+    pub shared_by_user_id: String,
+    pub permission: ShareGrantPermission,
+}
+
+/// Payload of a `scheduled_message_succeeded` notification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ScheduledMessageSucceededPayload {
+    /// The ID of the scheduled message
+    pub scheduled_message_id: String,
+    /// The chat the message was submitted to
+    pub chat_id: String,
+    /// The answer to the message
+    pub assistant_message_id: Option<String>,
+}
+
+/// Payload of a `scheduled_message_failed` notification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ScheduledMessageFailedPayload {
+    /// The ID of the scheduled message
+    pub scheduled_message_id: String,
+    /// The chat the message was submitted to, if it got that far
+    pub chat_id: Option<String>,
+    /// The error of the last attempt
+    pub error: String,
+}
+
+/// Payload of a `generation_failed` notification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct GenerationFailedPayload {
+    /// The chat in which the generation failed
+    pub chat_id: String,
+    /// The assistant message that was being generated, if it was created
+    pub message_id: Option<String>,
+}
+
+/// Payload of a notification, in the shape given by its kind.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum NotificationPayload {
+    ShareGrantCreated(ShareGrantCreatedPayload),
+    ScheduledMessageSucceeded(ScheduledMessageSucceededPayload),
+    ScheduledMessageFailed(ScheduledMessageFailedPayload),
+    GenerationFailed(GenerationFailedPayload),
+}
+
+impl NotificationPayload {
+    pub fn kind(&self) -> NotificationKind {
+        match self {
+            Self::ShareGrantCreated(_) => NotificationKind::ShareGrantCreated,
+            Self::ScheduledMessageSucceeded(_) => NotificationKind::ScheduledMessageSucceeded,
+            Self::ScheduledMessageFailed(_) => NotificationKind::ScheduledMessageFailed,
+            Self::GenerationFailed(_) => NotificationKind::GenerationFailed,
+        }
+    }
+
+    /// Deserialize a stored payload into the type of its kind.
+    pub fn from_stored(kind: NotificationKind, payload: JsonValue) -> Result<Self, Report> {
+        Ok(match kind {
+            NotificationKind::ShareGrantCreated => {
+                Self::ShareGrantCreated(serde_json::from_value(payload)?)
+            }
+            NotificationKind::ScheduledMessageSucceeded => {
+                Self::ScheduledMessageSucceeded(serde_json::from_value(payload)?)
+            }
+            NotificationKind::ScheduledMessageFailed => {
+                Self::ScheduledMessageFailed(serde_json::from_value(payload)?)
+            }
+            NotificationKind::GenerationFailed => {
+                Self::GenerationFailed(serde_json::from_value(payload)?)
+            }
+        })
+    }
+}
+
+/// Create a notification for a user.
+pub async fn create_notification<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+    payload: &NotificationPayload,
+) -> Result<notifications::Model, Report> {
+    Ok(notifications::ActiveModel {
+        user_id: ActiveValue::Set(user_id),
+        kind: ActiveValue::Set(payload.kind().as_db_str().to_string()),
+        payload: ActiveValue::Set(serde_json::to_value(payload)?),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?)
+}
+
+/// Notify the grantee of a newly created share grant.
+///
+/// Only grantees that are users referenced by their ID are notified. Organization user IDs and
+/// groups can't be resolved to users of Erato, and users don't get notified about sharing with
+/// themselves.
+pub async fn notify_share_grant_created<C: ConnectionTrait>(
+    conn: &C,
+    grant: &share_grants::Model,
+    shared_by_user_id: &str,
+) -> Result<(), Report> {
+    if grant.subject_type != "user"
+        || grant.subject_id_type != "id"
+        || grant.subject_id == shared_by_user_id
+    {
+        return Ok(());
+    }
+    let Ok(grantee_user_id) = Uuid::parse_str(&grant.subject_id) else {
+        return Ok(());
+    };
+    if Users::find_by_id(grantee_user_id)
+        .one(conn)
+        .await?
+        .is_none()
+    {
+        return Ok(());
+    }
+
+    create_notification(
+        conn,
+        grantee_user_id,
+        &NotificationPayload::ShareGrantCreated(ShareGrantCreatedPayload {
+            share_grant_id: grant.id.to_string(),
+            resource_type: grant.resource_type.clone(),
+            resource_id: grant.resource_id.clone(),
+            shared_by_user_id: shared_by_user_id.to_string(),
+            permission: ShareGrantPermission::parse(&grant.permission).unwrap_or_default(),
+        }),
+    )
+    .await?;
+    Ok(())
+}
+
+/// List the notifications of a user, unread ones first and the newest first within each group.
+///
+/// Returns the page of notifications, the total number of notifications of the user, and whether
+/// there are more after the page.
+pub async fn list_notifications(
+    conn: &DatabaseConnection,
+    user_id: Uuid,
+    limit: u64,
+    offset: u64,
+) -> Result<(Vec<notifications::Model>, u64, bool), Report> {
+    let notifications = Notifications::find()
+        .filter(notifications::Column::UserId.eq(user_id))
+        .order_by(notifications::Column::ReadAt.is_null(), Order::Desc)
+        .order_by(notifications::Column::CreatedAt, Order::Desc)
+        .order_by(notifications::Column::Id, Order::Desc)
+        .limit(limit)
+        .offset(offset)
+        .all(conn)
+        .await?;
+
+    let (total_count, has_more) =
+        pagination::calculate_total_count(offset, limit, notifications.len(), || async {
+            Notifications::find()
+                .filter(notifications::Column::UserId.eq(user_id))
+                .count(conn)
+                .await
+        })
+        .await?;
+
+    Ok((notifications, total_count, has_more))
+}
+
+/// Count the unread notifications of a user.
+pub async fn count_unread_notifications(
+    conn: &DatabaseConnection,
+    user_id: Uuid,
+) -> Result<u64, Report> {
+    Ok(Notifications::find()
+        .filter(notifications::Column::UserId.eq(user_id))
+        .filter(notifications::Column::ReadAt.is_null())
+        .count(conn)
+        .await?)
+}
+
+/// Mark a notification of a user as read.
+///
+/// Notifications that were already read keep the time they were first read at. Notifications of
+/// other users are reported as not found, so their existence is not leaked.
+pub async fn mark_notification_read(
+    conn: &DatabaseConnection,
+    user_id: Uuid,
+    notification_id: Uuid,
+) -> Result<notifications::Model, Report> {
+    let notification = Notifications::find_by_id(notification_id)
+        .filter(notifications::Column::UserId.eq(user_id))
+        .one(conn)
+        .await?
+        .ok_or_else(|| eyre!("Notification {} not found", notification_id))?;
+    if notification.read_at.is_some() {
+        return Ok(notification);
+    }
+
+    Ok(notifications::ActiveModel {
+        id: ActiveValue::Unchanged(notification.id),
+        read_at: ActiveValue::Set(Some(Utc::now().fixed_offset())),
+        ..Default::default()
+    }
+    .update(conn)
+    .await?)
+}
+
+/// Mark all unread notifications of a user as read, and return how many were marked.
+pub async fn mark_all_notifications_read(
+    conn: &DatabaseConnection,
+    user_id: Uuid,
+) -> Result<u64, Report> {
+    Ok(Notifications::update_many()
+        .col_expr(
+            notifications::Column::ReadAt,
+            Expr::value(Utc::now().fixed_offset()),
+        )
+        .filter(notifications::Column::UserId.eq(user_id))
+        .filter(notifications::Column::ReadAt.is_null())
+        .exec(conn)
+        .await?
+        .rows_affected)
+}
+
+/// Delete the notifications that were read before `read_before`, and return how many were
+/// deleted.
+pub async fn delete_read_notifications(
+    conn: &DatabaseConnection,
+    read_before: DateTime<Utc>,
+) -> Result<u64, Report> {
+    Ok(Notifications::delete_many()
+        .filter(notifications::Column::ReadAt.lt(read_before.fixed_offset()))
+        .exec(conn)
+        .await?
+        .rows_affected)
+}
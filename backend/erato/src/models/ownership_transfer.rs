@@ -0,0 +1,245 @@
+//! Transfers of chats and assistants to another owner, e.g. when their owner leaves the
+//! organization.
+//!
+//! Chats are transferred by [`crate::models::chat::transfer_chat_ownership`], assistants by
+//! [`transfer_assistant_ownership`]. Both record the transfer in `ownership_transfers`, and an
+//! invalidation of the policy data of all instances.
+
+use crate::db::entity::prelude::*;
+use crate::db::entity::{assistants, ownership_transfers, share_grants, users};
+use crate::models::share_grant::ShareGrantPermission;
+use crate::services::policy_invalidations::{PolicyInvalidationReason, record_policy_invalidation};
+use chrono::Utc;
+use eyre::{Report, WrapErr, eyre};
+use sea_orm::prelude::*;
+use sea_orm::{
+    ActiveValue, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect,
+    TransactionTrait,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Options of the transfer of a chat or an assistant
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OwnershipTransferOptions {
+    /// Share the resource with the previous owner for reading
+    pub keep_previous_owner_access: bool,
+    /// Attribute the messages the previous owner sent in a chat to the new owner
+    pub rewrite_attribution: bool,
+    /// Report the changes without applying them
+    pub dry_run: bool,
+}
+
+/// Find the new owner of a resource, who has to belong to the organization of the resource.
+///
+/// Only users that have signed in at least once exist, so resources can't be transferred to
+/// members of the organization directory that never used Erato. No stub user is created for them:
+/// users are identified by the issuer and subject of their ID token, which the directory doesn't
+/// know (Entra ID subjects differ per application), so the stub would never be signed in to.
+pub(crate) async fn find_new_owner<C: ConnectionTrait>(
+    conn: &C,
+    new_owner_user_id: &Uuid,
+    resource_type: &str,
+    resource_id: &Uuid,
+    resource_organization_id: Option<&str>,
+) -> Result<users::Model, Report> {
+    let new_owner = Users::find_by_id(*new_owner_user_id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| eyre!("User with ID {} not found", new_owner_user_id))?;
+    if resource_organization_id.is_some()
+        && resource_organization_id != new_owner.organization_id.as_deref()
+    {
+        return Err(eyre!(
+            "User {} does not belong to the organization of {} {}",
+            new_owner.id,
+            resource_type,
+            resource_id
+        ));
+    }
+    Ok(new_owner)
+}
+
+/// IDs of the share grants of a resource to a single user, which are redundant once the user
+/// owns the resource.
+pub(crate) async fn user_share_grant_ids<C: ConnectionTrait>(
+    conn: &C,
+    resource_type: &str,
+    resource_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<Vec<Uuid>, Report> {
+    Ok(ShareGrants::find()
+        .filter(share_grants::Column::ResourceType.eq(resource_type))
+        .filter(share_grants::Column::ResourceId.eq(resource_id.to_string()))
+        .filter(share_grants::Column::SubjectType.eq("user"))
+        .filter(share_grants::Column::SubjectIdType.eq("id"))
+        .filter(share_grants::Column::SubjectId.eq(user_id.to_string()))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|grant| grant.id)
+        .collect())
+}
+
+/// Share a resource with its previous owner for reading.
+pub(crate) async fn grant_previous_owner_read_access<C: ConnectionTrait>(
+    conn: &C,
+    resource_type: &str,
+    resource_id: &Uuid,
+    previous_owner_user_id: &str,
+    organization_id: Option<String>,
+) -> Result<(), Report> {
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    ShareGrants::insert(share_grants::ActiveModel {
+        id: ActiveValue::Set(Uuid::new_v4()),
+        resource_type: ActiveValue::Set(resource_type.to_string()),
+        resource_id: ActiveValue::Set(resource_id.to_string()),
+        subject_type: ActiveValue::Set("user".to_string()),
+        subject_id_type: ActiveValue::Set("id".to_string()),
+        subject_id: ActiveValue::Set(previous_owner_user_id.to_string()),
+        role: ActiveValue::Set("viewer".to_string()),
+        permission: ActiveValue::Set(ShareGrantPermission::Read.as_str().to_string()),
+        expires_at: ActiveValue::Set(None),
+        organization_id: ActiveValue::Set(organization_id),
+        created_at: ActiveValue::Set(now),
+        updated_at: ActiveValue::Set(now),
+    })
+    .exec(conn)
+    .await
+    .wrap_err("Failed to share the resource with its previous owner")?;
+    Ok(())
+}
+
+/// Record a transfer in the audit log.
+///
+/// `actor_user_id` is the user that transferred the resource, or `None` for the CLI.
+pub(crate) async fn record_ownership_transfer<C: ConnectionTrait>(
+    conn: &C,
+    resource_type: &str,
+    resource_id: &Uuid,
+    previous_owner_user_id: &str,
+    new_owner_user_id: &str,
+    actor_user_id: Option<&Uuid>,
+    options: &OwnershipTransferOptions,
+) -> Result<(), Report> {
+    OwnershipTransfers::insert(ownership_transfers::ActiveModel {
+        resource_type: ActiveValue::Set(resource_type.to_string()),
+        resource_id: ActiveValue::Set(*resource_id),
+        previous_owner_user_id: ActiveValue::Set(previous_owner_user_id.to_string()),
+        new_owner_user_id: ActiveValue::Set(new_owner_user_id.to_string()),
+        actor_user_id: ActiveValue::Set(actor_user_id.copied()),
+        keep_previous_owner_access: ActiveValue::Set(options.keep_previous_owner_access),
+        rewrite_attribution: ActiveValue::Set(options.rewrite_attribution),
+        ..Default::default()
+    })
+    .exec(conn)
+    .await
+    .wrap_err("Failed to record ownership transfer")?;
+    Ok(())
+}
+
+/// Result of the transfer of an assistant to another owner
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct AssistantOwnershipTransfer {
+    pub assistant_id: Uuid,
+    pub previous_owner_user_id: Uuid,
+    pub new_owner_user_id: Uuid,
+    /// Share grants of the assistant to the new owner, which are removed as the owner can access
+    /// the assistant anyway
+    pub removed_share_grant_ids: Vec<Uuid>,
+    /// Whether the assistant was shared with the previous owner for reading
+    pub previous_owner_access_kept: bool,
+}
+
+/// Transfer an assistant to another user.
+///
+/// Works like [`crate::models::chat::transfer_chat_ownership`]: `organization_id` restricts the
+/// lookup of the assistant to an organization (`None` for platform admins and the CLI), and with
+/// `dry_run` the changes are returned without applying them. Assistants have no messages, so
+/// `rewrite_attribution` has no effect.
+pub async fn transfer_assistant_ownership(
+    conn: &DatabaseConnection,
+    organization_id: Option<&str>,
+    assistant_id: &Uuid,
+    new_owner_user_id: &Uuid,
+    actor_user_id: Option<&Uuid>,
+    options: &OwnershipTransferOptions,
+) -> Result<AssistantOwnershipTransfer, Report> {
+    let txn = conn.begin().await?;
+    let mut query = Assistants::find_by_id(*assistant_id);
+    if let Some(organization_id) = organization_id {
+        query = query.filter(assistants::Column::OrganizationId.eq(organization_id));
+    }
+    let assistant = query
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or_else(|| eyre!("Assistant with ID {} not found", assistant_id))?;
+    let new_owner = find_new_owner(
+        &txn,
+        new_owner_user_id,
+        "assistant",
+        assistant_id,
+        assistant.organization_id.as_deref(),
+    )
+    .await?;
+    if assistant.owner_user_id == new_owner.id {
+        return Err(eyre!(
+            "Assistant {} is already owned by user {}",
+            assistant_id,
+            new_owner.id
+        ));
+    }
+
+    let transfer = AssistantOwnershipTransfer {
+        assistant_id: *assistant_id,
+        previous_owner_user_id: assistant.owner_user_id,
+        new_owner_user_id: new_owner.id,
+        removed_share_grant_ids: user_share_grant_ids(
+            &txn,
+            "assistant",
+            assistant_id,
+            &new_owner.id,
+        )
+        .await?,
+        previous_owner_access_kept: options.keep_previous_owner_access,
+    };
+    let organization_id = assistant.organization_id.clone();
+    let mut assistant_active: assistants::ActiveModel = assistant.into();
+    assistant_active.owner_user_id = ActiveValue::Set(new_owner.id);
+    assistant_active.update(&txn).await?;
+    if !transfer.removed_share_grant_ids.is_empty() {
+        ShareGrants::delete_many()
+            .filter(share_grants::Column::Id.is_in(transfer.removed_share_grant_ids.clone()))
+            .exec(&txn)
+            .await?;
+    }
+    if options.keep_previous_owner_access {
+        grant_previous_owner_read_access(
+            &txn,
+            "assistant",
+            assistant_id,
+            &transfer.previous_owner_user_id.to_string(),
+            organization_id,
+        )
+        .await?;
+    }
+    record_ownership_transfer(
+        &txn,
+        "assistant",
+        assistant_id,
+        &transfer.previous_owner_user_id.to_string(),
+        &transfer.new_owner_user_id.to_string(),
+        actor_user_id,
+        options,
+    )
+    .await?;
+    record_policy_invalidation(&txn, PolicyInvalidationReason::AssistantTransferred).await?;
+    if options.dry_run {
+        txn.rollback().await?;
+    } else {
+        txn.commit().await?;
+    }
+
+    Ok(transfer)
+}
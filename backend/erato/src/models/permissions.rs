@@ -7,22 +7,26 @@
 
 /// Returns whether the current user can edit a given chat.
 ///
-/// Current logic: only the chat owner can edit.
-///
-/// Future: Extend to collaborators/roles/policies as needed. Keep the
-/// signature stable so API layers stay unchanged.
-pub fn can_user_edit_chat(current_user_id: &str, owner_user_id: &str) -> bool {
-    current_user_id == owner_user_id
+/// Current logic: the chat owner, and users the chat was shared with via a
+/// share grant with `edit` permission (`has_edit_grant`), can edit.
+pub fn can_user_edit_chat(
+    current_user_id: &str,
+    owner_user_id: &str,
+    has_edit_grant: bool,
+) -> bool {
+    current_user_id == owner_user_id || has_edit_grant
 }
 
 /// Returns whether the current user can edit a given assistant.
 ///
-/// Current logic: only the assistant owner can edit.
-///
-/// Future: Extend to collaborators/roles/policies as needed. Keep the
-/// signature stable so API layers stay unchanged.
-pub fn can_user_edit_assistant(current_user_id: &str, owner_user_id: &str) -> bool {
-    current_user_id == owner_user_id
+/// Current logic: the assistant owner, and users the assistant was shared with
+/// via a share grant with `edit` permission (`has_edit_grant`), can edit.
+pub fn can_user_edit_assistant(
+    current_user_id: &str,
+    owner_user_id: &str,
+    has_edit_grant: bool,
+) -> bool {
+    current_user_id == owner_user_id || has_edit_grant
 }
 
 /// Placeholder for future, message-level permission checks.
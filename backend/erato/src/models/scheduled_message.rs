@@ -0,0 +1,209 @@
+//! Messages that users scheduled to be submitted at a later time.
+//!
+//! A scheduled message stores the submission together with the ID token claims of the user, so
+//! that the scheduler can submit it on behalf of the user once it is due. Due messages are
+//! claimed with `FOR UPDATE SKIP LOCKED`, so that every message is only submitted by one backend
+//! instance.
+
+use crate::db::entity::prelude::*;
+use crate::db::entity::scheduled_messages;
+use crate::metrics_constants::POSTGRES_QUERY_CLAIM_DUE_SCHEDULED_MESSAGES;
+use crate::query_metrics::named_statement_from_sql_and_values;
+use chrono::{DateTime, Utc};
+use eyre::{Report, eyre};
+use sea_orm::prelude::*;
+use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection, QueryOrder};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Time after which a message that is still running is assumed to be abandoned, e.g. because the
+/// instance that claimed it was stopped, and is claimed again.
+const RUNNING_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Status of a scheduled message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduledMessageStatus {
+    /// Waiting to be submitted, either for the first time or for a retry.
+    Pending,
+    /// Currently being submitted.
+    Running,
+    /// The message was submitted and answered.
+    Succeeded,
+    /// All attempts to submit the message failed.
+    Failed,
+}
+
+impl ScheduledMessageStatus {
+    pub fn as_db_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn from_db_str(status: &str) -> Result<Self, Report> {
+        match status {
+            "pending" => Ok(Self::Pending),
+            "running" => Ok(Self::Running),
+            "succeeded" => Ok(Self::Succeeded),
+            "failed" => Ok(Self::Failed),
+            _ => Err(eyre!("Unknown scheduled message status '{}'", status)),
+        }
+    }
+}
+
+/// Schedule a message of a user to be submitted at `run_at`.
+pub async fn create_scheduled_message(
+    conn: &DatabaseConnection,
+    owner_user_id: &Uuid,
+    request: JsonValue,
+    id_token_claims: JsonValue,
+    run_at: DateTime<Utc>,
+) -> Result<scheduled_messages::Model, Report> {
+    Ok(scheduled_messages::ActiveModel {
+        owner_user_id: ActiveValue::Set(*owner_user_id),
+        request: ActiveValue::Set(request),
+        id_token_claims: ActiveValue::Set(id_token_claims),
+        run_at: ActiveValue::Set(run_at.fixed_offset()),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?)
+}
+
+/// List the scheduled messages of a user, ordered by the time they are due.
+pub async fn list_scheduled_messages(
+    conn: &DatabaseConnection,
+    owner_user_id: &Uuid,
+) -> Result<Vec<scheduled_messages::Model>, Report> {
+    Ok(ScheduledMessages::find()
+        .filter(scheduled_messages::Column::OwnerUserId.eq(*owner_user_id))
+        .order_by_asc(scheduled_messages::Column::RunAt)
+        .order_by_asc(scheduled_messages::Column::Id)
+        .all(conn)
+        .await?)
+}
+
+/// Cancel a scheduled message of a user that has not been submitted yet.
+///
+/// Messages of other users are reported as not found, so their existence is not leaked.
+pub async fn cancel_scheduled_message(
+    conn: &DatabaseConnection,
+    owner_user_id: &Uuid,
+    scheduled_message_id: &Uuid,
+) -> Result<(), Report> {
+    let scheduled_message = ScheduledMessages::find_by_id(*scheduled_message_id)
+        .filter(scheduled_messages::Column::OwnerUserId.eq(*owner_user_id))
+        .one(conn)
+        .await?
+        .ok_or_else(|| eyre!("Scheduled message {} not found", scheduled_message_id))?;
+
+    // Filtered by status again, as the scheduler may have claimed it in the meantime.
+    let deleted = ScheduledMessages::delete_many()
+        .filter(scheduled_messages::Column::Id.eq(scheduled_message.id))
+        .filter(scheduled_messages::Column::Status.eq(ScheduledMessageStatus::Pending.as_db_str()))
+        .exec(conn)
+        .await?
+        .rows_affected;
+    if deleted == 0 {
+        return Err(eyre!(
+            "Scheduled message {} is not pending anymore",
+            scheduled_message_id
+        ));
+    }
+    Ok(())
+}
+
+/// Claim up to `limit` scheduled messages that are due at `now`, and mark them as running.
+///
+/// Messages that are claimed by another instance at the same time are skipped.
+pub async fn claim_due_scheduled_messages(
+    conn: &DatabaseConnection,
+    now: DateTime<Utc>,
+    limit: u64,
+) -> Result<Vec<scheduled_messages::Model>, Report> {
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_CLAIM_DUE_SCHEDULED_MESSAGES,
+        r#"
+        UPDATE scheduled_messages
+        SET status = 'running', attempts = attempts + 1, updated_at = now()
+        WHERE id IN (
+            SELECT id FROM scheduled_messages
+            WHERE (status = 'pending' AND run_at <= $1)
+               OR (status = 'running'
+                   AND updated_at < now() - make_interval(secs => $2::double precision))
+            ORDER BY run_at
+            LIMIT $3
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING *
+        "#,
+        [
+            now.into(),
+            RUNNING_TIMEOUT.as_secs_f64().into(),
+            (limit as i64).into(),
+        ],
+    );
+    Ok(ScheduledMessages::find()
+        .from_raw_sql(statement)
+        .all(conn)
+        .await?)
+}
+
+/// Record that a scheduled message was submitted and answered.
+pub async fn record_scheduled_message_success(
+    conn: &DatabaseConnection,
+    scheduled_message_id: &Uuid,
+    chat_id: Uuid,
+    assistant_message_id: Option<Uuid>,
+) -> Result<(), Report> {
+    scheduled_messages::ActiveModel {
+        id: ActiveValue::Unchanged(*scheduled_message_id),
+        status: ActiveValue::Set(ScheduledMessageStatus::Succeeded.as_db_str().to_string()),
+        last_error: ActiveValue::Set(None),
+        chat_id: ActiveValue::Set(Some(chat_id)),
+        assistant_message_id: ActiveValue::Set(assistant_message_id),
+        updated_at: ActiveValue::Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    }
+    .update(conn)
+    .await?;
+    Ok(())
+}
+
+/// Record that an attempt to submit a scheduled message failed.
+///
+/// The message is retried at `retry_at` if set, and marked as failed otherwise.
+pub async fn record_scheduled_message_failure(
+    conn: &DatabaseConnection,
+    scheduled_message_id: &Uuid,
+    chat_id: Option<Uuid>,
+    error: String,
+    retry_at: Option<DateTime<Utc>>,
+) -> Result<(), Report> {
+    let mut scheduled_message = scheduled_messages::ActiveModel {
+        id: ActiveValue::Unchanged(*scheduled_message_id),
+        last_error: ActiveValue::Set(Some(error)),
+        chat_id: ActiveValue::Set(chat_id),
+        updated_at: ActiveValue::Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    };
+    match retry_at {
+        Some(retry_at) => {
+            scheduled_message.status =
+                ActiveValue::Set(ScheduledMessageStatus::Pending.as_db_str().to_string());
+            scheduled_message.run_at = ActiveValue::Set(retry_at.fixed_offset());
+        }
+        None => {
+            scheduled_message.status =
+                ActiveValue::Set(ScheduledMessageStatus::Failed.as_db_str().to_string());
+        }
+    }
+    scheduled_message.update(conn).await?;
+    Ok(())
+}
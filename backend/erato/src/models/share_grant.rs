@@ -1,11 +1,47 @@
 use crate::db::entity::prelude::*;
-use crate::db::entity::share_grants;
+use crate::db::entity::{assistants, chats, share_grants, users};
+use crate::metrics_constants::POSTGRES_QUERY_SHARE_GRANTS_OF_MISSING_RESOURCES;
+use crate::models::notification::notify_share_grant_created;
+use crate::models::organization_condition;
 use crate::policy::prelude::*;
+use crate::query_metrics::named_statement_from_sql_and_values;
+use crate::services::policy_invalidations::{PolicyInvalidationReason, record_policy_invalidation};
 use eyre::{ContextCompat, Report, WrapErr, eyre};
 use sea_orm::prelude::*;
-use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, Set};
-use serde::Serialize;
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
 use sqlx::types::Uuid;
+use std::collections::HashSet;
+use utoipa::ToSchema;
+
+/// Permission level of a share grant
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareGrantPermission {
+    #[default]
+    Read,
+    Edit,
+}
+
+impl ShareGrantPermission {
+    /// Value as stored in the `permission` column.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShareGrantPermission::Read => "read",
+            ShareGrantPermission::Edit => "edit",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(ShareGrantPermission::Read),
+            "edit" => Some(ShareGrantPermission::Edit),
+            _ => None,
+        }
+    }
+}
 
 /// Serializable share grant information for API responses
 #[derive(Debug, Clone, Serialize)]
@@ -17,6 +53,8 @@ pub struct ShareGrantInfo {
     pub subject_id_type: String,
     pub subject_id: String,
     pub role: String,
+    pub permission: String,
+    pub expires_at: Option<DateTimeWithTimeZone>,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
 }
@@ -31,18 +69,111 @@ impl From<share_grants::Model> for ShareGrantInfo {
             subject_id_type: grant.subject_id_type,
             subject_id: grant.subject_id,
             role: grant.role,
+            permission: grant.permission,
+            expires_at: grant.expires_at,
             created_at: grant.created_at,
             updated_at: grant.updated_at,
         }
     }
 }
 
+/// Condition matching share grants that have not expired yet.
+fn active_share_grants_condition() -> Condition {
+    Condition::any()
+        .add(share_grants::Column::ExpiresAt.is_null())
+        .add(share_grants::Column::ExpiresAt.gt(chrono::Utc::now()))
+}
+
+/// Verify that the user owns the resource, as only owners can manage its share grants.
+///
+/// Resources of other organizations than the one of the subject are reported as not found.
+async fn ensure_resource_owner(
+    conn: &DatabaseConnection,
+    subject: &Subject,
+    user_uuid: Uuid,
+    resource_type: &str,
+    resource_id: &str,
+) -> Result<(), Report> {
+    let owner_user_id = match resource_type {
+        "assistant" => {
+            let resource_uuid =
+                Uuid::parse_str(resource_id).wrap_err("Invalid resource ID format")?;
+
+            Assistants::find_by_id(resource_uuid)
+                .filter(organization_condition(
+                    assistants::Column::OrganizationId,
+                    subject,
+                ))
+                .one(conn)
+                .await?
+                .wrap_err("Assistant not found")?
+                .owner_user_id
+        }
+        "chat" => {
+            let resource_uuid =
+                Uuid::parse_str(resource_id).wrap_err("Invalid resource ID format")?;
+
+            let chat = Chats::find_by_id(resource_uuid)
+                .filter(organization_condition(
+                    chats::Column::OrganizationId,
+                    subject,
+                ))
+                .one(conn)
+                .await?
+                .wrap_err("Chat not found")?;
+            Uuid::parse_str(&chat.owner_user_id).wrap_err("Invalid chat owner ID format")?
+        }
+        _ => {
+            return Err(eyre!("Unsupported resource type: {}", resource_type));
+        }
+    };
+
+    if owner_user_id != user_uuid {
+        return Err(eyre!("Access denied: User does not own this resource"));
+    }
+
+    Ok(())
+}
+
+/// Create a new share grant with `read` permission that does not expire
+///
+/// See [`create_share_grant_with_permission`].
+#[allow(clippy::too_many_arguments)]
+pub async fn create_share_grant(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    resource_type: String,
+    resource_id: String,
+    subject_type: String,
+    subject_id_type: String,
+    subject_id_value: String,
+    role: String,
+) -> Result<share_grants::Model, Report> {
+    create_share_grant_with_permission(
+        conn,
+        policy,
+        subject,
+        resource_type,
+        resource_id,
+        subject_type,
+        subject_id_type,
+        subject_id_value,
+        role,
+        ShareGrantPermission::Read,
+        None,
+    )
+    .await
+}
+
 /// Create a new share grant
 ///
 /// This function verifies that the user has permission to share the resource
-/// by checking ownership of the resource.
+/// by checking ownership of the resource. Grants with `edit` permission let the
+/// grantee modify the resource, but never delete or re-share it. If `expires_at`
+/// is set, it has to be in the future.
 #[allow(clippy::too_many_arguments)]
-pub async fn create_share_grant(
+pub async fn create_share_grant_with_permission(
     conn: &DatabaseConnection,
     policy: &PolicyEngine,
     subject: &Subject,
@@ -52,48 +183,90 @@ pub async fn create_share_grant(
     subject_id_type: String,
     subject_id_value: String,
     role: String,
+    permission: ShareGrantPermission,
+    expires_at: Option<DateTimeWithTimeZone>,
 ) -> Result<share_grants::Model, Report> {
     // Rebuild policy data if needed
     policy
         .rebuild_data_if_needed(conn, &crate::config::AppConfig::default())
         .await?;
 
+    ensure_can_share_resource(conn, policy, subject, &resource_type, &resource_id).await?;
+    validate_share_grant_options(&role, expires_at)?;
+    validate_share_grant_subject(
+        conn,
+        subject,
+        &subject_type,
+        &subject_id_type,
+        &subject_id_value,
+    )
+    .await?;
+
+    // Create the share grant record
+    let new_share_grant = new_share_grant_model(
+        subject,
+        resource_type,
+        resource_id,
+        ShareGrantee {
+            subject_type,
+            subject_id_type,
+            subject_id: subject_id_value,
+        },
+        role,
+        permission,
+        expires_at,
+    );
+
+    let txn = conn.begin().await?;
+    let created_grant = ShareGrants::insert(new_share_grant)
+        .exec_with_returning(&txn)
+        .await?;
+    notify_share_grant_created(&txn, &created_grant, subject.user_id()).await?;
+    record_policy_invalidation(&txn, PolicyInvalidationReason::ShareGrantChanged).await?;
+    txn.commit().await?;
+
+    // Invalidate policy data so it gets rebuilt with the new share grant
+    policy.invalidate_data().await;
+
+    Ok(created_grant)
+}
+
+/// Verify that the user owns the resource and is allowed to share it.
+async fn ensure_can_share_resource(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    resource_type: &str,
+    resource_id: &str,
+) -> Result<(), Report> {
     // Get the user ID from subject
     let user_id_str = subject.user_id();
     let user_uuid = Uuid::parse_str(user_id_str).wrap_err("Invalid user ID format")?;
 
     // Verify the user owns the resource they're trying to share
-    match resource_type.as_str() {
-        "assistant" => {
-            let resource_uuid =
-                Uuid::parse_str(&resource_id).wrap_err("Invalid resource ID format")?;
-
-            // Check that the assistant exists and is owned by the user
-            let assistant = Assistants::find_by_id(resource_uuid)
-                .one(conn)
-                .await?
-                .wrap_err("Assistant not found")?;
-
-            if assistant.owner_user_id != user_uuid {
-                return Err(eyre!("Access denied: User does not own this resource"));
-            }
-
-            // Authorize the share action
-            authorize!(
-                policy,
-                subject,
-                &Resource::Assistant(resource_id.clone()),
-                Action::Share
-            )?;
-        }
+    let resource = match resource_type {
+        "assistant" => Resource::Assistant(resource_id.to_string()),
+        "chat" => Resource::Chat(resource_id.to_string()),
         _ => {
             return Err(eyre!(
                 "Unsupported resource type for sharing: {}",
                 resource_type
             ));
         }
-    }
+    };
+    ensure_resource_owner(conn, subject, user_uuid, resource_type, resource_id).await?;
+
+    // Authorize the share action
+    authorize!(policy, subject, &resource, Action::Share)?;
+
+    Ok(())
+}
 
+/// Validate the role and expiry of new share grants.
+fn validate_share_grant_options(
+    role: &str,
+    expires_at: Option<DateTimeWithTimeZone>,
+) -> Result<(), Report> {
     // Validate role
     if role != "viewer" {
         return Err(eyre!(
@@ -102,6 +275,27 @@ pub async fn create_share_grant(
         ));
     }
 
+    // Validate expiry
+    if let Some(expires_at) = expires_at
+        && expires_at <= chrono::Utc::now()
+    {
+        return Err(eyre!(
+            "Invalid expires_at: {}. The expiry has to be in the future",
+            expires_at
+        ));
+    }
+
+    Ok(())
+}
+
+/// Validate the subject a resource is shared with.
+async fn validate_share_grant_subject(
+    conn: &DatabaseConnection,
+    subject: &Subject,
+    subject_type: &str,
+    subject_id_type: &str,
+    subject_id_value: &str,
+) -> Result<(), Report> {
     // Validate subject_type
     if subject_type != "user" && subject_type != "organization_group" {
         return Err(eyre!(
@@ -121,30 +315,287 @@ pub async fn create_share_grant(
         ));
     }
 
-    // Create the share grant record
-    let new_share_grant = share_grants::ActiveModel {
+    // Users can only be shared with inside of their organization. Users of other organizations
+    // are rejected the same way as unknown users, so that their existence can't be probed.
+    if subject.organization_id().is_some() && subject_type == "user" && subject_id_type == "id" {
+        let grantee = match Uuid::parse_str(subject_id_value) {
+            Ok(grantee_uuid) => {
+                Users::find_by_id(grantee_uuid)
+                    .filter(organization_condition(
+                        users::Column::OrganizationId,
+                        subject,
+                    ))
+                    .one(conn)
+                    .await?
+            }
+            Err(_) => None,
+        };
+        if grantee.is_none() {
+            return Err(eyre!(
+                "Invalid subject_id: {}. No user with this ID exists in the organization",
+                subject_id_value
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn new_share_grant_model(
+    subject: &Subject,
+    resource_type: String,
+    resource_id: String,
+    grantee: ShareGrantee,
+    role: String,
+    permission: ShareGrantPermission,
+    expires_at: Option<DateTimeWithTimeZone>,
+) -> share_grants::ActiveModel {
+    share_grants::ActiveModel {
         id: Set(Uuid::new_v4()),
         resource_type: Set(resource_type),
         resource_id: Set(resource_id),
-        subject_type: Set(subject_type),
-        subject_id_type: Set(subject_id_type),
-        subject_id: Set(subject_id_value),
+        subject_type: Set(grantee.subject_type),
+        subject_id_type: Set(grantee.subject_id_type),
+        subject_id: Set(grantee.subject_id),
         role: Set(role),
         created_at: Set(chrono::Utc::now().into()),
         updated_at: Set(chrono::Utc::now().into()),
-    };
+        permission: Set(permission.as_str().to_string()),
+        expires_at: Set(expires_at),
+        organization_id: Set(subject.organization_id().map(str::to_string)),
+    }
+}
 
-    let created_grant = ShareGrants::insert(new_share_grant)
-        .exec_with_returning(conn)
+/// Maximum number of grantees in a single bulk or sync request.
+pub const MAX_BULK_SHARE_GRANTEES: usize = 200;
+
+/// A subject that a resource is shared with
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+pub struct ShareGrantee {
+    /// The type of subject (`user` or `organization_group`)
+    pub subject_type: String,
+    /// The type of subject ID (`id`, `organization_user_id` or `organization_group_id`)
+    pub subject_id_type: String,
+    /// The ID of the subject
+    pub subject_id: String,
+}
+
+impl ShareGrantee {
+    fn of_grant(grant: &share_grants::Model) -> Self {
+        Self {
+            subject_type: grant.subject_type.clone(),
+            subject_id_type: grant.subject_id_type.clone(),
+            subject_id: grant.subject_id.clone(),
+        }
+    }
+}
+
+/// Outcome of a bulk or sync share grant request for a single grantee
+#[derive(Debug, Clone)]
+pub enum ShareGrantChange {
+    /// A share grant was created for the grantee.
+    Created(share_grants::Model),
+    /// The grantee already had a share grant, which was left unchanged.
+    AlreadyExists(share_grants::Model),
+    /// The grantee was rejected, with the reason.
+    Invalid(String),
+    /// The share grant of the grantee was removed, as it was not part of the synced set.
+    Removed(share_grants::Model),
+}
+
+/// Share a resource with multiple grantees at once
+///
+/// Grantees that already have a share grant with the role for the resource, or that are listed
+/// more than once, are reported as already existing. Invalid grantees are reported with the
+/// reason and don't prevent the others from being shared with. All share grants are created in
+/// one transaction, and the policy data is invalidated once.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_share_grants_bulk(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    resource_type: String,
+    resource_id: String,
+    grantees: Vec<ShareGrantee>,
+    role: String,
+    permission: ShareGrantPermission,
+    expires_at: Option<DateTimeWithTimeZone>,
+) -> Result<Vec<(ShareGrantee, ShareGrantChange)>, Report> {
+    apply_share_grants(
+        conn,
+        policy,
+        subject,
+        resource_type,
+        resource_id,
+        grantees,
+        role,
+        permission,
+        expires_at,
+        false,
+    )
+    .await
+}
+
+/// Set the full set of grantees of a resource
+///
+/// Creates share grants for the grantees that don't have one yet, like
+/// [`create_share_grants_bulk`], and removes the share grants of all other subjects in the same
+/// transaction. Removals are reported after the results of the requested grantees.
+#[allow(clippy::too_many_arguments)]
+pub async fn sync_share_grants(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    resource_type: String,
+    resource_id: String,
+    grantees: Vec<ShareGrantee>,
+    role: String,
+    permission: ShareGrantPermission,
+    expires_at: Option<DateTimeWithTimeZone>,
+) -> Result<Vec<(ShareGrantee, ShareGrantChange)>, Report> {
+    apply_share_grants(
+        conn,
+        policy,
+        subject,
+        resource_type,
+        resource_id,
+        grantees,
+        role,
+        permission,
+        expires_at,
+        true,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn apply_share_grants(
+    conn: &DatabaseConnection,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    resource_type: String,
+    resource_id: String,
+    grantees: Vec<ShareGrantee>,
+    role: String,
+    permission: ShareGrantPermission,
+    expires_at: Option<DateTimeWithTimeZone>,
+    remove_others: bool,
+) -> Result<Vec<(ShareGrantee, ShareGrantChange)>, Report> {
+    if grantees.len() > MAX_BULK_SHARE_GRANTEES {
+        return Err(eyre!(
+            "Invalid grantees: at most {} grantees can be shared with at once",
+            MAX_BULK_SHARE_GRANTEES
+        ));
+    }
+
+    // Rebuild policy data if needed
+    policy
+        .rebuild_data_if_needed(conn, &crate::config::AppConfig::default())
         .await?;
 
-    // Invalidate policy data so it gets rebuilt with the new share grant
+    ensure_can_share_resource(conn, policy, subject, &resource_type, &resource_id).await?;
+    validate_share_grant_options(&role, expires_at)?;
+
+    let txn = conn.begin().await?;
+    let existing_grants = ShareGrants::find()
+        .filter(share_grants::Column::ResourceType.eq(resource_type.as_str()))
+        .filter(share_grants::Column::ResourceId.eq(resource_id.as_str()))
+        .filter(share_grants::Column::Role.eq(role.as_str()))
+        .all(&txn)
+        .await?;
+
+    let mut results = Vec::with_capacity(grantees.len());
+    let mut requested = HashSet::new();
+    for grantee in grantees {
+        let existing_grant = existing_grants
+            .iter()
+            .find(|grant| ShareGrantee::of_grant(grant) == grantee);
+        let change = if let Some(existing_grant) = existing_grant {
+            ShareGrantChange::AlreadyExists(existing_grant.clone())
+        } else if let Some(previous_change) = results
+            .iter()
+            .find_map(|(previous, change)| (previous == &grantee).then_some(change))
+        {
+            // Grantees listed more than once get the outcome of their first occurrence
+            match previous_change {
+                ShareGrantChange::Created(grant) => ShareGrantChange::AlreadyExists(grant.clone()),
+                other => other.clone(),
+            }
+        } else if let Err(err) = validate_share_grant_subject(
+            conn,
+            subject,
+            &grantee.subject_type,
+            &grantee.subject_id_type,
+            &grantee.subject_id,
+        )
+        .await
+        {
+            ShareGrantChange::Invalid(err.to_string())
+        } else {
+            let created_grant = ShareGrants::insert(new_share_grant_model(
+                subject,
+                resource_type.clone(),
+                resource_id.clone(),
+                grantee.clone(),
+                role.clone(),
+                permission,
+                expires_at,
+            ))
+            .exec_with_returning(&txn)
+            .await?;
+            notify_share_grant_created(&txn, &created_grant, subject.user_id()).await?;
+            ShareGrantChange::Created(created_grant)
+        };
+        requested.insert(grantee.clone());
+        results.push((grantee, change));
+    }
+
+    if remove_others {
+        let removed_grants: Vec<share_grants::Model> = ShareGrants::find()
+            .filter(share_grants::Column::ResourceType.eq(resource_type.as_str()))
+            .filter(share_grants::Column::ResourceId.eq(resource_id.as_str()))
+            .all(&txn)
+            .await?
+            .into_iter()
+            .filter(|grant| !requested.contains(&ShareGrantee::of_grant(grant)))
+            .collect();
+        if !removed_grants.is_empty() {
+            ShareGrants::delete_many()
+                .filter(
+                    share_grants::Column::Id.is_in(
+                        removed_grants
+                            .iter()
+                            .map(|grant| grant.id)
+                            .collect::<Vec<_>>(),
+                    ),
+                )
+                .exec(&txn)
+                .await?;
+        }
+        results.extend(removed_grants.into_iter().map(|grant| {
+            (
+                ShareGrantee::of_grant(&grant),
+                ShareGrantChange::Removed(grant),
+            )
+        }));
+    }
+    if results.iter().any(|(_, change)| {
+        matches!(
+            change,
+            ShareGrantChange::Created(_) | ShareGrantChange::Removed(_)
+        )
+    }) {
+        record_policy_invalidation(&txn, PolicyInvalidationReason::ShareGrantChanged).await?;
+    }
+    txn.commit().await?;
+
+    // Invalidate policy data once for all changes
     policy.invalidate_data().await;
 
-    Ok(created_grant)
+    Ok(results)
 }
 
-/// List all share grants for a specific resource
+/// List all share grants for a specific resource, including expired ones
 ///
 /// Only the owner of the resource can list its share grants.
 pub async fn list_share_grants_for_resource(
@@ -159,24 +610,7 @@ pub async fn list_share_grants_for_resource(
     let user_uuid = Uuid::parse_str(user_id_str).wrap_err("Invalid user ID format")?;
 
     // Verify the user owns the resource
-    match resource_type.as_str() {
-        "assistant" => {
-            let resource_uuid =
-                Uuid::parse_str(&resource_id).wrap_err("Invalid resource ID format")?;
-
-            let assistant = Assistants::find_by_id(resource_uuid)
-                .one(conn)
-                .await?
-                .wrap_err("Assistant not found")?;
-
-            if assistant.owner_user_id != user_uuid {
-                return Err(eyre!("Access denied: User does not own this resource"));
-            }
-        }
-        _ => {
-            return Err(eyre!("Unsupported resource type: {}", resource_type));
-        }
-    }
+    ensure_resource_owner(conn, subject, user_uuid, &resource_type, &resource_id).await?;
 
     // Query all share grants for the resource
     let grants = ShareGrants::find()
@@ -216,35 +650,28 @@ pub async fn delete_share_grant(
         .wrap_err("Share grant not found")?;
 
     // Verify the user owns the resource
-    match grant.resource_type.as_str() {
-        "assistant" => {
-            let resource_uuid =
-                Uuid::parse_str(&grant.resource_id).wrap_err("Invalid resource ID format")?;
-
-            let assistant = Assistants::find_by_id(resource_uuid)
-                .one(conn)
-                .await?
-                .wrap_err("Assistant not found")?;
-
-            if assistant.owner_user_id != user_uuid {
-                return Err(eyre!("Access denied: User does not own this resource"));
-            }
-
-            // Authorize the delete action
-            authorize!(
-                policy,
-                subject,
-                &Resource::ShareGrant(grant_id.to_string()),
-                Action::Delete
-            )?;
-        }
-        _ => {
-            return Err(eyre!("Unsupported resource type: {}", grant.resource_type));
-        }
-    }
+    ensure_resource_owner(
+        conn,
+        subject,
+        user_uuid,
+        &grant.resource_type,
+        &grant.resource_id,
+    )
+    .await?;
+
+    // Authorize the delete action
+    authorize!(
+        policy,
+        subject,
+        &Resource::ShareGrant(grant_id.to_string()),
+        Action::Delete
+    )?;
 
     // Delete the share grant
-    ShareGrants::delete_by_id(grant_id).exec(conn).await?;
+    let txn = conn.begin().await?;
+    ShareGrants::delete_by_id(grant_id).exec(&txn).await?;
+    record_policy_invalidation(&txn, PolicyInvalidationReason::ShareGrantChanged).await?;
+    txn.commit().await?;
 
     // Invalidate policy data so it gets rebuilt without this share grant
     policy.invalidate_data().await;
@@ -252,6 +679,31 @@ pub async fn delete_share_grant(
     Ok(())
 }
 
+/// All share grants of chats and assistants that don't exist (anymore), oldest first.
+pub async fn get_share_grants_of_missing_resources(
+    conn: &DatabaseConnection,
+) -> Result<Vec<share_grants::Model>, Report> {
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_SHARE_GRANTS_OF_MISSING_RESOURCES,
+        r#"
+        SELECT g.*
+        FROM share_grants g
+        WHERE (g.resource_type = 'chat'
+               AND NOT EXISTS (SELECT 1 FROM chats c WHERE c.id::text = g.resource_id))
+           OR (g.resource_type = 'assistant'
+               AND NOT EXISTS (SELECT 1 FROM assistants a WHERE a.id::text = g.resource_id))
+        ORDER BY g.created_at, g.id
+        "#,
+        vec![],
+    );
+    ShareGrants::find()
+        .from_raw_sql(statement)
+        .all(conn)
+        .await
+        .wrap_err("Failed to get share grants of missing resources")
+}
+
 /// Get all resources shared with a specific subject
 ///
 /// This is used to populate the list of assistants available to a user
@@ -275,6 +727,8 @@ pub async fn get_resources_shared_with_subject(
 /// - `organization_user_id`: Optional organization-specific user ID (e.g., Azure AD's "oid" claim)
 /// - Share grants with `subject_id_type = "id"` are matched against `subject_id`
 /// - Share grants with `subject_id_type = "organization_user_id"` are matched against `organization_user_id`
+///
+/// Expired share grants are excluded.
 pub async fn get_resources_shared_with_subject_and_groups(
     conn: &DatabaseConnection,
     subject_id: &str,
@@ -313,7 +767,50 @@ pub async fn get_resources_shared_with_subject_and_groups(
         );
     }
 
-    let grants = ShareGrants::find().filter(condition).all(conn).await?;
+    let grants = ShareGrants::find()
+        .filter(condition)
+        .filter(active_share_grants_condition())
+        .all(conn)
+        .await?;
 
     Ok(grants)
 }
+
+/// Get the IDs of all resources of a type the subject can edit through a share grant
+///
+/// Only considers share grants with `edit` permission that have not expired.
+pub async fn get_edit_granted_resource_ids(
+    conn: &DatabaseConnection,
+    subject: &Subject,
+    resource_type: &str,
+) -> Result<HashSet<String>, Report> {
+    let grants = get_resources_shared_with_subject_and_groups(
+        conn,
+        subject.user_id(),
+        subject.organization_user_id(),
+        resource_type,
+        subject.organization_group_ids(),
+    )
+    .await?;
+
+    Ok(grants
+        .into_iter()
+        .filter(|grant| grant.permission == ShareGrantPermission::Edit.as_str())
+        .map(|grant| grant.resource_id)
+        .collect())
+}
+
+/// Delete all share grants that expired before the given point in time
+///
+/// Returns the number of deleted share grants.
+pub async fn delete_expired_share_grants(
+    conn: &DatabaseConnection,
+    expired_before: DateTimeWithTimeZone,
+) -> Result<u64, Report> {
+    let result = ShareGrants::delete_many()
+        .filter(share_grants::Column::ExpiresAt.lt(expired_before))
+        .exec(conn)
+        .await?;
+
+    Ok(result.rows_affected)
+}
@@ -1,8 +1,15 @@
 use crate::db::entity::prelude::*;
-use crate::db::entity::users;
-use eyre::Report;
+use crate::db::entity::{assistants, chats, file_uploads, messages, share_grants, users};
+use crate::metrics_constants::POSTGRES_QUERY_BACKFILL_SHARE_GRANT_ORGANIZATION;
+use crate::query_metrics::named_statement_from_sql_and_values;
+use eyre::{Report, eyre};
 use sea_orm::prelude::*;
-use sea_orm::{ActiveValue, DatabaseConnection, EntityTrait};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveValue, ConnectionTrait, DatabaseConnection, EntityTrait, QueryOrder, QuerySelect,
+    TransactionTrait,
+};
+use serde::Serialize;
 
 pub async fn get_or_create_user(
     conn: &DatabaseConnection,
@@ -32,3 +39,175 @@ pub async fn get_or_create_user(
         Ok(created_user)
     }
 }
+
+/// Set the organization of a user, as found in their latest ID token.
+///
+/// Records of the user that don't belong to an organization yet (e.g. created before the
+/// `organization_claim` was configured) are backfilled with the organization: their chats,
+/// assistants, file uploads, and the share grants of their chats and assistants. Records that
+/// already belong to an organization are never moved to another one.
+///
+/// Returns the number of backfilled records.
+pub async fn assign_user_organization(
+    conn: &DatabaseConnection,
+    user: &users::Model,
+    organization_id: Option<&str>,
+) -> Result<u64, Report> {
+    let txn = conn.begin().await?;
+    Users::update_many()
+        .col_expr(users::Column::OrganizationId, Expr::value(organization_id))
+        .filter(users::Column::Id.eq(user.id))
+        .exec(&txn)
+        .await?;
+
+    let Some(organization_id) = organization_id else {
+        txn.commit().await?;
+        return Ok(0);
+    };
+    let mut backfilled = 0;
+    backfilled += Chats::update_many()
+        .col_expr(chats::Column::OrganizationId, Expr::value(organization_id))
+        .filter(chats::Column::OwnerUserId.eq(user.id.to_string()))
+        .filter(chats::Column::OrganizationId.is_null())
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    backfilled += Assistants::update_many()
+        .col_expr(
+            assistants::Column::OrganizationId,
+            Expr::value(organization_id),
+        )
+        .filter(assistants::Column::OwnerUserId.eq(user.id))
+        .filter(assistants::Column::OrganizationId.is_null())
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    backfilled += FileUploads::update_many()
+        .col_expr(
+            file_uploads::Column::OrganizationId,
+            Expr::value(organization_id),
+        )
+        .filter(file_uploads::Column::OwnerUserId.eq(user.id.to_string()))
+        .filter(file_uploads::Column::OrganizationId.is_null())
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_BACKFILL_SHARE_GRANT_ORGANIZATION,
+        r#"
+        UPDATE share_grants
+        SET organization_id = $1
+        WHERE organization_id IS NULL
+          AND (
+            (resource_type = 'chat' AND resource_id IN (
+                SELECT id::text FROM chats WHERE owner_user_id = $2
+            ))
+            OR (resource_type = 'assistant' AND resource_id IN (
+                SELECT id::text FROM assistants WHERE owner_user_id = $3
+            ))
+          )
+        "#,
+        [
+            organization_id.into(),
+            user.id.to_string().into(),
+            user.id.into(),
+        ],
+    );
+    backfilled += txn.execute_raw(statement).await?.rows_affected();
+    txn.commit().await?;
+
+    if backfilled > 0 {
+        tracing::info!(
+            user_id = %user.id,
+            organization_id,
+            backfilled,
+            "Backfilled records of user with their organization"
+        );
+    }
+    Ok(backfilled)
+}
+
+/// Overview of the data of a user, for support and operations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserStats {
+    pub user_id: Uuid,
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub organization_id: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+    pub chats: u64,
+    pub archived_chats: u64,
+    /// Messages in the chats of the user, including answers
+    pub messages: u64,
+    /// Time of the most recent message in any of the chats of the user
+    pub last_message_at: Option<DateTimeWithTimeZone>,
+    pub assistants: u64,
+    pub file_uploads: u64,
+    /// Share grants of resources of other users to the user
+    pub share_grants_received: u64,
+}
+
+/// Collect an overview of the data of a user.
+pub async fn get_user_stats(
+    conn: &DatabaseConnection,
+    user_id: &Uuid,
+) -> Result<UserStats, Report> {
+    let user = Users::find_by_id(*user_id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| eyre!("User with ID {} not found", user_id))?;
+    let owner_user_id = user.id.to_string();
+
+    let chats = Chats::find()
+        .filter(chats::Column::OwnerUserId.eq(owner_user_id.as_str()))
+        .count(conn)
+        .await?;
+    let archived_chats = Chats::find()
+        .filter(chats::Column::OwnerUserId.eq(owner_user_id.as_str()))
+        .filter(chats::Column::ArchivedAt.is_not_null())
+        .count(conn)
+        .await?;
+    let messages_of_user = || {
+        Messages::find()
+            .inner_join(Chats)
+            .filter(chats::Column::OwnerUserId.eq(owner_user_id.as_str()))
+    };
+    let messages = messages_of_user().count(conn).await?;
+    let last_message_at = messages_of_user()
+        .order_by_desc(messages::Column::CreatedAt)
+        .one(conn)
+        .await?
+        .map(|message| message.created_at);
+    let assistants = Assistants::find()
+        .filter(assistants::Column::OwnerUserId.eq(user.id))
+        .count(conn)
+        .await?;
+    let file_uploads = FileUploads::find()
+        .filter(file_uploads::Column::OwnerUserId.eq(owner_user_id.as_str()))
+        .count(conn)
+        .await?;
+    let share_grants_received = ShareGrants::find()
+        .filter(share_grants::Column::SubjectType.eq("user"))
+        .filter(share_grants::Column::SubjectIdType.eq("id"))
+        .filter(share_grants::Column::SubjectId.eq(owner_user_id.as_str()))
+        .count(conn)
+        .await?;
+
+    Ok(UserStats {
+        user_id: user.id,
+        issuer: user.issuer,
+        subject: user.subject,
+        email: user.email,
+        organization_id: user.organization_id,
+        created_at: user.created_at,
+        chats,
+        archived_chats,
+        messages,
+        last_message_at,
+        assistants,
+        file_uploads,
+        share_grants_received,
+    })
+}
@@ -10,6 +10,7 @@ pub struct UpdateUserPreferencesInput {
     pub job_title: Option<Option<String>>,
     pub assistant_custom_instructions: Option<Option<String>>,
     pub assistant_additional_information: Option<Option<String>>,
+    pub default_selected_facets: Option<Option<Vec<String>>>,
 }
 
 pub async fn get_user_preferences(
@@ -40,6 +41,9 @@ pub async fn upsert_user_preferences(
             model.assistant_additional_information =
                 ActiveValue::Set(normalize_optional_text(value));
         }
+        if let Some(value) = input.default_selected_facets {
+            model.default_selected_facets = ActiveValue::Set(value.map(dedup_facet_ids));
+        }
 
         Ok(model.update(conn).await?)
     } else {
@@ -53,6 +57,12 @@ pub async fn upsert_user_preferences(
             assistant_additional_information: ActiveValue::Set(normalize_optional_text(
                 input.assistant_additional_information.unwrap_or(None),
             )),
+            default_selected_facets: ActiveValue::Set(
+                input
+                    .default_selected_facets
+                    .unwrap_or(None)
+                    .map(dedup_facet_ids),
+            ),
             ..Default::default()
         };
 
@@ -72,3 +82,16 @@ fn normalize_optional_text(value: Option<String>) -> Option<String> {
         }
     })
 }
+
+/// Removes duplicate facet ids while keeping the given order.
+///
+/// An empty list is kept as-is, as it explicitly selects no facets by default.
+fn dedup_facet_ids(facet_ids: Vec<String>) -> Vec<String> {
+    let mut deduped = Vec::new();
+    for facet_id in facet_ids {
+        if !deduped.contains(&facet_id) {
+            deduped.push(facet_id);
+        }
+    }
+    deduped
+}
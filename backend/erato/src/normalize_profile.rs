@@ -31,10 +31,17 @@ pub struct IdTokenProfile {
     pub organization_user_id: Option<String>,
     // Organization group IDs - same as groups, for sharing purposes
     pub organization_group_ids: Vec<String>,
+    // Organization (tenant) ID - from the claim configured in `organization_claim`
+    pub organization_id: Option<String>,
 }
 
 // Normalize profile from the ID token claims of different OIDC providers.
-pub fn normalize_profile(claims: Value) -> Result<IdTokenProfile, Report> {
+//
+// The organization ID is only read if an `organization_claim` is provided.
+pub fn normalize_profile(
+    claims: Value,
+    organization_claim: Option<&str>,
+) -> Result<IdTokenProfile, Report> {
     // Required claims per spec
     let iss = claims.get("iss").ok_or(eyre!("iss claim is required"))?;
     let sub = claims.get("sub").ok_or(eyre!("sub claim is required"))?;
@@ -86,6 +93,15 @@ pub fn normalize_profile(claims: Value) -> Result<IdTokenProfile, Report> {
     // Organization group IDs are the same as groups
     let organization_group_ids = groups.clone();
 
+    // Parse the configured organization claim, which may hold a string or a number
+    let organization_id = organization_claim
+        .and_then(|claim| claims.get(claim))
+        .and_then(|v| match v {
+            Value::String(s) if !s.is_empty() => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        });
+
     let profile = IdTokenProfile {
         iss: iss.as_str().map(String::from).unwrap(),
         sub: sub.as_str().map(String::from).unwrap(),
@@ -98,6 +114,7 @@ pub fn normalize_profile(claims: Value) -> Result<IdTokenProfile, Report> {
         groups,
         organization_user_id,
         organization_group_ids,
+        organization_id,
     };
 
     Ok(profile)
@@ -121,7 +138,7 @@ mod tests {
           "name": "admin",
           "sub": "CiQwOGE4Njg0Yi1kYjg4LTRiNzMtOTBhOS0zY2QxNjYxZjU0NjYSBWxvY2Fs"
         });
-        let profile = normalize_profile(claims).unwrap();
+        let profile = normalize_profile(claims, None).unwrap();
         assert_eq!(profile.iss, "http://0.0.0.0:5556");
         assert_eq!(
             profile.sub,
@@ -174,7 +191,7 @@ mod tests {
           "xms_pl": "en",
           "xms_tpl": "en"
         });
-        let profile = normalize_profile(claims).unwrap();
+        let profile = normalize_profile(claims, None).unwrap();
         assert_eq!(
             profile.iss,
             "https://login.microsoftonline.com/22222222-2222-2222-2222-222222222222/v2.0"
@@ -220,7 +237,7 @@ mod tests {
           "family_name": "User",
           "email": "admin@example.com"
         });
-        let profile = normalize_profile(claims).unwrap();
+        let profile = normalize_profile(claims, None).unwrap();
         assert_eq!(profile.iss, "http://localhost:8080/realms/erato");
         assert_eq!(profile.sub, "760960c1-6c60-400e-a176-78c71131be7d");
         assert_eq!(profile.email, Some("admin@example.com".to_string()));
@@ -246,7 +263,7 @@ mod tests {
             "sub": "test-user",
             "groups": ["group1", "group2", "group3"]
         });
-        let profile = normalize_profile(claims_array).unwrap();
+        let profile = normalize_profile(claims_array, None).unwrap();
         assert_eq!(
             profile.groups,
             vec![
@@ -262,7 +279,7 @@ mod tests {
             "sub": "test-user",
             "groups": "single-group"
         });
-        let profile = normalize_profile(claims_single).unwrap();
+        let profile = normalize_profile(claims_single, None).unwrap();
         assert_eq!(profile.groups, vec!["single-group".to_string()]);
 
         // Test with no groups claim
@@ -270,7 +287,7 @@ mod tests {
             "iss": "http://test.example.com",
             "sub": "test-user"
         });
-        let profile = normalize_profile(claims_no_groups).unwrap();
+        let profile = normalize_profile(claims_no_groups, None).unwrap();
         assert_eq!(profile.groups, Vec::<String>::new());
 
         // Test with invalid groups format (should default to empty)
@@ -279,8 +296,31 @@ mod tests {
             "sub": "test-user",
             "groups": 123
         });
-        let profile = normalize_profile(claims_invalid).unwrap();
+        let profile = normalize_profile(claims_invalid, None).unwrap();
         assert_eq!(profile.groups, Vec::<String>::new());
         assert_eq!(profile.organization_group_ids, Vec::<String>::new());
     }
+
+    #[test]
+    pub fn test_normalize_organization_claim() {
+        let claims = serde_json::json!({
+            "iss": "http://test.example.com",
+            "sub": "test-user",
+            "tid": "22222222-2222-2222-2222-222222222222",
+            "org_number": 42
+        });
+        let profile = normalize_profile(claims.clone(), Some("tid")).unwrap();
+        assert_eq!(
+            profile.organization_id,
+            Some("22222222-2222-2222-2222-222222222222".to_string())
+        );
+        let profile = normalize_profile(claims.clone(), Some("org_number")).unwrap();
+        assert_eq!(profile.organization_id, Some("42".to_string()));
+
+        // Without a configured claim, or if the claim is missing, there is no organization
+        let profile = normalize_profile(claims.clone(), None).unwrap();
+        assert_eq!(profile.organization_id, None);
+        let profile = normalize_profile(claims, Some("organization")).unwrap();
+        assert_eq!(profile.organization_id, None);
+    }
 }
@@ -1,20 +1,29 @@
-use crate::config::{AppConfig, FacetPermissionRule, McpServerPermissionRule, ModelPermissionRule};
+use crate::config::{
+    AppConfig, FacetPermissionRule, McpServerPermissionRule, ModelPermissionRule,
+    PolicyDataTieringConfig,
+};
 use crate::db::entity::prelude::*;
 use crate::db::entity::{
     assistant_file_uploads, assistant_hub_assistant_versions, assistants, chat_file_uploads,
     file_uploads, share_grants, share_links,
 };
 use crate::db::entity_ext::chats;
+use crate::metrics::{report_policy_cold_lookup, report_policy_dataset_size};
 use crate::policy::types::{
     Action, Resource, ResourceId, ResourceKind, Subject, SubjectId, SubjectKind,
 };
 use axum::http::StatusCode;
 use eyre::{Report, WrapErr, eyre};
+use moka::future::Cache;
 use regorus::Engine;
-use sea_orm::prelude::Uuid;
-use sea_orm::{DatabaseConnection, EntityTrait, FromQueryResult, QuerySelect};
+use sea_orm::prelude::{DateTimeWithTimeZone, Uuid};
+use sea_orm::sea_query::SelectStatement;
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, FromQueryResult, QueryFilter,
+    QuerySelect, QueryTrait,
+};
 use serde_json::{Value as JsonValue, json};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::{Mutex, RwLock};
 use tracing::instrument;
@@ -27,31 +36,47 @@ struct ChatPolicyAttributes {
     id: Uuid,
     owner_user_id: String,
     archived_at: Option<sea_orm::prelude::DateTimeWithTimeZone>,
+    organization_id: Option<String>,
+}
+
+impl ChatPolicyAttributes {
+    fn select() -> sea_orm::Select<chats::Entity> {
+        chats::Entity::find()
+            .select_only()
+            .column(chats::Column::Id)
+            .column(chats::Column::OwnerUserId)
+            .column(chats::Column::ArchivedAt)
+            .column(chats::Column::OrganizationId)
+    }
+
+    fn into_json(self) -> JsonValue {
+        json!({
+            "id": self.id.to_string(),
+            "owner_id": self.owner_user_id,
+            "archived_at": self.archived_at,
+            "organization_id": self.organization_id,
+        })
+    }
 }
 
 /// Fetch minimal chat data required for policy evaluation.
-/// Only queries the `id` and `owner_user_id` fields.
-async fn fetch_chat_policy_data(db: &DatabaseConnection) -> Result<JsonValue, Report> {
-    let chats: Vec<ChatPolicyAttributes> = chats::Entity::find()
-        .select_only()
-        .column(chats::Column::Id)
-        .column(chats::Column::OwnerUserId)
-        .column(chats::Column::ArchivedAt)
-        .into_model::<ChatPolicyAttributes>()
-        .all(db)
-        .await?;
+/// Only queries the `id`, `owner_user_id`, `archived_at` and `organization_id` fields.
+///
+/// Cold chats are left out if `cold_cutoffs` is given.
+async fn fetch_chat_policy_data(
+    db: &DatabaseConnection,
+    cold_cutoffs: Option<&ColdCutoffs>,
+) -> Result<JsonValue, Report> {
+    let mut query = ChatPolicyAttributes::select();
+    if let Some(cold_cutoffs) = cold_cutoffs {
+        query = query.filter(cold_cutoffs.cold_chats().not());
+    }
+    let chats: Vec<ChatPolicyAttributes> =
+        query.into_model::<ChatPolicyAttributes>().all(db).await?;
 
     let mut chat_attributes = serde_json::Map::new();
     for chat in chats {
-        let id_str = chat.id.to_string();
-        chat_attributes.insert(
-            id_str.clone(),
-            json!({
-                "id": id_str,
-                "owner_id": chat.owner_user_id,
-                "archived_at": chat.archived_at,
-            }),
-        );
+        chat_attributes.insert(chat.id.to_string(), chat.into_json());
     }
 
     Ok(json!(chat_attributes))
@@ -62,15 +87,19 @@ async fn fetch_chat_policy_data(db: &DatabaseConnection) -> Result<JsonValue, Re
 struct AssistantPolicyAttributes {
     id: Uuid,
     owner_user_id: Uuid,
+    organization_id: Option<String>,
+    visibility: String,
 }
 
 /// Fetch minimal assistant data required for policy evaluation.
-/// Only queries the `id` and `owner_user_id` fields.
+/// Only queries the `id`, `owner_user_id`, `organization_id` and `visibility` fields.
 async fn fetch_assistant_policy_data(db: &DatabaseConnection) -> Result<JsonValue, Report> {
     let assistants_list: Vec<AssistantPolicyAttributes> = Assistants::find()
         .select_only()
         .column(assistants::Column::Id)
         .column(assistants::Column::OwnerUserId)
+        .column(assistants::Column::OrganizationId)
+        .column(assistants::Column::Visibility)
         .into_model::<AssistantPolicyAttributes>()
         .all(db)
         .await?;
@@ -83,6 +112,8 @@ async fn fetch_assistant_policy_data(db: &DatabaseConnection) -> Result<JsonValu
             json!({
                 "id": id_str,
                 "owner_id": assistant.owner_user_id.to_string(),
+                "organization_id": assistant.organization_id,
+                "visibility": assistant.visibility,
             }),
         );
     }
@@ -95,15 +126,49 @@ async fn fetch_assistant_policy_data(db: &DatabaseConnection) -> Result<JsonValu
 struct FileUploadPolicyAttributes {
     id: Uuid,
     owner_user_id: String,
+    organization_id: Option<String>,
+}
+
+impl FileUploadPolicyAttributes {
+    fn select() -> sea_orm::Select<file_uploads::Entity> {
+        FileUploads::find()
+            .select_only()
+            .column(file_uploads::Column::Id)
+            .column(file_uploads::Column::OwnerUserId)
+            .column(file_uploads::Column::OrganizationId)
+    }
+
+    fn into_json(
+        self,
+        linked_chat_ids: Vec<String>,
+        linked_assistant_ids: Vec<String>,
+    ) -> JsonValue {
+        json!({
+            "id": self.id.to_string(),
+            "owner_id": self.owner_user_id,
+            "linked_chat_ids": linked_chat_ids,
+            "linked_assistant_ids": linked_assistant_ids,
+            "organization_id": self.organization_id,
+        })
+    }
 }
 
 /// Fetch minimal file upload data required for policy evaluation.
-/// Only queries the `id` and `owner_user_id` fields.
-async fn fetch_file_upload_policy_data(db: &DatabaseConnection) -> Result<JsonValue, Report> {
-    let file_uploads_list: Vec<FileUploadPolicyAttributes> = FileUploads::find()
-        .select_only()
-        .column(file_uploads::Column::Id)
-        .column(file_uploads::Column::OwnerUserId)
+/// Only queries the `id`, `owner_user_id` and `organization_id` fields.
+///
+/// Files linked to cold chats are left out if `cold_cutoffs` is given, as reading them is
+/// authorized through those chats.
+async fn fetch_file_upload_policy_data(
+    db: &DatabaseConnection,
+    cold_cutoffs: Option<&ColdCutoffs>,
+) -> Result<JsonValue, Report> {
+    let mut query = FileUploadPolicyAttributes::select();
+    if let Some(cold_cutoffs) = cold_cutoffs {
+        query = query.filter(
+            file_uploads::Column::Id.not_in_subquery(cold_cutoffs.file_uploads_of_cold_chats()),
+        );
+    }
+    let file_uploads_list: Vec<FileUploadPolicyAttributes> = query
         .into_model::<FileUploadPolicyAttributes>()
         .all(db)
         .await?;
@@ -142,23 +207,15 @@ async fn fetch_file_upload_policy_data(db: &DatabaseConnection) -> Result<JsonVa
 
     let mut file_upload_attributes = serde_json::Map::new();
     for file_upload in file_uploads_list {
-        let id_str = file_upload.id.to_string();
         let linked_chat_ids = linked_chat_ids_by_file
-            .get(&file_upload.id)
-            .cloned()
+            .remove(&file_upload.id)
             .unwrap_or_default();
         let linked_assistant_ids = linked_assistant_ids_by_file
-            .get(&file_upload.id)
-            .cloned()
+            .remove(&file_upload.id)
             .unwrap_or_default();
         file_upload_attributes.insert(
-            id_str.clone(),
-            json!({
-                "id": id_str,
-                "owner_id": file_upload.owner_user_id,
-                "linked_chat_ids": linked_chat_ids,
-                "linked_assistant_ids": linked_assistant_ids,
-            }),
+            file_upload.id.to_string(),
+            file_upload.into_json(linked_chat_ids, linked_assistant_ids),
         );
     }
 
@@ -166,8 +223,18 @@ async fn fetch_file_upload_policy_data(db: &DatabaseConnection) -> Result<JsonVa
 }
 
 /// Fetch share grants data for policy evaluation.
+///
+/// Share grants that already expired are left out. The expiry of the others is checked by the
+/// policy on every evaluation, as grants that expire later don't invalidate the data.
 async fn fetch_share_grants_policy_data(db: &DatabaseConnection) -> Result<JsonValue, Report> {
-    let grants: Vec<share_grants::Model> = ShareGrants::find().all(db).await?;
+    let grants: Vec<share_grants::Model> = ShareGrants::find()
+        .filter(
+            Condition::any()
+                .add(share_grants::Column::ExpiresAt.is_null())
+                .add(share_grants::Column::ExpiresAt.gt(chrono::Utc::now())),
+        )
+        .all(db)
+        .await?;
 
     let grants_array: Vec<JsonValue> = grants
         .into_iter()
@@ -180,6 +247,9 @@ async fn fetch_share_grants_policy_data(db: &DatabaseConnection) -> Result<JsonV
                 "subject_id_type": grant.subject_id_type,
                 "subject_id": grant.subject_id,
                 "role": grant.role,
+                "permission": grant.permission,
+                "organization_id": grant.organization_id,
+                "expires_at": grant.expires_at.map(|expires_at| expires_at.to_rfc3339()),
             })
         })
         .collect();
@@ -228,6 +298,297 @@ async fn fetch_share_links_policy_data(db: &DatabaseConnection) -> Result<JsonVa
     Ok(json!(links_array))
 }
 
+/// Which chats are cold, as of a rebuild of the policy data.
+///
+/// Cold chats, and the files linked to them, are left out of the in-memory policy data and are
+/// looked up in the database when they are authorized.
+#[derive(Debug, Clone)]
+struct ColdCutoffs {
+    archived_before: DateTimeWithTimeZone,
+    inactive_before: Option<DateTimeWithTimeZone>,
+    rebuilt_at: DateTimeWithTimeZone,
+}
+
+impl ColdCutoffs {
+    fn new(config: &PolicyDataTieringConfig) -> Self {
+        let now = chrono::Utc::now().fixed_offset();
+        let days_ago = |days: u64| {
+            now.checked_sub_days(chrono::Days::new(days))
+                .unwrap_or(chrono::DateTime::<chrono::Utc>::MIN_UTC.fixed_offset())
+        };
+        Self {
+            archived_before: days_ago(config.archived_after_days),
+            inactive_before: config.inactive_after_days.map(days_ago),
+            rebuilt_at: now,
+        }
+    }
+
+    /// Chats that are left out of the in-memory policy data.
+    fn cold_chats(&self) -> Condition {
+        let mut condition =
+            Condition::any().add(chats::Column::ArchivedAt.lt(self.archived_before));
+        if let Some(inactive_before) = self.inactive_before {
+            condition = condition.add(
+                Condition::all()
+                    .add(chats::Column::ArchivedAt.is_null())
+                    .add(chats::Column::UpdatedAt.lt(inactive_before)),
+            );
+        }
+        condition
+    }
+
+    /// Chats that are looked up in the database when authorizing them fails with the in-memory
+    /// data: the cold chats, and chats updated since the rebuild, which may have been cold at the
+    /// time.
+    fn lookup_chats(&self) -> Condition {
+        Condition::any()
+            .add(self.cold_chats())
+            .add(chats::Column::UpdatedAt.gte(self.rebuilt_at))
+    }
+
+    /// IDs of the files linked to cold chats.
+    fn file_uploads_of_cold_chats(&self) -> SelectStatement {
+        ChatFileUploads::find()
+            .select_only()
+            .column(chat_file_uploads::Column::FileUploadId)
+            .filter(
+                chat_file_uploads::Column::ChatId.in_subquery(
+                    chats::Entity::find()
+                        .select_only()
+                        .column(chats::Column::Id)
+                        .filter(self.cold_chats())
+                        .into_query(),
+                ),
+            )
+            .into_query()
+    }
+}
+
+/// A cold resource, as seen from the policy data of an organization.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ColdResourceKey {
+    resource_kind: &'static str,
+    resource_id: Uuid,
+    organization_id: Option<String>,
+}
+
+/// Looks up cold resources in the database, for the policy data of a single rebuild.
+#[derive(Debug)]
+struct ColdTier {
+    db: DatabaseConnection,
+    cutoffs: ColdCutoffs,
+    /// Recently authorized cold resources, kept in memory until the next rebuild.
+    promoted: Cache<ColdResourceKey, Arc<JsonValue>>,
+}
+
+fn organization_condition(column: impl ColumnTrait, organization_id: Option<&str>) -> Condition {
+    match organization_id {
+        Some(organization_id) => Condition::all().add(column.eq(organization_id)),
+        None => Condition::all().add(column.is_null()),
+    }
+}
+
+/// Fetch the policy data of a cold resource, to be added to the in-memory data of its
+/// organization.
+///
+/// For a file, this includes its linked cold chats, which grant access to it. Returns `None` if
+/// the resource doesn't exist, is not cold, or belongs to another organization.
+async fn fetch_cold_resource_data(
+    db: &DatabaseConnection,
+    cutoffs: &ColdCutoffs,
+    key: &ColdResourceKey,
+) -> Result<Option<JsonValue>, Report> {
+    let organization_id = key.organization_id.as_deref();
+    let lookup_chats = |query: sea_orm::Select<chats::Entity>| {
+        query
+            .filter(cutoffs.lookup_chats())
+            .into_model::<ChatPolicyAttributes>()
+    };
+    // Like in the in-memory data, only chats of the organization are included
+    let chat_attributes = |chats: Vec<ChatPolicyAttributes>| -> serde_json::Map<String, JsonValue> {
+        chats
+            .into_iter()
+            .filter(|chat| chat.organization_id.as_deref() == organization_id)
+            .map(|chat| (chat.id.to_string(), chat.into_json()))
+            .collect()
+    };
+
+    match key.resource_kind {
+        "chat" => {
+            let chats = lookup_chats(
+                ChatPolicyAttributes::select().filter(chats::Column::Id.eq(key.resource_id)),
+            )
+            .all(db)
+            .await?;
+            let chats = chat_attributes(chats);
+            if chats.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(json!({
+                "resource_attributes": { "chat": chats },
+            })))
+        }
+        "file_upload" => {
+            let linked_chat_ids: Vec<Uuid> = ChatFileUploads::find()
+                .select_only()
+                .column(chat_file_uploads::Column::ChatId)
+                .filter(chat_file_uploads::Column::FileUploadId.eq(key.resource_id))
+                .into_tuple()
+                .all(db)
+                .await?;
+            let linked_chats = lookup_chats(
+                ChatPolicyAttributes::select()
+                    .filter(chats::Column::Id.is_in(linked_chat_ids.clone())),
+            )
+            .all(db)
+            .await?;
+            if linked_chats.is_empty() {
+                return Ok(None);
+            }
+            let Some(file_upload) = FileUploadPolicyAttributes::select()
+                .filter(file_uploads::Column::Id.eq(key.resource_id))
+                .filter(organization_condition(
+                    file_uploads::Column::OrganizationId,
+                    organization_id,
+                ))
+                .into_model::<FileUploadPolicyAttributes>()
+                .one(db)
+                .await?
+            else {
+                return Ok(None);
+            };
+            let linked_assistant_ids: Vec<Uuid> = AssistantFileUploads::find()
+                .select_only()
+                .column(assistant_file_uploads::Column::AssistantId)
+                .filter(assistant_file_uploads::Column::FileUploadId.eq(key.resource_id))
+                .into_tuple()
+                .all(db)
+                .await?;
+            let file_upload = file_upload.into_json(
+                linked_chat_ids.iter().map(Uuid::to_string).collect(),
+                linked_assistant_ids.iter().map(Uuid::to_string).collect(),
+            );
+            Ok(Some(json!({
+                "resource_attributes": {
+                    "chat": chat_attributes(linked_chats),
+                    "file_upload": { key.resource_id.to_string(): file_upload },
+                },
+            })))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn organization_id_of(value: &JsonValue) -> Option<String> {
+    value
+        .get("organization_id")
+        .and_then(JsonValue::as_str)
+        .map(String::from)
+}
+
+/// Split resource attributes (keyed by resource ID) by the organization of the resources.
+fn partition_attributes_by_organization(
+    attributes: JsonValue,
+) -> HashMap<Option<String>, serde_json::Map<String, JsonValue>> {
+    let mut partitions: HashMap<Option<String>, serde_json::Map<String, JsonValue>> =
+        HashMap::new();
+    if let JsonValue::Object(attributes) = attributes {
+        for (resource_id, resource_attributes) in attributes {
+            partitions
+                .entry(organization_id_of(&resource_attributes))
+                .or_default()
+                .insert(resource_id, resource_attributes);
+        }
+    }
+    partitions
+}
+
+/// Split share grants by the organization they were created in.
+fn partition_share_grants_by_organization(
+    grants: JsonValue,
+) -> HashMap<Option<String>, Vec<JsonValue>> {
+    let mut partitions: HashMap<Option<String>, Vec<JsonValue>> = HashMap::new();
+    if let JsonValue::Array(grants) = grants {
+        for grant in grants {
+            partitions
+                .entry(organization_id_of(&grant))
+                .or_default()
+                .push(grant);
+        }
+    }
+    partitions
+}
+
+/// The resources of a single organization.
+#[derive(Debug, Default)]
+struct OrganizationResources {
+    chats: serde_json::Map<String, JsonValue>,
+    assistants: serde_json::Map<String, JsonValue>,
+    file_uploads: serde_json::Map<String, JsonValue>,
+    share_grants: Vec<JsonValue>,
+}
+
+/// Build the policy data for the users of a single organization.
+///
+/// Only contains the resources of that organization, and the config resources available to it.
+/// Share links and assistant hub versions only take effect together with the chat or assistant
+/// they refer to, so they are included for every organization.
+fn organization_policy_data(
+    config: &AppConfig,
+    organization_id: Option<&str>,
+    resources: OrganizationResources,
+    assistant_hub_versions_data: &JsonValue,
+    share_links_data: &JsonValue,
+) -> JsonValue {
+    let chat_provider_ids: Vec<String> = if config.chat_providers.is_some() {
+        config
+            .chat_provider_and_group_ids()
+            .into_iter()
+            .map(String::from)
+            .collect()
+    } else if config.chat_provider.is_some() {
+        vec!["default".to_string()]
+    } else {
+        Vec::new()
+    };
+    let chat_provider_data = config_resources_policy_data(
+        chat_provider_ids
+            .into_iter()
+            .filter(|id| config.organization_allows_chat_provider(organization_id, id)),
+    );
+    let mcp_server_data = config_resources_policy_data(config.mcp_servers.keys().cloned());
+    let facet_data = config_resources_policy_data(
+        config
+            .experimental_facets
+            .facets
+            .keys()
+            .filter(|id| config.organization_allows_facet(organization_id, id))
+            .cloned(),
+    );
+
+    // Combine all resource attributes
+    let resource_attributes = json!({
+        "chat": resources.chats,
+        "assistant": resources.assistants,
+        "file_upload": resources.file_uploads,
+        "chat_provider": chat_provider_data,
+        "mcp_server": mcp_server_data,
+        "facet": facet_data,
+    });
+    json!({
+        "resource_attributes": resource_attributes,
+        "share_grants": resources.share_grants,
+        "assistant_hub_versions": assistant_hub_versions_data,
+        "share_links": share_links_data,
+        "config": {
+            "chat_sharing": {
+                "enabled": config.chat_sharing.enabled,
+            },
+        },
+        "config_permissions": build_config_permissions_policy_data(config),
+    })
+}
+
 fn config_resources_policy_data(resource_ids: impl IntoIterator<Item = String>) -> JsonValue {
     let mut attributes = serde_json::Map::new();
     for resource_id in resource_ids {
@@ -336,14 +697,46 @@ macro_rules! authorize {
 }
 pub(crate) use authorize;
 
+/// Policy engines that are each loaded with the data of a single organization.
+#[derive(Debug, Clone)]
+struct OrganizationEngines {
+    /// Engine with the policy, but without data, that the other engines are created from.
+    policy_engine: Engine,
+    by_organization: HashMap<Option<String>, Engine>,
+    /// Engine for organizations that have no resources yet.
+    other_organizations: Engine,
+}
+
+impl OrganizationEngines {
+    fn for_organization(&self, organization_id: Option<&str>) -> &Engine {
+        self.by_organization
+            .get(&organization_id.map(String::from))
+            .unwrap_or(&self.other_organizations)
+    }
+}
+
+/// Authorizes actions of subjects on resources, based on the backend policy.
+///
+/// The policy data is partitioned by organization: authorization requests are evaluated with the
+/// data of the subject's organization only, so resources of other organizations can't be
+/// accessed, even with a known resource ID.
+///
+/// With tiering enabled, cold chats and files (see `ColdCutoffs`) are not part of the in-memory
+/// data. If authorizing a chat or file fails with the in-memory data, it is looked up in the
+/// database and evaluated again with its data added. As the policy only grants access based on
+/// the data of a resource, this gives the same result as evaluating with all data in memory.
 #[derive(Debug, Clone)]
 pub struct PolicyEngine {
-    engine: Arc<RwLock<Engine>>,
+    engines: Arc<RwLock<OrganizationEngines>>,
     data_needs_rebuild: Arc<RwLock<bool>>,
     /// Serializes rebuilds process-wide (shared by request-scoped clones):
     /// concurrent stale observers queue here and re-check staleness after
     /// acquiring, so they coalesce into a single rebuild.
     rebuild_lock: Arc<Mutex<()>>,
+    /// Lookup of cold resources for the current data, `None` if tiering is disabled.
+    cold_tier: Arc<RwLock<Option<Arc<ColdTier>>>>,
+    /// Cold resources looked up by a request-scoped clone, including the ones that weren't found.
+    request_cache: Option<Arc<Mutex<HashMap<ColdResourceKey, Option<Arc<JsonValue>>>>>>,
 }
 
 impl Default for PolicyEngine {
@@ -362,30 +755,64 @@ impl PolicyEngine {
             .wrap_err("Failed to read backend policy")
             .unwrap();
         Self {
-            engine: Arc::new(RwLock::new(engine)),
+            engines: Arc::new(RwLock::new(OrganizationEngines {
+                policy_engine: engine.clone(),
+                by_organization: HashMap::new(),
+                other_organizations: engine,
+            })),
             data_needs_rebuild: Arc::new(RwLock::new(true)),
             rebuild_lock: Arc::new(Mutex::new(())),
+            cold_tier: Arc::new(RwLock::new(None)),
+            request_cache: None,
         }
     }
 
     /// Clone the engine for use in a request handler.
     /// Unlike regular Clone, this creates an independent `data_needs_rebuild` state
     /// set to `false`, so that invalidating the global engine doesn't affect
-    /// cloned request-scoped engines.
+    /// cloned request-scoped engines. Cold resources are only looked up once per clone.
     pub fn clone_for_request(&self) -> Self {
         Self {
-            engine: self.engine.clone(),
+            engines: self.engines.clone(),
             data_needs_rebuild: Arc::new(RwLock::new(false)),
             rebuild_lock: self.rebuild_lock.clone(),
+            cold_tier: self.cold_tier.clone(),
+            request_cache: Some(Arc::new(Mutex::new(HashMap::new()))),
         }
     }
 
+    /// Set the data for subjects without organization.
+    #[cfg(test)]
     async fn set_data(&self, data: JsonValue) -> Result<(), Report> {
-        let mut guard = self.engine.write().await;
-        guard.clear_data();
-        guard
-            .add_data_json(&data.to_string())
-            .map_err(|e| eyre!(e))?;
+        self.set_organization_data(HashMap::from([(None, data)]), json!({}))
+            .await
+    }
+
+    /// Set the data of every organization, and the data for organizations without resources.
+    async fn set_organization_data(
+        &self,
+        data_by_organization: HashMap<Option<String>, JsonValue>,
+        other_organizations_data: JsonValue,
+    ) -> Result<(), Report> {
+        let policy_engine = self.engines.read().await.policy_engine.clone();
+        let mut data_size_bytes = 0;
+        let mut load_engine = |data: &JsonValue| -> Result<Engine, Report> {
+            let mut engine = policy_engine.clone();
+            let data_json = data.to_string();
+            data_size_bytes += data_json.len();
+            engine.add_data_json(&data_json).map_err(|e| eyre!(e))?;
+            Ok(engine)
+        };
+        let mut by_organization = HashMap::with_capacity(data_by_organization.len());
+        for (organization_id, data) in &data_by_organization {
+            by_organization.insert(organization_id.clone(), load_engine(data)?);
+        }
+        let other_organizations = load_engine(&other_organizations_data)?;
+        report_policy_dataset_size(data_size_bytes, by_organization.len() + 1);
+
+        let mut guard = self.engines.write().await;
+        guard.by_organization = by_organization;
+        guard.other_organizations = other_organizations;
         *self.data_needs_rebuild.write().await = false;
         Ok(())
     }
@@ -410,49 +837,65 @@ impl PolicyEngine {
         db: &DatabaseConnection,
         config: &AppConfig,
     ) -> Result<(), Report> {
+        let tiering = &config.policy_engine.tiering;
+        let cold_cutoffs = tiering.enabled.then(|| ColdCutoffs::new(tiering));
+
         // Fetch policy data for each resource type
-        let chat_data = fetch_chat_policy_data(db).await?;
+        let chat_data = fetch_chat_policy_data(db, cold_cutoffs.as_ref()).await?;
         let assistant_data = fetch_assistant_policy_data(db).await?;
-        let file_upload_data = fetch_file_upload_policy_data(db).await?;
+        let file_upload_data = fetch_file_upload_policy_data(db, cold_cutoffs.as_ref()).await?;
         let share_grants_data = fetch_share_grants_policy_data(db).await?;
         let assistant_hub_versions_data = fetch_assistant_hub_versions_policy_data(db).await?;
         let share_links_data = fetch_share_links_policy_data(db).await?;
-        let chat_provider_data = config_resources_policy_data(
-            if let Some(chat_providers) = config.chat_providers.as_ref() {
-                chat_providers.providers.keys().cloned().collect()
-            } else if config.chat_provider.is_some() {
-                vec!["default".to_string()]
-            } else {
-                Vec::new()
-            },
+
+        // Partition the resources by organization
+        let mut chats = partition_attributes_by_organization(chat_data);
+        let mut assistants = partition_attributes_by_organization(assistant_data);
+        let mut file_uploads = partition_attributes_by_organization(file_upload_data);
+        let mut share_grants = partition_share_grants_by_organization(share_grants_data);
+        let mut organization_ids: HashSet<Option<String>> = HashSet::from([None]);
+        organization_ids.extend(config.organizations.keys().cloned().map(Some));
+        organization_ids.extend(chats.keys().cloned());
+        organization_ids.extend(assistants.keys().cloned());
+        organization_ids.extend(file_uploads.keys().cloned());
+        organization_ids.extend(share_grants.keys().cloned());
+
+        let mut data_by_organization = HashMap::with_capacity(organization_ids.len());
+        for organization_id in organization_ids {
+            let resources = OrganizationResources {
+                chats: chats.remove(&organization_id).unwrap_or_default(),
+                assistants: assistants.remove(&organization_id).unwrap_or_default(),
+                file_uploads: file_uploads.remove(&organization_id).unwrap_or_default(),
+                share_grants: share_grants.remove(&organization_id).unwrap_or_default(),
+            };
+            let data = organization_policy_data(
+                config,
+                organization_id.as_deref(),
+                resources,
+                &assistant_hub_versions_data,
+                &share_links_data,
+            );
+            data_by_organization.insert(organization_id, data);
+        }
+        // Organizations that are neither configured nor have resources yet
+        let other_organizations_data = organization_policy_data(
+            config,
+            None,
+            OrganizationResources::default(),
+            &assistant_hub_versions_data,
+            &share_links_data,
         );
-        let mcp_server_data = config_resources_policy_data(config.mcp_servers.keys().cloned());
-        let facet_data =
-            config_resources_policy_data(config.experimental_facets.facets.keys().cloned());
-
-        // Combine all resource attributes
-        let resource_attributes = json!({
-            "chat": chat_data,
-            "assistant": assistant_data,
-            "file_upload": file_upload_data,
-            "chat_provider": chat_provider_data,
-            "mcp_server": mcp_server_data,
-            "facet": facet_data,
-        });
-        let policy_data = json!({
-            "resource_attributes": resource_attributes,
-            "share_grants": share_grants_data,
-            "assistant_hub_versions": assistant_hub_versions_data,
-            "share_links": share_links_data,
-            "config": {
-                "chat_sharing": {
-                    "enabled": config.chat_sharing.enabled,
-                },
-            },
-            "config_permissions": build_config_permissions_policy_data(config),
-        });
 
-        self.set_data(policy_data).await?;
+        self.set_organization_data(data_by_organization, other_organizations_data)
+            .await?;
+        // Cold resources promoted before the rebuild may have changed, so they are dropped
+        *self.cold_tier.write().await = cold_cutoffs.map(|cutoffs| {
+            Arc::new(ColdTier {
+                db: db.clone(),
+                cutoffs,
+                promoted: Cache::new(tiering.promoted_capacity),
+            })
+        });
         // info!("Finished policy data rebuild");
         Ok(())
     }
@@ -503,6 +946,7 @@ impl AuthorizeFull for PolicyEngine {
             resource_kind,
             resource_id,
             action,
+            None,
             &[],
             &[],
         )
@@ -512,6 +956,8 @@ impl AuthorizeFull for PolicyEngine {
 
 impl PolicyEngine {
     /// Authorize with additional context (e.g., organization_group_ids).
+    ///
+    /// Only the data of the organization `organization_id` is considered.
     #[allow(clippy::too_many_arguments)]
     pub async fn authorize_with_context(
         &self,
@@ -520,6 +966,7 @@ impl PolicyEngine {
         resource_kind: ResourceKind,
         resource_id: &ResourceId,
         action: Action,
+        organization_id: Option<&str>,
         organization_group_ids: &[String],
         groups: &[String],
     ) -> Result<(), Report> {
@@ -532,8 +979,9 @@ impl PolicyEngine {
         // First validate the resource_kind-action combination as an assertion
         authorize_general(resource_kind, action);
 
-        let engine = self.engine.read().await;
-        let mut engine = engine.clone();
+        let engines = self.engines.read().await;
+        let mut engine = engines.for_organization(organization_id).clone();
+        drop(engines);
 
         let input = json!({
             "subject_kind": subject_kind,
@@ -549,10 +997,29 @@ impl PolicyEngine {
             .set_input_json(&serde_json::to_string(&input)?)
             .map_err(|e| eyre!(e))?;
 
-        let result = engine
+        let mut result = engine
             .eval_bool_query("data.backend.allow".to_string(), false)
             .map_err(|e| eyre!(e))?;
 
+        if !result
+            && let Some(cold_data) = self
+                .cold_resource_data(resource_kind, resource_id, organization_id)
+                .await?
+        {
+            // Fails if the resource was in the in-memory data and changed since, which is
+            // covered by the next rebuild
+            match engine.add_data_json(&cold_data.to_string()) {
+                Ok(()) => {
+                    result = engine
+                        .eval_bool_query("data.backend.allow".to_string(), false)
+                        .map_err(|e| eyre!(e))?;
+                }
+                Err(err) => {
+                    tracing::debug!(error = %err, "Failed to add the data of a cold resource");
+                }
+            }
+        }
+
         if result {
             Ok(())
         } else {
@@ -560,6 +1027,59 @@ impl PolicyEngine {
         }
     }
 
+    /// The policy data of a cold chat or file, from the request cache, the promoted resources, or
+    /// the database.
+    async fn cold_resource_data(
+        &self,
+        resource_kind: ResourceKind,
+        resource_id: &ResourceId,
+        organization_id: Option<&str>,
+    ) -> Result<Option<Arc<JsonValue>>, Report> {
+        let resource_kind = match resource_kind {
+            ResourceKind::Chat => "chat",
+            ResourceKind::FileUpload => "file_upload",
+            _ => return Ok(None),
+        };
+        let Ok(resource_id) = Uuid::parse_str(&resource_id.0) else {
+            return Ok(None);
+        };
+        let Some(cold_tier) = self.cold_tier.read().await.clone() else {
+            return Ok(None);
+        };
+        let key = ColdResourceKey {
+            resource_kind,
+            resource_id,
+            organization_id: organization_id.map(String::from),
+        };
+
+        if let Some(request_cache) = &self.request_cache
+            && let Some(data) = request_cache.lock().await.get(&key)
+        {
+            report_policy_cold_lookup("request_cache");
+            return Ok(data.clone());
+        }
+        let data = match cold_tier.promoted.get(&key).await {
+            Some(data) => {
+                report_policy_cold_lookup("promoted");
+                Some(data)
+            }
+            None => {
+                report_policy_cold_lookup("database");
+                let data = fetch_cold_resource_data(&cold_tier.db, &cold_tier.cutoffs, &key)
+                    .await?
+                    .map(Arc::new);
+                if let Some(data) = &data {
+                    cold_tier.promoted.insert(key.clone(), data.clone()).await;
+                }
+                data
+            }
+        };
+        if let Some(request_cache) = &self.request_cache {
+            request_cache.lock().await.insert(key, data.clone());
+        }
+        Ok(data)
+    }
+
     async fn filter_authorized_config_resources(
         &self,
         subject: &Subject,
@@ -593,6 +1113,7 @@ impl PolicyEngine {
                     resource_kind,
                     &ResourceId(resource_id.clone()),
                     Action::Read,
+                    subject.organization_id(),
                     subject.organization_group_ids(),
                     groups,
                 )
@@ -702,6 +1223,7 @@ impl AuthorizeShort for PolicyEngine {
             resource_kind,
             &resource_id,
             action,
+            subject.organization_id(),
             organization_group_ids,
             &[],
         )
@@ -837,6 +1359,7 @@ mod tests {
     async fn test_authorize_with_organization_group_share_grant() {
         let subject = Subject::UserWithOrganizationInfo {
             id: "user_3".to_string(),
+            organization_id: None,
             organization_user_id: None,
             organization_group_ids: vec!["org-group-1".to_string(), "org-group-2".to_string()],
         };
@@ -877,6 +1400,7 @@ mod tests {
     async fn test_authorize_without_organization_group_share_grant() {
         let subject = Subject::UserWithOrganizationInfo {
             id: "user_3".to_string(),
+            organization_id: None,
             organization_user_id: None,
             organization_group_ids: vec!["org-group-2".to_string()], // Not in org-group-1
         };
@@ -1023,6 +1547,7 @@ mod tests {
     async fn test_authorize_file_upload_read_via_linked_assistant_org_group_share_grant() {
         let subject = Subject::UserWithOrganizationInfo {
             id: "user_3".to_string(),
+            organization_id: None,
             organization_user_id: None,
             organization_group_ids: vec!["org-group-1".to_string()],
         };
@@ -1160,6 +1685,62 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_authorize_only_considers_data_of_subject_organization() {
+        let engine = PolicyEngine::new();
+        engine
+            .set_organization_data(
+                HashMap::from([(
+                    Some("org-a".to_string()),
+                    json!({
+                        "resource_attributes": {
+                            "chat": {
+                                "chat_1": {
+                                    "id": "chat_1",
+                                    "owner_id": "user_1",
+                                    "organization_id": "org-a"
+                                }
+                            }
+                        }
+                    }),
+                )]),
+                json!({}),
+            )
+            .await
+            .unwrap();
+        let subject_in_organization =
+            |organization_id: Option<&str>| Subject::UserWithOrganizationInfo {
+                id: "user_1".to_string(),
+                organization_id: organization_id.map(String::from),
+                organization_user_id: None,
+                organization_group_ids: vec![],
+            };
+        let resource = Resource::Chat("chat_1".to_string());
+
+        let result = authorize!(
+            engine,
+            &subject_in_organization(Some("org-a")),
+            &resource,
+            Action::Read
+        );
+        assert!(result.is_ok());
+        // The data of other organizations is not visible, even with a matching owner ID
+        let result = authorize!(
+            engine,
+            &subject_in_organization(Some("org-b")),
+            &resource,
+            Action::Read
+        );
+        assert!(result.is_err());
+        let result = authorize!(
+            engine,
+            &subject_in_organization(None),
+            &resource,
+            Action::Read
+        );
+        assert!(result.is_err());
+    }
+
     async fn build_config_resource_test_engine() -> PolicyEngine {
         let engine = PolicyEngine::new();
         engine
@@ -15,6 +15,8 @@ pub enum Subject {
     User(String),
     UserWithOrganizationInfo {
         id: String,
+        /// The organization (tenant) of the user, which limits the data the user can access.
+        organization_id: Option<String>,
         organization_user_id: Option<String>,
         organization_group_ids: Vec<String>,
     },
@@ -41,6 +43,15 @@ impl Subject {
         }
     }
 
+    pub fn organization_id(&self) -> Option<&str> {
+        match self {
+            Subject::User(_) => None,
+            Subject::UserWithOrganizationInfo {
+                organization_id, ..
+            } => organization_id.as_deref(),
+        }
+    }
+
     pub fn organization_user_id(&self) -> Option<&str> {
         match self {
             Subject::User(_) => None,
@@ -0,0 +1,887 @@
+//! OpenAI-compatible chat completions API under `/compat/v1`.
+//!
+//! Lets existing OpenAI SDKs and tools use the chat providers of Erato. Requests are passed to
+//! the chat provider as they are, without the system prompts, assistants and facets of Erato
+//! chats, but the model permissions, the moderation of user messages and the per-user quotas of
+//! the chat providers are applied like for messages sent in the UI.
+//!
+//! The model of a request is mapped to a chat provider with `compat_api.models`, or used as the
+//! ID of a chat provider. With `compat_api.usage_recording = "chat"`, the last user message and
+//! the answer of each request are stored in a chat of the user titled `compat_api.chat_title`.
+//!
+//! Errors are returned in the shape of OpenAI errors, so that SDKs can surface them.
+
+use crate::config::CompatApiUsageRecording;
+use crate::db::entity::chats;
+use crate::models::chat::{ChatCreationStatus, get_or_create_chat};
+use crate::models::message::{
+    ContentPart, ContentPartText, GenerationMetadata, GenerationParameters, MessageRole,
+    MessageSchema, StopReason, get_latest_active_thread_message, submit_message,
+};
+use crate::policy::engine::PolicyEngine;
+use crate::server::api::v1beta::me_profile_middleware::{self, MeProfile};
+use crate::server::api::v1beta::message_streaming::validate_message_content;
+use crate::server::api::v1beta::policy_engine_middleware;
+use crate::services::chat_provider_quotas;
+use crate::services::genai::build_chat_options_for_completion;
+use crate::services::moderation::{ModerationOutcome, moderate_text};
+use crate::services::sentry::{capture_report, log_internal_server_error};
+use crate::services::user_events::UserEvent;
+use crate::state::{AppState, ChatProviderConfigWithId};
+use axum::extract::State;
+use axum::extract::rejection::JsonRejection;
+use axum::http::StatusCode;
+use axum::response::sse::{Event, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router, middleware};
+use chrono::Utc;
+use eyre::{Report, WrapErr};
+use futures::StreamExt;
+use genai::chat::{
+    ChatMessage as GenAiChatMessage, ChatRequest, ChatStreamEvent, StreamChunk, StreamEnd,
+};
+use sea_orm::prelude::Uuid;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value as JsonValue, json};
+use std::collections::HashMap;
+use tracing::Instrument;
+
+pub fn router(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/models", get(list_models))
+        .route("/chat/completions", post(chat_completions))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            policy_engine_middleware::policy_engine_middleware,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            app_state,
+            me_profile_middleware::user_profile_middleware,
+        ))
+}
+
+/// An error in the shape of the errors of the OpenAI API.
+#[derive(Debug)]
+pub struct CompatApiError {
+    status: StatusCode,
+    message: String,
+    error_type: &'static str,
+    param: Option<String>,
+    code: Option<&'static str>,
+}
+
+impl CompatApiError {
+    fn invalid_request(param: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+            error_type: "invalid_request_error",
+            param: param.map(str::to_string),
+            code: None,
+        }
+    }
+
+    fn unsupported_parameter(param: &str) -> Self {
+        Self {
+            code: Some("unsupported_parameter"),
+            ..Self::invalid_request(
+                Some(param),
+                format!("The parameter `{param}` is not supported by this API."),
+            )
+        }
+    }
+
+    fn not_found() -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: "The OpenAI-compatible API is not enabled.".to_string(),
+            error_type: "invalid_request_error",
+            param: None,
+            code: None,
+        }
+    }
+
+    fn model_not_found(model: &str) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: format!("The model `{model}` does not exist or you do not have access to it."),
+            error_type: "invalid_request_error",
+            param: Some("model".to_string()),
+            code: Some("model_not_found"),
+        }
+    }
+
+    fn content_policy_violation() -> Self {
+        Self {
+            code: Some("content_policy_violation"),
+            ..Self::invalid_request(
+                Some("messages"),
+                "The message was blocked by the content moderation.",
+            )
+        }
+    }
+
+    fn moderation_unavailable() -> Self {
+        Self {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            message: "The message could not be moderated. Please try again later.".to_string(),
+            error_type: "server_error",
+            param: None,
+            code: Some("moderation_unavailable"),
+        }
+    }
+
+    fn quota_exceeded(message: String) -> Self {
+        Self {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            message,
+            error_type: "insufficient_quota",
+            param: None,
+            code: Some("insufficient_quota"),
+        }
+    }
+
+    fn internal(report: Report) -> Self {
+        Self {
+            status: log_internal_server_error(report),
+            message: "The server had an error while processing your request.".to_string(),
+            error_type: "server_error",
+            param: None,
+            code: None,
+        }
+    }
+
+    fn body(&self) -> JsonValue {
+        json!({
+            "error": {
+                "message": self.message,
+                "type": self.error_type,
+                "param": self.param,
+                "code": self.code,
+            }
+        })
+    }
+}
+
+impl IntoResponse for CompatApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.body())).into_response()
+    }
+}
+
+impl From<JsonRejection> for CompatApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self {
+            status: rejection.status(),
+            ..Self::invalid_request(None, rejection.body_text())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatCompletionRequest {
+    model: String,
+    messages: Vec<ChatCompletionMessage>,
+    #[serde(default)]
+    stream: bool,
+    stream_options: Option<StreamOptions>,
+    max_tokens: Option<u32>,
+    max_completion_tokens: Option<u32>,
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    n: Option<u32>,
+    response_format: Option<ResponseFormat>,
+    // Parameters that are rejected, as they can't be passed on faithfully
+    tools: Option<JsonValue>,
+    tool_choice: Option<JsonValue>,
+    functions: Option<JsonValue>,
+    function_call: Option<JsonValue>,
+    logprobs: Option<bool>,
+    top_logprobs: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamOptions {
+    #[serde(default)]
+    include_usage: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseFormat {
+    #[serde(rename = "type")]
+    format_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionMessage {
+    role: String,
+    content: Option<ChatCompletionContent>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ChatCompletionContent {
+    Text(String),
+    Parts(Vec<ChatCompletionContentPart>),
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionContentPart {
+    #[serde(rename = "type")]
+    part_type: String,
+    text: Option<String>,
+}
+
+impl ChatCompletionMessage {
+    /// The text of the message. Only text content is supported.
+    fn text(&self, index: usize) -> Result<String, CompatApiError> {
+        match &self.content {
+            None => Ok(String::new()),
+            Some(ChatCompletionContent::Text(text)) => Ok(text.clone()),
+            Some(ChatCompletionContent::Parts(parts)) => parts
+                .iter()
+                .map(|part| match (part.part_type.as_str(), &part.text) {
+                    ("text", Some(text)) => Ok(text.as_str()),
+                    _ => Err(CompatApiError::invalid_request(
+                        Some(&format!("messages[{index}].content")),
+                        format!(
+                            "Content parts of type `{}` are not supported by this API.",
+                            part.part_type
+                        ),
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(|texts| texts.join("\n")),
+        }
+    }
+}
+
+impl ChatCompletionRequest {
+    fn check_supported(&self) -> Result<(), CompatApiError> {
+        let unsupported = [
+            ("tools", self.tools.is_some()),
+            ("tool_choice", self.tool_choice.is_some()),
+            ("functions", self.functions.is_some()),
+            ("function_call", self.function_call.is_some()),
+            ("logprobs", self.logprobs == Some(true)),
+            ("top_logprobs", self.top_logprobs.is_some()),
+            ("n", self.n.is_some_and(|n| n != 1)),
+            (
+                "response_format",
+                self.response_format
+                    .as_ref()
+                    .is_some_and(|format| format.format_type != "text"),
+            ),
+        ];
+        if let Some((param, _)) = unsupported.iter().find(|(_, unsupported)| *unsupported) {
+            return Err(CompatApiError::unsupported_parameter(param));
+        }
+        if self.messages.is_empty() {
+            return Err(CompatApiError::invalid_request(
+                Some("messages"),
+                "At least one message is required.",
+            ));
+        }
+        Ok(())
+    }
+
+    fn chat_request(&self) -> Result<ChatRequest, CompatApiError> {
+        let mut messages = Vec::with_capacity(self.messages.len());
+        for (index, message) in self.messages.iter().enumerate() {
+            let text = message.text(index)?;
+            messages.push(match message.role.as_str() {
+                "system" | "developer" => GenAiChatMessage::system(text),
+                "user" => GenAiChatMessage::user(text),
+                "assistant" => GenAiChatMessage::assistant(text),
+                role => {
+                    return Err(CompatApiError::invalid_request(
+                        Some(&format!("messages[{index}].role")),
+                        format!("Messages with the role `{role}` are not supported by this API."),
+                    ));
+                }
+            });
+        }
+        Ok(ChatRequest::new(messages))
+    }
+
+    /// The text of the last user message, which is moderated and recorded.
+    ///
+    /// As files can't be attached, the message is rejected if it has no text.
+    fn last_user_text(&self) -> Result<Option<String>, CompatApiError> {
+        let Some((index, message)) = self
+            .messages
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, message)| message.role == "user")
+        else {
+            return Ok(None);
+        };
+        let text = message.text(index)?;
+        validate_message_content(&text, &[]).map_err(|(_, error)| {
+            CompatApiError::invalid_request(Some(&format!("messages[{index}].content")), error)
+        })?;
+        Ok(Some(text))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+struct CompletionUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+}
+
+fn finish_reason(stop_reason: Option<StopReason>) -> &'static str {
+    match stop_reason {
+        Some(StopReason::MaxTokens) => "length",
+        Some(StopReason::ContentFilter) => "content_filter",
+        Some(StopReason::ToolCalls) => "tool_calls",
+        _ => "stop",
+    }
+}
+
+/// The chat provider IDs of the models of the user, and the names of the models mapped to them.
+async fn model_ids(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+) -> Result<Vec<(String, String)>, Report> {
+    let available_chat_provider_ids: Vec<String> = app_state
+        .available_models(policy, &me_user.to_subject(), &me_user.groups)
+        .await?
+        .into_iter()
+        .map(|model| model.chat_provider_id)
+        .collect();
+
+    let mut mapped: Vec<(String, String)> = app_state
+        .config
+        .compat_api
+        .models
+        .iter()
+        .filter(|(_, chat_provider_id)| available_chat_provider_ids.contains(chat_provider_id))
+        .map(|(model, chat_provider_id)| (model.clone(), chat_provider_id.clone()))
+        .collect();
+    mapped.sort();
+    mapped.extend(
+        available_chat_provider_ids
+            .into_iter()
+            .map(|chat_provider_id| (chat_provider_id.clone(), chat_provider_id)),
+    );
+    Ok(mapped)
+}
+
+/// List the models available to the user.
+pub async fn list_models(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+) -> Result<Json<JsonValue>, CompatApiError> {
+    if !app_state.config.compat_api.enabled {
+        return Err(CompatApiError::not_found());
+    }
+
+    let data: Vec<JsonValue> = model_ids(&app_state, &policy, &me_user)
+        .await
+        .map_err(CompatApiError::internal)?
+        .into_iter()
+        .map(|(model, _)| {
+            json!({
+                "id": model,
+                "object": "model",
+                "created": 0,
+                "owned_by": "erato",
+            })
+        })
+        .collect();
+    Ok(Json(json!({ "object": "list", "data": data })))
+}
+
+/// Everything that is needed to account for a completion once it has finished.
+struct CompletionRecorder {
+    app_state: AppState,
+    policy: PolicyEngine,
+    me_user: MeProfile,
+    chat_provider: ChatProviderConfigWithId,
+    user_text: Option<String>,
+}
+
+impl CompletionRecorder {
+    /// The chat provider the quota is counted against, which is the group if the chat provider
+    /// was selected from one.
+    fn quota_chat_provider_id(&self) -> &str {
+        self.chat_provider
+            .chat_provider_group_id
+            .as_deref()
+            .unwrap_or(&self.chat_provider.chat_provider_id)
+    }
+
+    async fn record(self, answer: String, usage: CompletionUsage, stop_reason: Option<StopReason>) {
+        if let Ok(user_id) = Uuid::parse_str(&self.me_user.id)
+            && let Err(error) = chat_provider_quotas::record_usage(
+                &self.app_state.db,
+                &self.app_state.config,
+                &user_id,
+                self.quota_chat_provider_id(),
+                usage.total_tokens.into(),
+                Utc::now(),
+            )
+            .await
+        {
+            tracing::warn!(error = ?error, "Failed to record compat API usage");
+            capture_report(&error);
+        }
+
+        if self.app_state.config.compat_api.usage_recording == CompatApiUsageRecording::Chat
+            && let Err(error) = self.store_exchange(answer, usage, stop_reason).await
+        {
+            tracing::warn!(error = ?error, "Failed to store compat API exchange");
+            capture_report(&error);
+        }
+    }
+
+    /// Store the last user message and the answer in the API chat of the user.
+    async fn store_exchange(
+        &self,
+        answer: String,
+        usage: CompletionUsage,
+        stop_reason: Option<StopReason>,
+    ) -> Result<(), Report> {
+        let app_state = &self.app_state;
+        let subject = self.me_user.to_subject();
+        let chat_title = app_state.config.compat_api.chat_title();
+
+        let existing_chat = chats::Entity::find()
+            .filter(chats::Column::OwnerUserId.eq(&self.me_user.id))
+            .filter(chats::Column::TitleByUserProvided.eq(chat_title))
+            .filter(chats::Column::ArchivedAt.is_null())
+            .order_by_desc(chats::Column::CreatedAt)
+            .one(&app_state.db)
+            .await?;
+        let (chat, chat_status) = get_or_create_chat(
+            &app_state.db,
+            &self.policy,
+            &subject,
+            existing_chat.as_ref().map(|chat| &chat.id),
+            &self.me_user.id,
+            None,
+            Some(chat_title.to_string()),
+        )
+        .await?;
+        if chat_status == ChatCreationStatus::Created {
+            app_state.global_policy_engine.invalidate_data().await;
+            app_state.user_events.publish(
+                &self.me_user.id,
+                UserEvent::ChatCreated { chat_id: chat.id },
+            );
+            // The new chat has to be in the policy data before we can write to it.
+            app_state
+                .global_policy_engine
+                .rebuild_data_if_needed(&app_state.db, &app_state.config)
+                .await
+                .wrap_err("Failed to rebuild policy data after chat creation")?;
+        }
+
+        let previous_message =
+            get_latest_active_thread_message(&app_state.db, &self.policy, &subject, &chat.id)
+                .await?;
+        let user_message = submit_message(
+            &app_state.db,
+            &self.policy,
+            &subject,
+            &chat.id,
+            text_message(
+                MessageRole::User,
+                self.user_text.clone().unwrap_or_default(),
+            )
+            .to_json()?,
+            previous_message.as_ref().map(|message| &message.id),
+            None,
+            None,
+            None,
+            &[],
+            None,
+            None,
+            None,
+            false,
+        )
+        .await?;
+        submit_message(
+            &app_state.db,
+            &self.policy,
+            &subject,
+            &chat.id,
+            text_message(MessageRole::Assistant, answer).to_json()?,
+            Some(&user_message.id),
+            None,
+            None,
+            None,
+            &[],
+            Some(GenerationParameters {
+                generation_chat_provider_id: Some(self.chat_provider.chat_provider_id.clone()),
+                requested_chat_provider_id: None,
+                fallback_from_chat_provider_id: None,
+                chat_provider_group_id: self.chat_provider.chat_provider_group_id.clone(),
+                request_context: None,
+                selected_facets: HashMap::new(),
+                action_facet_id: None,
+                action_facet_args: None,
+                response_language: None,
+            }),
+            Some(GenerationMetadata {
+                used_prompt_tokens: Some(usage.prompt_tokens),
+                used_completion_tokens: Some(usage.completion_tokens),
+                used_total_tokens: Some(usage.total_tokens),
+                stop_reason,
+                ..Default::default()
+            }),
+            None,
+            false,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+fn text_message(role: MessageRole, text: String) -> MessageSchema {
+    MessageSchema {
+        content: vec![ContentPart::Text(ContentPartText { text })],
+        role,
+        name: None,
+        additional_fields: HashMap::new(),
+    }
+}
+
+/// Resolve the model of a request to a chat provider that is available to the user.
+async fn resolve_chat_provider(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    model: &str,
+) -> Result<ChatProviderConfigWithId, CompatApiError> {
+    let chat_provider_id = model_ids(app_state, policy, me_user)
+        .await
+        .map_err(CompatApiError::internal)?
+        .into_iter()
+        .find(|(model_id, _)| model_id == model)
+        .map(|(_, chat_provider_id)| chat_provider_id)
+        .ok_or_else(|| CompatApiError::model_not_found(model))?;
+    app_state
+        .chat_provider_for_chatcompletion(
+            policy,
+            &me_user.to_subject(),
+            &me_user.groups,
+            Some(&chat_provider_id),
+            None,
+        )
+        .await
+        .map_err(CompatApiError::internal)
+}
+
+async fn check_quota(
+    app_state: &AppState,
+    me_user: &MeProfile,
+    quota_chat_provider_id: &str,
+) -> Result<(), CompatApiError> {
+    let Ok(user_id) = Uuid::parse_str(&me_user.id) else {
+        return Ok(());
+    };
+    let quota = chat_provider_quotas::user_quota_status(
+        &app_state.db,
+        &app_state.config,
+        &user_id,
+        &me_user.groups,
+        quota_chat_provider_id,
+        Utc::now(),
+    )
+    .await
+    .map_err(CompatApiError::internal)?;
+    match quota.filter(|quota| quota.exceeded) {
+        None => Ok(()),
+        Some(quota) => Err(CompatApiError::quota_exceeded(match quota.resets_at {
+            Some(resets_at) => format!(
+                "You have reached your usage quota for this model. It resets at {}.",
+                resets_at.to_rfc3339()
+            ),
+            None => "You have reached your usage quota for this model.".to_string(),
+        })),
+    }
+}
+
+/// Create a chat completion, optionally streamed as `chat.completion.chunk` events.
+pub async fn chat_completions(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    request: Result<Json<ChatCompletionRequest>, JsonRejection>,
+) -> Result<Response, CompatApiError> {
+    if !app_state.config.compat_api.enabled {
+        return Err(CompatApiError::not_found());
+    }
+    let Json(request) = request?;
+    request.check_supported()?;
+    let chat_request = request.chat_request()?;
+    let user_text = request.last_user_text()?;
+
+    let chat_provider =
+        resolve_chat_provider(&app_state, &policy, &me_user, &request.model).await?;
+
+    if let Some(user_text) = &user_text {
+        match moderate_text(&app_state.config.moderation, user_text).await {
+            ModerationOutcome::Moderated(result) if result.blocked => {
+                tracing::info!(
+                    categories = ?result.categories,
+                    "Compat API request blocked by moderation"
+                );
+                return Err(CompatApiError::content_policy_violation());
+            }
+            ModerationOutcome::Unavailable => return Err(CompatApiError::moderation_unavailable()),
+            _ => {}
+        }
+    }
+
+    let recorder = CompletionRecorder {
+        app_state: app_state.clone(),
+        policy,
+        me_user,
+        chat_provider,
+        user_text,
+    };
+    check_quota(
+        &app_state,
+        &recorder.me_user,
+        recorder.quota_chat_provider_id(),
+    )
+    .await?;
+
+    let chat_provider_config = &recorder.chat_provider.chat_provider_config;
+    let mut chat_options = build_chat_options_for_completion(
+        &chat_provider_config.model_settings,
+        &chat_provider_config.model_capabilities,
+    );
+    if let Some(temperature) = request.temperature {
+        chat_options = chat_options.with_temperature(temperature);
+    }
+    if let Some(top_p) = request.top_p {
+        chat_options = chat_options.with_top_p(top_p);
+    }
+    if let Some(max_tokens) = request.max_completion_tokens.or(request.max_tokens) {
+        chat_options = chat_options.with_max_tokens(max_tokens);
+    }
+
+    let genai_client = app_state
+        .genai_for_chat_provider_config_with_headers_context(chat_provider_config.clone(), None)
+        .map_err(CompatApiError::internal)?;
+    let mut stream = genai_client
+        .exec_chat_stream("PLACEHOLDER_MODEL", chat_request, Some(&chat_options))
+        .await
+        .wrap_err("Failed to start compat API stream")
+        .map_err(CompatApiError::internal)?
+        .stream;
+
+    let completion_id = format!("chatcmpl-{}", Uuid::new_v4().simple());
+    let created = Utc::now().timestamp();
+    let model = request.model.clone();
+
+    if !request.stream {
+        let mut answer = String::new();
+        let mut usage = CompletionUsage::default();
+        let mut stop_reason = None;
+        while let Some(event) = stream.next().await {
+            match event
+                .wrap_err("Compat API stream failed")
+                .map_err(CompatApiError::internal)?
+            {
+                ChatStreamEvent::Chunk(StreamChunk { content }) => answer.push_str(&content),
+                ChatStreamEvent::End(stream_end) => {
+                    (usage, stop_reason) = usage_and_stop_reason(&stream_end);
+                }
+                _ => {}
+            }
+        }
+        recorder.record(answer.clone(), usage, stop_reason).await;
+
+        return Ok(Json(json!({
+            "id": completion_id,
+            "object": "chat.completion",
+            "created": created,
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": answer },
+                "finish_reason": finish_reason(stop_reason),
+            }],
+            "usage": usage,
+        }))
+        .into_response());
+    }
+
+    let include_usage = request
+        .stream_options
+        .as_ref()
+        .is_some_and(|options| options.include_usage);
+    let chunk = move |delta: JsonValue, finish_reason: Option<&str>| {
+        json!({
+            "id": completion_id,
+            "object": "chat.completion.chunk",
+            "created": created,
+            "model": model,
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+        })
+    };
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Event, Report>>(100);
+
+    tokio::spawn(
+        async move {
+            let send = |data: JsonValue| {
+                let tx = tx.clone();
+                async move { tx.send(Ok(Event::default().data(data.to_string()))).await }
+            };
+            let mut answer = String::new();
+            let mut usage = CompletionUsage::default();
+            let mut stop_reason = None;
+
+            let _ = send(chunk(json!({ "role": "assistant", "content": "" }), None)).await;
+            while let Some(event) = stream.next().await {
+                match event {
+                    Ok(ChatStreamEvent::Chunk(StreamChunk { content })) => {
+                        answer.push_str(&content);
+                        // The completion is generated to the end even if the client disconnects
+                        let _ = send(chunk(json!({ "content": content }), None)).await;
+                    }
+                    Ok(ChatStreamEvent::End(stream_end)) => {
+                        (usage, stop_reason) = usage_and_stop_reason(&stream_end);
+                    }
+                    Ok(_) => {}
+                    Err(error) => {
+                        let error = Report::new(error).wrap_err("Compat API stream failed");
+                        let _ = send(CompatApiError::internal(error).body()).await;
+                        return;
+                    }
+                }
+            }
+
+            let _ = send(chunk(json!({}), Some(finish_reason(stop_reason)))).await;
+            if include_usage {
+                let mut usage_chunk = chunk(json!({}), None);
+                usage_chunk["choices"] = json!([]);
+                usage_chunk["usage"] = json!(usage);
+                let _ = send(usage_chunk).await;
+            }
+            let _ = tx.send(Ok(Event::default().data("[DONE]"))).await;
+
+            recorder.record(answer, usage, stop_reason).await;
+        }
+        .in_current_span(),
+    );
+
+    let receiver_stream = tokio_stream::wrappers::ReceiverStream::<Result<Event, Report>>::new(rx);
+    Ok(Sse::new(receiver_stream).into_response())
+}
+
+fn usage_and_stop_reason(stream_end: &StreamEnd) -> (CompletionUsage, Option<StopReason>) {
+    let usage = stream_end
+        .captured_usage
+        .as_ref()
+        .map(|usage| {
+            let prompt_tokens = usage.prompt_tokens.unwrap_or(0).max(0) as u32;
+            let completion_tokens = usage.completion_tokens.unwrap_or(0).max(0) as u32;
+            CompletionUsage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: usage
+                    .total_tokens
+                    .map(|total_tokens| total_tokens.max(0) as u32)
+                    .unwrap_or(prompt_tokens + completion_tokens),
+            }
+        })
+        .unwrap_or_default();
+    let stop_reason = stream_end
+        .captured_stop_reason
+        .as_ref()
+        .map(StopReason::from_genai);
+    (usage, stop_reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(request: JsonValue) -> ChatCompletionRequest {
+        serde_json::from_value(request).unwrap()
+    }
+
+    #[test]
+    fn rejects_unsupported_parameters() {
+        let base = json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "Hi" }],
+        });
+        assert!(parse(base.clone()).check_supported().is_ok());
+
+        for (param, value) in [
+            ("tools", json!([])),
+            ("n", json!(2)),
+            ("logprobs", json!(true)),
+            ("response_format", json!({ "type": "json_object" })),
+        ] {
+            let mut request = base.clone();
+            request[param] = value;
+            let error = parse(request).check_supported().unwrap_err();
+            assert_eq!(error.status, StatusCode::BAD_REQUEST);
+            assert_eq!(error.body()["error"]["param"], param);
+            assert_eq!(error.body()["error"]["code"], "unsupported_parameter");
+        }
+
+        let mut request = base;
+        request["response_format"] = json!({ "type": "text" });
+        request["n"] = json!(1);
+        assert!(parse(request).check_supported().is_ok());
+    }
+
+    #[test]
+    fn joins_text_parts_and_finds_last_user_message() {
+        let request = parse(json!({
+            "model": "gpt-4o",
+            "messages": [
+                { "role": "system", "content": "Be brief." },
+                { "role": "user", "content": [
+                    { "type": "text", "text": "First" },
+                    { "type": "text", "text": "Second" },
+                ] },
+                { "role": "assistant", "content": "Ok" },
+            ],
+        }));
+        assert_eq!(
+            request.last_user_text().unwrap().as_deref(),
+            Some("First\nSecond")
+        );
+        assert!(request.chat_request().is_ok());
+
+        let request = parse(json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": [
+                { "type": "image_url", "image_url": { "url": "https://example.com/a.png" } },
+            ] }],
+        }));
+        let error = request.chat_request().unwrap_err();
+        assert_eq!(error.body()["error"]["param"], "messages[0].content");
+    }
+
+    #[test]
+    fn rejects_whitespace_only_user_message() {
+        let request = parse(json!({
+            "model": "gpt-4o",
+            "messages": [
+                { "role": "user", "content": "Hi" },
+                { "role": "assistant", "content": "Hello" },
+                { "role": "user", "content": " \n\t " },
+            ],
+        }));
+        let error = request.last_user_text().unwrap_err();
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.body()["error"]["param"], "messages[2].content");
+    }
+}
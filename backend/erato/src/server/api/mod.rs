@@ -1 +1,2 @@
+pub(crate) mod compat_v1;
 pub(crate) mod v1beta;
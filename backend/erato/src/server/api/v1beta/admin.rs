@@ -0,0 +1,2021 @@
+use crate::config::BudgetCurrency;
+use crate::db::entity::prelude::{Chats, Messages};
+use crate::models::chat::{self, ChatOwnershipTransfer};
+use crate::models::message::{
+    GenerationChatOptions, GenerationMetadata, GenerationParameters, OfferedTools, RateLimitScope,
+    check_thread_integrity, find_cross_chat_message_links, repair_thread_integrity,
+};
+use crate::models::message_redaction::{
+    self, DEFAULT_REDACTION_REPLACEMENT, RedactionRequest, RedactionSpan,
+};
+use crate::models::message_thread_integrity::{ThreadIntegrityReport, ThreadRepairChange};
+use crate::models::ownership_transfer::{self, AssistantOwnershipTransfer};
+use crate::models::{assistant, file_upload};
+use crate::server::api::v1beta::budget::{
+    AssistantUsage, assistant_usage_to_csv, usage_by_assistant,
+};
+use crate::server::api::v1beta::me_profile_middleware::MeProfile;
+use crate::server::api::v1beta::ownership_transfers::{
+    TransferOwnershipRequest, finish_assistant_transfer, finish_chat_transfer,
+    transfer_error_status,
+};
+use crate::services::content_spillover::{self, CONTENT_SPILLOVER_MIGRATION_TASK};
+use crate::services::feature_flags::{
+    self, FeatureFlag, FeatureFlagDefinition, FeatureFlagSource,
+};
+use crate::services::file_pointer_migration::{self, FILE_POINTER_MIGRATION_TASK};
+use crate::services::file_processing_cached::purge_file_cached;
+use crate::services::file_storage_self_test::FileStorageSelfTestResult;
+use crate::services::generation_input_archive::{
+    self, GENERATION_INPUT_ARCHIVAL_DRY_RUN_TASK, GENERATION_INPUT_ARCHIVAL_TASK,
+};
+use crate::services::langfuse::LangfuseStatus;
+use crate::services::maintenance_tasks::get_maintenance_task_progress;
+use crate::services::prompt_composition::manifest::PromptManifest;
+use crate::services::provider_capture::{ProviderCaptureStore, StoredProviderCapture};
+use crate::services::seed::{DEFAULT_SEED, SeedOptions, SeedProfile, SeedSummary, seed_database};
+use crate::services::sentry::log_internal_server_error;
+use crate::services::usage_reporting::{UsageReport, build_usage_report};
+use crate::state::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use chrono::{DateTime, Days, FixedOffset, NaiveDate, NaiveTime, Utc};
+use eyre::WrapErr;
+use regex::Regex;
+use sea_orm::EntityTrait;
+use sea_orm::prelude::Uuid;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
+
+/// A feature flag and its effective value.
+#[derive(Debug, ToSchema, Serialize)]
+pub struct FeatureFlagStatus {
+    /// Name of the flag. Flags named after a config key override the toggle of that key
+    name: String,
+    /// Description of the flag, if any
+    description: Option<String>,
+    /// Whether the feature is enabled for users without a group override or percentage rollout
+    enabled: bool,
+    /// Where the current value comes from. A stored flag takes precedence over the config
+    source: FeatureFlagSource,
+    /// The value configured in the config files, or `null` if the flag has no config toggle
+    config_value: Option<bool>,
+    /// Value of the flag for the members of a group, by group ID. If several groups of a user
+    /// have an override, the feature is enabled if any of them enables it.
+    group_overrides: BTreeMap<String, bool>,
+    /// Percentage of users (0 to 100) that have the feature enabled, if the flag is rolled out
+    /// gradually
+    percentage_rollout: Option<u8>,
+}
+
+impl FeatureFlagStatus {
+    fn new(app_state: &AppState, name: &str) -> Self {
+        let config_value =
+            FeatureFlag::from_name(name).map(|flag| flag.config_value(&app_state.config));
+        match app_state.feature_flags.definition(name) {
+            Some(definition) => Self {
+                name: definition.key,
+                description: definition.description,
+                enabled: definition.default_on,
+                source: FeatureFlagSource::RuntimeOverride,
+                config_value,
+                group_overrides: definition.group_overrides,
+                percentage_rollout: definition.percentage_rollout,
+            },
+            None => Self {
+                name: name.to_string(),
+                description: None,
+                enabled: config_value.unwrap_or(false),
+                source: FeatureFlagSource::Config,
+                config_value,
+                group_overrides: BTreeMap::new(),
+                percentage_rollout: None,
+            },
+        }
+    }
+}
+
+/// Response for listing the feature flags
+#[derive(Debug, ToSchema, Serialize)]
+pub struct FeatureFlagsResponse {
+    flags: Vec<FeatureFlagStatus>,
+}
+
+/// Request to override a feature flag
+#[derive(Debug, ToSchema, Deserialize)]
+pub struct SetFeatureFlagRequest {
+    /// Whether the feature should be enabled
+    enabled: bool,
+}
+
+/// Request to create or replace a feature flag
+#[derive(Debug, ToSchema, Deserialize)]
+pub struct PutFeatureFlagRequest {
+    /// Description of the flag
+    #[serde(default)]
+    description: Option<String>,
+    /// Whether the feature is enabled for users without a group override or percentage rollout
+    #[serde(default)]
+    default_on: bool,
+    /// Value of the flag for the members of a group, by group ID
+    #[serde(default)]
+    group_overrides: BTreeMap<String, bool>,
+    /// Percentage of users (0 to 100) that have the feature enabled
+    #[serde(default)]
+    percentage_rollout: Option<u8>,
+}
+
+/// Request to seed the database with demo data
+#[derive(Debug, ToSchema, Deserialize)]
+pub struct SeedRequest {
+    /// The set of fixtures to create, either `minimal` or `demo`. Defaults to `minimal`.
+    #[serde(default)]
+    profile: Option<String>,
+    /// The seed value the fixtures are derived from. Seeding twice with the same value creates
+    /// no additional records. Defaults to `1`.
+    #[serde(default)]
+    seed: Option<u64>,
+}
+
+/// Query parameters for the budget report per assistant
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AssistantBudgetReportQuery {
+    /// First day of the reported period (inclusive), e.g. `2026-01-01`
+    from: NaiveDate,
+    /// Last day of the reported period (inclusive), e.g. `2026-01-31`
+    to: NaiveDate,
+    /// Only report the usage of this organization. Admins that belong to an organization
+    /// can only report the usage of their own organization.
+    organization_id: Option<String>,
+}
+
+/// Token usage and estimated cost per assistant across all users
+#[derive(Debug, ToSchema, Serialize)]
+pub struct AssistantBudgetReport {
+    /// First day of the reported period (inclusive)
+    from: NaiveDate,
+    /// Last day of the reported period (inclusive)
+    to: NaiveDate,
+    /// The currency configured for display purposes
+    budget_currency: BudgetCurrency,
+    /// Usage per assistant, sorted by descending estimated cost.
+    /// Chats without an assistant are reported with an `assistant_id` of `null`.
+    assistants: Vec<AssistantUsage>,
+}
+
+/// A message whose thread link points to a message of another chat
+#[derive(Debug, ToSchema, Serialize)]
+pub struct CrossChatMessageLink {
+    /// The ID of the message holding the link
+    message_id: String,
+    /// The chat of the message holding the link
+    chat_id: String,
+    /// The column the link is stored in, either `previous` or `sibling`
+    link_type: String,
+    /// The ID of the linked message
+    linked_message_id: String,
+    /// The chat of the linked message
+    linked_chat_id: String,
+}
+
+/// Result of the consistency check of the links between messages
+#[derive(Debug, ToSchema, Serialize)]
+pub struct MessageLinkConsistencyReport {
+    /// Messages linked to a message of another chat, ordered by message ID
+    cross_chat_links: Vec<CrossChatMessageLink>,
+}
+
+/// A file upload whose object is missing in the storage
+#[derive(Debug, ToSchema, Serialize)]
+pub struct MissingFileUpload {
+    /// The ID of the file upload
+    id: String,
+    /// The original filename of the file
+    filename: String,
+    /// The ID of the file storage provider the file was uploaded to
+    file_storage_provider_id: String,
+    /// The path of the missing object in the storage
+    file_storage_path: String,
+    /// The ID of the user that uploaded the file
+    owner_user_id: String,
+    /// The organization of the file, if any
+    organization_id: Option<String>,
+    /// When the file was uploaded
+    created_at: DateTime<FixedOffset>,
+}
+
+/// Report of the files whose object was found to be missing in the storage
+#[derive(Debug, ToSchema, Serialize)]
+pub struct MissingFilesReport {
+    /// Missing files, most recently uploaded first
+    files: Vec<MissingFileUpload>,
+}
+
+/// What to redact in a message
+#[derive(Debug, ToSchema, Deserialize)]
+pub struct RedactMessageRequest {
+    /// Redact all matches of this regular expression
+    pattern: Option<String>,
+    /// Redact these character ranges of the content parts of the message
+    #[serde(default)]
+    ranges: Vec<RedactionSpan>,
+    /// The text the redacted content is replaced with. Defaults to `[REDACTED]`.
+    replacement: Option<String>,
+    /// Redact a file attached to the message instead of the text of the message.
+    /// Redacts the transcript of an audio file, and purges the cached parsed contents of the file.
+    /// The stored file itself is not modified.
+    file_id: Option<String>,
+    /// Only report the locations that would be redacted, without modifying anything
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Result of the redaction of a message
+#[derive(Debug, ToSchema, Serialize)]
+pub struct RedactMessageResponse {
+    /// The ID of the redacted message
+    message_id: String,
+    /// The ID of the redacted file, if a file was redacted
+    file_id: Option<String>,
+    /// Whether this was a dry run, in which case nothing was modified
+    dry_run: bool,
+    /// The redacted locations, as character offsets in the content before the redaction
+    spans: Vec<RedactionSpan>,
+    /// Number of messages of the chat whose stored generation inputs were rewritten
+    updated_generation_inputs: u64,
+    /// Number of purged cache entries of the redacted file
+    purged_file_cache_entries: u64,
+}
+
+/// Request to repair the thread of a chat
+#[derive(Debug, ToSchema, Deserialize)]
+pub struct ThreadRepairRequest {
+    /// Only report the changes that would repair the thread, without modifying anything
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Result of the repair of the thread of a chat
+#[derive(Debug, ToSchema, Serialize)]
+pub struct ThreadRepairResponse {
+    /// Whether this was a dry run, in which case nothing was modified
+    dry_run: bool,
+    /// The integrity of the thread before the repair
+    report: ThreadIntegrityReport,
+    /// The changes that repair the thread, in the order they are applied
+    changes: Vec<ThreadRepairChange>,
+}
+
+/// A capture of the traffic to the chat provider of a generation
+#[derive(Debug, ToSchema, Serialize)]
+pub struct ProviderCaptureResponse {
+    /// The trace ID of the captured generation
+    trace_id: String,
+    /// Time-limited download URL of the capture, if captures are written to the file storage
+    download_url: Option<String>,
+    /// The capture, if captures are written to a local directory
+    #[schema(value_type = Option<Object>)]
+    capture: Option<serde_json::Value>,
+}
+
+/// Details of the generation of an assistant message, for debugging which tools and options the
+/// model was called with
+#[derive(Debug, ToSchema, Serialize)]
+pub struct MessageGenerationDetail {
+    /// The ID of the message
+    message_id: Uuid,
+    /// The chat provider that generated the message
+    chat_provider_id: Option<String>,
+    /// The options the chat provider was called with. Not recorded for messages generated before
+    /// the chat options were recorded.
+    chat_options: Option<GenerationChatOptions>,
+    /// The tools that were offered to the model. Not recorded for messages generated before the
+    /// offered tools were recorded.
+    offered_tools: Option<OfferedTools>,
+    /// MCP servers that were unavailable while preparing the generation, whose tools could not
+    /// be offered
+    mcp_servers_unavailable: Vec<String>,
+}
+
+/// Progress of the migration of inline file contents in stored generation inputs to file pointers
+#[derive(Debug, ToSchema, Serialize)]
+pub struct FilePointerMigrationStatus {
+    /// Whether the migration is currently running on this backend instance
+    running: bool,
+    /// Number of messages with a stored generation input that were processed in the current or
+    /// last run
+    processed_messages: i64,
+    /// Number of messages whose generation input was migrated in the current or last run
+    migrated_messages: i64,
+    /// Number of bytes by which the migrated generation inputs shrank in the current or last run
+    bytes_saved: i64,
+    /// When the current or last run was started, or `null` if the migration was never started
+    started_at: Option<DateTime<FixedOffset>>,
+    /// When the progress was last updated
+    updated_at: Option<DateTime<FixedOffset>>,
+    /// When the last run completed, or `null` if it hasn't completed yet
+    completed_at: Option<DateTime<FixedOffset>>,
+    /// The error that stopped the last run, if any
+    last_error: Option<String>,
+}
+
+/// Progress of the spillover of the content of oversized messages to the file storage
+#[derive(Debug, ToSchema, Serialize)]
+pub struct ContentSpilloverMigrationStatus {
+    /// Whether the migration is currently running on this backend instance
+    running: bool,
+    /// Number of messages above the size threshold that were processed in the current or last run
+    processed_messages: i64,
+    /// Number of messages whose content was moved to the file storage in the current or last run
+    migrated_messages: i64,
+    /// Number of bytes by which the migrated messages shrank in the current or last run
+    bytes_saved: i64,
+    /// When the current or last run was started, or `null` if the migration was never started
+    started_at: Option<DateTime<FixedOffset>>,
+    /// When the progress was last updated
+    updated_at: Option<DateTime<FixedOffset>>,
+    /// When the last run completed, or `null` if it hasn't completed yet
+    completed_at: Option<DateTime<FixedOffset>>,
+    /// The error that stopped the last run, if any
+    last_error: Option<String>,
+}
+
+/// Query parameters of the archival of old generation inputs
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct GenerationInputArchivalQuery {
+    /// Whether to refer to the dry run, which only estimates the savings without changing
+    /// anything. Defaults to `false`.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Progress of the archival of the generation inputs of old messages in archived chats
+#[derive(Debug, ToSchema, Serialize)]
+pub struct GenerationInputArchivalStatus {
+    /// Whether this is the progress of the dry run
+    dry_run: bool,
+    /// Whether the archival is currently running on this backend instance
+    running: bool,
+    /// Number of old assistant messages in archived chats that were processed in the current or
+    /// last run
+    processed_messages: i64,
+    /// Number of messages whose generation input was archived in the current or last run, or
+    /// would be archived in a dry run
+    archived_messages: i64,
+    /// Number of bytes saved by the archived generation inputs in the current or last run, or the
+    /// estimated savings in a dry run
+    bytes_saved: i64,
+    /// When the current or last run was started, or `null` if the archival was never started
+    started_at: Option<DateTime<FixedOffset>>,
+    /// When the progress was last updated
+    updated_at: Option<DateTime<FixedOffset>>,
+    /// When the last run completed, or `null` if it hasn't completed yet
+    completed_at: Option<DateTime<FixedOffset>>,
+    /// The error that stopped the last run, if any
+    last_error: Option<String>,
+}
+
+/// The rate limit state of a chat provider
+#[derive(Debug, ToSchema, Serialize)]
+pub struct ChatProviderStatus {
+    /// The ID of the chat provider
+    chat_provider_id: String,
+    /// Whether the chat provider is cooling down after it responded with a rate limit.
+    /// Providers that are cooling down are avoided where another provider can serve a generation.
+    cooling_down: bool,
+    /// When the cooldown ends, if the chat provider is cooling down
+    cooldown_until: Option<DateTime<Utc>>,
+    /// Which limit of the chat provider was exceeded, if it reported it
+    rate_limit_scope: Option<RateLimitScope>,
+}
+
+/// The rate limit state of a chat provider group
+#[derive(Debug, ToSchema, Serialize)]
+pub struct ChatProviderGroupStatus {
+    /// The ID of the chat provider group
+    chat_provider_group_id: String,
+    /// The IDs of the members of the group
+    members: Vec<String>,
+    /// Whether all members of the group are cooling down after a rate limit
+    cooling_down: bool,
+}
+
+/// The rate limit state of the chat providers on this backend instance
+#[derive(Debug, ToSchema, Serialize)]
+pub struct ChatProvidersStatusResponse {
+    /// The configured chat providers, ordered by ID
+    chat_providers: Vec<ChatProviderStatus>,
+    /// The configured chat provider groups, ordered by ID
+    chat_provider_groups: Vec<ChatProviderGroupStatus>,
+}
+
+/// Request to feature or unfeature an assistant
+#[derive(Debug, ToSchema, Deserialize)]
+pub struct SetAssistantFeaturedRequest {
+    /// Whether the assistant is featured
+    featured: bool,
+}
+
+/// Whether an assistant is featured
+#[derive(Debug, ToSchema, Serialize)]
+pub struct AssistantFeaturedStatus {
+    /// The ID of the assistant
+    assistant_id: String,
+    /// Whether the assistant is featured
+    featured: bool,
+}
+
+/// Result of the transfer of all chats of a user
+#[derive(Debug, ToSchema, Serialize)]
+pub struct TransferUserChatsResponse {
+    /// Whether this was a dry run, in which case nothing was modified
+    dry_run: bool,
+    /// The transfers of the chats, oldest chat first
+    transfers: Vec<ChatOwnershipTransfer>,
+}
+
+fn require_admin(app_state: &AppState, me_user: &MeProfile) -> Result<(), StatusCode> {
+    if app_state.config.admin.is_admin(&me_user.groups) {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// The organization an admin endpoint is restricted to.
+///
+/// Platform admins, who don't belong to an organization, may filter by any organization,
+/// while admins of an organization are always restricted to their own one.
+fn admin_organization_filter<'a>(
+    me_user: &'a MeProfile,
+    requested: Option<&'a str>,
+) -> Result<Option<&'a str>, StatusCode> {
+    match me_user.organization_id.as_deref() {
+        None => Ok(requested),
+        Some(own) if requested.is_none_or(|requested| requested == own) => Ok(Some(own)),
+        Some(_) => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+fn parse_flag_name(flag_name: &str) -> Result<FeatureFlag, StatusCode> {
+    FeatureFlag::from_name(flag_name).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// List the feature flags, which are the config toggles that can be overridden at runtime and
+/// all stored flags.
+///
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    get,
+    path = "/admin/feature-flags",
+    tag = "admin",
+    responses(
+        (status = OK, body = FeatureFlagsResponse),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_feature_flags(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<Json<FeatureFlagsResponse>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+
+    let stored = app_state.feature_flags.definitions();
+    let flags = FeatureFlag::ALL
+        .into_iter()
+        .map(|flag| flag.name().to_string())
+        .chain(
+            stored
+                .into_iter()
+                .map(|definition| definition.key)
+                .filter(|key| FeatureFlag::from_name(key).is_none()),
+        )
+        .map(|name| FeatureFlagStatus::new(&app_state, &name))
+        .collect();
+    Ok(Json(FeatureFlagsResponse { flags }))
+}
+
+/// Override the config value of a feature flag.
+///
+/// Sets the default value of the stored flag, and keeps its group overrides and percentage
+/// rollout. The override is stored in the database, and applies to all instances.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    post,
+    path = "/admin/feature-flags/{flag_name}",
+    tag = "admin",
+    request_body = SetFeatureFlagRequest,
+    params(
+        ("flag_name" = String, Path, description = "Name of the feature flag"),
+    ),
+    responses(
+        (status = OK, body = FeatureFlagStatus),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+        (status = NOT_FOUND, description = "When the feature flag does not exist"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn set_feature_flag(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(flag_name): Path<String>,
+    Json(request): Json<SetFeatureFlagRequest>,
+) -> Result<Json<FeatureFlagStatus>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    let flag = parse_flag_name(&flag_name)?;
+
+    let definition = app_state
+        .feature_flags
+        .definition(flag.name())
+        .unwrap_or_else(|| FeatureFlagDefinition::seeded(flag.name(), &app_state.config));
+    feature_flags::save_feature_flag(
+        &app_state.db,
+        &app_state.feature_flags,
+        FeatureFlagDefinition {
+            default_on: request.enabled,
+            ..definition
+        },
+    )
+    .await
+    .map_err(log_internal_server_error)?;
+    tracing::info!(
+        flag = flag.name(),
+        enabled = request.enabled,
+        user_id = %me_user.id,
+        "Feature flag overridden"
+    );
+    Ok(Json(FeatureFlagStatus::new(&app_state, flag.name())))
+}
+
+/// Create or replace a feature flag.
+///
+/// Flags that aren't named after a config toggle can be checked by name, and are part of the
+/// flags of the user in `/me/profile`. Changes are stored in the database, and applied by all
+/// instances within a few seconds.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    put,
+    path = "/admin/feature-flags/{flag_name}",
+    tag = "admin",
+    request_body = PutFeatureFlagRequest,
+    params(
+        ("flag_name" = String, Path, description = "Name of the feature flag"),
+    ),
+    responses(
+        (status = OK, body = FeatureFlagStatus),
+        (status = BAD_REQUEST, description = "When the name is empty or the percentage is above 100"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn put_feature_flag(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(flag_name): Path<String>,
+    Json(request): Json<PutFeatureFlagRequest>,
+) -> Result<Json<FeatureFlagStatus>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    if flag_name.trim().is_empty() || request.percentage_rollout.is_some_and(|pct| pct > 100) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    feature_flags::save_feature_flag(
+        &app_state.db,
+        &app_state.feature_flags,
+        FeatureFlagDefinition {
+            key: flag_name.clone(),
+            description: request.description,
+            default_on: request.default_on,
+            group_overrides: request.group_overrides,
+            percentage_rollout: request.percentage_rollout,
+        },
+    )
+    .await
+    .map_err(log_internal_server_error)?;
+    tracing::info!(
+        flag = flag_name.as_str(),
+        default_on = request.default_on,
+        user_id = %me_user.id,
+        "Feature flag saved"
+    );
+    Ok(Json(FeatureFlagStatus::new(&app_state, &flag_name)))
+}
+
+/// Delete a stored feature flag, reverting it to its config value.
+///
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    delete,
+    path = "/admin/feature-flags/{flag_name}",
+    tag = "admin",
+    params(
+        ("flag_name" = String, Path, description = "Name of the feature flag"),
+    ),
+    responses(
+        (status = OK, body = FeatureFlagStatus),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+        (status = NOT_FOUND, description = "When the feature flag does not exist"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_feature_flag_override(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(flag_name): Path<String>,
+) -> Result<Json<FeatureFlagStatus>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+
+    let deleted = feature_flags::delete_feature_flag(
+        &app_state.db,
+        &app_state.feature_flags,
+        &flag_name,
+    )
+    .await
+    .map_err(log_internal_server_error)?;
+    if deleted {
+        tracing::info!(
+            flag = flag_name.as_str(),
+            user_id = %me_user.id,
+            "Feature flag deleted"
+        );
+    } else if FeatureFlag::from_name(&flag_name).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(FeatureFlagStatus::new(&app_state, &flag_name)))
+}
+
+/// Seed the database with demo data.
+///
+/// Creates users, chats, assistants and share grants derived from the seed value.
+/// Records that already exist from a previous run with the same seed are left untouched.
+/// Not available in the `production` environment.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    post,
+    path = "/admin/seed",
+    tag = "admin",
+    request_body = SeedRequest,
+    responses(
+        (status = OK, body = SeedSummary),
+        (status = BAD_REQUEST, description = "When the seed profile does not exist"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin, or the environment is `production`"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn seed(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Json(request): Json<SeedRequest>,
+) -> Result<Json<SeedSummary>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    if app_state.config.is_production_environment() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let profile = match request.profile {
+        Some(name) => SeedProfile::from_name(&name).ok_or(StatusCode::BAD_REQUEST)?,
+        None => SeedProfile::default(),
+    };
+    let options = SeedOptions {
+        profile,
+        seed: request.seed.unwrap_or(DEFAULT_SEED),
+    };
+
+    let summary = seed_database(&app_state, &options)
+        .await
+        .wrap_err("Failed to seed database")
+        .map_err(log_internal_server_error)?;
+    tracing::info!(?summary, user_id = %me_user.id, "Seeded database");
+    Ok(Json(summary))
+}
+
+/// Report the token usage and estimated cost per assistant across all users.
+///
+/// Every generation is attributed to the assistant of the chat it happened in.
+/// Responds with CSV instead of JSON if the `Accept` header requests `text/csv`.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    get,
+    path = "/admin/budget/assistants",
+    tag = "admin",
+    params(AssistantBudgetReportQuery),
+    responses(
+        (status = OK, description = "Usage per assistant, as JSON or as CSV depending on the `Accept` header", content(
+            (AssistantBudgetReport = "application/json"),
+            (String = "text/csv"),
+        )),
+        (status = BAD_REQUEST, description = "When `from` is after `to`"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin, or an admin of an organization requests another organization"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn assistant_budget_report(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Query(query): Query<AssistantBudgetReportQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    if query.from > query.to {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let period_start = query.from.and_time(NaiveTime::MIN).and_utc();
+    let period_end = query
+        .to
+        .checked_add_days(Days::new(1))
+        .ok_or(StatusCode::BAD_REQUEST)?
+        .and_time(NaiveTime::MIN)
+        .and_utc();
+
+    let organization_id = admin_organization_filter(&me_user, query.organization_id.as_deref())?;
+
+    let assistants =
+        usage_by_assistant(&app_state, None, organization_id, period_start, period_end)
+            .await
+            .wrap_err("Failed to aggregate usage by assistant")
+            .map_err(log_internal_server_error)?;
+
+    let wants_csv = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/csv"));
+    if wants_csv {
+        let filename = format!("assistant-usage-{}-{}.csv", query.from, query.to);
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{filename}\""),
+                ),
+            ],
+            assistant_usage_to_csv(&assistants),
+        )
+            .into_response());
+    }
+
+    Ok(Json(AssistantBudgetReport {
+        from: query.from,
+        to: query.to,
+        budget_currency: app_state.config.budget.budget_currency.clone(),
+        assistants,
+    })
+    .into_response())
+}
+
+/// Check the links between messages for consistency.
+///
+/// Reports messages whose `previous_message_id` or `sibling_message_id` points to a message of
+/// another chat. New links like this are rejected when submitting messages, so this is meant as a
+/// one-off check for data created before that validation. Nothing is modified.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    get,
+    path = "/admin/consistency/message-links",
+    tag = "admin",
+    responses(
+        (status = OK, body = MessageLinkConsistencyReport),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn message_link_consistency(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<Json<MessageLinkConsistencyReport>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+
+    let links = find_cross_chat_message_links(&app_state.db)
+        .await
+        .wrap_err("Failed to check message links")
+        .map_err(log_internal_server_error)?;
+    if !links.is_empty() {
+        tracing::warn!(
+            count = links.len(),
+            "Found messages linked to messages of other chats"
+        );
+    }
+
+    Ok(Json(MessageLinkConsistencyReport {
+        cross_chat_links: links
+            .into_iter()
+            .map(|link| CrossChatMessageLink {
+                message_id: link.message_id.to_string(),
+                chat_id: link.chat_id.to_string(),
+                link_type: link.link_type,
+                linked_message_id: link.linked_message_id.to_string(),
+                linked_chat_id: link.linked_chat_id.to_string(),
+            })
+            .collect(),
+    }))
+}
+
+/// Check the thread of messages of a chat for corruptions.
+///
+/// Reports multiple ends of the active thread, active messages following inactive ones, links to
+/// messages that don't exist in the chat, and cycles of `previous_message_id` or
+/// `sibling_message_id`. Nothing is modified.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    get,
+    path = "/admin/chats/{chat_id}/thread-integrity",
+    tag = "admin",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat to check"),
+    ),
+    responses(
+        (status = OK, body = ThreadIntegrityReport),
+        (status = NOT_FOUND, description = "When the chat does not exist"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn thread_integrity(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(chat_id): Path<String>,
+) -> Result<Json<ThreadIntegrityReport>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let report = check_thread_integrity(&app_state.db, &chat_id)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("not found") {
+                StatusCode::NOT_FOUND
+            } else {
+                log_internal_server_error(e.wrap_err("Failed to check thread integrity"))
+            }
+        })?;
+
+    Ok(Json(report))
+}
+
+/// Repair the thread of messages of a chat.
+///
+/// Links to messages that don't exist in the chat are removed, cycles are broken at their oldest
+/// message, and the active thread is reset to the path ending in its most recent message. The
+/// repair is applied in a single transaction. With `dry_run`, the changes are only reported.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    post,
+    path = "/admin/chats/{chat_id}/thread-repair",
+    tag = "admin",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat to repair"),
+    ),
+    request_body = ThreadRepairRequest,
+    responses(
+        (status = OK, body = ThreadRepairResponse),
+        (status = NOT_FOUND, description = "When the chat does not exist"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn repair_thread(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(chat_id): Path<String>,
+    Json(request): Json<ThreadRepairRequest>,
+) -> Result<Json<ThreadRepairResponse>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let result = repair_thread_integrity(&app_state.db, &chat_id, request.dry_run)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("not found") {
+                StatusCode::NOT_FOUND
+            } else {
+                log_internal_server_error(e.wrap_err("Failed to repair thread"))
+            }
+        })?;
+    if !request.dry_run && !result.changes.is_empty() {
+        tracing::info!(
+            chat_id = %chat_id,
+            issues = result.report.issues.len(),
+            changes = result.changes.len(),
+            user_id = %me_user.id,
+            "Repaired thread of chat"
+        );
+    }
+
+    Ok(Json(ThreadRepairResponse {
+        dry_run: request.dry_run,
+        report: result.report,
+        changes: result.changes,
+    }))
+}
+
+/// List all files whose object is missing in the storage.
+///
+/// Files are marked as missing when reading their contents for a generation fails because the
+/// object doesn't exist (anymore), e.g. after a manual cleanup or a bucket lifecycle rule. They
+/// are marked as available again once their contents could be read.
+/// Only available to members of the groups configured in `admin.groups`. Admins that belong to an
+/// organization only see the files of their organization.
+#[utoipa::path(
+    get,
+    path = "/admin/files/missing",
+    tag = "admin",
+    responses(
+        (status = OK, body = MissingFilesReport),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn missing_files_report(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<Json<MissingFilesReport>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    let organization_id = admin_organization_filter(&me_user, None)?;
+
+    let files = file_upload::get_missing_file_uploads(&app_state.db, organization_id)
+        .await
+        .wrap_err("Failed to list missing files")
+        .map_err(log_internal_server_error)?;
+
+    Ok(Json(MissingFilesReport {
+        files: files
+            .into_iter()
+            .map(|file| MissingFileUpload {
+                id: file.id.to_string(),
+                filename: file.filename,
+                file_storage_provider_id: file.file_storage_provider_id,
+                file_storage_path: file.file_storage_path,
+                owner_user_id: file.owner_user_id,
+                organization_id: file.organization_id,
+                created_at: file.created_at,
+            })
+            .collect(),
+    }))
+}
+
+/// Redact content of a stored message.
+///
+/// Replaces the matches of `pattern` and the given `ranges` in the text of the message, and in
+/// the copies of that text in the stored generation inputs of the chat, so the redacted content
+/// is not sent to the model again. The redaction is recorded with the acting admin and the
+/// redacted locations, but without the redacted content.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    post,
+    path = "/admin/messages/{message_id}/redact",
+    tag = "admin",
+    params(
+        ("message_id" = String, Path, description = "The ID of the message to redact"),
+    ),
+    request_body = RedactMessageRequest,
+    responses(
+        (status = OK, body = RedactMessageResponse),
+        (status = BAD_REQUEST, description = "When neither a valid `pattern` nor `ranges` are provided, or a range is out of bounds"),
+        (status = NOT_FOUND, description = "When the message, or the file on the message, does not exist"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn redact_message(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(message_id): Path<String>,
+    Json(request): Json<RedactMessageRequest>,
+) -> Result<Json<RedactMessageResponse>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    let message_id = Uuid::parse_str(&message_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let file_id = request
+        .file_id
+        .as_deref()
+        .map(Uuid::parse_str)
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let actor_user_id = Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let pattern = request
+        .pattern
+        .as_deref()
+        .filter(|pattern| !pattern.is_empty())
+        .map(Regex::new)
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    if pattern.is_none() && request.ranges.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let replacement = request
+        .replacement
+        .as_deref()
+        .unwrap_or(DEFAULT_REDACTION_REPLACEMENT);
+
+    let result = message_redaction::redact_message(
+        &app_state.db,
+        &app_state.generation_input_archive,
+        &actor_user_id,
+        &message_id,
+        &RedactionRequest {
+            file_id,
+            pattern: pattern.as_ref(),
+            ranges: &request.ranges,
+            replacement,
+            dry_run: request.dry_run,
+        },
+    )
+    .await
+    .map_err(|e| {
+        let message = e.to_string();
+        if message.contains("not found") {
+            StatusCode::NOT_FOUND
+        } else if message.contains("Invalid redaction range") {
+            StatusCode::BAD_REQUEST
+        } else {
+            log_internal_server_error(e)
+        }
+    })?;
+
+    let purged_file_cache_entries = match file_id {
+        Some(file_id) if !request.dry_run => purge_file_cached(&app_state, &file_id).await,
+        _ => 0,
+    };
+    if !request.dry_run {
+        tracing::info!(
+            message_id = %message_id,
+            file_id = ?file_id,
+            spans = result.spans.len(),
+            user_id = %me_user.id,
+            "Redacted message"
+        );
+    }
+
+    Ok(Json(RedactMessageResponse {
+        message_id: message_id.to_string(),
+        file_id: file_id.map(|file_id| file_id.to_string()),
+        dry_run: request.dry_run,
+        spans: result.spans,
+        updated_generation_inputs: result.updated_generation_inputs,
+        purged_file_cache_entries,
+    }))
+}
+
+/// Retrieve a capture of the traffic to the chat provider of a generation.
+///
+/// Captures are only written with `debug.capture_provider_traffic.enabled`, and are named by the
+/// trace ID recorded as `provider_capture_id` in the generation metadata of the message.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    get,
+    path = "/admin/captures/{trace_id}",
+    tag = "admin",
+    params(
+        ("trace_id" = String, Path, description = "The trace ID of the captured generation"),
+    ),
+    responses(
+        (status = OK, body = ProviderCaptureResponse),
+        (status = NOT_FOUND, description = "When capturing is disabled, or there is no capture for the trace ID"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_provider_capture(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(trace_id): Path<String>,
+) -> Result<Json<ProviderCaptureResponse>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    let store = ProviderCaptureStore::from_app_state(&app_state).ok_or(StatusCode::NOT_FOUND)?;
+
+    let capture = store
+        .get(&trace_id)
+        .await
+        .wrap_err("Failed to read provider capture")
+        .map_err(log_internal_server_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let (download_url, capture) = match capture {
+        StoredProviderCapture::Url(url) => (Some(url), None),
+        StoredProviderCapture::Contents(contents) => (None, Some(contents)),
+    };
+    Ok(Json(ProviderCaptureResponse {
+        trace_id,
+        download_url,
+        capture,
+    }))
+}
+
+/// Retrieve the manifest of the prompts an assistant message was generated with.
+///
+/// The manifest lists the prompts of the generation with their source, the hash of their content
+/// and their length, and their full text with `admin.prompt_manifest.store_full_text`.
+/// Available to members of the groups configured in `admin.groups`, and to the owner of the chat
+/// with `admin.prompt_manifest.visible_to_chat_owner`.
+#[utoipa::path(
+    get,
+    path = "/admin/messages/{message_id}/prompt-manifest",
+    tag = "admin",
+    params(
+        ("message_id" = String, Path, description = "The ID of the assistant message"),
+    ),
+    responses(
+        (status = OK, body = PromptManifest),
+        (status = NOT_FOUND, description = "When the message does not exist, or has no prompt manifest because it is not an assistant message or was generated before manifests were recorded"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is neither an admin nor allowed to access the manifests of their own chats"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_message_prompt_manifest(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(message_id): Path<String>,
+) -> Result<Json<PromptManifest>, StatusCode> {
+    let is_admin = app_state.config.admin.is_admin(&me_user.groups);
+    if !is_admin && !app_state.config.admin.prompt_manifest.visible_to_chat_owner {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let message_id = Uuid::parse_str(&message_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let message = Messages::find_by_id(message_id)
+        .one(&app_state.db)
+        .await
+        .wrap_err("Failed to load message")
+        .map_err(log_internal_server_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if !is_admin {
+        let chat = Chats::find_by_id(message.chat_id)
+            .one(&app_state.db)
+            .await
+            .wrap_err("Failed to load chat")
+            .map_err(log_internal_server_error)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+        // Messages of chats of other users are reported as missing
+        if chat.owner_user_id != me_user.id {
+            return Err(StatusCode::NOT_FOUND);
+        }
+    }
+
+    let prompt_manifest = message
+        .generation_metadata
+        .and_then(|metadata| serde_json::from_value::<GenerationMetadata>(metadata).ok())
+        .and_then(|metadata| metadata.prompt_manifest)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(prompt_manifest))
+}
+
+/// Retrieve the tools, chat options and chat provider an assistant message was generated with.
+///
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    get,
+    path = "/admin/messages/{message_id}/generation-detail",
+    tag = "admin",
+    params(
+        ("message_id" = String, Path, description = "The ID of the assistant message"),
+    ),
+    responses(
+        (status = OK, body = MessageGenerationDetail),
+        (status = BAD_REQUEST, description = "When the message ID is not a valid UUID"),
+        (status = NOT_FOUND, description = "When the message does not exist"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_message_generation_detail(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(message_id): Path<String>,
+) -> Result<Json<MessageGenerationDetail>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    let message_id = Uuid::parse_str(&message_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let message = Messages::find_by_id(message_id)
+        .one(&app_state.db)
+        .await
+        .wrap_err("Failed to load message")
+        .map_err(log_internal_server_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let generation_parameters = message
+        .generation_parameters
+        .and_then(|parameters| serde_json::from_value::<GenerationParameters>(parameters).ok());
+    let generation_metadata = message
+        .generation_metadata
+        .and_then(|metadata| serde_json::from_value::<GenerationMetadata>(metadata).ok())
+        .unwrap_or_default();
+    Ok(Json(MessageGenerationDetail {
+        message_id,
+        chat_provider_id: generation_parameters
+            .and_then(|parameters| parameters.generation_chat_provider_id),
+        chat_options: generation_metadata.chat_options,
+        offered_tools: generation_metadata.offered_tools,
+        mcp_servers_unavailable: generation_metadata
+            .mcp_servers_unavailable
+            .unwrap_or_default(),
+    }))
+}
+
+async fn file_pointer_migration_status_of(
+    app_state: &AppState,
+) -> Result<FilePointerMigrationStatus, StatusCode> {
+    let progress = get_maintenance_task_progress(&app_state.db, FILE_POINTER_MIGRATION_TASK)
+        .await
+        .wrap_err("Failed to load the progress of the file pointer migration")
+        .map_err(log_internal_server_error)?;
+    let running = app_state
+        .background_tasks
+        .is_maintenance_task_running(FILE_POINTER_MIGRATION_TASK);
+    Ok(match progress {
+        Some(progress) => FilePointerMigrationStatus {
+            running,
+            processed_messages: progress.processed_messages,
+            migrated_messages: progress.migrated_messages,
+            bytes_saved: progress.bytes_saved,
+            started_at: Some(progress.started_at),
+            updated_at: Some(progress.updated_at),
+            completed_at: progress.completed_at,
+            last_error: progress.last_error,
+        },
+        None => FilePointerMigrationStatus {
+            running,
+            processed_messages: 0,
+            migrated_messages: 0,
+            bytes_saved: 0,
+            started_at: None,
+            updated_at: None,
+            completed_at: None,
+            last_error: None,
+        },
+    })
+}
+
+/// Get the progress of the migration of inline file contents to file pointers.
+///
+/// Only available to members of the groups configured in `admin.groups` that don't belong to an
+/// organization.
+#[utoipa::path(
+    get,
+    path = "/admin/maintenance/file-pointer-migration",
+    tag = "admin",
+    responses(
+        (status = OK, body = FilePointerMigrationStatus),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin, or the admin of an organization"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn file_pointer_migration_status(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<Json<FilePointerMigrationStatus>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    if me_user.organization_id.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(Json(file_pointer_migration_status_of(&app_state).await?))
+}
+
+/// Start the migration of inline file contents to file pointers.
+///
+/// Older messages store the contents of attached files inline in their generation inputs. The
+/// migration replaces the contents of files that still exist with file pointers in the
+/// background, in batches of `admin.file_pointer_migration.batch_size` messages. An unfinished
+/// run is resumed where it stopped, and a completed migration is started over.
+/// Only available to members of the groups configured in `admin.groups` that don't belong to an
+/// organization.
+#[utoipa::path(
+    post,
+    path = "/admin/maintenance/file-pointer-migration",
+    tag = "admin",
+    responses(
+        (status = ACCEPTED, body = FilePointerMigrationStatus, description = "The migration was started"),
+        (status = CONFLICT, description = "When the migration is already running"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin, or the admin of an organization"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn start_file_pointer_migration(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<(StatusCode, Json<FilePointerMigrationStatus>), StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    if me_user.organization_id.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let started = file_pointer_migration::start_file_pointer_migration(
+        &app_state.background_tasks,
+        app_state.db.clone(),
+        app_state.config.admin.file_pointer_migration.clone(),
+    );
+    if !started {
+        return Err(StatusCode::CONFLICT);
+    }
+    tracing::info!(
+        user_id = %me_user.id,
+        "Started the migration of inline file contents to file pointers"
+    );
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(file_pointer_migration_status_of(&app_state).await?),
+    ))
+}
+
+async fn content_spillover_migration_status_of(
+    app_state: &AppState,
+) -> Result<ContentSpilloverMigrationStatus, StatusCode> {
+    let progress = get_maintenance_task_progress(&app_state.db, CONTENT_SPILLOVER_MIGRATION_TASK)
+        .await
+        .wrap_err("Failed to load the progress of the content spillover migration")
+        .map_err(log_internal_server_error)?;
+    let running = app_state
+        .background_tasks
+        .is_maintenance_task_running(CONTENT_SPILLOVER_MIGRATION_TASK);
+    Ok(match progress {
+        Some(progress) => ContentSpilloverMigrationStatus {
+            running,
+            processed_messages: progress.processed_messages,
+            migrated_messages: progress.migrated_messages,
+            bytes_saved: progress.bytes_saved,
+            started_at: Some(progress.started_at),
+            updated_at: Some(progress.updated_at),
+            completed_at: progress.completed_at,
+            last_error: progress.last_error,
+        },
+        None => ContentSpilloverMigrationStatus {
+            running,
+            processed_messages: 0,
+            migrated_messages: 0,
+            bytes_saved: 0,
+            started_at: None,
+            updated_at: None,
+            completed_at: None,
+            last_error: None,
+        },
+    })
+}
+
+/// Get the progress of the spillover of oversized message content to the file storage.
+///
+/// Only available to members of the groups configured in `admin.groups` that don't belong to an
+/// organization.
+#[utoipa::path(
+    get,
+    path = "/admin/maintenance/content-spillover-migration",
+    tag = "admin",
+    responses(
+        (status = OK, body = ContentSpilloverMigrationStatus),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin, or the admin of an organization"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn content_spillover_migration_status(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<Json<ContentSpilloverMigrationStatus>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    if me_user.organization_id.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(Json(
+        content_spillover_migration_status_of(&app_state).await?,
+    ))
+}
+
+/// Start the spillover of oversized message content to the file storage.
+///
+/// Messages stored before `chat.content_spillover` was enabled, or with a higher threshold, keep
+/// their content inline. The migration moves the largest parts of messages above the threshold to
+/// the file storage in the background, in batches of
+/// `admin.content_spillover_migration.batch_size` messages. An unfinished run is resumed where it
+/// stopped, and a completed migration is started over.
+/// Only available to members of the groups configured in `admin.groups` that don't belong to an
+/// organization.
+#[utoipa::path(
+    post,
+    path = "/admin/maintenance/content-spillover-migration",
+    tag = "admin",
+    responses(
+        (status = ACCEPTED, body = ContentSpilloverMigrationStatus, description = "The migration was started"),
+        (status = CONFLICT, description = "When the migration is already running"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin, or the admin of an organization"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn start_content_spillover_migration(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<(StatusCode, Json<ContentSpilloverMigrationStatus>), StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    if me_user.organization_id.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let started = content_spillover::start_content_spillover_migration(
+        &app_state.background_tasks,
+        app_state.db.clone(),
+        app_state.default_file_storage_provider().clone(),
+        app_state.config.chat.content_spillover.clone(),
+        app_state.config.admin.content_spillover_migration.clone(),
+    );
+    if !started {
+        return Err(StatusCode::CONFLICT);
+    }
+    tracing::info!(
+        user_id = %me_user.id,
+        "Started the spillover of oversized message content"
+    );
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(content_spillover_migration_status_of(&app_state).await?),
+    ))
+}
+
+async fn generation_input_archival_status_of(
+    app_state: &AppState,
+    dry_run: bool,
+) -> Result<GenerationInputArchivalStatus, StatusCode> {
+    let task_name = if dry_run {
+        GENERATION_INPUT_ARCHIVAL_DRY_RUN_TASK
+    } else {
+        GENERATION_INPUT_ARCHIVAL_TASK
+    };
+    let progress = get_maintenance_task_progress(&app_state.db, task_name)
+        .await
+        .wrap_err("Failed to load the progress of the generation input archival")
+        .map_err(log_internal_server_error)?;
+    let running = app_state
+        .background_tasks
+        .is_maintenance_task_running(task_name);
+    Ok(match progress {
+        Some(progress) => GenerationInputArchivalStatus {
+            dry_run,
+            running,
+            processed_messages: progress.processed_messages,
+            archived_messages: progress.migrated_messages,
+            bytes_saved: progress.bytes_saved,
+            started_at: Some(progress.started_at),
+            updated_at: Some(progress.updated_at),
+            completed_at: progress.completed_at,
+            last_error: progress.last_error,
+        },
+        None => GenerationInputArchivalStatus {
+            dry_run,
+            running,
+            processed_messages: 0,
+            archived_messages: 0,
+            bytes_saved: 0,
+            started_at: None,
+            updated_at: None,
+            completed_at: None,
+            last_error: None,
+        },
+    })
+}
+
+/// Get the progress of the archival of old generation inputs, or of its dry run.
+///
+/// Only available to members of the groups configured in `admin.groups` that don't belong to an
+/// organization.
+#[utoipa::path(
+    get,
+    path = "/admin/maintenance/generation-input-archival",
+    tag = "admin",
+    params(GenerationInputArchivalQuery),
+    responses(
+        (status = OK, body = GenerationInputArchivalStatus),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin, or the admin of an organization"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn generation_input_archival_status(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Query(query): Query<GenerationInputArchivalQuery>,
+) -> Result<Json<GenerationInputArchivalStatus>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    if me_user.organization_id.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(Json(
+        generation_input_archival_status_of(&app_state, query.dry_run).await?,
+    ))
+}
+
+/// Start the archival of old generation inputs, or its dry run.
+///
+/// The generation inputs of assistant messages in archived chats that are older than
+/// `admin.generation_input_archival.min_age_days` are compressed and moved to the configured
+/// storage in the background, in batches of `admin.generation_input_archival.batch_size`
+/// messages. They are restored transparently when the messages are used for a generation again.
+/// The dry run goes through the same messages and estimates the savings without changing
+/// anything. An unfinished run is resumed where it stopped, and a completed one is started over.
+/// Only available to members of the groups configured in `admin.groups` that don't belong to an
+/// organization.
+#[utoipa::path(
+    post,
+    path = "/admin/maintenance/generation-input-archival",
+    tag = "admin",
+    params(GenerationInputArchivalQuery),
+    responses(
+        (status = ACCEPTED, body = GenerationInputArchivalStatus, description = "The archival was started"),
+        (status = CONFLICT, description = "When the archival, or the dry run if requested, is already running"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin, or the admin of an organization"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn start_generation_input_archival(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Query(query): Query<GenerationInputArchivalQuery>,
+) -> Result<(StatusCode, Json<GenerationInputArchivalStatus>), StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    if me_user.organization_id.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let started = generation_input_archive::start_generation_input_archival(
+        &app_state.background_tasks,
+        app_state.db.clone(),
+        app_state.generation_input_archive.clone(),
+        app_state.default_file_storage_provider_id(),
+        app_state.config.admin.generation_input_archival.clone(),
+        query.dry_run,
+    );
+    if !started {
+        return Err(StatusCode::CONFLICT);
+    }
+    tracing::info!(
+        user_id = %me_user.id,
+        dry_run = query.dry_run,
+        "Started the archival of old generation inputs"
+    );
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(
+            generation_input_archival_status_of(&app_state, query.dry_run).await?,
+        ),
+    ))
+}
+
+/// Get the rate limit state of the chat providers.
+///
+/// Chat providers that respond with a rate limit are put into a cooldown until the time they
+/// asked to retry at. The cooldowns are kept in memory, so the status only reflects the
+/// generations of the backend instance that serves the request.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    get,
+    path = "/admin/chat-providers/status",
+    tag = "admin",
+    responses(
+        (status = OK, body = ChatProvidersStatusResponse),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn chat_providers_status(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<Json<ChatProvidersStatusResponse>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+
+    let mut chat_provider_ids: Vec<&String> = app_state
+        .config
+        .chat_providers
+        .iter()
+        .flat_map(|chat_providers| chat_providers.providers.keys())
+        .collect();
+    chat_provider_ids.sort();
+    let chat_providers = chat_provider_ids
+        .into_iter()
+        .map(|chat_provider_id| {
+            let cooldown = app_state
+                .chat_provider_rate_limits
+                .cooldown(chat_provider_id);
+            ChatProviderStatus {
+                chat_provider_id: chat_provider_id.clone(),
+                cooling_down: cooldown.is_some(),
+                cooldown_until: cooldown.map(|cooldown| cooldown.until),
+                rate_limit_scope: cooldown.and_then(|cooldown| cooldown.scope),
+            }
+        })
+        .collect();
+
+    let mut chat_provider_groups: Vec<ChatProviderGroupStatus> = app_state
+        .config
+        .chat_provider_groups
+        .iter()
+        .map(|(group_id, group)| ChatProviderGroupStatus {
+            chat_provider_group_id: group_id.clone(),
+            members: group.members.clone(),
+            cooling_down: app_state.chat_provider_is_cooling_down(group_id),
+        })
+        .collect();
+    chat_provider_groups.sort_by(|a, b| a.chat_provider_group_id.cmp(&b.chat_provider_group_id));
+
+    Ok(Json(ChatProvidersStatusResponse {
+        chat_providers,
+        chat_provider_groups,
+    }))
+}
+
+/// Get the connectivity of the Langfuse integration and the batches that wait for a retry.
+///
+/// Ingestion batches that could not be delivered to Langfuse are queued in the database and
+/// retried with an exponential backoff. The connectivity and the number of evicted batches only
+/// reflect the requests of the backend instance that serves the request, while the queued
+/// batches include the ones of all instances.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    get,
+    path = "/admin/langfuse/status",
+    tag = "admin",
+    responses(
+        (status = OK, body = LangfuseStatus),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn langfuse_status(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<Json<LangfuseStatus>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+
+    let status = app_state
+        .langfuse_client
+        .status()
+        .await
+        .map_err(log_internal_server_error)?;
+    Ok(Json(status))
+}
+
+/// Run the self-test of a file storage provider.
+///
+/// Writes a small probe object, generates a pre-signed URL for it, fetches it via that URL and
+/// deletes it again. A failed self-test is returned with status 200, with the error of the failed
+/// step. Sharepoint is reported as unverifiable, as it only works with the access token of a user.
+/// Only available to members of the groups configured in `admin.groups` that don't belong to an
+/// organization.
+#[utoipa::path(
+    post,
+    path = "/admin/file-storage/{provider_id}/self-test",
+    tag = "admin",
+    params(
+        ("provider_id" = String, Path, description = "The ID of the file storage provider"),
+    ),
+    responses(
+        (status = OK, body = FileStorageSelfTestResult),
+        (status = NOT_FOUND, description = "When the file storage provider does not exist"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin, or the admin of an organization"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn file_storage_self_test(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(provider_id): Path<String>,
+) -> Result<Json<FileStorageSelfTestResult>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    if me_user.organization_id.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let file_storage = app_state
+        .file_storage_providers
+        .get(&provider_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let result = file_storage.self_test(&provider_id).await;
+    tracing::info!(
+        user_id = %me_user.id,
+        provider_id = %provider_id,
+        status = ?result.status,
+        "Ran the self-test of a file storage provider"
+    );
+
+    Ok(Json(result))
+}
+
+/// Preview the anonymous usage report.
+///
+/// Returns the exact report that is sent when `telemetry.usage_reporting` is enabled, whether it
+/// is enabled or not.
+/// Only available to members of the groups configured in `admin.groups` that don't belong to an
+/// organization.
+#[utoipa::path(
+    get,
+    path = "/admin/telemetry/preview",
+    tag = "admin",
+    responses(
+        (status = OK, body = UsageReport),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin, or the admin of an organization"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn telemetry_preview(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<Json<UsageReport>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    if me_user.organization_id.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let report = build_usage_report(&app_state)
+        .await
+        .map_err(log_internal_server_error)?;
+    Ok(Json(report))
+}
+
+/// Feature or unfeature an assistant.
+///
+/// Featured assistants are listed first when listing assistants with `sort=featured`. Admins of
+/// an organization can only feature the assistants of their own organization.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    put,
+    path = "/admin/assistants/{assistant_id}/featured",
+    tag = "admin",
+    params(
+        ("assistant_id" = String, Path, description = "The ID of the assistant"),
+    ),
+    request_body = SetAssistantFeaturedRequest,
+    responses(
+        (status = OK, body = AssistantFeaturedStatus),
+        (status = NOT_FOUND, description = "When the assistant does not exist, is archived, or belongs to another organization"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn set_assistant_featured(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(assistant_id): Path<String>,
+    Json(request): Json<SetAssistantFeaturedRequest>,
+) -> Result<Json<AssistantFeaturedStatus>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    let organization_id = admin_organization_filter(&me_user, None)?;
+    let assistant_id = Uuid::parse_str(&assistant_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let updated = assistant::set_assistant_featured(
+        &app_state.db,
+        organization_id,
+        assistant_id,
+        request.featured,
+    )
+    .await
+    .map_err(|e| {
+        if e.to_string().contains("not found") {
+            StatusCode::NOT_FOUND
+        } else {
+            log_internal_server_error(e)
+        }
+    })?;
+    app_state.global_policy_engine.invalidate_data().await;
+
+    Ok(Json(AssistantFeaturedStatus {
+        assistant_id: updated.id.to_string(),
+        featured: updated.featured,
+    }))
+}
+
+/// Transfer a chat to another owner.
+///
+/// Works like `POST /chats/{chat_id}/transfer-ownership`, for chats of any user. Admins of an
+/// organization can only transfer the chats of their own organization.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    post,
+    path = "/admin/chats/{chat_id}/transfer-ownership",
+    tag = "admin",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat to transfer"),
+    ),
+    request_body = TransferOwnershipRequest,
+    responses(
+        (status = OK, body = ChatOwnershipTransfer, description = "The applied changes, or the changes that would be applied for a dry run"),
+        (status = BAD_REQUEST, description = "When an ID is invalid, or the new owner already owns the chat or belongs to another organization"),
+        (status = NOT_FOUND, description = "When the chat or the new owner does not exist, or the chat belongs to another organization"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn transfer_chat_ownership(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(chat_id): Path<String>,
+    Json(request): Json<TransferOwnershipRequest>,
+) -> Result<Json<ChatOwnershipTransfer>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    let organization_id = admin_organization_filter(&me_user, None)?;
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let (new_owner_user_id, options) = request.parse()?;
+    let actor_user_id = Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let transfer = chat::transfer_chat_ownership(
+        &app_state.db,
+        organization_id,
+        &chat_id,
+        &new_owner_user_id,
+        Some(&actor_user_id),
+        &options,
+    )
+    .await
+    .map_err(transfer_error_status)?;
+    if !options.dry_run {
+        finish_chat_transfer(&app_state, &transfer, &me_user.id).await;
+    }
+
+    Ok(Json(transfer))
+}
+
+/// Transfer an assistant to another owner.
+///
+/// Works like `POST /assistants/{assistant_id}/transfer-ownership`, for assistants of any user.
+/// Admins of an organization can only transfer the assistants of their own organization.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    post,
+    path = "/admin/assistants/{assistant_id}/transfer-ownership",
+    tag = "admin",
+    params(
+        ("assistant_id" = String, Path, description = "The ID of the assistant to transfer"),
+    ),
+    request_body = TransferOwnershipRequest,
+    responses(
+        (status = OK, body = AssistantOwnershipTransfer, description = "The applied changes, or the changes that would be applied for a dry run"),
+        (status = BAD_REQUEST, description = "When an ID is invalid, or the new owner already owns the assistant or belongs to another organization"),
+        (status = NOT_FOUND, description = "When the assistant or the new owner does not exist, or the assistant belongs to another organization"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn transfer_assistant_ownership(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(assistant_id): Path<String>,
+    Json(request): Json<TransferOwnershipRequest>,
+) -> Result<Json<AssistantOwnershipTransfer>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    let organization_id = admin_organization_filter(&me_user, None)?;
+    let assistant_id = Uuid::parse_str(&assistant_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let (new_owner_user_id, options) = request.parse()?;
+    let actor_user_id = Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let transfer = ownership_transfer::transfer_assistant_ownership(
+        &app_state.db,
+        organization_id,
+        &assistant_id,
+        &new_owner_user_id,
+        Some(&actor_user_id),
+        &options,
+    )
+    .await
+    .map_err(transfer_error_status)?;
+    if !options.dry_run {
+        finish_assistant_transfer(&app_state, &transfer, &me_user.id).await;
+    }
+
+    Ok(Json(transfer))
+}
+
+/// Transfer all chats of a user to another owner, e.g. when the user leaves the organization.
+///
+/// The chats are transferred like with `POST /admin/chats/{chat_id}/transfer-ownership`, oldest
+/// first, in a single transaction: if the transfer of a chat fails, none of the chats are
+/// transferred. Admins of an organization can only transfer the chats of their own organization.
+/// Only available to members of the groups configured in `admin.groups`.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{user_id}/transfer-chats",
+    tag = "admin",
+    params(
+        ("user_id" = String, Path, description = "The ID of the user whose chats are transferred"),
+    ),
+    request_body = TransferOwnershipRequest,
+    responses(
+        (status = OK, body = TransferUserChatsResponse),
+        (status = BAD_REQUEST, description = "When an ID is invalid, the new owner is the same user, or belongs to another organization"),
+        (status = NOT_FOUND, description = "When the new owner does not exist"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user is not an admin"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn transfer_user_chats(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(user_id): Path<String>,
+    Json(request): Json<TransferOwnershipRequest>,
+) -> Result<Json<TransferUserChatsResponse>, StatusCode> {
+    require_admin(&app_state, &me_user)?;
+    let organization_id = admin_organization_filter(&me_user, None)?;
+    let user_id = Uuid::parse_str(&user_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let (new_owner_user_id, options) = request.parse()?;
+    let actor_user_id = Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let transfers = chat::transfer_chats_of_owner(
+        &app_state.db,
+        organization_id,
+        &user_id,
+        &new_owner_user_id,
+        Some(&actor_user_id),
+        &options,
+    )
+    .await
+    .map_err(transfer_error_status)?;
+    if !options.dry_run {
+        for transfer in &transfers {
+            finish_chat_transfer(&app_state, transfer, &me_user.id).await;
+        }
+    }
+
+    Ok(Json(TransferUserChatsResponse {
+        dry_run: options.dry_run,
+        transfers,
+    }))
+}
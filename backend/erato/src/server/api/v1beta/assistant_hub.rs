@@ -67,6 +67,8 @@ pub struct AssistantHubAssistantSnapshot {
     pub facet_ids: Option<Vec<String>>,
     pub default_chat_provider: Option<String>,
     pub enforce_facet_settings: bool,
+    pub welcome_message: Option<String>,
+    pub pinned_facet_ids: Option<Vec<String>>,
     pub created_at: DateTime<FixedOffset>,
     pub updated_at: DateTime<FixedOffset>,
 }
@@ -464,6 +466,8 @@ fn record_to_response(
             facet_ids: record.assistant.facet_ids,
             default_chat_provider: record.assistant.default_chat_provider,
             enforce_facet_settings: record.assistant.enforce_facet_settings,
+            welcome_message: record.assistant.welcome_message,
+            pinned_facet_ids: record.assistant.pinned_facet_ids,
             created_at: record.assistant.created_at,
             updated_at: record.assistant.updated_at,
         },
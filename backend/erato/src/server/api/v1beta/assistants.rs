@@ -1,12 +1,12 @@
 use crate::db::entity::prelude::Users;
-use crate::models::file_capability::{
-    FileCapability, find_file_capability_by_filename, get_file_capabilities,
-};
-use crate::models::file_upload::proxied_preview_url_for_file;
-use crate::models::{assistant, permissions, share_grant};
+use crate::models::assistant::{VISIBILITY_ORGANIZATION, VISIBILITY_PRIVATE};
+use crate::models::file_capability::{FileCapability, find_file_capability_by_filename};
+use crate::models::file_upload::{FileUrlKind, resolve_file_urls};
+use crate::models::{assistant, chat, permissions, share_grant};
 use crate::policy::engine::PolicyEngine;
 use crate::server::api::v1beta::me_profile_middleware::MeProfile;
-use crate::services::file_storage::is_missing_permissions_error;
+use crate::server::api::v1beta::user_file_capabilities;
+use crate::services::file_storage::SHAREPOINT_PROVIDER_ID;
 use crate::services::sentry::log_internal_server_error;
 use crate::state::AppState;
 use axum::extract::{Path, Query, State};
@@ -21,6 +21,61 @@ use utoipa::{IntoParams, ToSchema};
 
 use crate::models::assistant::FileInfo;
 
+/// Number of days of chats the popularity of assistants is based on
+const POPULARITY_WINDOW_DAYS: u32 = 30;
+
+/// Who can read an assistant
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AssistantVisibility {
+    /// Only the owner and the users the assistant is shared with
+    #[default]
+    Private,
+    /// All users of the organization of the assistant, without a share grant
+    Organization,
+}
+
+impl AssistantVisibility {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Private => VISIBILITY_PRIVATE,
+            Self::Organization => VISIBILITY_ORGANIZATION,
+        }
+    }
+
+    pub fn from_stored(visibility: &str) -> Self {
+        if visibility == VISIBILITY_ORGANIZATION {
+            Self::Organization
+        } else {
+            Self::Private
+        }
+    }
+}
+
+/// Order of the listed assistants
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AssistantSort {
+    /// Most recently updated first
+    #[default]
+    Recent,
+    /// Featured assistants first, then the most recently updated
+    Featured,
+    /// Most chats created in the organization in the last 30 days first
+    Popular,
+}
+
+/// A category that assistants can be assigned to
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AssistantCategory {
+    /// The ID of the category
+    pub id: String,
+    /// User-facing label of the category
+    pub display_name: String,
+    /// Icon identifier from the frontend icon system
+    pub icon: String,
+}
+
 /// An assistant model
 #[derive(Debug, Serialize, ToSchema)]
 pub struct Assistant {
@@ -52,6 +107,25 @@ pub struct Assistant {
     pub default_chat_provider: Option<String>,
     /// Whether chats derived from this assistant must use the configured facets
     pub enforce_facet_settings: bool,
+    /// Message shown as the first assistant message of new chats with this assistant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub welcome_message: Option<String>,
+    /// Facet IDs that are always enabled for chats derived from this assistant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub pinned_facet_ids: Option<Vec<String>>,
+    /// ID of the category of the assistant, one of the categories listed by
+    /// `/assistants/categories`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub category: Option<String>,
+    /// Tags for finding the assistant
+    pub tags: Vec<String>,
+    /// Who can read the assistant
+    pub visibility: AssistantVisibility,
+    /// Whether the assistant was featured by an admin
+    pub featured: bool,
     /// When this assistant was created
     pub created_at: DateTime<FixedOffset>,
     /// When this assistant was last updated
@@ -84,10 +158,13 @@ pub struct AssistantFile {
     pub id: String,
     /// The original filename
     pub filename: String,
-    /// Pre-signed URL for downloading the file.
+    /// URL for downloading the file, see `url_kind` for how to use it.
     /// Null when file contents are unavailable for the current user due to missing permissions.
     #[schema(nullable = true)]
     pub download_url: Option<String>,
+    /// Whether `download_url` can be fetched directly, or has to be requested with the
+    /// credentials of the user
+    pub url_kind: FileUrlKind,
     /// Proxied URL for inline preview without forcing download when available.
     #[schema(nullable = true)]
     pub preview_url: Option<String>,
@@ -131,6 +208,18 @@ pub struct CreateAssistantRequest {
     /// Whether chats derived from this assistant must use the configured facets
     #[serde(default)]
     pub enforce_facet_settings: bool,
+    /// Optional message shown as the first assistant message of new chats with this assistant
+    pub welcome_message: Option<String>,
+    /// Optional list of facet IDs that are always enabled for chats derived from this assistant
+    pub pinned_facet_ids: Option<Vec<String>>,
+    /// Optional ID of one of the categories listed by `/assistants/categories`
+    pub category: Option<String>,
+    /// Tags for finding the assistant
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Who can read the assistant. Defaults to `private`.
+    #[serde(default)]
+    pub visibility: AssistantVisibility,
     /// Optional list of file upload IDs to associate with this assistant
     pub file_ids: Option<Vec<String>>,
     /// Optional list of share grants to create with the assistant
@@ -162,6 +251,16 @@ pub struct UpdateAssistantRequest {
     pub default_chat_provider: Option<Option<String>>,
     /// Optional new enforcement flag for assistant facet settings
     pub enforce_facet_settings: Option<bool>,
+    /// Optional new welcome message for the assistant
+    pub welcome_message: Option<Option<String>>,
+    /// Optional new list of facet IDs that are always enabled for this assistant
+    pub pinned_facet_ids: Option<Option<Vec<String>>>,
+    /// Optional new category ID for the assistant
+    pub category: Option<Option<String>>,
+    /// Optional new list of tags for the assistant
+    pub tags: Option<Vec<String>>,
+    /// Optional new visibility for the assistant
+    pub visibility: Option<AssistantVisibility>,
     /// Optional list of file upload IDs to associate with this assistant
     pub file_ids: Option<Option<Vec<String>>>,
 }
@@ -189,6 +288,17 @@ pub struct ListAssistantsQuery {
     /// - `shared_with_user`: Only assistants shared with the user (= all - owned_by_user)
     #[serde(default = "default_sharing_relation")]
     pub sharing_relation: String,
+    /// Only list assistants of this category
+    pub category: Option<String>,
+    /// Only list assistants with this tag
+    pub tag: Option<String>,
+    /// Only list assistants with this visibility
+    pub visibility: Option<AssistantVisibility>,
+    /// Only list assistants that are (`true`) or aren't (`false`) featured
+    pub featured: Option<bool>,
+    /// Order of the assistants. Defaults to `recent`.
+    #[serde(default)]
+    pub sort: AssistantSort,
 }
 
 fn default_sharing_relation() -> String {
@@ -204,107 +314,39 @@ pub struct ArchiveAssistantResponse {
     pub archived_at: DateTime<FixedOffset>,
 }
 
-/// Helper function to convert FileInfo to AssistantFile with presigned download URL
-async fn file_info_to_assistant_file(
+/// Helper function to convert FileInfo to AssistantFile with a resolved download URL
+pub(crate) async fn file_info_to_assistant_file(
     file: FileInfo,
     file_capability: FileCapability,
     app_state: &AppState,
     access_token: Option<&str>,
 ) -> Result<AssistantFile, StatusCode> {
-    use crate::services::file_storage::SharepointContext;
-
-    // Get the file storage provider
-    let file_storage_provider = app_state
-        .file_storage_providers
-        .get(&file.file_storage_provider_id)
-        .ok_or_else(|| {
-            tracing::error!(
-                "File storage provider '{}' not found for file '{}'",
-                file.file_storage_provider_id,
-                file.id
-            );
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-    // Build the context for Sharepoint (will be ignored by other providers)
-    let sharepoint_ctx = access_token.map(|token| SharepointContext {
-        access_token: token,
-    });
-
-    // Generate a presigned download URL. If the user lacks permission for this Sharepoint file,
-    // return the file metadata with a hint flag instead of failing the whole request.
-    let (download_url, preview_url, file_contents_unavailable_missing_permissions) =
-        if file_storage_provider.is_sharepoint() {
-            match file_storage_provider
-                .get_sharepoint_file_metadata_with_context(
-                    &file.file_storage_path,
-                    sharepoint_ctx.as_ref(),
-                )
-                .await
-            {
-                Ok(metadata) => (
-                    Some(metadata.download_url),
-                    Some(proxied_preview_url_for_file(&file.id)),
-                    false,
-                ),
-                Err(e) if is_missing_permissions_error(&e) => {
-                    tracing::warn!(
-                        file_id = %file.id,
-                        error = %e,
-                        "Download URL unavailable due to missing file permissions"
-                    );
-                    (None, None, true)
-                }
-                Err(e) => {
-                    tracing::error!(
-                        "Failed to generate download URL for file {}: {}",
-                        file.id,
-                        e
-                    );
-                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
-                }
-            }
-        } else {
-            match file_storage_provider
-                .generate_presigned_download_url_with_context(
-                    &file.file_storage_path,
-                    None,
-                    Some(&file.filename),
-                    sharepoint_ctx.as_ref(),
-                )
-                .await
-            {
-                Ok(url) => (
-                    Some(url),
-                    Some(proxied_preview_url_for_file(&file.id)),
-                    false,
-                ),
-                Err(e) if is_missing_permissions_error(&e) => {
-                    tracing::warn!(
-                        file_id = %file.id,
-                        error = %e,
-                        "Download URL unavailable due to missing file permissions"
-                    );
-                    (None, None, true)
-                }
-                Err(e) => {
-                    tracing::error!(
-                        "Failed to generate download URL for file {}: {}",
-                        file.id,
-                        e
-                    );
-                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
-                }
-            }
-        };
+    // If the user lacks permission for this file, return the file metadata with a hint flag
+    // instead of failing the whole request.
+    let urls = resolve_file_urls(
+        &file.id,
+        &file.filename,
+        &file.file_storage_provider_id,
+        &file.file_storage_path,
+        &app_state.file_storage_providers,
+        access_token,
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to resolve download URL for file {}: {}", file.id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let file_contents_unavailable_missing_permissions =
+        urls.file_contents_unavailable_missing_permissions;
 
     Ok(AssistantFile {
         id: file.id.to_string(),
         filename: file.filename,
-        download_url,
-        preview_url,
+        download_url: (!file_contents_unavailable_missing_permissions).then_some(urls.download_url),
+        url_kind: urls.url_kind,
+        preview_url: urls.preview_url,
         file_contents_unavailable_missing_permissions,
-        is_sharepoint_file: file_storage_provider.is_sharepoint(),
+        is_sharepoint_file: file.file_storage_provider_id == SHAREPOINT_PROVIDER_ID,
         file_capability,
     })
 }
@@ -333,12 +375,42 @@ async fn owner_email_for_user_id(
     }
 }
 
+/// IDs of the assistants the user can edit through a share grant with `edit` permission.
+async fn edit_granted_assistant_ids(
+    app_state: &AppState,
+    me_user: &MeProfile,
+) -> Result<HashSet<String>, StatusCode> {
+    share_grant::get_edit_granted_resource_ids(&app_state.db, &me_user.to_subject(), "assistant")
+        .await
+        .map_err(log_internal_server_error)
+}
+
+/// Only categories from `assistants.categories` can be assigned to assistants.
+fn validate_assistant_category(
+    app_state: &AppState,
+    category: Option<&str>,
+) -> Result<(), StatusCode> {
+    match category {
+        Some(category)
+            if !app_state
+                .config
+                .assistants
+                .categories
+                .contains_key(category) =>
+        {
+            Err(StatusCode::BAD_REQUEST)
+        }
+        _ => Ok(()),
+    }
+}
+
 async fn validate_assistant_config_permissions(
     app_state: &AppState,
     policy: &PolicyEngine,
     me_user: &MeProfile,
     mcp_server_ids: Option<&[String]>,
     facet_ids: Option<&[String]>,
+    pinned_facet_ids: Option<&[String]>,
     default_chat_provider: Option<&str>,
 ) -> Result<(), StatusCode> {
     let subject = me_user.to_subject();
@@ -376,7 +448,7 @@ async fn validate_assistant_config_permissions(
         }
     }
 
-    if let Some(facet_ids) = facet_ids {
+    for facet_ids in [facet_ids, pinned_facet_ids].into_iter().flatten() {
         let requested_ids: Vec<String> = facet_ids.to_vec();
         let allowed: HashSet<String> = policy
             .filter_authorized_facet_ids(&subject, &me_user.groups, &requested_ids)
@@ -430,9 +502,11 @@ pub async fn create_assistant(
         &me_user,
         request.mcp_server_ids.as_deref(),
         request.facet_ids.as_deref(),
+        request.pinned_facet_ids.as_deref(),
         request.default_chat_provider.as_deref(),
     )
     .await?;
+    validate_assistant_category(&app_state, request.category.as_deref())?;
 
     if let Some(max_prompt_length) = app_state.config.assistants.max_system_prompt_length
         && request.prompt.len() > max_prompt_length
@@ -452,6 +526,11 @@ pub async fn create_assistant(
         request.facet_ids,
         request.default_chat_provider,
         request.enforce_facet_settings,
+        request.welcome_message,
+        request.pinned_facet_ids,
+        request.category,
+        request.tags,
+        request.visibility.as_str(),
     )
     .await
     .map_err(log_internal_server_error)?;
@@ -532,25 +611,7 @@ pub async fn create_assistant(
     .await
     .map_err(log_internal_server_error)?;
 
-    // Determine if any available model supports image understanding
-    let available_models = app_state
-        .available_models(&policy, &me_user.to_subject(), &me_user.groups)
-        .await
-        .map_err(log_internal_server_error)?;
-    let (supports_image_understanding, supports_audio_input) =
-        available_models
-            .iter()
-            .fold((false, false), |(image, audio), model| {
-                let config = app_state.config.get_chat_provider(&model.chat_provider_id);
-                (
-                    image || config.model_capabilities.supports_image_understanding,
-                    audio || config.model_capabilities.supports_audio_input,
-                )
-            });
-
-    // Get all file capabilities for this user
-    let all_capabilities =
-        get_file_capabilities(supports_image_understanding, supports_audio_input);
+    let all_capabilities = user_file_capabilities(&app_state, &policy, &me_user).await?;
 
     // Convert files to API format with presigned download URLs
     let mut api_files = Vec::new();
@@ -583,12 +644,19 @@ pub async fn create_assistant(
                     facet_ids: assistant_with_files.facet_ids,
                     default_chat_provider: assistant_with_files.default_chat_provider,
                     enforce_facet_settings: assistant_with_files.enforce_facet_settings,
+                    welcome_message: assistant_with_files.welcome_message,
+                    pinned_facet_ids: assistant_with_files.pinned_facet_ids,
+                    category: assistant_with_files.category,
+                    tags: assistant_with_files.tags,
+                    visibility: AssistantVisibility::from_stored(&assistant_with_files.visibility),
+                    featured: assistant_with_files.featured,
                     created_at: assistant_with_files.created_at,
                     updated_at: assistant_with_files.updated_at,
                     archived_at: assistant_with_files.archived_at,
                     can_edit: permissions::can_user_edit_assistant(
                         &me_user.id,
                         &assistant_with_files.owner_user_id.to_string(),
+                        false,
                     ),
                 },
                 files: api_files,
@@ -631,7 +699,7 @@ pub async fn list_assistants(
     }
 
     // Get all assistants for the user with the specified filter
-    let assistants = assistant::get_user_assistants(
+    let mut assistants = assistant::get_user_assistants(
         &app_state.db,
         &policy,
         &me_user.to_subject(),
@@ -640,6 +708,45 @@ pub async fn list_assistants(
     .await
     .map_err(log_internal_server_error)?;
 
+    assistants.retain(|assistant| {
+        query
+            .category
+            .as_ref()
+            .is_none_or(|category| assistant.category.as_ref() == Some(category))
+            && query
+                .tag
+                .as_ref()
+                .is_none_or(|tag| assistant.tags.contains(tag))
+            && query.visibility.is_none_or(|visibility| {
+                AssistantVisibility::from_stored(&assistant.visibility) == visibility
+            })
+            && query
+                .featured
+                .is_none_or(|featured| assistant.featured == featured)
+    });
+
+    // The assistants are ordered by most recent update, which the stable sorts below keep for ties
+    match query.sort {
+        AssistantSort::Recent => {}
+        AssistantSort::Featured => {
+            assistants.sort_by_key(|assistant| !assistant.featured);
+        }
+        AssistantSort::Popular => {
+            let usage_counts = chat::get_assistant_usage_counts(
+                &app_state.db,
+                &me_user.to_subject(),
+                POPULARITY_WINDOW_DAYS,
+            )
+            .await
+            .map_err(log_internal_server_error)?;
+            assistants.sort_by_key(|assistant| {
+                std::cmp::Reverse(usage_counts.get(&assistant.id).copied().unwrap_or(0))
+            });
+        }
+    }
+
+    let edit_granted_ids = edit_granted_assistant_ids(&app_state, &me_user).await?;
+
     // Convert to API format
     let current_user_id = &me_user.id;
     let mut api_assistants = Vec::with_capacity(assistants.len());
@@ -655,12 +762,19 @@ pub async fn list_assistants(
             facet_ids: assistant.facet_ids,
             default_chat_provider: assistant.default_chat_provider,
             enforce_facet_settings: assistant.enforce_facet_settings,
+            welcome_message: assistant.welcome_message,
+            pinned_facet_ids: assistant.pinned_facet_ids,
+            category: assistant.category,
+            tags: assistant.tags,
+            visibility: AssistantVisibility::from_stored(&assistant.visibility),
+            featured: assistant.featured,
             created_at: assistant.created_at,
             updated_at: assistant.updated_at,
             archived_at: assistant.archived_at,
             can_edit: permissions::can_user_edit_assistant(
                 current_user_id,
                 &assistant.owner_user_id.to_string(),
+                edit_granted_ids.contains(&assistant.id.to_string()),
             ),
         });
     }
@@ -714,25 +828,7 @@ pub async fn get_assistant(
         }
     })?;
 
-    // Determine if any available model supports image understanding
-    let available_models = app_state
-        .available_models(&policy, &me_user.to_subject(), &me_user.groups)
-        .await
-        .map_err(log_internal_server_error)?;
-    let (supports_image_understanding, supports_audio_input) =
-        available_models
-            .iter()
-            .fold((false, false), |(image, audio), model| {
-                let config = app_state.config.get_chat_provider(&model.chat_provider_id);
-                (
-                    image || config.model_capabilities.supports_image_understanding,
-                    audio || config.model_capabilities.supports_audio_input,
-                )
-            });
-
-    // Get all file capabilities for this user
-    let all_capabilities =
-        get_file_capabilities(supports_image_understanding, supports_audio_input);
+    let all_capabilities = user_file_capabilities(&app_state, &policy, &me_user).await?;
 
     // Convert files to API format with presigned download URLs
     let mut api_files = Vec::new();
@@ -750,6 +846,7 @@ pub async fn get_assistant(
 
     let owner_email =
         owner_email_for_user_id(&app_state, &assistant_with_files.owner_user_id).await;
+    let edit_granted_ids = edit_granted_assistant_ids(&app_state, &me_user).await?;
 
     Ok(Json(AssistantWithFiles {
         assistant: Assistant {
@@ -762,12 +859,19 @@ pub async fn get_assistant(
             facet_ids: assistant_with_files.facet_ids,
             default_chat_provider: assistant_with_files.default_chat_provider,
             enforce_facet_settings: assistant_with_files.enforce_facet_settings,
+            welcome_message: assistant_with_files.welcome_message,
+            pinned_facet_ids: assistant_with_files.pinned_facet_ids,
+            category: assistant_with_files.category,
+            tags: assistant_with_files.tags,
+            visibility: AssistantVisibility::from_stored(&assistant_with_files.visibility),
+            featured: assistant_with_files.featured,
             created_at: assistant_with_files.created_at,
             updated_at: assistant_with_files.updated_at,
             archived_at: assistant_with_files.archived_at,
             can_edit: permissions::can_user_edit_assistant(
                 &me_user.id,
                 &assistant_with_files.owner_user_id.to_string(),
+                edit_granted_ids.contains(&assistant_with_files.id.to_string()),
             ),
         },
         files: api_files,
@@ -813,12 +917,23 @@ pub async fn update_assistant(
             .as_ref()
             .and_then(|ids| ids.as_deref()),
         request.facet_ids.as_ref().and_then(|ids| ids.as_deref()),
+        request
+            .pinned_facet_ids
+            .as_ref()
+            .and_then(|ids| ids.as_deref()),
         request
             .default_chat_provider
             .as_ref()
             .and_then(|provider| provider.as_deref()),
     )
     .await?;
+    validate_assistant_category(
+        &app_state,
+        request
+            .category
+            .as_ref()
+            .and_then(|category| category.as_deref()),
+    )?;
 
     if let Some(max_prompt_length) = app_state.config.assistants.max_system_prompt_length
         && let Some(prompt) = &request.prompt
@@ -840,6 +955,11 @@ pub async fn update_assistant(
         request.facet_ids,
         request.default_chat_provider,
         request.enforce_facet_settings,
+        request.welcome_message,
+        request.pinned_facet_ids,
+        request.category,
+        request.tags,
+        request.visibility.map(AssistantVisibility::as_str),
     )
     .await
     .map_err(|e| {
@@ -950,25 +1070,7 @@ pub async fn update_assistant(
     .await
     .map_err(log_internal_server_error)?;
 
-    // Determine if any available model supports image understanding
-    let available_models = app_state
-        .available_models(&policy, &me_user.to_subject(), &me_user.groups)
-        .await
-        .map_err(log_internal_server_error)?;
-    let (supports_image_understanding, supports_audio_input) =
-        available_models
-            .iter()
-            .fold((false, false), |(image, audio), model| {
-                let config = app_state.config.get_chat_provider(&model.chat_provider_id);
-                (
-                    image || config.model_capabilities.supports_image_understanding,
-                    audio || config.model_capabilities.supports_audio_input,
-                )
-            });
-
-    // Get all file capabilities for this user
-    let all_capabilities =
-        get_file_capabilities(supports_image_understanding, supports_audio_input);
+    let all_capabilities = user_file_capabilities(&app_state, &policy, &me_user).await?;
 
     // Convert files to API format with presigned download URLs
     let mut api_files = Vec::new();
@@ -986,6 +1088,7 @@ pub async fn update_assistant(
 
     let owner_email =
         owner_email_for_user_id(&app_state, &assistant_with_files.owner_user_id).await;
+    let edit_granted_ids = edit_granted_assistant_ids(&app_state, &me_user).await?;
 
     Ok(Json(UpdateAssistantResponse {
         assistant: AssistantWithFiles {
@@ -999,12 +1102,19 @@ pub async fn update_assistant(
                 facet_ids: assistant_with_files.facet_ids,
                 default_chat_provider: assistant_with_files.default_chat_provider,
                 enforce_facet_settings: assistant_with_files.enforce_facet_settings,
+                welcome_message: assistant_with_files.welcome_message,
+                pinned_facet_ids: assistant_with_files.pinned_facet_ids,
+                category: assistant_with_files.category,
+                tags: assistant_with_files.tags,
+                visibility: AssistantVisibility::from_stored(&assistant_with_files.visibility),
+                featured: assistant_with_files.featured,
                 created_at: assistant_with_files.created_at,
                 updated_at: assistant_with_files.updated_at,
                 archived_at: assistant_with_files.archived_at,
                 can_edit: permissions::can_user_edit_assistant(
                     &me_user.id,
                     &assistant_with_files.owner_user_id.to_string(),
+                    edit_granted_ids.contains(&assistant_with_files.id.to_string()),
                 ),
             },
             files: api_files,
@@ -1069,3 +1179,35 @@ pub async fn archive_assistant(
         archived_at: archived_assistant.archived_at.unwrap(),
     }))
 }
+
+/// List the categories that assistants can be assigned to
+#[utoipa::path(
+    get,
+    path = "/assistants/categories",
+    tag = "assistants",
+    responses(
+        (status = OK, body = Vec<AssistantCategory>, description = "The categories configured in `assistants.categories`, ordered by display name"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_assistant_categories(
+    State(app_state): State<AppState>,
+) -> Json<Vec<AssistantCategory>> {
+    let mut categories: Vec<AssistantCategory> = app_state
+        .config
+        .assistants
+        .categories
+        .iter()
+        .map(|(id, category)| AssistantCategory {
+            id: id.clone(),
+            display_name: category.display_name.clone(),
+            icon: category.icon.clone(),
+        })
+        .collect();
+    categories.sort_by(|a, b| a.display_name.cmp(&b.display_name));
+
+    Json(categories)
+}
@@ -1468,6 +1468,7 @@ fn audio_provider(
         return Ok(ChatProviderConfigWithId {
             chat_provider_id: provider_id.to_string(),
             chat_provider_config: provider.clone(),
+            chat_provider_group_id: None,
         });
     }
 
@@ -1488,6 +1489,7 @@ fn audio_provider(
         return Ok(ChatProviderConfigWithId {
             chat_provider_id: provider_id.clone(),
             chat_provider_config: app_state.config.get_chat_provider(provider_id).clone(),
+            chat_provider_group_id: None,
         });
     }
 
@@ -1504,6 +1506,7 @@ fn audio_provider(
     Ok(ChatProviderConfigWithId {
         chat_provider_id: "default".to_string(),
         chat_provider_config: provider.clone(),
+        chat_provider_group_id: None,
     })
 }
 
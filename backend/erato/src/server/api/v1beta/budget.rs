@@ -1,15 +1,53 @@
-use crate::config::BudgetCurrency;
-use crate::metrics_constants::POSTGRES_QUERY_USER_SPENDING_BY_PROVIDER;
+use crate::config::{BudgetConfig, BudgetCurrency, BudgetPeriod, ModelCapabilities};
+use crate::metrics_constants::{
+    POSTGRES_QUERY_USAGE_BY_ASSISTANT, POSTGRES_QUERY_USER_SPENDING_BY_PROVIDER,
+};
 use crate::query_metrics::named_statement_from_sql_and_values;
 use crate::server::api::v1beta::me_profile_middleware::MeProfile;
 use crate::state::AppState;
-use axum::extract::State;
+use axum::extract::{Query, State};
 use axum::{Extension, Json};
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, Utc};
 use eyre::Report;
+use sea_orm::prelude::Uuid;
 use sea_orm::{DatabaseConnection, FromQueryResult};
-use serde::Serialize;
-use utoipa::ToSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::{IntoParams, ToSchema};
+
+/// Dimension the budget spending can be broken down by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetBreakdown {
+    /// Spending per assistant of the chats the generations happened in
+    Assistant,
+}
+
+/// Query parameters for the budget status
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct BudgetStatusQuery {
+    /// Additionally break down the spending of the current budget period
+    #[serde(default)]
+    pub breakdown: Option<BudgetBreakdown>,
+}
+
+/// Token usage and estimated cost of the generations in chats with one assistant
+#[derive(Debug, Clone, ToSchema, Serialize)]
+pub struct AssistantUsage {
+    /// The ID of the assistant, or `null` for chats without an assistant
+    pub assistant_id: Option<Uuid>,
+    /// The name of the assistant, or `null` for chats without an assistant
+    pub assistant_name: Option<String>,
+    /// Number of generations
+    pub generation_count: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub reasoning_tokens: u64,
+    pub total_tokens: u64,
+    /// Estimated cost based on the pricing configured for the chat providers, in the currency of
+    /// the global budget
+    pub estimated_cost: f64,
+}
 
 /// Budget status information for the current user
 #[derive(Debug, ToSchema, Serialize)]
@@ -36,6 +74,39 @@ pub struct BudgetStatusResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
     budget_currency: Option<BudgetCurrency>,
+    /// Spending in the current budget period per assistant.
+    /// Only present if requested via `breakdown=assistant`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    assistants: Option<Vec<AssistantUsage>>,
+    /// All budgets that apply to the user: the global budget (if `max_budget` is set) and the
+    /// configured `budgets` whose scope matches the user
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    scopes: Option<Vec<BudgetScopeStatus>>,
+    /// Whether any of the budgets that apply to the user is used up
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    exhausted: Option<bool>,
+}
+
+/// Spending of the current user against one budget
+#[derive(Debug, ToSchema, Serialize)]
+pub struct BudgetScopeStatus {
+    /// The scope of the budget, `global` or `group:<group-id>`
+    scope: String,
+    /// The budget limit for the period, in `budget_currency`
+    budget_limit: f64,
+    /// The currency of the limit and the spending
+    budget_currency: BudgetCurrency,
+    /// Spending of the user in the current period, converted to `budget_currency`
+    current_spending: f64,
+    /// Budget left in the current period (never negative)
+    remaining: f64,
+    /// Start of the current period
+    period_start: DateTime<Utc>,
+    /// End of the current period (exclusive), at which the spending is reset
+    period_end: DateTime<Utc>,
 }
 
 /// Database result for user token usage aggregation by chat provider
@@ -45,11 +116,41 @@ struct UserTokenUsageByProvider {
     total_prompt_tokens: Option<i64>,
     total_completion_tokens: Option<i64>,
     total_reasoning_tokens: Option<i64>,
+    total_cache_read_tokens: Option<i64>,
+    total_cache_write_tokens: Option<i64>,
+}
+
+/// Database result for token usage aggregation by assistant and chat provider
+#[derive(Debug, FromQueryResult)]
+struct TokenUsageByAssistant {
+    assistant_id: Option<Uuid>,
+    assistant_name: Option<String>,
+    chat_provider_id: String,
+    generation_count: i64,
+    total_prompt_tokens: Option<i64>,
+    total_completion_tokens: Option<i64>,
+    total_reasoning_tokens: Option<i64>,
+    total_cache_read_tokens: Option<i64>,
+    total_cache_write_tokens: Option<i64>,
+    total_tokens: Option<i64>,
+}
+
+/// Token counts of the usage of a chat provider, for the estimation of its cost
+#[derive(Debug, Clone, Copy, Default)]
+struct TokenCounts {
+    prompt: i64,
+    completion: i64,
+    reasoning: i64,
+    /// Prompt tokens that were read from the prompt cache of the provider, included in `prompt`
+    cache_read: i64,
+    /// Prompt tokens that were written to the prompt cache of the provider, included in `prompt`
+    cache_write: i64,
 }
 
 #[utoipa::path(
     get,
     path = "/me/budget",
+    params(BudgetStatusQuery),
     responses(
         (status = OK, body = BudgetStatusResponse),
         (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
@@ -62,6 +163,7 @@ struct UserTokenUsageByProvider {
 pub async fn budget_status(
     State(app_state): State<AppState>,
     Extension(me_user): Extension<MeProfile>,
+    Query(query): Query<BudgetStatusQuery>,
 ) -> Result<Json<BudgetStatusResponse>, axum::http::StatusCode> {
     let budget_config = &app_state.config.budget;
 
@@ -74,39 +176,105 @@ pub async fn budget_status(
             warn_threshold: None,
             budget_limit: None,
             budget_currency: None,
+            assistants: None,
+            scopes: None,
+            exhausted: None,
         }));
     }
 
-    // Calculate current budget period dates
     let now = Utc::now();
-    let period_duration = Duration::days(budget_config.budget_period_days as i64);
+    let (current_period_start, current_period_end) =
+        rolling_period_bounds(budget_config.budget_period_days, now);
 
-    // For simplicity, we'll calculate the period start as the beginning of the current period
-    // This assumes periods start from a fixed point and roll over every N days
-    // A more sophisticated implementation might use user-specific period starts
-    let days_since_epoch = now.timestamp() / (24 * 60 * 60);
-    let period_number = days_since_epoch / budget_config.budget_period_days as i64;
-    let period_start_timestamp =
-        period_number * budget_config.budget_period_days as i64 * 24 * 60 * 60;
-    let current_period_start =
-        DateTime::from_timestamp(period_start_timestamp, 0).unwrap_or(now - period_duration);
-    let current_period_end = current_period_start + period_duration;
-
-    // Calculate current spending for the user in this period
-    let current_spending = match calculate_user_spending(
-        &app_state.db,
-        &me_user.id,
-        current_period_start,
-        current_period_end,
-        &app_state,
-    )
-    .await
+    // The global budget, followed by the configured budgets that apply to the user
+    let mut applicable_budgets = Vec::new();
+    if let Some(max_budget) = budget_config.max_budget {
+        applicable_budgets.push((
+            "global".to_string(),
+            max_budget,
+            &budget_config.budget_currency,
+            (current_period_start, current_period_end),
+        ));
+    }
+    for budget in budget_config
+        .budgets
+        .iter()
+        .filter(|budget| budget.applies_to(&me_user.groups))
     {
-        Ok(spending) => Some(spending),
-        Err(e) => {
-            tracing::error!("Failed to calculate user spending: {}", e);
-            return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+        applicable_budgets.push((
+            budget.scope.clone(),
+            budget.limit,
+            &budget.currency,
+            calendar_period_bounds(budget.period, now),
+        ));
+    }
+
+    // Aggregate the usage once per distinct period
+    let mut usage_by_period: HashMap<
+        (DateTime<Utc>, DateTime<Utc>),
+        Vec<UserTokenUsageByProvider>,
+    > = HashMap::new();
+    let periods = std::iter::once((current_period_start, current_period_end))
+        .chain(applicable_budgets.iter().map(|(_, _, _, period)| *period));
+    for (period_start, period_end) in periods {
+        if usage_by_period.contains_key(&(period_start, period_end)) {
+            continue;
+        }
+        match user_usage_by_provider(&app_state.db, &me_user.id, period_start, period_end).await {
+            Ok(usage) => {
+                usage_by_period.insert((period_start, period_end), usage);
+            }
+            Err(e) => {
+                tracing::error!("Failed to calculate user spending: {}", e);
+                return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+            }
         }
+    }
+
+    let current_spending = Some(spending_in_currency(
+        &app_state,
+        &usage_by_period[&(current_period_start, current_period_end)],
+        &budget_config.budget_currency,
+    ));
+
+    let scopes: Vec<BudgetScopeStatus> = applicable_budgets
+        .into_iter()
+        .map(|(scope, limit, currency, (period_start, period_end))| {
+            let spending = spending_in_currency(
+                &app_state,
+                &usage_by_period[&(period_start, period_end)],
+                currency,
+            );
+            BudgetScopeStatus {
+                scope,
+                budget_limit: limit,
+                budget_currency: currency.clone(),
+                current_spending: spending,
+                remaining: (limit - spending).max(0.0),
+                period_start,
+                period_end,
+            }
+        })
+        .collect();
+    let exhausted = scopes.iter().any(|scope| scope.remaining <= 0.0);
+
+    let assistants = match query.breakdown {
+        Some(BudgetBreakdown::Assistant) => match usage_by_assistant(
+            &app_state,
+            Some(&me_user.id),
+            None,
+            current_period_start,
+            current_period_end,
+        )
+        .await
+        {
+            Ok(assistants) => Some(assistants),
+            Err(e) => {
+                tracing::error!("Failed to calculate user spending by assistant: {}", e);
+                return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        },
+        None => None,
     };
 
     Ok(Json(BudgetStatusResponse {
@@ -116,24 +284,61 @@ pub async fn budget_status(
         warn_threshold: Some(budget_config.warn_threshold),
         budget_limit: budget_config.max_budget,
         budget_currency: Some(budget_config.budget_currency.clone()),
+        assistants,
+        scopes: Some(scopes),
+        exhausted: Some(exhausted),
     }))
 }
 
-/// Calculate the total spending for a user in a given time period
-async fn calculate_user_spending(
+/// Start and (exclusive) end of the period of `period_days` days that contains `now`.
+///
+/// Periods are counted from the Unix epoch, so they roll over at the same time for all users.
+fn rolling_period_bounds(period_days: u32, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let period_duration = Duration::days(period_days as i64);
+    let days_since_epoch = now.timestamp() / (24 * 60 * 60);
+    let period_number = days_since_epoch / period_days as i64;
+    let period_start_timestamp = period_number * period_days as i64 * 24 * 60 * 60;
+    let period_start =
+        DateTime::from_timestamp(period_start_timestamp, 0).unwrap_or(now - period_duration);
+    (period_start, period_start + period_duration)
+}
+
+/// Start and (exclusive) end of the calendar month or quarter (in UTC) that contains `now`.
+fn calendar_period_bounds(
+    period: BudgetPeriod,
+    now: DateTime<Utc>,
+) -> (DateTime<Utc>, DateTime<Utc>) {
+    let (first_month, months) = match period {
+        BudgetPeriod::Monthly => (now.month(), 1),
+        BudgetPeriod::Quarterly => ((now.month0() / 3) * 3 + 1, 3),
+    };
+    let start = NaiveDate::from_ymd_opt(now.year(), first_month, 1)
+        .expect("the first day of a month is a valid date");
+    let end = start
+        .checked_add_months(Months::new(months))
+        .unwrap_or(NaiveDate::MAX);
+    (
+        start.and_time(NaiveTime::MIN).and_utc(),
+        end.and_time(NaiveTime::MIN).and_utc(),
+    )
+}
+
+/// Aggregate the token usage of a user in a given time period by chat provider
+async fn user_usage_by_provider(
     db: &DatabaseConnection,
     user_id: &str,
     period_start: DateTime<Utc>,
     period_end: DateTime<Utc>,
-    app_state: &AppState,
-) -> Result<f64, Report> {
+) -> Result<Vec<UserTokenUsageByProvider>, Report> {
     // Use the updated view to get token usage grouped by chat provider
     let sql = r#"
         SELECT 
             chat_provider_id,
             SUM(total_prompt_tokens)::BIGINT as total_prompt_tokens,
             SUM(total_completion_tokens)::BIGINT as total_completion_tokens,
-            SUM(total_reasoning_tokens)::BIGINT as total_reasoning_tokens
+            SUM(total_reasoning_tokens)::BIGINT as total_reasoning_tokens,
+            SUM(total_cache_read_tokens)::BIGINT as total_cache_read_tokens,
+            SUM(total_cache_write_tokens)::BIGINT as total_cache_write_tokens
         FROM user_daily_token_usage
         WHERE user_id = $1
           AND usage_date >= $2::date
@@ -155,43 +360,370 @@ async fn calculate_user_spending(
         .all(db)
         .await?;
 
-    let mut total_cost = 0.0;
+    Ok(usage_results)
+}
 
+/// The total cost of the usage of a user, in the given currency
+fn spending_in_currency(
+    app_state: &AppState,
+    usage_by_provider: &[UserTokenUsageByProvider],
+    currency: &BudgetCurrency,
+) -> f64 {
     // Calculate cost for each provider separately using their specific pricing
-    for usage in usage_results {
-        let prompt_tokens = usage.total_prompt_tokens.unwrap_or(0) as f64;
-        let completion_tokens = usage.total_completion_tokens.unwrap_or(0) as f64;
-        let reasoning_tokens = usage.total_reasoning_tokens.unwrap_or(0) as f64;
-
-        // Find the provider configuration for this usage
-        let provider_config = if let Some(chat_providers) = &app_state.config.chat_providers {
-            chat_providers.providers.get(&usage.chat_provider_id)
-        } else if let Some(single_provider) = &app_state.config.chat_provider {
-            // For legacy single provider, match any provider ID
-            Some(single_provider)
-        } else {
-            None
-        };
+    usage_by_provider
+        .iter()
+        .map(|usage| {
+            let tokens = TokenCounts {
+                prompt: usage.total_prompt_tokens.unwrap_or(0),
+                completion: usage.total_completion_tokens.unwrap_or(0),
+                reasoning: usage.total_reasoning_tokens.unwrap_or(0),
+                cache_read: usage.total_cache_read_tokens.unwrap_or(0),
+                cache_write: usage.total_cache_write_tokens.unwrap_or(0),
+            };
+            estimate_cost(app_state, &usage.chat_provider_id, tokens, currency)
+        })
+        .sum()
+}
 
-        if let Some(provider) = provider_config {
-            // Calculate costs per million tokens using provider-specific pricing
-            let prompt_cost = (prompt_tokens / 1_000_000.0)
-                * provider.model_capabilities.cost_input_tokens_per_1m;
-            let completion_cost = (completion_tokens / 1_000_000.0)
-                * provider.model_capabilities.cost_output_tokens_per_1m;
-            // Reasoning tokens are priced the same as output tokens
-            let reasoning_cost = (reasoning_tokens / 1_000_000.0)
-                * provider.model_capabilities.cost_output_tokens_per_1m;
-
-            total_cost += prompt_cost + completion_cost + reasoning_cost;
-        } else {
-            // If we can't find the provider config, log a warning but continue
+/// Estimate the cost of token usage with the pricing of the chat provider, in the given currency.
+///
+/// Returns `0.0` (and logs a warning) if the chat provider is not configured.
+fn estimate_cost(
+    app_state: &AppState,
+    chat_provider_id: &str,
+    tokens: TokenCounts,
+    currency: &BudgetCurrency,
+) -> f64 {
+    // Find the provider configuration for this usage
+    let provider_config = if let Some(chat_providers) = &app_state.config.chat_providers {
+        chat_providers.providers.get(chat_provider_id)
+    } else if let Some(single_provider) = &app_state.config.chat_provider {
+        // For legacy single provider, match any provider ID
+        Some(single_provider)
+    } else {
+        None
+    };
+
+    let Some(provider) = provider_config else {
+        // If we can't find the provider config, log a warning but continue
+        tracing::warn!(
+            "No provider configuration found for chat_provider_id: {}, skipping cost calculation",
+            chat_provider_id
+        );
+        return 0.0;
+    };
+
+    let cost = token_cost(&provider.model_capabilities, tokens);
+    match &provider.model_capabilities.cost_currency {
+        Some(cost_currency) => {
+            convert_cost(&app_state.config.budget, cost, cost_currency, currency)
+        }
+        None => cost,
+    }
+}
+
+/// The cost of token usage with the provider-specific pricing, in the currency of the prices.
+fn token_cost(model_capabilities: &ModelCapabilities, tokens: TokenCounts) -> f64 {
+    let cost_per_1m = |tokens: i64, price: f64| (tokens as f64 / 1_000_000.0) * price;
+    let input_price = model_capabilities.cost_input_tokens_per_1m;
+    // Prompt tokens that were read from or written to the prompt cache are priced like other
+    // prompt tokens, unless the model has prices for them
+    let cached_prompt_tokens = tokens.cache_read + tokens.cache_write;
+    let uncached_prompt_tokens = (tokens.prompt - cached_prompt_tokens).max(0);
+    let cache_read_price = model_capabilities
+        .cost_cache_read_tokens_per_1m
+        .unwrap_or(input_price);
+    let cache_write_price = model_capabilities
+        .cost_cache_write_tokens_per_1m
+        .unwrap_or(input_price);
+    let prompt_cost = cost_per_1m(uncached_prompt_tokens, input_price)
+        + cost_per_1m(tokens.cache_read, cache_read_price)
+        + cost_per_1m(tokens.cache_write, cache_write_price);
+    let completion_cost = cost_per_1m(
+        tokens.completion,
+        model_capabilities.cost_output_tokens_per_1m,
+    );
+    // Reasoning tokens are priced the same as output tokens
+    let reasoning_cost = cost_per_1m(
+        tokens.reasoning,
+        model_capabilities.cost_output_tokens_per_1m,
+    );
+
+    prompt_cost + completion_cost + reasoning_cost
+}
+
+/// Convert a cost between currencies with the configured conversion rates.
+///
+/// Costs are left as they are (and a warning is logged) if no conversion rate is configured.
+fn convert_cost(
+    budget_config: &BudgetConfig,
+    cost: f64,
+    from: &BudgetCurrency,
+    to: &BudgetCurrency,
+) -> f64 {
+    match budget_config.conversion_rate(from, to) {
+        Some(rate) => cost * rate,
+        None => {
             tracing::warn!(
-                "No provider configuration found for chat_provider_id: {}, skipping cost calculation",
-                usage.chat_provider_id
+                "No conversion rate from {:?} to {:?} configured, using unconverted cost",
+                from,
+                to
             );
+            cost
         }
     }
+}
+
+/// Aggregate the token usage and estimated cost of all generations in a time period by the
+/// assistant of the chat they happened in.
+///
+/// If `user_id` is given, only chats owned by that user are taken into account, and if
+/// `organization_id` is given, only chats of that organization.
+/// Sorted by descending estimated cost.
+pub(crate) async fn usage_by_assistant(
+    app_state: &AppState,
+    user_id: Option<&str>,
+    organization_id: Option<&str>,
+    period_start: DateTime<Utc>,
+    period_end: DateTime<Utc>,
+) -> Result<Vec<AssistantUsage>, Report> {
+    // Grouped by chat provider as well, as the pricing differs between them.
+    // The date range scan is supported by `idx_messages_generation_created_at`.
+    let sql = r#"
+        SELECT
+            c.assistant_id,
+            a.name AS assistant_name,
+            COALESCE(m.generation_parameters->>'generation_chat_provider_id', 'unknown') AS chat_provider_id,
+            COUNT(*)::BIGINT AS generation_count,
+            SUM(COALESCE((m.generation_metadata->>'used_prompt_tokens')::BIGINT, 0))::BIGINT AS total_prompt_tokens,
+            SUM(COALESCE((m.generation_metadata->>'used_completion_tokens')::BIGINT, 0))::BIGINT AS total_completion_tokens,
+            SUM(COALESCE((m.generation_metadata->>'used_reasoning_tokens')::BIGINT, 0))::BIGINT AS total_reasoning_tokens,
+            SUM(COALESCE((m.generation_metadata->>'used_cache_read_tokens')::BIGINT, 0))::BIGINT AS total_cache_read_tokens,
+            SUM(COALESCE((m.generation_metadata->>'used_cache_write_tokens')::BIGINT, 0))::BIGINT AS total_cache_write_tokens,
+            SUM(COALESCE((m.generation_metadata->>'used_total_tokens')::BIGINT, 0))::BIGINT AS total_tokens
+        FROM messages m
+        JOIN chats c ON m.chat_id = c.id
+        LEFT JOIN assistants a ON c.assistant_id = a.id
+        WHERE m.generation_metadata ? 'used_total_tokens'
+          AND m.created_at >= $1
+          AND m.created_at < $2
+          AND ($3::TEXT IS NULL OR c.owner_user_id = $3)
+          AND ($4::TEXT IS NULL OR c.organization_id = $4)
+        GROUP BY c.assistant_id, a.name, 3
+    "#;
+
+    let usage_results =
+        TokenUsageByAssistant::find_by_statement(named_statement_from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            POSTGRES_QUERY_USAGE_BY_ASSISTANT,
+            sql,
+            vec![
+                period_start.into(),
+                period_end.into(),
+                user_id.map(ToString::to_string).into(),
+                organization_id.map(ToString::to_string).into(),
+            ],
+        ))
+        .all(&app_state.db)
+        .await?;
+
+    let mut assistants: HashMap<Option<Uuid>, AssistantUsage> = HashMap::new();
+    for usage in usage_results {
+        let tokens = TokenCounts {
+            prompt: usage.total_prompt_tokens.unwrap_or(0),
+            completion: usage.total_completion_tokens.unwrap_or(0),
+            reasoning: usage.total_reasoning_tokens.unwrap_or(0),
+            cache_read: usage.total_cache_read_tokens.unwrap_or(0),
+            cache_write: usage.total_cache_write_tokens.unwrap_or(0),
+        };
+        let estimated_cost = estimate_cost(
+            app_state,
+            &usage.chat_provider_id,
+            tokens,
+            &app_state.config.budget.budget_currency,
+        );
 
-    Ok(total_cost)
+        let entry = assistants
+            .entry(usage.assistant_id)
+            .or_insert_with(|| AssistantUsage {
+                assistant_id: usage.assistant_id,
+                assistant_name: usage.assistant_name.clone(),
+                generation_count: 0,
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                reasoning_tokens: 0,
+                total_tokens: 0,
+                estimated_cost: 0.0,
+            });
+        entry.generation_count += usage.generation_count.max(0) as u64;
+        entry.prompt_tokens += tokens.prompt.max(0) as u64;
+        entry.completion_tokens += tokens.completion.max(0) as u64;
+        entry.reasoning_tokens += tokens.reasoning.max(0) as u64;
+        entry.total_tokens += usage.total_tokens.unwrap_or(0).max(0) as u64;
+        entry.estimated_cost += estimated_cost;
+    }
+
+    let mut assistants: Vec<AssistantUsage> = assistants.into_values().collect();
+    assistants.sort_by(|a, b| {
+        b.estimated_cost
+            .total_cmp(&a.estimated_cost)
+            .then_with(|| b.total_tokens.cmp(&a.total_tokens))
+            .then_with(|| a.assistant_name.cmp(&b.assistant_name))
+    });
+    Ok(assistants)
+}
+
+/// Serialize the usage per assistant as CSV, with a header row.
+pub(crate) fn assistant_usage_to_csv(assistants: &[AssistantUsage]) -> String {
+    let mut csv = String::from(
+        "assistant_id,assistant_name,generation_count,prompt_tokens,completion_tokens,reasoning_tokens,total_tokens,estimated_cost\n",
+    );
+    for usage in assistants {
+        let fields = [
+            usage
+                .assistant_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            csv_escape(usage.assistant_name.as_deref().unwrap_or_default()),
+            usage.generation_count.to_string(),
+            usage.prompt_tokens.to_string(),
+            usage.completion_tokens.to_string(),
+            usage.reasoning_tokens.to_string(),
+            usage.total_tokens.to_string(),
+            usage.estimated_cost.to_string(),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break.
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CurrencyConversionRate;
+
+    fn utc(date: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(date)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn monthly_period_is_the_calendar_month() {
+        assert_eq!(
+            calendar_period_bounds(BudgetPeriod::Monthly, utc("2026-01-31T23:59:59Z")),
+            (utc("2026-01-01T00:00:00Z"), utc("2026-02-01T00:00:00Z"))
+        );
+        assert_eq!(
+            calendar_period_bounds(BudgetPeriod::Monthly, utc("2026-12-01T00:00:00Z")),
+            (utc("2026-12-01T00:00:00Z"), utc("2027-01-01T00:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn quarterly_period_is_the_calendar_quarter() {
+        assert_eq!(
+            calendar_period_bounds(BudgetPeriod::Quarterly, utc("2026-03-31T23:59:59Z")),
+            (utc("2026-01-01T00:00:00Z"), utc("2026-04-01T00:00:00Z"))
+        );
+        assert_eq!(
+            calendar_period_bounds(BudgetPeriod::Quarterly, utc("2026-04-01T00:00:00Z")),
+            (utc("2026-04-01T00:00:00Z"), utc("2026-07-01T00:00:00Z"))
+        );
+        assert_eq!(
+            calendar_period_bounds(BudgetPeriod::Quarterly, utc("2026-11-15T12:00:00Z")),
+            (utc("2026-10-01T00:00:00Z"), utc("2027-01-01T00:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn rolling_period_is_counted_from_the_epoch() {
+        // 2026-01-15 is day 20468 after the epoch, in the 30-day period starting on day 20460
+        assert_eq!(
+            rolling_period_bounds(30, utc("2026-01-15T08:00:00Z")),
+            (utc("2026-01-07T00:00:00Z"), utc("2026-02-06T00:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn cached_prompt_tokens_use_the_cache_prices() {
+        let tokens = TokenCounts {
+            prompt: 3_000_000,
+            completion: 1_000_000,
+            cache_read: 1_000_000,
+            cache_write: 1_000_000,
+            ..Default::default()
+        };
+        let mut model_capabilities = ModelCapabilities {
+            cost_input_tokens_per_1m: 3.0,
+            cost_output_tokens_per_1m: 15.0,
+            ..Default::default()
+        };
+        // Without cache prices, cached tokens are priced like other prompt tokens
+        assert_eq!(token_cost(&model_capabilities, tokens), 24.0);
+
+        model_capabilities.cost_cache_read_tokens_per_1m = Some(0.5);
+        model_capabilities.cost_cache_write_tokens_per_1m = Some(3.75);
+        assert_eq!(token_cost(&model_capabilities, tokens), 22.25);
+    }
+
+    #[test]
+    fn converts_costs_with_direct_and_inverse_rates() {
+        let budget_config = BudgetConfig {
+            conversion_rates: vec![CurrencyConversionRate {
+                from: BudgetCurrency::USD,
+                to: BudgetCurrency::EUR,
+                rate: 0.8,
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            convert_cost(
+                &budget_config,
+                10.0,
+                &BudgetCurrency::USD,
+                &BudgetCurrency::EUR
+            ),
+            8.0
+        );
+        assert_eq!(
+            convert_cost(
+                &budget_config,
+                8.0,
+                &BudgetCurrency::EUR,
+                &BudgetCurrency::USD
+            ),
+            10.0
+        );
+        assert_eq!(
+            convert_cost(
+                &budget_config,
+                3.0,
+                &BudgetCurrency::EUR,
+                &BudgetCurrency::EUR
+            ),
+            3.0
+        );
+        // Without a conversion rate, costs are taken as they are
+        assert_eq!(
+            convert_cost(
+                &BudgetConfig::default(),
+                10.0,
+                &BudgetCurrency::USD,
+                &BudgetCurrency::EUR
+            ),
+            10.0
+        );
+    }
 }
@@ -0,0 +1,382 @@
+//! Export of chat transcripts.
+
+use crate::db::entity::messages;
+use crate::models;
+use crate::models::message::{
+    ContentPart, GetChatMessagesOptions, MessageCursor, MessageOrder, MessageRole, MessageSchema,
+    get_message_by_id,
+};
+use crate::policy::engine::PolicyEngine;
+use crate::server::api::v1beta::me_profile_middleware::MeProfile;
+use crate::services::chat_export::{PageLimitExceeded, PdfTranscriptWriter, TranscriptBlock};
+use crate::services::content_spillover::resolve_blob_pointers;
+use crate::services::file_storage::{
+    ContentDispositionKind, SharepointContext, build_content_disposition,
+};
+use crate::services::sentry::log_internal_server_error;
+use crate::state::AppState;
+use axum::Extension;
+use axum::extract::{Path, Query, State};
+use axum::http::header::{CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64_STANDARD};
+use chrono::Utc;
+use eyre::{Report, WrapErr};
+use sea_orm::prelude::Uuid;
+use serde::Deserialize;
+use std::collections::HashSet;
+use utoipa::{IntoParams, ToSchema};
+
+/// Number of messages that are loaded from the database at a time.
+const EXPORT_BATCH_SIZE: u64 = 50;
+
+/// Format of a chat export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatExportFormat {
+    /// A PDF document with the messages, embedded images and summaries of tool calls
+    Pdf,
+}
+
+/// Query parameters for exporting a chat
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ChatExportQuery {
+    /// Format of the export
+    pub format: ChatExportFormat,
+    /// Only export the messages of the active thread starting at this message
+    #[serde(default)]
+    pub from_message_id: Option<Uuid>,
+    /// Only export the messages of the active thread up to and including this message
+    #[serde(default)]
+    pub to_message_id: Option<Uuid>,
+}
+
+/// Export the active thread of a chat as a document.
+///
+/// Exports that would exceed `chat_export.max_pdf_pages` are rejected; narrow down the exported
+/// messages with `from_message_id` and `to_message_id` in that case.
+#[utoipa::path(
+    get,
+    path = "/chats/{chat_id}/export",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat to export"),
+        ChatExportQuery
+    ),
+    responses(
+        (status = OK, description = "The exported chat", content_type = "application/pdf", body = Vec<u8>),
+        (status = BAD_REQUEST, description = "Invalid chat ID, format or message range"),
+        (status = NOT_FOUND, description = "When the chat or one of the messages of the range does not exist or is not accessible"),
+        (status = PAYLOAD_TOO_LARGE, description = "When the export exceeds the maximum number of pages", content_type = "text/plain", body = String),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "When an internal server error occurs")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn export_chat(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path(chat_id): Path<String>,
+    Query(query): Query<ChatExportQuery>,
+) -> Result<Response, StatusCode> {
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+
+    let from_message = match query.from_message_id {
+        Some(message_id) => {
+            Some(get_thread_message(&app_state, &policy, &me_user, chat_id, message_id).await?)
+        }
+        None => None,
+    };
+    let to_message = match query.to_message_id {
+        Some(message_id) => {
+            Some(get_thread_message(&app_state, &policy, &me_user, chat_id, message_id).await?)
+        }
+        None => None,
+    };
+    if let (Some(from), Some(to)) = (&from_message, &to_message)
+        && (from.created_at, from.id) > (to.created_at, to.id)
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let exported_at = Utc::now();
+    let mut writer = PdfTranscriptWriter::new(
+        &app_state.config.chat_export,
+        format!(
+            "Chat {chat_id} - exported {}",
+            exported_at.format("%Y-%m-%d %H:%M UTC")
+        ),
+    );
+
+    let rendered = render_messages(
+        &app_state,
+        &policy,
+        &me_user,
+        chat_id,
+        from_message,
+        to_message.map(|message| message.id),
+        &mut writer,
+    )
+    .await;
+    let message_count = match rendered {
+        Ok(message_count) => message_count,
+        Err(e) => {
+            if let Some(limit) = e.downcast_ref::<PageLimitExceeded>() {
+                return Ok((StatusCode::PAYLOAD_TOO_LARGE, limit.to_string()).into_response());
+            }
+            let s = e.to_string();
+            if s.contains("not found")
+                || s.contains("Access denied")
+                || s.contains("not authorized")
+            {
+                return Err(StatusCode::NOT_FOUND);
+            }
+            return Err(log_internal_server_error(e));
+        }
+    };
+
+    let page_count = writer.page_count();
+    let pdf = writer.finish().map_err(log_internal_server_error)?;
+    tracing::info!(
+        chat_id = %chat_id,
+        user_id = %me_user.id,
+        format = "pdf",
+        from_message_id = ?query.from_message_id,
+        to_message_id = ?query.to_message_id,
+        message_count,
+        page_count,
+        "Chat exported"
+    );
+
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/pdf"));
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("private, no-store"));
+    let filename = format!("chat-{chat_id}.pdf");
+    let content_disposition = HeaderValue::from_str(&build_content_disposition(
+        ContentDispositionKind::Attachment,
+        Some(filename.as_str()),
+    ))
+    .map_err(|e| log_internal_server_error(e.into()))?;
+    headers.insert(CONTENT_DISPOSITION, content_disposition);
+    Ok((headers, pdf).into_response())
+}
+
+/// Get a message that bounds the exported range, which has to be in the active thread of the chat.
+async fn get_thread_message(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    chat_id: Uuid,
+    message_id: Uuid,
+) -> Result<messages::Model, StatusCode> {
+    let message = get_message_by_id(&app_state.db, policy, &me_user.to_subject(), &message_id)
+        .await
+        .map_err(|e| {
+            let s = e.to_string();
+            if s.contains("not found")
+                || s.contains("Access denied")
+                || s.contains("not authorized")
+            {
+                StatusCode::NOT_FOUND
+            } else {
+                log_internal_server_error(e)
+            }
+        })?;
+    if message.chat_id != chat_id || !message.is_message_in_active_thread {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(message)
+}
+
+/// Render the messages of the active thread in batches, so that only one batch of messages is
+/// held in memory at a time. Returns the number of rendered messages.
+async fn render_messages(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    chat_id: Uuid,
+    from_message: Option<messages::Model>,
+    to_message_id: Option<Uuid>,
+    writer: &mut PdfTranscriptWriter,
+) -> Result<usize, Report> {
+    writer.add_title("Chat transcript")?;
+
+    let mut message_count = 0;
+    // Listing continues after the cursor, so the first message of the range is rendered on its own.
+    let mut cursor = None;
+    if let Some(from_message) = from_message {
+        render_message(app_state, policy, me_user, &from_message, writer).await?;
+        message_count += 1;
+        if Some(from_message.id) == to_message_id {
+            return Ok(message_count);
+        }
+        cursor = Some(MessageCursor {
+            created_at: from_message.created_at,
+            id: from_message.id,
+            backward: false,
+        });
+    }
+
+    loop {
+        let (batch, stats) = models::message::get_chat_messages(
+            &app_state.db,
+            policy,
+            &me_user.to_subject(),
+            &chat_id,
+            GetChatMessagesOptions {
+                limit: Some(EXPORT_BATCH_SIZE),
+                offset: None,
+                order: MessageOrder::Asc,
+                page_order: None,
+                cursor: cursor.take(),
+            },
+        )
+        .await?;
+
+        for message in batch
+            .iter()
+            .filter(|message| message.is_message_in_active_thread)
+        {
+            render_message(app_state, policy, me_user, message, writer).await?;
+            message_count += 1;
+            if Some(message.id) == to_message_id {
+                return Ok(message_count);
+            }
+        }
+
+        match stats.next_cursor {
+            Some(next_cursor) => cursor = Some(MessageCursor::decode(&next_cursor)?),
+            None => return Ok(message_count),
+        }
+    }
+}
+
+async fn render_message(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    message: &messages::Model,
+    writer: &mut PdfTranscriptWriter,
+) -> Result<(), Report> {
+    let schema: MessageSchema =
+        serde_json::from_value(message.raw_message.clone()).wrap_err("Failed to parse message")?;
+    let role = match schema.role {
+        MessageRole::System => return Ok(()),
+        MessageRole::User => "User",
+        MessageRole::Assistant => "Assistant",
+        MessageRole::Tool => "Tool",
+    };
+
+    let mut blocks = Vec::new();
+    let mut embedded_file_ids = HashSet::new();
+    let content =
+        resolve_blob_pointers(app_state.default_file_storage_provider(), schema.content).await;
+    for part in content {
+        match part {
+            ContentPart::Text(text) => blocks.push(TranscriptBlock::Text(text.text)),
+            ContentPart::ToolUse(tool_use) => blocks.push(TranscriptBlock::ToolCall {
+                tool_name: tool_use.tool_name,
+                status: serde_json::to_value(&tool_use.status)?
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            }),
+            ContentPart::ImageFilePointer(pointer) => {
+                if embedded_file_ids.insert(pointer.file_upload_id) {
+                    blocks
+                        .push(file_block(app_state, policy, me_user, pointer.file_upload_id).await);
+                }
+            }
+            ContentPart::Image(image) => match BASE64_STANDARD.decode(&image.base64_data) {
+                Ok(bytes) => blocks.push(TranscriptBlock::Image {
+                    filename: None,
+                    bytes,
+                }),
+                Err(err) => tracing::warn!("Skipping image with invalid base64 data: {}", err),
+            },
+            // Reasoning, file contents and action facets are part of the prompt, not the transcript.
+            // Blob pointers were resolved above.
+            ContentPart::Reasoning(_)
+            | ContentPart::TextFilePointer(_)
+            | ContentPart::ActionFacetMarker(_)
+            | ContentPart::BlobPointer(_) => {}
+        }
+    }
+    for file_id in message.input_file_uploads.iter().flatten() {
+        if embedded_file_ids.insert(*file_id) {
+            blocks.push(file_block(app_state, policy, me_user, *file_id).await);
+        }
+    }
+
+    writer.add_message(role, message.created_at, blocks)
+}
+
+/// Images are embedded, other files are listed by name. Files that can not be read don't fail the
+/// export, but are listed as unavailable.
+async fn file_block(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    file_id: Uuid,
+) -> TranscriptBlock {
+    let file_upload = match models::file_upload::get_file_upload_by_id(
+        &app_state.db,
+        policy,
+        &me_user.to_subject(),
+        &file_id,
+    )
+    .await
+    {
+        Ok(file_upload) => file_upload,
+        Err(err) => {
+            tracing::warn!("Failed to get file '{}' for export: {:?}", file_id, err);
+            return TranscriptBlock::Attachment {
+                filename: format!("Unavailable file ({file_id})"),
+            };
+        }
+    };
+    if image::ImageFormat::from_path(&file_upload.filename).is_err() {
+        return TranscriptBlock::Attachment {
+            filename: file_upload.filename,
+        };
+    }
+
+    let Some(file_storage) = app_state
+        .file_storage_providers
+        .get(&file_upload.file_storage_provider_id)
+    else {
+        tracing::warn!(
+            "File storage provider '{}' not found for export",
+            file_upload.file_storage_provider_id
+        );
+        return TranscriptBlock::Attachment {
+            filename: file_upload.filename,
+        };
+    };
+    let sharepoint_ctx = me_user
+        .access_token
+        .as_deref()
+        .map(|access_token| SharepointContext { access_token });
+    match file_storage
+        .read_file_to_bytes_with_context(&file_upload.file_storage_path, sharepoint_ctx.as_ref())
+        .await
+    {
+        Ok(bytes) => TranscriptBlock::Image {
+            filename: Some(file_upload.filename),
+            bytes,
+        },
+        Err(err) => {
+            tracing::warn!("Failed to read image '{}' for export: {:?}", file_id, err);
+            TranscriptBlock::Attachment {
+                filename: file_upload.filename,
+            }
+        }
+    }
+}
@@ -2,16 +2,48 @@
 //!
 //! These routes allow users to list organization users and groups
 //! for sharing features.
+//!
+//! Both endpoints return one page at a time. Searches are forwarded to the MS Graph API, and the
+//! `@odata.nextLink` of a page is handed to clients as an opaque `next_cursor`. The number of
+//! MS Graph requests per request is bounded by
+//! `integrations.experimental_entra_id.max_graph_calls_per_request`.
 
 use crate::server::api::v1beta::me_profile_middleware::MeProfile;
+use crate::server::api::v1beta::message_streaming::retry_after_from_headers;
 use crate::state::AppState;
 use axum::extract::{Query, State};
-use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
 use axum::{Extension, Json};
-use graph_rs_sdk::{GraphClient, GraphClientConfiguration, ODataQuery};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL_SAFE};
+use eyre::{Report, WrapErr, eyre};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use reqwest_tracing::{SpanBackendWithUrl, TracingMiddleware};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
 use utoipa::{IntoParams, ToSchema};
 
+/// Number of users or groups returned per page if no `limit` is given
+const DEFAULT_LIMIT: usize = 100;
+
+/// Maximum page size, which is also the maximum page size of the MS Graph API
+const MAX_LIMIT: usize = 999;
+
+/// Maximum length of search queries, in characters
+const MAX_QUERY_CHARS: usize = 100;
+
+/// Throttled MS Graph requests are retried within the same request if MS Graph asks to wait at
+/// most this long. Otherwise the request fails with 503.
+const MAX_RETRY_AFTER_WAIT_SECONDS: u64 = 2;
+
+/// Retry hint for throttled MS Graph requests that didn't include a `Retry-After` header
+const DEFAULT_RETRY_AFTER_SECONDS: u64 = 10;
+
+const USER_SELECT: &str = "id,displayName,mail,userPrincipalName,jobTitle";
+const GROUP_SELECT: &str = "id,displayName,description";
+
 /// Query parameters for listing organization users
 #[derive(Debug, Deserialize, ToSchema, IntoParams)]
 pub struct ListUsersQuery {
@@ -20,13 +52,23 @@ pub struct ListUsersQuery {
     #[serde(default)]
     pub is_involved: bool,
 
-    /// Optional search query to filter users by displayName.
-    /// Uses Microsoft Graph $search parameter with fuzzy, tokenized matching.
-    /// When provided (even if empty), only the first page of results is returned for performance.
-    /// This optimizes search field implementations where typing starts with an empty query.
+    /// Optional search query, matched against the display name, mail and user principal name.
+    /// Forwarded to the MS Graph `$search` parameter, which does tokenized prefix matching.
+    /// At most 100 characters. `query` is accepted as an alias for existing clients.
+    #[serde(default, alias = "query")]
+    #[param(nullable = false)]
+    pub q: Option<String>,
+
+    /// Maximum number of users to return. Defaults to 100, and is capped at 999.
+    #[serde(default)]
+    #[param(nullable = false)]
+    pub limit: Option<u64>,
+
+    /// The `next_cursor` of a previous response, to fetch the next page.
+    /// The cursor carries the filters of the first request, so the other parameters are ignored.
     #[serde(default)]
     #[param(nullable = false)]
-    pub query: Option<String>,
+    pub cursor: Option<String>,
 }
 
 /// Query parameters for listing organization groups
@@ -37,19 +79,29 @@ pub struct ListGroupsQuery {
     #[serde(default)]
     pub is_involved: bool,
 
-    /// Optional search query to filter groups by displayName.
-    /// Uses Microsoft Graph $search parameter with fuzzy, tokenized matching.
-    /// When provided (even if empty), only the first page of results is returned for performance.
-    /// This optimizes search field implementations where typing starts with an empty query.
+    /// Optional search query, matched against the display name and description.
+    /// Forwarded to the MS Graph `$search` parameter, which does tokenized prefix matching.
+    /// At most 100 characters. `query` is accepted as an alias for existing clients.
+    #[serde(default, alias = "query")]
+    #[param(nullable = false)]
+    pub q: Option<String>,
+
+    /// Maximum number of groups to return. Defaults to 100, and is capped at 999.
+    #[serde(default)]
+    #[param(nullable = false)]
+    pub limit: Option<u64>,
+
+    /// The `next_cursor` of a previous response, to fetch the next page.
+    /// The cursor carries the filters of the first request, so the other parameters are ignored.
     #[serde(default)]
     #[param(nullable = false)]
-    pub query: Option<String>,
+    pub cursor: Option<String>,
 }
 
 /// An organization user
 #[derive(Debug, Serialize, ToSchema, Clone)]
 pub struct OrganizationUser {
-    /// The unique ID of the user
+    /// The unique ID of the user (the Entra ID object ID)
     pub id: String,
     /// The display name of the user
     pub display_name: String,
@@ -57,6 +109,8 @@ pub struct OrganizationUser {
     pub job_title: Option<String>,
     /// The email address of the user
     pub mail: Option<String>,
+    /// The user principal name of the user, which is also set for users without a mailbox
+    pub user_principal_name: Option<String>,
     /// The subject type ID to use when creating a share grant (always "organization_user_id")
     pub subject_type_id: String,
 }
@@ -64,17 +118,23 @@ pub struct OrganizationUser {
 /// Response for the organization users endpoint
 #[derive(Debug, Serialize, ToSchema)]
 pub struct OrganizationUsersResponse {
-    /// List of users in the organization
+    /// The current page of users in the organization
     pub users: Vec<OrganizationUser>,
+    /// Cursor for fetching the next page, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub next_cursor: Option<String>,
 }
 
 /// An organization group
 #[derive(Debug, Serialize, ToSchema, Clone)]
 pub struct OrganizationGroup {
-    /// The unique ID of the group
+    /// The unique ID of the group (the Entra ID object ID)
     pub id: String,
     /// The display name of the group
     pub display_name: String,
+    /// The description of the group
+    pub description: Option<String>,
     /// The subject type ID to use when creating a share grant (always "organization_group_id")
     pub subject_type_id: String,
 }
@@ -82,8 +142,142 @@ pub struct OrganizationGroup {
 /// Response for the organization groups endpoint
 #[derive(Debug, Serialize, ToSchema)]
 pub struct OrganizationGroupsResponse {
-    /// List of groups in the organization
+    /// The current page of groups in the organization
     pub groups: Vec<OrganizationGroup>,
+    /// Cursor for fetching the next page, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub next_cursor: Option<String>,
+}
+
+/// Body of the structured error responses of the organization endpoints
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EntraIdErrorResponse {
+    /// Machine-readable error code, either `entra_id_disabled` or `graph_throttled`
+    pub error: String,
+    /// Human-readable description of the error
+    pub message: String,
+    /// Number of seconds after which the request can be retried, for `graph_throttled`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub retry_after_seconds: Option<u64>,
+}
+
+/// Errors of the organization endpoints.
+#[derive(Debug)]
+pub enum EntraIdApiError {
+    /// The Entra ID integration is not enabled
+    Disabled,
+    /// MS Graph throttled the requests of the user
+    Throttled { retry_after_seconds: u64 },
+    /// Any other error, without a body
+    Status(StatusCode),
+}
+
+impl From<StatusCode> for EntraIdApiError {
+    fn from(status: StatusCode) -> Self {
+        Self::Status(status)
+    }
+}
+
+impl IntoResponse for EntraIdApiError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Disabled => (
+                StatusCode::NOT_FOUND,
+                Json(EntraIdErrorResponse {
+                    error: "entra_id_disabled".to_string(),
+                    message: "The Entra ID integration is not enabled".to_string(),
+                    retry_after_seconds: None,
+                }),
+            )
+                .into_response(),
+            Self::Throttled {
+                retry_after_seconds,
+            } => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(header::RETRY_AFTER, retry_after_seconds.to_string())],
+                Json(EntraIdErrorResponse {
+                    error: "graph_throttled".to_string(),
+                    message: "Microsoft Graph is throttling requests, please retry later"
+                        .to_string(),
+                    retry_after_seconds: Some(retry_after_seconds),
+                }),
+            )
+                .into_response(),
+            Self::Status(status) => status.into_response(),
+        }
+    }
+}
+
+impl From<GraphError> for EntraIdApiError {
+    fn from(error: GraphError) -> Self {
+        match error {
+            GraphError::Throttled {
+                retry_after_seconds,
+            } => Self::Throttled {
+                retry_after_seconds,
+            },
+            GraphError::BudgetExhausted => {
+                tracing::error!("Exhausted the MS Graph call budget of the request");
+                Self::Status(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            GraphError::Failed(report) => {
+                tracing::error!("Failed to fetch from the MS Graph API: {:?}", report);
+                Self::Status(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+}
+
+/// Opaque cursor of the organization endpoints, as returned in `next_cursor`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum OrganizationCursor {
+    /// The `@odata.nextLink` of the previous MS Graph page
+    NextLink { url: String, search: bool },
+    /// Offset into the users the requesting user shares a group with, which are listed by erato
+    InvolvedUsers {
+        offset: usize,
+        limit: usize,
+        q: Option<String>,
+    },
+}
+
+impl OrganizationCursor {
+    fn encode(&self) -> Result<String, Report> {
+        let json = serde_json::to_vec(self).wrap_err("Failed to serialize organization cursor")?;
+        Ok(BASE64_URL_SAFE.encode(json))
+    }
+
+    /// Decode a cursor, and reject next links that don't point at the configured MS Graph API.
+    fn decode(encoded: &str, graph_api_base_url: &str) -> Result<Self, StatusCode> {
+        let cursor = BASE64_URL_SAFE
+            .decode(encoded)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Self>(&json).ok())
+            .ok_or_else(|| {
+                tracing::warn!("Invalid organization cursor");
+                StatusCode::BAD_REQUEST
+            })?;
+        if let Self::NextLink { url, .. } = &cursor
+            && !url.starts_with(&format!("{}/", graph_api_base_url.trim_end_matches('/')))
+        {
+            tracing::warn!("Organization cursor points outside of the MS Graph API");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Ok(cursor)
+    }
+}
+
+fn encode_cursor(cursor: Option<OrganizationCursor>) -> Result<Option<String>, StatusCode> {
+    cursor
+        .map(|cursor| cursor.encode())
+        .transpose()
+        .map_err(|e| {
+            tracing::error!("Failed to encode organization cursor: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
 }
 
 /// Helper to extract access token from the user profile.
@@ -97,155 +291,341 @@ fn get_access_token(me_user: &MeProfile) -> Result<&str, StatusCode> {
     })
 }
 
-/// Check if Entra ID integration is enabled in the config.
-/// Returns true if enabled, false otherwise (no error thrown).
-fn check_entra_id_enabled(app_state: &AppState) -> bool {
-    app_state.config.integrations.experimental_entra_id.enabled
+/// Check that the Entra ID integration is enabled in the config.
+fn check_entra_id_enabled(app_state: &AppState) -> Result<(), EntraIdApiError> {
+    if app_state.config.integrations.experimental_entra_id.enabled {
+        Ok(())
+    } else {
+        tracing::warn!("Entra ID integration is not enabled");
+        Err(EntraIdApiError::Disabled)
+    }
 }
 
-/// Create a GraphClient with the user's access token.
-fn create_graph_client(access_token: &str) -> GraphClient {
-    GraphClient::from(
-        GraphClientConfiguration::new()
-            .access_token(access_token)
-            .connection_verbose(true),
-    )
+/// Validate the search query, and strip the characters that would break the `$search`
+/// expression it is placed in. Empty queries are treated as no query.
+fn normalize_search_query(q: Option<&str>) -> Result<Option<String>, StatusCode> {
+    let Some(q) = q else {
+        return Ok(None);
+    };
+    let trimmed = q.trim();
+    if trimmed.chars().count() > MAX_QUERY_CHARS {
+        tracing::warn!(
+            "Query too long: {} chars (max {})",
+            trimmed.chars().count(),
+            MAX_QUERY_CHARS
+        );
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let sanitized: String = trimmed
+        .chars()
+        .filter(|c| *c != '"' && *c != '\\')
+        .collect();
+    Ok(Some(sanitized.trim().to_string()).filter(|q| !q.is_empty()))
 }
 
-/// Fetch all groups the current user belongs to using transitive_member_of.
-/// Returns a Vec of group IDs.
-async fn fetch_user_groups(client: &GraphClient) -> Result<Vec<String>, StatusCode> {
-    let response_deque = client
-        .me()
-        .transitive_member_of()
-        .as_group()
-        .select(&["id"])
-        .paging()
-        .json::<GroupsResponse>()
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to fetch user's groups from Graph API: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+fn page_size(limit: Option<u64>) -> usize {
+    limit
+        .map_or(DEFAULT_LIMIT, |limit| {
+            usize::try_from(limit).unwrap_or(MAX_LIMIT)
+        })
+        .clamp(1, MAX_LIMIT)
+}
 
-    let mut group_ids = Vec::new();
-    for response in response_deque {
-        let groups_response = response.into_body().map_err(|e| {
-            tracing::error!("Failed to parse groups response: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+/// The `$search` expression matching the query against the given properties.
+fn search_expression(q: &str, properties: &[&str]) -> String {
+    properties
+        .iter()
+        .map(|property| format!("\"{property}:{q}\""))
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
 
-        for group_item in &groups_response.value {
-            group_ids.push(group_item.id.clone());
+/// Errors of MS Graph requests.
+#[derive(Debug)]
+pub enum GraphError {
+    /// The request made as many MS Graph requests as it may
+    BudgetExhausted,
+    /// MS Graph throttled the requests, and asked to wait longer than we retry within a request
+    Throttled {
+        retry_after_seconds: u64,
+    },
+    Failed(Report),
+}
+
+/// A page of an MS Graph collection.
+#[derive(Debug, Deserialize)]
+struct GraphPage<T> {
+    value: Vec<T>,
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
+}
+
+/// MS Graph requests of a single API request, bounded by the configured call budget.
+struct GraphSession<'a> {
+    http_client: ClientWithMiddleware,
+    base_url: &'a str,
+    access_token: &'a str,
+    remaining_calls: usize,
+}
+
+impl<'a> GraphSession<'a> {
+    fn new(app_state: &'a AppState, access_token: &'a str) -> Self {
+        let config = &app_state.config.integrations.experimental_entra_id;
+        let http_client = ClientBuilder::new(reqwest::Client::new())
+            .with(TracingMiddleware::<SpanBackendWithUrl>::new())
+            .build();
+        Self {
+            http_client,
+            base_url: config.graph_api_base_url.trim_end_matches('/'),
+            access_token,
+            remaining_calls: config.max_graph_calls_per_request,
         }
     }
 
-    Ok(group_ids)
-}
+    /// URL of a MS Graph collection, with the given query parameters.
+    fn url(&self, path: &str, params: &[(&str, String)]) -> Result<String, GraphError> {
+        reqwest::Url::parse_with_params(&format!("{}{}", self.base_url, path), params)
+            .map(String::from)
+            .map_err(|e| GraphError::Failed(eyre!(e).wrap_err("Invalid MS Graph URL")))
+    }
 
-/// Fetch all user IDs that are members of the given groups.
-/// Returns a HashSet of user IDs.
-async fn fetch_users_in_groups(
-    client: &GraphClient,
-    group_ids: &[String],
-) -> Result<std::collections::HashSet<String>, StatusCode> {
-    use std::collections::HashSet;
-
-    let mut user_ids = HashSet::new();
-
-    for group_id in group_ids {
-        // Fetch members of this group
-        // Note: Some groups may not exist or be inaccessible (e.g., deleted groups, special directory objects)
-        // We skip these groups with a warning instead of failing the entire request
-        let response_deque = match client
-            .groups()
-            .id(group_id)
-            .list_members()
-            .select(&["id"])
-            .paging()
-            .json::<GroupMembersResponse>()
-            .await
-        {
-            Ok(deque) => deque,
-            Err(e) => {
-                tracing::warn!(
-                    "Skipping group {} - unable to fetch members (group may not exist or be inaccessible): {:?}",
-                    group_id,
-                    e
-                );
-                continue;
+    /// Fetch a page of a MS Graph collection. `search` has to be set for requests with a
+    /// `$search` parameter, which require eventual consistency.
+    ///
+    /// Throttled requests are retried if MS Graph asks to wait at most
+    /// [`MAX_RETRY_AFTER_WAIT_SECONDS`], and the retry fits into the call budget.
+    async fn get_page<T: DeserializeOwned>(
+        &mut self,
+        url: &str,
+        search: bool,
+    ) -> Result<GraphPage<T>, GraphError> {
+        loop {
+            if self.remaining_calls == 0 {
+                return Err(GraphError::BudgetExhausted);
             }
-        };
+            self.remaining_calls -= 1;
 
-        for response in response_deque {
-            let members_response = match response.into_body() {
-                Ok(resp) => resp,
-                Err(e) => {
-                    tracing::warn!(
-                        "Skipping group {} - failed to parse members response: {:?}",
-                        group_id,
-                        e
+            let mut request = self.http_client.get(url).bearer_auth(self.access_token);
+            if search {
+                request = request.header("ConsistencyLevel", "eventual");
+            }
+            let response = request
+                .send()
+                .await
+                .map_err(|e| GraphError::Failed(eyre!(e).wrap_err("Failed to request MS Graph")))?;
+
+            let status = response.status();
+            if status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || status == reqwest::StatusCode::SERVICE_UNAVAILABLE
+            {
+                let retry_after_seconds = retry_after_from_headers(response.headers())
+                    .unwrap_or(DEFAULT_RETRY_AFTER_SECONDS);
+                if retry_after_seconds <= MAX_RETRY_AFTER_WAIT_SECONDS && self.remaining_calls > 0 {
+                    tracing::info!(
+                        retry_after_seconds,
+                        "MS Graph throttled the request, retrying"
                     );
+                    tokio::time::sleep(Duration::from_secs(retry_after_seconds)).await;
                     continue;
                 }
-            };
-
-            for member_item in &members_response.value {
-                user_ids.insert(member_item.id.clone());
+                tracing::warn!(retry_after_seconds, "MS Graph throttled the request");
+                return Err(GraphError::Throttled {
+                    retry_after_seconds,
+                });
             }
+            if !status.is_success() {
+                return Err(GraphError::Failed(eyre!(
+                    "MS Graph returned unsuccessful status: {}",
+                    status
+                )));
+            }
+
+            return response.json::<GraphPage<T>>().await.map_err(|e| {
+                GraphError::Failed(eyre!(e).wrap_err("Failed to parse MS Graph page"))
+            });
         }
     }
 
-    Ok(user_ids)
-}
-
-/// Helper struct for deserializing users from MS Graph API.
-#[derive(Debug, serde::Deserialize)]
-struct UsersResponse {
-    value: Vec<UserItem>,
+    /// Fetch pages starting at `url` until at least one item is kept by `keep`, and return the
+    /// kept items with the cursor of the next page.
+    ///
+    /// Pages that only contain dropped items (e.g. the requesting user) are skipped, as long as
+    /// the call budget allows. Once it is exhausted, the items so far are returned with a cursor,
+    /// so that clients can continue from there.
+    async fn collect_page<T: DeserializeOwned>(
+        &mut self,
+        url: String,
+        search: bool,
+        keep: impl Fn(&T) -> bool,
+    ) -> Result<(Vec<T>, Option<OrganizationCursor>), GraphError> {
+        let mut next_url = url;
+        let mut items = Vec::new();
+        loop {
+            let page = self.get_page::<T>(&next_url, search).await?;
+            items.extend(page.value.into_iter().filter(&keep));
+            let Some(next_link) = page.next_link else {
+                return Ok((items, None));
+            };
+            let cursor = OrganizationCursor::NextLink {
+                url: next_link.clone(),
+                search,
+            };
+            if !items.is_empty() || self.remaining_calls == 0 {
+                return Ok((items, Some(cursor)));
+            }
+            next_url = next_link;
+        }
+    }
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct UserItem {
+/// A user as returned by the MS Graph API.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphUser {
     id: String,
-    #[serde(rename = "displayName")]
     display_name: Option<String>,
-    #[serde(rename = "jobTitle")]
     job_title: Option<String>,
     mail: Option<String>,
+    user_principal_name: Option<String>,
 }
 
-/// Helper struct for deserializing groups from MS Graph API.
-#[derive(Debug, serde::Deserialize)]
-struct GroupsResponse {
-    value: Vec<GroupItem>,
+impl From<GraphUser> for OrganizationUser {
+    fn from(user: GraphUser) -> Self {
+        Self {
+            id: user.id,
+            display_name: user.display_name.unwrap_or_default(),
+            job_title: user.job_title,
+            mail: user.mail,
+            user_principal_name: user.user_principal_name,
+            subject_type_id: "organization_user_id".to_string(),
+        }
+    }
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct GroupItem {
+/// A group as returned by the MS Graph API.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphGroup {
     id: String,
-    #[serde(rename = "displayName")]
     display_name: Option<String>,
+    description: Option<String>,
 }
 
-/// Helper struct for deserializing group members from MS Graph API.
-#[derive(Debug, serde::Deserialize)]
-struct GroupMembersResponse {
-    value: Vec<GroupMemberItem>,
+impl From<GraphGroup> for OrganizationGroup {
+    fn from(group: GraphGroup) -> Self {
+        Self {
+            id: group.id,
+            display_name: group.display_name.unwrap_or_default(),
+            description: group.description,
+            subject_type_id: "organization_group_id".to_string(),
+        }
+    }
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct GroupMemberItem {
-    id: String,
+/// List the users that share at least one group with the requesting user, within the call
+/// budget.
+///
+/// MS Graph can't list them directly, so they are collected from the members of all groups of the
+/// requesting user, filtered and sorted by erato, and paginated with an offset. If the call budget
+/// doesn't cover all groups, the users of the groups fetched so far are returned.
+async fn list_involved_users(
+    graph: &mut GraphSession<'_>,
+    current_user_id: Option<&str>,
+    q: Option<&str>,
+    offset: usize,
+    limit: usize,
+) -> Result<(Vec<OrganizationUser>, Option<OrganizationCursor>), GraphError> {
+    let mut group_ids = Vec::new();
+    let mut next_url = Some(graph.url(
+        "/me/transitiveMemberOf/microsoft.graph.group",
+        &[
+            ("$select", "id".to_string()),
+            ("$top", MAX_LIMIT.to_string()),
+        ],
+    )?);
+    while let Some(url) = next_url {
+        let page = graph.get_page::<GraphGroup>(&url, false).await?;
+        group_ids.extend(page.value.into_iter().map(|group| group.id));
+        next_url = page.next_link;
+    }
+
+    let mut users = BTreeMap::new();
+    'groups: for group_id in &group_ids {
+        let mut next_url = Some(graph.url(
+            &format!("/groups/{group_id}/members/microsoft.graph.user"),
+            &[
+                ("$select", USER_SELECT.to_string()),
+                ("$top", MAX_LIMIT.to_string()),
+            ],
+        )?);
+        while let Some(url) = next_url {
+            let page = match graph.get_page::<GraphUser>(&url, false).await {
+                Ok(page) => page,
+                Err(GraphError::BudgetExhausted) => {
+                    tracing::warn!(
+                        "Exhausted the MS Graph call budget while listing the members of {} groups, returning the members found so far",
+                        group_ids.len()
+                    );
+                    break 'groups;
+                }
+                // Some groups may not exist or be inaccessible (e.g., deleted groups, special
+                // directory objects). We skip these groups with a warning instead of failing the
+                // entire request.
+                Err(GraphError::Failed(e)) => {
+                    tracing::warn!(
+                        "Skipping group {} - unable to fetch members: {:?}",
+                        group_id,
+                        e
+                    );
+                    continue 'groups;
+                }
+                Err(e) => return Err(e),
+            };
+            for user in page.value {
+                users.entry(user.id.clone()).or_insert(user);
+            }
+            next_url = page.next_link;
+        }
+    }
+
+    let q = q.map(str::to_lowercase);
+    let mut matching: Vec<OrganizationUser> = users
+        .into_values()
+        .filter(|user| Some(user.id.as_str()) != current_user_id)
+        .filter(|user| {
+            let Some(q) = &q else {
+                return true;
+            };
+            [&user.display_name, &user.mail, &user.user_principal_name]
+                .into_iter()
+                .flatten()
+                .any(|value| value.to_lowercase().contains(q))
+        })
+        .map(OrganizationUser::from)
+        .collect();
+    matching.sort_by(|a, b| {
+        a.display_name
+            .to_lowercase()
+            .cmp(&b.display_name.to_lowercase())
+            .then_with(|| a.id.cmp(&b.id))
+    });
+
+    let next_offset = offset + limit;
+    let cursor = (matching.len() > next_offset).then(|| OrganizationCursor::InvolvedUsers {
+        offset: next_offset,
+        limit,
+        q: q.clone(),
+    });
+    let page = matching.into_iter().skip(offset).take(limit).collect();
+    Ok((page, cursor))
 }
 
-/// List all users in the organization.
+/// List the users in the organization, one page at a time.
 ///
-/// If the Entra ID integration is not enabled, returns an empty list.
-/// If enabled, fetches users from the MS Graph API.
+/// Returns 404 if the Entra ID integration is not enabled.
 /// When is_involved=true, only returns users who share at least one group with the requesting user.
-/// When query parameter is provided (even if empty), returns only the first page for performance.
-/// Non-empty queries use $search for fuzzy matching.
+/// Non-empty queries are forwarded to the MS Graph `$search` parameter.
+/// The requesting user is never included.
 #[utoipa::path(
     get,
     path = "/me/organization/users",
@@ -254,9 +634,12 @@ struct GroupMemberItem {
         ListUsersQuery
     ),
     responses(
-        (status = OK, body = OrganizationUsersResponse, description = "List of organization users"),
+        (status = OK, body = OrganizationUsersResponse, description = "A page of organization users"),
+        (status = BAD_REQUEST, description = "Invalid query or cursor"),
         (status = UNAUTHORIZED, description = "No access token available"),
-        (status = INTERNAL_SERVER_ERROR, description = "Failed to retrieve users")
+        (status = NOT_FOUND, body = EntraIdErrorResponse, description = "The Entra ID integration is not enabled"),
+        (status = INTERNAL_SERVER_ERROR, description = "Failed to retrieve users"),
+        (status = SERVICE_UNAVAILABLE, body = EntraIdErrorResponse, description = "MS Graph is throttling requests; retry after the `Retry-After` header")
     ),
     security(
         ("bearer_auth" = [])
@@ -266,188 +649,78 @@ pub async fn list_organization_users(
     State(app_state): State<AppState>,
     Extension(me_user): Extension<MeProfile>,
     Query(query): Query<ListUsersQuery>,
-) -> Result<Json<OrganizationUsersResponse>, StatusCode> {
-    // If Entra ID is not enabled, return empty list
-    if !check_entra_id_enabled(&app_state) {
-        tracing::warn!("Entra ID integration is not enabled, returning empty users list");
-        return Ok(Json(OrganizationUsersResponse { users: Vec::new() }));
-    }
-
+) -> Result<Json<OrganizationUsersResponse>, EntraIdApiError> {
+    check_entra_id_enabled(&app_state)?;
     let access_token = get_access_token(&me_user)?;
-    let client = create_graph_client(access_token);
-
-    // If is_involved filter is enabled, first fetch the user's groups using transitive_member_of,
-    // then fetch all users in those groups
-    let involved_user_ids = if query.is_involved {
-        let user_group_ids = fetch_user_groups(&client).await?;
-        if user_group_ids.is_empty() {
-            // User is not in any groups, return empty list
-            tracing::info!("User is not in any groups, returning empty users list");
-            return Ok(Json(OrganizationUsersResponse { users: Vec::new() }));
+    let mut graph = GraphSession::new(&app_state, access_token);
+    let current_user_id = me_user.profile.organization_user_id.as_deref();
+
+    let cursor = query
+        .cursor
+        .as_deref()
+        .map(|cursor| OrganizationCursor::decode(cursor, graph.base_url))
+        .transpose()?;
+    let (users, next_cursor) = match cursor {
+        Some(OrganizationCursor::InvolvedUsers { offset, limit, q }) => {
+            list_involved_users(&mut graph, current_user_id, q.as_deref(), offset, limit).await?
         }
-        Some(fetch_users_in_groups(&client, &user_group_ids).await?)
-    } else {
-        None
-    };
-
-    // Use the graph-rs-sdk's built-in pagination support
-    // This automatically handles all @odata.nextLink pages
-    // When search query is provided, only fetch the first page for performance
-    let mut users = Vec::new();
-
-    if let Some(ref search_query) = query.query {
-        // Validate and sanitize query
-        let trimmed = search_query.trim();
-        if trimmed.len() > 100 {
-            tracing::warn!("Query too long: {} chars (max 100)", trimmed.len());
-            return Err(StatusCode::BAD_REQUEST);
+        Some(OrganizationCursor::NextLink { url, search }) => {
+            let (users, cursor) = graph
+                .collect_page(url, search, |user: &GraphUser| {
+                    Some(user.id.as_str()) != current_user_id
+                })
+                .await?;
+            (
+                users.into_iter().map(OrganizationUser::from).collect(),
+                cursor,
+            )
         }
-
-        // When query parameter is provided (even if empty), only fetch first page for performance
-        // This is useful for search field implementations where typing starts with an empty query
-        let users_response = if trimmed.is_empty() {
-            // Empty query - fetch first page without search
-            tracing::info!("Fetching first page of users (empty query)");
-            client
-                .users()
-                .list_user()
-                .select(&["id", "displayName", "jobTitle", "mail"])
-                .top("999")
-                .send()
-                .await
-                .map_err(|e| {
-                    tracing::error!("Failed to fetch users from Graph API: {:?}", e);
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?
-                .json::<UsersResponse>()
-                .await
-                .map_err(|e| {
-                    tracing::error!("Failed to parse users response: {:?}", e);
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?
-        } else {
-            // Non-empty query - use $search and fetch only first page
-            tracing::info!("Searching users with query: {}", trimmed);
-            client
-                .users()
-                .list_user()
-                .select(&["id", "displayName", "jobTitle", "mail"])
-                .header(
-                    HeaderName::from_static("consistencylevel"),
-                    HeaderValue::from_static("eventual"),
-                ) // Required for $search
-                .search(format!("\"displayName:{}\"", trimmed))
-                // See https://learn.microsoft.com/en-us/graph/api/user-list?view=graph-rest-1.0&tabs=http#optional-query-parameters
-                // > The default and maximum page sizes are 100 and 999 user objects respectively
-                .top("999")
-                .send()
-                .await
-                .map_err(|e| {
-                    tracing::error!("Failed to search users from Graph API: {:?}", e);
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?
-                .json::<UsersResponse>()
-                .await
-                .map_err(|e| {
-                    tracing::error!("Failed to parse users search response: {:?}", e);
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?
-        };
-
-        // Process the results
-        for user_item in &users_response.value {
-            // Filter out the current user (no need to share with ourselves)
-            if let Some(ref current_user_id) = me_user.profile.organization_user_id
-                && &user_item.id == current_user_id
-            {
-                continue;
-            }
-
-            // If is_involved filter is enabled, only include users that are in the involved_user_ids set
-            if let Some(ref involved_ids) = involved_user_ids
-                && !involved_ids.contains(&user_item.id)
-            {
-                continue;
-            }
-
-            users.push(OrganizationUser {
-                id: user_item.id.clone(),
-                display_name: user_item.display_name.clone().unwrap_or_default(),
-                job_title: user_item.job_title.clone(),
-                mail: user_item.mail.clone(),
-                subject_type_id: "organization_user_id".to_string(),
-            });
-        }
-
-        // Early return with results
-        tracing::info!("Found {} users (first page only)", users.len());
-        return Ok(Json(OrganizationUsersResponse { users }));
-    }
-
-    // No search query (or empty query) - use existing pagination to fetch all pages
-    let response_deque = client
-        .users()
-        .list_user()
-        .select(&["id", "displayName", "jobTitle", "mail"])
-        .paging()
-        .json::<UsersResponse>()
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to fetch users from Graph API: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-    // Collect all users from all pages
-    for response in response_deque {
-        let users_response = response.into_body().map_err(|e| {
-            tracing::error!("Failed to parse users response: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-        for user_item in &users_response.value {
-            // Filter out the current user (no need to share with ourselves)
-            if let Some(ref current_user_id) = me_user.profile.organization_user_id
-                && &user_item.id == current_user_id
-            {
-                continue;
-            }
-
-            // If is_involved filter is enabled, only include users that are in the involved_user_ids set
-            if let Some(ref involved_ids) = involved_user_ids
-                && !involved_ids.contains(&user_item.id)
-            {
-                continue;
+        None => {
+            let q = normalize_search_query(query.q.as_deref())?;
+            let limit = page_size(query.limit);
+            if query.is_involved {
+                list_involved_users(&mut graph, current_user_id, q.as_deref(), 0, limit).await?
+            } else {
+                let mut params = vec![
+                    ("$select", USER_SELECT.to_string()),
+                    ("$top", limit.to_string()),
+                ];
+                if let Some(q) = &q {
+                    params.push((
+                        "$search",
+                        search_expression(q, &["displayName", "mail", "userPrincipalName"]),
+                    ));
+                }
+                let url = graph.url("/users", &params)?;
+                let (users, cursor) = graph
+                    .collect_page(url, q.is_some(), |user: &GraphUser| {
+                        Some(user.id.as_str()) != current_user_id
+                    })
+                    .await?;
+                (
+                    users.into_iter().map(OrganizationUser::from).collect(),
+                    cursor,
+                )
             }
-
-            users.push(OrganizationUser {
-                id: user_item.id.clone(),
-                display_name: user_item.display_name.clone().unwrap_or_default(),
-                job_title: user_item.job_title.clone(),
-                mail: user_item.mail.clone(),
-                subject_type_id: "organization_user_id".to_string(),
-            });
         }
-    }
-
-    if query.is_involved {
-        tracing::info!(
-            "Filtered to {} users (sharing groups with requesting user) from organization",
-            users.len()
-        );
-    } else {
-        tracing::info!("Fetched {} users from organization", users.len());
-    }
+    };
 
-    Ok(Json(OrganizationUsersResponse { users }))
+    tracing::info!(
+        "Fetched {} organization users (more available: {})",
+        users.len(),
+        next_cursor.is_some()
+    );
+    Ok(Json(OrganizationUsersResponse {
+        users,
+        next_cursor: encode_cursor(next_cursor)?,
+    }))
 }
 
-/// List all groups in the organization.
+/// List the groups in the organization, one page at a time.
 ///
-/// If the Entra ID integration is not enabled, returns an empty list.
-/// If enabled, fetches groups from the MS Graph API.
+/// Returns 404 if the Entra ID integration is not enabled.
 /// When is_involved=true, only returns groups the user is a member of.
-/// When query parameter is provided (even if empty), returns only the first page for performance.
-/// Non-empty queries use $search for fuzzy matching.
+/// Non-empty queries are forwarded to the MS Graph `$search` parameter.
 #[utoipa::path(
     get,
     path = "/me/organization/groups",
@@ -456,9 +729,12 @@ pub async fn list_organization_users(
         ListGroupsQuery
     ),
     responses(
-        (status = OK, body = OrganizationGroupsResponse, description = "List of organization groups"),
+        (status = OK, body = OrganizationGroupsResponse, description = "A page of organization groups"),
+        (status = BAD_REQUEST, description = "Invalid query or cursor"),
         (status = UNAUTHORIZED, description = "No access token available"),
-        (status = INTERNAL_SERVER_ERROR, description = "Failed to retrieve groups")
+        (status = NOT_FOUND, body = EntraIdErrorResponse, description = "The Entra ID integration is not enabled"),
+        (status = INTERNAL_SERVER_ERROR, description = "Failed to retrieve groups"),
+        (status = SERVICE_UNAVAILABLE, body = EntraIdErrorResponse, description = "MS Graph is throttling requests; retry after the `Retry-After` header")
     ),
     security(
         ("bearer_auth" = [])
@@ -468,199 +744,55 @@ pub async fn list_organization_groups(
     State(app_state): State<AppState>,
     Extension(me_user): Extension<MeProfile>,
     Query(query): Query<ListGroupsQuery>,
-) -> Result<Json<OrganizationGroupsResponse>, StatusCode> {
-    // If Entra ID is not enabled, return empty list
-    if !check_entra_id_enabled(&app_state) {
-        tracing::warn!("Entra ID integration is not enabled, returning empty groups list");
-        return Ok(Json(OrganizationGroupsResponse { groups: Vec::new() }));
-    }
-
+) -> Result<Json<OrganizationGroupsResponse>, EntraIdApiError> {
+    check_entra_id_enabled(&app_state)?;
     let access_token = get_access_token(&me_user)?;
-    let client = create_graph_client(access_token);
-
-    // Use the graph-rs-sdk's built-in pagination support
-    // This automatically handles all @odata.nextLink pages
-    // When query parameter is provided (even if empty), only fetch the first page for performance
-    let mut groups = Vec::new();
-
-    if let Some(ref q) = query.query {
-        // Validate query length
-        let trimmed = q.trim();
-        if trimmed.len() > 100 {
-            tracing::warn!("Query too long: {} chars (max 100)", trimmed.len());
-            return Err(StatusCode::BAD_REQUEST);
+    let mut graph = GraphSession::new(&app_state, access_token);
+
+    let cursor = query
+        .cursor
+        .as_deref()
+        .map(|cursor| OrganizationCursor::decode(cursor, graph.base_url))
+        .transpose()?;
+    let (url, search) = match cursor {
+        Some(OrganizationCursor::NextLink { url, search }) => (url, search),
+        Some(OrganizationCursor::InvolvedUsers { .. }) => {
+            tracing::warn!("Organization users cursor used for groups");
+            return Err(StatusCode::BAD_REQUEST.into());
         }
-
-        // When query parameter is provided (even if empty), only fetch first page for performance
-        // This is useful for search field implementations where typing starts with an empty query
-        let groups_response = if trimmed.is_empty() {
-            // Empty query - fetch first page without search
-            if query.is_involved {
-                tracing::info!("Fetching first page of user's groups (empty query)");
-                client
-                    .me()
-                    .transitive_member_of()
-                    .as_group()
-                    .select(&["id", "displayName"])
-                    .top("999")
-                    .send()
-                    .await
-                    .map_err(|e| {
-                        tracing::error!("Failed to fetch user's groups from Graph API: {:?}", e);
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    })?
-                    .json::<GroupsResponse>()
-                    .await
-                    .map_err(|e| {
-                        tracing::error!("Failed to parse groups response: {:?}", e);
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    })?
-            } else {
-                tracing::info!("Fetching first page of organization groups (empty query)");
-                client
-                    .groups()
-                    .list_group()
-                    .select(&["id", "displayName"])
-                    .top("999")
-                    .send()
-                    .await
-                    .map_err(|e| {
-                        tracing::error!("Failed to fetch groups from Graph API: {:?}", e);
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    })?
-                    .json::<GroupsResponse>()
-                    .await
-                    .map_err(|e| {
-                        tracing::error!("Failed to parse groups response: {:?}", e);
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    })?
+        None => {
+            let q = normalize_search_query(query.q.as_deref())?;
+            let mut params = vec![
+                ("$select", GROUP_SELECT.to_string()),
+                ("$top", page_size(query.limit).to_string()),
+            ];
+            if let Some(q) = &q {
+                params.push((
+                    "$search",
+                    search_expression(q, &["displayName", "description"]),
+                ));
             }
-        } else {
-            // Non-empty query - use $search and fetch only first page
-            if query.is_involved {
-                tracing::info!("Searching user's groups with query: {}", trimmed);
-                client
-                    .me()
-                    .transitive_member_of()
-                    .as_group()
-                    .select(&["id", "displayName"])
-                    .header(
-                        HeaderName::from_static("consistencylevel"),
-                        HeaderValue::from_static("eventual"),
-                    ) // Required for $search
-                    .search(format!("\"displayName:{}\"", trimmed))
-                    // See https://learn.microsoft.com/en-us/graph/api/group-list?view=graph-rest-1.0&tabs=http#optional-query-parameters
-                    // > The default and maximum page sizes are 100 and 999 group objects respectively
-                    .top("999")
-                    .send()
-                    .await
-                    .map_err(|e| {
-                        tracing::error!("Failed to search user's groups from Graph API: {:?}", e);
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    })?
-                    .json::<GroupsResponse>()
-                    .await
-                    .map_err(|e| {
-                        tracing::error!("Failed to parse groups search response: {:?}", e);
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    })?
+            let path = if query.is_involved {
+                "/me/transitiveMemberOf/microsoft.graph.group"
             } else {
-                tracing::info!("Searching organization groups with query: {}", trimmed);
-                client
-                    .groups()
-                    .list_group()
-                    .select(&["id", "displayName"])
-                    .header(
-                        HeaderName::from_static("consistencylevel"),
-                        HeaderValue::from_static("eventual"),
-                    ) // Required for $search
-                    .search(format!("\"displayName:{}\"", trimmed))
-                    // See https://learn.microsoft.com/en-us/graph/api/group-list?view=graph-rest-1.0&tabs=http#optional-query-parameters
-                    // > The default and maximum page sizes are 100 and 999 group objects respectively
-                    .top("999")
-                    .send()
-                    .await
-                    .map_err(|e| {
-                        tracing::error!("Failed to search groups from Graph API: {:?}", e);
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    })?
-                    .json::<GroupsResponse>()
-                    .await
-                    .map_err(|e| {
-                        tracing::error!("Failed to parse groups search response: {:?}", e);
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    })?
-            }
-        };
-
-        // Process the results
-        for group_item in &groups_response.value {
-            groups.push(OrganizationGroup {
-                id: group_item.id.clone(),
-                display_name: group_item.display_name.clone().unwrap_or_default(),
-                subject_type_id: "organization_group_id".to_string(),
-            });
+                "/groups"
+            };
+            (graph.url(path, &params)?, q.is_some())
         }
-
-        // Early return with results
-        tracing::info!("Found {} groups (first page only)", groups.len());
-        return Ok(Json(OrganizationGroupsResponse { groups }));
-    }
-
-    // No search query - use existing pagination to fetch all pages
-    let response_deque = if query.is_involved {
-        // When is_involved=true, use me().transitive_member_of() to get only groups the user belongs to
-        client
-            .me()
-            .transitive_member_of()
-            .as_group()
-            .select(&["id", "displayName"])
-            .paging()
-            .json::<GroupsResponse>()
-            .await
-            .map_err(|e| {
-                tracing::error!("Failed to fetch user's groups from Graph API: {:?}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?
-    } else {
-        // When is_involved=false, fetch all groups in the organization
-        client
-            .groups()
-            .list_group()
-            .select(&["id", "displayName"])
-            .paging()
-            .json::<GroupsResponse>()
-            .await
-            .map_err(|e| {
-                tracing::error!("Failed to fetch groups from Graph API: {:?}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?
     };
 
-    // Collect all groups from all pages
-    for response in response_deque {
-        let groups_response = response.into_body().map_err(|e| {
-            tracing::error!("Failed to parse groups response: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-        for group_item in &groups_response.value {
-            groups.push(OrganizationGroup {
-                id: group_item.id.clone(),
-                display_name: group_item.display_name.clone().unwrap_or_default(),
-                subject_type_id: "organization_group_id".to_string(),
-            });
-        }
-    }
-
-    if query.is_involved {
-        tracing::info!(
-            "Fetched {} groups (user is member of) from organization",
-            groups.len()
-        );
-    } else {
-        tracing::info!("Fetched {} groups from organization", groups.len());
-    }
-
-    Ok(Json(OrganizationGroupsResponse { groups }))
+    let (groups, next_cursor) = graph
+        .collect_page(url, search, |_: &GraphGroup| true)
+        .await?;
+    let groups: Vec<OrganizationGroup> = groups.into_iter().map(OrganizationGroup::from).collect();
+
+    tracing::info!(
+        "Fetched {} organization groups (more available: {})",
+        groups.len(),
+        next_cursor.is_some()
+    );
+    Ok(Json(OrganizationGroupsResponse {
+        groups,
+        next_cursor: encode_cursor(next_cursor)?,
+    }))
 }
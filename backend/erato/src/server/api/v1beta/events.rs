@@ -0,0 +1,67 @@
+use crate::server::api::v1beta::me_profile_middleware::MeProfile;
+use crate::services::user_events::UserEvent;
+use crate::state::AppState;
+use axum::Extension;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use eyre::Report;
+use futures::Stream;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+
+/// Stream notifications about the authenticated user's chats.
+///
+/// A long-lived stream, separate from the message streams, that tells every open
+/// client of the user when a generation in one of their chats starts, completes or
+/// fails, and when a chat is created or archived. Clients can use it to e.g. resume
+/// a generation that was started on another device.
+///
+/// Delivery is best-effort: events are only sent to currently open streams, and a
+/// client that falls too far behind skips the events it missed.
+#[utoipa::path(
+    get,
+    path = "/me/events",
+    responses(
+        (status = OK, content_type = "text/event-stream", body = UserEvent),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = TOO_MANY_REQUESTS, description = "The user already has the maximum number of open event streams"),
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn user_events_sse(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Report>>>, StatusCode> {
+    let (receiver, connection) = app_state
+        .user_events
+        .subscribe(&me_user.id)
+        .ok_or(StatusCode::TOO_MANY_REQUESTS)?;
+
+    let events = BroadcastStream::new(receiver).filter_map(move |result| {
+        // Keeps the connection counted for as long as the stream is open.
+        let _connection = &connection;
+        match result {
+            Ok(event) => Some(
+                Event::default()
+                    .event(event.event_type())
+                    .json_data(&event)
+                    .map_err(Report::from),
+            ),
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                tracing::debug!(skipped, "User event stream lagged behind");
+                None
+            }
+        }
+    });
+
+    Ok(Sse::new(events).keep_alive(
+        KeepAlive::new()
+            .interval(Duration::from_secs(1))
+            .text("keep-alive-text"),
+    ))
+}
@@ -1,9 +1,16 @@
 use crate::db::entity::prelude::FileUploads;
-use crate::models::file_upload;
-use crate::models::message::{ContentPart, ContentPartText, GenerationInputMessages, InputMessage};
+use crate::models::file_upload::{self, FileStorageStatus, UnavailableFile};
+use crate::models::message::{
+    ContentPart, ContentPartText, GenerationInputMessages, InputMessage, MessageRole,
+};
 use crate::server::api::v1beta::message_streaming::FileContent;
+use crate::services::content_spillover::resolve_blob_pointer;
 use crate::services::file_processing_cached::get_file_cached;
-use crate::services::file_storage::{SharepointContext, is_missing_permissions_error};
+use crate::services::file_processor::{FileProcessingError, file_processing_error};
+use crate::services::file_storage::{
+    SharepointContext, is_missing_permissions_error, is_not_found_error,
+};
+use crate::services::file_synopsis::FileSynopsis;
 use crate::services::prompt_composition::transforms::render_action_facet_template;
 use crate::state::AppState;
 use eyre::Report;
@@ -11,29 +18,73 @@ use sea_orm::EntityTrait;
 use sea_orm::prelude::Uuid;
 
 /// Format an error message for files that cannot be retrieved
+///
+/// The message tells the model what to say to the user about the processing error, if the
+/// reason is known.
 pub(crate) fn format_file_error_message(
     filename: &str,
     file_id: Uuid,
-    is_parsing_error: bool,
+    error: Option<&FileProcessingError>,
 ) -> String {
     let mut content = String::new();
     content.push_str("File:\n");
     content.push_str(&format!("file name: {}\n", filename));
     content.push_str(&format!("file_id: erato_file_id:{}\n", file_id));
 
-    if is_parsing_error {
-        content.push_str(
-            "No file contents available as the file was not parseable. This info should be returned to the user."
-        );
-    } else {
-        content.push_str(
+    match error {
+        Some(FileProcessingError::Unsupported) => content.push_str(
+            "No file contents are available because the format of this file is not supported. \
+             Tell the user that this file type can't be read, and suggest converting it to a \
+             supported format such as PDF, Word or plain text.",
+        ),
+        Some(FileProcessingError::PasswordProtected) => content.push_str(
+            "No file contents are available because the file is password-protected. \
+             Tell the user that the file is encrypted, and ask them to upload a version of it \
+             without password protection.",
+        ),
+        Some(FileProcessingError::Corrupt) => content.push_str(
+            "No file contents are available because the file is damaged or incomplete. \
+             Tell the user that the file appears to be corrupt, and ask them to check the file \
+             and upload it again.",
+        ),
+        Some(FileProcessingError::TooLarge { limit }) => content.push_str(&format!(
+            "No file contents are available because the file exceeds the size limit of {} for \
+             reading files. Tell the user that the file is too large, and ask them to upload a \
+             smaller file or only the relevant part of it.",
+            format_size_limit(*limit)
+        )),
+        Some(FileProcessingError::Timeout) => content.push_str(
+            "No file contents are available because reading the file took too long. \
+             Tell the user that processing the file timed out, and that it may work if they try \
+             again.",
+        ),
+        Some(FileProcessingError::StorageUnavailable) => content.push_str(
+            "No file contents are available because the file could not be loaded from the \
+             storage. Tell the user that the file is temporarily unavailable, and that they can \
+             try again later.",
+        ),
+        Some(FileProcessingError::ParserBug(_)) => content.push_str(
+            "No file contents are available because of an unexpected error while reading the \
+             file. Tell the user that the file could not be read due to an internal error, and \
+             that they should contact support if this issue persists.",
+        ),
+        None => content.push_str(
             "Unable to retrieve file contents due to an unknown error. Please contact support if this issue persists."
-        );
+        ),
     }
 
     content
 }
 
+fn format_size_limit(limit: u64) -> String {
+    const MB: u64 = 1024 * 1024;
+    if limit >= MB {
+        format!("{} MB", limit / MB)
+    } else {
+        format!("{} bytes", limit)
+    }
+}
+
 /// Format an error message for files that are inaccessible due to missing permissions.
 pub(crate) fn format_file_permission_error_message(filename: &str, file_id: Uuid) -> String {
     let mut content = String::new();
@@ -46,12 +97,39 @@ pub(crate) fn format_file_permission_error_message(filename: &str, file_id: Uuid
     content
 }
 
+/// Format an error message for files whose object is missing in the storage.
+pub(crate) fn format_file_missing_message(filename: &str, file_id: Uuid) -> String {
+    let mut content = String::new();
+    content.push_str("File:\n");
+    content.push_str(&format!("file name: {}\n", filename));
+    content.push_str(&format!("file_id: erato_file_id:{}\n", file_id));
+    content.push_str(
+        "The file contents are unavailable because the file no longer exists in the storage. \
+         Tell the user that this file is unavailable and that they need to upload it again, \
+         instead of answering as if you had seen its contents.",
+    );
+    content
+}
+
 /// Format successful file content with metadata header
-pub(crate) fn format_successful_file_content(filename: &str, file_id: Uuid, text: &str) -> String {
+///
+/// If a synopsis is given, it is placed in front of the file contents.
+pub(crate) fn format_successful_file_content(
+    filename: &str,
+    file_id: Uuid,
+    text: &str,
+    synopsis: Option<&FileSynopsis>,
+) -> String {
     let mut content = String::new();
     content.push_str("File:\n");
     content.push_str(&format!("file name: {}\n", filename));
     content.push_str(&format!("file_id: erato_file_id:{}\n", file_id));
+    if let Some(synopsis) = synopsis {
+        let paragraph = synopsis.to_paragraph();
+        if !paragraph.is_empty() {
+            content.push_str(&format!("Synopsis: {}\n", paragraph));
+        }
+    }
     content.push_str("File contents\n");
     content.push_str("---\n");
     content.push_str(text);
@@ -62,35 +140,74 @@ pub(crate) fn format_successful_file_content(filename: &str, file_id: Uuid, text
 
 /// Resolve TextFilePointer and ImageFilePointer content parts in generation input messages by extracting file contents JIT.
 /// This prevents storing duplicate file contents in the database.
+///
+/// BlobPointer content parts are resolved to the content parts that were moved to the file
+/// storage as well.
+///
+/// Also returns the files whose object is missing in the storage, which are replaced by a
+/// placeholder and marked as missing.
 pub(crate) async fn resolve_file_pointers_in_generation_input(
     app_state: &AppState,
     generation_input_messages: GenerationInputMessages,
     access_token: Option<&str>,
-) -> Result<GenerationInputMessages, Report> {
+) -> Result<(GenerationInputMessages, Vec<UnavailableFile>), Report> {
     // Build the context for Sharepoint (will be ignored by other providers)
     let sharepoint_ctx = access_token.map(|token| SharepointContext {
         access_token: token,
     });
 
     let mut resolved_messages = Vec::new();
+    let mut unavailable_files: Vec<UnavailableFile> = Vec::new();
 
     for input_message in generation_input_messages.messages {
         let resolved_contents = match input_message.content {
             ContentPart::TextFilePointer(ref file_pointer) => {
                 let file_upload_id = file_pointer.file_upload_id;
                 vec![
-                    resolve_file_pointer(app_state, file_upload_id, false, sharepoint_ctx.as_ref())
-                        .await,
+                    resolve_file_pointer(
+                        app_state,
+                        file_upload_id,
+                        false,
+                        sharepoint_ctx.as_ref(),
+                        &mut unavailable_files,
+                    )
+                    .await,
                 ]
             }
             ContentPart::ImageFilePointer(ref file_pointer) => {
                 let file_upload_id = file_pointer.file_upload_id;
                 vec![
                     format_image_file_pointer_message(file_upload_id),
-                    resolve_file_pointer(app_state, file_upload_id, true, sharepoint_ctx.as_ref())
-                        .await,
+                    resolve_file_pointer(
+                        app_state,
+                        file_upload_id,
+                        true,
+                        sharepoint_ctx.as_ref(),
+                        &mut unavailable_files,
+                    )
+                    .await,
                 ]
             }
+            ContentPart::BlobPointer(pointer) => {
+                let content =
+                    resolve_blob_pointer(app_state.default_file_storage_provider(), pointer).await;
+                // Tool uses of previous assistant messages are replayed as the call of the
+                // assistant followed by the result of the tool, like in `resolve_sequence`
+                if matches!(content, ContentPart::ToolUse(_))
+                    && input_message.role == MessageRole::Assistant
+                {
+                    resolved_messages.push(InputMessage {
+                        role: MessageRole::Assistant,
+                        content: content.clone(),
+                    });
+                    resolved_messages.push(InputMessage {
+                        role: MessageRole::Tool,
+                        content,
+                    });
+                    continue;
+                }
+                vec![content]
+            }
             // Pass through other content parts unchanged
             other => vec![other],
         };
@@ -103,9 +220,12 @@ pub(crate) async fn resolve_file_pointers_in_generation_input(
         }
     }
 
-    Ok(GenerationInputMessages {
-        messages: resolved_messages,
-    })
+    Ok((
+        GenerationInputMessages {
+            messages: resolved_messages,
+        },
+        unavailable_files,
+    ))
 }
 
 /// Sentinel tag wrapping a rendered action-facet directive in the user
@@ -179,11 +299,15 @@ fn format_image_file_pointer_message(file_upload_id: Uuid) -> ContentPart {
 }
 
 /// Helper function to resolve a file pointer (text or image) to its actual content
+///
+/// Files whose object is missing in the storage are marked as missing and added to
+/// `unavailable_files`, as are files whose contents were deleted by their owner.
 async fn resolve_file_pointer(
     app_state: &AppState,
     file_upload_id: Uuid,
     is_image_pointer: bool,
     sharepoint_ctx: Option<&SharepointContext<'_>>,
+    unavailable_files: &mut Vec<UnavailableFile>,
 ) -> ContentPart {
     let file_upload_result = FileUploads::find_by_id(file_upload_id)
         .one(&app_state.db)
@@ -195,6 +319,22 @@ async fn resolve_file_pointer(
                 .file_storage_providers
                 .get(&file.file_storage_provider_id);
 
+            if FileStorageStatus::of(&file) == FileStorageStatus::Deleted {
+                tracing::info!(
+                    "File {}: {} was deleted by its owner, using missing file placeholder text",
+                    file.filename,
+                    file_upload_id
+                );
+                if !unavailable_files.iter().any(|f| f.id == file_upload_id) {
+                    unavailable_files.push(UnavailableFile {
+                        id: file_upload_id,
+                        filename: file.filename.clone(),
+                    });
+                }
+                let content = format_file_missing_message(&file.filename, file_upload_id);
+                return ContentPart::Text(ContentPartText { text: content });
+            }
+
             if !is_image_pointer
                 && let Some(blocking_reason) =
                     file_upload::get_audio_transcription_blocking_reason(&file)
@@ -204,7 +344,7 @@ async fn resolve_file_pointer(
                     file_upload_id,
                     blocking_reason
                 );
-                let content = format_file_error_message(&file.filename, file_upload_id, false);
+                let content = format_file_error_message(&file.filename, file_upload_id, None);
                 return ContentPart::Text(ContentPartText { text: content });
             }
 
@@ -215,13 +355,17 @@ async fn resolve_file_pointer(
                     "Using completed audio transcription for file pointer: {}",
                     file_upload_id
                 );
-                let content =
-                    format_successful_file_content(&file.filename, file_upload_id, &transcript);
+                let content = format_successful_file_content(
+                    &file.filename,
+                    file_upload_id,
+                    &transcript,
+                    None,
+                );
                 return ContentPart::Text(ContentPartText { text: content });
             }
 
             if let Some(file_storage) = file_storage {
-                match get_file_cached(
+                let file_contents_result = get_file_cached(
                     app_state,
                     &file_upload_id,
                     file_storage,
@@ -229,8 +373,15 @@ async fn resolve_file_pointer(
                     &file.filename,
                     sharepoint_ctx,
                 )
-                .await
+                .await;
+                if file_contents_result.is_ok()
+                    && FileStorageStatus::of(&file) == FileStorageStatus::Missing
                 {
+                    // The object is available again (e.g. restored from a backup)
+                    update_storage_status(app_state, file_upload_id, FileStorageStatus::Ok).await;
+                }
+
+                match file_contents_result {
                     Ok(file_contents) => match (&file_contents.content, is_image_pointer) {
                         (FileContent::Text(text), false) => {
                             tracing::debug!(
@@ -244,6 +395,7 @@ async fn resolve_file_pointer(
                                 &file.filename,
                                 file_upload_id,
                                 text,
+                                file_contents.synopsis.as_ref(),
                             );
                             ContentPart::Text(ContentPartText { text: content })
                         }
@@ -268,7 +420,7 @@ async fn resolve_file_pointer(
                                 file_upload_id
                             );
                             let content =
-                                format_file_error_message(&file.filename, file_upload_id, false);
+                                format_file_error_message(&file.filename, file_upload_id, None);
                             ContentPart::Text(ContentPartText { text: content })
                         }
                         (FileContent::Image { .. }, false) => {
@@ -277,11 +429,35 @@ async fn resolve_file_pointer(
                                 file_upload_id
                             );
                             let content =
-                                format_file_error_message(&file.filename, file_upload_id, false);
+                                format_file_error_message(&file.filename, file_upload_id, None);
                             ContentPart::Text(ContentPartText { text: content })
                         }
                     },
                     Err(err) => {
+                        if is_not_found_error(&err) {
+                            tracing::warn!(
+                                "File {}: {} is missing in the storage: {}, using missing file placeholder text",
+                                file.filename,
+                                file_upload_id,
+                                err
+                            );
+                            update_storage_status(
+                                app_state,
+                                file_upload_id,
+                                FileStorageStatus::Missing,
+                            )
+                            .await;
+                            if !unavailable_files.iter().any(|f| f.id == file_upload_id) {
+                                unavailable_files.push(UnavailableFile {
+                                    id: file_upload_id,
+                                    filename: file.filename.clone(),
+                                });
+                            }
+                            let content =
+                                format_file_missing_message(&file.filename, file_upload_id);
+                            return ContentPart::Text(ContentPartText { text: content });
+                        }
+
                         if is_missing_permissions_error(&err) {
                             tracing::warn!(
                                 "Failed to get file contents for {}: {} - missing permissions: {}, using permission placeholder text",
@@ -296,8 +472,7 @@ async fn resolve_file_pointer(
                             return ContentPart::Text(ContentPartText { text: content });
                         }
 
-                        let is_parsing_error =
-                            err.to_string().contains("parse") || err.to_string().contains("Parse");
+                        let processing_error = file_processing_error(&err);
 
                         tracing::warn!(
                             "Failed to get file contents for {}: {} - Error: {}, using placeholder text",
@@ -308,7 +483,7 @@ async fn resolve_file_pointer(
                         let content = format_file_error_message(
                             &file.filename,
                             file_upload_id,
-                            is_parsing_error,
+                            processing_error.as_ref(),
                         );
                         ContentPart::Text(ContentPartText { text: content })
                     }
@@ -319,7 +494,7 @@ async fn resolve_file_pointer(
                     file.file_storage_provider_id,
                     file_upload_id
                 );
-                let content = format_file_error_message(&file.filename, file_upload_id, false);
+                let content = format_file_error_message(&file.filename, file_upload_id, None);
                 ContentPart::Text(ContentPartText { text: content })
             }
         }
@@ -328,7 +503,7 @@ async fn resolve_file_pointer(
                 "File upload {} referenced in file pointer not found, using placeholder text",
                 file_upload_id
             );
-            let content = format_file_error_message("Unknown", file_upload_id, false);
+            let content = format_file_error_message("Unknown", file_upload_id, None);
             ContentPart::Text(ContentPartText { text: content })
         }
         Err(err) => {
@@ -337,8 +512,30 @@ async fn resolve_file_pointer(
                 file_upload_id,
                 err
             );
-            let content = format_file_error_message("Unknown", file_upload_id, false);
+            let content = format_file_error_message("Unknown", file_upload_id, None);
             ContentPart::Text(ContentPartText { text: content })
         }
     }
 }
+
+/// Record the storage status of a file. Failing to do so doesn't fail the generation.
+async fn update_storage_status(
+    app_state: &AppState,
+    file_upload_id: Uuid,
+    status: FileStorageStatus,
+) {
+    match file_upload::set_file_upload_storage_status(&app_state.db, &file_upload_id, status).await
+    {
+        Ok(true) => tracing::info!(
+            file_id = %file_upload_id,
+            storage_status = status.as_str(),
+            "Updated storage status of file upload"
+        ),
+        Ok(false) => {}
+        Err(err) => tracing::warn!(
+            file_id = %file_upload_id,
+            error = %err,
+            "Failed to update storage status of file upload"
+        ),
+    }
+}
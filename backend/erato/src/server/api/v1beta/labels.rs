@@ -0,0 +1,326 @@
+use crate::db::entity::labels;
+use crate::models::label::{self, UpdateLabelInput};
+use crate::policy::engine::PolicyEngine;
+use crate::server::api::v1beta::deserialize_patch_optional_string;
+use crate::server::api::v1beta::me_profile_middleware::MeProfile;
+use crate::services::sentry::log_internal_server_error;
+use crate::state::AppState;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::{DateTime, FixedOffset};
+use eyre::Report;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use utoipa::ToSchema;
+
+/// A label for organizing chats
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Label {
+    /// The unique ID of the label
+    pub id: String,
+    /// The display name of the label
+    pub name: String,
+    /// The color of the label as a `#rrggbb` hex string
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub color: Option<String>,
+    /// When this label was created
+    pub created_at: DateTime<FixedOffset>,
+    /// When this label was last updated
+    pub updated_at: DateTime<FixedOffset>,
+}
+
+impl From<labels::Model> for Label {
+    fn from(label: labels::Model) -> Self {
+        Self {
+            id: label.id.to_string(),
+            name: label.name,
+            color: label.color,
+            created_at: label.created_at,
+            updated_at: label.updated_at,
+        }
+    }
+}
+
+/// Request to create a new label
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateLabelRequest {
+    /// The display name of the label. Must be unique among the user's labels.
+    pub name: String,
+    /// Optional color of the label as a `#rrggbb` hex string
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+/// Request to update a label. Omitted fields are left unchanged.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateLabelRequest {
+    /// New display name of the label
+    #[serde(default)]
+    pub name: Option<String>,
+    /// New color of the label as a `#rrggbb` hex string; `null` clears the color
+    #[serde(default, deserialize_with = "deserialize_patch_optional_string")]
+    pub color: Option<Option<String>>,
+}
+
+/// Response when listing labels
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListLabelsResponse {
+    /// The labels of the user, ordered by name
+    pub labels: Vec<Label>,
+}
+
+fn owner_user_id(me_user: &MeProfile) -> Result<Uuid, StatusCode> {
+    Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn map_label_error(e: Report) -> StatusCode {
+    let s = e.to_string();
+    if s.contains("not found") {
+        StatusCode::NOT_FOUND
+    } else if s.contains("not authorized") {
+        StatusCode::FORBIDDEN
+    } else if s.contains("Invalid") {
+        StatusCode::BAD_REQUEST
+    } else if s.contains("already exists") || s.contains("limit reached") {
+        StatusCode::CONFLICT
+    } else {
+        log_internal_server_error(e)
+    }
+}
+
+/// List the labels of the authenticated user
+#[utoipa::path(
+    get,
+    path = "/me/labels",
+    tag = "labels",
+    responses(
+        (status = OK, body = ListLabelsResponse, description = "Successfully retrieved labels"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_labels(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<Json<ListLabelsResponse>, StatusCode> {
+    let labels = label::list_labels(&app_state.db, &owner_user_id(&me_user)?)
+        .await
+        .map_err(log_internal_server_error)?;
+
+    Ok(Json(ListLabelsResponse {
+        labels: labels.into_iter().map(Label::from).collect(),
+    }))
+}
+
+/// Create a label for the authenticated user
+#[utoipa::path(
+    post,
+    path = "/me/labels",
+    tag = "labels",
+    request_body = CreateLabelRequest,
+    responses(
+        (status = CREATED, body = Label, description = "Successfully created the label"),
+        (status = BAD_REQUEST, description = "Invalid label name or color"),
+        (status = CONFLICT, description = "A label with this name already exists, or the label limit is reached"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_label(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Json(request): Json<CreateLabelRequest>,
+) -> Result<(StatusCode, Json<Label>), StatusCode> {
+    let created_label = label::create_label(
+        &app_state.db,
+        &owner_user_id(&me_user)?,
+        request.name,
+        request.color,
+    )
+    .await
+    .map_err(map_label_error)?;
+
+    Ok((StatusCode::CREATED, Json(created_label.into())))
+}
+
+/// Update a label of the authenticated user
+#[utoipa::path(
+    put,
+    path = "/me/labels/{label_id}",
+    tag = "labels",
+    params(
+        ("label_id" = String, Path, description = "The ID of the label to update")
+    ),
+    request_body = UpdateLabelRequest,
+    responses(
+        (status = OK, body = Label, description = "Successfully updated the label"),
+        (status = BAD_REQUEST, description = "Invalid label ID, name or color"),
+        (status = NOT_FOUND, description = "Label not found"),
+        (status = CONFLICT, description = "A label with this name already exists"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_label(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(label_id): Path<String>,
+    Json(request): Json<UpdateLabelRequest>,
+) -> Result<Json<Label>, StatusCode> {
+    let label_id = Uuid::parse_str(&label_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let updated_label = label::update_label(
+        &app_state.db,
+        &owner_user_id(&me_user)?,
+        &label_id,
+        UpdateLabelInput {
+            name: request.name,
+            color: request.color,
+        },
+    )
+    .await
+    .map_err(map_label_error)?;
+
+    Ok(Json(updated_label.into()))
+}
+
+/// Delete a label of the authenticated user
+///
+/// The label is removed from all chats it was assigned to.
+#[utoipa::path(
+    delete,
+    path = "/me/labels/{label_id}",
+    tag = "labels",
+    params(
+        ("label_id" = String, Path, description = "The ID of the label to delete")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "Successfully deleted the label"),
+        (status = BAD_REQUEST, description = "Invalid label ID format"),
+        (status = NOT_FOUND, description = "Label not found"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_label(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(label_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let label_id = Uuid::parse_str(&label_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    label::delete_label(&app_state.db, &owner_user_id(&me_user)?, &label_id)
+        .await
+        .map_err(map_label_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Assign a label to a chat
+#[utoipa::path(
+    post,
+    path = "/chats/{chat_id}/labels/{label_id}",
+    tag = "labels",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat"),
+        ("label_id" = String, Path, description = "The ID of the label to assign")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "The label is assigned to the chat"),
+        (status = BAD_REQUEST, description = "Invalid chat or label ID format"),
+        (status = FORBIDDEN, description = "User is not allowed to modify the chat"),
+        (status = NOT_FOUND, description = "Chat or label not found"),
+        (status = CONFLICT, description = "The chat already has the maximum number of labels"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn add_chat_label(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path((chat_id, label_id)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let label_id = Uuid::parse_str(&label_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+
+    label::add_label_to_chat(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &chat_id,
+        &label_id,
+    )
+    .await
+    .map_err(map_label_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove a label from a chat
+#[utoipa::path(
+    delete,
+    path = "/chats/{chat_id}/labels/{label_id}",
+    tag = "labels",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat"),
+        ("label_id" = String, Path, description = "The ID of the label to remove")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "The label is no longer assigned to the chat"),
+        (status = BAD_REQUEST, description = "Invalid chat or label ID format"),
+        (status = FORBIDDEN, description = "User is not allowed to modify the chat"),
+        (status = NOT_FOUND, description = "Chat or label not found"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn remove_chat_label(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path((chat_id, label_id)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let label_id = Uuid::parse_str(&label_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+
+    label::remove_label_from_chat(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &chat_id,
+        &label_id,
+    )
+    .await
+    .map_err(map_label_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -28,6 +28,10 @@ pub struct McpServerStatus {
     pub id: String,
     pub authentication_mode: String,
     pub connection_status: McpServerStatusValue,
+    /// Number of sessions the backend currently keeps open to the server, across all chats
+    pub active_sessions: u64,
+    /// Age of the oldest of these sessions in seconds, if there are any
+    pub oldest_session_age_seconds: Option<u64>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -75,6 +79,7 @@ pub async fn list_mcp_servers(
     let user_id = parse_user_id(&me_user)?;
     let auth_context = auth_context(&app_state, &me_user, user_id);
     let server_ids = authorized_server_ids(&app_state, &me_user, &policy).await?;
+    let session_stats = app_state.mcp_servers.session_stats().await;
 
     let mut servers = Vec::with_capacity(server_ids.len());
     for server_id in server_ids {
@@ -85,10 +90,13 @@ pub async fn list_mcp_servers(
             .mcp_servers
             .probe_connection(&server_id, &auth_context)
             .await;
+        let stats = session_stats.get(&server_id).cloned().unwrap_or_default();
         servers.push(McpServerStatus {
             id: server_id,
             authentication_mode: authentication_mode_name(&config.authentication).to_string(),
             connection_status: map_status(connection_status),
+            active_sessions: stats.active_sessions as u64,
+            oldest_session_age_seconds: stats.oldest_session_age.map(|age| age.as_secs()),
         });
     }
 
@@ -263,7 +271,7 @@ fn auth_context<'a>(
     McpRequestAuthContext {
         app_state: Some(app_state),
         user_id: Some(user_id),
-        oidc_token: Some(&me_user.oidc_token),
+        oidc_token: me_user.oidc_token.as_deref(),
         access_token: me_user.access_token.as_deref(),
     }
 }
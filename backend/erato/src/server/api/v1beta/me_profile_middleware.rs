@@ -1,18 +1,24 @@
-use crate::config::{I18nLanguageConfig, LanguageDetectionPriority};
-use crate::models::user::get_or_create_user;
+use crate::config::{
+    AuthMode, I18nLanguageConfig, LanguageDetectionPriority, TrustedHeadersConfig,
+};
+use crate::models::user::{assign_user_organization, get_or_create_user};
 use crate::models::user_preference::get_user_preferences;
 use crate::normalize_profile::{IdTokenProfile, normalize_profile};
 use crate::policy::prelude::Subject;
 use crate::state::AppState;
-use axum::extract::{Request, State};
+use axum::extract::{ConnectInfo, Request, State};
 use axum::http;
+use axum::http::HeaderMap;
 use axum::http::StatusCode;
 use axum::middleware::Next;
 use axum::response::Response;
 use headers::authorization::{Bearer, Credentials};
+use ipnet::IpNet;
 use jsonwebtoken::dangerous::insecure_decode;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
 use tracing::instrument;
 use utoipa::ToSchema;
 
@@ -59,6 +65,13 @@ pub struct UserProfile {
     /// These can be used as subject_id when creating share grants
     /// with subject_id_type "organization_group_id".
     pub organization_group_ids: Vec<String>,
+    /// ID of the organization (tenant) of the user, from the claim configured in
+    /// `organization_claim`.
+    ///
+    /// The user can only access data of this organization.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub organization_id: Option<String>,
     /// Preferred name to address the user with.
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
@@ -75,6 +88,26 @@ pub struct UserProfile {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
     pub preference_assistant_additional_information: Option<String>,
+    /// Facet IDs that should be enabled by default for new chats.
+    ///
+    /// Overrides the configured `default_selected_facets` when set. An empty list selects no
+    /// facets by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub preference_default_selected_facets: Option<Vec<String>>,
+    /// Number of notifications of the user that were not read yet.
+    ///
+    /// Only included in the response of `/me/profile`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub unread_notifications: Option<u64>,
+    /// The feature flags evaluated for the user, by name, taking group overrides and percentage
+    /// rollouts into account.
+    ///
+    /// Only included in the response of `/me/profile`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub feature_flags: Option<BTreeMap<String, bool>>,
 }
 
 impl UserProfile {
@@ -89,10 +122,14 @@ impl UserProfile {
             groups: profile.groups,
             organization_user_id: profile.organization_user_id,
             organization_group_ids: profile.organization_group_ids,
+            organization_id: profile.organization_id,
             preference_nickname: None,
             preference_job_title: None,
             preference_assistant_custom_instructions: None,
             preference_assistant_additional_information: None,
+            preference_default_selected_facets: None,
+            unread_notifications: None,
+            feature_flags: None,
         }
     }
 
@@ -146,6 +183,7 @@ impl UserProfile {
             self.preference_assistant_custom_instructions = prefs.assistant_custom_instructions;
             self.preference_assistant_additional_information =
                 prefs.assistant_additional_information;
+            self.preference_default_selected_facets = prefs.default_selected_facets;
         }
     }
 }
@@ -159,7 +197,8 @@ pub struct MeProfile {
     /// The user profile extracted from the JWT token.
     pub profile: UserProfile,
     /// The raw OIDC token received via the Authorization header.
-    pub oidc_token: String,
+    /// This is `None` in the `trusted_headers` auth mode, where the proxy doesn't forward it.
+    pub oidc_token: Option<String>,
     /// The raw ID token claims for consumer-specific rendering.
     pub id_token_claims: Value,
     /// The raw access token for external APIs like MS Graph.
@@ -180,12 +219,14 @@ impl std::ops::Deref for MeProfile {
 impl MeProfile {
     pub fn to_subject(&self) -> Subject {
         // Use UserWithGroups if we have organization-specific information
-        // (either organization_user_id or organization_group_ids)
-        if self.profile.organization_user_id.is_some()
+        // (either organization_id, organization_user_id or organization_group_ids)
+        if self.profile.organization_id.is_some()
+            || self.profile.organization_user_id.is_some()
             || !self.profile.organization_group_ids.is_empty()
         {
             Subject::UserWithOrganizationInfo {
                 id: self.profile.id.clone(),
+                organization_id: self.profile.organization_id.clone(),
                 organization_user_id: self.profile.organization_user_id.clone(),
                 organization_group_ids: self.profile.organization_group_ids.clone(),
             }
@@ -208,8 +249,26 @@ pub async fn user_profile_from_token(
         Err(_) => return Err(StatusCode::UNAUTHORIZED),
     };
     let id_token_claims = token_data.claims;
+    let user_profile =
+        user_profile_from_id_token_claims(app_state, &id_token_claims, accept_language_header)
+            .await?;
 
-    let normalized_profile = normalize_profile(id_token_claims.clone());
+    Ok((user_profile, id_token_claims))
+}
+
+/// Build the profile of the user identified by the given ID token claims.
+///
+/// Used for requests with a token, as well as for work done on behalf of a user without a
+/// request, e.g. submitting a scheduled message.
+pub async fn user_profile_from_id_token_claims(
+    app_state: &AppState,
+    id_token_claims: &Value,
+    accept_language_header: Option<&str>,
+) -> Result<UserProfile, StatusCode> {
+    let normalized_profile = normalize_profile(
+        id_token_claims.clone(),
+        app_state.config.organization_claim.as_deref(),
+    );
     let normalized_profile = normalized_profile.map_err(|_e| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let user = get_or_create_user(
@@ -220,6 +279,16 @@ pub async fn user_profile_from_token(
     )
     .await
     .map_err(|_e| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if user.organization_id != normalized_profile.organization_id {
+        let organization_id = normalized_profile.organization_id.as_deref();
+        let backfilled = assign_user_organization(&app_state.db, &user, organization_id)
+            .await
+            .map_err(|_e| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if backfilled > 0 {
+            // The policy data has to include the records in their new organization
+            app_state.global_policy_engine.invalidate_data().await;
+        }
+    }
 
     let user_id = user.id.to_string();
     let id_token_xms_pl = normalized_profile.id_token_xms_pl.clone();
@@ -236,7 +305,7 @@ pub async fn user_profile_from_token(
         .map_err(|_e| StatusCode::INTERNAL_SERVER_ERROR)?;
     user_profile.apply_user_preferences(prefs);
 
-    Ok((user_profile, id_token_claims))
+    Ok(user_profile)
 }
 
 /// Middleware that extracts and validates user profile from JWT token
@@ -247,48 +316,153 @@ pub async fn user_profile_from_token(
 ///
 /// If the `X-Forwarded-Access-Token` header is present (typically set by oauth2-proxy),
 /// it will be stored in the `MeProfile` for use with external APIs like MS Graph.
+///
+/// In the `trusted_headers` auth mode, the profile is built from the identity headers of a
+/// trusted proxy instead, see [`me_profile_from_trusted_headers`].
 pub(crate) async fn user_profile_middleware(
     State(app_state): State<AppState>,
     mut req: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    let auth_header = req
-        .headers()
+    let me_profile = match app_state.config.auth.mode {
+        AuthMode::Jwt => me_profile_from_jwt(&app_state, req.headers()).await?,
+        AuthMode::TrustedHeaders => {
+            let peer_ip = req
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip());
+            me_profile_from_trusted_headers(&app_state, req.headers(), peer_ip).await?
+        }
+    };
+    req.extensions_mut().insert(me_profile);
+    Ok(next.run(req).await)
+}
+
+async fn me_profile_from_jwt(
+    app_state: &AppState,
+    headers: &HeaderMap,
+) -> Result<MeProfile, StatusCode> {
+    let auth_header = headers
         .get(http::header::AUTHORIZATION)
         .and_then(Bearer::decode)
         .ok_or(StatusCode::UNAUTHORIZED)?;
 
     // Extract the forwarded access token if present (for external API access like MS Graph)
-    let forwarded_access_token = req
-        .headers()
+    let forwarded_access_token = headers
         .get(X_FORWARDED_ACCESS_TOKEN)
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
-    let accept_language_header = req
-        .headers()
-        .get(http::header::ACCEPT_LANGUAGE)
-        .and_then(|value| value.to_str().ok())
-        .map(str::to_owned);
 
     if let Ok((current_user, id_token_claims)) = user_profile_from_token(
-        &app_state,
+        app_state,
         auth_header.token(),
-        accept_language_header.as_deref(),
+        accept_language_header(headers),
     )
     .await
     {
-        req.extensions_mut().insert(MeProfile {
+        Ok(MeProfile {
             profile: current_user,
-            oidc_token: auth_header.token().to_string(),
+            oidc_token: Some(auth_header.token().to_string()),
             id_token_claims,
             access_token: forwarded_access_token,
-        });
-        Ok(next.run(req).await)
+        })
     } else {
         Err(StatusCode::UNAUTHORIZED)
     }
 }
 
+/// Build the profile of a request in the `trusted_headers` auth mode.
+///
+/// The identity headers are only accepted from the addresses in
+/// `auth.trusted_headers.trusted_proxies`, and are turned into ID token claims, so that they are
+/// normalized like the claims of the `jwt` mode.
+async fn me_profile_from_trusted_headers(
+    app_state: &AppState,
+    headers: &HeaderMap,
+    peer_ip: Option<IpAddr>,
+) -> Result<MeProfile, StatusCode> {
+    let config = &app_state.config.auth.trusted_headers;
+    if !is_trusted_proxy(&app_state.trusted_proxy_networks, peer_ip) {
+        tracing::warn!(
+            ?peer_ip,
+            "Rejected the identity headers of a request that doesn't come from a trusted proxy"
+        );
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let id_token_claims = id_token_claims_from_trusted_headers(config, headers)
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let profile = user_profile_from_id_token_claims(
+        app_state,
+        &id_token_claims,
+        accept_language_header(headers),
+    )
+    .await?;
+    Ok(MeProfile {
+        profile,
+        // The proxy doesn't forward any token of the user.
+        oidc_token: None,
+        id_token_claims,
+        access_token: None,
+    })
+}
+
+fn is_trusted_proxy(trusted_proxies: &[IpNet], peer_ip: Option<IpAddr>) -> bool {
+    let Some(peer_ip) = peer_ip else {
+        return false;
+    };
+    // Dual-stack listeners report IPv4 peers as IPv4-mapped IPv6 addresses
+    let peer_ip = peer_ip.to_canonical();
+    trusted_proxies
+        .iter()
+        .any(|network| network.contains(&peer_ip))
+}
+
+/// Build ID token claims from the identity headers of the `trusted_headers` auth mode.
+///
+/// Returns `None` if the header with the user ID is missing or empty.
+fn id_token_claims_from_trusted_headers(
+    config: &TrustedHeadersConfig,
+    headers: &HeaderMap,
+) -> Option<Value> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+
+    let mut claims = json!({
+        "iss": config.issuer,
+        "sub": header(&config.user_id_header)?,
+    });
+    if let Some(email) = header(&config.email_header) {
+        claims["email"] = json!(email);
+    }
+    if let Some(name) = header(&config.name_header) {
+        claims["name"] = json!(name);
+    }
+    if let Some(language) = header(&config.preferred_language_header) {
+        claims["xms_pl"] = json!(language);
+    }
+    if let Some(groups) = header(&config.groups_header) {
+        let groups: Vec<&str> = groups
+            .split(config.groups_separator.as_str())
+            .map(str::trim)
+            .filter(|group| !group.is_empty())
+            .collect();
+        claims["groups"] = json!(groups);
+    }
+    Some(claims)
+}
+
+fn accept_language_header(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(http::header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+}
+
 const SUPPORTED_LANGUAGES: [&str; 5] = ["en", "de", "fr", "pl", "es"];
 
 fn normalize_supported_language(raw_language: Option<&str>) -> Option<&str> {
@@ -321,9 +495,16 @@ fn parse_language_candidates(raw_language: &str) -> Vec<String> {
 #[cfg(test)]
 mod tests {
     use super::UserProfile;
-    use super::{normalize_supported_language, parse_language_candidates};
-    use crate::config::{I18nLanguageConfig, LanguageDetectionPriority};
-    use crate::normalize_profile::IdTokenProfile;
+    use super::{
+        id_token_claims_from_trusted_headers, is_trusted_proxy, normalize_supported_language,
+        parse_language_candidates,
+    };
+    use crate::config::{
+        AuthConfig, AuthMode, I18nLanguageConfig, LanguageDetectionPriority, TrustedHeadersConfig,
+    };
+    use crate::normalize_profile::{IdTokenProfile, normalize_profile};
+    use axum::http::HeaderMap;
+    use serde_json::json;
 
     #[test]
     fn parses_accept_language_candidates_in_priority_order() {
@@ -366,6 +547,7 @@ mod tests {
                 groups: Vec::new(),
                 organization_user_id: None,
                 organization_group_ids: Vec::new(),
+                organization_id: None,
             },
             "user-id".to_string(),
         );
@@ -399,6 +581,7 @@ mod tests {
                 groups: Vec::new(),
                 organization_user_id: None,
                 organization_group_ids: Vec::new(),
+                organization_id: None,
             },
             "user-id".to_string(),
         );
@@ -435,6 +618,7 @@ mod tests {
                 groups: Vec::new(),
                 organization_user_id: None,
                 organization_group_ids: Vec::new(),
+                organization_id: None,
             },
             "user-id".to_string(),
         );
@@ -452,4 +636,122 @@ mod tests {
         );
         assert_eq!(user_profile.preferred_language, "es");
     }
+
+    #[test]
+    fn builds_id_token_claims_from_trusted_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Forwarded-User", "user-1".parse().unwrap());
+        headers.insert("X-Forwarded-Email", "user@example.com".parse().unwrap());
+        headers.insert("X-Forwarded-Preferred-Username", "Jane Doe".parse().unwrap());
+        headers.insert("X-Forwarded-Groups", " admins, ,users ".parse().unwrap());
+        headers.insert("X-Forwarded-Preferred-Language", "de-DE".parse().unwrap());
+
+        let claims =
+            id_token_claims_from_trusted_headers(&TrustedHeadersConfig::default(), &headers)
+                .unwrap();
+        assert_eq!(
+            claims,
+            json!({
+                "iss": "trusted-headers",
+                "sub": "user-1",
+                "email": "user@example.com",
+                "name": "Jane Doe",
+                "xms_pl": "de-DE",
+                "groups": ["admins", "users"],
+            })
+        );
+
+        let profile = normalize_profile(claims, None).unwrap();
+        assert_eq!(profile.groups, vec!["admins", "users"]);
+        assert_eq!(profile.organization_group_ids, vec!["admins", "users"]);
+        assert_eq!(profile.preferred_language.as_deref(), Some("de-DE"));
+    }
+
+    #[test]
+    fn reads_trusted_headers_with_configured_names_and_separator() {
+        let config = TrustedHeadersConfig {
+            user_id_header: "X-Auth-Subject".to_string(),
+            groups_header: "X-Auth-Groups".to_string(),
+            groups_separator: ";".to_string(),
+            issuer: "https://idp.example.com".to_string(),
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Auth-Subject", "user-1".parse().unwrap());
+        headers.insert("X-Auth-Groups", "a,b;c".parse().unwrap());
+        headers.insert("X-Forwarded-User", "someone-else".parse().unwrap());
+
+        let claims = id_token_claims_from_trusted_headers(&config, &headers).unwrap();
+        assert_eq!(
+            claims,
+            json!({
+                "iss": "https://idp.example.com",
+                "sub": "user-1",
+                "groups": ["a,b", "c"],
+            })
+        );
+    }
+
+    #[test]
+    fn requires_the_user_id_header() {
+        let config = TrustedHeadersConfig::default();
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Forwarded-Email", "user@example.com".parse().unwrap());
+        assert!(id_token_claims_from_trusted_headers(&config, &headers).is_none());
+
+        headers.insert("X-Forwarded-User", "  ".parse().unwrap());
+        assert!(id_token_claims_from_trusted_headers(&config, &headers).is_none());
+    }
+
+    #[test]
+    fn only_trusts_peers_in_the_trusted_proxy_networks() {
+        let config = TrustedHeadersConfig {
+            trusted_proxies: vec![
+                "10.0.0.0/8".to_string(),
+                "192.168.1.5".to_string(),
+                "fd00::/8".to_string(),
+            ],
+            ..Default::default()
+        };
+        let networks = config.trusted_proxy_networks().unwrap();
+
+        let is_trusted =
+            |peer_ip: &str| is_trusted_proxy(&networks, Some(peer_ip.parse().unwrap()));
+        assert!(is_trusted("10.1.2.3"));
+        assert!(is_trusted("::ffff:10.1.2.3"));
+        assert!(is_trusted("192.168.1.5"));
+        assert!(is_trusted("fd12::1"));
+        assert!(!is_trusted("192.168.1.6"));
+        assert!(!is_trusted("11.0.0.1"));
+        assert!(!is_trusted_proxy(&networks, None));
+    }
+
+    #[test]
+    fn rejects_invalid_trusted_proxies() {
+        for proxy in ["10.0.0.0/33", "proxy.example.com"] {
+            let config = TrustedHeadersConfig {
+                trusted_proxies: vec![proxy.to_string()],
+                ..Default::default()
+            };
+            assert!(config.trusted_proxy_networks().is_err());
+        }
+    }
+
+    #[test]
+    fn requires_trusted_proxies_in_the_trusted_headers_mode() {
+        let mut config = AuthConfig {
+            mode: AuthMode::TrustedHeaders,
+            ..Default::default()
+        };
+        assert!(config.trusted_proxy_networks().is_err());
+        assert!(config.validate().is_err());
+
+        config.trusted_headers.trusted_proxies = vec!["10.0.0.0/8".to_string()];
+        assert_eq!(config.trusted_proxy_networks().unwrap().len(), 1);
+
+        // The jwt mode doesn't read identity headers, so the networks aren't parsed
+        config.mode = AuthMode::Jwt;
+        config.trusted_headers.trusted_proxies = vec!["proxy.example.com".to_string()];
+        assert!(config.trusted_proxy_networks().unwrap().is_empty());
+    }
 }
@@ -0,0 +1,145 @@
+//! Search within the messages of a chat.
+
+use crate::models::message_search::{
+    MIN_SEARCH_QUERY_CHARS, MessageSearchOptions, SearchMatchOffset, search_chat_messages,
+};
+use crate::policy::engine::PolicyEngine;
+use crate::server::api::v1beta::me_profile_middleware::MeProfile;
+use crate::services::sentry::log_internal_server_error;
+use crate::state::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::{DateTime, FixedOffset};
+use sea_orm::prelude::Uuid;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Default number of matches returned per page.
+const DEFAULT_SEARCH_LIMIT: u64 = 20;
+/// Maximum number of matches returned per page.
+const MAX_SEARCH_LIMIT: u64 = 100;
+
+/// Query parameters for searching the messages of a chat
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ChatMessageSearchQuery {
+    /// Text to search for, case-insensitively. Must be at least 2 characters long.
+    pub q: String,
+    /// Whether to also search messages that are not part of the active thread. Defaults to false.
+    #[serde(default)]
+    pub include_inactive: Option<bool>,
+    /// Maximum number of matches to return. Defaults to 20, and is capped at 100.
+    #[serde(default)]
+    pub limit: Option<u64>,
+    /// Number of matches to skip for pagination. Defaults to 0.
+    #[serde(default)]
+    pub offset: Option<u64>,
+}
+
+/// A text content part of a message that matches the search query
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatMessageSearchMatch {
+    /// The ID of the message
+    message_id: String,
+    /// Index of the matching content part in the content of the message
+    content_index: usize,
+    /// Excerpt of the text of the content part around the first match
+    snippet: String,
+    /// Character offsets of the matches within the snippet
+    match_offsets: Vec<SearchMatchOffset>,
+    /// When the message was created
+    created_at: DateTime<FixedOffset>,
+}
+
+/// Response of searching the messages of a chat
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatMessageSearchResponse {
+    /// The matches, ordered by the position of the message in the chat
+    matches: Vec<ChatMessageSearchMatch>,
+    /// Total number of matches
+    total_count: i64,
+    /// Whether there are more matches after this page
+    has_more: bool,
+}
+
+/// Search the text of the messages of a chat.
+///
+/// Returns one match per text content part that contains the query, so that clients can jump to
+/// matches without loading all messages of the chat. Offsets count characters (Unicode code
+/// points), not bytes.
+#[utoipa::path(
+    get,
+    path = "/chats/{chat_id}/messages/search",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat to search"),
+        ChatMessageSearchQuery
+    ),
+    responses(
+        (status = OK, body = ChatMessageSearchResponse, description = "Successfully searched the messages"),
+        (status = BAD_REQUEST, description = "Invalid chat ID, or a query shorter than 2 characters"),
+        (status = NOT_FOUND, description = "When the chat does not exist or is not accessible"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error while searching the messages")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn search_chat_messages_endpoint(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path(chat_id): Path<String>,
+    Query(query): Query<ChatMessageSearchQuery>,
+) -> Result<Json<ChatMessageSearchResponse>, StatusCode> {
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let search_query = query.q.trim();
+    if search_query.chars().count() < MIN_SEARCH_QUERY_CHARS {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+
+    let options = MessageSearchOptions {
+        include_inactive: query.include_inactive.unwrap_or(false),
+        limit: query
+            .limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .min(MAX_SEARCH_LIMIT),
+        offset: query.offset.unwrap_or(0),
+    };
+    let (matches, total_count) = search_chat_messages(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &chat_id,
+        search_query,
+        options,
+    )
+    .await
+    .map_err(|e| {
+        let s = e.to_string();
+        if s.contains("not found") || s.contains("Access denied") || s.contains("not authorized") {
+            StatusCode::NOT_FOUND
+        } else {
+            log_internal_server_error(e)
+        }
+    })?;
+
+    let has_more = options.offset + (matches.len() as u64) < total_count;
+    Ok(Json(ChatMessageSearchResponse {
+        matches: matches
+            .into_iter()
+            .map(|m| ChatMessageSearchMatch {
+                message_id: m.message_id.to_string(),
+                content_index: m.content_index,
+                snippet: m.snippet,
+                match_offsets: m.match_offsets,
+                created_at: m.created_at,
+            })
+            .collect(),
+        total_count: total_count as i64,
+        has_more,
+    }))
+}
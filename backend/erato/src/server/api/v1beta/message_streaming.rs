@@ -1,24 +1,33 @@
-use crate::config::{ExperimentalFacetsConfig, HallucinationSuppressionConfig};
+use crate::config::{
+    ChatConfig, ExperimentalFacetsConfig, GenerationStatusConfig, HallucinationSuppressionConfig,
+    InputModality, ModelCapabilities, ModelReasoningEffort, ModelSettings, ModelVerbosity,
+};
 use crate::db::entity_ext::{chats, messages};
 use crate::metrics::{
     report_chat_provider_generation_error, report_chat_provider_time_to_first_token,
-    report_chat_provider_time_to_last_token,
+    report_chat_provider_time_to_last_token, report_message_generation_duration,
+    report_message_time_to_first_token, report_prompt_injection_warning, report_renderable_block,
 };
 use crate::models::chat::{
-    ChatCreationStatus, get_chat_by_message_id, get_or_create_chat,
+    AssistantConfiguration, ChatCreationStatus, get_chat_by_message_id, get_or_create_chat,
     get_or_create_chat_by_previous_message_id,
 };
+use crate::models::file_capability::input_modality_for_filename;
+use crate::models::file_upload::UnavailableFile;
 use crate::models::message::{
-    ContentPart, ContentPartImage, ContentPartReasoning, ContentPartText, GenerationErrorType,
-    GenerationInputMessages, GenerationMetadata, GenerationParameters, GenerationRequestContext,
-    MessageRole, MessageSchema, ToolCallStatus as MessageToolCallStatus, ToolUse,
-    get_generation_chat_provider_id_for_replaced_user_message,
-    get_generation_chat_provider_id_from_message, get_message_by_id, submit_message,
-    update_message_generation_metadata,
+    ContentPart, ContentPartImage, ContentPartReasoning, ContentPartText, GenerationChatOptions,
+    GenerationErrorType, GenerationInputMessages, GenerationMetadata, GenerationParameters,
+    GenerationRequestContext, MessageRole, MessageSchema, OfferedTool, OfferedTools,
+    OutputComplianceMatch, OutputTransformation, PromptInjectionWarning, RateLimitScope, StopReason,
+    TokenBreakdown, ToolCallStatus as MessageToolCallStatus, ToolUse,
+    check_previous_message_for_chat, get_generation_chat_provider_id_for_replaced_user_message,
+    get_generation_chat_provider_id_from_message, get_input_detected_language_from_message,
+    get_message_by_id, get_selected_chat_provider_id_from_message, new_ephemeral_message,
+    save_message_content_draft, strip_image_urls_from_content, submit_message,
+    update_message_generation_chat_provider_id, update_message_generation_metadata,
 };
-use crate::policy::engine::PolicyEngine;
-use crate::policy::types::Subject;
-use crate::server::api::v1beta::ChatMessage;
+use crate::policy::engine::{PolicyEngine, authorize};
+use crate::policy::types::{Action, Resource, Subject};
 use crate::server::api::v1beta::file_resolution::{
     resolve_action_facet_markers_in_generation_input, resolve_file_pointers_in_generation_input,
 };
@@ -26,39 +35,74 @@ use crate::server::api::v1beta::me_profile_middleware::MeProfile;
 use crate::server::api::v1beta::message_streaming_file_extraction::{
     parse_content_filter_error_from_mcp_tool_result, post_process_mcp_tool_result,
 };
+use crate::server::api::v1beta::{
+    ChatMessage, PromptOptimizerRequest, prepare_prompt_optimizer_chat_request,
+};
 use crate::services::background_tasks::{
-    StreamingEvent, StreamingTask, TaskCleanupGuard, ToolCallStatus as BgToolCallStatus,
+    GenerationInProgressError, StreamingEvent, StreamingTask, TaskCleanupGuard,
+    ToolCallStatus as BgToolCallStatus,
 };
+use crate::services::chat_provider_quotas::{self, QuotaExceededError};
 use crate::services::client_tools::{ClientToolDelivery, ClientToolOutcome};
-use crate::services::genai::{build_chat_options_for_completion, build_chat_options_for_summary};
+use crate::services::content_spillover::{
+    SPILL_ORIGIN_WRITE, spill_oversized_content, spill_oversized_raw_message,
+};
+use crate::services::ephemeral_chats::EphemeralChat;
+use crate::services::file_synopsis::FileSynopsis;
+use crate::services::genai::{
+    GenAIChatStreamResponse, build_chat_options_for_completion, build_chat_options_for_summary,
+};
 use crate::services::genai_langfuse::{
     TracedGenerationBuilder, create_otel_error_generation_from_chat, create_otel_tool_call_span,
     create_tool_call_span_from_chat, create_trace_from_chat, create_trace_metadata,
     create_trace_with_generation_from_chat, generate_langfuse_ids, generate_name_from_chat_request,
     langfuse_model_tag, langfuse_tool_called_tag,
 };
+use crate::services::generation_cache;
+use crate::services::generation_queue::{GenerationAdmission, GenerationPermit};
 use crate::services::langfuse::TracingLangfuseClient;
+use crate::services::language_detection::{detect_message_language, resolve_response_language};
+use crate::services::long_messages::{
+    AutoAttachedMessage, auto_attach_long_message, split_long_text_parts,
+    split_long_text_parts_in_raw_message,
+};
+use crate::services::markdown_normalization::normalize_markdown_content;
 use crate::services::mcp_manager::{McpRequestAuthContext, convert_mcp_tools_to_genai_tools};
+use crate::services::moderation::{
+    ModerationOutcome, moderate_unsaved_user_message, moderate_user_message,
+};
+use crate::services::output_compliance::{OutputComplianceFilter, OutputComplianceViolation};
+use crate::services::output_pacing::pace_streaming_events;
+use crate::services::output_transformers::{OutputTransformerFailure, OutputTransformers};
+use crate::services::prompt_composition::manifest::PromptManifest;
+use crate::services::prompt_composition::token_breakdown::count_token_breakdown;
 use crate::services::prompt_composition::traits::{
     FileResolver, MessageRepository, PromptProvider,
 };
 use crate::services::prompt_composition::{
     AppStateFileResolver, AppStatePromptProvider, DatabaseMessageRepository,
-    PromptCompositionUserInput, compose_prompt_messages,
+    InMemoryMessageRepository, PromptCompositionUserInput, SyntheticMessageRepository,
+    compose_prompt_messages,
 };
 use crate::services::prompt_composition::{
-    build_mcp_tool_allowlist, build_model_settings_for_facets,
+    build_mcp_tool_allowlist, build_model_settings_for_facets, mark_stable_prefix_as_cacheable,
 };
 use crate::services::prompt_guardrails::{
     prompt_injection_filter_details, scan_chat_request_for_prompt_injection,
 };
-use crate::services::sentry::capture_report;
+use crate::services::provider_capture::{ProviderTrafficCapture, store_provider_capture};
+use crate::services::renderable_blocks::find_renderable_blocks;
+use crate::services::sentry::{capture_report, log_internal_server_error};
 use crate::services::template_rendering::contexts::chat_provider_headers::ChatProviderHeadersContext;
+use crate::services::untrusted_content::{
+    UntrustedContentGuard, redact_system_prompt, system_prompt_text,
+};
+use crate::services::user_events::UserEvent;
 use crate::state::{AppState, ChatProviderConfigWithId};
 use axum::extract::State;
-use axum::http::HeaderMap;
-use axum::response::Sse;
+use axum::http::{HeaderMap, HeaderValue};
 use axum::response::sse::Event;
+use axum::response::{IntoResponse, Response, Sse};
 use axum::{Extension, Json};
 use chrono::Utc;
 use eyre::{OptionExt, WrapErr};
@@ -67,14 +111,16 @@ use futures::Stream;
 use genai::chat::{
     ChatMessage as GenAiChatMessage, ChatOptions, ChatRequest, ChatRole, ChatStreamEvent,
     ContentPart as GenAiContentPart, MessageContent, ReasoningItem, ReasoningSummaryText,
-    StreamChunk, StreamEnd,
+    StreamChunk, StreamEnd, ToolName as GenaiToolName,
 };
 use sea_orm::JsonValue;
 use sea_orm::prelude::Uuid;
 use serde::Serialize;
 use serde_json::{Value, json};
-use std::collections::{BTreeSet, HashMap, HashSet};
-use std::sync::Arc;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll};
 use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::mpsc::Sender;
 use tokio_stream::StreamExt as _;
@@ -84,8 +130,16 @@ use utoipa::ToSchema;
 
 const HALLUCINATION_LOOP_ERROR_DESCRIPTION: &str =
     "Generation aborted. Hallucination loop detected. Please regenerate the message.";
+const COMPLIANCE_POLICY_ERROR_DESCRIPTION: &str =
+    "Generation aborted. The answer violated a compliance policy.";
+const TRANSFORMER_FAILED_ERROR_DESCRIPTION: &str =
+    "Generation aborted. The answer could not be post-processed.";
 const PROMPT_INJECTION_FILTER_ERROR_DESCRIPTION: &str =
     "The request was filtered because it matched a configured prompt injection guardrail.";
+const MODERATION_BLOCKED_ERROR_DESCRIPTION: &str =
+    "The message was blocked by the content moderation and was not passed to the model.";
+const MODERATION_UNAVAILABLE_ERROR_DESCRIPTION: &str =
+    "The message was blocked because the content moderation is currently unavailable.";
 
 fn is_openai_responses_provider_kind(provider_kind: &str) -> bool {
     matches!(provider_kind, "openai_responses" | "azure_openai_responses")
@@ -248,25 +302,18 @@ fn openai_responses_reasoning_replay_model_matches(
 }
 
 async fn collect_reasoning_replay_messages(
-    app_state: &AppState,
-    policy: &PolicyEngine,
-    subject: &Subject,
+    message_repo: &impl MessageRepository,
     just_submitted_user_message_id: &Uuid,
     current_chat_provider_id: &str,
     compat_no_replay_summary: bool,
 ) -> Result<Vec<OpenAiResponsesReasoningReplayMessage>, Report> {
-    let mut current_message = get_message_by_id(
-        &app_state.db,
-        policy,
-        subject,
-        just_submitted_user_message_id,
-    )
-    .await?;
+    let mut current_message = message_repo
+        .get_message_by_id(just_submitted_user_message_id)
+        .await?;
     let mut replay_messages = Vec::new();
 
     while let Some(previous_message_id) = current_message.previous_message_id {
-        current_message =
-            get_message_by_id(&app_state.db, policy, subject, &previous_message_id).await?;
+        current_message = message_repo.get_message_by_id(&previous_message_id).await?;
         let parsed_message = MessageSchema::validate(&current_message.raw_message)?;
         if parsed_message.role != MessageRole::Assistant {
             continue;
@@ -340,6 +387,26 @@ pub struct ActionFacetRequest {
     pub args: HashMap<String, String>,
 }
 
+/// Role of a submitted message.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageSubmitRole {
+    /// A message of the user.
+    #[default]
+    User,
+    /// A system-level steering message of a privileged client, e.g. about a change of context.
+    System,
+}
+
+impl MessageSubmitRole {
+    fn message_role(self) -> MessageRole {
+        match self {
+            MessageSubmitRole::User => MessageRole::User,
+            MessageSubmitRole::System => MessageRole::System,
+        }
+    }
+}
+
 const X_ERATO_PLATFORM_HEADER: &str = "X-Erato-Platform";
 const DEFAULT_ERATO_PLATFORM: &str = "web";
 
@@ -353,7 +420,7 @@ pub struct MeProfileChatRequestInput<'a> {
     pub user_groups: &'a [String],
     pub organization_user_id: Option<&'a str>,
     pub organization_group_ids: &'a [String],
-    pub oidc_token: &'a str,
+    pub oidc_token: Option<&'a str>,
     pub access_token: Option<&'a str>,
     pub preferred_language: &'a str,
     pub user_preference_nickname: Option<&'a str>,
@@ -371,7 +438,7 @@ impl<'a> MeProfileChatRequestInput<'a> {
             user_groups: &me_profile.groups,
             organization_user_id: me_profile.organization_user_id.as_deref(),
             organization_group_ids: &me_profile.organization_group_ids,
-            oidc_token: &me_profile.oidc_token,
+            oidc_token: me_profile.oidc_token.as_deref(),
             access_token: me_profile.access_token.as_deref(),
             preferred_language: &me_profile.preferred_language,
             user_preference_nickname: me_profile.preference_nickname.as_deref(),
@@ -397,6 +464,11 @@ pub struct MessageSubmitStreamingResponseChatCreated {
 pub struct MessageSubmitStreamingResponseUserMessageSaved {
     message_id: Uuid,
     message: ChatMessage,
+    /// The ID of the file the text of the message was attached as, because it exceeded
+    /// `chat.long_messages.max_inline_bytes`. The message only keeps a preview of the text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    auto_attached_file_id: Option<Uuid>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -405,18 +477,36 @@ pub struct MessageSubmitStreamingResponseMessageComplete {
     message_id: Uuid,
     content: Vec<ContentPart>,
     message: ChatMessage,
+    /// OpenTelemetry trace ID of the generation (if OTel is enabled)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    trace_id: Option<String>,
+    /// OpenTelemetry span ID of the generation of this message (if OTel is enabled)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    span_id: Option<String>,
 }
 
 #[derive(Serialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct MessageSubmitStreamingResponseAssistantMessageStarted {
     message_id: Uuid,
+    /// OpenTelemetry trace ID of the generation (if OTel is enabled)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    trace_id: Option<String>,
+    /// OpenTelemetry span ID of the generation of this message (if OTel is enabled)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    span_id: Option<String>,
 }
 
 #[derive(Serialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct MessageSubmitStreamingResponseMessageTextDelta {
     message_id: Uuid,
+    /// Position of the text part in the content of the message. The index is allocated when the
+    /// text part is opened, and the part keeps it in the completed message.
     content_index: usize,
     new_text: String,
 }
@@ -425,14 +515,31 @@ pub struct MessageSubmitStreamingResponseMessageTextDelta {
 #[serde(rename_all = "snake_case")]
 pub struct MessageSubmitStreamingResponseMessageReasoningDelta {
     message_id: Uuid,
+    /// Position of the reasoning part in the content of the message. The index is allocated when
+    /// the reasoning part is opened, and the part keeps it in the completed message.
     content_index: usize,
     new_text: String,
 }
 
+/// Sent while the arguments of a tool call are streamed by the model. Concatenating the
+/// `args_fragment`s of a tool call yields its raw arguments, which are sent parsed as the `input`
+/// of `tool_call_proposed` once complete.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MessageSubmitStreamingResponseToolCallArgumentsDelta {
+    message_id: Uuid,
+    tool_call_id: String,
+    args_fragment: String,
+}
+
 #[derive(Serialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct MessageSubmitStreamingResponseToolCallProposed {
     message_id: Uuid,
+    /// Position of the tool call in the content of the message. The index is allocated when the
+    /// tool call is proposed, and all later events of the tool call refer to it. Parts without
+    /// events, like images extracted from the output of a tool, are only part of the completed
+    /// message, but are counted by the indices of later parts.
     content_index: usize,
     tool_call_id: String,
     tool_name: String,
@@ -447,6 +554,8 @@ pub struct MessageSubmitStreamingResponseToolCallProposed {
 #[serde(rename_all = "snake_case")]
 pub struct MessageSubmitStreamingResponseClientToolCall {
     message_id: Uuid,
+    /// Position of the tool call in the content of the message, as sent with
+    /// `tool_call_proposed`
     content_index: usize,
     tool_call_id: String,
     tool_name: String,
@@ -466,6 +575,8 @@ pub enum ToolCallStatus {
 #[serde(rename_all = "snake_case")]
 pub struct MessageSubmitStreamingResponseToolCallUpdate {
     message_id: Uuid,
+    /// Position of the tool call in the content of the message, as sent with
+    /// `tool_call_proposed`
     content_index: usize,
     tool_call_id: String,
     tool_name: String,
@@ -477,6 +588,51 @@ pub struct MessageSubmitStreamingResponseToolCallUpdate {
     output: Option<JsonValue>,
 }
 
+/// Sent when a possible prompt injection was found in the contents of an attached file or in the
+/// output of a tool. The generation continues; the warning is also recorded on the message.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MessageSubmitStreamingResponsePromptInjectionWarning {
+    message_id: Uuid,
+    #[serde(flatten)]
+    warning: PromptInjectionWarning,
+}
+
+/// Sent before the generation starts when the contents of attached files are unavailable, because
+/// their objects are missing in the storage. The model is instructed to tell the user about it.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MessageSubmitStreamingResponseFilesUnavailable {
+    message_id: Uuid,
+    files: Vec<UnavailableFile>,
+}
+
+/// Sent before the generation starts when the requested chat provider is no longer configured,
+/// and the generation falls back to another chat provider.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MessageSubmitStreamingResponseProviderFallback {
+    message_id: Uuid,
+    /// The chat provider that was requested, which is no longer configured
+    requested_chat_provider_id: String,
+    /// The chat provider that generates the message instead
+    chat_provider_id: String,
+}
+
+/// Sent while the generation waits in the queue, because the maximum number of concurrent
+/// generations is reached. Repeated every few seconds and whenever the position changes.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MessageSubmitStreamingResponseQueued {
+    /// Position of the generation in the queue, `1` for the generation that starts next
+    position: usize,
+    /// Rough estimate of the seconds until the generation starts. Only available once
+    /// generations have completed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    estimated_wait_seconds: Option<u64>,
+}
+
 #[derive(Serialize, ToSchema, Clone, Debug)]
 #[serde(rename_all = "snake_case")]
 pub struct MessageSubmitStreamingResponseError {
@@ -501,9 +657,18 @@ trait SendAsSseEvent {
             data_json = json.as_str(),
             "Sending response event"
         );
-        tx.send(Ok(Event::default().event(self.tag()).data(json)))
+        // The channel is only closed once the client disconnected. Generations continue in the
+        // background, where they can be resumed, so the event is dropped instead of failing.
+        if tx
+            .send(Ok(Event::default().event(self.tag()).data(json)))
             .await
-            .map_err(|err| eyre!("Failed to send {} event: {err}", self.tag()))?;
+            .is_err()
+        {
+            tracing::debug!(
+                tag = self.tag(),
+                "Dropping response event, as the client disconnected"
+            );
+        }
         Ok(())
     }
 }
@@ -566,7 +731,68 @@ async fn send_background_event(task: &StreamingTask, event: StreamingEvent, cont
     }
 }
 
-#[derive(Clone, serde::Deserialize, ToSchema)]
+/// SSE stream of a generation that notices when its client disconnects.
+///
+/// Generations continue in the background when their client disconnects, so that they can be
+/// resumed. Dropping the stream before the generation completed marks the generation as detached,
+/// unless another client is still subscribed to it. With `chat.abort_on_client_disconnect`, the
+/// generation is aborted instead.
+struct ClientDisconnectGuard<S> {
+    stream: Option<Pin<Box<S>>>,
+    // Weak, so that the stream still ends once the task is dropped
+    task: Weak<StreamingTask>,
+    abort_on_client_disconnect: bool,
+}
+
+impl<S> ClientDisconnectGuard<S> {
+    fn new(stream: S, task: Option<&Arc<StreamingTask>>, app_state: &AppState) -> Self {
+        Self {
+            stream: Some(Box::pin(stream)),
+            task: task.map(Arc::downgrade).unwrap_or_default(),
+            abort_on_client_disconnect: app_state.config.chat.abort_on_client_disconnect,
+        }
+    }
+}
+
+impl<S: Stream> Stream for ClientDisconnectGuard<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        match self.stream.as_mut() {
+            Some(stream) => stream.as_mut().poll_next(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+impl<S> Drop for ClientDisconnectGuard<S> {
+    fn drop(&mut self) {
+        // Unsubscribe this client before counting the remaining ones
+        self.stream.take();
+        let Some(task) = self.task.upgrade() else {
+            return;
+        };
+        if task.is_completed() || task.subscriber_count() > 0 {
+            return;
+        }
+
+        task.mark_client_detached();
+        if self.abort_on_client_disconnect {
+            tracing::info!(
+                message_id = %task.message_id(),
+                "Aborting generation, as its client disconnected"
+            );
+            task.request_abort();
+        } else {
+            tracing::debug!(
+                message_id = %task.message_id(),
+                "Continuing generation in the background, as its client disconnected"
+            );
+        }
+    }
+}
+
+#[derive(Clone, serde::Deserialize, Serialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct MessageSubmitRequest {
     #[schema(example = "00000000-0000-0000-0000-000000000000")]
@@ -575,7 +801,8 @@ pub struct MessageSubmitRequest {
     #[schema(example = "00000000-0000-0000-0000-000000000000")]
     /// The ID of an existing chat to use. If provided, the chat with this ID will be used instead of creating a new one.
     /// This is useful for scenarios where you have created a chat first (e.g. for file uploads) before sending the first message.
-    existing_chat_id: Option<Uuid>,
+    /// Messages submitted to an ephemeral chat are only held in memory, and are never stored.
+    pub(crate) existing_chat_id: Option<Uuid>,
     #[schema(example = "Hello, world!")]
     /// The text of the message.
     #[allow(dead_code)]
@@ -585,6 +812,12 @@ pub struct MessageSubmitRequest {
     /// The files should normally only be provided with the first message they appear in the chat. After that they can assumed to be part of the chat history.
     #[serde(default)]
     input_files_ids: Vec<Uuid>,
+    #[schema(example = "[\"00000000-0000-0000-0000-000000000000\"]")]
+    /// IDs of files of earlier messages of the chat to include again next to this message, without
+    /// uploading them again. Files that are not part of the chat history are ignored.
+    /// Only has an effect if `chat.file_recency` is enabled.
+    #[serde(default)]
+    refresh_file_ids: Vec<Uuid>,
     #[schema(example = "primary")]
     /// The ID of the chat provider to use for generation. If not provided, will use the highest priority model for the user.
     #[schema(nullable = false)]
@@ -603,13 +836,116 @@ pub struct MessageSubmitRequest {
     selected_facet_ids: Vec<String>,
     /// Optional action facet to apply during this generation.
     action_facet: Option<ActionFacetRequest>,
+    #[schema(example = "de")]
+    /// Optional language code the response should be written in. If not provided, the detected
+    /// language of the user message, the language of the chat or the user's preferred language
+    /// is used.
+    #[schema(nullable = false)]
+    response_language: Option<String>,
+    /// If true, the message is not submitted. Instead, the request that would be sent to the
+    /// LLM is composed and returned as a single JSON response, without persisting anything.
+    /// Only available if `debug.allow_dry_run` is enabled.
+    #[serde(default)]
+    pub(crate) dry_run: bool,
+    /// The role the message is stored with. `system` messages are only accepted from members of
+    /// the groups in `chat.allow_client_system_messages_groups`, and can't have input files.
+    #[serde(default)]
+    role: MessageSubmitRole,
+    /// Whether an answer is generated for the message. Defaults to `true` for user messages
+    /// and `false` for system messages. Only system messages can be stored without an answer.
+    #[schema(nullable = false)]
+    generate: Option<bool>,
+}
+
+impl MessageSubmitRequest {
+    /// Whether an answer is generated for the submitted message.
+    pub(crate) fn generates_answer(&self) -> bool {
+        self.generate
+            .unwrap_or(self.role == MessageSubmitRole::User)
+    }
+
+    /// The request with the text of the message replaced by its preview, and the file the text
+    /// was attached as added to the input files.
+    fn with_auto_attached_message(&self, attached: &AutoAttachedMessage) -> Self {
+        let mut request = self.clone();
+        request.user_message = attached.preview.clone();
+        request.input_files_ids.push(attached.file_upload_id);
+        request
+    }
+}
+
+/// Response of a message submission with `dry_run: true`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MessageSubmitDryRunResponse {
+    /// The ID of the chat the message would be submitted to. Not set if a new chat would be created.
+    #[schema(nullable = false)]
+    chat_id: Option<Uuid>,
+    /// The ID of the chat provider that would be used for generation.
+    chat_provider_id: String,
+    /// The composed message sequence in the OpenAI chat completions format.
+    /// Long file contents are truncated. The message that ends the part of the prompt the provider
+    /// may cache has a `cache_control` entry.
+    #[schema(value_type = Vec<Object>)]
+    messages: Vec<JsonValue>,
+    /// The tools that would be offered to the LLM, in the OpenAI chat completions format.
+    #[schema(value_type = Vec<Object>)]
+    tools: Vec<JsonValue>,
+    /// The tools that would be offered to the LLM, as they would be stored with the generated
+    /// message.
+    offered_tools: OfferedTools,
+    /// The effective chat options of the generation.
+    chat_options: MessageSubmitDryRunChatOptions,
+    /// Estimated prompt tokens of the composed request, if they could be counted.
+    #[schema(nullable = false)]
+    token_estimate: Option<MessageSubmitDryRunTokenEstimate>,
+    /// Manifest of the prompts the request is composed of, as it would be stored with the
+    /// generated message.
+    prompt_manifest: PromptManifest,
+}
+
+/// Effective model settings of a dry run, after applying the selected facets.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MessageSubmitDryRunChatOptions {
+    #[schema(nullable = false)]
+    temperature: Option<f64>,
+    #[schema(nullable = false)]
+    top_p: Option<f64>,
+    #[schema(nullable = false)]
+    reasoning_effort: Option<ModelReasoningEffort>,
+    #[schema(nullable = false)]
+    verbosity: Option<ModelVerbosity>,
+}
+
+/// Estimated prompt tokens of a dry run per part of the prompt.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MessageSubmitDryRunTokenEstimate {
+    system_prompt_tokens: u32,
+    chat_history_tokens: u32,
+    file_content_tokens: u32,
+    user_message_tokens: u32,
+    total_tokens: u32,
+}
+
+impl From<TokenBreakdown> for MessageSubmitDryRunTokenEstimate {
+    fn from(breakdown: TokenBreakdown) -> Self {
+        Self {
+            total_tokens: breakdown.system_prompt_tokens
+                + breakdown.chat_history_tokens
+                + breakdown.file_content_tokens
+                + breakdown.user_message_tokens,
+            system_prompt_tokens: breakdown.system_prompt_tokens,
+            chat_history_tokens: breakdown.chat_history_tokens,
+            file_content_tokens: breakdown.file_content_tokens,
+            user_message_tokens: breakdown.user_message_tokens,
+        }
+    }
 }
 
 #[derive(Serialize, ToSchema)]
 #[serde(tag = "message_type")]
 pub enum MessageSubmitStreamingResponseMessage {
     #[serde(rename = "chat_created")]
-    /// May optionally be sent at the start of the stream to indicate that a new chat has been created.
+    /// Sent right before `user_message_saved` if a new chat has been created for the message.
     ChatCreated(MessageSubmitStreamingResponseChatCreated),
     #[serde(rename = "user_message_saved")]
     /// Sent at the start of the stream to indicate that the user's message has been saved.
@@ -626,6 +962,9 @@ pub enum MessageSubmitStreamingResponseMessage {
     #[serde(rename = "reasoning_delta")]
     /// Sent whenever a new reasoning chunk is generated by the assistant.
     ReasoningDelta(MessageSubmitStreamingResponseMessageReasoningDelta),
+    #[serde(rename = "tool_call_arguments_delta")]
+    /// Sent whenever a new chunk of the arguments of a tool call is generated by the assistant.
+    ToolCallArgumentsDelta(MessageSubmitStreamingResponseToolCallArgumentsDelta),
     #[serde(rename = "tool_call_proposed")]
     /// Sent when the LLM proposes a tool call to be part of the assistant message.
     ToolCallProposed(MessageSubmitStreamingResponseToolCallProposed),
@@ -636,6 +975,19 @@ pub enum MessageSubmitStreamingResponseMessage {
     /// Sent when the model calls a facet client tool: the turn is suspended
     /// until the client executes it and POSTs the result back.
     ClientToolCall(MessageSubmitStreamingResponseClientToolCall),
+    #[serde(rename = "prompt_injection_warning")]
+    /// Sent when a possible prompt injection was found in a file or tool output.
+    PromptInjectionWarning(MessageSubmitStreamingResponsePromptInjectionWarning),
+    #[serde(rename = "files_unavailable")]
+    /// Sent before the generation when attached files are missing in the storage.
+    FilesUnavailable(MessageSubmitStreamingResponseFilesUnavailable),
+    #[serde(rename = "provider_fallback")]
+    /// Sent before the generation when the requested chat provider is no longer configured.
+    ProviderFallback(MessageSubmitStreamingResponseProviderFallback),
+    #[serde(rename = "queued")]
+    /// Sent while the generation waits for the number of concurrent generations to drop below
+    /// the limit.
+    Queued(MessageSubmitStreamingResponseQueued),
     #[serde(rename = "error")]
     /// Sent when an error occurs during message generation.
     Error(MessageSubmitStreamingResponseError),
@@ -650,9 +1002,14 @@ impl SendAsSseEvent for MessageSubmitStreamingResponseMessage {
             Self::AssistantMessageCompleted(_) => "assistant_message_completed",
             Self::TextDelta(_) => "text_delta",
             Self::ReasoningDelta(_) => "reasoning_delta",
+            Self::ToolCallArgumentsDelta(_) => "tool_call_arguments_delta",
             Self::ToolCallProposed(_) => "tool_call_proposed",
             Self::ToolCallUpdate(_) => "tool_call_update",
             Self::ClientToolCall(_) => "client_tool_call",
+            Self::PromptInjectionWarning(_) => "prompt_injection_warning",
+            Self::FilesUnavailable(_) => "files_unavailable",
+            Self::ProviderFallback(_) => "provider_fallback",
+            Self::Queued(_) => "queued",
             Self::Error(_) => "error",
         }
     }
@@ -706,6 +1063,14 @@ impl From<MessageSubmitStreamingResponseMessageReasoningDelta>
     }
 }
 
+impl From<MessageSubmitStreamingResponseToolCallArgumentsDelta>
+    for MessageSubmitStreamingResponseMessage
+{
+    fn from(value: MessageSubmitStreamingResponseToolCallArgumentsDelta) -> Self {
+        MessageSubmitStreamingResponseMessage::ToolCallArgumentsDelta(value)
+    }
+}
+
 impl From<MessageSubmitStreamingResponseToolCallProposed>
     for MessageSubmitStreamingResponseMessage
 {
@@ -726,6 +1091,30 @@ impl From<MessageSubmitStreamingResponseToolCallUpdate> for MessageSubmitStreami
     }
 }
 
+impl From<MessageSubmitStreamingResponsePromptInjectionWarning>
+    for MessageSubmitStreamingResponseMessage
+{
+    fn from(value: MessageSubmitStreamingResponsePromptInjectionWarning) -> Self {
+        MessageSubmitStreamingResponseMessage::PromptInjectionWarning(value)
+    }
+}
+
+impl From<MessageSubmitStreamingResponseFilesUnavailable>
+    for MessageSubmitStreamingResponseMessage
+{
+    fn from(value: MessageSubmitStreamingResponseFilesUnavailable) -> Self {
+        MessageSubmitStreamingResponseMessage::FilesUnavailable(value)
+    }
+}
+
+impl From<MessageSubmitStreamingResponseProviderFallback>
+    for MessageSubmitStreamingResponseMessage
+{
+    fn from(value: MessageSubmitStreamingResponseProviderFallback) -> Self {
+        MessageSubmitStreamingResponseMessage::ProviderFallback(value)
+    }
+}
+
 impl From<MessageSubmitStreamingResponseError> for MessageSubmitStreamingResponseMessage {
     fn from(value: MessageSubmitStreamingResponseError) -> Self {
         MessageSubmitStreamingResponseMessage::Error(value)
@@ -736,6 +1125,7 @@ fn parse_error_body(
     body: &Value,
     message_id: Uuid,
     status_code: Option<u16>,
+    header_retry_after_seconds: Option<u64>,
 ) -> Option<MessageSubmitStreamingResponseError> {
     let error_obj = body.get("error")?;
     let error_code = error_obj
@@ -743,6 +1133,7 @@ fn parse_error_body(
         .and_then(|c| c.as_str())
         .or_else(|| error_obj.get("type").and_then(|t| t.as_str()))
         .or_else(|| error_obj.get("error_type").and_then(|t| t.as_str()));
+    let error_type = error_obj.get("type").and_then(|t| t.as_str());
     let error_message = error_obj
         .get("message")
         .and_then(|m| m.as_str())
@@ -765,11 +1156,24 @@ fn parse_error_body(
         });
     }
 
-    if error_code == Some("429") || status_code == Some(429) || message_is_rate_limit {
+    if matches!(error_code, Some("429" | "rate_limit_exceeded"))
+        || status_code == Some(429)
+        || message_is_rate_limit
+    {
+        let retry_after_seconds = header_retry_after_seconds
+            .or_else(|| {
+                error_obj
+                    .get("retry_after")
+                    .and_then(|retry_after| retry_after.as_f64())
+                    .map(|seconds| seconds.ceil() as u64)
+            })
+            .or_else(|| retry_after_from_message(error_message));
         return Some(MessageSubmitStreamingResponseError {
             message_id: Some(message_id),
-            error: GenerationErrorType::RateLimit {
+            error: GenerationErrorType::RateLimited {
                 error_description: error_message.to_string(),
+                retry_after_seconds,
+                scope: rate_limit_scope(error_type, error_message),
             },
         });
     }
@@ -783,6 +1187,77 @@ fn parse_error_body(
     })
 }
 
+/// The number of seconds after which a provider accepts requests again, from the `Retry-After`
+/// header, or the `retry-after-ms` header that Azure OpenAI sends alongside it.
+pub(crate) fn retry_after_from_headers(headers: &HeaderMap) -> Option<u64> {
+    let header_value = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+    };
+    if let Some(retry_after) = header_value("retry-after") {
+        if let Ok(seconds) = retry_after.parse::<u64>() {
+            return Some(seconds);
+        }
+        // The header may also contain an HTTP date
+        if let Ok(retry_at) = chrono::DateTime::parse_from_rfc2822(retry_after) {
+            return Some(
+                (retry_at.with_timezone(&Utc) - Utc::now())
+                    .num_seconds()
+                    .max(0) as u64,
+            );
+        }
+    }
+    header_value("retry-after-ms")
+        .and_then(|millis| millis.parse::<f64>().ok())
+        .map(|millis| (millis / 1000.0).ceil() as u64)
+}
+
+/// The number of seconds after which a provider accepts requests again, from the message of a
+/// rate limit error, e.g. "Please retry after 20 seconds" (Azure OpenAI) or
+/// "Please try again in 1.5s" (OpenAI).
+fn retry_after_from_message(message: &str) -> Option<u64> {
+    let message = message.to_lowercase();
+    ["retry after ", "try again in "].iter().find_map(|prefix| {
+        let hint = &message[message.find(prefix)? + prefix.len()..];
+        let number_len = hint
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(hint.len());
+        let number = hint[..number_len].parse::<f64>().ok()?;
+        let unit = hint[number_len..].trim_start();
+        let seconds = if unit.starts_with("ms") || unit.starts_with("millisecond") {
+            number / 1000.0
+        } else if unit.starts_with('m') {
+            number * 60.0
+        } else {
+            number
+        };
+        Some(seconds.ceil() as u64)
+    })
+}
+
+/// Which limit a rate limit error is about, from the error type that OpenAI reports (`tokens` or
+/// `requests`), or from the message, e.g. "exceeded token rate limit" (Azure OpenAI).
+fn rate_limit_scope(error_type: Option<&str>, message: &str) -> Option<RateLimitScope> {
+    match error_type {
+        Some("tokens") => return Some(RateLimitScope::Tokens),
+        Some("requests") => return Some(RateLimitScope::Requests),
+        _ => {}
+    }
+    let message = message.to_lowercase();
+    if message.contains("token rate limit") || message.contains("tokens per") {
+        Some(RateLimitScope::Tokens)
+    } else if message.contains("call rate limit")
+        || message.contains("request rate limit")
+        || message.contains("requests per")
+    {
+        Some(RateLimitScope::Requests)
+    } else {
+        None
+    }
+}
+
 fn extract_embedded_json_payload(error_text: &str) -> Option<Value> {
     // Some upstream errors include JSON body as text like:
     // "... Status: 400 ... Body: {\"error\":{...}}"
@@ -799,34 +1274,47 @@ fn extract_embedded_json_payload(error_text: &str) -> Option<Value> {
 }
 
 /// Parse an error from the LLM streaming response and create an appropriate error type.
-async fn parse_streaming_error(
-    err: genai::Error,
+fn parse_streaming_error(
+    err: &genai::Error,
     message_id: Uuid,
 ) -> MessageSubmitStreamingResponseError {
     let err_text = err.to_string();
 
-    if let genai::Error::ChatResponse { body, .. } = &err
-        && let Some(parsed) = parse_error_body(body, message_id, None)
+    if let genai::Error::ChatResponse { body, .. } = err
+        && let Some(parsed) = parse_error_body(body, message_id, None, None)
     {
         return parsed;
     }
 
     if let genai::Error::WebModelCall { webc_error, .. }
-    | genai::Error::WebAdapterCall { webc_error, .. } = &err
+    | genai::Error::WebAdapterCall { webc_error, .. } = err
     {
         match webc_error {
-            genai::webc::Error::ResponseFailedStatus { status, body, .. } => {
+            genai::webc::Error::ResponseFailedStatus {
+                status,
+                body,
+                headers,
+                ..
+            } => {
+                let header_retry_after_seconds = retry_after_from_headers(headers);
                 if let Ok(body_json) = serde_json::from_str::<Value>(body)
-                    && let Some(parsed) =
-                        parse_error_body(&body_json, message_id, Some(status.as_u16()))
+                    && let Some(parsed) = parse_error_body(
+                        &body_json,
+                        message_id,
+                        Some(status.as_u16()),
+                        header_retry_after_seconds,
+                    )
                 {
                     return parsed;
                 }
                 if status.as_u16() == 429 {
                     return MessageSubmitStreamingResponseError {
                         message_id: Some(message_id),
-                        error: GenerationErrorType::RateLimit {
+                        error: GenerationErrorType::RateLimited {
                             error_description: body.clone(),
+                            retry_after_seconds: header_retry_after_seconds
+                                .or_else(|| retry_after_from_message(body)),
+                            scope: rate_limit_scope(None, body),
                         },
                     };
                 }
@@ -852,7 +1340,7 @@ async fn parse_streaming_error(
     }
 
     if let Some(payload_json) = extract_embedded_json_payload(&err_text)
-        && let Some(parsed) = parse_error_body(&payload_json, message_id, None)
+        && let Some(parsed) = parse_error_body(&payload_json, message_id, None, None)
     {
         return parsed;
     }
@@ -884,50 +1372,184 @@ async fn fetch_non_streaming_error(
             .exec_chat("PLACEHOLDER_MODEL", chat_request, Some(chat_options))
             .await
         {
-            Err(err) => Some(parse_streaming_error(err, message_id).await),
+            Err(err) => Some(parse_streaming_error(&err, message_id)),
             Ok(_) => None,
         },
     )
 }
-/// Convert a StreamingEvent to an SSE Event for message submission
-fn streaming_event_to_sse(event: &StreamingEvent) -> Result<Event, Report> {
+
+/// Starts the chat stream of a generation turn.
+///
+/// For generations of a chat provider group, the first event of the stream is awaited, so that a
+/// member that is rate limited can be replaced by another member of the group before anything is
+/// forwarded to the client. The outcome is recorded for the member, and the member that served
+/// the stream is returned alongside it.
+async fn start_chat_stream(
+    app_state: &AppState,
+    chat_request: &ChatRequest,
+    chat_options: &ChatOptions,
+    message_id: Uuid,
+    chat_provider_id: Option<&str>,
+    chat_provider_group_id: Option<&str>,
+    chat_provider_headers_context: &ChatProviderHeadersContext<'_>,
+) -> Result<(genai::Result<GenAIChatStreamResponse>, Option<String>), Report> {
+    let group = chat_provider_group_id
+        .and_then(|group_id| Some((group_id, app_state.config.chat_provider_group(group_id)?)));
+    let mut member_id = chat_provider_id.map(String::from);
+    let mut failed_member_ids: Vec<String> = vec![];
+    loop {
+        let genai_client = app_state
+            .genai_for_chat_provider_id_with_headers_context(
+                member_id.as_deref(),
+                Some(chat_provider_headers_context),
+            )
+            .wrap_err("Unable to choose chat provider")?;
+        let request_start = Instant::now();
+        let result = genai_client
+            .exec_chat_stream(
+                "PLACEHOLDER_MODEL",
+                chat_request.clone(),
+                Some(chat_options),
+            )
+            .await;
+        let (Some((group_id, _)), Some(current_member_id)) = (group, member_id.clone()) else {
+            return Ok((result, member_id));
+        };
+
+        // Errors of the provider may only surface with the first event of the stream
+        let result = match result {
+            Ok(mut response) => match response.stream.next().await {
+                Some(Err(err)) => Err(err),
+                first_event => Ok(GenAIChatStreamResponse::new(
+                    futures::stream::iter(first_event).chain(response.stream),
+                )),
+            },
+            Err(err) => Err(err),
+        };
+        let err = match result {
+            Ok(response) => {
+                app_state
+                    .chat_provider_groups
+                    .record_success(&current_member_id, request_start.elapsed());
+                return Ok((Ok(response), member_id));
+            }
+            Err(err) => err,
+        };
+        app_state
+            .chat_provider_groups
+            .record_error(&current_member_id);
+
+        let error = parse_streaming_error(&err, message_id).error;
+        if !matches!(error, GenerationErrorType::RateLimited { .. }) {
+            return Ok((Err(err), member_id));
+        }
+        record_chat_provider_rate_limit(app_state, Some(&current_member_id), &error);
+        failed_member_ids.push(current_member_id.clone());
+        let Some(next_member_id) =
+            app_state.select_chat_provider_group_member(group_id, &failed_member_ids)
+        else {
+            return Ok((Err(err), member_id));
+        };
+        log_chat_completion_generation_error(&current_member_id, message_id, &error);
+        report_chat_provider_generation_error(&current_member_id, &error);
+        tracing::warn!(
+            chat_provider_group_id = group_id,
+            chat_provider_id = current_member_id,
+            next_chat_provider_id = next_member_id,
+            message_id = %message_id,
+            "Chat provider is rate limited, retrying the generation with another member of the group"
+        );
+        member_id = Some(next_member_id);
+    }
+}
+
+/// Sets the W3C `traceparent` header of the generation span on a streaming response, so clients
+/// can correlate the stream with the backend trace before the first event arrives.
+fn with_traceparent_header(response: impl IntoResponse, span: &tracing::Span) -> Response {
+    let mut response = response.into_response();
+    if let Some(correlation) = crate::telemetry::TraceCorrelation::of_span(span)
+        && let Ok(value) = HeaderValue::from_str(&correlation.traceparent())
+    {
+        response.headers_mut().insert("traceparent", value);
+    }
+    response
+}
+
+/// OpenTelemetry trace and span ID of the current span, which is the message-level generation
+/// span inside of generation tasks.
+fn current_trace_ids() -> (Option<String>, Option<String>) {
+    crate::telemetry::TraceCorrelation::current()
+        .map(|correlation| (Some(correlation.trace_id), Some(correlation.span_id)))
+        .unwrap_or_default()
+}
+
+fn insert_trace_ids(
+    data_value: &mut JsonValue,
+    trace_id: &Option<String>,
+    span_id: &Option<String>,
+) {
+    if let JsonValue::Object(map) = data_value {
+        if let Some(trace_id) = trace_id {
+            map.insert("trace_id".to_string(), JsonValue::String(trace_id.clone()));
+        }
+        if let Some(span_id) = span_id {
+            map.insert("span_id".to_string(), JsonValue::String(span_id.clone()));
+        }
+    }
+}
+
+/// Convert a StreamingEvent to its event name and the JSON payload that clients receive.
+///
+/// Shared by the SSE and the WebSocket transports, so that both deliver identical events.
+pub(crate) fn streaming_event_to_json(
+    event: &StreamingEvent,
+) -> Result<(&'static str, JsonValue), Report> {
     let (event_name, data) = match event {
         StreamingEvent::ChatCreated { chat_id } => {
-            let data = serde_json::to_string(&serde_json::json!({
+            let data = serde_json::json!({
                 "message_type": "chat_created",
                 "chat_id": chat_id.to_string()
-            }))?;
+            });
             ("chat_created", data)
         }
         StreamingEvent::UserMessageSaved {
             message_id,
             message,
+            auto_attached_file_id,
         } => {
-            let data = serde_json::to_string(&serde_json::json!({
+            let mut data = serde_json::json!({
                 "message_type": "user_message_saved",
                 "message_id": message_id.to_string(),
                 "message": message
-            }))?;
+            });
+            if let Some(file_id) = auto_attached_file_id {
+                data["auto_attached_file_id"] = JsonValue::String(file_id.to_string());
+            }
             ("user_message_saved", data)
         }
-        StreamingEvent::AssistantMessageStarted { message_id } => {
-            let data = serde_json::to_string(&serde_json::json!({
+        StreamingEvent::AssistantMessageStarted {
+            message_id,
+            trace_id,
+            span_id,
+        } => {
+            let mut data_value = serde_json::json!({
                 "message_type": "assistant_message_started",
                 "message_id": message_id.to_string()
-            }))?;
-            ("assistant_message_started", data)
+            });
+            insert_trace_ids(&mut data_value, trace_id, span_id);
+            ("assistant_message_started", data_value)
         }
         StreamingEvent::TextDelta {
             message_id,
             content_index,
             new_text,
         } => {
-            let data = serde_json::to_string(&serde_json::json!({
+            let data = serde_json::json!({
                 "message_type": "text_delta",
                 "message_id": message_id.to_string(),
                 "content_index": content_index,
                 "new_text": new_text
-            }))?;
+            });
             ("text_delta", data)
         }
         StreamingEvent::ReasoningDelta {
@@ -935,14 +1557,27 @@ fn streaming_event_to_sse(event: &StreamingEvent) -> Result<Event, Report> {
             content_index,
             new_text,
         } => {
-            let data = serde_json::to_string(&serde_json::json!({
+            let data = serde_json::json!({
                 "message_type": "reasoning_delta",
                 "message_id": message_id.to_string(),
                 "content_index": content_index,
                 "new_text": new_text
-            }))?;
+            });
             ("reasoning_delta", data)
         }
+        StreamingEvent::ToolCallArgumentsDelta {
+            message_id,
+            tool_call_id,
+            args_fragment,
+        } => {
+            let data = serde_json::json!({
+                "message_type": "tool_call_arguments_delta",
+                "message_id": message_id.to_string(),
+                "tool_call_id": tool_call_id,
+                "args_fragment": args_fragment
+            });
+            ("tool_call_arguments_delta", data)
+        }
         StreamingEvent::ToolCallProposed {
             message_id,
             content_index,
@@ -950,14 +1585,14 @@ fn streaming_event_to_sse(event: &StreamingEvent) -> Result<Event, Report> {
             tool_name,
             input,
         } => {
-            let data = serde_json::to_string(&serde_json::json!({
+            let data = serde_json::json!({
                 "message_type": "tool_call_proposed",
                 "message_id": message_id.to_string(),
                 "content_index": content_index,
                 "tool_call_id": tool_call_id,
                 "tool_name": tool_name,
                 "input": input
-            }))?;
+            });
             ("tool_call_proposed", data)
         }
         StreamingEvent::ToolCallUpdate {
@@ -975,7 +1610,7 @@ fn streaming_event_to_sse(event: &StreamingEvent) -> Result<Event, Report> {
                 BgToolCallStatus::Success => "success",
                 BgToolCallStatus::Error => "error",
             };
-            let data = serde_json::to_string(&serde_json::json!({
+            let data = serde_json::json!({
                 "message_type": "tool_call_update",
                 "message_id": message_id.to_string(),
                 "content_index": content_index,
@@ -985,7 +1620,7 @@ fn streaming_event_to_sse(event: &StreamingEvent) -> Result<Event, Report> {
                 "status": status_str,
                 "progress_message": progress_message,
                 "output": output
-            }))?;
+            });
             ("tool_call_update", data)
         }
         StreamingEvent::ClientToolCall {
@@ -995,28 +1630,81 @@ fn streaming_event_to_sse(event: &StreamingEvent) -> Result<Event, Report> {
             tool_name,
             input,
         } => {
-            let data = serde_json::to_string(&serde_json::json!({
+            let data = serde_json::json!({
                 "message_type": "client_tool_call",
                 "message_id": message_id.to_string(),
                 "content_index": content_index,
                 "tool_call_id": tool_call_id,
                 "tool_name": tool_name,
                 "input": input
-            }))?;
+            });
             ("client_tool_call", data)
         }
+        StreamingEvent::PromptInjectionWarning {
+            message_id,
+            warning,
+        } => {
+            let mut data_value = serde_json::to_value(warning)?;
+            if let JsonValue::Object(map) = &mut data_value {
+                map.insert(
+                    "message_type".to_string(),
+                    JsonValue::String("prompt_injection_warning".to_string()),
+                );
+                map.insert(
+                    "message_id".to_string(),
+                    JsonValue::String(message_id.to_string()),
+                );
+            }
+            ("prompt_injection_warning", data_value)
+        }
+        StreamingEvent::FilesUnavailable { message_id, files } => {
+            let data = serde_json::json!({
+                "message_type": "files_unavailable",
+                "message_id": message_id.to_string(),
+                "files": files
+            });
+            ("files_unavailable", data)
+        }
+        StreamingEvent::ProviderFallback {
+            message_id,
+            requested_chat_provider_id,
+            chat_provider_id,
+        } => {
+            let data = serde_json::json!({
+                "message_type": "provider_fallback",
+                "message_id": message_id.to_string(),
+                "requested_chat_provider_id": requested_chat_provider_id,
+                "chat_provider_id": chat_provider_id
+            });
+            ("provider_fallback", data)
+        }
+        StreamingEvent::Queued {
+            position,
+            estimated_wait_seconds,
+        } => {
+            let data = serde_json::to_value(MessageSubmitStreamingResponseMessage::Queued(
+                MessageSubmitStreamingResponseQueued {
+                    position: *position,
+                    estimated_wait_seconds: *estimated_wait_seconds,
+                },
+            ))?;
+            ("queued", data)
+        }
         StreamingEvent::AssistantMessageCompleted {
             message_id,
             content,
             message,
+            trace_id,
+            span_id,
         } => {
-            let data = serde_json::to_string(&serde_json::json!({
+            let mut data_value = serde_json::json!({
                 "message_type": "assistant_message_completed",
                 "message_id": message_id.to_string(),
                 "content": content,
                 "message": message
-            }))?;
-            ("assistant_message_completed", data)
+            });
+            insert_trace_ids(&mut data_value, trace_id, span_id);
+            ("assistant_message_completed", data_value)
         }
         StreamingEvent::Error { error } => {
             let data_value = match error {
@@ -1036,18 +1724,25 @@ fn streaming_event_to_sse(event: &StreamingEvent) -> Result<Event, Report> {
                     "message_type": "error"
                 }),
             };
-            let data = serde_json::to_string(&data_value)?;
-            ("error", data)
+            ("error", data_value)
         }
         StreamingEvent::StreamEnd => {
-            let data = serde_json::to_string(&serde_json::json!({
+            let data = serde_json::json!({
                 "message_type": "stream_end"
-            }))?;
+            });
             ("stream_end", data)
         }
     };
 
-    Ok(Event::default().event(event_name).data(data))
+    Ok((event_name, data))
+}
+
+/// Convert a StreamingEvent to an SSE Event for message submission
+fn streaming_event_to_sse(event: &StreamingEvent) -> Result<Event, Report> {
+    let (event_name, data) = streaming_event_to_json(event)?;
+    Ok(Event::default()
+        .event(event_name)
+        .data(serde_json::to_string(&data)?))
 }
 
 #[derive(serde::Deserialize, ToSchema)]
@@ -1065,10 +1760,25 @@ pub struct RegenerateMessageRequest {
     selected_facet_ids: Vec<String>,
     /// Optional action facet to apply during this generation.
     action_facet: Option<ActionFacetRequest>,
+    #[schema(example = "de")]
+    /// Optional language code the response should be written in. If not provided, the detected
+    /// language of the user message, the language of the chat or the user's preferred language
+    /// is used.
+    #[schema(nullable = false)]
+    response_language: Option<String>,
 }
 
-#[derive(Serialize, ToSchema)]
-#[serde(tag = "message_type")]
+#[derive(serde::Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ContinueMessageRequest {
+    #[schema(example = "00000000-0000-0000-0000-000000000000")]
+    /// The ID of the assistant message that should be continued. Its generation must have
+    /// stopped because the maximum number of output tokens was reached.
+    current_message_id: Uuid,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(tag = "message_type")]
 #[allow(clippy::large_enum_variant)]
 pub enum RegenerateMessageStreamingResponseMessage {
     #[serde(rename = "assistant_message_started")]
@@ -1083,6 +1793,9 @@ pub enum RegenerateMessageStreamingResponseMessage {
     #[serde(rename = "reasoning_delta")]
     /// Sent whenever a new reasoning chunk is generated by the assistant.
     ReasoningDelta(MessageSubmitStreamingResponseMessageReasoningDelta),
+    #[serde(rename = "tool_call_arguments_delta")]
+    /// Sent whenever a new chunk of the arguments of a tool call is generated by the assistant.
+    ToolCallArgumentsDelta(MessageSubmitStreamingResponseToolCallArgumentsDelta),
     #[serde(rename = "tool_call_proposed")]
     /// Sent when the LLM proposes a tool call to be part of the assistant message.
     ToolCallProposed(MessageSubmitStreamingResponseToolCallProposed),
@@ -1093,6 +1806,15 @@ pub enum RegenerateMessageStreamingResponseMessage {
     /// Sent when the model calls a facet client tool: the turn is suspended
     /// until the client executes it and POSTs the result back.
     ClientToolCall(MessageSubmitStreamingResponseClientToolCall),
+    #[serde(rename = "prompt_injection_warning")]
+    /// Sent when a possible prompt injection was found in a file or tool output.
+    PromptInjectionWarning(MessageSubmitStreamingResponsePromptInjectionWarning),
+    #[serde(rename = "files_unavailable")]
+    /// Sent before the generation when attached files are missing in the storage.
+    FilesUnavailable(MessageSubmitStreamingResponseFilesUnavailable),
+    #[serde(rename = "provider_fallback")]
+    /// Sent before the generation when the requested chat provider is no longer configured.
+    ProviderFallback(MessageSubmitStreamingResponseProviderFallback),
     #[serde(rename = "error")]
     /// Sent when an error occurs during message generation.
     Error(MessageSubmitStreamingResponseError),
@@ -1105,9 +1827,13 @@ impl SendAsSseEvent for RegenerateMessageStreamingResponseMessage {
             Self::AssistantMessageCompleted(_) => "assistant_message_completed",
             Self::TextDelta(_) => "text_delta",
             Self::ReasoningDelta(_) => "reasoning_delta",
+            Self::ToolCallArgumentsDelta(_) => "tool_call_arguments_delta",
             Self::ToolCallProposed(_) => "tool_call_proposed",
             Self::ToolCallUpdate(_) => "tool_call_update",
             Self::ClientToolCall(_) => "client_tool_call",
+            Self::PromptInjectionWarning(_) => "prompt_injection_warning",
+            Self::FilesUnavailable(_) => "files_unavailable",
+            Self::ProviderFallback(_) => "provider_fallback",
             Self::Error(_) => "error",
         }
     }
@@ -1149,6 +1875,14 @@ impl From<MessageSubmitStreamingResponseMessageReasoningDelta>
     }
 }
 
+impl From<MessageSubmitStreamingResponseToolCallArgumentsDelta>
+    for RegenerateMessageStreamingResponseMessage
+{
+    fn from(value: MessageSubmitStreamingResponseToolCallArgumentsDelta) -> Self {
+        RegenerateMessageStreamingResponseMessage::ToolCallArgumentsDelta(value)
+    }
+}
+
 impl From<MessageSubmitStreamingResponseToolCallProposed>
     for RegenerateMessageStreamingResponseMessage
 {
@@ -1173,6 +1907,30 @@ impl From<MessageSubmitStreamingResponseToolCallUpdate>
     }
 }
 
+impl From<MessageSubmitStreamingResponsePromptInjectionWarning>
+    for RegenerateMessageStreamingResponseMessage
+{
+    fn from(value: MessageSubmitStreamingResponsePromptInjectionWarning) -> Self {
+        RegenerateMessageStreamingResponseMessage::PromptInjectionWarning(value)
+    }
+}
+
+impl From<MessageSubmitStreamingResponseFilesUnavailable>
+    for RegenerateMessageStreamingResponseMessage
+{
+    fn from(value: MessageSubmitStreamingResponseFilesUnavailable) -> Self {
+        RegenerateMessageStreamingResponseMessage::FilesUnavailable(value)
+    }
+}
+
+impl From<MessageSubmitStreamingResponseProviderFallback>
+    for RegenerateMessageStreamingResponseMessage
+{
+    fn from(value: MessageSubmitStreamingResponseProviderFallback) -> Self {
+        RegenerateMessageStreamingResponseMessage::ProviderFallback(value)
+    }
+}
+
 impl From<MessageSubmitStreamingResponseError> for RegenerateMessageStreamingResponseMessage {
     fn from(value: MessageSubmitStreamingResponseError) -> Self {
         RegenerateMessageStreamingResponseMessage::Error(value)
@@ -1201,6 +1959,12 @@ pub struct EditMessageRequest {
     selected_facet_ids: Vec<String>,
     /// Optional action facet to apply during this generation.
     action_facet: Option<ActionFacetRequest>,
+    #[schema(example = "de")]
+    /// Optional language code the response should be written in. If not provided, the detected
+    /// language of the user message, the language of the chat or the user's preferred language
+    /// is used.
+    #[schema(nullable = false)]
+    response_language: Option<String>,
 }
 
 #[derive(serde::Deserialize, ToSchema)]
@@ -1287,6 +2051,9 @@ pub enum EditMessageStreamingResponseMessage {
     #[serde(rename = "reasoning_delta")]
     /// Sent whenever a new reasoning chunk is generated by the assistant.
     ReasoningDelta(MessageSubmitStreamingResponseMessageReasoningDelta),
+    #[serde(rename = "tool_call_arguments_delta")]
+    /// Sent whenever a new chunk of the arguments of a tool call is generated by the assistant.
+    ToolCallArgumentsDelta(MessageSubmitStreamingResponseToolCallArgumentsDelta),
     #[serde(rename = "tool_call_proposed")]
     /// Sent when the LLM proposes a tool call to be part of the assistant message.
     ToolCallProposed(MessageSubmitStreamingResponseToolCallProposed),
@@ -1297,6 +2064,15 @@ pub enum EditMessageStreamingResponseMessage {
     /// Sent when the model calls a facet client tool: the turn is suspended
     /// until the client executes it and POSTs the result back.
     ClientToolCall(MessageSubmitStreamingResponseClientToolCall),
+    #[serde(rename = "prompt_injection_warning")]
+    /// Sent when a possible prompt injection was found in a file or tool output.
+    PromptInjectionWarning(MessageSubmitStreamingResponsePromptInjectionWarning),
+    #[serde(rename = "files_unavailable")]
+    /// Sent before the generation when attached files are missing in the storage.
+    FilesUnavailable(MessageSubmitStreamingResponseFilesUnavailable),
+    #[serde(rename = "provider_fallback")]
+    /// Sent before the generation when the requested chat provider is no longer configured.
+    ProviderFallback(MessageSubmitStreamingResponseProviderFallback),
     #[serde(rename = "error")]
     /// Sent when an error occurs during message generation.
     Error(MessageSubmitStreamingResponseError),
@@ -1312,9 +2088,13 @@ impl SendAsSseEvent for EditMessageStreamingResponseMessage {
             Self::AssistantMessageCompleted(_) => "assistant_message_completed",
             Self::TextDelta(_) => "text_delta",
             Self::ReasoningDelta(_) => "reasoning_delta",
+            Self::ToolCallArgumentsDelta(_) => "tool_call_arguments_delta",
             Self::ToolCallProposed(_) => "tool_call_proposed",
             Self::ToolCallUpdate(_) => "tool_call_update",
             Self::ClientToolCall(_) => "client_tool_call",
+            Self::PromptInjectionWarning(_) => "prompt_injection_warning",
+            Self::FilesUnavailable(_) => "files_unavailable",
+            Self::ProviderFallback(_) => "provider_fallback",
             Self::Error(_) => "error",
             Self::UserMessageSaved(_) => "user_message_saved",
         }
@@ -1359,6 +2139,14 @@ impl From<MessageSubmitStreamingResponseMessageReasoningDelta>
     }
 }
 
+impl From<MessageSubmitStreamingResponseToolCallArgumentsDelta>
+    for EditMessageStreamingResponseMessage
+{
+    fn from(value: MessageSubmitStreamingResponseToolCallArgumentsDelta) -> Self {
+        EditMessageStreamingResponseMessage::ToolCallArgumentsDelta(value)
+    }
+}
+
 impl From<MessageSubmitStreamingResponseToolCallProposed> for EditMessageStreamingResponseMessage {
     fn from(value: MessageSubmitStreamingResponseToolCallProposed) -> Self {
         EditMessageStreamingResponseMessage::ToolCallProposed(value)
@@ -1377,12 +2165,88 @@ impl From<MessageSubmitStreamingResponseToolCallUpdate> for EditMessageStreaming
     }
 }
 
+impl From<MessageSubmitStreamingResponsePromptInjectionWarning>
+    for EditMessageStreamingResponseMessage
+{
+    fn from(value: MessageSubmitStreamingResponsePromptInjectionWarning) -> Self {
+        EditMessageStreamingResponseMessage::PromptInjectionWarning(value)
+    }
+}
+
+impl From<MessageSubmitStreamingResponseFilesUnavailable> for EditMessageStreamingResponseMessage {
+    fn from(value: MessageSubmitStreamingResponseFilesUnavailable) -> Self {
+        EditMessageStreamingResponseMessage::FilesUnavailable(value)
+    }
+}
+
+impl From<MessageSubmitStreamingResponseProviderFallback> for EditMessageStreamingResponseMessage {
+    fn from(value: MessageSubmitStreamingResponseProviderFallback) -> Self {
+        EditMessageStreamingResponseMessage::ProviderFallback(value)
+    }
+}
+
 impl From<MessageSubmitStreamingResponseError> for EditMessageStreamingResponseMessage {
     fn from(value: MessageSubmitStreamingResponseError) -> Self {
         EditMessageStreamingResponseMessage::Error(value)
     }
 }
 
+fn submitted_message_raw_json(
+    role: MessageSubmitRole,
+    user_message: &str,
+    user_id: &str,
+) -> JsonValue {
+    json!({
+        "role": role.message_role().to_string(),
+        "content": vec![json!({
+            "content_type": "text",
+            "text": submitted_message_text(user_message)})],
+        "name": user_id
+    })
+}
+
+/// The text a submitted message is stored with.
+///
+/// Text that only consists of whitespace is stored as empty, so that it is left out of the prompt.
+fn submitted_message_text(user_message: &str) -> &str {
+    if user_message.trim().is_empty() {
+        ""
+    } else {
+        user_message
+    }
+}
+
+/// Validate that a submitted message has text, or at least one attached file.
+///
+/// Shared by all paths that submit user messages, so that no generation is started for a message
+/// without content.
+pub(crate) fn validate_message_content(
+    user_message: &str,
+    input_file_ids: &[Uuid],
+) -> Result<(), (axum::http::StatusCode, String)> {
+    if user_message.trim().is_empty() && input_file_ids.is_empty() {
+        return Err((
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+            "The message must contain text or at least one attached file".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The placeholder that is displayed in place of the text of a message that only carries files.
+fn file_only_message_placeholder(
+    app_state: &AppState,
+    user_message: &str,
+    input_file_ids: &[Uuid],
+) -> Option<String> {
+    (user_message.trim().is_empty() && !input_file_ids.is_empty())
+        .then(|| app_state.config.chat.file_only_message_placeholder.clone())
+}
+
+/// Save the submitted message, and send the `user_message_saved` event.
+///
+/// If the chat was created for this message, it is provisional until the message is saved, so the
+/// `chat_created` event is only sent, right before `user_message_saved`, once the chat is durable.
 #[allow(clippy::too_many_arguments)]
 async fn bg_stream_save_user_message(
     task: &Arc<StreamingTask>,
@@ -1390,18 +2254,25 @@ async fn bg_stream_save_user_message(
     policy: &PolicyEngine,
     me_user: &MeProfile,
     chat: &chats::Model,
+    chat_was_created: bool,
     previous_message_id: Option<&Uuid>,
+    role: MessageSubmitRole,
     user_message: &str,
     input_files_ids: &[Uuid],
+    auto_attached_file_id: Option<Uuid>,
     input_parameters: Option<crate::models::message::InputParameters>,
 ) -> Result<messages::Model, Report> {
-    let user_message_json = json!({
-        "role": "user",
-        "content": vec![json!({
-            "content_type": "text",
-            "text": user_message.to_owned()})],
-        "name": me_user.id
-    });
+    let user_message_json = split_long_text_parts_in_raw_message(
+        &app_state.config.chat.long_messages,
+        submitted_message_raw_json(role, user_message, &me_user.id),
+    )?;
+    let user_message_json = spill_oversized_raw_message(
+        app_state.default_file_storage_provider(),
+        &app_state.config.chat.content_spillover,
+        user_message_json,
+    )
+    .await
+    .wrap_err("Failed to spill oversized user message content")?;
 
     let saved_user_message = submit_message(
         &app_state.db,
@@ -1412,20 +2283,33 @@ async fn bg_stream_save_user_message(
         previous_message_id,
         None,
         None,
+        None,
         input_files_ids,
         None,
         None,
         input_parameters,
+        false,
     )
     .await
     .wrap_err("Failed to submit user message")?;
 
+    if chat_was_created {
+        tracing::info!("Sending ChatCreated event for chat_id: {}", chat.id);
+        task.send_event(StreamingEvent::ChatCreated { chat_id: chat.id })
+            .await
+            .map_err(Report::msg)?;
+        app_state
+            .user_events
+            .publish(&me_user.id, UserEvent::ChatCreated { chat_id: chat.id });
+    }
+
     let saved_user_message_wrapped = ChatMessage::from_model(saved_user_message.clone())
         .wrap_err("Failed to convert user message")?;
 
     task.send_event(StreamingEvent::UserMessageSaved {
         message_id: saved_user_message.id,
         message: saved_user_message_wrapped,
+        auto_attached_file_id,
     })
     .await
     .map_err(Report::msg)?;
@@ -1452,6 +2336,12 @@ pub struct PreparedChatRequest {
     generation_request_context: GenerationRequestContext,
     // MCP servers that were unavailable while listing tools for this request.
     mcp_servers_unavailable: Vec<String>,
+    // Estimated prompt tokens per part of the prompt, if they could be counted.
+    token_breakdown: Option<TokenBreakdown>,
+    // Manifest of the prompts the generation input was composed of.
+    prompt_manifest: PromptManifest,
+    // Files whose contents are unavailable because their object is missing in the storage.
+    unavailable_files: Vec<UnavailableFile>,
     // Filtered MCP tools available to this request, including server routing info.
     available_mcp_tools: Vec<crate::services::mcp_session_manager::ManagedTool>,
     // Park budgets of the client tools OFFERED to this request, keyed by the
@@ -1463,6 +2353,8 @@ pub struct PreparedChatRequest {
     chat_request: ChatRequest,
     // Prepared `genai` `ChatOptions` (e.g. reasoning effort)
     chat_options: ChatOptions,
+    // Model settings of the chat provider after applying the selected facets
+    effective_model_settings: ModelSettings,
 }
 
 impl PreparedChatRequest {
@@ -1771,6 +2663,11 @@ pub(crate) fn active_hidden_facet_ids(
     ids
 }
 
+/// Resolves the facets selected for a generation.
+///
+/// Facets pinned on the assistant of the chat are always unioned into the selection (ahead of
+/// the requested ones, so they win when only a single facet may be selected), regardless of
+/// what the client sent.
 pub(crate) fn resolve_effective_selected_facet_ids(
     config: &ExperimentalFacetsConfig,
     requested_facet_ids: &[String],
@@ -1785,12 +2682,23 @@ pub(crate) fn resolve_effective_selected_facet_ids(
     } else {
         requested_facet_ids
     };
+    let pinned_facet_ids = assistant_config
+        .and_then(|assistant| assistant.pinned_facet_ids.as_deref())
+        .unwrap_or(&[]);
 
-    sanitize_selected_facet_ids(config, selected_facet_ids)
+    let selected_facet_ids: Vec<String> = pinned_facet_ids
+        .iter()
+        .chain(selected_facet_ids)
+        .cloned()
+        .collect();
+    sanitize_selected_facet_ids(config, &selected_facet_ids)
 }
 
 /// Prepares a chat request for LLM generation.
 ///
+/// If `unsaved_user_message` is provided, it is used as the just submitted user message
+/// instead of loading it from the database (used for dry runs).
+///
 /// This function is boxed to reduce stack usage, as it has a deep async call chain.
 #[allow(clippy::too_many_arguments)]
 fn prepare_chat_request<'a>(
@@ -1800,28 +2708,19 @@ fn prepare_chat_request<'a>(
     user_input: PromptCompositionUserInput,
     generation_request_context: GenerationRequestContext,
     me_profile_input: &'a MeProfileChatRequestInput<'a>,
+    unsaved_user_message: Option<messages::Model>,
 ) -> std::pin::Pin<
     Box<dyn std::future::Future<Output = Result<PreparedChatRequest, Report>> + Send + 'a>,
 > {
     Box::pin(async move {
-        // Create subject from chat owner with organization info if available
-        let subject = if me_profile_input.organization_user_id.is_some()
-            || !me_profile_input.organization_group_ids.is_empty()
-        {
-            crate::policy::types::Subject::UserWithOrganizationInfo {
-                id: chat.owner_user_id.clone(),
-                organization_user_id: me_profile_input.organization_user_id.map(String::from),
-                organization_group_ids: me_profile_input.organization_group_ids.to_vec(),
-            }
-        } else {
-            crate::policy::types::Subject::User(chat.owner_user_id.clone())
-        };
+        let subject = chat_owner_subject(chat, me_profile_input);
 
         // Create the dependency adapters
         let message_repo = DatabaseMessageRepository {
             conn: &app_state.db,
             policy,
             subject: &subject,
+            generation_input_archive: &app_state.generation_input_archive,
         };
         let file_resolver = AppStateFileResolver {
             app_state,
@@ -1840,6 +2739,83 @@ fn prepare_chat_request<'a>(
             policy,
             &subject,
             chat,
+            app_state.config.assistants.freeze_assistant_config,
+        )
+        .await?;
+
+        match unsaved_user_message {
+            Some(synthetic_message) => {
+                let message_repo = SyntheticMessageRepository {
+                    base: message_repo,
+                    synthetic_message,
+                };
+                prepare_chat_request_with_adapters(
+                    app_state,
+                    policy,
+                    chat,
+                    user_input,
+                    generation_request_context,
+                    me_profile_input,
+                    assistant_config,
+                    &message_repo,
+                    &file_resolver,
+                    &prompt_provider,
+                )
+                .await
+            }
+            None => {
+                prepare_chat_request_with_adapters(
+                    app_state,
+                    policy,
+                    chat,
+                    user_input,
+                    generation_request_context,
+                    me_profile_input,
+                    assistant_config,
+                    &message_repo,
+                    &file_resolver,
+                    &prompt_provider,
+                )
+                .await
+            }
+        }
+    })
+}
+
+/// Prepares a chat request for LLM generation in an ephemeral chat, whose messages are only held
+/// in memory.
+///
+/// This function is boxed to reduce stack usage, as it has a deep async call chain.
+fn prepare_ephemeral_chat_request<'a>(
+    app_state: &'a AppState,
+    policy: &'a PolicyEngine,
+    chat: &'a chats::Model,
+    messages: &'a [messages::Model],
+    user_input: PromptCompositionUserInput,
+    generation_request_context: GenerationRequestContext,
+    me_profile_input: &'a MeProfileChatRequestInput<'a>,
+) -> std::pin::Pin<
+    Box<dyn std::future::Future<Output = Result<PreparedChatRequest, Report>> + Send + 'a>,
+> {
+    Box::pin(async move {
+        let subject = chat_owner_subject(chat, me_profile_input);
+        let message_repo = InMemoryMessageRepository { messages };
+        let file_resolver = AppStateFileResolver {
+            app_state,
+            access_token: me_profile_input.access_token,
+        };
+        let prompt_provider = AppStatePromptProvider {
+            app_state,
+            policy,
+            subject: &subject,
+            access_token: me_profile_input.access_token,
+        };
+        let assistant_config = crate::models::chat::get_chat_assistant_configuration(
+            &app_state.db,
+            policy,
+            &subject,
+            chat,
+            app_state.config.assistants.freeze_assistant_config,
         )
         .await?;
 
@@ -1859,6 +2835,27 @@ fn prepare_chat_request<'a>(
     })
 }
 
+/// The owner of a chat as the subject of the policy checks of its generations, with organization
+/// info if available.
+fn chat_owner_subject(
+    chat: &chats::Model,
+    me_profile_input: &MeProfileChatRequestInput<'_>,
+) -> crate::policy::types::Subject {
+    if chat.organization_id.is_some()
+        || me_profile_input.organization_user_id.is_some()
+        || !me_profile_input.organization_group_ids.is_empty()
+    {
+        crate::policy::types::Subject::UserWithOrganizationInfo {
+            id: chat.owner_user_id.clone(),
+            organization_id: chat.organization_id.clone(),
+            organization_user_id: me_profile_input.organization_user_id.map(String::from),
+            organization_group_ids: me_profile_input.organization_group_ids.to_vec(),
+        }
+    } else {
+        crate::policy::types::Subject::User(chat.owner_user_id.clone())
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub(crate) async fn prepare_chat_request_with_adapters(
     app_state: &AppState,
@@ -1899,22 +2896,27 @@ pub(crate) async fn prepare_chat_request_with_adapters(
 
     // Determine the chat provider to use
     let requested_chat_provider_id = user_input.requested_chat_provider_id.as_deref();
-    let effective_chat_provider_id = requested_chat_provider_id.or_else(|| {
-        assistant_config
-            .as_ref()
-            .and_then(|a| a.default_chat_provider.as_deref())
-    });
+    let assistant_chat_provider_id = assistant_config
+        .as_ref()
+        .and_then(|a| a.default_chat_provider.as_deref());
+    let effective_chat_provider_id = requested_chat_provider_id.or(assistant_chat_provider_id);
+    // Chats may still refer to chat providers that were removed from the configuration, in which
+    // case the generation falls back to another chat provider.
+    let fallback_from_chat_provider_id = effective_chat_provider_id
+        .filter(|chat_provider_id| !app_state.config.is_known_chat_provider_id(chat_provider_id));
 
     // Resolve chat provider configuration
     let ChatProviderConfigWithId {
         chat_provider_config,
         chat_provider_id,
+        chat_provider_group_id,
     } = app_state
         .chat_provider_for_chatcompletion(
             policy,
             &me_profile_input.subject,
             me_profile_input.user_groups,
             effective_chat_provider_id,
+            assistant_chat_provider_id,
         )
         .await?;
 
@@ -1977,7 +2979,7 @@ pub(crate) async fn prepare_chat_request_with_adapters(
     let mcp_auth_context = McpRequestAuthContext {
         app_state: Some(app_state),
         user_id: me_profile_input.user_id,
-        oidc_token: Some(me_profile_input.oidc_token),
+        oidc_token: me_profile_input.oidc_token,
         access_token: me_profile_input.access_token,
     };
 
@@ -1997,16 +2999,34 @@ pub(crate) async fn prepare_chat_request_with_adapters(
         &generation_mcp_tools,
     );
 
+    let language_detection_config = app_state.message_language_detection_config();
+    let detected_language = if language_detection_config.enabled {
+        let just_submitted_user_message = message_repo
+            .get_message_by_id(&user_input.just_submitted_user_message_id)
+            .await?;
+        get_input_detected_language_from_message(&just_submitted_user_message)?
+    } else {
+        None
+    };
+    let response_language = resolve_response_language(
+        user_input.requested_response_language.as_deref(),
+        detected_language.as_ref(),
+        language_detection_config.min_confidence,
+        chat.response_language.as_deref(),
+        me_profile_input.preferred_language,
+    );
+
     // Use the new prompt composition service
-    let generation_input_messages = compose_prompt_messages(
+    let (generation_input_messages, prompt_manifest) = compose_prompt_messages(
         message_repo,
         file_resolver,
         prompt_provider,
         chat,
         &user_input,
         &chat_provider_config,
+        chat_provider_id.as_str(),
         &app_state.config.experimental_facets,
-        Some(me_profile_input.preferred_language),
+        Some(response_language.code.as_str()),
         me_profile_input.user_preference_nickname,
         me_profile_input.user_preference_job_title,
         me_profile_input.user_preference_assistant_custom_instructions,
@@ -2014,16 +3034,18 @@ pub(crate) async fn prepare_chat_request_with_adapters(
         Some(&facet_tool_expansions),
         &app_state.config.action_facets.facets,
         generation_request_context.platform.as_deref(),
+        &app_state.config.chat.file_recency,
     )
     .await?;
 
     // Resolve TextFilePointer to Text by extracting file contents JIT
-    let resolved_generation_input_messages = resolve_file_pointers_in_generation_input(
-        app_state,
-        generation_input_messages.clone(),
-        me_profile_input.access_token,
-    )
-    .await?;
+    let (resolved_generation_input_messages, unavailable_files) =
+        resolve_file_pointers_in_generation_input(
+            app_state,
+            generation_input_messages.clone(),
+            me_profile_input.access_token,
+        )
+        .await?;
 
     // Render any ActionFacetMarker entries against the current config.
     // Saved snapshot keeps the markers; only the about-to-be-sent
@@ -2035,6 +3057,16 @@ pub(crate) async fn prepare_chat_request_with_adapters(
         resolved_generation_input_messages,
     );
 
+    // The breakdown is informational only, so failing to count must not fail the generation.
+    let token_breakdown =
+        match count_token_breakdown(app_state, &resolved_generation_input_messages).await {
+            Ok(token_breakdown) => Some(token_breakdown),
+            Err(error) => {
+                warn_and_capture_error("count prompt token breakdown", &error);
+                None
+            }
+        };
+
     // Build genai ChatRequest (messages + tools) + ChatOptions
     let effective_model_settings = build_model_settings_for_facets(
         &chat_provider_config.model_settings,
@@ -2045,6 +3077,12 @@ pub(crate) async fn prepare_chat_request_with_adapters(
     let mut chat_request = resolved_generation_input_messages
         .clone()
         .into_chat_request();
+    if chat_provider_config
+        .model_capabilities
+        .supports_prompt_caching
+    {
+        mark_stable_prefix_as_cacheable(&mut chat_request, &generation_input_messages);
+    }
     let did_prior_assistant_chat_provider_change = prior_assistant_chat_provider_changed(
         message_repo,
         &user_input.just_submitted_user_message_id,
@@ -2192,9 +3230,7 @@ pub(crate) async fn prepare_chat_request_with_adapters(
         chat_request = chat_request.with_store(false);
         if !did_prior_assistant_chat_provider_change {
             let reasoning_replay_messages = collect_reasoning_replay_messages(
-                app_state,
-                policy,
-                &me_profile_input.subject,
+                message_repo,
                 &user_input.just_submitted_user_message_id,
                 chat_provider_id.as_str(),
                 effective_model_settings.compat_no_replay_summary,
@@ -2215,7 +3251,17 @@ pub(crate) async fn prepare_chat_request_with_adapters(
 
     // Create generation parameters with the determined chat provider ID
     let generation_parameters = GenerationParameters {
+        requested_chat_provider_id: effective_chat_provider_id
+            .filter(|requested| {
+                app_state
+                    .config
+                    .chat_provider_aliases
+                    .contains_key(*requested)
+            })
+            .map(String::from),
+        fallback_from_chat_provider_id: fallback_from_chat_provider_id.map(String::from),
         generation_chat_provider_id: Some(chat_provider_id),
+        chat_provider_group_id,
         request_context: Some(generation_request_context.clone()),
         selected_facets: build_selected_facets(
             &app_state.config.experimental_facets,
@@ -2223,6 +3269,7 @@ pub(crate) async fn prepare_chat_request_with_adapters(
         ),
         action_facet_id: user_input.action_facet.as_ref().map(|af| af.id.clone()),
         action_facet_args: user_input.action_facet.as_ref().map(|af| af.args.clone()),
+        response_language: Some(response_language),
     };
 
     // Return the unresolved version for saving to DB (to avoid duplicating file contents)
@@ -2232,10 +3279,20 @@ pub(crate) async fn prepare_chat_request_with_adapters(
         generation_parameters,
         generation_request_context,
         mcp_servers_unavailable: tool_discovery.unavailable_server_ids,
+        token_breakdown,
+        // The full text of the prompts is only kept if configured, the hashes are enough to
+        // trace which prompts were used
+        prompt_manifest: if app_state.config.admin.prompt_manifest.store_full_text {
+            prompt_manifest
+        } else {
+            prompt_manifest.without_text()
+        },
+        unavailable_files,
         available_mcp_tools: generation_mcp_tools.clone(),
         offered_client_tool_timeouts,
         chat_request,
         chat_options,
+        effective_model_settings,
     })
 }
 
@@ -2266,20 +3323,225 @@ fn append_reasoning_delta_part(content: &mut Vec<ContentPart>, new_text: String)
     }
 }
 
-fn insert_reasoning_part_before_text(content: &mut Vec<ContentPart>, text: String) -> usize {
-    let insertion_index = content
-        .iter()
-        .position(|part| matches!(part, ContentPart::Text(_)))
-        .unwrap_or(content.len());
+/// Periodically saves the content streamed so far as the draft of the assistant message, so that
+/// a crash of the process doesn't lose the whole answer.
+///
+/// A draft is saved once `content_draft_flush_interval_secs` have passed or
+/// `content_draft_flush_deltas` deltas were streamed since the last save, but never more often
+/// than every `content_draft_min_flush_interval_ms`.
+struct ContentDraftWriter {
+    message_id: Uuid,
+    flush_interval: Option<Duration>,
+    flush_deltas: u64,
+    min_flush_interval: Duration,
+    last_flush: Instant,
+    pending_deltas: u64,
+}
 
-    content.insert(
-        insertion_index,
-        ContentPart::Reasoning(ContentPartReasoning {
-            text,
-            ..Default::default()
-        }),
-    );
-    insertion_index
+impl ContentDraftWriter {
+    fn new(config: &GenerationStatusConfig, message_id: Uuid) -> Self {
+        Self {
+            message_id,
+            flush_interval: (config.content_draft_flush_interval_secs > 0)
+                .then(|| Duration::from_secs(config.content_draft_flush_interval_secs)),
+            flush_deltas: config.content_draft_flush_deltas,
+            min_flush_interval: Duration::from_millis(config.content_draft_min_flush_interval_ms),
+            last_flush: Instant::now(),
+            pending_deltas: 0,
+        }
+    }
+
+    fn should_flush(&self, now: Instant) -> bool {
+        let Some(flush_interval) = self.flush_interval else {
+            return false;
+        };
+        let since_last_flush = now.duration_since(self.last_flush);
+        self.pending_deltas > 0
+            && since_last_flush >= self.min_flush_interval
+            && (since_last_flush >= flush_interval
+                || (self.flush_deltas > 0 && self.pending_deltas >= self.flush_deltas))
+    }
+
+    /// Record a streamed delta, saving the draft if it is due.
+    async fn record_delta(&mut self, conn: &sea_orm::DatabaseConnection, content: &[ContentPart]) {
+        self.pending_deltas += 1;
+        let now = Instant::now();
+        if !self.should_flush(now) {
+            return;
+        }
+        if let Err(error) = save_message_content_draft(conn, &self.message_id, content).await {
+            warn_and_capture_error("save assistant message content draft", &error);
+        }
+        self.last_flush = now;
+        self.pending_deltas = 0;
+    }
+}
+
+/// Turns the streamed chunks of tool calls into `tool_call_arguments_delta` fragments, sending
+/// at most one fragment per tool call every `tool_call_arguments_delta_interval_ms`.
+///
+/// Depending on the chat provider, a chunk contains either the arguments accumulated so far or
+/// only the newly generated part, so the full arguments are tracked per tool call and fragments
+/// are derived from what was already sent.
+struct ToolCallArgumentsDeltaCoalescer {
+    interval: Duration,
+    calls: Vec<StreamedToolCallArguments>,
+}
+
+struct StreamedToolCallArguments {
+    tool_call_id: String,
+    arguments: String,
+    sent_len: usize,
+    last_sent: Option<Instant>,
+}
+
+impl ToolCallArgumentsDeltaCoalescer {
+    fn new(config: &ChatConfig) -> Self {
+        Self {
+            interval: Duration::from_millis(config.tool_call_arguments_delta_interval_ms),
+            calls: Vec::new(),
+        }
+    }
+
+    /// Record a streamed chunk of the arguments of a tool call, returning the fragment to send if
+    /// one is due.
+    fn observe(
+        &mut self,
+        tool_call_id: &str,
+        fn_arguments: &JsonValue,
+        now: Instant,
+    ) -> Option<String> {
+        let chunk = match fn_arguments {
+            JsonValue::Null => String::new(),
+            JsonValue::String(chunk) => chunk.clone(),
+            other => other.to_string(),
+        };
+        let call = match self
+            .calls
+            .iter_mut()
+            .position(|call| call.tool_call_id == tool_call_id)
+        {
+            Some(index) => &mut self.calls[index],
+            None => {
+                self.calls.push(StreamedToolCallArguments {
+                    tool_call_id: tool_call_id.to_string(),
+                    arguments: String::new(),
+                    sent_len: 0,
+                    last_sent: None,
+                });
+                self.calls.last_mut().expect("The tool call was just added")
+            }
+        };
+        if chunk.starts_with(&call.arguments) {
+            call.arguments = chunk;
+        } else {
+            call.arguments.push_str(&chunk);
+        }
+
+        if call.arguments.len() == call.sent_len
+            || call
+                .last_sent
+                .is_some_and(|last_sent| now.duration_since(last_sent) < self.interval)
+        {
+            return None;
+        }
+        call.last_sent = Some(now);
+        Some(call.take_pending())
+    }
+
+    /// Return the fragments that were held back, as `(tool_call_id, args_fragment)`.
+    fn flush(&mut self) -> Vec<(String, String)> {
+        self.calls
+            .iter_mut()
+            .filter(|call| call.arguments.len() > call.sent_len)
+            .map(|call| (call.tool_call_id.clone(), call.take_pending()))
+            .collect()
+    }
+}
+
+impl StreamedToolCallArguments {
+    fn take_pending(&mut self) -> String {
+        let fragment = self.arguments[self.sent_len..].to_string();
+        self.sent_len = self.arguments.len();
+        fragment
+    }
+}
+
+/// Open a new reasoning part for reasoning that is only captured at the end of a turn.
+///
+/// The part is appended, also if text of the turn was already streamed, as parts keep the
+/// content index they were announced with.
+fn append_reasoning_part(content: &mut Vec<ContentPart>, text: String) -> usize {
+    content.push(ContentPart::Reasoning(ContentPartReasoning {
+        text,
+        ..Default::default()
+    }));
+    content.len() - 1
+}
+
+/// Open a new text part for text that was not streamed, e.g. text the chat provider only
+/// returned with the end of the response.
+fn append_text_part(content: &mut Vec<ContentPart>, text: String) -> usize {
+    content.push(ContentPart::Text(ContentPartText { text }));
+    content.len() - 1
+}
+
+/// Kind of a content part that was announced to clients with a `content_index`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AnnouncedContentPart {
+    Text,
+    Reasoning,
+    ToolUse { tool_call_id: String },
+}
+
+/// The content indices announced by the events of a generation.
+///
+/// Content indices are allocated when a part begins, i.e. when a text or reasoning segment is
+/// opened or a tool call is proposed. All later events of the part refer to the same index, and
+/// the part is stored at that position of the content of the message. Clients can rely on
+/// assembling the content from the events in any order.
+#[derive(Debug, Default)]
+struct AnnouncedContentParts {
+    parts: BTreeMap<usize, AnnouncedContentPart>,
+}
+
+impl AnnouncedContentParts {
+    fn announce_text(&mut self, content_index: usize) {
+        self.parts.insert(content_index, AnnouncedContentPart::Text);
+    }
+
+    fn announce_reasoning(&mut self, content_index: usize) {
+        self.parts
+            .insert(content_index, AnnouncedContentPart::Reasoning);
+    }
+
+    fn announce_tool_use(&mut self, content_index: usize, tool_call_id: &str) {
+        self.parts.insert(
+            content_index,
+            AnnouncedContentPart::ToolUse {
+                tool_call_id: tool_call_id.to_string(),
+            },
+        );
+    }
+
+    /// Describe the announced parts that are not stored at their content index.
+    fn mismatches(&self, content: &[ContentPart]) -> Vec<String> {
+        self.parts
+            .iter()
+            .filter_map(|(content_index, announced)| {
+                let matches = match (announced, content.get(*content_index)) {
+                    (AnnouncedContentPart::Text, Some(ContentPart::Text(_))) => true,
+                    (AnnouncedContentPart::Reasoning, Some(ContentPart::Reasoning(_))) => true,
+                    (
+                        AnnouncedContentPart::ToolUse { tool_call_id },
+                        Some(ContentPart::ToolUse(tool_use)),
+                    ) => tool_use.tool_call_id == *tool_call_id,
+                    _ => false,
+                };
+                (!matches).then(|| format!("{announced:?} announced at {content_index}"))
+            })
+            .collect()
+    }
 }
 
 struct HallucinationSuppressionState {
@@ -2321,19 +3583,109 @@ fn hallucination_loop_error_event(message_id: Uuid) -> MessageSubmitStreamingRes
     }
 }
 
-fn log_chat_completion_generation_error(
-    chat_provider_id: &str,
+fn compliance_policy_error_event(
     message_id: Uuid,
-    error: &GenerationErrorType,
-) {
-    tracing::error!(
-        chat_provider_id,
-        message_id = %message_id,
-        error = ?error,
-        "Chat completion generation failed"
-    );
-}
-
+    violation: OutputComplianceViolation,
+) -> MessageSubmitStreamingResponseError {
+    MessageSubmitStreamingResponseError {
+        message_id: Some(message_id),
+        error: GenerationErrorType::CompliancePolicy {
+            error_description: COMPLIANCE_POLICY_ERROR_DESCRIPTION.to_string(),
+            matched_rule_id: violation.rule_id,
+        },
+    }
+}
+
+fn transformer_failed_error_event(
+    message_id: Uuid,
+    failure: OutputTransformerFailure,
+) -> MessageSubmitStreamingResponseError {
+    MessageSubmitStreamingResponseError {
+        message_id: Some(message_id),
+        error: GenerationErrorType::TransformerFailed {
+            error_description: TRANSFORMER_FAILED_ERROR_DESCRIPTION.to_string(),
+            transformer: failure.transformer,
+        },
+    }
+}
+
+/// Release the text held back by the output compliance rules and the `streaming_buffered` output
+/// transformers at the end of the streamed text of a turn.
+async fn finish_streamed_output(
+    output_compliance: &mut OutputComplianceFilter,
+    output_transformers: &mut OutputTransformers,
+    message_id: Uuid,
+) -> Result<String, MessageSubmitStreamingResponseError> {
+    let released = output_compliance
+        .finish()
+        .map_err(|violation| compliance_policy_error_event(message_id, violation))?;
+    output_transformers
+        .transform_complete(&released)
+        .await
+        .map_err(|failure| transformer_failed_error_event(message_id, failure))
+}
+
+/// Apply the output compliance rules and then the `streaming_buffered` output transformers to a
+/// complete text.
+async fn filter_complete_output(
+    output_compliance: &mut OutputComplianceFilter,
+    output_transformers: &mut OutputTransformers,
+    text: &str,
+    message_id: Uuid,
+) -> Result<String, MessageSubmitStreamingResponseError> {
+    let text = output_compliance
+        .filter_complete(text)
+        .map_err(|violation| compliance_policy_error_event(message_id, violation))?;
+    output_transformers
+        .transform_complete(&text)
+        .await
+        .map_err(|failure| transformer_failed_error_event(message_id, failure))
+}
+
+fn log_chat_completion_generation_error(
+    chat_provider_id: &str,
+    message_id: Uuid,
+    error: &GenerationErrorType,
+) {
+    tracing::error!(
+        chat_provider_id,
+        message_id = %message_id,
+        error = ?error,
+        "Chat completion generation failed"
+    );
+}
+
+/// Puts the chat provider of a generation into a cooldown if it rejected the generation because
+/// of a rate limit, so that provider selection prefers other providers in the meantime.
+fn record_chat_provider_rate_limit(
+    app_state: &AppState,
+    chat_provider_id: Option<&str>,
+    error: &GenerationErrorType,
+) {
+    let (
+        Some(chat_provider_id),
+        GenerationErrorType::RateLimited {
+            retry_after_seconds,
+            scope,
+            ..
+        },
+    ) = (chat_provider_id, error)
+    else {
+        return;
+    };
+    let cooldown = app_state.chat_provider_rate_limits.record_rate_limit(
+        chat_provider_id,
+        *retry_after_seconds,
+        *scope,
+    );
+    tracing::warn!(
+        chat_provider_id,
+        cooldown_until = %cooldown.until,
+        scope = ?cooldown.scope,
+        "Chat provider is rate limited"
+    );
+}
+
 fn langfuse_model_name(app_state: &AppState, chat_provider_id: Option<&str>) -> String {
     let provider_id = match chat_provider_id {
         Some(provider_id) => Some(provider_id),
@@ -2462,9 +3814,13 @@ async fn stream_generate_chat_completion<
     MSG: SendAsSseEvent
         + From<MessageSubmitStreamingResponseMessageTextDelta>
         + From<MessageSubmitStreamingResponseMessageReasoningDelta>
+        + From<MessageSubmitStreamingResponseToolCallArgumentsDelta>
         + From<MessageSubmitStreamingResponseToolCallProposed>
         + From<MessageSubmitStreamingResponseToolCallUpdate>
         + From<MessageSubmitStreamingResponseClientToolCall>
+        + From<MessageSubmitStreamingResponsePromptInjectionWarning>
+        + From<MessageSubmitStreamingResponseFilesUnavailable>
+        + From<MessageSubmitStreamingResponseProviderFallback>
         + From<MessageSubmitStreamingResponseError>,
 >(
     tx: Sender<Result<Event, Report>>,
@@ -2475,18 +3831,32 @@ async fn stream_generate_chat_completion<
     langfuse_trace_enrichment: LangfuseTraceEnrichment,
     chat_options: ChatOptions,
     assistant_message_id: Uuid,
+    // Content of the assistant message that the generation continues, empty for new messages
+    initial_content: Vec<ContentPart>,
     user_id: String,
     chat_id: Uuid,
     chat_provider_id: Option<&str>,
+    chat_provider_group_id: Option<&str>,
+    // The requested chat provider that is no longer configured, if the generation fell back
+    fallback_from_chat_provider_id: Option<&str>,
     _user_groups: &[String],
     mcp_auth_context: McpRequestAuthContext<'_>,
     mcp_servers_unavailable: Vec<String>,
+    token_breakdown: Option<TokenBreakdown>,
+    prompt_manifest: Option<PromptManifest>,
+    unavailable_files: Vec<UnavailableFile>,
     allowed_tool_names: HashSet<String>,
     available_mcp_tools: Vec<crate::services::mcp_session_manager::ManagedTool>,
     offered_client_tool_timeouts: HashMap<String, Option<u64>>,
     chat_provider_headers_context: &'a ChatProviderHeadersContext<'a>,
     streaming_task: Option<&Arc<StreamingTask>>,
     assistant_id: Option<Uuid>,
+    // Whether a cached generation may be replayed. Regenerations skip the cache, but still
+    // replace the cached generation with the regenerated one.
+    replay_from_generation_cache: bool,
+    // Whether the generation is part of an ephemeral chat. Nothing of it is written to the
+    // database or sent to Langfuse, the provider traffic capture and output transformers.
+    ephemeral: bool,
 ) -> Result<(Vec<ContentPart>, Option<GenerationMetadata>), Report> {
     // Record the real assistant message id on the streaming task. `start_task`
     // only had a placeholder id; client-tool results are routed to a task by
@@ -2494,11 +3864,20 @@ async fn stream_generate_chat_completion<
     // be the real one before any client tool can be called.
     if let Some(task) = streaming_task {
         task.set_message_id(assistant_message_id);
+        app_state
+            .background_tasks
+            .record_generation_message_id(&chat_id, task.generation_id, assistant_message_id)
+            .await;
     }
+    let generation_start = Instant::now();
+    // Recorded before the request is extended with the results of tool calls
+    let offered_tools = offered_tools(&chat_request, &available_mcp_tools);
+    let generation_chat_options = GenerationChatOptions::from_genai(&chat_options);
 
     // Initialize Langfuse tracing if enabled
     let langfuse_enabled = app_state.config.integrations.langfuse.enabled
-        && app_state.config.integrations.langfuse.tracing_enabled;
+        && app_state.config.integrations.langfuse.tracing_enabled
+        && !ephemeral;
 
     let tracing_client = if langfuse_enabled {
         let (_, trace_id) = generate_langfuse_ids();
@@ -2568,8 +3947,38 @@ async fn stream_generate_chat_completion<
     // conflicting calls after the first are answered with an error.
     let mut client_action_already_proposed = false;
 
-    let mut current_message_content: Vec<ContentPart> = vec![];
+    let mut current_message_content: Vec<ContentPart> = initial_content;
+    let mut announced_content_parts = AnnouncedContentParts::default();
     let mut current_turn_chat_request = chat_request.clone();
+    let mut content_draft_writer =
+        ContentDraftWriter::new(&app_state.config.generation_status, assistant_message_id);
+    if ephemeral {
+        content_draft_writer.flush_interval = None;
+    }
+
+    // Protect against prompt injection through file contents and tool outputs
+    let system_prompt = system_prompt_text(&chat_request);
+    let untrusted_content_guard = UntrustedContentGuard::new(&app_state.config.security)
+        .wrap_err("Failed to set up untrusted content guard")?;
+    let mut prompt_injection_warnings =
+        untrusted_content_guard.guard_chat_request(&mut current_turn_chat_request);
+    send_prompt_injection_warnings::<MSG>(
+        &prompt_injection_warnings,
+        assistant_message_id,
+        &tx,
+        streaming_task,
+    )
+    .await?;
+    send_files_unavailable::<MSG>(unavailable_files, assistant_message_id, &tx, streaming_task)
+        .await?;
+    send_provider_fallback::<MSG>(
+        fallback_from_chat_provider_id,
+        chat_provider_group_id.or(chat_provider_id),
+        assistant_message_id,
+        &tx,
+        streaming_task,
+    )
+    .await?;
     let fallback_chat_provider_id = if chat_provider_id.is_none() {
         match app_state.config.determine_chat_provider(None, None) {
             Ok(provider_id) => Some(provider_id),
@@ -2593,6 +4002,18 @@ async fn stream_generate_chat_completion<
         .unwrap_or_default();
     let mut hallucination_suppression =
         HallucinationSuppressionState::new(hallucination_suppression_config);
+    let mut output_compliance = OutputComplianceFilter::new(
+        &app_state.config.guardrails.output_compliance,
+        assistant_id,
+        assistant_message_id,
+    )?;
+    let output_transformer_configs = if ephemeral {
+        &[][..]
+    } else {
+        &app_state.config.chat.output_transformers[..]
+    };
+    let mut output_transformers =
+        OutputTransformers::new(output_transformer_configs, assistant_message_id);
     let available_mcp_tools_by_name: HashMap<
         String,
         crate::services::mcp_session_manager::ManagedTool,
@@ -2606,10 +4027,29 @@ async fn stream_generate_chat_completion<
     let mut total_completion_tokens = 0u32;
     let mut total_total_tokens = 0u32;
     let mut total_reasoning_tokens = 0u32;
+    let mut total_cache_read_tokens = 0u32;
+    let mut total_cache_write_tokens = 0u32;
     let mut captured_reasoning_summary = String::new();
     let mut captured_reasoning_items: Vec<genai::chat::ReasoningItem> = vec![];
     let mut captured_reasoning_item_encrypted_content: Vec<String> = vec![];
-
+    // Stop reason reported by the provider for the latest turn
+    let mut provider_stop_reason: Option<StopReason> = None;
+    // Time to the first text chunk of the latest turn. Only the final turn counts, so that time
+    // spent in earlier tool-call turns and in executing tool calls is excluded.
+    let mut final_turn_time_to_first_token: Option<Duration> = None;
+
+    let otel_trace_id =
+        crate::telemetry::TraceCorrelation::current().map(|correlation| correlation.trace_id);
+    let mut provider_capture = if ephemeral {
+        None
+    } else {
+        ProviderTrafficCapture::start(
+            &app_state.config.debug.capture_provider_traffic,
+            otel_trace_id.as_deref(),
+            assistant_message_id,
+            chat_provider_id,
+        )
+    };
     let build_generation_metadata =
         |total_prompt_tokens: u32,
          total_completion_tokens: u32,
@@ -2629,9 +4069,12 @@ async fn stream_generate_chat_completion<
                 || reasoning_items.is_some()
                 || reasoning_item_encrypted_content.is_some()
                 || langfuse_trace_id.is_some()
+                || otel_trace_id.is_some()
                 || was_aborted
                 || error.is_some()
                 || !mcp_servers_unavailable.is_empty()
+                || token_breakdown.is_some()
+                || prompt_manifest.is_some()
             {
                 Some(GenerationMetadata {
                     used_prompt_tokens: if total_prompt_tokens > 0 {
@@ -2654,14 +4097,32 @@ async fn stream_generate_chat_completion<
                     } else {
                         None
                     },
+                    used_cache_read_tokens: None,
+                    used_cache_write_tokens: None,
                     reasoning_summary,
                     reasoning_items,
                     reasoning_item_encrypted_content,
                     langfuse_trace_id,
+                    otel_trace_id: otel_trace_id.clone(),
                     was_aborted: was_aborted.then_some(true),
                     error,
                     mcp_servers_unavailable: (!mcp_servers_unavailable.is_empty())
                         .then(|| mcp_servers_unavailable.clone()),
+                    token_breakdown: token_breakdown.clone(),
+                    prompt_manifest: prompt_manifest.clone(),
+                    renderable_blocks: None,
+                    prompt_injection_warnings: None,
+                    output_compliance_matches: None,
+                    output_transformations: None,
+                    stop_reason: None,
+                    time_to_first_token_ms: None,
+                    generation_duration_ms: None,
+                    moderation: None,
+                    provider_capture_id: None,
+                    cache_hit: None,
+                    markdown_normalized: None,
+                    offered_tools: None,
+                    chat_options: None,
                 })
             } else {
                 None
@@ -2674,9 +4135,45 @@ async fn stream_generate_chat_completion<
     let mut tool_call_parent_observation_ids: HashMap<String, String> = HashMap::new();
     let mut tool_call_started_at: HashMap<String, String> = HashMap::new();
 
-    'loop_call_turns: loop {
+    // The chat provider that serves the generation, which changes if a chat provider group
+    // switches to another member
+    let mut served_chat_provider_id = chat_provider_id.map(String::from);
+
+    // New messages that are answered without tool calls are cached, and replayed for identical
+    // requests to the same chat provider
+    let generation_cache_config = &app_state.config.chat.generation_cache;
+    let generation_cache_target = chat_provider_group_id
+        .or(chat_provider_id)
+        .or(fallback_chat_provider_id)
+        .unwrap_or("default")
+        .to_string();
+    let generation_cache_key = if !ephemeral
+        && current_message_content.is_empty()
+        && generation_cache::applies_to(
+            generation_cache_config,
+            &current_turn_chat_request,
+            assistant_id,
+        ) {
+        match generation_cache::cache_key(
+            &generation_cache_target,
+            &current_turn_chat_request,
+            &chat_options,
+        ) {
+            Ok(cache_key) => Some(cache_key),
+            Err(error) => {
+                warn_and_capture_error("compute generation cache key", &error);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let mut generation_cache_hit = false;
+
+    let generation_result = 'loop_call_turns: loop {
         current_turn += 1;
         tracing::debug!("Starting chat completion turn {}", current_turn);
+        let chat_provider_id = served_chat_provider_id.as_deref();
         let chat_provider_metric_label = chat_provider_id.unwrap_or("unknown");
         let provider_request_start = Instant::now();
         let mut first_response_elapsed: Option<Duration> = None;
@@ -2731,15 +4228,30 @@ async fn stream_generate_chat_completion<
             } else {
                 current_tool_call_count += 1;
             }
+            // The part of the tool call is allocated when it is proposed, and all events of the
+            // call refer to its position
+            let content_index = current_message_content.len();
             // Emit event for tool call proposed
             {
                 let unfinished_tool_call = unfinished_tool_call.clone();
                 let tool_call_start_time = now_timestamp();
                 tool_call_started_at
-                    .insert(unfinished_tool_call.call_id.clone(), tool_call_start_time);
+                    .insert(unfinished_tool_call.call_id.clone(), tool_call_start_time.clone());
+                current_message_content.push(ContentPart::ToolUse(ToolUse {
+                    tool_call_id: unfinished_tool_call.call_id.clone(),
+                    status: MessageToolCallStatus::InProgress,
+                    tool_name: unfinished_tool_call.fn_name.clone(),
+                    input: Some(unfinished_tool_call.fn_arguments.clone()),
+                    progress_message: None,
+                    output: None,
+                    started_at: Some(tool_call_start_time),
+                    ended_at: None,
+                }));
+                announced_content_parts
+                    .announce_tool_use(content_index, &unfinished_tool_call.call_id);
                 let proposed_call = MessageSubmitStreamingResponseToolCallProposed {
                     message_id: assistant_message_id,
-                    content_index: current_message_content.len(),
+                    content_index,
                     tool_call_id: unfinished_tool_call.call_id.clone(),
                     tool_name: unfinished_tool_call.fn_name.clone(),
                     input: Some(unfinished_tool_call.fn_arguments.clone()),
@@ -2750,7 +4262,7 @@ async fn stream_generate_chat_completion<
                         task,
                         StreamingEvent::ToolCallProposed {
                             message_id: assistant_message_id,
-                            content_index: current_message_content.len(),
+                            content_index,
                             tool_call_id: unfinished_tool_call.call_id,
                             tool_name: unfinished_tool_call.fn_name,
                             input: Some(unfinished_tool_call.fn_arguments),
@@ -2786,7 +4298,7 @@ async fn stream_generate_chat_completion<
                     Some(&error_message),
                 )
                 .await;
-                current_message_content.push(ContentPart::ToolUse(ToolUse {
+                current_message_content[content_index] = ContentPart::ToolUse(ToolUse {
                     tool_call_id: unfinished_tool_call.call_id.clone(),
                     status: MessageToolCallStatus::Error,
                     tool_name: unfinished_tool_call.fn_name.clone(),
@@ -2795,7 +4307,7 @@ async fn stream_generate_chat_completion<
                     output: Some(json!({ "status": "error", "error": error_message })),
                     started_at: Some(tool_call_started),
                     ended_at: Some(now_timestamp()),
-                }));
+                });
                 current_turn_tool_responses.push(genai::chat::ToolResponse {
                     call_id: unfinished_tool_call.call_id.clone(),
                     content: error_message,
@@ -2863,7 +4375,7 @@ async fn stream_generate_chat_completion<
                     matches!(status, ToolCallStatus::Error).then(|| response_text.clone());
                 let update_event = MessageSubmitStreamingResponseToolCallUpdate {
                     message_id: assistant_message_id,
-                    content_index: current_message_content.len(),
+                    content_index,
                     tool_call_id: unfinished_tool_call.call_id.clone(),
                     tool_name: unfinished_tool_call.fn_name.clone(),
                     input: Some(unfinished_tool_call.fn_arguments.clone()),
@@ -2876,7 +4388,7 @@ async fn stream_generate_chat_completion<
                         task,
                         StreamingEvent::ToolCallUpdate {
                             message_id: assistant_message_id,
-                            content_index: current_message_content.len(),
+                            content_index,
                             tool_call_id: unfinished_tool_call.call_id.clone(),
                             tool_name: unfinished_tool_call.fn_name.clone(),
                             input: Some(unfinished_tool_call.fn_arguments.clone()),
@@ -2890,7 +4402,7 @@ async fn stream_generate_chat_completion<
                 }
                 let message: MSG = update_event.into();
                 send_generation_event(&message, tx.clone()).await?;
-                current_message_content.push(ContentPart::ToolUse(ToolUse {
+                current_message_content[content_index] = ContentPart::ToolUse(ToolUse {
                     tool_call_id: unfinished_tool_call.call_id.clone(),
                     status: message_status,
                     tool_name: unfinished_tool_call.fn_name.clone(),
@@ -2899,7 +4411,7 @@ async fn stream_generate_chat_completion<
                     output: Some(output_value.clone()),
                     started_at: Some(tool_call_started),
                     ended_at: Some(now_timestamp()),
-                }));
+                });
                 persist_otel_tool_call(
                     tracing_client.as_ref(),
                     &unfinished_tool_call,
@@ -2931,7 +4443,6 @@ async fn stream_generate_chat_completion<
             if !available_mcp_tools_by_name.contains_key(unfinished_tool_call.fn_name.as_str()) {
                 let call_id = unfinished_tool_call.call_id.clone();
                 let tool_name = unfinished_tool_call.fn_name.clone();
-                let content_index = current_message_content.len();
                 let tool_input = unfinished_tool_call.fn_arguments.clone();
                 let tool_call_started = tool_call_started_at
                     .remove(&call_id)
@@ -2944,7 +4455,7 @@ async fn stream_generate_chat_completion<
                     // answer the model with an error so it can recover.
                     let response_text =
                         "Client tool execution is unavailable for this request.".to_string();
-                    current_message_content.push(ContentPart::ToolUse(ToolUse {
+                    current_message_content[content_index] = ContentPart::ToolUse(ToolUse {
                         tool_call_id: call_id.clone(),
                         status: MessageToolCallStatus::Error,
                         tool_name: tool_name.clone(),
@@ -2953,7 +4464,7 @@ async fn stream_generate_chat_completion<
                         output: Some(json!({ "status": "error", "error": response_text })),
                         started_at: Some(tool_call_started),
                         ended_at: Some(now_timestamp()),
-                    }));
+                    });
                     persist_otel_tool_call(
                         tracing_client.as_ref(),
                         &unfinished_tool_call,
@@ -3051,6 +4562,16 @@ async fn stream_generate_chat_completion<
                     Park::Aborted => {
                         // User cancelled: discard any raced-in result and mirror
                         // the drain's abort exit.
+                        current_message_content[content_index] = ContentPart::ToolUse(ToolUse {
+                            tool_call_id: call_id.clone(),
+                            status: MessageToolCallStatus::Error,
+                            tool_name: tool_name.clone(),
+                            input: Some(tool_input.clone()),
+                            progress_message: None,
+                            output: Some(json!({ "status": "cancelled", "reason": "aborted" })),
+                            started_at: Some(tool_call_started),
+                            ended_at: Some(now_timestamp()),
+                        });
                         persist_otel_tool_call(
                             tracing_client.as_ref(),
                             &unfinished_tool_call,
@@ -3149,7 +4670,7 @@ async fn stream_generate_chat_completion<
                     warn_and_capture_error("send best-effort client tool result SSE event", &error);
                 }
 
-                current_message_content.push(ContentPart::ToolUse(ToolUse {
+                current_message_content[content_index] = ContentPart::ToolUse(ToolUse {
                     tool_call_id: call_id.clone(),
                     status: message_status,
                     tool_name: tool_name.clone(),
@@ -3158,7 +4679,7 @@ async fn stream_generate_chat_completion<
                     output: Some(output_value.clone()),
                     started_at: Some(tool_call_started),
                     ended_at: Some(now_timestamp()),
-                }));
+                });
                 persist_otel_tool_call(
                     tracing_client.as_ref(),
                     &unfinished_tool_call,
@@ -3191,6 +4712,23 @@ async fn stream_generate_chat_completion<
                     return Err(error);
                 }
             };
+            let mut unfinished_tool_call = unfinished_tool_call;
+            if app_state
+                .config
+                .security
+                .redact_system_prompt_from_tool_arguments
+                && redact_system_prompt(
+                    &mut unfinished_tool_call.fn_arguments,
+                    &system_prompt,
+                    app_state.config.security.system_prompt_redaction_min_chars,
+                )
+            {
+                tracing::warn!(
+                    tool_name = %unfinished_tool_call.fn_name,
+                    tool_call_id = %unfinished_tool_call.call_id,
+                    "Redacted text of the system prompt from tool call arguments"
+                );
+            }
             let managed_tool_call = crate::services::mcp_manager::ManagedToolCall {
                 server_id: managed_tool.server_id.clone(),
                 tool_call: unfinished_tool_call.clone(),
@@ -3284,6 +4822,17 @@ async fn stream_generate_chat_completion<
                             Some(&tool_error),
                         )
                         .await;
+                        // The filtered output is not stored
+                        current_message_content[content_index] = ContentPart::ToolUse(ToolUse {
+                            tool_call_id: unfinished_tool_call.call_id.clone(),
+                            status: MessageToolCallStatus::Error,
+                            tool_name: unfinished_tool_call.fn_name.clone(),
+                            input: Some(unfinished_tool_call.fn_arguments.clone()),
+                            progress_message: None,
+                            output: None,
+                            started_at: Some(tool_call_started),
+                            ended_at: Some(now_timestamp()),
+                        });
                         let error_payload = Some(content_filter_error.clone());
                         let error_event = MessageSubmitStreamingResponseError {
                             message_id: Some(assistant_message_id),
@@ -3347,6 +4896,7 @@ async fn stream_generate_chat_completion<
                         policy,
                         subject,
                         chat_id,
+                        &managed_tool.server_id,
                         &unfinished_tool_call,
                         output_schema.as_ref(),
                         &tool_call_result,
@@ -3375,8 +4925,34 @@ async fn stream_generate_chat_completion<
                             return Err(error);
                         }
                     };
-                    let tool_response = post_processed.tool_response;
+                    let mut tool_response = post_processed.tool_response;
                     let output_value = post_processed.output_value;
+                    let (status, bg_status, message_status) = if post_processed.is_error {
+                        (
+                            ToolCallStatus::Error,
+                            BgToolCallStatus::Error,
+                            MessageToolCallStatus::Error,
+                        )
+                    } else {
+                        (
+                            ToolCallStatus::Success,
+                            BgToolCallStatus::Success,
+                            MessageToolCallStatus::Success,
+                        )
+                    };
+                    let tool_error = post_processed
+                        .is_error
+                        .then(|| tool_response.content.clone());
+                    let tool_warnings =
+                        untrusted_content_guard.guard_tool_response(&mut tool_response);
+                    send_prompt_injection_warnings::<MSG>(
+                        &tool_warnings,
+                        assistant_message_id,
+                        &tx,
+                        streaming_task,
+                    )
+                    .await?;
+                    prompt_injection_warnings.extend(tool_warnings);
                     let image_content_parts = post_processed.image_content_parts;
 
                     persist_otel_tool_call(
@@ -3388,7 +4964,7 @@ async fn stream_generate_chat_completion<
                         tool_call_parent_observation_id.clone(),
                         assistant_id,
                         &langfuse_trace_enrichment.platform,
-                        None,
+                        tool_error.as_deref(),
                     )
                     .await;
 
@@ -3398,11 +4974,11 @@ async fn stream_generate_chat_completion<
                         let output_value_for_event = output_value.clone();
                         let proposed_call = MessageSubmitStreamingResponseToolCallUpdate {
                             message_id: assistant_message_id,
-                            content_index: current_message_content.len(),
+                            content_index,
                             tool_call_id: finished_tool_call.call_id.clone(),
                             tool_name: finished_tool_call.fn_name.clone(),
                             input: Some(finished_tool_call.fn_arguments.clone()),
-                            status: ToolCallStatus::Success,
+                            status,
                             progress_message: None,
                             output: output_value_for_event.clone(),
                         };
@@ -3412,11 +4988,11 @@ async fn stream_generate_chat_completion<
                                 task,
                                 StreamingEvent::ToolCallUpdate {
                                     message_id: assistant_message_id,
-                                    content_index: current_message_content.len(),
+                                    content_index,
                                     tool_call_id: finished_tool_call.call_id,
                                     tool_name: finished_tool_call.fn_name,
                                     input: Some(finished_tool_call.fn_arguments),
-                                    status: BgToolCallStatus::Success,
+                                    status: bg_status,
                                     progress_message: None,
                                     output: output_value_for_event,
                                 },
@@ -3430,16 +5006,18 @@ async fn stream_generate_chat_completion<
                     // Add to current message content
                     {
                         let finished_tool_call = unfinished_tool_call.clone();
-                        current_message_content.push(ContentPart::ToolUse(ToolUse {
+                        current_message_content[content_index] = ContentPart::ToolUse(ToolUse {
                             tool_call_id: finished_tool_call.call_id,
-                            status: MessageToolCallStatus::Success,
+                            status: message_status,
                             tool_name: finished_tool_call.fn_name,
                             input: Some(finished_tool_call.fn_arguments),
                             progress_message: None,
                             output: output_value.clone(),
                             started_at: Some(tool_call_started.clone()),
                             ended_at: Some(now_timestamp()),
-                        }));
+                        });
+                        // Images extracted from the output follow the tool call, and are only
+                        // sent to clients with the completed message
                         if !image_content_parts.is_empty() {
                             current_message_content.extend(image_content_parts);
                         }
@@ -3560,23 +5138,79 @@ async fn stream_generate_chat_completion<
             }
         }
 
-        let genai_client = app_state
-            .genai_for_chat_provider_id_with_headers_context(
-                chat_provider_id,
-                Some(chat_provider_headers_context),
-            )
-            .wrap_err("Unable to choose chat provider")?;
-        let chat_stream = match genai_client
-            .exec_chat_stream(
-                "PLACEHOLDER_MODEL",
-                current_turn_chat_request.clone(),
-                Some(&chat_options),
-            )
-            .await
+        let turn_request_start = Instant::now();
+        final_turn_time_to_first_token = None;
+        if let Some(capture) = provider_capture.as_mut() {
+            capture.record_request(&current_turn_chat_request, &chat_options);
+        }
+        let cached_generation = match generation_cache_key.as_deref() {
+            Some(cache_key) if current_turn == 1 && replay_from_generation_cache => {
+                generation_cache::lookup_cached_generation(
+                    &app_state.db,
+                    generation_cache_config,
+                    cache_key,
+                    &generation_cache_target,
+                )
+                .await
+                .unwrap_or_else(|error| {
+                    warn_and_capture_error("look up cached generation", &error);
+                    None
+                })
+            }
+            _ => None,
+        };
+        let (chat_stream_result, serving_chat_provider_id) = match cached_generation {
+            Some(cached_content) => {
+                generation_cache_hit = true;
+                (
+                    Ok(generation_cache::replay_cached_generation(
+                        cached_content,
+                        Duration::from_millis(generation_cache_config.replay_chunk_delay_ms),
+                    )),
+                    chat_provider_id.map(String::from),
+                )
+            }
+            None => {
+                start_chat_stream(
+                    app_state,
+                    &current_turn_chat_request,
+                    &chat_options,
+                    assistant_message_id,
+                    chat_provider_id,
+                    chat_provider_group_id,
+                    chat_provider_headers_context,
+                )
+                .await?
+            }
+        };
+        // Another member of the chat provider group took over the generation
+        if serving_chat_provider_id.as_deref() != chat_provider_id
+            && let Some(serving_chat_provider_id) = serving_chat_provider_id
         {
+            if !ephemeral
+                && let Err(error) = update_message_generation_chat_provider_id(
+                    &app_state.db,
+                    policy,
+                    subject,
+                    &assistant_message_id,
+                    &serving_chat_provider_id,
+                )
+                .await
+            {
+                warn_and_capture_error("record chat provider group member", &error);
+            }
+            served_chat_provider_id = Some(serving_chat_provider_id);
+        }
+        let chat_provider_id = served_chat_provider_id.as_deref();
+        let chat_provider_metric_label = chat_provider_id.unwrap_or("unknown");
+        let turn_langfuse_model_name = langfuse_model_name(app_state, chat_provider_id);
+        let chat_stream = match chat_stream_result {
             Ok(stream) => stream,
             Err(err) => {
-                let error_event = parse_streaming_error(err, assistant_message_id).await;
+                if let Some(capture) = provider_capture.as_mut() {
+                    capture.record_error(&err);
+                }
+                let error_event = parse_streaming_error(&err, assistant_message_id);
                 log_chat_completion_generation_error(
                     chat_provider_metric_label,
                     assistant_message_id,
@@ -3586,6 +5220,7 @@ async fn stream_generate_chat_completion<
                     chat_provider_metric_label,
                     &error_event.error,
                 );
+                record_chat_provider_rate_limit(app_state, chat_provider_id, &error_event.error);
                 persist_otel_generation_error(
                     tracing_client.as_ref(),
                     &turn_obs_id,
@@ -3640,6 +5275,10 @@ async fn stream_generate_chat_completion<
         let turn_content_start_index = current_message_content.len();
         let mut current_turn_streamed_text = String::new();
         let mut current_turn_streamed_reasoning = String::new();
+        let mut tool_call_arguments_deltas =
+            ToolCallArgumentsDeltaCoalescer::new(&app_state.config.chat);
+        // Error of the output compliance rules or the output transformers, which ends the turn
+        let mut output_error: Option<MessageSubmitStreamingResponseError> = None;
         // Await until stream end
         let mut stream_end: Option<StreamEnd> = None;
         loop {
@@ -3775,6 +5414,10 @@ async fn stream_generate_chat_completion<
                     ChatStreamEvent::Chunk(StreamChunk { content }) => {
                         let elapsed = provider_request_start.elapsed();
                         first_response_elapsed.get_or_insert(elapsed);
+                        if !content.is_empty() {
+                            final_turn_time_to_first_token
+                                .get_or_insert(turn_request_start.elapsed());
+                        }
                         if hallucination_suppression.observe_text_delta(&content) {
                             let error_event = hallucination_loop_error_event(assistant_message_id);
                             log_chat_completion_generation_error(
@@ -3845,29 +5488,44 @@ async fn stream_generate_chat_completion<
                                 generation_metadata,
                             ));
                         }
-                        current_turn_streamed_text.push_str(&content);
-                        let content_index =
-                            append_text_delta_part(&mut current_message_content, content.clone());
-                        let delta = MessageSubmitStreamingResponseMessageTextDelta {
-                            message_id: assistant_message_id,
-                            content_index,
-                            new_text: content.clone(),
+                        let released = match output_compliance.push(&content) {
+                            Ok(released) => released,
+                            Err(violation) => {
+                                output_error = Some(compliance_policy_error_event(
+                                    assistant_message_id,
+                                    violation,
+                                ));
+                                break;
+                            }
                         };
-                        // Forward to streaming_task if present
-                        if let Some(task) = streaming_task {
-                            send_background_event(
-                                task,
-                                StreamingEvent::TextDelta {
-                                    message_id: assistant_message_id,
-                                    content_index,
-                                    new_text: content,
-                                },
-                                "broadcast text delta",
-                            )
-                            .await;
+                        let released = match output_transformers.push(&released).await {
+                            Ok(released) => released,
+                            Err(failure) => {
+                                output_error = Some(transformer_failed_error_event(
+                                    assistant_message_id,
+                                    failure,
+                                ));
+                                break;
+                            }
+                        };
+                        // Text held back by the output compliance rules or the output transformers
+                        // is sent with a later chunk
+                        if released.is_empty() && !content.is_empty() {
+                            continue;
                         }
-                        let message: MSG = delta.into();
-                        send_generation_event(&message, tx.clone()).await?;
+                        current_turn_streamed_text.push_str(&released);
+                        send_text_delta::<MSG>(
+                            released,
+                            assistant_message_id,
+                            &mut current_message_content,
+                            &mut announced_content_parts,
+                            &tx,
+                            streaming_task,
+                        )
+                        .await?;
+                        content_draft_writer
+                            .record_delta(&app_state.db, &current_message_content)
+                            .await;
                     }
                     ChatStreamEvent::ReasoningChunk(StreamChunk { content }) => {
                         captured_reasoning_summary.push_str(&content);
@@ -3876,6 +5534,7 @@ async fn stream_generate_chat_completion<
                             &mut current_message_content,
                             content.clone(),
                         );
+                        announced_content_parts.announce_reasoning(content_index);
                         if let Some(ContentPart::Reasoning(reasoning_part)) =
                             current_message_content.get_mut(content_index)
                         {
@@ -3902,12 +5561,31 @@ async fn stream_generate_chat_completion<
                         }
                         let message: MSG = delta.into();
                         send_generation_event(&message, tx.clone()).await?;
+                        content_draft_writer
+                            .record_delta(&app_state.db, &current_message_content)
+                            .await;
                     }
                     ChatStreamEvent::ThoughtSignatureChunk(StreamChunk { content }) => {
                         if !content.is_empty() {
                             captured_reasoning_item_encrypted_content.push(content);
                         }
                     }
+                    ChatStreamEvent::ToolCallChunk(chunk) => {
+                        let tool_call = chunk.tool_call;
+                        if let Some(args_fragment) = tool_call_arguments_deltas.observe(
+                            &tool_call.call_id,
+                            &tool_call.fn_arguments,
+                            Instant::now(),
+                        ) {
+                            send_tool_call_arguments_deltas::<MSG>(
+                                vec![(tool_call.call_id, args_fragment)],
+                                assistant_message_id,
+                                &tx,
+                                streaming_task,
+                            )
+                            .await?;
+                        }
+                    }
                     ChatStreamEvent::End(end) => {
                         if first_response_elapsed.is_some() {
                             last_response_elapsed = Some(provider_request_start.elapsed());
@@ -3927,7 +5605,10 @@ async fn stream_generate_chat_completion<
                         continue;
                     }
 
-                    let error_event = parse_streaming_error(err, assistant_message_id).await;
+                    if let Some(capture) = provider_capture.as_mut() {
+                        capture.record_error(&err);
+                    }
+                    let error_event = parse_streaming_error(&err, assistant_message_id);
                     log_chat_completion_generation_error(
                         chat_provider_metric_label,
                         assistant_message_id,
@@ -3937,6 +5618,11 @@ async fn stream_generate_chat_completion<
                         chat_provider_metric_label,
                         &error_event.error,
                     );
+                    record_chat_provider_rate_limit(
+                        app_state,
+                        chat_provider_id,
+                        &error_event.error,
+                    );
                     persist_otel_generation_error(
                         tracing_client.as_ref(),
                         &turn_obs_id,
@@ -3987,12 +5673,132 @@ async fn stream_generate_chat_completion<
                 }
             }
         }
-        if let Some(stream_end) = stream_end {
+        // Text held back by the output compliance rules and the output transformers is sent once
+        // the text of the turn is complete, and text that was not streamed is checked as a whole.
+        let mut unstreamed_texts = Vec::new();
+        if output_error.is_none() {
+            match finish_streamed_output(
+                &mut output_compliance,
+                &mut output_transformers,
+                assistant_message_id,
+            )
+            .await
+            {
+                Ok(released) if !released.is_empty() => {
+                    current_turn_streamed_text.push_str(&released);
+                    send_text_delta::<MSG>(
+                        released,
+                        assistant_message_id,
+                        &mut current_message_content,
+                        &mut announced_content_parts,
+                        &tx,
+                        streaming_task,
+                    )
+                    .await?;
+                    content_draft_writer
+                        .record_delta(&app_state.db, &current_message_content)
+                        .await;
+                }
+                Ok(_) => {}
+                Err(error_event) => output_error = Some(error_event),
+            }
+        }
+        if output_error.is_none()
+            && current_turn_streamed_text.is_empty()
+            && let Some(captured_texts) = stream_end.as_ref().and_then(|end| end.captured_texts())
+        {
+            for captured_text in captured_texts {
+                match filter_complete_output(
+                    &mut output_compliance,
+                    &mut output_transformers,
+                    &captured_text.to_string(),
+                    assistant_message_id,
+                )
+                .await
+                {
+                    Ok(text) => unstreamed_texts.push(text),
+                    Err(error_event) => {
+                        output_error = Some(error_event);
+                        break;
+                    }
+                }
+            }
+        }
+        if let Some(error_event) = output_error {
+            log_chat_completion_generation_error(
+                chat_provider_metric_label,
+                assistant_message_id,
+                &error_event.error,
+            );
+            report_chat_provider_generation_error(chat_provider_metric_label, &error_event.error);
+            persist_otel_generation_error(
+                tracing_client.as_ref(),
+                &turn_obs_id,
+                &current_turn_chat_request,
+                &current_message_content,
+                &error_event.error,
+                &turn_langfuse_model_name,
+                &turn_langfuse_generation_name,
+                turn_start_time,
+                assistant_id,
+                &all_tool_names,
+                &langfuse_trace_enrichment.platform,
+            )
+            .await;
             if let Some(elapsed) = first_response_elapsed {
                 report_chat_provider_time_to_first_token(chat_provider_metric_label, elapsed);
             }
-            if let Some(elapsed) = last_response_elapsed {
-                report_chat_provider_time_to_last_token(chat_provider_metric_label, elapsed);
+            let error_payload = Some(error_event.error.clone());
+
+            if let Some(task) = streaming_task
+                && let Some(error_json) = serialize_json_value(
+                    MessageSubmitStreamingResponseMessage::Error(error_event.clone()),
+                    "serialize output error event",
+                )
+            {
+                send_background_event(
+                    task,
+                    StreamingEvent::Error {
+                        error: Some(error_json),
+                    },
+                    "broadcast output error",
+                )
+                .await;
+            }
+
+            let message: MSG = error_event.into();
+            send_generation_event(&message, tx.clone()).await?;
+            let generation_metadata = build_generation_metadata(
+                total_prompt_tokens,
+                total_completion_tokens,
+                total_total_tokens,
+                total_reasoning_tokens,
+                langfuse_trace_id.clone(),
+                true,
+                error_payload,
+                non_empty_string(&captured_reasoning_summary),
+                non_empty_vec(&captured_reasoning_items),
+                non_empty_vec(&captured_reasoning_item_encrypted_content),
+            );
+            break 'loop_call_turns Ok((current_message_content, generation_metadata));
+        }
+        // Fragments held back by the throttling are sent before the tool calls are proposed.
+        send_tool_call_arguments_deltas::<MSG>(
+            tool_call_arguments_deltas.flush(),
+            assistant_message_id,
+            &tx,
+            streaming_task,
+        )
+        .await?;
+        if let Some(stream_end) = stream_end {
+            // Replayed generations say nothing about the latency of the chat provider
+            if !generation_cache_hit {
+                if let Some(elapsed) = first_response_elapsed {
+                    report_chat_provider_time_to_first_token(chat_provider_metric_label, elapsed);
+                }
+                if let Some(elapsed) = last_response_elapsed {
+                    report_chat_provider_time_to_last_token(chat_provider_metric_label, elapsed);
+                }
             }
             let mut current_turn_captured_reasoning_summary = String::new();
             if let Some(reasoning_content) = stream_end.captured_reasoning_content.as_ref() {
@@ -4030,10 +5836,11 @@ async fn stream_generate_chat_completion<
                 && !current_turn_captured_reasoning_summary.is_empty()
             {
                 let now = now_timestamp();
-                let content_index = insert_reasoning_part_before_text(
+                let content_index = append_reasoning_part(
                     &mut current_message_content,
                     current_turn_captured_reasoning_summary.clone(),
                 );
+                announced_content_parts.announce_reasoning(content_index);
                 if let Some(ContentPart::Reasoning(reasoning_part)) =
                     current_message_content.get_mut(content_index)
                 {
@@ -4063,18 +5870,30 @@ async fn stream_generate_chat_completion<
 
             #[allow(clippy::collapsible_match)]
             #[allow(clippy::single_match)]
-            if let Some(captured_texts) = stream_end.captured_texts() {
+            if stream_end.captured_texts().is_some() {
                 if current_turn_streamed_text.is_empty() {
-                    for captured_text in captured_texts {
-                        current_message_content.push(ContentPart::Text(ContentPartText {
-                            text: captured_text.into(),
-                        }));
+                    for captured_text in unstreamed_texts {
+                        let content_index =
+                            append_text_part(&mut current_message_content, captured_text.clone());
+                        announced_content_parts.announce_text(content_index);
+                        send_text_delta_event::<MSG>(
+                            captured_text,
+                            content_index,
+                            assistant_message_id,
+                            &tx,
+                            streaming_task,
+                        )
+                        .await?;
                     }
                 }
             } else if !current_turn_streamed_text.is_empty() {
                 // Text chunks have already been appended to current_message_content.
             }
 
+            if let Some(stop_reason) = stream_end.captured_stop_reason.as_ref() {
+                provider_stop_reason = Some(StopReason::from_genai(stop_reason));
+            }
+
             // Accumulate usage statistics from this turn
             if let Some(usage) = stream_end.captured_usage.as_ref() {
                 if let Some(prompt_tokens) = usage.prompt_tokens {
@@ -4092,9 +5911,22 @@ async fn stream_generate_chat_completion<
                 {
                     total_reasoning_tokens += reasoning_tokens as u32;
                 }
+                // Prompt tokens that were read from or written to the prompt cache of the provider
+                if let Some(details) = &usage.prompt_tokens_details {
+                    if let Some(cached_tokens) = details.cached_tokens {
+                        total_cache_read_tokens += cached_tokens as u32;
+                    }
+                    if let Some(cache_creation_tokens) = details.cache_creation_tokens {
+                        total_cache_write_tokens += cache_creation_tokens as u32;
+                    }
+                }
             }
 
-            // Send Langfuse tracing for this turn if enabled
+            if let Some(capture) = provider_capture.as_mut() {
+                capture.record_response(&current_message_content[turn_content_start_index..]);
+            }
+
+            // Send Langfuse tracing for this turn if enabled
             if let (Some(client), Some(turn_start)) = (&tracing_client, turn_start_time) {
                 let turn_end_time = SystemTime::now();
                 let model_name = turn_langfuse_model_name.clone();
@@ -4119,6 +5951,9 @@ async fn stream_generate_chat_completion<
                     }
                 }
 
+                let completion_start_time =
+                    first_response_elapsed.map(|elapsed| turn_start + elapsed);
+
                 // Clone client and data for async task
                 let client = client.clone();
                 let request = current_turn_chat_request.clone();
@@ -4148,7 +5983,7 @@ async fn stream_generate_chat_completion<
                             Some(generation_name),
                             Some(turn_start),
                             Some(turn_end_time),
-                            None, // completion_start_time
+                            completion_start_time,
                             assistant_id_for_langfuse,
                             if uses_otel {
                                 &accumulated_tool_names
@@ -4161,11 +5996,16 @@ async fn stream_generate_chat_completion<
                         )
                         .await
                     } else {
-                        TracedGenerationBuilder::new(turn_obs_id)
+                        let mut generation = TracedGenerationBuilder::new(turn_obs_id)
                             .with_model(model_name)
                             .with_start_time(turn_start)
                             .with_end_time(turn_end_time)
-                            .with_name(generation_name)
+                            .with_name(generation_name);
+                        if let Some(completion_start_time) = completion_start_time {
+                            generation =
+                                generation.with_completion_start_time(completion_start_time);
+                        }
+                        generation
                             .build_and_send(
                                 &client,
                                 &request,
@@ -4273,6 +6113,8 @@ async fn stream_generate_chat_completion<
                         let trace_tool_names = all_tool_names.clone();
                         let trace_model_tags = all_model_tags.clone();
                         let trace_mcp_servers_unavailable = mcp_servers_unavailable.clone();
+                        let trace_time_to_first_token = final_turn_time_to_first_token;
+                        let trace_generation_duration = generation_start.elapsed();
                         tokio::spawn(async move {
                             // Update trace output
                             if let Err(e) = client.update_trace_output(output_json).await {
@@ -4288,12 +6130,16 @@ async fn stream_generate_chat_completion<
                                 );
                             }
 
-                            // Update trace metadata with assistant_id and tool calls
-                            if let Some(metadata) = trace_enrichment.trace_metadata(
-                                assistant_id_for_trace,
-                                &trace_tool_names,
-                                &trace_mcp_servers_unavailable,
-                                false,
+                            // Update trace metadata with assistant_id, tool calls and timings
+                            if let Some(metadata) = with_generation_timings_trace_metadata(
+                                trace_enrichment.trace_metadata(
+                                    assistant_id_for_trace,
+                                    &trace_tool_names,
+                                    &trace_mcp_servers_unavailable,
+                                    false,
+                                ),
+                                trace_time_to_first_token,
+                                trace_generation_duration,
                             ) {
                                 if let Err(e) = client.update_trace_metadata(metadata).await {
                                     tracing::warn!(
@@ -4352,11 +6198,15 @@ async fn stream_generate_chat_completion<
                     let trace_id = client.trace_id().to_string();
                     let trace_tags =
                         langfuse_trace_enrichment.all_tags(&all_model_tags, &all_tool_names);
-                    let trace_metadata = langfuse_trace_enrichment.trace_metadata(
-                        assistant_id,
-                        &all_tool_names,
-                        &mcp_servers_unavailable,
-                        false,
+                    let trace_metadata = with_generation_timings_trace_metadata(
+                        langfuse_trace_enrichment.trace_metadata(
+                            assistant_id,
+                            &all_tool_names,
+                            &mcp_servers_unavailable,
+                            false,
+                        ),
+                        final_turn_time_to_first_token,
+                        generation_start.elapsed(),
                     );
                     tokio::spawn(async move {
                         if let Err(e) = client.update_trace_output(output_json).await {
@@ -4426,6 +6276,7 @@ async fn stream_generate_chat_completion<
                     chat_provider_metric_label,
                     &error_event.error,
                 );
+                record_chat_provider_rate_limit(app_state, chat_provider_id, &error_event.error);
                 persist_otel_generation_error(
                     tracing_client.as_ref(),
                     &turn_obs_id,
@@ -4479,7 +6330,550 @@ async fn stream_generate_chat_completion<
                 "Non-streaming chat generation failed without a parseable provider error"
             ));
         }
+    };
+
+    let generation_duration = generation_start.elapsed();
+    let chat_provider_metric_label = served_chat_provider_id.as_deref().unwrap_or("unknown");
+    if let Some(time_to_first_token) = final_turn_time_to_first_token {
+        report_message_time_to_first_token(chat_provider_metric_label, time_to_first_token);
+    }
+    report_message_generation_duration(chat_provider_metric_label, generation_duration);
+    // Only generations that ended in the first turn are cached, as later turns follow tool calls
+    if let Some(cache_key) = generation_cache_key.as_deref()
+        && !generation_cache_hit
+        && current_turn == 1
+        && let Ok((content, generation_metadata)) = &generation_result
+        && !content.is_empty()
+        && generation_metadata
+            .as_ref()
+            .is_none_or(|metadata| metadata.error.is_none() && metadata.was_aborted.is_none())
+        && let Err(error) = generation_cache::store_cached_generation(
+            &app_state.db,
+            cache_key,
+            chat_provider_metric_label,
+            content,
+            total_prompt_tokens,
+            total_completion_tokens,
+            total_total_tokens,
+        )
+        .await
+    {
+        warn_and_capture_error("store generation in cache", &error);
+    }
+    // Count the generation against the quota of the chat provider the user selected
+    if let Ok((_, generation_metadata)) = &generation_result
+        && generation_metadata
+            .as_ref()
+            .is_none_or(|metadata| metadata.error.is_none())
+        && let Some(quota_chat_provider_id) = chat_provider_group_id
+            .or(chat_provider_id)
+            .or(fallback_chat_provider_id)
+        && let Ok(user_id) = Uuid::parse_str(&user_id)
+        && let Err(error) = chat_provider_quotas::record_usage(
+            &app_state.db,
+            &app_state.config,
+            &user_id,
+            quota_chat_provider_id,
+            total_total_tokens.into(),
+            Utc::now(),
+        )
+        .await
+    {
+        warn_and_capture_error("record chat provider usage", &error);
+    }
+    let provider_capture_id = match provider_capture {
+        Some(capture) => store_provider_capture(app_state, capture).await,
+        None => None,
+    };
+
+    let (mut content, generation_metadata) = generation_result?;
+    let mut generation_metadata = with_markdown_normalization(
+        generation_metadata,
+        &mut content,
+        app_state.config.chat.normalize_markdown,
+    );
+    // Only answers that didn't fail are post-processed by the `final` output transformers
+    if generation_metadata
+        .as_ref()
+        .is_none_or(|metadata| metadata.error.is_none())
+    {
+        match output_transformers.transform_final(content.clone()).await {
+            Ok(transformed) => content = transformed,
+            Err(failure) => {
+                let error_event = transformer_failed_error_event(assistant_message_id, failure);
+                log_chat_completion_generation_error(
+                    chat_provider_metric_label,
+                    assistant_message_id,
+                    &error_event.error,
+                );
+                report_chat_provider_generation_error(
+                    chat_provider_metric_label,
+                    &error_event.error,
+                );
+                if let Some(task) = streaming_task
+                    && let Some(error_json) = serialize_json_value(
+                        MessageSubmitStreamingResponseMessage::Error(error_event.clone()),
+                        "serialize output error event",
+                    )
+                {
+                    send_background_event(
+                        task,
+                        StreamingEvent::Error {
+                            error: Some(error_json),
+                        },
+                        "broadcast output error",
+                    )
+                    .await;
+                }
+                // The untransformed text is not stored, so that it isn't shown to users
+                content.retain(|part| !matches!(part, ContentPart::Text(_)));
+                generation_metadata = Some(GenerationMetadata {
+                    was_aborted: Some(true),
+                    error: Some(error_event.error.clone()),
+                    ..generation_metadata.unwrap_or_default()
+                });
+                let message: MSG = error_event.into();
+                send_generation_event(&message, tx.clone()).await?;
+            }
+        }
+    }
+    let generation_metadata = with_generation_timings(
+        generation_metadata,
+        final_turn_time_to_first_token,
+        generation_duration,
+    );
+    let generation_metadata = with_renderable_blocks(generation_metadata, &content);
+    let generation_metadata = with_stop_reason(generation_metadata, provider_stop_reason);
+    let generation_metadata =
+        with_prompt_injection_warnings(generation_metadata, prompt_injection_warnings);
+    let generation_metadata =
+        with_output_compliance_matches(generation_metadata, output_compliance.into_matches());
+    let generation_metadata = with_output_transformations(
+        generation_metadata,
+        output_transformers.into_transformations(),
+    );
+    let generation_metadata = with_provider_capture(generation_metadata, provider_capture_id);
+    let generation_metadata = with_generation_cache_hit(generation_metadata, generation_cache_hit);
+    let generation_metadata =
+        with_offered_tools(generation_metadata, offered_tools, generation_chat_options);
+    let generation_metadata = with_prompt_cache_usage(
+        generation_metadata,
+        total_cache_read_tokens,
+        total_cache_write_tokens,
+    );
+    // Clients assemble the content from the events, so the completed message must have the parts
+    // at the announced content indices. Failed generations may drop parts, e.g. untransformed
+    // text.
+    if cfg!(debug_assertions)
+        && generation_metadata
+            .as_ref()
+            .is_none_or(|metadata| metadata.error.is_none())
+    {
+        let mismatches = announced_content_parts.mismatches(&content);
+        debug_assert!(
+            mismatches.is_empty(),
+            "Completed message doesn't match the content indices of the events: {mismatches:?}"
+        );
+    }
+    Ok((content, generation_metadata))
+}
+
+/// Report possible prompt injections in file contents and tool outputs to the metrics and the
+/// client.
+async fn send_prompt_injection_warnings<
+    MSG: SendAsSseEvent + From<MessageSubmitStreamingResponsePromptInjectionWarning>,
+>(
+    warnings: &[PromptInjectionWarning],
+    message_id: Uuid,
+    tx: &Sender<Result<Event, Report>>,
+    streaming_task: Option<&Arc<StreamingTask>>,
+) -> Result<(), Report> {
+    for warning in warnings {
+        tracing::warn!(
+            %message_id,
+            pattern_id = %warning.pattern_id,
+            source = ?warning.source,
+            "Possible prompt injection found in untrusted content"
+        );
+        report_prompt_injection_warning(warning);
+        if let Some(task) = streaming_task {
+            send_background_event(
+                task,
+                StreamingEvent::PromptInjectionWarning {
+                    message_id,
+                    warning: warning.clone(),
+                },
+                "broadcast prompt injection warning",
+            )
+            .await;
+        }
+        let message: MSG = MessageSubmitStreamingResponsePromptInjectionWarning {
+            message_id,
+            warning: warning.clone(),
+        }
+        .into();
+        send_generation_event(&message, tx.clone()).await?;
+    }
+    Ok(())
+}
+
+/// Send the streamed fragments of tool call arguments, as `(tool_call_id, args_fragment)`.
+async fn send_tool_call_arguments_deltas<
+    MSG: SendAsSseEvent + From<MessageSubmitStreamingResponseToolCallArgumentsDelta>,
+>(
+    fragments: Vec<(String, String)>,
+    message_id: Uuid,
+    tx: &Sender<Result<Event, Report>>,
+    streaming_task: Option<&Arc<StreamingTask>>,
+) -> Result<(), Report> {
+    for (tool_call_id, args_fragment) in fragments {
+        if let Some(task) = streaming_task {
+            send_background_event(
+                task,
+                StreamingEvent::ToolCallArgumentsDelta {
+                    message_id,
+                    tool_call_id: tool_call_id.clone(),
+                    args_fragment: args_fragment.clone(),
+                },
+                "broadcast tool call arguments delta",
+            )
+            .await;
+        }
+        let message: MSG = MessageSubmitStreamingResponseToolCallArgumentsDelta {
+            message_id,
+            tool_call_id,
+            args_fragment,
+        }
+        .into();
+        send_generation_event(&message, tx.clone()).await?;
+    }
+    Ok(())
+}
+
+/// Append a chunk of generated text to the content of the message, and send it to the client.
+async fn send_text_delta<
+    MSG: SendAsSseEvent + From<MessageSubmitStreamingResponseMessageTextDelta>,
+>(
+    text: String,
+    message_id: Uuid,
+    message_content: &mut Vec<ContentPart>,
+    announced_content_parts: &mut AnnouncedContentParts,
+    tx: &Sender<Result<Event, Report>>,
+    streaming_task: Option<&Arc<StreamingTask>>,
+) -> Result<(), Report> {
+    let content_index = append_text_delta_part(message_content, text.clone());
+    announced_content_parts.announce_text(content_index);
+    send_text_delta_event::<MSG>(text, content_index, message_id, tx, streaming_task).await
+}
+
+/// Send a chunk of text of the part at `content_index` to the client.
+async fn send_text_delta_event<
+    MSG: SendAsSseEvent + From<MessageSubmitStreamingResponseMessageTextDelta>,
+>(
+    text: String,
+    content_index: usize,
+    message_id: Uuid,
+    tx: &Sender<Result<Event, Report>>,
+    streaming_task: Option<&Arc<StreamingTask>>,
+) -> Result<(), Report> {
+    if let Some(task) = streaming_task {
+        send_background_event(
+            task,
+            StreamingEvent::TextDelta {
+                message_id,
+                content_index,
+                new_text: text.clone(),
+            },
+            "broadcast text delta",
+        )
+        .await;
+    }
+    let message: MSG = MessageSubmitStreamingResponseMessageTextDelta {
+        message_id,
+        content_index,
+        new_text: text,
+    }
+    .into();
+    send_generation_event(&message, tx.clone()).await
+}
+
+/// Inform the client about attached files whose contents are unavailable to the model.
+async fn send_files_unavailable<
+    MSG: SendAsSseEvent + From<MessageSubmitStreamingResponseFilesUnavailable>,
+>(
+    files: Vec<UnavailableFile>,
+    message_id: Uuid,
+    tx: &Sender<Result<Event, Report>>,
+    streaming_task: Option<&Arc<StreamingTask>>,
+) -> Result<(), Report> {
+    if files.is_empty() {
+        return Ok(());
+    }
+    if let Some(task) = streaming_task {
+        send_background_event(
+            task,
+            StreamingEvent::FilesUnavailable {
+                message_id,
+                files: files.clone(),
+            },
+            "broadcast unavailable files",
+        )
+        .await;
+    }
+    let message: MSG = MessageSubmitStreamingResponseFilesUnavailable { message_id, files }.into();
+    send_generation_event(&message, tx.clone()).await
+}
+
+async fn send_provider_fallback<
+    MSG: SendAsSseEvent + From<MessageSubmitStreamingResponseProviderFallback>,
+>(
+    requested_chat_provider_id: Option<&str>,
+    chat_provider_id: Option<&str>,
+    message_id: Uuid,
+    tx: &Sender<Result<Event, Report>>,
+    streaming_task: Option<&Arc<StreamingTask>>,
+) -> Result<(), Report> {
+    let (Some(requested_chat_provider_id), Some(chat_provider_id)) =
+        (requested_chat_provider_id, chat_provider_id)
+    else {
+        return Ok(());
+    };
+    if let Some(task) = streaming_task {
+        send_background_event(
+            task,
+            StreamingEvent::ProviderFallback {
+                message_id,
+                requested_chat_provider_id: requested_chat_provider_id.to_string(),
+                chat_provider_id: chat_provider_id.to_string(),
+            },
+            "broadcast chat provider fallback",
+        )
+        .await;
+    }
+    let message: MSG = MessageSubmitStreamingResponseProviderFallback {
+        message_id,
+        requested_chat_provider_id: requested_chat_provider_id.to_string(),
+        chat_provider_id: chat_provider_id.to_string(),
+    }
+    .into();
+    send_generation_event(&message, tx.clone()).await
+}
+
+/// Record the possible prompt injections found in the untrusted content of the generation.
+fn with_prompt_injection_warnings(
+    generation_metadata: Option<GenerationMetadata>,
+    prompt_injection_warnings: Vec<PromptInjectionWarning>,
+) -> Option<GenerationMetadata> {
+    if prompt_injection_warnings.is_empty() {
+        return generation_metadata;
+    }
+    Some(GenerationMetadata {
+        prompt_injection_warnings: Some(prompt_injection_warnings),
+        ..generation_metadata.unwrap_or_default()
+    })
+}
+
+fn with_output_compliance_matches(
+    generation_metadata: Option<GenerationMetadata>,
+    output_compliance_matches: Vec<OutputComplianceMatch>,
+) -> Option<GenerationMetadata> {
+    if output_compliance_matches.is_empty() {
+        return generation_metadata;
+    }
+    Some(GenerationMetadata {
+        output_compliance_matches: Some(output_compliance_matches),
+        ..generation_metadata.unwrap_or_default()
+    })
+}
+
+fn with_output_transformations(
+    generation_metadata: Option<GenerationMetadata>,
+    output_transformations: Vec<OutputTransformation>,
+) -> Option<GenerationMetadata> {
+    if output_transformations.is_empty() {
+        return generation_metadata;
+    }
+    Some(GenerationMetadata {
+        output_transformations: Some(output_transformations),
+        ..generation_metadata.unwrap_or_default()
+    })
+}
+
+/// Normalize the markdown of the final text of the generation, and record whether it was changed.
+fn with_markdown_normalization(
+    generation_metadata: Option<GenerationMetadata>,
+    content: &mut [ContentPart],
+    normalize_markdown: bool,
+) -> Option<GenerationMetadata> {
+    if !normalize_markdown {
+        return generation_metadata;
+    }
+    let markdown_normalized = normalize_markdown_content(content);
+    Some(GenerationMetadata {
+        markdown_normalized: Some(markdown_normalized),
+        ..generation_metadata.unwrap_or_default()
+    })
+}
+
+/// Record the time to the first token of the final turn and the total duration of the generation.
+fn with_generation_timings(
+    generation_metadata: Option<GenerationMetadata>,
+    time_to_first_token: Option<Duration>,
+    generation_duration: Duration,
+) -> Option<GenerationMetadata> {
+    Some(GenerationMetadata {
+        time_to_first_token_ms: time_to_first_token.map(duration_millis),
+        generation_duration_ms: Some(duration_millis(generation_duration)),
+        ..generation_metadata.unwrap_or_default()
+    })
+}
+
+/// Add the timings of the generation to the Langfuse trace metadata.
+fn with_generation_timings_trace_metadata(
+    trace_metadata: Option<JsonValue>,
+    time_to_first_token: Option<Duration>,
+    generation_duration: Duration,
+) -> Option<JsonValue> {
+    let mut trace_metadata = trace_metadata.unwrap_or_else(|| json!({}));
+    if let Some(metadata) = trace_metadata.as_object_mut() {
+        if let Some(time_to_first_token) = time_to_first_token {
+            metadata.insert(
+                "time_to_first_token_ms".to_string(),
+                json!(duration_millis(time_to_first_token)),
+            );
+        }
+        metadata.insert(
+            "generation_duration_ms".to_string(),
+            json!(duration_millis(generation_duration)),
+        );
+    }
+    Some(trace_metadata)
+}
+
+fn duration_millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Record why the generation ended. Aborts and errors take precedence over the stop reason
+/// reported by the provider, and a missing provider stop reason counts as a natural end.
+/// Record the ID of the capture of the traffic to the chat provider, if the generation was
+/// captured.
+fn with_provider_capture(
+    generation_metadata: Option<GenerationMetadata>,
+    provider_capture_id: Option<String>,
+) -> Option<GenerationMetadata> {
+    match provider_capture_id {
+        Some(provider_capture_id) => Some(GenerationMetadata {
+            provider_capture_id: Some(provider_capture_id),
+            ..generation_metadata.unwrap_or_default()
+        }),
+        None => generation_metadata,
+    }
+}
+
+/// Record that the generation was replayed from the generation cache.
+fn with_generation_cache_hit(
+    generation_metadata: Option<GenerationMetadata>,
+    generation_cache_hit: bool,
+) -> Option<GenerationMetadata> {
+    if !generation_cache_hit {
+        return generation_metadata;
+    }
+    Some(GenerationMetadata {
+        cache_hit: Some(true),
+        ..generation_metadata.unwrap_or_default()
+    })
+}
+
+/// Record the prompt tokens that were read from or written to the prompt cache of the provider.
+fn with_prompt_cache_usage(
+    generation_metadata: Option<GenerationMetadata>,
+    cache_read_tokens: u32,
+    cache_write_tokens: u32,
+) -> Option<GenerationMetadata> {
+    if cache_read_tokens == 0 && cache_write_tokens == 0 {
+        return generation_metadata;
     }
+    Some(GenerationMetadata {
+        used_cache_read_tokens: (cache_read_tokens > 0).then_some(cache_read_tokens),
+        used_cache_write_tokens: (cache_write_tokens > 0).then_some(cache_write_tokens),
+        ..generation_metadata.unwrap_or_default()
+    })
+}
+
+/// Record the tools that were offered to the model and the options the chat provider was called
+/// with.
+fn with_offered_tools(
+    generation_metadata: Option<GenerationMetadata>,
+    offered_tools: OfferedTools,
+    chat_options: GenerationChatOptions,
+) -> Option<GenerationMetadata> {
+    Some(GenerationMetadata {
+        offered_tools: Some(offered_tools),
+        chat_options: Some(chat_options),
+        ..generation_metadata.unwrap_or_default()
+    })
+}
+
+/// The tools of a request, with the MCP server that provides them.
+fn offered_tools(
+    chat_request: &ChatRequest,
+    available_mcp_tools: &[crate::services::mcp_session_manager::ManagedTool],
+) -> OfferedTools {
+    let tools = chat_request
+        .tools
+        .iter()
+        .flatten()
+        .map(|tool| {
+            let name = match &tool.name {
+                GenaiToolName::Custom(name) => name.clone(),
+                other => format!("{other:?}"),
+            };
+            OfferedTool {
+                server_id: available_mcp_tools
+                    .iter()
+                    .find(|mcp_tool| mcp_tool.tool.name == name)
+                    .map(|mcp_tool| mcp_tool.server_id.clone()),
+                name,
+            }
+        })
+        .collect();
+    OfferedTools::new(tools)
+}
+
+fn with_stop_reason(
+    generation_metadata: Option<GenerationMetadata>,
+    provider_stop_reason: Option<StopReason>,
+) -> Option<GenerationMetadata> {
+    let generation_metadata = generation_metadata.unwrap_or_default();
+    let stop_reason = if generation_metadata.was_aborted == Some(true) {
+        StopReason::Cancelled
+    } else if generation_metadata.error.is_some() {
+        StopReason::Error
+    } else {
+        provider_stop_reason.unwrap_or(StopReason::Completed)
+    };
+    Some(GenerationMetadata {
+        stop_reason: Some(stop_reason),
+        ..generation_metadata
+    })
+}
+
+/// Record the mermaid diagrams and math blocks of the final message content in the
+/// generation metadata, so the client can fall back to plain code rendering for invalid ones.
+fn with_renderable_blocks(
+    generation_metadata: Option<GenerationMetadata>,
+    content: &[ContentPart],
+) -> Option<GenerationMetadata> {
+    let renderable_blocks = find_renderable_blocks(content);
+    if renderable_blocks.is_empty() {
+        return generation_metadata;
+    }
+    renderable_blocks.iter().for_each(report_renderable_block);
+    Some(GenerationMetadata {
+        renderable_blocks: Some(renderable_blocks),
+        ..generation_metadata.unwrap_or_default()
+    })
 }
 
 // New background task version
@@ -4492,12 +6886,23 @@ async fn bg_stream_update_assistant_message_completion(
     me_user: &MeProfile,
     assistant_message_id: Uuid,
 ) -> Result<(), Report> {
+    let stored_content_parts = spill_oversized_content(
+        app_state.default_file_storage_provider(),
+        &app_state.config.chat.content_spillover,
+        SPILL_ORIGIN_WRITE,
+        split_long_text_parts(
+            &app_state.config.chat.long_messages,
+            final_content_parts.clone(),
+        ),
+    )
+    .await
+    .wrap_err("Failed to spill oversized assistant message content")?;
     let updated_assistant_message = crate::models::message::update_message_content(
         &app_state.db,
         policy,
         &me_user.to_subject(),
         &assistant_message_id,
-        final_content_parts.clone(),
+        stored_content_parts,
     )
     .await
     .wrap_err("Failed to update assistant message content")?;
@@ -4515,10 +6920,13 @@ async fn bg_stream_update_assistant_message_completion(
     let mut updated_assistant_message_wrapped = updated_assistant_message_wrapped;
     updated_assistant_message_wrapped.content = hydrated_final_content_parts.clone();
 
+    let (trace_id, span_id) = current_trace_ids();
     task.send_event(StreamingEvent::AssistantMessageCompleted {
         message_id: updated_assistant_message.id,
         content: hydrated_final_content_parts,
         message: updated_assistant_message_wrapped,
+        trace_id,
+        span_id,
     })
     .await
     .map_err(Report::msg)?;
@@ -4538,12 +6946,23 @@ async fn stream_update_assistant_message_completion<
     assistant_message_id: Uuid,
 ) -> Result<(), Report> {
     // Update the assistant message in the database
+    let stored_content_parts = spill_oversized_content(
+        app_state.default_file_storage_provider(),
+        &app_state.config.chat.content_spillover,
+        SPILL_ORIGIN_WRITE,
+        split_long_text_parts(
+            &app_state.config.chat.long_messages,
+            final_content_parts.clone(),
+        ),
+    )
+    .await
+    .wrap_err("Failed to spill oversized assistant message content")?;
     let updated_assistant_message = crate::models::message::update_message_content(
         &app_state.db,
         policy,
         &me_user.to_subject(),
         &assistant_message_id,
-        final_content_parts.clone(),
+        stored_content_parts,
     )
     .await
     .wrap_err("Failed to update assistant message content")?;
@@ -4560,10 +6979,13 @@ async fn stream_update_assistant_message_completion<
     .wrap_err("Failed to hydrate image URLs")?;
     updated_assistant_message_wrapped.content = hydrated_final_content_parts.clone();
 
+    let (trace_id, span_id) = current_trace_ids();
     let message_completed_event: MSG = MessageSubmitStreamingResponseMessageComplete {
         message_id: updated_assistant_message.id, // This is assistant_message_id
         content: hydrated_final_content_parts,
         message: updated_assistant_message_wrapped,
+        trace_id,
+        span_id,
     }
     .into();
     message_completed_event
@@ -4879,7 +7301,8 @@ fn summary_user_message_text_from_generation_input(
                 | ContentPart::TextFilePointer(_)
                 | ContentPart::ImageFilePointer(_)
                 | ContentPart::Image(_)
-                | ContentPart::ActionFacetMarker(_) => None,
+                | ContentPart::ActionFacetMarker(_)
+                | ContentPart::BlobPointer(_) => None,
             }
         })
 }
@@ -4931,6 +7354,7 @@ async fn resolve_audio_transcripts_for_summary(
                 &filename,
                 file_upload_id,
                 &transcript,
+                None,
             );
             message.content = ContentPart::Text(ContentPartText { text });
         }
@@ -4953,6 +7377,8 @@ pub struct FileContentsForGeneration {
     pub id: Uuid,
     pub filename: String,
     pub content: FileContent,
+    /// Synopsis of the parsed text (text files only)
+    pub synopsis: Option<FileSynopsis>,
 }
 
 #[derive(Debug, Clone)]
@@ -5059,6 +7485,7 @@ async fn get_assistant_files_for_generation(
                 id: file_id,
                 filename,
                 content: FileContent::Text(text),
+                synopsis: file_contents.synopsis,
             })
         }
     });
@@ -5581,6 +8008,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolve_unions_pinned_assistant_facets_into_selection() {
+        use super::resolve_effective_selected_facet_ids;
+        use crate::config::{ExperimentalFacetsConfig, FacetConfig};
+        use crate::models::assistant::AssistantWithFiles;
+        use sea_orm::prelude::{DateTimeWithTimeZone, Uuid};
+        use std::collections::HashMap;
+
+        let facet = |display_name: &str| FacetConfig {
+            display_name: display_name.to_string(),
+            ..Default::default()
+        };
+        let mut config = ExperimentalFacetsConfig {
+            facets: HashMap::from([
+                ("web_search".to_string(), facet("Web")),
+                ("extended_thinking".to_string(), facet("Thinking")),
+            ]),
+            ..Default::default()
+        };
+        let now: DateTimeWithTimeZone = chrono::Utc::now().into();
+        let assistant = AssistantWithFiles {
+            id: Uuid::new_v4(),
+            owner_user_id: Uuid::new_v4(),
+            name: "Researcher".to_string(),
+            description: None,
+            prompt: "You research things.".to_string(),
+            mcp_server_ids: None,
+            facet_ids: None,
+            default_chat_provider: None,
+            enforce_facet_settings: false,
+            welcome_message: None,
+            pinned_facet_ids: Some(vec!["web_search".to_string()]),
+            category: None,
+            tags: vec![],
+            visibility: "private".to_string(),
+            featured: false,
+            archived_at: None,
+            created_at: now,
+            updated_at: now,
+            files: vec![],
+        };
+
+        // The pinned facet is enabled even if the client deselected it.
+        assert_eq!(
+            resolve_effective_selected_facet_ids(&config, &[], Some(&assistant)),
+            vec!["web_search".to_string()]
+        );
+        assert_eq!(
+            resolve_effective_selected_facet_ids(
+                &config,
+                &["extended_thinking".to_string(), "web_search".to_string()],
+                Some(&assistant),
+            ),
+            vec!["web_search".to_string(), "extended_thinking".to_string()]
+        );
+
+        // With a single selectable facet, the pinned one wins.
+        config.only_single_facet = true;
+        assert_eq!(
+            resolve_effective_selected_facet_ids(
+                &config,
+                &["extended_thinking".to_string()],
+                Some(&assistant),
+            ),
+            vec!["web_search".to_string()]
+        );
+    }
+
     #[test]
     fn merge_action_facet_allowlist_leaves_none_unrestricted() {
         // No MCP allowlist active (all tools allowed): an action facet must not
@@ -5902,7 +8397,7 @@ async fn validate_message_role(
     policy: &PolicyEngine,
     me_user: &MeProfile,
     message_id: &Uuid,
-    expected_role: MessageRole,
+    expected_roles: &[MessageRole],
     message_name: &str, // e.g., "previous_message_id", "current_message_id", "message_id"
 ) -> Result<MessageSchema, (axum::http::StatusCode, String)> {
     let message =
@@ -5915,7 +8410,16 @@ async fn validate_message_role(
             }
             Ok(msg) => msg,
         };
+    check_message_role(&message, expected_roles, message_name)
+}
 
+/// Checks that a message has the expected role, like `validate_message_role`, for a message that
+/// was already loaded.
+fn check_message_role(
+    message: &messages::Model,
+    expected_roles: &[MessageRole],
+    message_name: &str,
+) -> Result<MessageSchema, (axum::http::StatusCode, String)> {
     let message_parsed = match MessageSchema::validate(&message.raw_message) {
         Err(err) => {
             return Err((
@@ -5926,18 +8430,17 @@ async fn validate_message_role(
         Ok(parsed) => parsed,
     };
 
-    if message_parsed.role != expected_role {
+    if !expected_roles.contains(&message_parsed.role) {
+        let expected_roles: Vec<String> = expected_roles
+            .iter()
+            .map(|role| format!("`{role}`"))
+            .collect();
         return Err((
             axum::http::StatusCode::BAD_REQUEST,
             format!(
-                "The provided `{}` must be the message ID of a message with role `{}`.",
+                "The provided `{}` must be the message ID of a message with role {}.",
                 message_name,
-                match expected_role {
-                    MessageRole::User => "user",
-                    MessageRole::Assistant => "assistant",
-                    MessageRole::System => "system",
-                    MessageRole::Tool => "tool",
-                }
+                expected_roles.join(" or ")
             ),
         ));
     }
@@ -5981,7 +8484,7 @@ async fn validate_regenerate_request(
         policy,
         me_user,
         current_message_id,
-        MessageRole::Assistant,
+        &[MessageRole::Assistant],
         "current_message_id",
     )
     .await?;
@@ -6012,7 +8515,7 @@ async fn validate_regenerate_request(
         policy,
         me_user,
         &previous_message_id,
-        MessageRole::User,
+        &[MessageRole::User],
         "previous message of the provided current_message_id",
     )
     .await?;
@@ -6023,29 +8526,79 @@ async fn validate_regenerate_request(
     })
 }
 
-/// Validates the submit endpoint requirements:
-/// - previous_message_id (if provided) must exist and be an assistant message
+/// Validates the continue endpoint requirements:
+/// - the regenerate requirements for current_message_id
+/// - the generation of the current message must have stopped at the max-token limit
+async fn validate_continue_request(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    current_message_id: &Uuid,
+) -> Result<RegenerateValidationResult, (axum::http::StatusCode, String)> {
+    let validation_result =
+        validate_regenerate_request(app_state, policy, me_user, current_message_id).await?;
+
+    let stop_reason = validation_result
+        .current_message
+        .generation_metadata
+        .as_ref()
+        .and_then(|metadata| serde_json::from_value::<GenerationMetadata>(metadata.clone()).ok())
+        .and_then(|metadata| metadata.stop_reason);
+    if stop_reason != Some(StopReason::MaxTokens) {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "The current message did not stop at the max-token limit and can not be continued"
+                .to_string(),
+        ));
+    }
+
+    Ok(validation_result)
+}
+
+/// Validates the submit endpoint requirements:
+/// - previous_message_id (if provided) must exist and be an assistant message
+///
+/// In an ephemeral chat, the previous message is looked up in the messages of the chat.
 async fn validate_submit_request(
     app_state: &AppState,
     policy: &PolicyEngine,
     me_user: &MeProfile,
     previous_message_id: Option<&Uuid>,
     input_file_ids: &[Uuid],
-) -> Result<(), (axum::http::StatusCode, String)> {
-    validate_file_uploads_for_message_submit(app_state, policy, me_user, input_file_ids).await?;
+    ephemeral_chat: Option<&EphemeralChat>,
+) -> Result<Vec<InputFileModality>, (axum::http::StatusCode, String)> {
+    let input_files =
+        validate_file_uploads_for_message_submit(app_state, policy, me_user, input_file_ids)
+            .await?;
 
-    if let Some(prev_msg_id) = previous_message_id {
+    // System messages that weren't answered can be followed by further messages
+    let expected_roles = [MessageRole::Assistant, MessageRole::System];
+    if let Some(prev_msg_id) = previous_message_id
+        && let Some(ephemeral_chat) = ephemeral_chat
+    {
+        let message = ephemeral_chat
+            .messages
+            .iter()
+            .find(|message| message.id == *prev_msg_id)
+            .ok_or_else(|| {
+                (
+                    axum::http::StatusCode::BAD_REQUEST,
+                    "Failed to get previous_message_id: Message not found".to_string(),
+                )
+            })?;
+        check_message_role(message, &expected_roles, "previous_message_id")?;
+    } else if let Some(prev_msg_id) = previous_message_id {
         validate_message_role(
             app_state,
             policy,
             me_user,
             prev_msg_id,
-            MessageRole::Assistant,
+            &expected_roles,
             "previous_message_id",
         )
         .await?;
     }
-    Ok(())
+    Ok(input_files)
 }
 
 /// Validates the edit endpoint requirements:
@@ -6070,7 +8623,7 @@ async fn validate_edit_request(
         policy,
         me_user,
         message_id,
-        MessageRole::User,
+        &[MessageRole::User],
         "message_id",
     )
     .await?;
@@ -6083,8 +8636,9 @@ async fn validate_file_uploads_for_message_submit(
     policy: &PolicyEngine,
     me_user: &MeProfile,
     input_file_ids: &[Uuid],
-) -> Result<(), (axum::http::StatusCode, String)> {
+) -> Result<Vec<InputFileModality>, (axum::http::StatusCode, String)> {
     let mut audio_attachment_count = 0usize;
+    let mut input_files = Vec::with_capacity(input_file_ids.len());
 
     for file_upload_id in input_file_ids {
         let file_upload = crate::models::file_upload::get_file_upload_by_id(
@@ -6117,9 +8671,392 @@ async fn validate_file_uploads_for_message_submit(
                 "Only one audio transcription attachment is allowed per message.".to_string(),
             ));
         }
+
+        input_files.push(InputFileModality {
+            modality: input_modality_for_filename(&file_upload.filename),
+            file_id: file_upload.id,
+            filename: file_upload.filename,
+        });
     }
 
-    Ok(())
+    Ok(input_files)
+}
+
+/// An attachment of a message, with the modality it is provided to the model as.
+struct InputFileModality {
+    file_id: Uuid,
+    filename: String,
+    modality: InputModality,
+}
+
+/// Why an attachment can't be processed by the chat provider of a generation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UnsupportedInputFileReason {
+    /// The chat provider doesn't accept attachments of this modality
+    UnsupportedModality,
+    /// The message has more attachments than the chat provider accepts
+    TooManyFiles,
+}
+
+/// An attachment that can't be processed by the chat provider of a generation
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UnsupportedInputFile {
+    /// The ID of the file
+    pub file_id: String,
+    /// The name of the file
+    pub filename: String,
+    /// The modality the file is provided to the model as
+    pub modality: InputModality,
+    pub reason: UnsupportedInputFileReason,
+}
+
+/// Error body of a message submission or edit with attachments that the chat provider of the
+/// generation can't process
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UnsupportedInputFilesError {
+    pub error: String,
+    /// The chat provider the message would be generated with
+    pub chat_provider_id: String,
+    /// The attachments the chat provider can't process
+    pub unsupported_files: Vec<UnsupportedInputFile>,
+    /// Another chat provider available to the user that can process all attachments of the
+    /// message
+    #[schema(nullable = false)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggested_chat_provider_id: Option<String>,
+}
+
+/// Error body of a generation when none of the chat providers it may use is available to the user
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatProviderUnavailableError {
+    pub error: String,
+    /// The chat provider that was requested, or that the chat or assistant refers to
+    #[schema(nullable = false)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested_chat_provider_id: Option<String>,
+    /// The chat providers available to the user, in priority order
+    pub available_chat_provider_ids: Vec<String>,
+}
+
+/// The attachments that a model with the given capabilities can't process, in the order they
+/// were attached.
+fn unsupported_input_files(
+    model_capabilities: &ModelCapabilities,
+    input_files: &[InputFileModality],
+) -> Vec<UnsupportedInputFile> {
+    let mut accepted_files = 0usize;
+    input_files
+        .iter()
+        .filter_map(|input_file| {
+            let reason = if !model_capabilities.supports_input_modality(input_file.modality) {
+                UnsupportedInputFileReason::UnsupportedModality
+            } else if model_capabilities
+                .max_input_files
+                .is_some_and(|max_input_files| accepted_files >= max_input_files)
+            {
+                UnsupportedInputFileReason::TooManyFiles
+            } else {
+                accepted_files += 1;
+                return None;
+            };
+            Some(UnsupportedInputFile {
+                file_id: input_file.file_id.to_string(),
+                filename: input_file.filename.clone(),
+                modality: input_file.modality,
+                reason,
+            })
+        })
+        .collect()
+}
+
+/// Rejects attachments that the chat provider of the generation can't process with 422, before
+/// anything is stored.
+///
+/// The body is a JSON [`UnsupportedInputFilesError`]. When another chat provider available to the
+/// user can process all attachments, it is suggested, so that clients can offer to switch.
+async fn validate_input_files_for_chat_provider(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    requested_chat_provider_id: Option<&str>,
+    input_files: &[InputFileModality],
+) -> Result<(), (axum::http::StatusCode, String)> {
+    if input_files.is_empty() {
+        return Ok(());
+    }
+
+    let chat_provider_allowlist =
+        chat_provider_allowlist_for_user(app_state, policy, me_user).await?;
+    let allowlist_refs: Option<Vec<&str>> = chat_provider_allowlist
+        .as_ref()
+        .map(|list| list.iter().map(|s| s.as_str()).collect());
+    // Chat providers that are not available to the user are rejected by
+    // `validate_chat_provider_available`.
+    let Ok(chat_provider_id) = app_state.config.determine_chat_provider_with_fallback(
+        allowlist_refs.as_deref(),
+        requested_chat_provider_id,
+        None,
+    ) else {
+        return Ok(());
+    };
+
+    let unsupported_files = unsupported_input_files(
+        &app_state
+            .config
+            .get_chat_provider(chat_provider_id)
+            .model_capabilities,
+        input_files,
+    );
+    if unsupported_files.is_empty() {
+        return Ok(());
+    }
+
+    let suggested_chat_provider_id = app_state
+        .config
+        .available_chat_providers(allowlist_refs.as_deref())
+        .into_iter()
+        .filter(|provider_id| *provider_id != chat_provider_id)
+        .find(|provider_id| {
+            unsupported_input_files(
+                &app_state
+                    .config
+                    .get_chat_provider(provider_id)
+                    .model_capabilities,
+                input_files,
+            )
+            .is_empty()
+        })
+        .map(String::from);
+    let error = UnsupportedInputFilesError {
+        error: format!(
+            "The selected model can't process the attached files: {}",
+            unsupported_files
+                .iter()
+                .map(|file| file.filename.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        chat_provider_id: chat_provider_id.to_string(),
+        unsupported_files,
+        suggested_chat_provider_id,
+    };
+    let body = serde_json::to_string(&error).map_err(|e| {
+        (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to serialize the error: {}", e),
+        )
+    })?;
+    Err((axum::http::StatusCode::UNPROCESSABLE_ENTITY, body))
+}
+
+/// The chat providers the user may use, or `None` if all of them are available.
+async fn chat_provider_allowlist_for_user(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+) -> Result<Option<Vec<String>>, (axum::http::StatusCode, String)> {
+    app_state
+        .determine_chat_provider_allowlist_for_user(policy, &me_user.to_subject(), &me_user.groups)
+        .await
+        .map_err(|e| {
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to determine available chat providers: {}", e),
+            )
+        })
+}
+
+/// Reject a generation with 422 before anything is stored if no chat provider is available for
+/// it.
+///
+/// A requested chat provider that is no longer configured falls back to the available chat
+/// provider with the highest priority, so this only rejects chat providers that are not available
+/// to the user, or users that can't use any chat provider. The body is a JSON
+/// [`ChatProviderUnavailableError`].
+async fn validate_chat_provider_available(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    requested_chat_provider_id: Option<&str>,
+) -> Result<(), (axum::http::StatusCode, String)> {
+    let chat_provider_allowlist =
+        chat_provider_allowlist_for_user(app_state, policy, me_user).await?;
+    let allowlist_refs: Option<Vec<&str>> = chat_provider_allowlist
+        .as_ref()
+        .map(|list| list.iter().map(|s| s.as_str()).collect());
+    let Err(report) = app_state.config.determine_chat_provider_with_fallback(
+        allowlist_refs.as_deref(),
+        requested_chat_provider_id,
+        None,
+    ) else {
+        return Ok(());
+    };
+
+    tracing::info!(
+        requested_chat_provider_id,
+        error = %report,
+        "No chat provider is available for the generation"
+    );
+    let available_chat_provider_ids: Vec<String> = app_state
+        .config
+        .available_chat_providers(allowlist_refs.as_deref())
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let error = ChatProviderUnavailableError {
+        error: match requested_chat_provider_id {
+            Some(requested) => format!(
+                "The model '{}' is not available. Available models: {}",
+                requested,
+                available_chat_provider_ids.join(", ")
+            ),
+            None => "No model is available".to_string(),
+        },
+        requested_chat_provider_id: requested_chat_provider_id.map(String::from),
+        available_chat_provider_ids,
+    };
+    let body = serde_json::to_string(&error).map_err(|e| {
+        (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to serialize the error: {}", e),
+        )
+    })?;
+    Err((axum::http::StatusCode::UNPROCESSABLE_ENTITY, body))
+}
+
+/// Reject a generation with 429 if the user reached their quota of the chat provider./// Reject a generation with 429 if the user reached their quota of the chat provider.
+///
+/// The body is JSON, with the usage of every limit of the quota and the time at which the
+/// reached limits are reset.
+async fn validate_user_quota_for_chat_provider(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    requested_chat_provider_id: Option<&str>,
+) -> Result<(), (axum::http::StatusCode, String)> {
+    if !app_state.config.any_chat_provider_has_user_quota() {
+        return Ok(());
+    }
+
+    let chat_provider_allowlist =
+        chat_provider_allowlist_for_user(app_state, policy, me_user).await?;
+    let allowlist_refs: Option<Vec<&str>> = chat_provider_allowlist
+        .as_ref()
+        .map(|list| list.iter().map(|s| s.as_str()).collect());
+    // Chat providers that are not available to the user are rejected by
+    // `validate_chat_provider_available`.
+    let Ok(chat_provider_id) = app_state.config.determine_chat_provider_with_fallback(
+        allowlist_refs.as_deref(),
+        requested_chat_provider_id,
+        None,
+    ) else {
+        return Ok(());
+    };
+    let Ok(user_id) = Uuid::parse_str(&me_user.id) else {
+        return Ok(());
+    };
+
+    let quota = chat_provider_quotas::user_quota_status(
+        &app_state.db,
+        &app_state.config,
+        &user_id,
+        &me_user.groups,
+        chat_provider_id,
+        Utc::now(),
+    )
+    .await
+    .map_err(|e| {
+        (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to determine the usage quota: {}", e),
+        )
+    })?;
+    let Some(quota) = quota.filter(|quota| quota.exceeded) else {
+        return Ok(());
+    };
+
+    let error = QuotaExceededError {
+        error: match quota.resets_at {
+            Some(resets_at) => format!(
+                "You have reached your usage quota for the selected model. It resets at {}.",
+                resets_at.to_rfc3339()
+            ),
+            None => "You have reached your usage quota for the selected model.".to_string(),
+        },
+        quota,
+    };
+    let body = serde_json::to_string(&error).map_err(|e| {
+        (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to serialize the error: {}", e),
+        )
+    })?;
+    Err((axum::http::StatusCode::TOO_MANY_REQUESTS, body))
+}
+
+/// The default chat provider of the assistant a submitted message is answered by, if any.
+///
+/// Chats or assistants that can't be loaded are ignored here, and reported when the generation
+/// starts.
+async fn submit_assistant_default_chat_provider(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    request: &MessageSubmitRequest,
+) -> Option<String> {
+    let subject = me_user.to_subject();
+    let ephemeral_chat = request
+        .existing_chat_id
+        .and_then(|chat_id| app_state.ephemeral_chats.get(&chat_id, &me_user.id));
+    let chat = if let Some(ephemeral_chat) = ephemeral_chat {
+        Some(ephemeral_chat.chat)
+    } else if let Some(existing_chat_id) = request.existing_chat_id.as_ref() {
+        get_or_create_chat(
+            &app_state.db,
+            policy,
+            &subject,
+            Some(existing_chat_id),
+            &me_user.id,
+            None,
+            None,
+        )
+        .await
+        .ok()
+        .map(|(chat, _)| chat)
+    } else if let Some(previous_message_id) = request.previous_message_id.as_ref() {
+        get_chat_by_message_id(&app_state.db, policy, &subject, previous_message_id)
+            .await
+            .ok()
+    } else {
+        None
+    };
+
+    let assistant = match chat {
+        Some(chat) => crate::models::chat::get_chat_assistant_configuration(
+            &app_state.db,
+            policy,
+            &subject,
+            &chat,
+            app_state.config.assistants.freeze_assistant_config,
+        )
+        .await
+        .ok()
+        .flatten(),
+        None => match request.assistant_id {
+            Some(assistant_id) => crate::models::assistant::get_assistant_with_files(
+                &app_state.db,
+                policy,
+                &subject,
+                assistant_id,
+                false,
+            )
+            .await
+            .ok(),
+            None => None,
+        },
+    };
+    assistant?.default_chat_provider
 }
 
 /// Reject a write into an archived chat with 409 CONFLICT.
@@ -6138,62 +9075,610 @@ fn reject_if_archived(chat: &chats::Model) -> Result<(), (axum::http::StatusCode
     Ok(())
 }
 
-#[utoipa::path(
-    post,
-    path = "/me/messages/submitstream",
-    request_body = MessageSubmitRequest,
-    responses(
-        (status = OK, content_type="text/event-stream", body = MessageSubmitStreamingResponseMessage),
-        (status = BAD_REQUEST, description = "When validation fails (e.g., invalid previous_message_id)"),
-        (status = NOT_FOUND, description = "When the chat does not exist or is not accessible"),
-        (status = CONFLICT, description = "When the chat is archived"),
-        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
-        (status = INTERNAL_SERVER_ERROR, description = "When an internal server error occurs")
-    ),
-    security(
-        ("bearer_auth" = [])
-    )
-)]
-pub async fn message_submit_sse(
-    State(app_state): State<AppState>,
-    Extension(policy): Extension<PolicyEngine>,
-    Extension(me_user): Extension<MeProfile>,
-    headers: HeaderMap,
-    Json(request): Json<MessageSubmitRequest>,
-) -> Result<Sse<impl Stream<Item = Result<Event, Report>>>, (axum::http::StatusCode, String)> {
-    // Validate request parameters
-    validate_submit_request(
-        &app_state,
-        &policy,
-        &me_user,
-        request.previous_message_id.as_ref(),
-        request.input_files_ids.as_slice(),
+/// Reject a generation in a chat in which another replica of the backend is generating an answer
+/// with 409 CONFLICT.
+///
+/// The body is the JSON error, so that clients can follow the running generation instead.
+fn generation_in_progress_response(
+    error: GenerationInProgressError,
+) -> (axum::http::StatusCode, String) {
+    match serde_json::to_string(&error) {
+        Ok(body) => (axum::http::StatusCode::CONFLICT, body),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to serialize the error: {}", e),
+        ),
+    }
+}
+
+/// Reject a generation with 429 TOO MANY REQUESTS because the maximum number of concurrent
+/// generations is reached, and generations over it are not queued.
+fn generation_limit_reached_response() -> (axum::http::StatusCode, String) {
+    (
+        axum::http::StatusCode::TOO_MANY_REQUESTS,
+        "Too many answers are being generated right now. Please try again in a moment.".to_string(),
     )
-    .await?;
+}
 
-    // Validate action facet before spawning background task (returns HTTP 400 on failure)
-    let generation_request_context = generation_request_context_from_headers(&headers);
-    let platform = generation_request_context
-        .platform
-        .as_deref()
-        .unwrap_or(DEFAULT_ERATO_PLATFORM);
-    warn_unknown_platform(&app_state.config, platform);
-    validate_action_facet(&app_state.config, request.action_facet.as_ref(), platform)?;
+/// Whether the error was raised by [`check_previous_message_for_chat`].
+fn is_invalid_previous_message_error(error: &Report) -> bool {
+    error.to_string().contains("Invalid previous_message_id")
+}
 
-    // Determine the chat_id first so we can use it as the background task key
-    let (chat_id, chat_was_created) = if let Some(existing_chat_id) = request.existing_chat_id {
-        let (chat, _) = get_or_create_chat(
-            &app_state.db,
-            &policy,
-            &me_user.to_subject(),
-            Some(&existing_chat_id),
-            &me_user.id,
-            None,
-            None,
+/// Inconsistent previous messages are reported with 422, so clients can tell them apart from a
+/// missing chat.
+fn invalid_previous_message_response(error: Report) -> (axum::http::StatusCode, String) {
+    (
+        axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+        error.to_string(),
+    )
+}
+
+/// Validates that the previous message (if any) can be continued in the given chat: it must belong
+/// to the chat and, unless `allow_branching` is set, be part of its active thread.
+async fn validate_previous_message_for_chat(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    previous_message_id: Option<&Uuid>,
+    chat_id: &Uuid,
+    allow_branching: bool,
+) -> Result<(), (axum::http::StatusCode, String)> {
+    let Some(previous_message_id) = previous_message_id else {
+        return Ok(());
+    };
+    let previous_message = get_message_by_id(
+        &app_state.db,
+        policy,
+        &me_user.to_subject(),
+        previous_message_id,
+    )
+    .await
+    .map_err(|err| {
+        (
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("Failed to get previous_message_id: {}", err),
         )
-        .await
-        .map_err(|e| {
-            let s = e.to_string();
+    })?;
+    check_previous_message_for_chat(&previous_message, chat_id, allow_branching)
+        .map_err(invalid_previous_message_response)
+}
+
+/// Input parameters that are saved with a submitted user message.
+fn submit_user_input_parameters(
+    app_state: &AppState,
+    request: &MessageSubmitRequest,
+) -> Option<crate::models::message::InputParameters> {
+    let detected_language = detect_message_language(
+        &request.user_message,
+        &app_state.message_language_detection_config(),
+    );
+    let placeholder_text =
+        file_only_message_placeholder(app_state, &request.user_message, &request.input_files_ids);
+    (request.action_facet.is_some() || detected_language.is_some() || placeholder_text.is_some())
+        .then(|| crate::models::message::InputParameters {
+            action_facet_id: request.action_facet.as_ref().map(|af| af.id.clone()),
+            action_facet_args: request.action_facet.as_ref().map(|af| af.args.clone()),
+            detected_language,
+            placeholder_text,
+        })
+}
+
+fn submit_prompt_composition_user_input(
+    request: &MessageSubmitRequest,
+    just_submitted_user_message_id: Uuid,
+) -> PromptCompositionUserInput {
+    PromptCompositionUserInput {
+        just_submitted_user_message_id,
+        requested_chat_provider_id: request.chat_provider_id.clone(),
+        new_input_file_ids: request.input_files_ids.clone(),
+        refresh_file_ids: request.refresh_file_ids.clone(),
+        selected_facet_ids: request.selected_facet_ids.clone(),
+        action_facet: request.action_facet.as_ref().map(|af| {
+            crate::services::prompt_composition::types::ActionFacetUserInput {
+                id: af.id.clone(),
+                args: af.args.clone(),
+            }
+        }),
+        requested_response_language: request.response_language.clone(),
+    }
+}
+
+/// Maximum number of characters of a file content that is included in a dry run response.
+const DRY_RUN_MAX_FILE_CONTENT_CHARS: usize = 2000;
+
+/// Truncate the text of a file content part to [`DRY_RUN_MAX_FILE_CONTENT_CHARS`].
+fn truncate_dry_run_file_content(text: &mut String) {
+    if !text.starts_with("File:\n") {
+        return;
+    }
+    let char_count = text.chars().count();
+    if char_count <= DRY_RUN_MAX_FILE_CONTENT_CHARS {
+        return;
+    }
+    let truncated: String = text.chars().take(DRY_RUN_MAX_FILE_CONTENT_CHARS).collect();
+    *text = format!(
+        "{truncated}\n[... {} more characters truncated]",
+        char_count - DRY_RUN_MAX_FILE_CONTENT_CHARS
+    );
+}
+
+fn truncate_dry_run_file_contents(messages: &mut [JsonValue]) {
+    for message in messages {
+        match message.get_mut("content") {
+            Some(JsonValue::String(text)) => truncate_dry_run_file_content(text),
+            Some(JsonValue::Array(parts)) => {
+                for part in parts {
+                    if let Some(JsonValue::String(text)) = part.get_mut("text") {
+                        truncate_dry_run_file_content(text);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn map_dry_run_chat_error(
+    error: Report,
+    not_found_message: &str,
+    internal_error_message: &str,
+) -> (axum::http::StatusCode, String) {
+    let s = error.to_string();
+    if s.contains("not found") || s.contains("Access denied") || s.contains("not authorized") {
+        (
+            axum::http::StatusCode::NOT_FOUND,
+            not_found_message.to_string(),
+        )
+    } else {
+        (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            internal_error_message.to_string(),
+        )
+    }
+}
+
+fn dry_run_internal_error(error: Report) -> (axum::http::StatusCode, String) {
+    let message = error.to_string();
+    (log_internal_server_error(error), message)
+}
+
+/// Run a message submission up to the point where the LLM would be called.
+///
+/// Resolves the chat, checks the policies and prepares the chat request exactly like a
+/// regular submission, but persists nothing: a new chat and the user message only exist
+/// in memory, no background task is started and the policy engine is not invalidated.
+async fn message_submit_dry_run(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    request: &MessageSubmitRequest,
+    generation_request_context: GenerationRequestContext,
+) -> Result<MessageSubmitDryRunResponse, (axum::http::StatusCode, String)> {
+    if !app_state.config.debug.allow_dry_run {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "Dry runs are not enabled".to_string(),
+        ));
+    }
+
+    let subject = me_user.to_subject();
+    let existing_chat = if let Some(existing_chat_id) = request.existing_chat_id {
+        let (chat, _) = get_or_create_chat(
+            &app_state.db,
+            policy,
+            &subject,
+            Some(&existing_chat_id),
+            &me_user.id,
+            None,
+            None,
+        )
+        .await
+        .map_err(|e| map_dry_run_chat_error(e, "Chat not found", "Failed to load chat"))?;
+        validate_previous_message_for_chat(
+            app_state,
+            policy,
+            me_user,
+            request.previous_message_id.as_ref(),
+            &chat.id,
+            false,
+        )
+        .await?;
+        Some(chat)
+    } else if let Some(previous_message_id) = request.previous_message_id.as_ref() {
+        let chat = get_chat_by_message_id(&app_state.db, policy, &subject, previous_message_id)
+            .await
+            .map_err(|e| {
+                map_dry_run_chat_error(
+                    e,
+                    "Chat or previous message not found",
+                    "Failed to load chat",
+                )
+            })?;
+        validate_previous_message_for_chat(
+            app_state,
+            policy,
+            me_user,
+            Some(previous_message_id),
+            &chat.id,
+            false,
+        )
+        .await?;
+        Some(chat)
+    } else {
+        None
+    };
+
+    let is_existing_chat = existing_chat.is_some();
+    let chat = match existing_chat {
+        Some(chat) => {
+            reject_if_archived(&chat)?;
+            authorize!(
+                policy,
+                &subject,
+                &Resource::Chat(chat.id.to_string()),
+                Action::SubmitMessage
+            )
+            .map_err(|e| (axum::http::StatusCode::FORBIDDEN, e.to_string()))?;
+            chat
+        }
+        None => {
+            authorize!(policy, &subject, &Resource::ChatSingleton, Action::Create)
+                .map_err(|e| (axum::http::StatusCode::FORBIDDEN, e.to_string()))?;
+            let assistant_configuration = request
+                .assistant_id
+                .map(|assistant_id| AssistantConfiguration::new(assistant_id).to_json())
+                .transpose()
+                .map_err(dry_run_internal_error)?;
+            let now = Utc::now().into();
+            chats::Model {
+                id: Uuid::new_v4(),
+                owner_user_id: me_user.id.clone(),
+                created_at: now,
+                updated_at: now,
+                title_by_summary: None,
+                archived_at: None,
+                title_by_user_provided: request.title_by_user_provided.clone(),
+                assistant_configuration,
+                assistant_id: request.assistant_id,
+                active_generation_id: None,
+                generation_state: None,
+                generation_started_at: None,
+                generation_heartbeat_at: None,
+                generation_ended_at: None,
+                organization_id: me_user.organization_id.clone(),
+                generation_replica_id: None,
+                generation_message_id: None,
+                assistant_snapshot: None,
+                provisional: false,
+                custom_instruction: None,
+                response_language: None,
+            }
+        }
+    };
+
+    let input_parameters = submit_user_input_parameters(app_state, request)
+        .map(serde_json::to_value)
+        .transpose()
+        .map_err(|e| dry_run_internal_error(Report::new(e)))?;
+    let now = Utc::now().into();
+    let unsaved_user_message = messages::Model {
+        id: Uuid::new_v4(),
+        chat_id: chat.id,
+        raw_message: submitted_message_raw_json(request.role, &request.user_message, &me_user.id),
+        created_at: now,
+        updated_at: now,
+        previous_message_id: request.previous_message_id,
+        sibling_message_id: None,
+        is_message_in_active_thread: true,
+        generation_input_messages: None,
+        input_file_uploads: (!request.input_files_ids.is_empty())
+            .then(|| request.input_files_ids.clone()),
+        generation_parameters: None,
+        generation_metadata: None,
+        input_parameters,
+        is_welcome_message: false,
+        content_draft: None,
+        edit_of_message_id: None,
+    };
+
+    let me_profile_input = MeProfileChatRequestInput::from_me_profile(me_user);
+    let user_input = submit_prompt_composition_user_input(request, unsaved_user_message.id);
+    let prepared = prepare_chat_request(
+        app_state,
+        policy,
+        &chat,
+        user_input,
+        generation_request_context,
+        &me_profile_input,
+        Some(unsaved_user_message),
+    )
+    .await
+    .map_err(|e| dry_run_internal_error(e.wrap_err("Failed to prepare chat request")))?;
+
+    let request_parts =
+        crate::services::genai::into_openai_request_parts(&prepared.chat_request)
+            .map_err(|e| dry_run_internal_error(e.wrap_err("Failed to convert chat request")))?;
+    let mut messages = request_parts.messages;
+    truncate_dry_run_file_contents(&mut messages);
+    let offered_tools = offered_tools(&prepared.chat_request, &prepared.available_mcp_tools);
+
+    let model_settings = prepared.effective_model_settings;
+    Ok(MessageSubmitDryRunResponse {
+        chat_id: is_existing_chat.then_some(chat.id),
+        chat_provider_id: prepared
+            .generation_parameters
+            .generation_chat_provider_id
+            .unwrap_or_default(),
+        messages,
+        tools: request_parts.tools.unwrap_or_default(),
+        offered_tools,
+        chat_options: MessageSubmitDryRunChatOptions {
+            temperature: model_settings.temperature,
+            top_p: model_settings.top_p,
+            reasoning_effort: model_settings.reasoning_effort,
+            verbosity: model_settings.verbosity,
+        },
+        token_estimate: prepared.token_breakdown.map(Into::into),
+        prompt_manifest: prepared.prompt_manifest,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/me/messages/submitstream",
+    request_body = MessageSubmitRequest,
+    responses(
+        (status = OK, content(
+            (MessageSubmitStreamingResponseMessage = "text/event-stream"),
+            (MessageSubmitDryRunResponse = "application/json"),
+        )),
+        (status = BAD_REQUEST, description = "When validation fails (e.g., invalid previous_message_id), or when dry runs are requested but not enabled"),
+        (status = FORBIDDEN, description = "When a dry run is not allowed to submit a message to the chat"),
+        (status = NOT_FOUND, description = "When the chat does not exist or is not accessible"),
+        (status = CONFLICT, body = GenerationInProgressError, description = "When the chat is archived. Also when another replica of the backend is generating an answer in the chat, with a JSON body that contains the message being generated and the replica generating it"),
+        (status = UNPROCESSABLE_ENTITY, body = UnsupportedInputFilesError, description = "When the message is empty or only consists of whitespace, and no file is attached. Also when the chat provider of the generation can't process the attached files. The body is JSON, with a suggested chat provider that can process them, if available. Also when `previous_message_id` belongs to another chat than `existing_chat_id`, or is not part of the chat's active thread. Also when the chat provider of the generation is not available to the user, with a `ChatProviderUnavailableError` body"),
+        (status = TOO_MANY_REQUESTS, body = QuotaExceededError, description = "When the user reached their quota of the chat provider of the generation. The body is JSON, with the usage of the quota and the time it resets at. Also when the maximum number of concurrent generations is reached and `chat.queue_when_limited` is disabled"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "When an internal server error occurs")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn message_submit_sse(
+    State(app_state): State<AppState>,
+    Extension(policy): Extension<PolicyEngine>,
+    Extension(me_user): Extension<MeProfile>,
+    headers: HeaderMap,
+    Json(request): Json<MessageSubmitRequest>,
+) -> Result<Response, (axum::http::StatusCode, String)> {
+    let generation_request_context =
+        validate_message_submit(&app_state, &policy, &me_user, &request, &headers).await?;
+
+    if request.dry_run {
+        let response = message_submit_dry_run(
+            &app_state,
+            &policy,
+            &me_user,
+            &request,
+            generation_request_context,
+        )
+        .await?;
+        return Ok(Json(response).into_response());
+    }
+
+    let (chat_id, broadcast_rx, generation_span) = start_message_submit(
+        &app_state,
+        &policy,
+        &me_user,
+        request,
+        generation_request_context,
+    )
+    .await?;
+    // Only missing if the generation already ended
+    let task = app_state.background_tasks.get_task(&chat_id).await;
+
+    // Convert broadcast receiver to SSE stream
+    let event_stream = {
+        use futures::StreamExt;
+        let broadcast_stream = tokio_stream::wrappers::BroadcastStream::new(broadcast_rx);
+        let streaming_events = futures::StreamExt::filter_map(broadcast_stream, |result| {
+            futures::future::ready(match result {
+                Ok(streaming_event) => Some(streaming_event),
+                Err(tokio_stream::wrappers::errors::BroadcastStreamRecvError::Lagged(n)) => {
+                    tracing::warn!("Client lagged behind by {} events", n);
+                    None
+                }
+            })
+        });
+        // Only this stream is paced; the generation and resumed streams are never slowed down.
+        // Weak, so that the stream still ends once the task is dropped
+        let pacing_task = task.as_ref().map(Arc::downgrade);
+        pace_streaming_events(Box::pin(streaming_events), move || {
+            pacing_task
+                .as_ref()
+                .and_then(Weak::upgrade)
+                .and_then(|task| task.output_pacing())
+        })
+        // Convert StreamingEvent to SSE Event
+        .map(|streaming_event| streaming_event_to_sse(&streaming_event))
+        .inspect(|event| {
+            if let Err(err) = event {
+                log_and_capture_error("submit SSE serialization", err);
+            }
+        })
+    };
+
+    let sse = Sse::new(ClientDisconnectGuard::new(
+        event_stream,
+        task.as_ref(),
+        &app_state,
+    ))
+    .keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(Duration::from_secs(1))
+            .text("keep-alive-text"),
+    );
+    Ok(with_traceparent_header(sse, &generation_span))
+}
+
+/// Validate a message submission before anything is stored, and derive the context of the
+/// generation request from the request headers.
+pub(crate) async fn validate_message_submit(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    request: &MessageSubmitRequest,
+    headers: &HeaderMap,
+) -> Result<GenerationRequestContext, (axum::http::StatusCode, String)> {
+    validate_submit_role(app_state, me_user, request)?;
+    validate_message_content(&request.user_message, &request.input_files_ids)?;
+    let ephemeral_chat = request
+        .existing_chat_id
+        .and_then(|chat_id| app_state.ephemeral_chats.get(&chat_id, &me_user.id));
+    // Validate request parameters
+    let input_files = validate_submit_request(
+        app_state,
+        policy,
+        me_user,
+        request.previous_message_id.as_ref(),
+        request.input_files_ids.as_slice(),
+        ephemeral_chat.as_ref(),
+    )
+    .await?;
+    let requested_chat_provider_id = match request.chat_provider_id.clone() {
+        Some(chat_provider_id) => Some(chat_provider_id),
+        None => submit_assistant_default_chat_provider(app_state, policy, me_user, request).await,
+    };
+    validate_chat_provider_available(
+        app_state,
+        policy,
+        me_user,
+        requested_chat_provider_id.as_deref(),
+    )
+    .await?;
+    // Messages that aren't answered don't count against the quota
+    if request.generates_answer()
+        && (!input_files.is_empty() || app_state.config.any_chat_provider_has_user_quota())
+    {
+        validate_input_files_for_chat_provider(
+            app_state,
+            policy,
+            me_user,
+            requested_chat_provider_id.as_deref(),
+            &input_files,
+        )
+        .await?;
+        validate_user_quota_for_chat_provider(
+            app_state,
+            policy,
+            me_user,
+            requested_chat_provider_id.as_deref(),
+        )
+        .await?;
+    }
+
+    // Validate action facet before spawning background task (returns HTTP 400 on failure)
+    let generation_request_context = generation_request_context_from_headers(headers);
+    let platform = generation_request_context
+        .platform
+        .as_deref()
+        .unwrap_or(DEFAULT_ERATO_PLATFORM);
+    warn_unknown_platform(&app_state.config, platform);
+    validate_action_facet(&app_state.config, request.action_facet.as_ref(), platform)?;
+
+    Ok(generation_request_context)
+}
+
+/// Validate the role of a submitted message, and whether it is answered.
+fn validate_submit_role(
+    app_state: &AppState,
+    me_user: &MeProfile,
+    request: &MessageSubmitRequest,
+) -> Result<(), (axum::http::StatusCode, String)> {
+    match request.role {
+        MessageSubmitRole::User => {
+            if !request.generates_answer() {
+                return Err((
+                    axum::http::StatusCode::BAD_REQUEST,
+                    "Only system messages can be submitted without generating an answer"
+                        .to_string(),
+                ));
+            }
+        }
+        MessageSubmitRole::System => {
+            if !app_state
+                .config
+                .chat
+                .allows_client_system_messages(&me_user.groups)
+            {
+                return Err((
+                    axum::http::StatusCode::FORBIDDEN,
+                    "Not allowed to submit system messages".to_string(),
+                ));
+            }
+            if !request.input_files_ids.is_empty() {
+                return Err((
+                    axum::http::StatusCode::BAD_REQUEST,
+                    "System messages can't have input files".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolve the chat of a message submission and spawn the background task that generates the
+/// answer.
+///
+/// Returns the ID of the chat, a receiver of the events of the generation, and the span of the
+/// generation.
+pub(crate) async fn start_message_submit(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    request: MessageSubmitRequest,
+    generation_request_context: GenerationRequestContext,
+) -> Result<
+    (
+        Uuid,
+        tokio::sync::broadcast::Receiver<StreamingEvent>,
+        tracing::Span,
+    ),
+    (axum::http::StatusCode, String),
+> {
+    // Messages that aren't answered don't count against the concurrency limit
+    let admission = if request.generates_answer() {
+        app_state
+            .background_tasks
+            .admit_generation()
+            .ok_or_else(generation_limit_reached_response)?
+    } else {
+        GenerationAdmission::Started(GenerationPermit::unlimited())
+    };
+
+    // Ephemeral chats are only held in memory, so they are never resolved from the database
+    let ephemeral_chat_id = request
+        .existing_chat_id
+        .filter(|chat_id| app_state.ephemeral_chats.contains(chat_id, &me_user.id));
+
+    // Determine the chat_id first so we can use it as the background task key
+    let (chat_id, chat_was_created) = if let Some(ephemeral_chat_id) = ephemeral_chat_id {
+        (ephemeral_chat_id, false)
+    } else if let Some(existing_chat_id) = request.existing_chat_id {
+        let (chat, _) = get_or_create_chat(
+            &app_state.db,
+            policy,
+            &me_user.to_subject(),
+            Some(&existing_chat_id),
+            &me_user.id,
+            None,
+            None,
+        )
+        .await
+        .map_err(|e| {
+            let s = e.to_string();
             if s.contains("not found")
                 || s.contains("Access denied")
                 || s.contains("not authorized")
@@ -6210,12 +9695,22 @@ pub async fn message_submit_sse(
             }
         })?;
         reject_if_archived(&chat)?;
+        // Without this, a message of another chat could be linked into this chat's thread.
+        validate_previous_message_for_chat(
+            app_state,
+            policy,
+            me_user,
+            request.previous_message_id.as_ref(),
+            &existing_chat_id,
+            false,
+        )
+        .await?;
         (existing_chat_id, false)
     } else {
         // Need to get or create chat to determine the chat_id
         let (chat, chat_status) = get_or_create_chat_by_previous_message_id(
             &app_state.db,
-            &policy,
+            policy,
             &me_user.to_subject(),
             request.previous_message_id.as_ref(),
             &me_user.id,
@@ -6225,7 +9720,9 @@ pub async fn message_submit_sse(
         .await
         .map_err(|e| {
             let s = e.to_string();
-            if s.contains("not found")
+            if is_invalid_previous_message_error(&e) {
+                invalid_previous_message_response(e)
+            } else if s.contains("not found")
                 || s.contains("Access denied")
                 || s.contains("not authorized")
             {
@@ -6245,6 +9742,8 @@ pub async fn message_submit_sse(
         // unaffected; only writes resolved onto an existing archived chat 409.
         reject_if_archived(&chat)?;
 
+        // The chat is provisional until the user message is saved, so it's only announced to the
+        // client and the other sessions of the user by the background task.
         let was_created = chat_status == ChatCreationStatus::Created;
         if was_created {
             app_state.global_policy_engine.invalidate_data().await;
@@ -6253,20 +9752,30 @@ pub async fn message_submit_sse(
         (chat.id, was_created)
     };
 
-    // Start or get background task for this chat
-    let (broadcast_rx, task) = app_state
-        .background_tasks
-        .start_task(chat_id, Uuid::new_v4()) // message_id will be set later
-        .await;
+    // Start or get background task for this chat, the message_id will be set later. The
+    // generations of ephemeral chats are not announced to the other sessions of the user, and
+    // never leave a notification behind.
+    let task_start = if ephemeral_chat_id.is_some() {
+        app_state
+            .background_tasks
+            .start_task(chat_id, Uuid::new_v4())
+            .await
+    } else {
+        app_state
+            .background_tasks
+            .start_task_for_user(chat_id, Uuid::new_v4(), &me_user.id)
+            .await
+    };
+    let (broadcast_rx, task) = task_start.map_err(generation_in_progress_response)?;
 
     // Clone variables for the background task
     let app_state_bg = app_state.clone();
     let policy_bg = policy.clone();
     let me_user_bg = me_user.clone();
     let task_clone = Arc::clone(&task);
-    let request_clone = request.clone();
 
     // Spawn the background generation task
+    let generation_span = crate::telemetry::assistant_message_generation_span(&chat_id);
     tokio::spawn(
         async move {
             let mut cleanup_guard = TaskCleanupGuard::new(
@@ -6275,17 +9784,32 @@ pub async fn message_submit_sse(
                 task_clone.generation_id,
             );
             tracing::info!("Starting background task for chat_id: {}", chat_id);
-            let result = run_message_submit_task(
-                &task_clone,
-                &app_state_bg,
-                &policy_bg,
-                &me_user_bg,
-                &request_clone,
-                generation_request_context,
-                chat_id,
-                chat_was_created,
-            )
-            .await;
+            let result = if ephemeral_chat_id.is_some() {
+                run_ephemeral_message_submit_task(
+                    &task_clone,
+                    &app_state_bg,
+                    &policy_bg,
+                    &me_user_bg,
+                    &request,
+                    generation_request_context,
+                    chat_id,
+                    admission,
+                )
+                .await
+            } else {
+                run_message_submit_task(
+                    &task_clone,
+                    &app_state_bg,
+                    &policy_bg,
+                    &me_user_bg,
+                    &request,
+                    generation_request_context,
+                    chat_id,
+                    chat_was_created,
+                    admission,
+                )
+                .await
+            };
 
             let generation_failed = result.is_err();
             match result {
@@ -6343,40 +9867,10 @@ pub async fn message_submit_sse(
                 .remove_task(&chat_id, task_clone.generation_id, outcome)
                 .await;
         }
-        .in_current_span(),
+        .instrument(generation_span.clone()),
     );
 
-    // Convert broadcast receiver to SSE stream
-    let event_stream = {
-        use futures::StreamExt;
-        let broadcast_stream = tokio_stream::wrappers::BroadcastStream::new(broadcast_rx);
-        futures::StreamExt::filter_map(broadcast_stream, |result| {
-            futures::future::ready(match result {
-                Ok(streaming_event) => {
-                    // Convert StreamingEvent to SSE Event
-                    match streaming_event_to_sse(&streaming_event) {
-                        Ok(sse_event) => Some(Ok(sse_event)),
-                        Err(e) => Some(Err(e)),
-                    }
-                }
-                Err(tokio_stream::wrappers::errors::BroadcastStreamRecvError::Lagged(n)) => {
-                    tracing::warn!("Client lagged behind by {} events", n);
-                    None
-                }
-            })
-        })
-        .inspect(|event| {
-            if let Err(err) = event {
-                log_and_capture_error("submit SSE serialization", err);
-            }
-        })
-    };
-
-    Ok(Sse::new(event_stream).keep_alive(
-        axum::response::sse::KeepAlive::new()
-            .interval(Duration::from_secs(1))
-            .text("keep-alive-text"),
-    ))
+    Ok((chat_id, broadcast_rx, generation_span))
 }
 
 #[cfg(test)]
@@ -6437,13 +9931,31 @@ mod reasoning_replay_tests {
             used_completion_tokens: None,
             used_total_tokens: None,
             used_reasoning_tokens: None,
+            used_cache_read_tokens: None,
+            used_cache_write_tokens: None,
             reasoning_summary,
             reasoning_items,
             reasoning_item_encrypted_content,
             langfuse_trace_id: None,
+            otel_trace_id: None,
             was_aborted: None,
             error: None,
             mcp_servers_unavailable: None,
+            token_breakdown: None,
+            prompt_manifest: None,
+            renderable_blocks: None,
+            prompt_injection_warnings: None,
+            output_compliance_matches: None,
+            output_transformations: None,
+            stop_reason: None,
+            time_to_first_token_ms: None,
+            generation_duration_ms: None,
+            moderation: None,
+            provider_capture_id: None,
+            cache_hit: None,
+            markdown_normalized: None,
+            offered_tools: None,
+            chat_options: None,
         }
     }
 
@@ -6599,17 +10111,25 @@ mod reasoning_replay_tests {
     fn openai_responses_reasoning_replay_is_disabled_when_chat_provider_changes() {
         let matching_parameters = GenerationParameters {
             generation_chat_provider_id: Some("responses-opus".to_string()),
+            requested_chat_provider_id: None,
+            fallback_from_chat_provider_id: None,
+            chat_provider_group_id: None,
             request_context: None,
             selected_facets: HashMap::new(),
             action_facet_id: None,
             action_facet_args: None,
+            response_language: None,
         };
         let changed_parameters = GenerationParameters {
             generation_chat_provider_id: Some("responses-sonnet".to_string()),
+            requested_chat_provider_id: None,
+            fallback_from_chat_provider_id: None,
+            chat_provider_group_id: None,
             request_context: None,
             selected_facets: HashMap::new(),
             action_facet_id: None,
             action_facet_args: None,
+            response_language: None,
         };
 
         assert!(openai_responses_reasoning_replay_model_matches(
@@ -6761,28 +10281,58 @@ mod reasoning_replay_tests {
     }
 
     #[test]
-    fn completed_response_reasoning_is_inserted_before_streamed_text() {
+    fn completed_response_reasoning_keeps_streamed_text_index() {
         let mut content = vec![ContentPart::Text(ContentPartText {
             text: "answer".to_string(),
         })];
+        let mut announced = AnnouncedContentParts::default();
+        announced.announce_text(0);
 
-        assert_eq!(
-            insert_reasoning_part_before_text(&mut content, "summary".into()),
-            0
-        );
+        let content_index = append_reasoning_part(&mut content, "summary".into());
+        announced.announce_reasoning(content_index);
 
+        assert_eq!(content_index, 1);
         assert_eq!(
             content,
             vec![
+                ContentPart::Text(ContentPartText {
+                    text: "answer".to_string()
+                }),
                 ContentPart::Reasoning(ContentPartReasoning {
                     text: "summary".to_string(),
                     ..Default::default()
                 }),
-                ContentPart::Text(ContentPartText {
-                    text: "answer".to_string()
-                }),
             ]
         );
+        assert!(announced.mismatches(&content).is_empty());
+    }
+
+    #[test]
+    fn announced_content_parts_detect_moved_parts() {
+        let tool_use = |tool_call_id: &str| {
+            ContentPart::ToolUse(ToolUse {
+                tool_call_id: tool_call_id.to_string(),
+                ..Default::default()
+            })
+        };
+        let mut announced = AnnouncedContentParts::default();
+        announced.announce_text(0);
+        announced.announce_tool_use(1, "call_a");
+        announced.announce_tool_use(2, "call_b");
+
+        let mut content = vec![
+            ContentPart::Text(ContentPartText {
+                text: "answer".to_string(),
+            }),
+            tool_use("call_a"),
+            tool_use("call_b"),
+        ];
+        assert!(announced.mismatches(&content).is_empty());
+
+        content.swap(1, 2);
+        assert_eq!(announced.mismatches(&content).len(), 2);
+        content.truncate(1);
+        assert_eq!(announced.mismatches(&content).len(), 2);
     }
 
     #[test]
@@ -6929,6 +10479,8 @@ mod summary_generation_tests {
             audio_transcription: audio_transcription.map(str::to_string),
             created_at: chrono::Utc::now().into(),
             updated_at: chrono::Utc::now().into(),
+            organization_id: None,
+            storage_status: "ok".to_string(),
         }
     }
 
@@ -7088,13 +10640,32 @@ fn generation_metadata_for_error(error: GenerationErrorType) -> GenerationMetada
         used_completion_tokens: None,
         used_total_tokens: None,
         used_reasoning_tokens: None,
+        used_cache_read_tokens: None,
+        used_cache_write_tokens: None,
         reasoning_summary: None,
         reasoning_items: None,
         reasoning_item_encrypted_content: None,
         langfuse_trace_id: None,
+        otel_trace_id: crate::telemetry::TraceCorrelation::current()
+            .map(|correlation| correlation.trace_id),
         was_aborted: None,
         error: Some(error),
         mcp_servers_unavailable: None,
+        token_breakdown: None,
+        prompt_manifest: None,
+        renderable_blocks: None,
+        prompt_injection_warnings: None,
+        output_compliance_matches: None,
+        output_transformations: None,
+        stop_reason: Some(StopReason::Error),
+        time_to_first_token_ms: None,
+        generation_duration_ms: None,
+        moderation: None,
+        provider_capture_id: None,
+        cache_hit: None,
+        markdown_normalized: None,
+        offered_tools: None,
+        chat_options: None,
     }
 }
 
@@ -7127,27 +10698,336 @@ async fn persist_background_generation_failure(
         );
         capture_report(&error);
     }
-
-    let error_event = MessageSubmitStreamingResponseError {
-        message_id: Some(assistant_message_id),
-        error: generation_error,
-    };
-    let error =
-        match serde_json::to_value(MessageSubmitStreamingResponseMessage::Error(error_event)) {
-            Ok(error) => Some(error),
-            Err(error) => {
-                let error =
-                    Report::new(error).wrap_err("Failed to serialize generation error event");
-                log_and_capture_error("persist_background_generation_failure", &error);
-                None
-            }
-        };
-    send_background_event(
-        task,
-        StreamingEvent::Error { error },
-        "broadcast persisted generation failure",
-    )
-    .await;
+
+    send_background_generation_failure(task, assistant_message_id, generation_error).await;
+}
+
+/// Send the error of a generation that failed to a background task.
+async fn send_background_generation_failure(
+    task: &Arc<StreamingTask>,
+    assistant_message_id: Uuid,
+    generation_error: GenerationErrorType,
+) {
+    let error_event = MessageSubmitStreamingResponseError {
+        message_id: Some(assistant_message_id),
+        error: generation_error,
+    };
+    let error =
+        match serde_json::to_value(MessageSubmitStreamingResponseMessage::Error(error_event)) {
+            Ok(error) => Some(error),
+            Err(error) => {
+                let error =
+                    Report::new(error).wrap_err("Failed to serialize generation error event");
+                log_and_capture_error("send_background_generation_failure", &error);
+                None
+            }
+        };
+    send_background_event(
+        task,
+        StreamingEvent::Error { error },
+        "broadcast generation failure",
+    )
+    .await;
+}
+
+/// Moderate the user message a generation answers, before the chat request is prepared.
+///
+/// Returns the error the assistant message is saved with instead of being generated, if the
+/// user message is blocked.
+async fn moderation_blocking_error(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    user_message: &messages::Model,
+) -> Result<Option<GenerationErrorType>, Report> {
+    let outcome = moderate_user_message(
+        app_state,
+        policy,
+        &me_user.to_subject(),
+        user_message,
+        me_user.access_token.as_deref(),
+    )
+    .await
+    .wrap_err("Failed to moderate user message")?;
+    Ok(moderation_outcome_error(outcome))
+}
+
+/// The error an assistant message is completed with instead of being generated, if the outcome
+/// of the moderation blocks the user message.
+fn moderation_outcome_error(outcome: ModerationOutcome) -> Option<GenerationErrorType> {
+    match outcome {
+        ModerationOutcome::Moderated(result) if result.blocked => {
+            Some(GenerationErrorType::ModerationBlocked {
+                error_description: MODERATION_BLOCKED_ERROR_DESCRIPTION.to_string(),
+                categories: result.categories,
+            })
+        }
+        ModerationOutcome::Unavailable => Some(GenerationErrorType::ModerationBlocked {
+            error_description: MODERATION_UNAVAILABLE_ERROR_DESCRIPTION.to_string(),
+            categories: vec![],
+        }),
+        ModerationOutcome::Moderated(_) | ModerationOutcome::Skipped => None,
+    }
+}
+
+/// Save the assistant message answering a user message that was blocked by the moderation,
+/// without generating it.
+#[allow(clippy::too_many_arguments)]
+async fn save_moderation_blocked_assistant_message(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    chat_id: &Uuid,
+    user_message_id: &Uuid,
+    sibling_message_id: Option<&Uuid>,
+    error: GenerationErrorType,
+) -> Result<(messages::Model, ChatMessage), Report> {
+    let assistant_message = submit_message(
+        &app_state.db,
+        policy,
+        &me_user.to_subject(),
+        chat_id,
+        json!({ "role": "assistant", "content": [] }),
+        Some(user_message_id),
+        sibling_message_id,
+        None,
+        None,
+        &[],
+        None,
+        Some(generation_metadata_for_error(error)),
+        None,
+        false,
+    )
+    .await
+    .wrap_err("Failed to save assistant message for blocked user message")?;
+    let assistant_message_wrapped = ChatMessage::from_model(assistant_message.clone())
+        .wrap_err("Failed to convert assistant message for blocked user message")?;
+    Ok((assistant_message, assistant_message_wrapped))
+}
+
+/// Emit the lifecycle of an assistant message that was blocked by the moderation to a background
+/// task: the message is started, fails with the moderation error and is completed right away.
+async fn bg_send_moderation_blocked_events(
+    task: &Arc<StreamingTask>,
+    assistant_message: messages::Model,
+    assistant_message_wrapped: ChatMessage,
+    error: GenerationErrorType,
+) -> Result<(), Report> {
+    let (trace_id, span_id) = current_trace_ids();
+    task.send_event(StreamingEvent::AssistantMessageStarted {
+        message_id: assistant_message.id,
+        trace_id: trace_id.clone(),
+        span_id: span_id.clone(),
+    })
+    .await
+    .map_err(Report::msg)?;
+    send_background_event(
+        task,
+        StreamingEvent::Error {
+            error: serialize_json_value(
+                MessageSubmitStreamingResponseMessage::Error(MessageSubmitStreamingResponseError {
+                    message_id: Some(assistant_message.id),
+                    error,
+                }),
+                "serialize moderation error event",
+            ),
+        },
+        "broadcast moderation error",
+    )
+    .await;
+    task.send_event(StreamingEvent::AssistantMessageCompleted {
+        message_id: assistant_message.id,
+        content: vec![],
+        message: assistant_message_wrapped,
+        trace_id,
+        span_id,
+    })
+    .await
+    .map_err(Report::msg)?;
+    Ok(())
+}
+
+/// Emit the lifecycle of an assistant message that was blocked by the moderation: the message is
+/// started, fails with the moderation error and is completed right away.
+async fn send_moderation_blocked_events<MSG>(
+    tx: Sender<Result<Event, Report>>,
+    assistant_message: messages::Model,
+    assistant_message_wrapped: ChatMessage,
+    error: GenerationErrorType,
+) -> Result<(), Report>
+where
+    MSG: SendAsSseEvent
+        + From<MessageSubmitStreamingResponseAssistantMessageStarted>
+        + From<MessageSubmitStreamingResponseError>
+        + From<MessageSubmitStreamingResponseMessageComplete>,
+{
+    let (trace_id, span_id) = current_trace_ids();
+    let started: MSG = MessageSubmitStreamingResponseAssistantMessageStarted {
+        message_id: assistant_message.id,
+        trace_id: trace_id.clone(),
+        span_id: span_id.clone(),
+    }
+    .into();
+    started.send_event_report(tx.clone()).await?;
+    let error: MSG = MessageSubmitStreamingResponseError {
+        message_id: Some(assistant_message.id),
+        error,
+    }
+    .into();
+    error.send_event_report(tx.clone()).await?;
+    let completed: MSG = MessageSubmitStreamingResponseMessageComplete {
+        message_id: assistant_message.id,
+        content: vec![],
+        message: assistant_message_wrapped,
+        trace_id,
+        span_id,
+    }
+    .into();
+    completed.send_event_report(tx).await
+}
+
+#[cfg(test)]
+mod content_draft_writer_tests {
+    use super::*;
+
+    fn writer(interval_secs: u64, deltas: u64, min_interval_ms: u64) -> ContentDraftWriter {
+        ContentDraftWriter::new(
+            &GenerationStatusConfig {
+                content_draft_flush_interval_secs: interval_secs,
+                content_draft_flush_deltas: deltas,
+                content_draft_min_flush_interval_ms: min_interval_ms,
+                ..Default::default()
+            },
+            Uuid::new_v4(),
+        )
+    }
+
+    #[test]
+    fn flushes_after_interval_or_delta_count() {
+        let mut writer = writer(5, 3, 0);
+        let start = writer.last_flush;
+
+        writer.pending_deltas = 1;
+        assert!(!writer.should_flush(start + Duration::from_secs(1)));
+        assert!(writer.should_flush(start + Duration::from_secs(5)));
+
+        writer.pending_deltas = 3;
+        assert!(writer.should_flush(start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn caps_flush_rate() {
+        let mut writer = writer(5, 1, 1000);
+        let start = writer.last_flush;
+        writer.pending_deltas = 10;
+
+        assert!(!writer.should_flush(start + Duration::from_millis(500)));
+        assert!(writer.should_flush(start + Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn never_flushes_when_disabled_or_without_new_content() {
+        let mut disabled = writer(0, 1, 0);
+        disabled.pending_deltas = 10;
+        assert!(!disabled.should_flush(disabled.last_flush + Duration::from_secs(60)));
+
+        let idle = writer(5, 1, 0);
+        assert!(!idle.should_flush(idle.last_flush + Duration::from_secs(60)));
+    }
+}
+
+#[cfg(test)]
+mod tool_call_arguments_delta_coalescer_tests {
+    use super::*;
+
+    fn coalescer(interval_ms: u64) -> ToolCallArgumentsDeltaCoalescer {
+        ToolCallArgumentsDeltaCoalescer::new(&ChatConfig {
+            tool_call_arguments_delta_interval_ms: interval_ms,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn derives_fragments_from_accumulated_arguments() {
+        let mut coalescer = coalescer(0);
+        let now = Instant::now();
+
+        assert_eq!(
+            coalescer.observe("call-1", &json!("{\"query\":"), now),
+            Some("{\"query\":".to_string())
+        );
+        assert_eq!(
+            coalescer.observe("call-1", &json!("{\"query\":\"rust\"}"), now),
+            Some("\"rust\"}".to_string())
+        );
+        assert_eq!(
+            coalescer.observe("call-1", &json!("{\"query\":\"rust\"}"), now),
+            None
+        );
+    }
+
+    #[test]
+    fn appends_partial_arguments() {
+        let mut coalescer = coalescer(0);
+        let now = Instant::now();
+
+        coalescer.observe("call-1", &json!("{\"a\":1"), now);
+        assert_eq!(
+            coalescer.observe("call-1", &json!(",\"b\":2}"), now),
+            Some(",\"b\":2}".to_string())
+        );
+        assert_eq!(coalescer.calls[0].arguments, "{\"a\":1,\"b\":2}");
+    }
+
+    #[test]
+    fn coalesces_fragments_within_interval() {
+        let mut coalescer = coalescer(100);
+        let start = Instant::now();
+
+        assert_eq!(
+            coalescer.observe("call-1", &json!("{\"a\""), start),
+            Some("{\"a\"".to_string())
+        );
+        assert_eq!(
+            coalescer.observe(
+                "call-1",
+                &json!("{\"a\":1"),
+                start + Duration::from_millis(30)
+            ),
+            None
+        );
+        assert_eq!(
+            coalescer.observe("call-2", &json!("{}"), start + Duration::from_millis(30)),
+            Some("{}".to_string())
+        );
+        assert_eq!(
+            coalescer.observe(
+                "call-1",
+                &json!("{\"a\":1,"),
+                start + Duration::from_millis(60)
+            ),
+            None
+        );
+        assert_eq!(
+            coalescer.observe(
+                "call-1",
+                &json!("{\"a\":1,\"b\""),
+                start + Duration::from_millis(100)
+            ),
+            Some(":1,\"b\"".to_string())
+        );
+        coalescer.observe(
+            "call-1",
+            &json!("{\"a\":1,\"b\":2}"),
+            start + Duration::from_millis(120),
+        );
+
+        assert_eq!(
+            coalescer.flush(),
+            vec![("call-1".to_string(), ":2}".to_string())]
+        );
+        assert!(coalescer.flush().is_empty());
+    }
 }
 
 #[cfg(test)]
@@ -7183,22 +11063,15 @@ async fn run_message_submit_task(
     generation_request_context: GenerationRequestContext,
     chat_id: Uuid,
     chat_was_created: bool,
+    admission: GenerationAdmission,
 ) -> Result<(), Report> {
     tracing::info!("run_message_submit_task started for chat_id: {}", chat_id);
 
-    // Send ChatCreated before the policy rebuild so client navigation is not
-    // serialized behind it. This is authz-safe: the chat row is committed and
-    // the global engine invalidated before this task was spawned, so the
-    // client's post-navigation requests wait on (or perform) the same
-    // single-flight rebuild in the middleware and can never observe
-    // pre-create policy data. A rebuild failure no longer suppresses
-    // navigation; it surfaces as an error frame in the created chat.
+    // The ChatCreated event is only sent once the user message is saved, as the chat is
+    // provisional until then (see `bg_stream_save_user_message`). If the policy rebuild or the
+    // save fails, the client is never navigated to the chat, which is never listed, and is
+    // removed by the cleanup worker.
     if chat_was_created {
-        tracing::info!("Sending ChatCreated event for chat_id: {}", chat_id);
-        task.send_event(StreamingEvent::ChatCreated { chat_id })
-            .await
-            .map_err(Report::msg)?;
-
         tracing::info!("Rebuilding policy data for newly created chat");
         // Via the global engine: `policy` is a request-scoped clone whose
         // severed staleness flag cannot see the handler's invalidation.
@@ -7227,25 +11100,38 @@ async fn run_message_submit_task(
 
     tracing::info!("Chat fetched successfully, id: {}", chat.id);
 
+    // Long texts are attached as a file, and the message only keeps a preview of them
+    let auto_attached_message = auto_attach_long_message(
+        app_state,
+        policy,
+        &me_user.to_subject(),
+        &chat.id,
+        &request.user_message,
+    )
+    .await
+    .wrap_err("Failed to attach long message as a file")?;
+    let auto_attached_request = auto_attached_message
+        .as_ref()
+        .map(|attached| request.with_auto_attached_message(attached));
+    let request = auto_attached_request.as_ref().unwrap_or(request);
+
     // Save user message
     tracing::info!("Saving user message");
-    let user_input_parameters =
-        request
-            .action_facet
-            .as_ref()
-            .map(|af| crate::models::message::InputParameters {
-                action_facet_id: Some(af.id.clone()),
-                action_facet_args: Some(af.args.clone()),
-            });
+    let user_input_parameters = submit_user_input_parameters(app_state, request);
     let saved_user_message = bg_stream_save_user_message(
         task,
         app_state,
         policy,
         me_user,
         &chat,
+        chat_was_created,
         request.previous_message_id.as_ref(),
+        request.role,
         &request.user_message,
         &request.input_files_ids,
+        auto_attached_message
+            .as_ref()
+            .map(|attached| attached.file_upload_id),
         user_input_parameters,
     )
     .await
@@ -7253,20 +11139,47 @@ async fn run_message_submit_task(
 
     tracing::info!("User message saved, id: {}", saved_user_message.id);
 
+    if !request.generates_answer() {
+        return Ok(());
+    }
+
+    if let Some(error) =
+        moderation_blocking_error(app_state, policy, me_user, &saved_user_message).await?
+    {
+        let (assistant_message, assistant_message_wrapped) =
+            save_moderation_blocked_assistant_message(
+                app_state,
+                policy,
+                me_user,
+                &chat.id,
+                &saved_user_message.id,
+                None,
+                error.clone(),
+            )
+            .await?;
+        return bg_send_moderation_blocked_events(
+            task,
+            assistant_message,
+            assistant_message_wrapped,
+            error,
+        )
+        .await;
+    }
+
+    // The user message is saved while the generation is queued, so that it's not lost when the
+    // client disconnects. The capacity is held until the generation ends.
+    let Some(_generation_permit) = app_state
+        .background_tasks
+        .wait_for_generation_start(admission, &me_user.id, task)
+        .await
+    else {
+        tracing::info!("Generation was aborted while it was queued");
+        return Ok(());
+    };
+
     // Prepare chat request
     let me_profile_input = MeProfileChatRequestInput::from_me_profile(me_user);
-    let user_input = PromptCompositionUserInput {
-        just_submitted_user_message_id: saved_user_message.id,
-        requested_chat_provider_id: request.chat_provider_id.clone(),
-        new_input_file_ids: request.input_files_ids.clone(),
-        selected_facet_ids: request.selected_facet_ids.clone(),
-        action_facet: request.action_facet.as_ref().map(|af| {
-            crate::services::prompt_composition::types::ActionFacetUserInput {
-                id: af.id.clone(),
-                args: af.args.clone(),
-            }
-        }),
-    };
+    let user_input = submit_prompt_composition_user_input(request, saved_user_message.id);
     let PreparedChatRequest {
         chat_request,
         chat_options,
@@ -7274,8 +11187,12 @@ async fn run_message_submit_task(
         generation_parameters,
         generation_request_context,
         mcp_servers_unavailable,
+        token_breakdown,
+        prompt_manifest,
+        unavailable_files,
         available_mcp_tools,
         offered_client_tool_timeouts,
+        effective_model_settings,
     } = prepare_chat_request(
         app_state,
         policy,
@@ -7283,9 +11200,11 @@ async fn run_message_submit_task(
         user_input,
         generation_request_context,
         &me_profile_input,
+        None,
     )
     .await
     .wrap_err("Failed to prepare chat request")?;
+    task.set_output_pacing(effective_model_settings.output_pacing);
 
     // Spawn chat summary generation if needed. Use the composed prompt input
     // so summary generation sees the same first-turn structure as chat
@@ -7338,6 +11257,9 @@ async fn run_message_submit_task(
         .generation_chat_provider_id
         .clone()
         .unwrap_or_else(|| "unknown".to_string());
+    let chat_provider_group_id = generation_parameters.chat_provider_group_id.clone();
+    let fallback_from_chat_provider_id =
+        generation_parameters.fallback_from_chat_provider_id.clone();
     let allowed_tool_names: HashSet<String> = chat_request
         .tools
         .as_ref()
@@ -7365,18 +11287,23 @@ async fn run_message_submit_task(
         empty_assistant_message_json,
         Some(&saved_user_message.id),
         None,
+        None,
         Some(generation_input_messages.clone()),
         &[],
         Some(generation_parameters),
         None,
         None,
+        false,
     )
     .await
     .wrap_err("Failed to submit initial assistant message")?;
 
     // Emit AssistantMessageStarted event
+    let (trace_id, span_id) = current_trace_ids();
     task.send_event(StreamingEvent::AssistantMessageStarted {
         message_id: initial_assistant_message.id,
+        trace_id,
+        span_id,
     })
     .await
     .map_err(Report::msg)?;
@@ -7399,7 +11326,7 @@ async fn run_message_submit_task(
     let mcp_auth_context = McpRequestAuthContext {
         app_state: Some(app_state),
         user_id: Uuid::parse_str(&me_user.id).ok(),
-        oidc_token: Some(&me_user.oidc_token),
+        oidc_token: me_user.oidc_token.as_deref(),
         access_token: me_user.access_token.as_deref(),
     };
     let generation_task = stream_generate_chat_completion::<MessageSubmitStreamingResponseMessage>(
@@ -7411,18 +11338,26 @@ async fn run_message_submit_task(
         langfuse_trace_enrichment,
         chat_options,
         initial_assistant_message.id,
+        vec![],
         me_user.id.clone(),
         chat.id,
         Some(chat_provider_id.as_str()),
+        chat_provider_group_id.as_deref(),
+        fallback_from_chat_provider_id.as_deref(),
         &me_user.groups,
         mcp_auth_context,
         mcp_servers_unavailable,
+        token_breakdown,
+        Some(prompt_manifest),
+        unavailable_files,
         allowed_tool_names,
         available_mcp_tools,
         offered_client_tool_timeouts,
         &chat_provider_headers_context,
         Some(task),
         chat.assistant_id,
+        true,
+        false,
     );
 
     let (end_content, generation_metadata) = match generation_task.await {
@@ -7443,32 +11378,12 @@ async fn run_message_submit_task(
         }
     };
 
-    if let Some(metadata) = generation_metadata.as_ref()
-        && metadata.error.is_some()
-    {
-        let has_error_event = task
-            .get_event_history()
-            .await
-            .iter()
-            .any(|event| matches!(event, StreamingEvent::Error { .. }));
-
-        if !has_error_event {
-            let mut error_value = metadata
-                .error
-                .clone()
-                .and_then(|error| serialize_json_value(error, "serialize generation error"));
-            if let Some(JsonValue::Object(map)) = error_value.as_mut() {
-                map.entry("message_id".to_string())
-                    .or_insert(JsonValue::String(initial_assistant_message.id.to_string()));
-            }
-            send_background_event(
-                task,
-                StreamingEvent::Error { error: error_value },
-                "broadcast structured generation error",
-            )
-            .await;
-        }
-    }
+    bg_send_generation_metadata_error(
+        task,
+        generation_metadata.as_ref(),
+        initial_assistant_message.id,
+    )
+    .await;
 
     let generation_was_aborted = generation_metadata
         .as_ref()
@@ -7505,7 +11420,318 @@ async fn run_message_submit_task(
     )
     .await?;
 
-    // Note: stream_end event is sent in the spawning wrapper in message_submit_sse
+    // Note: stream_end event is sent in the spawning wrapper in message_submit_sse
+
+    Ok(())
+}
+
+/// Broadcast the error a generation ended with to a background task, unless an error event was
+/// already sent during the generation.
+async fn bg_send_generation_metadata_error(
+    task: &Arc<StreamingTask>,
+    generation_metadata: Option<&GenerationMetadata>,
+    assistant_message_id: Uuid,
+) {
+    let Some(error) = generation_metadata.and_then(|metadata| metadata.error.clone()) else {
+        return;
+    };
+    let has_error_event = task
+        .get_event_history()
+        .await
+        .iter()
+        .any(|event| matches!(event, StreamingEvent::Error { .. }));
+    if has_error_event {
+        return;
+    }
+
+    let mut error_value = serialize_json_value(error, "serialize generation error");
+    if let Some(JsonValue::Object(map)) = error_value.as_mut() {
+        map.entry("message_id".to_string())
+            .or_insert(JsonValue::String(assistant_message_id.to_string()));
+    }
+    send_background_event(
+        task,
+        StreamingEvent::Error { error: error_value },
+        "broadcast structured generation error",
+    )
+    .await;
+}
+
+/// Build an empty assistant message of an ephemeral chat, answering the given user message.
+fn new_ephemeral_assistant_message(
+    chat_id: &Uuid,
+    user_message_id: &Uuid,
+    generation_input_messages: Option<GenerationInputMessages>,
+    generation_parameters: Option<GenerationParameters>,
+    generation_metadata: Option<GenerationMetadata>,
+) -> Result<messages::Model, Report> {
+    new_ephemeral_message(
+        chat_id,
+        json!({ "role": "assistant", "content": [] }),
+        Some(user_message_id),
+        generation_input_messages,
+        &[],
+        generation_parameters,
+        generation_metadata,
+        None,
+    )
+}
+
+/// Run the message submission task of an ephemeral chat in the background.
+///
+/// Like `run_message_submit_task`, but the messages are only added to the chat in memory, and the
+/// generation is not traced, cached or summarized. Policies, quotas, moderation and the tool
+/// allowlists apply as for any other chat.
+#[allow(clippy::too_many_arguments)]
+async fn run_ephemeral_message_submit_task(
+    task: &Arc<StreamingTask>,
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    request: &MessageSubmitRequest,
+    generation_request_context: GenerationRequestContext,
+    chat_id: Uuid,
+    admission: GenerationAdmission,
+) -> Result<(), Report> {
+    let user_input_parameters = submit_user_input_parameters(app_state, request);
+    let user_message = new_ephemeral_message(
+        &chat_id,
+        submitted_message_raw_json(request.role, &request.user_message, &me_user.id),
+        request.previous_message_id.as_ref(),
+        None,
+        &request.input_files_ids,
+        None,
+        None,
+        user_input_parameters,
+    )
+    .wrap_err("Failed to build user message")?;
+    app_state.ephemeral_chats.add_message(user_message.clone())?;
+    let user_message_wrapped = ChatMessage::from_model(user_message.clone())
+        .wrap_err("Failed to convert user message")?;
+    task.send_event(StreamingEvent::UserMessageSaved {
+        message_id: user_message.id,
+        message: user_message_wrapped,
+        auto_attached_file_id: None,
+    })
+    .await
+    .map_err(Report::msg)?;
+
+    if !request.generates_answer() {
+        return Ok(());
+    }
+
+    let moderation_outcome =
+        moderate_unsaved_user_message(app_state, &user_message, me_user.access_token.as_deref())
+            .await
+            .wrap_err("Failed to moderate user message")?;
+    if let Some(error) = moderation_outcome_error(moderation_outcome) {
+        let assistant_message = new_ephemeral_assistant_message(
+            &chat_id,
+            &user_message.id,
+            None,
+            None,
+            Some(generation_metadata_for_error(error.clone())),
+        )?;
+        app_state
+            .ephemeral_chats
+            .add_message(assistant_message.clone())?;
+        let assistant_message_wrapped = ChatMessage::from_model(assistant_message.clone())
+            .wrap_err("Failed to convert assistant message for blocked user message")?;
+        return bg_send_moderation_blocked_events(
+            task,
+            assistant_message,
+            assistant_message_wrapped,
+            error,
+        )
+        .await;
+    }
+
+    let Some(_generation_permit) = app_state
+        .background_tasks
+        .wait_for_generation_start(admission, &me_user.id, task)
+        .await
+    else {
+        tracing::info!("Generation was aborted while it was queued");
+        return Ok(());
+    };
+
+    // The chat may have expired while the generation was queued
+    let ephemeral_chat = app_state
+        .ephemeral_chats
+        .get(&chat_id, &me_user.id)
+        .ok_or_eyre("Ephemeral chat has expired")?;
+    let me_profile_input = MeProfileChatRequestInput::from_me_profile(me_user);
+    let user_input = submit_prompt_composition_user_input(request, user_message.id);
+    let PreparedChatRequest {
+        chat_request,
+        chat_options,
+        generation_input_messages,
+        generation_parameters,
+        generation_request_context: _,
+        mcp_servers_unavailable,
+        token_breakdown,
+        prompt_manifest,
+        unavailable_files,
+        available_mcp_tools,
+        offered_client_tool_timeouts,
+        effective_model_settings,
+    } = prepare_ephemeral_chat_request(
+        app_state,
+        policy,
+        &ephemeral_chat.chat,
+        &ephemeral_chat.messages,
+        user_input,
+        generation_request_context,
+        &me_profile_input,
+    )
+    .await
+    .wrap_err("Failed to prepare chat request")?;
+    task.set_output_pacing(effective_model_settings.output_pacing);
+
+    let chat_provider_id = generation_parameters
+        .generation_chat_provider_id
+        .clone()
+        .unwrap_or_else(|| "unknown".to_string());
+    let chat_provider_group_id = generation_parameters.chat_provider_group_id.clone();
+    let fallback_from_chat_provider_id =
+        generation_parameters.fallback_from_chat_provider_id.clone();
+    let allowed_tool_names: HashSet<String> = chat_request
+        .tools
+        .as_ref()
+        .map(|tools| {
+            tools
+                .iter()
+                .map(|tool| tool.name.to_string())
+                .collect::<HashSet<_>>()
+        })
+        .unwrap_or_default();
+    let chat_provider_headers_context =
+        ChatProviderHeadersContext::new(&me_user.id, &me_user.id_token_claims);
+
+    let mut assistant_message = new_ephemeral_assistant_message(
+        &chat_id,
+        &user_message.id,
+        Some(generation_input_messages),
+        Some(generation_parameters),
+        None,
+    )?;
+    app_state
+        .ephemeral_chats
+        .add_message(assistant_message.clone())?;
+
+    let (trace_id, span_id) = current_trace_ids();
+    task.send_event(StreamingEvent::AssistantMessageStarted {
+        message_id: assistant_message.id,
+        trace_id,
+        span_id,
+    })
+    .await
+    .map_err(Report::msg)?;
+
+    // Events are forwarded to the StreamingTask by stream_generate_chat_completion
+    let (temp_tx, mut temp_rx) = tokio::sync::mpsc::channel::<Result<Event, Report>>(100);
+    tokio::spawn(async move {
+        while let Some(event) = temp_rx.recv().await {
+            if let Err(error) = event {
+                log_and_capture_error("background generation SSE bridge", &error);
+            }
+        }
+    });
+
+    let subject = me_user.to_subject();
+    let mcp_auth_context = McpRequestAuthContext {
+        app_state: Some(app_state),
+        user_id: Uuid::parse_str(&me_user.id).ok(),
+        oidc_token: me_user.oidc_token.as_deref(),
+        access_token: me_user.access_token.as_deref(),
+    };
+    let generation_task = stream_generate_chat_completion::<MessageSubmitStreamingResponseMessage>(
+        temp_tx.clone(),
+        app_state,
+        policy,
+        &subject,
+        chat_request,
+        LangfuseTraceEnrichment::default(),
+        chat_options,
+        assistant_message.id,
+        vec![],
+        me_user.id.clone(),
+        chat_id,
+        Some(chat_provider_id.as_str()),
+        chat_provider_group_id.as_deref(),
+        fallback_from_chat_provider_id.as_deref(),
+        &me_user.groups,
+        mcp_auth_context,
+        mcp_servers_unavailable,
+        token_breakdown,
+        Some(prompt_manifest),
+        unavailable_files,
+        allowed_tool_names,
+        available_mcp_tools,
+        offered_client_tool_timeouts,
+        &chat_provider_headers_context,
+        Some(task),
+        ephemeral_chat.chat.assistant_id,
+        false,
+        true,
+    );
+
+    let (end_content, generation_metadata) = match generation_task.await {
+        Ok(result) => result,
+        Err(error) => {
+            let error = error.wrap_err("Failed during chat completion generation");
+            let generation_error = internal_generation_error(&error);
+            report_chat_provider_generation_error(&chat_provider_id, &generation_error);
+            send_background_generation_failure(task, assistant_message.id, generation_error)
+                .await;
+            return Err(error);
+        }
+    };
+
+    bg_send_generation_metadata_error(task, generation_metadata.as_ref(), assistant_message.id)
+        .await;
+
+    let generation_was_aborted = generation_metadata
+        .as_ref()
+        .and_then(|metadata| metadata.was_aborted)
+        .unwrap_or(false);
+    let end_content = if generation_was_aborted {
+        ensure_saved_assistant_content_for_abort(end_content)
+    } else {
+        end_content
+    };
+
+    let mut raw_message = MessageSchema::validate(&assistant_message.raw_message)?;
+    raw_message.content = strip_image_urls_from_content(end_content.clone());
+    assistant_message.raw_message = raw_message.to_json()?;
+    assistant_message.generation_metadata =
+        generation_metadata.map(serde_json::to_value).transpose()?;
+    assistant_message.updated_at = Utc::now().fixed_offset();
+    app_state
+        .ephemeral_chats
+        .add_message(assistant_message.clone())?;
+
+    let hydrated_content = crate::models::message::regenerate_image_urls_in_content(
+        &app_state.db,
+        end_content,
+        &app_state.file_storage_providers,
+    )
+    .await
+    .wrap_err("Failed to hydrate image URLs")?;
+    let mut assistant_message_wrapped = ChatMessage::from_model(assistant_message.clone())
+        .wrap_err("Failed to convert assistant message")?;
+    assistant_message_wrapped.content = hydrated_content.clone();
+
+    let (trace_id, span_id) = current_trace_ids();
+    task.send_event(StreamingEvent::AssistantMessageCompleted {
+        message_id: assistant_message.id,
+        content: hydrated_content,
+        message: assistant_message_wrapped,
+        trace_id,
+        span_id,
+    })
+    .await
+    .map_err(Report::msg)?;
 
     Ok(())
 }
@@ -7518,7 +11744,9 @@ async fn run_message_submit_task(
         (status = OK, content_type="text/event-stream", body = RegenerateMessageStreamingResponseMessage),
         (status = BAD_REQUEST, description = "When validation fails (e.g., invalid message role)"),
         (status = NOT_FOUND, description = "When the chat does not exist or is not accessible"),
-        (status = CONFLICT, description = "When the chat is archived"),
+        (status = CONFLICT, body = GenerationInProgressError, description = "When the chat is archived. Also when another replica of the backend is generating an answer in the chat, with a JSON body that contains the message being generated and the replica generating it"),
+        (status = UNPROCESSABLE_ENTITY, body = ChatProviderUnavailableError, description = "When the chat provider of the generation is not available to the user, and no other chat provider is available to fall back to. The body is JSON, with the chat providers that are available to the user"),
+        (status = TOO_MANY_REQUESTS, body = QuotaExceededError, description = "When the user reached their quota of the chat provider of the generation. The body is JSON, with the usage of the quota and the time it resets at"),
         (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
         (status = INTERNAL_SERVER_ERROR, description = "When an internal server error occurs")
     ),
@@ -7532,7 +11760,7 @@ pub async fn regenerate_message_sse(
     Extension(policy): Extension<PolicyEngine>,
     headers: HeaderMap,
     Json(request): Json<RegenerateMessageRequest>,
-) -> Result<Sse<impl Stream<Item = Result<Event, Report>>>, (axum::http::StatusCode, String)> {
+) -> Result<Response, (axum::http::StatusCode, String)> {
     // Validate request parameters
     let validation_result =
         validate_regenerate_request(&app_state, &policy, &me_user, &request.current_message_id)
@@ -7570,293 +11798,698 @@ pub async fn regenerate_message_sse(
     })?;
     reject_if_archived(&chat)?;
 
+    // The regeneration uses the chat provider that was selected for the regenerated message,
+    // unless another one is requested.
+    let requested_chat_provider_id = match request.chat_provider_id.clone() {
+        Some(chat_provider_id) => Some(chat_provider_id),
+        None => get_selected_chat_provider_id_from_message(&validation_result.current_message)
+            .ok()
+            .flatten(),
+    };
+    validate_chat_provider_available(
+        &app_state,
+        &policy,
+        &me_user,
+        requested_chat_provider_id.as_deref(),
+    )
+    .await?;
+    if app_state.config.any_chat_provider_has_user_quota() {
+        validate_user_quota_for_chat_provider(
+            &app_state,
+            &policy,
+            &me_user,
+            requested_chat_provider_id.as_deref(),
+        )
+        .await?;
+    }
+
     // Create a channel for sending events
     let (tx, rx) = tokio::sync::mpsc::channel::<Result<Event, Report>>(100);
     let (_abort_rx, task) = app_state
         .background_tasks
-        .start_task(chat.id, Uuid::new_v4())
-        .await;
+        .start_task_for_user(chat.id, Uuid::new_v4(), &me_user.id)
+        .await
+        .map_err(generation_in_progress_response)?;
 
     // Move validated messages into the task
     let previous_message = validation_result.previous_message;
     let current_message = validation_result.current_message;
 
     let task_for_stream = task.clone();
+    // Built before the generation takes ownership of the app state
+    let receiver_stream = ClientDisconnectGuard::new(
+        tokio_stream::wrappers::ReceiverStream::<Result<Event, Report>>::new(rx),
+        Some(&task),
+        &app_state,
+    );
     let app_state_for_cleanup = app_state.clone();
     let chat_id_for_cleanup = chat.id;
 
     // Spawn a task to process the request and send events
-    tokio::spawn(async move {
-        let mut cleanup_guard = TaskCleanupGuard::new(
-            app_state_for_cleanup.background_tasks.clone(),
-            chat_id_for_cleanup,
-            task_for_stream.generation_id,
-        );
-        let result: Result<(), Report> = async {
-            let input_files_for_previous_message = previous_message
-                .input_file_uploads
-                .clone()
-                .unwrap_or_default();
-            let fallback_chat_provider_id = if request.chat_provider_id.is_none() {
-                match get_generation_chat_provider_id_from_message(&current_message) {
-                    Ok(chat_provider_id) => chat_provider_id,
-                    Err(error) => {
-                        warn_and_capture_error("read regenerate fallback chat provider", &error);
-                        None
+    let generation_span = crate::telemetry::assistant_message_generation_span(&chat_id_for_cleanup);
+    tokio::spawn(
+        async move {
+            let mut cleanup_guard = TaskCleanupGuard::new(
+                app_state_for_cleanup.background_tasks.clone(),
+                chat_id_for_cleanup,
+                task_for_stream.generation_id,
+            );
+            let result: Result<(), Report> = async {
+                let input_files_for_previous_message = previous_message
+                    .input_file_uploads
+                    .clone()
+                    .unwrap_or_default();
+                let fallback_chat_provider_id = if request.chat_provider_id.is_none() {
+                    match get_selected_chat_provider_id_from_message(&current_message) {
+                        Ok(chat_provider_id) => chat_provider_id,
+                        Err(error) => {
+                            warn_and_capture_error(
+                                "read regenerate fallback chat provider",
+                                &error,
+                            );
+                            None
+                        }
                     }
-                }
-            } else {
-                None
-            };
-            // Mirror the chat-provider fallback for the action facet: a
-            // regenerate without an explicit facet re-applies the one the
-            // original generation ran under ("same input, new sample"),
-            // including any client-action tool it implied. Re-validated
-            // against the CURRENT config — a facet that was removed or
-            // changed since is dropped (regenerate proceeds without it)
-            // rather than failing the request.
-            let fallback_action_facet = if request.action_facet.is_none() {
-                match crate::models::message::get_generation_action_facet_from_message(
-                    &current_message,
-                ) {
-                    Ok(action_facet) => action_facet,
-                    Err(error) => {
-                        warn_and_capture_error("read regenerate fallback action facet", &error);
-                        None
+                } else {
+                    None
+                };
+                // Mirror the chat-provider fallback for the action facet: a
+                // regenerate without an explicit facet re-applies the one the
+                // original generation ran under ("same input, new sample"),
+                // including any client-action tool it implied. Re-validated
+                // against the CURRENT config — a facet that was removed or
+                // changed since is dropped (regenerate proceeds without it)
+                // rather than failing the request.
+                let fallback_action_facet = if request.action_facet.is_none() {
+                    match crate::models::message::get_generation_action_facet_from_message(
+                        &current_message,
+                    ) {
+                        Ok(action_facet) => action_facet,
+                        Err(error) => {
+                            warn_and_capture_error("read regenerate fallback action facet", &error);
+                            None
+                        }
                     }
-                }
-                .map(|(id, args)| ActionFacetRequest { id, args })
-                .filter(|af| {
-                    let platform = generation_request_context
-                        .platform
-                        .as_deref()
-                        .unwrap_or(DEFAULT_ERATO_PLATFORM);
-                    match validate_action_facet(&app_state.config, Some(af), platform) {
-                        Ok(()) => true,
-                        Err((_, reason)) => {
-                            tracing::warn!(
-                                "Dropping stored action facet '{}' on regenerate: {}",
-                                af.id,
-                                reason
-                            );
-                            false
+                    .map(|(id, args)| ActionFacetRequest { id, args })
+                    .filter(|af| {
+                        let platform = generation_request_context
+                            .platform
+                            .as_deref()
+                            .unwrap_or(DEFAULT_ERATO_PLATFORM);
+                        match validate_action_facet(&app_state.config, Some(af), platform) {
+                            Ok(()) => true,
+                            Err((_, reason)) => {
+                                tracing::warn!(
+                                    "Dropping stored action facet '{}' on regenerate: {}",
+                                    af.id,
+                                    reason
+                                );
+                                false
+                            }
                         }
+                    })
+                } else {
+                    None
+                };
+
+                // Moderation results are stored on the user message, so a blocked message stays
+                // blocked when its answer is regenerated.
+                if let Some(error) =
+                    moderation_blocking_error(&app_state, &policy, &me_user, &previous_message)
+                        .await?
+                {
+                    let (assistant_message, assistant_message_wrapped) =
+                        save_moderation_blocked_assistant_message(
+                            &app_state,
+                            &policy,
+                            &me_user,
+                            &chat.id,
+                            &previous_message.id,
+                            Some(&request.current_message_id),
+                            error.clone(),
+                        )
+                        .await?;
+                    return send_moderation_blocked_events::<
+                        RegenerateMessageStreamingResponseMessage,
+                    >(
+                        tx.clone(),
+                        assistant_message,
+                        assistant_message_wrapped,
+                        error,
+                    )
+                    .await;
+                }
+
+                let me_profile_input = MeProfileChatRequestInput::from_me_profile(&me_user);
+                let user_input = crate::services::prompt_composition::PromptCompositionUserInput {
+                    just_submitted_user_message_id: previous_message.id,
+                    requested_chat_provider_id: request
+                        .chat_provider_id
+                        .clone()
+                        .or(fallback_chat_provider_id),
+                    new_input_file_ids: input_files_for_previous_message,
+                    refresh_file_ids: vec![],
+                    selected_facet_ids: request.selected_facet_ids.clone(),
+                    action_facet: request
+                        .action_facet
+                        .as_ref()
+                        .or(fallback_action_facet.as_ref())
+                        .map(|af| {
+                            crate::services::prompt_composition::types::ActionFacetUserInput {
+                                id: af.id.clone(),
+                                args: af.args.clone(),
+                            }
+                        }),
+                    requested_response_language: request.response_language.clone(),
+                };
+                let PreparedChatRequest {
+                    chat_request,
+                    chat_options,
+                    generation_input_messages,
+                    generation_parameters,
+                    generation_request_context,
+                    mcp_servers_unavailable,
+                    token_breakdown,
+                    prompt_manifest,
+                    unavailable_files,
+                    available_mcp_tools,
+                    offered_client_tool_timeouts,
+                    effective_model_settings: _,
+                } = prepare_chat_request(
+                    &app_state,
+                    &policy,
+                    &chat,
+                    user_input,
+                    generation_request_context.clone(),
+                    &me_profile_input,
+                    None,
+                )
+                .await
+                .wrap_err("Failed to prepare regenerate chat request")?;
+
+                let langfuse_trace_enrichment = match build_langfuse_trace_enrichment(
+                    &app_state,
+                    &policy,
+                    &me_user.to_subject(),
+                    &generation_input_messages,
+                    chat.assistant_id,
+                    &generation_request_context,
+                )
+                .await
+                {
+                    Ok(enrichment) => enrichment,
+                    Err(err) => {
+                        warn_and_capture_error("build regenerate Langfuse trace enrichment", &err);
+                        LangfuseTraceEnrichment::default()
                     }
-                })
-            } else {
-                None
-            };
+                };
 
-            let me_profile_input = MeProfileChatRequestInput::from_me_profile(&me_user);
-            let user_input = crate::services::prompt_composition::PromptCompositionUserInput {
-                just_submitted_user_message_id: previous_message.id,
-                requested_chat_provider_id: request
-                    .chat_provider_id
+                let empty_assistant_message_json = json!({ "role": "assistant", "content": [] });
+                let chat_provider_id = generation_parameters
+                    .generation_chat_provider_id
                     .clone()
-                    .or(fallback_chat_provider_id),
-                new_input_file_ids: input_files_for_previous_message,
-                selected_facet_ids: request.selected_facet_ids.clone(),
-                action_facet: request
-                    .action_facet
+                    .unwrap_or_else(|| "unknown".to_string());
+                let chat_provider_group_id = generation_parameters.chat_provider_group_id.clone();
+                let fallback_from_chat_provider_id =
+                    generation_parameters.fallback_from_chat_provider_id.clone();
+                let allowed_tool_names: HashSet<String> = chat_request
+                    .tools
                     .as_ref()
-                    .or(fallback_action_facet.as_ref())
-                    .map(
-                        |af| crate::services::prompt_composition::types::ActionFacetUserInput {
-                            id: af.id.clone(),
-                            args: af.args.clone(),
-                        },
-                    ),
-            };
-            let PreparedChatRequest {
-                chat_request,
-                chat_options,
-                generation_input_messages,
-                generation_parameters,
-                generation_request_context,
-                mcp_servers_unavailable,
-                available_mcp_tools,
-                offered_client_tool_timeouts,
-            } = prepare_chat_request(
-                &app_state,
-                &policy,
-                &chat,
-                user_input,
-                generation_request_context.clone(),
-                &me_profile_input,
-            )
-            .await
-            .wrap_err("Failed to prepare regenerate chat request")?;
-
-            let langfuse_trace_enrichment = match build_langfuse_trace_enrichment(
-                &app_state,
-                &policy,
-                &me_user.to_subject(),
-                &generation_input_messages,
-                chat.assistant_id,
-                &generation_request_context,
-            )
-            .await
-            {
-                Ok(enrichment) => enrichment,
-                Err(err) => {
-                    warn_and_capture_error("build regenerate Langfuse trace enrichment", &err);
-                    LangfuseTraceEnrichment::default()
-                }
-            };
+                    .map(|tools| {
+                        tools
+                            .iter()
+                            .map(|tool| tool.name.to_string())
+                            .collect::<HashSet<_>>()
+                    })
+                    .unwrap_or_default();
 
-            let empty_assistant_message_json = json!({ "role": "assistant", "content": [] });
-            let chat_provider_id = generation_parameters
-                .generation_chat_provider_id
-                .clone()
-                .unwrap_or_else(|| "unknown".to_string());
-            let allowed_tool_names: HashSet<String> = chat_request
-                .tools
-                .as_ref()
-                .map(|tools| {
-                    tools
-                        .iter()
-                        .map(|tool| tool.name.to_string())
-                        .collect::<HashSet<_>>()
-                })
-                .unwrap_or_default();
+                let initial_assistant_message = submit_message(
+                    &app_state.db,
+                    &policy,
+                    &me_user.to_subject(),
+                    &chat.id,
+                    empty_assistant_message_json,
+                    Some(&previous_message.id),
+                    Some(&request.current_message_id),
+                    None,
+                    Some(generation_input_messages.clone()),
+                    &[],
+                    Some(generation_parameters),
+                    None,
+                    None,
+                    false,
+                )
+                .await
+                .wrap_err("Failed to submit initial assistant message for regenerate")?;
+
+                let (trace_id, span_id) = current_trace_ids();
+                let assistant_started_event: RegenerateMessageStreamingResponseMessage =
+                    MessageSubmitStreamingResponseAssistantMessageStarted {
+                        message_id: initial_assistant_message.id,
+                        trace_id,
+                        span_id,
+                    }
+                    .into();
+                assistant_started_event
+                    .send_event_report(tx.clone())
+                    .await?;
+
+                let subject = me_user.to_subject();
+                let chat_provider_headers_context =
+                    ChatProviderHeadersContext::new(&me_user.id, &me_user.id_token_claims);
+                let mcp_auth_context = McpRequestAuthContext {
+                    app_state: Some(&app_state),
+                    user_id: Uuid::parse_str(&me_user.id).ok(),
+                    oidc_token: me_user.oidc_token.as_deref(),
+                    access_token: me_user.access_token.as_deref(),
+                };
+                let generation_result =
+                    stream_generate_chat_completion::<RegenerateMessageStreamingResponseMessage>(
+                        tx.clone(),
+                        &app_state,
+                        &policy,
+                        &subject,
+                        chat_request,
+                        langfuse_trace_enrichment,
+                        chat_options,
+                        initial_assistant_message.id,
+                        vec![],
+                        me_user.id.clone(),
+                        chat.id,
+                        Some(chat_provider_id.as_str()),
+                        chat_provider_group_id.as_deref(),
+                        fallback_from_chat_provider_id.as_deref(),
+                        &me_user.groups,
+                        mcp_auth_context,
+                        mcp_servers_unavailable,
+                        token_breakdown,
+                        Some(prompt_manifest),
+                        unavailable_files,
+                        allowed_tool_names,
+                        available_mcp_tools,
+                        offered_client_tool_timeouts,
+                        &chat_provider_headers_context,
+                        Some(&task_for_stream),
+                        chat.assistant_id,
+                        false,
+                        false,
+                    )
+                    .await;
+                let (end_content, generation_metadata) = match generation_result {
+                    Ok(result) => result,
+                    Err(error) => {
+                        let error = error.wrap_err("Failed during chat completion generation");
+                        persist_background_generation_failure(
+                            &task_for_stream,
+                            &app_state,
+                            &policy,
+                            &me_user,
+                            initial_assistant_message.id,
+                            &chat_provider_id,
+                            &error,
+                        )
+                        .await;
+                        return Err(error);
+                    }
+                };
 
-            let initial_assistant_message = submit_message(
-                &app_state.db,
-                &policy,
-                &me_user.to_subject(),
-                &chat.id,
-                empty_assistant_message_json,
-                Some(&previous_message.id),
-                Some(&request.current_message_id),
-                Some(generation_input_messages.clone()),
-                &[],
-                Some(generation_parameters),
-                None,
-                None,
-            )
-            .await
-            .wrap_err("Failed to submit initial assistant message for regenerate")?;
+                let generation_was_aborted = generation_metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.was_aborted)
+                    .unwrap_or(false);
 
-            let assistant_started_event: RegenerateMessageStreamingResponseMessage =
-                MessageSubmitStreamingResponseAssistantMessageStarted {
-                    message_id: initial_assistant_message.id,
+                if let Some(metadata) = generation_metadata
+                    && let Err(err) = update_message_generation_metadata(
+                        &app_state.db,
+                        &policy,
+                        &me_user.to_subject(),
+                        &initial_assistant_message.id,
+                        metadata,
+                    )
+                    .await
+                {
+                    warn_and_capture_error("update regenerate generation metadata", &err);
                 }
-                .into();
-            assistant_started_event
-                .send_event_report(tx.clone())
-                .await?;
 
-            let subject = me_user.to_subject();
-            let chat_provider_headers_context =
-                ChatProviderHeadersContext::new(&me_user.id, &me_user.id_token_claims);
-            let mcp_auth_context = McpRequestAuthContext {
-                app_state: Some(&app_state),
-                user_id: Uuid::parse_str(&me_user.id).ok(),
-                oidc_token: Some(&me_user.oidc_token),
-                access_token: me_user.access_token.as_deref(),
-            };
-            let generation_result =
-                stream_generate_chat_completion::<RegenerateMessageStreamingResponseMessage>(
+                let end_content = if generation_was_aborted {
+                    ensure_saved_assistant_content_for_abort(end_content)
+                } else {
+                    end_content
+                };
+                stream_update_assistant_message_completion::<
+                    RegenerateMessageStreamingResponseMessage,
+                >(
                     tx.clone(),
                     &app_state,
                     &policy,
-                    &subject,
-                    chat_request,
-                    langfuse_trace_enrichment,
+                    end_content,
+                    &me_user,
+                    initial_assistant_message.id,
+                )
+                .await?;
+
+                Ok(())
+            }
+            .await;
+
+            let generation_failed = result.is_err();
+            if let Err(error) = result {
+                forward_error_report(&tx, &error).await;
+                log_and_capture_error("regenerate message background task", &error);
+            }
+
+            let outcome = task_for_stream.derive_outcome(generation_failed);
+            task_for_stream.mark_completed();
+            cleanup_guard.disarm();
+            app_state_for_cleanup
+                .background_tasks
+                .remove_task(&chat_id_for_cleanup, task_for_stream.generation_id, outcome)
+                .await;
+        }
+        .instrument(generation_span.clone()),
+    );
+
+    let sse = Sse::new(receiver_stream).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(Duration::from_secs(1))
+            .text("keep-alive-text"),
+    );
+    Ok(with_traceparent_header(sse, &generation_span))
+}
+
+/// Continue an assistant message that was cut off at the max-token limit.
+///
+/// The follow-up generation is appended to the same assistant message instead of creating a
+/// sibling, so text deltas extend the last text content part of the message. No
+/// `assistant_message_started` event is sent, as the message already exists.
+#[utoipa::path(
+    post,
+    path = "/me/messages/continuestream",
+    request_body = ContinueMessageRequest,
+    responses(
+        (status = OK, content_type="text/event-stream", body = RegenerateMessageStreamingResponseMessage),
+        (status = BAD_REQUEST, description = "When validation fails (e.g., the message can not be continued)"),
+        (status = NOT_FOUND, description = "When the chat does not exist or is not accessible"),
+        (status = CONFLICT, body = GenerationInProgressError, description = "When the chat is archived. Also when another replica of the backend is generating an answer in the chat, with a JSON body that contains the message being generated and the replica generating it"),
+        (status = UNPROCESSABLE_ENTITY, body = ChatProviderUnavailableError, description = "When the chat provider of the generation is not available to the user, and no other chat provider is available to fall back to. The body is JSON, with the chat providers that are available to the user"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "When an internal server error occurs")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn continue_message_sse(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    headers: HeaderMap,
+    Json(request): Json<ContinueMessageRequest>,
+) -> Result<Response, (axum::http::StatusCode, String)> {
+    let validation_result =
+        validate_continue_request(&app_state, &policy, &me_user, &request.current_message_id)
+            .await?;
+
+    let generation_request_context = generation_request_context_from_headers(&headers);
+    let platform = generation_request_context
+        .platform
+        .as_deref()
+        .unwrap_or(DEFAULT_ERATO_PLATFORM);
+    warn_unknown_platform(&app_state.config, platform);
+
+    let chat = get_chat_by_message_id(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &request.current_message_id,
+    )
+    .await
+    .map_err(|e| {
+        let s = e.to_string();
+        if s.contains("not found") || s.contains("Access denied") || s.contains("not authorized") {
+            (
+                axum::http::StatusCode::NOT_FOUND,
+                "Chat not found".to_string(),
+            )
+        } else {
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to load chat for continuation: {}", e),
+            )
+        }
+    })?;
+    reject_if_archived(&chat)?;
+    validate_chat_provider_available(
+        &app_state,
+        &policy,
+        &me_user,
+        get_selected_chat_provider_id_from_message(&validation_result.current_message)
+            .ok()
+            .flatten()
+            .as_deref(),
+    )
+    .await?;
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Event, Report>>(100);
+    let (_abort_rx, task) = app_state
+        .background_tasks
+        .start_task_for_user(chat.id, Uuid::new_v4(), &me_user.id)
+        .await
+        .map_err(generation_in_progress_response)?;
+
+    let previous_message = validation_result.previous_message;
+    let current_message = validation_result.current_message;
+
+    let task_for_stream = task.clone();
+    // Built before the generation takes ownership of the app state
+    let receiver_stream = ClientDisconnectGuard::new(
+        tokio_stream::wrappers::ReceiverStream::<Result<Event, Report>>::new(rx),
+        Some(&task),
+        &app_state,
+    );
+    let app_state_for_cleanup = app_state.clone();
+    let chat_id_for_cleanup = chat.id;
+
+    let generation_span = crate::telemetry::assistant_message_generation_span(&chat_id_for_cleanup);
+    tokio::spawn(
+        async move {
+            let mut cleanup_guard = TaskCleanupGuard::new(
+                app_state_for_cleanup.background_tasks.clone(),
+                chat_id_for_cleanup,
+                task_for_stream.generation_id,
+            );
+            let result: Result<(), Report> = async {
+                let existing_content = MessageSchema::validate(&current_message.raw_message)
+                    .wrap_err("Failed to parse assistant message to continue")?
+                    .content;
+                let existing_text: String = existing_content
+                    .iter()
+                    .filter_map(|part| match part {
+                        ContentPart::Text(text) => Some(text.text.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+
+                // The continuation runs with the chat provider of the cut-off generation
+                let requested_chat_provider_id =
+                    match get_selected_chat_provider_id_from_message(&current_message) {
+                        Ok(chat_provider_id) => chat_provider_id,
+                        Err(error) => {
+                            warn_and_capture_error("read continue chat provider", &error);
+                            None
+                        }
+                    };
+
+                let me_profile_input = MeProfileChatRequestInput::from_me_profile(&me_user);
+                let user_input = crate::services::prompt_composition::PromptCompositionUserInput {
+                    just_submitted_user_message_id: previous_message.id,
+                    requested_chat_provider_id,
+                    new_input_file_ids: previous_message
+                        .input_file_uploads
+                        .clone()
+                        .unwrap_or_default(),
+                    refresh_file_ids: vec![],
+                    selected_facet_ids: vec![],
+                    action_facet: None,
+                    requested_response_language: None,
+                };
+                let PreparedChatRequest {
+                    mut chat_request,
                     chat_options,
-                    initial_assistant_message.id,
-                    me_user.id.clone(),
-                    chat.id,
-                    Some(chat_provider_id.as_str()),
-                    &me_user.groups,
-                    mcp_auth_context,
+                    generation_input_messages,
+                    generation_parameters,
+                    generation_request_context,
                     mcp_servers_unavailable,
-                    allowed_tool_names,
+                    token_breakdown,
+                    prompt_manifest,
+                    unavailable_files,
                     available_mcp_tools,
                     offered_client_tool_timeouts,
-                    &chat_provider_headers_context,
-                    Some(&task_for_stream),
+                    effective_model_settings: _,
+                } = prepare_chat_request(
+                    &app_state,
+                    &policy,
+                    &chat,
+                    user_input,
+                    generation_request_context.clone(),
+                    &me_profile_input,
+                    None,
+                )
+                .await
+                .wrap_err("Failed to prepare continue chat request")?;
+                // Replay the cut-off response, so the model picks up where it stopped
+                chat_request
+                    .messages
+                    .push(GenAiChatMessage::assistant(existing_text));
+
+                let langfuse_trace_enrichment = match build_langfuse_trace_enrichment(
+                    &app_state,
+                    &policy,
+                    &me_user.to_subject(),
+                    &generation_input_messages,
                     chat.assistant_id,
+                    &generation_request_context,
                 )
-                .await;
-            let (end_content, generation_metadata) = match generation_result {
-                Ok(result) => result,
-                Err(error) => {
-                    let error = error.wrap_err("Failed during chat completion generation");
-                    persist_background_generation_failure(
-                        &task_for_stream,
+                .await
+                {
+                    Ok(enrichment) => enrichment,
+                    Err(err) => {
+                        warn_and_capture_error("build continue Langfuse trace enrichment", &err);
+                        LangfuseTraceEnrichment::default()
+                    }
+                };
+
+                let chat_provider_id = generation_parameters
+                    .generation_chat_provider_id
+                    .clone()
+                    .unwrap_or_else(|| "unknown".to_string());
+                let chat_provider_group_id = generation_parameters.chat_provider_group_id.clone();
+                let fallback_from_chat_provider_id =
+                    generation_parameters.fallback_from_chat_provider_id.clone();
+                let allowed_tool_names: HashSet<String> = chat_request
+                    .tools
+                    .as_ref()
+                    .map(|tools| {
+                        tools
+                            .iter()
+                            .map(|tool| tool.name.to_string())
+                            .collect::<HashSet<_>>()
+                    })
+                    .unwrap_or_default();
+
+                let subject = me_user.to_subject();
+                let chat_provider_headers_context =
+                    ChatProviderHeadersContext::new(&me_user.id, &me_user.id_token_claims);
+                let mcp_auth_context = McpRequestAuthContext {
+                    app_state: Some(&app_state),
+                    user_id: Uuid::parse_str(&me_user.id).ok(),
+                    oidc_token: me_user.oidc_token.as_deref(),
+                    access_token: me_user.access_token.as_deref(),
+                };
+                let generation_result =
+                    stream_generate_chat_completion::<RegenerateMessageStreamingResponseMessage>(
+                        tx.clone(),
                         &app_state,
                         &policy,
-                        &me_user,
-                        initial_assistant_message.id,
-                        &chat_provider_id,
-                        &error,
+                        &subject,
+                        chat_request,
+                        langfuse_trace_enrichment,
+                        chat_options,
+                        current_message.id,
+                        existing_content,
+                        me_user.id.clone(),
+                        chat.id,
+                        Some(chat_provider_id.as_str()),
+                        chat_provider_group_id.as_deref(),
+                        fallback_from_chat_provider_id.as_deref(),
+                        &me_user.groups,
+                        mcp_auth_context,
+                        mcp_servers_unavailable,
+                        token_breakdown,
+                        Some(prompt_manifest),
+                        unavailable_files,
+                        allowed_tool_names,
+                        available_mcp_tools,
+                        offered_client_tool_timeouts,
+                        &chat_provider_headers_context,
+                        Some(&task_for_stream),
+                        chat.assistant_id,
+                        false,
+                        false,
                     )
                     .await;
-                    return Err(error);
-                }
-            };
+                let (end_content, generation_metadata) = match generation_result {
+                    Ok(result) => result,
+                    Err(error) => {
+                        let error = error.wrap_err("Failed during chat completion continuation");
+                        persist_background_generation_failure(
+                            &task_for_stream,
+                            &app_state,
+                            &policy,
+                            &me_user,
+                            current_message.id,
+                            &chat_provider_id,
+                            &error,
+                        )
+                        .await;
+                        return Err(error);
+                    }
+                };
 
-            let generation_was_aborted = generation_metadata
-                .as_ref()
-                .and_then(|metadata| metadata.was_aborted)
-                .unwrap_or(false);
+                // The metadata of the continuation replaces the one of the cut-off generation,
+                // so the message is only continuable again if the continuation was cut off too.
+                if let Some(metadata) = generation_metadata
+                    && let Err(err) = update_message_generation_metadata(
+                        &app_state.db,
+                        &policy,
+                        &me_user.to_subject(),
+                        &current_message.id,
+                        metadata,
+                    )
+                    .await
+                {
+                    warn_and_capture_error("update continue generation metadata", &err);
+                }
 
-            if let Some(metadata) = generation_metadata
-                && let Err(err) = update_message_generation_metadata(
-                    &app_state.db,
+                stream_update_assistant_message_completion::<
+                    RegenerateMessageStreamingResponseMessage,
+                >(
+                    tx.clone(),
+                    &app_state,
                     &policy,
-                    &me_user.to_subject(),
-                    &initial_assistant_message.id,
-                    metadata,
+                    end_content,
+                    &me_user,
+                    current_message.id,
                 )
-                .await
-            {
-                warn_and_capture_error("update regenerate generation metadata", &err);
-            }
+                .await?;
 
-            let end_content = if generation_was_aborted {
-                ensure_saved_assistant_content_for_abort(end_content)
-            } else {
-                end_content
-            };
-            stream_update_assistant_message_completion::<RegenerateMessageStreamingResponseMessage>(
-                tx.clone(),
-                &app_state,
-                &policy,
-                end_content,
-                &me_user,
-                initial_assistant_message.id,
-            )
-            .await?;
+                Ok(())
+            }
+            .await;
 
-            Ok(())
-        }
-        .await;
+            let generation_failed = result.is_err();
+            if let Err(error) = result {
+                forward_error_report(&tx, &error).await;
+                log_and_capture_error("continue message background task", &error);
+            }
 
-        let generation_failed = result.is_err();
-        if let Err(error) = result {
-            forward_error_report(&tx, &error).await;
-            log_and_capture_error("regenerate message background task", &error);
+            let outcome = task_for_stream.derive_outcome(generation_failed);
+            task_for_stream.mark_completed();
+            cleanup_guard.disarm();
+            app_state_for_cleanup
+                .background_tasks
+                .remove_task(&chat_id_for_cleanup, task_for_stream.generation_id, outcome)
+                .await;
         }
+        .instrument(generation_span.clone()),
+    );
 
-        let outcome = task_for_stream.derive_outcome(generation_failed);
-        task_for_stream.mark_completed();
-        cleanup_guard.disarm();
-        app_state_for_cleanup
-            .background_tasks
-            .remove_task(&chat_id_for_cleanup, task_for_stream.generation_id, outcome)
-            .await;
-    });
-
-    // Convert the receiver into a stream and return it
-    let receiver_stream = tokio_stream::wrappers::ReceiverStream::<Result<Event, Report>>::new(rx);
-
-    Ok(Sse::new(receiver_stream).keep_alive(
+    let sse = Sse::new(receiver_stream).keep_alive(
         axum::response::sse::KeepAlive::new()
             .interval(Duration::from_secs(1))
             .text("keep-alive-text"),
-    ))
+    );
+    Ok(with_traceparent_header(sse, &generation_span))
 }
 
 #[utoipa::path(
@@ -7867,7 +12500,9 @@ pub async fn regenerate_message_sse(
         (status = OK, content_type="text/event-stream", body = EditMessageStreamingResponseMessage),
         (status = BAD_REQUEST, description = "When validation fails (e.g., invalid message role)"),
         (status = NOT_FOUND, description = "When the chat does not exist or is not accessible"),
-        (status = CONFLICT, description = "When the chat is archived"),
+        (status = CONFLICT, body = GenerationInProgressError, description = "When the chat is archived. Also when another replica of the backend is generating an answer in the chat, with a JSON body that contains the message being generated and the replica generating it"),
+        (status = UNPROCESSABLE_ENTITY, body = UnsupportedInputFilesError, description = "When the replacement message is empty or only consists of whitespace, and no replacement file is attached. Also when the chat provider of the generation can't process the replacement files. The body is JSON, with a suggested chat provider that can process them, if available. Also when the previous message of the edited message belongs to another chat. Also when the chat provider of the generation is not available to the user, with a `ChatProviderUnavailableError` body"),
+        (status = TOO_MANY_REQUESTS, body = QuotaExceededError, description = "When the user reached their quota of the chat provider of the generation. The body is JSON, with the usage of the quota and the time it resets at"),
         (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
         (status = INTERNAL_SERVER_ERROR, description = "When an internal server error occurs")
     ),
@@ -7881,11 +12516,26 @@ pub async fn edit_message_sse(
     Extension(policy): Extension<PolicyEngine>,
     headers: HeaderMap,
     Json(request): Json<EditMessageRequest>,
-) -> Result<Sse<impl Stream<Item = Result<Event, Report>>>, (axum::http::StatusCode, String)> {
+) -> Result<Response, (axum::http::StatusCode, String)> {
     // Validate request parameters
+    validate_message_content(
+        &request.replace_user_message,
+        &request.replace_input_files_ids,
+    )?;
     let message_to_edit =
         validate_edit_request(&app_state, &policy, &me_user, &request.message_id).await?;
-    validate_file_uploads_for_message_submit(
+    // Edits branch off the edited message, so its previous message may be outside the active
+    // thread, but it must still belong to the same chat.
+    validate_previous_message_for_chat(
+        &app_state,
+        &policy,
+        &me_user,
+        message_to_edit.previous_message_id.as_ref(),
+        &message_to_edit.chat_id,
+        true,
+    )
+    .await?;
+    let input_files = validate_file_uploads_for_message_submit(
         &app_state,
         &policy,
         &me_user,
@@ -7925,12 +12575,64 @@ pub async fn edit_message_sse(
     })?;
     reject_if_archived(&chat)?;
 
+    // The generation uses the same chat provider as the answer to the edited message, unless
+    // another one is requested.
+    let requested_chat_provider_id = match request.chat_provider_id.clone() {
+        Some(chat_provider_id) => Some(chat_provider_id),
+        None => match get_generation_chat_provider_id_for_replaced_user_message(
+            &app_state.db,
+            &message_to_edit.id,
+        )
+        .await
+        .ok()
+        .flatten()
+        {
+            Some(chat_provider_id) => Some(chat_provider_id),
+            None => crate::models::chat::get_chat_assistant_configuration(
+                &app_state.db,
+                &policy,
+                &me_user.to_subject(),
+                &chat,
+                app_state.config.assistants.freeze_assistant_config,
+            )
+            .await
+            .ok()
+            .flatten()
+            .and_then(|assistant| assistant.default_chat_provider),
+        },
+    };
+    validate_chat_provider_available(
+        &app_state,
+        &policy,
+        &me_user,
+        requested_chat_provider_id.as_deref(),
+    )
+    .await?;
+    if !input_files.is_empty() || app_state.config.any_chat_provider_has_user_quota() {
+        validate_input_files_for_chat_provider(
+            &app_state,
+            &policy,
+            &me_user,
+            requested_chat_provider_id.as_deref(),
+            &input_files,
+        )
+        .await?;
+        validate_user_quota_for_chat_provider(
+            &app_state,
+            &policy,
+            &me_user,
+            requested_chat_provider_id.as_deref(),
+        )
+        .await?;
+    }
+
     // Create a channel for sending events
     let (tx, rx) = tokio::sync::mpsc::channel::<Result<Event, Report>>(100);
     let (_abort_rx, task) = app_state
         .background_tasks
-        .start_task(chat.id, Uuid::new_v4())
-        .await;
+        .start_task_for_user(chat.id, Uuid::new_v4(), &me_user.id)
+        .await
+        .map_err(generation_in_progress_response)?;
 
     // Move request data into the task
     let replace_user_message = request.replace_user_message;
@@ -7970,285 +12672,410 @@ pub async fn edit_message_sse(
             },
         ),
     };
-    let edit_input_parameters =
-        resolved_action_facet
-            .as_ref()
-            .map(|af| crate::models::message::InputParameters {
-                action_facet_id: Some(af.id.clone()),
-                action_facet_args: Some(af.args.clone()),
-            });
+    let detected_language = detect_message_language(
+        &replace_user_message,
+        &app_state.message_language_detection_config(),
+    );
+    let placeholder_text = file_only_message_placeholder(
+        &app_state,
+        &replace_user_message,
+        &replace_input_files_ids,
+    );
+    let edit_input_parameters = (resolved_action_facet.is_some()
+        || detected_language.is_some()
+        || placeholder_text.is_some())
+    .then(|| crate::models::message::InputParameters {
+        action_facet_id: resolved_action_facet.as_ref().map(|af| af.id.clone()),
+        action_facet_args: resolved_action_facet.as_ref().map(|af| af.args.clone()),
+        detected_language,
+        placeholder_text,
+    });
     let task_for_stream = task.clone();
+    // Built before the generation takes ownership of the app state
+    let receiver_stream = ClientDisconnectGuard::new(
+        tokio_stream::wrappers::ReceiverStream::<Result<Event, Report>>::new(rx),
+        Some(&task),
+        &app_state,
+    );
     let app_state_for_cleanup = app_state.clone();
     let chat_id_for_cleanup = chat.id;
 
     // Spawn a task to process the request and send events
-    tokio::spawn(async move {
-        let mut cleanup_guard = TaskCleanupGuard::new(
-            app_state_for_cleanup.background_tasks.clone(),
-            chat_id_for_cleanup,
-            task_for_stream.generation_id,
-        );
-        let result: Result<(), Report> = async {
-            let user_message = json!({
-                "role": "user",
-                "content": vec![json!({
-                    "content_type": "text",
-                    "text": replace_user_message
-                })],
-                "name": me_user.id
-            });
-
-            let saved_user_message = submit_message(
-                &app_state.db,
-                &policy,
-                &me_user.to_subject(),
-                &chat.id,
-                user_message,
-                message_to_edit.previous_message_id.as_ref(),
-                Some(&message_to_edit.id),
-                None,
-                &replace_input_files_ids,
-                None,
-                None,
-                edit_input_parameters,
-            )
-            .await
-            .wrap_err("Failed to submit edited user message")?;
-
-            let saved_user_message_wrapped = ChatMessage::from_model(saved_user_message.clone())
-                .wrap_err("Failed to convert saved edited user message")?;
-
-            let user_message_saved: EditMessageStreamingResponseMessage =
-                MessageSubmitStreamingResponseUserMessageSaved {
-                    message_id: saved_user_message.id,
-                    message: saved_user_message_wrapped,
-                }
-                .into();
-            user_message_saved.send_event_report(tx.clone()).await?;
+    let generation_span = crate::telemetry::assistant_message_generation_span(&chat_id_for_cleanup);
+    tokio::spawn(
+        async move {
+            let mut cleanup_guard = TaskCleanupGuard::new(
+                app_state_for_cleanup.background_tasks.clone(),
+                chat_id_for_cleanup,
+                task_for_stream.generation_id,
+            );
+            let result: Result<(), Report> = async {
+                let user_message = split_long_text_parts_in_raw_message(
+                    &app_state.config.chat.long_messages,
+                    json!({
+                        "role": "user",
+                        "content": vec![json!({
+                            "content_type": "text",
+                            "text": submitted_message_text(&replace_user_message)
+                        })],
+                        "name": me_user.id
+                    }),
+                )?;
+                let user_message = spill_oversized_raw_message(
+                    app_state.default_file_storage_provider(),
+                    &app_state.config.chat.content_spillover,
+                    user_message,
+                )
+                .await
+                .wrap_err("Failed to spill oversized user message content")?;
 
-            let me_profile_input = MeProfileChatRequestInput::from_me_profile(&me_user);
-            let fallback_chat_provider_id = if request.chat_provider_id.is_none() {
-                match get_generation_chat_provider_id_for_replaced_user_message(
+                let saved_user_message = submit_message(
                     &app_state.db,
-                    &message_to_edit.id,
+                    &policy,
+                    &me_user.to_subject(),
+                    &chat.id,
+                    user_message,
+                    message_to_edit.previous_message_id.as_ref(),
+                    Some(&message_to_edit.id),
+                    Some(&message_to_edit.id),
+                    None,
+                    &replace_input_files_ids,
+                    None,
+                    None,
+                    edit_input_parameters,
+                    false,
                 )
                 .await
-                {
-                    Ok(chat_provider_id) => chat_provider_id,
-                    Err(error) => {
-                        warn_and_capture_error("read edit fallback chat provider", &error);
-                        None
-                    }
-                }
-            } else {
-                None
-            };
-            let user_input = PromptCompositionUserInput {
-                just_submitted_user_message_id: saved_user_message.id,
-                requested_chat_provider_id: request
-                    .chat_provider_id
-                    .clone()
-                    .or(fallback_chat_provider_id),
-                new_input_file_ids: replace_input_files_ids,
-                selected_facet_ids: request.selected_facet_ids.clone(),
-                // Resolved above (request facet, else the validated stored
-                // fallback); the same value is persisted via edit_input_parameters.
-                action_facet: resolved_action_facet.as_ref().map(|af| {
-                    crate::services::prompt_composition::types::ActionFacetUserInput {
-                        id: af.id.clone(),
-                        args: af.args.clone(),
-                    }
-                }),
-            };
-            let PreparedChatRequest {
-                chat_request,
-                chat_options,
-                generation_input_messages,
-                generation_parameters,
-                generation_request_context,
-                mcp_servers_unavailable,
-                available_mcp_tools,
-                offered_client_tool_timeouts,
-            } = prepare_chat_request(
-                &app_state,
-                &policy,
-                &chat,
-                user_input,
-                generation_request_context.clone(),
-                &me_profile_input,
-            )
-            .await
-            .wrap_err("Failed to prepare edited chat request")?;
-
-            let langfuse_trace_enrichment = match build_langfuse_trace_enrichment(
-                &app_state,
-                &policy,
-                &me_user.to_subject(),
-                &generation_input_messages,
-                chat.assistant_id,
-                &generation_request_context,
-            )
-            .await
-            {
-                Ok(enrichment) => enrichment,
-                Err(err) => {
-                    warn_and_capture_error("build edit Langfuse trace enrichment", &err);
-                    LangfuseTraceEnrichment::default()
-                }
-            };
+                .wrap_err("Failed to submit edited user message")?;
 
-            let empty_assistant_message_json = json!({ "role": "assistant", "content": [] });
-            let chat_provider_id = generation_parameters
-                .generation_chat_provider_id
-                .clone()
-                .unwrap_or_else(|| "unknown".to_string());
-            let allowed_tool_names: HashSet<String> = chat_request
-                .tools
-                .as_ref()
-                .map(|tools| {
-                    tools
-                        .iter()
-                        .map(|tool| tool.name.to_string())
-                        .collect::<HashSet<_>>()
-                })
-                .unwrap_or_default();
+                let saved_user_message_wrapped =
+                    ChatMessage::from_model(saved_user_message.clone())
+                        .wrap_err("Failed to convert saved edited user message")?;
 
-            let initial_assistant_message = submit_message(
-                &app_state.db,
-                &policy,
-                &me_user.to_subject(),
-                &chat.id,
-                empty_assistant_message_json,
-                Some(&saved_user_message.id),
-                None,
-                Some(generation_input_messages.clone()),
-                &[],
-                Some(generation_parameters),
-                None,
-                None,
-            )
-            .await
-            .wrap_err("Failed to submit initial assistant message for edit")?;
+                let user_message_saved: EditMessageStreamingResponseMessage =
+                    MessageSubmitStreamingResponseUserMessageSaved {
+                        message_id: saved_user_message.id,
+                        message: saved_user_message_wrapped,
+                        auto_attached_file_id: None,
+                    }
+                    .into();
+                user_message_saved.send_event_report(tx.clone()).await?;
 
-            let assistant_started_event: EditMessageStreamingResponseMessage =
-                MessageSubmitStreamingResponseAssistantMessageStarted {
-                    message_id: initial_assistant_message.id,
+                if let Some(error) =
+                    moderation_blocking_error(&app_state, &policy, &me_user, &saved_user_message)
+                        .await?
+                {
+                    let (assistant_message, assistant_message_wrapped) =
+                        save_moderation_blocked_assistant_message(
+                            &app_state,
+                            &policy,
+                            &me_user,
+                            &chat.id,
+                            &saved_user_message.id,
+                            None,
+                            error.clone(),
+                        )
+                        .await?;
+                    return send_moderation_blocked_events::<EditMessageStreamingResponseMessage>(
+                        tx.clone(),
+                        assistant_message,
+                        assistant_message_wrapped,
+                        error,
+                    )
+                    .await;
                 }
-                .into();
-            assistant_started_event
-                .send_event_report(tx.clone())
-                .await?;
 
-            let subject = me_user.to_subject();
-            let chat_provider_headers_context =
-                ChatProviderHeadersContext::new(&me_user.id, &me_user.id_token_claims);
-            let mcp_auth_context = McpRequestAuthContext {
-                app_state: Some(&app_state),
-                user_id: Uuid::parse_str(&me_user.id).ok(),
-                oidc_token: Some(&me_user.oidc_token),
-                access_token: me_user.access_token.as_deref(),
-            };
-            let generation_result =
-                stream_generate_chat_completion::<EditMessageStreamingResponseMessage>(
-                    tx.clone(),
-                    &app_state,
-                    &policy,
-                    &subject,
+                let me_profile_input = MeProfileChatRequestInput::from_me_profile(&me_user);
+                let fallback_chat_provider_id = if request.chat_provider_id.is_none() {
+                    match get_generation_chat_provider_id_for_replaced_user_message(
+                        &app_state.db,
+                        &message_to_edit.id,
+                    )
+                    .await
+                    {
+                        Ok(chat_provider_id) => chat_provider_id,
+                        Err(error) => {
+                            warn_and_capture_error("read edit fallback chat provider", &error);
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                let user_input = PromptCompositionUserInput {
+                    just_submitted_user_message_id: saved_user_message.id,
+                    requested_chat_provider_id: request
+                        .chat_provider_id
+                        .clone()
+                        .or(fallback_chat_provider_id),
+                    new_input_file_ids: replace_input_files_ids,
+                    refresh_file_ids: vec![],
+                    selected_facet_ids: request.selected_facet_ids.clone(),
+                    // Resolved above (request facet, else the validated stored
+                    // fallback); the same value is persisted via edit_input_parameters.
+                    action_facet: resolved_action_facet.as_ref().map(|af| {
+                        crate::services::prompt_composition::types::ActionFacetUserInput {
+                            id: af.id.clone(),
+                            args: af.args.clone(),
+                        }
+                    }),
+                    requested_response_language: request.response_language.clone(),
+                };
+                let PreparedChatRequest {
                     chat_request,
-                    langfuse_trace_enrichment,
                     chat_options,
-                    initial_assistant_message.id,
-                    me_user.id.clone(),
-                    chat.id,
-                    Some(chat_provider_id.as_str()),
-                    &me_user.groups,
-                    mcp_auth_context,
+                    generation_input_messages,
+                    generation_parameters,
+                    generation_request_context,
                     mcp_servers_unavailable,
-                    allowed_tool_names,
+                    token_breakdown,
+                    prompt_manifest,
+                    unavailable_files,
                     available_mcp_tools,
                     offered_client_tool_timeouts,
-                    &chat_provider_headers_context,
-                    Some(&task_for_stream),
+                    effective_model_settings: _,
+                } = prepare_chat_request(
+                    &app_state,
+                    &policy,
+                    &chat,
+                    user_input,
+                    generation_request_context.clone(),
+                    &me_profile_input,
+                    None,
+                )
+                .await
+                .wrap_err("Failed to prepare edited chat request")?;
+
+                let langfuse_trace_enrichment = match build_langfuse_trace_enrichment(
+                    &app_state,
+                    &policy,
+                    &me_user.to_subject(),
+                    &generation_input_messages,
                     chat.assistant_id,
+                    &generation_request_context,
                 )
-                .await;
-            let (end_content, generation_metadata) = match generation_result {
-                Ok(result) => result,
-                Err(error) => {
-                    let error = error.wrap_err("Failed during chat completion generation");
-                    persist_background_generation_failure(
-                        &task_for_stream,
+                .await
+                {
+                    Ok(enrichment) => enrichment,
+                    Err(err) => {
+                        warn_and_capture_error("build edit Langfuse trace enrichment", &err);
+                        LangfuseTraceEnrichment::default()
+                    }
+                };
+
+                let empty_assistant_message_json = json!({ "role": "assistant", "content": [] });
+                let chat_provider_id = generation_parameters
+                    .generation_chat_provider_id
+                    .clone()
+                    .unwrap_or_else(|| "unknown".to_string());
+                let chat_provider_group_id = generation_parameters.chat_provider_group_id.clone();
+                let fallback_from_chat_provider_id =
+                    generation_parameters.fallback_from_chat_provider_id.clone();
+                let allowed_tool_names: HashSet<String> = chat_request
+                    .tools
+                    .as_ref()
+                    .map(|tools| {
+                        tools
+                            .iter()
+                            .map(|tool| tool.name.to_string())
+                            .collect::<HashSet<_>>()
+                    })
+                    .unwrap_or_default();
+
+                let initial_assistant_message = submit_message(
+                    &app_state.db,
+                    &policy,
+                    &me_user.to_subject(),
+                    &chat.id,
+                    empty_assistant_message_json,
+                    Some(&saved_user_message.id),
+                    None,
+                    None,
+                    Some(generation_input_messages.clone()),
+                    &[],
+                    Some(generation_parameters),
+                    None,
+                    None,
+                    false,
+                )
+                .await
+                .wrap_err("Failed to submit initial assistant message for edit")?;
+
+                let (trace_id, span_id) = current_trace_ids();
+                let assistant_started_event: EditMessageStreamingResponseMessage =
+                    MessageSubmitStreamingResponseAssistantMessageStarted {
+                        message_id: initial_assistant_message.id,
+                        trace_id,
+                        span_id,
+                    }
+                    .into();
+                assistant_started_event
+                    .send_event_report(tx.clone())
+                    .await?;
+
+                let subject = me_user.to_subject();
+                let chat_provider_headers_context =
+                    ChatProviderHeadersContext::new(&me_user.id, &me_user.id_token_claims);
+                let mcp_auth_context = McpRequestAuthContext {
+                    app_state: Some(&app_state),
+                    user_id: Uuid::parse_str(&me_user.id).ok(),
+                    oidc_token: me_user.oidc_token.as_deref(),
+                    access_token: me_user.access_token.as_deref(),
+                };
+                let generation_result =
+                    stream_generate_chat_completion::<EditMessageStreamingResponseMessage>(
+                        tx.clone(),
                         &app_state,
                         &policy,
-                        &me_user,
-                        initial_assistant_message.id,
-                        &chat_provider_id,
-                        &error,
+                        &subject,
+                        chat_request,
+                        langfuse_trace_enrichment,
+                        chat_options,
+                        initial_assistant_message.id,
+                        vec![],
+                        me_user.id.clone(),
+                        chat.id,
+                        Some(chat_provider_id.as_str()),
+                        chat_provider_group_id.as_deref(),
+                        fallback_from_chat_provider_id.as_deref(),
+                        &me_user.groups,
+                        mcp_auth_context,
+                        mcp_servers_unavailable,
+                        token_breakdown,
+                        Some(prompt_manifest),
+                        unavailable_files,
+                        allowed_tool_names,
+                        available_mcp_tools,
+                        offered_client_tool_timeouts,
+                        &chat_provider_headers_context,
+                        Some(&task_for_stream),
+                        chat.assistant_id,
+                        true,
+                        false,
+                    )
+                    .await;
+                let (end_content, generation_metadata) = match generation_result {
+                    Ok(result) => result,
+                    Err(error) => {
+                        let error = error.wrap_err("Failed during chat completion generation");
+                        persist_background_generation_failure(
+                            &task_for_stream,
+                            &app_state,
+                            &policy,
+                            &me_user,
+                            initial_assistant_message.id,
+                            &chat_provider_id,
+                            &error,
+                        )
+                        .await;
+                        return Err(error);
+                    }
+                };
+
+                let generation_was_aborted = generation_metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.was_aborted)
+                    .unwrap_or(false);
+
+                if let Some(metadata) = generation_metadata
+                    && let Err(err) = update_message_generation_metadata(
+                        &app_state.db,
+                        &policy,
+                        &me_user.to_subject(),
+                        &initial_assistant_message.id,
+                        metadata,
                     )
-                    .await;
-                    return Err(error);
+                    .await
+                {
+                    warn_and_capture_error("update edit generation metadata", &err);
                 }
-            };
-
-            let generation_was_aborted = generation_metadata
-                .as_ref()
-                .and_then(|metadata| metadata.was_aborted)
-                .unwrap_or(false);
 
-            if let Some(metadata) = generation_metadata
-                && let Err(err) = update_message_generation_metadata(
-                    &app_state.db,
+                let end_content = if generation_was_aborted {
+                    ensure_saved_assistant_content_for_abort(end_content)
+                } else {
+                    end_content
+                };
+                stream_update_assistant_message_completion::<EditMessageStreamingResponseMessage>(
+                    tx.clone(),
+                    &app_state,
                     &policy,
-                    &me_user.to_subject(),
-                    &initial_assistant_message.id,
-                    metadata,
+                    end_content,
+                    &me_user,
+                    initial_assistant_message.id,
                 )
-                .await
-            {
-                warn_and_capture_error("update edit generation metadata", &err);
-            }
+                .await?;
 
-            let end_content = if generation_was_aborted {
-                ensure_saved_assistant_content_for_abort(end_content)
-            } else {
-                end_content
-            };
-            stream_update_assistant_message_completion::<EditMessageStreamingResponseMessage>(
-                tx.clone(),
-                &app_state,
-                &policy,
-                end_content,
-                &me_user,
-                initial_assistant_message.id,
-            )
-            .await?;
+                Ok(())
+            }
+            .await;
 
-            Ok(())
-        }
-        .await;
+            let generation_failed = result.is_err();
+            if let Err(error) = result {
+                forward_error_report(&tx, &error).await;
+                log_and_capture_error("edit message background task", &error);
+            }
 
-        let generation_failed = result.is_err();
-        if let Err(error) = result {
-            forward_error_report(&tx, &error).await;
-            log_and_capture_error("edit message background task", &error);
+            let outcome = task_for_stream.derive_outcome(generation_failed);
+            task_for_stream.mark_completed();
+            cleanup_guard.disarm();
+            app_state_for_cleanup
+                .background_tasks
+                .remove_task(&chat_id_for_cleanup, task_for_stream.generation_id, outcome)
+                .await;
         }
+        .instrument(generation_span.clone()),
+    );
 
-        let outcome = task_for_stream.derive_outcome(generation_failed);
-        task_for_stream.mark_completed();
-        cleanup_guard.disarm();
-        app_state_for_cleanup
-            .background_tasks
-            .remove_task(&chat_id_for_cleanup, task_for_stream.generation_id, outcome)
-            .await;
-    });
-
-    // Convert the receiver into a stream and return it
-    let receiver_stream = tokio_stream::wrappers::ReceiverStream::<Result<Event, Report>>::new(rx);
-
-    Ok(Sse::new(receiver_stream).keep_alive(
+    let sse = Sse::new(receiver_stream).keep_alive(
         axum::response::sse::KeepAlive::new()
             .interval(Duration::from_secs(1))
             .text("keep-alive-text"),
+    );
+    Ok(with_traceparent_header(sse, &generation_span))
+}
+
+/// The background task of the active generation of a chat, after verifying that the user has
+/// access to the chat.
+pub(crate) async fn active_task_for_chat(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    chat_id: &Uuid,
+) -> Result<Arc<StreamingTask>, (axum::http::StatusCode, String)> {
+    // Ephemeral chats are only held in memory, and can only be accessed by their owner
+    if !app_state.ephemeral_chats.contains(chat_id, &me_user.id) {
+        get_or_create_chat(
+            &app_state.db,
+            policy,
+            &me_user.to_subject(),
+            Some(chat_id),
+            &me_user.id,
+            None,
+            None,
+        )
+        .await
+        .map_err(|e| {
+            (
+                axum::http::StatusCode::FORBIDDEN,
+                format!("Access denied to chat: {}", e),
+            )
+        })?;
+    }
+
+    if let Some(task) = app_state.background_tasks.get_task(chat_id).await {
+        return Ok(task);
+    }
+    // The generation can only be followed on the replica that runs it
+    if let Some(error) = app_state
+        .background_tasks
+        .generation_on_other_replica(chat_id)
+        .await
+    {
+        return Err(generation_in_progress_response(error));
+    }
+    Err((
+        axum::http::StatusCode::NOT_FOUND,
+        "No active generation task found for this chat".to_string(),
     ))
 }
 
@@ -8259,6 +13086,7 @@ pub async fn edit_message_sse(
     responses(
         (status = OK, body = AbortStreamResponse),
         (status = NOT_FOUND, description = "No active generation task found for this chat"),
+        (status = CONFLICT, body = GenerationInProgressError, description = "When the generation runs on another replica of the backend. The body is JSON, with the replica that runs it"),
         (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
         (status = FORBIDDEN, description = "When the user has no access to the chat"),
         (status = INTERNAL_SERVER_ERROR, description = "When an internal server error occurs")
@@ -8273,32 +13101,7 @@ pub async fn abort_message_stream(
     Extension(me_user): Extension<MeProfile>,
     Json(request): Json<AbortStreamRequest>,
 ) -> Result<Json<AbortStreamResponse>, (axum::http::StatusCode, String)> {
-    let _chat = get_or_create_chat(
-        &app_state.db,
-        &policy,
-        &me_user.to_subject(),
-        Some(&request.chat_id),
-        &me_user.id,
-        None,
-        None,
-    )
-    .await
-    .map_err(|e| {
-        (
-            axum::http::StatusCode::FORBIDDEN,
-            format!("Access denied to chat: {}", e),
-        )
-    })?
-    .0;
-
-    let task = app_state
-        .background_tasks
-        .get_task(&request.chat_id)
-        .await
-        .ok_or((
-            axum::http::StatusCode::NOT_FOUND,
-            "No active generation task found for this chat".to_string(),
-        ))?;
+    let task = active_task_for_chat(&app_state, &policy, &me_user, &request.chat_id).await?;
 
     task.request_abort();
 
@@ -8330,23 +13133,27 @@ pub async fn client_tool_result(
 ) -> Result<Json<ClientToolResultResponse>, (axum::http::StatusCode, String)> {
     // Per-chat ownership gate, identical to abort/resume — middleware alone does
     // not enforce per-chat access.
-    let _chat = get_or_create_chat(
-        &app_state.db,
-        &policy,
-        &me_user.to_subject(),
-        Some(&request.chat_id),
-        &me_user.id,
-        None,
-        None,
-    )
-    .await
-    .map_err(|e| {
-        (
-            axum::http::StatusCode::FORBIDDEN,
-            format!("Access denied to chat: {}", e),
+    if !app_state
+        .ephemeral_chats
+        .contains(&request.chat_id, &me_user.id)
+    {
+        get_or_create_chat(
+            &app_state.db,
+            &policy,
+            &me_user.to_subject(),
+            Some(&request.chat_id),
+            &me_user.id,
+            None,
+            None,
         )
-    })?
-    .0;
+        .await
+        .map_err(|e| {
+            (
+                axum::http::StatusCode::FORBIDDEN,
+                format!("Access denied to chat: {}", e),
+            )
+        })?;
+    }
 
     let task = app_state
         .background_tasks
@@ -8394,6 +13201,7 @@ pub async fn client_tool_result(
     responses(
         (status = OK, content_type="text/event-stream", body = MessageSubmitStreamingResponseMessage),
         (status = NOT_FOUND, description = "No active generation task found for this chat"),
+        (status = CONFLICT, body = GenerationInProgressError, description = "When the generation runs on another replica of the backend. The body is JSON, with the replica that runs it"),
         (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
         (status = INTERNAL_SERVER_ERROR, description = "When an internal server error occurs")
     ),
@@ -8407,34 +13215,7 @@ pub async fn resume_message_sse(
     Extension(me_user): Extension<MeProfile>,
     Json(request): Json<ResumeStreamRequest>,
 ) -> Result<Sse<impl Stream<Item = Result<Event, Report>>>, (axum::http::StatusCode, String)> {
-    // Verify user has access to this chat
-    let _chat = get_or_create_chat(
-        &app_state.db,
-        &policy,
-        &me_user.to_subject(),
-        Some(&request.chat_id),
-        &me_user.id,
-        None,
-        None,
-    )
-    .await
-    .map_err(|e| {
-        (
-            axum::http::StatusCode::FORBIDDEN,
-            format!("Access denied to chat: {}", e),
-        )
-    })?
-    .0;
-
-    // Get the background task for this chat
-    let task = app_state
-        .background_tasks
-        .get_task(&request.chat_id)
-        .await
-        .ok_or((
-            axum::http::StatusCode::NOT_FOUND,
-            "No active generation task found for this chat".to_string(),
-        ))?;
+    let task = active_task_for_chat(&app_state, &policy, &me_user, &request.chat_id).await?;
 
     // Get the event history
     let event_history = task.get_event_history().await;
@@ -8479,7 +13260,152 @@ pub async fn resume_message_sse(
         }
     });
 
-    Ok(Sse::new(event_stream).keep_alive(
+    Ok(Sse::new(ClientDisconnectGuard::new(
+        event_stream,
+        Some(&task),
+        &app_state,
+    ))
+    .keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(Duration::from_secs(1))
+            .text("keep-alive-text"),
+    ))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PromptOptimizerStreamingResponseTextDelta {
+    new_text: String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PromptOptimizerStreamingResponseCompleted {
+    optimized_prompt: String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(tag = "message_type")]
+pub enum PromptOptimizerStreamingResponseMessage {
+    #[serde(rename = "text_delta")]
+    /// Sent whenever a new chunk of the optimized prompt is generated.
+    TextDelta(PromptOptimizerStreamingResponseTextDelta),
+    #[serde(rename = "completed")]
+    /// Sent once the optimized prompt has been generated in full.
+    Completed(PromptOptimizerStreamingResponseCompleted),
+    #[serde(rename = "error")]
+    /// Sent when the optimization fails. No `completed` event follows.
+    Error(MessageSubmitStreamingResponseError),
+}
+
+impl SendAsSseEvent for PromptOptimizerStreamingResponseMessage {
+    fn tag(&self) -> &'static str {
+        match self {
+            Self::TextDelta(_) => "text_delta",
+            Self::Completed(_) => "completed",
+            Self::Error(_) => "error",
+        }
+    }
+
+    fn data_json(&self) -> Result<String, Report> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+/// Optimize a prompt using the configured prompt optimizer, streaming the optimized prompt.
+///
+/// Accepts the same request as `/prompt-optimizer`. Nothing is persisted.
+#[utoipa::path(
+    post,
+    path = "/prompt-optimizer/stream",
+    request_body = PromptOptimizerRequest,
+    responses(
+        (status = OK, content_type="text/event-stream", body = PromptOptimizerStreamingResponseMessage),
+        (status = BAD_REQUEST, description = "Invalid prompt"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = NOT_FOUND, description = "Prompt optimizer is not enabled, or the chat or message was not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn prompt_optimizer_sse(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Json(request): Json<PromptOptimizerRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Report>>>, (axum::http::StatusCode, String)> {
+    let (chat_request, chat_options) =
+        prepare_prompt_optimizer_chat_request(&app_state, &policy, &me_user, request)
+            .await
+            .map_err(|status| (status, "Unable to optimize prompt".to_string()))?;
+    let genai_client = app_state.genai_for_prompt_optimizer().map_err(|e| {
+        (
+            log_internal_server_error(e),
+            "Failed to get prompt optimizer chat provider".to_string(),
+        )
+    })?;
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Event, Report>>(100);
+
+    tokio::spawn(
+        async move {
+            let result: Result<(), Report> = async {
+                let mut stream = genai_client
+                    .exec_chat_stream("PLACEHOLDER_MODEL", chat_request, Some(&chat_options))
+                    .await
+                    .wrap_err("Failed to start prompt optimizer stream")?
+                    .stream;
+
+                let mut optimized_prompt = String::new();
+                while let Some(event) = stream.next().await {
+                    if let ChatStreamEvent::Chunk(StreamChunk { content }) =
+                        event.wrap_err("Prompt optimizer stream failed")?
+                    {
+                        optimized_prompt.push_str(&content);
+                        PromptOptimizerStreamingResponseMessage::TextDelta(
+                            PromptOptimizerStreamingResponseTextDelta { new_text: content },
+                        )
+                        .send_event_report(tx.clone())
+                        .await?;
+                    }
+                }
+
+                PromptOptimizerStreamingResponseMessage::Completed(
+                    PromptOptimizerStreamingResponseCompleted { optimized_prompt },
+                )
+                .send_event_report(tx.clone())
+                .await
+            }
+            .await;
+
+            if let Err(error) = result {
+                log_and_capture_error("prompt optimizer stream", &error);
+                // The detailed error is captured server-side; the client gets only a
+                // generic message.
+                let error_event = PromptOptimizerStreamingResponseMessage::Error(
+                    MessageSubmitStreamingResponseError {
+                        message_id: None,
+                        error: GenerationErrorType::InternalError {
+                            error_description: "The prompt could not be optimized.".to_string(),
+                        },
+                    },
+                );
+                if let Err(send_error) = error_event.send_event_report(tx).await {
+                    tracing::debug!(
+                        error = ?send_error,
+                        "Could not forward error to a closed SSE stream"
+                    );
+                }
+            }
+        }
+        .in_current_span(),
+    );
+
+    let receiver_stream = tokio_stream::wrappers::ReceiverStream::<Result<Event, Report>>::new(rx);
+
+    Ok(Sse::new(receiver_stream).keep_alive(
         axum::response::sse::KeepAlive::new()
             .interval(Duration::from_secs(1))
             .text("keep-alive-text"),
@@ -8515,3 +13441,87 @@ mod client_tool_result_request_tests {
         assert_eq!(with_value.result, Some(serde_json::json!({ "slots": 3 })));
     }
 }
+
+#[cfg(test)]
+mod rate_limit_error_tests {
+    use super::{GenerationErrorType, RateLimitScope, parse_error_body, retry_after_from_headers};
+    use axum::http::{HeaderMap, HeaderValue};
+    use sea_orm::prelude::Uuid;
+    use serde_json::json;
+
+    fn rate_limit(
+        body: serde_json::Value,
+        header_retry_after_seconds: Option<u64>,
+    ) -> (Option<u64>, Option<RateLimitScope>) {
+        let error = parse_error_body(&body, Uuid::nil(), Some(429), header_retry_after_seconds)
+            .expect("parsed error")
+            .error;
+        match error {
+            GenerationErrorType::RateLimited {
+                retry_after_seconds,
+                scope,
+                ..
+            } => (retry_after_seconds, scope),
+            other => panic!("Expected a rate limit error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_openai_rate_limit_body() {
+        let body = json!({
+            "error": {
+                "message": "Rate limit reached for gpt-4o on tokens per min (TPM): Limit 30000, Used 29000, Requested 2000. Please try again in 2.5s.",
+                "type": "tokens",
+                "code": "rate_limit_exceeded",
+            }
+        });
+        assert_eq!(
+            rate_limit(body, None),
+            (Some(3), Some(RateLimitScope::Tokens))
+        );
+    }
+
+    #[test]
+    fn parses_azure_rate_limit_body() {
+        let body = json!({
+            "error": {
+                "code": "429",
+                "message": "Requests to the ChatCompletions_Create Operation have exceeded call rate limit of your current pricing tier. Please retry after 12 seconds.",
+            }
+        });
+        assert_eq!(
+            rate_limit(body, None),
+            (Some(12), Some(RateLimitScope::Requests))
+        );
+    }
+
+    #[test]
+    fn prefers_retry_after_header() {
+        let body = json!({
+            "error": {
+                "code": "rate_limit_exceeded",
+                "message": "Rate limit exceeded. Please try again in 1m.",
+            }
+        });
+        assert_eq!(rate_limit(body.clone(), Some(42)), (Some(42), None));
+        assert_eq!(rate_limit(body, None), (Some(60), None));
+    }
+
+    #[test]
+    fn parses_retry_after_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after_from_headers(&headers), None);
+
+        headers.insert("retry-after-ms", HeaderValue::from_static("1500"));
+        assert_eq!(retry_after_from_headers(&headers), Some(2));
+
+        headers.insert("retry-after", HeaderValue::from_static("42"));
+        assert_eq!(retry_after_from_headers(&headers), Some(42));
+
+        headers.insert(
+            "retry-after",
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after_from_headers(&headers), Some(0));
+    }
+}
@@ -1,12 +1,15 @@
+use crate::config::McpSchemaViolationMode;
+use crate::metrics::report_mcp_tool_schema_violation;
 use crate::models::message::{ContentPart, ContentPartImageFilePointer, GenerationErrorType};
 use crate::policy::engine::PolicyEngine;
 use crate::policy::types::Subject;
+use crate::services::mcp_output_schema::{coerce_tool_output, validate_tool_output};
 use crate::state::AppState;
 use eyre::{Report, WrapErr, eyre};
 use genai::chat::{ToolCall, ToolResponse};
 use sea_orm::JsonValue;
 use sea_orm::prelude::Uuid;
-use serde_json::Value;
+use serde_json::{Value, json};
 use std::collections::HashSet;
 use std::time::SystemTime;
 
@@ -34,6 +37,9 @@ pub struct McpToolPostProcessResult {
     pub tool_response: ToolResponse,
     pub output_value: Option<JsonValue>,
     pub image_content_parts: Vec<ContentPart>,
+    /// Whether the result was turned into an error, because it didn't match the output schema of
+    /// the tool.
+    pub is_error: bool,
 }
 
 fn json_pointer_escape(segment: &str) -> String {
@@ -332,6 +338,16 @@ async fn process_mcp_file_outputs(
     if extracted_fields.is_empty() {
         return Ok(Vec::new());
     }
+    if !app_state
+        .config
+        .file_uploads
+        .generated_image_storage_allowed()
+    {
+        return Err(eyre!(
+            "Storing images returned by MCP tools is disabled, as uploading files is disabled and \
+             `file_uploads.storage_carve_outs.generated_images` is not set"
+        ));
+    }
 
     let mut image_pointers = Vec::new();
     let mut replacements = Vec::new();
@@ -500,11 +516,34 @@ pub fn parse_content_filter_error_from_mcp_tool_result(
     None
 }
 
+/// Turn the violations of the output schema of a tool into an error result for the tool call.
+fn schema_violation_error_result(
+    unfinished_tool_call: &ToolCall,
+    violations: &[String],
+) -> McpToolPostProcessResult {
+    let error = "The result of the tool doesn't match its output schema";
+    McpToolPostProcessResult {
+        tool_response: ToolResponse {
+            call_id: unfinished_tool_call.call_id.clone(),
+            content: format!("MCP tool error: {error}:\n- {}", violations.join("\n- ")),
+        },
+        output_value: Some(json!({
+            "status": "error",
+            "error": error,
+            "schema_violations": violations,
+        })),
+        image_content_parts: Vec::new(),
+        is_error: true,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn post_process_mcp_tool_result(
     app_state: &AppState,
     policy: &PolicyEngine,
     subject: &Subject,
     chat_id: Uuid,
+    mcp_server_id: &str,
     unfinished_tool_call: &ToolCall,
     output_schema: Option<&std::sync::Arc<rmcp::model::JsonObject>>,
     tool_call_result: &rmcp::model::CallToolResult,
@@ -514,9 +553,69 @@ pub async fn post_process_mcp_tool_result(
         .ok()
         .or(Some(JsonValue::String(tool_response_content.clone())));
     let mut image_content_parts: Vec<ContentPart> = Vec::new();
+    let mut schema_violation_note = None;
 
     if let Some(output_schema) = output_schema {
         let output_schema_value = Value::Object(output_schema.as_ref().clone());
+        // Error results don't have to match the output schema
+        if tool_call_result.is_error != Some(true)
+            && let Some(structured_output) = tool_call_result
+                .structured_content
+                .clone()
+                .or_else(|| serde_json::from_str(&tool_response_content).ok())
+        {
+            let violations = validate_tool_output(&output_schema_value, &structured_output);
+            if !violations.is_empty() {
+                let mode = app_state
+                    .config
+                    .mcp_servers
+                    .get(mcp_server_id)
+                    .map(|server| server.on_schema_violation)
+                    .unwrap_or_default();
+                report_mcp_tool_schema_violation(
+                    mcp_server_id,
+                    &unfinished_tool_call.fn_name,
+                    mode,
+                );
+                tracing::warn!(
+                    mcp_server_id,
+                    tool_name = %unfinished_tool_call.fn_name,
+                    ?mode,
+                    ?violations,
+                    "MCP tool result doesn't match the output schema of the tool"
+                );
+                match mode {
+                    McpSchemaViolationMode::Warn => {
+                        schema_violation_note = Some(format!(
+                            "[Validation note: this result doesn't match the output schema of the tool:\n- {}]",
+                            violations.join("\n- ")
+                        ));
+                    }
+                    McpSchemaViolationMode::Error => {
+                        return Ok(schema_violation_error_result(
+                            unfinished_tool_call,
+                            &violations,
+                        ));
+                    }
+                    McpSchemaViolationMode::Coerce => {
+                        let mut coerced_output = structured_output;
+                        coerce_tool_output(&output_schema_value, &mut coerced_output);
+                        let remaining_violations =
+                            validate_tool_output(&output_schema_value, &coerced_output);
+                        if !remaining_violations.is_empty() {
+                            return Ok(schema_violation_error_result(
+                                unfinished_tool_call,
+                                &remaining_violations,
+                            ));
+                        }
+                        tool_response_content = serde_json::to_string(&coerced_output)
+                            .wrap_err("Failed to serialize coerced MCP tool output")?;
+                        output_value = Some(coerced_output);
+                    }
+                }
+            }
+        }
+
         let schema_paths = collect_file_content_paths(&output_schema_value);
         if !schema_paths.is_empty() {
             let mut output_json: Value = serde_json::from_str(&tool_response_content)
@@ -536,6 +635,11 @@ pub async fn post_process_mcp_tool_result(
         }
     }
 
+    // Appended after the file extraction, which needs the output to be valid JSON
+    if let Some(schema_violation_note) = schema_violation_note {
+        tool_response_content = format!("{tool_response_content}\n\n{schema_violation_note}");
+    }
+
     Ok(McpToolPostProcessResult {
         tool_response: ToolResponse {
             call_id: unfinished_tool_call.call_id.clone(),
@@ -543,6 +647,7 @@ pub async fn post_process_mcp_tool_result(
         },
         output_value,
         image_content_parts,
+        is_error: false,
     })
 }
 
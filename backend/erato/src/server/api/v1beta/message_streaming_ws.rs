@@ -0,0 +1,346 @@
+use crate::policy::engine::PolicyEngine;
+use crate::server::api::v1beta::me_profile_middleware::MeProfile;
+use crate::server::api::v1beta::message_streaming::{
+    MessageSubmitRequest, active_task_for_chat, start_message_submit, streaming_event_to_json,
+    validate_message_submit,
+};
+use crate::services::background_tasks::StreamingEvent;
+use crate::state::AppState;
+use axum::Extension;
+use axum::extract::State;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use futures::{SinkExt, StreamExt};
+use sea_orm::JsonValue;
+use sea_orm::prelude::Uuid;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinSet;
+use tracing::instrument;
+
+/// Number of frames that may wait for the client before the forwarding of an operation pauses.
+///
+/// While an operation is paused, the events of its generation pile up in the broadcast channel
+/// of the generation, which drops the oldest ones once it is full. The generation itself never
+/// waits for the client.
+const OUTGOING_FRAME_BUFFER: usize = 64;
+
+/// A frame sent by the client. Every operation carries a correlation ID chosen by the client,
+/// which is copied into all frames the server sends for that operation.
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum ClientFrame {
+    /// Submit a message, like `POST /me/messages/submitstream`.
+    Submit {
+        correlation_id: String,
+        payload: MessageSubmitRequest,
+    },
+    /// Stream the events of the active generation of a chat, like
+    /// `POST /me/messages/resumestream`.
+    Resume {
+        correlation_id: String,
+        chat_id: Uuid,
+    },
+    /// Stop the active generation of a chat, like `POST /me/messages/abortstream`.
+    Cancel {
+        correlation_id: String,
+        chat_id: Uuid,
+    },
+}
+
+/// Streams generations over a WebSocket, as an alternative to the SSE endpoints.
+///
+/// Authentication and policies apply when the connection is upgraded. The server frames of an
+/// operation are the JSON events of the corresponding SSE endpoint with an added
+/// `correlation_id`.
+#[instrument(skip_all)]
+pub async fn message_stream_socket(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Response {
+    if !app_state.config.websocket_streaming.enabled {
+        return (
+            StatusCode::NOT_FOUND,
+            "WebSocket streaming is not enabled".to_string(),
+        )
+            .into_response();
+    }
+
+    ws.on_upgrade(move |socket| handle_socket(app_state, me_user, policy, headers, socket))
+        .into_response()
+}
+
+async fn handle_socket(
+    app_state: AppState,
+    me_user: MeProfile,
+    policy: PolicyEngine,
+    headers: HeaderMap,
+    socket: WebSocket,
+) {
+    let config = app_state.config.websocket_streaming.clone();
+    let idle_timeout = Duration::from_secs(config.idle_timeout_secs);
+    let (mut sender, mut receiver) = socket.split();
+
+    // All frames go through one writer task, so that operations can stream concurrently.
+    let (frame_tx, mut frame_rx) = mpsc::channel::<JsonValue>(OUTGOING_FRAME_BUFFER);
+    let writer = tokio::spawn(async move {
+        while let Some(frame) = frame_rx.recv().await {
+            let text = match serde_json::to_string(&frame) {
+                Ok(text) => text,
+                Err(error) => {
+                    tracing::warn!(error = %error, "Failed to serialize message stream socket frame");
+                    continue;
+                }
+            };
+            if let Err(error) = sender.send(Message::Text(text.into())).await {
+                tracing::warn!(error = %error, "Failed to write message stream socket frame");
+                break;
+            }
+        }
+        let _ = sender.close().await;
+    });
+
+    let mut operations = JoinSet::new();
+    loop {
+        let message = match tokio::time::timeout(idle_timeout, receiver.next()).await {
+            Ok(Some(Ok(message))) => message,
+            Ok(Some(Err(error))) => {
+                tracing::warn!(error = %error, "Message stream socket read failed");
+                break;
+            }
+            Ok(None) => break,
+            Err(_) => {
+                while operations.try_join_next().is_some() {}
+                if operations.is_empty() {
+                    tracing::debug!("Closing idle message stream socket");
+                    break;
+                }
+                continue;
+            }
+        };
+
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            Message::Binary(_) | Message::Ping(_) | Message::Pong(_) => continue,
+        };
+        let frame = match serde_json::from_str::<ClientFrame>(&text) {
+            Ok(frame) => frame,
+            Err(error) => {
+                let frame = operation_error(
+                    None,
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid message stream socket frame: {}", error),
+                );
+                if frame_tx.send(frame).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        while operations.try_join_next().is_some() {}
+        let result = match frame {
+            ClientFrame::Submit {
+                correlation_id,
+                payload,
+            } => {
+                if operations.len() >= config.max_operations_per_connection {
+                    Err((correlation_id, too_many_operations()))
+                } else {
+                    match submit(&app_state, &policy, &me_user, &headers, payload).await {
+                        Ok(events) => {
+                            operations.spawn(forward_events(
+                                correlation_id,
+                                vec![],
+                                events,
+                                frame_tx.clone(),
+                            ));
+                            Ok(())
+                        }
+                        Err(error) => Err((correlation_id, error)),
+                    }
+                }
+            }
+            ClientFrame::Resume {
+                correlation_id,
+                chat_id,
+            } => {
+                if operations.len() >= config.max_operations_per_connection {
+                    Err((correlation_id, too_many_operations()))
+                } else {
+                    match active_task_for_chat(&app_state, &policy, &me_user, &chat_id).await {
+                        Ok(task) => {
+                            let history = task.get_event_history().await;
+                            operations.spawn(forward_events(
+                                correlation_id,
+                                history,
+                                task.subscribe(),
+                                frame_tx.clone(),
+                            ));
+                            Ok(())
+                        }
+                        Err(error) => Err((correlation_id, error)),
+                    }
+                }
+            }
+            ClientFrame::Cancel {
+                correlation_id,
+                chat_id,
+            } => match active_task_for_chat(&app_state, &policy, &me_user, &chat_id).await {
+                Ok(task) => {
+                    task.request_abort();
+                    let frame = json!({
+                        "message_type": "abort_requested",
+                        "correlation_id": correlation_id,
+                    });
+                    if frame_tx.send(frame).await.is_err() {
+                        break;
+                    }
+                    Ok(())
+                }
+                Err(error) => Err((correlation_id, error)),
+            },
+        };
+
+        if let Err((correlation_id, (status_code, error))) = result
+            && frame_tx
+                .send(operation_error(Some(correlation_id), status_code, error))
+                .await
+                .is_err()
+        {
+            break;
+        }
+    }
+
+    // Generations keep running in the background and can be resumed on another connection.
+    operations.shutdown().await;
+    drop(frame_tx);
+    let _ = writer.await;
+}
+
+async fn submit(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    headers: &HeaderMap,
+    request: MessageSubmitRequest,
+) -> Result<broadcast::Receiver<StreamingEvent>, (StatusCode, String)> {
+    let generation_request_context =
+        validate_message_submit(app_state, policy, me_user, &request, headers).await?;
+    if request.dry_run {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Dry runs are not supported over WebSocket streaming".to_string(),
+        ));
+    }
+
+    let (_chat_id, events, _generation_span) = start_message_submit(
+        app_state,
+        policy,
+        me_user,
+        request,
+        generation_request_context,
+    )
+    .await?;
+    Ok(events)
+}
+
+/// Forwards the events of a generation to the client until the stream of the generation ends.
+///
+/// If the client falls behind the generation, the skipped events are reported with a `resync`
+/// frame, after which the client can `resume` to receive the full history of the generation.
+async fn forward_events(
+    correlation_id: String,
+    history: Vec<StreamingEvent>,
+    mut events: broadcast::Receiver<StreamingEvent>,
+    frame_tx: mpsc::Sender<JsonValue>,
+) {
+    for event in &history {
+        if !forward_event(&correlation_id, event, &frame_tx).await {
+            return;
+        }
+    }
+    if history
+        .iter()
+        .any(|event| matches!(event, StreamingEvent::StreamEnd))
+    {
+        return;
+    }
+
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                if !forward_event(&correlation_id, &event, &frame_tx).await
+                    || matches!(event, StreamingEvent::StreamEnd)
+                {
+                    return;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped_events)) => {
+                tracing::warn!(
+                    correlation_id = %correlation_id,
+                    "Message stream socket client lagged behind by {} events",
+                    skipped_events
+                );
+                let frame = json!({
+                    "message_type": "resync",
+                    "correlation_id": correlation_id,
+                    "skipped_events": skipped_events,
+                });
+                if frame_tx.send(frame).await.is_err() {
+                    return;
+                }
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Sends one event to the client. Returns false if the connection is gone.
+async fn forward_event(
+    correlation_id: &str,
+    event: &StreamingEvent,
+    frame_tx: &mpsc::Sender<JsonValue>,
+) -> bool {
+    let mut frame = match streaming_event_to_json(event) {
+        Ok((_, frame)) => frame,
+        Err(error) => {
+            tracing::warn!(error = %error, "Failed to serialize streaming event");
+            return true;
+        }
+    };
+    if let JsonValue::Object(map) = &mut frame {
+        map.insert(
+            "correlation_id".to_string(),
+            JsonValue::String(correlation_id.to_string()),
+        );
+    }
+    frame_tx.send(frame).await.is_ok()
+}
+
+fn too_many_operations() -> (StatusCode, String) {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        "Too many concurrent operations on this connection".to_string(),
+    )
+}
+
+fn operation_error(
+    correlation_id: Option<String>,
+    status_code: StatusCode,
+    error: String,
+) -> JsonValue {
+    json!({
+        "message_type": "operation_error",
+        "correlation_id": correlation_id,
+        "status_code": status_code.as_u16(),
+        "error": error,
+    })
+}
@@ -1,37 +1,57 @@
 #![allow(deprecated)]
+pub mod admin;
 pub mod assistant_hub;
 pub mod assistants;
 pub mod audio_transcription;
 pub mod budget;
+pub mod chat_export;
 pub mod desktop_sidecar;
 pub mod entra_id;
+pub mod events;
 mod file_resolution;
+pub mod labels;
 pub mod mcp_servers;
 pub mod me_profile_middleware;
+pub mod message_search;
 pub mod message_streaming;
 mod message_streaming_file_extraction;
+pub mod message_streaming_ws;
 pub mod ms_office;
+pub mod notifications;
+pub mod ownership_transfers;
 pub mod policy_engine_middleware;
+pub mod scheduled_messages;
 pub mod share_grants;
 pub mod share_links;
 pub mod sharepoint;
 pub mod token_usage;
 
+use crate::config::{InputModality, LINK_FILE_SOURCES};
 use crate::db::entity_ext::{chats, messages};
 use crate::models;
 use crate::models::assistant::create_standalone_file_upload;
 use crate::models::chat::{
-    RecentChatsFilter, archive_all_unarchived_chats_for_owner, archive_chat,
-    get_frequent_assistants, get_generating_chats, get_or_create_chat, get_recent_chats,
-    resolve_chat_display_name, update_chat_title_by_user_provided,
+    AssistantSnapshot, MAX_CHAT_CUSTOM_INSTRUCTION_CHARS, RecentChatsFilter,
+    archive_all_unarchived_chats_for_owner, archive_chat, get_frequent_assistants,
+    get_generating_chats, get_or_create_chat, get_recent_chats, is_assistant_config_frozen,
+    parse_assistant_snapshot, resolve_chat_display_name, update_chat_custom_instruction,
+    update_chat_response_language, update_chat_title_by_user_provided,
 };
 use crate::models::file_capability::{
-    FileCapability, FileOperation, find_file_capability_by_filename, get_file_capabilities,
+    FileCapability, FileCapabilityContext, FileOperation, evaluate_file_capabilities,
+    file_capabilities_for_chat_providers, find_file_capability_by_filename,
+    input_modality_for_filename,
+};
+use crate::models::file_upload::{
+    AudioTranscriptionMetadata, FileStorageStatus, FileUploadReference, FileUrlKind,
+    proxied_preview_url_for_file,
 };
-use crate::models::file_upload::{AudioTranscriptionMetadata, proxied_preview_url_for_file};
 use crate::models::message::{
-    ContentPart, GenerationErrorType, GenerationMetadata, GenerationParameters, MessageSchema,
+    ContentPart, GenerationErrorType, GenerationMetadata, GenerationParameters,
+    GetChatMessagesOptions, MessageCursor, MessageOrder, MessageSchema, ModerationResult,
+    PromptInjectionWarning, RenderableBlock, StopReason,
 };
+use crate::models::message_reaction::MessageReaction;
 use crate::models::permissions;
 use crate::policy::engine::PolicyEngine;
 use crate::policy::engine::authorize;
@@ -51,9 +71,15 @@ use crate::server::api::v1beta::assistant_hub::{
     withdraw_assistant_hub_version,
 };
 use crate::server::api::v1beta::assistants::{
-    ArchiveAssistantResponse, Assistant, AssistantFile, AssistantWithFiles, CreateAssistantRequest,
-    CreateAssistantResponse, UpdateAssistantRequest, UpdateAssistantResponse, archive_assistant,
-    create_assistant, get_assistant, list_assistants, update_assistant,
+    ArchiveAssistantResponse, Assistant, AssistantCategory, AssistantFile, AssistantSort,
+    AssistantVisibility, AssistantWithFiles, CreateAssistantRequest, CreateAssistantResponse,
+    UpdateAssistantRequest, UpdateAssistantResponse, archive_assistant, create_assistant,
+    file_info_to_assistant_file, get_assistant, list_assistant_categories, list_assistants,
+    update_assistant,
+};
+use crate::server::api::v1beta::labels::{
+    CreateLabelRequest, Label, ListLabelsResponse, UpdateLabelRequest, add_chat_label,
+    create_label, delete_label, list_labels, remove_chat_label, update_label,
 };
 use crate::server::api::v1beta::mcp_servers::{
     CompleteMcpServerOauthResponse, DisconnectMcpServerOauthResponse, ListMcpServersResponse,
@@ -62,30 +88,47 @@ use crate::server::api::v1beta::mcp_servers::{
 };
 use crate::server::api::v1beta::me_profile_middleware::{MeProfile, UserProfile};
 use crate::server::api::v1beta::message_streaming::{
-    __path_abort_message_stream, __path_client_tool_result, __path_edit_message_sse,
-    __path_message_submit_sse, __path_regenerate_message_sse, __path_resume_message_sse,
-    AbortStreamRequest, AbortStreamResponse, ActionFacetRequest, ClientToolResultRequest,
-    ClientToolResultResponse, EditMessageRequest, EditMessageStreamingResponseMessage,
-    MessageSubmitRequest, MessageSubmitStreamingResponseMessage, ResumeStreamRequest,
-    abort_message_stream, client_tool_result, edit_message_sse, message_submit_sse,
-    regenerate_message_sse, resume_message_sse,
+    __path_abort_message_stream, __path_client_tool_result, __path_continue_message_sse,
+    __path_edit_message_sse, __path_message_submit_sse, __path_prompt_optimizer_sse,
+    __path_regenerate_message_sse, __path_resume_message_sse, AbortStreamRequest,
+    AbortStreamResponse, ActionFacetRequest, ChatProviderUnavailableError, ClientToolResultRequest,
+    ClientToolResultResponse, ContinueMessageRequest, EditMessageRequest,
+    EditMessageStreamingResponseMessage, MessageSubmitDryRunChatOptions,
+    MessageSubmitDryRunResponse, MessageSubmitDryRunTokenEstimate, MessageSubmitRequest,
+    MessageSubmitRole, MessageSubmitStreamingResponseMessage,
+    PromptOptimizerStreamingResponseMessage, ResumeStreamRequest, UnsupportedInputFile,
+    UnsupportedInputFileReason, UnsupportedInputFilesError, abort_message_stream,
+    client_tool_result, continue_message_sse, edit_message_sse, message_submit_sse,
+    prompt_optimizer_sse, regenerate_message_sse, resume_message_sse,
 };
 use crate::server::api::v1beta::share_grants::{
-    CreateShareGrantRequest, CreateShareGrantResponse, ListShareGrantsResponse, ShareGrant,
-    create_share_grant, delete_share_grant, list_share_grants,
+    BulkCreateShareGrantsRequest, CreateShareGrantRequest, CreateShareGrantResponse,
+    ListShareGrantsResponse, ShareGrant, ShareGrantChangeResult, ShareGrantChangeStatus,
+    ShareGrantChangesResponse, SyncShareGrantsRequest, bulk_create_share_grants,
+    create_share_grant, delete_share_grant, list_share_grants, sync_share_grants,
 };
 use crate::server::api::v1beta::share_links::{
     ResolveShareLinkResponse, SetShareLinkRequest, SetShareLinkResponse, ShareLink,
     ShareLinkForResourceResponse, ShareLinkQuery, get_share_link_for_resource, resolve_share_link,
     set_share_link,
 };
+use crate::services::chat_provider_quotas::{self, ChatProviderQuotaStatus};
+use crate::services::ephemeral_chats::start_ephemeral_chat_purge;
+use crate::services::feature_flags::FeatureFlag;
+use crate::services::file_processing_cached::{cached_file_processing_error, purge_file_cached};
+use crate::services::file_processor::FileProcessingErrorKind;
 use crate::services::file_storage::{
     ContentDispositionKind, SHAREPOINT_PROVIDER_ID, build_content_disposition,
 };
 use crate::services::genai::build_chat_options_for_completion;
+use crate::services::language_detection::parse_language_code;
+use crate::services::model_selection::{ModelRecommendation, ModelRequirements};
+use crate::services::moderation::{ModerationOutcome, moderate_text};
+use crate::services::prompt_optimizer::build_conversation_context;
 use crate::services::sentry::log_internal_server_error;
 use crate::services::template_rendering::consumers::error_report::ErrorReportRenderer;
 use crate::services::template_rendering::contexts::error_report::ErrorReportContext;
+use crate::services::user_events::UserEvent;
 use crate::state::{AppState, ChatProviderConfigWithId};
 use axum::extract::{DefaultBodyLimit, Path, State};
 use axum::http::header::{CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE};
@@ -95,9 +138,9 @@ use axum::routing::{get, post, put};
 use axum::{Extension, Json, Router, middleware};
 use axum_extra::extract::Multipart;
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64_STANDARD};
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, FixedOffset, Utc};
 use eyre::{Report, WrapErr, eyre};
-use genai::chat::{ChatMessage as GenAiChatMessage, ChatRequest};
+use genai::chat::{ChatMessage as GenAiChatMessage, ChatOptions, ChatRequest};
 use sea_orm::EntityTrait;
 use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::types::{Uuid, chrono};
@@ -133,18 +176,66 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
         .route("/starter-prompts", get(starter_prompts))
         .route("/messages/submitstream", post(message_submit_sse))
         .route("/messages/regeneratestream", post(regenerate_message_sse))
+        .route("/messages/continuestream", post(continue_message_sse))
         .route("/messages/editstream", post(edit_message_sse))
         .route("/messages/abortstream", post(abort_message_stream))
         .route("/messages/resumestream", post(resume_message_sse))
         .route("/messages/clienttoolresult", post(client_tool_result))
+        .route("/ws", get(message_streaming_ws::message_stream_socket))
+        .route(
+            "/messages/schedule",
+            post(scheduled_messages::schedule_message),
+        )
+        .route(
+            "/scheduled-messages",
+            get(scheduled_messages::list_scheduled_messages),
+        )
+        .route(
+            "/scheduled-messages/{scheduled_message_id}",
+            axum::routing::delete(scheduled_messages::cancel_scheduled_message),
+        )
+        .route("/notifications", get(notifications::list_notifications))
+        .route(
+            "/notifications/read-all",
+            post(notifications::mark_all_notifications_read),
+        )
+        .route(
+            "/notifications/{notification_id}/read",
+            post(notifications::mark_notification_read),
+        )
         .route("/recent_chats", get(recent_chats))
+        .route(
+            "/chats/by-assistant/{assistant_id}",
+            get(recent_chats_by_assistant),
+        )
         .route("/generating", get(generating_chats))
+        .route("/events", get(events::user_events_sse))
         .route("/frequent_assistants", get(frequent_assistants))
         .route("/chats", post(create_chat))
-        .route("/chats/{chat_id}", put(update_chat))
+        .route("/chats/{chat_id}", get(chat_detail).put(update_chat))
+        .route("/chats/{chat_id}/read-state", put(update_chat_read_state))
+        .route(
+            "/chats/{chat_id}/custom-instruction",
+            put(update_chat_custom_instruction_endpoint),
+        )
+        .route(
+            "/chats/{chat_id}/response-language",
+            put(update_chat_response_language_endpoint),
+        )
+        .route(
+            "/chats/{chat_id}/refresh-assistant-snapshot",
+            post(refresh_chat_assistant_snapshot),
+        )
+        .route(
+            "/chats/{chat_id}/messages/{message_id}/token-breakdown",
+            get(token_usage::message_token_breakdown),
+        )
         .route("/chats/archive_all", post(archive_all_chats_endpoint))
+        .route("/labels", get(list_labels).post(create_label))
+        .route("/labels/{label_id}", put(update_label).delete(delete_label))
         .route("/files", post(upload_file))
         .route("/files/link", post(link_file))
+        .route("/files/{file_id}", axum::routing::delete(delete_file))
         .route(
             "/files/audio-transcriptions/socket",
             get(audio_transcription::audio_transcription_socket),
@@ -154,6 +245,7 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
             get(audio_transcription::audio_dictation_socket),
         )
         .route("/models", get(available_models))
+        .route("/models/recommend", post(recommend_models))
         .route("/mcp_servers", get(list_mcp_servers))
         .route(
             "/mcp_servers/{server_id}/oauth/start",
@@ -198,11 +290,33 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
     // Should at a later time use a more generic middleware that can use a non-me profile as a Subject
     let authenticated_routes = Router::new()
         .route("/chats/{chat_id}/messages", get(chat_messages))
+        .route(
+            "/chats/{chat_id}/messages/search",
+            get(message_search::search_chat_messages_endpoint),
+        )
+        .route("/chats/{chat_id}/export", get(chat_export::export_chat))
         .route("/chats/{chat_id}/archive", post(archive_chat_endpoint))
+        .route(
+            "/chats/{chat_id}/transfer-ownership",
+            post(ownership_transfers::transfer_chat_ownership),
+        )
+        .route(
+            "/chats/{chat_id}/labels/{label_id}",
+            post(add_chat_label).delete(remove_chat_label),
+        )
         .route(
             "/messages/{message_id}/feedback",
             put(submit_message_feedback).delete(delete_message_feedback),
         )
+        .route(
+            "/messages/{message_id}/reactions/{emoji}",
+            put(add_message_reaction).delete(remove_message_reaction),
+        )
+        .route(
+            "/messages/{message_id}/content/{index}",
+            get(message_content_part),
+        )
+        .route("/messages/{message_id}/history", get(message_edit_history))
         .route("/files/{file_id}", get(get_file))
         .route("/files/{file_id}/preview", get(get_file_preview))
         .route(
@@ -216,15 +330,21 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
                 .layer(DefaultBodyLimit::max(max_upload_size * 10)),
         )
         .route("/prompt-optimizer", post(prompt_optimizer))
+        .route("/prompt-optimizer/stream", post(prompt_optimizer_sse))
         // Assistants routes - manually registered for clarity and consistency
         .route("/assistants", post(create_assistant))
         .route("/assistants", get(list_assistants))
+        .route("/assistants/categories", get(list_assistant_categories))
         .route("/assistants/{assistant_id}", get(get_assistant))
         .route("/assistants/{assistant_id}", put(update_assistant))
         .route(
             "/assistants/{assistant_id}/archive",
             post(archive_assistant),
         )
+        .route(
+            "/assistants/{assistant_id}/transfer-ownership",
+            post(ownership_transfers::transfer_assistant_ownership),
+        )
         .route("/assistant-hub/config", get(assistant_hub_config))
         .route(
             "/assistant-hub/assistants",
@@ -281,6 +401,8 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
         // Share grants routes
         .route("/share-grants", post(create_share_grant))
         .route("/share-grants", get(list_share_grants))
+        .route("/share-grants/bulk", post(bulk_create_share_grants))
+        .route("/share-grants/sync", put(sync_share_grants))
         .route(
             "/share-grants/{grant_id}",
             axum::routing::delete(delete_share_grant),
@@ -343,6 +465,104 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
     app.into()
 }
 
+/// Routes of the admin API.
+///
+/// These are mounted next to the routes of [`router`] on the public listener, or on the internal
+/// listener if one is configured (see `internal_http_port`).
+pub fn admin_router(app_state: AppState) -> OpenApiRouter<AppState> {
+    let admin_routes = Router::new()
+        .route("/admin/feature-flags", get(admin::list_feature_flags))
+        .route(
+            "/admin/feature-flags/{flag_name}",
+            post(admin::set_feature_flag)
+                .put(admin::put_feature_flag)
+                .delete(admin::delete_feature_flag_override),
+        )
+        .route("/admin/seed", post(admin::seed))
+        .route(
+            "/admin/budget/assistants",
+            get(admin::assistant_budget_report),
+        )
+        .route(
+            "/admin/consistency/message-links",
+            get(admin::message_link_consistency),
+        )
+        .route(
+            "/admin/chats/{chat_id}/thread-integrity",
+            get(admin::thread_integrity),
+        )
+        .route(
+            "/admin/chats/{chat_id}/thread-repair",
+            post(admin::repair_thread),
+        )
+        .route("/admin/files/missing", get(admin::missing_files_report))
+        .route(
+            "/admin/messages/{message_id}/redact",
+            post(admin::redact_message),
+        )
+        .route(
+            "/admin/captures/{trace_id}",
+            get(admin::get_provider_capture),
+        )
+        .route(
+            "/admin/messages/{message_id}/prompt-manifest",
+            get(admin::get_message_prompt_manifest),
+        )
+        .route(
+            "/admin/messages/{message_id}/generation-detail",
+            get(admin::get_message_generation_detail),
+        )
+        .route(
+            "/admin/maintenance/file-pointer-migration",
+            get(admin::file_pointer_migration_status).post(admin::start_file_pointer_migration),
+        )
+        .route(
+            "/admin/maintenance/content-spillover-migration",
+            get(admin::content_spillover_migration_status)
+                .post(admin::start_content_spillover_migration),
+        )
+        .route(
+            "/admin/maintenance/generation-input-archival",
+            get(admin::generation_input_archival_status)
+                .post(admin::start_generation_input_archival),
+        )
+        .route(
+            "/admin/chat-providers/status",
+            get(admin::chat_providers_status),
+        )
+        .route("/admin/langfuse/status", get(admin::langfuse_status))
+        .route(
+            "/admin/file-storage/{provider_id}/self-test",
+            post(admin::file_storage_self_test),
+        )
+        .route(
+            "/admin/assistants/{assistant_id}/featured",
+            put(admin::set_assistant_featured),
+        )
+        .route(
+            "/admin/chats/{chat_id}/transfer-ownership",
+            post(admin::transfer_chat_ownership),
+        )
+        .route(
+            "/admin/assistants/{assistant_id}/transfer-ownership",
+            post(admin::transfer_assistant_ownership),
+        )
+        .route(
+            "/admin/users/{user_id}/transfer-chats",
+            post(admin::transfer_user_chats),
+        )
+        .route("/admin/telemetry/preview", get(admin::telemetry_preview))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            policy_engine_middleware::policy_engine_middleware,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            app_state,
+            me_profile_middleware::user_profile_middleware,
+        ));
+    admin_routes.into()
+}
+
 #[derive(OpenApi)]
 #[openapi(
     paths(
@@ -353,28 +573,59 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
         facets,
         starter_prompts,
         chat_messages,
+        message_search::search_chat_messages_endpoint,
+        chat_export::export_chat,
         submit_message_feedback,
         delete_message_feedback,
+        add_message_reaction,
+        remove_message_reaction,
+        message_content_part,
+        message_edit_history,
         recent_chats,
+        recent_chats_by_assistant,
         generating_chats,
         frequent_assistants,
         upload_file,
         link_file,
         get_file,
         get_file_preview,
+        delete_file,
         message_submit_sse,
         regenerate_message_sse,
+        continue_message_sse,
         edit_message_sse,
         abort_message_stream,
         resume_message_sse,
         client_tool_result,
         create_chat,
+        chat_detail,
         update_chat,
+        update_chat_read_state,
+        update_chat_custom_instruction_endpoint,
+        update_chat_response_language_endpoint,
+        refresh_chat_assistant_snapshot,
         archive_all_chats_endpoint,
         archive_chat_endpoint,
+        ownership_transfers::transfer_chat_ownership,
+        labels::list_labels,
+        labels::create_label,
+        labels::update_label,
+        labels::delete_label,
+        labels::add_chat_label,
+        labels::remove_chat_label,
+        scheduled_messages::schedule_message,
+        scheduled_messages::list_scheduled_messages,
+        scheduled_messages::cancel_scheduled_message,
+        notifications::list_notifications,
+        notifications::mark_notification_read,
+        notifications::mark_all_notifications_read,
         token_usage::token_usage_estimate,
+        token_usage::message_token_breakdown,
+        events::user_events_sse,
         prompt_optimizer,
+        prompt_optimizer_sse,
         available_models,
+        recommend_models,
         mcp_servers::list_mcp_servers,
         mcp_servers::start_mcp_server_oauth,
         mcp_servers::complete_mcp_server_oauth,
@@ -386,9 +637,11 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
         desktop_sidecar::download_distribution_artifact,
         assistants::create_assistant,
         assistants::list_assistants,
+        assistants::list_assistant_categories,
         assistants::get_assistant,
         assistants::update_assistant,
         assistants::archive_assistant,
+        ownership_transfers::transfer_assistant_ownership,
         assistant_hub::assistant_hub_config,
         assistant_hub::list_assistant_hub_assistants,
         assistant_hub::get_assistant_hub_assistant,
@@ -405,6 +658,8 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
         assistant_hub::set_assistant_hub_version_featured,
         share_grants::create_share_grant,
         share_grants::list_share_grants,
+        share_grants::bulk_create_share_grants,
+        share_grants::sync_share_grants,
         share_grants::delete_share_grant,
         share_links::get_share_link_for_resource,
         share_links::set_share_link,
@@ -415,7 +670,35 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
         sharepoint::get_drive_item,
         sharepoint::get_drive_item_children,
         entra_id::list_organization_users,
-        entra_id::list_organization_groups
+        entra_id::list_organization_groups,
+        admin::list_feature_flags,
+        admin::set_feature_flag,
+        admin::put_feature_flag,
+        admin::delete_feature_flag_override,
+        admin::seed,
+        admin::assistant_budget_report,
+        admin::message_link_consistency,
+        admin::thread_integrity,
+        admin::repair_thread,
+        admin::missing_files_report,
+        admin::redact_message,
+        admin::get_provider_capture,
+        admin::get_message_prompt_manifest,
+        admin::get_message_generation_detail,
+        admin::file_pointer_migration_status,
+        admin::start_file_pointer_migration,
+        admin::content_spillover_migration_status,
+        admin::start_content_spillover_migration,
+        admin::generation_input_archival_status,
+        admin::start_generation_input_archival,
+        admin::chat_providers_status,
+        admin::langfuse_status,
+        admin::file_storage_self_test,
+        admin::set_assistant_featured,
+        admin::transfer_chat_ownership,
+        admin::transfer_assistant_ownership,
+        admin::transfer_user_chats,
+        admin::telemetry_preview
     ),
     components(schemas(
         Message,
@@ -428,24 +711,64 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
         StarterPromptInfo,
         StarterPromptsResponse,
         ChatMessage,
+        ChatMessageUsage,
         ChatMessageStats,
         ChatMessagesResponse,
+        MessageOrder,
         RecentChatStats,
         RecentChatsResponse,
+        ChatDetail,
+        AssistantSnapshot,
+        RefreshAssistantSnapshotResponse,
         GenerationChatState,
         GeneratingChat,
         GeneratingChatsResponse,
         FileUploadItem,
+        FileUrlKind,
+        crate::models::file_upload::FileStorageStatus,
+        FileProcessingErrorKind,
+        crate::models::file_upload::FileUploadReference,
+        crate::models::file_upload::FileUploadReferenceKind,
+        FileStillReferencedError,
         FileUploadResponse,
+        FileUploadWarning,
+        FileUploadError,
         LinkFileRequest,
+        FileSourceDisabledError,
+        FileSourceDisabledReason,
+        UnsupportedFileError,
         SharepointProviderMetadata,
         MessageSubmitStreamingResponseMessage,
         UserProfile,
         UpdateProfilePreferencesRequest,
         MessageSubmitRequest,
+        MessageSubmitRole,
+        MessageSubmitDryRunResponse,
+        MessageSubmitDryRunChatOptions,
+        MessageSubmitDryRunTokenEstimate,
+        crate::models::message::OfferedTools,
+        crate::models::message::OfferedTool,
+        crate::models::message::GenerationChatOptions,
+        crate::services::prompt_composition::manifest::PromptManifest,
+        crate::services::prompt_composition::manifest::PromptManifestComponent,
+        crate::services::prompt_composition::manifest::PromptComponentKind,
+        UnsupportedInputFilesError,
+        ChatProviderUnavailableError,
+        UnsupportedInputFile,
+        UnsupportedInputFileReason,
+        InputModality,
+        crate::services::chat_provider_quotas::QuotaExceededError,
+        ChatProviderQuotaStatus,
+        crate::services::chat_provider_quotas::QuotaLimitStatus,
+        crate::services::chat_provider_quotas::QuotaWindow,
+        crate::services::chat_provider_quotas::QuotaMetric,
+        crate::services::background_tasks::GenerationInProgressError,
+        crate::config::ModelReasoningEffort,
+        crate::config::ModelVerbosity,
         ActionFacetRequest,
         EditMessageRequest,
         EditMessageStreamingResponseMessage,
+        ContinueMessageRequest,
         AbortStreamRequest,
         AbortStreamResponse,
         ResumeStreamRequest,
@@ -455,10 +778,36 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
         CreateChatResponse,
         UpdateChatRequest,
         UpdateChatResponse,
+        UpdateChatReadStateRequest,
+        ChatReadState,
+        UpdateChatCustomInstructionRequest,
+        ChatCustomInstruction,
+        UpdateChatResponseLanguageRequest,
+        ChatResponseLanguage,
         ArchiveChatRequest,
         ArchiveChatResponse,
         ArchiveAllChatsResponse,
+        Label,
+        CreateLabelRequest,
+        UpdateLabelRequest,
+        ListLabelsResponse,
+        scheduled_messages::ScheduledMessage,
+        scheduled_messages::ScheduleMessageRequest,
+        scheduled_messages::ListScheduledMessagesResponse,
+        crate::models::scheduled_message::ScheduledMessageStatus,
+        notifications::Notification,
+        notifications::NotificationStats,
+        notifications::ListNotificationsResponse,
+        notifications::MarkAllNotificationsReadResponse,
+        crate::models::notification::NotificationKind,
+        crate::models::notification::NotificationPayload,
+        crate::models::notification::ShareGrantCreatedPayload,
+        crate::models::notification::ScheduledMessageSucceededPayload,
+        crate::models::notification::ScheduledMessageFailedPayload,
+        crate::models::notification::GenerationFailedPayload,
         ChatModel,
+        ModelRecommendationRequest,
+        ModelRecommendation,
         McpServerStatusValue,
         McpServerStatus,
         ListMcpServersResponse,
@@ -468,10 +817,17 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
         FileCapability,
         FileOperation,
         FileCapabilitiesQuery,
+        DeleteFileQuery,
         FeedbackSentiment,
         MessageFeedbackRequest,
         MessageFeedback,
+        MessageReaction,
+        MessageReactionsResponse,
+        MessageVersion,
         Assistant,
+        AssistantVisibility,
+        AssistantSort,
+        AssistantCategory,
         AssistantWithFiles,
         AssistantFile,
         CreateAssistantRequest,
@@ -499,6 +855,13 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
         CreateShareGrantRequest,
         CreateShareGrantResponse,
         ListShareGrantsResponse,
+        BulkCreateShareGrantsRequest,
+        SyncShareGrantsRequest,
+        ShareGrantChangeStatus,
+        ShareGrantChangeResult,
+        ShareGrantChangesResponse,
+        crate::models::share_grant::ShareGrantPermission,
+        crate::models::share_grant::ShareGrantee,
         ShareLink,
         ShareLinkForResourceResponse,
         ShareLinkQuery,
@@ -509,9 +872,67 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
         token_usage::TokenUsageStats,
         token_usage::TokenUsageResponseFileItem,
         token_usage::TokenUsageResponse,
+        token_usage::MessageTokenBreakdown,
+        token_usage::MessageTokenBreakdownResponse,
+        UserEvent,
         PromptOptimizerRequest,
         PromptOptimizerResponse,
+        PromptOptimizerStreamingResponseMessage,
         budget::BudgetStatusResponse,
+        budget::BudgetScopeStatus,
+        budget::BudgetBreakdown,
+        chat_export::ChatExportFormat,
+        message_search::ChatMessageSearchMatch,
+        message_search::ChatMessageSearchResponse,
+        crate::models::message_search::SearchMatchOffset,
+        budget::AssistantUsage,
+        admin::FeatureFlagStatus,
+        admin::FeatureFlagsResponse,
+        admin::SetFeatureFlagRequest,
+        admin::PutFeatureFlagRequest,
+        admin::SeedRequest,
+        admin::AssistantBudgetReport,
+        admin::CrossChatMessageLink,
+        admin::MessageLinkConsistencyReport,
+        admin::ThreadRepairRequest,
+        admin::ThreadRepairResponse,
+        admin::MissingFileUpload,
+        admin::MissingFilesReport,
+        admin::RedactMessageRequest,
+        admin::RedactMessageResponse,
+        admin::ProviderCaptureResponse,
+        admin::MessageGenerationDetail,
+        admin::FilePointerMigrationStatus,
+        admin::ContentSpilloverMigrationStatus,
+        admin::GenerationInputArchivalStatus,
+        admin::ChatProviderStatus,
+        admin::ChatProviderGroupStatus,
+        admin::ChatProvidersStatusResponse,
+        admin::SetAssistantFeaturedRequest,
+        admin::AssistantFeaturedStatus,
+        admin::TransferUserChatsResponse,
+        ownership_transfers::TransferOwnershipRequest,
+        crate::models::chat::ChatOwnershipTransfer,
+        crate::models::ownership_transfer::AssistantOwnershipTransfer,
+        crate::services::langfuse::LangfuseStatus,
+        crate::services::file_storage_self_test::FileStorageSelfTestResult,
+        crate::services::file_storage_self_test::FileStorageSelfTestStatus,
+        crate::services::file_storage_self_test::FileStorageSelfTestStep,
+        crate::services::file_storage_self_test::FileStorageSelfTestStepKind,
+        crate::services::usage_reporting::UsageReport,
+        crate::services::usage_reporting::SoftwareVersion,
+        crate::services::usage_reporting::UsageFeature,
+        crate::services::usage_reporting::CountBucket,
+        crate::services::usage_reporting::RateBucket,
+        crate::services::usage_reporting::DatabaseSizeBucket,
+        crate::models::message_redaction::RedactionSpan,
+        crate::models::message_thread_integrity::ThreadIntegrityReport,
+        crate::models::message_thread_integrity::ThreadIntegrityIssue,
+        crate::models::message_thread_integrity::ThreadIntegrityIssueKind,
+        crate::models::message_thread_integrity::ThreadRepairChange,
+        crate::models::message_thread_integrity::ThreadRepairAction,
+        crate::services::seed::SeedSummary,
+        crate::services::feature_flags::FeatureFlagSource,
         crate::config::DesktopSidecarOrganizationConfiguration,
         desktop_sidecar::DesktopSidecarDistributionResponse,
         desktop_sidecar::DesktopSidecarDistributionTargetResponse,
@@ -526,7 +947,8 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
         entra_id::OrganizationUser,
         entra_id::OrganizationUsersResponse,
         entra_id::OrganizationGroup,
-        entra_id::OrganizationGroupsResponse
+        entra_id::OrganizationGroupsResponse,
+        entra_id::EntraIdErrorResponse
     ))
 )]
 pub struct ApiV1ApiDoc;
@@ -547,6 +969,45 @@ pub struct ChatModel {
     model_description: Option<String>,
     /// Optional icon identifier of the model shown to users
     model_icon: Option<String>,
+    /// Maximum number of files that may be attached to a message, if limited
+    #[schema(nullable = false)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_input_files: Option<usize>,
+    /// Kinds of attachments the model accepts
+    supported_input_modalities: Vec<InputModality>,
+    /// Whether the model is available to the user. Only `false` for the last model of a recent
+    /// chat whose chat provider was removed or is not available to the user anymore, in which case
+    /// the display name is the chat provider ID.
+    available: bool,
+    /// Usage of the user against the quota of the model, if it has one and the user isn't
+    /// exempt from it
+    #[schema(nullable = false)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quota: Option<ChatProviderQuotaStatus>,
+    /// Whether the model is the one recommended to the user when nothing is known about the
+    /// chat. Always `false` for the last model of a recent chat.
+    recommended: bool,
+}
+
+/// What is known about the chat a model is recommended for. All fields are optional.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct ModelRecommendationRequest {
+    /// The chat the next message is sent in. Models that can process the attachments of the chat
+    /// and the tools of its assistant are recommended, and the model that generated its last
+    /// answer is preferred.
+    #[serde(default)]
+    #[schema(nullable = false)]
+    pub chat_id: Option<Uuid>,
+    /// The files attached to the next message
+    #[serde(default)]
+    #[schema(nullable = false)]
+    pub input_files_ids: Option<Vec<Uuid>>,
+    /// Whether the chat uses tools
+    #[serde(default)]
+    pub needs_tools: bool,
+    /// Whether the chat contains images
+    #[serde(default)]
+    pub needs_vision: bool,
 }
 
 pub async fn fallback() -> impl IntoResponse {
@@ -577,6 +1038,17 @@ pub async fn profile(
 ) -> Result<Json<UserProfile>, StatusCode> {
     let mut profile = me_user.profile.clone();
     enrich_profile_with_entra_id_photo(&app_state, &me_user, &mut profile).await;
+    let user_id = Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    profile.unread_notifications = Some(
+        models::notification::count_unread_notifications(&app_state.db, user_id)
+            .await
+            .map_err(log_internal_server_error)?,
+    );
+    profile.feature_flags = Some(app_state.feature_flags.evaluate_all(
+        &app_state.config,
+        &me_user.id,
+        &me_user.groups,
+    ));
     Ok(Json(profile))
 }
 
@@ -741,6 +1213,11 @@ pub struct UpdateProfilePreferencesRequest {
     #[serde(default, deserialize_with = "deserialize_patch_optional_string")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub preference_assistant_additional_information: Option<Option<String>>,
+    /// Facet IDs to enable by default for new chats, overriding the configured defaults.
+    /// `null` resets to the configured defaults.
+    #[serde(default, deserialize_with = "deserialize_patch_optional_string_list")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preference_default_selected_facets: Option<Option<Vec<String>>>,
 }
 
 fn deserialize_patch_optional_string<'de, D>(
@@ -752,12 +1229,22 @@ where
     Ok(Some(Option::<String>::deserialize(deserializer)?))
 }
 
+fn deserialize_patch_optional_string_list<'de, D>(
+    deserializer: D,
+) -> Result<Option<Option<Vec<String>>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Some(Option::<Vec<String>>::deserialize(deserializer)?))
+}
+
 #[utoipa::path(
     put,
     path = "/me/profile/preferences",
     request_body = UpdateProfilePreferencesRequest,
     responses(
         (status = OK, body = UserProfile),
+        (status = BAD_REQUEST, description = "When a default selected facet ID is unknown"),
         (status = UNAUTHORIZED, description = "When no valid JWT token is provided")
     ),
     security(
@@ -771,6 +1258,19 @@ pub async fn update_profile_preferences(
 ) -> Result<Json<UserProfile>, StatusCode> {
     let user_id = Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    if let Some(Some(facet_ids)) = &request.preference_default_selected_facets
+        && facet_ids.iter().any(|facet_id| {
+            !app_state
+                .config
+                .experimental_facets
+                .facets
+                .get(facet_id)
+                .is_some_and(|facet| !facet.hidden)
+        })
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
     let updated_prefs = models::user_preference::upsert_user_preferences(
         &app_state.db,
         &user_id,
@@ -779,6 +1279,7 @@ pub async fn update_profile_preferences(
             job_title: request.preference_job_title,
             assistant_custom_instructions: request.preference_assistant_custom_instructions,
             assistant_additional_information: request.preference_assistant_additional_information,
+            default_selected_facets: request.preference_default_selected_facets,
         },
     )
     .await
@@ -790,6 +1291,7 @@ pub async fn update_profile_preferences(
     profile.preference_assistant_custom_instructions = updated_prefs.assistant_custom_instructions;
     profile.preference_assistant_additional_information =
         updated_prefs.assistant_additional_information;
+    profile.preference_default_selected_facets = updated_prefs.default_selected_facets;
 
     Ok(Json(profile))
 }
@@ -801,7 +1303,16 @@ pub struct FacetInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
     icon: Option<String>,
+    /// Whether the facet is enabled by default for new chats.
+    ///
+    /// Pinned facets of the requested assistant are always enabled. Otherwise the user's
+    /// `preference_default_selected_facets` take precedence over the configured
+    /// `default_selected_facets`.
     default_enabled: bool,
+    /// Whether the facet is pinned by the assistant given via `assistant_id`, and therefore
+    /// always enabled for its chats.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pinned: bool,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -873,9 +1384,14 @@ pub struct StarterPromptsResponse {
 #[utoipa::path(
     get,
     path = "/me/facets",
+    params(
+        ("assistant_id" = Option<String>, Query, description = "Optional assistant ID. Facets pinned by this assistant are marked as `pinned` and enabled by default."),
+    ),
     responses(
         (status = OK, body = FacetsResponse),
-        (status = UNAUTHORIZED, description = "When no valid JWT token is provided")
+        (status = BAD_REQUEST, description = "When the assistant ID is not a valid UUID"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = NOT_FOUND, description = "When the assistant does not exist or is not accessible")
     ),
     security(
         ("bearer_auth" = [])
@@ -885,9 +1401,40 @@ pub async fn facets(
     State(app_state): State<AppState>,
     Extension(me_user): Extension<MeProfile>,
     Extension(policy): Extension<PolicyEngine>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
 ) -> Result<Json<FacetsResponse>, StatusCode> {
     let config = &app_state.config.experimental_facets;
-    let authorized_facet_ids: HashSet<String> = policy
+    let pinned_facet_ids: HashSet<String> = match params.get("assistant_id") {
+        Some(assistant_id) => {
+            let assistant_id =
+                Uuid::parse_str(assistant_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+            models::assistant::get_assistant_by_id_allow_archived(
+                &app_state.db,
+                &me_user.to_subject(),
+                assistant_id,
+            )
+            .await
+            .map_err(|e| {
+                if e.to_string().contains("not found") || e.to_string().contains("Access denied") {
+                    StatusCode::NOT_FOUND
+                } else {
+                    log_internal_server_error(e)
+                }
+            })?
+            .pinned_facet_ids
+            .unwrap_or_default()
+            .into_iter()
+            .collect()
+        }
+        None => HashSet::new(),
+    };
+    // Pinned facets > the user's preferred defaults > the configured defaults
+    let default_selected_facets = me_user
+        .profile
+        .preference_default_selected_facets
+        .as_ref()
+        .unwrap_or(&config.default_selected_facets);
+    let mut authorized_facet_ids: HashSet<String> = policy
         .filter_authorized_facet_ids(
             &me_user.to_subject(),
             &me_user.groups,
@@ -897,6 +1444,13 @@ pub async fn facets(
         .map_err(log_internal_server_error)?
         .into_iter()
         .collect();
+    // While the facets flag is off for the user, no facets are offered
+    if !app_state
+        .feature(FeatureFlag::Facets.name(), &me_user.id, &me_user.groups)
+        .enabled
+    {
+        authorized_facet_ids.clear();
+    }
     let mut facets = Vec::new();
     let mut seen = HashSet::new();
 
@@ -908,12 +1462,14 @@ pub async fn facets(
             if facet.hidden {
                 continue;
             }
-            let default_enabled = config.default_selected_facets.contains(facet_id);
+            let pinned = pinned_facet_ids.contains(facet_id);
+            let default_enabled = pinned || default_selected_facets.contains(facet_id);
             facets.push(FacetInfo {
                 id: facet_id.clone(),
                 display_name: facet.display_name.clone(),
                 icon: facet.icon.clone(),
                 default_enabled,
+                pinned,
             });
             seen.insert(facet_id.clone());
         }
@@ -930,12 +1486,14 @@ pub async fn facets(
     remaining.sort();
     for facet_id in remaining {
         if let Some(facet) = config.facets.get(&facet_id) {
-            let default_enabled = config.default_selected_facets.contains(&facet_id);
+            let pinned = pinned_facet_ids.contains(&facet_id);
+            let default_enabled = pinned || default_selected_facets.contains(&facet_id);
             facets.push(FacetInfo {
                 id: facet_id,
                 display_name: facet.display_name.clone(),
                 icon: facet.icon.clone(),
                 default_enabled,
+                pinned,
             });
         }
     }
@@ -989,7 +1547,10 @@ pub async fn starter_prompts(
     Extension(policy): Extension<PolicyEngine>,
 ) -> Result<Json<StarterPromptsResponse>, StatusCode> {
     let config = &app_state.config.starter_prompts;
-    if !config.enabled {
+    if !app_state
+        .feature(FeatureFlag::StarterPrompts.name(), &me_user.id, &me_user.groups)
+        .enabled
+    {
         return Err(StatusCode::NOT_FOUND);
     }
 
@@ -1211,11 +1772,29 @@ pub struct RecentChat {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
     assistant_name: Option<String>,
+    /// Instruction of the user that is added to the prompt of every generation of the chat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    custom_instruction: Option<String>,
+    /// Language the assistant responds in, unless the message request or the detected language
+    /// of the latest user message asks for another one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    response_language: Option<String>,
     /// Start time of the chat's generation, present only while it is running
     /// with a fresh heartbeat
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
     active_generation_started_at: Option<DateTime<FixedOffset>>,
+    /// Labels assigned to this chat, ordered by name
+    labels: Vec<Label>,
+    /// The last message the current user has marked as read in this chat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    last_read_message_id: Option<String>,
+    /// Number of assistant messages in the active thread that are newer than the last read
+    /// message of the current user. Counts all assistant messages if the chat was never marked as read.
+    unread_count: i64,
 }
 
 /// Sentiment for message feedback
@@ -1282,6 +1861,11 @@ pub struct ChatMessage {
     role: String,
     /// The text content of the message
     content: Vec<ContentPart>,
+    /// Text to display in place of the text of a user message that was submitted without text, but
+    /// with attached files. It was not passed to the model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    placeholder_text: Option<String>,
     /// Optional error information if generation failed
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
@@ -1294,6 +1878,33 @@ pub struct ChatMessage {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
     mcp_servers_unavailable: Option<Vec<String>>,
+    /// Mermaid diagrams and math blocks in the text content. Blocks that failed validation
+    /// should be rendered as plain code.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    renderable_blocks: Option<Vec<RenderableBlock>>,
+    /// Possible prompt injections found in the file contents and tool outputs that were passed to
+    /// the model during the generation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    prompt_injection_warnings: Option<Vec<PromptInjectionWarning>>,
+    /// Why the generation of the message ended
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    stop_reason: Option<StopReason>,
+    /// Whether the generation was cut off and can be continued via `/me/messages/continuestream`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    continuable: Option<bool>,
+    /// Token usage and timings of the generation of the message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    usage: Option<ChatMessageUsage>,
+    /// Hash of the manifest of the prompts the message was generated with. Differs between
+    /// messages that were generated with different prompts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    prompt_manifest_hash: Option<String>,
     /// When the message was created
     created_at: DateTime<FixedOffset>,
     /// When the message was last updated
@@ -1306,6 +1917,15 @@ pub struct ChatMessage {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
     sibling_message_id: Option<String>,
+    /// Whether this message is an edited version of another message. Regenerated messages have a
+    /// sibling as well, but are not edited.
+    #[serde(default)]
+    edited: bool,
+    /// The ID of the message this message is an edited version of, if any. The previous versions
+    /// are available via `/messages/{message_id}/history`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    edit_of_message_id: Option<String>,
     /// Whether this message is in the active thread
     is_message_in_active_thread: bool,
     /// The IDs of the files that were used to generate this message
@@ -1316,6 +1936,9 @@ pub struct ChatMessage {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
     feedback: Option<MessageFeedback>,
+    /// Emoji reactions of the users that can read the chat, aggregated per emoji
+    #[serde(default)]
+    reactions: Vec<MessageReaction>,
     /// The action facet ID supplied with this user message, if any
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
@@ -1324,6 +1947,94 @@ pub struct ChatMessage {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
     action_facet_args: Option<HashMap<String, String>>,
+    /// Result of the content moderation of this user message, if it was moderated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    moderation: Option<ModerationResult>,
+}
+
+/// Token usage and timings of the generation of an assistant message
+#[derive(Debug, Clone, ToSchema, Serialize, Deserialize)]
+pub struct ChatMessageUsage {
+    /// Number of prompt tokens, summed over all model turns
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    prompt_tokens: Option<u32>,
+    /// Number of completion tokens, summed over all model turns
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    completion_tokens: Option<u32>,
+    /// Total number of tokens, summed over all model turns
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    total_tokens: Option<u32>,
+    /// Number of reasoning tokens, summed over all model turns
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    reasoning_tokens: Option<u32>,
+    /// Number of prompt tokens that were read from the prompt cache of the provider, summed over
+    /// all model turns. They are included in `prompt_tokens`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    cache_read_tokens: Option<u32>,
+    /// Number of prompt tokens that were written to the prompt cache of the provider, summed over
+    /// all model turns. They are included in `prompt_tokens`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    cache_write_tokens: Option<u32>,
+    /// Milliseconds from dispatching the request of the final model turn until its first streamed
+    /// text chunk. Earlier turns that ended in tool calls and the execution of those tool calls
+    /// are excluded, so this reflects the responsiveness of the model for the answer itself.
+    /// Not present if no text was streamed, or for messages generated before timings were
+    /// recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    time_to_first_token_ms: Option<u64>,
+    /// Milliseconds from the start of the generation until its completion, including all tool-call
+    /// turns
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    generation_duration_ms: Option<u64>,
+    /// Whether any tools were offered to the model. Not present for messages generated before the
+    /// offered tools were recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    tools_offered: Option<bool>,
+    /// Number of tools that were offered to the model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    offered_tool_count: Option<usize>,
+}
+
+impl ChatMessageUsage {
+    fn from_generation_metadata(metadata: &GenerationMetadata) -> Option<Self> {
+        let usage = Self {
+            prompt_tokens: metadata.used_prompt_tokens,
+            completion_tokens: metadata.used_completion_tokens,
+            total_tokens: metadata.used_total_tokens,
+            reasoning_tokens: metadata.used_reasoning_tokens,
+            cache_read_tokens: metadata.used_cache_read_tokens,
+            cache_write_tokens: metadata.used_cache_write_tokens,
+            time_to_first_token_ms: metadata.time_to_first_token_ms,
+            generation_duration_ms: metadata.generation_duration_ms,
+            tools_offered: metadata
+                .offered_tools
+                .as_ref()
+                .map(|offered_tools| offered_tools.count > 0),
+            offered_tool_count: metadata
+                .offered_tools
+                .as_ref()
+                .map(|offered_tools| offered_tools.count),
+        };
+        (usage.prompt_tokens.is_some()
+            || usage.completion_tokens.is_some()
+            || usage.total_tokens.is_some()
+            || usage.reasoning_tokens.is_some()
+            || usage.time_to_first_token_ms.is_some()
+            || usage.generation_duration_ms.is_some()
+            || usage.offered_tool_count.is_some())
+        .then_some(usage)
+    }
 }
 
 /// Statistics for a list of chat messages
@@ -1337,6 +2048,14 @@ pub struct ChatMessageStats {
     returned_count: usize,
     /// Whether there are more messages available
     has_more: bool,
+    /// Cursor for fetching the next page in the requested order, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    next_cursor: Option<String>,
+    /// Cursor for fetching the previous page in the requested order, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    prev_cursor: Option<String>,
 }
 
 /// Response for the chat_messages endpoint
@@ -1346,6 +2065,14 @@ pub struct ChatMessagesResponse {
     messages: Vec<ChatMessage>,
     /// Statistics about the message list
     stats: ChatMessageStats,
+    /// Same as `stats.next_cursor`; kept for existing clients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    next_cursor: Option<String>,
+    /// Same as `stats.prev_cursor`; kept for existing clients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    prev_cursor: Option<String>,
 }
 
 /// Statistics for a list of recent chats
@@ -1370,6 +2097,23 @@ pub struct RecentChatsResponse {
     stats: RecentChatStats,
 }
 
+/// Response for the single-chat endpoint
+#[derive(Debug, ToSchema, Serialize)]
+pub struct ChatDetail {
+    /// The chat, in the same shape as in the recent chats list
+    #[serde(flatten)]
+    chat: RecentChat,
+    /// Full metadata of the files uploaded to this chat, including download URLs
+    file_upload_details: Vec<FileUploadItem>,
+    /// Snapshot of the configuration of the assistant of the chat, taken when the chat was
+    /// created or last refreshed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    assistant_snapshot: Option<AssistantSnapshot>,
+    /// Whether the chat uses the assistant snapshot instead of the live assistant
+    frozen: bool,
+}
+
 /// State of a chat's most recent generation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ToSchema, Serialize)]
 #[serde(rename_all = "lowercase")]
@@ -1438,8 +2182,11 @@ pub struct FileUploadItem {
     id: String,
     /// The original filename of the uploaded file
     filename: String,
-    /// Pre-signed URL for downloading the file directly from storage
+    /// URL for downloading the file, see `url_kind` for how to use it
     download_url: String,
+    /// Whether `download_url` can be fetched directly, or has to be requested with the
+    /// credentials of the user
+    url_kind: FileUrlKind,
     /// Proxied URL for inline preview without forcing download when available
     preview_url: Option<String>,
     /// Indicates that file contents are unavailable for the current user due to missing permissions.
@@ -1453,6 +2200,14 @@ pub struct FileUploadItem {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
     audio_transcription: Option<crate::models::file_upload::AudioTranscriptionMetadata>,
+    /// Whether the file is available in the storage. `missing` when its object was not found the
+    /// last time its contents were read, so the file can be shown as broken.
+    storage_status: FileStorageStatus,
+    /// Why the contents of the file can't be read, if that is known from a previous attempt
+    /// that will fail again (e.g. a password-protected file).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    processing_error: Option<FileProcessingErrorKind>,
 }
 
 /// Minimal file reference containing only the file ID
@@ -1467,14 +2222,87 @@ pub struct FileReference {
 pub struct FileUploadResponse {
     /// The list of uploaded files with their IDs and filenames
     files: Vec<FileUploadItem>,
+    /// Warnings about accepted files that none of the available models can use, see
+    /// `file_uploads.reject_unsupported`
+    warnings: Vec<FileUploadWarning>,
+    /// Files that were rejected, while the other files of the upload were accepted
+    errors: Vec<FileUploadError>,
+}
+
+/// Warning about an accepted file that none of the available models can use
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FileUploadWarning {
+    /// The ID of the file
+    file_id: String,
+    /// The name of the file
+    filename: String,
+    /// Human-readable reason why the file can't be used
+    reason: String,
+}
+
+/// Error about a file that was rejected, as it is empty or a text file that only contains
+/// whitespace
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FileUploadError {
+    /// The name of the file
+    filename: String,
+    /// Human-readable reason why the file was rejected
+    reason: String,
+}
+
+/// Error body of an upload or link of a file that none of the available models can use
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UnsupportedFileError {
+    /// Human-readable reason why the file can't be used
+    pub error: String,
+    /// The name of the rejected file
+    pub filename: String,
+    /// The file capability that was evaluated for the file
+    pub file_capability: FileCapability,
+}
+
+/// The `422 Unprocessable Entity` response for a file that none of the available models can use.
+fn unsupported_file_response(
+    filename: String,
+    file_capability: FileCapability,
+    reason: String,
+) -> axum::response::Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(UnsupportedFileError {
+            error: reason,
+            filename,
+            file_capability,
+        }),
+    )
+        .into_response()
+}
+
+/// Evaluate the file capabilities for all models available to the user.
+pub(crate) async fn user_file_capabilities(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+) -> Result<Vec<FileCapability>, StatusCode> {
+    evaluate_file_capabilities(
+        app_state,
+        policy,
+        FileCapabilityContext {
+            subject: &me_user.to_subject(),
+            user_groups: &me_user.groups,
+            model_id: None,
+        },
+    )
+    .await
+    .map_err(log_internal_server_error)
 }
 
 fn initial_audio_transcription_for_file(
     app_state: &AppState,
-    supports_audio_input: bool,
     file_capability: &FileCapability,
 ) -> Option<AudioTranscriptionMetadata> {
-    if !app_state.config.audio_transcription.enabled || !supports_audio_input {
+    // The audio capability only has operations if an available model accepts audio input
+    if !app_state.config.audio_transcription.enabled {
         return None;
     }
 
@@ -1511,11 +2339,40 @@ pub struct LinkFileRequest {
     pub provider_metadata: serde_json::Value,
 }
 
+/// Why files can't be added from a source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSourceDisabledReason {
+    /// Uploading files from the device is disabled (`file_uploads.local_upload_enabled`)
+    LocalUploadDisabled,
+    /// Linking files from the source is disabled (`file_uploads.link_enabled_sources`)
+    LinkSourceDisabled,
+}
+
+/// Error body of a file upload or link from a source that is disabled
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FileSourceDisabledError {
+    pub error: String,
+    /// Why files can't be added from the source
+    pub reason: FileSourceDisabledReason,
+}
+
 /// Request to optimize a prompt using the configured prompt optimizer.
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct PromptOptimizerRequest {
     /// The prompt to optimize.
     pub prompt: String,
+    /// Optional ID of the chat the prompt will be sent in. If provided, recent messages
+    /// of the chat's active thread are passed to the optimizer as context.
+    #[serde(default)]
+    #[schema(nullable = false)]
+    pub chat_id: Option<Uuid>,
+    /// Optional ID of the message the prompt will follow. If provided, the context ends at
+    /// this message instead of the latest message of the chat. Must belong to `chat_id`
+    /// if both are provided.
+    #[serde(default)]
+    #[schema(nullable = false)]
+    pub previous_message_id: Option<Uuid>,
 }
 
 /// Response containing the optimized prompt.
@@ -1530,6 +2387,16 @@ pub struct PromptOptimizerResponse {
 /// This endpoint accepts a multipart form with one or more files and returns UUIDs for each.
 /// If chat_id is provided, files are associated with that chat. If not provided, files are created
 /// as standalone uploads that can be linked to assistants later.
+///
+/// Responds with 403 if uploads are disabled (`file_uploads.local_upload_enabled`). Standalone
+/// uploads stay available if `file_uploads.storage_carve_outs.assistant_files` is set.
+///
+/// Files that none of the models available to the user can use are accepted with an entry in
+/// `warnings`, or rejected with 422 if `file_uploads.reject_unsupported` is set. In that case,
+/// the files of the request before the rejected one are kept.
+///
+/// Empty files, and text files that only contain whitespace, are not stored. They are listed in
+/// `errors`, while the other files of the request are uploaded.
 #[utoipa::path(
     post,
     path = "/me/files",
@@ -1541,7 +2408,9 @@ pub struct PromptOptimizerResponse {
     responses(
         (status = OK, body = FileUploadResponse),
         (status = BAD_REQUEST, description = "Invalid file upload"),
+        (status = FORBIDDEN, body = FileSourceDisabledError, description = "When uploading files is disabled"),
         (status = PAYLOAD_TOO_LARGE, description = "Uploaded file exceeds size limit"),
+        (status = UNPROCESSABLE_ENTITY, body = UnsupportedFileError, description = "When none of the available models can use a file and `file_uploads.reject_unsupported` is set"),
         (status = INTERNAL_SERVER_ERROR, description = "Server error"),
     )
 )]
@@ -1551,7 +2420,7 @@ pub async fn upload_file(
     Extension(policy): Extension<PolicyEngine>,
     axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
     mut multipart: Multipart,
-) -> Result<Json<FileUploadResponse>, StatusCode> {
+) -> Result<axum::response::Response, StatusCode> {
     let max_upload_size = app_state
         .config
         .max_upload_size_bytes()
@@ -1567,28 +2436,33 @@ pub async fn upload_file(
     } else {
         None
     };
+    let ephemeral_chat_id =
+        chat_id.filter(|chat_id| app_state.ephemeral_chats.contains(chat_id, &me_user.id));
 
-    // Determine if any available model supports image understanding or audio input
-    let available_models = app_state
-        .available_models(&policy, &me_user.to_subject(), &me_user.groups)
-        .await
-        .map_err(log_internal_server_error)?;
-    let (supports_image_understanding, supports_audio_input) =
-        available_models
-            .iter()
-            .fold((false, false), |(image, audio), model| {
-                let config = app_state.config.get_chat_provider(&model.chat_provider_id);
-                (
-                    image || config.model_capabilities.supports_image_understanding,
-                    audio || config.model_capabilities.supports_audio_input,
-                )
-            });
+    // Standalone uploads are the files of assistants, which may have a carve-out
+    let file_uploads = &app_state.config.file_uploads;
+    let upload_allowed = if chat_id.is_some() {
+        file_uploads.local_upload_enabled
+    } else {
+        file_uploads.assistant_file_upload_allowed()
+    };
+    if !upload_allowed {
+        tracing::warn!("User {} tried to upload a file while uploads are disabled", me_user.id);
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(FileSourceDisabledError {
+                error: "Uploading files is disabled".to_string(),
+                reason: FileSourceDisabledReason::LocalUploadDisabled,
+            }),
+        )
+            .into_response());
+    }
 
-    // Get all file capabilities for this user
-    let all_capabilities =
-        get_file_capabilities(supports_image_understanding, supports_audio_input);
+    let all_capabilities = user_file_capabilities(&app_state, &policy, &me_user).await?;
 
     let mut uploaded_files = Vec::new();
+    let mut warnings = Vec::new();
+    let mut errors = Vec::new();
 
     // Process the multipart form
     while let Some(mut field) = multipart.next_field().await.map_err(|e| {
@@ -1602,10 +2476,26 @@ pub async fn upload_file(
             .unwrap_or_else(|| "unnamed_file".to_string());
         let content_type = effective_upload_content_type(&filename, field.content_type());
 
-        // Generate a random UUID for the file
-        let file_id = Uuid::new_v4();
-        let file_path = file_id.to_string();
-
+        // Evaluate the file capability for this file, rejecting unsupported files before storing
+        let file_capability = find_file_capability_by_filename(&all_capabilities, &filename);
+        let unsupported_reason = file_capability.unsupported_reason();
+        if let Some(reason) = unsupported_reason.clone()
+            && app_state.config.file_uploads.reject_unsupported
+        {
+            tracing::warn!(
+                "User {} tried to upload unsupported file '{}': {}",
+                me_user.id,
+                filename,
+                reason
+            );
+            drain_multipart_field(&mut field).await?;
+            return Ok(unsupported_file_response(filename, file_capability, reason));
+        }
+
+        // Generate a random UUID for the file
+        let file_id = Uuid::new_v4();
+        let file_path = file_id.to_string();
+
         let file_storage_provider = app_state.default_file_storage_provider();
 
         // Upload the file to the storage provider
@@ -1617,7 +2507,7 @@ pub async fn upload_file(
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
 
-        let size_bytes = stream_multipart_field_with_limit(
+        let (size_bytes, whitespace_only) = stream_multipart_field_with_limit(
             &mut field,
             &mut multipart,
             &mut writer,
@@ -1632,8 +2522,31 @@ pub async fn upload_file(
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
 
-        // Store the file metadata in the database
-        let file_upload = if let Some(ref chat_id) = chat_id {
+        // Empty files would only fail once their contents are extracted
+        let empty_reason = if size_bytes == 0 {
+            Some("The file is empty")
+        } else if whitespace_only && file_capability.id == "text" {
+            Some("The file only contains whitespace")
+        } else {
+            None
+        };
+        if let Some(reason) = empty_reason {
+            tracing::warn!("User {} uploaded empty file '{}'", me_user.id, filename);
+            if let Err(e) = file_storage_provider.delete_file(&file_path).await {
+                tracing::warn!("Failed to delete empty file '{}': {}", file_path, e);
+            }
+            errors.push(FileUploadError {
+                filename,
+                reason: reason.to_string(),
+            });
+            continue;
+        }
+
+        // Store the file metadata in the database. Ephemeral chats are never stored, so their
+        // files are standalone uploads, which are deleted together with the chat.
+        let file_upload = if let Some(ref chat_id) = chat_id
+            && ephemeral_chat_id.is_none()
+        {
             // Create file upload linked to chat
             models::file_upload::create_file_upload(
                 &app_state.db,
@@ -1661,6 +2574,12 @@ pub async fn upload_file(
             tracing::error!("Failed to create file upload record: {}", e);
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
+        if let Some(ephemeral_chat_id) = &ephemeral_chat_id {
+            app_state
+                .ephemeral_chats
+                .add_file_upload(ephemeral_chat_id, file_upload.id)
+                .map_err(log_internal_server_error)?;
+        }
 
         // Generate a pre-signed download URL
         let download_url = file_storage_provider
@@ -1681,13 +2600,8 @@ pub async fn upload_file(
             file_upload.id
         );
 
-        // Evaluate the file capability for this file
-        let file_capability = find_file_capability_by_filename(&all_capabilities, &filename);
-        let audio_transcription = initial_audio_transcription_for_file(
-            &app_state,
-            supports_audio_input,
-            &file_capability,
-        );
+        let audio_transcription =
+            initial_audio_transcription_for_file(&app_state, &file_capability);
 
         if let Some(audio_transcription) = audio_transcription.clone() {
             models::file_upload::set_audio_transcription_metadata(
@@ -1702,21 +2616,32 @@ pub async fn upload_file(
             })?;
         }
 
+        if let Some(reason) = unsupported_reason {
+            warnings.push(FileUploadWarning {
+                file_id: file_upload.id.to_string(),
+                filename: filename.clone(),
+                reason,
+            });
+        }
+
         // Add this file to our list of uploaded files
         uploaded_files.push(FileUploadItem {
             id: file_upload.id.to_string(),
             filename,
             download_url,
+            url_kind: FileUrlKind::Direct,
             preview_url: Some(preview_url),
             file_contents_unavailable_missing_permissions: false,
             is_sharepoint_file: false,
             file_capability,
             audio_transcription,
+            storage_status: FileStorageStatus::Ok,
+            processing_error: None,
         });
     }
 
-    // If no files were uploaded, return an error
-    if uploaded_files.is_empty() {
+    // If the request contained no files, return an error
+    if uploaded_files.is_empty() && errors.is_empty() {
         return Err(StatusCode::BAD_REQUEST);
     }
 
@@ -1725,9 +2650,15 @@ pub async fn upload_file(
     // Return the list of uploaded files
     Ok(Json(FileUploadResponse {
         files: uploaded_files,
-    }))
+        warnings,
+        errors,
+    })
+    .into_response())
 }
 
+/// Stream a multipart field to the writer.
+///
+/// Returns the size of the field in bytes, and whether it only consists of whitespace.
 async fn stream_multipart_field_with_limit(
     field: &mut axum_extra::extract::multipart::Field,
     _multipart: &mut Multipart,
@@ -1735,8 +2666,9 @@ async fn stream_multipart_field_with_limit(
     max_upload_size: usize,
     user_id: &str,
     filename: &str,
-) -> Result<usize, StatusCode> {
+) -> Result<(usize, bool), StatusCode> {
     let mut size_bytes = 0usize;
+    let mut whitespace_only = true;
 
     while let Some(chunk) = field.chunk().await.map_err(|e| {
         tracing::error!("Failed to read multipart chunk: {} - {}", e, e.status());
@@ -1768,6 +2700,7 @@ async fn stream_multipart_field_with_limit(
         }
 
         size_bytes += chunk.len();
+        whitespace_only &= chunk.iter().all(u8::is_ascii_whitespace);
 
         writer.write(chunk.to_vec()).await.map_err(|e| {
             tracing::error!("Failed to write file chunk: {}", e);
@@ -1775,7 +2708,7 @@ async fn stream_multipart_field_with_limit(
         })?;
     }
 
-    Ok(size_bytes)
+    Ok((size_bytes, whitespace_only))
 }
 
 async fn drain_multipart_field(
@@ -1804,6 +2737,9 @@ async fn drain_multipart_field(
 /// allowing it to be used in chat messages or attached to assistants.
 /// If chat_id is provided, the file is associated with that chat. If not provided,
 /// the file is created as a standalone upload.
+///
+/// Only the sources in `file_uploads.link_enabled_sources` are accepted. Files that none of the
+/// models available to the user can use are handled like in `POST /me/files`.
 #[utoipa::path(
     post,
     path = "/me/files/link",
@@ -1813,7 +2749,10 @@ async fn drain_multipart_field(
         (status = OK, body = FileUploadResponse),
         (status = BAD_REQUEST, description = "Invalid request or unsupported source"),
         (status = UNAUTHORIZED, description = "No access token available for external provider"),
+        (status = FORBIDDEN, body = FileSourceDisabledError, description = "When linking files from the source is disabled"),
         (status = NOT_FOUND, description = "File not found or integration not enabled"),
+        (status = CONFLICT, description = "The auth mode doesn't provide the access token of the user for the source, e.g. `trusted_headers`"),
+        (status = UNPROCESSABLE_ENTITY, body = UnsupportedFileError, description = "When none of the available models can use the file and `file_uploads.reject_unsupported` is set"),
         (status = INTERNAL_SERVER_ERROR, description = "Server error"),
     )
 )]
@@ -1822,8 +2761,27 @@ pub async fn link_file(
     Extension(me_user): Extension<MeProfile>,
     Extension(policy): Extension<PolicyEngine>,
     Json(request): Json<LinkFileRequest>,
-) -> Result<Json<FileUploadResponse>, StatusCode> {
-    match request.source.as_str() {
+) -> Result<axum::response::Response, StatusCode> {
+    let source = request.source.as_str();
+    if LINK_FILE_SOURCES.contains(&source)
+        && !app_state.config.file_uploads.link_source_enabled(source)
+    {
+        tracing::warn!(
+            "User {} tried to link a file from disabled source '{}'",
+            me_user.id,
+            source
+        );
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(FileSourceDisabledError {
+                error: format!("Linking files from '{}' is disabled", source),
+                reason: FileSourceDisabledReason::LinkSourceDisabled,
+            }),
+        )
+            .into_response());
+    }
+
+    match source {
         "sharepoint" => link_sharepoint_file_impl(&app_state, &me_user, &policy, &request).await,
         _ => {
             tracing::error!("Unsupported file source: {}", request.source);
@@ -1838,39 +2796,11 @@ async fn link_sharepoint_file_impl(
     me_user: &MeProfile,
     policy: &PolicyEngine,
     request: &LinkFileRequest,
-) -> Result<Json<FileUploadResponse>, StatusCode> {
+) -> Result<axum::response::Response, StatusCode> {
     use graph_rs_sdk::{GraphClient, GraphClientConfiguration};
 
-    // Determine if any available model supports image understanding or audio input
-    let available_models = app_state
-        .available_models(policy, &me_user.to_subject(), &me_user.groups)
-        .await
-        .map_err(log_internal_server_error)?;
-    let (supports_image_understanding, supports_audio_input) =
-        available_models
-            .iter()
-            .fold((false, false), |(image, audio), model| {
-                let config = app_state.config.get_chat_provider(&model.chat_provider_id);
-                (
-                    image || config.model_capabilities.supports_image_understanding,
-                    audio || config.model_capabilities.supports_audio_input,
-                )
-            });
-
-    // Get all file capabilities for this user
-    let all_capabilities =
-        get_file_capabilities(supports_image_understanding, supports_audio_input);
-
-    // Check if SharePoint integration is enabled
-    if !app_state
-        .config
-        .integrations
-        .experimental_sharepoint
-        .enabled
-    {
-        tracing::warn!("Sharepoint integration is not enabled");
-        return Err(StatusCode::NOT_FOUND);
-    }
+    sharepoint::check_sharepoint_enabled(app_state)?;
+    let all_capabilities = user_file_capabilities(app_state, policy, me_user).await?;
 
     // Get access token from user profile
     let access_token = me_user.access_token.as_deref().ok_or_else(|| {
@@ -1929,6 +2859,21 @@ async fn link_sharepoint_file_impl(
         return Err(StatusCode::BAD_REQUEST);
     }
 
+    // Evaluate the file capability for this file, rejecting unsupported files before linking
+    let file_capability = find_file_capability_by_filename(&all_capabilities, &filename);
+    let unsupported_reason = file_capability.unsupported_reason();
+    if let Some(reason) = unsupported_reason.clone()
+        && app_state.config.file_uploads.reject_unsupported
+    {
+        tracing::warn!(
+            "User {} tried to link unsupported file '{}': {}",
+            me_user.id,
+            filename,
+            reason
+        );
+        return Ok(unsupported_file_response(filename, file_capability, reason));
+    }
+
     // Extract the SharePoint download URL from the MS Graph API response
     let download_url = item_json
         .get("@microsoft.graph.downloadUrl")
@@ -1962,10 +2907,7 @@ async fn link_sharepoint_file_impl(
         file_upload.id
     );
 
-    // Evaluate the file capability for this file
-    let file_capability = find_file_capability_by_filename(&all_capabilities, &filename);
-    let audio_transcription =
-        initial_audio_transcription_for_file(app_state, supports_audio_input, &file_capability);
+    let audio_transcription = initial_audio_transcription_for_file(app_state, &file_capability);
 
     if let Some(audio_transcription) = audio_transcription.clone() {
         models::file_upload::set_audio_transcription_metadata(
@@ -1982,18 +2924,33 @@ async fn link_sharepoint_file_impl(
 
     app_state.global_policy_engine.invalidate_data().await;
 
+    let warnings = unsupported_reason
+        .map(|reason| FileUploadWarning {
+            file_id: file_upload.id.to_string(),
+            filename: filename.clone(),
+            reason,
+        })
+        .into_iter()
+        .collect();
+
     Ok(Json(FileUploadResponse {
         files: vec![FileUploadItem {
             id: file_upload.id.to_string(),
             filename,
             preview_url: Some(proxied_preview_url_for_file(&file_upload.id)),
             download_url,
+            url_kind: FileUrlKind::Direct,
             file_contents_unavailable_missing_permissions: false,
             is_sharepoint_file: true,
             file_capability,
             audio_transcription,
+            storage_status: FileStorageStatus::Ok,
+            processing_error: None,
         }],
-    }))
+        warnings,
+        errors: Vec::new(),
+    })
+    .into_response())
 }
 
 impl ChatMessage {
@@ -2015,18 +2972,51 @@ impl ChatMessage {
         let mcp_servers_unavailable = generation_metadata
             .as_ref()
             .and_then(|metadata| metadata.mcp_servers_unavailable.clone());
+        let renderable_blocks = generation_metadata
+            .as_ref()
+            .and_then(|metadata| metadata.renderable_blocks.clone());
+        let prompt_injection_warnings = generation_metadata
+            .as_ref()
+            .and_then(|metadata| metadata.prompt_injection_warnings.clone());
+        let stop_reason = generation_metadata
+            .as_ref()
+            .and_then(|metadata| metadata.stop_reason);
+        let usage = generation_metadata
+            .as_ref()
+            .and_then(ChatMessageUsage::from_generation_metadata);
+        let moderation = generation_metadata
+            .as_ref()
+            .and_then(|metadata| metadata.moderation.clone());
+        let prompt_manifest_hash = generation_metadata
+            .as_ref()
+            .and_then(|metadata| metadata.prompt_manifest.as_ref())
+            .map(|manifest| manifest.hash.clone());
+        let input_parameters = msg.input_parameters.as_ref().and_then(|p| {
+            serde_json::from_value::<crate::models::message::InputParameters>(p.clone()).ok()
+        });
         Ok(ChatMessage {
             id: msg.id.to_string(),
             chat_id: msg.chat_id.to_string(),
             role: parsed_message.role.to_string(),
             content: parsed_message.content,
+            placeholder_text: input_parameters
+                .as_ref()
+                .and_then(|p| p.placeholder_text.clone()),
             error,
             error_report: None,
             mcp_servers_unavailable,
-            created_at: msg.created_at,
-            updated_at: msg.updated_at,
+            renderable_blocks,
+            prompt_injection_warnings,
+            stop_reason,
+            continuable: (stop_reason == Some(StopReason::MaxTokens)).then_some(true),
+            usage,
+            prompt_manifest_hash,
+            created_at: messages::api_timestamp(msg.created_at_utc()),
+            updated_at: messages::api_timestamp(msg.updated_at_utc()),
             previous_message_id: msg.previous_message_id.map(|id| id.to_string()),
             sibling_message_id: msg.sibling_message_id.map(|id| id.to_string()),
+            edited: msg.edit_of_message_id.is_some(),
+            edit_of_message_id: msg.edit_of_message_id.map(|id| id.to_string()),
             is_message_in_active_thread: msg.is_message_in_active_thread,
             input_files_ids: msg
                 .input_file_uploads
@@ -2042,22 +3032,12 @@ impl ChatMessage {
                 created_at: f.created_at,
                 updated_at: f.updated_at,
             }),
-            action_facet_id: msg
-                .input_parameters
-                .as_ref()
-                .and_then(|p| {
-                    serde_json::from_value::<crate::models::message::InputParameters>(p.clone())
-                        .ok()
-                })
-                .and_then(|p| p.action_facet_id),
-            action_facet_args: msg
-                .input_parameters
+            reactions: vec![], // Will be populated separately
+            action_facet_id: input_parameters
                 .as_ref()
-                .and_then(|p| {
-                    serde_json::from_value::<crate::models::message::InputParameters>(p.clone())
-                        .ok()
-                })
-                .and_then(|p| p.action_facet_args),
+                .and_then(|p| p.action_facet_id.clone()),
+            action_facet_args: input_parameters.and_then(|p| p.action_facet_args),
+            moderation,
         })
     }
 }
@@ -2111,13 +3091,24 @@ fn error_description(error: &GenerationErrorType) -> &str {
         GenerationErrorType::ContentFilter {
             error_description, ..
         }
-        | GenerationErrorType::RateLimit { error_description }
+        | GenerationErrorType::RateLimited {
+            error_description, ..
+        }
         | GenerationErrorType::ModelUnavailable { error_description }
         | GenerationErrorType::InvalidRequest { error_description }
         | GenerationErrorType::ProviderError {
             error_description, ..
         }
         | GenerationErrorType::HallucinationLoop { error_description }
+        | GenerationErrorType::ModerationBlocked {
+            error_description, ..
+        }
+        | GenerationErrorType::CompliancePolicy {
+            error_description, ..
+        }
+        | GenerationErrorType::TransformerFailed {
+            error_description, ..
+        }
         | GenerationErrorType::InternalError { error_description } => error_description,
     }
 }
@@ -2256,6 +3247,288 @@ pub async fn delete_message_feedback(
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Response of adding or removing a reaction
+#[derive(Debug, ToSchema, Serialize)]
+pub struct MessageReactionsResponse {
+    /// The reactions on the message after the change, aggregated per emoji
+    reactions: Vec<MessageReaction>,
+}
+
+fn message_reaction_error_status(e: Report) -> StatusCode {
+    let error_msg = e.to_string().to_lowercase();
+    if error_msg.contains("not found") || error_msg.contains("not authorized") {
+        StatusCode::NOT_FOUND
+    } else if error_msg.contains("not allowed") {
+        StatusCode::BAD_REQUEST
+    } else {
+        log_internal_server_error(e)
+    }
+}
+
+async fn message_reactions_response(
+    app_state: &AppState,
+    user_id: &Uuid,
+    message_id: &Uuid,
+) -> Result<Json<MessageReactionsResponse>, StatusCode> {
+    let reactions = models::message_reaction::get_reactions_for_messages(
+        &app_state.db,
+        user_id,
+        &[*message_id],
+    )
+    .await
+    .map_err(log_internal_server_error)?
+    .remove(message_id)
+    .unwrap_or_default();
+    Ok(Json(MessageReactionsResponse { reactions }))
+}
+
+/// React to a message with an emoji
+///
+/// Anyone that can read the chat can react. Reacting with an emoji the user already reacted with
+/// is a no-op. The emoji must be one of `frontend.message_reaction_emojis`.
+#[utoipa::path(
+    put,
+    path = "/messages/{message_id}/reactions/{emoji}",
+    params(
+        ("message_id" = String, Path, description = "The ID of the message to react to"),
+        ("emoji" = String, Path, description = "The emoji to react with, percent-encoded")
+    ),
+    responses(
+        (status = OK, body = MessageReactionsResponse, description = "The reactions on the message after adding the reaction"),
+        (status = BAD_REQUEST, description = "Invalid message ID, or the emoji is not allowed"),
+        (status = NOT_FOUND, description = "Message not found or not accessible"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error while adding the reaction")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn add_message_reaction(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path((message_id, emoji)): Path<(String, String)>,
+) -> Result<Json<MessageReactionsResponse>, StatusCode> {
+    let message_id = Uuid::parse_str(&message_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let user_id = Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+
+    models::message_reaction::add_reaction(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &user_id,
+        &message_id,
+        &emoji,
+        &app_state.config.frontend.message_reaction_emojis,
+    )
+    .await
+    .map_err(message_reaction_error_status)?;
+
+    message_reactions_response(&app_state, &user_id, &message_id).await
+}
+
+/// Remove an emoji reaction from a message
+///
+/// Removing a reaction the user doesn't have is a no-op.
+#[utoipa::path(
+    delete,
+    path = "/messages/{message_id}/reactions/{emoji}",
+    params(
+        ("message_id" = String, Path, description = "The ID of the message to remove the reaction from"),
+        ("emoji" = String, Path, description = "The emoji of the reaction, percent-encoded")
+    ),
+    responses(
+        (status = OK, body = MessageReactionsResponse, description = "The reactions on the message after removing the reaction"),
+        (status = BAD_REQUEST, description = "Invalid message ID"),
+        (status = NOT_FOUND, description = "Message not found or not accessible"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error while removing the reaction")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn remove_message_reaction(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path((message_id, emoji)): Path<(String, String)>,
+) -> Result<Json<MessageReactionsResponse>, StatusCode> {
+    let message_id = Uuid::parse_str(&message_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let user_id = Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+
+    models::message_reaction::remove_reaction(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &user_id,
+        &message_id,
+        &emoji,
+    )
+    .await
+    .map_err(message_reaction_error_status)?;
+
+    message_reactions_response(&app_state, &user_id, &message_id).await
+}
+
+/// Get a part of the content of a message in full.
+///
+/// Large parts of oversized messages are moved to the file storage, and only a preview of them
+/// is kept in the message as a `blob_pointer` part (see `chat.content_spillover`). For those, the
+/// original part is streamed from the file storage. Other parts are returned as they are.
+#[utoipa::path(
+    get,
+    path = "/messages/{message_id}/content/{index}",
+    params(
+        ("message_id" = String, Path, description = "The ID of the message"),
+        ("index" = usize, Path, description = "The index of the part in the content of the message")
+    ),
+    responses(
+        (status = OK, body = ContentPart, description = "The content part"),
+        (status = BAD_REQUEST, description = "Invalid message ID"),
+        (status = NOT_FOUND, description = "Message or content part not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error while reading the content part")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn message_content_part(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path((message_id, index)): Path<(String, usize)>,
+) -> Result<axum::response::Response, StatusCode> {
+    let message_id = Uuid::parse_str(&message_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+
+    let message = models::message::get_message_by_id(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &message_id,
+    )
+    .await
+    .map_err(|_| StatusCode::NOT_FOUND)?;
+    let part = MessageSchema::validate(&message.raw_message)
+        .map_err(log_internal_server_error)?
+        .content
+        .into_iter()
+        .nth(index)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let ContentPart::BlobPointer(pointer) = part else {
+        return Ok(Json(part).into_response());
+    };
+    let blob = app_state
+        .default_file_storage_provider()
+        .get_file_reader(&pointer.blob_path)
+        .await
+        .map_err(log_internal_server_error)?
+        .into_bytes_stream(..)
+        .await
+        .wrap_err("Failed to stream spilled message content")
+        .map_err(log_internal_server_error)?;
+
+    Ok((
+        [(CONTENT_TYPE, "application/json")],
+        axum::body::Body::from_stream(blob),
+    )
+        .into_response())
+}
+
+/// Maximum number of characters of the text previews of the previous versions of a message
+const MESSAGE_VERSION_PREVIEW_CHARS: usize = 200;
+
+/// A previous version of an edited message
+#[derive(Debug, ToSchema, Serialize)]
+pub struct MessageVersion {
+    /// The ID of the message of this version
+    id: String,
+    /// The beginning of the text of this version
+    text_preview: String,
+    /// When this version was created
+    created_at: DateTime<FixedOffset>,
+    /// The ID of the user that wrote this version, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    editor: Option<String>,
+}
+
+/// Get the previous versions of an edited message
+///
+/// Returns the versions the message was edited from, starting with the original message, or an
+/// empty list if the message is not an edit. Regenerations of a message are not included.
+#[utoipa::path(
+    get,
+    path = "/messages/{message_id}/history",
+    params(
+        ("message_id" = String, Path, description = "The ID of the message")
+    ),
+    responses(
+        (status = OK, body = Vec<MessageVersion>, description = "The previous versions of the message"),
+        (status = BAD_REQUEST, description = "Invalid message ID"),
+        (status = NOT_FOUND, description = "Message not found or not accessible"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error while reading the versions")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn message_edit_history(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path(message_id): Path<String>,
+) -> Result<Json<Vec<MessageVersion>>, StatusCode> {
+    let message_id = Uuid::parse_str(&message_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+
+    let versions = models::message::get_message_edit_history(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &message_id,
+    )
+    .await
+    .map_err(|e| {
+        let error_msg = e.to_string().to_lowercase();
+        if error_msg.contains("not found") || error_msg.contains("not authorized") {
+            StatusCode::NOT_FOUND
+        } else {
+            log_internal_server_error(e)
+        }
+    })?;
+
+    versions
+        .into_iter()
+        .map(|version| {
+            let parsed_message = MessageSchema::validate(&version.raw_message)?;
+            Ok(MessageVersion {
+                id: version.id.to_string(),
+                text_preview: parsed_message.text_preview(MESSAGE_VERSION_PREVIEW_CHARS),
+                created_at: messages::api_timestamp(version.created_at_utc()),
+                editor: parsed_message.name,
+            })
+        })
+        .collect::<Result<Vec<_>, Report>>()
+        .map(Json)
+        .map_err(log_internal_server_error)
+}
+
 #[utoipa::path(get, path = "/messages", responses((status = OK, body = Vec<Message>)))]
 pub async fn messages() -> Json<Vec<Message>> {
     vec![].into()
@@ -2276,11 +3549,14 @@ pub async fn chats() -> Json<Vec<Chat>> {
     params(
         ("chat_id" = String, Path, description = "The ID of the chat to get messages for"),
         ("limit" = Option<u64>, Query, description = "Maximum number of messages to return per page. Defaults to 100 if not provided. Larger values may impact performance."),
-        ("offset" = Option<u64>, Query, description = "Number of messages to skip for pagination. Defaults to 0 if not provided.")
+        ("offset" = Option<u64>, Query, description = "Number of messages to skip for pagination. Defaults to 0 if not provided. Can't be combined with `cursor`."),
+        ("order" = Option<MessageOrder>, Query, description = "Order in which the messages are paged through, by creation time. Defaults to `desc` (newest first)."),
+        ("page_order" = Option<MessageOrder>, Query, description = "Order of the messages within the returned page. Defaults to `order`. Use `order=desc&page_order=asc` to load the latest messages first while rendering each page oldest first."),
+        ("cursor" = Option<String>, Query, description = "Opaque cursor from `stats.next_cursor` or `stats.prev_cursor` of a previous response. Can't be combined with `offset`.")
     ),
     responses(
         (status = OK, body = ChatMessagesResponse, description = "Successfully retrieved messages with pagination metadata"),
-        (status = BAD_REQUEST, description = "Invalid chat ID format"),
+        (status = BAD_REQUEST, description = "Invalid chat ID format, order or cursor, or both `cursor` and `offset` provided"),
         (status = NOT_FOUND, description = "When the chat does not exist or is not accessible"),
         (status = INTERNAL_SERVER_ERROR, description = "Server error while retrieving messages")
     ),
@@ -2307,14 +3583,43 @@ pub async fn chat_messages(
 
     let offset = params.get("offset").and_then(|o| o.parse::<u64>().ok());
 
+    let order = params
+        .get("order")
+        .map(|o| o.parse::<MessageOrder>())
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+        .unwrap_or_default();
+
+    let page_order = params
+        .get("page_order")
+        .map(|o| o.parse::<MessageOrder>())
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let cursor = params
+        .get("cursor")
+        .map(|c| MessageCursor::decode(c))
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    // A cursor already pins the position in the listing, so an offset on top is ambiguous
+    if cursor.is_some() && params.contains_key("offset") {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
     // Get the messages for this chat
     let (messages, stats) = models::message::get_chat_messages(
         &app_state.db,
         &policy,
         &me_user.to_subject(),
         &chat_id,
-        limit,
-        offset,
+        GetChatMessagesOptions {
+            limit,
+            offset,
+            order,
+            page_order,
+            cursor,
+        },
     )
     .await
     .map_err(|e| {
@@ -2333,6 +3638,12 @@ pub async fn chat_messages(
             .await
             .wrap_err("Failed to get message feedbacks")
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let user_id = Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut reactions =
+        models::message_reaction::get_reactions_for_messages(&app_state.db, &user_id, &message_ids)
+            .await
+            .wrap_err("Failed to get message reactions")
+            .map_err(log_internal_server_error)?;
 
     // Collect all unique file IDs from all messages
     let all_file_ids: std::collections::HashSet<Uuid> = messages
@@ -2345,25 +3656,7 @@ pub async fn chat_messages(
         })
         .collect();
 
-    // Determine if any available model supports image understanding
-    let available_models = app_state
-        .available_models(&policy, &me_user.to_subject(), &me_user.groups)
-        .await
-        .map_err(log_internal_server_error)?;
-    let (supports_image_understanding, supports_audio_input) =
-        available_models
-            .iter()
-            .fold((false, false), |(image, audio), model| {
-                let config = app_state.config.get_chat_provider(&model.chat_provider_id);
-                (
-                    image || config.model_capabilities.supports_image_understanding,
-                    audio || config.model_capabilities.supports_audio_input,
-                )
-            });
-
-    // Get all file capabilities for this user
-    let all_capabilities =
-        get_file_capabilities(supports_image_understanding, supports_audio_input);
+    let all_capabilities = user_file_capabilities(&app_state, &policy, &me_user).await?;
 
     // Fetch all file uploads with their download URLs
     let mut file_uploads_map = std::collections::HashMap::new();
@@ -2380,12 +3673,16 @@ pub async fn chat_messages(
         {
             let file_capability =
                 find_file_capability_by_filename(&all_capabilities, &file_upload.filename);
+            let processing_error = cached_file_processing_error(&app_state, &file_upload.id)
+                .await
+                .map(|error| error.kind());
             file_uploads_map.insert(
                 file_id,
                 FileUploadItem {
                     id: file_upload.id.to_string(),
                     filename: file_upload.filename,
                     download_url: file_upload.download_url,
+                    url_kind: file_upload.url_kind,
                     preview_url: file_upload.preview_url,
                     file_contents_unavailable_missing_permissions: file_upload
                         .file_contents_unavailable_missing_permissions,
@@ -2393,6 +3690,8 @@ pub async fn chat_messages(
                         == SHAREPOINT_PROVIDER_ID,
                     file_capability,
                     audio_transcription: file_upload.audio_transcription,
+                    storage_status: file_upload.storage_status,
+                    processing_error,
                 },
             );
         }
@@ -2411,6 +3710,7 @@ pub async fn chat_messages(
         .map(|msg| {
             let feedback = feedbacks.get(&msg.id).cloned();
             let mut chat_message = ChatMessage::from_model_with_feedback(msg.clone(), feedback)?;
+            chat_message.reactions = reactions.remove(&msg.id).unwrap_or_default();
             chat_message.error_report = chat_message.error.as_ref().map(|error| {
                 render_message_error_report(&app_state.config, &msg, assistant_id, error)
             });
@@ -2455,7 +3755,11 @@ pub async fn chat_messages(
             current_offset: stats.current_offset,
             returned_count: stats.returned_count,
             has_more: stats.has_more,
+            next_cursor: stats.next_cursor.clone(),
+            prev_cursor: stats.prev_cursor.clone(),
         },
+        next_cursor: stats.next_cursor,
+        prev_cursor: stats.prev_cursor,
     };
 
     Ok(Json(response))
@@ -2468,10 +3772,12 @@ pub async fn chat_messages(
         ("limit" = Option<u64>, Query, description = "Maximum number of chats to return per page. Defaults to 30 if not provided. Larger values may impact performance."),
         ("offset" = Option<u64>, Query, description = "Number of chats to skip for pagination. Defaults to 0 if not provided."),
         ("include_archived" = Option<bool>, Query, description = "Whether to include archived chats in results. Defaults to false if not provided."),
-        ("q" = Option<String>, Query, description = "Optional full-text search query for chat titles. User-provided titles take precedence over generated summary titles. Empty values are treated like an unfiltered recent chats list.")
+        ("q" = Option<String>, Query, description = "Optional full-text search query for chat titles. User-provided titles take precedence over generated summary titles. Empty values are treated like an unfiltered recent chats list."),
+        ("label_id" = Option<String>, Query, description = "Only include chats that have the label with this ID assigned.")
     ),
     responses(
         (status = OK, body = RecentChatsResponse, description = "Successfully retrieved chats with pagination metadata"),
+        (status = BAD_REQUEST, description = "Invalid label ID format"),
         (status = INTERNAL_SERVER_ERROR, description = "Server error while retrieving chats")
     ),
     security(
@@ -2498,7 +3804,110 @@ pub async fn recent_chats(
         .and_then(|a| a.parse::<bool>().ok())
         .unwrap_or(false);
     let search_query = params.get("q").map(String::as_str);
+    let label_id = params
+        .get("label_id")
+        .map(|id| Uuid::parse_str(id))
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    list_recent_chats(
+        &app_state,
+        &me_user,
+        &policy,
+        RecentChatsFilter {
+            limit,
+            offset,
+            include_archived,
+            search_query,
+            label_id,
+            assistant_id: None,
+        },
+    )
+    .await
+}
+
+/// List the user's recent chats that were created with a specific assistant.
+///
+/// Shorthand for the recent chats listing filtered to one assistant. Chats with an
+/// archived assistant are still listed.
+#[utoipa::path(
+    get,
+    path = "/me/chats/by-assistant/{assistant_id}",
+    params(
+        ("assistant_id" = String, Path, description = "The ID of the assistant the chats were created with"),
+        ("limit" = Option<u64>, Query, description = "Maximum number of chats to return per page. Defaults to 30 if not provided. Larger values may impact performance."),
+        ("offset" = Option<u64>, Query, description = "Number of chats to skip for pagination. Defaults to 0 if not provided."),
+        ("include_archived" = Option<bool>, Query, description = "Whether to include archived chats in results. Defaults to false if not provided.")
+    ),
+    responses(
+        (status = OK, body = RecentChatsResponse, description = "Successfully retrieved the chats created with the assistant"),
+        (status = BAD_REQUEST, description = "Invalid assistant ID format"),
+        (status = NOT_FOUND, description = "Assistant not found or not accessible"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error while retrieving chats")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn recent_chats_by_assistant(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path(assistant_id): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<RecentChatsResponse>, StatusCode> {
+    let assistant_id = Uuid::parse_str(&assistant_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let limit = params
+        .get("limit")
+        .and_then(|l| l.parse::<u64>().ok())
+        .unwrap_or(30);
+    let offset = params
+        .get("offset")
+        .and_then(|o| o.parse::<u64>().ok())
+        .unwrap_or(0);
+    let include_archived = params
+        .get("include_archived")
+        .and_then(|a| a.parse::<bool>().ok())
+        .unwrap_or(false);
+
+    // Verify the user (still) has access to the assistant
+    models::assistant::get_assistant_by_id_allow_archived(
+        &app_state.db,
+        &me_user.to_subject(),
+        assistant_id,
+    )
+    .await
+    .map_err(|e| {
+        if e.to_string().contains("not found") || e.to_string().contains("Access denied") {
+            StatusCode::NOT_FOUND
+        } else {
+            log_internal_server_error(e)
+        }
+    })?;
+
+    list_recent_chats(
+        &app_state,
+        &me_user,
+        &policy,
+        RecentChatsFilter {
+            limit,
+            offset,
+            include_archived,
+            search_query: None,
+            label_id: None,
+            assistant_id: Some(assistant_id),
+        },
+    )
+    .await
+}
 
+/// Shared implementation of the recent chats listings.
+async fn list_recent_chats(
+    app_state: &AppState,
+    me_user: &MeProfile,
+    policy: &PolicyEngine,
+    filter: RecentChatsFilter<'_>,
+) -> Result<Json<RecentChatsResponse>, StatusCode> {
     policy
         .rebuild_data_if_needed(&app_state.db, &app_state.config)
         .await
@@ -2513,15 +3922,10 @@ pub async fn recent_chats(
     // Call the get_recent_chats function
     let (model_chats, stats) = get_recent_chats(
         &app_state.db,
-        &policy,
+        policy,
         &me_user.to_subject(),
         &user_id,
-        RecentChatsFilter {
-            limit,
-            offset,
-            include_archived,
-            search_query,
-        },
+        filter,
         app_state.config.generation_status.stale_after_secs,
     )
     .await
@@ -2529,16 +3933,17 @@ pub async fn recent_chats(
 
     // Convert from model RecentChat to API RecentChat
     let available_models = app_state
-        .available_models(&policy, &me_user.to_subject(), &me_user.groups)
+        .available_models(policy, &me_user.to_subject(), &me_user.groups)
         .await
         .map_err(log_internal_server_error)?;
     let api_chats = extend_recent_chats_to_api_model(
         model_chats,
         &app_state.db,
-        &policy,
+        policy,
         &me_user.to_subject(),
         &me_user.id,
         &available_models,
+        &app_state.config,
     )
     .await
     .map_err(|e| {
@@ -2652,6 +4057,7 @@ async fn extend_recent_chats_to_api_model(
     subject: &Subject,
     current_user_id: &str,
     available_models: &[crate::state::AvailableModel],
+    config: &crate::config::AppConfig,
 ) -> Result<Vec<RecentChat>, Report> {
     use futures::future::join_all;
 
@@ -2677,57 +4083,104 @@ async fn extend_recent_chats_to_api_model(
     // Execute all file upload fetches in parallel
     let file_uploads_results = join_all(file_upload_futures).await;
 
+    let edit_granted_chat_ids =
+        models::share_grant::get_edit_granted_resource_ids(db, subject, "chat").await?;
+
     // Build the API RecentChat objects
     let mut api_chats = Vec::with_capacity(model_chats.len());
     for (chat, file_uploads_result) in model_chats.into_iter().zip(file_uploads_results) {
         let file_uploads = file_uploads_result?;
-
-        // Convert file uploads to FileReference (just IDs)
-        let file_references: Vec<FileReference> = file_uploads
-            .into_iter()
-            .map(|upload| FileReference {
-                id: upload.id.to_string(),
-            })
-            .collect();
-
-        // Get the last model information based on the last chat provider ID
-        let last_model = if let Some(ref provider_id) = chat.last_chat_provider_id {
-            available_models
-                .iter()
-                .find(|model| &model.chat_provider_id == provider_id)
-                .map(|model| ChatModel {
-                    chat_provider_id: model.chat_provider_id.clone(),
-                    model_display_name: model.model_display_name.clone(),
-                    model_description: model.model_description.clone(),
-                    model_icon: model.model_icon.clone(),
-                })
-        } else {
-            None
-        };
-
-        let can_edit = permissions::can_user_edit_chat(current_user_id, &chat.owner_user_id);
-
-        api_chats.push(RecentChat {
-            id: chat.id,
-            title_by_summary: chat.title_by_summary,
-            title_by_user_provided: chat.title_by_user_provided,
-            title_resolved: chat.title_resolved,
-            last_message_at: chat.last_message_at,
-            file_uploads: file_references,
-            archived_at: chat.archived_at,
-            last_chat_provider_id: chat.last_chat_provider_id.clone(),
-            last_selected_facets: chat.last_selected_facets.clone(),
-            last_model,
+        let can_edit = permissions::can_user_edit_chat(
+            current_user_id,
+            &chat.owner_user_id,
+            edit_granted_chat_ids.contains(&chat.id),
+        );
+        api_chats.push(extend_recent_chat_to_api_model(
+            chat,
+            file_uploads.iter().map(|upload| upload.id),
             can_edit,
-            assistant_id: chat.assistant_id.map(|id| id.to_string()),
-            assistant_name: chat.assistant_name,
-            active_generation_started_at: chat.active_generation_started_at,
-        });
+            available_models,
+            config,
+        ));
     }
 
     Ok(api_chats)
 }
 
+/// Extends a single model RecentChat to an API RecentChat.
+fn extend_recent_chat_to_api_model(
+    chat: models::chat::RecentChat,
+    file_upload_ids: impl IntoIterator<Item = Uuid>,
+    can_edit: bool,
+    available_models: &[crate::state::AvailableModel],
+    config: &crate::config::AppConfig,
+) -> RecentChat {
+    // Convert file uploads to FileReference (just IDs)
+    let file_references: Vec<FileReference> = file_upload_ids
+        .into_iter()
+        .map(|id| FileReference { id: id.to_string() })
+        .collect();
+
+    // Get the last model information based on the last chat provider ID. Chats that were
+    // last generated with an aliased provider report the provider the alias resolves to.
+    let last_chat_provider_id = chat
+        .last_chat_provider_id
+        .as_deref()
+        .map(|provider_id| config.resolve_chat_provider_alias(provider_id).to_string());
+    // Models that are not available anymore are still reported, so that clients can show them.
+    let last_model = last_chat_provider_id.as_ref().map(|provider_id| {
+        match available_models
+            .iter()
+            .find(|model| &model.chat_provider_id == provider_id)
+        {
+            Some(model) => ChatModel {
+                chat_provider_id: model.chat_provider_id.clone(),
+                model_display_name: model.model_display_name.clone(),
+                model_description: model.model_description.clone(),
+                model_icon: model.model_icon.clone(),
+                max_input_files: model.max_input_files,
+                supported_input_modalities: model.supported_input_modalities.clone(),
+                available: true,
+                quota: None,
+                recommended: false,
+            },
+            None => ChatModel {
+                chat_provider_id: provider_id.clone(),
+                model_display_name: provider_id.clone(),
+                model_description: None,
+                model_icon: None,
+                max_input_files: None,
+                supported_input_modalities: vec![],
+                available: false,
+                quota: None,
+                recommended: false,
+            },
+        }
+    });
+
+    RecentChat {
+        id: chat.id,
+        title_by_summary: chat.title_by_summary,
+        title_by_user_provided: chat.title_by_user_provided,
+        title_resolved: chat.title_resolved,
+        last_message_at: chat.last_message_at,
+        file_uploads: file_references,
+        archived_at: chat.archived_at,
+        last_chat_provider_id,
+        last_selected_facets: chat.last_selected_facets,
+        last_model,
+        can_edit,
+        assistant_id: chat.assistant_id.map(|id| id.to_string()),
+        assistant_name: chat.assistant_name,
+        custom_instruction: chat.custom_instruction,
+        response_language: chat.response_language,
+        active_generation_started_at: chat.active_generation_started_at,
+        labels: chat.labels.into_iter().map(Label::from).collect(),
+        last_read_message_id: chat.last_read_message_id.map(|id| id.to_string()),
+        unread_count: chat.unread_count,
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/me/frequent_assistants",
@@ -2782,80 +4235,70 @@ pub async fn frequent_assistants(
     .await
     .map_err(log_internal_server_error)?;
 
-    // Determine if any available model supports image understanding
-    let available_models = app_state
-        .available_models(&policy, &me_user.to_subject(), &me_user.groups)
-        .await
-        .map_err(log_internal_server_error)?;
-    let (supports_image_understanding, supports_audio_input) =
-        available_models
-            .iter()
-            .fold((false, false), |(image, audio), model| {
-                let config = app_state.config.get_chat_provider(&model.chat_provider_id);
-                (
-                    image || config.model_capabilities.supports_image_understanding,
-                    audio || config.model_capabilities.supports_audio_input,
-                )
-            });
+    let all_capabilities = user_file_capabilities(&app_state, &policy, &me_user).await?;
 
-    // Get all file capabilities for this user
-    let all_capabilities =
-        get_file_capabilities(supports_image_understanding, supports_audio_input);
+    let edit_granted_assistant_ids = models::share_grant::get_edit_granted_resource_ids(
+        &app_state.db,
+        &me_user.to_subject(),
+        "assistant",
+    )
+    .await
+    .map_err(log_internal_server_error)?;
 
     // Convert from model FrequentAssistant to API FrequentAssistantItem
     let current_user_id = &user_id;
-    let api_assistants: Vec<FrequentAssistantItem> = frequent
-        .into_iter()
-        .map(|fa| {
-            // Convert files to API format with download URLs
-            let api_files = fa
-                .assistant
-                .files
-                .into_iter()
-                .map(|file| {
-                    let file_capability =
-                        find_file_capability_by_filename(&all_capabilities, &file.filename);
-                    AssistantFile {
-                        id: file.id.to_string(),
-                        filename: file.filename,
-                        download_url: Some(format!("/api/v1beta/files/{}", file.id)),
-                        preview_url: Some(proxied_preview_url_for_file(&file.id)),
-                        file_contents_unavailable_missing_permissions: false,
-                        is_sharepoint_file: file.file_storage_provider_id == SHAREPOINT_PROVIDER_ID,
-                        file_capability,
-                    }
-                })
-                .collect();
+    let mut api_assistants = Vec::with_capacity(frequent.len());
+    for fa in frequent {
+        // Convert files to API format with download URLs
+        let mut api_files = Vec::with_capacity(fa.assistant.files.len());
+        for file in fa.assistant.files {
+            let file_capability =
+                find_file_capability_by_filename(&all_capabilities, &file.filename);
+            let assistant_file = file_info_to_assistant_file(
+                file,
+                file_capability,
+                &app_state,
+                me_user.access_token.as_deref(),
+            )
+            .await?;
+            api_files.push(assistant_file);
+        }
 
-            // Convert the model assistant to the API AssistantWithFiles format
-            let api_assistant = AssistantWithFiles {
-                assistant: Assistant {
-                    id: fa.assistant.id.to_string(),
-                    name: fa.assistant.name,
-                    description: fa.assistant.description,
-                    owner_email: None,
-                    prompt: fa.assistant.prompt,
-                    mcp_server_ids: fa.assistant.mcp_server_ids,
-                    facet_ids: fa.assistant.facet_ids,
-                    default_chat_provider: fa.assistant.default_chat_provider,
-                    enforce_facet_settings: fa.assistant.enforce_facet_settings,
-                    created_at: fa.assistant.created_at,
-                    updated_at: fa.assistant.updated_at,
-                    archived_at: fa.assistant.archived_at,
-                    can_edit: permissions::can_user_edit_assistant(
-                        current_user_id,
-                        &fa.assistant.owner_user_id.to_string(),
-                    ),
-                },
-                files: api_files,
-            };
+        // Convert the model assistant to the API AssistantWithFiles format
+        let api_assistant = AssistantWithFiles {
+            assistant: Assistant {
+                id: fa.assistant.id.to_string(),
+                name: fa.assistant.name,
+                description: fa.assistant.description,
+                owner_email: None,
+                prompt: fa.assistant.prompt,
+                mcp_server_ids: fa.assistant.mcp_server_ids,
+                facet_ids: fa.assistant.facet_ids,
+                default_chat_provider: fa.assistant.default_chat_provider,
+                enforce_facet_settings: fa.assistant.enforce_facet_settings,
+                welcome_message: fa.assistant.welcome_message,
+                pinned_facet_ids: fa.assistant.pinned_facet_ids,
+                category: fa.assistant.category,
+                tags: fa.assistant.tags,
+                visibility: AssistantVisibility::from_stored(&fa.assistant.visibility),
+                featured: fa.assistant.featured,
+                created_at: fa.assistant.created_at,
+                updated_at: fa.assistant.updated_at,
+                archived_at: fa.assistant.archived_at,
+                can_edit: permissions::can_user_edit_assistant(
+                    current_user_id,
+                    &fa.assistant.owner_user_id.to_string(),
+                    edit_granted_assistant_ids.contains(&fa.assistant.id.to_string()),
+                ),
+            },
+            files: api_files,
+        };
 
-            FrequentAssistantItem {
-                assistant: api_assistant,
-                usage_count: fa.usage_count,
-            }
-        })
-        .collect();
+        api_assistants.push(FrequentAssistantItem {
+            assistant: api_assistant,
+            usage_count: fa.usage_count,
+        });
+    }
 
     let response = FrequentAssistantsResponse {
         assistants: api_assistants,
@@ -2877,6 +4320,11 @@ pub struct CreateChatRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(nullable = false)]
     title_by_user_provided: Option<String>,
+    /// Whether to create an ephemeral chat, whose messages are only held in memory and never
+    /// stored. Ephemeral chats expire after a while, are not listed in the recent chats, and have
+    /// no welcome message. Requires `chat.ephemeral_chats.enabled`.
+    #[serde(default)]
+    ephemeral: bool,
 }
 
 /// Response for create_chat endpoint
@@ -2884,6 +4332,10 @@ pub struct CreateChatRequest {
 pub struct CreateChatResponse {
     /// The ID of the newly created chat
     chat_id: String,
+    /// The ID of the assistant's welcome message, if one was added to the chat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    welcome_message_id: Option<String>,
 }
 
 /// Request to update mutable chat fields.
@@ -2916,6 +4368,9 @@ pub struct UpdateChatResponse {
 ///
 /// This endpoint allows creating a new chat without requiring an initial message.
 /// This is useful for scenarios where you want to upload files before sending the first message.
+///
+/// With `ephemeral`, the chat is only held in memory by the backend instance, and the messages
+/// submitted to it are never stored.
 #[utoipa::path(
     post,
     path = "/me/chats",
@@ -2923,6 +4378,8 @@ pub struct UpdateChatResponse {
     responses(
         (status = OK, body = CreateChatResponse, description = "Successfully created a new chat"),
         (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = NOT_FOUND, description = "When an ephemeral chat is requested, but ephemeral chats are not enabled"),
+        (status = TOO_MANY_REQUESTS, description = "When the user already holds the maximum number of ephemeral chats"),
         (status = INTERNAL_SERVER_ERROR, description = "Server error")
     ),
     security(
@@ -2938,17 +4395,27 @@ pub async fn create_chat(
     let CreateChatRequest {
         assistant_id,
         title_by_user_provided,
+        ephemeral,
     } = request;
 
+    if ephemeral
+        && !app_state
+            .feature(FeatureFlag::EphemeralChats.name(), &me_user.id, &me_user.groups)
+            .enabled
+    {
+        tracing::warn!("Ephemeral chats are not enabled");
+        return Err(StatusCode::NOT_FOUND);
+    }
+
     // Parse and validate assistant_id if provided
-    let assistant_id = if let Some(assistant_id_str) = assistant_id {
+    let assistant = if let Some(assistant_id_str) = assistant_id {
         let parsed_id = Uuid::parse_str(&assistant_id_str).map_err(|_| {
             tracing::error!("Invalid assistant ID format: {}", assistant_id_str);
             StatusCode::BAD_REQUEST
         })?;
 
         // Verify user has access to the assistant
-        models::assistant::get_assistant_by_id(
+        let assistant = models::assistant::get_assistant_by_id(
             &app_state.db,
             &policy,
             &me_user.to_subject(),
@@ -2964,11 +4431,44 @@ pub async fn create_chat(
             }
         })?;
 
-        Some(parsed_id)
+        Some(assistant)
     } else {
         None
     };
 
+    if ephemeral {
+        let chat = models::chat::new_ephemeral_chat(
+            &app_state.db,
+            &policy,
+            &me_user.to_subject(),
+            &me_user.id,
+            assistant.as_ref().map(|assistant| &assistant.id),
+            title_by_user_provided,
+        )
+        .await
+        .map_err(log_internal_server_error)?;
+        let chat_id = chat.id;
+        let config = &app_state.config.chat.ephemeral_chats;
+        if !app_state.ephemeral_chats.insert(
+            chat,
+            Duration::from_secs(config.ttl_secs),
+            config.max_chats_per_user,
+        ) {
+            return Err(StatusCode::TOO_MANY_REQUESTS);
+        }
+        start_ephemeral_chat_purge(&app_state);
+        // Only record that the session happened, never anything about its content.
+        tracing::info!(
+            user_id = %me_user.id,
+            chat_id = %chat_id,
+            "Ephemeral chat session started"
+        );
+        return Ok(Json(CreateChatResponse {
+            chat_id: chat_id.to_string(),
+            welcome_message_id: None,
+        }));
+    }
+
     // Create a new chat
     let (chat, chat_status) = get_or_create_chat(
         &app_state.db,
@@ -2976,86 +4476,623 @@ pub async fn create_chat(
         &me_user.to_subject(),
         None,
         &me_user.id,
-        assistant_id.as_ref(),
+        assistant.as_ref().map(|assistant| &assistant.id),
         title_by_user_provided,
     )
     .await
     .map_err(log_internal_server_error)?;
 
+    let mut welcome_message_id = None;
+
     // Invalidate policy engine if a new chat was created
     if chat_status == models::chat::ChatCreationStatus::Created {
         app_state.global_policy_engine.invalidate_data().await;
+        app_state
+            .user_events
+            .publish(&me_user.id, UserEvent::ChatCreated { chat_id: chat.id });
+
+        let welcome_message = assistant
+            .as_ref()
+            .and_then(|assistant| assistant.welcome_message.as_deref())
+            .filter(|message| !message.trim().is_empty());
+        if let Some(welcome_message) = welcome_message {
+            // The new chat has to be in the policy data before we can write to it.
+            app_state
+                .global_policy_engine
+                .rebuild_data_if_needed(&app_state.db, &app_state.config)
+                .await
+                .map_err(log_internal_server_error)?;
+
+            let message = models::message::submit_welcome_message(
+                &app_state.db,
+                &policy,
+                &me_user.to_subject(),
+                &chat.id,
+                welcome_message,
+            )
+            .await
+            .map_err(log_internal_server_error)?;
+            welcome_message_id = Some(message.id.to_string());
+        }
     }
 
     Ok(Json(CreateChatResponse {
         chat_id: chat.id.to_string(),
+        welcome_message_id,
     }))
 }
 
-/// Update mutable fields on a chat.
+/// Get a single chat.
 ///
-/// Currently supports updating only `title_by_user_provided`.
+/// Returns the chat in the same shape as the entries of `/me/recent_chats`, so that clients can
+/// show the metadata of chats outside of the loaded recent chats page. Archived chats and chats
+/// shared with the user are returned as well.
 #[utoipa::path(
-    put,
+    get,
     path = "/me/chats/{chat_id}",
     params(
-        ("chat_id" = String, Path, description = "The ID of the chat to update")
+        ("chat_id" = String, Path, description = "The ID of the chat")
     ),
-    request_body = UpdateChatRequest,
     responses(
-        (status = OK, body = UpdateChatResponse, description = "Successfully updated the chat"),
+        (status = OK, body = ChatDetail, description = "Successfully retrieved the chat"),
         (status = BAD_REQUEST, description = "Invalid chat ID format"),
-        (status = NOT_FOUND, description = "Chat not found"),
-        (status = UNAUTHORIZED, description = "User not authorized to update this chat"),
-        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+        (status = NOT_FOUND, description = "When the chat does not exist or is not accessible"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error while retrieving the chat")
     ),
     security(
         ("bearer_auth" = [])
     )
 )]
-pub async fn update_chat(
+pub async fn chat_detail(
     State(app_state): State<AppState>,
     Extension(me_user): Extension<MeProfile>,
     Extension(policy): Extension<PolicyEngine>,
     Path(chat_id): Path<String>,
-    Json(request): Json<UpdateChatRequest>,
-) -> Result<Json<UpdateChatResponse>, StatusCode> {
+) -> Result<Json<ChatDetail>, StatusCode> {
     let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
 
     policy
         .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
         .await?;
 
-    let updated_chat = update_chat_title_by_user_provided(
+    let subject = me_user.to_subject();
+    let chat = models::chat::get_recent_chat_by_id(
         &app_state.db,
         &policy,
-        &me_user.to_subject(),
+        &subject,
+        &me_user.id,
         &chat_id,
-        request.title_by_user_provided,
+        app_state.config.generation_status.stale_after_secs,
     )
     .await
-    .map_err(|e| {
-        if e.to_string().contains("not found") {
-            StatusCode::NOT_FOUND
-        } else {
-            log_internal_server_error(e)
-        }
-    })?;
+    .map_err(log_internal_server_error)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+    let chat_model = chats::Entity::find_by_id(chat_id)
+        .one(&app_state.db)
+        .await
+        .wrap_err("Failed to get chat")
+        .map_err(log_internal_server_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let assistant_snapshot =
+        parse_assistant_snapshot(&chat_model).map_err(log_internal_server_error)?;
+    let frozen = is_assistant_config_frozen(
+        &chat_model,
+        app_state.config.assistants.freeze_assistant_config,
+    );
 
-    let title_resolved = resolve_chat_display_name(
-        updated_chat.title_by_user_provided.as_deref(),
-        updated_chat.title_by_summary.as_deref(),
+    let file_uploads = models::file_upload::get_chat_file_uploads_with_urls_and_token(
+        &app_state.db,
+        &policy,
+        &subject,
+        &chat_id,
+        &app_state.file_storage_providers,
+        me_user.access_token.as_deref(),
+    )
+    .await
+    .map_err(log_internal_server_error)?;
+
+    let edit_granted_chat_ids =
+        models::share_grant::get_edit_granted_resource_ids(&app_state.db, &subject, "chat")
+            .await
+            .map_err(log_internal_server_error)?;
+    let can_edit = permissions::can_user_edit_chat(
+        &me_user.id,
+        &chat.owner_user_id,
+        edit_granted_chat_ids.contains(&chat.id),
     );
 
-    Ok(Json(UpdateChatResponse {
-        chat_id: updated_chat.id.to_string(),
-        title_by_summary: updated_chat.title_by_summary,
-        title_by_user_provided: updated_chat.title_by_user_provided,
-        title_resolved,
+    let available_models = app_state
+        .available_models(&policy, &subject, &me_user.groups)
+        .await
+        .map_err(log_internal_server_error)?;
+    let all_capabilities = file_capabilities_for_chat_providers(
+        &app_state.config,
+        available_models
+            .iter()
+            .map(|model| model.chat_provider_id.as_str()),
+    );
+
+    let chat = extend_recent_chat_to_api_model(
+        chat,
+        file_uploads.iter().map(|upload| upload.id),
+        can_edit,
+        &available_models,
+        &app_state.config,
+    );
+    let mut processing_errors = HashMap::new();
+    for file_upload in &file_uploads {
+        if let Some(error) = cached_file_processing_error(&app_state, &file_upload.id).await {
+            processing_errors.insert(file_upload.id, error.kind());
+        }
+    }
+    let file_upload_details = file_uploads
+        .into_iter()
+        .map(|file_upload| FileUploadItem {
+            processing_error: processing_errors.get(&file_upload.id).copied(),
+            id: file_upload.id.to_string(),
+            file_capability: find_file_capability_by_filename(
+                &all_capabilities,
+                &file_upload.filename,
+            ),
+            filename: file_upload.filename,
+            download_url: file_upload.download_url,
+            url_kind: file_upload.url_kind,
+            preview_url: file_upload.preview_url,
+            file_contents_unavailable_missing_permissions: file_upload
+                .file_contents_unavailable_missing_permissions,
+            is_sharepoint_file: file_upload.file_storage_provider_id == SHAREPOINT_PROVIDER_ID,
+            audio_transcription: file_upload.audio_transcription,
+            storage_status: file_upload.storage_status,
+        })
+        .collect();
+
+    Ok(Json(ChatDetail {
+        chat,
+        file_upload_details,
+        assistant_snapshot,
+        frozen,
     }))
 }
 
-/// Get a single file by its ID
+/// Update mutable fields on a chat.
+///
+/// Currently supports updating only `title_by_user_provided`.
+#[utoipa::path(
+    put,
+    path = "/me/chats/{chat_id}",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat to update")
+    ),
+    request_body = UpdateChatRequest,
+    responses(
+        (status = OK, body = UpdateChatResponse, description = "Successfully updated the chat"),
+        (status = BAD_REQUEST, description = "Invalid chat ID format"),
+        (status = NOT_FOUND, description = "Chat not found"),
+        (status = UNAUTHORIZED, description = "User not authorized to update this chat"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_chat(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path(chat_id): Path<String>,
+    Json(request): Json<UpdateChatRequest>,
+) -> Result<Json<UpdateChatResponse>, StatusCode> {
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+
+    let updated_chat = update_chat_title_by_user_provided(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &chat_id,
+        request.title_by_user_provided,
+    )
+    .await
+    .map_err(|e| {
+        if e.to_string().contains("not found") {
+            StatusCode::NOT_FOUND
+        } else {
+            log_internal_server_error(e)
+        }
+    })?;
+
+    let title_resolved = resolve_chat_display_name(
+        updated_chat.title_by_user_provided.as_deref(),
+        updated_chat.title_by_summary.as_deref(),
+    );
+
+    Ok(Json(UpdateChatResponse {
+        chat_id: updated_chat.id.to_string(),
+        title_by_summary: updated_chat.title_by_summary,
+        title_by_user_provided: updated_chat.title_by_user_provided,
+        title_resolved,
+    }))
+}
+
+/// Request to set the custom instruction of a chat.
+#[derive(Deserialize, ToSchema, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct UpdateChatCustomInstructionRequest {
+    /// Instruction that is added to the prompt of every generation of the chat, e.g. "Answer in
+    /// bullet points". At most 2000 characters. `null`, or an instruction that only consists of
+    /// whitespace, removes a previously set instruction.
+    custom_instruction: Option<String>,
+}
+
+/// Custom instruction of a chat.
+#[derive(Deserialize, ToSchema, Serialize)]
+pub struct ChatCustomInstruction {
+    /// The ID of the chat
+    chat_id: String,
+    /// Instruction that is added to the prompt of every generation of the chat
+    custom_instruction: Option<String>,
+}
+
+/// Set the custom instruction of a chat.
+///
+/// The instruction is added to the prompt of every following generation of the chat, after the
+/// assistant prompt and before the chat history, so changes and removals apply to the next
+/// generation. Only users who can edit the chat may set it. With `moderation.enabled`, the
+/// instruction is moderated before it is saved.
+#[utoipa::path(
+    put,
+    path = "/me/chats/{chat_id}/custom-instruction",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat")
+    ),
+    request_body = UpdateChatCustomInstructionRequest,
+    responses(
+        (status = OK, body = ChatCustomInstruction, description = "Successfully updated the custom instruction"),
+        (status = BAD_REQUEST, description = "Invalid chat ID format"),
+        (status = NOT_FOUND, description = "Chat not found"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user can't edit the chat"),
+        (status = UNPROCESSABLE_ENTITY, description = "When the instruction is longer than 2000 characters, or was blocked by the content moderation"),
+        (status = SERVICE_UNAVAILABLE, description = "When the content moderation is unavailable"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_chat_custom_instruction_endpoint(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path(chat_id): Path<String>,
+    Json(request): Json<UpdateChatCustomInstructionRequest>,
+) -> Result<Json<ChatCustomInstruction>, StatusCode> {
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let custom_instruction = request
+        .custom_instruction
+        .map(|instruction| instruction.trim().to_string())
+        .filter(|instruction| !instruction.is_empty());
+    if custom_instruction
+        .as_ref()
+        .is_some_and(|instruction| instruction.chars().count() > MAX_CHAT_CUSTOM_INSTRUCTION_CHARS)
+    {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+    // Authorized before the moderation, so the moderation service is only called for chats the
+    // user can edit
+    authorize!(
+        policy,
+        &me_user.to_subject(),
+        &Resource::Chat(chat_id.to_string()),
+        Action::Update
+    )
+    .map_err(|_| StatusCode::FORBIDDEN)?;
+
+    if let Some(instruction) = &custom_instruction {
+        match moderate_text(&app_state.config.moderation, instruction).await {
+            ModerationOutcome::Moderated(result) if result.blocked => {
+                tracing::info!(
+                    chat_id = %chat_id,
+                    categories = ?result.categories,
+                    "Custom instruction blocked by moderation"
+                );
+                return Err(StatusCode::UNPROCESSABLE_ENTITY);
+            }
+            ModerationOutcome::Unavailable => return Err(StatusCode::SERVICE_UNAVAILABLE),
+            _ => {}
+        }
+    }
+
+    let updated_chat = update_chat_custom_instruction(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &chat_id,
+        custom_instruction,
+    )
+    .await
+    .map_err(|e| {
+        if e.to_string().contains("not found") {
+            StatusCode::NOT_FOUND
+        } else if e.to_string().contains("not authorized") {
+            StatusCode::FORBIDDEN
+        } else {
+            log_internal_server_error(e)
+        }
+    })?;
+
+    Ok(Json(ChatCustomInstruction {
+        chat_id: updated_chat.id.to_string(),
+        custom_instruction: updated_chat.custom_instruction,
+    }))
+}
+
+/// Request to set the response language of a chat.
+#[derive(Deserialize, ToSchema, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct UpdateChatResponseLanguageRequest {
+    /// ISO 639-1 code of the language the assistant should respond in, e.g. "de". Language tags
+    /// like "de-AT" are reduced to their primary subtag. `null`, or an empty string, removes a
+    /// previously set language.
+    #[schema(example = "de")]
+    response_language: Option<String>,
+}
+
+/// Response language of a chat.
+#[derive(Deserialize, ToSchema, Serialize)]
+pub struct ChatResponseLanguage {
+    /// The ID of the chat
+    chat_id: String,
+    /// ISO 639-1 code of the language the assistant responds in
+    response_language: Option<String>,
+}
+
+/// Set the response language of a chat.
+///
+/// The language is used for every following generation of the chat, unless the message request
+/// asks for a `response_language` or the latest user message was detected to be written in
+/// another language (see `i18n.message_language_detection`). Without it, the preferred language
+/// of the user is used. Only users who can edit the chat may set it.
+#[utoipa::path(
+    put,
+    path = "/me/chats/{chat_id}/response-language",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat")
+    ),
+    request_body = UpdateChatResponseLanguageRequest,
+    responses(
+        (status = OK, body = ChatResponseLanguage, description = "Successfully updated the response language"),
+        (status = BAD_REQUEST, description = "Invalid chat ID format"),
+        (status = NOT_FOUND, description = "Chat not found"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user can't edit the chat"),
+        (status = UNPROCESSABLE_ENTITY, description = "When the response language is not a language code"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_chat_response_language_endpoint(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path(chat_id): Path<String>,
+    Json(request): Json<UpdateChatResponseLanguageRequest>,
+) -> Result<Json<ChatResponseLanguage>, StatusCode> {
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let response_language = request
+        .response_language
+        .filter(|language| !language.trim().is_empty())
+        .map(|language| parse_language_code(&language).ok_or(StatusCode::UNPROCESSABLE_ENTITY))
+        .transpose()?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+
+    let updated_chat = update_chat_response_language(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &chat_id,
+        response_language,
+    )
+    .await
+    .map_err(|e| {
+        if e.to_string().contains("not found") {
+            StatusCode::NOT_FOUND
+        } else if e.to_string().contains("not authorized") {
+            StatusCode::FORBIDDEN
+        } else {
+            log_internal_server_error(e)
+        }
+    })?;
+
+    Ok(Json(ChatResponseLanguage {
+        chat_id: updated_chat.id.to_string(),
+        response_language: updated_chat.response_language,
+    }))
+}
+
+/// Request to update the read state of a chat.
+#[derive(Deserialize, ToSchema, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct UpdateChatReadStateRequest {
+    /// The ID of the last message of the chat that the user has read
+    last_read_message_id: String,
+}
+
+/// Read state of a chat for the current user.
+#[derive(Deserialize, ToSchema, Serialize)]
+pub struct ChatReadState {
+    /// The ID of the chat
+    chat_id: String,
+    /// The ID of the last message of the chat that the user has read
+    last_read_message_id: String,
+    /// Number of assistant messages in the active thread that are newer than the last read message
+    unread_count: i64,
+    /// When the read state was last updated
+    updated_at: DateTime<FixedOffset>,
+}
+
+/// Mark a chat as read up to a message.
+///
+/// Read states are tracked per user, so they are shared across the user's devices and
+/// independent between the users of a shared chat. Submitting messages does not update the read
+/// state; clients mark messages as read once they have been displayed.
+#[utoipa::path(
+    put,
+    path = "/me/chats/{chat_id}/read-state",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat")
+    ),
+    request_body = UpdateChatReadStateRequest,
+    responses(
+        (status = OK, body = ChatReadState, description = "Successfully updated the read state"),
+        (status = BAD_REQUEST, description = "Invalid chat or message ID format"),
+        (status = NOT_FOUND, description = "Chat or message not found"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_chat_read_state(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path(chat_id): Path<String>,
+    Json(request): Json<UpdateChatReadStateRequest>,
+) -> Result<Json<ChatReadState>, StatusCode> {
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let last_read_message_id =
+        Uuid::parse_str(&request.last_read_message_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let user_id = Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+
+    let read_state = models::chat_read_state::set_chat_read_state(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &user_id,
+        &chat_id,
+        &last_read_message_id,
+    )
+    .await
+    .map_err(|e| {
+        let s = e.to_string();
+        // Chats the user can't read are reported as not found, so their existence is not leaked.
+        if s.contains("not found") || s.contains("Access denied") || s.contains("not authorized") {
+            StatusCode::NOT_FOUND
+        } else {
+            log_internal_server_error(e)
+        }
+    })?;
+    let unread_count =
+        models::chat_read_state::count_unread_messages(&app_state.db, &user_id, &chat_id)
+            .await
+            .map_err(log_internal_server_error)?;
+
+    Ok(Json(ChatReadState {
+        chat_id: read_state.chat_id.to_string(),
+        last_read_message_id: read_state.last_read_message_id.to_string(),
+        unread_count,
+        updated_at: read_state.updated_at,
+    }))
+}
+
+/// Response of refreshing the assistant snapshot of a chat
+#[derive(Debug, ToSchema, Serialize)]
+pub struct RefreshAssistantSnapshotResponse {
+    /// The ID of the chat
+    chat_id: String,
+    /// The new snapshot of the assistant configuration
+    assistant_snapshot: AssistantSnapshot,
+    /// Whether the chat uses the snapshot instead of the live assistant
+    frozen: bool,
+}
+
+/// Take a new snapshot of the assistant configuration of a chat.
+///
+/// Replaces the snapshot taken when the chat was created with the current configuration of its
+/// assistant. With `assistants.freeze_assistant_config` enabled, the chat uses the new
+/// configuration from then on. Only the owner of the chat can refresh the snapshot.
+#[utoipa::path(
+    post,
+    path = "/me/chats/{chat_id}/refresh-assistant-snapshot",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat")
+    ),
+    responses(
+        (status = OK, body = RefreshAssistantSnapshotResponse, description = "Successfully refreshed the snapshot"),
+        (status = BAD_REQUEST, description = "Invalid chat ID format, or the chat has no assistant"),
+        (status = FORBIDDEN, description = "When the user is not the owner of the chat"),
+        (status = NOT_FOUND, description = "When the chat or its assistant does not exist"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn refresh_chat_assistant_snapshot(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path(chat_id): Path<String>,
+) -> Result<Json<RefreshAssistantSnapshotResponse>, StatusCode> {
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+
+    let chat = models::chat::refresh_assistant_snapshot(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &chat_id,
+    )
+    .await
+    .map_err(|e| {
+        let s = e.to_string();
+        if s.contains("not found") {
+            StatusCode::NOT_FOUND
+        } else if s.contains("Access denied") || s.contains("not authorized") {
+            StatusCode::FORBIDDEN
+        } else if s.contains("has no assistant") {
+            StatusCode::BAD_REQUEST
+        } else {
+            log_internal_server_error(e)
+        }
+    })?;
+    let assistant_snapshot = parse_assistant_snapshot(&chat)
+        .map_err(log_internal_server_error)?
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(RefreshAssistantSnapshotResponse {
+        chat_id: chat.id.to_string(),
+        assistant_snapshot,
+        frozen: is_assistant_config_frozen(
+            &chat,
+            app_state.config.assistants.freeze_assistant_config,
+        ),
+    }))
+}
+
+/// Get a single file by its ID
 ///
 /// This endpoint retrieves information about a specific file by its ID.
 #[utoipa::path(
@@ -3068,40 +5105,23 @@ pub async fn update_chat(
         (status = OK, body = FileUploadItem, description = "Successfully retrieved the file"),
         (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
         (status = NOT_FOUND, description = "When the file doesn't exist or doesn't belong to the user"),
+        (status = GONE, description = "When the contents of the file were deleted"),
         (status = INTERNAL_SERVER_ERROR, description = "Server error")
     ),
     security(
         ("bearer_auth" = [])
     )
 )]
-pub async fn get_file(
-    State(app_state): State<AppState>,
-    Extension(me_user): Extension<MeProfile>,
-    Extension(policy): Extension<PolicyEngine>,
-    Path(file_id): Path<String>,
-) -> Result<Json<FileUploadItem>, StatusCode> {
-    // Parse the file ID
-    let file_id = Uuid::parse_str(&file_id).map_err(|_| StatusCode::BAD_REQUEST)?;
-
-    // Determine if any available model supports image understanding
-    let available_models = app_state
-        .available_models(&policy, &me_user.to_subject(), &me_user.groups)
-        .await
-        .map_err(log_internal_server_error)?;
-    let (supports_image_understanding, supports_audio_input) =
-        available_models
-            .iter()
-            .fold((false, false), |(image, audio), model| {
-                let config = app_state.config.get_chat_provider(&model.chat_provider_id);
-                (
-                    image || config.model_capabilities.supports_image_understanding,
-                    audio || config.model_capabilities.supports_audio_input,
-                )
-            });
+pub async fn get_file(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path(file_id): Path<String>,
+) -> Result<Json<FileUploadItem>, StatusCode> {
+    // Parse the file ID
+    let file_id = Uuid::parse_str(&file_id).map_err(|_| StatusCode::BAD_REQUEST)?;
 
-    // Get all file capabilities for this user
-    let all_capabilities =
-        get_file_capabilities(supports_image_understanding, supports_audio_input);
+    let all_capabilities = user_file_capabilities(&app_state, &policy, &me_user).await?;
 
     // Get the file upload record with its download URL
     let file_upload = models::file_upload::get_file_upload_with_url_and_token(
@@ -3122,22 +5142,31 @@ pub async fn get_file(
             StatusCode::INTERNAL_SERVER_ERROR
         }
     })?;
+    if file_upload.storage_status == FileStorageStatus::Deleted {
+        return Err(StatusCode::GONE);
+    }
 
     // Evaluate the file capability for this file
     let file_capability =
         find_file_capability_by_filename(&all_capabilities, &file_upload.filename);
+    let processing_error = cached_file_processing_error(&app_state, &file_upload.id)
+        .await
+        .map(|error| error.kind());
 
     // Convert to FileUploadItem and return
     Ok(Json(FileUploadItem {
         id: file_upload.id.to_string(),
         filename: file_upload.filename,
         download_url: file_upload.download_url,
+        url_kind: file_upload.url_kind,
         preview_url: file_upload.preview_url,
         file_contents_unavailable_missing_permissions: file_upload
             .file_contents_unavailable_missing_permissions,
         is_sharepoint_file: file_upload.file_storage_provider_id == SHAREPOINT_PROVIDER_ID,
         file_capability,
         audio_transcription: file_upload.audio_transcription,
+        storage_status: file_upload.storage_status,
+        processing_error,
     }))
 }
 
@@ -3211,6 +5240,7 @@ pub(super) fn effective_upload_content_type(
         (status = OK, description = "Successfully retrieved the file preview"),
         (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
         (status = NOT_FOUND, description = "When the file doesn't exist or doesn't belong to the user"),
+        (status = GONE, description = "When the contents of the file were deleted"),
         (status = INTERNAL_SERVER_ERROR, description = "Server error")
     ),
     security(
@@ -3239,6 +5269,9 @@ pub async fn get_file_preview(
             StatusCode::INTERNAL_SERVER_ERROR
         }
     })?;
+    if FileStorageStatus::of(&file_upload) == FileStorageStatus::Deleted {
+        return Err(StatusCode::GONE);
+    }
 
     let file_storage = app_state
         .file_storage_providers
@@ -3286,6 +5319,137 @@ pub async fn get_file_preview(
     Ok((headers, bytes))
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DeleteFileQuery {
+    /// Delete the contents of the file even though it is still referenced
+    #[serde(default)]
+    force: bool,
+}
+
+/// Response when a file can't be deleted because it is still referenced
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FileStillReferencedError {
+    pub error: String,
+    /// The messages and assistants that reference the file
+    pub references: Vec<FileUploadReference>,
+}
+
+/// Delete a file
+///
+/// Files that are not referenced by any message or assistant are removed together with their
+/// contents. Files that are still referenced are only deleted with `force=true`: the references
+/// are kept, but the contents of the file are removed and it is marked as deleted. Afterwards,
+/// retrieving the file returns 410, and generations see a placeholder instead of its contents.
+#[utoipa::path(
+    delete,
+    path = "/me/files/{file_id}",
+    params(
+        ("file_id" = String, Path, description = "The ID of the file to delete"),
+        ("force" = Option<bool>, Query, description = "Delete the contents of the file even though it is still referenced"),
+    ),
+    responses(
+        (status = NO_CONTENT, description = "The file was deleted"),
+        (status = BAD_REQUEST, description = "Invalid file ID format"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the file doesn't belong to the user"),
+        (status = NOT_FOUND, description = "When the file doesn't exist or the user can't access it"),
+        (status = CONFLICT, body = FileStillReferencedError, description = "When the file is still referenced and `force` is not set"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_file(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path(file_id): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<DeleteFileQuery>,
+) -> Result<axum::response::Response, StatusCode> {
+    let file_id = Uuid::parse_str(&file_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let actor_user_id =
+        Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let file_upload = models::file_upload::get_file_upload_by_id(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        &file_id,
+    )
+    .await
+    .map_err(|e| {
+        if e.to_string().contains("not found") || e.to_string().contains("access denied") {
+            StatusCode::NOT_FOUND
+        } else {
+            log_internal_server_error(e)
+        }
+    })?;
+    if file_upload.owner_user_id != me_user.id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let references = models::file_upload::get_file_upload_references(&app_state.db, &file_id)
+        .await
+        .map_err(log_internal_server_error)?;
+    if !references.is_empty() && !params.force {
+        return Ok((
+            StatusCode::CONFLICT,
+            Json(FileStillReferencedError {
+                error: "The file is still referenced".to_string(),
+                references,
+            }),
+        )
+            .into_response());
+    }
+
+    let file_storage = app_state
+        .file_storage_providers
+        .get(&file_upload.file_storage_provider_id)
+        .ok_or_else(|| {
+            tracing::error!(
+                "File storage provider '{}' not found for deletion",
+                file_upload.file_storage_provider_id
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    // The row is updated first, so that a failed update never leaves it pointing to a deleted
+    // object.
+    models::file_upload::delete_file_upload(
+        &app_state.db,
+        &actor_user_id,
+        &file_upload,
+        &references,
+    )
+    .await
+    .map_err(log_internal_server_error)?;
+    app_state.global_policy_engine.invalidate_data().await;
+    purge_file_cached(&app_state, &file_id).await;
+
+    // Sharepoint files are only linked, so their contents are left alone. The deletion is already
+    // committed, so a failure only leaves an orphaned object behind.
+    if !file_storage.is_sharepoint()
+        && let Err(err) = file_storage
+            .delete_file(&file_upload.file_storage_path)
+            .await
+    {
+        tracing::warn!(
+            file_id = %file_id,
+            error = %err,
+            "Failed to delete the object of a deleted file upload"
+        );
+    }
+
+    tracing::info!(
+        file_id = %file_id,
+        forced = !references.is_empty(),
+        references = references.len(),
+        "Deleted file upload"
+    );
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
 /// Request to archive a chat
 #[derive(Deserialize, ToSchema, Serialize)]
 pub struct ArchiveChatRequest {
@@ -3360,6 +5524,16 @@ pub async fn archive_chat_endpoint(
         })?;
 
     app_state.global_policy_engine.invalidate_data().await;
+    app_state
+        .mcp_servers
+        .evict_chat_sessions(updated_chat.id)
+        .await;
+    app_state.user_events.publish(
+        &updated_chat.owner_user_id,
+        UserEvent::ChatArchived {
+            chat_id: updated_chat.id,
+        },
+    );
 
     // Check if archived_at is set (it should be)
     let archived_at = updated_chat.archived_at.ok_or_else(|| {
@@ -3405,29 +5579,19 @@ pub async fn archive_all_chats_endpoint(
     }))
 }
 
-/// Optimize a prompt using the configured prompt optimizer.
-#[utoipa::path(
-    post,
-    path = "/prompt-optimizer",
-    request_body = PromptOptimizerRequest,
-    responses(
-        (status = OK, body = PromptOptimizerResponse, description = "Successfully optimized the prompt"),
-        (status = BAD_REQUEST, description = "Invalid prompt"),
-        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
-        (status = NOT_FOUND, description = "Prompt optimizer is not enabled"),
-        (status = INTERNAL_SERVER_ERROR, description = "Server error")
-    ),
-    security(
-        ("bearer_auth" = [])
-    )
-)]
-pub async fn prompt_optimizer(
-    State(app_state): State<AppState>,
-    Extension(me_user): Extension<MeProfile>,
-    Extension(policy): Extension<PolicyEngine>,
-    Json(request): Json<PromptOptimizerRequest>,
-) -> Result<Json<PromptOptimizerResponse>, StatusCode> {
-    if !app_state.config.prompt_optimizer.enabled {
+/// Validate and authorize a prompt optimizer request, and build the chat request for it.
+///
+/// Shared by the non-streaming and the streaming prompt optimizer endpoints.
+pub(crate) async fn prepare_prompt_optimizer_chat_request(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    me_user: &MeProfile,
+    request: PromptOptimizerRequest,
+) -> Result<(ChatRequest, ChatOptions), StatusCode> {
+    if !app_state
+        .feature(FeatureFlag::PromptOptimizer.name(), &me_user.id, &me_user.groups)
+        .enabled
+    {
         tracing::warn!("Prompt optimizer is not enabled");
         return Err(StatusCode::NOT_FOUND);
     }
@@ -3440,9 +5604,10 @@ pub async fn prompt_optimizer(
         .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
         .await?;
 
+    let subject = me_user.to_subject();
     authorize!(
         policy,
-        &me_user.to_subject(),
+        &subject,
         &Resource::PromptOptimizerSingleton,
         Action::Create
     )
@@ -3451,6 +5616,23 @@ pub async fn prompt_optimizer(
         StatusCode::UNAUTHORIZED
     })?;
 
+    let conversation_context = build_conversation_context(
+        app_state,
+        policy,
+        &subject,
+        request.chat_id.as_ref(),
+        request.previous_message_id.as_ref(),
+    )
+    .await
+    .map_err(|e| {
+        let s = e.to_string();
+        if s.contains("not found") || s.contains("Access denied") || s.contains("not authorized") {
+            StatusCode::NOT_FOUND
+        } else {
+            log_internal_server_error(e)
+        }
+    })?;
+
     let prompt_spec = app_state
         .config
         .prompt_optimizer
@@ -3484,8 +5666,42 @@ pub async fn prompt_optimizer(
     );
     let mut chat_request: ChatRequest = Default::default();
     chat_request = chat_request.append_message(GenAiChatMessage::system(system_prompt));
+    if let Some(conversation_context) = conversation_context {
+        chat_request = chat_request.append_message(GenAiChatMessage::system(format!(
+            "The prompt will be sent as the next message in an ongoing conversation. \
+             Recent messages of that conversation:\n\n{conversation_context}"
+        )));
+    }
     chat_request = chat_request.append_message(GenAiChatMessage::user(request.prompt));
 
+    Ok((chat_request, chat_options))
+}
+
+/// Optimize a prompt using the configured prompt optimizer.
+#[utoipa::path(
+    post,
+    path = "/prompt-optimizer",
+    request_body = PromptOptimizerRequest,
+    responses(
+        (status = OK, body = PromptOptimizerResponse, description = "Successfully optimized the prompt"),
+        (status = BAD_REQUEST, description = "Invalid prompt"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = NOT_FOUND, description = "Prompt optimizer is not enabled, or the chat or message was not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn prompt_optimizer(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Json(request): Json<PromptOptimizerRequest>,
+) -> Result<Json<PromptOptimizerResponse>, StatusCode> {
+    let (chat_request, chat_options) =
+        prepare_prompt_optimizer_chat_request(&app_state, &policy, &me_user, request).await?;
+
     let optimized_completion = app_state
         .genai_for_prompt_optimizer()
         .map_err(log_internal_server_error)?
@@ -3510,7 +5726,9 @@ pub async fn prompt_optimizer(
 /// Get available chat models for the user
 ///
 /// This endpoint returns all available chat models (providers) that the user can use.
-/// Each model includes the provider ID and display name.
+/// Each model includes the provider ID and display name, and the usage of the user against the
+/// quota of the model, if it has one. The model that `/me/models/recommend` ranks first without
+/// any context is marked as `recommended`.
 #[utoipa::path(
     get,
     path = "/me/models",
@@ -3528,27 +5746,189 @@ pub async fn available_models(
     Extension(me_user): Extension<MeProfile>,
     Extension(policy): Extension<PolicyEngine>,
 ) -> Result<Json<Vec<ChatModel>>, StatusCode> {
-    let models = app_state
+    let available_models = app_state
         .available_models(&policy, &me_user.to_subject(), &me_user.groups)
         .await
-        .map_err(log_internal_server_error)?
+        .map_err(log_internal_server_error)?;
+
+    let recommended_chat_provider_id = app_state
+        .recommend_models(
+            &available_models,
+            &me_user.groups,
+            &ModelRequirements::default(),
+            None,
+        )
         .into_iter()
-        .map(|model| ChatModel {
+        .next()
+        .map(|recommendation| recommendation.chat_provider_id);
+
+    let user_id = Uuid::parse_str(&me_user.id).ok();
+    let now = Utc::now();
+    let mut models = Vec::with_capacity(available_models.len());
+    for model in available_models {
+        let recommended = recommended_chat_provider_id.as_ref() == Some(&model.chat_provider_id);
+        let quota = match user_id {
+            Some(user_id) if app_state.config.any_chat_provider_has_user_quota() => {
+                chat_provider_quotas::user_quota_status(
+                    &app_state.db,
+                    &app_state.config,
+                    &user_id,
+                    &me_user.groups,
+                    &model.chat_provider_id,
+                    now,
+                )
+                .await
+                .map_err(log_internal_server_error)?
+            }
+            _ => None,
+        };
+        models.push(ChatModel {
             chat_provider_id: model.chat_provider_id,
             model_display_name: model.model_display_name,
             model_description: model.model_description,
             model_icon: model.model_icon,
-        })
-        .collect();
+            max_input_files: model.max_input_files,
+            supported_input_modalities: model.supported_input_modalities,
+            available: true,
+            quota,
+            recommended,
+        });
+    }
 
     Ok(Json(models))
 }
 
+/// Recommend chat models for a chat
+///
+/// Ranks the chat models available to the user by how well they fit the chat: models that can't
+/// process its attachments or tools are left out, and the others are scored by their recent
+/// health, the default model of the groups of the user and the model that answered last in the
+/// chat. Models with the same score are ranked in the configured priority order. Each model
+/// comes with human-readable reasons for its score.
+#[utoipa::path(
+    post,
+    path = "/me/models/recommend",
+    request_body = ModelRecommendationRequest,
+    responses(
+        (status = OK, body = Vec<ModelRecommendation>, description = "The recommended models, best first"),
+        (status = BAD_REQUEST, description = "When an input file can't be loaded"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = NOT_FOUND, description = "When the chat does not exist or is not accessible"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn recommend_models(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Json(request): Json<ModelRecommendationRequest>,
+) -> Result<Json<Vec<ModelRecommendation>>, StatusCode> {
+    let subject = me_user.to_subject();
+    let mut requirements = ModelRequirements {
+        needs_tools: request.needs_tools,
+        ..Default::default()
+    };
+    if request.needs_vision {
+        requirements.input_modalities.push(InputModality::Image);
+    }
+
+    let mut last_chat_provider_id = None;
+    if let Some(chat_id) = request.chat_id {
+        policy
+            .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+            .await?;
+        let chat = chats::Entity::find_by_id(chat_id)
+            .one(&app_state.db)
+            .await
+            .wrap_err("Failed to get chat")
+            .map_err(log_internal_server_error)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+        authorize!(
+            policy,
+            &subject,
+            &Resource::Chat(chat_id.to_string()),
+            Action::Read
+        )
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+        let file_uploads =
+            models::file_upload::get_chat_file_uploads(&app_state.db, &policy, &subject, &chat_id)
+                .await
+                .map_err(log_internal_server_error)?;
+        requirements.input_modalities.extend(
+            file_uploads
+                .iter()
+                .map(|file_upload| input_modality_for_filename(&file_upload.filename)),
+        );
+
+        let assistant = models::chat::get_chat_assistant_configuration(
+            &app_state.db,
+            &policy,
+            &subject,
+            &chat,
+            app_state.config.assistants.freeze_assistant_config,
+        )
+        .await
+        .map_err(log_internal_server_error)?;
+        if assistant
+            .and_then(|assistant| assistant.mcp_server_ids)
+            .is_some_and(|mcp_server_ids| !mcp_server_ids.is_empty())
+        {
+            requirements.needs_tools = true;
+        }
+
+        last_chat_provider_id = models::chat::get_last_chat_provider_id(&app_state.db, &chat_id)
+            .await
+            .map_err(log_internal_server_error)?
+            .map(|provider_id| {
+                app_state
+                    .config
+                    .resolve_chat_provider_alias(&provider_id)
+                    .to_string()
+            });
+    }
+
+    let input_file_ids = request.input_files_ids.unwrap_or_default();
+    requirements.input_files = input_file_ids.len();
+    for file_upload_id in &input_file_ids {
+        let file_upload = models::file_upload::get_file_upload_by_id(
+            &app_state.db,
+            &policy,
+            &subject,
+            file_upload_id,
+        )
+        .await
+        .map_err(|e| {
+            tracing::warn!("Unable to load input file {}: {}", file_upload_id, e);
+            StatusCode::BAD_REQUEST
+        })?;
+        requirements
+            .input_modalities
+            .push(input_modality_for_filename(&file_upload.filename));
+    }
+
+    let available_models = app_state
+        .available_models(&policy, &subject, &me_user.groups)
+        .await
+        .map_err(log_internal_server_error)?;
+    Ok(Json(app_state.recommend_models(
+        &available_models,
+        &me_user.groups,
+        &requirements,
+        last_chat_provider_id.as_deref(),
+    )))
+}
+
 /// Get available file capabilities
 ///
 /// This endpoint returns all available file capabilities based on the configured
 /// file processors and model capabilities. An optional model_id can be provided
-/// to get capabilities specific to that model (particularly for image understanding).
+/// to get capabilities specific to that model, based on the attachment modalities it
+/// supports (`model_capabilities.supported_input_modalities`). The `sources` of each capability
+/// reflect whether files can be uploaded and which sources they can be linked from.
 #[utoipa::path(
     get,
     path = "/me/file-capabilities",
@@ -3571,51 +5951,41 @@ pub async fn file_capabilities(
     Extension(policy): Extension<PolicyEngine>,
     axum::extract::Query(params): axum::extract::Query<FileCapabilitiesQuery>,
 ) -> Result<Json<Vec<FileCapability>>, StatusCode> {
-    // Determine if image understanding is supported
-    let (supports_image_understanding, supports_audio_input) =
-        if let Some(model_id) = params.model_id {
-            if !policy
-                .filter_authorized_chat_provider_ids(
-                    &me_user.to_subject(),
-                    &me_user.groups,
-                    std::slice::from_ref(&model_id),
-                )
-                .await
-                .map_err(log_internal_server_error)?
-                .contains(&model_id)
-            {
-                return Err(StatusCode::NOT_FOUND);
-            }
-
-            // Get the specific model's capabilities
-            let provider_config = app_state.config.get_chat_provider(&model_id);
-
-            (
-                provider_config
-                    .model_capabilities
-                    .supports_image_understanding,
-                provider_config.model_capabilities.supports_audio_input,
+    if let Some(model_id) = &params.model_id
+        && !policy
+            .filter_authorized_chat_provider_ids(
+                &me_user.to_subject(),
+                &me_user.groups,
+                std::slice::from_ref(model_id),
             )
-        } else {
-            // Without a specific model, check if ANY available model supports image understanding
-            let available_models = app_state
-                .available_models(&policy, &me_user.to_subject(), &me_user.groups)
-                .await
-                .map_err(log_internal_server_error)?;
+            .await
+            .map_err(log_internal_server_error)?
+            .contains(model_id)
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
 
-            available_models
-                .iter()
-                .fold((false, false), |(image, audio), model| {
-                    let config = app_state.config.get_chat_provider(&model.chat_provider_id);
-                    (
-                        image || config.model_capabilities.supports_image_understanding,
-                        audio || config.model_capabilities.supports_audio_input,
-                    )
-                })
-        };
+    let mut capabilities = evaluate_file_capabilities(
+        &app_state,
+        &policy,
+        FileCapabilityContext {
+            subject: &me_user.to_subject(),
+            user_groups: &me_user.groups,
+            model_id: params.model_id.as_deref(),
+        },
+    )
+    .await
+    .map_err(log_internal_server_error)?;
 
-    // Get file capabilities based on image support
-    let capabilities = get_file_capabilities(supports_image_understanding, supports_audio_input);
+    let sources: Vec<String> = app_state
+        .config
+        .available_file_sources()
+        .into_iter()
+        .map(ToString::to_string)
+        .collect();
+    for capability in &mut capabilities {
+        capability.sources = sources.clone();
+    }
 
     Ok(Json(capabilities))
 }
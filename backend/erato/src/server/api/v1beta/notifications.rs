@@ -0,0 +1,203 @@
+use crate::db::entity::notifications;
+use crate::models::notification::{self, NotificationKind, NotificationPayload};
+use crate::server::api::v1beta::me_profile_middleware::MeProfile;
+use crate::services::sentry::log_internal_server_error;
+use crate::state::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::{DateTime, FixedOffset};
+use eyre::Report;
+use serde::Serialize;
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// Default number of notifications per page
+const DEFAULT_NOTIFICATIONS_LIMIT: u64 = 30;
+
+/// A notification in the inbox of the user
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Notification {
+    /// The unique ID of the notification
+    pub id: String,
+    pub kind: NotificationKind,
+    pub payload: NotificationPayload,
+    /// When the notification was created
+    pub created_at: DateTime<FixedOffset>,
+    /// When the notification was read, if it was
+    pub read_at: Option<DateTime<FixedOffset>>,
+}
+
+impl TryFrom<notifications::Model> for Notification {
+    type Error = Report;
+
+    fn try_from(notification: notifications::Model) -> Result<Self, Self::Error> {
+        let kind = NotificationKind::from_db_str(&notification.kind)?;
+        Ok(Self {
+            id: notification.id.to_string(),
+            kind,
+            payload: NotificationPayload::from_stored(kind, notification.payload)?,
+            created_at: notification.created_at,
+            read_at: notification.read_at,
+        })
+    }
+}
+
+/// Statistics for a list of notifications
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NotificationStats {
+    /// Total number of notifications of the user
+    pub total_count: i64,
+    /// Current offset in the list
+    pub current_offset: u64,
+    /// Number of notifications in the current response
+    pub returned_count: usize,
+    /// Whether there are more notifications available
+    pub has_more: bool,
+}
+
+/// Response when listing notifications
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListNotificationsResponse {
+    /// The notifications of the user, unread ones first and the newest first within each group
+    pub notifications: Vec<Notification>,
+    /// Statistics about the notification list
+    pub stats: NotificationStats,
+}
+
+/// Response when marking all notifications as read
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MarkAllNotificationsReadResponse {
+    /// Number of notifications that were marked as read
+    pub marked_count: u64,
+}
+
+fn user_id(me_user: &MeProfile) -> Result<Uuid, StatusCode> {
+    Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// List the notifications of the authenticated user
+///
+/// Unread notifications are listed first. Read notifications are deleted after
+/// `cleanup_read_notifications_max_age_days`.
+#[utoipa::path(
+    get,
+    path = "/me/notifications",
+    tag = "notifications",
+    params(
+        ("limit" = Option<u64>, Query, description = "Maximum number of notifications to return per page. Defaults to 30 if not provided."),
+        ("offset" = Option<u64>, Query, description = "Number of notifications to skip for pagination. Defaults to 0 if not provided.")
+    ),
+    responses(
+        (status = OK, body = ListNotificationsResponse, description = "Successfully retrieved notifications"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_notifications(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<ListNotificationsResponse>, StatusCode> {
+    let limit = params
+        .get("limit")
+        .and_then(|l| l.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_NOTIFICATIONS_LIMIT);
+    let offset = params
+        .get("offset")
+        .and_then(|o| o.parse::<u64>().ok())
+        .unwrap_or(0);
+
+    let (notifications, total_count, has_more) =
+        notification::list_notifications(&app_state.db, user_id(&me_user)?, limit, offset)
+            .await
+            .map_err(log_internal_server_error)?;
+    let notifications = notifications
+        .into_iter()
+        .map(Notification::try_from)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(log_internal_server_error)?;
+
+    Ok(Json(ListNotificationsResponse {
+        stats: NotificationStats {
+            total_count: crate::models::pagination::u64_to_i64_count(total_count),
+            current_offset: offset,
+            returned_count: notifications.len(),
+            has_more,
+        },
+        notifications,
+    }))
+}
+
+/// Mark a notification as read
+///
+/// Marking a notification that was already read keeps the time it was first read at.
+#[utoipa::path(
+    post,
+    path = "/me/notifications/{notification_id}/read",
+    tag = "notifications",
+    params(
+        ("notification_id" = String, Path, description = "The ID of the notification to mark as read")
+    ),
+    responses(
+        (status = OK, body = Notification, description = "The notification was marked as read"),
+        (status = BAD_REQUEST, description = "Invalid notification ID format"),
+        (status = NOT_FOUND, description = "Notification not found"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn mark_notification_read(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(notification_id): Path<String>,
+) -> Result<Json<Notification>, StatusCode> {
+    let notification_id = Uuid::parse_str(&notification_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let notification =
+        notification::mark_notification_read(&app_state.db, user_id(&me_user)?, notification_id)
+            .await
+            .map_err(|e| {
+                if e.to_string().contains("not found") {
+                    StatusCode::NOT_FOUND
+                } else {
+                    log_internal_server_error(e)
+                }
+            })?;
+
+    Ok(Json(
+        Notification::try_from(notification).map_err(log_internal_server_error)?,
+    ))
+}
+
+/// Mark all notifications of the authenticated user as read
+#[utoipa::path(
+    post,
+    path = "/me/notifications/read-all",
+    tag = "notifications",
+    responses(
+        (status = OK, body = MarkAllNotificationsReadResponse, description = "All notifications were marked as read"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn mark_all_notifications_read(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<Json<MarkAllNotificationsReadResponse>, StatusCode> {
+    let marked_count = notification::mark_all_notifications_read(&app_state.db, user_id(&me_user)?)
+        .await
+        .map_err(log_internal_server_error)?;
+
+    Ok(Json(MarkAllNotificationsReadResponse { marked_count }))
+}
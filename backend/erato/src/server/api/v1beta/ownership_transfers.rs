@@ -0,0 +1,232 @@
+//! Transfers of chats and assistants to another owner.
+//!
+//! Owners can hand over their own chats and assistants through these routes. Admins can transfer
+//! the chats and assistants of any user of their organization through the admin API.
+
+use crate::models::chat::{self, ChatOwnershipTransfer};
+use crate::models::ownership_transfer::{self, AssistantOwnershipTransfer, OwnershipTransferOptions};
+use crate::policy::prelude::*;
+use crate::server::api::v1beta::me_profile_middleware::MeProfile;
+use crate::services::sentry::log_internal_server_error;
+use crate::state::AppState;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use eyre::Report;
+use serde::Deserialize;
+use sqlx::types::Uuid;
+use utoipa::ToSchema;
+
+/// Request to transfer a chat or an assistant to another owner
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TransferOwnershipRequest {
+    /// The ID of the user that becomes the owner. The user has to have signed in at least once,
+    /// and has to belong to the organization of the chat or assistant
+    new_owner_user_id: String,
+    /// Share the chat or assistant with the previous owner for reading
+    #[serde(default)]
+    keep_previous_owner_access: bool,
+    /// Attribute the messages the previous owner sent in the chat to the new owner. Messages of
+    /// grantees with edit access keep their attribution. Has no effect for assistants
+    #[serde(default)]
+    rewrite_attribution: bool,
+    /// Only report the changes, without applying them
+    #[serde(default)]
+    dry_run: bool,
+}
+
+impl TransferOwnershipRequest {
+    /// The ID of the new owner and the options of the transfer.
+    pub(crate) fn parse(&self) -> Result<(Uuid, OwnershipTransferOptions), StatusCode> {
+        let new_owner_user_id =
+            Uuid::parse_str(&self.new_owner_user_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+        Ok((
+            new_owner_user_id,
+            OwnershipTransferOptions {
+                keep_previous_owner_access: self.keep_previous_owner_access,
+                rewrite_attribution: self.rewrite_attribution,
+                dry_run: self.dry_run,
+            },
+        ))
+    }
+}
+
+/// Status code for a failed transfer.
+pub(crate) fn transfer_error_status(e: Report) -> StatusCode {
+    let message = e.to_string();
+    if message.contains("not found") {
+        StatusCode::NOT_FOUND
+    } else if message.contains("already owned")
+        || message.contains("does not belong to the organization")
+        || message.contains("to the same user")
+    {
+        StatusCode::BAD_REQUEST
+    } else {
+        log_internal_server_error(e)
+    }
+}
+
+/// Update the state of this instance after a chat was transferred.
+pub(crate) async fn finish_chat_transfer(
+    app_state: &AppState,
+    transfer: &ChatOwnershipTransfer,
+    actor_user_id: &str,
+) {
+    app_state.global_policy_engine.invalidate_data().await;
+    // MCP sessions of the chat may be authenticated with the credentials of the previous owner
+    app_state
+        .mcp_servers
+        .evict_chat_sessions(transfer.chat_id)
+        .await;
+    tracing::info!(
+        chat_id = %transfer.chat_id,
+        previous_owner_user_id = %transfer.previous_owner_user_id,
+        new_owner_user_id = %transfer.new_owner_user_id,
+        user_id = %actor_user_id,
+        "Transferred chat to new owner"
+    );
+}
+
+/// Update the state of this instance after an assistant was transferred.
+pub(crate) async fn finish_assistant_transfer(
+    app_state: &AppState,
+    transfer: &AssistantOwnershipTransfer,
+    actor_user_id: &str,
+) {
+    app_state.global_policy_engine.invalidate_data().await;
+    tracing::info!(
+        assistant_id = %transfer.assistant_id,
+        previous_owner_user_id = %transfer.previous_owner_user_id,
+        new_owner_user_id = %transfer.new_owner_user_id,
+        user_id = %actor_user_id,
+        "Transferred assistant to new owner"
+    );
+}
+
+/// Transfer a chat to another owner
+///
+/// Makes another user of the organization the owner of the chat. The share grant of the chat to
+/// the new owner is removed, as the owner can access the chat anyway, and so are the labels of
+/// the previous owner. Other share grants are kept. Only available to the owner of the chat.
+#[utoipa::path(
+    post,
+    path = "/chats/{chat_id}/transfer-ownership",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat to transfer")
+    ),
+    request_body = TransferOwnershipRequest,
+    responses(
+        (status = OK, body = ChatOwnershipTransfer, description = "The applied changes, or the changes that would be applied for a dry run"),
+        (status = BAD_REQUEST, description = "Invalid IDs, or the new owner already owns the chat or belongs to another organization"),
+        (status = NOT_FOUND, description = "Chat or new owner not found"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user doesn't own the chat"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn transfer_chat_ownership(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path(chat_id): Path<String>,
+    Json(request): Json<TransferOwnershipRequest>,
+) -> Result<Json<ChatOwnershipTransfer>, StatusCode> {
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let (new_owner_user_id, options) = request.parse()?;
+    let actor_user_id = Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+    // Only the owner may share a chat, and so hand it over
+    authorize!(
+        policy,
+        &me_user.to_subject(),
+        &Resource::Chat(chat_id.to_string()),
+        Action::Share
+    )
+    .map_err(|_| StatusCode::FORBIDDEN)?;
+
+    let transfer = chat::transfer_chat_ownership(
+        &app_state.db,
+        me_user.organization_id.as_deref(),
+        &chat_id,
+        &new_owner_user_id,
+        Some(&actor_user_id),
+        &options,
+    )
+    .await
+    .map_err(transfer_error_status)?;
+    if !options.dry_run {
+        finish_chat_transfer(&app_state, &transfer, &me_user.id).await;
+    }
+
+    Ok(Json(transfer))
+}
+
+/// Transfer an assistant to another owner
+///
+/// Makes another user of the organization the owner of the assistant. The share grant of the
+/// assistant to the new owner is removed, as the owner can access the assistant anyway. Other
+/// share grants are kept. Only available to the owner of the assistant.
+#[utoipa::path(
+    post,
+    path = "/assistants/{assistant_id}/transfer-ownership",
+    tag = "assistants",
+    params(
+        ("assistant_id" = String, Path, description = "The ID of the assistant to transfer")
+    ),
+    request_body = TransferOwnershipRequest,
+    responses(
+        (status = OK, body = AssistantOwnershipTransfer, description = "The applied changes, or the changes that would be applied for a dry run"),
+        (status = BAD_REQUEST, description = "Invalid IDs, or the new owner already owns the assistant or belongs to another organization"),
+        (status = NOT_FOUND, description = "Assistant or new owner not found"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = FORBIDDEN, description = "When the user doesn't own the assistant"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn transfer_assistant_ownership(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path(assistant_id): Path<String>,
+    Json(request): Json<TransferOwnershipRequest>,
+) -> Result<Json<AssistantOwnershipTransfer>, StatusCode> {
+    let assistant_id = Uuid::parse_str(&assistant_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let (new_owner_user_id, options) = request.parse()?;
+    let actor_user_id = Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+    authorize!(
+        policy,
+        &me_user.to_subject(),
+        &Resource::Assistant(assistant_id.to_string()),
+        Action::Share
+    )
+    .map_err(|_| StatusCode::FORBIDDEN)?;
+
+    let transfer = ownership_transfer::transfer_assistant_ownership(
+        &app_state.db,
+        me_user.organization_id.as_deref(),
+        &assistant_id,
+        &new_owner_user_id,
+        Some(&actor_user_id),
+        &options,
+    )
+    .await
+    .map_err(transfer_error_status)?;
+    if !options.dry_run {
+        finish_assistant_transfer(&app_state, &transfer, &me_user.id).await;
+    }
+
+    Ok(Json(transfer))
+}
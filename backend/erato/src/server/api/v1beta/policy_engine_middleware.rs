@@ -7,7 +7,7 @@ use std::time::Duration;
 
 /// Time threshold for rebuilding policy data
 /// If the last rebuild was more than this duration ago, trigger a rebuild
-const POLICY_REBUILD_THRESHOLD: Duration = Duration::from_secs(60); // 1 minute
+pub(crate) const POLICY_REBUILD_THRESHOLD: Duration = Duration::from_secs(60); // 1 minute
 
 /// Middleware that provides a PolicyEngine for each request
 ///
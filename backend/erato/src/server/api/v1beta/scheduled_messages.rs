@@ -0,0 +1,260 @@
+use crate::db::entity::scheduled_messages;
+use crate::models::scheduled_message::{self, ScheduledMessageStatus};
+use crate::policy::engine::PolicyEngine;
+use crate::server::api::v1beta::me_profile_middleware::MeProfile;
+use crate::server::api::v1beta::message_streaming::{
+    MessageSubmitRequest, validate_message_submit,
+};
+use crate::services::sentry::log_internal_server_error;
+use crate::state::AppState;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::{Extension, Json};
+use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+use eyre::Report;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use utoipa::ToSchema;
+
+/// A message that is scheduled to be submitted at a later time
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ScheduledMessage {
+    /// The unique ID of the scheduled message
+    pub id: String,
+    /// The text of the message
+    pub user_message: String,
+    /// When the message is submitted. Moved forward when a failed attempt is retried.
+    pub run_at: DateTime<FixedOffset>,
+    pub status: ScheduledMessageStatus,
+    /// Number of attempts to submit the message so far
+    pub attempts: i32,
+    /// The error of the last failed attempt
+    pub last_error: Option<String>,
+    /// The chat the message was submitted to
+    pub chat_id: Option<String>,
+    /// The answer to the message, once it was submitted successfully
+    pub assistant_message_id: Option<String>,
+    /// When the message was scheduled
+    pub created_at: DateTime<FixedOffset>,
+}
+
+impl TryFrom<scheduled_messages::Model> for ScheduledMessage {
+    type Error = Report;
+
+    fn try_from(scheduled_message: scheduled_messages::Model) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: scheduled_message.id.to_string(),
+            user_message: scheduled_message.request["user_message"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            run_at: scheduled_message.run_at,
+            status: ScheduledMessageStatus::from_db_str(&scheduled_message.status)?,
+            attempts: scheduled_message.attempts,
+            last_error: scheduled_message.last_error,
+            chat_id: scheduled_message.chat_id.map(|id| id.to_string()),
+            assistant_message_id: scheduled_message
+                .assistant_message_id
+                .map(|id| id.to_string()),
+            created_at: scheduled_message.created_at,
+        })
+    }
+}
+
+/// Request to schedule a message. Accepts the fields of a message submission, except for
+/// `dry_run`.
+#[derive(Deserialize, ToSchema)]
+pub struct ScheduleMessageRequest {
+    /// When the message should be submitted. Must be in the future, and at most
+    /// `scheduled_messages.max_horizon_secs` ahead.
+    pub run_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub message: MessageSubmitRequest,
+}
+
+/// Response when listing scheduled messages
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListScheduledMessagesResponse {
+    /// The scheduled messages of the user, ordered by the time they are submitted
+    pub scheduled_messages: Vec<ScheduledMessage>,
+}
+
+fn owner_user_id(me_user: &MeProfile) -> Result<Uuid, StatusCode> {
+    Uuid::parse_str(&me_user.id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn map_scheduled_message_error(e: Report) -> StatusCode {
+    let s = e.to_string();
+    if s.contains("not found") {
+        StatusCode::NOT_FOUND
+    } else if s.contains("not pending") {
+        StatusCode::CONFLICT
+    } else {
+        log_internal_server_error(e)
+    }
+}
+
+/// Schedule a message to be submitted at a later time
+///
+/// The message is validated when it is scheduled, and submitted on behalf of the user once it is
+/// due. Checks that depend on the time of submission, like moderation, apply when it is
+/// submitted. Failed attempts are retried a limited number of times.
+#[utoipa::path(
+    post,
+    path = "/me/messages/schedule",
+    tag = "scheduled_messages",
+    request_body = ScheduleMessageRequest,
+    responses(
+        (status = OK, body = ScheduledMessage, description = "The message was scheduled"),
+        (status = BAD_REQUEST, description = "When `run_at` is not in the future or too far ahead, when `dry_run` is set, when the message is submitted to an ephemeral chat, or when the message fails validation"),
+        (status = UNPROCESSABLE_ENTITY, description = "When the message is empty or only consists of whitespace, and no file is attached"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn schedule_message(
+    State(app_state): State<AppState>,
+    Extension(policy): Extension<PolicyEngine>,
+    Extension(me_user): Extension<MeProfile>,
+    headers: HeaderMap,
+    Json(request): Json<ScheduleMessageRequest>,
+) -> Result<Json<ScheduledMessage>, (StatusCode, String)> {
+    let owner_user_id =
+        owner_user_id(&me_user).map_err(|status| (status, "Invalid user ID".to_string()))?;
+
+    let now = Utc::now();
+    let max_horizon_secs = app_state.config.scheduled_messages.max_horizon_secs;
+    if request.run_at <= now {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "run_at must be in the future".to_string(),
+        ));
+    }
+    if request.run_at > now + TimeDelta::seconds(max_horizon_secs as i64) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "run_at must be at most {} seconds in the future",
+                max_horizon_secs
+            ),
+        ));
+    }
+    if request.message.dry_run {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Dry runs can't be scheduled".to_string(),
+        ));
+    }
+    if let Some(chat_id) = request.message.existing_chat_id
+        && app_state.ephemeral_chats.contains(&chat_id, &me_user.id)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Messages of ephemeral chats can't be scheduled".to_string(),
+        ));
+    }
+    validate_message_submit(&app_state, &policy, &me_user, &request.message, &headers).await?;
+
+    let stored_request = serde_json::to_value(&request.message).map_err(|e| {
+        (
+            log_internal_server_error(e.into()),
+            "Failed to store the message".to_string(),
+        )
+    })?;
+    let scheduled_message = scheduled_message::create_scheduled_message(
+        &app_state.db,
+        &owner_user_id,
+        stored_request,
+        me_user.id_token_claims.clone(),
+        request.run_at,
+    )
+    .await
+    .and_then(ScheduledMessage::try_from)
+    .map_err(|e| {
+        (
+            log_internal_server_error(e),
+            "Failed to schedule the message".to_string(),
+        )
+    })?;
+
+    Ok(Json(scheduled_message))
+}
+
+/// List the scheduled messages of the authenticated user
+///
+/// Includes messages that were already submitted or failed, with the outcome of their last
+/// attempt.
+#[utoipa::path(
+    get,
+    path = "/me/scheduled-messages",
+    tag = "scheduled_messages",
+    responses(
+        (status = OK, body = ListScheduledMessagesResponse, description = "Successfully retrieved scheduled messages"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_scheduled_messages(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+) -> Result<Json<ListScheduledMessagesResponse>, StatusCode> {
+    let scheduled_messages =
+        scheduled_message::list_scheduled_messages(&app_state.db, &owner_user_id(&me_user)?)
+            .await
+            .and_then(|scheduled_messages| {
+                scheduled_messages
+                    .into_iter()
+                    .map(ScheduledMessage::try_from)
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(log_internal_server_error)?;
+
+    Ok(Json(ListScheduledMessagesResponse { scheduled_messages }))
+}
+
+/// Cancel a scheduled message
+///
+/// Only messages that have not been submitted yet can be cancelled.
+#[utoipa::path(
+    delete,
+    path = "/me/scheduled-messages/{scheduled_message_id}",
+    tag = "scheduled_messages",
+    params(
+        ("scheduled_message_id" = String, Path, description = "The ID of the scheduled message to cancel")
+    ),
+    responses(
+        (status = NO_CONTENT, description = "The scheduled message was cancelled"),
+        (status = BAD_REQUEST, description = "Invalid scheduled message ID format"),
+        (status = NOT_FOUND, description = "Scheduled message not found"),
+        (status = CONFLICT, description = "The message is already being submitted or was submitted"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn cancel_scheduled_message(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Path(scheduled_message_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let scheduled_message_id =
+        Uuid::parse_str(&scheduled_message_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    scheduled_message::cancel_scheduled_message(
+        &app_state.db,
+        &owner_user_id(&me_user)?,
+        &scheduled_message_id,
+    )
+    .await
+    .map_err(map_scheduled_message_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
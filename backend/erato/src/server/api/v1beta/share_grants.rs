@@ -1,5 +1,5 @@
 use crate::db::entity::share_grants;
-use crate::models::share_grant;
+use crate::models::share_grant::{self, ShareGrantChange, ShareGrantPermission, ShareGrantee};
 use crate::policy::engine::PolicyEngine;
 use crate::server::api::v1beta::entra_id::{OrganizationGroup, OrganizationUser};
 use crate::server::api::v1beta::me_profile_middleware::MeProfile;
@@ -37,7 +37,7 @@ struct GraphGroupItem {
 pub struct ShareGrant {
     /// The unique ID of the share grant
     pub id: String,
-    /// The type of resource being shared (e.g., "assistant")
+    /// The type of resource being shared (e.g., "assistant" or "chat")
     pub resource_type: String,
     /// The ID of the resource being shared
     pub resource_id: String,
@@ -49,6 +49,12 @@ pub struct ShareGrant {
     pub subject_id: String,
     /// The role being granted (e.g., "viewer")
     pub role: String,
+    /// The permission being granted. `edit` allows modifying the resource, but not deleting or re-sharing it
+    pub permission: ShareGrantPermission,
+    /// When this share grant expires. Not set if it does not expire
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub expires_at: Option<DateTime<FixedOffset>>,
     /// When this share grant was created
     pub created_at: DateTime<FixedOffset>,
     /// When this share grant was last updated
@@ -62,7 +68,7 @@ pub struct ShareGrant {
 /// Request to create a new share grant
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateShareGrantRequest {
-    /// The type of resource to share (e.g., "assistant")
+    /// The type of resource to share (e.g., "assistant" or "chat")
     pub resource_type: String,
     /// The ID of the resource to share
     pub resource_id: String,
@@ -74,6 +80,12 @@ pub struct CreateShareGrantRequest {
     pub subject_id: String,
     /// The role to grant (e.g., "viewer")
     pub role: String,
+    /// The permission to grant. Defaults to `read`
+    #[serde(default)]
+    pub permission: ShareGrantPermission,
+    /// When the share grant should expire. Has to be in the future. If not set, the share grant does not expire
+    #[schema(nullable = false)]
+    pub expires_at: Option<DateTime<FixedOffset>>,
 }
 
 /// Response when creating a share grant
@@ -84,6 +96,83 @@ pub struct CreateShareGrantResponse {
     pub share_grant: ShareGrant,
 }
 
+/// Request to share a resource with multiple grantees at once
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkCreateShareGrantsRequest {
+    /// The type of resource to share (e.g., "assistant" or "chat")
+    pub resource_type: String,
+    /// The ID of the resource to share
+    pub resource_id: String,
+    /// The users and organization groups to grant access to. At most 200 per request
+    pub grantees: Vec<ShareGrantee>,
+    /// The role to grant (e.g., "viewer")
+    pub role: String,
+    /// The permission to grant. Defaults to `read`
+    #[serde(default)]
+    pub permission: ShareGrantPermission,
+    /// When the created share grants should expire. Has to be in the future. If not set, the share grants do not expire
+    #[schema(nullable = false)]
+    pub expires_at: Option<DateTime<FixedOffset>>,
+}
+
+/// Request to set the full set of grantees of a resource
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SyncShareGrantsRequest {
+    /// The type of resource (e.g., "assistant" or "chat")
+    pub resource_type: String,
+    /// The ID of the resource
+    pub resource_id: String,
+    /// The users and organization groups that should have access. Share grants of all other subjects are removed. At most 200 per request
+    pub grantees: Vec<ShareGrantee>,
+    /// The role to grant to new grantees (e.g., "viewer")
+    pub role: String,
+    /// The permission to grant to new grantees. Defaults to `read`
+    #[serde(default)]
+    pub permission: ShareGrantPermission,
+    /// When the created share grants should expire. Has to be in the future. If not set, the share grants do not expire
+    #[schema(nullable = false)]
+    pub expires_at: Option<DateTime<FixedOffset>>,
+}
+
+/// What happened to the share grant of a grantee
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareGrantChangeStatus {
+    /// A share grant was created
+    Created,
+    /// The grantee already had a share grant, which was left unchanged
+    AlreadyExists,
+    /// The grantee was rejected, see `error`
+    Invalid,
+    /// The share grant was removed, as the grantee was not part of the synced set
+    Removed,
+}
+
+/// The result of a bulk or sync request for a single grantee
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ShareGrantChangeResult {
+    /// The grantee
+    #[serde(flatten)]
+    pub grantee: ShareGrantee,
+    /// What happened to the share grant of the grantee
+    pub status: ShareGrantChangeStatus,
+    /// The created, existing or removed share grant. Not set for invalid grantees
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub share_grant: Option<ShareGrant>,
+    /// Why the grantee was rejected. Only set for invalid grantees
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub error: Option<String>,
+}
+
+/// Response of a bulk or sync share grants request
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ShareGrantChangesResponse {
+    /// The results for the requested grantees, in the order of the request, followed by the removed share grants
+    pub results: Vec<ShareGrantChangeResult>,
+}
+
 /// Query parameters for listing share grants
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct ListShareGrantsQuery {
@@ -265,6 +354,42 @@ async fn fetch_profiles_for_grants(
     (user_profiles, group_profiles)
 }
 
+fn api_share_grant(
+    grant: share_grants::Model,
+    user_profiles: &HashMap<String, OrganizationUser>,
+    group_profiles: &HashMap<String, OrganizationGroup>,
+) -> ShareGrant {
+    let user_profile =
+        if grant.subject_type == "user" && grant.subject_id_type == "organization_user_id" {
+            user_profiles.get(&grant.subject_id).cloned()
+        } else {
+            None
+        };
+    let group_profile = if grant.subject_type == "organization_group"
+        && grant.subject_id_type == "organization_group_id"
+    {
+        group_profiles.get(&grant.subject_id).cloned()
+    } else {
+        None
+    };
+
+    ShareGrant {
+        id: grant.id.to_string(),
+        resource_type: grant.resource_type,
+        resource_id: grant.resource_id,
+        subject_type: grant.subject_type,
+        subject_id_type: grant.subject_id_type,
+        subject_id: grant.subject_id,
+        role: grant.role,
+        permission: ShareGrantPermission::parse(&grant.permission).unwrap_or_default(),
+        expires_at: grant.expires_at,
+        created_at: grant.created_at,
+        updated_at: grant.updated_at,
+        user_profile,
+        group_profile,
+    }
+}
+
 /// Create a new share grant
 #[utoipa::path(
     post,
@@ -289,7 +414,7 @@ pub async fn create_share_grant(
     Json(request): Json<CreateShareGrantRequest>,
 ) -> Result<(StatusCode, Json<CreateShareGrantResponse>), StatusCode> {
     // Create the share grant
-    let created_grant = share_grant::create_share_grant(
+    let created_grant = share_grant::create_share_grant_with_permission(
         &app_state.db,
         &policy,
         &me_user.to_subject(),
@@ -299,6 +424,8 @@ pub async fn create_share_grant(
         request.subject_id_type,
         request.subject_id,
         request.role,
+        request.permission,
+        request.expires_at,
     )
     .await
     .map_err(|e| {
@@ -336,50 +463,209 @@ pub async fn create_share_grant(
         created_grant.resource_id
     );
 
+    app_state.global_policy_engine.invalidate_data().await;
+
     let (user_profiles, group_profiles) =
         fetch_profiles_for_grants(&app_state, &me_user, std::slice::from_ref(&created_grant)).await;
-    let user_profile = if created_grant.subject_type == "user"
-        && created_grant.subject_id_type == "organization_user_id"
-    {
-        user_profiles.get(&created_grant.subject_id).cloned()
-    } else {
-        None
-    };
-    let group_profile = if created_grant.subject_type == "organization_group"
-        && created_grant.subject_id_type == "organization_group_id"
-    {
-        group_profiles.get(&created_grant.subject_id).cloned()
-    } else {
-        None
-    };
 
     Ok((
         StatusCode::CREATED,
         Json(CreateShareGrantResponse {
-            share_grant: ShareGrant {
-                id: created_grant.id.to_string(),
-                resource_type: created_grant.resource_type,
-                resource_id: created_grant.resource_id,
-                subject_type: created_grant.subject_type,
-                subject_id_type: created_grant.subject_id_type,
-                subject_id: created_grant.subject_id,
-                role: created_grant.role,
-                created_at: created_grant.created_at,
-                updated_at: created_grant.updated_at,
-                user_profile,
-                group_profile,
-            },
+            share_grant: api_share_grant(created_grant, &user_profiles, &group_profiles),
         }),
     ))
 }
 
+fn share_grant_changes_error_status(me_user: &MeProfile, e: eyre::Report) -> StatusCode {
+    if e.to_string().contains("Access denied") || e.to_string().contains("does not own") {
+        tracing::warn!(
+            "User {} attempted to share a resource they don't own: {}",
+            me_user.id,
+            e
+        );
+        StatusCode::FORBIDDEN
+    } else if e.to_string().contains("not found") {
+        StatusCode::NOT_FOUND
+    } else if e.to_string().contains("Invalid") || e.to_string().contains("Unsupported") {
+        tracing::warn!(
+            "Invalid share grants request from user {}: {}",
+            me_user.id,
+            e
+        );
+        StatusCode::BAD_REQUEST
+    } else {
+        log_internal_server_error(e)
+    }
+}
+
+async fn share_grant_changes_response(
+    app_state: &AppState,
+    me_user: &MeProfile,
+    changes: Vec<(ShareGrantee, ShareGrantChange)>,
+) -> ShareGrantChangesResponse {
+    let grants: Vec<share_grants::Model> = changes
+        .iter()
+        .filter_map(|(_, change)| match change {
+            ShareGrantChange::Created(grant)
+            | ShareGrantChange::AlreadyExists(grant)
+            | ShareGrantChange::Removed(grant) => Some(grant.clone()),
+            ShareGrantChange::Invalid(_) => None,
+        })
+        .collect();
+    let (user_profiles, group_profiles) =
+        fetch_profiles_for_grants(app_state, me_user, &grants).await;
+
+    let results = changes
+        .into_iter()
+        .map(|(grantee, change)| {
+            let (status, grant, error) = match change {
+                ShareGrantChange::Created(grant) => {
+                    (ShareGrantChangeStatus::Created, Some(grant), None)
+                }
+                ShareGrantChange::AlreadyExists(grant) => {
+                    (ShareGrantChangeStatus::AlreadyExists, Some(grant), None)
+                }
+                ShareGrantChange::Invalid(error) => {
+                    (ShareGrantChangeStatus::Invalid, None, Some(error))
+                }
+                ShareGrantChange::Removed(grant) => {
+                    (ShareGrantChangeStatus::Removed, Some(grant), None)
+                }
+            };
+            ShareGrantChangeResult {
+                grantee,
+                status,
+                share_grant: grant
+                    .map(|grant| api_share_grant(grant, &user_profiles, &group_profiles)),
+                error,
+            }
+        })
+        .collect();
+
+    ShareGrantChangesResponse { results }
+}
+
+/// Share a resource with multiple grantees
+///
+/// Grantees that already have a share grant for the resource are left unchanged. Invalid
+/// grantees are reported in the results without failing the request.
+#[utoipa::path(
+    post,
+    path = "/share-grants/bulk",
+    tag = "share_grants",
+    request_body = BulkCreateShareGrantsRequest,
+    responses(
+        (status = OK, body = ShareGrantChangesResponse, description = "The result for each grantee"),
+        (status = BAD_REQUEST, description = "Invalid request data, or more than 200 grantees"),
+        (status = FORBIDDEN, description = "User does not own the resource"),
+        (status = NOT_FOUND, description = "Resource not found"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn bulk_create_share_grants(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Json(request): Json<BulkCreateShareGrantsRequest>,
+) -> Result<Json<ShareGrantChangesResponse>, StatusCode> {
+    let changes = share_grant::create_share_grants_bulk(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        request.resource_type.clone(),
+        request.resource_id.clone(),
+        request.grantees,
+        request.role,
+        request.permission,
+        request.expires_at,
+    )
+    .await
+    .map_err(|e| share_grant_changes_error_status(&me_user, e))?;
+
+    tracing::info!(
+        "User {} shared resource {}:{} with {} grantees",
+        me_user.id,
+        request.resource_type,
+        request.resource_id,
+        changes
+            .iter()
+            .filter(|(_, change)| matches!(change, ShareGrantChange::Created(_)))
+            .count()
+    );
+
+    app_state.global_policy_engine.invalidate_data().await;
+
+    Ok(Json(
+        share_grant_changes_response(&app_state, &me_user, changes).await,
+    ))
+}
+
+/// Set the grantees of a resource
+///
+/// Creates share grants for the given grantees that don't have one yet, and removes the share
+/// grants of all other subjects.
+#[utoipa::path(
+    put,
+    path = "/share-grants/sync",
+    tag = "share_grants",
+    request_body = SyncShareGrantsRequest,
+    responses(
+        (status = OK, body = ShareGrantChangesResponse, description = "The result for each requested grantee, followed by the removed share grants"),
+        (status = BAD_REQUEST, description = "Invalid request data, or more than 200 grantees"),
+        (status = FORBIDDEN, description = "User does not own the resource"),
+        (status = NOT_FOUND, description = "Resource not found"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn sync_share_grants(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Json(request): Json<SyncShareGrantsRequest>,
+) -> Result<Json<ShareGrantChangesResponse>, StatusCode> {
+    let changes = share_grant::sync_share_grants(
+        &app_state.db,
+        &policy,
+        &me_user.to_subject(),
+        request.resource_type.clone(),
+        request.resource_id.clone(),
+        request.grantees,
+        request.role,
+        request.permission,
+        request.expires_at,
+    )
+    .await
+    .map_err(|e| share_grant_changes_error_status(&me_user, e))?;
+
+    tracing::info!(
+        "User {} synced the share grants of resource {}:{}",
+        me_user.id,
+        request.resource_type,
+        request.resource_id
+    );
+
+    app_state.global_policy_engine.invalidate_data().await;
+
+    Ok(Json(
+        share_grant_changes_response(&app_state, &me_user, changes).await,
+    ))
+}
+
 /// List share grants for a resource
 #[utoipa::path(
     get,
     path = "/share-grants",
     tag = "share_grants",
     params(
-        ("resource_type" = String, Query, description = "The type of resource (e.g., 'assistant')"),
+        ("resource_type" = String, Query, description = "The type of resource (e.g., 'assistant' or 'chat')"),
         ("resource_id" = String, Query, description = "The ID of the resource")
     ),
     responses(
@@ -427,36 +713,7 @@ pub async fn list_share_grants(
         fetch_profiles_for_grants(&app_state, &me_user, &grants).await;
     let api_grants = grants
         .into_iter()
-        .map(|grant| {
-            let user_profile = if grant.subject_type == "user"
-                && grant.subject_id_type == "organization_user_id"
-            {
-                user_profiles.get(&grant.subject_id).cloned()
-            } else {
-                None
-            };
-            let group_profile = if grant.subject_type == "organization_group"
-                && grant.subject_id_type == "organization_group_id"
-            {
-                group_profiles.get(&grant.subject_id).cloned()
-            } else {
-                None
-            };
-
-            ShareGrant {
-                id: grant.id.to_string(),
-                resource_type: grant.resource_type,
-                resource_id: grant.resource_id,
-                subject_type: grant.subject_type,
-                subject_id_type: grant.subject_id_type,
-                subject_id: grant.subject_id,
-                role: grant.role,
-                created_at: grant.created_at,
-                updated_at: grant.updated_at,
-                user_profile,
-                group_profile,
-            }
-        })
+        .map(|grant| api_share_grant(grant, &user_profiles, &group_profiles))
         .collect();
 
     Ok(Json(ListShareGrantsResponse { grants: api_grants }))
@@ -511,5 +768,7 @@ pub async fn delete_share_grant(
 
     tracing::info!("User {} deleted share grant {}", me_user.id, grant_id);
 
+    app_state.global_policy_engine.invalidate_data().await;
+
     Ok(StatusCode::NO_CONTENT)
 }
@@ -120,7 +120,10 @@ fn get_access_token(me_user: &MeProfile) -> Result<&str, StatusCode> {
 }
 
 /// Check if Sharepoint integration is enabled in the config.
-fn check_sharepoint_enabled(app_state: &AppState) -> Result<(), StatusCode> {
+///
+/// Responds with `409 Conflict` if it is enabled, but the auth mode doesn't provide the access
+/// token of the user that MS Graph requires.
+pub(crate) fn check_sharepoint_enabled(app_state: &AppState) -> Result<(), StatusCode> {
     if !app_state
         .config
         .integrations
@@ -130,6 +133,10 @@ fn check_sharepoint_enabled(app_state: &AppState) -> Result<(), StatusCode> {
         tracing::warn!("Sharepoint integration is not enabled");
         return Err(StatusCode::NOT_FOUND);
     }
+    if !app_state.config.auth.mode.provides_access_token() {
+        tracing::warn!("Sharepoint integration needs an auth mode with access tokens");
+        return Err(StatusCode::CONFLICT);
+    }
     Ok(())
 }
 
@@ -1428,6 +1435,7 @@ async fn fetch_shared_drive_details(
     responses(
         (status = OK, body = AllDrivesResponse, description = "List of accessible drives"),
         (status = UNAUTHORIZED, description = "No access token available"),
+        (status = CONFLICT, description = "The auth mode doesn't provide the access token of the user, e.g. `trusted_headers`"),
         (status = NOT_FOUND, description = "Sharepoint integration is not enabled"),
         (status = INTERNAL_SERVER_ERROR, description = "Failed to retrieve drives")
     ),
@@ -1707,6 +1715,7 @@ pub async fn all_drives(
     responses(
         (status = OK, body = DriveItemsResponse, description = "List of items in the drive root"),
         (status = UNAUTHORIZED, description = "No access token available"),
+        (status = CONFLICT, description = "The auth mode doesn't provide the access token of the user, e.g. `trusted_headers`"),
         (status = NOT_FOUND, description = "Drive not found or Sharepoint integration is not enabled"),
         (status = INTERNAL_SERVER_ERROR, description = "Failed to retrieve drive items")
     ),
@@ -1760,6 +1769,7 @@ pub async fn get_drive_root(
     responses(
         (status = OK, body = DriveItemResponse, description = "Drive item details"),
         (status = UNAUTHORIZED, description = "No access token available"),
+        (status = CONFLICT, description = "The auth mode doesn't provide the access token of the user, e.g. `trusted_headers`"),
         (status = NOT_FOUND, description = "Item not found or Sharepoint integration is not enabled"),
         (status = INTERNAL_SERVER_ERROR, description = "Failed to retrieve item")
     ),
@@ -1815,6 +1825,7 @@ pub async fn get_drive_item(
     responses(
         (status = OK, body = DriveItemsResponse, description = "List of items in the folder"),
         (status = UNAUTHORIZED, description = "No access token available"),
+        (status = CONFLICT, description = "The auth mode doesn't provide the access token of the user, e.g. `trusted_headers`"),
         (status = NOT_FOUND, description = "Folder not found or Sharepoint integration is not enabled"),
         (status = INTERNAL_SERVER_ERROR, description = "Failed to retrieve folder children")
     ),
@@ -3,8 +3,8 @@ use crate::db::entity::messages;
 use crate::db::entity::prelude::*;
 use crate::models::chat::get_chat_by_message_id;
 use crate::models::message::{
-    ContentPart, ContentPartText, GenerationInputMessages, GenerationRequestContext, MessageRole,
-    MessageSchema,
+    ContentPart, ContentPartText, GenerationInputMessages, GenerationMetadata,
+    GenerationRequestContext, MessageRole, MessageSchema, get_message_by_id,
 };
 use crate::policy::engine::{PolicyEngine, authorize};
 use crate::policy::types::{Action, Resource};
@@ -15,17 +15,18 @@ use crate::server::api::v1beta::message_streaming::{
     prepare_chat_request_with_adapters, remove_null_characters,
     resolve_effective_selected_facet_ids,
 };
-use crate::services::file_parsing::parse_file;
+use crate::services::file_parsing::{ParseLimits, parse_file};
 use crate::services::file_processing_cached;
+use crate::services::file_processor::FileProcessingErrorKind;
 use crate::services::file_storage::SharepointContext;
-use crate::services::prompt_composition::traits::MessageRepository;
+use crate::services::file_synopsis::build_file_synopsis;
 use crate::services::prompt_composition::{
     AppStateFileResolver, AppStatePromptProvider, DatabaseMessageRepository,
-    PromptCompositionUserInput,
+    PromptCompositionUserInput, SyntheticMessageRepository,
 };
+use crate::services::sentry::log_internal_server_error;
 use crate::state::{AppState, ChatProviderConfigWithId};
-use async_trait::async_trait;
-use axum::extract::State;
+use axum::extract::{Path, State};
 use axum::{Extension, Json};
 use base64::{Engine as _, engine::general_purpose};
 use chrono::Utc;
@@ -156,6 +157,10 @@ pub struct TokenUsageResponseFileItem {
     filename: String,
     /// Number of tokens used for this file's content
     token_count: usize,
+    /// Why the contents of the file could not be processed. The file then uses no tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    processing_error: Option<FileProcessingErrorKind>,
 }
 
 /// Response for the token_usage_estimate endpoint
@@ -167,50 +172,6 @@ pub struct TokenUsageResponse {
     file_details: Vec<TokenUsageResponseFileItem>,
 }
 
-struct SyntheticMessageRepository<'a> {
-    base: DatabaseMessageRepository<'a>,
-    synthetic_message: messages::Model,
-}
-
-#[async_trait]
-impl<'a> MessageRepository for SyntheticMessageRepository<'a> {
-    async fn get_message_by_id(&self, message_id: &Uuid) -> Result<messages::Model, Report> {
-        if *message_id == self.synthetic_message.id {
-            Ok(self.synthetic_message.clone())
-        } else {
-            self.base.get_message_by_id(message_id).await
-        }
-    }
-
-    async fn get_generation_input_messages(
-        &self,
-        previous_message_id: &Uuid,
-        num_messages: usize,
-    ) -> Result<Vec<messages::Model>, Report> {
-        let mut messages_vec = Vec::new();
-        let mut current_message_id = Some(*previous_message_id);
-        let mut count = 0;
-
-        while let Some(msg_id) = current_message_id {
-            if count >= num_messages {
-                break;
-            }
-
-            let message = if msg_id == self.synthetic_message.id {
-                self.synthetic_message.clone()
-            } else {
-                self.base.get_message_by_id(&msg_id).await?
-            };
-            current_message_id = message.previous_message_id;
-            messages_vec.push(message);
-            count += 1;
-        }
-
-        messages_vec.reverse();
-        Ok(messages_vec)
-    }
-}
-
 #[utoipa::path(
     post,
     path = "/token_usage/estimate",
@@ -282,6 +243,7 @@ pub async fn token_usage_estimate(
             &policy,
             &subject,
             &resolved_chat,
+            app_state.config.assistants.freeze_assistant_config,
         )
         .await
         .map_err(|err| {
@@ -323,6 +285,7 @@ pub async fn token_usage_estimate(
             &policy,
             &subject,
             &resolved_chat,
+            app_state.config.assistants.freeze_assistant_config,
         )
         .await
         .map_err(|err| {
@@ -371,6 +334,13 @@ pub async fn token_usage_estimate(
             generation_started_at: None,
             generation_heartbeat_at: None,
             generation_ended_at: None,
+            organization_id: me_user.organization_id.clone(),
+            generation_replica_id: None,
+            generation_message_id: None,
+            assistant_snapshot: None,
+            provisional: false,
+            custom_instruction: None,
+            response_language: None,
         };
         chat = Some(synthetic_chat);
     }
@@ -413,25 +383,26 @@ pub async fn token_usage_estimate(
             })?
     };
 
-    let mut files_for_generation = file_processing_cached::process_files_parallel_cached(
-        &app_state,
-        &policy,
-        &me_user,
-        &input_file_ids,
-        me_user
-            .access_token
-            .as_ref()
-            .map(|token| SharepointContext {
-                access_token: token,
-            }),
-    )
-    .await
-    .map_err(|err| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to process input files: {}", err),
+    let (mut files_for_generation, unprocessable_files) =
+        file_processing_cached::process_files_parallel_cached(
+            &app_state,
+            &policy,
+            &me_user,
+            &input_file_ids,
+            me_user
+                .access_token
+                .as_ref()
+                .map(|token| SharepointContext {
+                    access_token: token,
+                }),
         )
-    })?;
+        .await
+        .map_err(|err| {
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to process input files: {}", err),
+            )
+        })?;
 
     // Inline file payloads (e.g. the Outlook add-in's previewed email body).
     // Track the index range of virtual entries so the chat-exists branch
@@ -460,7 +431,12 @@ pub async fn token_usage_estimate(
             if let FileContent::Text(ref text) = file.content {
                 let file_id = file.id;
                 let filename = file.filename.clone();
-                let formatted = format_successful_file_content(&filename, file_id, text);
+                let formatted = format_successful_file_content(
+                    &filename,
+                    file_id,
+                    text,
+                    file.synopsis.as_ref(),
+                );
                 Some(async move {
                     let token_count =
                         file_processing_cached::get_token_count_cached(app_state_ref, &formatted)
@@ -472,6 +448,7 @@ pub async fn token_usage_estimate(
                         id: file_id.to_string(),
                         filename,
                         token_count,
+                        processing_error: None,
                     })
                 })
             } else {
@@ -493,6 +470,16 @@ pub async fn token_usage_estimate(
                 }
             }
         }
+        file_details.extend(
+            unprocessable_files
+                .iter()
+                .map(|file| TokenUsageResponseFileItem {
+                    id: file.id.to_string(),
+                    filename: file.filename.clone(),
+                    token_count: 0,
+                    processing_error: Some(file.error.kind()),
+                }),
+        );
 
         span.record("total_tokens", total_file_tokens);
         (file_details, total_file_tokens)
@@ -531,12 +518,16 @@ pub async fn token_usage_estimate(
             generation_parameters: None,
             generation_metadata: None,
             input_parameters: None,
+            is_welcome_message: false,
+            content_draft: None,
+            edit_of_message_id: None,
         };
 
         let base_repo = DatabaseMessageRepository {
             conn: &app_state.db,
             policy: &policy,
             subject: &subject,
+            generation_input_archive: &app_state.generation_input_archive,
         };
         let message_repo = SyntheticMessageRepository {
             base: base_repo,
@@ -573,6 +564,7 @@ pub async fn token_usage_estimate(
             just_submitted_user_message_id: synthetic_message_id,
             requested_chat_provider_id: request.chat_provider_id.clone(),
             new_input_file_ids: input_file_ids.clone(),
+            refresh_file_ids: vec![],
             selected_facet_ids,
             action_facet: request.action_facet.as_ref().map(|af| {
                 crate::services::prompt_composition::types::ActionFacetUserInput {
@@ -580,6 +572,7 @@ pub async fn token_usage_estimate(
                     args: af.args.clone(),
                 }
             }),
+            requested_response_language: None,
         };
         let me_profile_input = MeProfileChatRequestInput::from_me_profile(&me_user);
 
@@ -611,7 +604,12 @@ pub async fn token_usage_estimate(
         // them here. Without this, `total_tokens` would under-count.
         for file in &files_for_generation[virtual_files_range.clone()] {
             if let FileContent::Text(ref text) = file.content {
-                let formatted = format_successful_file_content(&file.filename, file.id, text);
+                let formatted = format_successful_file_content(
+                    &file.filename,
+                    file.id,
+                    text,
+                    file.synopsis.as_ref(),
+                );
                 chat_request.messages.push(ChatMessage::user(formatted));
             }
         }
@@ -630,7 +628,12 @@ pub async fn token_usage_estimate(
         }
         for file in &files_for_generation {
             if let FileContent::Text(ref text) = file.content {
-                let formatted = format_successful_file_content(&file.filename, file.id, text);
+                let formatted = format_successful_file_content(
+                    &file.filename,
+                    file.id,
+                    text,
+                    file.synopsis.as_ref(),
+                );
                 messages
                     .messages
                     .push(crate::models::message::InputMessage {
@@ -661,21 +664,22 @@ pub async fn token_usage_estimate(
         .saturating_sub(total_file_tokens);
 
     let requested_chat_provider_id = request.chat_provider_id.as_deref();
-    let effective_chat_provider_id = requested_chat_provider_id.or_else(|| {
-        assistant_config
-            .as_ref()
-            .and_then(|a| a.default_chat_provider.as_deref())
-    });
+    let assistant_chat_provider_id = assistant_config
+        .as_ref()
+        .and_then(|a| a.default_chat_provider.as_deref());
+    let effective_chat_provider_id = requested_chat_provider_id.or(assistant_chat_provider_id);
 
     let ChatProviderConfigWithId {
         chat_provider_config,
         chat_provider_id,
+        chat_provider_group_id,
     } = app_state
         .chat_provider_for_chatcompletion(
             &policy,
             &me_user.to_subject(),
             &me_user.groups,
             effective_chat_provider_id,
+            assistant_chat_provider_id,
         )
         .await
         .map_err(|err| {
@@ -695,7 +699,7 @@ pub async fn token_usage_estimate(
             file_tokens: total_file_tokens,
             max_tokens: max_context_tokens,
             remaining_tokens,
-            chat_provider_id,
+            chat_provider_id: chat_provider_group_id.unwrap_or(chat_provider_id),
         },
         file_details,
     }))
@@ -762,6 +766,7 @@ async fn process_virtual_files(
             app_state.file_processor.as_ref(),
             bytes,
             content_type.as_deref(),
+            ParseLimits::from_config(&app_state.config.file_processor),
         )
         .await
         .map_err(|err| {
@@ -771,10 +776,18 @@ async fn process_virtual_files(
             )
         })?;
 
+        let text = remove_null_characters(&parsed);
+        let synopsis = build_file_synopsis(
+            &text,
+            &vf.filename,
+            content_type.as_deref(),
+            app_state.config.chat.file_synopsis_sentences,
+        );
         converted.push(FileContentsForGeneration {
             id: Uuid::new_v4(),
             filename: vf.filename.clone(),
-            content: FileContent::Text(remove_null_characters(&parsed)),
+            content: FileContent::Text(text),
+            synopsis: Some(synopsis),
         });
     }
 
@@ -857,3 +870,133 @@ async fn count_tokens_for_chat_request(
     span.record("total_tokens", total_tokens);
     Ok(total_tokens)
 }
+
+/// Estimated prompt tokens per part of the prompt of a generated message
+#[derive(Debug, ToSchema, Serialize)]
+pub struct MessageTokenBreakdown {
+    /// Number of tokens in the system prompt, assistant prompt and facet prompts
+    system_prompt_tokens: u32,
+    /// Number of tokens in previous messages (chat history)
+    chat_history_tokens: u32,
+    /// Number of tokens in file contents
+    file_content_tokens: u32,
+    /// Number of tokens in the user message
+    user_message_tokens: u32,
+    /// Number of prompt tokens reported by the provider, summed over all generation turns
+    /// (e.g. tool calls). Falls back to the sum of the estimated parts if not reported.
+    total_prompt_tokens: u32,
+    /// Number of completion tokens reported by the provider
+    completion_tokens: u32,
+}
+
+/// Response for the message_token_breakdown endpoint
+#[derive(Debug, ToSchema, Serialize)]
+pub struct MessageTokenBreakdownResponse {
+    /// The ID of the message
+    message_id: String,
+    /// Number of prompt tokens reported by the provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    prompt_tokens: Option<u32>,
+    /// Number of completion tokens reported by the provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    completion_tokens: Option<u32>,
+    /// Total number of tokens reported by the provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    total_tokens: Option<u32>,
+    /// Breakdown of the prompt tokens.
+    /// `null` for messages generated before the breakdown was recorded.
+    breakdown: Option<MessageTokenBreakdown>,
+}
+
+/// Get the token usage of a generated message, broken down by part of the prompt
+#[utoipa::path(
+    get,
+    path = "/me/chats/{chat_id}/messages/{message_id}/token-breakdown",
+    params(
+        ("chat_id" = String, Path, description = "The ID of the chat"),
+        ("message_id" = String, Path, description = "The ID of the message")
+    ),
+    responses(
+        (status = OK, body = MessageTokenBreakdownResponse),
+        (status = BAD_REQUEST, description = "Invalid chat or message ID format"),
+        (status = NOT_FOUND, description = "Message not found in the chat"),
+        (status = UNAUTHORIZED, description = "When no valid JWT token is provided"),
+        (status = INTERNAL_SERVER_ERROR, description = "When an internal server error occurs")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn message_token_breakdown(
+    State(app_state): State<AppState>,
+    Extension(me_user): Extension<MeProfile>,
+    Extension(policy): Extension<PolicyEngine>,
+    Path((chat_id, message_id)): Path<(String, String)>,
+) -> Result<Json<MessageTokenBreakdownResponse>, axum::http::StatusCode> {
+    let chat_id = Uuid::parse_str(&chat_id).map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+    let message_id =
+        Uuid::parse_str(&message_id).map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
+
+    policy
+        .rebuild_data_if_needed_req(&app_state.db, &app_state.config)
+        .await?;
+
+    let message = get_message_by_id(&app_state.db, &policy, &me_user.to_subject(), &message_id)
+        .await
+        .map_err(|e| {
+            let s = e.to_string();
+            if s.contains("not found")
+                || s.contains("Access denied")
+                || s.contains("not authorized")
+            {
+                axum::http::StatusCode::NOT_FOUND
+            } else {
+                log_internal_server_error(e)
+            }
+        })?;
+    if message.chat_id != chat_id {
+        return Err(axum::http::StatusCode::NOT_FOUND);
+    }
+
+    let generation_metadata = message
+        .generation_metadata
+        .map(serde_json::from_value::<GenerationMetadata>)
+        .transpose()
+        .map_err(|e| log_internal_server_error(e.into()))?;
+    let Some(generation_metadata) = generation_metadata else {
+        return Ok(Json(MessageTokenBreakdownResponse {
+            message_id: message.id.to_string(),
+            prompt_tokens: None,
+            completion_tokens: None,
+            total_tokens: None,
+            breakdown: None,
+        }));
+    };
+
+    let breakdown = generation_metadata
+        .token_breakdown
+        .map(|parts| MessageTokenBreakdown {
+            total_prompt_tokens: generation_metadata.used_prompt_tokens.unwrap_or(
+                parts.system_prompt_tokens
+                    + parts.chat_history_tokens
+                    + parts.file_content_tokens
+                    + parts.user_message_tokens,
+            ),
+            completion_tokens: generation_metadata.used_completion_tokens.unwrap_or(0),
+            system_prompt_tokens: parts.system_prompt_tokens,
+            chat_history_tokens: parts.chat_history_tokens,
+            file_content_tokens: parts.file_content_tokens,
+            user_message_tokens: parts.user_message_tokens,
+        });
+
+    Ok(Json(MessageTokenBreakdownResponse {
+        message_id: message.id.to_string(),
+        prompt_tokens: generation_metadata.used_prompt_tokens,
+        completion_tokens: generation_metadata.used_completion_tokens,
+        total_tokens: generation_metadata.used_total_tokens,
+        breakdown,
+    }))
+}
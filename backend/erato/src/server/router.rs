@@ -5,10 +5,11 @@ use crate::frontend_environment::DeploymentVersion;
 use crate::profiling::{memory_profile_flamegraph, memory_profile_pprof};
 use crate::state::AppState;
 use axum::Extension;
-use axum::extract::{Query, State};
+use axum::extract::{Query, Request, State};
 use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::middleware::{self, Next};
 use axum::response::{IntoResponse, Response};
-use axum::routing::get;
+use axum::routing::{any, get};
 use serde::Deserialize;
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
@@ -16,6 +17,7 @@ use std::path::{Path, PathBuf};
 use url::Url;
 use utoipa::{IntoParams, OpenApi};
 use utoipa_axum::router::OpenApiRouter;
+use utoipa_scalar::{Scalar, Servable as ScalarServable};
 // use utoipa_axum::routes;
 
 /// Get health of the API.
@@ -451,19 +453,50 @@ async fn favicon_svg(State(app_state): State<AppState>) -> Response {
     favicon(State(app_state), "favicon.svg").await
 }
 
-pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
-    // build our application with a route
+/// Header with the deployment version that is added to all API responses.
+pub const ERATO_VERSION_HEADER: &str = "x-erato-version";
+
+/// Adds the deployment version to the response, so clients and support can tell which build
+/// answered a request.
+async fn version_header_middleware(req: Request, next: Next) -> Response {
+    let version = req
+        .extensions()
+        .get::<DeploymentVersion>()
+        .map_or(env!("CARGO_PKG_VERSION"), DeploymentVersion::version)
+        .to_string();
+    let mut response = next.run(req).await;
+    if let Ok(value) = HeaderValue::from_str(&version) {
+        response.headers_mut().insert(ERATO_VERSION_HEADER, value);
+    }
+    response
+}
 
-    let router = OpenApiRouter::new()
-        .route("/health", get(health).head(health))
-        .route("/favicon.ico", get(favicon_ico))
-        .route("/favicon.svg", get(favicon_svg))
-        .route("/office-addin/manifest.xml", get(office_addin_manifest))
+/// Routes serving the OpenAPI spec at `/openapi.json` and the API docs at `/scalar`.
+pub fn api_docs_routes<S>(spec: utoipa::openapi::OpenApi) -> axum::Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    axum::Router::new()
+        .merge(Scalar::with_url("/scalar", spec.clone()))
         .route(
-            "/office-addin/manifest-exchange-server.xml",
-            get(office_addin_exchange_server_manifest),
+            "/openapi.json",
+            get(move || async move { axum::Json(spec.clone()) }),
         )
-        .nest("/api/v1beta", crate::server::api::v1beta::router(app_state));
+}
+
+/// Marks the first API request, which releases actors that are only started after it.
+async fn first_request_middleware(
+    State(app_state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    app_state.actor_manager.mark_first_request();
+    next.run(req).await
+}
+
+/// Routes for operating the server that are not part of the API, like health checks.
+fn operational_routes() -> OpenApiRouter<AppState> {
+    let router = OpenApiRouter::new().route("/health", get(health).head(health));
 
     #[cfg(all(feature = "profiling", target_os = "linux"))]
     let router = router
@@ -476,6 +509,81 @@ pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
     router
 }
 
+/// Routes of the public listener.
+///
+/// The internal routes (operational routes and admin API) are only included if
+/// `include_internal_routes` is set, and respond with 404 otherwise.
+fn public_routes(app_state: AppState, include_internal_routes: bool) -> OpenApiRouter<AppState> {
+    let api_router = crate::server::api::v1beta::router(app_state.clone());
+    let api_router = if include_internal_routes {
+        api_router.merge(crate::server::api::v1beta::admin_router(app_state.clone()))
+    } else {
+        api_router
+    };
+
+    let router = OpenApiRouter::new()
+        .route("/favicon.ico", get(favicon_ico))
+        .route("/favicon.svg", get(favicon_svg))
+        .route("/office-addin/manifest.xml", get(office_addin_manifest))
+        .route(
+            "/office-addin/manifest-exchange-server.xml",
+            get(office_addin_exchange_server_manifest),
+        )
+        .nest(
+            "/api/v1beta",
+            api_router
+                .layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    first_request_middleware,
+                ))
+                .layer(middleware::from_fn(version_header_middleware)),
+        )
+        // Not part of the OpenAPI spec, as it mirrors the OpenAI API
+        .nest(
+            "/compat/v1",
+            crate::server::api::compat_v1::router(app_state)
+                .layer(middleware::from_fn(version_header_middleware))
+                .into(),
+        );
+
+    if include_internal_routes {
+        router.merge(operational_routes())
+    } else {
+        // Paths that are not routed fall back to the frontend, so the operational routes need
+        // an explicit 404. The admin API is covered by the fallback of the API router.
+        router
+            .route("/health", any(crate::server::api::v1beta::fallback))
+            .route("/debug/{*path}", any(crate::server::api::v1beta::fallback))
+    }
+}
+
+/// Router serving all routes on a single listener.
+pub fn router(app_state: AppState) -> OpenApiRouter<AppState> {
+    public_routes(app_state, true)
+}
+
+/// Router of the internal listener, serving the operational routes and the admin API.
+pub fn internal_router(app_state: AppState) -> OpenApiRouter<AppState> {
+    operational_routes().nest(
+        "/api/v1beta",
+        crate::server::api::v1beta::admin_router(app_state)
+            .layer(middleware::from_fn(version_header_middleware)),
+    )
+}
+
+/// Routers of the public listener and, if `internal_http_port` is configured, the internal listener.
+pub fn listener_routers(
+    app_state: AppState,
+) -> (OpenApiRouter<AppState>, Option<OpenApiRouter<AppState>>) {
+    if app_state.config.internal_http_port.is_none() {
+        return (router(app_state), None);
+    }
+    (
+        public_routes(app_state.clone(), false),
+        Some(internal_router(app_state)),
+    )
+}
+
 #[derive(OpenApi)]
 #[openapi(
     paths(health, office_addin_manifest, office_addin_exchange_server_manifest),
@@ -0,0 +1,674 @@
+//! Commands of `erato admin`, for operators of a running instance.
+//!
+//! The commands work on the database and the file storage directly, through the same model
+//! functions as the API, so that they behave the same way. Every command returns a report, which
+//! is printed as text, or as JSON with `--json`. Mutating commands support `--dry-run`, and
+//! destructive ones refuse to run without `--yes`.
+
+use crate::db::entity::prelude::Chats;
+use crate::models::chat::{
+    ChatOwnershipTransfer, get_chats_of_missing_owners, resolve_chat_display_name,
+    transfer_chat_ownership,
+};
+use crate::models::file_upload::{FileUploadLookup, get_unreferenced_file_uploads};
+use crate::models::message::{
+    CrossChatMessageLink, find_cross_chat_message_links, get_all_chat_messages,
+};
+use crate::models::message_thread_integrity::{ThreadIntegrityReport, analyze_thread_integrity};
+use crate::models::ownership_transfer::OwnershipTransferOptions;
+use crate::models::share_grant::get_share_grants_of_missing_resources;
+use crate::models::user::{UserStats, get_user_stats};
+use crate::services::file_parsing::ParseLimits;
+use crate::services::file_processing_cached::{is_image_file, parse_text_file_bytes};
+use crate::services::file_storage::is_not_found_error;
+use crate::services::policy_invalidations::{PolicyInvalidationReason, record_policy_invalidation};
+use crate::state::AppState;
+use eyre::{Report, eyre};
+use sea_orm::EntityTrait;
+use sea_orm::prelude::{DateTimeWithTimeZone, Uuid};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::Instant;
+
+/// A command of `erato admin`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminCommand {
+    UserStats { user_id: Uuid },
+    ChatInspect { chat_id: Uuid },
+    ChatTransfer {
+        chat_id: Uuid,
+        new_owner_user_id: Uuid,
+    },
+    FileVerify { file_id: Uuid },
+    PolicyRebuild,
+    OrphanReport,
+}
+
+impl AdminCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AdminCommand::UserStats { .. } => "user-stats",
+            AdminCommand::ChatInspect { .. } => "chat-inspect",
+            AdminCommand::ChatTransfer { .. } => "chat-transfer",
+            AdminCommand::FileVerify { .. } => "file-verify",
+            AdminCommand::PolicyRebuild => "policy-rebuild",
+            AdminCommand::OrphanReport => "orphan-report",
+        }
+    }
+
+    fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            AdminCommand::ChatTransfer { .. } | AdminCommand::PolicyRebuild
+        )
+    }
+
+    fn is_destructive(&self) -> bool {
+        matches!(self, AdminCommand::ChatTransfer { .. })
+    }
+}
+
+/// Flags of `erato admin`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AdminCommandOptions {
+    /// Print the report as JSON
+    pub json: bool,
+    /// Report the changes of a mutating command without applying them
+    pub dry_run: bool,
+    /// Confirm a destructive command
+    pub yes: bool,
+}
+
+/// Run a command, and render its report for printing.
+pub async fn run_admin_command(
+    app_state: &AppState,
+    command: &AdminCommand,
+    options: &AdminCommandOptions,
+) -> Result<String, Report> {
+    if options.dry_run && !command.is_mutating() {
+        return Err(eyre!(
+            "`{}` doesn't modify anything and has no --dry-run",
+            command.name()
+        ));
+    }
+    if command.is_destructive() && !options.dry_run && !options.yes {
+        return Err(eyre!(
+            "Refusing to run `{}` without --yes. Use --dry-run to see its changes first",
+            command.name()
+        ));
+    }
+
+    match command {
+        AdminCommand::UserStats { user_id } => {
+            render(&get_user_stats(&app_state.db, user_id).await?, options)
+        }
+        AdminCommand::ChatInspect { chat_id } => {
+            render(&inspect_chat(app_state, chat_id).await?, options)
+        }
+        AdminCommand::ChatTransfer {
+            chat_id,
+            new_owner_user_id,
+        } => {
+            let transfer =
+                transfer_chat(app_state, chat_id, new_owner_user_id, options.dry_run).await?;
+            render(&transfer, options)
+        }
+        AdminCommand::FileVerify { file_id } => {
+            render(&verify_file(app_state, file_id).await?, options)
+        }
+        AdminCommand::PolicyRebuild => {
+            render(&rebuild_policy(app_state, options.dry_run).await?, options)
+        }
+        AdminCommand::OrphanReport => render(&report_orphans(app_state).await?, options),
+    }
+}
+
+fn render<R: Serialize + fmt::Display>(
+    report: &R,
+    options: &AdminCommandOptions,
+) -> Result<String, Report> {
+    if options.json {
+        Ok(serde_json::to_string_pretty(report)?)
+    } else {
+        Ok(report.to_string())
+    }
+}
+
+impl fmt::Display for UserStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "User {} ({} / {})", self.user_id, self.issuer, self.subject)?;
+        writeln!(f, "  email: {}", self.email.as_deref().unwrap_or("-"))?;
+        writeln!(
+            f,
+            "  organization: {}",
+            self.organization_id.as_deref().unwrap_or("-")
+        )?;
+        writeln!(f, "  created: {}", self.created_at)?;
+        writeln!(f, "  chats: {} ({} archived)", self.chats, self.archived_chats)?;
+        writeln!(f, "  messages: {}", self.messages)?;
+        match &self.last_message_at {
+            Some(last_message_at) => writeln!(f, "  last message: {last_message_at}")?,
+            None => writeln!(f, "  last message: -")?,
+        }
+        writeln!(f, "  assistants: {}", self.assistants)?;
+        writeln!(f, "  file uploads: {}", self.file_uploads)?;
+        write!(f, "  share grants received: {}", self.share_grants_received)
+    }
+}
+
+/// A message of an inspected chat
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InspectedMessage {
+    pub id: Uuid,
+    pub role: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+    pub previous_message_id: Option<Uuid>,
+    pub sibling_message_id: Option<Uuid>,
+    pub edit_of_message_id: Option<Uuid>,
+    pub is_message_in_active_thread: bool,
+    /// Number of messages before the message in its thread
+    pub depth: usize,
+    /// Size of the stored message
+    pub size_bytes: usize,
+    /// Size of the stored generation inputs of the message
+    pub generation_input_size_bytes: usize,
+    /// The type of the error the generation of the message failed with
+    pub generation_error: Option<String>,
+}
+
+/// Structure and health of a chat
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChatInspection {
+    pub chat_id: Uuid,
+    pub title: String,
+    pub owner_user_id: String,
+    pub organization_id: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+    pub archived_at: Option<DateTimeWithTimeZone>,
+    pub generation_state: Option<String>,
+    /// The messages of the chat, oldest first
+    pub messages: Vec<InspectedMessage>,
+    pub total_size_bytes: usize,
+    pub generation_errors: usize,
+    pub thread_integrity: ThreadIntegrityReport,
+}
+
+/// Inspect the thread, the message sizes and the generation errors of a chat.
+pub async fn inspect_chat(app_state: &AppState, chat_id: &Uuid) -> Result<ChatInspection, Report> {
+    let chat = Chats::find_by_id(*chat_id)
+        .one(&app_state.db)
+        .await?
+        .ok_or_else(|| eyre!("Chat with ID {} not found", chat_id))?;
+    let chat_messages = get_all_chat_messages(&app_state.db, chat_id).await?;
+
+    let previous: HashMap<Uuid, Option<Uuid>> = chat_messages
+        .iter()
+        .map(|message| (message.id, message.previous_message_id))
+        .collect();
+    let messages: Vec<InspectedMessage> = chat_messages
+        .iter()
+        .map(|message| InspectedMessage {
+            id: message.id,
+            role: message.raw_message["role"].as_str().map(ToOwned::to_owned),
+            created_at: message.created_at,
+            previous_message_id: message.previous_message_id,
+            sibling_message_id: message.sibling_message_id,
+            edit_of_message_id: message.edit_of_message_id,
+            is_message_in_active_thread: message.is_message_in_active_thread,
+            depth: thread_depth(&previous, message.id),
+            size_bytes: message.raw_message.to_string().len(),
+            generation_input_size_bytes: message
+                .generation_input_messages
+                .as_ref()
+                .map_or(0, |inputs| inputs.to_string().len()),
+            generation_error: message
+                .generation_metadata
+                .as_ref()
+                .and_then(|metadata| metadata.get("error"))
+                .map(|error| {
+                    error["error_type"]
+                        .as_str()
+                        .unwrap_or("unknown")
+                        .to_string()
+                }),
+        })
+        .collect();
+
+    Ok(ChatInspection {
+        chat_id: chat.id,
+        title: resolve_chat_display_name(
+            chat.title_by_user_provided.as_deref(),
+            chat.title_by_summary.as_deref(),
+        ),
+        owner_user_id: chat.owner_user_id,
+        organization_id: chat.organization_id,
+        created_at: chat.created_at,
+        archived_at: chat.archived_at,
+        generation_state: chat.generation_state,
+        total_size_bytes: messages
+            .iter()
+            .map(|message| message.size_bytes + message.generation_input_size_bytes)
+            .sum(),
+        generation_errors: messages
+            .iter()
+            .filter(|message| message.generation_error.is_some())
+            .count(),
+        thread_integrity: analyze_thread_integrity(*chat_id, &chat_messages),
+        messages,
+    })
+}
+
+/// Number of previous messages of a message, stopping at links out of the chat and at cycles.
+fn thread_depth(previous: &HashMap<Uuid, Option<Uuid>>, message_id: Uuid) -> usize {
+    let mut visited = HashSet::from([message_id]);
+    let mut current = message_id;
+    while let Some(Some(previous_id)) = previous.get(&current) {
+        if !previous.contains_key(previous_id) || !visited.insert(*previous_id) {
+            break;
+        }
+        current = *previous_id;
+    }
+    visited.len() - 1
+}
+
+impl fmt::Display for ChatInspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Chat {} \"{}\"", self.chat_id, self.title)?;
+        writeln!(f, "  owner: {}", self.owner_user_id)?;
+        writeln!(f, "  created: {}", self.created_at)?;
+        if let Some(archived_at) = &self.archived_at {
+            writeln!(f, "  archived: {archived_at}")?;
+        }
+        if let Some(generation_state) = &self.generation_state {
+            writeln!(f, "  generation: {generation_state}")?;
+        }
+        writeln!(
+            f,
+            "  {} messages, {} bytes, {} generation errors",
+            self.messages.len(),
+            self.total_size_bytes,
+            self.generation_errors
+        )?;
+        writeln!(f, "Messages:")?;
+        for message in &self.messages {
+            write!(
+                f,
+                "  {}{} {} ({} bytes)",
+                "  ".repeat(message.depth),
+                message.id,
+                message.role.as_deref().unwrap_or("?"),
+                message.size_bytes + message.generation_input_size_bytes
+            )?;
+            if message.is_message_in_active_thread {
+                write!(f, " active")?;
+            }
+            if let Some(edit_of_message_id) = message.edit_of_message_id {
+                write!(f, " edit of {edit_of_message_id}")?;
+            } else if let Some(sibling_message_id) = message.sibling_message_id {
+                write!(f, " sibling of {sibling_message_id}")?;
+            }
+            if let Some(generation_error) = &message.generation_error {
+                write!(f, " error: {generation_error}")?;
+            }
+            writeln!(f)?;
+        }
+        if self.thread_integrity.is_intact() {
+            return write!(f, "Thread: intact");
+        }
+        write!(f, "Thread: {} issues", self.thread_integrity.issues.len())?;
+        for issue in &self.thread_integrity.issues {
+            let message_ids: Vec<String> = issue.message_ids.iter().map(Uuid::to_string).collect();
+            write!(f, "\n  {}: {}", issue.kind.as_str(), message_ids.join(", "))?;
+            if let Some(linked_message_id) = issue.linked_message_id {
+                write!(f, " -> {linked_message_id}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Transfer a chat to another user, see [`transfer_chat_ownership`], and invalidate the policy
+/// data of this instance.
+pub async fn transfer_chat(
+    app_state: &AppState,
+    chat_id: &Uuid,
+    new_owner_user_id: &Uuid,
+    dry_run: bool,
+) -> Result<ChatOwnershipTransfer, Report> {
+    let transfer = transfer_chat_ownership(
+        &app_state.db,
+        None,
+        chat_id,
+        new_owner_user_id,
+        None,
+        &OwnershipTransferOptions {
+            dry_run,
+            ..Default::default()
+        },
+    )
+    .await?;
+    if !dry_run {
+        app_state.global_policy_engine.invalidate_data().await;
+        tracing::info!(
+            chat_id = %chat_id,
+            previous_owner_user_id = %transfer.previous_owner_user_id,
+            new_owner_user_id = %transfer.new_owner_user_id,
+            "Transferred chat to new owner"
+        );
+    }
+    Ok(transfer)
+}
+
+impl fmt::Display for ChatOwnershipTransfer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Chat {}: owner {} -> {}",
+            self.chat_id, self.previous_owner_user_id, self.new_owner_user_id
+        )?;
+        writeln!(
+            f,
+            "  removed share grants to the new owner: {}",
+            self.removed_share_grant_ids.len()
+        )?;
+        write!(
+            f,
+            "  removed labels of the previous owner: {}",
+            self.removed_label_ids.len()
+        )
+    }
+}
+
+/// Result of the check of the object of a file in its storage
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum FileObjectCheck {
+    Exists {
+        size_bytes: u64,
+        content_type: Option<String>,
+    },
+    Missing,
+    Failed { error: String },
+    Skipped { reason: String },
+}
+
+/// Result of the extraction of the text of a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum FileParseCheck {
+    Parsed { chars: usize },
+    Failed { error: String },
+    Skipped { reason: String },
+}
+
+/// Whether a file exists in its storage and can be parsed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileVerification {
+    pub file_id: Uuid,
+    pub filename: String,
+    pub file_storage_provider_id: String,
+    pub file_storage_path: String,
+    /// The storage status recorded for the file
+    pub storage_status: String,
+    pub object: FileObjectCheck,
+    pub parse: FileParseCheck,
+}
+
+/// Check that the object of a file exists, and that its text can be extracted the same way as
+/// for generations, bypassing the caches.
+pub async fn verify_file(app_state: &AppState, file_id: &Uuid) -> Result<FileVerification, Report> {
+    let file = app_state
+        .db
+        .find_file_upload(*file_id)
+        .await?
+        .ok_or_else(|| eyre!("File with ID {} not found", file_id))?;
+    let skipped = |reason: &str| FileParseCheck::Skipped {
+        reason: reason.to_string(),
+    };
+
+    let (object, parse) = match app_state
+        .file_storage_providers
+        .get(&file.file_storage_provider_id)
+    {
+        None => (
+            FileObjectCheck::Failed {
+                error: "The file storage provider is not configured".to_string(),
+            },
+            skipped("the object could not be checked"),
+        ),
+        Some(storage) if storage.is_sharepoint() => (
+            FileObjectCheck::Skipped {
+                reason: "Sharepoint files can only be read with the access token of a user"
+                    .to_string(),
+            },
+            skipped("the object could not be checked"),
+        ),
+        Some(storage) => match storage.stat_object(&file.file_storage_path).await {
+            Err(err) if is_not_found_error(&err) => {
+                (FileObjectCheck::Missing, skipped("the object is missing"))
+            }
+            Err(err) => (
+                FileObjectCheck::Failed {
+                    error: format!("{err:#}"),
+                },
+                skipped("the object could not be checked"),
+            ),
+            Ok(metadata) => {
+                let parse = if is_image_file(&file.filename) {
+                    skipped("images are passed to the model without parsing")
+                } else {
+                    let parsed = match storage.read_file_to_bytes(&file.file_storage_path).await {
+                        Ok(file_bytes) => {
+                            parse_text_file_bytes(
+                                app_state.file_processor.as_ref(),
+                                file_bytes,
+                                &file.filename,
+                                metadata.content_type.as_deref(),
+                                ParseLimits::from_config(&app_state.config.file_processor),
+                            )
+                            .await
+                        }
+                        Err(err) => Err(err),
+                    };
+                    match parsed {
+                        Ok(text) => FileParseCheck::Parsed {
+                            chars: text.chars().count(),
+                        },
+                        Err(err) => FileParseCheck::Failed {
+                            error: format!("{err:#}"),
+                        },
+                    }
+                };
+                (
+                    FileObjectCheck::Exists {
+                        size_bytes: metadata.size_bytes,
+                        content_type: metadata.content_type,
+                    },
+                    parse,
+                )
+            }
+        },
+    };
+
+    Ok(FileVerification {
+        file_id: file.id,
+        filename: file.filename,
+        file_storage_provider_id: file.file_storage_provider_id,
+        file_storage_path: file.file_storage_path,
+        storage_status: file.storage_status,
+        object,
+        parse,
+    })
+}
+
+impl fmt::Display for FileVerification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "File {} \"{}\"", self.file_id, self.filename)?;
+        writeln!(
+            f,
+            "  storage: {}:{} ({})",
+            self.file_storage_provider_id, self.file_storage_path, self.storage_status
+        )?;
+        match &self.object {
+            FileObjectCheck::Exists {
+                size_bytes,
+                content_type,
+            } => writeln!(
+                f,
+                "  object: exists, {size_bytes} bytes, {}",
+                content_type.as_deref().unwrap_or("unknown content type")
+            )?,
+            FileObjectCheck::Missing => writeln!(f, "  object: missing")?,
+            FileObjectCheck::Failed { error } => writeln!(f, "  object: failed: {error}")?,
+            FileObjectCheck::Skipped { reason } => writeln!(f, "  object: skipped, {reason}")?,
+        }
+        match &self.parse {
+            FileParseCheck::Parsed { chars } => write!(f, "  parse: ok, {chars} characters"),
+            FileParseCheck::Failed { error } => write!(f, "  parse: failed: {error}"),
+            FileParseCheck::Skipped { reason } => write!(f, "  parse: skipped, {reason}"),
+        }
+    }
+}
+
+/// Result of a rebuild of the policy data
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PolicyRebuild {
+    pub duration_ms: u128,
+    /// Whether the other instances were notified to rebuild their policy data as well
+    pub propagated: bool,
+}
+
+/// Rebuild the policy data, to check that it can be built from the current database.
+///
+/// Unless `dry_run`, an invalidation is recorded in the outbox, so that all running instances
+/// rebuild their policy data as well.
+pub async fn rebuild_policy(app_state: &AppState, dry_run: bool) -> Result<PolicyRebuild, Report> {
+    let started_at = Instant::now();
+    app_state.global_policy_engine.invalidate_data().await;
+    app_state
+        .global_policy_engine
+        .rebuild_data_if_needed(&app_state.db, &app_state.config)
+        .await?;
+    let duration_ms = started_at.elapsed().as_millis();
+    if !dry_run {
+        record_policy_invalidation(&app_state.db, PolicyInvalidationReason::ManualRebuild).await?;
+    }
+    Ok(PolicyRebuild {
+        duration_ms,
+        propagated: !dry_run,
+    })
+}
+
+impl fmt::Display for PolicyRebuild {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rebuilt policy data in {} ms", self.duration_ms)?;
+        if self.propagated {
+            write!(f, ", running instances will rebuild as well")
+        } else {
+            write!(f, ", running instances were not notified")
+        }
+    }
+}
+
+/// A record that refers to nothing, or to something that doesn't exist
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OrphanedRecord {
+    pub id: Uuid,
+    /// What the record is, or what it refers to
+    pub description: String,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+/// Records left behind by crashes, deleted users or bugs of older versions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OrphanReport {
+    /// Files that are neither linked to a chat or an assistant, nor attached to a message
+    pub unreferenced_file_uploads: Vec<OrphanedRecord>,
+    /// Share grants of chats and assistants that don't exist anymore
+    pub share_grants_of_missing_resources: Vec<OrphanedRecord>,
+    /// Chats whose owner doesn't exist as a user
+    pub chats_of_missing_owners: Vec<OrphanedRecord>,
+    /// Messages linked to messages of other chats
+    pub cross_chat_message_links: Vec<CrossChatMessageLink>,
+}
+
+/// Find orphaned records. Nothing is modified.
+pub async fn report_orphans(app_state: &AppState) -> Result<OrphanReport, Report> {
+    let db = &app_state.db;
+    Ok(OrphanReport {
+        unreferenced_file_uploads: get_unreferenced_file_uploads(db)
+            .await?
+            .into_iter()
+            .map(|file| OrphanedRecord {
+                id: file.id,
+                description: format!("{} of user {}", file.filename, file.owner_user_id),
+                created_at: file.created_at,
+            })
+            .collect(),
+        share_grants_of_missing_resources: get_share_grants_of_missing_resources(db)
+            .await?
+            .into_iter()
+            .map(|grant| OrphanedRecord {
+                id: grant.id,
+                description: format!(
+                    "{} {} shared with {} {}",
+                    grant.resource_type,
+                    grant.resource_id,
+                    grant.subject_type,
+                    grant.subject_id
+                ),
+                created_at: grant.created_at,
+            })
+            .collect(),
+        chats_of_missing_owners: get_chats_of_missing_owners(db)
+            .await?
+            .into_iter()
+            .map(|chat| OrphanedRecord {
+                id: chat.id,
+                description: format!("owned by {}", chat.owner_user_id),
+                created_at: chat.created_at,
+            })
+            .collect(),
+        cross_chat_message_links: find_cross_chat_message_links(db).await?,
+    })
+}
+
+impl fmt::Display for OrphanReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (title, records) in [
+            ("Unreferenced files", &self.unreferenced_file_uploads),
+            (
+                "Share grants of missing resources",
+                &self.share_grants_of_missing_resources,
+            ),
+            ("Chats of missing owners", &self.chats_of_missing_owners),
+        ] {
+            writeln!(f, "{title}: {}", records.len())?;
+            for record in records {
+                writeln!(
+                    f,
+                    "  {} {} ({})",
+                    record.id, record.description, record.created_at
+                )?;
+            }
+        }
+        write!(
+            f,
+            "Messages linked to other chats: {}",
+            self.cross_chat_message_links.len()
+        )?;
+        for link in &self.cross_chat_message_links {
+            write!(
+                f,
+                "\n  {} of chat {} -> {} of chat {} ({})",
+                link.message_id,
+                link.chat_id,
+                link.linked_message_id,
+                link.linked_chat_id,
+                link.link_type
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -4,16 +4,24 @@
 //! tasks that can continue in the background even if the client disconnects.
 //! Clients can resume streaming from any point by reconnecting.
 
-use crate::config::GenerationStatusConfig;
+use crate::config::{GenerationStatusConfig, OutputPacingConfig};
+use crate::db::entity::prelude::Chats;
+use crate::metrics::report_detached_generation_completed;
 use crate::metrics_constants::{
     POSTGRES_QUERY_GENERATION_CLEANUP, POSTGRES_QUERY_GENERATION_FINISH,
-    POSTGRES_QUERY_GENERATION_HEARTBEAT, POSTGRES_QUERY_GENERATION_REAP,
-    POSTGRES_QUERY_GENERATION_START,
+    POSTGRES_QUERY_GENERATION_HEARTBEAT, POSTGRES_QUERY_GENERATION_MESSAGE,
+    POSTGRES_QUERY_GENERATION_REAP, POSTGRES_QUERY_GENERATION_START,
+};
+use crate::models::file_upload::UnavailableFile;
+use crate::models::message::finalize_stale_message_content_drafts;
+use crate::models::message::{ContentPart, PromptInjectionWarning};
+use crate::models::notification::{
+    GenerationFailedPayload, NotificationPayload, create_notification,
 };
-use crate::models::message::ContentPart;
 use crate::query_metrics::named_statement_from_sql_and_values;
 use crate::server::api::v1beta::ChatMessage;
-use sea_orm::{ConnectionTrait, DatabaseConnection, JsonValue};
+use chrono::Utc;
+use sea_orm::{ConnectionTrait, DatabaseConnection, EntityTrait, JsonValue};
 use serde::{Deserialize, Serialize};
 use sqlx::types::Uuid;
 use std::collections::HashMap;
@@ -22,12 +30,19 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use tokio::sync::{Notify, RwLock, broadcast, oneshot};
 use tokio::task::JoinHandle;
+use utoipa::ToSchema;
 
 use crate::services::client_tools::{ClientToolDelivery, ClientToolOutcome};
+use crate::services::generation_queue::{GenerationAdmission, GenerationPermit, GenerationQueue};
+use crate::services::provider_capture::{ProviderCaptureStore, cleanup_expired_provider_captures};
+use crate::services::user_events::{UserEvent, UserEventRegistry};
 
 /// Maximum number of events to store in history per task
 const MAX_EVENT_HISTORY: usize = 10_000;
 
+/// Interval at which expired chat provider traffic captures are deleted
+const PROVIDER_CAPTURE_CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 /// Terminal outcome of a generation, persisted as the chat's generation state.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TaskOutcome {
@@ -44,6 +59,18 @@ impl TaskOutcome {
     }
 }
 
+/// Error of a generation that was rejected because another replica of the backend is generating
+/// an answer in the chat
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, ToSchema)]
+pub struct GenerationInProgressError {
+    /// Description of the error
+    pub error: String,
+    /// The ID of the assistant message that is being generated, once it was created
+    pub message_id: Option<String>,
+    /// The ID of the backend replica that runs the generation
+    pub replica_id: String,
+}
+
 /// Manager for background streaming tasks
 #[derive(Clone, Debug)]
 pub struct BackgroundTaskManager {
@@ -55,6 +82,21 @@ pub struct BackgroundTaskManager {
     db: Option<DatabaseConnection>,
     /// Handle to the heartbeat/reaper task, kept alive with the manager.
     _maintenance_task: Option<Arc<JoinHandle<()>>>,
+    /// Handle to the task deleting expired chat provider traffic captures.
+    _provider_capture_cleanup_task: Option<Arc<JoinHandle<()>>>,
+    /// Notifies the owners of tasks started via `start_task_for_user` about the
+    /// start and end of their generations.
+    user_events: Option<UserEventRegistry>,
+    /// Long-running maintenance tasks by name, e.g. data migrations started by admins.
+    maintenance_tasks: Arc<std::sync::Mutex<HashMap<&'static str, JoinHandle<()>>>>,
+    /// Identifies this replica in the leases of its generations.
+    replica_id: String,
+    /// Seconds after the last heartbeat before the lease of another replica is considered expired.
+    stale_after_secs: u64,
+    /// Limits the number of concurrent generations, if configured.
+    generation_queue: Option<GenerationQueue>,
+    /// Whether generations over the limit wait in the queue instead of being rejected.
+    queue_when_limited: bool,
 }
 
 impl BackgroundTaskManager {
@@ -62,11 +104,17 @@ impl BackgroundTaskManager {
     pub fn new(db: Option<DatabaseConnection>, config: GenerationStatusConfig) -> Self {
         let tasks: Arc<RwLock<HashMap<Uuid, Arc<StreamingTask>>>> =
             Arc::new(RwLock::new(HashMap::new()));
+        let replica_id = config
+            .replica_id
+            .clone()
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let stale_after_secs = config.stale_after_secs;
 
         let maintenance_task = db.clone().map(|db| {
             let tasks = Arc::clone(&tasks);
+            let replica_id = replica_id.clone();
             Arc::new(tokio::spawn(async move {
-                Self::run_maintenance_task(db, tasks, config).await;
+                Self::run_maintenance_task(db, tasks, config, replica_id).await;
             }))
         });
 
@@ -74,30 +122,174 @@ impl BackgroundTaskManager {
             tasks,
             db,
             _maintenance_task: maintenance_task,
+            _provider_capture_cleanup_task: None,
+            user_events: None,
+            maintenance_tasks: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            replica_id,
+            stale_after_secs,
+            generation_queue: None,
+            queue_when_limited: false,
+        }
+    }
+
+    /// The ID of this replica, as stored in the leases of its generations.
+    pub fn replica_id(&self) -> &str {
+        &self.replica_id
+    }
+
+    /// Periodically delete chat provider traffic captures older than `retention_hours`.
+    pub fn with_provider_capture_cleanup(
+        mut self,
+        db: DatabaseConnection,
+        store: ProviderCaptureStore,
+        retention_hours: u64,
+    ) -> Self {
+        self._provider_capture_cleanup_task = Some(Arc::new(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PROVIDER_CAPTURE_CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                match cleanup_expired_provider_captures(&db, &store, retention_hours).await {
+                    Ok(0) => {}
+                    Ok(deleted) => {
+                        tracing::info!(deleted, "Deleted expired chat provider traffic captures");
+                    }
+                    Err(err) => {
+                        tracing::warn!(error = %err, "Failed to clean up chat provider traffic captures");
+                    }
+                }
+            }
+        })));
+        self
+    }
+
+    /// Publish generation events of user-owned tasks to the given registry.
+    pub fn with_user_events(mut self, user_events: UserEventRegistry) -> Self {
+        self.user_events = Some(user_events);
+        self
+    }
+
+    /// Limit the number of generations that run at the same time on this replica.
+    ///
+    /// Generations over the limit are rejected, or wait in a queue if `queue_when_limited` is set.
+    pub fn with_generation_limit(
+        mut self,
+        max_concurrent_generations: Option<usize>,
+        queue_when_limited: bool,
+    ) -> Self {
+        self.generation_queue = max_concurrent_generations.map(GenerationQueue::new);
+        self.queue_when_limited = queue_when_limited;
+        self
+    }
+
+    /// Admit a new generation under the concurrency limit of this replica.
+    ///
+    /// Returns `None` if the limit is reached and generations over it are rejected.
+    pub fn admit_generation(&self) -> Option<GenerationAdmission> {
+        let Some(queue) = &self.generation_queue else {
+            return Some(GenerationAdmission::Started(GenerationPermit::unlimited()));
+        };
+        match queue.try_acquire() {
+            Some(permit) => Some(GenerationAdmission::Started(permit)),
+            None if self.queue_when_limited => Some(GenerationAdmission::Queued),
+            None => None,
         }
     }
 
+    /// Wait until an admitted generation can start, sending `queued` events to the task while it
+    /// waits. The generation holds its capacity until the returned permit is dropped.
+    ///
+    /// Returns `None` if the generation was aborted while it was queued.
+    pub async fn wait_for_generation_start(
+        &self,
+        admission: GenerationAdmission,
+        owner_user_id: &str,
+        task: &Arc<StreamingTask>,
+    ) -> Option<GenerationPermit> {
+        match (admission, &self.generation_queue) {
+            (GenerationAdmission::Started(permit), _) => Some(permit),
+            (GenerationAdmission::Queued, Some(queue)) => queue.wait(owner_user_id, task).await,
+            (GenerationAdmission::Queued, None) => Some(GenerationPermit::unlimited()),
+        }
+    }
+
+    /// Run a maintenance task in the background, unless a task with the same name is running.
+    ///
+    /// Returns whether the task was started.
+    pub fn start_maintenance_task<F>(&self, name: &'static str, task: F) -> bool
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut maintenance_tasks = self.maintenance_tasks.lock().unwrap();
+        if maintenance_tasks
+            .get(name)
+            .is_some_and(|handle| !handle.is_finished())
+        {
+            return false;
+        }
+        maintenance_tasks.insert(name, tokio::spawn(task));
+        true
+    }
+
+    /// Whether the maintenance task with the given name is currently running.
+    pub fn is_maintenance_task_running(&self, name: &str) -> bool {
+        self.maintenance_tasks
+            .lock()
+            .unwrap()
+            .get(name)
+            .is_some_and(|handle| !handle.is_finished())
+    }
+
     /// Start a new background task for the given chat
     ///
-    /// If a task already exists for this chat, it will be replaced.
+    /// If a task already exists for this chat, it will be replaced. Fails if another replica holds
+    /// an unexpired lease on a generation in the chat.
     /// Returns a receiver for live events and the task handle.
     pub async fn start_task(
         &self,
         chat_id: Uuid,
         message_id: Uuid,
-    ) -> (broadcast::Receiver<StreamingEvent>, Arc<StreamingTask>) {
+    ) -> Result<(broadcast::Receiver<StreamingEvent>, Arc<StreamingTask>), GenerationInProgressError>
+    {
+        self.start_task_with_owner(chat_id, message_id, None).await
+    }
+
+    /// Start a new background task for a chat of the given user.
+    ///
+    /// Like `start_task`, but additionally notifies the user's other sessions once
+    /// the generation has started and when it ends.
+    pub async fn start_task_for_user(
+        &self,
+        chat_id: Uuid,
+        message_id: Uuid,
+        owner_user_id: &str,
+    ) -> Result<(broadcast::Receiver<StreamingEvent>, Arc<StreamingTask>), GenerationInProgressError>
+    {
+        let owner = self.user_events.clone().map(|user_events| TaskEventOwner {
+            user_events,
+            user_id: owner_user_id.to_string(),
+            chat_id,
+        });
+        self.start_task_with_owner(chat_id, message_id, owner).await
+    }
+
+    async fn start_task_with_owner(
+        &self,
+        chat_id: Uuid,
+        message_id: Uuid,
+        owner: Option<TaskEventOwner>,
+    ) -> Result<(broadcast::Receiver<StreamingEvent>, Arc<StreamingTask>), GenerationInProgressError>
+    {
         // Create a new streaming task
-        let task = Arc::new(StreamingTask::new(message_id, Uuid::new_v4()));
+        let mut task = StreamingTask::new(message_id, Uuid::new_v4());
+        task.event_owner = owner;
+        let task = Arc::new(task);
         let receiver = task.subscribe();
 
-        // Insert into the map, replacing any existing task
-        {
-            let mut tasks = self.tasks.write().await;
-            tasks.insert(chat_id, Arc::clone(&task));
-        }
-
-        // Best-effort lease claim: a failed status write must never fail a
-        // healthy generation.
+        // Claim the lease before the task becomes visible. Leases of this
+        // replica are taken over (the task is replaced below), while the
+        // unexpired lease of another replica rejects the generation.
+        // Otherwise the claim is best-effort: a failed status write must never
+        // fail a healthy generation.
         if let Some(db) = &self.db {
             let statement = named_statement_from_sql_and_values(
                 sea_orm::DatabaseBackend::Postgres,
@@ -108,21 +300,115 @@ impl BackgroundTaskManager {
                     generation_state = 'running',
                     generation_started_at = now(),
                     generation_heartbeat_at = now(),
-                    generation_ended_at = NULL
+                    generation_ended_at = NULL,
+                    generation_replica_id = $3,
+                    generation_message_id = NULL
                 WHERE id = $2
+                  AND (generation_state IS DISTINCT FROM 'running'
+                       OR generation_replica_id IS NOT DISTINCT FROM $3
+                       OR generation_heartbeat_at < now() - make_interval(secs => $4::double precision))
                 "#,
-                [task.generation_id.into(), chat_id.into()],
+                [
+                    task.generation_id.into(),
+                    chat_id.into(),
+                    self.replica_id.clone().into(),
+                    (self.stale_after_secs as f64).into(),
+                ],
             );
-            if let Err(err) = db.execute_raw(statement).await {
+            match db.execute_raw(statement).await {
+                Ok(result) if result.rows_affected() == 0 => {
+                    if let Some(conflict) = self.generation_on_other_replica(&chat_id).await {
+                        return Err(conflict);
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    tracing::warn!(
+                        chat_id = %chat_id,
+                        error = %err,
+                        "Failed to persist generation start"
+                    );
+                }
+            }
+        }
+
+        // Insert into the map, replacing any existing task
+        {
+            let mut tasks = self.tasks.write().await;
+            tasks.insert(chat_id, Arc::clone(&task));
+        }
+
+        Ok((receiver, task))
+    }
+
+    /// The generation that another replica is running in the given chat, if it holds an
+    /// unexpired lease.
+    pub async fn generation_on_other_replica(
+        &self,
+        chat_id: &Uuid,
+    ) -> Option<GenerationInProgressError> {
+        let db = self.db.as_ref()?;
+        let chat = match Chats::find_by_id(*chat_id).one(db).await {
+            Ok(chat) => chat?,
+            Err(err) => {
                 tracing::warn!(
                     chat_id = %chat_id,
                     error = %err,
-                    "Failed to persist generation start"
+                    "Failed to load the generation lease of a chat"
                 );
+                return None;
             }
+        };
+        let heartbeat_age = Utc::now().fixed_offset() - chat.generation_heartbeat_at?;
+        if chat.generation_state.as_deref() != Some("running")
+            || chat.generation_replica_id.as_deref() == Some(self.replica_id.as_str())
+            || heartbeat_age.num_seconds() >= self.stale_after_secs as i64
+        {
+            return None;
         }
 
-        (receiver, task)
+        let replica_id = chat
+            .generation_replica_id
+            .unwrap_or_else(|| "unknown".to_string());
+        Some(GenerationInProgressError {
+            error: format!(
+                "An answer is already being generated in this chat by backend replica '{}'",
+                replica_id
+            ),
+            message_id: chat.generation_message_id.map(|id| id.to_string()),
+            replica_id,
+        })
+    }
+
+    /// Record the assistant message of a generation in its lease, so that it can be reported to
+    /// clients of other replicas.
+    pub async fn record_generation_message_id(
+        &self,
+        chat_id: &Uuid,
+        generation_id: Uuid,
+        message_id: Uuid,
+    ) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        let statement = named_statement_from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            POSTGRES_QUERY_GENERATION_MESSAGE,
+            r#"
+            UPDATE chats
+            SET generation_message_id = $1
+            WHERE id = $2
+              AND active_generation_id = $3
+            "#,
+            [message_id.into(), (*chat_id).into(), generation_id.into()],
+        );
+        if let Err(err) = db.execute_raw(statement).await {
+            tracing::warn!(
+                chat_id = %chat_id,
+                error = %err,
+                "Failed to persist the message of a generation"
+            );
+        }
     }
 
     /// Get an existing task for the given chat
@@ -137,17 +423,50 @@ impl BackgroundTaskManager {
     /// outcome. Gated on `generation_id` so a stale wrapper finishing late
     /// cannot remove (or mark terminal) a replacement generation.
     pub async fn remove_task(&self, chat_id: &Uuid, generation_id: Uuid, outcome: TaskOutcome) {
-        {
+        let removed_task = {
             let mut tasks = self.tasks.write().await;
             if tasks
                 .get(chat_id)
                 .is_some_and(|task| task.generation_id == generation_id)
             {
-                tasks.remove(chat_id);
+                tasks.remove(chat_id)
+            } else {
+                None
+            }
+        };
+        // Failures that no client saw are kept in the inbox of the owner
+        let mut unseen_failure = None;
+        if let Some(task) = removed_task {
+            task.notify_owner_of_outcome(outcome);
+            if outcome == TaskOutcome::Completed && task.is_client_detached() {
+                report_detached_generation_completed();
+            }
+            if outcome == TaskOutcome::Errored && task.subscriber_count() == 0 {
+                unseen_failure = task
+                    .owner_user_id()
+                    .map(|owner_user_id| (owner_user_id, task.known_message_id()));
             }
         }
 
         if let Some(db) = &self.db {
+            if let Some((owner_user_id, message_id)) = unseen_failure
+                && let Err(err) = create_notification(
+                    db,
+                    owner_user_id,
+                    &NotificationPayload::GenerationFailed(GenerationFailedPayload {
+                        chat_id: chat_id.to_string(),
+                        message_id: message_id.map(|id| id.to_string()),
+                    }),
+                )
+                .await
+            {
+                tracing::warn!(
+                    chat_id = %chat_id,
+                    error = %err,
+                    "Failed to notify the owner of a failed generation"
+                );
+            }
+
             let statement = named_statement_from_sql_and_values(
                 sea_orm::DatabaseBackend::Postgres,
                 POSTGRES_QUERY_GENERATION_FINISH,
@@ -180,6 +499,7 @@ impl BackgroundTaskManager {
         db: DatabaseConnection,
         tasks: Arc<RwLock<HashMap<Uuid, Arc<StreamingTask>>>>,
         config: GenerationStatusConfig,
+        replica_id: String,
     ) {
         let mut interval =
             tokio::time::interval(Duration::from_secs(config.heartbeat_interval_secs.max(1)));
@@ -200,7 +520,8 @@ impl BackgroundTaskManager {
             if !chat_ids.is_empty() {
                 // The heartbeat also re-asserts the lease: two same-chat
                 // starts can race their start UPDATEs, and the map is this
-                // process's source of truth.
+                // process's source of truth. Leases that another replica took
+                // over after they expired are left alone.
                 let statement = named_statement_from_sql_and_values(
                     sea_orm::DatabaseBackend::Postgres,
                     POSTGRES_QUERY_GENERATION_HEARTBEAT,
@@ -214,8 +535,13 @@ impl BackgroundTaskManager {
                     ) active
                     WHERE chats.id = active.chat_id
                       AND chats.generation_state = 'running'
+                      AND chats.generation_replica_id IS NOT DISTINCT FROM $3
                     "#,
-                    [chat_ids.into(), generation_ids.into()],
+                    [
+                        chat_ids.into(),
+                        generation_ids.into(),
+                        replica_id.clone().into(),
+                    ],
                 );
                 if let Err(err) = db.execute_raw(statement).await {
                     tracing::warn!(error = %err, "Failed to heartbeat running generations");
@@ -237,6 +563,17 @@ impl BackgroundTaskManager {
                 tracing::warn!(error = %err, "Failed to reap stale generations");
             }
 
+            // Merge the content streamed by generations that ended without completing (e.g.
+            // reaped above) into their assistant messages.
+            match finalize_stale_message_content_drafts(&db, config.stale_after_secs).await {
+                Ok(0) => {}
+                Ok(finalized) => tracing::info!(
+                    finalized,
+                    "Finalized content drafts of interrupted generations"
+                ),
+                Err(err) => tracing::warn!(error = %err, "Failed to finalize content drafts"),
+            }
+
             // Clear terminal rows once past the retention window, so the
             // partial index only ever covers currently relevant rows.
             let statement = named_statement_from_sql_and_values(
@@ -316,6 +653,13 @@ impl Drop for TaskCleanupGuard {
     }
 }
 
+/// The user that is notified about the start and end of a task's generation.
+struct TaskEventOwner {
+    user_events: UserEventRegistry,
+    user_id: String,
+    chat_id: Uuid,
+}
+
 /// A streaming task that manages event broadcasting and history
 pub struct StreamingTask {
     /// Identity of this generation attempt, distinguishing it from other
@@ -326,6 +670,13 @@ pub struct StreamingTask {
     /// the message) and updated via `set_message_id`. Behind a lock so it can be
     /// corrected after the task is shared. Read via `message_id()`.
     message_id: std::sync::RwLock<Uuid>,
+    /// Whether `message_id` holds the real id, i.e. `set_message_id` was called.
+    message_id_known: AtomicBool,
+    /// Pacing of the text streamed to clients, from the model settings of the chat provider once
+    /// the generation chose it.
+    output_pacing: std::sync::RwLock<Option<OutputPacingConfig>>,
+    /// Owner to notify about the generation, if started via `start_task_for_user`.
+    event_owner: Option<TaskEventOwner>,
     /// Broadcast sender for live events
     event_tx: broadcast::Sender<StreamingEvent>,
     /// Storage for all events (for replay)
@@ -337,6 +688,8 @@ pub struct StreamingTask {
     completed: Arc<AtomicBool>,
     /// Whether cancellation was requested by the user
     abort_requested: Arc<AtomicBool>,
+    /// Whether the generation continued without a client after its last client disconnected
+    client_detached: AtomicBool,
     /// Notifies waiters when an abort is requested
     abort_notify: Arc<Notify>,
     /// Senders for in-flight client-executed tool calls, keyed by tool_call_id.
@@ -370,11 +723,15 @@ impl StreamingTask {
         Self {
             generation_id,
             message_id: std::sync::RwLock::new(message_id),
+            message_id_known: AtomicBool::new(false),
+            output_pacing: std::sync::RwLock::new(None),
+            event_owner: None,
             event_tx,
             event_history: Arc::new(RwLock::new(Vec::new())),
             saw_error: Arc::new(AtomicBool::new(false)),
             completed: Arc::new(AtomicBool::new(false)),
             abort_requested: Arc::new(AtomicBool::new(false)),
+            client_detached: AtomicBool::new(false),
             abort_notify: Arc::new(Notify::new()),
             pending_client_tools: Arc::new(RwLock::new(HashMap::new())),
         }
@@ -388,8 +745,68 @@ impl StreamingTask {
     /// Record the real assistant message id once the generation task has created
     /// it. Client-tool results are routed to a task by this id, so it must match
     /// the id the client received in the `client_tool_call` event.
+    ///
+    /// The first call also notifies the task's owner that the generation started.
     pub fn set_message_id(&self, message_id: Uuid) {
         *self.message_id.write().expect("message_id lock poisoned") = message_id;
+        if !self.message_id_known.swap(true, Ordering::SeqCst)
+            && let Some(owner) = &self.event_owner
+        {
+            owner.user_events.publish(
+                &owner.user_id,
+                UserEvent::GenerationStarted {
+                    chat_id: owner.chat_id,
+                    message_id,
+                },
+            );
+        }
+    }
+
+    /// The pacing of the text streamed to clients, if the generation configured it.
+    pub fn output_pacing(&self) -> Option<OutputPacingConfig> {
+        self.output_pacing
+            .read()
+            .expect("output_pacing lock poisoned")
+            .clone()
+    }
+
+    /// Record the pacing of the text streamed to clients, once the chat provider of the
+    /// generation is known.
+    pub fn set_output_pacing(&self, output_pacing: Option<OutputPacingConfig>) {
+        *self.output_pacing.write().expect("output_pacing lock poisoned") = output_pacing;
+    }
+
+    fn notify_owner_of_outcome(&self, outcome: TaskOutcome) {
+        let Some(owner) = &self.event_owner else {
+            return;
+        };
+        let chat_id = owner.chat_id;
+        let message_id = self.known_message_id();
+        let event = match outcome {
+            TaskOutcome::Completed => UserEvent::GenerationCompleted {
+                chat_id,
+                message_id,
+            },
+            TaskOutcome::Errored => UserEvent::GenerationFailed {
+                chat_id,
+                message_id,
+            },
+        };
+        owner.user_events.publish(&owner.user_id, event);
+    }
+
+    /// The ID of the assistant message of this task, once it was assigned.
+    fn known_message_id(&self) -> Option<Uuid> {
+        self.message_id_known
+            .load(Ordering::SeqCst)
+            .then(|| self.message_id())
+    }
+
+    /// The ID of the user that owns the chat of this task, if known.
+    fn owner_user_id(&self) -> Option<Uuid> {
+        self.event_owner
+            .as_ref()
+            .and_then(|owner| Uuid::parse_str(&owner.user_id).ok())
     }
 
     /// Subscribe to live events from this task
@@ -463,6 +880,16 @@ impl StreamingTask {
         self.abort_requested.load(Ordering::SeqCst)
     }
 
+    /// Record that the last client of the generation disconnected before it completed.
+    pub fn mark_client_detached(&self) {
+        self.client_detached.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the last client of the generation disconnected before it completed.
+    pub fn is_client_detached(&self) -> bool {
+        self.client_detached.load(Ordering::SeqCst)
+    }
+
     /// Wait until cancellation has been requested.
     pub async fn wait_for_abort(&self) {
         // Register as a waiter BEFORE re-checking the flag. `request_abort` uses
@@ -541,10 +968,21 @@ pub enum StreamingEvent {
     UserMessageSaved {
         message_id: Uuid,
         message: ChatMessage,
+        /// The file the text of the message was attached as, if it was too long
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        auto_attached_file_id: Option<Uuid>,
     },
     /// Assistant message generation started
     #[serde(rename = "assistant_message_started")]
-    AssistantMessageStarted { message_id: Uuid },
+    AssistantMessageStarted {
+        message_id: Uuid,
+        /// OpenTelemetry trace ID of the generation (if OTel is enabled)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        trace_id: Option<String>,
+        /// OpenTelemetry span ID of the generation of this message (if OTel is enabled)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        span_id: Option<String>,
+    },
     /// A text delta was generated
     #[serde(rename = "text_delta")]
     TextDelta {
@@ -559,6 +997,13 @@ pub enum StreamingEvent {
         content_index: usize,
         new_text: String,
     },
+    /// A chunk of the arguments of a tool call was streamed by the LLM
+    #[serde(rename = "tool_call_arguments_delta")]
+    ToolCallArgumentsDelta {
+        message_id: Uuid,
+        tool_call_id: String,
+        args_fragment: String,
+    },
     /// A tool call was proposed by the LLM
     #[serde(rename = "tool_call_proposed")]
     ToolCallProposed {
@@ -597,12 +1042,46 @@ pub enum StreamingEvent {
         #[serde(skip_serializing_if = "Option::is_none")]
         input: Option<JsonValue>,
     },
+    /// A possible prompt injection was found in a file or tool output
+    #[serde(rename = "prompt_injection_warning")]
+    PromptInjectionWarning {
+        message_id: Uuid,
+        warning: PromptInjectionWarning,
+    },
+    /// Attached files are unavailable because their objects are missing in the storage
+    #[serde(rename = "files_unavailable")]
+    FilesUnavailable {
+        message_id: Uuid,
+        files: Vec<UnavailableFile>,
+    },
+    /// The requested chat provider is no longer configured, and the generation falls back to
+    /// another chat provider
+    #[serde(rename = "provider_fallback")]
+    ProviderFallback {
+        message_id: Uuid,
+        requested_chat_provider_id: String,
+        chat_provider_id: String,
+    },
+    /// The generation waits in the queue until the number of concurrent generations drops
+    /// below the limit
+    #[serde(rename = "queued")]
+    Queued {
+        position: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        estimated_wait_seconds: Option<u64>,
+    },
     /// Assistant message was completed
     #[serde(rename = "assistant_message_completed")]
     AssistantMessageCompleted {
         message_id: Uuid,
         content: Vec<ContentPart>,
         message: ChatMessage,
+        /// OpenTelemetry trace ID of the generation (if OTel is enabled)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        trace_id: Option<String>,
+        /// OpenTelemetry span ID of the generation of this message (if OTel is enabled)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        span_id: Option<String>,
     },
     /// An error occurred during message generation
     #[serde(rename = "error")]
@@ -634,7 +1113,7 @@ mod tests {
         let chat_id = Uuid::new_v4();
         let message_id = Uuid::new_v4();
 
-        let (_receiver, task) = manager.start_task(chat_id, message_id).await;
+        let (_receiver, task) = manager.start_task(chat_id, message_id).await.unwrap();
 
         assert_eq!(task.message_id(), message_id);
         assert!(!task.is_completed());
@@ -642,18 +1121,40 @@ mod tests {
         assert!(!task.is_abort_requested());
     }
 
+    #[tokio::test]
+    async fn test_maintenance_task_runs_once_at_a_time() {
+        let manager = BackgroundTaskManager::new(None, GenerationStatusConfig::default());
+        let (finish_tx, finish_rx) = oneshot::channel::<()>();
+
+        assert!(manager.start_maintenance_task("migration", async move {
+            let _ = finish_rx.await;
+        }));
+        assert!(manager.is_maintenance_task_running("migration"));
+        assert!(!manager.start_maintenance_task("migration", async {}));
+
+        finish_tx.send(()).unwrap();
+        while manager.is_maintenance_task_running("migration") {
+            tokio::task::yield_now().await;
+        }
+        assert!(manager.start_maintenance_task("migration", async {}));
+    }
+
     #[tokio::test]
     async fn test_event_storage_and_replay() {
         let manager = BackgroundTaskManager::new(None, GenerationStatusConfig::default());
         let chat_id = Uuid::new_v4();
         let message_id = Uuid::new_v4();
 
-        let (_receiver, task) = manager.start_task(chat_id, message_id).await;
+        let (_receiver, task) = manager.start_task(chat_id, message_id).await.unwrap();
 
         // Send some events
-        task.send_event(StreamingEvent::AssistantMessageStarted { message_id })
-            .await
-            .unwrap();
+        task.send_event(StreamingEvent::AssistantMessageStarted {
+            message_id,
+            trace_id: None,
+            span_id: None,
+        })
+        .await
+        .unwrap();
         task.send_event(StreamingEvent::TextDelta {
             message_id,
             content_index: 0,
@@ -673,7 +1174,7 @@ mod tests {
         let chat_id = Uuid::new_v4();
         let message_id = Uuid::new_v4();
 
-        let (_receiver, task) = manager.start_task(chat_id, message_id).await;
+        let (_receiver, task) = manager.start_task(chat_id, message_id).await.unwrap();
 
         assert!(!task.is_completed());
         task.mark_completed();
@@ -687,8 +1188,8 @@ mod tests {
         let message_id_1 = Uuid::new_v4();
         let message_id_2 = Uuid::new_v4();
 
-        let (_receiver1, task1) = manager.start_task(chat_id, message_id_1).await;
-        let (_receiver2, task2) = manager.start_task(chat_id, message_id_2).await;
+        let (_receiver1, task1) = manager.start_task(chat_id, message_id_1).await.unwrap();
+        let (_receiver2, task2) = manager.start_task(chat_id, message_id_2).await.unwrap();
 
         // Task 2 should have replaced task 1
         assert_ne!(task1.message_id(), task2.message_id());
@@ -705,7 +1206,7 @@ mod tests {
         let message_id = Uuid::new_v4();
 
         let generation_id = {
-            let (_receiver, task) = manager.start_task(chat_id, message_id).await;
+            let (_receiver, task) = manager.start_task(chat_id, message_id).await.unwrap();
             // Task exists here
             assert!(manager.get_task(&chat_id).await.is_some());
             task.generation_id
@@ -726,8 +1227,8 @@ mod tests {
         let manager = BackgroundTaskManager::new(None, GenerationStatusConfig::default());
         let chat_id = Uuid::new_v4();
 
-        let (_receiver1, task1) = manager.start_task(chat_id, Uuid::new_v4()).await;
-        let (_receiver2, task2) = manager.start_task(chat_id, Uuid::new_v4()).await;
+        let (_receiver1, task1) = manager.start_task(chat_id, Uuid::new_v4()).await.unwrap();
+        let (_receiver2, task2) = manager.start_task(chat_id, Uuid::new_v4()).await.unwrap();
 
         // A stale wrapper removing with the replaced generation's id must not
         // evict the replacement task.
@@ -788,7 +1289,7 @@ mod tests {
         let chat_id = Uuid::new_v4();
         let message_id = Uuid::new_v4();
 
-        let (_receiver, task) = manager.start_task(chat_id, message_id).await;
+        let (_receiver, task) = manager.start_task(chat_id, message_id).await.unwrap();
         assert!(!task.is_abort_requested());
 
         task.request_abort();
@@ -796,6 +1297,17 @@ mod tests {
         assert!(task.is_abort_requested());
     }
 
+    #[tokio::test]
+    async fn test_client_detached() {
+        let task = StreamingTask::new(Uuid::new_v4(), Uuid::new_v4());
+        assert!(!task.is_client_detached());
+
+        task.mark_client_detached();
+
+        assert!(task.is_client_detached());
+        assert!(!task.is_abort_requested());
+    }
+
     #[tokio::test]
     async fn set_message_id_updates_the_routing_id() {
         let task = StreamingTask::new(Uuid::new_v4(), Uuid::new_v4());
@@ -855,4 +1367,40 @@ mod tests {
             .await;
         assert_eq!(delivery, ClientToolDelivery::Unknown);
     }
+
+    #[tokio::test]
+    async fn user_task_notifies_owner_of_start_and_outcome() {
+        let user_events = UserEventRegistry::new();
+        let manager = BackgroundTaskManager::new(None, GenerationStatusConfig::default())
+            .with_user_events(user_events.clone());
+        let (mut events, _connection) = user_events.subscribe("user-a").unwrap();
+        let chat_id = Uuid::new_v4();
+        let message_id = Uuid::new_v4();
+
+        let (_receiver, task) = manager
+            .start_task_for_user(chat_id, Uuid::new_v4(), "user-a")
+            .await
+            .unwrap();
+        task.set_message_id(message_id);
+        task.set_message_id(message_id);
+        manager
+            .remove_task(&chat_id, task.generation_id, TaskOutcome::Errored)
+            .await;
+
+        assert_eq!(
+            events.recv().await.unwrap(),
+            UserEvent::GenerationStarted {
+                chat_id,
+                message_id
+            }
+        );
+        assert_eq!(
+            events.recv().await.unwrap(),
+            UserEvent::GenerationFailed {
+                chat_id,
+                message_id: Some(message_id)
+            }
+        );
+        assert!(events.try_recv().is_err());
+    }
 }
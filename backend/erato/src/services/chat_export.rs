@@ -0,0 +1,740 @@
+//! Rendering of chat transcripts as PDF documents.
+//!
+//! Pages are laid out and encoded one at a time, so apart from the message that is currently
+//! being rendered only the encoded document is kept in memory. Its size is bounded by
+//! `chat_export.max_pdf_pages` and `chat_export.max_total_image_bytes`.
+//!
+//! Text is set in the standard Helvetica and Courier fonts with `WinAnsiEncoding`, so no fonts
+//! have to be embedded. Characters outside of that encoding are replaced by `?`.
+
+use crate::config::ChatExportConfig;
+use chrono::{DateTime, FixedOffset};
+use eyre::{Report, WrapErr};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat, dictionary};
+use std::fmt;
+
+// A4 in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+const FOOTER_Y: f32 = 28.0;
+
+const TITLE_FONT_SIZE: f32 = 16.0;
+const HEADER_FONT_SIZE: f32 = 10.5;
+const BODY_FONT_SIZE: f32 = 10.0;
+const CODE_FONT_SIZE: f32 = 8.5;
+const FOOTER_FONT_SIZE: f32 = 7.5;
+const LINE_HEIGHT_FACTOR: f32 = 1.35;
+const CODE_INDENT: f32 = 12.0;
+const MESSAGE_SPACING: f32 = 14.0;
+
+const JPEG_QUALITY: u8 = 80;
+// Images are rendered at 96 DPI, but never wider than the content area.
+const POINTS_PER_PIXEL: f32 = 0.75;
+
+/// The export would have more pages than allowed by `chat_export.max_pdf_pages`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageLimitExceeded {
+    pub max_pages: usize,
+}
+
+impl fmt::Display for PageLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The export exceeds the maximum of {} pages. Narrow down the exported messages with \
+             `from_message_id` and `to_message_id`.",
+            self.max_pages
+        )
+    }
+}
+
+impl std::error::Error for PageLimitExceeded {}
+
+/// A part of a message in the transcript.
+#[derive(Debug, Clone)]
+pub enum TranscriptBlock {
+    /// Markdown text. Fenced code blocks are set in a monospaced font.
+    Text(String),
+    /// A tool call, rendered as a one-line summary without its input and output.
+    ToolCall { tool_name: String, status: String },
+    /// An attached file that is listed by name.
+    Attachment { filename: String },
+    /// An image in any format the `image` crate can decode. Scaled down and embedded as JPEG.
+    Image {
+        filename: Option<String>,
+        bytes: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Font {
+    Regular,
+    Bold,
+    Mono,
+}
+
+impl Font {
+    const ALL: [Font; 3] = [Font::Regular, Font::Bold, Font::Mono];
+
+    fn resource_name(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Mono => "F3",
+        }
+    }
+
+    fn base_font(self) -> &'static str {
+        match self {
+            Font::Regular => "Helvetica",
+            Font::Bold => "Helvetica-Bold",
+            Font::Mono => "Courier",
+        }
+    }
+
+    /// Width of a glyph relative to the font size, used for line wrapping.
+    ///
+    /// Exact for Courier. For Helvetica this is a slightly generous average, so that lines with
+    /// many wide glyphs still fit.
+    fn glyph_width(self) -> f32 {
+        match self {
+            Font::Regular => 0.55,
+            Font::Bold => 0.6,
+            Font::Mono => 0.6,
+        }
+    }
+
+    fn max_chars(self, font_size: f32, width: f32) -> usize {
+        ((width / (font_size * self.glyph_width())) as usize).max(1)
+    }
+}
+
+/// Incrementally renders a chat transcript into a PDF document.
+pub struct PdfTranscriptWriter {
+    config: ChatExportConfig,
+    footer: String,
+    document: Document,
+    pages_id: ObjectId,
+    fonts_id: ObjectId,
+    page_ids: Vec<ObjectId>,
+    /// Drawing operations and images of the page that is currently being laid out
+    operations: Vec<Operation>,
+    page_images: Vec<(String, ObjectId)>,
+    page_open: bool,
+    /// Baseline of the last line on the current page, from the bottom of the page
+    cursor_y: f32,
+    image_count: usize,
+    image_bytes: usize,
+}
+
+impl PdfTranscriptWriter {
+    /// Create a writer. The `footer` is printed on every page, next to the page number.
+    pub fn new(config: &ChatExportConfig, footer: String) -> Self {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let mut fonts = Dictionary::new();
+        for font in Font::ALL {
+            let font_id = document.add_object(dictionary! {
+                "Type" => "Font",
+                "Subtype" => "Type1",
+                "BaseFont" => font.base_font(),
+                "Encoding" => "WinAnsiEncoding",
+            });
+            fonts.set(font.resource_name(), font_id);
+        }
+        let fonts_id = document.add_object(fonts);
+
+        Self {
+            config: config.clone(),
+            footer,
+            document,
+            pages_id,
+            fonts_id,
+            page_ids: Vec::new(),
+            operations: Vec::new(),
+            page_images: Vec::new(),
+            page_open: false,
+            cursor_y: 0.0,
+            image_count: 0,
+            image_bytes: 0,
+        }
+    }
+
+    /// Number of pages rendered so far, including the current one.
+    pub fn page_count(&self) -> usize {
+        self.page_ids.len() + usize::from(self.page_open)
+    }
+
+    /// Add a title line.
+    pub fn add_title(&mut self, title: &str) -> Result<(), Report> {
+        let max_chars = Font::Bold.max_chars(TITLE_FONT_SIZE, CONTENT_WIDTH);
+        for line in wrap_line(title, max_chars) {
+            self.text_line(Font::Bold, TITLE_FONT_SIZE, 0.0, &line)?;
+        }
+        self.space(MESSAGE_SPACING)
+    }
+
+    /// Add a message with a header line naming the author and the time it was sent.
+    pub fn add_message(
+        &mut self,
+        role: &str,
+        sent_at: DateTime<FixedOffset>,
+        blocks: Vec<TranscriptBlock>,
+    ) -> Result<(), Report> {
+        // Keep the header together with at least one line of content.
+        self.ensure_space(line_height(HEADER_FONT_SIZE) + line_height(BODY_FONT_SIZE) + 4.0)?;
+        let header = format!("{role} - {}", sent_at.format("%Y-%m-%d %H:%M:%S %:z"));
+        self.text_line(Font::Bold, HEADER_FONT_SIZE, 0.0, &header)?;
+        self.rule()?;
+
+        for block in blocks {
+            match block {
+                TranscriptBlock::Text(text) => self.markdown(&text)?,
+                TranscriptBlock::ToolCall { tool_name, status } => {
+                    self.wrapped(
+                        Font::Mono,
+                        CODE_FONT_SIZE,
+                        0.0,
+                        &format!("[+] Tool call: {tool_name} ({status})"),
+                    )?;
+                }
+                TranscriptBlock::Attachment { filename } => {
+                    self.wrapped(
+                        Font::Regular,
+                        BODY_FONT_SIZE,
+                        0.0,
+                        &format!("Attachment: {filename}"),
+                    )?;
+                }
+                TranscriptBlock::Image { filename, bytes } => {
+                    self.image(filename.as_deref(), &bytes)?;
+                }
+            }
+        }
+
+        self.space(MESSAGE_SPACING)
+    }
+
+    /// Finish the last page and encode the document.
+    pub fn finish(mut self) -> Result<Vec<u8>, Report> {
+        if !self.page_open {
+            self.new_page()?;
+        }
+        self.flush_page()?;
+
+        let page_count = self.page_ids.len() as i64;
+        let kids: Vec<Object> = self.page_ids.iter().map(|id| (*id).into()).collect();
+        self.document.objects.insert(
+            self.pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => page_count,
+                "MediaBox" => vec![0.into(), 0.into(), PAGE_WIDTH.into(), PAGE_HEIGHT.into()],
+            }),
+        );
+        let catalog_id = self.document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => self.pages_id,
+        });
+        self.document.trailer.set("Root", catalog_id);
+        self.document.compress();
+
+        let mut bytes = Vec::new();
+        self.document
+            .save_to(&mut bytes)
+            .wrap_err("Failed to encode PDF document")?;
+        Ok(bytes)
+    }
+
+    fn markdown(&mut self, text: &str) -> Result<(), Report> {
+        let mut in_code_block = false;
+        for line in text.lines() {
+            let line = line.replace('\t', "    ");
+            if line.trim_start().starts_with("```") {
+                in_code_block = !in_code_block;
+                continue;
+            }
+            if in_code_block {
+                self.wrapped(Font::Mono, CODE_FONT_SIZE, CODE_INDENT, &line)?;
+            } else if let Some(heading) = markdown_heading(&line) {
+                self.wrapped(Font::Bold, BODY_FONT_SIZE, 0.0, heading)?;
+            } else if line.trim().is_empty() {
+                self.space(line_height(BODY_FONT_SIZE) / 2.0)?;
+            } else {
+                self.wrapped(Font::Regular, BODY_FONT_SIZE, 0.0, &line)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn image(&mut self, filename: Option<&str>, bytes: &[u8]) -> Result<(), Report> {
+        let label = filename.unwrap_or("image");
+        let encoded = match self.encode_image(bytes) {
+            Ok(encoded) => encoded,
+            Err(err) => {
+                tracing::warn!("Failed to embed image '{}' in PDF export: {:?}", label, err);
+                return self.wrapped(
+                    Font::Regular,
+                    BODY_FONT_SIZE,
+                    0.0,
+                    &format!("[Image could not be embedded: {label}]"),
+                );
+            }
+        };
+        let Some((jpeg, width_px, height_px)) = encoded else {
+            return self.wrapped(
+                Font::Regular,
+                BODY_FONT_SIZE,
+                0.0,
+                &format!("[Image omitted, the export size limit was reached: {label}]"),
+            );
+        };
+
+        let max_height = PAGE_HEIGHT - 2.0 * MARGIN;
+        let mut width = (width_px as f32 * POINTS_PER_PIXEL).min(CONTENT_WIDTH);
+        let mut height = width * height_px as f32 / width_px as f32;
+        if height > max_height {
+            width *= max_height / height;
+            height = max_height;
+        }
+
+        let image_id = self.document.add_object(
+            Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Image",
+                    "Width" => width_px as i64,
+                    "Height" => height_px as i64,
+                    "ColorSpace" => "DeviceRGB",
+                    "BitsPerComponent" => 8,
+                    "Filter" => "DCTDecode",
+                },
+                jpeg,
+            )
+            .with_compression(false),
+        );
+        self.image_count += 1;
+        let name = format!("Im{}", self.image_count);
+
+        self.ensure_space(height + 4.0)?;
+        self.cursor_y -= height + 4.0;
+        self.page_images.push((name.clone(), image_id));
+        self.operations.extend([
+            Operation::new("q", vec![]),
+            Operation::new(
+                "cm",
+                vec![
+                    width.into(),
+                    0.into(),
+                    0.into(),
+                    height.into(),
+                    MARGIN.into(),
+                    self.cursor_y.into(),
+                ],
+            ),
+            Operation::new("Do", vec![Object::Name(name.into_bytes())]),
+            Operation::new("Q", vec![]),
+        ]);
+        Ok(())
+    }
+
+    /// Scale down and re-encode an image as JPEG.
+    ///
+    /// Returns `None` if the image does not fit into the remaining image budget.
+    fn encode_image(&mut self, bytes: &[u8]) -> Result<Option<(Vec<u8>, u32, u32)>, Report> {
+        let mut image = image::load_from_memory(bytes).wrap_err("Failed to decode image")?;
+        if image.width() > self.config.max_image_width_px {
+            image = image.resize(
+                self.config.max_image_width_px,
+                u32::MAX,
+                FilterType::Triangle,
+            );
+        }
+        let rgb = image.to_rgb8();
+
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY)
+            .encode_image(&rgb)
+            .wrap_err("Failed to encode image as JPEG")?;
+
+        if self.image_bytes + jpeg.len() > self.config.max_total_image_bytes {
+            return Ok(None);
+        }
+        self.image_bytes += jpeg.len();
+        Ok(Some((jpeg, rgb.width(), rgb.height())))
+    }
+
+    fn wrapped(
+        &mut self,
+        font: Font,
+        font_size: f32,
+        indent: f32,
+        text: &str,
+    ) -> Result<(), Report> {
+        let max_chars = font.max_chars(font_size, CONTENT_WIDTH - indent);
+        for line in wrap_line(text, max_chars) {
+            self.text_line(font, font_size, indent, &line)?;
+        }
+        Ok(())
+    }
+
+    fn text_line(
+        &mut self,
+        font: Font,
+        font_size: f32,
+        indent: f32,
+        text: &str,
+    ) -> Result<(), Report> {
+        let height = line_height(font_size);
+        self.ensure_space(height)?;
+        self.cursor_y -= height;
+        self.operations.extend([
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec![font.resource_name().into(), font_size.into()]),
+            Operation::new("Td", vec![(MARGIN + indent).into(), self.cursor_y.into()]),
+            Operation::new(
+                "Tj",
+                vec![Object::String(encode_win_ansi(text), StringFormat::Literal)],
+            ),
+            Operation::new("ET", vec![]),
+        ]);
+        Ok(())
+    }
+
+    /// A thin horizontal line below the current line.
+    fn rule(&mut self) -> Result<(), Report> {
+        self.space(3.0)?;
+        self.operations.extend([
+            Operation::new("w", vec![Object::Real(0.5)]),
+            Operation::new("m", vec![MARGIN.into(), self.cursor_y.into()]),
+            Operation::new(
+                "l",
+                vec![(PAGE_WIDTH - MARGIN).into(), self.cursor_y.into()],
+            ),
+            Operation::new("S", vec![]),
+        ]);
+        Ok(())
+    }
+
+    fn space(&mut self, height: f32) -> Result<(), Report> {
+        if !self.page_open {
+            return Ok(());
+        }
+        // Vertical space at the end of a page is dropped instead of carried over.
+        self.cursor_y = (self.cursor_y - height).max(MARGIN);
+        Ok(())
+    }
+
+    /// Start a new page if less than `height` is left on the current one.
+    fn ensure_space(&mut self, height: f32) -> Result<(), Report> {
+        if self.page_open && self.cursor_y - height >= MARGIN {
+            return Ok(());
+        }
+        if self.page_open {
+            self.flush_page()?;
+        }
+        self.new_page()
+    }
+
+    fn new_page(&mut self) -> Result<(), Report> {
+        if self.page_ids.len() >= self.config.max_pdf_pages {
+            return Err(PageLimitExceeded {
+                max_pages: self.config.max_pdf_pages,
+            }
+            .into());
+        }
+        self.page_open = true;
+        self.cursor_y = PAGE_HEIGHT - MARGIN;
+        Ok(())
+    }
+
+    /// Encode the current page and add it to the document.
+    fn flush_page(&mut self) -> Result<(), Report> {
+        let page_number = self.page_ids.len() + 1;
+        let page_label = format!("Page {page_number}");
+        let page_label_x = PAGE_WIDTH
+            - MARGIN
+            - page_label.len() as f32 * FOOTER_FONT_SIZE * Font::Regular.glyph_width();
+        for (x, text) in [(MARGIN, self.footer.clone()), (page_label_x, page_label)] {
+            self.operations.extend([
+                Operation::new("BT", vec![]),
+                Operation::new(
+                    "Tf",
+                    vec![
+                        Font::Regular.resource_name().into(),
+                        FOOTER_FONT_SIZE.into(),
+                    ],
+                ),
+                Operation::new("Td", vec![x.into(), FOOTER_Y.into()]),
+                Operation::new(
+                    "Tj",
+                    vec![Object::String(
+                        encode_win_ansi(&text),
+                        StringFormat::Literal,
+                    )],
+                ),
+                Operation::new("ET", vec![]),
+            ]);
+        }
+
+        let content = Content {
+            operations: std::mem::take(&mut self.operations),
+        };
+        let content_id = self.document.add_object(Stream::new(
+            dictionary! {},
+            content.encode().wrap_err("Failed to encode PDF page")?,
+        ));
+
+        let mut images = Dictionary::new();
+        for (name, image_id) in std::mem::take(&mut self.page_images) {
+            images.set(name, image_id);
+        }
+        let resources = dictionary! {
+            "Font" => self.fonts_id,
+            "XObject" => images,
+        };
+        let page_id = self.document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => self.pages_id,
+            "Contents" => content_id,
+            "Resources" => resources,
+        });
+        self.page_ids.push(page_id);
+        self.page_open = false;
+        Ok(())
+    }
+}
+
+fn line_height(font_size: f32) -> f32 {
+    font_size * LINE_HEIGHT_FACTOR
+}
+
+fn markdown_heading(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+        Some(trimmed[level..].trim())
+    } else {
+        None
+    }
+}
+
+/// Wrap a line at word boundaries so that no line is longer than `max_chars`.
+///
+/// Words that are longer than a line are split. Leading whitespace is kept, so that indentation
+/// of code is preserved.
+fn wrap_line(text: &str, max_chars: usize) -> Vec<String> {
+    let indent: String = text.chars().take_while(|c| c.is_whitespace()).collect();
+    let indent = if indent.chars().count() < max_chars / 2 {
+        indent
+    } else {
+        String::new()
+    };
+    let indent_len = indent.chars().count();
+
+    let mut lines = Vec::new();
+    let mut current = indent.clone();
+    let mut current_len = indent_len;
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        loop {
+            let separator = usize::from(current_len > indent_len);
+            if current_len + separator + word.len() <= max_chars {
+                if separator == 1 {
+                    current.push(' ');
+                }
+                current.extend(word.iter());
+                current_len += separator + word.len();
+                break;
+            }
+            if current_len > indent_len {
+                lines.push(std::mem::replace(&mut current, indent.clone()));
+                current_len = indent_len;
+                continue;
+            }
+            // The word alone does not fit on a line
+            let rest = word.split_off(max_chars - indent_len);
+            current.extend(word.iter());
+            lines.push(std::mem::replace(&mut current, indent.clone()));
+            word = rest;
+        }
+    }
+    if current_len > indent_len || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Encode text in the `WinAnsiEncoding` of the standard PDF fonts.
+fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .filter(|c| !c.is_control())
+        .map(|c| match c {
+            ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+            '€' => 0x80,
+            '‚' => 0x82,
+            '„' => 0x84,
+            '…' => 0x85,
+            '•' => 0x95,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '–' => 0x96,
+            '—' => 0x97,
+            '™' => 0x99,
+            _ => b'?',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, ImageFormat, Rgb};
+    use std::io::Cursor;
+
+    fn sent_at() -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339("2026-03-01T12:00:00+00:00").unwrap()
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = ImageBuffer::from_fn(width, height, |x, y| {
+            Rgb([(x % 256) as u8, (y % 256) as u8, 128])
+        });
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    fn image_count(pdf: &[u8]) -> usize {
+        let document = Document::load_mem(pdf).expect("export should be a valid PDF");
+        document
+            .objects
+            .values()
+            .filter_map(|object| object.as_stream().ok())
+            .filter(|stream| {
+                stream
+                    .dict
+                    .get(b"Subtype")
+                    .and_then(|subtype| subtype.as_name())
+                    .is_ok_and(|subtype| subtype == b"Image")
+            })
+            .count()
+    }
+
+    #[test]
+    fn wraps_lines_at_word_boundaries() {
+        assert_eq!(
+            wrap_line("the quick brown fox jumps", 10),
+            vec!["the quick", "brown fox", "jumps"]
+        );
+        assert_eq!(wrap_line("abcdefghijkl", 5), vec!["abcde", "fghij", "kl"]);
+        assert_eq!(
+            wrap_line("    let x = 1;", 10),
+            vec!["    let x", "    = 1;"]
+        );
+        assert_eq!(wrap_line("", 10), vec![""]);
+    }
+
+    #[test]
+    fn encodes_text_as_win_ansi() {
+        assert_eq!(
+            encode_win_ansi("Grüße – 5€"),
+            b"Gr\xfc\xdfe \x96 5\x80".to_vec()
+        );
+        assert_eq!(encode_win_ansi("日本"), b"??".to_vec());
+    }
+
+    #[test]
+    fn renders_long_transcripts_on_multiple_pages() {
+        let mut writer = PdfTranscriptWriter::new(&ChatExportConfig::default(), "footer".into());
+        writer.add_title("Chat").unwrap();
+        for i in 0..40 {
+            writer
+                .add_message(
+                    "User",
+                    sent_at(),
+                    vec![TranscriptBlock::Text(format!(
+                        "Message {i}\n\n```rust\nfn main() {{}}\n```\n{}",
+                        "lorem ipsum ".repeat(40)
+                    ))],
+                )
+                .unwrap();
+        }
+        let page_count = writer.page_count();
+        assert!(page_count > 1);
+
+        let pdf = writer.finish().unwrap();
+        assert!(pdf.starts_with(b"%PDF-"));
+        let document = Document::load_mem(&pdf).unwrap();
+        assert_eq!(document.get_pages().len(), page_count);
+    }
+
+    #[test]
+    fn rejects_transcripts_beyond_the_page_limit() {
+        let config = ChatExportConfig {
+            max_pdf_pages: 2,
+            ..ChatExportConfig::default()
+        };
+        let mut writer = PdfTranscriptWriter::new(&config, "footer".into());
+        let err = (0..100)
+            .try_for_each(|_| {
+                writer.add_message(
+                    "Assistant",
+                    sent_at(),
+                    vec![TranscriptBlock::Text("lorem ipsum ".repeat(100))],
+                )
+            })
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PageLimitExceeded>(),
+            Some(&PageLimitExceeded { max_pages: 2 })
+        );
+    }
+
+    #[test]
+    fn embeds_images_within_the_size_budget() {
+        let mut writer = PdfTranscriptWriter::new(&ChatExportConfig::default(), "footer".into());
+        writer
+            .add_message(
+                "User",
+                sent_at(),
+                vec![TranscriptBlock::Image {
+                    filename: Some("chart.png".into()),
+                    bytes: png(64, 32),
+                }],
+            )
+            .unwrap();
+        assert_eq!(image_count(&writer.finish().unwrap()), 1);
+
+        let config = ChatExportConfig {
+            max_total_image_bytes: 100,
+            ..ChatExportConfig::default()
+        };
+        let mut writer = PdfTranscriptWriter::new(&config, "footer".into());
+        writer
+            .add_message(
+                "User",
+                sent_at(),
+                vec![
+                    TranscriptBlock::Image {
+                        filename: Some("chart.png".into()),
+                        bytes: png(64, 32),
+                    },
+                    TranscriptBlock::Image {
+                        filename: None,
+                        bytes: b"not an image".to_vec(),
+                    },
+                ],
+            )
+            .unwrap();
+        assert_eq!(image_count(&writer.finish().unwrap()), 0);
+    }
+}
@@ -0,0 +1,349 @@
+//! Selection of the member of a chat provider group that serves a generation.
+//!
+//! Groups spread the generations for one model across several chat providers, e.g. the
+//! deployments of a model in different regions. The outcomes of recent generations are kept
+//! in memory per member, so that the `least_errors` strategy can route around deployments
+//! that are rate limited or unavailable.
+
+use crate::config::{ChatProviderGroupConfig, ChatProviderGroupStrategy};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long the outcome of a generation counts towards the health of a member.
+const OUTCOME_WINDOW: Duration = Duration::from_secs(300);
+/// Maximum number of outcomes that are kept per member.
+const MAX_OUTCOMES: usize = 100;
+/// Members whose average latency exceeds the one of the fastest member by this factor are
+/// skipped by the `least_errors` strategy.
+const SLOW_MEMBER_LATENCY_FACTOR: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy)]
+struct Outcome {
+    at: Instant,
+    /// Time until the stream started, for successful generations.
+    latency: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+struct BalancerState {
+    outcomes: HashMap<String, VecDeque<Outcome>>,
+    round_robin_counters: HashMap<String, usize>,
+}
+
+impl BalancerState {
+    fn record(&mut self, member_id: &str, outcome: Outcome) {
+        let outcomes = self.outcomes.entry(member_id.to_string()).or_default();
+        outcomes.push_back(outcome);
+        while outcomes.len() > MAX_OUTCOMES {
+            outcomes.pop_front();
+        }
+    }
+
+    fn recent_outcomes(&self, member_id: &str) -> impl Iterator<Item = &Outcome> {
+        let now = Instant::now();
+        self.outcomes
+            .get(member_id)
+            .into_iter()
+            .flatten()
+            .filter(move |outcome| now.duration_since(outcome.at) <= OUTCOME_WINDOW)
+    }
+
+    fn error_rate(&self, member_id: &str) -> f64 {
+        let (total, errors) = self
+            .recent_outcomes(member_id)
+            .fold((0usize, 0usize), |(total, errors), outcome| {
+                (total + 1, errors + usize::from(outcome.latency.is_none()))
+            });
+        if total == 0 {
+            0.0
+        } else {
+            errors as f64 / total as f64
+        }
+    }
+
+    fn average_latency(&self, member_id: &str) -> Option<Duration> {
+        let latencies: Vec<Duration> = self
+            .recent_outcomes(member_id)
+            .filter_map(|outcome| outcome.latency)
+            .collect();
+        if latencies.is_empty() {
+            return None;
+        }
+        Some(latencies.iter().sum::<Duration>() / latencies.len() as u32)
+    }
+
+    fn next_in_turn<'a>(&mut self, group_id: &str, candidates: &[&'a String]) -> &'a String {
+        let counter = self
+            .round_robin_counters
+            .entry(group_id.to_string())
+            .or_default();
+        let selected = candidates[*counter % candidates.len()];
+        *counter = counter.wrapping_add(1);
+        selected
+    }
+}
+
+/// Selects the members of chat provider groups and tracks their health, shared across all
+/// requests.
+#[derive(Debug, Clone, Default)]
+pub struct ChatProviderGroupBalancer {
+    state: Arc<Mutex<BalancerState>>,
+}
+
+impl ChatProviderGroupBalancer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Selects the member of the group that serves the next generation.
+    ///
+    /// Members in `excluded`, e.g. ones that already failed for the current generation, are
+    /// skipped. Returns `None` if no member is left.
+    pub fn select_member(
+        &self,
+        group_id: &str,
+        group: &ChatProviderGroupConfig,
+        excluded: &[String],
+    ) -> Option<String> {
+        self.select_member_with_random(group_id, group, excluded, rand::random::<f64>())
+    }
+
+    /// Like [`Self::select_member`], with the random number in `[0, 1)` that the `weighted`
+    /// strategy uses.
+    fn select_member_with_random(
+        &self,
+        group_id: &str,
+        group: &ChatProviderGroupConfig,
+        excluded: &[String],
+        random: f64,
+    ) -> Option<String> {
+        let candidates: Vec<&String> = group
+            .members
+            .iter()
+            .filter(|member_id| !excluded.contains(member_id))
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let mut state = self
+            .state
+            .lock()
+            .expect("chat provider group state poisoned");
+        let selected = match group.strategy {
+            ChatProviderGroupStrategy::RoundRobin => state.next_in_turn(group_id, &candidates),
+            ChatProviderGroupStrategy::Weighted => {
+                let weighted: Vec<(&String, u32)> = candidates
+                    .iter()
+                    .map(|member_id| (*member_id, group.weight(member_id)))
+                    .filter(|(_, weight)| *weight > 0)
+                    .collect();
+                let total_weight: u32 = weighted.iter().map(|(_, weight)| weight).sum();
+                if total_weight == 0 {
+                    return None;
+                }
+                let mut remaining = random * f64::from(total_weight);
+                let mut selected = weighted[weighted.len() - 1].0;
+                for (member_id, weight) in &weighted {
+                    if remaining < f64::from(*weight) {
+                        selected = *member_id;
+                        break;
+                    }
+                    remaining -= f64::from(*weight);
+                }
+                selected
+            }
+            ChatProviderGroupStrategy::LeastErrors => {
+                let error_rates: Vec<f64> = candidates
+                    .iter()
+                    .map(|member_id| state.error_rate(member_id))
+                    .collect();
+                let min_error_rate = error_rates.iter().copied().fold(f64::INFINITY, f64::min);
+                let healthiest: Vec<&String> = candidates
+                    .iter()
+                    .zip(&error_rates)
+                    .filter(|(_, error_rate)| **error_rate <= min_error_rate)
+                    .map(|(member_id, _)| *member_id)
+                    .collect();
+
+                let fastest_latency = healthiest
+                    .iter()
+                    .filter_map(|member_id| state.average_latency(member_id))
+                    .min();
+                let responsive: Vec<&String> = match fastest_latency {
+                    Some(fastest_latency) => healthiest
+                        .iter()
+                        .copied()
+                        .filter(|member_id| {
+                            state.average_latency(member_id).is_none_or(|latency| {
+                                latency.as_secs_f64()
+                                    <= fastest_latency.as_secs_f64() * SLOW_MEMBER_LATENCY_FACTOR
+                            })
+                        })
+                        .collect(),
+                    None => healthiest,
+                };
+                state.next_in_turn(group_id, &responsive)
+            }
+        };
+        Some(selected.clone())
+    }
+
+    /// Records that a member started streaming a generation after `latency`.
+    pub fn record_success(&self, member_id: &str, latency: Duration) {
+        self.state
+            .lock()
+            .expect("chat provider group state poisoned")
+            .record(
+                member_id,
+                Outcome {
+                    at: Instant::now(),
+                    latency: Some(latency),
+                },
+            );
+    }
+
+    /// Records that a member failed to serve a generation.
+    pub fn record_error(&self, member_id: &str) {
+        self.state
+            .lock()
+            .expect("chat provider group state poisoned")
+            .record(
+                member_id,
+                Outcome {
+                    at: Instant::now(),
+                    latency: None,
+                },
+            );
+    }
+
+    /// Share of the recent generations of a member that failed.
+    pub fn error_rate(&self, member_id: &str) -> f64 {
+        self.state
+            .lock()
+            .expect("chat provider group state poisoned")
+            .error_rate(member_id)
+    }
+
+    /// Average time until the recent generations of a member started streaming, if any of them
+    /// succeeded.
+    pub fn average_latency(&self, member_id: &str) -> Option<Duration> {
+        self.state
+            .lock()
+            .expect("chat provider group state poisoned")
+            .average_latency(member_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(
+        strategy: ChatProviderGroupStrategy,
+        weights: &[(&str, u32)],
+    ) -> ChatProviderGroupConfig {
+        ChatProviderGroupConfig {
+            members: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            strategy,
+            weights: weights
+                .iter()
+                .map(|(member_id, weight)| (member_id.to_string(), *weight))
+                .collect(),
+            model_display_name: None,
+        }
+    }
+
+    fn selections(
+        balancer: &ChatProviderGroupBalancer,
+        group: &ChatProviderGroupConfig,
+        count: usize,
+    ) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for i in 0..count {
+            let random = i as f64 / count as f64;
+            let member_id = balancer
+                .select_member_with_random("group", group, &[], random)
+                .unwrap();
+            *counts.entry(member_id).or_default() += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn test_round_robin_takes_turns() {
+        let balancer = ChatProviderGroupBalancer::new();
+        let group = group(ChatProviderGroupStrategy::RoundRobin, &[]);
+
+        let selected: Vec<String> = (0..4)
+            .map(|_| balancer.select_member("group", &group, &[]).unwrap())
+            .collect();
+
+        assert_eq!(selected, vec!["a", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_weighted_follows_weights() {
+        let balancer = ChatProviderGroupBalancer::new();
+        let group = group(
+            ChatProviderGroupStrategy::Weighted,
+            &[("a", 3), ("b", 1), ("c", 0)],
+        );
+
+        let counts = selections(&balancer, &group, 400);
+
+        assert_eq!(counts.get("a"), Some(&300));
+        assert_eq!(counts.get("b"), Some(&100));
+        assert_eq!(counts.get("c"), None);
+    }
+
+    #[test]
+    fn test_excluded_members_are_skipped() {
+        let balancer = ChatProviderGroupBalancer::new();
+        let group = group(ChatProviderGroupStrategy::RoundRobin, &[]);
+        let excluded = vec!["a".to_string(), "b".to_string()];
+
+        for _ in 0..3 {
+            assert_eq!(
+                balancer
+                    .select_member("group", &group, &excluded)
+                    .as_deref(),
+                Some("c")
+            );
+        }
+        let all = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        assert_eq!(balancer.select_member("group", &group, &all), None);
+    }
+
+    #[test]
+    fn test_least_errors_avoids_failing_members() {
+        let balancer = ChatProviderGroupBalancer::new();
+        let group = group(ChatProviderGroupStrategy::LeastErrors, &[]);
+        balancer.record_error("a");
+        balancer.record_success("b", Duration::from_millis(100));
+        balancer.record_success("c", Duration::from_millis(100));
+
+        let counts = selections(&balancer, &group, 10);
+
+        assert_eq!(counts.get("a"), None);
+        assert_eq!(counts.get("b"), Some(&5));
+        assert_eq!(counts.get("c"), Some(&5));
+        assert_eq!(balancer.error_rate("a"), 1.0);
+    }
+
+    #[test]
+    fn test_least_errors_avoids_slow_members() {
+        let balancer = ChatProviderGroupBalancer::new();
+        let group = group(ChatProviderGroupStrategy::LeastErrors, &[]);
+        balancer.record_success("a", Duration::from_millis(100));
+        balancer.record_success("b", Duration::from_millis(150));
+        balancer.record_success("c", Duration::from_millis(500));
+
+        let counts = selections(&balancer, &group, 10);
+
+        assert_eq!(counts.get("a"), Some(&5));
+        assert_eq!(counts.get("b"), Some(&5));
+        assert_eq!(counts.get("c"), None);
+    }
+}
@@ -0,0 +1,383 @@
+//! Per-user quotas of chat providers.
+//!
+//! A chat provider can limit the number of generations and tokens of each user per day and per
+//! week (`chat_providers.providers.<provider-id>.user_quota`). Usage is counted per user, chat
+//! provider and day (UTC) in `provider_usage_counters` when a generation completes, and the usage
+//! of a week is the sum of its days. The windows are derived from the current time when the usage
+//! is read, so they reset without any scheduled cleanup.
+//!
+//! Usage is only counted while a chat provider has a quota. As the usage of a generation is only
+//! known once it completes, concurrent generations may exceed a limit slightly.
+
+use crate::config::{AppConfig, ChatProviderUserQuotaConfig};
+use crate::db::entity::prelude::*;
+use crate::db::entity::provider_usage_counters;
+use crate::metrics_constants::POSTGRES_QUERY_RECORD_PROVIDER_USAGE;
+use crate::query_metrics::named_statement_from_sql_and_values;
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, Utc};
+use eyre::Report;
+use sea_orm::prelude::Uuid;
+use sea_orm::{ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Window a quota limit applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaWindow {
+    /// A day, starting at midnight UTC
+    Day,
+    /// A week, starting on Monday at midnight UTC
+    Week,
+}
+
+impl QuotaWindow {
+    /// First day (inclusive) and last day (exclusive) of the window that contains `now`.
+    pub fn bounds(self, now: DateTime<Utc>) -> (NaiveDate, NaiveDate) {
+        let today = now.date_naive();
+        match self {
+            QuotaWindow::Day => (today, today + Days::new(1)),
+            QuotaWindow::Week => {
+                let start = today - Days::new(today.weekday().num_days_from_monday() as u64);
+                (start, start + Days::new(7))
+            }
+        }
+    }
+
+    /// When the window that contains `now` ends.
+    pub fn resets_at(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        self.bounds(now).1.and_time(NaiveTime::MIN).and_utc()
+    }
+}
+
+/// What a quota limit counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaMetric {
+    /// Completed generations
+    Messages,
+    /// Tokens used by completed generations
+    Tokens,
+}
+
+/// Usage of the user against one limit of a quota
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct QuotaLimitStatus {
+    pub window: QuotaWindow,
+    pub metric: QuotaMetric,
+    /// Usage in the current window
+    pub used: u64,
+    /// The configured limit for the window
+    pub limit: u64,
+    /// When the current window ends and the usage is reset
+    pub resets_at: DateTime<Utc>,
+}
+
+impl QuotaLimitStatus {
+    pub fn is_exceeded(&self) -> bool {
+        self.used >= self.limit
+    }
+}
+
+/// Usage of the user against the quota of a chat provider
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ChatProviderQuotaStatus {
+    /// The chat provider the quota belongs to
+    pub chat_provider_id: String,
+    /// The configured limits, with the usage in their current window
+    pub limits: Vec<QuotaLimitStatus>,
+    /// Whether a limit is reached, so no messages can be sent to the chat provider until
+    /// `resets_at`
+    pub exceeded: bool,
+    /// When all reached limits are reset. Only present if a limit is reached.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub resets_at: Option<DateTime<Utc>>,
+}
+
+/// Error of a generation that was rejected because the user reached the quota of the chat
+/// provider
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuotaExceededError {
+    /// Description of the error
+    pub error: String,
+    #[serde(flatten)]
+    pub quota: ChatProviderQuotaStatus,
+}
+
+/// Usage of a chat provider by a user within a window
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct WindowUsage {
+    messages: u64,
+    tokens: u64,
+}
+
+fn usage_since(counters: &[provider_usage_counters::Model], start: NaiveDate) -> WindowUsage {
+    counters
+        .iter()
+        .filter(|counter| counter.window_start >= start)
+        .fold(WindowUsage::default(), |usage, counter| WindowUsage {
+            messages: usage.messages + counter.messages.max(0) as u64,
+            tokens: usage.tokens + counter.tokens.max(0) as u64,
+        })
+}
+
+/// The quota of a chat provider, if it has any limits.
+fn configured_quota<'a>(
+    config: &'a AppConfig,
+    chat_provider_id: &str,
+) -> Option<&'a ChatProviderUserQuotaConfig> {
+    config
+        .get_chat_provider(chat_provider_id)
+        .user_quota
+        .as_ref()
+        .filter(|quota| quota.has_limits())
+}
+
+/// Compare the usage of the counters with the limits of a quota.
+fn quota_status_from_counters(
+    chat_provider_id: &str,
+    quota: &ChatProviderUserQuotaConfig,
+    counters: &[provider_usage_counters::Model],
+    now: DateTime<Utc>,
+) -> ChatProviderQuotaStatus {
+    let mut limits = Vec::new();
+    for (window, messages_limit, tokens_limit) in [
+        (
+            QuotaWindow::Day,
+            quota.messages_per_day,
+            quota.tokens_per_day,
+        ),
+        (
+            QuotaWindow::Week,
+            quota.messages_per_week,
+            quota.tokens_per_week,
+        ),
+    ] {
+        let usage = usage_since(counters, window.bounds(now).0);
+        let resets_at = window.resets_at(now);
+        for (metric, limit, used) in [
+            (QuotaMetric::Messages, messages_limit, usage.messages),
+            (QuotaMetric::Tokens, tokens_limit, usage.tokens),
+        ] {
+            if let Some(limit) = limit {
+                limits.push(QuotaLimitStatus {
+                    window,
+                    metric,
+                    used,
+                    limit,
+                    resets_at,
+                });
+            }
+        }
+    }
+
+    let resets_at = limits
+        .iter()
+        .filter(|limit| limit.is_exceeded())
+        .map(|limit| limit.resets_at)
+        .max();
+    ChatProviderQuotaStatus {
+        chat_provider_id: chat_provider_id.to_string(),
+        limits,
+        exceeded: resets_at.is_some(),
+        resets_at,
+    }
+}
+
+/// The usage of a user against the quota of a chat provider.
+///
+/// Returns `None` if the chat provider has no quota, or the user is exempt from it.
+pub async fn user_quota_status(
+    db: &DatabaseConnection,
+    config: &AppConfig,
+    user_id: &Uuid,
+    user_groups: &[String],
+    chat_provider_id: &str,
+    now: DateTime<Utc>,
+) -> Result<Option<ChatProviderQuotaStatus>, Report> {
+    let Some(quota) = configured_quota(config, chat_provider_id) else {
+        return Ok(None);
+    };
+    if quota.is_exempt(user_groups) {
+        return Ok(None);
+    }
+
+    // The current week always includes the current day
+    let (week_start, _) = QuotaWindow::Week.bounds(now);
+    let counters = ProviderUsageCounters::find()
+        .filter(provider_usage_counters::Column::UserId.eq(*user_id))
+        .filter(provider_usage_counters::Column::ChatProviderId.eq(chat_provider_id))
+        .filter(provider_usage_counters::Column::WindowStart.gte(week_start))
+        .all(db)
+        .await?;
+
+    Ok(Some(quota_status_from_counters(
+        chat_provider_id,
+        quota,
+        &counters,
+        now,
+    )))
+}
+
+/// Count a completed generation against the quota of the chat provider.
+///
+/// Does nothing if the chat provider has no quota. Generations of exempt users are counted as
+/// well, so their usage is known if the exemption is removed.
+pub async fn record_usage(
+    db: &DatabaseConnection,
+    config: &AppConfig,
+    user_id: &Uuid,
+    chat_provider_id: &str,
+    tokens: u64,
+    now: DateTime<Utc>,
+) -> Result<(), Report> {
+    if configured_quota(config, chat_provider_id).is_none() {
+        return Ok(());
+    }
+
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_RECORD_PROVIDER_USAGE,
+        r#"
+        INSERT INTO provider_usage_counters (user_id, chat_provider_id, window_start, messages, tokens)
+        VALUES ($1, $2, $3, 1, $4)
+        ON CONFLICT (user_id, chat_provider_id, window_start) DO UPDATE
+        SET messages = provider_usage_counters.messages + 1,
+            tokens = provider_usage_counters.tokens + EXCLUDED.tokens
+        "#,
+        [
+            (*user_id).into(),
+            chat_provider_id.into(),
+            now.date_naive().into(),
+            (tokens.min(i64::MAX as u64) as i64).into(),
+        ],
+    );
+    db.execute_raw(statement).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn counter(
+        window_start: NaiveDate,
+        messages: i64,
+        tokens: i64,
+    ) -> provider_usage_counters::Model {
+        provider_usage_counters::Model {
+            user_id: Uuid::nil(),
+            chat_provider_id: "main".to_string(),
+            window_start,
+            messages,
+            tokens,
+            created_at: Utc::now().into(),
+            updated_at: Utc::now().into(),
+        }
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_windows_reset_at_day_and_week_boundaries() {
+        // Sunday, the last day of the week
+        let sunday = Utc.with_ymd_and_hms(2026, 10, 18, 23, 59, 59).unwrap();
+        let monday = Utc.with_ymd_and_hms(2026, 10, 19, 0, 0, 0).unwrap();
+        let next_monday = Utc.with_ymd_and_hms(2026, 10, 26, 0, 0, 0).unwrap();
+
+        assert_eq!(
+            QuotaWindow::Day.bounds(sunday),
+            (date(2026, 10, 18), date(2026, 10, 19))
+        );
+        assert_eq!(
+            QuotaWindow::Week.bounds(sunday),
+            (date(2026, 10, 12), date(2026, 10, 19))
+        );
+        assert_eq!(QuotaWindow::Day.resets_at(sunday), monday);
+        assert_eq!(QuotaWindow::Week.resets_at(sunday), monday);
+
+        assert_eq!(
+            QuotaWindow::Week.bounds(monday),
+            (date(2026, 10, 19), date(2026, 10, 26))
+        );
+        assert_eq!(
+            QuotaWindow::Day.resets_at(monday),
+            Utc.with_ymd_and_hms(2026, 10, 20, 0, 0, 0).unwrap()
+        );
+        assert_eq!(QuotaWindow::Week.resets_at(monday), next_monday);
+    }
+
+    #[test]
+    fn test_quota_status_sums_days_of_the_week() {
+        let quota = ChatProviderUserQuotaConfig {
+            messages_per_day: Some(2),
+            tokens_per_week: Some(1000),
+            ..Default::default()
+        };
+        let counters = vec![
+            counter(date(2026, 10, 12), 5, 600),
+            counter(date(2026, 10, 15), 1, 300),
+        ];
+        let thursday = Utc.with_ymd_and_hms(2026, 10, 15, 12, 0, 0).unwrap();
+
+        let status = quota_status_from_counters("main", &quota, &counters, thursday);
+        assert_eq!(
+            status.limits,
+            vec![
+                QuotaLimitStatus {
+                    window: QuotaWindow::Day,
+                    metric: QuotaMetric::Messages,
+                    used: 1,
+                    limit: 2,
+                    resets_at: Utc.with_ymd_and_hms(2026, 10, 16, 0, 0, 0).unwrap(),
+                },
+                QuotaLimitStatus {
+                    window: QuotaWindow::Week,
+                    metric: QuotaMetric::Tokens,
+                    used: 900,
+                    limit: 1000,
+                    resets_at: Utc.with_ymd_and_hms(2026, 10, 19, 0, 0, 0).unwrap(),
+                },
+            ]
+        );
+        assert!(!status.exceeded);
+        assert_eq!(status.resets_at, None);
+
+        // The previous week doesn't count anymore
+        let next_monday = Utc.with_ymd_and_hms(2026, 10, 19, 8, 0, 0).unwrap();
+        let status = quota_status_from_counters("main", &quota, &counters, next_monday);
+        assert!(status.limits.iter().all(|limit| limit.used == 0));
+    }
+
+    #[test]
+    fn test_quota_status_resets_when_all_exceeded_limits_reset() {
+        let quota = ChatProviderUserQuotaConfig {
+            messages_per_day: Some(1),
+            messages_per_week: Some(3),
+            ..Default::default()
+        };
+        let wednesday = Utc.with_ymd_and_hms(2026, 10, 14, 9, 0, 0).unwrap();
+
+        let counters = vec![counter(date(2026, 10, 14), 1, 10)];
+        let status = quota_status_from_counters("main", &quota, &counters, wednesday);
+        assert!(status.exceeded);
+        assert_eq!(
+            status.resets_at,
+            Some(Utc.with_ymd_and_hms(2026, 10, 15, 0, 0, 0).unwrap())
+        );
+
+        let counters = vec![
+            counter(date(2026, 10, 12), 2, 10),
+            counter(date(2026, 10, 14), 1, 10),
+        ];
+        let status = quota_status_from_counters("main", &quota, &counters, wednesday);
+        assert_eq!(
+            status.resets_at,
+            Some(Utc.with_ymd_and_hms(2026, 10, 19, 0, 0, 0).unwrap())
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Cooldowns of chat providers that rejected generations because of a rate limit.
+//!
+//! When a provider responds with a rate limit, it is put into a cooldown until the time it asked
+//! clients to retry at. Provider selection prefers providers that are not cooling down, so that
+//! the following generations don't run into the same limit. Cooldowns are only kept in memory
+//! of the backend instance that received the rate limit response.
+
+use crate::models::message::RateLimitScope;
+use chrono::{DateTime, TimeDelta, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Cooldown for rate limit responses that don't say when to retry.
+const DEFAULT_COOLDOWN_SECONDS: u64 = 30;
+/// Upper bound of a cooldown, so that a bogus retry hint can't take a provider out of rotation
+/// for long.
+const MAX_COOLDOWN_SECONDS: u64 = 600;
+
+/// A chat provider that is cooling down after a rate limit response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChatProviderCooldown {
+    /// When the provider accepts requests again
+    pub until: DateTime<Utc>,
+    /// Which limit of the provider was exceeded, if it reported it
+    pub scope: Option<RateLimitScope>,
+}
+
+/// In-memory cooldowns of chat providers, shared across all requests.
+#[derive(Debug, Clone, Default)]
+pub struct ChatProviderRateLimits {
+    cooldowns: Arc<RwLock<HashMap<String, ChatProviderCooldown>>>,
+}
+
+impl ChatProviderRateLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Puts a chat provider into a cooldown after it responded with a rate limit.
+    ///
+    /// A running cooldown is only ever extended. Returns the cooldown of the provider.
+    pub fn record_rate_limit(
+        &self,
+        chat_provider_id: &str,
+        retry_after_seconds: Option<u64>,
+        scope: Option<RateLimitScope>,
+    ) -> ChatProviderCooldown {
+        self.record_rate_limit_at(chat_provider_id, retry_after_seconds, scope, Utc::now())
+    }
+
+    fn record_rate_limit_at(
+        &self,
+        chat_provider_id: &str,
+        retry_after_seconds: Option<u64>,
+        scope: Option<RateLimitScope>,
+        now: DateTime<Utc>,
+    ) -> ChatProviderCooldown {
+        let seconds = retry_after_seconds
+            .unwrap_or(DEFAULT_COOLDOWN_SECONDS)
+            .min(MAX_COOLDOWN_SECONDS);
+        let cooldown = ChatProviderCooldown {
+            until: now + TimeDelta::seconds(seconds as i64),
+            scope,
+        };
+
+        let mut cooldowns = self
+            .cooldowns
+            .write()
+            .expect("chat provider rate limits poisoned");
+        let cooldown = match cooldowns.get(chat_provider_id) {
+            Some(existing) if existing.until > cooldown.until => *existing,
+            _ => cooldown,
+        };
+        cooldowns.insert(chat_provider_id.to_string(), cooldown);
+        cooldown
+    }
+
+    /// The running cooldown of a chat provider, if any.
+    pub fn cooldown(&self, chat_provider_id: &str) -> Option<ChatProviderCooldown> {
+        self.cooldown_at(chat_provider_id, Utc::now())
+    }
+
+    fn cooldown_at(
+        &self,
+        chat_provider_id: &str,
+        now: DateTime<Utc>,
+    ) -> Option<ChatProviderCooldown> {
+        self.cooldowns
+            .read()
+            .expect("chat provider rate limits poisoned")
+            .get(chat_provider_id)
+            .filter(|cooldown| cooldown.until > now)
+            .copied()
+    }
+
+    /// Whether a chat provider is cooling down after a rate limit response.
+    pub fn is_cooling_down(&self, chat_provider_id: &str) -> bool {
+        self.cooldown(chat_provider_id).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_follows_retry_hint() {
+        let rate_limits = ChatProviderRateLimits::new();
+        let now = Utc::now();
+
+        let cooldown =
+            rate_limits.record_rate_limit_at("a", Some(20), Some(RateLimitScope::Tokens), now);
+        rate_limits.record_rate_limit_at("b", None, None, now);
+        rate_limits.record_rate_limit_at("c", Some(86_400), None, now);
+
+        assert_eq!(cooldown.until, now + TimeDelta::seconds(20));
+        assert_eq!(
+            rate_limits.cooldown_at("a", now + TimeDelta::seconds(19)),
+            Some(cooldown)
+        );
+        assert_eq!(
+            rate_limits.cooldown_at("a", now + TimeDelta::seconds(20)),
+            None
+        );
+        assert_eq!(
+            rate_limits
+                .cooldown_at("b", now)
+                .map(|cooldown| cooldown.until),
+            Some(now + TimeDelta::seconds(30))
+        );
+        assert_eq!(
+            rate_limits
+                .cooldown_at("c", now)
+                .map(|cooldown| cooldown.until),
+            Some(now + TimeDelta::seconds(600))
+        );
+        assert_eq!(rate_limits.cooldown_at("d", now), None);
+    }
+
+    #[test]
+    fn test_cooldown_is_only_extended() {
+        let rate_limits = ChatProviderRateLimits::new();
+        let now = Utc::now();
+
+        rate_limits.record_rate_limit_at("a", Some(60), Some(RateLimitScope::Requests), now);
+        let cooldown = rate_limits.record_rate_limit_at("a", Some(5), None, now);
+
+        assert_eq!(cooldown.until, now + TimeDelta::seconds(60));
+        assert_eq!(cooldown.scope, Some(RateLimitScope::Requests));
+    }
+}
@@ -0,0 +1,400 @@
+//! Spillover of the content of oversized messages to the file storage.
+//!
+//! Tool outputs and pasted logs can make a single message several megabytes large, which bloats
+//! the messages table and its backups and slows down loading the messages of a chat. When a
+//! message whose serialized content exceeds `chat.content_spillover.threshold_bytes` is stored,
+//! its largest text and tool use parts are moved to the default file storage until it fits, and
+//! replaced with `BlobPointer` parts that keep a preview of their content.
+//!
+//! A blob holds the serialized content part and is addressed by its hash, so storing the same
+//! content twice writes the same blob. Blob pointers are resolved to the original parts when
+//! generating, like file pointers, and the full part is served by
+//! `GET /messages/{message_id}/content/{index}`.
+//!
+//! Messages stored before are moved by a resumable maintenance task, see
+//! [`crate::services::maintenance_tasks`].
+
+use crate::config::{ContentSpilloverConfig, ContentSpilloverMigrationConfig};
+use crate::db::entity::messages;
+use crate::metrics::report_message_content_spill;
+use crate::metrics_constants::POSTGRES_QUERY_CONTENT_SPILLOVER_MIGRATION_BATCH;
+use crate::models::message::{ContentPart, ContentPartBlobPointer, ContentPartText, MessageSchema};
+use crate::query_metrics::named_statement_from_sql_and_values;
+use crate::services::background_tasks::BackgroundTaskManager;
+use crate::services::file_storage::FileStorage;
+use crate::services::maintenance_tasks::{
+    MaintenanceTaskBatch, MaintenanceTaskProgress, lock_maintenance_task_progress,
+    run_maintenance_task, start_maintenance_task, update_maintenance_task_progress,
+};
+use eyre::{Report, WrapErr};
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, DatabaseConnection, FromQueryResult, TransactionTrait};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+
+/// Name of the migration in the maintenance task progress and the background task manager.
+pub const CONTENT_SPILLOVER_MIGRATION_TASK: &str = "content_spillover_migration";
+
+/// Prefix of the paths of the blobs in the file storage.
+pub const CONTENT_BLOB_PREFIX: &str = "message-content/";
+
+/// Origin of a spill that is reported in the metrics, for messages that are being stored.
+pub const SPILL_ORIGIN_WRITE: &str = "write";
+
+/// Origin of a spill that is reported in the metrics, for messages moved by the migration.
+const SPILL_ORIGIN_MIGRATION: &str = "migration";
+
+#[derive(Debug, FromQueryResult)]
+struct MessageRawMessage {
+    id: Uuid,
+    created_at: DateTimeWithTimeZone,
+    raw_message: Json,
+}
+
+fn serialized_size<T: Serialize + ?Sized>(value: &T) -> Result<u64, Report> {
+    Ok(serde_json::to_vec(value)?.len() as u64)
+}
+
+/// The indices of the parts to move to the file storage, largest first, so that the serialized
+/// content roughly fits into `threshold_bytes`.
+///
+/// Only text and tool use parts are moved. Returns no parts if the content already fits.
+fn parts_to_spill(content: &[ContentPart], threshold_bytes: u64) -> Result<Vec<usize>, Report> {
+    let mut remaining_size = serialized_size(content)?;
+    if remaining_size <= threshold_bytes {
+        return Ok(Vec::new());
+    }
+
+    let mut candidates = Vec::new();
+    for (index, part) in content.iter().enumerate() {
+        if matches!(part, ContentPart::Text(_) | ContentPart::ToolUse(_)) {
+            candidates.push((index, serialized_size(part)?));
+        }
+    }
+    candidates.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    let mut indices = Vec::new();
+    for (index, size) in candidates {
+        if remaining_size <= threshold_bytes {
+            break;
+        }
+        indices.push(index);
+        remaining_size = remaining_size.saturating_sub(size);
+    }
+    Ok(indices)
+}
+
+/// The beginning of the text of a part, which is kept inline when the part is moved.
+///
+/// For tool uses, this is the output of the tool, or its input if it has no output yet.
+fn preview_text(part: &ContentPart, preview_chars: usize) -> String {
+    let text = match part {
+        ContentPart::Text(text) => text.text.clone(),
+        ContentPart::ToolUse(tool_use) => tool_use
+            .output
+            .as_ref()
+            .or(tool_use.input.as_ref())
+            .map(JsonValue::to_string)
+            .unwrap_or_default(),
+        _ => String::new(),
+    };
+    text.chars().take(preview_chars).collect()
+}
+
+fn blob_path(blob: &[u8]) -> String {
+    let hash: String = Sha256::digest(blob)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("{CONTENT_BLOB_PREFIX}{hash}.json")
+}
+
+/// Move the largest parts of the content of a message to the file storage if it exceeds the
+/// configured threshold.
+///
+/// Returns the content unchanged if spillover is disabled or the content fits.
+pub async fn spill_oversized_content(
+    storage: &FileStorage,
+    config: &ContentSpilloverConfig,
+    origin: &'static str,
+    mut content: Vec<ContentPart>,
+) -> Result<Vec<ContentPart>, Report> {
+    if !config.enabled {
+        return Ok(content);
+    }
+    let indices = parts_to_spill(&content, config.threshold_bytes)?;
+    if indices.is_empty() {
+        return Ok(content);
+    }
+
+    let mut spilled_bytes = 0;
+    for &index in &indices {
+        let blob = serde_json::to_vec(&content[index])?;
+        let size = blob.len() as u64;
+        let blob_path = blob_path(&blob);
+        let mut writer = storage
+            .upload_file_writer(&blob_path, Some("application/json"))
+            .await?;
+        writer
+            .write(blob)
+            .await
+            .wrap_err("Failed to write spilled message content")?;
+        writer.close().await?;
+
+        spilled_bytes += size;
+        content[index] = ContentPart::BlobPointer(ContentPartBlobPointer {
+            blob_path,
+            size,
+            preview: preview_text(&content[index], config.preview_chars),
+        });
+    }
+    report_message_content_spill(origin, indices.len(), spilled_bytes);
+
+    Ok(content)
+}
+
+/// Like [`spill_oversized_content`], for the raw JSON of a message that is about to be stored.
+pub async fn spill_oversized_raw_message(
+    storage: &FileStorage,
+    config: &ContentSpilloverConfig,
+    raw_message: JsonValue,
+) -> Result<JsonValue, Report> {
+    if !config.enabled {
+        return Ok(raw_message);
+    }
+    let mut message = MessageSchema::validate(&raw_message)?;
+    message.content =
+        spill_oversized_content(storage, config, SPILL_ORIGIN_WRITE, message.content).await?;
+    message.to_json()
+}
+
+/// Load the content part that was moved to the file storage from it.
+pub async fn load_spilled_content_part(
+    storage: &FileStorage,
+    pointer: &ContentPartBlobPointer,
+) -> Result<ContentPart, Report> {
+    let blob = storage
+        .read_file_to_bytes(&pointer.blob_path)
+        .await
+        .wrap_err_with(|| {
+            format!(
+                "Failed to read spilled message content {}",
+                pointer.blob_path
+            )
+        })?;
+    serde_json::from_slice(&blob).wrap_err("Failed to parse spilled message content")
+}
+
+/// Resolve a blob pointer to the content part it was created for.
+///
+/// Falls back to a text part with the preview if the blob can't be loaded, so that a missing
+/// blob doesn't fail the generation.
+pub async fn resolve_blob_pointer(
+    storage: &FileStorage,
+    pointer: ContentPartBlobPointer,
+) -> ContentPart {
+    match load_spilled_content_part(storage, &pointer).await {
+        Ok(content) => content,
+        Err(err) => {
+            tracing::warn!(
+                error = %err,
+                blob_path = %pointer.blob_path,
+                "Falling back to the preview of spilled message content"
+            );
+            ContentPart::Text(ContentPartText {
+                text: pointer.preview,
+            })
+        }
+    }
+}
+
+/// Resolve all blob pointers of the content of a message.
+pub async fn resolve_blob_pointers(
+    storage: &FileStorage,
+    content: Vec<ContentPart>,
+) -> Vec<ContentPart> {
+    let mut resolved = Vec::with_capacity(content.len());
+    for part in content {
+        resolved.push(match part {
+            ContentPart::BlobPointer(pointer) => resolve_blob_pointer(storage, pointer).await,
+            other => other,
+        });
+    }
+    resolved
+}
+
+/// Start the migration of oversized messages in the background.
+///
+/// Returns `false` if the migration is already running.
+pub fn start_content_spillover_migration(
+    background_tasks: &BackgroundTaskManager,
+    db: DatabaseConnection,
+    storage: FileStorage,
+    spillover: ContentSpilloverConfig,
+    config: ContentSpilloverMigrationConfig,
+) -> bool {
+    start_maintenance_task(background_tasks, CONTENT_SPILLOVER_MIGRATION_TASK, async move {
+        run_content_spillover_migration(&db, &storage, &spillover, &config).await
+    })
+}
+
+/// Migrate all remaining oversized messages, waiting `batch_interval_ms` between batches, see
+/// [`run_maintenance_task`].
+pub async fn run_content_spillover_migration(
+    db: &DatabaseConnection,
+    storage: &FileStorage,
+    spillover: &ContentSpilloverConfig,
+    config: &ContentSpilloverMigrationConfig,
+) -> Result<MaintenanceTaskProgress, Report> {
+    run_maintenance_task(db, CONTENT_SPILLOVER_MIGRATION_TASK, config.batch_interval_ms, || {
+        migrate_content_spillover_batch(db, storage, spillover, config.batch_size.max(1))
+    })
+    .await
+}
+
+/// Migrate the next batch of up to `batch_size` oversized messages after the persisted position.
+///
+/// Only messages whose stored JSON exceeds the threshold are processed. The progress row is
+/// locked for the duration of the batch, see [`lock_maintenance_task_progress`].
+pub async fn migrate_content_spillover_batch(
+    db: &DatabaseConnection,
+    storage: &FileStorage,
+    spillover: &ContentSpilloverConfig,
+    batch_size: u64,
+) -> Result<MaintenanceTaskBatch, Report> {
+    let txn = db.begin().await?;
+    let progress = lock_maintenance_task_progress(&txn, CONTENT_SPILLOVER_MIGRATION_TASK).await?;
+
+    let rows = MessageRawMessage::find_by_statement(named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_CONTENT_SPILLOVER_MIGRATION_BATCH,
+        r#"
+        SELECT id, created_at, raw_message
+        FROM messages
+        WHERE octet_length(raw_message::text) > $4
+          AND ($1::timestamptz IS NULL OR (created_at, id) > ($1::timestamptz, $2::uuid))
+        ORDER BY created_at, id
+        LIMIT $3
+        "#,
+        [
+            progress.cursor_created_at.into(),
+            progress.cursor_id.into(),
+            (batch_size.min(i64::MAX as u64) as i64).into(),
+            (spillover.threshold_bytes.min(i64::MAX as u64) as i64).into(),
+        ],
+    ))
+    .all(&txn)
+    .await?;
+
+    let mut batch = MaintenanceTaskBatch {
+        processed_messages: rows.len() as u64,
+        ..Default::default()
+    };
+    let cursor = rows.last().map(|row| (row.created_at, row.id));
+    for row in rows {
+        // Rows that can't be parsed are skipped, they can't be loaded either
+        let Ok(mut message) = MessageSchema::validate(&row.raw_message) else {
+            continue;
+        };
+        let content = spill_oversized_content(
+            storage,
+            spillover,
+            SPILL_ORIGIN_MIGRATION,
+            message.content.clone(),
+        )
+        .await?;
+        if content == message.content {
+            continue;
+        }
+        message.content = content;
+
+        let migrated = message.to_json()?;
+        let bytes_before = serialized_size(&row.raw_message)? as i64;
+        let bytes_after = serialized_size(&migrated)? as i64;
+        messages::ActiveModel {
+            id: ActiveValue::Unchanged(row.id),
+            raw_message: ActiveValue::Set(migrated),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .wrap_err("Failed to update the content of a message")?;
+
+        batch.migrated_messages += 1;
+        batch.bytes_saved += bytes_before - bytes_after;
+    }
+
+    update_maintenance_task_progress(&txn, CONTENT_SPILLOVER_MIGRATION_TASK, cursor, &batch)
+        .await?;
+    txn.commit().await?;
+
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::{ToolCallStatus, ToolUse};
+    use serde_json::json;
+
+    fn text(text: &str) -> ContentPart {
+        ContentPart::Text(ContentPartText {
+            text: text.to_string(),
+        })
+    }
+
+    fn tool_use(output: JsonValue) -> ContentPart {
+        ContentPart::ToolUse(ToolUse {
+            tool_call_id: "call_1".to_string(),
+            status: ToolCallStatus::Success,
+            tool_name: "read_logs".to_string(),
+            progress_message: None,
+            input: Some(json!({ "service": "backend" })),
+            output: Some(output),
+            started_at: None,
+            ended_at: None,
+        })
+    }
+
+    #[test]
+    fn test_spills_largest_parts_until_content_fits() {
+        let content = vec![
+            text(&"a".repeat(300)),
+            tool_use(json!("b".repeat(2000))),
+            text(&"c".repeat(1000)),
+            text("short"),
+        ];
+
+        assert_eq!(parts_to_spill(&content, 1500).unwrap(), vec![1]);
+        assert_eq!(parts_to_spill(&content, 500).unwrap(), vec![1, 2]);
+        assert!(parts_to_spill(&content, 10_000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_only_spills_text_and_tool_use_parts() {
+        let content = vec![
+            ContentPart::Reasoning(Default::default()),
+            ContentPart::BlobPointer(ContentPartBlobPointer {
+                blob_path: format!("{CONTENT_BLOB_PREFIX}abc.json"),
+                size: 10_000,
+                preview: "x".repeat(2000),
+            }),
+        ];
+
+        assert!(parts_to_spill(&content, 100).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_preview_and_blob_path() {
+        let part = tool_use(json!({ "lines": ["error: disk full"] }));
+
+        assert_eq!(preview_text(&part, 10), "{\"lines\":[");
+        assert_eq!(preview_text(&text("héllo"), 2), "hé");
+
+        let path = blob_path(b"content");
+        assert!(path.starts_with(CONTENT_BLOB_PREFIX) && path.ends_with(".json"));
+        assert_eq!(path, blob_path(b"content"));
+        assert_ne!(path, blob_path(b"other content"));
+    }
+}
@@ -0,0 +1,278 @@
+//! Ephemeral ("incognito") chats, whose messages are never written to the database.
+//!
+//! With `chat.ephemeral_chats.enabled`, a chat can be created as ephemeral. The chat and its
+//! messages only live in the memory of the instance that created it, for at most `ttl_secs`, and
+//! a user may hold at most `max_chats_per_user` of them at the same time. Files attached to an
+//! ephemeral chat are regular file uploads, which are deleted by a maintenance task together with
+//! the chat once it expired. As nothing is shared between instances, an ephemeral chat can only
+//! be used (and its generations resumed) on the instance that holds it.
+
+use crate::db::entity::prelude::*;
+use crate::db::entity::{chats, messages};
+use crate::services::file_processing_cached::purge_file_cached;
+use crate::state::AppState;
+use eyre::{Report, eyre};
+use sea_orm::EntityTrait;
+use sea_orm::prelude::Uuid;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Name of the purge in the background task manager.
+pub const EPHEMERAL_CHAT_PURGE_TASK: &str = "ephemeral_chat_purge";
+
+/// Upper bound of the interval in which expired chats are purged.
+const MAX_PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// An ephemeral chat, with all of its messages.
+#[derive(Debug, Clone)]
+pub struct EphemeralChat {
+    pub chat: chats::Model,
+    /// Messages of the chat, in the order they were added.
+    pub messages: Vec<messages::Model>,
+    /// File uploads attached to the chat, which are deleted once it expired.
+    pub file_upload_ids: Vec<Uuid>,
+    pub expires_at: Instant,
+}
+
+/// In-memory store of the ephemeral chats of this instance.
+#[derive(Debug, Clone, Default)]
+pub struct EphemeralChatStore {
+    chats: Arc<Mutex<HashMap<Uuid, EphemeralChat>>>,
+}
+
+impl EphemeralChatStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a chat that expires after `ttl`.
+    ///
+    /// Returns false, without adding the chat, if its owner already holds `max_chats_per_user`
+    /// unexpired ephemeral chats.
+    pub fn insert(&self, chat: chats::Model, ttl: Duration, max_chats_per_user: usize) -> bool {
+        let now = Instant::now();
+        let mut chats = self.chats.lock().unwrap();
+        let held = chats
+            .values()
+            .filter(|held| held.chat.owner_user_id == chat.owner_user_id && held.expires_at > now)
+            .count();
+        if held >= max_chats_per_user {
+            return false;
+        }
+        chats.insert(
+            chat.id,
+            EphemeralChat {
+                chat,
+                messages: Vec::new(),
+                file_upload_ids: Vec::new(),
+                expires_at: now + ttl,
+            },
+        );
+        true
+    }
+
+    /// The unexpired ephemeral chat with the given ID, if it is owned by the user.
+    pub fn get(&self, chat_id: &Uuid, owner_user_id: &str) -> Option<EphemeralChat> {
+        self.chats
+            .lock()
+            .unwrap()
+            .get(chat_id)
+            .filter(|held| held.chat.owner_user_id == owner_user_id)
+            .filter(|held| held.expires_at > Instant::now())
+            .cloned()
+    }
+
+    /// Whether the user owns an unexpired ephemeral chat with the given ID.
+    pub fn contains(&self, chat_id: &Uuid, owner_user_id: &str) -> bool {
+        self.get(chat_id, owner_user_id).is_some()
+    }
+
+    /// Add a message to an ephemeral chat, or replace the message with the same ID.
+    ///
+    /// The thread ending in the message becomes the active thread of the chat.
+    pub fn add_message(&self, message: messages::Model) -> Result<(), Report> {
+        let mut chats = self.chats.lock().unwrap();
+        let held = chats
+            .get_mut(&message.chat_id)
+            .ok_or_else(|| eyre!("Ephemeral chat {} has expired", message.chat_id))?;
+
+        let mut active_ids = vec![message.id];
+        let mut previous_id = message.previous_message_id;
+        while let Some(id) = previous_id {
+            active_ids.push(id);
+            previous_id = held
+                .messages
+                .iter()
+                .find(|held_message| held_message.id == id)
+                .and_then(|held_message| held_message.previous_message_id);
+        }
+        match held
+            .messages
+            .iter_mut()
+            .find(|held_message| held_message.id == message.id)
+        {
+            Some(held_message) => *held_message = message,
+            None => held.messages.push(message),
+        }
+        for held_message in &mut held.messages {
+            held_message.is_message_in_active_thread = active_ids.contains(&held_message.id);
+        }
+        Ok(())
+    }
+
+    /// Attach a file upload to an ephemeral chat, so that it is deleted with the chat.
+    pub fn add_file_upload(&self, chat_id: &Uuid, file_upload_id: Uuid) -> Result<(), Report> {
+        self.chats
+            .lock()
+            .unwrap()
+            .get_mut(chat_id)
+            .ok_or_else(|| eyre!("Ephemeral chat {chat_id} has expired"))?
+            .file_upload_ids
+            .push(file_upload_id);
+        Ok(())
+    }
+
+    /// Remove all expired chats from the store, and return them.
+    pub fn take_expired(&self) -> Vec<EphemeralChat> {
+        let now = Instant::now();
+        let mut chats = self.chats.lock().unwrap();
+        let expired_ids: Vec<Uuid> = chats
+            .values()
+            .filter(|held| held.expires_at <= now)
+            .map(|held| held.chat.id)
+            .collect();
+        expired_ids
+            .iter()
+            .filter_map(|chat_id| chats.remove(chat_id))
+            .collect()
+    }
+}
+
+/// Delete a file upload of an expired ephemeral chat, from the storage and the database.
+async fn delete_ephemeral_file_upload(app_state: &AppState, file_id: Uuid) -> Result<(), Report> {
+    let Some(file_upload) = FileUploads::find_by_id(file_id).one(&app_state.db).await? else {
+        return Ok(());
+    };
+    let file_storage = app_state
+        .file_storage_providers
+        .get(&file_upload.file_storage_provider_id)
+        .ok_or_else(|| {
+            eyre!(
+                "File storage provider '{}' not found",
+                file_upload.file_storage_provider_id
+            )
+        })?;
+    // Sharepoint files are only linked, so their contents are left alone
+    if !file_storage.is_sharepoint() {
+        file_storage
+            .delete_file(&file_upload.file_storage_path)
+            .await?;
+    }
+    purge_file_cached(app_state, &file_id).await;
+    FileUploads::delete_by_id(file_id).exec(&app_state.db).await?;
+    Ok(())
+}
+
+/// Start the purge of expired ephemeral chats, unless it is already running.
+///
+/// The purge is started with the first ephemeral chat, as the feature may be enabled at runtime.
+pub fn start_ephemeral_chat_purge(app_state: &AppState) -> bool {
+    let config = app_state.config.chat.ephemeral_chats.clone();
+    let purge_app_state = app_state.clone();
+    app_state
+        .background_tasks
+        .start_maintenance_task(EPHEMERAL_CHAT_PURGE_TASK, async move {
+            let period = Duration::from_secs(config.ttl_secs.max(1)).min(MAX_PURGE_INTERVAL);
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                for expired in purge_app_state.ephemeral_chats.take_expired() {
+                    for file_id in expired.file_upload_ids {
+                        if let Err(err) =
+                            delete_ephemeral_file_upload(&purge_app_state, file_id).await
+                        {
+                            tracing::warn!(
+                                file_id = %file_id,
+                                error = %err,
+                                "Failed to delete a file of an expired ephemeral chat"
+                            );
+                        }
+                    }
+                    tracing::info!(
+                        chat_id = %expired.chat.id,
+                        user_id = %expired.chat.owner_user_id,
+                        "Ephemeral chat session expired"
+                    );
+                }
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn chat(owner_user_id: &str) -> chats::Model {
+        let now = Utc::now().fixed_offset();
+        chats::Model {
+            id: Uuid::new_v4(),
+            owner_user_id: owner_user_id.to_string(),
+            created_at: now,
+            updated_at: now,
+            title_by_summary: None,
+            archived_at: None,
+            assistant_configuration: None,
+            assistant_id: None,
+            title_by_user_provided: None,
+            active_generation_id: None,
+            generation_state: None,
+            generation_started_at: None,
+            generation_heartbeat_at: None,
+            generation_ended_at: None,
+            organization_id: None,
+            generation_replica_id: None,
+            generation_message_id: None,
+            assistant_snapshot: None,
+            provisional: false,
+            custom_instruction: None,
+            response_language: None,
+        }
+    }
+
+    #[test]
+    fn chats_are_capped_per_user() {
+        let store = EphemeralChatStore::new();
+        let ttl = Duration::from_secs(60);
+
+        assert!(store.insert(chat("user-a"), ttl, 2));
+        assert!(store.insert(chat("user-a"), ttl, 2));
+        assert!(!store.insert(chat("user-a"), ttl, 2));
+        assert!(store.insert(chat("user-b"), ttl, 2));
+    }
+
+    #[test]
+    fn expired_chats_are_hidden_and_taken() {
+        let store = EphemeralChatStore::new();
+        let expired = chat("user-a");
+        let expired_id = expired.id;
+        let live = chat("user-a");
+        let live_id = live.id;
+
+        assert!(store.insert(expired, Duration::ZERO, 2));
+        assert!(store.insert(live, Duration::from_secs(60), 2));
+
+        assert!(!store.contains(&expired_id, "user-a"));
+        assert!(store.contains(&live_id, "user-a"));
+        assert!(!store.contains(&live_id, "user-b"));
+
+        let taken: Vec<Uuid> = store
+            .take_expired()
+            .into_iter()
+            .map(|held| held.chat.id)
+            .collect();
+        assert_eq!(taken, vec![expired_id]);
+        assert!(store.take_expired().is_empty());
+    }
+}
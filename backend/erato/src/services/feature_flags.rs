@@ -0,0 +1,468 @@
+//! Feature flags, which let admins switch features on or off and roll them out to a subset of
+//! users without editing the config and restarting the server.
+//!
+//! Flags are stored in the `feature_flags` table. Every instance keeps them in memory, refreshes
+//! them from the database every [`REFRESH_INTERVAL`], and right away after an admin changed them
+//! through the instance. Flags named after a config toggle ([`FeatureFlag`]) use the value of the
+//! toggle while they have no row in the table. The frontend environment is rendered once at
+//! startup from the config, so the flags evaluated for the current user are part of
+//! `/me/profile` instead.
+
+use crate::config::AppConfig;
+use crate::db::entity::feature_flags;
+use crate::db::entity::prelude::*;
+use crate::state::AppState;
+use eyre::{Report, WrapErr};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveValue, DatabaseConnection, EntityTrait};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Name of the refresh in the background task manager.
+pub const FEATURE_FLAGS_REFRESH_TASK: &str = "feature_flags_refresh";
+
+/// Interval of the refreshes from the database, which bounds how long instances take to observe
+/// a change made through another instance.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A config toggle that can be overridden by a feature flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FeatureFlag {
+    EphemeralChats,
+    Facets,
+    MessageLanguageDetection,
+    PromptOptimizer,
+    StarterPrompts,
+}
+
+impl FeatureFlag {
+    pub const ALL: [FeatureFlag; 5] = [
+        FeatureFlag::EphemeralChats,
+        FeatureFlag::Facets,
+        FeatureFlag::MessageLanguageDetection,
+        FeatureFlag::PromptOptimizer,
+        FeatureFlag::StarterPrompts,
+    ];
+
+    /// Name of the flag, which is the config key of the toggle it overrides.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FeatureFlag::EphemeralChats => "chat.ephemeral_chats.enabled",
+            FeatureFlag::Facets => "experimental_facets",
+            FeatureFlag::MessageLanguageDetection => "i18n.message_language_detection.enabled",
+            FeatureFlag::PromptOptimizer => "prompt_optimizer.enabled",
+            FeatureFlag::StarterPrompts => "starter_prompts.enabled",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|flag| flag.name() == name)
+    }
+
+    /// Value of the toggle in the config, ignoring feature flags.
+    ///
+    /// Facets are shown as soon as any facet is configured.
+    pub fn config_value(&self, config: &AppConfig) -> bool {
+        match self {
+            FeatureFlag::EphemeralChats => config.chat.ephemeral_chats.enabled,
+            FeatureFlag::Facets => !config.experimental_facets.facets.is_empty(),
+            FeatureFlag::MessageLanguageDetection => config.i18n.message_language_detection.enabled,
+            FeatureFlag::PromptOptimizer => config.prompt_optimizer.enabled,
+            FeatureFlag::StarterPrompts => config.starter_prompts.enabled,
+        }
+    }
+}
+
+/// Where the effective value of a feature flag comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeatureFlagSource {
+    Config,
+    RuntimeOverride,
+}
+
+/// Why a feature flag has its value for a user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureDecisionReason {
+    /// The flag is not stored, so the value of the config toggle is used. Unknown flags are
+    /// disabled.
+    Config,
+    /// The user is a member of a group with an override
+    GroupOverride,
+    /// The user is in the percentage rollout of the flag
+    PercentageRollout,
+    /// The `default_on` value of the flag
+    Default,
+}
+
+/// Whether a feature is enabled for a user, and why.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureDecision {
+    pub enabled: bool,
+    pub reason: FeatureDecisionReason,
+}
+
+/// A feature flag as stored in the `feature_flags` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureFlagDefinition {
+    pub key: String,
+    pub description: Option<String>,
+    /// Value for users that are neither in a group override nor in the percentage rollout.
+    pub default_on: bool,
+    /// Value for the members of a group, by group ID.
+    pub group_overrides: BTreeMap<String, bool>,
+    /// Percentage of users (0 to 100) that have the flag enabled.
+    pub percentage_rollout: Option<u8>,
+}
+
+impl FeatureFlagDefinition {
+    /// A definition with the value of the config toggle as default, and no overrides.
+    pub fn seeded(key: &str, config: &AppConfig) -> Self {
+        Self {
+            key: key.to_string(),
+            description: None,
+            default_on: FeatureFlag::from_name(key).is_some_and(|flag| flag.config_value(config)),
+            group_overrides: BTreeMap::new(),
+            percentage_rollout: None,
+        }
+    }
+
+    fn from_model(model: feature_flags::Model) -> Result<Self, Report> {
+        let group_overrides = serde_json::from_value(model.group_overrides)
+            .wrap_err_with(|| format!("Invalid group overrides of feature flag {}", model.key))?;
+        Ok(Self {
+            group_overrides,
+            percentage_rollout: model
+                .percentage_rollout
+                .map(|percentage| percentage.clamp(0, 100) as u8),
+            key: model.key,
+            description: model.description,
+            default_on: model.default_on,
+        })
+    }
+
+    /// Evaluate the flag for a user.
+    ///
+    /// Group overrides take precedence, and the flag is enabled if any group of the user enables
+    /// it. Otherwise, users in the percentage rollout have the flag enabled, and all other users
+    /// get `default_on`.
+    pub fn evaluate(&self, user_id: &str, groups: &[String]) -> FeatureDecision {
+        let mut group_values = groups
+            .iter()
+            .filter_map(|group| self.group_overrides.get(group))
+            .peekable();
+        if group_values.peek().is_some() {
+            return FeatureDecision {
+                enabled: group_values.any(|enabled| *enabled),
+                reason: FeatureDecisionReason::GroupOverride,
+            };
+        }
+        if let Some(percentage) = self.percentage_rollout
+            && rollout_bucket(&self.key, user_id) < percentage
+        {
+            return FeatureDecision {
+                enabled: true,
+                reason: FeatureDecisionReason::PercentageRollout,
+            };
+        }
+        FeatureDecision {
+            enabled: self.default_on,
+            reason: FeatureDecisionReason::Default,
+        }
+    }
+}
+
+/// The stable bucket (0 to 99) of a user in the percentage rollout of a flag.
+///
+/// The key is part of the hash, so that the same users don't get every flag first.
+pub fn rollout_bucket(key: &str, user_id: &str) -> u8 {
+    let hash = Sha256::digest(format!("{key}:{user_id}").as_bytes());
+    let value = u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]);
+    (value % 100) as u8
+}
+
+/// In-memory cache of the stored feature flags, shared across all requests.
+#[derive(Debug, Clone, Default)]
+pub struct FeatureFlagStore {
+    definitions: Arc<RwLock<HashMap<String, FeatureFlagDefinition>>>,
+}
+
+impl FeatureFlagStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn definition(&self, key: &str) -> Option<FeatureFlagDefinition> {
+        let definitions = self.definitions.read().expect("feature flag store poisoned");
+        definitions.get(key).cloned()
+    }
+
+    /// All stored flags, sorted by key.
+    pub fn definitions(&self) -> Vec<FeatureFlagDefinition> {
+        let definitions = self.definitions.read().expect("feature flag store poisoned");
+        let mut definitions: Vec<_> = definitions.values().cloned().collect();
+        definitions.sort_by(|a, b| a.key.cmp(&b.key));
+        definitions
+    }
+
+    /// Replace the cached flags.
+    pub fn replace(&self, definitions: Vec<FeatureFlagDefinition>) {
+        let mut cached = self.definitions.write().expect("feature flag store poisoned");
+        *cached = definitions
+            .into_iter()
+            .map(|definition| (definition.key.clone(), definition))
+            .collect();
+    }
+
+    /// Replace the cached flags with the ones stored in the database.
+    pub async fn refresh(&self, db: &DatabaseConnection) -> Result<(), Report> {
+        self.replace(load_feature_flags(db).await?);
+        Ok(())
+    }
+
+    /// The value of a config toggle for users without a group override or percentage rollout,
+    /// together with where it comes from.
+    pub fn effective_value(
+        &self,
+        flag: FeatureFlag,
+        config: &AppConfig,
+    ) -> (bool, FeatureFlagSource) {
+        match self.definition(flag.name()) {
+            Some(definition) => (definition.default_on, FeatureFlagSource::RuntimeOverride),
+            None => (flag.config_value(config), FeatureFlagSource::Config),
+        }
+    }
+
+    pub fn is_enabled(&self, flag: FeatureFlag, config: &AppConfig) -> bool {
+        self.effective_value(flag, config).0
+    }
+
+    /// Evaluate a flag for a user.
+    pub fn evaluate(
+        &self,
+        key: &str,
+        config: &AppConfig,
+        user_id: &str,
+        groups: &[String],
+    ) -> FeatureDecision {
+        match self.definition(key) {
+            Some(definition) => definition.evaluate(user_id, groups),
+            None => FeatureDecision {
+                enabled: FeatureFlag::from_name(key).is_some_and(|flag| flag.config_value(config)),
+                reason: FeatureDecisionReason::Config,
+            },
+        }
+    }
+
+    /// Evaluate the config toggles and all stored flags for a user, by key.
+    pub fn evaluate_all(
+        &self,
+        config: &AppConfig,
+        user_id: &str,
+        groups: &[String],
+    ) -> BTreeMap<String, bool> {
+        let keys = FeatureFlag::ALL
+            .into_iter()
+            .map(|flag| flag.name().to_string())
+            .chain(self.definitions().into_iter().map(|definition| definition.key));
+        keys.map(|key| {
+            let enabled = self.evaluate(&key, config, user_id, groups).enabled;
+            (key, enabled)
+        })
+        .collect()
+    }
+}
+
+/// Load all feature flags stored in the database.
+pub async fn load_feature_flags(
+    db: &DatabaseConnection,
+) -> Result<Vec<FeatureFlagDefinition>, Report> {
+    FeatureFlags::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(FeatureFlagDefinition::from_model)
+        .collect()
+}
+
+/// Store a feature flag, replacing a previous definition of the same key, and refresh the
+/// cache of the instance.
+pub async fn save_feature_flag(
+    db: &DatabaseConnection,
+    store: &FeatureFlagStore,
+    definition: FeatureFlagDefinition,
+) -> Result<(), Report> {
+    let group_overrides = serde_json::to_value(&definition.group_overrides)?;
+    FeatureFlags::insert(feature_flags::ActiveModel {
+        key: ActiveValue::Set(definition.key),
+        description: ActiveValue::Set(definition.description),
+        default_on: ActiveValue::Set(definition.default_on),
+        group_overrides: ActiveValue::Set(group_overrides),
+        percentage_rollout: ActiveValue::Set(definition.percentage_rollout.map(i16::from)),
+        updated_at: ActiveValue::Set(chrono::Utc::now().into()),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::column(feature_flags::Column::Key)
+            .update_columns([
+                feature_flags::Column::Description,
+                feature_flags::Column::DefaultOn,
+                feature_flags::Column::GroupOverrides,
+                feature_flags::Column::PercentageRollout,
+                feature_flags::Column::UpdatedAt,
+            ])
+            .to_owned(),
+    )
+    .exec(db)
+    .await?;
+    store.refresh(db).await
+}
+
+/// Delete a stored feature flag, and refresh the cache of the instance.
+///
+/// Returns whether the flag was stored.
+pub async fn delete_feature_flag(
+    db: &DatabaseConnection,
+    store: &FeatureFlagStore,
+    key: &str,
+) -> Result<bool, Report> {
+    let result = FeatureFlags::delete_by_id(key.to_string()).exec(db).await?;
+    store.refresh(db).await?;
+    Ok(result.rows_affected > 0)
+}
+
+/// Start refreshing the feature flags from the database in the background.
+pub fn start_feature_flags_refresh(app_state: &AppState) -> bool {
+    let db = app_state.db.clone();
+    let store = app_state.feature_flags.clone();
+    app_state
+        .background_tasks
+        .start_maintenance_task(FEATURE_FLAGS_REFRESH_TASK, async move {
+            let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(err) = store.refresh(&db).await {
+                    tracing::warn!(error = %err, "Failed to refresh feature flags");
+                }
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(
+        group_overrides: &[(&str, bool)],
+        percentage_rollout: Option<u8>,
+    ) -> FeatureFlagDefinition {
+        FeatureFlagDefinition {
+            key: "new_sidebar".to_string(),
+            description: None,
+            default_on: false,
+            group_overrides: group_overrides
+                .iter()
+                .map(|(group, enabled)| (group.to_string(), *enabled))
+                .collect(),
+            percentage_rollout,
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for flag in FeatureFlag::ALL {
+            assert_eq!(FeatureFlag::from_name(flag.name()), Some(flag));
+        }
+        assert_eq!(FeatureFlag::from_name("unknown.enabled"), None);
+    }
+
+    #[test]
+    fn stored_flags_take_precedence_over_config() {
+        let mut config = AppConfig::default();
+        config.prompt_optimizer.enabled = true;
+        let store = FeatureFlagStore::new();
+
+        assert_eq!(
+            store.effective_value(FeatureFlag::PromptOptimizer, &config),
+            (true, FeatureFlagSource::Config)
+        );
+
+        store.replace(vec![FeatureFlagDefinition {
+            default_on: false,
+            ..FeatureFlagDefinition::seeded("prompt_optimizer.enabled", &config)
+        }]);
+        assert_eq!(
+            store.effective_value(FeatureFlag::PromptOptimizer, &config),
+            (false, FeatureFlagSource::RuntimeOverride)
+        );
+        // Clones share the cache
+        assert!(
+            !store
+                .clone()
+                .is_enabled(FeatureFlag::PromptOptimizer, &config)
+        );
+
+        store.replace(Vec::new());
+        assert!(store.is_enabled(FeatureFlag::PromptOptimizer, &config));
+        assert_eq!(
+            store.evaluate("unknown", &config, "user", &[]),
+            FeatureDecision {
+                enabled: false,
+                reason: FeatureDecisionReason::Config,
+            }
+        );
+    }
+
+    #[test]
+    fn group_overrides_take_precedence_over_rollout_and_default() {
+        let flag = definition(&[("beta", true), ("legal", false)], Some(100));
+
+        let decision = flag.evaluate("user", &["legal".to_string()]);
+        assert_eq!(decision.reason, FeatureDecisionReason::GroupOverride);
+        assert!(!decision.enabled);
+
+        // An enabling group wins over a disabling one
+        let decision = flag.evaluate("user", &["legal".to_string(), "beta".to_string()]);
+        assert_eq!(decision.reason, FeatureDecisionReason::GroupOverride);
+        assert!(decision.enabled);
+
+        let decision = flag.evaluate("user", &["other".to_string()]);
+        assert_eq!(decision.reason, FeatureDecisionReason::PercentageRollout);
+        assert!(decision.enabled);
+
+        let decision = definition(&[], Some(0)).evaluate("user", &[]);
+        assert_eq!(decision.reason, FeatureDecisionReason::Default);
+        assert!(!decision.enabled);
+    }
+
+    #[test]
+    fn percentage_rollout_is_deterministic() {
+        let user_ids: Vec<String> = (0..1000).map(|i| format!("user-{i}")).collect();
+        for user_id in &user_ids {
+            let bucket = rollout_bucket("new_sidebar", user_id);
+            assert!(bucket < 100);
+            assert_eq!(bucket, rollout_bucket("new_sidebar", user_id));
+        }
+
+        let enabled = |percentage| {
+            let flag = definition(&[], Some(percentage));
+            user_ids
+                .iter()
+                .filter(|user_id| flag.evaluate(user_id, &[]).enabled)
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let quarter = enabled(25);
+        let half = enabled(50);
+        assert!((150..350).contains(&quarter.len()));
+        assert!((400..600).contains(&half.len()));
+        // Raising the percentage only adds users
+        assert!(quarter.iter().all(|user_id| half.contains(user_id)));
+        assert!(enabled(0).is_empty());
+        assert_eq!(enabled(100).len(), user_ids.len());
+    }
+}
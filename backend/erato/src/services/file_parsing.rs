@@ -1,12 +1,162 @@
-use crate::services::file_processor::FileProcessor;
+use crate::config::FileProcessorConfig;
+use crate::metrics::report_file_processing_error;
+use crate::services::file_processor::{FileProcessingError, FileProcessor, file_processing_error};
 use eyre::Report;
+use std::time::Duration;
 use tracing::instrument;
 
+const DEFAULT_PARSE_TIMEOUT_SECONDS: u64 = 120;
+
+/// Limits applied to the extraction of the contents of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseLimits {
+    pub max_file_size_bytes: Option<u64>,
+    pub timeout: Option<Duration>,
+}
+
+impl ParseLimits {
+    pub fn from_config(config: &FileProcessorConfig) -> Self {
+        Self {
+            max_file_size_bytes: config.max_file_size_mb.map(|mb| mb * 1024 * 1024),
+            timeout: Some(Duration::from_secs(
+                config
+                    .timeout_seconds
+                    .unwrap_or(DEFAULT_PARSE_TIMEOUT_SECONDS),
+            )),
+        }
+    }
+}
+
+/// Extract the text of a file.
+///
+/// Files above the size limit fail with [`FileProcessingError::TooLarge`] without being parsed,
+/// and parsing that exceeds the timeout fails with [`FileProcessingError::Timeout`]. The blocking
+/// parser work can't be cancelled, so it still runs to completion in the background.
 #[instrument(skip_all)]
 pub async fn parse_file(
     file_processor: &dyn FileProcessor,
     file_bytes: Vec<u8>,
     mime_type: Option<&str>,
+    limits: ParseLimits,
+) -> Result<String, Report> {
+    let result = parse_file_with_limits(file_processor, file_bytes, mime_type, limits).await;
+    if let Err(err) = &result
+        && let Some(error) = file_processing_error(err)
+    {
+        report_file_processing_error(error.kind());
+    }
+    result
+}
+
+async fn parse_file_with_limits(
+    file_processor: &dyn FileProcessor,
+    file_bytes: Vec<u8>,
+    mime_type: Option<&str>,
+    limits: ParseLimits,
 ) -> Result<String, Report> {
-    file_processor.parse_file(file_bytes, mime_type).await
+    if let Some(limit) = limits.max_file_size_bytes
+        && file_bytes.len() as u64 > limit
+    {
+        return Err(Report::new(FileProcessingError::TooLarge { limit }));
+    }
+
+    let parsing = file_processor.parse_file(file_bytes, mime_type);
+    match limits.timeout {
+        Some(timeout) => tokio::time::timeout(timeout, parsing)
+            .await
+            .map_err(|_| Report::new(FileProcessingError::Timeout))?,
+        None => parsing.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::file_processor::XbergProcessor;
+    use async_trait::async_trait;
+
+    /// File processor that takes longer than any of the timeouts used in the tests.
+    struct SlowProcessor;
+
+    #[async_trait]
+    impl FileProcessor for SlowProcessor {
+        async fn parse_file(
+            &self,
+            _file_bytes: Vec<u8>,
+            _mime_type: Option<&str>,
+        ) -> Result<String, Report> {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(String::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn parse_file_rejects_oversized_text() {
+        let limits = ParseLimits {
+            max_file_size_bytes: Some(1024 * 1024),
+            timeout: None,
+        };
+        let oversized_text = "All work and no play makes Jack a dull boy.\n"
+            .repeat(50_000)
+            .into_bytes();
+
+        let err = parse_file(&XbergProcessor, oversized_text, Some("text/plain"), limits)
+            .await
+            .expect_err("oversized text should not be parsed");
+
+        assert_eq!(
+            file_processing_error(&err),
+            Some(FileProcessingError::TooLarge { limit: 1024 * 1024 })
+        );
+    }
+
+    #[tokio::test]
+    async fn parse_file_accepts_text_within_the_size_limit() {
+        let limits = ParseLimits {
+            max_file_size_bytes: Some(1024 * 1024),
+            timeout: None,
+        };
+
+        let text = parse_file(
+            &XbergProcessor,
+            b"A short note.".to_vec(),
+            Some("text/plain"),
+            limits,
+        )
+        .await
+        .expect("small text should be parsed");
+
+        assert!(text.contains("A short note."));
+    }
+
+    #[tokio::test]
+    async fn parse_file_times_out() {
+        let limits = ParseLimits {
+            max_file_size_bytes: None,
+            timeout: Some(Duration::from_millis(50)),
+        };
+
+        let err = parse_file(&SlowProcessor, b"slow".to_vec(), None, limits)
+            .await
+            .expect_err("parsing should time out");
+
+        assert_eq!(
+            file_processing_error(&err),
+            Some(FileProcessingError::Timeout)
+        );
+    }
+
+    #[test]
+    fn parse_limits_from_config() {
+        let limits = ParseLimits::from_config(&FileProcessorConfig {
+            max_file_size_mb: Some(5),
+            ..Default::default()
+        });
+
+        assert_eq!(limits.max_file_size_bytes, Some(5 * 1024 * 1024));
+        assert_eq!(
+            limits.timeout,
+            Some(Duration::from_secs(DEFAULT_PARSE_TIMEOUT_SECONDS))
+        );
+    }
 }
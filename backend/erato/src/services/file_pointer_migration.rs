@@ -0,0 +1,412 @@
+//! Migration of inline file contents in stored generation inputs to file pointers.
+//!
+//! Before file pointers existed, the resolved contents of attached files were stored inline in
+//! `generation_input_messages`: text files as a text part starting with a `File:` header that
+//! carries the `erato_file_id:` of the file, and images as an `image_file_pointer:` text part
+//! followed by the base64 encoded image. Besides bloating the messages table, these copies are
+//! sent again on every regeneration instead of the current contents of the file.
+//!
+//! The migration walks over all messages in the order of `(created_at, id)` in batches, and
+//! replaces inline contents of files that still exist with `TextFilePointer` and
+//! `ImageFilePointer` parts, which are resolved to the same contents when generating. Contents
+//! of files that no longer exist are kept, as they can't be resolved anymore. The position of the
+//! last processed message is persisted after every batch, so the migration resumes where it
+//! stopped after a restart.
+
+use crate::config::FilePointerMigrationConfig;
+use crate::db::entity::{file_uploads, messages};
+use crate::metrics::report_inline_file_contents_write;
+use crate::metrics_constants::{
+    POSTGRES_QUERY_FILE_POINTER_MIGRATION_BATCH, POSTGRES_QUERY_RECORD_FILE_POINTER_MIGRATION,
+};
+use crate::models::message::{
+    ContentPart, ContentPartImageFilePointer, ContentPartTextFilePointer, GenerationInputMessages,
+    InputMessage,
+};
+use crate::query_metrics::named_statement_from_sql_and_values;
+use crate::services::background_tasks::BackgroundTaskManager;
+use crate::services::maintenance_tasks::{
+    MaintenanceTaskBatch, MaintenanceTaskProgress, lock_maintenance_task_progress,
+    run_maintenance_task, start_maintenance_task, update_maintenance_task_progress,
+};
+use crate::services::prompt_composition::token_breakdown::FILE_CONTENT_PREFIX;
+use crate::services::untrusted_content::file_id_from_file_content;
+use eyre::{Report, WrapErr};
+use sea_orm::prelude::*;
+use sea_orm::{
+    ActiveValue, ConnectionTrait, DatabaseConnection, FromQueryResult, QuerySelect,
+    TransactionTrait,
+};
+use std::collections::HashSet;
+
+/// Name of the migration in the maintenance task progress and the background task manager.
+pub const FILE_POINTER_MIGRATION_TASK: &str = "file_pointer_migration";
+
+/// Prefix of the text part that precedes an inline image (see `format_image_file_pointer_message`).
+const IMAGE_FILE_POINTER_MARKER_PREFIX: &str = "image_file_pointer: erato-file://";
+
+#[derive(Debug, FromQueryResult)]
+struct MessageGenerationInput {
+    id: Uuid,
+    created_at: DateTimeWithTimeZone,
+    generation_input_messages: Json,
+}
+
+/// The file upload referenced by an inline file content part, if it is one.
+///
+/// For inline images, this is the marker text part that precedes the image.
+fn inline_file_upload_id(content: &ContentPart) -> Option<Uuid> {
+    let ContentPart::Text(text) = content else {
+        return None;
+    };
+    if text.text.starts_with(FILE_CONTENT_PREFIX) {
+        return file_id_from_file_content(&text.text);
+    }
+    text.text
+        .strip_prefix(IMAGE_FILE_POINTER_MARKER_PREFIX)
+        .and_then(|file_id| Uuid::parse_str(file_id.trim()).ok())
+}
+
+/// The IDs of all files whose contents are stored inline in the generation input.
+pub fn inline_file_upload_ids(generation_input: &GenerationInputMessages) -> Vec<Uuid> {
+    let mut file_upload_ids = Vec::new();
+    for message in &generation_input.messages {
+        if let Some(file_upload_id) = inline_file_upload_id(&message.content)
+            && !file_upload_ids.contains(&file_upload_id)
+        {
+            file_upload_ids.push(file_upload_id);
+        }
+    }
+    file_upload_ids
+}
+
+/// Replace the inline contents of the files in `existing_file_upload_ids` with file pointers.
+///
+/// Returns the number of replaced content parts, counting an inline image and its marker as one.
+pub fn replace_inline_file_contents(
+    generation_input: &mut GenerationInputMessages,
+    existing_file_upload_ids: &HashSet<Uuid>,
+) -> usize {
+    let mut replaced_parts = 0;
+    let mut messages = Vec::with_capacity(generation_input.messages.len());
+    let mut input_messages = std::mem::take(&mut generation_input.messages)
+        .into_iter()
+        .peekable();
+    while let Some(message) = input_messages.next() {
+        let Some(file_upload_id) = inline_file_upload_id(&message.content)
+            .filter(|file_upload_id| existing_file_upload_ids.contains(file_upload_id))
+        else {
+            messages.push(message);
+            continue;
+        };
+
+        let ContentPart::Text(text) = &message.content else {
+            unreachable!("inline file contents are always text parts");
+        };
+        let content = if text.text.starts_with(FILE_CONTENT_PREFIX) {
+            ContentPart::TextFilePointer(ContentPartTextFilePointer { file_upload_id })
+        } else {
+            // The marker is only replaced together with the image it precedes
+            match input_messages.peek() {
+                Some(image)
+                    if image.role == message.role
+                        && matches!(image.content, ContentPart::Image(_)) =>
+                {
+                    input_messages.next();
+                    ContentPart::ImageFilePointer(ContentPartImageFilePointer {
+                        file_upload_id,
+                        download_url: None,
+                        preview_url: None,
+                    })
+                }
+                _ => {
+                    messages.push(message);
+                    continue;
+                }
+            }
+        };
+        replaced_parts += 1;
+        messages.push(InputMessage {
+            role: message.role,
+            content,
+        });
+    }
+    generation_input.messages = messages;
+    replaced_parts
+}
+
+/// The subset of the given file uploads that exist.
+pub async fn existing_file_upload_ids<C: ConnectionTrait>(
+    conn: &C,
+    file_upload_ids: &[Uuid],
+) -> Result<HashSet<Uuid>, Report> {
+    if file_upload_ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let existing: Vec<Uuid> = file_uploads::Entity::find()
+        .select_only()
+        .column(file_uploads::Column::Id)
+        .filter(file_uploads::Column::Id.is_in(file_upload_ids.iter().copied()))
+        .into_tuple()
+        .all(conn)
+        .await?;
+    Ok(existing.into_iter().collect())
+}
+
+/// Check that a generation input that is about to be stored has no inline contents of files
+/// that could be referenced with a file pointer instead.
+///
+/// Violations are reported as a metric and logged, and fail debug builds.
+pub async fn check_no_inline_file_contents(
+    conn: &DatabaseConnection,
+    generation_input: &GenerationInputMessages,
+) -> Result<(), Report> {
+    let file_upload_ids = inline_file_upload_ids(generation_input);
+    if file_upload_ids.is_empty() {
+        return Ok(());
+    }
+    let existing = existing_file_upload_ids(conn, &file_upload_ids).await?;
+    if !existing.is_empty() {
+        report_inline_file_contents_write(existing.len() as u64);
+        tracing::error!(
+            file_upload_ids = ?existing,
+            "Storing generation input with inline file contents instead of file pointers"
+        );
+    }
+    debug_assert!(
+        existing.is_empty(),
+        "Generation inputs must reference files with file pointers, found inline contents of {existing:?}"
+    );
+    Ok(())
+}
+
+/// Start the file pointer migration in the background.
+///
+/// Returns `false` if the migration is already running.
+pub fn start_file_pointer_migration(
+    background_tasks: &BackgroundTaskManager,
+    db: DatabaseConnection,
+    config: FilePointerMigrationConfig,
+) -> bool {
+    start_maintenance_task(background_tasks, FILE_POINTER_MIGRATION_TASK, async move {
+        run_file_pointer_migration(&db, &config).await
+    })
+}
+
+/// Migrate all remaining messages, waiting `batch_interval_ms` between batches, see
+/// [`run_maintenance_task`].
+pub async fn run_file_pointer_migration(
+    db: &DatabaseConnection,
+    config: &FilePointerMigrationConfig,
+) -> Result<MaintenanceTaskProgress, Report> {
+    run_maintenance_task(db, FILE_POINTER_MIGRATION_TASK, config.batch_interval_ms, || {
+        migrate_file_pointer_batch(db, config.batch_size.max(1))
+    })
+    .await
+}
+
+/// Migrate the next batch of up to `batch_size` messages after the persisted position.
+///
+/// The progress row is locked for the duration of the batch, so that several instances running
+/// the migration at the same time process disjoint batches. An empty batch marks the migration
+/// as completed.
+pub async fn migrate_file_pointer_batch(
+    db: &DatabaseConnection,
+    batch_size: u64,
+) -> Result<MaintenanceTaskBatch, Report> {
+    let txn = db.begin().await?;
+    let progress = lock_maintenance_task_progress(&txn, FILE_POINTER_MIGRATION_TASK).await?;
+
+    let rows = MessageGenerationInput::find_by_statement(named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_FILE_POINTER_MIGRATION_BATCH,
+        r#"
+        SELECT id, created_at, generation_input_messages
+        FROM messages
+        WHERE generation_input_messages IS NOT NULL
+          AND ($1::timestamptz IS NULL OR (created_at, id) > ($1::timestamptz, $2::uuid))
+        ORDER BY created_at, id
+        LIMIT $3
+        "#,
+        [
+            progress.cursor_created_at.into(),
+            progress.cursor_id.into(),
+            (batch_size.min(i64::MAX as u64) as i64).into(),
+        ],
+    ))
+    .all(&txn)
+    .await?;
+
+    let mut batch = MaintenanceTaskBatch {
+        processed_messages: rows.len() as u64,
+        ..Default::default()
+    };
+    let cursor = rows.last().map(|row| (row.created_at, row.id));
+    for row in rows {
+        // Rows that can't be parsed are skipped, they are rejected when generating as well
+        let Ok(mut generation_input) =
+            GenerationInputMessages::validate(&row.generation_input_messages)
+        else {
+            continue;
+        };
+        let file_upload_ids = inline_file_upload_ids(&generation_input);
+        if file_upload_ids.is_empty() {
+            continue;
+        }
+        let existing = existing_file_upload_ids(&txn, &file_upload_ids).await?;
+        let replaced_parts = replace_inline_file_contents(&mut generation_input, &existing);
+        if replaced_parts == 0 {
+            continue;
+        }
+
+        let migrated = serde_json::to_value(&generation_input)?;
+        let bytes_before = serde_json::to_vec(&row.generation_input_messages)?.len() as i64;
+        let bytes_after = serde_json::to_vec(&migrated)?.len() as i64;
+        messages::ActiveModel {
+            id: ActiveValue::Unchanged(row.id),
+            generation_input_messages: ActiveValue::Set(Some(migrated)),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .wrap_err("Failed to update the generation input of a message")?;
+        txn.execute_raw(named_statement_from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            POSTGRES_QUERY_RECORD_FILE_POINTER_MIGRATION,
+            r#"
+            INSERT INTO message_file_pointer_migrations
+                (message_id, replaced_parts, bytes_before, bytes_after)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (message_id) DO UPDATE SET
+                replaced_parts = message_file_pointer_migrations.replaced_parts
+                    + EXCLUDED.replaced_parts,
+                bytes_after = EXCLUDED.bytes_after,
+                migrated_at = now()
+            "#,
+            [
+                row.id.into(),
+                (replaced_parts as i32).into(),
+                bytes_before.into(),
+                bytes_after.into(),
+            ],
+        ))
+        .await?;
+
+        batch.migrated_messages += 1;
+        batch.bytes_saved += bytes_before - bytes_after;
+    }
+
+    update_maintenance_task_progress(&txn, FILE_POINTER_MIGRATION_TASK, cursor, &batch).await?;
+    txn.commit().await?;
+
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::{ContentPartImage, ContentPartText, MessageRole};
+
+    fn text(role: MessageRole, text: &str) -> InputMessage {
+        InputMessage {
+            role,
+            content: ContentPart::Text(ContentPartText {
+                text: text.to_string(),
+            }),
+        }
+    }
+
+    fn image(role: MessageRole) -> InputMessage {
+        InputMessage {
+            role,
+            content: ContentPart::Image(ContentPartImage {
+                content_type: "image/png".to_string(),
+                base64_data: "aGVsbG8=".to_string(),
+            }),
+        }
+    }
+
+    fn file_content(file_id: Uuid) -> String {
+        format!(
+            "File:\nfile name: notes.txt\nfile_id: erato_file_id:{file_id}\nFile contents\n---\nHello\n---"
+        )
+    }
+
+    #[test]
+    fn test_replaces_inline_contents_of_existing_files() {
+        let text_file_id = Uuid::new_v4();
+        let image_file_id = Uuid::new_v4();
+        let mut generation_input = GenerationInputMessages {
+            messages: vec![
+                text(MessageRole::System, "You are a helpful assistant"),
+                text(MessageRole::User, &file_content(text_file_id)),
+                text(
+                    MessageRole::User,
+                    &format!("image_file_pointer: erato-file://{image_file_id}"),
+                ),
+                image(MessageRole::User),
+                text(MessageRole::User, "What do the files say?"),
+            ],
+        };
+        assert_eq!(
+            inline_file_upload_ids(&generation_input),
+            vec![text_file_id, image_file_id]
+        );
+
+        let existing = HashSet::from([text_file_id, image_file_id]);
+        let replaced_parts = replace_inline_file_contents(&mut generation_input, &existing);
+
+        assert_eq!(replaced_parts, 2);
+        let contents: Vec<&ContentPart> = generation_input
+            .messages
+            .iter()
+            .map(|message| &message.content)
+            .collect();
+        assert_eq!(contents.len(), 4);
+        assert_eq!(
+            contents[1],
+            &ContentPart::TextFilePointer(ContentPartTextFilePointer {
+                file_upload_id: text_file_id
+            })
+        );
+        assert_eq!(
+            contents[2],
+            &ContentPart::ImageFilePointer(ContentPartImageFilePointer {
+                file_upload_id: image_file_id,
+                download_url: None,
+                preview_url: None,
+            })
+        );
+        assert!(inline_file_upload_ids(&generation_input).is_empty());
+    }
+
+    #[test]
+    fn test_keeps_inline_contents_of_missing_files() {
+        let file_id = Uuid::new_v4();
+        let mut generation_input = GenerationInputMessages {
+            messages: vec![
+                text(MessageRole::User, &file_content(file_id)),
+                // A marker without the image it precedes is not an inline image
+                text(
+                    MessageRole::User,
+                    &format!("image_file_pointer: erato-file://{file_id}"),
+                ),
+                text(MessageRole::User, "Attached files:\n1. notes.txt"),
+            ],
+        };
+
+        assert_eq!(
+            replace_inline_file_contents(&mut generation_input, &HashSet::new()),
+            0
+        );
+        assert_eq!(
+            replace_inline_file_contents(&mut generation_input, &HashSet::from([file_id])),
+            1
+        );
+        assert!(matches!(
+            generation_input.messages[1].content,
+            ContentPart::Text(_)
+        ));
+        assert_eq!(generation_input.messages.len(), 3);
+    }
+}
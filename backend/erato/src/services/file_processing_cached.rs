@@ -1,13 +1,19 @@
 use crate::db::entity::prelude::FileUploads;
+use crate::metrics::report_file_processing_error;
 use crate::policy::engine::PolicyEngine;
 use crate::server::api::v1beta::me_profile_middleware::MeProfile;
 use crate::server::api::v1beta::message_streaming::{
     FileContent, FileContentsForGeneration, remove_null_characters,
 };
-use crate::services::file_parsing::parse_file;
-use crate::services::file_storage::{FileStorage, SharepointContext};
-use crate::state::{AppState, FileCacheKey};
+use crate::services::file_parsing::{ParseLimits, parse_file};
+use crate::services::file_processor::{FileProcessingError, file_processing_error};
+use crate::services::file_storage::{
+    FileStorage, SharepointContext, is_missing_permissions_error, is_not_found_error,
+};
+use crate::services::file_synopsis::build_file_synopsis;
+use crate::state::{AppState, FailedFileContents, FileCacheKey, ParsedFileContents};
 use eyre::{ContextCompat, OptionExt, Report, WrapErr};
+use moka::future::Cache;
 use sea_orm::EntityTrait;
 use sea_orm::prelude::Uuid;
 use std::sync::Arc;
@@ -15,7 +21,7 @@ use tiktoken_rs::o200k_base;
 use tracing::{Instrument, instrument};
 
 /// Helper function to determine if a file is an image based on extension
-fn is_image_file(filename: &str) -> bool {
+pub(crate) fn is_image_file(filename: &str) -> bool {
     if let Some(extension) = filename.rsplit('.').next() {
         matches!(
             extension.to_lowercase().as_str(),
@@ -74,17 +80,73 @@ fn effective_text_mime_type(filename: &str, storage_mime_type: Option<&str>) ->
     storage_mime_type.map(ToOwned::to_owned)
 }
 
-async fn parse_text_file_bytes(
+pub(crate) async fn parse_text_file_bytes(
     file_processor: &dyn crate::services::file_processor::FileProcessor,
     file_bytes: Vec<u8>,
     filename: &str,
     storage_mime_type: Option<&str>,
+    limits: ParseLimits,
 ) -> Result<String, Report> {
     let mime_type = effective_text_mime_type(filename, storage_mime_type);
-    let parsed_content = parse_file(file_processor, file_bytes, mime_type.as_deref()).await?;
+    let parsed_content =
+        parse_file(file_processor, file_bytes, mime_type.as_deref(), limits).await?;
     Ok(remove_null_characters(&parsed_content))
 }
 
+/// Mark a failed storage access as [`FileProcessingError::StorageUnavailable`].
+///
+/// Missing objects and missing permissions are left as they are, as callers handle them
+/// separately.
+fn storage_unavailable(err: Report) -> Report {
+    if is_not_found_error(&err) || is_missing_permissions_error(&err) {
+        return err;
+    }
+    report_file_processing_error(FileProcessingError::StorageUnavailable.kind());
+    let message = format!("{:#}", err);
+    Report::new(FileProcessingError::StorageUnavailable).wrap_err(message)
+}
+
+/// Take the error of a cache loader back out of the cache.
+///
+/// If other callers share the error, it is rebuilt from its message and its processing error.
+fn unwrap_shared_error(arc_err: Arc<Report>) -> Report {
+    Arc::try_unwrap(arc_err).unwrap_or_else(|arc| match file_processing_error(&arc) {
+        Some(error) => Report::new(error).wrap_err(arc.to_string()),
+        None => eyre::eyre!("{}", arc),
+    })
+}
+
+/// The deterministic failure to parse the version of the file of the cache key, if it was seen
+/// before.
+async fn previous_processing_failure(
+    cache: &Cache<Uuid, FailedFileContents>,
+    cache_key: &FileCacheKey,
+) -> Option<FileProcessingError> {
+    let failed = cache.get(&cache_key.file_id).await?;
+    failed.error_for(cache_key).cloned()
+}
+
+/// Remember the failure to parse a file, if parsing it again would fail the same way.
+async fn remember_processing_failure(
+    cache: &Cache<Uuid, FailedFileContents>,
+    cache_key: &FileCacheKey,
+    err: &Report,
+) {
+    if let Some(error) = file_processing_error(err)
+        && error.is_deterministic()
+    {
+        cache
+            .insert(
+                cache_key.file_id,
+                FailedFileContents {
+                    etag: cache_key.etag.clone(),
+                    error,
+                },
+            )
+            .await;
+    }
+}
+
 /// Get raw file bytes from cache or storage
 #[instrument(
     skip_all,
@@ -122,7 +184,8 @@ async fn get_file_bytes_cached<'a>(
                 .wrap_err(format!(
                     "Failed to read file from storage: {}",
                     file_storage_path
-                ))?;
+                ))
+                .map_err(storage_unavailable)?;
 
             span.record("file_bytes_length", file_bytes.len());
             tracing::debug!(
@@ -135,18 +198,21 @@ async fn get_file_bytes_cached<'a>(
             Ok::<_, Report>(file_bytes)
         })
         .await
-        .map_err(|arc_err| Arc::try_unwrap(arc_err).unwrap_or_else(|arc| eyre::eyre!("{}", arc)))?;
+        .map_err(unwrap_shared_error)?;
 
     span.record("file_bytes_length", result.len());
     Ok(result)
 }
 
-/// Get parsed text file contents from cache or fetch/parse
+/// Get parsed text file contents (and their synopsis) from cache or fetch/parse
 ///
 /// This function now operates in two tiers:
 /// 1. Check file_contents_cache for parsed text
 /// 2. If miss, check file_bytes_cache for raw bytes, then parse
 /// 3. If miss, fetch from storage, cache bytes, then parse
+///
+/// Files that failed to parse with a deterministic [`FileProcessingError`] are remembered in
+/// file_processing_errors_cache, and fail with the same error without being parsed again.
 #[instrument(
     skip_all,
     fields(
@@ -163,12 +229,24 @@ pub async fn get_file_contents_cached<'a>(
     file_storage_path: &str,
     filename: &str,
     sharepoint_ctx: Option<&SharepointContext<'a>>,
-) -> Result<String, Report> {
+) -> Result<ParsedFileContents, Report> {
     let file_id_str = cache_key.file_id.to_string();
     let span = tracing::Span::current();
     span.record("file_id", &file_id_str);
     span.record("file_storage_path", file_storage_path);
 
+    if let Some(error) =
+        previous_processing_failure(&app_state.file_processing_errors_cache, cache_key).await
+    {
+        tracing::debug!(
+            file_id = %cache_key.file_id,
+            etag = ?cache_key.etag,
+            error = %error,
+            "File previously failed to parse"
+        );
+        return Err(Report::new(error));
+    }
+
     // First check the parsed content cache
     let result = app_state
         .file_contents_cache
@@ -192,7 +270,8 @@ pub async fn get_file_contents_cached<'a>(
             span.record("file_bytes_length", file_bytes.len());
             let storage_mime_type = file_storage
                 .get_file_content_type_with_context(file_storage_path, sharepoint_ctx)
-                .await?;
+                .await
+                .map_err(storage_unavailable)?;
 
             // Parse the file using the configured file processor
             let _permit = app_state
@@ -205,8 +284,15 @@ pub async fn get_file_contents_cached<'a>(
                 file_bytes,
                 filename,
                 storage_mime_type.as_deref(),
+                ParseLimits::from_config(&app_state.config.file_processor),
             )
             .await?;
+            let synopsis = build_file_synopsis(
+                &content,
+                filename,
+                effective_text_mime_type(filename, storage_mime_type.as_deref()).as_deref(),
+                app_state.config.chat.file_synopsis_sentences,
+            );
 
             tracing::debug!(
                 file_id = %cache_key.file_id,
@@ -215,12 +301,24 @@ pub async fn get_file_contents_cached<'a>(
                 "File parsed and cached"
             );
 
-            Ok::<_, Report>(content)
+            Ok::<_, Report>(ParsedFileContents {
+                text: content,
+                synopsis,
+            })
         })
         .await
-        .map_err(|arc_err| Arc::try_unwrap(arc_err).unwrap_or_else(|arc| eyre::eyre!("{}", arc)))?;
+        .map_err(unwrap_shared_error);
+
+    let result = match result {
+        Ok(result) => result,
+        Err(err) => {
+            remember_processing_failure(&app_state.file_processing_errors_cache, cache_key, &err)
+                .await;
+            return Err(err);
+        }
+    };
 
-    span.record("content_length", result.len());
+    span.record("content_length", result.text.len());
     Ok(result)
 }
 
@@ -255,7 +353,9 @@ pub fn get_file_cached<'a>(
         span.record("filename", filename);
 
         let cache_key =
-            get_file_cache_key(file_storage, file_id, file_storage_path, sharepoint_ctx).await?;
+            get_file_cache_key(file_storage, file_id, file_storage_path, sharepoint_ctx)
+                .await
+                .map_err(storage_unavailable)?;
 
         let is_image = is_image_file(filename);
         span.record("file_type", if is_image { "image" } else { "text" });
@@ -294,6 +394,7 @@ pub fn get_file_cached<'a>(
                     raw_bytes,
                     mime_type,
                 },
+                synopsis: None,
             })
         } else {
             // Text path: cache both bytes and parsed content
@@ -303,7 +404,7 @@ pub fn get_file_cached<'a>(
                 "Processing as text file"
             );
 
-            let text = get_file_contents_cached(
+            let parsed = get_file_contents_cached(
                 app_state,
                 &cache_key,
                 file_storage,
@@ -316,14 +417,15 @@ pub fn get_file_cached<'a>(
             tracing::debug!(
                 file_id = %file_id,
                 filename = %filename,
-                text_len = text.len(),
+                text_len = parsed.text.len(),
                 "Text file loaded and parsed"
             );
 
             Ok(FileContentsForGeneration {
                 id: *file_id,
                 filename: filename.to_string(),
-                content: FileContent::Text(text),
+                content: FileContent::Text(parsed.text),
+                synopsis: Some(parsed.synopsis),
             })
         }
     })
@@ -350,6 +452,56 @@ async fn get_file_cache_key<'a>(
     })
 }
 
+/// Remove the cached bytes and parsed contents of a file (for all of its ETags).
+///
+/// Returns the number of removed cache entries.
+pub async fn purge_file_cached(app_state: &AppState, file_id: &Uuid) -> u64 {
+    let mut purged = 0;
+    let contents_keys: Vec<FileCacheKey> = app_state
+        .file_contents_cache
+        .iter()
+        .filter(|(key, _)| key.file_id == *file_id)
+        .map(|(key, _)| (*key).clone())
+        .collect();
+    for key in contents_keys {
+        if app_state.file_contents_cache.remove(&key).await.is_some() {
+            purged += 1;
+        }
+    }
+    let bytes_keys: Vec<FileCacheKey> = app_state
+        .file_bytes_cache
+        .iter()
+        .filter(|(key, _)| key.file_id == *file_id)
+        .map(|(key, _)| (*key).clone())
+        .collect();
+    for key in bytes_keys {
+        if app_state.file_bytes_cache.remove(&key).await.is_some() {
+            purged += 1;
+        }
+    }
+    if app_state
+        .file_processing_errors_cache
+        .remove(file_id)
+        .await
+        .is_some()
+    {
+        purged += 1;
+    }
+    purged
+}
+
+/// The deterministic processing failure of a file, if it is cached for any version of it.
+pub async fn cached_file_processing_error(
+    app_state: &AppState,
+    file_id: &Uuid,
+) -> Option<FileProcessingError> {
+    app_state
+        .file_processing_errors_cache
+        .get(file_id)
+        .await
+        .map(|failed| failed.error)
+}
+
 /// Get token count from cache or calculate
 #[instrument(
     skip_all,
@@ -407,9 +559,18 @@ pub async fn get_token_count_cached(app_state: &AppState, content: &str) -> Resu
     Ok(token_count)
 }
 
+/// A file whose contents could not be processed.
+#[derive(Debug, Clone)]
+pub struct UnprocessableFile {
+    pub id: Uuid,
+    pub filename: String,
+    pub error: FileProcessingError,
+}
+
 /// Process a single file and return its contents (with caching).
 ///
-/// Now uses the unified get_file_cached function.
+/// Now uses the unified get_file_cached function. A file that can't be processed is returned as
+/// the inner error, so the caller can report why.
 /// This function is boxed to reduce stack usage.
 #[instrument(
     skip_all,
@@ -429,8 +590,9 @@ pub fn process_single_file_cached<'a>(
     sharepoint_ctx: Option<&'a SharepointContext<'a>>,
 ) -> std::pin::Pin<
     Box<
-        dyn std::future::Future<Output = Result<Option<FileContentsForGeneration>, Report>>
-            + Send
+        dyn std::future::Future<
+                Output = Result<Result<FileContentsForGeneration, UnprocessableFile>, Report>,
+            > + Send
             + 'a,
     >,
 > {
@@ -496,20 +658,26 @@ pub fn process_single_file_cached<'a>(
                     "Successfully processed file"
                 );
 
-                Ok(Some(file_contents))
+                Ok(Ok(file_contents))
             }
             Err(err) => {
                 tracing::warn!(
                     file_id = %file_id,
                     filename = %file_upload.filename,
                     error = %err,
-                    "Failed to process file - returning None"
+                    "Failed to process file - returning it as unprocessable"
                 );
                 span.record("error", true);
 
-                // On error, return None instead of placeholder
-                // Caller decides how to handle missing files
-                Ok(None)
+                // Missing objects and permissions end up here as well, which both mean that the
+                // file can't be read from the storage for this user.
+                // Caller decides how to handle unprocessable files
+                Ok(Err(UnprocessableFile {
+                    id: *file_id,
+                    filename: file_upload.filename,
+                    error: file_processing_error(&err)
+                        .unwrap_or(FileProcessingError::StorageUnavailable),
+                }))
             }
         }
     })
@@ -517,6 +685,7 @@ pub fn process_single_file_cached<'a>(
 
 /// Process multiple files in parallel with caching.
 ///
+/// Returns the contents of the processed files, and the files that could not be processed.
 /// This function is boxed to reduce stack usage, as it has a deep async call chain.
 #[instrument(
     skip_all,
@@ -535,8 +704,9 @@ pub fn process_files_parallel_cached<'a>(
     sharepoint_ctx: Option<SharepointContext<'a>>,
 ) -> std::pin::Pin<
     Box<
-        dyn std::future::Future<Output = Result<Vec<FileContentsForGeneration>, Report>>
-            + Send
+        dyn std::future::Future<
+                Output = Result<(Vec<FileContentsForGeneration>, Vec<UnprocessableFile>), Report>,
+            > + Send
             + 'a,
     >,
 > {
@@ -561,19 +731,17 @@ pub fn process_files_parallel_cached<'a>(
             }
         });
 
-        let results: Vec<Result<Option<FileContentsForGeneration>, Report>> =
+        let results: Vec<Result<Result<FileContentsForGeneration, UnprocessableFile>, Report>> =
             futures::future::join_all(futures).await;
 
         // Collect all results (including files that had parsing errors)
         let mut converted_files = vec![];
+        let mut unprocessable_files = vec![];
 
         for result in results {
             match result {
-                Ok(Some(file_contents)) => converted_files.push(file_contents),
-                Ok(None) => {
-                    // This should not happen anymore, but keep for safety
-                    tracing::warn!("Unexpected None result from file processing");
-                }
+                Ok(Ok(file_contents)) => converted_files.push(file_contents),
+                Ok(Err(unprocessable)) => unprocessable_files.push(unprocessable),
                 Err(err) => {
                     span.record("successful_files", converted_files.len());
                     span.record("had_error", true);
@@ -584,21 +752,98 @@ pub fn process_files_parallel_cached<'a>(
         }
 
         span.record("successful_files", converted_files.len());
+        span.record("failed_files", unprocessable_files.len());
 
         tracing::debug!(
             num_files = file_ids.len(),
             processed = converted_files.len(),
+            unprocessable = unprocessable_files.len(),
             "Completed parallel file processing"
         );
 
-        Ok(converted_files)
+        Ok((converted_files, unprocessable_files))
     })
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{effective_text_mime_type, parse_text_file_bytes};
-    use crate::services::file_processor::XbergProcessor;
+    use super::{
+        effective_text_mime_type, parse_text_file_bytes, previous_processing_failure,
+        remember_processing_failure, storage_unavailable,
+    };
+    use crate::services::file_parsing::ParseLimits;
+    use crate::services::file_processor::{
+        FileProcessingError, XbergProcessor, file_processing_error,
+    };
+    use crate::services::file_storage::{FileStorageError, is_not_found_error};
+    use crate::state::{FailedFileContents, FileCacheKey};
+    use eyre::Report;
+    use moka::future::Cache;
+    use sea_orm::prelude::Uuid;
+
+    fn cache_key(etag: Option<&str>) -> FileCacheKey {
+        FileCacheKey {
+            file_id: Uuid::nil(),
+            etag: etag.map(String::from),
+        }
+    }
+
+    #[tokio::test]
+    async fn deterministic_processing_failures_are_remembered() {
+        let cache: Cache<Uuid, FailedFileContents> = Cache::new(10);
+        let key = cache_key(Some("v1"));
+
+        remember_processing_failure(
+            &cache,
+            &key,
+            &Report::new(FileProcessingError::PasswordProtected),
+        )
+        .await;
+
+        assert_eq!(
+            previous_processing_failure(&cache, &key).await,
+            Some(FileProcessingError::PasswordProtected)
+        );
+        // A new version of the file is parsed again
+        assert_eq!(
+            previous_processing_failure(&cache, &cache_key(Some("v2"))).await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn timeouts_are_not_remembered() {
+        let cache: Cache<Uuid, FailedFileContents> = Cache::new(10);
+        let key = cache_key(None);
+
+        remember_processing_failure(&cache, &key, &Report::new(FileProcessingError::Timeout)).await;
+        remember_processing_failure(
+            &cache,
+            &key,
+            &Report::new(FileProcessingError::StorageUnavailable),
+        )
+        .await;
+        remember_processing_failure(&cache, &key, &eyre::eyre!("unclassified error")).await;
+
+        assert_eq!(previous_processing_failure(&cache, &key).await, None);
+    }
+
+    #[test]
+    fn storage_failures_are_reported_as_storage_unavailable() {
+        let err = storage_unavailable(eyre::eyre!("connection reset by peer"));
+        assert_eq!(
+            file_processing_error(&err),
+            Some(FileProcessingError::StorageUnavailable)
+        );
+        assert!(err.to_string().contains("connection reset by peer"));
+
+        // Missing objects are handled by the callers
+        let err = storage_unavailable(Report::new(FileStorageError::NotFound {
+            path: "missing.pdf".to_string(),
+        }));
+        assert!(is_not_found_error(&err));
+        assert_eq!(file_processing_error(&err), None);
+    }
 
     #[test]
     fn effective_text_mime_type_forces_eml_to_message_rfc822() {
@@ -671,9 +916,15 @@ Content-Transfer-Encoding: 7bit
 "#
         .to_vec();
 
-        let extracted = parse_text_file_bytes(&XbergProcessor, eml_bytes, "digest.eml", None)
-            .await
-            .expect("expected .eml to parse through the email extractor");
+        let extracted = parse_text_file_bytes(
+            &XbergProcessor,
+            eml_bytes,
+            "digest.eml",
+            None,
+            ParseLimits::default(),
+        )
+        .await
+        .expect("expected .eml to parse through the email extractor");
 
         assert!(extracted.contains("Persisted EML MIME fallback"));
         assert!(extracted.contains("sender@example.com"));
@@ -1,9 +1,12 @@
 use async_trait::async_trait;
 use base64::{Engine as _, engine::general_purpose};
-use eyre::{Context, Report};
+use eyre::Report;
+use serde::Serialize;
+use std::fmt;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::Instrument;
+use utoipa::ToSchema;
 use xberg::detect_mime_type_from_bytes;
 
 mod calendar_vcard;
@@ -89,6 +92,152 @@ struct EmailTnefStats {
     decoded_message_bytes: usize,
 }
 
+/// Why the contents of a file could not be extracted. Wrapped in the `Report` returned by file
+/// processing, so callers can tell the user what went wrong and cache deterministic failures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileProcessingError {
+    /// The format of the file is not supported.
+    Unsupported,
+    /// The file is encrypted and can't be read without its password.
+    PasswordProtected,
+    /// The file is damaged or truncated.
+    Corrupt,
+    /// The file is larger than the limit (in bytes) for extracting its contents.
+    TooLarge { limit: u64 },
+    /// Extracting the contents took longer than allowed.
+    Timeout,
+    /// The file could not be read from the storage.
+    StorageUnavailable,
+    /// The parser failed in an unexpected way.
+    ParserBug(String),
+}
+
+/// Kind of a [`FileProcessingError`], as exposed in the API and in metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileProcessingErrorKind {
+    Unsupported,
+    PasswordProtected,
+    Corrupt,
+    TooLarge,
+    Timeout,
+    StorageUnavailable,
+    ParserBug,
+}
+
+impl FileProcessingErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileProcessingErrorKind::Unsupported => "unsupported",
+            FileProcessingErrorKind::PasswordProtected => "password_protected",
+            FileProcessingErrorKind::Corrupt => "corrupt",
+            FileProcessingErrorKind::TooLarge => "too_large",
+            FileProcessingErrorKind::Timeout => "timeout",
+            FileProcessingErrorKind::StorageUnavailable => "storage_unavailable",
+            FileProcessingErrorKind::ParserBug => "parser_bug",
+        }
+    }
+}
+
+impl FileProcessingError {
+    pub fn kind(&self) -> FileProcessingErrorKind {
+        match self {
+            FileProcessingError::Unsupported => FileProcessingErrorKind::Unsupported,
+            FileProcessingError::PasswordProtected => FileProcessingErrorKind::PasswordProtected,
+            FileProcessingError::Corrupt => FileProcessingErrorKind::Corrupt,
+            FileProcessingError::TooLarge { .. } => FileProcessingErrorKind::TooLarge,
+            FileProcessingError::Timeout => FileProcessingErrorKind::Timeout,
+            FileProcessingError::StorageUnavailable => FileProcessingErrorKind::StorageUnavailable,
+            FileProcessingError::ParserBug(_) => FileProcessingErrorKind::ParserBug,
+        }
+    }
+
+    /// Whether processing the same file again fails the same way. Only those failures may be
+    /// cached, as the others can go away on a retry.
+    pub fn is_deterministic(&self) -> bool {
+        match self {
+            FileProcessingError::Unsupported
+            | FileProcessingError::PasswordProtected
+            | FileProcessingError::Corrupt
+            | FileProcessingError::TooLarge { .. } => true,
+            FileProcessingError::Timeout
+            | FileProcessingError::StorageUnavailable
+            | FileProcessingError::ParserBug(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for FileProcessingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileProcessingError::Unsupported => write!(f, "Unsupported file format"),
+            FileProcessingError::PasswordProtected => write!(f, "File is password-protected"),
+            FileProcessingError::Corrupt => write!(f, "File is corrupt"),
+            FileProcessingError::TooLarge { limit } => {
+                write!(f, "File exceeds the size limit of {} bytes", limit)
+            }
+            FileProcessingError::Timeout => write!(f, "File processing timed out"),
+            FileProcessingError::StorageUnavailable => {
+                write!(f, "File could not be read from the storage")
+            }
+            FileProcessingError::ParserBug(detail) => {
+                write!(f, "File processing failed unexpectedly: {}", detail)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FileProcessingError {}
+
+/// The processing error a report was caused by, if any.
+pub fn file_processing_error(error: &Report) -> Option<FileProcessingError> {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<FileProcessingError>())
+        .cloned()
+}
+
+/// Classify an error of xberg. xberg reports most failures of a parser with a message only, so
+/// everything but unsupported formats is told apart by it.
+fn classify_xberg_error(error: &xberg::XbergError) -> FileProcessingError {
+    if matches!(error, xberg::XbergError::UnsupportedFormat(_)) {
+        return FileProcessingError::Unsupported;
+    }
+    let message = error.to_string();
+    let lowercase = message.to_lowercase();
+    if lowercase.contains("password") || lowercase.contains("encrypt") {
+        FileProcessingError::PasswordProtected
+    } else if lowercase.contains("unsupported") {
+        FileProcessingError::Unsupported
+    } else if [
+        "corrupt",
+        "invalid",
+        "malformed",
+        "truncated",
+        "unexpected end",
+        "eof",
+        "zip",
+        "pars",
+    ]
+    .iter()
+    .any(|needle| lowercase.contains(needle))
+    {
+        FileProcessingError::Corrupt
+    } else {
+        FileProcessingError::ParserBug(message)
+    }
+}
+
+fn xberg_extraction_error(error: xberg::XbergError) -> Report {
+    let classified = classify_xberg_error(&error);
+    tracing::warn!(
+        error = %error,
+        classified = classified.kind().as_str(),
+        "Xberg extraction failed"
+    );
+    Report::new(classified)
+}
+
 /// Trait for file processors that extract text content from file bytes
 #[async_trait]
 pub trait FileProcessor: Send + Sync {
@@ -1539,7 +1688,10 @@ impl FileProcessor for XbergProcessor {
                     normalize_xberg_mime(mime_type)
                 } else {
                     let timer = StepTimer::start("file_processor.detect_mime");
-                    let detected = detect_mime_type(&file_bytes)?;
+                    let detected = detect_mime_type(&file_bytes).map_err(|err| {
+                        tracing::warn!(error = %err, "Failed to detect MIME type");
+                        Report::new(FileProcessingError::Unsupported)
+                    })?;
                     timer.finish();
                     normalize_xberg_mime(&detected)
                 };
@@ -1553,8 +1705,9 @@ impl FileProcessor for XbergProcessor {
                 }
 
                 let timer = StepTimer::start("file_processor.register_extractors");
-                register_calendar_vcard_extractor()
-                    .wrap_err("Failed to register calendar/vcard extractor")?;
+                register_calendar_vcard_extractor().map_err(|err| {
+                    Report::new(FileProcessingError::ParserBug(format!("{:#}", err)))
+                })?;
                 timer.finish();
 
                 // xberg rejects bare `multipart/*` types ("Unsupported format"), but those are
@@ -1702,7 +1855,7 @@ impl FileProcessor for XbergProcessor {
                         );
                         let retry_started = Instant::now();
                         let retry_result = extract_xberg_bytes_sync(&file_bytes, &mime_type, &config)
-                            .wrap_err("Xberg extraction failed")?;
+                            .map_err(xberg_extraction_error)?;
                         let retry_elapsed = retry_started.elapsed();
                         tracing::trace!(
                             elapsed_ms = retry_elapsed.as_millis(),
@@ -1723,7 +1876,7 @@ impl FileProcessor for XbergProcessor {
                             error = %err,
                             "Normal xberg extraction failed"
                         );
-                        return Err(err).wrap_err("Xberg extraction failed");
+                        return Err(xberg_extraction_error(err));
                     }
                 };
                 let elapsed = timer.finish();
@@ -1841,7 +1994,13 @@ impl FileProcessor for XbergProcessor {
                 Ok(content)
             })
             .in_current_span()
-            .await??,
+            .await
+            .map_err(|err| {
+                Report::new(FileProcessingError::ParserBug(format!(
+                    "File processing task failed: {}",
+                    err
+                )))
+            })??,
         )
     }
 }
@@ -3015,4 +3174,77 @@ Content-Type: text/html; charset=utf-8
             "pasted base64 blob leaked:\n{extracted}"
         );
     }
+
+    #[tokio::test]
+    async fn test_xberg_reports_password_protected_pdf() {
+        let pdf_bytes = read_test_fixture("password_protected.pdf");
+
+        let err = XbergProcessor
+            .parse_file(pdf_bytes, Some("application/pdf"))
+            .await
+            .expect_err("encrypted PDF should not be extracted");
+
+        assert_eq!(
+            file_processing_error(&err),
+            Some(FileProcessingError::PasswordProtected)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_xberg_reports_truncated_docx_as_corrupt() {
+        let docx_bytes = read_test_fixture("truncated.docx");
+
+        let err = XbergProcessor
+            .parse_file(docx_bytes, Some(DOCX_MIME_TYPE))
+            .await
+            .expect_err("truncated DOCX should not be extracted");
+
+        assert_eq!(
+            file_processing_error(&err),
+            Some(FileProcessingError::Corrupt)
+        );
+    }
+
+    #[test]
+    fn test_classify_xberg_error() {
+        assert_eq!(
+            classify_xberg_error(&xberg::XbergError::UnsupportedFormat(
+                "application/x-unknown".to_string()
+            )),
+            FileProcessingError::Unsupported
+        );
+        assert_eq!(
+            classify_xberg_error(&xberg::XbergError::Other(
+                "xberg extraction returned no document result".to_string()
+            )),
+            FileProcessingError::ParserBug(
+                xberg::XbergError::Other(
+                    "xberg extraction returned no document result".to_string()
+                )
+                .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_only_deterministic_processing_errors_are_cacheable() {
+        assert!(FileProcessingError::Unsupported.is_deterministic());
+        assert!(FileProcessingError::PasswordProtected.is_deterministic());
+        assert!(FileProcessingError::Corrupt.is_deterministic());
+        assert!(FileProcessingError::TooLarge { limit: 1024 }.is_deterministic());
+        assert!(!FileProcessingError::Timeout.is_deterministic());
+        assert!(!FileProcessingError::StorageUnavailable.is_deterministic());
+        assert!(!FileProcessingError::ParserBug("panic".to_string()).is_deterministic());
+    }
+
+    #[test]
+    fn test_file_processing_error_is_found_in_wrapped_report() {
+        let err = Report::new(FileProcessingError::Timeout).wrap_err("Failed to parse file");
+
+        assert_eq!(
+            file_processing_error(&err),
+            Some(FileProcessingError::Timeout)
+        );
+        assert_eq!(file_processing_error(&eyre::eyre!("other error")), None);
+    }
 }
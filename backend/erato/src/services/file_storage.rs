@@ -11,6 +11,7 @@ use hmac::{Hmac, Mac};
 use opendal::{Operator, Reader, Writer};
 use percent_encoding::{AsciiSet, CONTROLS, NON_ALPHANUMERIC, utf8_percent_encode};
 use sha2::Sha256;
+use std::fmt;
 use std::time::Duration;
 use tracing::instrument;
 use url::Url;
@@ -41,6 +42,46 @@ const PATH_ENCODE_SET: AsciiSet = NON_ALPHANUMERIC
     .remove(b')');
 const AZBLOB_SERVICE_SAS_VERSION: &str = "2023-11-03";
 
+/// Errors of the file storage that callers handle distinctly from other (possibly transient)
+/// failures. Mapped from the errors of each provider, and wrapped in the returned `Report`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileStorageError {
+    /// The object doesn't exist in the storage (anymore).
+    NotFound { path: String },
+}
+
+impl fmt::Display for FileStorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileStorageError::NotFound { path } => {
+                write!(f, "Object not found in file storage: {}", path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FileStorageError {}
+
+/// Whether the error was caused by a missing object in the storage.
+pub fn is_not_found_error(error: &Report) -> bool {
+    error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<FileStorageError>(),
+            Some(FileStorageError::NotFound { .. })
+        )
+    })
+}
+
+fn map_opendal_error(path: &str, error: opendal::Error) -> Report {
+    if error.kind() == opendal::ErrorKind::NotFound {
+        Report::new(FileStorageError::NotFound {
+            path: path.to_string(),
+        })
+    } else {
+        error.into()
+    }
+}
+
 /// File storage backend supporting multiple providers.
 ///
 /// - `OpenDal`: Uses OpenDAL for S3/AzBlob storage (static credentials at construction time)
@@ -327,6 +368,16 @@ impl FileStorage {
             )),
         }
     }
+
+    /// Delete a file (only supported for OpenDAL storage). Deleting a missing file succeeds.
+    pub async fn delete_file(&self, path: &str) -> Result<(), Report> {
+        match self {
+            Self::OpenDal(storage) => storage.delete_file(path).await,
+            Self::Sharepoint(_) => Err(eyre::eyre!(
+                "File deletion via Sharepoint storage is not supported"
+            )),
+        }
+    }
 }
 
 impl OpenDalStorage {
@@ -406,14 +457,20 @@ impl OpenDalStorage {
 
     /// Read a file from the storage and return a Reader
     pub async fn get_file_reader(&self, path: &str) -> Result<Reader, Report> {
-        Ok(self.opendal_operator.reader(path).await?)
+        self.opendal_operator
+            .reader(path)
+            .await
+            .map_err(|err| map_opendal_error(path, err))
     }
 
     /// Read a complete file from storage and return its contents as a byte array
     pub async fn read_file_to_bytes(&self, path: &str) -> Result<Vec<u8>, Report> {
         let reader = self.get_file_reader(path).await?;
         let mut buffer = Vec::new();
-        reader.read_into(&mut buffer, ..).await?;
+        reader
+            .read_into(&mut buffer, ..)
+            .await
+            .map_err(|err| map_opendal_error(path, err))?;
         Ok(buffer)
     }
 
@@ -421,13 +478,22 @@ impl OpenDalStorage {
         Ok(self
             .opendal_operator
             .stat(path)
-            .await?
+            .await
+            .map_err(|err| map_opendal_error(path, err))?
             .content_type()
             .map(ToOwned::to_owned))
     }
 
+    pub async fn delete_file(&self, path: &str) -> Result<(), Report> {
+        Ok(self.opendal_operator.delete(path).await?)
+    }
+
     pub async fn stat_object(&self, path: &str) -> Result<FileStorageObjectMetadata, Report> {
-        let metadata = self.opendal_operator.stat(path).await?;
+        let metadata = self
+            .opendal_operator
+            .stat(path)
+            .await
+            .map_err(|err| map_opendal_error(path, err))?;
         Ok(FileStorageObjectMetadata {
             size_bytes: metadata.content_length(),
             content_type: metadata.content_type().map(ToOwned::to_owned),
@@ -871,6 +937,12 @@ impl SharepointStorage {
             .await
             .wrap_err("Failed to download file from Sharepoint")?;
 
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(FileStorageError::NotFound {
+                path: path.to_string(),
+            }
+            .into());
+        }
         if !response.status().is_success() {
             return Err(eyre::eyre!(
                 "Failed to download file from Sharepoint: HTTP {}",
@@ -926,6 +998,13 @@ impl SharepointStorage {
             .await
             .wrap_err("Failed to get drive item from MS Graph API")?;
 
+        if response.status().as_u16() == 404 {
+            return Err(FileStorageError::NotFound {
+                path: format!("{} | {}", drive_id, item_id),
+            }
+            .into());
+        }
+
         let item: serde_json::Value = response
             .json()
             .await
@@ -1003,6 +1082,8 @@ mod tests {
     use super::build_azblob_service_sas_url;
     use super::build_content_disposition;
     use super::build_presign_content_disposition;
+    use super::is_not_found_error;
+    use super::map_opendal_error;
     use super::preview_content_type_for_filename;
     use crate::config::StorageProviderAzBlobConfig;
     use base64::{Engine as _, engine::general_purpose::STANDARD};
@@ -1171,4 +1252,22 @@ mod tests {
             Some("message/rfc822")
         );
     }
+
+    #[test]
+    fn map_opendal_error_detects_missing_objects() {
+        let not_found = map_opendal_error(
+            "files/missing.txt",
+            opendal::Error::new(opendal::ErrorKind::NotFound, "object not found"),
+        );
+        assert!(is_not_found_error(&not_found));
+        assert!(is_not_found_error(
+            &not_found.wrap_err("Failed to read file contents")
+        ));
+
+        let denied = map_opendal_error(
+            "files/denied.txt",
+            opendal::Error::new(opendal::ErrorKind::PermissionDenied, "access denied"),
+        );
+        assert!(!is_not_found_error(&denied));
+    }
 }
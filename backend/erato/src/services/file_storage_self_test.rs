@@ -0,0 +1,300 @@
+//! Self-test of the file storage providers.
+//!
+//! Misconfigured credentials or endpoints of a storage provider otherwise only surface when the
+//! first file is uploaded. The self-test goes through the operations uploads and downloads rely
+//! on: writing a small probe object, generating a pre-signed URL for it, fetching it via that URL
+//! and deleting it again. It runs for every provider at startup, and on demand via the admin API.
+//!
+//! Sharepoint can't be verified, as it has no credentials of its own and only works with the
+//! access token of a user.
+
+use crate::services::file_storage::FileStorage;
+use crate::state::AppState;
+use chrono::{DateTime, Utc};
+use eyre::{Report, WrapErr, eyre};
+use sea_orm::prelude::Uuid;
+use serde::Serialize;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// Prefix of the paths of the probe objects.
+const PROBE_PATH_PREFIX: &str = "erato-self-test";
+const PROBE_CONTENT: &[u8] = b"erato file storage self-test";
+const PROBE_CONTENT_TYPE: &str = "text/plain";
+/// Maximum duration of each step of the self-test.
+const STEP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Expiry of the pre-signed URL of the probe object.
+const PRESIGNED_URL_EXPIRY: Duration = Duration::from_secs(60);
+
+/// Overall result of the self-test of a file storage provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStorageSelfTestStatus {
+    /// All steps succeeded
+    Healthy,
+    /// At least one step failed
+    Unhealthy,
+    /// The provider can't be tested without the access token of a user
+    Unverifiable,
+}
+
+/// A step of the self-test of a file storage provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStorageSelfTestStepKind {
+    /// Writing the probe object
+    Write,
+    /// Generating a pre-signed URL for the probe object
+    Presign,
+    /// Fetching the probe object via the pre-signed URL
+    Fetch,
+    /// Deleting the probe object
+    Delete,
+}
+
+/// The outcome of a step of the self-test
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct FileStorageSelfTestStep {
+    pub step: FileStorageSelfTestStepKind,
+    pub succeeded: bool,
+    /// How long the step took, in milliseconds
+    pub latency_ms: u64,
+    /// The error of a failed step
+    pub error: Option<String>,
+}
+
+/// Result of the self-test of a file storage provider
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct FileStorageSelfTestResult {
+    pub provider_id: String,
+    pub status: FileStorageSelfTestStatus,
+    pub tested_at: DateTime<Utc>,
+    /// The steps that were run, in order. Steps after a failed one are skipped, except for
+    /// deleting a probe object that was written.
+    pub steps: Vec<FileStorageSelfTestStep>,
+    /// Why the provider could not be tested
+    pub detail: Option<String>,
+}
+
+impl FileStorageSelfTestResult {
+    pub fn is_healthy(&self) -> bool {
+        self.status == FileStorageSelfTestStatus::Healthy
+    }
+
+    /// The first failed step, if any.
+    pub fn failed_step(&self) -> Option<&FileStorageSelfTestStep> {
+        self.steps.iter().find(|step| !step.succeeded)
+    }
+}
+
+impl FileStorage {
+    /// Run the self-test of the provider, and report its result in the
+    /// `erato_file_storage_healthy` metric.
+    pub async fn self_test(&self, provider_id: &str) -> FileStorageSelfTestResult {
+        let tested_at = Utc::now();
+        let result = match self {
+            Self::OpenDal(_) => {
+                let steps = self.run_self_test_steps().await;
+                let status = if steps.iter().all(|step| step.succeeded) {
+                    FileStorageSelfTestStatus::Healthy
+                } else {
+                    FileStorageSelfTestStatus::Unhealthy
+                };
+                FileStorageSelfTestResult {
+                    provider_id: provider_id.to_string(),
+                    status,
+                    tested_at,
+                    steps,
+                    detail: None,
+                }
+            }
+            Self::Sharepoint(_) => FileStorageSelfTestResult {
+                provider_id: provider_id.to_string(),
+                status: FileStorageSelfTestStatus::Unverifiable,
+                tested_at,
+                steps: vec![],
+                detail: Some("user-token-only, unverifiable".to_string()),
+            },
+        };
+
+        if result.status != FileStorageSelfTestStatus::Unverifiable {
+            crate::metrics::report_file_storage_self_test(provider_id, result.is_healthy());
+        }
+        result
+    }
+
+    async fn run_self_test_steps(&self) -> Vec<FileStorageSelfTestStep> {
+        let path = format!("{}/{}", PROBE_PATH_PREFIX, Uuid::new_v4());
+        let mut steps = Vec::new();
+
+        let write = run_step(FileStorageSelfTestStepKind::Write, self.write_probe(&path)).await;
+        let written = write.succeeded;
+        steps.push(write);
+
+        if written {
+            let mut presigned_url = None;
+            let presign = run_step(FileStorageSelfTestStepKind::Presign, async {
+                presigned_url = Some(
+                    self.generate_presigned_download_url(&path, Some(PRESIGNED_URL_EXPIRY), None)
+                        .await?,
+                );
+                Ok(())
+            })
+            .await;
+            steps.push(presign);
+
+            if let Some(url) = presigned_url {
+                steps.push(run_step(FileStorageSelfTestStepKind::Fetch, fetch_probe(&url)).await);
+            }
+
+            steps.push(
+                run_step(FileStorageSelfTestStepKind::Delete, self.delete_file(&path)).await,
+            );
+        }
+
+        steps
+    }
+
+    async fn write_probe(&self, path: &str) -> Result<(), Report> {
+        let mut writer = self
+            .upload_file_writer(path, Some(PROBE_CONTENT_TYPE))
+            .await?;
+        writer
+            .write(PROBE_CONTENT)
+            .await
+            .wrap_err("Failed to write the probe object")?;
+        writer
+            .close()
+            .await
+            .wrap_err("Failed to close the writer of the probe object")?;
+        Ok(())
+    }
+}
+
+/// Fetch the probe object via its pre-signed URL, and check its content.
+async fn fetch_probe(url: &str) -> Result<(), Report> {
+    let response = reqwest::Client::builder()
+        .timeout(STEP_TIMEOUT)
+        .build()?
+        .get(url)
+        .send()
+        .await
+        .wrap_err("Failed to fetch the probe object")?
+        .error_for_status()
+        .wrap_err("Failed to fetch the probe object")?;
+    let content = response
+        .bytes()
+        .await
+        .wrap_err("Failed to read the probe object")?;
+    if content.as_ref() != PROBE_CONTENT {
+        return Err(eyre!(
+            "The fetched probe object has unexpected content ({} bytes)",
+            content.len()
+        ));
+    }
+    Ok(())
+}
+
+async fn run_step(
+    step: FileStorageSelfTestStepKind,
+    future: impl Future<Output = Result<(), Report>>,
+) -> FileStorageSelfTestStep {
+    let started_at = Instant::now();
+    let result = match tokio::time::timeout(STEP_TIMEOUT, future).await {
+        Ok(result) => result,
+        Err(_) => Err(eyre!("Timed out after {}s", STEP_TIMEOUT.as_secs())),
+    };
+    FileStorageSelfTestStep {
+        step,
+        succeeded: result.is_ok(),
+        latency_ms: started_at.elapsed().as_millis() as u64,
+        error: result.err().map(|err| format!("{:#}", err)),
+    }
+}
+
+/// Run the self-test of every configured file storage provider, in order of their IDs.
+///
+/// Failures are logged as warnings, and returned as an error for providers that are configured as
+/// `required`.
+pub async fn run_file_storage_self_tests(
+    app_state: &AppState,
+) -> Result<Vec<FileStorageSelfTestResult>, Report> {
+    let mut provider_ids: Vec<&String> = app_state.file_storage_providers.keys().collect();
+    provider_ids.sort();
+
+    let mut results = Vec::with_capacity(provider_ids.len());
+    let mut failed_required_providers = Vec::new();
+    for provider_id in provider_ids {
+        let result = app_state.file_storage_providers[provider_id]
+            .self_test(provider_id)
+            .await;
+        match result.status {
+            FileStorageSelfTestStatus::Healthy => {
+                tracing::info!(provider_id = %provider_id, "File storage self-test succeeded");
+            }
+            FileStorageSelfTestStatus::Unverifiable => {
+                tracing::info!(
+                    provider_id = %provider_id,
+                    detail = result.detail.as_deref().unwrap_or_default(),
+                    "File storage self-test skipped"
+                );
+            }
+            FileStorageSelfTestStatus::Unhealthy => {
+                let failed_step = result.failed_step();
+                tracing::warn!(
+                    provider_id = %provider_id,
+                    step = ?failed_step.map(|step| step.step),
+                    error = failed_step.and_then(|step| step.error.as_deref()).unwrap_or_default(),
+                    "File storage self-test failed"
+                );
+                let required = app_state
+                    .config
+                    .file_storage_providers
+                    .get(provider_id)
+                    .is_some_and(|config| config.required);
+                if required {
+                    failed_required_providers.push(provider_id.clone());
+                }
+            }
+        }
+        results.push(result);
+    }
+
+    if !failed_required_providers.is_empty() {
+        return Err(eyre!(
+            "Self-test of required file storage providers failed: {}",
+            failed_required_providers.join(", ")
+        ));
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sharepoint_is_unverifiable() {
+        let result = FileStorage::sharepoint().self_test("sharepoint").await;
+
+        assert_eq!(result.status, FileStorageSelfTestStatus::Unverifiable);
+        assert!(result.steps.is_empty());
+        assert_eq!(
+            result.detail.as_deref(),
+            Some("user-token-only, unverifiable")
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_step_records_error_and_latency() {
+        let step = run_step(FileStorageSelfTestStepKind::Write, async {
+            Err::<(), _>(eyre!("Access denied"))
+        })
+        .await;
+
+        assert_eq!(step.step, FileStorageSelfTestStepKind::Write);
+        assert!(!step.succeeded);
+        assert_eq!(step.error.as_deref(), Some("Access denied"));
+    }
+}
@@ -0,0 +1,358 @@
+//! Short synopses of attached files.
+//!
+//! When several large files are attached, the model has a hard time telling them apart. A
+//! synopsis (detected language, page or sheet count and the first few sentences) is placed in
+//! front of the contents of each file, and turns with multiple files additionally get a manifest
+//! listing all files with their synopses before the file contents.
+//!
+//! Synopses are computed from the parsed text of a file, once per file, and cached together with
+//! it (see `file_processing_cached`).
+
+use crate::services::language_detection::{iso_639_1_code, truncate_to_char_boundary};
+use sea_orm::prelude::Uuid;
+
+/// Prefix of the manifest message (see [`format_file_manifest`]).
+pub const FILE_MANIFEST_PREFIX: &str = "Attached files:\n";
+
+/// Number of bytes of the file text the language is detected from.
+const LANGUAGE_DETECTION_MAX_BYTES: usize = 4096;
+/// Sentences longer than this are cut off in the synopsis.
+const MAX_SENTENCE_CHARS: usize = 300;
+
+const PAGE_MARKER_START: &str = "<page number=\"";
+const ESCAPED_PAGE_MARKER_START: &str = "\\<page number=\"";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileSynopsis {
+    /// ISO 639-1 code of the language the file is written in, if it could be detected reliably
+    pub language: Option<String>,
+    /// Number of pages, for documents with page markers
+    pub page_count: Option<usize>,
+    /// Number of sheets, for spreadsheets
+    pub sheet_count: Option<usize>,
+    /// The first sentences of the file
+    pub excerpt: String,
+}
+
+impl FileSynopsis {
+    /// Approximate size in bytes, used to weigh cache entries.
+    pub fn size(&self) -> usize {
+        self.excerpt.len() + self.language.as_ref().map_or(0, String::len)
+    }
+
+    /// Render the synopsis as a single paragraph, e.g.
+    /// `(language: en, 12 pages) The report covers the third quarter.`
+    pub fn to_paragraph(&self) -> String {
+        let mut details = Vec::new();
+        if let Some(language) = &self.language {
+            details.push(format!("language: {language}"));
+        }
+        if let Some(page_count) = self.page_count {
+            details.push(pluralize(page_count, "page"));
+        }
+        if let Some(sheet_count) = self.sheet_count {
+            details.push(pluralize(sheet_count, "sheet"));
+        }
+
+        match (details.is_empty(), self.excerpt.is_empty()) {
+            (true, _) => self.excerpt.clone(),
+            (false, true) => format!("({})", details.join(", ")),
+            (false, false) => format!("({}) {}", details.join(", "), self.excerpt),
+        }
+    }
+}
+
+fn pluralize(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+/// Build the synopsis of the parsed text of a file.
+///
+/// `max_sentences` limits the number of sentences in the excerpt; `0` leaves the excerpt empty.
+pub fn build_file_synopsis(
+    text: &str,
+    filename: &str,
+    mime_type: Option<&str>,
+    max_sentences: usize,
+) -> FileSynopsis {
+    let (page_count, sheet_count) = if is_spreadsheet(filename, mime_type) {
+        // Sheets are extracted as markdown sections, each starting with a `## <sheet name>` heading
+        let sheet_count = text.lines().filter(|line| line.starts_with("## ")).count();
+        (None, Some(sheet_count.max(1)))
+    } else {
+        (count_pages(text), None)
+    };
+
+    let prose = text
+        .lines()
+        .map(strip_page_markers)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    FileSynopsis {
+        language: detect_language(&prose),
+        page_count,
+        sheet_count,
+        excerpt: first_sentences(&prose, max_sentences).join(" "),
+    }
+}
+
+fn is_spreadsheet(filename: &str, mime_type: Option<&str>) -> bool {
+    let is_spreadsheet_mime_type = mime_type.is_some_and(|mime_type| {
+        matches!(
+            mime_type,
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+                | "application/vnd.ms-excel"
+                | "application/vnd.ms-excel.sheet.macroenabled.12"
+                | "application/vnd.oasis.opendocument.spreadsheet"
+        )
+    });
+    let is_spreadsheet_extension = filename.rsplit_once('.').is_some_and(|(_, extension)| {
+        matches!(
+            extension.to_ascii_lowercase().as_str(),
+            "xlsx" | "xls" | "xlsm" | "ods"
+        )
+    });
+    is_spreadsheet_mime_type || is_spreadsheet_extension
+}
+
+/// Highest page number of the `<page number="N">` markers inserted by the file processor.
+fn count_pages(text: &str) -> Option<usize> {
+    text.match_indices(PAGE_MARKER_START)
+        .filter_map(|(index, _)| {
+            let number = &text[index + PAGE_MARKER_START.len()..];
+            let end = number.find('"')?;
+            number[..end].parse::<usize>().ok()
+        })
+        .max()
+}
+
+fn strip_page_markers(line: &str) -> String {
+    let mut line = line.to_string();
+    for marker_start in [ESCAPED_PAGE_MARKER_START, PAGE_MARKER_START] {
+        while let Some(start) = line.find(marker_start) {
+            let Some(end) = line[start..].find('>') else {
+                break;
+            };
+            line.replace_range(start..start + end + 1, "");
+        }
+    }
+    line
+}
+
+fn detect_language(text: &str) -> Option<String> {
+    let info = whatlang::detect(truncate_to_char_boundary(
+        text,
+        LANGUAGE_DETECTION_MAX_BYTES,
+    ))?;
+    if !info.is_reliable() {
+        return None;
+    }
+    iso_639_1_code(info.lang()).map(ToString::to_string)
+}
+
+/// The first sentences of the prose of a markdown text.
+///
+/// Headings count as sentences of their own, while tables and code blocks are skipped.
+fn first_sentences(text: &str, max_sentences: usize) -> Vec<String> {
+    let mut sentences = Vec::new();
+    if max_sentences == 0 {
+        return sentences;
+    }
+
+    let mut paragraph = String::new();
+    let mut in_code_block = false;
+    for line in text.lines().chain(std::iter::once("")) {
+        let line = line.trim();
+        if line.starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        let is_heading = line.starts_with('#');
+        let is_paragraph_break = line.is_empty()
+            || is_heading
+            || line.starts_with('|')
+            || line.chars().all(|c| matches!(c, '-' | '=' | '*' | '_'));
+        if in_code_block || is_paragraph_break {
+            sentences.extend(split_sentences(&paragraph));
+            paragraph.clear();
+            if is_heading && !in_code_block {
+                sentences.extend(split_sentences(line.trim_start_matches('#')));
+            }
+        } else {
+            let line = line
+                .trim_start_matches(['>', '-', '*', '+'])
+                .replace("**", "");
+            if !paragraph.is_empty() {
+                paragraph.push(' ');
+            }
+            paragraph.push_str(line.trim());
+        }
+        if sentences.len() >= max_sentences {
+            break;
+        }
+    }
+
+    sentences.truncate(max_sentences);
+    sentences
+}
+
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        current.push(c);
+        let is_sentence_end =
+            matches!(c, '.' | '!' | '?') && chars.peek().is_none_or(|next| next.is_whitespace());
+        if is_sentence_end {
+            push_sentence(&mut sentences, &current);
+            current.clear();
+        }
+    }
+    push_sentence(&mut sentences, &current);
+    sentences
+}
+
+fn push_sentence(sentences: &mut Vec<String>, sentence: &str) {
+    let sentence = sentence.trim();
+    if sentence.is_empty() {
+        return;
+    }
+    if sentence.chars().count() > MAX_SENTENCE_CHARS {
+        let truncated: String = sentence.chars().take(MAX_SENTENCE_CHARS).collect();
+        sentences.push(format!("{}…", truncated.trim_end()));
+    } else {
+        sentences.push(sentence.to_string());
+    }
+}
+
+/// Format the manifest listing all files attached in a turn, placed before their contents.
+pub fn format_file_manifest(files: &[(String, Uuid, Option<FileSynopsis>)]) -> String {
+    let mut manifest = String::from(FILE_MANIFEST_PREFIX);
+    for (index, (filename, file_id, synopsis)) in files.iter().enumerate() {
+        manifest.push_str(&format!(
+            "{}. {} (file_id: erato_file_id:{})",
+            index + 1,
+            filename,
+            file_id
+        ));
+        if let Some(synopsis) = synopsis {
+            let paragraph = synopsis.to_paragraph();
+            if !paragraph.is_empty() {
+                manifest.push_str(": ");
+                manifest.push_str(&paragraph);
+            }
+        }
+        manifest.push('\n');
+    }
+    manifest.push_str("The contents of each file follow below.");
+    manifest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT_FIXTURE: &str = "Quarterly Update\n\nRevenue grew by 12% in the third quarter. The growth was driven by the new enterprise plan! Did churn go down? It did, by two points.\nHiring will continue next year.";
+
+    const PDF_FIXTURE: &str = "<page number=\"1\">\n## Multi-Page Report\n\n\"A comprehensive and content-heavy report that includes text, images, and tables for thorough testing of pagination and complex layouts.\"\n\n<page number=\"2\">\n### Table of Contents\n\n| Section | Page |\n| --- | --- |\n| Introduction | 3 |\n\n<page number=\"3\">\n### Introduction\n\nThis section introduces the report and highlights the key objectives. The purpose of this report is to analyze data.";
+
+    const SPREADSHEET_FIXTURE: &str = "## Revenue\n\n| Quarter | Revenue |\n| --- | --- |\n| Q1 | 100 |\n\n## Headcount\n\n| Department | People |\n| --- | --- |\n| Engineering | 12 |\n\n## Notes\n\nFigures are preliminary and will be audited in January.";
+
+    #[test]
+    fn text_synopsis_contains_the_first_sentences() {
+        let synopsis = build_file_synopsis(TEXT_FIXTURE, "update.txt", Some("text/plain"), 3);
+        assert_eq!(
+            synopsis.excerpt,
+            "Quarterly Update Revenue grew by 12% in the third quarter. The growth was driven by the new enterprise plan!"
+        );
+        assert_eq!(synopsis.language.as_deref(), Some("en"));
+        assert_eq!(synopsis.page_count, None);
+        assert_eq!(synopsis.sheet_count, None);
+
+        let synopsis = build_file_synopsis(TEXT_FIXTURE, "update.txt", Some("text/plain"), 0);
+        assert_eq!(synopsis.excerpt, "");
+    }
+
+    #[test]
+    fn pdf_synopsis_counts_pages_and_skips_markers_and_tables() {
+        let synopsis = build_file_synopsis(PDF_FIXTURE, "report.pdf", Some("application/pdf"), 4);
+        assert_eq!(synopsis.page_count, Some(3));
+        assert_eq!(synopsis.sheet_count, None);
+        assert_eq!(synopsis.language.as_deref(), Some("en"));
+        assert_eq!(
+            synopsis.excerpt,
+            "Multi-Page Report \"A comprehensive and content-heavy report that includes text, images, and tables for thorough testing of pagination and complex layouts.\" Table of Contents Introduction"
+        );
+        assert!(
+            synopsis
+                .to_paragraph()
+                .starts_with("(language: en, 3 pages) Multi-Page Report")
+        );
+    }
+
+    #[test]
+    fn pdf_synopsis_handles_escaped_page_markers() {
+        let text = "\\<page number=\"1\"\\>\n\n## Acme Inc. Company Overview\n\n## Revenue Development \\<page number=\"2\"\\>";
+        let synopsis = build_file_synopsis(text, "overview.pptx", None, 3);
+        assert_eq!(synopsis.page_count, Some(2));
+        assert_eq!(
+            synopsis.excerpt,
+            "Acme Inc. Company Overview Revenue Development"
+        );
+    }
+
+    #[test]
+    fn spreadsheet_synopsis_counts_sheets() {
+        let synopsis = build_file_synopsis(
+            SPREADSHEET_FIXTURE,
+            "figures.xlsx",
+            Some("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+            3,
+        );
+        assert_eq!(synopsis.sheet_count, Some(3));
+        assert_eq!(synopsis.page_count, None);
+        assert_eq!(synopsis.excerpt, "Revenue Headcount Notes");
+
+        // The extension is enough if the MIME type is unknown
+        let synopsis = build_file_synopsis(SPREADSHEET_FIXTURE, "figures.ods", None, 3);
+        assert_eq!(synopsis.sheet_count, Some(3));
+    }
+
+    #[test]
+    fn long_sentences_are_truncated() {
+        let text = format!("{}.", "word ".repeat(100));
+        let synopsis = build_file_synopsis(&text, "long.txt", None, 1);
+        assert!(synopsis.excerpt.ends_with('…'));
+        assert_eq!(synopsis.excerpt.chars().count(), MAX_SENTENCE_CHARS);
+    }
+
+    #[test]
+    fn manifest_lists_all_files() {
+        let first_id = Uuid::nil();
+        let manifest = format_file_manifest(&[
+            (
+                "report.pdf".to_string(),
+                first_id,
+                Some(FileSynopsis {
+                    language: Some("de".to_string()),
+                    page_count: Some(1),
+                    sheet_count: None,
+                    excerpt: "Ein Bericht.".to_string(),
+                }),
+            ),
+            ("notes.txt".to_string(), first_id, None),
+        ]);
+        assert_eq!(
+            manifest,
+            format!(
+                "Attached files:\n1. report.pdf (file_id: erato_file_id:{first_id}): (language: de, 1 page) Ein Bericht.\n2. notes.txt (file_id: erato_file_id:{first_id})\nThe contents of each file follow below."
+            )
+        );
+    }
+}
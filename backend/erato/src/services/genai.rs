@@ -125,6 +125,14 @@ impl From<ContentPart> for GenAiMessageContent {
                 );
                 GenAiMessageContent::from_text(String::new())
             }
+            ContentPart::BlobPointer(pointer) => {
+                // This should never happen after resolve_file_pointers_in_generation_input
+                // Log error and fall back to the preview of the content
+                tracing::error!(
+                    "BlobPointer found during LLM conversion - should have been resolved"
+                );
+                GenAiMessageContent::from_text(pointer.preview)
+            }
         }
     }
 }
@@ -192,6 +200,7 @@ pub fn into_openai_request_parts(chat_req: &ChatRequest) -> Result<OpenAIRequest
 
     // -- Process the messages
     for msg in &chat_req.messages {
+        let message_start = messages.len();
         match msg.role {
             GenAiChatRole::System => {
                 if let Some(text) = msg.content.first_text() {
@@ -265,6 +274,16 @@ pub fn into_openai_request_parts(chat_req: &ChatRequest) -> Result<OpenAIRequest
                 }
             }
         }
+        // Cache hints change how the prompt is billed, so they are kept
+        if msg
+            .options
+            .as_ref()
+            .is_some_and(|options| options.cache_control.is_some())
+        {
+            for message in &mut messages[message_start..] {
+                message["cache_control"] = json!({ "type": "ephemeral" });
+            }
+        }
     }
 
     // -- Process the tools
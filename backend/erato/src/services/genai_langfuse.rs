@@ -172,6 +172,13 @@ pub fn convert_content_parts_to_json(content_parts: &[ContentPart]) -> Result<Js
                     "args": marker.args,
                 }));
             }
+            ContentPart::BlobPointer(pointer) => {
+                output_parts.push(json!({
+                    "type": "blob_pointer",
+                    "blob_path": pointer.blob_path,
+                    "size": pointer.size
+                }));
+            }
         }
     }
 
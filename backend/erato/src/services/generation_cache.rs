@@ -0,0 +1,375 @@
+//! Exact-match cache of generations, for assistants that are used on the same inputs over and
+//! over again.
+//!
+//! With `chat.generation_cache.enabled`, the first request of a generation is hashed together
+//! with the chat provider and the generation options. If a generation for the same key was
+//! cached within `ttl_secs`, its content is replayed as if it was streamed by the chat provider,
+//! without sending a request to the provider. Only generations that completed without tool calls
+//! and without errors are cached. The cache lives in Postgres, so it is shared by all instances,
+//! and is evicted by a maintenance task.
+
+use crate::config::GenerationCacheConfig;
+use crate::db::entity::prelude::*;
+use crate::metrics::report_generation_cache_lookup;
+use crate::metrics_constants::{
+    POSTGRES_QUERY_EVICT_GENERATION_CACHE, POSTGRES_QUERY_HIT_GENERATION_CACHE,
+    POSTGRES_QUERY_STORE_GENERATION_CACHE,
+};
+use crate::models::message::ContentPart;
+use crate::query_metrics::named_statement_from_sql_and_values;
+use crate::services::genai::{GenAIChatStreamResponse, into_openai_request_parts};
+use crate::state::AppState;
+use eyre::Report;
+use futures::{StreamExt, stream};
+use genai::chat::{ChatOptions, ChatRequest, ChatStreamEvent, StreamChunk, StreamEnd};
+use sea_orm::prelude::Uuid;
+use sea_orm::{ConnectionTrait, DatabaseConnection, EntityTrait};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+/// Name of the eviction in the background task manager.
+pub const GENERATION_CACHE_EVICTION_TASK: &str = "generation_cache_eviction";
+
+/// Approximate number of characters per chunk of a replayed generation.
+const REPLAY_CHUNK_CHARS: usize = 32;
+
+/// Whether generations with the given request may be cached.
+pub fn applies_to(
+    config: &GenerationCacheConfig,
+    chat_request: &ChatRequest,
+    assistant_id: Option<Uuid>,
+) -> bool {
+    if !config.enabled {
+        return false;
+    }
+    let offers_tools = chat_request
+        .tools
+        .as_ref()
+        .is_some_and(|tools| !tools.is_empty());
+    if offers_tools && !config.cache_with_tools {
+        return false;
+    }
+    match &config.assistant_ids {
+        Some(assistant_ids) => assistant_id.is_some_and(|assistant_id| {
+            assistant_ids
+                .iter()
+                .any(|id| id.eq_ignore_ascii_case(&assistant_id.to_string()))
+        }),
+        None => true,
+    }
+}
+
+/// The cache key of a request, as hex-encoded SHA-256 over the request in the shape it is sent to
+/// the chat provider, the chat provider and the generation options.
+pub fn cache_key(
+    chat_provider_id: &str,
+    chat_request: &ChatRequest,
+    chat_options: &ChatOptions,
+) -> Result<String, Report> {
+    let parts = into_openai_request_parts(chat_request)?;
+    let key = json!({
+        "chat_provider_id": chat_provider_id,
+        "messages": parts.messages,
+        "tools": parts.tools,
+        "options": {
+            "temperature": chat_options.temperature,
+            "top_p": chat_options.top_p,
+            "max_tokens": chat_options.max_tokens,
+            "reasoning_effort": chat_options
+                .reasoning_effort
+                .as_ref()
+                .map(|effort| format!("{effort:?}")),
+            "verbosity": chat_options
+                .verbosity
+                .as_ref()
+                .map(|verbosity| format!("{verbosity:?}")),
+        },
+    });
+    Ok(Sha256::digest(serde_json::to_vec(&key)?)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Look up the content of a cached generation that is not older than `ttl_secs`, and count the
+/// hit.
+pub async fn lookup_cached_generation(
+    db: &DatabaseConnection,
+    config: &GenerationCacheConfig,
+    cache_key: &str,
+    chat_provider_id: &str,
+) -> Result<Option<Vec<ContentPart>>, Report> {
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_HIT_GENERATION_CACHE,
+        r#"
+        UPDATE generation_cache_entries
+        SET hit_count = hit_count + 1, last_hit_at = now()
+        WHERE cache_key = $1
+          AND created_at > now() - make_interval(secs => $2::double precision)
+        RETURNING *
+        "#,
+        [cache_key.into(), (config.ttl_secs as f64).into()],
+    );
+    let entry = GenerationCacheEntries::find()
+        .from_raw_sql(statement)
+        .one(db)
+        .await?;
+    report_generation_cache_lookup(chat_provider_id, entry.is_some());
+    match entry {
+        Some(entry) => Ok(Some(serde_json::from_value(entry.content)?)),
+        None => Ok(None),
+    }
+}
+
+/// Cache the content of a generation, replacing an expired generation with the same key.
+pub async fn store_cached_generation(
+    db: &DatabaseConnection,
+    cache_key: &str,
+    chat_provider_id: &str,
+    content: &[ContentPart],
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+) -> Result<(), Report> {
+    let usage = json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": total_tokens,
+    });
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_STORE_GENERATION_CACHE,
+        r#"
+        INSERT INTO generation_cache_entries (cache_key, chat_provider_id, content, usage)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (cache_key) DO UPDATE
+        SET chat_provider_id = EXCLUDED.chat_provider_id,
+            content = EXCLUDED.content,
+            usage = EXCLUDED.usage,
+            hit_count = 0,
+            created_at = now(),
+            last_hit_at = NULL
+        "#,
+        [
+            cache_key.into(),
+            chat_provider_id.into(),
+            serde_json::to_value(content)?.into(),
+            usage.into(),
+        ],
+    );
+    db.execute_raw(statement).await?;
+    Ok(())
+}
+
+/// Delete the cached generations that are older than `ttl_secs`, and the oldest ones beyond
+/// `max_entries`. Returns the number of deleted generations.
+pub async fn evict_cached_generations(
+    db: &DatabaseConnection,
+    config: &GenerationCacheConfig,
+) -> Result<u64, Report> {
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_EVICT_GENERATION_CACHE,
+        r#"
+        DELETE FROM generation_cache_entries
+        WHERE created_at <= now() - make_interval(secs => $1::double precision)
+           OR cache_key IN (
+               SELECT cache_key FROM generation_cache_entries
+               ORDER BY created_at DESC
+               OFFSET $2
+           )
+        "#,
+        [
+            (config.ttl_secs as f64).into(),
+            (config.max_entries.min(i64::MAX as u64) as i64).into(),
+        ],
+    );
+    Ok(db.execute_raw(statement).await?.rows_affected())
+}
+
+/// Start the eviction of the cache, if the cache is enabled and the eviction is not already
+/// running.
+pub fn start_generation_cache_eviction(app_state: &AppState) -> bool {
+    let config = app_state.config.chat.generation_cache.clone();
+    if !config.enabled {
+        return false;
+    }
+    let db = app_state.db.clone();
+    app_state
+        .background_tasks
+        .start_maintenance_task(GENERATION_CACHE_EVICTION_TASK, async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(config.eviction_interval_secs.max(1)));
+            loop {
+                interval.tick().await;
+                match evict_cached_generations(&db, &config).await {
+                    Ok(0) => {}
+                    Ok(evicted) => {
+                        tracing::debug!(evicted, "Evicted cached generations");
+                    }
+                    Err(err) => {
+                        tracing::warn!(error = %err, "Failed to evict cached generations");
+                    }
+                }
+            }
+        })
+}
+
+/// Replay the content of a cached generation as the stream of a chat provider.
+///
+/// The stream ends without usage, as no tokens were used.
+pub fn replay_cached_generation(
+    content: Vec<ContentPart>,
+    chunk_delay: Duration,
+) -> GenAIChatStreamResponse {
+    let mut events = vec![ChatStreamEvent::Start];
+    for part in content {
+        match part {
+            ContentPart::Text(text) => events.extend(
+                replay_chunks(&text.text)
+                    .map(|content| ChatStreamEvent::Chunk(StreamChunk { content })),
+            ),
+            ContentPart::Reasoning(reasoning) => events.extend(
+                replay_chunks(&reasoning.text)
+                    .map(|content| ChatStreamEvent::ReasoningChunk(StreamChunk { content })),
+            ),
+            _ => {}
+        }
+    }
+    events.push(ChatStreamEvent::End(StreamEnd::default()));
+
+    GenAIChatStreamResponse::new(stream::iter(events).then(move |event| async move {
+        if !chunk_delay.is_zero()
+            && matches!(
+                event,
+                ChatStreamEvent::Chunk(_) | ChatStreamEvent::ReasoningChunk(_)
+            )
+        {
+            tokio::time::sleep(chunk_delay).await;
+        }
+        Ok::<_, genai::Error>(event)
+    }))
+}
+
+/// Split a text into chunks of about `REPLAY_CHUNK_CHARS` characters, at word boundaries.
+fn replay_chunks(text: &str) -> impl Iterator<Item = String> {
+    let mut chunks = vec![];
+    let mut chunk = String::new();
+    for word in text.split_inclusive(char::is_whitespace) {
+        chunk.push_str(word);
+        if chunk.chars().count() >= REPLAY_CHUNK_CHARS {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks.into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::ContentPartText;
+    use crate::services::client_actions::build_client_action_tool;
+    use genai::chat::ChatMessage;
+
+    fn enabled_config() -> GenerationCacheConfig {
+        GenerationCacheConfig {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn requests_offering_tools_are_not_cached_unless_overridden() {
+        let request = ChatRequest::new(vec![ChatMessage::user("Summarize this ticket")]);
+        let mut request_with_tools = request.clone();
+        request_with_tools.tools = Some(vec![build_client_action_tool(
+            &["open_ticket".to_string()],
+            false,
+        )]);
+
+        assert!(applies_to(&enabled_config(), &request, None));
+        assert!(!applies_to(&enabled_config(), &request_with_tools, None));
+        assert!(applies_to(
+            &GenerationCacheConfig {
+                cache_with_tools: true,
+                ..enabled_config()
+            },
+            &request_with_tools,
+            None
+        ));
+        assert!(!applies_to(
+            &GenerationCacheConfig::default(),
+            &request,
+            None
+        ));
+    }
+
+    #[test]
+    fn assistant_ids_restrict_caching_to_opted_in_assistants() {
+        let request = ChatRequest::new(vec![ChatMessage::user("Summarize this ticket")]);
+        let assistant_id = Uuid::new_v4();
+        let config = GenerationCacheConfig {
+            assistant_ids: Some(vec![assistant_id.to_string()]),
+            ..enabled_config()
+        };
+
+        assert!(applies_to(&config, &request, Some(assistant_id)));
+        assert!(!applies_to(&config, &request, Some(Uuid::new_v4())));
+        assert!(!applies_to(&config, &request, None));
+    }
+
+    #[test]
+    fn cache_key_depends_on_request_provider_and_options() {
+        let request = ChatRequest::new(vec![ChatMessage::user("Summarize this ticket")]);
+        let other_request = ChatRequest::new(vec![ChatMessage::user("Summarize that ticket")]);
+        let options = ChatOptions::default();
+
+        let key = cache_key("primary", &request, &options).unwrap();
+        assert_eq!(key, cache_key("primary", &request, &options).unwrap());
+        assert_eq!(key.len(), 64);
+        assert_ne!(key, cache_key("secondary", &request, &options).unwrap());
+        assert_ne!(key, cache_key("primary", &other_request, &options).unwrap());
+        assert_ne!(
+            key,
+            cache_key(
+                "primary",
+                &request,
+                &ChatOptions::default().with_temperature(0.5)
+            )
+            .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn replay_streams_the_cached_text_in_chunks() {
+        let text = "The ticket reports that the login page times out for users of the EU region.";
+        let mut replay = replay_cached_generation(
+            vec![ContentPart::Text(ContentPartText {
+                text: text.to_string(),
+            })],
+            Duration::ZERO,
+        );
+
+        let mut chunks = vec![];
+        let mut ended = false;
+        while let Some(event) = replay.stream.next().await {
+            match event.unwrap() {
+                ChatStreamEvent::Chunk(chunk) => chunks.push(chunk.content),
+                ChatStreamEvent::End(end) => {
+                    assert!(end.captured_usage.is_none());
+                    ended = true;
+                }
+                _ => {}
+            }
+        }
+
+        assert!(ended);
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), text);
+    }
+}
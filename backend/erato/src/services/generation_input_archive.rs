@@ -0,0 +1,470 @@
+//! Archival of the generation inputs of old messages in archived chats.
+//!
+//! Every assistant message stores the full input it was generated with in
+//! `generation_input_messages`, which repeats the history of the chat up to that message and
+//! makes up most of the size of the messages table. Archived chats can't be continued, so the
+//! generation inputs of their assistant messages older than
+//! `admin.generation_input_archival.min_age_days` are compressed with zstd and moved to the
+//! `archived_generation_inputs` table or the file storage, depending on
+//! `admin.generation_input_archival.storage`.
+//!
+//! The generation input of an archived message is replaced with a small pointer object, so the
+//! column is still set for these messages. The pointer lists the files referenced by the
+//! generation input, so they still count as referenced by the message. Whenever a message is
+//! loaded for composing a prompt, e.g. to regenerate or edit a later message, the pointer is
+//! transparently replaced with the original generation input, which is decompressed from the
+//! exact JSON that was stored, so the composed prompt doesn't change.
+//!
+//! The archival runs as a resumable maintenance task, see [`crate::services::maintenance_tasks`].
+//! A dry run goes through the same messages without changing anything and reports the number of
+//! bytes that would be saved.
+
+use crate::config::{GenerationInputArchivalConfig, GenerationInputArchiveStorage};
+use crate::db::entity::prelude::*;
+use crate::db::entity::{archived_generation_inputs, messages};
+use crate::metrics::{report_generation_input_rehydration, report_generation_inputs_archived};
+use crate::metrics_constants::POSTGRES_QUERY_GENERATION_INPUT_ARCHIVAL_BATCH;
+use crate::models::message::{ContentPart, GenerationInputMessages};
+use crate::query_metrics::named_statement_from_sql_and_values;
+use crate::services::background_tasks::BackgroundTaskManager;
+use crate::services::file_pointer_migration::inline_file_upload_ids;
+use crate::services::file_storage::FileStorage;
+use crate::services::maintenance_tasks::{
+    MaintenanceTaskBatch, MaintenanceTaskProgress, lock_maintenance_task_progress,
+    run_maintenance_task, start_maintenance_task, update_maintenance_task_progress,
+};
+use chrono::Utc;
+use eyre::{Report, WrapErr, eyre};
+use moka::future::Cache;
+use sea_orm::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveValue, DatabaseConnection, FromQueryResult, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Name of the archival in the maintenance task progress and the background task manager.
+pub const GENERATION_INPUT_ARCHIVAL_TASK: &str = "generation_input_archival";
+
+/// Name of the dry run of the archival, which has its own progress.
+pub const GENERATION_INPUT_ARCHIVAL_DRY_RUN_TASK: &str = "generation_input_archival_dry_run";
+
+/// Prefix of the paths of the compressed generation inputs in the file storage.
+pub const ARCHIVED_GENERATION_INPUT_PREFIX: &str = "archived-generation-inputs/";
+
+/// Key of the pointer object that replaces an archived generation input.
+const POINTER_KEY: &str = "archived_generation_input";
+
+/// Where the compressed generation input of a message is stored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "storage", rename_all = "snake_case")]
+pub enum ArchivedGenerationInputLocation {
+    /// In the `archived_generation_inputs` table, keyed by the ID of the message.
+    Database,
+    /// In a file storage provider.
+    FileStorage {
+        file_storage_provider_id: String,
+        path: String,
+    },
+}
+
+/// The pointer that is stored in `generation_input_messages` instead of an archived generation
+/// input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedGenerationInput {
+    #[serde(flatten)]
+    pub location: ArchivedGenerationInputLocation,
+    /// Size of the JSON of the generation input.
+    pub uncompressed_bytes: u64,
+    /// Size of the compressed JSON.
+    pub compressed_bytes: u64,
+    /// IDs of the files referenced by the generation input.
+    pub file_upload_ids: Vec<Uuid>,
+}
+
+impl ArchivedGenerationInput {
+    /// The pointer of an archived generation input, or `None` if the generation input wasn't
+    /// archived.
+    pub fn from_generation_input(generation_input: &JsonValue) -> Option<Self> {
+        serde_json::from_value(generation_input.get(POINTER_KEY)?.clone()).ok()
+    }
+
+    fn to_generation_input(&self) -> Result<JsonValue, Report> {
+        Ok(serde_json::json!({ POINTER_KEY: self }))
+    }
+}
+
+/// Restores archived generation inputs, keeping the most recently restored ones in memory.
+#[derive(Debug, Clone)]
+pub struct GenerationInputArchive {
+    file_storage_providers: HashMap<String, FileStorage>,
+    cache: Cache<Uuid, JsonValue>,
+}
+
+impl GenerationInputArchive {
+    pub fn new(file_storage_providers: HashMap<String, FileStorage>, cache_entries: u64) -> Self {
+        Self {
+            file_storage_providers,
+            cache: Cache::builder()
+                .max_capacity(cache_entries)
+                .time_to_idle(Duration::from_hours(1))
+                .build(),
+        }
+    }
+
+    fn file_storage(&self, file_storage_provider_id: &str) -> Result<&FileStorage, Report> {
+        self.file_storage_providers
+            .get(file_storage_provider_id)
+            .ok_or_else(|| eyre!("Unknown file storage provider {file_storage_provider_id}"))
+    }
+
+    /// Replace the pointer of an archived generation input of a message with the original
+    /// generation input. Other messages are returned unchanged.
+    pub async fn rehydrate<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        mut message: messages::Model,
+    ) -> Result<messages::Model, Report> {
+        let Some(archived) = message
+            .generation_input_messages
+            .as_ref()
+            .and_then(ArchivedGenerationInput::from_generation_input)
+        else {
+            return Ok(message);
+        };
+        message.generation_input_messages = Some(self.load(conn, message.id, &archived).await?);
+        Ok(message)
+    }
+
+    /// Load and decompress the archived generation input of a message.
+    pub async fn load<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        message_id: Uuid,
+        archived: &ArchivedGenerationInput,
+    ) -> Result<JsonValue, Report> {
+        if let Some(generation_input) = self.cache.get(&message_id).await {
+            report_generation_input_rehydration(true);
+            return Ok(generation_input);
+        }
+
+        let compressed = match &archived.location {
+            ArchivedGenerationInputLocation::Database => {
+                ArchivedGenerationInputs::find_by_id(message_id)
+                    .one(conn)
+                    .await?
+                    .ok_or_else(|| {
+                        eyre!("Missing archived generation input of message {message_id}")
+                    })?
+                    .compressed_input
+            }
+            ArchivedGenerationInputLocation::FileStorage {
+                file_storage_provider_id,
+                path,
+            } => self
+                .file_storage(file_storage_provider_id)?
+                .read_file_to_bytes(path)
+                .await
+                .wrap_err_with(|| format!("Failed to read archived generation input {path}"))?,
+        };
+        let json = zstd::decode_all(compressed.as_slice())
+            .wrap_err("Failed to decompress archived generation input")?;
+        let generation_input: JsonValue =
+            serde_json::from_slice(&json).wrap_err("Failed to parse archived generation input")?;
+
+        self.cache.insert(message_id, generation_input.clone()).await;
+        report_generation_input_rehydration(false);
+        Ok(generation_input)
+    }
+
+    /// Delete the archived copy of the generation input of a message.
+    ///
+    /// Only call this after the restored generation input was stored in the message, so that a
+    /// failure in between doesn't lose it.
+    pub async fn delete(
+        &self,
+        db: &DatabaseConnection,
+        message_id: Uuid,
+        archived: &ArchivedGenerationInput,
+    ) -> Result<(), Report> {
+        self.cache.invalidate(&message_id).await;
+        match &archived.location {
+            ArchivedGenerationInputLocation::Database => {
+                ArchivedGenerationInputs::delete_by_id(message_id)
+                    .exec(db)
+                    .await?;
+            }
+            ArchivedGenerationInputLocation::FileStorage {
+                file_storage_provider_id,
+                path,
+            } => {
+                self.file_storage(file_storage_provider_id)?
+                    .delete_file(path)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// IDs of the files whose contents or pointers are part of the generation input.
+fn referenced_file_upload_ids(generation_input: &GenerationInputMessages) -> Vec<Uuid> {
+    let mut file_upload_ids = inline_file_upload_ids(generation_input);
+    for message in &generation_input.messages {
+        let file_upload_id = match &message.content {
+            ContentPart::TextFilePointer(pointer) => pointer.file_upload_id,
+            ContentPart::ImageFilePointer(pointer) => pointer.file_upload_id,
+            _ => continue,
+        };
+        if !file_upload_ids.contains(&file_upload_id) {
+            file_upload_ids.push(file_upload_id);
+        }
+    }
+    file_upload_ids
+}
+
+#[derive(Debug, FromQueryResult)]
+struct ArchivalCandidate {
+    id: Uuid,
+    created_at: DateTimeWithTimeZone,
+    generation_input: String,
+}
+
+fn task_name(dry_run: bool) -> &'static str {
+    if dry_run {
+        GENERATION_INPUT_ARCHIVAL_DRY_RUN_TASK
+    } else {
+        GENERATION_INPUT_ARCHIVAL_TASK
+    }
+}
+
+/// Start the archival of old generation inputs in the background.
+///
+/// With `dry_run`, the savings are estimated without changing anything. Returns `false` if the
+/// archival, or the dry run, is already running.
+pub fn start_generation_input_archival(
+    background_tasks: &BackgroundTaskManager,
+    db: DatabaseConnection,
+    archive: GenerationInputArchive,
+    file_storage_provider_id: String,
+    config: GenerationInputArchivalConfig,
+    dry_run: bool,
+) -> bool {
+    start_maintenance_task(background_tasks, task_name(dry_run), async move {
+        run_generation_input_archival(&db, &archive, &file_storage_provider_id, &config, dry_run)
+            .await
+    })
+}
+
+/// Archive all remaining generation inputs, waiting `batch_interval_ms` between batches, see
+/// [`run_maintenance_task`].
+pub async fn run_generation_input_archival(
+    db: &DatabaseConnection,
+    archive: &GenerationInputArchive,
+    file_storage_provider_id: &str,
+    config: &GenerationInputArchivalConfig,
+    dry_run: bool,
+) -> Result<MaintenanceTaskProgress, Report> {
+    run_maintenance_task(db, task_name(dry_run), config.batch_interval_ms, || {
+        archive_generation_input_batch(db, archive, file_storage_provider_id, config, dry_run)
+    })
+    .await
+}
+
+/// Archive the generation inputs of the next batch of up to `batch_size` assistant messages in
+/// archived chats that are older than `min_age_days`, after the persisted position.
+///
+/// The messages of the batch are locked until it is committed, so that a concurrent redaction
+/// can't be overwritten with the pointer. Generation inputs that don't get smaller are skipped.
+/// With `dry_run`, the counters are updated as if the batch was archived, but nothing else.
+pub async fn archive_generation_input_batch(
+    db: &DatabaseConnection,
+    archive: &GenerationInputArchive,
+    file_storage_provider_id: &str,
+    config: &GenerationInputArchivalConfig,
+    dry_run: bool,
+) -> Result<MaintenanceTaskBatch, Report> {
+    let task_name = task_name(dry_run);
+    let txn = db.begin().await?;
+    let progress = lock_maintenance_task_progress(&txn, task_name).await?;
+
+    let created_before =
+        Utc::now().fixed_offset() - chrono::Duration::days(config.min_age_days as i64);
+    let rows = ArchivalCandidate::find_by_statement(named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_GENERATION_INPUT_ARCHIVAL_BATCH,
+        r#"
+        SELECT m.id, m.created_at, m.generation_input_messages::text AS generation_input
+        FROM messages m
+        JOIN chats c ON c.id = m.chat_id
+        WHERE c.archived_at IS NOT NULL
+          AND m.raw_message->>'role' = 'assistant'
+          AND m.created_at < $4
+          AND m.generation_input_messages IS NOT NULL
+          AND m.generation_input_messages -> 'archived_generation_input' IS NULL
+          AND ($1::timestamptz IS NULL OR (m.created_at, m.id) > ($1::timestamptz, $2::uuid))
+        ORDER BY m.created_at, m.id
+        LIMIT $3
+        FOR UPDATE OF m
+        "#,
+        [
+            progress.cursor_created_at.into(),
+            progress.cursor_id.into(),
+            (config.batch_size.max(1).min(i64::MAX as u64) as i64).into(),
+            created_before.into(),
+        ],
+    ))
+    .all(&txn)
+    .await?;
+
+    let mut batch = MaintenanceTaskBatch {
+        processed_messages: rows.len() as u64,
+        ..Default::default()
+    };
+    let cursor = rows.last().map(|row| (row.created_at, row.id));
+    for row in rows {
+        // Rows that can't be parsed are skipped, they are rejected when generating as well
+        let Ok(json) = serde_json::from_str::<JsonValue>(&row.generation_input) else {
+            continue;
+        };
+        let Ok(generation_input) = GenerationInputMessages::validate(&json) else {
+            continue;
+        };
+
+        // The JSON is compressed as returned by the database, so that it is restored to the
+        // same value
+        let compressed = zstd::bulk::compress(
+            row.generation_input.as_bytes(),
+            config.compression_level,
+        )
+        .wrap_err("Failed to compress generation input")?;
+        let location = match config.storage {
+            GenerationInputArchiveStorage::Database => ArchivedGenerationInputLocation::Database,
+            GenerationInputArchiveStorage::FileStorage => {
+                ArchivedGenerationInputLocation::FileStorage {
+                    file_storage_provider_id: file_storage_provider_id.to_string(),
+                    path: format!("{ARCHIVED_GENERATION_INPUT_PREFIX}{}.json.zst", row.id),
+                }
+            }
+        };
+        let archived = ArchivedGenerationInput {
+            location,
+            uncompressed_bytes: row.generation_input.len() as u64,
+            compressed_bytes: compressed.len() as u64,
+            file_upload_ids: referenced_file_upload_ids(&generation_input),
+        };
+        let pointer = archived.to_generation_input()?;
+        let bytes_saved = row.generation_input.len() as i64
+            - compressed.len() as i64
+            - serde_json::to_vec(&pointer)?.len() as i64;
+        if bytes_saved <= 0 {
+            continue;
+        }
+        batch.migrated_messages += 1;
+        batch.bytes_saved += bytes_saved;
+        if dry_run {
+            continue;
+        }
+
+        match &archived.location {
+            ArchivedGenerationInputLocation::Database => {
+                ArchivedGenerationInputs::insert(archived_generation_inputs::ActiveModel {
+                    message_id: ActiveValue::Set(row.id),
+                    compressed_input: ActiveValue::Set(compressed),
+                    created_at: ActiveValue::Set(Utc::now().into()),
+                })
+                .on_conflict(
+                    OnConflict::column(archived_generation_inputs::Column::MessageId)
+                        .update_columns([
+                            archived_generation_inputs::Column::CompressedInput,
+                            archived_generation_inputs::Column::CreatedAt,
+                        ])
+                        .to_owned(),
+                )
+                .exec(&txn)
+                .await
+                .wrap_err("Failed to store the archived generation input")?;
+            }
+            ArchivedGenerationInputLocation::FileStorage {
+                file_storage_provider_id,
+                path,
+            } => {
+                // Written before the pointer is committed; an interrupted batch leaves a file
+                // that is overwritten when the message is archived again
+                let mut writer = archive
+                    .file_storage(file_storage_provider_id)?
+                    .upload_file_writer(path, Some("application/zstd"))
+                    .await?;
+                writer
+                    .write(compressed)
+                    .await
+                    .wrap_err("Failed to write archived generation input")?;
+                writer.close().await?;
+            }
+        }
+        messages::ActiveModel {
+            id: ActiveValue::Unchanged(row.id),
+            generation_input_messages: ActiveValue::Set(Some(pointer)),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .wrap_err("Failed to replace the generation input of a message")?;
+    }
+
+    update_maintenance_task_progress(&txn, task_name, cursor, &batch).await?;
+    txn.commit().await?;
+
+    if !dry_run && batch.migrated_messages > 0 {
+        let storage = match config.storage {
+            GenerationInputArchiveStorage::Database => "database",
+            GenerationInputArchiveStorage::FileStorage => "file_storage",
+        };
+        report_generation_inputs_archived(storage, batch.migrated_messages, batch.bytes_saved);
+    }
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_pointer_round_trip() {
+        let archived = ArchivedGenerationInput {
+            location: ArchivedGenerationInputLocation::FileStorage {
+                file_storage_provider_id: "s3".to_string(),
+                path: "archived-generation-inputs/a.json.zst".to_string(),
+            },
+            uncompressed_bytes: 2000,
+            compressed_bytes: 300,
+            file_upload_ids: vec![Uuid::nil()],
+        };
+        let pointer = archived.to_generation_input().unwrap();
+
+        assert_eq!(pointer[POINTER_KEY]["storage"], "file_storage");
+        assert_eq!(
+            ArchivedGenerationInput::from_generation_input(&pointer),
+            Some(archived)
+        );
+        assert!(
+            pointer
+                .to_string()
+                .contains("00000000-0000-0000-0000-000000000000")
+        );
+    }
+
+    #[test]
+    fn test_generation_inputs_are_not_pointers() {
+        let generation_input = json!({
+            "messages": [{ "role": "user", "content": { "content_type": "text", "text": "Hi" } }],
+        });
+
+        assert_eq!(
+            ArchivedGenerationInput::from_generation_input(&generation_input),
+            None
+        );
+    }
+}
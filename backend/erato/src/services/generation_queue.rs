@@ -0,0 +1,463 @@
+//! Queue of generations that wait for capacity when the number of concurrent generations is
+//! limited.
+//!
+//! Generations over `chat.max_concurrent_generations` wait in a queue per user. Whenever capacity
+//! frees up, the dispatcher starts the next queued generation, taking turns between the users with
+//! queued generations so that the backlog of one user can't starve the others. Queued generations
+//! are told their position and an estimate of their wait via `queued` events. The queue is only
+//! kept in memory of the replica that received the generations.
+
+use crate::metrics::{report_generation_queue_depth, report_generation_queue_wait};
+use crate::services::background_tasks::{StreamingEvent, StreamingTask};
+use sqlx::types::Uuid;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, oneshot};
+use tokio::task::JoinHandle;
+
+/// Interval at which queued generations are sent their position, even if it didn't change
+const QUEUE_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of recently completed generations the wait estimate is derived from
+const RECENT_DURATIONS: usize = 20;
+
+/// How a new generation gets to run under the concurrency limit.
+#[derive(Debug)]
+pub enum GenerationAdmission {
+    /// The generation can start right away
+    Started(GenerationPermit),
+    /// The generation has to wait in the queue until capacity frees up
+    Queued,
+}
+
+/// Capacity held by a running generation, released when it is dropped.
+pub struct GenerationPermit {
+    /// `None` if the number of generations is not limited
+    shared: Option<Arc<QueueShared>>,
+    started_at: Instant,
+}
+
+impl GenerationPermit {
+    /// A permit that doesn't count against any limit.
+    pub fn unlimited() -> Self {
+        Self {
+            shared: None,
+            started_at: Instant::now(),
+        }
+    }
+}
+
+impl std::fmt::Debug for GenerationPermit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GenerationPermit")
+            .field("limited", &self.shared.is_some())
+            .field("started_at", &self.started_at)
+            .finish()
+    }
+}
+
+impl Drop for GenerationPermit {
+    fn drop(&mut self) {
+        let Some(shared) = &self.shared else {
+            return;
+        };
+        {
+            let mut state = shared.state.lock().unwrap();
+            state.running = state.running.saturating_sub(1);
+            if state.recent_durations.len() >= RECENT_DURATIONS {
+                state.recent_durations.pop_front();
+            }
+            state.recent_durations.push_back(self.started_at.elapsed());
+        }
+        shared.changed.notify_one();
+    }
+}
+
+/// Limits the number of concurrent generations, and queues generations over the limit.
+#[derive(Clone)]
+pub struct GenerationQueue {
+    shared: Arc<QueueShared>,
+    /// Handle to the dispatcher, kept alive with the queue.
+    _dispatcher: Arc<JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for GenerationQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.shared.state.lock().unwrap();
+        f.debug_struct("GenerationQueue")
+            .field("max_concurrent", &self.shared.max_concurrent)
+            .field("running", &state.running)
+            .field("depth", &state.depth())
+            .finish()
+    }
+}
+
+struct QueueShared {
+    max_concurrent: usize,
+    state: Mutex<QueueState>,
+    /// Wakes up the dispatcher when capacity frees up or the queue changes
+    changed: Notify,
+}
+
+#[derive(Default)]
+struct QueueState {
+    running: usize,
+    /// Users with queued generations, in the order they are served in. Users without queued
+    /// generations are removed.
+    users: VecDeque<UserQueue>,
+    recent_durations: VecDeque<Duration>,
+}
+
+struct UserQueue {
+    user_id: String,
+    generations: VecDeque<QueuedGeneration>,
+}
+
+struct QueuedGeneration {
+    task: Arc<StreamingTask>,
+    enqueued_at: Instant,
+    start: oneshot::Sender<GenerationPermit>,
+}
+
+/// Position of a queued generation, as sent in its `queued` events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct QueueStatus {
+    position: usize,
+    estimated_wait: Option<Duration>,
+}
+
+impl QueueState {
+    fn depth(&self) -> usize {
+        self.users.iter().map(|user| user.generations.len()).sum()
+    }
+
+    /// Take the next generation to start, taking turns between users.
+    fn pop_next(&mut self) -> Option<QueuedGeneration> {
+        let mut user = self.users.pop_front()?;
+        let generation = user.generations.pop_front();
+        if !user.generations.is_empty() {
+            self.users.push_back(user);
+        }
+        generation
+    }
+
+    fn average_duration(&self) -> Option<Duration> {
+        let count = u32::try_from(self.recent_durations.len()).ok()?;
+        if count == 0 {
+            return None;
+        }
+        Some(self.recent_durations.iter().sum::<Duration>() / count)
+    }
+}
+
+impl GenerationQueue {
+    /// Create a queue that runs at most `max_concurrent` generations at the same time.
+    pub fn new(max_concurrent: usize) -> Self {
+        let shared = Arc::new(QueueShared {
+            max_concurrent: max_concurrent.max(1),
+            state: Mutex::new(QueueState::default()),
+            changed: Notify::new(),
+        });
+        let dispatcher = {
+            let shared = Arc::clone(&shared);
+            tokio::spawn(async move {
+                Self::run_dispatcher(shared).await;
+            })
+        };
+        Self {
+            shared,
+            _dispatcher: Arc::new(dispatcher),
+        }
+    }
+
+    /// Take capacity for a generation, if it is available and no other generation is queued.
+    pub fn try_acquire(&self) -> Option<GenerationPermit> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.running >= self.shared.max_concurrent || !state.users.is_empty() {
+            return None;
+        }
+        state.running += 1;
+        Some(GenerationPermit {
+            shared: Some(Arc::clone(&self.shared)),
+            started_at: Instant::now(),
+        })
+    }
+
+    /// Queue the generation of the given task until capacity frees up.
+    ///
+    /// Returns `None` if the generation was aborted while it was queued.
+    pub async fn wait(&self, user_id: &str, task: &Arc<StreamingTask>) -> Option<GenerationPermit> {
+        let start = self.enqueue(user_id, Arc::clone(task));
+        tokio::select! {
+            permit = start => permit.ok(),
+            _ = task.wait_for_abort() => {
+                self.cancel(task.generation_id);
+                None
+            }
+        }
+    }
+
+    fn enqueue(
+        &self,
+        user_id: &str,
+        task: Arc<StreamingTask>,
+    ) -> oneshot::Receiver<GenerationPermit> {
+        let (start, receiver) = oneshot::channel();
+        let generation = QueuedGeneration {
+            task,
+            enqueued_at: Instant::now(),
+            start,
+        };
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            match state.users.iter_mut().find(|user| user.user_id == user_id) {
+                Some(user) => user.generations.push_back(generation),
+                None => state.users.push_back(UserQueue {
+                    user_id: user_id.to_string(),
+                    generations: VecDeque::from([generation]),
+                }),
+            }
+            report_generation_queue_depth(state.depth());
+        }
+        self.shared.changed.notify_one();
+        receiver
+    }
+
+    /// Remove the queued generation with the given ID, e.g. because it was aborted.
+    pub fn cancel(&self, generation_id: Uuid) {
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            for user in state.users.iter_mut() {
+                user.generations
+                    .retain(|generation| generation.task.generation_id != generation_id);
+            }
+            state.users.retain(|user| !user.generations.is_empty());
+            report_generation_queue_depth(state.depth());
+        }
+        self.shared.changed.notify_one();
+    }
+
+    /// Start queued generations whenever capacity frees up, and keep the remaining ones informed
+    /// about their position.
+    async fn run_dispatcher(shared: Arc<QueueShared>) {
+        let mut interval = tokio::time::interval(QUEUE_UPDATE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = shared.changed.notified() => {}
+                _ = interval.tick() => {}
+            }
+
+            for (task, status) in Self::dispatch(&shared) {
+                let event = StreamingEvent::Queued {
+                    position: status.position,
+                    estimated_wait_seconds: status.estimated_wait.map(|wait| wait.as_secs()),
+                };
+                if let Err(err) = task.send_event(event).await {
+                    tracing::warn!(error = %err, "Failed to send the queue position of a generation");
+                }
+            }
+        }
+    }
+
+    /// Start as many queued generations as there is capacity for, and return the status of the
+    /// generations that are still queued.
+    fn dispatch(shared: &Arc<QueueShared>) -> Vec<(Arc<StreamingTask>, QueueStatus)> {
+        // Permits of generations whose waiter is gone are only released after the lock is
+        // dropped, because releasing them takes the lock.
+        let mut unclaimed_permits = Vec::new();
+        let mut state = shared.state.lock().unwrap();
+        while state.running < shared.max_concurrent {
+            let Some(generation) = state.pop_next() else {
+                break;
+            };
+            if generation.start.is_closed() {
+                continue;
+            }
+            state.running += 1;
+            report_generation_queue_wait(generation.enqueued_at.elapsed());
+            let permit = GenerationPermit {
+                shared: Some(Arc::clone(shared)),
+                started_at: Instant::now(),
+            };
+            if let Err(permit) = generation.start.send(permit) {
+                unclaimed_permits.push(permit);
+            }
+        }
+        report_generation_queue_depth(state.depth());
+
+        let queue_lengths: Vec<usize> = state
+            .users
+            .iter()
+            .map(|user| user.generations.len())
+            .collect();
+        let average_duration = state.average_duration();
+        let statuses = state
+            .users
+            .iter()
+            .enumerate()
+            .flat_map(|(user_index, user)| {
+                let queue_lengths = &queue_lengths;
+                user.generations
+                    .iter()
+                    .enumerate()
+                    .map(move |(generation_index, generation)| {
+                        let position = queue_position(queue_lengths, user_index, generation_index);
+                        let status = QueueStatus {
+                            position,
+                            estimated_wait: estimated_wait(
+                                position,
+                                shared.max_concurrent,
+                                average_duration,
+                            ),
+                        };
+                        (Arc::clone(&generation.task), status)
+                    })
+            })
+            .collect();
+        drop(state);
+        drop(unclaimed_permits);
+        statuses
+    }
+}
+
+/// Position (starting at 1) of a queued generation in the order the queue is served in.
+///
+/// `queue_lengths` holds the number of queued generations of each user in the order users are
+/// served in, and the generation is the `generation_index`th one of the `user_index`th user.
+fn queue_position(queue_lengths: &[usize], user_index: usize, generation_index: usize) -> usize {
+    let served_before: usize = queue_lengths
+        .iter()
+        .enumerate()
+        .map(|(index, &length)| {
+            if index < user_index {
+                length.min(generation_index + 1)
+            } else {
+                length.min(generation_index)
+            }
+        })
+        .sum();
+    served_before + 1
+}
+
+/// Rough estimate of the wait of a queued generation, assuming the generations ahead of it take
+/// as long as the recent ones on average. `None` until a generation has completed.
+fn estimated_wait(
+    position: usize,
+    max_concurrent: usize,
+    average_duration: Option<Duration>,
+) -> Option<Duration> {
+    let rounds = u32::try_from(position.div_ceil(max_concurrent)).ok()?;
+    Some(average_duration? * rounds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GenerationStatusConfig;
+    use crate::services::background_tasks::BackgroundTaskManager;
+
+    async fn new_task(manager: &BackgroundTaskManager) -> Arc<StreamingTask> {
+        let (_receiver, task) = manager
+            .start_task(Uuid::new_v4(), Uuid::new_v4())
+            .await
+            .unwrap();
+        task
+    }
+
+    #[test]
+    fn queue_position_takes_turns_between_users() {
+        // User A has three queued generations, user B one and user C two.
+        let queue_lengths = [3, 1, 2];
+        // Served in the order A1, B1, C1, A2, C2, A3
+        assert_eq!(queue_position(&queue_lengths, 0, 0), 1);
+        assert_eq!(queue_position(&queue_lengths, 1, 0), 2);
+        assert_eq!(queue_position(&queue_lengths, 2, 0), 3);
+        assert_eq!(queue_position(&queue_lengths, 0, 1), 4);
+        assert_eq!(queue_position(&queue_lengths, 2, 1), 5);
+        assert_eq!(queue_position(&queue_lengths, 0, 2), 6);
+    }
+
+    #[test]
+    fn estimated_wait_counts_rounds_of_concurrent_generations() {
+        let average = Some(Duration::from_secs(10));
+        assert_eq!(estimated_wait(1, 2, average), Some(Duration::from_secs(10)));
+        assert_eq!(estimated_wait(2, 2, average), Some(Duration::from_secs(10)));
+        assert_eq!(estimated_wait(3, 2, average), Some(Duration::from_secs(20)));
+        assert_eq!(estimated_wait(3, 2, None), None);
+    }
+
+    #[tokio::test]
+    async fn generations_start_when_capacity_frees_up() {
+        let manager = BackgroundTaskManager::new(None, GenerationStatusConfig::default());
+        let queue = GenerationQueue::new(1);
+        let running = queue.try_acquire().expect("capacity should be available");
+        assert!(queue.try_acquire().is_none());
+
+        let task = new_task(&manager).await;
+        let mut events = task.subscribe();
+        let waiting = {
+            let queue = queue.clone();
+            let task = Arc::clone(&task);
+            tokio::spawn(async move { queue.wait("user-a", &task).await })
+        };
+
+        let event = events.recv().await.unwrap();
+        assert!(matches!(
+            event,
+            StreamingEvent::Queued {
+                position: 1,
+                estimated_wait_seconds: None
+            }
+        ));
+
+        drop(running);
+        let permit = waiting.await.unwrap();
+        assert!(permit.is_some());
+        assert!(queue.try_acquire().is_none());
+    }
+
+    #[tokio::test]
+    async fn queue_takes_turns_between_users() {
+        let manager = BackgroundTaskManager::new(None, GenerationStatusConfig::default());
+        let queue = GenerationQueue::new(1);
+        let running = queue.try_acquire().unwrap();
+
+        let a1 = queue.enqueue("user-a", new_task(&manager).await);
+        let mut a2 = queue.enqueue("user-a", new_task(&manager).await);
+        let b1 = queue.enqueue("user-b", new_task(&manager).await);
+
+        drop(running);
+        let permit = a1.await.expect("a1 should start first");
+        assert!(a2.try_recv().is_err());
+        drop(permit);
+        let permit = b1.await.expect("b1 should start before a2");
+        assert!(a2.try_recv().is_err());
+        drop(permit);
+        a2.await.expect("a2 should start last");
+    }
+
+    #[tokio::test]
+    async fn aborted_generations_leave_the_queue() {
+        let manager = BackgroundTaskManager::new(None, GenerationStatusConfig::default());
+        let queue = GenerationQueue::new(1);
+        let running = queue.try_acquire().unwrap();
+
+        let task = new_task(&manager).await;
+        let waiting = {
+            let queue = queue.clone();
+            let task = Arc::clone(&task);
+            tokio::spawn(async move { queue.wait("user-a", &task).await })
+        };
+        while queue.shared.state.lock().unwrap().depth() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        task.request_abort();
+        assert!(waiting.await.unwrap().is_none());
+        assert_eq!(queue.shared.state.lock().unwrap().depth(), 0);
+
+        drop(running);
+        assert!(queue.try_acquire().is_some());
+    }
+}
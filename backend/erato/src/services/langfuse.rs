@@ -1,4 +1,6 @@
-use crate::config::LangfuseConfig;
+use crate::config::{LangfuseConfig, LangfuseDeadLetterQueueConfig};
+use crate::metrics::report_langfuse_consecutive_failures;
+use crate::services::langfuse_dead_letters::LangfuseDeadLetterQueue;
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64_STANDARD};
 use chrono::{DateTime, Utc};
 use eyre::{Result, eyre};
@@ -9,12 +11,17 @@ use opentelemetry::{Array, InstrumentationScope, KeyValue, StringValue, Value};
 use opentelemetry_otlp::{Protocol, WithExportConfig, WithHttpConfig};
 use opentelemetry_sdk::Resource;
 use opentelemetry_sdk::trace::{SpanData, SpanEvents, SpanExporter, SpanLinks};
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
+use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
+use tokio::sync::Notify;
+use utoipa::ToSchema;
 
 const LANGFUSE_OTEL_ENDPOINT_PATH: &str = "/api/public/otel/v1/traces";
 const LANGFUSE_TRACE_NAME: &str = "langfuse.trace.name";
@@ -41,6 +48,154 @@ const LANGFUSE_OBSERVATION_COMPLETION_START_TIME: &str =
 const LANGFUSE_OBSERVATION_METADATA_PREFIX: &str = "langfuse.observation.metadata";
 const LANGFUSE_VERSION: &str = "langfuse.version";
 
+/// Maximum time `LangfuseClient::flush` waits for pending requests to complete.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Requests to Langfuse that are still in flight, shared between all clones of a client.
+#[derive(Debug, Default)]
+struct PendingRequests {
+    count: AtomicUsize,
+    idle: Notify,
+}
+
+impl PendingRequests {
+    /// Track a request until the returned guard is dropped.
+    fn track(&self) -> PendingRequestGuard<'_> {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        PendingRequestGuard(self)
+    }
+
+    fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    async fn wait_until_idle(&self) {
+        loop {
+            let idle = self.idle.notified();
+            tokio::pin!(idle);
+            // Register for the notification before checking, so a request finishing
+            // in between is not missed.
+            idle.as_mut().enable();
+            if self.count() == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+struct PendingRequestGuard<'a>(&'a PendingRequests);
+
+impl Drop for PendingRequestGuard<'_> {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+/// Outcome of the recent requests to Langfuse, shared between all clones of a client.
+#[derive(Debug, Default)]
+pub(crate) struct LangfuseHealth(Mutex<LangfuseHealthState>);
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct LangfuseHealthState {
+    /// Whether the last request reached Langfuse, `None` before the first request
+    pub connected: Option<bool>,
+    pub last_successful_flush_at: Option<DateTime<Utc>>,
+    pub last_failure_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u64,
+}
+
+impl LangfuseHealth {
+    pub(crate) fn state(&self) -> LangfuseHealthState {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn record_success(&self) {
+        let mut state = self.0.lock().unwrap();
+        state.connected = Some(true);
+        state.last_successful_flush_at = Some(Utc::now());
+        state.consecutive_failures = 0;
+        report_langfuse_consecutive_failures(0);
+    }
+
+    fn record_connected(&self) {
+        let mut state = self.0.lock().unwrap();
+        state.connected = Some(true);
+        state.consecutive_failures = 0;
+        report_langfuse_consecutive_failures(0);
+    }
+
+    fn record_failure(&self, error: &eyre::Report) {
+        let mut state = self.0.lock().unwrap();
+        state.connected = Some(false);
+        state.last_failure_at = Some(Utc::now());
+        state.last_error = Some(error.to_string());
+        state.consecutive_failures += 1;
+        report_langfuse_consecutive_failures(state.consecutive_failures);
+    }
+}
+
+/// Why Langfuse did not accept an ingestion batch.
+#[derive(Debug)]
+pub(crate) enum IngestionFailure {
+    /// Langfuse could not be reached or could not process the batch at the moment, so sending it
+    /// again later may succeed.
+    Unavailable(eyre::Report),
+    /// Langfuse rejected the batch itself, so sending it again would fail as well.
+    Rejected(eyre::Report),
+}
+
+impl IngestionFailure {
+    fn from_status(status: StatusCode, error: eyre::Report) -> Self {
+        let retryable = status.is_server_error()
+            || matches!(
+                status,
+                StatusCode::REQUEST_TIMEOUT
+                    | StatusCode::TOO_MANY_REQUESTS
+                    | StatusCode::UNAUTHORIZED
+                    | StatusCode::FORBIDDEN
+            );
+        if retryable {
+            Self::Unavailable(error)
+        } else {
+            Self::Rejected(error)
+        }
+    }
+
+    pub(crate) fn into_report(self) -> eyre::Report {
+        match self {
+            Self::Unavailable(error) | Self::Rejected(error) => error,
+        }
+    }
+}
+
+/// The connectivity of the Langfuse integration and the batches that wait for a retry
+#[derive(Debug, ToSchema, Serialize)]
+pub struct LangfuseStatus {
+    /// Whether the Langfuse integration is enabled
+    pub enabled: bool,
+    /// Whether the last request of this backend instance reached Langfuse, or `null` if it
+    /// hasn't sent a request yet
+    pub connected: Option<bool>,
+    /// When this backend instance last delivered data to Langfuse
+    pub last_successful_flush_at: Option<DateTime<Utc>>,
+    /// When the last request of this backend instance to Langfuse failed
+    pub last_failure_at: Option<DateTime<Utc>>,
+    /// The error of the last failed request
+    pub last_error: Option<String>,
+    /// Number of requests that failed since the last successful one
+    pub consecutive_failures: u64,
+    /// Number of ingestion batches that wait for a retry, including the ones of other instances
+    pub queued_batches: u64,
+    /// Number of batches this backend instance dropped because the queue was full
+    pub evicted_batches: u64,
+    /// When the queued batches are retried next, if there are any
+    pub next_retry_at: Option<DateTime<Utc>>,
+}
+
 /// Langfuse client for sending tracing data
 #[derive(Debug, Clone)]
 pub struct LangfuseClient {
@@ -51,6 +206,10 @@ pub struct LangfuseClient {
     enabled: bool,
     environment: Option<String>,
     use_otel: bool,
+    pending_requests: Arc<PendingRequests>,
+    health: Arc<LangfuseHealth>,
+    dead_letter_queue_config: LangfuseDeadLetterQueueConfig,
+    dead_letters: Option<LangfuseDeadLetterQueue>,
 }
 
 impl LangfuseClient {
@@ -86,6 +245,10 @@ impl LangfuseClient {
                 enabled: false,
                 environment: None,
                 use_otel: false,
+                pending_requests: Arc::default(),
+                health: Arc::default(),
+                dead_letter_queue_config: config.dead_letter_queue.clone(),
+                dead_letters: None,
             });
         }
 
@@ -124,9 +287,129 @@ impl LangfuseClient {
             enabled: true,
             environment,
             use_otel: config.use_otel,
+            pending_requests: Arc::default(),
+            health: Arc::default(),
+            dead_letter_queue_config: config.dead_letter_queue.clone(),
+            dead_letters: None,
+        })
+    }
+
+    /// Queue ingestion batches that could not be delivered in the database, so they can be
+    /// retried once Langfuse is reachable again.
+    ///
+    /// Does nothing if the client is disabled or `dead_letter_queue.max_batches` is `0`.
+    pub fn with_dead_letter_queue(mut self, db: DatabaseConnection) -> Self {
+        if self.enabled && self.dead_letter_queue_config.max_batches > 0 {
+            self.dead_letters = Some(LangfuseDeadLetterQueue::new(
+                db,
+                self.dead_letter_queue_config.clone(),
+            ));
+        }
+        self
+    }
+
+    /// The queue of undelivered ingestion batches, if the client has one.
+    pub fn dead_letters(&self) -> Option<&LangfuseDeadLetterQueue> {
+        self.dead_letters.as_ref()
+    }
+
+    pub(crate) fn health(&self) -> &LangfuseHealth {
+        &self.health
+    }
+
+    /// Store the buffered undelivered batches and send the queued batches to Langfuse, oldest
+    /// first, until one of them fails.
+    ///
+    /// Ignores the backoff after earlier failures.
+    pub async fn retry_dead_letters(&self) -> Result<()> {
+        let Some(dead_letters) = &self.dead_letters else {
+            return Ok(());
+        };
+        dead_letters.persist().await?;
+        dead_letters.deliver(self).await
+    }
+
+    /// Check whether Langfuse is reachable, via its health endpoint.
+    pub async fn check_connectivity(&self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let url = format!("{}/api/public/health", self.base_url);
+        let result = match self.client.get(&url).send().await {
+            Ok(response) if response.status().is_success() => Ok(()),
+            Ok(response) => Err(eyre!(
+                "Langfuse health check failed with status {}",
+                response.status()
+            )),
+            Err(e) => Err(eyre!("Failed to reach Langfuse: {}", e)),
+        };
+        match &result {
+            Ok(()) => self.health.record_connected(),
+            Err(error) => self.health.record_failure(error),
+        }
+        result
+    }
+
+    /// The connectivity of the client and the state of its dead-letter queue.
+    pub async fn status(&self) -> Result<LangfuseStatus> {
+        let health = self.health.state();
+        let (queued_batches, evicted_batches, next_retry_at) = match &self.dead_letters {
+            Some(dead_letters) => {
+                let queued_batches = dead_letters.refresh_depth().await?;
+                let next_retry_at = if queued_batches > 0 {
+                    Some(dead_letters.next_retry_at(&health))
+                } else {
+                    None
+                };
+                (queued_batches, dead_letters.evicted(), next_retry_at)
+            }
+            None => (0, 0, None),
+        };
+        Ok(LangfuseStatus {
+            enabled: self.enabled,
+            connected: health.connected,
+            last_successful_flush_at: health.last_successful_flush_at,
+            last_failure_at: health.last_failure_at,
+            last_error: health.last_error,
+            consecutive_failures: health.consecutive_failures,
+            queued_batches,
+            evicted_batches,
+            next_retry_at,
         })
     }
 
+    /// Wait until all traces that are currently being sent to Langfuse have been sent.
+    ///
+    /// Meant to be called on shutdown, so buffered traces are not lost. Gives up after
+    /// 10 seconds. Undelivered batches that are still buffered are stored in the dead-letter
+    /// queue.
+    pub async fn flush(&self) -> Result<()> {
+        self.flush_with_timeout(FLUSH_TIMEOUT).await
+    }
+
+    async fn flush_with_timeout(&self, timeout: Duration) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let pending = self.pending_requests.count();
+        tokio::time::timeout(timeout, self.pending_requests.wait_until_idle())
+            .await
+            .map_err(|_| {
+                eyre!(
+                    "Timed out after {:?} with {} Langfuse traces still pending",
+                    timeout,
+                    self.pending_requests.count()
+                )
+            })?;
+        tracing::info!("Flushed {} Langfuse traces", pending);
+        if let Some(dead_letters) = &self.dead_letters {
+            dead_letters.persist().await?;
+        }
+        Ok(())
+    }
+
     /// Create a trace in Langfuse
     pub async fn create_trace(&self, request: CreateTraceRequest) -> Result<()> {
         if !self.enabled {
@@ -762,7 +1045,57 @@ impl LangfuseClient {
     }
 
     /// Send an ingestion batch to Langfuse
+    ///
+    /// With a dead-letter queue, batches that can't be delivered are queued for a retry, and so
+    /// are all batches while the queue is not empty, to deliver them in order. Queuing never
+    /// waits, so the callers stay non-blocking even while Langfuse is unreachable.
     async fn send_ingestion_batch(&self, batch: IngestionBatch) -> Result<()> {
+        let Some(dead_letters) = &self.dead_letters else {
+            return self
+                .post_ingestion_batch(&batch)
+                .await
+                .map_err(IngestionFailure::into_report);
+        };
+
+        let batch = serde_json::to_value(&batch)?;
+        if !dead_letters.is_empty() {
+            tracing::debug!("Langfuse dead-letter queue is not empty, queuing ingestion batch");
+            dead_letters.push(batch);
+            return Ok(());
+        }
+        match self.post_ingestion_batch(&batch).await {
+            Ok(()) => Ok(()),
+            Err(IngestionFailure::Unavailable(error)) => {
+                tracing::warn!(
+                    error = %error,
+                    "Failed to send ingestion batch to Langfuse, queued it for a retry"
+                );
+                dead_letters.push(batch);
+                Ok(())
+            }
+            Err(IngestionFailure::Rejected(error)) => Err(error),
+        }
+    }
+
+    /// Post an ingestion batch to Langfuse, and record whether Langfuse was reachable.
+    pub(crate) async fn post_ingestion_batch<T: Serialize>(
+        &self,
+        batch: &T,
+    ) -> Result<(), IngestionFailure> {
+        let result = self.post_ingestion_batch_unrecorded(batch).await;
+        match &result {
+            Ok(()) => self.health.record_success(),
+            Err(IngestionFailure::Unavailable(error)) => self.health.record_failure(error),
+            Err(IngestionFailure::Rejected(_)) => {}
+        }
+        result
+    }
+
+    async fn post_ingestion_batch_unrecorded<T: Serialize>(
+        &self,
+        batch: &T,
+    ) -> Result<(), IngestionFailure> {
+        let _pending = self.pending_requests.track();
         let url = format!("{}/api/public/ingestion", self.base_url);
 
         tracing::debug!(
@@ -794,7 +1127,7 @@ impl LangfuseClient {
                     url = %url,
                     "Failed to send HTTP request to Langfuse"
                 );
-                eyre!("Failed to send request to Langfuse: {}", e)
+                IngestionFailure::Unavailable(eyre!("Failed to send request to Langfuse: {}", e))
             })?;
 
         let status = response.status();
@@ -817,10 +1150,9 @@ impl LangfuseClient {
                 "Langfuse ingestion request failed"
             );
 
-            return Err(eyre!(
-                "Langfuse ingestion failed with status {}: {}",
+            return Err(IngestionFailure::from_status(
                 status,
-                body
+                eyre!("Langfuse ingestion failed with status {}: {}", status, body),
             ));
         }
 
@@ -846,12 +1178,12 @@ impl LangfuseClient {
 
                                 // Return error with details from the first error
                                 if let Some(first_error) = multi_status.errors.first() {
-                                    return Err(eyre!(
+                                    return Err(IngestionFailure::Rejected(eyre!(
                                         "Langfuse ingestion error for {}: {} - {}",
                                         first_error.id,
                                         first_error.message,
                                         first_error.error.as_deref().unwrap_or("No details")
-                                    ));
+                                    )));
                                 }
                             } else {
                                 tracing::debug!(
@@ -899,6 +1231,7 @@ impl LangfuseClient {
     }
 
     async fn send_otel_spans(&self, spans: Vec<SpanData>) -> Result<()> {
+        let _pending = self.pending_requests.track();
         let endpoint = format!("{}{}", self.base_url, LANGFUSE_OTEL_ENDPOINT_PATH);
         let auth = BASE64_STANDARD.encode(format!("{}:{}", self.public_key, self.secret_key));
         let mut headers = HashMap::new();
@@ -911,7 +1244,7 @@ impl LangfuseClient {
             "Sending Langfuse OTLP spans"
         );
 
-        tokio::task::spawn_blocking(move || -> Result<()> {
+        let result = tokio::task::spawn_blocking(move || -> Result<()> {
             let mut exporter = opentelemetry_otlp::SpanExporter::builder()
                 .with_http()
                 .with_endpoint(endpoint)
@@ -936,9 +1269,13 @@ impl LangfuseClient {
             Ok(())
         })
         .await
-        .map_err(|e| eyre!("Langfuse OTLP export task failed: {}", e))??;
+        .unwrap_or_else(|e| Err(eyre!("Langfuse OTLP export task failed: {}", e)));
 
-        Ok(())
+        match &result {
+            Ok(()) => self.health.record_success(),
+            Err(error) => self.health.record_failure(error),
+        }
+        result
     }
 }
 
@@ -1884,6 +2221,43 @@ mod tests {
         (format!("http://{addr}"), receiver)
     }
 
+    /// Mock Langfuse ingestion endpoint that answers after `delay` and counts the
+    /// received batches.
+    async fn counting_langfuse_server(delay: Duration) -> (String, Arc<AtomicUsize>) {
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = received.clone();
+        let app = Router::new().route(
+            "/api/public/ingestion",
+            post(move || {
+                let counter = counter.clone();
+                async move {
+                    tokio::time::sleep(delay).await;
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    "{}"
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{addr}"), received)
+    }
+
+    /// Send `count` traces in the background and wait until all of them are in flight.
+    async fn send_traces_in_background(client: &LangfuseClient, count: usize) {
+        for _ in 0..count {
+            let client = client.clone();
+            tokio::spawn(async move {
+                client.create_trace(test_trace_request()).await.unwrap();
+            });
+        }
+        while client.pending_requests.count() < count {
+            tokio::task::yield_now().await;
+        }
+    }
+
     fn enabled_config(base_url: String, use_otel: bool) -> LangfuseConfig {
         LangfuseConfig {
             enabled: true,
@@ -2414,4 +2788,39 @@ mod tests {
             "58406520a006649127e371903a2de979"
         );
     }
+
+    #[tokio::test]
+    async fn flush_waits_for_pending_traces() {
+        let (base_url, received) = counting_langfuse_server(Duration::from_millis(200)).await;
+        let client = LangfuseClient::from_config(&enabled_config(base_url, false), None).unwrap();
+
+        send_traces_in_background(&client, 3).await;
+        assert_eq!(received.load(Ordering::SeqCst), 0);
+
+        client.flush().await.unwrap();
+        assert_eq!(received.load(Ordering::SeqCst), 3);
+        assert_eq!(client.pending_requests.count(), 0);
+
+        // Nothing left to wait for
+        client.flush().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn flush_times_out_on_unresponsive_server() {
+        let (base_url, received) = counting_langfuse_server(Duration::from_secs(30)).await;
+        let client = LangfuseClient::from_config(&enabled_config(base_url, false), None).unwrap();
+
+        send_traces_in_background(&client, 1).await;
+
+        let error = client
+            .flush_with_timeout(Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("1 Langfuse traces still pending")
+        );
+        assert_eq!(received.load(Ordering::SeqCst), 0);
+    }
 }
@@ -0,0 +1,330 @@
+//! Dead-letter queue for Langfuse ingestion batches that could not be delivered.
+//!
+//! When Langfuse can't be reached, the [`LangfuseClient`] buffers the batch in memory. A
+//! background task stores the buffered batches in the `langfuse_dead_letters` table every
+//! [`PERSIST_INTERVAL`], and sends the stored batches to Langfuse again, oldest first, with an
+//! exponential backoff after failed attempts. While the queue is not empty, the client queues new
+//! batches right away instead of sending them, so that Langfuse receives them in order.
+//!
+//! Queuing never waits for the database or for Langfuse. The queue holds at most
+//! `integrations.langfuse.dead_letter_queue.max_batches` batches and drops the oldest ones first.
+//! Spans that are sent via OTEL are not queued.
+
+use crate::config::LangfuseDeadLetterQueueConfig;
+use crate::db::entity::langfuse_dead_letters;
+use crate::db::entity::prelude::*;
+use crate::metrics::{
+    report_langfuse_dead_letter_evictions, report_langfuse_dead_letter_queue_depth,
+};
+use crate::metrics_constants::{
+    POSTGRES_QUERY_CLAIM_LANGFUSE_DEAD_LETTER, POSTGRES_QUERY_EVICT_LANGFUSE_DEAD_LETTERS,
+};
+use crate::query_metrics::{named_statement_from_sql_and_values, named_statement_from_string};
+use crate::services::langfuse::{IngestionFailure, LangfuseClient, LangfuseHealthState};
+use crate::state::AppState;
+use chrono::{DateTime, TimeDelta, Utc};
+use eyre::Result;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ConnectionTrait, DatabaseConnection, EntityTrait,
+    PaginatorTrait, TransactionTrait,
+};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Name of the retry loop in the background task manager.
+pub const LANGFUSE_DEAD_LETTER_RETRY_TASK: &str = "langfuse_dead_letter_retry";
+
+/// Interval in which buffered batches are stored and the queue is checked for a due retry.
+const PERSIST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Queue of ingestion batches that wait to be sent to Langfuse again, shared between all clones
+/// of a client.
+#[derive(Clone)]
+pub struct LangfuseDeadLetterQueue {
+    inner: Arc<DeadLetterQueueInner>,
+}
+
+struct DeadLetterQueueInner {
+    db: DatabaseConnection,
+    config: LangfuseDeadLetterQueueConfig,
+    /// Batches that are not stored in the database yet, oldest first
+    buffer: Mutex<VecDeque<serde_json::Value>>,
+    /// Number of stored batches as of the last check, including the ones of other instances
+    stored: AtomicUsize,
+    /// Number of batches this instance dropped because the queue was full
+    evicted: AtomicU64,
+}
+
+impl std::fmt::Debug for LangfuseDeadLetterQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LangfuseDeadLetterQueue")
+            .field("config", &self.inner.config)
+            .field("depth", &self.depth())
+            .finish()
+    }
+}
+
+impl LangfuseDeadLetterQueue {
+    pub(crate) fn new(db: DatabaseConnection, config: LangfuseDeadLetterQueueConfig) -> Self {
+        Self {
+            inner: Arc::new(DeadLetterQueueInner {
+                db,
+                config,
+                buffer: Mutex::default(),
+                stored: AtomicUsize::new(0),
+                evicted: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Number of batches that wait for a retry, as far as this instance knows.
+    pub fn depth(&self) -> usize {
+        self.inner.buffer.lock().unwrap().len() + self.inner.stored.load(Ordering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depth() == 0
+    }
+
+    /// Number of batches this instance dropped because the queue was full.
+    pub fn evicted(&self) -> u64 {
+        self.inner.evicted.load(Ordering::SeqCst)
+    }
+
+    /// Buffer a batch until it is stored by [`Self::persist`].
+    pub(crate) fn push(&self, batch: serde_json::Value) {
+        let evicted = {
+            let mut buffer = self.inner.buffer.lock().unwrap();
+            buffer.push_back(batch);
+            let evicted = buffer
+                .len()
+                .saturating_sub(self.inner.config.max_batches as usize);
+            buffer.drain(..evicted);
+            evicted
+        };
+        self.record_evictions(evicted as u64);
+        report_langfuse_dead_letter_queue_depth(self.depth());
+    }
+
+    fn record_evictions(&self, count: u64) {
+        if count == 0 {
+            return;
+        }
+        tracing::warn!(
+            count,
+            "Langfuse dead-letter queue is full, dropped the oldest batches"
+        );
+        self.inner.evicted.fetch_add(count, Ordering::SeqCst);
+        report_langfuse_dead_letter_evictions(count);
+    }
+
+    /// Store the buffered batches in the database, and drop the oldest stored batches that
+    /// exceed the size of the queue.
+    pub async fn persist(&self) -> Result<()> {
+        let batches: Vec<serde_json::Value> = self.inner.buffer.lock().unwrap().drain(..).collect();
+        if batches.is_empty() {
+            return Ok(());
+        }
+
+        let count = batches.len();
+        let models = batches
+            .iter()
+            .map(|batch| langfuse_dead_letters::ActiveModel {
+                batch: ActiveValue::Set(batch.clone()),
+                ..Default::default()
+            });
+        if let Err(err) = LangfuseDeadLetters::insert_many(models)
+            .exec(&self.inner.db)
+            .await
+        {
+            // Keep the batches in front of the ones that were buffered in the meantime
+            let mut buffer = self.inner.buffer.lock().unwrap();
+            for batch in batches.into_iter().rev() {
+                buffer.push_front(batch);
+            }
+            return Err(err.into());
+        }
+        self.inner.stored.fetch_add(count, Ordering::SeqCst);
+        tracing::debug!(count, "Stored undelivered Langfuse ingestion batches");
+
+        let statement = named_statement_from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            POSTGRES_QUERY_EVICT_LANGFUSE_DEAD_LETTERS,
+            r#"
+            DELETE FROM langfuse_dead_letters
+            WHERE id IN (
+                SELECT id FROM langfuse_dead_letters
+                ORDER BY id DESC
+                OFFSET $1
+            )
+            "#,
+            [(self.inner.config.max_batches.min(i64::MAX as u64) as i64).into()],
+        );
+        let evicted = self.inner.db.execute_raw(statement).await?.rows_affected();
+        self.record_evictions(evicted);
+        self.refresh_depth().await?;
+        Ok(())
+    }
+
+    /// Count the stored batches, including the ones of other instances, and return the number
+    /// of batches that wait for a retry.
+    pub(crate) async fn refresh_depth(&self) -> Result<u64> {
+        let stored = LangfuseDeadLetters::find().count(&self.inner.db).await?;
+        self.inner.stored.store(stored as usize, Ordering::SeqCst);
+        let depth = self.depth();
+        report_langfuse_dead_letter_queue_depth(depth);
+        Ok(depth as u64)
+    }
+
+    /// When the queued batches are due for a retry, after the backoff of the failures in a row.
+    pub(crate) fn next_retry_at(&self, health: &LangfuseHealthState) -> DateTime<Utc> {
+        match health.last_failure_at {
+            Some(last_failure_at) if health.consecutive_failures > 0 => {
+                let delay_secs = retry_delay_secs(&self.inner.config, health.consecutive_failures);
+                last_failure_at + TimeDelta::seconds(delay_secs as i64)
+            }
+            _ => Utc::now(),
+        }
+    }
+
+    /// Send the stored batches to Langfuse, oldest first, until one of them fails.
+    ///
+    /// The oldest batch stays locked while it is sent, so only one instance delivers batches at
+    /// a time. Batches that Langfuse rejects are dropped, as sending them again would fail again.
+    pub(crate) async fn deliver(&self, client: &LangfuseClient) -> Result<()> {
+        loop {
+            let txn = self.inner.db.begin().await?;
+            let statement = named_statement_from_string(
+                sea_orm::DatabaseBackend::Postgres,
+                POSTGRES_QUERY_CLAIM_LANGFUSE_DEAD_LETTER,
+                r#"
+                SELECT * FROM langfuse_dead_letters
+                WHERE id = (SELECT min(id) FROM langfuse_dead_letters)
+                FOR UPDATE SKIP LOCKED
+                "#,
+            );
+            let Some(dead_letter) = LangfuseDeadLetters::find()
+                .from_raw_sql(statement)
+                .one(&txn)
+                .await?
+            else {
+                break;
+            };
+
+            match client.post_ingestion_batch(&dead_letter.batch).await {
+                Ok(()) => {
+                    LangfuseDeadLetters::delete_by_id(dead_letter.id)
+                        .exec(&txn)
+                        .await?;
+                    txn.commit().await?;
+                }
+                Err(IngestionFailure::Rejected(error)) => {
+                    tracing::error!(
+                        error = %error,
+                        attempts = dead_letter.attempts,
+                        "Langfuse rejected a queued ingestion batch, dropping it"
+                    );
+                    LangfuseDeadLetters::delete_by_id(dead_letter.id)
+                        .exec(&txn)
+                        .await?;
+                    txn.commit().await?;
+                }
+                Err(IngestionFailure::Unavailable(error)) => {
+                    langfuse_dead_letters::ActiveModel {
+                        id: ActiveValue::Unchanged(dead_letter.id),
+                        attempts: ActiveValue::Set(dead_letter.attempts + 1),
+                        last_error: ActiveValue::Set(Some(error.to_string())),
+                        updated_at: ActiveValue::Set(Utc::now().fixed_offset()),
+                        ..Default::default()
+                    }
+                    .update(&txn)
+                    .await?;
+                    txn.commit().await?;
+                    self.refresh_depth().await?;
+                    return Err(error);
+                }
+            }
+        }
+        self.refresh_depth().await?;
+        Ok(())
+    }
+}
+
+/// Delay before the next retry after `consecutive_failures` failed requests in a row.
+fn retry_delay_secs(config: &LangfuseDeadLetterQueueConfig, consecutive_failures: u64) -> u64 {
+    let exponent = consecutive_failures.saturating_sub(1).min(32) as u32;
+    config
+        .retry_initial_delay_seconds
+        .saturating_mul(1 << exponent)
+        .min(config.retry_max_delay_seconds)
+}
+
+/// Start the loop that stores the buffered batches of the Langfuse client and retries the
+/// queued ones once they are due.
+///
+/// On startup, checks whether Langfuse is reachable and logs the number of queued batches.
+/// Returns whether the loop was started, which it isn't if the client has no dead-letter queue.
+pub fn start_langfuse_dead_letter_retry(app_state: &AppState) -> bool {
+    let client = app_state.langfuse_client.clone();
+    let Some(dead_letters) = client.dead_letters().cloned() else {
+        return false;
+    };
+    app_state
+        .background_tasks
+        .start_maintenance_task(LANGFUSE_DEAD_LETTER_RETRY_TASK, async move {
+            match client.check_connectivity().await {
+                Ok(()) => tracing::info!("Langfuse is reachable"),
+                Err(err) => tracing::warn!(error = %err, "Langfuse is not reachable"),
+            }
+            match dead_letters.refresh_depth().await {
+                Ok(0) => {}
+                Ok(queued) => tracing::warn!(
+                    queued,
+                    "Langfuse dead-letter queue holds undelivered batches, retrying them"
+                ),
+                Err(err) => tracing::warn!(error = %err, "Failed to count queued Langfuse batches"),
+            }
+
+            let mut interval = tokio::time::interval(PERSIST_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(err) = dead_letters.persist().await {
+                    tracing::warn!(error = %err, "Failed to store undelivered Langfuse batches");
+                    continue;
+                }
+                if dead_letters.is_empty()
+                    || dead_letters.next_retry_at(&client.health().state()) > Utc::now()
+                {
+                    continue;
+                }
+                if let Err(err) = dead_letters.deliver(&client).await {
+                    tracing::warn!(
+                        error = %err,
+                        queued = dead_letters.depth(),
+                        "Failed to deliver queued Langfuse batches"
+                    );
+                }
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_maximum() {
+        let config = LangfuseDeadLetterQueueConfig {
+            max_batches: 10,
+            retry_initial_delay_seconds: 5,
+            retry_max_delay_seconds: 60,
+        };
+        let delays: Vec<u64> = (1..=6)
+            .map(|failures| retry_delay_secs(&config, failures))
+            .collect();
+        assert_eq!(delays, vec![5, 10, 20, 40, 60, 60]);
+        assert_eq!(retry_delay_secs(&config, u64::MAX), 60);
+    }
+}
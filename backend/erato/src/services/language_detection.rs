@@ -0,0 +1,270 @@
+//! Detection of the language a user message is written in, and resolution of
+//! the language the assistant should respond in.
+//!
+//! Detection is intentionally cheap: it only looks at a bounded prefix of the
+//! message and skips messages that are too short to be classified reliably.
+
+use crate::config::MessageLanguageDetectionConfig;
+use crate::models::message::{DetectedLanguage, ResponseLanguage, ResponseLanguageSource};
+use whatlang::Lang;
+
+/// Detect the language of a user message.
+///
+/// Returns `None` if detection is disabled, the message is shorter than
+/// `min_chars`, or the language could not be mapped to an ISO 639-1 code.
+pub fn detect_message_language(
+    text: &str,
+    config: &MessageLanguageDetectionConfig,
+) -> Option<DetectedLanguage> {
+    if !config.enabled {
+        return None;
+    }
+
+    let text = text.trim();
+    if text.chars().count() < config.min_chars {
+        return None;
+    }
+
+    let info = whatlang::detect(truncate_to_char_boundary(text, config.max_bytes))?;
+    Some(DetectedLanguage {
+        code: iso_639_1_code(info.lang())?.to_string(),
+        confidence: info.confidence(),
+    })
+}
+
+/// Resolve the language the assistant should respond in.
+///
+/// Precedence: explicit request override, then the detected language of the
+/// latest user message (only if its confidence exceeds `min_confidence`), then
+/// the chat-level setting, then the profile preference.
+pub fn resolve_response_language(
+    request_override: Option<&str>,
+    detected_language: Option<&DetectedLanguage>,
+    min_confidence: f64,
+    chat_language: Option<&str>,
+    profile_language: &str,
+) -> ResponseLanguage {
+    if let Some(code) = request_override.and_then(normalize_language_code) {
+        return ResponseLanguage {
+            code,
+            source: ResponseLanguageSource::RequestOverride,
+            confidence: None,
+        };
+    }
+
+    if let Some(detected) = detected_language.filter(|d| d.confidence > min_confidence) {
+        return ResponseLanguage {
+            code: detected.code.clone(),
+            source: ResponseLanguageSource::DetectedMessageLanguage,
+            confidence: Some(detected.confidence),
+        };
+    }
+
+    if let Some(code) = chat_language.and_then(normalize_language_code) {
+        return ResponseLanguage {
+            code,
+            source: ResponseLanguageSource::ChatSetting,
+            confidence: None,
+        };
+    }
+
+    ResponseLanguage {
+        code: profile_language.to_string(),
+        source: ResponseLanguageSource::ProfilePreference,
+        confidence: None,
+    }
+}
+
+/// Parse the response language of a chat setting, accepting language tags like `de-AT` for their
+/// primary subtag. Returns `None` if the primary subtag isn't a two or three letter code.
+pub fn parse_language_code(raw: &str) -> Option<String> {
+    normalize_language_code(raw).filter(|code| {
+        (2..=3).contains(&code.len()) && code.bytes().all(|b| b.is_ascii_lowercase())
+    })
+}
+
+/// Normalize a language tag like `de-AT` or `pt_BR` to its primary subtag.
+fn normalize_language_code(raw: &str) -> Option<String> {
+    raw.split(['-', '_'])
+        .next()
+        .map(str::trim)
+        .filter(|code| !code.is_empty())
+        .map(str::to_ascii_lowercase)
+}
+
+pub(crate) fn truncate_to_char_boundary(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Map a detected language to the ISO 639-1 code used for response languages.
+///
+/// Only languages that can be rendered as a response-language hint are mapped.
+pub(crate) fn iso_639_1_code(lang: Lang) -> Option<&'static str> {
+    let code = match lang {
+        Lang::Eng => "en",
+        Lang::Deu => "de",
+        Lang::Spa => "es",
+        Lang::Fra => "fr",
+        Lang::Ita => "it",
+        Lang::Por => "pt",
+        Lang::Nld => "nl",
+        Lang::Pol => "pl",
+        Lang::Rus => "ru",
+        Lang::Cmn => "zh",
+        Lang::Jpn => "ja",
+        Lang::Kor => "ko",
+        Lang::Swe => "sv",
+        Lang::Nob => "no",
+        Lang::Dan => "da",
+        Lang::Fin => "fi",
+        Lang::Tur => "tr",
+        Lang::Vie => "vi",
+        Lang::Tha => "th",
+        Lang::Ara => "ar",
+        Lang::Hin => "hi",
+        Lang::Ben => "bn",
+        Lang::Pes => "fa",
+        Lang::Ind => "id",
+        Lang::Ukr => "uk",
+        Lang::Ces => "cs",
+        Lang::Ell => "el",
+        Lang::Heb => "he",
+        Lang::Ron => "ro",
+        Lang::Hun => "hu",
+        _ => return None,
+    };
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: [(&str, &str); 5] = [
+        (
+            "en",
+            "Could you please summarize the attached report and highlight the most important risks for the next quarter?",
+        ),
+        (
+            "de",
+            "Kannst du mir bitte den angehängten Bericht zusammenfassen und die wichtigsten Risiken für das nächste Quartal hervorheben?",
+        ),
+        (
+            "fr",
+            "Pourriez-vous résumer le rapport ci-joint et souligner les risques les plus importants pour le prochain trimestre ?",
+        ),
+        (
+            "es",
+            "¿Podrías resumir el informe adjunto y destacar los riesgos más importantes para el próximo trimestre, por favor?",
+        ),
+        (
+            "pl",
+            "Czy możesz streścić załączony raport i wskazać najważniejsze zagrożenia na następny kwartał?",
+        ),
+    ];
+
+    fn detected(code: &str, confidence: f64) -> DetectedLanguage {
+        DetectedLanguage {
+            code: code.to_string(),
+            confidence,
+        }
+    }
+
+    #[test]
+    fn detects_fixture_languages() {
+        let config = MessageLanguageDetectionConfig::default();
+        for (expected_code, text) in FIXTURES {
+            let language = detect_message_language(text, &config)
+                .unwrap_or_else(|| panic!("Expected a language for {expected_code} fixture"));
+            assert_eq!(language.code, expected_code);
+            assert!(
+                language.confidence > config.min_confidence,
+                "Expected a confident detection for {expected_code}, got {}",
+                language.confidence
+            );
+        }
+    }
+
+    #[test]
+    fn skips_short_messages_and_disabled_detection() {
+        let config = MessageLanguageDetectionConfig::default();
+        assert_eq!(detect_message_language("Danke schön!", &config), None);
+
+        let disabled = MessageLanguageDetectionConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        assert_eq!(detect_message_language(FIXTURES[1].1, &disabled), None);
+    }
+
+    #[test]
+    fn only_analyzes_the_first_max_bytes() {
+        let config = MessageLanguageDetectionConfig {
+            max_bytes: FIXTURES[1].1.len(),
+            ..Default::default()
+        };
+        let text = format!("{} {}", FIXTURES[1].1, FIXTURES[0].1.repeat(10));
+        assert_eq!(
+            detect_message_language(&text, &config).map(|l| l.code),
+            Some("de".to_string())
+        );
+        assert_eq!(truncate_to_char_boundary("zäh", 2), "z");
+    }
+
+    #[test]
+    fn detected_language_is_only_used_above_threshold() {
+        let below = resolve_response_language(None, Some(&detected("de", 0.4)), 0.5, None, "en");
+        assert_eq!(below.code, "en");
+        assert_eq!(below.source, ResponseLanguageSource::ProfilePreference);
+
+        let at = resolve_response_language(None, Some(&detected("de", 0.5)), 0.5, None, "en");
+        assert_eq!(at.source, ResponseLanguageSource::ProfilePreference);
+
+        let above = resolve_response_language(None, Some(&detected("de", 0.9)), 0.5, None, "en");
+        assert_eq!(above.code, "de");
+        assert_eq!(
+            above.source,
+            ResponseLanguageSource::DetectedMessageLanguage
+        );
+        assert_eq!(above.confidence, Some(0.9));
+    }
+
+    #[test]
+    fn resolves_response_language_by_precedence() {
+        let detected_fr = detected("fr", 0.9);
+
+        let language =
+            resolve_response_language(Some("es-MX"), Some(&detected_fr), 0.5, Some("pl"), "en");
+        assert_eq!(language.code, "es");
+        assert_eq!(language.source, ResponseLanguageSource::RequestOverride);
+
+        let language =
+            resolve_response_language(Some(" "), Some(&detected_fr), 0.5, Some("pl"), "en");
+        assert_eq!(language.code, "fr");
+
+        let language = resolve_response_language(None, None, 0.5, Some("pl"), "en");
+        assert_eq!(language.code, "pl");
+        assert_eq!(language.source, ResponseLanguageSource::ChatSetting);
+
+        let language = resolve_response_language(None, None, 0.5, None, "de");
+        assert_eq!(language.code, "de");
+        assert_eq!(language.source, ResponseLanguageSource::ProfilePreference);
+    }
+
+    #[test]
+    fn parses_language_codes_of_chat_settings() {
+        assert_eq!(parse_language_code("de"), Some("de".to_string()));
+        assert_eq!(parse_language_code(" pt_BR "), Some("pt".to_string()));
+        assert_eq!(parse_language_code("FIL"), Some("fil".to_string()));
+        assert_eq!(parse_language_code("german"), None);
+        assert_eq!(parse_language_code("d3"), None);
+        assert_eq!(parse_language_code(""), None);
+    }
+}
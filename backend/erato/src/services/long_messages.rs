@@ -0,0 +1,240 @@
+//! Handling of long messages, like log files that are pasted into the message box.
+//!
+//! The text of a submitted message that exceeds `chat.long_messages.max_inline_bytes` is stored
+//! as a text file that is attached to the message, and the message only keeps a preview of it.
+//! This keeps the `user_message_saved` event small, as some proxies drop large SSE frames, while
+//! the file is included in the prompt like any uploaded file.
+//!
+//! Independent of that, text parts that exceed `chat.long_messages.max_text_part_bytes` are split
+//! into several text parts when a message is stored, so that no single JSON string of a message
+//! grows without bounds. Parts are split after a line break where possible.
+
+use crate::config::LongMessagesConfig;
+use crate::models::file_upload::create_file_upload;
+use crate::models::message::{ContentPart, ContentPartText, MessageSchema};
+use crate::policy::engine::PolicyEngine;
+use crate::policy::types::Subject;
+use crate::state::AppState;
+use eyre::{Report, WrapErr};
+use sea_orm::prelude::Uuid;
+use serde_json::Value as JsonValue;
+
+/// Name of the file the text of a long message is attached as.
+pub const AUTO_ATTACHED_MESSAGE_FILENAME: &str = "pasted-message.txt";
+
+/// A submitted message whose text was attached as a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoAttachedMessage {
+    /// The beginning of the text, which is kept in the message
+    pub preview: String,
+    /// The file upload that holds the full text
+    pub file_upload_id: Uuid,
+}
+
+/// Whether the text of a submitted message is attached as a file.
+pub fn exceeds_inline_size(config: &LongMessagesConfig, text: &str) -> bool {
+    config.auto_attach && text.len() as u64 > config.max_inline_bytes
+}
+
+/// The beginning of a text that was attached as a file, which is kept in the message.
+pub fn inline_preview(text: &str, preview_chars: usize) -> String {
+    let mut preview: String = text.chars().take(preview_chars).collect();
+    preview.push('…');
+    preview
+}
+
+/// Store the text of a submitted message as a text file attached to the chat, if it exceeds
+/// `chat.long_messages.max_inline_bytes`.
+///
+/// Returns `None` if the text fits, or attaching is disabled.
+pub async fn auto_attach_long_message(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    chat_id: &Uuid,
+    text: &str,
+) -> Result<Option<AutoAttachedMessage>, Report> {
+    let config = &app_state.config.chat.long_messages;
+    if !exceeds_inline_size(config, text) {
+        return Ok(None);
+    }
+
+    let file_storage_path = Uuid::new_v4().to_string();
+    let mut writer = app_state
+        .default_file_storage_provider()
+        .upload_file_writer(&file_storage_path, Some("text/plain"))
+        .await
+        .wrap_err("Failed to create writer for the attached message")?;
+    writer
+        .write(text.as_bytes().to_vec())
+        .await
+        .wrap_err("Failed to write the attached message")?;
+    writer
+        .close()
+        .await
+        .wrap_err("Failed to close the writer of the attached message")?;
+
+    let file_upload = create_file_upload(
+        &app_state.db,
+        policy,
+        subject,
+        chat_id,
+        AUTO_ATTACHED_MESSAGE_FILENAME.to_string(),
+        app_state.default_file_storage_provider_id(),
+        file_storage_path,
+    )
+    .await?;
+    // The file must enter the policy data before the client fetches it
+    app_state.global_policy_engine.invalidate_data().await;
+
+    Ok(Some(AutoAttachedMessage {
+        preview: inline_preview(text, config.preview_chars),
+        file_upload_id: file_upload.id,
+    }))
+}
+
+/// The position at which the first part of a text that exceeds `max_bytes` ends.
+///
+/// This is right after the last line break of the first `max_bytes`, unless that would make the
+/// part less than half as long, and the last character boundary otherwise.
+fn split_index(text: &str, max_bytes: usize) -> usize {
+    let mut index = max_bytes.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    if index == 0 {
+        // A single character that is longer than `max_bytes`
+        return text.chars().next().map_or(text.len(), char::len_utf8);
+    }
+    match text[..index].rfind('\n') {
+        Some(newline) if newline + 1 >= index / 2 => newline + 1,
+        _ => index,
+    }
+}
+
+/// Split a text that exceeds `max_bytes` into parts of at most `max_bytes` bytes.
+fn split_text(text: &str, max_bytes: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while rest.len() > max_bytes {
+        let (part, remainder) = rest.split_at(split_index(rest, max_bytes));
+        parts.push(part.to_string());
+        rest = remainder;
+    }
+    if !rest.is_empty() {
+        parts.push(rest.to_string());
+    }
+    parts
+}
+
+/// Split the text parts of the content of a message that exceed
+/// `chat.long_messages.max_text_part_bytes`.
+///
+/// Returns the content unchanged if splitting is disabled.
+pub fn split_long_text_parts(
+    config: &LongMessagesConfig,
+    content: Vec<ContentPart>,
+) -> Vec<ContentPart> {
+    if !config.split_text_parts {
+        return content;
+    }
+    let max_bytes = usize::try_from(config.max_text_part_bytes)
+        .unwrap_or(usize::MAX)
+        .max(1);
+
+    let mut split_content = Vec::with_capacity(content.len());
+    for part in content {
+        match part {
+            ContentPart::Text(part) if part.text.len() > max_bytes => {
+                split_content.extend(
+                    split_text(&part.text, max_bytes)
+                        .into_iter()
+                        .map(|text| ContentPart::Text(ContentPartText { text })),
+                );
+            }
+            other => split_content.push(other),
+        }
+    }
+    split_content
+}
+
+/// Like [`split_long_text_parts`], for the raw JSON of a message that is about to be stored.
+pub fn split_long_text_parts_in_raw_message(
+    config: &LongMessagesConfig,
+    raw_message: JsonValue,
+) -> Result<JsonValue, Report> {
+    if !config.split_text_parts {
+        return Ok(raw_message);
+    }
+    let mut message = MessageSchema::validate(&raw_message)?;
+    message.content = split_long_text_parts(config, message.content);
+    message.to_json()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> ContentPart {
+        ContentPart::Text(ContentPartText {
+            text: text.to_string(),
+        })
+    }
+
+    fn texts(content: &[ContentPart]) -> Vec<&str> {
+        content
+            .iter()
+            .map(|part| match part {
+                ContentPart::Text(part) => part.text.as_str(),
+                _ => panic!("Expected a text part"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_splits_after_line_breaks() {
+        let config = LongMessagesConfig {
+            max_text_part_bytes: 10,
+            ..Default::default()
+        };
+        let content = vec![text("line one\nline two\n"), text("ok")];
+
+        assert_eq!(
+            texts(&split_long_text_parts(&config, content)),
+            vec!["line one\n", "line two\n", "ok"]
+        );
+    }
+
+    #[test]
+    fn test_splits_long_lines_at_character_boundaries() {
+        let config = LongMessagesConfig {
+            max_text_part_bytes: 5,
+            ..Default::default()
+        };
+        let content = vec![text("ééééé")];
+
+        assert_eq!(
+            texts(&split_long_text_parts(&config, content.clone())),
+            vec!["éé", "éé", "é"]
+        );
+        assert_eq!(split_text("€", 1), vec!["€"]);
+
+        let disabled = LongMessagesConfig {
+            split_text_parts: false,
+            ..config
+        };
+        assert_eq!(split_long_text_parts(&disabled, content.clone()), content);
+    }
+
+    #[test]
+    fn test_inline_size_and_preview() {
+        let config = LongMessagesConfig {
+            max_inline_bytes: 4,
+            ..Default::default()
+        };
+
+        assert!(!exceeds_inline_size(&config, "four"));
+        assert!(exceeds_inline_size(&config, "fives"));
+        assert_eq!(inline_preview("héllo world", 5), "héllo…");
+    }
+}
@@ -0,0 +1,221 @@
+//! Resumable maintenance tasks that process all messages in batches.
+//!
+//! A task walks over the messages in the order of `(created_at, id)`. After every batch, the
+//! position of the last processed message and the counters of the batch are persisted in
+//! `maintenance_task_progress`, so that the task resumes where it stopped after a restart. The
+//! progress row is locked for the duration of a batch, so that several instances running the
+//! same task process disjoint batches.
+//!
+//! A task only implements the processing of a single batch, which [`run_maintenance_task`] repeats
+//! until a batch is empty.
+
+use crate::metrics_constants::{
+    POSTGRES_QUERY_GET_MAINTENANCE_TASK_PROGRESS, POSTGRES_QUERY_LOCK_MAINTENANCE_TASK_PROGRESS,
+    POSTGRES_QUERY_RESET_MAINTENANCE_TASK_PROGRESS, POSTGRES_QUERY_SET_MAINTENANCE_TASK_ERROR,
+    POSTGRES_QUERY_START_MAINTENANCE_TASK, POSTGRES_QUERY_UPDATE_MAINTENANCE_TASK_PROGRESS,
+};
+use crate::query_metrics::named_statement_from_sql_and_values;
+use crate::services::background_tasks::BackgroundTaskManager;
+use eyre::{Report, eyre};
+use sea_orm::prelude::*;
+use sea_orm::{ConnectionTrait, DatabaseConnection, FromQueryResult};
+use std::future::Future;
+use std::time::Duration;
+
+/// Progress of a resumable maintenance task.
+#[derive(Debug, Clone, FromQueryResult)]
+pub struct MaintenanceTaskProgress {
+    pub task_name: String,
+    pub cursor_created_at: Option<DateTimeWithTimeZone>,
+    pub cursor_id: Option<Uuid>,
+    pub processed_messages: i64,
+    pub migrated_messages: i64,
+    pub bytes_saved: i64,
+    pub last_error: Option<String>,
+    pub started_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+    pub completed_at: Option<DateTimeWithTimeZone>,
+}
+
+/// Outcome of migrating a single batch of messages of a maintenance task.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaintenanceTaskBatch {
+    pub processed_messages: u64,
+    pub migrated_messages: u64,
+    pub bytes_saved: i64,
+}
+
+/// Get the progress of a maintenance task, if it was ever started.
+pub async fn get_maintenance_task_progress(
+    conn: &DatabaseConnection,
+    task_name: &str,
+) -> Result<Option<MaintenanceTaskProgress>, Report> {
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_GET_MAINTENANCE_TASK_PROGRESS,
+        "SELECT * FROM maintenance_task_progress WHERE task_name = $1",
+        [task_name.into()],
+    );
+    Ok(MaintenanceTaskProgress::find_by_statement(statement)
+        .one(conn)
+        .await?)
+}
+
+/// Start a run of a maintenance task, resuming an unfinished run and starting over if the last
+/// run completed.
+async fn start_maintenance_task_run(
+    db: &DatabaseConnection,
+    task_name: &str,
+) -> Result<(), Report> {
+    if get_maintenance_task_progress(db, task_name)
+        .await?
+        .is_some_and(|progress| progress.completed_at.is_some())
+    {
+        db.execute_raw(named_statement_from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            POSTGRES_QUERY_RESET_MAINTENANCE_TASK_PROGRESS,
+            "DELETE FROM maintenance_task_progress WHERE task_name = $1",
+            [task_name.into()],
+        ))
+        .await?;
+    }
+    db.execute_raw(named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_START_MAINTENANCE_TASK,
+        r#"
+        INSERT INTO maintenance_task_progress (task_name) VALUES ($1)
+        ON CONFLICT (task_name) DO UPDATE SET last_error = NULL, updated_at = now()
+        "#,
+        [task_name.into()],
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Save the error that stopped a run of a maintenance task. Failing to save it is only logged.
+async fn save_maintenance_task_error(db: &DatabaseConnection, task_name: &str, err: &Report) {
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_SET_MAINTENANCE_TASK_ERROR,
+        r#"
+        UPDATE maintenance_task_progress
+        SET last_error = $2, updated_at = now()
+        WHERE task_name = $1
+        "#,
+        [task_name.into(), err.to_string().into()],
+    );
+    if let Err(save_err) = db.execute_raw(statement).await {
+        tracing::warn!(error = %save_err, task_name, "Failed to save the error of a maintenance task");
+    }
+}
+
+/// Lock the progress of a started maintenance task for the duration of a batch.
+pub(crate) async fn lock_maintenance_task_progress<C: ConnectionTrait>(
+    txn: &C,
+    task_name: &str,
+) -> Result<MaintenanceTaskProgress, Report> {
+    MaintenanceTaskProgress::find_by_statement(named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_LOCK_MAINTENANCE_TASK_PROGRESS,
+        "SELECT * FROM maintenance_task_progress WHERE task_name = $1 FOR UPDATE",
+        [task_name.into()],
+    ))
+    .one(txn)
+    .await?
+    .ok_or_else(|| eyre!("The maintenance task {task_name} has not been started"))
+}
+
+/// Persist the position after a batch of a maintenance task and add up its counters. An empty
+/// batch marks the run as completed.
+pub(crate) async fn update_maintenance_task_progress<C: ConnectionTrait>(
+    txn: &C,
+    task_name: &str,
+    cursor: Option<(DateTimeWithTimeZone, Uuid)>,
+    batch: &MaintenanceTaskBatch,
+) -> Result<(), Report> {
+    let (cursor_created_at, cursor_id) = cursor.unzip();
+    txn.execute_raw(named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_UPDATE_MAINTENANCE_TASK_PROGRESS,
+        r#"
+        UPDATE maintenance_task_progress
+        SET cursor_created_at = COALESCE($2, cursor_created_at),
+            cursor_id = COALESCE($3, cursor_id),
+            processed_messages = processed_messages + $4,
+            migrated_messages = migrated_messages + $5,
+            bytes_saved = bytes_saved + $6,
+            completed_at = CASE WHEN $4 = 0 THEN now() END,
+            updated_at = now()
+        WHERE task_name = $1
+        "#,
+        [
+            task_name.into(),
+            cursor_created_at.into(),
+            cursor_id.into(),
+            (batch.processed_messages as i64).into(),
+            (batch.migrated_messages as i64).into(),
+            batch.bytes_saved.into(),
+        ],
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Run the remaining batches of a maintenance task, waiting `batch_interval_ms` between batches.
+///
+/// Resumes from the persisted position of an unfinished run, and starts over if the last run
+/// completed. `run_batch` processes the next batch after the persisted position, and the run
+/// ends with the first empty batch. An error stops the run and is saved in its progress.
+pub async fn run_maintenance_task<F, Fut>(
+    db: &DatabaseConnection,
+    task_name: &str,
+    batch_interval_ms: u64,
+    mut run_batch: F,
+) -> Result<MaintenanceTaskProgress, Report>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<MaintenanceTaskBatch, Report>>,
+{
+    start_maintenance_task_run(db, task_name).await?;
+
+    loop {
+        match run_batch().await {
+            Ok(batch) if batch.processed_messages == 0 => break,
+            Ok(_) => {}
+            Err(err) => {
+                save_maintenance_task_error(db, task_name, &err).await;
+                return Err(err);
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(batch_interval_ms)).await;
+    }
+
+    get_maintenance_task_progress(db, task_name)
+        .await?
+        .ok_or_else(|| eyre!("Progress of the maintenance task {task_name} disappeared"))
+}
+
+/// Run a maintenance task in the background, see [`run_maintenance_task`], and log its outcome.
+///
+/// Returns `false` if the task is already running.
+pub fn start_maintenance_task<Fut>(
+    background_tasks: &BackgroundTaskManager,
+    task_name: &'static str,
+    run: Fut,
+) -> bool
+where
+    Fut: Future<Output = Result<MaintenanceTaskProgress, Report>> + Send + 'static,
+{
+    background_tasks.start_maintenance_task(task_name, async move {
+        match run.await {
+            Ok(progress) => tracing::info!(
+                task_name,
+                processed_messages = progress.processed_messages,
+                migrated_messages = progress.migrated_messages,
+                bytes_saved = progress.bytes_saved,
+                "Completed maintenance task"
+            ),
+            Err(err) => tracing::warn!(task_name, error = %err, "Maintenance task failed"),
+        }
+    })
+}
@@ -0,0 +1,349 @@
+//! Normalization of the markdown of generated answers before they are stored (see
+//! `chat.normalize_markdown` in the config).
+//!
+//! Answers that are cut off, e.g. by an error of the chat provider, often end inside a code block,
+//! so that everything rendered after it becomes code, or with emphasis that is never closed. The
+//! rules only touch the end of a text, and are intentionally conservative:
+//! - a code fence that is not closed is closed, replacing a partial closing fence
+//! - `*` and `**` emphasis left open on the last line is closed
+//! - a trailing backslash that escapes nothing, or a numeric character reference without its `;`,
+//!   is removed
+//!
+//! Content inside code and balanced markdown is never modified. The streamed deltas are not
+//! normalized, only the text of the completed message.
+
+use crate::models::message::ContentPart;
+use crate::services::renderable_blocks::{Fence, line_offsets, opening_fence, strip_fence_indent};
+
+/// Characters after which a `*` delimiter run can open emphasis, besides whitespace.
+const EMPHASIS_OPENING_PUNCTUATION: &[char] = &['(', '[', '{', '"', '\''];
+
+/// Normalize the markdown of the text parts of a message. Returns whether any text was changed.
+pub fn normalize_markdown_content(content: &mut [ContentPart]) -> bool {
+    let mut changed = false;
+    for part in content.iter_mut() {
+        if let ContentPart::Text(text_part) = part
+            && let Some(normalized) = normalize_markdown(&text_part.text)
+        {
+            text_part.text = normalized;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Normalize the markdown of a single text. Returns `None` if the text is left unchanged.
+pub fn normalize_markdown(text: &str) -> Option<String> {
+    let normalized = match text_end(text)? {
+        TextEnd::CodeBlock { fence, last_line } => close_code_block(text, &fence, last_line),
+        TextEnd::Line { start } => close_last_line(text, start)?,
+    };
+    (normalized != text).then_some(normalized)
+}
+
+enum TextEnd<'a> {
+    /// The text ends inside a fenced code block. `last_line` is the start offset and content of
+    /// the last line, unless that is the opening fence.
+    CodeBlock {
+        fence: Fence,
+        last_line: Option<(usize, &'a str)>,
+    },
+    /// The text ends with the line starting at the given offset, outside of fenced code.
+    Line { start: usize },
+}
+
+/// Find where a text ends. `None` for an empty text.
+fn text_end(text: &str) -> Option<TextEnd<'_>> {
+    let lines = line_offsets(text);
+    let mut open_fence: Option<(Fence, usize)> = None;
+    for (index, &(_, line)) in lines.iter().enumerate() {
+        open_fence = match open_fence {
+            Some((fence, _)) if fence.is_closed_by(line) => None,
+            Some(open) => Some(open),
+            None => opening_fence(line).map(|fence| (fence, index)),
+        };
+    }
+    let last_index = lines.len().checked_sub(1)?;
+    Some(match open_fence {
+        Some((fence, opening_index)) => TextEnd::CodeBlock {
+            fence,
+            last_line: (last_index > opening_index).then_some(lines[last_index]),
+        },
+        None => TextEnd::Line {
+            start: lines[last_index].0,
+        },
+    })
+}
+
+/// Close the code block a text ends in. A last line that is the start of the closing fence, i.e.
+/// shorter than the opening fence, is completed instead.
+fn close_code_block(text: &str, fence: &Fence, last_line: Option<(usize, &str)>) -> String {
+    let closing_fence = fence.marker.to_string().repeat(fence.length);
+    if let Some((start, line)) = last_line
+        && !text.ends_with('\n')
+        && opening_fence(line).is_none()
+        && let Some(rest) = strip_fence_indent(line)
+        && !rest.is_empty()
+        && rest.chars().all(|c| c == fence.marker)
+    {
+        return format!("{}{closing_fence}", &text[..start]);
+    }
+    let separator = if text.ends_with('\n') { "" } else { "\n" };
+    format!("{text}{separator}{closing_fence}")
+}
+
+/// Remove a partial escape sequence at the end of a text, and close the emphasis left open on
+/// its last line. Returns `None` if the last line can't be changed safely.
+fn close_last_line(text: &str, start: usize) -> Option<String> {
+    let line = &text[start..];
+    // Indented code, or the continuation of a list item, and table rows
+    if line.starts_with("    ") || line.starts_with('\t') || line.trim_start().starts_with('|') {
+        return None;
+    }
+
+    let stripped = strip_partial_escape(text);
+    let open_emphasis = open_emphasis(&stripped[start.min(stripped.len())..])?;
+    let mut normalized = stripped.to_string();
+    let content_end = normalized.trim_end().len();
+    let closing_delimiters: String = open_emphasis
+        .iter()
+        .rev()
+        .map(|&length| "*".repeat(length))
+        .collect();
+    normalized.insert_str(content_end, &closing_delimiters);
+    Some(normalized)
+}
+
+/// Remove an escape sequence cut off at the end of a text: a backslash that escapes nothing, or a
+/// numeric character reference without its closing `;`.
+fn strip_partial_escape(text: &str) -> &str {
+    let backslashes = text.len() - text.trim_end_matches('\\').len();
+    if backslashes % 2 == 1 {
+        return &text[..text.len() - 1];
+    }
+    if let Some(index) = text.rfind("&#") {
+        let reference = &text[index + 2..];
+        let is_partial = match reference.strip_prefix(['x', 'X']) {
+            Some(hex_digits) => hex_digits.chars().all(|c| c.is_ascii_hexdigit()),
+            None => reference.chars().all(|c| c.is_ascii_digit()),
+        };
+        if is_partial {
+            return &text[..index];
+        }
+    }
+    text
+}
+
+/// Lengths of the `*` delimiter runs left open at the end of a line, innermost last.
+///
+/// A run opens emphasis if it is followed by a non-whitespace character and preceded by
+/// whitespace or opening punctuation, so list bullets, multiplications and intraword asterisks
+/// are never treated as openers. Returns `None` if the line ends inside an inline code span.
+fn open_emphasis(line: &str) -> Option<Vec<usize>> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut open: Vec<usize> = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        match chars[index] {
+            // Escaped character
+            '\\' => index += 2,
+            '`' => {
+                // Inline code span, closed by a backtick run of the same length
+                let length = run_length(&chars, index);
+                let closing = (index + length..chars.len()).find(|&position| {
+                    chars[position] == '`'
+                        && chars[position - 1] != '`'
+                        && run_length(&chars, position) == length
+                })?;
+                index = closing + length;
+            }
+            '*' => {
+                let length = run_length(&chars, index);
+                let before = index.checked_sub(1).map(|position| chars[position]);
+                let after = chars.get(index + length).copied();
+                let can_close = before.is_some_and(|c| !c.is_whitespace());
+                let can_open = after.is_some_and(|c| !c.is_whitespace())
+                    && before.is_none_or(|c| {
+                        c.is_whitespace() || EMPHASIS_OPENING_PUNCTUATION.contains(&c)
+                    });
+
+                let mut remaining = length;
+                if can_close {
+                    while remaining > 0 {
+                        let Some(innermost) = open.last_mut() else {
+                            break;
+                        };
+                        if *innermost <= remaining {
+                            remaining -= *innermost;
+                            open.pop();
+                        } else {
+                            *innermost -= remaining;
+                            remaining = 0;
+                        }
+                    }
+                }
+                if can_open && remaining > 0 && remaining <= 3 {
+                    open.push(remaining);
+                }
+                index += length;
+            }
+            _ => index += 1,
+        }
+    }
+    Some(open)
+}
+
+/// Number of consecutive occurrences of the character at `index`.
+fn run_length(chars: &[char], index: usize) -> usize {
+    chars[index..]
+        .iter()
+        .take_while(|&&c| c == chars[index])
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::ContentPartText;
+
+    fn text_part(text: &str) -> ContentPart {
+        ContentPart::Text(ContentPartText {
+            text: text.to_string(),
+        })
+    }
+
+    /// Broken markdown, and the expected normalized text.
+    const BROKEN: &[(&str, &str)] = &[
+        // Unterminated code fences
+        (
+            "Here is the code:\n\n```rust\nfn main() {\n",
+            "Here is the code:\n\n```rust\nfn main() {\n```",
+        ),
+        (
+            "```python\nprint('hi')",
+            "```python\nprint('hi')\n```",
+        ),
+        ("~~~\ncode", "~~~\ncode\n~~~"),
+        ("Done.\n\n```", "Done.\n\n```\n```"),
+        ("  ```sh\nls -la", "  ```sh\nls -la\n```"),
+        (
+            "````markdown\n```rust\nlet x = 1;\n```\n",
+            "````markdown\n```rust\nlet x = 1;\n```\n````",
+        ),
+        (
+            "```js\nconst a = '*';\nconst b = \"\\",
+            "```js\nconst a = '*';\nconst b = \"\\\n```",
+        ),
+        // Partial closing fences
+        (
+            "```python\nprint('hi')\n``",
+            "```python\nprint('hi')\n```",
+        ),
+        ("~~~~\ncode\n~~", "~~~~\ncode\n~~~~"),
+        // Unclosed emphasis
+        ("This is **important", "This is **important**"),
+        ("This is *very **important", "This is *very **important***"),
+        (
+            "**Note:** this is *emphasized",
+            "**Note:** this is *emphasized*",
+        ),
+        ("- **Step 1\n", "- **Step 1**\n"),
+        ("(*aside", "(*aside*"),
+        ("***Bold italic", "***Bold italic***"),
+        ("**Bold *and italic**", "**Bold *and italic***"),
+        ("Trailing space **bold  ", "Trailing space **bold**  "),
+        (
+            "```\ncode\n```\n\nAnd **finally",
+            "```\ncode\n```\n\nAnd **finally**",
+        ),
+        // Partial escape sequences
+        ("Trailing backslash \\", "Trailing backslash "),
+        ("Three backslashes \\\\\\", "Three backslashes \\\\"),
+        ("An emoji &#x1F6", "An emoji "),
+        ("Copyright &#16", "Copyright "),
+        ("Cut at &#", "Cut at "),
+        ("**Bold with escape\\", "**Bold with escape**"),
+    ];
+
+    /// Markdown that must be left as is.
+    const UNCHANGED: &[&str] = &[
+        "",
+        "Plain text.",
+        "```rust\nfn main() {}\n```",
+        "```rust\nfn main() {}\n```\n\nDone.",
+        "````\n```\nnested\n```\n````",
+        "```\nunclosed **bold\n```",
+        "Some **bold** and *italic* text.",
+        "***Bold italic***",
+        "Compute 2 * 3 and 4*5.",
+        "* item one\n* item two",
+        "***",
+        "Footnote*",
+        "Unfinished bold **",
+        "snake_case_name and __init__",
+        "Use `**kwargs` here",
+        "Use ``code with ` inside`` here",
+        "Inline code `not closed **bold",
+        "    indented **code",
+        "| **a | b |",
+        "Escaped \\*not emphasis",
+        "Line break\\\\",
+        "R&D and &amp; &#169;",
+        "**bold\n\nnew paragraph",
+        "Price: $2^*$ units",
+    ];
+
+    #[test]
+    fn normalizes_broken_markdown() {
+        for (text, expected) in BROKEN {
+            assert_eq!(
+                normalize_markdown(text).as_deref(),
+                Some(*expected),
+                "Unexpected normalization of {text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn leaves_valid_markdown_unchanged() {
+        for text in UNCHANGED {
+            assert_eq!(
+                normalize_markdown(text),
+                None,
+                "Expected {text:?} to be unchanged"
+            );
+        }
+    }
+
+    #[test]
+    fn normalization_is_idempotent() {
+        for (_, normalized) in BROKEN {
+            assert_eq!(
+                normalize_markdown(normalized),
+                None,
+                "Expected {normalized:?} to be normalized already"
+            );
+        }
+    }
+
+    #[test]
+    fn normalizes_each_text_part() {
+        let mut content = vec![
+            text_part("```rust\nfn main() {"),
+            text_part("All **good**."),
+            text_part("Then **this"),
+        ];
+
+        assert!(normalize_markdown_content(&mut content));
+        assert_eq!(
+            content,
+            vec![
+                text_part("```rust\nfn main() {\n```"),
+                text_part("All **good**."),
+                text_part("Then **this**"),
+            ]
+        );
+
+        assert!(!normalize_markdown_content(&mut content));
+    }
+}
@@ -1,7 +1,7 @@
 use crate::config::AppConfig;
 use crate::db::entity::prelude::FileUploads;
 use crate::services::file_storage::SharepointContext;
-use crate::services::mcp_session_manager::{ManagedTool, McpSessionManager};
+use crate::services::mcp_session_manager::{ManagedTool, McpSessionManager, McpSessionStats};
 use crate::state::AppState;
 use base64::{Engine as _, engine::general_purpose::STANDARD};
 use eyre::{OptionExt, Report, WrapErr, eyre};
@@ -11,7 +11,7 @@ use genai::chat::ToolName as GenaiToolName;
 use rmcp::model::CallToolRequestParams;
 use sea_orm::EntityTrait;
 use sea_orm::prelude::Uuid;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -82,6 +82,16 @@ impl McpServers {
             .await;
     }
 
+    /// Close the MCP sessions of a chat, e.g. because it was archived
+    pub async fn evict_chat_sessions(&self, chat_id: Uuid) {
+        self.session_manager.evict_chat_sessions(chat_id).await;
+    }
+
+    /// Number and age of the active sessions of each configured MCP server
+    pub async fn session_stats(&self) -> HashMap<String, McpSessionStats> {
+        self.session_manager.session_stats().await
+    }
+
     /// List all available tools for a specific chat
     pub async fn list_tools(
         &self,
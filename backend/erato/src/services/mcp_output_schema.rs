@@ -0,0 +1,237 @@
+//! Validation of MCP tool results against the output schema declared by the tool.
+//!
+//! What happens with a result that doesn't match the schema is configured per MCP server via
+//! `on_schema_violation`. In `coerce` mode, the result is filtered down to the fields that match
+//! the schema, which only handles the common cases of undeclared fields and optional fields of
+//! the wrong type. Results that still don't match afterwards are treated as in `error` mode.
+
+use serde_json::Value;
+
+/// Maximum nesting depth that is followed when coercing a result.
+const MAX_COERCE_DEPTH: usize = 32;
+
+/// Validate a tool result against an output schema, and return a description of every violation.
+///
+/// Schemas that can't be compiled are logged and skipped, so a broken schema of a tool doesn't
+/// break all of its calls.
+pub fn validate_tool_output(schema: &Value, output: &Value) -> Vec<String> {
+    let validator = match jsonschema::validator_for(schema) {
+        Ok(validator) => validator,
+        Err(err) => {
+            tracing::warn!(error = %err, "Skipping validation against invalid MCP output schema");
+            return Vec::new();
+        }
+    };
+    validator
+        .iter_errors(output)
+        .map(|error| {
+            let path = error.instance_path.to_string();
+            if path.is_empty() {
+                format!("(root): {error}")
+            } else {
+                format!("{path}: {error}")
+            }
+        })
+        .collect()
+}
+
+/// Drop the fields of a tool result that don't match the output schema, in place.
+///
+/// Properties that are not declared in `properties` are dropped, unless the schema explicitly
+/// allows additional properties. Optional properties whose value doesn't match their declared
+/// `type` are dropped as well. Required properties are never dropped, so the result may still
+/// be invalid afterwards.
+pub fn coerce_tool_output(schema: &Value, output: &mut Value) {
+    coerce_value(schema, schema, output, 0);
+}
+
+fn coerce_value(root: &Value, schema: &Value, value: &mut Value, depth: usize) {
+    if depth > MAX_COERCE_DEPTH {
+        return;
+    }
+    let Some(schema) = resolve_ref(root, schema) else {
+        return;
+    };
+
+    match value {
+        Value::Object(object) => {
+            let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+                return;
+            };
+            let required: Vec<&str> = schema
+                .get("required")
+                .and_then(Value::as_array)
+                .map(|required| required.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            let allows_additional = matches!(
+                schema.get("additionalProperties"),
+                Some(Value::Bool(true)) | Some(Value::Object(_))
+            );
+
+            object.retain(|key, field| match properties.get(key) {
+                Some(property_schema) => {
+                    required.contains(&key.as_str())
+                        || matches_declared_type(root, property_schema, field)
+                }
+                None => allows_additional,
+            });
+            for (key, field) in object.iter_mut() {
+                if let Some(property_schema) = properties.get(key) {
+                    coerce_value(root, property_schema, field, depth + 1);
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(items_schema) = schema.get("items") {
+                for item in items {
+                    coerce_value(root, items_schema, item, depth + 1);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Resolve a local `$ref` (`#/...`) of a schema, following chains of references.
+fn resolve_ref<'a>(root: &'a Value, mut schema: &'a Value) -> Option<&'a Value> {
+    for _ in 0..MAX_COERCE_DEPTH {
+        let Some(reference) = schema.get("$ref").and_then(Value::as_str) else {
+            return Some(schema);
+        };
+        schema = root.pointer(reference.strip_prefix('#')?)?;
+    }
+    None
+}
+
+/// Whether a value matches the `type` of a schema. Schemas without a `type` match everything.
+fn matches_declared_type(root: &Value, schema: &Value, value: &Value) -> bool {
+    let Some(schema) = resolve_ref(root, schema) else {
+        return true;
+    };
+    match schema.get("type") {
+        Some(Value::String(declared)) => matches_type(declared, value),
+        Some(Value::Array(declared)) => declared
+            .iter()
+            .filter_map(Value::as_str)
+            .any(|declared| matches_type(declared, value)),
+        _ => true,
+    }
+}
+
+fn matches_type(declared: &str, value: &Value) -> bool {
+    match declared {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64()
+                || value.is_u64()
+                || value.as_f64().is_some_and(|number| number.fract() == 0.0)
+        }
+        "string" => value.is_string(),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn weather_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "conditions": { "type": "string" },
+                "humidity_percent": { "type": ["integer", "null"], "minimum": 0 },
+                "location": { "$ref": "#/$defs/Location" }
+            },
+            "required": ["conditions"],
+            "$defs": {
+                "Location": {
+                    "type": "object",
+                    "properties": {
+                        "city": { "type": "string" }
+                    },
+                    "required": ["city"]
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn validate_tool_output_reports_violations_with_paths() {
+        let violations = validate_tool_output(
+            &weather_schema(),
+            &json!({
+                "conditions": "sunny",
+                "humidity_percent": "high",
+                "location": {}
+            }),
+        );
+
+        assert_eq!(violations.len(), 2, "{violations:?}");
+        assert!(
+            violations
+                .iter()
+                .any(|violation| violation.starts_with("/humidity_percent: "))
+        );
+        assert!(
+            violations
+                .iter()
+                .any(|violation| violation.starts_with("/location: "))
+        );
+        assert!(
+            validate_tool_output(&weather_schema(), &json!({ "conditions": "sunny" })).is_empty()
+        );
+    }
+
+    #[test]
+    fn validate_tool_output_skips_invalid_schemas() {
+        let schema = json!({ "type": 12 });
+
+        assert!(validate_tool_output(&schema, &json!({ "anything": true })).is_empty());
+    }
+
+    #[test]
+    fn coerce_tool_output_drops_undeclared_and_mistyped_optional_fields() {
+        let schema = weather_schema();
+        let mut output = json!({
+            "conditions": "sunny",
+            "humidity_percent": "high",
+            "debug_trace": ["step 1"],
+            "location": { "city": "Berlin", "district": "Mitte" }
+        });
+
+        coerce_tool_output(&schema, &mut output);
+
+        assert_eq!(
+            output,
+            json!({
+                "conditions": "sunny",
+                "location": { "city": "Berlin" }
+            })
+        );
+        assert!(validate_tool_output(&schema, &output).is_empty());
+    }
+
+    #[test]
+    fn coerce_tool_output_keeps_required_fields_and_allowed_additional_properties() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "conditions": { "type": "string" }
+            },
+            "required": ["conditions"],
+            "additionalProperties": true
+        });
+        let mut output = json!({ "conditions": 21, "extra": "kept" });
+
+        coerce_tool_output(&schema, &mut output);
+
+        assert_eq!(output, json!({ "conditions": 21, "extra": "kept" }));
+        assert_eq!(validate_tool_output(&schema, &output).len(), 1);
+    }
+}
@@ -1,19 +1,24 @@
 use crate::config::{
     AppConfig, McpServerAuthenticationConfig, McpServerConfig, McpServerForwardedCredential,
 };
-use crate::metrics::report_mcp_active_sessions_for_server;
+use crate::metrics::{report_mcp_active_sessions_for_server, report_mcp_oldest_session_age};
 use crate::services::mcp_manager::{McpRequestAuthContext, ToolDiscoveryResult};
 use crate::services::mcp_oauth::resolve_oauth_access_token;
-use crate::services::mcp_transports::{EmptyClientHandler, create_mcp_service};
+use crate::services::mcp_transports::{
+    FORWARDED_CREDENTIAL_UNAVAILABLE, McpClientHandler, create_mcp_service,
+    forwarded_credential_unavailable,
+};
 use eyre::{Report, eyre};
 use futures::future::join_all;
-use rmcp::model::{CallToolRequestParams, CallToolResult, Tool};
+use rmcp::model::{CallToolRequestParams, CallToolResult, ClientRequest, PingRequest, Tool};
 use rmcp::service::{Peer, RoleClient, RunningService};
 use sea_orm::prelude::Uuid;
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex as AsyncMutex, RwLock};
 use tokio::task::JoinHandle;
 use tracing::{debug, info, warn};
 
@@ -24,22 +29,53 @@ pub enum McpServerConnectionStatus {
     NeedsAuthentication,
 }
 
+/// Tool list of a session, and when it was fetched
+#[derive(Debug)]
+struct CachedTools {
+    tools: Vec<Tool>,
+    fetched_at: SystemTime,
+}
+
+impl CachedTools {
+    fn new(tools: Vec<Tool>) -> Self {
+        Self {
+            tools,
+            fetched_at: SystemTime::now(),
+        }
+    }
+}
+
 /// Represents a single MCP session for a specific chat and server
+///
+/// Sessions are shared between the generations of a chat, so all operations take `&self` and
+/// the mutable state is kept behind locks.
 #[derive(Debug)]
 struct McpSession {
     /// The rmcp running service (must be kept alive to maintain the connection)
-    _service: RunningService<RoleClient, EmptyClientHandler>,
+    _service: RunningService<RoleClient, McpClientHandler>,
     /// The rmcp peer instance for making requests
     peer: Peer<RoleClient>,
+    /// Client handler of the service, which records tool list change notifications
+    handler: McpClientHandler,
     /// Server ID this session is for
-    #[allow(dead_code)]
     server_id: String,
     /// Cached list of available tools
-    tools: Vec<Tool>,
+    tools: AsyncMutex<CachedTools>,
+    /// Whether the server sends `notifications/tools/list_changed`, in which case the cached
+    /// tool list is reused until such a notification is received
+    announces_tool_list_changes: bool,
+    /// How long the cached tool list is reused if the server doesn't announce changes
+    tool_list_ttl: Duration,
+    /// When the session was established
+    created_at: SystemTime,
     /// Timestamp of last activity (tool call or tool list request)
-    last_activity: SystemTime,
+    last_activity: Mutex<SystemTime>,
+    /// Timestamp of the last successful request to the server, including keep-alive pings
+    last_contact: Mutex<SystemTime>,
     /// Maximum idle time before this session is evicted
     max_idle_duration: Duration,
+    /// Fingerprint of the per-user credential the session was opened with, if any
+    credential_fingerprint: Option<String>,
 }
 
 impl McpSession {
@@ -48,7 +84,8 @@ impl McpSession {
         server_id: String,
         config: &McpServerConfig,
         auth_context: &McpRequestAuthContext<'_>,
-        default_max_idle_seconds: u64,
+        timeouts: &SessionTimeouts,
+        credential_fingerprint: Option<String>,
     ) -> Result<Self, Report> {
         debug!(
             server_id = %server_id,
@@ -56,7 +93,8 @@ impl McpSession {
             "Creating MCP service connection"
         );
 
-        let service = create_mcp_service(&server_id, config, auth_context)
+        let handler = McpClientHandler::default();
+        let service = create_mcp_service(&server_id, config, auth_context, handler.clone())
             .await
             .map_err(|e| eyre!("Failed to create MCP service: {}", e))?;
 
@@ -67,6 +105,11 @@ impl McpSession {
 
         // Get a peer reference from the running service
         let peer = service.peer().clone();
+        let announces_tool_list_changes = peer
+            .peer_info()
+            .and_then(|info| info.capabilities.tools.as_ref())
+            .and_then(|tools| tools.list_changed)
+            .unwrap_or(false);
 
         // Fetch the initial tool list using Default::default() as shown in the example
         let tools_result = peer
@@ -77,52 +120,105 @@ impl McpSession {
         debug!(
             server_id = %server_id,
             num_tools = tools_result.tools.len(),
+            announces_tool_list_changes,
             "Successfully fetched tools from MCP server"
         );
 
+        let now = SystemTime::now();
         Ok(Self {
             _service: service, // Keep the service alive!
             peer,
+            handler,
             server_id,
-            tools: tools_result.tools,
-            last_activity: SystemTime::now(),
+            tools: AsyncMutex::new(CachedTools::new(tools_result.tools)),
+            announces_tool_list_changes,
+            tool_list_ttl: timeouts.tool_list_ttl,
+            created_at: now,
+            last_activity: Mutex::new(now),
+            last_contact: Mutex::new(now),
             max_idle_duration: Duration::from_secs(
                 config
                     .max_session_idle_seconds
-                    .unwrap_or(default_max_idle_seconds),
+                    .unwrap_or(timeouts.default_max_idle_seconds),
             ),
+            credential_fingerprint,
         })
     }
 
     /// Update the last activity timestamp
-    fn touch(&mut self) {
-        self.last_activity = SystemTime::now();
+    fn touch(&self) {
+        let now = SystemTime::now();
+        *self.last_activity.lock().unwrap() = now;
+        *self.last_contact.lock().unwrap() = now;
     }
 
     /// Check if this session has been inactive for longer than the given duration
     fn is_inactive(&self) -> bool {
-        if let Ok(elapsed) = self.last_activity.elapsed() {
+        if let Ok(elapsed) = self.last_activity.lock().unwrap().elapsed() {
             elapsed > self.max_idle_duration
         } else {
             false
         }
     }
 
-    /// Refresh the tools list from the server
-    async fn refresh_tools(&mut self) -> Result<(), Report> {
-        let tools_result = self
-            .peer
+    /// Check if the server wasn't contacted for at least the keep-alive interval
+    fn needs_keep_alive(&self, interval: Duration) -> bool {
+        self.last_contact
+            .lock()
+            .unwrap()
+            .elapsed()
+            .is_ok_and(|elapsed| elapsed >= interval)
+    }
+
+    fn age(&self) -> Duration {
+        self.created_at.elapsed().unwrap_or_default()
+    }
+
+    /// The tools of the server, from the cache unless it is stale
+    async fn tools(&self) -> Result<Vec<Tool>, Report> {
+        let mut cached = self.tools.lock().await;
+        // Take the flag before fetching, so that a change announced during the fetch isn't lost
+        let tools_changed = self.handler.take_tools_changed();
+        let expired = !self.announces_tool_list_changes
+            && cached
+                .fetched_at
+                .elapsed()
+                .is_ok_and(|elapsed| elapsed > self.tool_list_ttl);
+        if tools_changed || expired {
+            debug!(
+                server_id = %self.server_id,
+                tools_changed,
+                "Fetching changed or expired tool list of MCP session"
+            );
+            if let Err(e) = Self::fetch_tools(&self.peer, &mut cached).await {
+                self.handler.mark_tools_changed();
+                return Err(e);
+            }
+        }
+
+        self.touch();
+        Ok(cached.tools.clone())
+    }
+
+    async fn fetch_tools(peer: &Peer<RoleClient>, cached: &mut CachedTools) -> Result<(), Report> {
+        let tools_result = peer
             .list_tools(Default::default())
             .await
             .map_err(|e| eyre!("Failed to refresh tools: {}", e))?;
+        *cached = CachedTools::new(tools_result.tools);
+        Ok(())
+    }
 
-        self.tools = tools_result.tools;
+    /// Refresh the tools list from the server
+    async fn refresh_tools(&self) -> Result<(), Report> {
+        let mut cached = self.tools.lock().await;
+        Self::fetch_tools(&self.peer, &mut cached).await?;
         self.touch();
         Ok(())
     }
 
     /// Call a tool on this session
-    async fn call_tool(&mut self, params: CallToolRequestParams) -> Result<CallToolResult, Report> {
+    async fn call_tool(&self, params: CallToolRequestParams) -> Result<CallToolResult, Report> {
         let result = self
             .peer
             .call_tool(params)
@@ -132,30 +228,113 @@ impl McpSession {
         self.touch();
         Ok(result)
     }
+
+    /// Ping the server, without counting as activity of the session
+    async fn ping(&self) -> Result<(), Report> {
+        self.peer
+            .send_request(ClientRequest::PingRequest(PingRequest::default()))
+            .await
+            .map_err(|e| eyre!("Failed to ping MCP server: {}", e))?;
+
+        *self.last_contact.lock().unwrap() = SystemTime::now();
+        Ok(())
+    }
+}
+
+/// Idle, keep-alive and tool list durations that apply to all sessions
+#[derive(Debug, Clone)]
+struct SessionTimeouts {
+    /// Global default max idle time for MCP sessions (seconds)
+    default_max_idle_seconds: u64,
+    /// Interval of the keep-alive pings, if enabled
+    keep_alive_interval: Option<Duration>,
+    /// How long tool lists are cached for servers that don't announce changes
+    tool_list_ttl: Duration,
+}
+
+impl SessionTimeouts {
+    fn new(config: &AppConfig) -> Self {
+        let global = &config.mcp_servers_global;
+        Self {
+            default_max_idle_seconds: global.max_session_idle_seconds.unwrap_or(60 * 60),
+            keep_alive_interval: Some(global.session_keep_alive_seconds.unwrap_or(120))
+                .filter(|seconds| *seconds > 0)
+                .map(Duration::from_secs),
+            tool_list_ttl: Duration::from_secs(global.tool_list_ttl_seconds.unwrap_or(5 * 60)),
+        }
+    }
+
+    /// How often the background task checks for inactive sessions and sends keep-alive pings
+    fn maintenance_interval(&self) -> Duration {
+        let interval = Duration::from_secs(60);
+        self.keep_alive_interval
+            .map_or(interval, |keep_alive| keep_alive.min(interval))
+    }
+}
+
+/// Statistics of the sessions of an MCP server
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct McpSessionStats {
+    pub active_sessions: usize,
+    pub oldest_session_age: Option<Duration>,
+}
+
+/// The per-user credential that is sent to an MCP server.
+struct SessionCredential {
+    /// Identity the session is scoped to, so that users never share a session.
+    /// This is the user ID if known, and the credential fingerprint otherwise.
+    identity: String,
+    /// SHA-256 fingerprint of the credential, used to detect refreshed credentials without
+    /// keeping the secret itself around.
+    fingerprint: String,
 }
 
-type SessionAuthKey = Option<String>;
-type SessionKey = (Uuid, String, SessionAuthKey);
+impl SessionCredential {
+    fn new(user_id: Option<Uuid>, credential: &str) -> Self {
+        let fingerprint = credential_fingerprint(credential);
+        Self {
+            identity: user_id
+                .map(|user_id| user_id.to_string())
+                .unwrap_or_else(|| fingerprint.clone()),
+            fingerprint,
+        }
+    }
+}
+
+fn credential_fingerprint(credential: &str) -> String {
+    Sha256::digest(credential.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+type SessionIdentity = Option<String>;
+type SessionKey = (Uuid, String, SessionIdentity);
+type SessionMap = HashMap<SessionKey, Arc<McpSession>>;
 
 /// Manages MCP sessions on a per-chat basis
+///
+/// A session is established lazily when a chat first uses a server, and is then reused by the
+/// following generations of the chat until it is idle for too long or the chat is archived.
 #[derive(Debug)]
 pub struct McpSessionManager {
-    /// Map of (chat_id, server_id) to active sessions
-    sessions: Arc<RwLock<HashMap<SessionKey, McpSession>>>,
+    /// Map of (chat_id, server_id, identity) to active sessions
+    sessions: Arc<RwLock<SessionMap>>,
     /// Server configurations from the app config
     server_configs: HashMap<String, McpServerConfig>,
-    /// Global default max idle time for MCP sessions (seconds)
-    default_max_idle_seconds: u64,
+    /// Idle, keep-alive and tool list durations
+    timeouts: SessionTimeouts,
     /// Handle to the background cleanup task
     _cleanup_task: JoinHandle<()>,
 }
 
 impl McpSessionManager {
-    async fn session_auth_key(
+    /// The per-user credential of a request, for servers that don't use a static credential.
+    async fn session_credential(
         server_id: &str,
         config: &McpServerConfig,
         auth_context: &McpRequestAuthContext<'_>,
-    ) -> Result<SessionAuthKey, Report> {
+    ) -> Result<Option<SessionCredential>, Report> {
         match &config.authentication {
             McpServerAuthenticationConfig::None | McpServerAuthenticationConfig::Fixed { .. } => {
                 Ok(None)
@@ -163,12 +342,12 @@ impl McpSessionManager {
             McpServerAuthenticationConfig::Forwarded { forwarded } => match forwarded.credential {
                 McpServerForwardedCredential::AccessToken => auth_context
                     .access_token
-                    .map(|token| Some(format!("access_token:{token}")))
-                    .ok_or_else(|| eyre!("Missing forwarded access token for MCP server")),
+                    .map(|token| Some(SessionCredential::new(auth_context.user_id, token)))
+                    .ok_or_else(|| forwarded_credential_unavailable(server_id, "access token")),
                 McpServerForwardedCredential::OidcIdToken => auth_context
                     .oidc_token
-                    .map(|token| Some(format!("oidc_id_token:{token}")))
-                    .ok_or_else(|| eyre!("Missing forwarded OIDC token for MCP server")),
+                    .map(|token| Some(SessionCredential::new(auth_context.user_id, token)))
+                    .ok_or_else(|| forwarded_credential_unavailable(server_id, "OIDC ID token")),
             },
             McpServerAuthenticationConfig::Oauth2 { oauth2 } => {
                 let app_state = auth_context.app_state.ok_or_else(|| {
@@ -181,18 +360,25 @@ impl McpSessionManager {
                     resolve_oauth_access_token(app_state, user_id, server_id, config, oauth2)
                         .await
                         .map_err(|error| eyre!(error.to_string()))?;
-                Ok(Some(format!("oauth2:{token}")))
+                Ok(Some(SessionCredential::new(Some(user_id), &token)))
             }
         }
     }
 
+    async fn session_identity(
+        server_id: &str,
+        config: &McpServerConfig,
+        auth_context: &McpRequestAuthContext<'_>,
+    ) -> Result<SessionIdentity, Report> {
+        Ok(Self::session_credential(server_id, config, auth_context)
+            .await?
+            .map(|credential| credential.identity))
+    }
+
     /// Create a new session manager with the given configuration
     pub fn new(config: &AppConfig) -> Self {
         let server_configs = config.mcp_servers.clone();
-        let default_max_idle_seconds = config
-            .mcp_servers_global
-            .max_session_idle_seconds
-            .unwrap_or(60 * 60);
+        let timeouts = SessionTimeouts::new(config);
         let sessions = Arc::new(RwLock::new(HashMap::new()));
         let configured_server_ids: Vec<String> = server_configs.keys().cloned().collect();
 
@@ -200,103 +386,187 @@ impl McpSessionManager {
         let cleanup_task = {
             let sessions = Arc::clone(&sessions);
             let configured_server_ids = configured_server_ids.clone();
+            let timeouts = timeouts.clone();
             tokio::spawn(async move {
-                Self::run_cleanup_task(sessions, configured_server_ids).await;
+                Self::run_cleanup_task(sessions, configured_server_ids, timeouts).await;
             })
         };
 
         Self {
             sessions,
             server_configs,
-            default_max_idle_seconds,
+            timeouts,
             _cleanup_task: cleanup_task,
         }
     }
 
+    fn session_stats_per_server(sessions: &SessionMap) -> HashMap<String, McpSessionStats> {
+        let mut stats_per_server: HashMap<String, McpSessionStats> = HashMap::new();
+
+        for ((_, server_id, _), session) in sessions {
+            let stats = stats_per_server.entry(server_id.clone()).or_default();
+            stats.active_sessions += 1;
+            stats.oldest_session_age = stats.oldest_session_age.max(Some(session.age()));
+        }
+
+        stats_per_server
+    }
+
     fn update_active_session_metrics(
-        sessions: &HashMap<SessionKey, McpSession>,
+        sessions: &SessionMap,
         configured_server_ids: impl Iterator<Item = String>,
     ) {
-        let mut active_sessions_per_server = HashMap::new();
-
-        for (_, server_id, _) in sessions.keys() {
-            *active_sessions_per_server
-                .entry(server_id.as_str())
-                .or_insert(0usize) += 1;
-        }
+        let stats_per_server = Self::session_stats_per_server(sessions);
 
         for server_id in configured_server_ids {
-            let active_session_count = active_sessions_per_server
+            let stats = stats_per_server
                 .get(server_id.as_str())
-                .copied()
-                .unwrap_or(0);
-            report_mcp_active_sessions_for_server(&server_id, active_session_count);
+                .cloned()
+                .unwrap_or_default();
+            report_mcp_active_sessions_for_server(&server_id, stats.active_sessions);
+            report_mcp_oldest_session_age(&server_id, stats.oldest_session_age);
         }
     }
 
-    /// Background task that periodically cleans up inactive sessions
+    /// Background task that periodically cleans up inactive sessions and keeps the others alive
     async fn run_cleanup_task(
-        sessions: Arc<RwLock<HashMap<SessionKey, McpSession>>>,
+        sessions: Arc<RwLock<SessionMap>>,
         configured_server_ids: Vec<String>,
+        timeouts: SessionTimeouts,
     ) {
-        let mut interval = tokio::time::interval(Duration::from_secs(60)); // Run every 60 seconds
+        let mut interval = tokio::time::interval(timeouts.maintenance_interval());
 
         loop {
             interval.tick().await;
 
-            let mut sessions_guard = sessions.write().await;
-            let initial_count = sessions_guard.len();
+            {
+                let mut sessions_guard = sessions.write().await;
+                let initial_count = sessions_guard.len();
+
+                // Remove inactive sessions
+                sessions_guard.retain(|(chat_id, server_id, _), session| {
+                    let should_keep = !session.is_inactive();
+                    if !should_keep {
+                        info!(
+                            chat_id = %chat_id,
+                            server_id = %server_id,
+                            "Expiring inactive MCP session"
+                        );
+                    }
+                    should_keep
+                });
 
-            // Remove inactive sessions
-            sessions_guard.retain(|(chat_id, server_id, _), session| {
-                let should_keep = !session.is_inactive();
-                if !should_keep {
+                let removed_count = initial_count - sessions_guard.len();
+                if removed_count > 0 {
                     info!(
-                        chat_id = %chat_id,
-                        server_id = %server_id,
-                        "Expiring inactive MCP session"
+                        "Cleaned up {} inactive MCP sessions, {} remaining",
+                        removed_count,
+                        sessions_guard.len()
                     );
                 }
-                should_keep
-            });
-
-            let removed_count = initial_count - sessions_guard.len();
-            if removed_count > 0 {
-                Self::update_active_session_metrics(
-                    &sessions_guard,
-                    configured_server_ids.iter().cloned(),
-                );
+            }
+
+            if let Some(keep_alive_interval) = timeouts.keep_alive_interval {
+                Self::keep_sessions_alive(&sessions, keep_alive_interval).await;
+            }
+
+            // Also reported without changes, as the ages of the sessions grow
+            Self::update_active_session_metrics(
+                &*sessions.read().await,
+                configured_server_ids.iter().cloned(),
+            );
+        }
+    }
+
+    /// Ping the servers of the sessions without recent contact, and evict the sessions that
+    /// fail the ping. They are re-established on their next use.
+    async fn keep_sessions_alive(sessions: &RwLock<SessionMap>, keep_alive_interval: Duration) {
+        let due_sessions: Vec<(SessionKey, Arc<McpSession>)> = sessions
+            .read()
+            .await
+            .iter()
+            .filter(|(_, session)| session.needs_keep_alive(keep_alive_interval))
+            .map(|(key, session)| (key.clone(), Arc::clone(session)))
+            .collect();
+        if due_sessions.is_empty() {
+            return;
+        }
+
+        let results = join_all(
+            due_sessions
+                .iter()
+                .map(|(_, session)| async move { session.ping().await }),
+        )
+        .await;
+
+        let mut sessions_guard = sessions.write().await;
+        for ((key, session), result) in due_sessions.into_iter().zip(results) {
+            let Err(e) = result else {
+                continue;
+            };
+            if Self::remove_if_current(&mut sessions_guard, &key, &session) {
                 info!(
-                    "Cleaned up {} inactive MCP sessions, {} remaining",
-                    removed_count,
-                    sessions_guard.len()
+                    chat_id = %key.0,
+                    server_id = %key.1,
+                    error = %e,
+                    "Evicted MCP session that failed a keep-alive ping"
                 );
             }
         }
     }
 
+    /// Remove the session of a key, unless it was already replaced by a newer session
+    fn remove_if_current(
+        sessions: &mut SessionMap,
+        key: &SessionKey,
+        session: &Arc<McpSession>,
+    ) -> bool {
+        if sessions
+            .get(key)
+            .is_some_and(|current| Arc::ptr_eq(current, session))
+        {
+            sessions.remove(key);
+            true
+        } else {
+            false
+        }
+    }
+
     /// Get or create a session for the given chat and server
     async fn get_or_create_session(
         &self,
         chat_id: Uuid,
         server_id: &str,
         auth_context: &McpRequestAuthContext<'_>,
-    ) -> Result<SessionKey, Report> {
+    ) -> Result<(SessionKey, Arc<McpSession>), Report> {
         let config = self
             .server_configs
             .get(server_id)
             .ok_or_else(|| eyre!("MCP server '{}' not found in configuration", server_id))?;
+        let credential = Self::session_credential(server_id, config, auth_context).await?;
+        let credential_fingerprint = credential
+            .as_ref()
+            .map(|credential| credential.fingerprint.clone());
         let key = (
             chat_id,
             server_id.to_string(),
-            Self::session_auth_key(server_id, config, auth_context).await?,
+            credential.map(|credential| credential.identity),
         );
 
         // Check if session already exists
         {
             let sessions_guard = self.sessions.read().await;
-            if sessions_guard.contains_key(&key) {
-                return Ok(key);
+            if let Some(session) = sessions_guard.get(&key) {
+                if session.credential_fingerprint == credential_fingerprint {
+                    return Ok((key, Arc::clone(session)));
+                }
+                // The session was opened with a credential that has since been refreshed, so
+                // reconnect with the current one. The new session replaces the stale one.
+                debug!(
+                    chat_id = %chat_id,
+                    server_id = %server_id,
+                    "Credential of MCP session changed, reconnecting"
+                );
             }
         }
 
@@ -306,16 +576,25 @@ impl McpSessionManager {
             "Creating new MCP session"
         );
 
-        let session = McpSession::new(
-            server_id.to_string(),
-            config,
-            auth_context,
-            self.default_max_idle_seconds,
-        )
-        .await?;
+        let session = Arc::new(
+            McpSession::new(
+                server_id.to_string(),
+                config,
+                auth_context,
+                &self.timeouts,
+                credential_fingerprint,
+            )
+            .await?,
+        );
 
         let mut sessions_guard = self.sessions.write().await;
-        sessions_guard.insert(key.clone(), session);
+        // Another generation of the chat may have established a session in the meantime
+        if let Some(existing) = sessions_guard.get(&key)
+            && existing.credential_fingerprint == session.credential_fingerprint
+        {
+            return Ok((key, Arc::clone(existing)));
+        }
+        sessions_guard.insert(key.clone(), Arc::clone(&session));
         Self::update_active_session_metrics(&sessions_guard, self.server_configs.keys().cloned());
 
         info!(
@@ -324,13 +603,53 @@ impl McpSessionManager {
             "Created new MCP session"
         );
 
-        Ok(key)
+        Ok((key, session))
+    }
+
+    /// Run an operation on the session of a chat and server
+    /// Retries once with a new session if the session has become invalid, e.g. because it was
+    /// expired by the server, so that in-flight generations don't notice the reconnection
+    async fn with_session<T, F, Fut>(
+        &self,
+        chat_id: Uuid,
+        server_id: &str,
+        auth_context: &McpRequestAuthContext<'_>,
+        operation: F,
+    ) -> Result<T, Report>
+    where
+        F: Fn(Arc<McpSession>) -> Fut,
+        Fut: Future<Output = Result<T, Report>>,
+    {
+        let (key, session) = self
+            .get_or_create_session(chat_id, server_id, auth_context)
+            .await?;
+
+        match operation(Arc::clone(&session)).await {
+            Ok(result) => Ok(result),
+            Err(e) if Self::is_session_invalid_error(&e) => {
+                warn!(
+                    chat_id = %chat_id,
+                    server_id = %server_id,
+                    error = %e,
+                    "MCP session appears to be invalid, recreating and retrying"
+                );
+
+                self.invalidate_session(&key, &session).await;
+
+                let (_, session) = self
+                    .get_or_create_session(chat_id, server_id, auth_context)
+                    .await?;
+                operation(session).await
+            }
+            Err(e) => Err(e),
+        }
     }
 
     /// Remove a session from the cache (used when a session becomes invalid)
-    async fn invalidate_session(&self, key: &SessionKey) {
+    /// Sessions that already replaced the invalid one are kept.
+    async fn invalidate_session(&self, key: &SessionKey, session: &Arc<McpSession>) {
         let mut sessions_guard = self.sessions.write().await;
-        if sessions_guard.remove(key).is_some() {
+        if Self::remove_if_current(&mut sessions_guard, key, session) {
             Self::update_active_session_metrics(
                 &sessions_guard,
                 self.server_configs.keys().cloned(),
@@ -343,14 +662,54 @@ impl McpSessionManager {
         }
     }
 
+    /// Remove the session of a key, e.g. after a connectivity check
+    async fn remove_session(&self, key: &SessionKey) {
+        let mut sessions_guard = self.sessions.write().await;
+        if sessions_guard.remove(key).is_some() {
+            Self::update_active_session_metrics(
+                &sessions_guard,
+                self.server_configs.keys().cloned(),
+            );
+        }
+    }
+
+    /// Close all sessions of a chat, e.g. because it was archived
+    pub async fn evict_chat_sessions(&self, chat_id: Uuid) {
+        let mut sessions_guard = self.sessions.write().await;
+        let initial_count = sessions_guard.len();
+        sessions_guard.retain(|(session_chat_id, _, _), _| *session_chat_id != chat_id);
+
+        let removed_count = initial_count - sessions_guard.len();
+        if removed_count > 0 {
+            Self::update_active_session_metrics(
+                &sessions_guard,
+                self.server_configs.keys().cloned(),
+            );
+            info!(
+                chat_id = %chat_id,
+                removed_count,
+                "Evicted MCP sessions of chat"
+            );
+        }
+    }
+
+    /// Number and age of the active sessions of each configured server
+    pub async fn session_stats(&self) -> HashMap<String, McpSessionStats> {
+        let mut stats_per_server = Self::session_stats_per_server(&*self.sessions.read().await);
+        for server_id in self.server_configs.keys() {
+            stats_per_server.entry(server_id.clone()).or_default();
+        }
+        stats_per_server
+    }
+
     pub async fn invalidate_oauth_sessions_for_token(&self, server_id: &str, access_token: &str) {
-        let session_auth_key = format!("oauth2:{access_token}");
+        let fingerprint = credential_fingerprint(access_token);
         let mut sessions_guard = self.sessions.write().await;
         let initial_count = sessions_guard.len();
 
-        sessions_guard.retain(|(_, existing_server_id, existing_auth_key), _| {
+        sessions_guard.retain(|(_, existing_server_id, _), session| {
             !(existing_server_id == server_id
-                && existing_auth_key.as_deref() == Some(session_auth_key.as_str()))
+                && session.credential_fingerprint.as_deref() == Some(fingerprint.as_str()))
         });
 
         if sessions_guard.len() != initial_count {
@@ -415,19 +774,20 @@ impl McpSessionManager {
             .cloned()
             .collect();
 
-        let creation_results = join_all(server_ids.iter().map(|server_id| async move {
+        let discovery_results = join_all(server_ids.iter().map(|server_id| async move {
             (
                 server_id.clone(),
-                self.get_or_create_session(chat_id, server_id, auth_context)
-                    .await,
+                self.with_session(chat_id, server_id, auth_context, |session| async move {
+                    session.tools().await
+                })
+                .await,
             )
         }))
         .await;
 
-        let sessions_guard = self.sessions.read().await;
-        for (server_id, creation_result) in creation_results {
-            let key = match creation_result {
-                Ok(key) => key,
+        for (server_id, discovery_result) in discovery_results {
+            let tools = match discovery_result {
+                Ok(tools) => tools,
                 Err(e) => {
                     if Self::is_missing_forwarded_credential_error(&e) {
                         debug!(
@@ -466,14 +826,10 @@ impl McpSessionManager {
                     continue;
                 }
             };
-            if let Some(session) = sessions_guard.get(&key) {
-                for tool in &session.tools {
-                    all_tools.push(ManagedTool {
-                        server_id: server_id.clone(),
-                        tool: tool.clone(),
-                    });
-                }
-            }
+            all_tools.extend(tools.into_iter().map(|tool| ManagedTool {
+                server_id: server_id.clone(),
+                tool,
+            }));
         }
 
         debug!(
@@ -492,8 +848,7 @@ impl McpSessionManager {
 
     fn is_missing_forwarded_credential_error(error: &Report) -> bool {
         let error_msg = error.to_string();
-        error_msg.contains("Missing forwarded access token for MCP server")
-            || error_msg.contains("Missing forwarded OIDC token for MCP server")
+        error_msg.contains(FORWARDED_CREDENTIAL_UNAVAILABLE)
     }
 
     fn is_auth_denied_error(error: &Report) -> bool {
@@ -528,60 +883,11 @@ impl McpSessionManager {
         params: CallToolRequestParams,
         auth_context: &McpRequestAuthContext<'_>,
     ) -> Result<CallToolResult, Report> {
-        // Try calling the tool with the current session
-        match self
-            .call_tool_internal(chat_id, server_id, params.clone(), auth_context)
-            .await
-        {
-            Ok(result) => Ok(result),
-            Err(e) if Self::is_session_invalid_error(&e) => {
-                let config = self.server_configs.get(server_id).ok_or_else(|| {
-                    eyre!("MCP server '{}' not found in configuration", server_id)
-                })?;
-                let key = (
-                    chat_id,
-                    server_id.to_string(),
-                    Self::session_auth_key(server_id, config, auth_context).await?,
-                );
-                warn!(
-                    chat_id = %chat_id,
-                    server_id = %server_id,
-                    error = %e,
-                    "MCP session appears to be invalid, recreating and retrying"
-                );
-
-                // Invalidate the session
-                self.invalidate_session(&key).await;
-
-                // Retry with a new session
-                self.call_tool_internal(chat_id, server_id, params, auth_context)
-                    .await
-            }
-            Err(e) => Err(e),
-        }
-    }
-
-    /// Internal implementation of call_tool without retry logic
-    async fn call_tool_internal(
-        &self,
-        chat_id: Uuid,
-        server_id: &str,
-        params: CallToolRequestParams,
-        auth_context: &McpRequestAuthContext<'_>,
-    ) -> Result<CallToolResult, Report> {
-        // Ensure session exists
-        let key = self
-            .get_or_create_session(chat_id, server_id, auth_context)
-            .await?;
-
-        // Call the tool
-        let mut sessions_guard = self.sessions.write().await;
-
-        let session = sessions_guard
-            .get_mut(&key)
-            .ok_or_else(|| eyre!("Session not found after creation"))?;
-
-        session.call_tool(params).await
+        self.with_session(chat_id, server_id, auth_context, |session| {
+            let params = params.clone();
+            async move { session.call_tool(params).await }
+        })
+        .await
     }
 
     /// Manually refresh the tools list for a specific chat and server
@@ -592,56 +898,10 @@ impl McpSessionManager {
         server_id: &str,
         auth_context: &McpRequestAuthContext<'_>,
     ) -> Result<(), Report> {
-        match self
-            .refresh_tools_internal(chat_id, server_id, auth_context)
-            .await
-        {
-            Ok(()) => Ok(()),
-            Err(e) if Self::is_session_invalid_error(&e) => {
-                let config = self.server_configs.get(server_id).ok_or_else(|| {
-                    eyre!("MCP server '{}' not found in configuration", server_id)
-                })?;
-                let key = (
-                    chat_id,
-                    server_id.to_string(),
-                    Self::session_auth_key(server_id, config, auth_context).await?,
-                );
-                warn!(
-                    chat_id = %chat_id,
-                    server_id = %server_id,
-                    error = %e,
-                    "MCP session appears to be invalid during refresh, recreating and retrying"
-                );
-
-                // Invalidate the session
-                self.invalidate_session(&key).await;
-
-                // Retry with a new session
-                self.refresh_tools_internal(chat_id, server_id, auth_context)
-                    .await
-            }
-            Err(e) => Err(e),
-        }
-    }
-
-    /// Internal implementation of refresh_tools without retry logic
-    async fn refresh_tools_internal(
-        &self,
-        chat_id: Uuid,
-        server_id: &str,
-        auth_context: &McpRequestAuthContext<'_>,
-    ) -> Result<(), Report> {
-        let key = self
-            .get_or_create_session(chat_id, server_id, auth_context)
-            .await?;
-
-        let mut sessions_guard = self.sessions.write().await;
-
-        if let Some(session) = sessions_guard.get_mut(&key) {
-            session.refresh_tools().await?;
-        }
-
-        Ok(())
+        self.with_session(chat_id, server_id, auth_context, |session| async move {
+            session.refresh_tools().await
+        })
+        .await
     }
 
     /// Perform connectivity checks for all configured MCP servers
@@ -685,17 +945,13 @@ impl McpSessionManager {
                 .get_or_create_session(test_chat_id, server_id, &auth_context)
                 .await
             {
-                Ok(key) => {
-                    // Get the session to check how many tools are available
-                    let sessions_guard = self.sessions.read().await;
-
-                    if let Some(session) = sessions_guard.get(&key) {
-                        info!(
-                            server_id = %server_id,
-                            num_tools = session.tools.len(),
-                            "✓ MCP server connection successful"
-                        );
-                    }
+                Ok((_, session)) => {
+                    let num_tools = session.tools.lock().await.tools.len();
+                    info!(
+                        server_id = %server_id,
+                        num_tools,
+                        "✓ MCP server connection successful"
+                    );
                 }
                 Err(e) => {
                     warn!(
@@ -709,11 +965,11 @@ impl McpSessionManager {
             }
 
             // Drop the startup connectivity-check session immediately.
-            if let Ok(session_auth_key) =
-                Self::session_auth_key(server_id, config, &auth_context).await
+            if let Ok(session_identity) =
+                Self::session_identity(server_id, config, &auth_context).await
             {
-                let key = (test_chat_id, server_id.clone(), session_auth_key);
-                self.invalidate_session(&key).await;
+                let key = (test_chat_id, server_id.clone(), session_identity);
+                self.remove_session(&key).await;
             }
         }
 
@@ -729,16 +985,35 @@ impl McpSessionManager {
             return McpServerConnectionStatus::Failure;
         };
 
+        // A session of the same user (or a shared one) shows that the server is reachable,
+        // without establishing another one
+        if let Ok(credential) = Self::session_credential(server_id, config, auth_context).await {
+            let identity = credential
+                .as_ref()
+                .map(|credential| credential.identity.clone());
+            let fingerprint = credential.map(|credential| credential.fingerprint);
+            let has_live_session = self.sessions.read().await.iter().any(
+                |((_, existing_server_id, existing_identity), session)| {
+                    existing_server_id == server_id
+                        && *existing_identity == identity
+                        && session.credential_fingerprint == fingerprint
+                },
+            );
+            if has_live_session {
+                return McpServerConnectionStatus::Success;
+            }
+        }
+
         let probe_chat_id = Uuid::nil();
         let result = self
             .get_or_create_session(probe_chat_id, server_id, auth_context)
             .await;
 
-        let session_auth_key = Self::session_auth_key(server_id, config, auth_context)
+        let session_identity = Self::session_identity(server_id, config, auth_context)
             .await
             .ok();
-        if let Some(session_auth_key) = session_auth_key {
-            self.invalidate_session(&(probe_chat_id, server_id.to_string(), session_auth_key))
+        if let Some(session_identity) = session_identity {
+            self.remove_session(&(probe_chat_id, server_id.to_string(), session_identity))
                 .await;
         }
 
@@ -12,22 +12,40 @@ use crate::services::template_rendering::contexts::mcp_id_token::McpForwardedIdT
 use eyre::{Report, eyre};
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use rmcp::ClientHandler;
-use rmcp::service::{RoleClient, RunningService, ServiceExt};
+use rmcp::service::{NotificationContext, RoleClient, RunningService, ServiceExt};
 use rmcp::transport::StreamableHttpClientTransport;
 use rmcp::transport::streamable_http_client::StreamableHttpClientTransportConfig;
 use rmcp_sse::{SseClientConfig, SseClientTransport};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Start of the error for a forwarded credential that the request doesn't carry, e.g. the ID
+/// token in the `trusted_headers` auth mode.
+pub(crate) const FORWARDED_CREDENTIAL_UNAVAILABLE: &str = "Forwarded credential unavailable";
+
+/// Error for a forwarded credential that the request doesn't carry.
+pub(crate) fn forwarded_credential_unavailable(server_id: &str, credential: &str) -> Report {
+    eyre!(
+        "{}: the request carries no {} to forward to MCP server '{}'",
+        FORWARDED_CREDENTIAL_UNAVAILABLE,
+        credential,
+        server_id
+    )
+}
 
 fn apply_auth_header(
     headers: &mut HeaderMap,
     header_name: &str,
     header_value: &str,
 ) -> Result<(), Report> {
+    let mut header_value = HeaderValue::from_str(header_value)
+        .map_err(|e| eyre!("Invalid MCP auth header value for '{}': {}", header_name, e))?;
+    // Keeps the credential out of `Debug` output, e.g. in request logs
+    header_value.set_sensitive(true);
     headers.insert(
         HeaderName::from_bytes(header_name.as_bytes())
             .map_err(|e| eyre!("Invalid MCP auth header name '{}': {}", header_name, e))?,
-        HeaderValue::from_str(header_value)
-            .map_err(|e| eyre!("Invalid MCP auth header value for '{}': {}", header_name, e))?,
+        header_value,
     );
     Ok(())
 }
@@ -45,7 +63,7 @@ async fn apply_auth_headers(
                 let renderer = McpAccessTokenAuthHeaderRenderer::new();
                 let token = auth_context
                     .access_token
-                    .ok_or_else(|| eyre!("Missing forwarded access token for MCP server"))?;
+                    .ok_or_else(|| forwarded_credential_unavailable(server_id, "access token"))?;
                 let header_value = renderer.render(
                     FORWARDED_ACCESS_TOKEN_AUTH_HEADER_TEMPLATE,
                     &McpForwardedAccessTokenContext {
@@ -59,7 +77,7 @@ async fn apply_auth_headers(
                 let renderer = McpIdTokenAuthHeaderRenderer::new();
                 let token = auth_context
                     .oidc_token
-                    .ok_or_else(|| eyre!("Missing forwarded OIDC token for MCP server"))?;
+                    .ok_or_else(|| forwarded_credential_unavailable(server_id, "OIDC ID token"))?;
                 let header_value = renderer.render(
                     FORWARDED_ID_TOKEN_AUTH_HEADER_TEMPLATE,
                     &McpForwardedIdTokenContext {
@@ -145,10 +163,13 @@ pub async fn create_mcp_service(
     server_id: &str,
     config: &McpServerConfig,
     auth_context: &McpRequestAuthContext<'_>,
-) -> Result<RunningService<RoleClient, EmptyClientHandler>, Report> {
+    handler: McpClientHandler,
+) -> Result<RunningService<RoleClient, McpClientHandler>, Report> {
     match config.transport_type.as_str() {
-        "sse" => create_sse_service(server_id, config, auth_context).await,
-        "streamable_http" => create_streamable_http_service(server_id, config, auth_context).await,
+        "sse" => create_sse_service(server_id, config, auth_context, handler).await,
+        "streamable_http" => {
+            create_streamable_http_service(server_id, config, auth_context, handler).await
+        }
         other => Err(eyre!(
             "Unsupported transport type '{}'. Supported types are 'sse' and 'streamable_http'",
             other
@@ -161,7 +182,8 @@ async fn create_sse_service(
     server_id: &str,
     config: &McpServerConfig,
     auth_context: &McpRequestAuthContext<'_>,
-) -> Result<RunningService<RoleClient, EmptyClientHandler>, Report> {
+    handler: McpClientHandler,
+) -> Result<RunningService<RoleClient, McpClientHandler>, Report> {
     use tracing::debug;
 
     debug!(url = %config.url, "Starting SSE transport");
@@ -179,9 +201,6 @@ async fn create_sse_service(
 
     debug!("SSE transport created, initializing service");
 
-    // Create a client handler (empty for now, can be customized later)
-    let handler = EmptyClientHandler;
-
     // Create the peer using the service extension trait
     let running_service = handler
         .serve(transport)
@@ -198,7 +217,8 @@ async fn create_streamable_http_service(
     server_id: &str,
     config: &McpServerConfig,
     auth_context: &McpRequestAuthContext<'_>,
-) -> Result<RunningService<RoleClient, EmptyClientHandler>, Report> {
+    handler: McpClientHandler,
+) -> Result<RunningService<RoleClient, McpClientHandler>, Report> {
     use tracing::debug;
 
     debug!(url = %config.url, "Creating Streamable HTTP transport");
@@ -211,9 +231,6 @@ async fn create_streamable_http_service(
 
     debug!("Streamable HTTP transport created, initializing service");
 
-    // Create a client handler (empty for now, can be customized later)
-    let handler = EmptyClientHandler;
-
     // Create the peer using the service extension trait
     let running_service = handler.serve(transport).await.map_err(|e| {
         eyre!(
@@ -227,8 +244,26 @@ async fn create_streamable_http_service(
     Ok(running_service)
 }
 
-/// Empty client handler that doesn't handle any client-side notifications
-#[derive(Debug, Clone)]
-pub struct EmptyClientHandler;
+/// Client handler that records when the server announces a change of its tool list
+#[derive(Debug, Clone, Default)]
+pub struct McpClientHandler {
+    tools_changed: Arc<AtomicBool>,
+}
 
-impl ClientHandler for EmptyClientHandler {}
+impl McpClientHandler {
+    /// Whether the tool list changed since the last call, resetting the flag
+    pub fn take_tools_changed(&self) -> bool {
+        self.tools_changed.swap(false, Ordering::AcqRel)
+    }
+
+    /// Mark the tool list as changed, e.g. because fetching the changed list failed
+    pub fn mark_tools_changed(&self) {
+        self.tools_changed.store(true, Ordering::Release);
+    }
+}
+
+impl ClientHandler for McpClientHandler {
+    async fn on_tool_list_changed(&self, _context: NotificationContext<RoleClient>) {
+        self.mark_tools_changed();
+    }
+}
@@ -1,21 +1,56 @@
+pub mod admin_commands;
 pub mod background_tasks;
+pub mod chat_export;
+pub mod chat_provider_groups;
+pub mod chat_provider_quotas;
+pub mod chat_provider_rate_limits;
 pub mod client_actions;
 pub mod client_tools;
+pub mod content_spillover;
 pub mod desktop_sidecar_distribution;
+pub mod ephemeral_chats;
+pub mod feature_flags;
 pub mod file_parsing;
+pub mod file_pointer_migration;
 pub mod file_processing_cached;
 pub mod file_processor;
 pub mod file_storage;
+pub mod file_storage_self_test;
+pub mod file_synopsis;
 pub mod genai;
 pub mod genai_langfuse;
+pub mod generation_cache;
+pub mod generation_input_archive;
+pub mod generation_queue;
 pub mod langfuse;
+pub mod langfuse_dead_letters;
+pub mod language_detection;
+pub mod long_messages;
+pub mod maintenance_tasks;
+pub mod markdown_normalization;
 pub mod mcp_manager;
 pub mod mcp_oauth;
+pub mod mcp_output_schema;
 pub mod mcp_session_manager;
 pub mod mcp_transports;
+pub mod model_selection;
+pub mod moderation;
+pub mod output_compliance;
+pub mod output_pacing;
+pub mod output_transformers;
+pub mod policy_invalidations;
 pub mod prompt_composition;
 pub mod prompt_guardrails;
+pub mod prompt_optimizer;
+pub mod provider_capture;
+pub mod renderable_blocks;
+pub mod scheduled_messages;
+pub mod seed;
 pub mod template_rendering;
+pub mod thread_integrity_scan;
+pub mod untrusted_content;
+pub mod usage_reporting;
+pub mod user_events;
 
 #[cfg(feature = "sentry")]
 pub mod sentry;
@@ -0,0 +1,344 @@
+//! Recommendation of the chat model a user should pick.
+//!
+//! The models available to a user are ranked by what the chat needs from them, their recent
+//! health and the configured preferences. Models that can't serve the chat at all, e.g. ones
+//! without image input for a chat with images, are left out. The remaining models are scored,
+//! and models with the same score keep the order of `chat_providers.priority_order`.
+
+use crate::config::{InputModality, ModelCapabilities};
+use serde::Serialize;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Score of a model that nothing speaks for or against.
+const BASE_SCORE: i32 = 100;
+const GROUP_DEFAULT_BONUS: i32 = 30;
+const LAST_USED_BONUS: i32 = 10;
+const COOLING_DOWN_PENALTY: i32 = 60;
+const DEGRADED_PENALTY: i32 = 40;
+const SLOW_PENALTY: i32 = 10;
+/// Models whose recent generations failed at least at this rate are considered degraded.
+const DEGRADED_ERROR_RATE: f64 = 0.25;
+/// Models whose average latency exceeds the one of the fastest model by this factor are
+/// considered slow.
+const SLOW_LATENCY_FACTOR: f64 = 2.0;
+
+/// What a chat needs from the model that generates its answers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelRequirements {
+    /// Kinds of the attachments the model has to process
+    pub input_modalities: Vec<InputModality>,
+    /// Number of files attached to the next message
+    pub input_files: usize,
+    pub needs_tools: bool,
+}
+
+/// Recent health of a chat provider, as tracked by the backend instance.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModelHealth {
+    /// Whether the provider is cooling down after a rate limit
+    pub cooling_down: bool,
+    /// Share of the recent generations that failed
+    pub error_rate: f64,
+    /// Average time until recent generations started streaming
+    pub average_latency: Option<Duration>,
+}
+
+/// A model available to the user, with what is known about it.
+#[derive(Debug, Clone)]
+pub struct ModelCandidate<'a> {
+    pub chat_provider_id: &'a str,
+    pub model_capabilities: &'a ModelCapabilities,
+    pub health: ModelHealth,
+    /// Whether the model is the default of one of the groups of the user
+    pub group_default: bool,
+    /// Whether the model generated the last answer of the chat
+    pub last_used: bool,
+}
+
+/// A model recommended to the user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ModelRecommendation {
+    /// The ID of the chat provider
+    pub chat_provider_id: String,
+    /// Score of the model. Models with a higher score are recommended first.
+    pub score: i32,
+    /// Human-readable reasons for the score, e.g. "supports image input"
+    pub reasons: Vec<String>,
+}
+
+/// Rank the candidates, which are given in priority order, by how well they fit the
+/// requirements.
+///
+/// Candidates that don't meet the requirements are left out. Candidates with the same score
+/// keep their order.
+pub fn rank_models(
+    candidates: &[ModelCandidate<'_>],
+    requirements: &ModelRequirements,
+) -> Vec<ModelRecommendation> {
+    let eligible: Vec<&ModelCandidate<'_>> = candidates
+        .iter()
+        .filter(|candidate| meets_requirements(candidate.model_capabilities, requirements))
+        .collect();
+    let fastest_latency = eligible
+        .iter()
+        .filter_map(|candidate| candidate.health.average_latency)
+        .min();
+
+    let mut recommendations: Vec<ModelRecommendation> = eligible
+        .iter()
+        .enumerate()
+        .map(|(position, candidate)| {
+            score_candidate(candidate, requirements, position, fastest_latency)
+        })
+        .collect();
+    // Stable, so that ties are broken by the priority order
+    recommendations.sort_by(|a, b| b.score.cmp(&a.score));
+    recommendations
+}
+
+fn meets_requirements(capabilities: &ModelCapabilities, requirements: &ModelRequirements) -> bool {
+    requirements
+        .input_modalities
+        .iter()
+        .all(|modality| capabilities.supports_input_modality(*modality))
+        && capabilities
+            .max_input_files
+            .is_none_or(|max_input_files| requirements.input_files <= max_input_files)
+        && (!requirements.needs_tools || capabilities.supports_tools)
+}
+
+fn score_candidate(
+    candidate: &ModelCandidate<'_>,
+    requirements: &ModelRequirements,
+    position: usize,
+    fastest_latency: Option<Duration>,
+) -> ModelRecommendation {
+    let mut score = BASE_SCORE;
+    let mut reasons = Vec::new();
+
+    if requirements.input_modalities.contains(&InputModality::Image) {
+        reasons.push("supports image input".to_string());
+    }
+    if requirements.input_modalities.contains(&InputModality::Audio) {
+        reasons.push("supports audio input".to_string());
+    }
+    if requirements.needs_tools {
+        reasons.push("supports tools".to_string());
+    }
+    if candidate.group_default {
+        score += GROUP_DEFAULT_BONUS;
+        reasons.push("your group default".to_string());
+    }
+    if candidate.last_used {
+        score += LAST_USED_BONUS;
+        reasons.push("used last in this chat".to_string());
+    }
+
+    let health = &candidate.health;
+    if health.cooling_down {
+        score -= COOLING_DOWN_PENALTY;
+        reasons.push("currently rate limited".to_string());
+    }
+    if health.error_rate >= DEGRADED_ERROR_RATE {
+        score -= DEGRADED_PENALTY;
+        reasons.push("currently degraded".to_string());
+    }
+    if let (Some(latency), Some(fastest_latency)) = (health.average_latency, fastest_latency)
+        && latency.as_secs_f64() > fastest_latency.as_secs_f64() * SLOW_LATENCY_FACTOR
+    {
+        score -= SLOW_PENALTY;
+        reasons.push("currently responding slowly".to_string());
+    }
+
+    if position == 0 {
+        reasons.push("highest configured priority".to_string());
+    }
+
+    ModelRecommendation {
+        chat_provider_id: candidate.chat_provider_id.to_string(),
+        score,
+        reasons,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities(supports_image_understanding: bool) -> ModelCapabilities {
+        ModelCapabilities {
+            supports_image_understanding,
+            ..Default::default()
+        }
+    }
+
+    fn candidate<'a>(
+        chat_provider_id: &'a str,
+        model_capabilities: &'a ModelCapabilities,
+    ) -> ModelCandidate<'a> {
+        ModelCandidate {
+            chat_provider_id,
+            model_capabilities,
+            health: ModelHealth::default(),
+            group_default: false,
+            last_used: false,
+        }
+    }
+
+    fn ids(recommendations: &[ModelRecommendation]) -> Vec<&str> {
+        recommendations
+            .iter()
+            .map(|recommendation| recommendation.chat_provider_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn vision_requirement_filters_models_without_image_input() {
+        let text_only = capabilities(false);
+        let vision = capabilities(true);
+        let candidates = [candidate("text", &text_only), candidate("vision", &vision)];
+        let requirements = ModelRequirements {
+            input_modalities: vec![InputModality::Image],
+            ..Default::default()
+        };
+
+        let recommendations = rank_models(&candidates, &requirements);
+
+        assert_eq!(ids(&recommendations), vec!["vision"]);
+        assert!(
+            recommendations[0]
+                .reasons
+                .contains(&"supports image input".to_string())
+        );
+    }
+
+    #[test]
+    fn too_many_files_and_missing_tools_filter_models() {
+        let limited = ModelCapabilities {
+            max_input_files: Some(1),
+            ..Default::default()
+        };
+        let without_tools = ModelCapabilities {
+            supports_tools: false,
+            ..Default::default()
+        };
+        let unrestricted = ModelCapabilities::default();
+        let candidates = [
+            candidate("limited", &limited),
+            candidate("without-tools", &without_tools),
+            candidate("unrestricted", &unrestricted),
+        ];
+
+        let recommendations = rank_models(
+            &candidates,
+            &ModelRequirements {
+                input_files: 2,
+                ..Default::default()
+            },
+        );
+        assert_eq!(ids(&recommendations), vec!["without-tools", "unrestricted"]);
+
+        let recommendations = rank_models(
+            &candidates,
+            &ModelRequirements {
+                needs_tools: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(ids(&recommendations), vec!["limited", "unrestricted"]);
+    }
+
+    #[test]
+    fn degraded_models_are_demoted() {
+        let model_capabilities = ModelCapabilities::default();
+        let mut degraded = candidate("degraded", &model_capabilities);
+        degraded.health.error_rate = 0.5;
+        let mut rate_limited = candidate("rate-limited", &model_capabilities);
+        rate_limited.health.cooling_down = true;
+        let candidates = [
+            degraded,
+            rate_limited,
+            candidate("healthy", &model_capabilities),
+        ];
+
+        let recommendations = rank_models(&candidates, &ModelRequirements::default());
+
+        assert_eq!(
+            ids(&recommendations),
+            vec!["healthy", "degraded", "rate-limited"]
+        );
+        assert!(
+            recommendations[1]
+                .reasons
+                .contains(&"currently degraded".to_string())
+        );
+        assert!(
+            recommendations[2]
+                .reasons
+                .contains(&"currently rate limited".to_string())
+        );
+    }
+
+    #[test]
+    fn slow_models_are_demoted() {
+        let model_capabilities = ModelCapabilities::default();
+        let mut slow = candidate("slow", &model_capabilities);
+        slow.health.average_latency = Some(Duration::from_secs(5));
+        let mut fast = candidate("fast", &model_capabilities);
+        fast.health.average_latency = Some(Duration::from_secs(1));
+
+        let recommendations = rank_models(&[slow, fast], &ModelRequirements::default());
+
+        assert_eq!(ids(&recommendations), vec!["fast", "slow"]);
+        assert_eq!(recommendations[1].score, BASE_SCORE - SLOW_PENALTY);
+    }
+
+    #[test]
+    fn ties_are_broken_by_priority() {
+        let model_capabilities = ModelCapabilities::default();
+        let candidates = [
+            candidate("first", &model_capabilities),
+            candidate("second", &model_capabilities),
+            candidate("third", &model_capabilities),
+        ];
+
+        let recommendations = rank_models(&candidates, &ModelRequirements::default());
+
+        assert_eq!(ids(&recommendations), vec!["first", "second", "third"]);
+        assert!(
+            recommendations
+                .iter()
+                .all(|recommendation| recommendation.score == BASE_SCORE)
+        );
+        assert_eq!(
+            recommendations[0].reasons,
+            vec!["highest configured priority".to_string()]
+        );
+    }
+
+    #[test]
+    fn group_default_and_last_used_model_are_preferred() {
+        let model_capabilities = ModelCapabilities::default();
+        let mut last_used = candidate("last-used", &model_capabilities);
+        last_used.last_used = true;
+        let mut group_default = candidate("group-default", &model_capabilities);
+        group_default.group_default = true;
+        let candidates = [
+            candidate("first", &model_capabilities),
+            last_used,
+            group_default,
+        ];
+
+        let recommendations = rank_models(&candidates, &ModelRequirements::default());
+
+        assert_eq!(
+            ids(&recommendations),
+            vec!["group-default", "last-used", "first"]
+        );
+        assert_eq!(
+            recommendations[0].reasons,
+            vec!["your group default".to_string()]
+        );
+    }
+}
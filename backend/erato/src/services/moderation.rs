@@ -0,0 +1,436 @@
+//! Content moderation of user messages before they are passed to the model.
+//!
+//! The content filters of the model providers only act on the generated output. With
+//! `moderation.enabled`, the text of a user message (and optionally the synopses of its attached
+//! files) is classified by a moderation service after the message has been saved, and before the
+//! chat request is prepared. Depending on `moderation.action`, flagged messages are either
+//! blocked, so that no generation happens, or only annotated.
+//!
+//! Results are stored in the generation metadata of the user message, and reused whenever the
+//! message is moderated again (e.g. when the answer to it is regenerated).
+
+use crate::config::{ModerationAction, ModerationConfig, ModerationProviderKind};
+use crate::db::entity::messages;
+use crate::db::entity::prelude::FileUploads;
+use crate::models::message::{
+    GenerationMetadata, MessageSchema, ModerationResult, update_message_generation_metadata,
+};
+use crate::policy::engine::PolicyEngine;
+use crate::policy::types::Subject;
+use crate::services::file_processing_cached;
+use crate::services::file_storage::SharepointContext;
+use crate::services::sentry::capture_report;
+use crate::state::AppState;
+use eyre::{OptionExt, Report, WrapErr, eyre};
+use sea_orm::EntityTrait;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Outcome of the moderation of a user message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModerationOutcome {
+    /// Moderation is disabled, there was nothing to moderate, or the moderation service failed
+    /// and `moderation.fail_open` is set.
+    Skipped,
+    /// The message was classified by the moderation service.
+    Moderated(ModerationResult),
+    /// The moderation service failed and `moderation.fail_open` is not set.
+    Unavailable,
+}
+
+/// Classification of an input by the moderation service, before `moderation.categories_blocklist`
+/// and `moderation.action` are applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Classification {
+    flagged: bool,
+    categories: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiModerationResponse {
+    results: Vec<OpenAiModerationResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiModerationResult {
+    flagged: bool,
+    #[serde(default)]
+    categories: BTreeMap<String, Option<bool>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookModerationResponse {
+    #[serde(default)]
+    flagged: Option<bool>,
+    #[serde(default)]
+    categories: Vec<String>,
+}
+
+/// Moderate a saved user message.
+///
+/// A result already stored on the message is reused. Otherwise the message is classified by the
+/// moderation service and the result is stored on the message. Failures of the moderation
+/// service are handled according to `moderation.fail_open`, and are not stored, so the message
+/// is classified again the next time it is moderated.
+pub async fn moderate_user_message(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    message: &messages::Model,
+    access_token: Option<&str>,
+) -> Result<ModerationOutcome, Report> {
+    let config = &app_state.config.moderation;
+    if !config.enabled {
+        return Ok(ModerationOutcome::Skipped);
+    }
+    if let Some(moderation) = stored_moderation(message) {
+        return Ok(ModerationOutcome::Moderated(moderation));
+    }
+
+    let input = moderation_input(app_state, config, message, access_token).await?;
+    if input.trim().is_empty() {
+        return Ok(ModerationOutcome::Skipped);
+    }
+
+    let classification = match classify(config, &input).await {
+        Ok(classification) => classification,
+        Err(error) => {
+            tracing::warn!(
+                message_id = %message.id,
+                fail_open = config.fail_open,
+                error = ?error,
+                "Failed to moderate user message"
+            );
+            capture_report(&error);
+            return Ok(if config.fail_open {
+                ModerationOutcome::Skipped
+            } else {
+                ModerationOutcome::Unavailable
+            });
+        }
+    };
+
+    let result = evaluate(config, classification);
+    if result.flagged {
+        tracing::info!(
+            message_id = %message.id,
+            categories = ?result.categories,
+            blocked = result.blocked,
+            "User message flagged by moderation"
+        );
+    }
+    store_moderation(app_state, policy, subject, message, result.clone()).await?;
+    Ok(ModerationOutcome::Moderated(result))
+}
+
+/// Moderate a user message that is never saved, e.g. a message of an ephemeral chat.
+///
+/// Like [`moderate_user_message`], but the result is not stored on the message.
+pub async fn moderate_unsaved_user_message(
+    app_state: &AppState,
+    message: &messages::Model,
+    access_token: Option<&str>,
+) -> Result<ModerationOutcome, Report> {
+    let config = &app_state.config.moderation;
+    if !config.enabled {
+        return Ok(ModerationOutcome::Skipped);
+    }
+    let input = moderation_input(app_state, config, message, access_token).await?;
+    Ok(moderate_text(config, &input).await)
+}
+
+/// Moderate a text that is not stored as a message, e.g. the input of the compat API.
+///
+/// Failures of the moderation service are handled according to `moderation.fail_open`.
+pub async fn moderate_text(config: &ModerationConfig, input: &str) -> ModerationOutcome {
+    if !config.enabled || input.trim().is_empty() {
+        return ModerationOutcome::Skipped;
+    }
+    match classify(config, input).await {
+        Ok(classification) => ModerationOutcome::Moderated(evaluate(config, classification)),
+        Err(error) => {
+            tracing::warn!(
+                fail_open = config.fail_open,
+                error = ?error,
+                "Failed to moderate text"
+            );
+            capture_report(&error);
+            if config.fail_open {
+                ModerationOutcome::Skipped
+            } else {
+                ModerationOutcome::Unavailable
+            }
+        }
+    }
+}
+
+fn stored_moderation(message: &messages::Model) -> Option<ModerationResult> {
+    message
+        .generation_metadata
+        .clone()
+        .and_then(|metadata| serde_json::from_value::<GenerationMetadata>(metadata).ok())
+        .and_then(|metadata| metadata.moderation)
+}
+
+async fn store_moderation(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    message: &messages::Model,
+    moderation: ModerationResult,
+) -> Result<(), Report> {
+    let mut generation_metadata = message
+        .generation_metadata
+        .clone()
+        .map(serde_json::from_value::<GenerationMetadata>)
+        .transpose()
+        .wrap_err("Failed to parse generation metadata of user message")?
+        .unwrap_or_default();
+    generation_metadata.moderation = Some(moderation);
+    update_message_generation_metadata(
+        &app_state.db,
+        policy,
+        subject,
+        &message.id,
+        generation_metadata,
+    )
+    .await
+    .wrap_err("Failed to store moderation result")?;
+    Ok(())
+}
+
+/// The text that is classified for a message: its text parts, followed by the synopses of the
+/// attached files if `moderation.include_file_synopses` is set.
+async fn moderation_input(
+    app_state: &AppState,
+    config: &ModerationConfig,
+    message: &messages::Model,
+    access_token: Option<&str>,
+) -> Result<String, Report> {
+    let mut input = MessageSchema::validate(&message.raw_message)?.full_text();
+    if !config.include_file_synopses {
+        return Ok(input);
+    }
+
+    for file_id in message.input_file_uploads.iter().flatten() {
+        match file_synopsis_paragraph(app_state, file_id, access_token).await {
+            Ok(Some(paragraph)) => {
+                input.push_str("\n\n");
+                input.push_str(&paragraph);
+            }
+            Ok(None) => {}
+            Err(error) => {
+                tracing::warn!(
+                    file_id = %file_id,
+                    error = ?error,
+                    "Failed to read file synopsis for moderation"
+                );
+            }
+        }
+    }
+    Ok(input)
+}
+
+async fn file_synopsis_paragraph(
+    app_state: &AppState,
+    file_id: &sea_orm::prelude::Uuid,
+    access_token: Option<&str>,
+) -> Result<Option<String>, Report> {
+    let file = FileUploads::find_by_id(*file_id)
+        .one(&app_state.db)
+        .await?
+        .ok_or_eyre("File upload not found")?;
+    let file_storage = app_state
+        .file_storage_providers
+        .get(&file.file_storage_provider_id)
+        .ok_or_eyre("File storage provider not found")?;
+    let sharepoint_ctx = access_token.map(|token| SharepointContext {
+        access_token: token,
+    });
+    let file_contents = file_processing_cached::get_file_cached(
+        app_state,
+        file_id,
+        file_storage,
+        &file.file_storage_path,
+        &file.filename,
+        sharepoint_ctx.as_ref(),
+    )
+    .await?;
+    Ok(file_contents
+        .synopsis
+        .map(|synopsis| format!("{}: {}", file.filename, synopsis.to_paragraph())))
+}
+
+async fn classify(config: &ModerationConfig, input: &str) -> Result<Classification, Report> {
+    let endpoint = config
+        .endpoint
+        .as_deref()
+        .ok_or_eyre("`moderation.endpoint` is not set")?;
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_seconds))
+        .build()
+        .wrap_err("Failed to build moderation HTTP client")?;
+
+    let mut body = json!({ "input": input });
+    if config.provider == ModerationProviderKind::OpenaiModeration
+        && let Some(model) = &config.model
+    {
+        body["model"] = json!(model);
+    }
+    let mut request = client.post(endpoint).json(&body);
+    if let Some(api_key) = &config.api_key {
+        request = request.bearer_auth(api_key.expose_secret());
+    }
+
+    let response = request
+        .send()
+        .await
+        .wrap_err("Failed to reach the moderation service")?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(eyre!("Moderation service responded with status {status}"));
+    }
+    let body = response
+        .bytes()
+        .await
+        .wrap_err("Failed to read moderation response")?;
+    match config.provider {
+        ModerationProviderKind::OpenaiModeration => parse_openai_moderation_response(&body),
+        ModerationProviderKind::Webhook => parse_webhook_moderation_response(&body),
+    }
+}
+
+fn parse_openai_moderation_response(body: &[u8]) -> Result<Classification, Report> {
+    let response: OpenAiModerationResponse =
+        serde_json::from_slice(body).wrap_err("Failed to parse OpenAI moderation response")?;
+    let mut classification = Classification::default();
+    for result in response.results {
+        classification.flagged |= result.flagged;
+        classification.categories.extend(
+            result
+                .categories
+                .into_iter()
+                .filter(|(_, flagged)| *flagged == Some(true))
+                .map(|(category, _)| category),
+        );
+    }
+    classification.categories.sort_unstable();
+    classification.categories.dedup();
+    Ok(classification)
+}
+
+fn parse_webhook_moderation_response(body: &[u8]) -> Result<Classification, Report> {
+    let response: WebhookModerationResponse =
+        serde_json::from_slice(body).wrap_err("Failed to parse moderation webhook response")?;
+    Ok(Classification {
+        flagged: response.flagged.unwrap_or(!response.categories.is_empty()),
+        categories: response.categories,
+    })
+}
+
+/// Apply `moderation.categories_blocklist` and `moderation.action` to a classification.
+fn evaluate(config: &ModerationConfig, classification: Classification) -> ModerationResult {
+    let categories: Vec<String> = if config.categories_blocklist.is_empty() {
+        classification.categories
+    } else {
+        classification
+            .categories
+            .into_iter()
+            .filter(|category| config.categories_blocklist.contains(category))
+            .collect()
+    };
+    let flagged = classification.flagged
+        && (config.categories_blocklist.is_empty() || !categories.is_empty());
+    ModerationResult {
+        flagged,
+        categories: if flagged { categories } else { Vec::new() },
+        blocked: flagged && config.action == ModerationAction::Block,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_flagged_categories_of_openai_response() {
+        let body = br#"{
+            "id": "modr-1",
+            "model": "omni-moderation-latest",
+            "results": [{
+                "flagged": true,
+                "categories": { "violence": true, "harassment": false, "self-harm": true, "sexual": null }
+            }]
+        }"#;
+        assert_eq!(
+            parse_openai_moderation_response(body).unwrap(),
+            Classification {
+                flagged: true,
+                categories: vec!["self-harm".to_string(), "violence".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn webhook_response_without_flag_is_flagged_by_categories() {
+        assert_eq!(
+            parse_webhook_moderation_response(br#"{ "categories": ["hate"] }"#).unwrap(),
+            Classification {
+                flagged: true,
+                categories: vec!["hate".to_string()],
+            }
+        );
+        assert_eq!(
+            parse_webhook_moderation_response(br#"{ "flagged": false }"#).unwrap(),
+            Classification::default()
+        );
+    }
+
+    #[test]
+    fn blocklist_limits_acted_upon_categories() {
+        let config = ModerationConfig {
+            categories_blocklist: vec!["violence".to_string()],
+            ..Default::default()
+        };
+        let classification = |categories: &[&str]| Classification {
+            flagged: true,
+            categories: categories.iter().map(ToString::to_string).collect(),
+        };
+
+        assert_eq!(
+            evaluate(&config, classification(&["harassment", "violence"])),
+            ModerationResult {
+                flagged: true,
+                categories: vec!["violence".to_string()],
+                blocked: true,
+            }
+        );
+        assert_eq!(
+            evaluate(&config, classification(&["harassment"])),
+            ModerationResult {
+                flagged: false,
+                categories: vec![],
+                blocked: false,
+            }
+        );
+    }
+
+    #[test]
+    fn flag_action_does_not_block() {
+        let config = ModerationConfig {
+            action: ModerationAction::Flag,
+            ..Default::default()
+        };
+        let result = evaluate(
+            &config,
+            Classification {
+                flagged: true,
+                categories: vec!["hate".to_string()],
+            },
+        );
+        assert!(result.flagged);
+        assert!(!result.blocked);
+    }
+}
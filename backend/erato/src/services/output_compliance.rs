@@ -0,0 +1,387 @@
+//! Output compliance rules (see `guardrails.output_compliance` in the config), applied to the text
+//! of generated answers while it is streamed.
+//!
+//! A match of a rule can be split across the chunks of the stream, so the filter holds back the
+//! end of the streamed text until it can't be the start of a match anymore. Fixed phrases hold
+//! back one character less than the longest phrase, and regex rules `max_holdback_chars`, which
+//! bounds the latency added to the stream.
+
+use crate::config::{OutputComplianceAction, OutputComplianceConfig, PromptPatternType};
+use crate::metrics::report_output_compliance_match;
+use crate::models::message::OutputComplianceMatch;
+use eyre::{Report, WrapErr};
+use regex::{Regex, RegexBuilder};
+use sqlx::types::Uuid;
+
+/// Replaces the text matched by `mask` rules.
+pub const OUTPUT_COMPLIANCE_MASK: &str = "[redacted]";
+
+/// A match of an `abort` rule, which ends the generation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputComplianceViolation {
+    pub rule_id: String,
+}
+
+struct CompiledRule {
+    id: String,
+    regex: Regex,
+    action: OutputComplianceAction,
+}
+
+/// Applies the output compliance rules of an assistant to the streamed text of a generation.
+pub struct OutputComplianceFilter {
+    message_id: Uuid,
+    rules: Vec<CompiledRule>,
+    holdback_chars: usize,
+    /// Text that was pushed, but not returned yet
+    pending: String,
+    matches: Vec<OutputComplianceMatch>,
+}
+
+impl OutputComplianceFilter {
+    /// Create the filter for the generation of the given message, with the global rules and the
+    /// rules of the assistant of the chat, if any.
+    pub fn new(
+        config: &OutputComplianceConfig,
+        assistant_id: Option<Uuid>,
+        message_id: Uuid,
+    ) -> Result<Self, Report> {
+        let assistant_rules = assistant_id
+            .and_then(|assistant_id| config.assistants.get(&assistant_id.to_string()))
+            .map(|assistant| &assistant.rules);
+        let mut rules = Vec::new();
+        let mut holdback_chars = 0;
+        for (rule_id, rule) in config
+            .rules
+            .iter()
+            .chain(assistant_rules.into_iter().flatten())
+        {
+            let pattern = match rule.r#type {
+                PromptPatternType::Fixed => {
+                    let phrase_chars = rule.pattern.chars().count();
+                    holdback_chars = holdback_chars.max(phrase_chars.saturating_sub(1));
+                    regex::escape(&rule.pattern)
+                }
+                PromptPatternType::Regex => {
+                    holdback_chars = config.max_holdback_chars;
+                    rule.pattern.clone()
+                }
+            };
+            let regex = RegexBuilder::new(&pattern)
+                .case_insensitive(rule.case_insensitive)
+                .build()
+                .wrap_err_with(|| {
+                    format!("Failed to compile output compliance rule '{rule_id}'")
+                })?;
+            rules.push(CompiledRule {
+                id: rule_id.clone(),
+                regex,
+                action: rule.action,
+            });
+        }
+        // Rules that match at the same position are applied in the order of their IDs
+        rules.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Ok(Self {
+            message_id,
+            rules,
+            holdback_chars: holdback_chars.min(config.max_holdback_chars),
+            pending: String::new(),
+            matches: Vec::new(),
+        })
+    }
+
+    /// Add streamed text, and return the text that can be sent on, with the matches of `mask`
+    /// rules replaced. The end of the text is held back until it can't be the start of a match
+    /// anymore.
+    pub fn push(&mut self, text: &str) -> Result<String, OutputComplianceViolation> {
+        if self.rules.is_empty() {
+            return Ok(text.to_string());
+        }
+        self.pending.push_str(text);
+        self.release(false)
+    }
+
+    /// Return the text that is still held back, at the end of the streamed text.
+    pub fn finish(&mut self) -> Result<String, OutputComplianceViolation> {
+        self.release(true)
+    }
+
+    /// Apply the rules to a complete text, e.g. of a chat provider that doesn't stream its answer.
+    pub fn filter_complete(&mut self, text: &str) -> Result<String, OutputComplianceViolation> {
+        let mut released = self.push(text)?;
+        released.push_str(&self.finish()?);
+        Ok(released)
+    }
+
+    /// The matches of the rules so far, in the order they were found.
+    pub fn into_matches(self) -> Vec<OutputComplianceMatch> {
+        self.matches
+    }
+
+    fn release(&mut self, complete: bool) -> Result<String, OutputComplianceViolation> {
+        let mut released = String::new();
+        loop {
+            let boundary = if complete {
+                self.pending.len()
+            } else {
+                self.holdback_boundary()
+            };
+            match self.first_match() {
+                Some((start, end, rule_index)) if start < boundary => {
+                    let rule = &self.rules[rule_index];
+                    tracing::info!(
+                        message_id = %self.message_id,
+                        rule_id = %rule.id,
+                        action = ?rule.action,
+                        "Generated text matched an output compliance rule"
+                    );
+                    report_output_compliance_match(&rule.id, rule.action);
+                    self.matches.push(OutputComplianceMatch {
+                        rule_id: rule.id.clone(),
+                        action: rule.action,
+                    });
+                    match rule.action {
+                        OutputComplianceAction::Abort => {
+                            self.pending.clear();
+                            return Err(OutputComplianceViolation {
+                                rule_id: rule.id.clone(),
+                            });
+                        }
+                        OutputComplianceAction::Mask => {
+                            released.push_str(&self.pending[..start]);
+                            released.push_str(OUTPUT_COMPLIANCE_MASK);
+                            self.pending.drain(..end);
+                        }
+                    }
+                }
+                _ => {
+                    released.push_str(&self.pending[..boundary]);
+                    self.pending.drain(..boundary);
+                    return Ok(released);
+                }
+            }
+        }
+    }
+
+    /// Byte index up to which the pending text can be released, keeping `holdback_chars` back.
+    fn holdback_boundary(&self) -> usize {
+        let pending_chars = self.pending.chars().count();
+        if pending_chars <= self.holdback_chars {
+            return 0;
+        }
+        self.pending
+            .char_indices()
+            .nth(pending_chars - self.holdback_chars)
+            .map_or(self.pending.len(), |(index, _)| index)
+    }
+
+    /// The earliest non-empty match of any rule in the pending text, as `(start, end, rule_index)`.
+    fn first_match(&self) -> Option<(usize, usize, usize)> {
+        self.rules
+            .iter()
+            .enumerate()
+            .filter_map(|(rule_index, rule)| {
+                rule.regex
+                    .find_iter(&self.pending)
+                    .find(|found| !found.is_empty())
+                    .map(|found| (found.start(), found.end(), rule_index))
+            })
+            .min_by_key(|&(start, _, _)| start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AssistantOutputComplianceConfig, OutputComplianceRuleConfig};
+    use std::collections::HashMap;
+
+    fn rule(
+        r#type: PromptPatternType,
+        pattern: &str,
+        action: OutputComplianceAction,
+    ) -> OutputComplianceRuleConfig {
+        OutputComplianceRuleConfig {
+            r#type,
+            pattern: pattern.to_string(),
+            case_insensitive: true,
+            action,
+        }
+    }
+
+    fn filter(rules: Vec<(&str, OutputComplianceRuleConfig)>) -> OutputComplianceFilter {
+        let config = OutputComplianceConfig {
+            rules: rules
+                .into_iter()
+                .map(|(rule_id, rule)| (rule_id.to_string(), rule))
+                .collect(),
+            ..Default::default()
+        };
+        OutputComplianceFilter::new(&config, None, Uuid::nil()).expect("valid rules")
+    }
+
+    /// Push the chunks and finish, and return the released text.
+    fn stream(
+        filter: &mut OutputComplianceFilter,
+        chunks: &[&str],
+    ) -> Result<String, OutputComplianceViolation> {
+        let mut released = String::new();
+        for chunk in chunks {
+            released.push_str(&filter.push(chunk)?);
+        }
+        released.push_str(&filter.finish()?);
+        Ok(released)
+    }
+
+    #[test]
+    fn masks_phrase_split_across_chunks() {
+        let mut filter = filter(vec![(
+            "competitor",
+            rule(
+                PromptPatternType::Fixed,
+                "Acme Corp",
+                OutputComplianceAction::Mask,
+            ),
+        )]);
+
+        let first = filter.push("Try the products of Acme").unwrap();
+        assert_eq!(first, "Try the products");
+        let released = stream(&mut filter, &[" Corp instead."]).unwrap();
+
+        assert_eq!(released, " of [redacted] instead.");
+        assert_eq!(
+            filter.into_matches(),
+            vec![OutputComplianceMatch {
+                rule_id: "competitor".to_string(),
+                action: OutputComplianceAction::Mask,
+            }]
+        );
+    }
+
+    #[test]
+    fn aborts_before_phrase_split_across_chunks_is_released() {
+        let mut filter = filter(vec![(
+            "competitor",
+            rule(
+                PromptPatternType::Fixed,
+                "Acme Corp",
+                OutputComplianceAction::Abort,
+            ),
+        )]);
+
+        let first = filter.push("Try ACME").unwrap();
+        assert!(!first.to_lowercase().contains("acme"));
+        let violation = filter.push(" corp instead.").unwrap_err();
+
+        assert_eq!(violation.rule_id, "competitor");
+    }
+
+    #[test]
+    fn respects_case_sensitivity() {
+        let mut case_sensitive = rule(
+            PromptPatternType::Fixed,
+            "Acme",
+            OutputComplianceAction::Mask,
+        );
+        case_sensitive.case_insensitive = false;
+        let mut filter = filter(vec![("competitor", case_sensitive)]);
+
+        let released = stream(&mut filter, &["acme and Ac", "me"]).unwrap();
+
+        assert_eq!(released, "acme and [redacted]");
+    }
+
+    #[test]
+    fn masks_regex_matches_at_the_end_of_the_text() {
+        let mut filter = filter(vec![(
+            "ticket",
+            rule(
+                PromptPatternType::Regex,
+                r"TICKET-\d+",
+                OutputComplianceAction::Mask,
+            ),
+        )]);
+
+        let released = stream(&mut filter, &["See ticket-12", "34"]).unwrap();
+
+        assert_eq!(released, "See [redacted]");
+    }
+
+    #[test]
+    fn holds_back_at_most_the_configured_number_of_characters() {
+        let config = OutputComplianceConfig {
+            rules: HashMap::from([(
+                "ticket".to_string(),
+                rule(
+                    PromptPatternType::Regex,
+                    r"TICKET-\d+",
+                    OutputComplianceAction::Mask,
+                ),
+            )]),
+            max_holdback_chars: 5,
+            ..Default::default()
+        };
+        let mut filter = OutputComplianceFilter::new(&config, None, Uuid::nil()).unwrap();
+
+        assert_eq!(filter.push("Hello wörld").unwrap(), "Hello ");
+        assert_eq!(filter.finish().unwrap(), "wörld");
+    }
+
+    #[test]
+    fn fixed_phrases_hold_back_one_character_less_than_the_longest_phrase() {
+        let mut filter = filter(vec![(
+            "competitor",
+            rule(
+                PromptPatternType::Fixed,
+                "Acme",
+                OutputComplianceAction::Mask,
+            ),
+        )]);
+
+        assert_eq!(filter.push("Hello world").unwrap(), "Hello wo");
+    }
+
+    #[test]
+    fn passes_text_through_without_rules() {
+        let mut filter = filter(vec![]);
+
+        assert_eq!(filter.push("Acme Corp").unwrap(), "Acme Corp");
+        assert_eq!(filter.finish().unwrap(), "");
+    }
+
+    #[test]
+    fn applies_the_rules_of_the_assistant() {
+        let assistant_id = Uuid::new_v4();
+        let config = OutputComplianceConfig {
+            assistants: HashMap::from([(
+                assistant_id.to_string(),
+                AssistantOutputComplianceConfig {
+                    rules: HashMap::from([(
+                        "internal".to_string(),
+                        rule(
+                            PromptPatternType::Fixed,
+                            "Project X",
+                            OutputComplianceAction::Mask,
+                        ),
+                    )]),
+                },
+            )]),
+            ..Default::default()
+        };
+
+        let mut with_assistant =
+            OutputComplianceFilter::new(&config, Some(assistant_id), Uuid::nil()).unwrap();
+        let mut without_assistant =
+            OutputComplianceFilter::new(&config, None, Uuid::nil()).unwrap();
+
+        assert_eq!(
+            stream(&mut with_assistant, &["About Project X"]).unwrap(),
+            "About [redacted]"
+        );
+        assert_eq!(
+            stream(&mut without_assistant, &["About Project X"]).unwrap(),
+            "About Project X"
+        );
+    }
+}
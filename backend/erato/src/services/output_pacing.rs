@@ -0,0 +1,389 @@
+//! Pacing of the text streamed to clients (see `model_settings.output_pacing` of the chat
+//! providers).
+//!
+//! Some providers return a whole answer within a few hundred milliseconds, as a handful of large
+//! chunks, which clients render as one blob. With pacing, the text deltas of a generation are
+//! split into chunks of at most `max_chunk_chars` characters, which are emitted at about
+//! `target_chars_per_second`. Other events keep their position in the stream, but are never
+//! delayed by more than `max_delay_ms`: once that is reached, the text before them is emitted at
+//! once.
+//!
+//! Only the stream of a client is paced. The stored message, and the event history that resumed
+//! streams are replayed from, contain the text as it was generated.
+
+use crate::config::OutputPacingConfig;
+use crate::services::background_tasks::StreamingEvent;
+use futures::{Stream, StreamExt};
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Schedules the events of a stream according to the pacing config of its generation.
+#[derive(Debug, Default)]
+pub struct OutputPacer {
+    /// Events waiting to be emitted, with the time they are due at
+    queue: VecDeque<(Instant, StreamingEvent)>,
+    /// When the next chunk of text may be emitted at the earliest
+    next_text_at: Option<Instant>,
+    /// Maximum delay of other events, from the config of the last paced text
+    max_delay: Duration,
+}
+
+impl OutputPacer {
+    /// Schedule an event that arrived at `now`. Text deltas are paced if `config` enables it.
+    pub fn push(
+        &mut self,
+        event: StreamingEvent,
+        config: Option<&OutputPacingConfig>,
+        now: Instant,
+    ) {
+        match (event, config) {
+            (
+                StreamingEvent::TextDelta {
+                    message_id,
+                    content_index,
+                    new_text,
+                },
+                Some(config),
+            ) if config.enabled => {
+                self.max_delay = Duration::from_millis(config.max_delay_ms);
+                let mut at = self.next_text_at.map_or(now, |next_at| next_at.max(now));
+                for chunk in split_text(&new_text, config.max_chunk_chars) {
+                    let duration = emission_duration(chunk.chars().count(), config);
+                    self.queue.push_back((
+                        at,
+                        StreamingEvent::TextDelta {
+                            message_id,
+                            content_index,
+                            new_text: chunk,
+                        },
+                    ));
+                    at += duration;
+                }
+                self.next_text_at = Some(at);
+            }
+            (event, _) => {
+                let deadline = now + self.max_delay;
+                for (at, _) in self.queue.iter_mut() {
+                    if *at > deadline {
+                        *at = deadline;
+                    }
+                }
+                self.next_text_at = self.next_text_at.map(|next_at| next_at.min(deadline));
+                let at = self.queue.back().map_or(now, |(at, _)| (*at).max(now));
+                self.queue.push_back((at, event));
+            }
+        }
+    }
+
+    /// When the next event is due, if any is waiting.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.queue.front().map(|(at, _)| *at)
+    }
+
+    /// Take the next event, if it is due at `now`.
+    pub fn pop_due(&mut self, now: Instant) -> Option<StreamingEvent> {
+        if self.next_due()? > now {
+            return None;
+        }
+        self.queue.pop_front().map(|(_, event)| event)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+/// Pace the events of a generation. `config` is looked up for every text delta, as the pacing
+/// config is only known once the chat provider of the generation was chosen.
+pub fn pace_streaming_events<S, F>(events: S, config: F) -> impl Stream<Item = StreamingEvent>
+where
+    S: Stream<Item = StreamingEvent> + Unpin,
+    F: Fn() -> Option<OutputPacingConfig>,
+{
+    struct State<S, F> {
+        events: S,
+        config: F,
+        pacer: OutputPacer,
+        input_done: bool,
+    }
+
+    let state = State {
+        events,
+        config,
+        pacer: OutputPacer::default(),
+        input_done: false,
+    };
+    futures::stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.pacer.pop_due(Instant::now()) {
+                return Some((event, state));
+            }
+            let next_due = state.pacer.next_due();
+            if state.input_done {
+                tokio::time::sleep_until(next_due?).await;
+                continue;
+            }
+            tokio::select! {
+                event = state.events.next() => match event {
+                    Some(event) => {
+                        let config = match &event {
+                            StreamingEvent::TextDelta { .. } => (state.config)(),
+                            _ => None,
+                        };
+                        state.pacer.push(event, config.as_ref(), Instant::now());
+                    }
+                    None => state.input_done = true,
+                },
+                _ = sleep_until_due(next_due) => {}
+            }
+        }
+    })
+}
+
+async fn sleep_until_due(next_due: Option<Instant>) {
+    match next_due {
+        Some(at) => tokio::time::sleep_until(at).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Split a text into chunks of at most `max_chunk_chars` characters. Chunks end after the last
+/// whitespace that fits, so that words are only split if they are longer than a chunk.
+pub fn split_text(text: &str, max_chunk_chars: usize) -> Vec<String> {
+    let max_chunk_chars = max_chunk_chars.max(1);
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let Some((limit, _)) = rest.char_indices().nth(max_chunk_chars) else {
+            chunks.push(rest.to_string());
+            break;
+        };
+        let end = rest[..limit]
+            .char_indices()
+            .filter(|(_, c)| c.is_whitespace())
+            .map(|(index, c)| index + c.len_utf8())
+            .last()
+            .unwrap_or(limit);
+        chunks.push(rest[..end].to_string());
+        rest = &rest[end..];
+    }
+    chunks
+}
+
+/// How long emitting a chunk of text takes at the target rate.
+pub fn emission_duration(chars: usize, config: &OutputPacingConfig) -> Duration {
+    Duration::from_secs_f64(chars as f64 / f64::from(config.target_chars_per_second.max(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::prelude::Uuid;
+
+    fn pacing(target_chars_per_second: u32, max_chunk_chars: usize) -> OutputPacingConfig {
+        OutputPacingConfig {
+            enabled: true,
+            target_chars_per_second,
+            max_chunk_chars,
+            max_delay_ms: 1000,
+        }
+    }
+
+    fn text_delta(text: &str) -> StreamingEvent {
+        StreamingEvent::TextDelta {
+            message_id: Uuid::nil(),
+            content_index: 0,
+            new_text: text.to_string(),
+        }
+    }
+
+    /// Drain the pacer, returning each event with its delay after `start`.
+    fn drain(pacer: &mut OutputPacer, start: Instant) -> Vec<(Duration, StreamingEvent)> {
+        let mut events = Vec::new();
+        while let Some(at) = pacer.next_due() {
+            let event = pacer.pop_due(at).unwrap();
+            events.push((at - start, event));
+        }
+        events
+    }
+
+    fn delta_text(event: &StreamingEvent) -> &str {
+        match event {
+            StreamingEvent::TextDelta { new_text, .. } => new_text,
+            other => panic!("Expected a text delta, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn splits_text_at_whitespace() {
+        assert_eq!(
+            split_text("The quick brown fox jumps", 10),
+            vec!["The quick ", "brown fox ", "jumps"]
+        );
+    }
+
+    #[test]
+    fn splits_long_words_and_multibyte_characters() {
+        assert_eq!(split_text("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        assert_eq!(split_text("äöüß€", 2), vec!["äö", "üß", "€"]);
+        assert_eq!(split_text("short", 10), vec!["short"]);
+        assert!(split_text("", 10).is_empty());
+        assert_eq!(split_text("ab", 0), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn emission_duration_follows_target_rate() {
+        let config = pacing(100, 10);
+        assert_eq!(emission_duration(10, &config), Duration::from_millis(100));
+        assert_eq!(emission_duration(50, &config), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn paces_large_chunks_at_target_rate() {
+        let config = pacing(100, 10);
+        let start = Instant::now();
+        let mut pacer = OutputPacer::default();
+        pacer.push(text_delta(&"a".repeat(35)), Some(&config), start);
+
+        let events = drain(&mut pacer, start);
+        let delays: Vec<Duration> = events.iter().map(|(delay, _)| *delay).collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::ZERO,
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+                Duration::from_millis(300),
+            ]
+        );
+        let lengths: Vec<usize> = events
+            .iter()
+            .map(|(_, event)| delta_text(event).len())
+            .collect();
+        assert_eq!(lengths, vec![10, 10, 10, 5]);
+    }
+
+    #[test]
+    fn continues_after_the_scheduled_text() {
+        let config = pacing(100, 10);
+        let start = Instant::now();
+        let mut pacer = OutputPacer::default();
+        pacer.push(text_delta(&"a".repeat(20)), Some(&config), start);
+        pacer.push(
+            text_delta(&"b".repeat(10)),
+            Some(&config),
+            start + Duration::from_millis(50),
+        );
+
+        let delays: Vec<Duration> = drain(&mut pacer, start)
+            .iter()
+            .map(|(delay, _)| *delay)
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::ZERO,
+                Duration::from_millis(100),
+                Duration::from_millis(200)
+            ]
+        );
+    }
+
+    #[test]
+    fn slow_text_is_not_delayed() {
+        let config = pacing(100, 10);
+        let start = Instant::now();
+        let mut pacer = OutputPacer::default();
+        pacer.push(text_delta("abcde"), Some(&config), start);
+        assert!(pacer.pop_due(start).is_some());
+
+        let later = start + Duration::from_secs(1);
+        pacer.push(text_delta("fghij"), Some(&config), later);
+        assert!(pacer.pop_due(later).is_some());
+        assert!(pacer.is_empty());
+    }
+
+    #[test]
+    fn other_events_keep_their_position() {
+        let config = pacing(100, 10);
+        let start = Instant::now();
+        let mut pacer = OutputPacer::default();
+        pacer.push(text_delta(&"a".repeat(30)), Some(&config), start);
+        pacer.push(StreamingEvent::StreamEnd, Some(&config), start);
+
+        let events = drain(&mut pacer, start);
+        assert_eq!(events.len(), 4);
+        let (delay, event) = events.last().unwrap();
+        assert_eq!(*delay, Duration::from_millis(200));
+        assert!(matches!(event, StreamingEvent::StreamEnd));
+    }
+
+    #[test]
+    fn other_events_are_delayed_at_most_max_delay() {
+        let config = pacing(100, 10);
+        let start = Instant::now();
+        let mut pacer = OutputPacer::default();
+        // 5 seconds of text at the target rate
+        pacer.push(text_delta(&"a".repeat(500)), Some(&config), start);
+        pacer.push(StreamingEvent::StreamEnd, Some(&config), start);
+
+        let events = drain(&mut pacer, start);
+        let text: String = events[..events.len() - 1]
+            .iter()
+            .map(|(_, event)| delta_text(event))
+            .collect();
+        assert_eq!(text, "a".repeat(500));
+        assert!(
+            events
+                .iter()
+                .all(|(delay, _)| *delay <= Duration::from_millis(1000))
+        );
+        assert!(matches!(
+            events.last().unwrap(),
+            (delay, StreamingEvent::StreamEnd) if *delay == Duration::from_millis(1000)
+        ));
+    }
+
+    #[test]
+    fn disabled_pacing_passes_events_through() {
+        let config = OutputPacingConfig {
+            enabled: false,
+            ..pacing(100, 10)
+        };
+        let start = Instant::now();
+        let mut pacer = OutputPacer::default();
+        pacer.push(text_delta(&"a".repeat(100)), Some(&config), start);
+        pacer.push(text_delta(&"b".repeat(100)), None, start);
+
+        let events = drain(&mut pacer, start);
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|(delay, _)| delay.is_zero()));
+    }
+
+    #[tokio::test]
+    async fn paced_stream_emits_all_text_in_order() {
+        let events = futures::stream::iter(vec![
+            text_delta(&"a".repeat(25)),
+            text_delta(&"b".repeat(5)),
+            StreamingEvent::StreamEnd,
+        ]);
+        let start = Instant::now();
+        let paced: Vec<(Duration, StreamingEvent)> =
+            pace_streaming_events(events, || Some(pacing(1000, 10)))
+                .map(|event| (start.elapsed(), event))
+                .collect()
+                .await;
+
+        let texts: Vec<&str> = paced
+            .iter()
+            .filter(|(_, event)| matches!(event, StreamingEvent::TextDelta { .. }))
+            .map(|(_, event)| delta_text(event))
+            .collect();
+        assert_eq!(texts, vec!["aaaaaaaaaa", "aaaaaaaaaa", "aaaaa", "bbbbb"]);
+        let (elapsed, last) = paced.last().unwrap();
+        assert!(matches!(last, StreamingEvent::StreamEnd));
+        // The last chunk of text is due 25ms after the first one
+        assert!(*elapsed >= Duration::from_millis(25));
+    }
+}
@@ -0,0 +1,325 @@
+//! Post-processing of generated answers by webhooks (see `chat.output_transformers` in the
+//! config), e.g. to enforce terminology, translate answers or append disclaimers.
+//!
+//! `final` transformers receive the content parts of the completed answer, and return the content
+//! that is stored and sent with the `assistant_message_completed` event. `streaming_buffered`
+//! transformers receive the streamed text in chunks of `buffer_chars` characters, and return the
+//! text that is streamed in its place, which adds the time of a request to every chunk.
+//!
+//! Requests are signed with the `secret` of the transformer (see
+//! [`output_transformer_signature`]). A transformer that fails or can't be reached either passes
+//! the untransformed content on, or ends the generation, depending on its `fail_mode`.
+
+use crate::config::{OutputTransformerConfig, OutputTransformerFailMode, OutputTransformerMode};
+use crate::models::message::{ContentPart, OutputTransformation};
+use crate::services::sentry::capture_report;
+use chrono::Utc;
+use eyre::{Report, WrapErr, eyre};
+use hmac::{Hmac, KeyInit, Mac};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value as JsonValue, json};
+use sha2::Sha256;
+use sqlx::types::Uuid;
+use std::time::Duration;
+
+/// Header carrying the signature of a request, see [`output_transformer_signature`].
+pub const OUTPUT_TRANSFORMER_SIGNATURE_HEADER: &str = "X-Erato-Signature";
+/// Header carrying the Unix timestamp, in seconds, the request was signed at.
+pub const OUTPUT_TRANSFORMER_TIMESTAMP_HEADER: &str = "X-Erato-Timestamp";
+
+/// A transformer with `fail_mode = "closed"` that failed, which ends the generation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputTransformerFailure {
+    pub transformer: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FinalTransformResponse {
+    content: Vec<ContentPart>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamingTransformResponse {
+    text: String,
+}
+
+/// A `streaming_buffered` transformer, with the text that was pushed to it but not sent yet.
+struct StreamingStage {
+    config: OutputTransformerConfig,
+    pending: String,
+}
+
+/// Applies the configured output transformers to a generation.
+pub struct OutputTransformers {
+    message_id: Uuid,
+    client: reqwest::Client,
+    streaming_stages: Vec<StreamingStage>,
+    final_transformers: Vec<OutputTransformerConfig>,
+    transformations: Vec<OutputTransformation>,
+}
+
+impl OutputTransformers {
+    /// Create the transformers for the generation of the given message.
+    pub fn new(config: &[OutputTransformerConfig], message_id: Uuid) -> Self {
+        let (streaming, final_transformers): (Vec<_>, Vec<_>) = config
+            .iter()
+            .cloned()
+            .partition(|transformer| transformer.mode == OutputTransformerMode::StreamingBuffered);
+        Self {
+            message_id,
+            client: reqwest::Client::new(),
+            streaming_stages: streaming
+                .into_iter()
+                .map(|config| StreamingStage {
+                    config,
+                    pending: String::new(),
+                })
+                .collect(),
+            final_transformers,
+            transformations: Vec::new(),
+        }
+    }
+
+    /// Add streamed text, and return the text that can be sent on. Text is passed through the
+    /// `streaming_buffered` transformers in order, each of which holds it back until it has
+    /// `buffer_chars` characters.
+    pub async fn push(&mut self, text: &str) -> Result<String, OutputTransformerFailure> {
+        self.release(text, false).await
+    }
+
+    /// Return the text that is still held back, at the end of the streamed text.
+    pub async fn finish(&mut self) -> Result<String, OutputTransformerFailure> {
+        self.release("", true).await
+    }
+
+    /// Apply the `streaming_buffered` transformers to a complete text, e.g. of a chat provider that
+    /// doesn't stream its answer.
+    pub async fn transform_complete(
+        &mut self,
+        text: &str,
+    ) -> Result<String, OutputTransformerFailure> {
+        let mut released = self.push(text).await?;
+        released.push_str(&self.finish().await?);
+        Ok(released)
+    }
+
+    /// Apply the `final` transformers to the content of the completed answer.
+    pub async fn transform_final(
+        &mut self,
+        content: Vec<ContentPart>,
+    ) -> Result<Vec<ContentPart>, OutputTransformerFailure> {
+        let mut content = content;
+        for transformer in &self.final_transformers {
+            let body = json!({
+                "transformer": transformer.name,
+                "mode": "final",
+                "message_id": self.message_id,
+                "content": content,
+            });
+            match call_transformer::<FinalTransformResponse>(&self.client, transformer, &body)
+                .await
+            {
+                Ok(response) => {
+                    let changed_bytes =
+                        changed_bytes(&text_of(&content), &text_of(&response.content));
+                    record_transformation(&mut self.transformations, transformer, changed_bytes);
+                    content = response.content;
+                }
+                Err(error) => handle_failure(self.message_id, transformer, &error)?,
+            }
+        }
+        Ok(content)
+    }
+
+    /// The transformations applied so far, one per transformer, in the order they were first
+    /// applied.
+    pub fn into_transformations(self) -> Vec<OutputTransformation> {
+        self.transformations
+    }
+
+    async fn release(
+        &mut self,
+        text: &str,
+        complete: bool,
+    ) -> Result<String, OutputTransformerFailure> {
+        let mut text = text.to_string();
+        for stage in &mut self.streaming_stages {
+            stage.pending.push_str(&text);
+            let buffered = stage.pending.chars().count() >= stage.config.buffer_chars;
+            if stage.pending.is_empty() || !(complete || buffered) {
+                text = String::new();
+                continue;
+            }
+            let chunk = std::mem::take(&mut stage.pending);
+            let body = json!({
+                "transformer": stage.config.name,
+                "mode": "streaming_buffered",
+                "message_id": self.message_id,
+                "text": chunk,
+            });
+            text = match call_transformer::<StreamingTransformResponse>(
+                &self.client,
+                &stage.config,
+                &body,
+            )
+            .await
+            {
+                Ok(response) => {
+                    let changed_bytes = changed_bytes(&chunk, &response.text);
+                    record_transformation(&mut self.transformations, &stage.config, changed_bytes);
+                    response.text
+                }
+                Err(error) => {
+                    handle_failure(self.message_id, &stage.config, &error)?;
+                    chunk
+                }
+            };
+        }
+        Ok(text)
+    }
+}
+
+/// Signature of a request to an output transformer: `sha256=` followed by the hex-encoded
+/// HMAC-SHA256 of `<timestamp>.<body>`, keyed with the secret of the transformer.
+pub fn output_transformer_signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{timestamp}.").as_bytes());
+    mac.update(body);
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("sha256={signature}")
+}
+
+async fn call_transformer<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    transformer: &OutputTransformerConfig,
+    body: &JsonValue,
+) -> Result<T, Report> {
+    let body =
+        serde_json::to_vec(body).wrap_err("Failed to serialize output transformer request")?;
+    let timestamp = Utc::now().timestamp();
+    let signature =
+        output_transformer_signature(transformer.secret.expose_secret(), timestamp, &body);
+    let response = client
+        .post(&transformer.url)
+        .timeout(Duration::from_millis(transformer.timeout_ms))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(OUTPUT_TRANSFORMER_TIMESTAMP_HEADER, timestamp.to_string())
+        .header(OUTPUT_TRANSFORMER_SIGNATURE_HEADER, signature)
+        .body(body)
+        .send()
+        .await
+        .wrap_err("Failed to reach the output transformer")?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(eyre!("Output transformer responded with status {status}"));
+    }
+    let body = response
+        .bytes()
+        .await
+        .wrap_err("Failed to read output transformer response")?;
+    serde_json::from_slice(&body).wrap_err("Failed to parse output transformer response")
+}
+
+/// Log a failed transformer, and end the generation if its `fail_mode` is `closed`.
+fn handle_failure(
+    message_id: Uuid,
+    transformer: &OutputTransformerConfig,
+    error: &Report,
+) -> Result<(), OutputTransformerFailure> {
+    tracing::warn!(
+        %message_id,
+        transformer = %transformer.name,
+        fail_mode = ?transformer.fail_mode,
+        error = ?error,
+        "Output transformer failed"
+    );
+    capture_report(error);
+    match transformer.fail_mode {
+        OutputTransformerFailMode::Open => Ok(()),
+        OutputTransformerFailMode::Closed => Err(OutputTransformerFailure {
+            transformer: transformer.name.clone(),
+        }),
+    }
+}
+
+fn record_transformation(
+    transformations: &mut Vec<OutputTransformation>,
+    transformer: &OutputTransformerConfig,
+    changed_bytes: usize,
+) {
+    match transformations
+        .iter_mut()
+        .find(|transformation| transformation.transformer == transformer.name)
+    {
+        Some(transformation) => transformation.changed_bytes += changed_bytes,
+        None => transformations.push(OutputTransformation {
+            transformer: transformer.name.clone(),
+            changed_bytes,
+        }),
+    }
+}
+
+fn text_of(content: &[ContentPart]) -> String {
+    content
+        .iter()
+        .filter_map(|part| match part {
+            ContentPart::Text(text) => Some(text.text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Number of bytes changed between two texts: the length of the longer of the two parts that
+/// remain after removing their common prefix and suffix.
+fn changed_bytes(original: &str, transformed: &str) -> usize {
+    let (original, transformed) = (original.as_bytes(), transformed.as_bytes());
+    let prefix = original
+        .iter()
+        .zip(transformed)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = original[prefix..]
+        .iter()
+        .rev()
+        .zip(transformed[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (original.len() - prefix - suffix).max(transformed.len() - prefix - suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_bytes_counts_the_differing_middle() {
+        assert_eq!(changed_bytes("Hello world", "Hello world"), 0);
+        assert_eq!(changed_bytes("Hello world", "Hello brave world"), 6);
+        assert_eq!(changed_bytes("Hello world", "Hello"), 6);
+        assert_eq!(changed_bytes("colour", "color"), 1);
+        assert_eq!(changed_bytes("", "Disclaimer"), 10);
+    }
+
+    #[test]
+    fn signature_covers_timestamp_and_body() {
+        let signature = output_transformer_signature("secret", 1700000000, b"{}");
+
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_ne!(
+            signature,
+            output_transformer_signature("secret", 1700000001, b"{}")
+        );
+        assert_ne!(
+            signature,
+            output_transformer_signature("other", 1700000000, b"{}")
+        );
+    }
+}
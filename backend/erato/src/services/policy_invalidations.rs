@@ -0,0 +1,237 @@
+//! Propagation of policy invalidations to all backend instances.
+//!
+//! Every instance keeps its own in-memory policy data, so a change that invalidates it, e.g. a
+//! created chat, has to reach the other instances as well. Such changes record an invalidation
+//! in the `policy_invalidations` outbox, in the same transaction as the change. Every insert into
+//! the outbox is notified on the [`POLICY_INVALIDATIONS_CHANNEL`] by a database trigger, which
+//! the listener of each instance passes on to its [`GlobalPolicyEngine`]. As notifications are
+//! lost while the listener is disconnected, the listener also polls the outbox for IDs above the
+//! high-water mark of the instance. The policy data is invalidated by the next rebuild check.
+
+use crate::db::entity::policy_invalidations;
+use crate::db::entity::prelude::*;
+use crate::metrics::report_policy_invalidation_propagation;
+use crate::state::{AppState, GlobalPolicyEngine};
+use eyre::Report;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{
+    ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder,
+};
+use serde::Deserialize;
+use sqlx::postgres::{PgListener, PgNotification};
+use std::time::Duration;
+
+/// Name of the listener in the background task manager.
+pub const POLICY_INVALIDATION_LISTENER_TASK: &str = "policy_invalidation_listener";
+/// Channel the inserts into the outbox are notified on.
+pub const POLICY_INVALIDATIONS_CHANNEL: &str = "policy_invalidations";
+
+/// Interval of the polls of the outbox, which bounds the propagation delay of invalidations
+/// whose notification was lost.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Invalidations are kept for this long, which is well above the time all instances need to
+/// observe them.
+const RETENTION_SECS: i64 = 60 * 60;
+
+/// The kind of change that invalidated the policy data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyInvalidationReason {
+    ChatCreated,
+    ChatArchived,
+    ChatTransferred,
+    AssistantTransferred,
+    FileLinked,
+    ShareGrantChanged,
+    /// Requested by an operator, e.g. through `erato admin policy-rebuild`
+    ManualRebuild,
+}
+
+impl PolicyInvalidationReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PolicyInvalidationReason::ChatCreated => "chat_created",
+            PolicyInvalidationReason::ChatArchived => "chat_archived",
+            PolicyInvalidationReason::ChatTransferred => "chat_transferred",
+            PolicyInvalidationReason::AssistantTransferred => "assistant_transferred",
+            PolicyInvalidationReason::FileLinked => "file_linked",
+            PolicyInvalidationReason::ShareGrantChanged => "share_grant_changed",
+            PolicyInvalidationReason::ManualRebuild => "manual_rebuild",
+        }
+    }
+}
+
+/// Payload of the notifications on [`POLICY_INVALIDATIONS_CHANNEL`]
+#[derive(Debug, Deserialize)]
+struct PolicyInvalidationNotification {
+    id: i64,
+    created_at: DateTimeWithTimeZone,
+}
+
+/// Record an invalidation of the policy data of all instances.
+///
+/// Meant to be called with the transaction of the change that invalidates the policy data, so
+/// that other instances don't observe the invalidation before the change.
+pub async fn record_policy_invalidation<C: ConnectionTrait>(
+    conn: &C,
+    reason: PolicyInvalidationReason,
+) -> Result<(), Report> {
+    PolicyInvalidations::insert(policy_invalidations::ActiveModel {
+        reason: ActiveValue::Set(reason.as_str().to_string()),
+        ..Default::default()
+    })
+    .exec(conn)
+    .await?;
+    Ok(())
+}
+
+/// Observe the invalidations of the outbox above the high-water mark of the policy engine.
+///
+/// Returns whether there were any.
+pub async fn poll_policy_invalidations(
+    db: &DatabaseConnection,
+    policy: &GlobalPolicyEngine,
+) -> Result<bool, Report> {
+    let latest = PolicyInvalidations::find()
+        .filter(policy_invalidations::Column::Id.gt(policy.invalidation_high_water_mark()))
+        .order_by_desc(policy_invalidations::Column::Id)
+        .one(db)
+        .await?;
+    let Some(latest) = latest else {
+        return Ok(false);
+    };
+    if policy.observe_invalidation(latest.id) {
+        report_propagation(latest.created_at, "poll");
+    }
+    Ok(true)
+}
+
+/// Delete the invalidations older than [`RETENTION_SECS`].
+///
+/// Returns the number of deleted invalidations.
+pub async fn delete_expired_policy_invalidations(db: &DatabaseConnection) -> Result<u64, Report> {
+    let expired_before = chrono::Utc::now() - chrono::Duration::seconds(RETENTION_SECS);
+    let result = PolicyInvalidations::delete_many()
+        .filter(policy_invalidations::Column::CreatedAt.lt(expired_before))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+/// Start the listener for invalidations of other instances, if it is not already running.
+pub fn start_policy_invalidation_listener(app_state: &AppState) -> bool {
+    let db = app_state.db.clone();
+    let policy = app_state.global_policy_engine.clone();
+    app_state
+        .background_tasks
+        .start_maintenance_task(POLICY_INVALIDATION_LISTENER_TASK, async move {
+            // The policy data is built after the start anyway, so the invalidations up to now
+            // don't need to be applied
+            match PolicyInvalidations::find()
+                .order_by_desc(policy_invalidations::Column::Id)
+                .one(&db)
+                .await
+            {
+                Ok(latest) => {
+                    policy.init_invalidation_high_water_mark(latest.map_or(0, |latest| latest.id));
+                }
+                Err(err) => {
+                    tracing::warn!(error = %err, "Failed to load the latest policy invalidation");
+                }
+            }
+
+            let mut listener = connect_listener(&db).await;
+            let mut poll_interval = tokio::time::interval(POLL_INTERVAL);
+            let mut cleanup_interval = tokio::time::interval(CLEANUP_INTERVAL);
+            loop {
+                tokio::select! {
+                    notification = recv(&mut listener) => match notification {
+                        Ok(notification) => observe_notification(&policy, &notification),
+                        Err(err) => {
+                            tracing::warn!(
+                                error = %err,
+                                "Lost the connection for policy invalidation notifications"
+                            );
+                            // Reconnected by the next poll, which also covers the notifications
+                            // missed in between
+                            listener = None;
+                        }
+                    },
+                    _ = poll_interval.tick() => {
+                        if listener.is_none() {
+                            listener = connect_listener(&db).await;
+                        }
+                        if let Err(err) = poll_policy_invalidations(&db, &policy).await {
+                            tracing::warn!(error = %err, "Failed to poll policy invalidations");
+                        }
+                    }
+                    _ = cleanup_interval.tick() => {
+                        match delete_expired_policy_invalidations(&db).await {
+                            Ok(0) => {}
+                            Ok(deleted) => {
+                                tracing::debug!(deleted, "Deleted expired policy invalidations");
+                            }
+                            Err(err) => {
+                                tracing::warn!(
+                                    error = %err,
+                                    "Failed to delete expired policy invalidations"
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        })
+}
+
+async fn connect_listener(db: &DatabaseConnection) -> Option<PgListener> {
+    let result = async {
+        let mut listener = PgListener::connect_with(db.get_postgres_connection_pool()).await?;
+        listener.listen(POLICY_INVALIDATIONS_CHANNEL).await?;
+        Ok::<_, sqlx::Error>(listener)
+    }
+    .await;
+    match result {
+        Ok(listener) => Some(listener),
+        Err(err) => {
+            tracing::warn!(
+                error = %err,
+                "Failed to listen for policy invalidations, falling back to polling"
+            );
+            None
+        }
+    }
+}
+
+/// Wait for the next notification, or forever without a listener.
+async fn recv(listener: &mut Option<PgListener>) -> Result<PgNotification, sqlx::Error> {
+    match listener {
+        Some(listener) => listener.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+fn observe_notification(policy: &GlobalPolicyEngine, notification: &PgNotification) {
+    match serde_json::from_str::<PolicyInvalidationNotification>(notification.payload()) {
+        Ok(invalidation) => {
+            policy.observe_invalidation(invalidation.id);
+            report_propagation(invalidation.created_at, "notify");
+        }
+        Err(err) => {
+            tracing::warn!(
+                error = %err,
+                payload = notification.payload(),
+                "Failed to parse policy invalidation notification"
+            );
+        }
+    }
+}
+
+fn report_propagation(created_at: DateTimeWithTimeZone, source: &'static str) {
+    // Clocks of the database and the instance may differ slightly
+    let lag = (chrono::Utc::now() - created_at.with_timezone(&chrono::Utc))
+        .to_std()
+        .unwrap_or_default();
+    report_policy_invalidation_propagation(lag, source);
+}
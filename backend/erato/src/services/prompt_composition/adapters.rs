@@ -6,26 +6,42 @@ use crate::models::assistant::AssistantWithFiles;
 use crate::models::message::ContentPartImage;
 use crate::policy::prelude::*;
 use crate::server::api::v1beta::message_streaming::FileContentsForGeneration;
+use crate::services::file_parsing::{ParseLimits, parse_file};
+use crate::services::file_pointer_migration::existing_file_upload_ids;
 use crate::services::file_processing_cached;
 use crate::services::file_storage::{SharepointContext, is_missing_permissions_error};
+use crate::services::file_synopsis::{FileSynopsis, build_file_synopsis, format_file_manifest};
+use crate::services::generation_input_archive::GenerationInputArchive;
 use crate::state::AppState;
 use async_trait::async_trait;
 use eyre::{Context, ContextCompat, OptionExt, Report};
 use sea_orm::prelude::Uuid;
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use std::collections::HashSet;
 
 /// Database-backed implementation of the MessageRepository trait.
 /// Wraps existing functions from the models::message module.
+///
+/// Archived generation inputs are restored, so that they are composed like any other.
 pub struct DatabaseMessageRepository<'a> {
     pub conn: &'a sea_orm::DatabaseConnection,
     pub policy: &'a PolicyEngine,
     pub subject: &'a Subject,
+    pub generation_input_archive: &'a GenerationInputArchive,
 }
 
 #[async_trait]
 impl<'a> MessageRepository for DatabaseMessageRepository<'a> {
     async fn get_message_by_id(&self, message_id: &Uuid) -> Result<messages::Model, Report> {
-        crate::models::message::get_message_by_id(self.conn, self.policy, self.subject, message_id)
+        let message = crate::models::message::get_message_by_id(
+            self.conn,
+            self.policy,
+            self.subject,
+            message_id,
+        )
+        .await?;
+        self.generation_input_archive
+            .rehydrate(self.conn, message)
             .await
     }
 
@@ -56,6 +72,94 @@ impl<'a> MessageRepository for DatabaseMessageRepository<'a> {
     }
 }
 
+/// MessageRepository that serves one message which has not been saved to the database,
+/// and delegates all other lookups to the database.
+///
+/// Used to compose the prompt for a hypothetical user message, e.g. for token estimates
+/// and dry runs.
+pub struct SyntheticMessageRepository<'a> {
+    pub base: DatabaseMessageRepository<'a>,
+    pub synthetic_message: messages::Model,
+}
+
+#[async_trait]
+impl<'a> MessageRepository for SyntheticMessageRepository<'a> {
+    async fn get_message_by_id(&self, message_id: &Uuid) -> Result<messages::Model, Report> {
+        if *message_id == self.synthetic_message.id {
+            Ok(self.synthetic_message.clone())
+        } else {
+            self.base.get_message_by_id(message_id).await
+        }
+    }
+
+    async fn get_generation_input_messages(
+        &self,
+        previous_message_id: &Uuid,
+        num_messages: usize,
+    ) -> Result<Vec<messages::Model>, Report> {
+        let mut messages_vec = Vec::new();
+        let mut current_message_id = Some(*previous_message_id);
+        let mut count = 0;
+
+        while let Some(msg_id) = current_message_id {
+            if count >= num_messages {
+                break;
+            }
+
+            let message = if msg_id == self.synthetic_message.id {
+                self.synthetic_message.clone()
+            } else {
+                self.base.get_message_by_id(&msg_id).await?
+            };
+            current_message_id = message.previous_message_id;
+            messages_vec.push(message);
+            count += 1;
+        }
+
+        messages_vec.reverse();
+        Ok(messages_vec)
+    }
+}
+
+/// MessageRepository over messages that are only held in memory, e.g. the messages of an
+/// ephemeral chat.
+pub struct InMemoryMessageRepository<'a> {
+    pub messages: &'a [messages::Model],
+}
+
+#[async_trait]
+impl<'a> MessageRepository for InMemoryMessageRepository<'a> {
+    async fn get_message_by_id(&self, message_id: &Uuid) -> Result<messages::Model, Report> {
+        self.messages
+            .iter()
+            .find(|message| message.id == *message_id)
+            .cloned()
+            .ok_or_eyre("Message not found")
+    }
+
+    async fn get_generation_input_messages(
+        &self,
+        previous_message_id: &Uuid,
+        num_messages: usize,
+    ) -> Result<Vec<messages::Model>, Report> {
+        let mut messages_vec = Vec::new();
+        let mut current_message_id = Some(*previous_message_id);
+
+        while let Some(msg_id) = current_message_id {
+            if messages_vec.len() >= num_messages {
+                break;
+            }
+
+            let message = self.get_message_by_id(&msg_id).await?;
+            current_message_id = message.previous_message_id;
+            messages_vec.push(message);
+        }
+
+        messages_vec.reverse();
+        Ok(messages_vec)
+    }
+}
+
 /// AppState-backed implementation of the FileResolver trait.
 /// Wraps the file resolution logic from message_streaming.rs
 pub struct AppStateFileResolver<'a> {
@@ -100,18 +204,27 @@ impl<'a> FileResolver for AppStateFileResolver<'a> {
             .wrap_err("Failed to read file from storage")?;
 
         // Parse the file to extract text
-        let text = self
-            .app_state
-            .file_processor
-            .parse_file(file_bytes, content_type.as_deref())
-            .await
-            .wrap_err("Failed to parse file")?;
+        let text = parse_file(
+            self.app_state.file_processor.as_ref(),
+            file_bytes,
+            content_type.as_deref(),
+            ParseLimits::from_config(&self.app_state.config.file_processor),
+        )
+        .await
+        .wrap_err("Failed to parse file")?;
 
         // Remove null characters (for Postgres compatibility)
         let text = remove_null_characters(&text);
 
+        let synopsis = build_file_synopsis(
+            &text,
+            &file.filename,
+            content_type.as_deref(),
+            self.app_state.config.chat.file_synopsis_sentences,
+        );
+
         // Format the content
-        let content = format_successful_file_content(&file.filename, file_id, &text);
+        let content = format_successful_file_content(&file.filename, file_id, &text, &synopsis);
 
         Ok(content)
     }
@@ -231,6 +344,71 @@ impl<'a> FileResolver for AppStateFileResolver<'a> {
 
         Ok(is_image)
     }
+
+    async fn existing_file_ids(&self, file_ids: &[Uuid]) -> Result<HashSet<Uuid>, Report> {
+        existing_file_upload_ids(&self.app_state.db, file_ids).await
+    }
+
+    async fn resolve_file_manifest(&self, file_ids: &[Uuid]) -> Result<Option<String>, Report> {
+        if !self.app_state.config.chat.file_manifest {
+            return Ok(None);
+        }
+        let sharepoint_ctx = self.get_sharepoint_context();
+
+        let mut files = Vec::with_capacity(file_ids.len());
+        for file_id in file_ids {
+            let file = FileUploads::find_by_id(*file_id)
+                .one(&self.app_state.db)
+                .await?
+                .wrap_err("File upload not found")?;
+
+            // Audio files are represented by their transcript, which is not parsed by the file
+            // processor (see `file_resolution`)
+            let synopsis = if let Some(transcript) =
+                crate::models::file_upload::get_audio_transcript_if_ready(&file)
+            {
+                Some(build_file_synopsis(
+                    &transcript,
+                    &file.filename,
+                    None,
+                    self.app_state.config.chat.file_synopsis_sentences,
+                ))
+            } else if let Some(file_storage) = self
+                .app_state
+                .file_storage_providers
+                .get(&file.file_storage_provider_id)
+            {
+                // The parsed contents are cached, so the file is not parsed again when its
+                // pointer is resolved afterwards
+                match file_processing_cached::get_file_cached(
+                    self.app_state,
+                    file_id,
+                    file_storage,
+                    &file.file_storage_path,
+                    &file.filename,
+                    sharepoint_ctx.as_ref(),
+                )
+                .await
+                {
+                    Ok(file_contents) => file_contents.synopsis,
+                    Err(err) => {
+                        tracing::warn!(
+                            "Failed to build synopsis of file {} for the file manifest: {}",
+                            file_id,
+                            err
+                        );
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+
+            files.push((file.filename, *file_id, synopsis));
+        }
+
+        Ok(Some(format_file_manifest(&files)))
+    }
 }
 
 /// AppState-backed implementation of the PromptProvider trait.
@@ -273,6 +451,7 @@ impl<'a> PromptProvider for AppStatePromptProvider<'a> {
             self.policy,
             self.subject,
             chat,
+            self.app_state.config.assistants.freeze_assistant_config,
         )
         .await?;
 
@@ -338,11 +517,20 @@ fn remove_null_characters(text: &str) -> String {
     text.replace('\0', "")
 }
 
-fn format_successful_file_content(filename: &str, file_id: Uuid, text: &str) -> String {
+fn format_successful_file_content(
+    filename: &str,
+    file_id: Uuid,
+    text: &str,
+    synopsis: &FileSynopsis,
+) -> String {
     let mut content = String::new();
     content.push_str("File:\n");
     content.push_str(&format!("file name: {}\n", filename));
     content.push_str(&format!("file_id: erato_file_id:{}\n", file_id));
+    let paragraph = synopsis.to_paragraph();
+    if !paragraph.is_empty() {
+        content.push_str(&format!("Synopsis: {}\n", paragraph));
+    }
     content.push_str("File contents\n");
     content.push_str(text);
     content
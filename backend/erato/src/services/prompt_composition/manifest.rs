@@ -0,0 +1,389 @@
+//! Manifest of the prompts a generation was composed of.
+//!
+//! The manifest records where each prompt of a generation came from, together with a hash and
+//! the length of its content, so it can be traced which prompts produced an answer without
+//! storing the prompts themselves. Prompts that are replayed from the history of the chat are
+//! taken over from the manifest of the message they are replayed from.
+
+use crate::config::ActionFacetConfig;
+use crate::db::entity::chats;
+use crate::models::message::{GenerationInputMessages, GenerationMetadata, MessageRole};
+use crate::services::prompt_composition::traits::MessageRepository;
+use crate::services::prompt_composition::transforms::{
+    render_action_facet_template, render_chat_custom_instruction, render_facet_template,
+};
+use crate::services::prompt_composition::types::{
+    AbstractChatSequence, AbstractChatSequencePart, PromptSpec,
+};
+use eyre::Report;
+use sea_orm::prelude::Uuid;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// Kind of a prompt of a generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptComponentKind {
+    /// The system prompt of the chat provider
+    SystemPrompt,
+    /// The prompt of the assistant of the chat
+    AssistantPrompt,
+    /// The prompt template announcing a facet that was enabled
+    FacetPromptTemplate,
+    /// The additional system prompt of a facet
+    FacetSystemPrompt,
+    /// The directive of the action facet of the message
+    ActionFacetPrompt,
+    /// The custom instruction of the chat
+    ChatCustomInstruction,
+}
+
+/// A prompt of a generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PromptManifestComponent {
+    pub kind: PromptComponentKind,
+    /// ID of the source of the prompt: the chat provider for the system prompt, the assistant
+    /// for the assistant prompt, the facet for facet prompts, and the chat for its custom
+    /// instruction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub source_id: Option<String>,
+    /// Hex-encoded SHA-256 hash of the content of the prompt
+    pub content_sha256: String,
+    /// Length of the content of the prompt in characters
+    pub length: usize,
+    /// ID of the message whose generation the prompt was first composed for, if it was replayed
+    /// from the history of the chat
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub replayed_from_message_id: Option<String>,
+    /// The content of the prompt. Only stored if `admin.prompt_manifest.store_full_text` is
+    /// enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = false)]
+    pub text: Option<String>,
+}
+
+impl PromptManifestComponent {
+    fn new(kind: PromptComponentKind, source_id: Option<String>, text: String) -> Self {
+        Self {
+            kind,
+            source_id,
+            content_sha256: sha256_hex(text.as_bytes()),
+            length: text.chars().count(),
+            replayed_from_message_id: None,
+            text: Some(text),
+        }
+    }
+}
+
+/// The prompts a generation was composed of, in the order they were added to the chat.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PromptManifest {
+    pub components: Vec<PromptManifestComponent>,
+    /// Whether all prompts of the generation are listed. Prompts replayed from messages generated
+    /// before manifests were recorded are unknown.
+    pub complete: bool,
+    /// Hex-encoded SHA-256 hash over the kind, source and content hash of the components. Equal
+    /// for generations composed of the same prompts.
+    pub hash: String,
+    /// IDs of files of the chat history that were moved next to the newest user message (see
+    /// `chat.file_recency`). Not part of the hash.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub refreshed_file_ids: Vec<Uuid>,
+}
+
+impl PromptManifest {
+    fn new(components: Vec<PromptManifestComponent>, complete: bool) -> Self {
+        let hash_input: Vec<_> = components
+            .iter()
+            .map(|component| {
+                (
+                    component.kind,
+                    component.source_id.as_deref(),
+                    component.content_sha256.as_str(),
+                )
+            })
+            .collect();
+        let hash = sha256_hex(&serde_json::to_vec(&(hash_input, complete)).unwrap_or_default());
+        Self {
+            components,
+            complete,
+            hash,
+            refreshed_file_ids: Vec::new(),
+        }
+    }
+
+    /// The manifest without the content of the prompts, which doesn't change its hash.
+    pub fn without_text(mut self) -> Self {
+        for component in &mut self.components {
+            component.text = None;
+        }
+        self
+    }
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Build the manifest of the prompts of an abstract sequence, including the content of the
+/// prompts.
+///
+/// Follows `resolve_sequence` in which prompts end up in the generation input: the system
+/// prompts of the history are only replayed if no prompt was composed before them, and the
+/// custom instruction of the chat follows the system prompts.
+pub async fn build_prompt_manifest(
+    abstract_seq: &AbstractChatSequence,
+    message_repo: &impl MessageRepository,
+    chat: &chats::Model,
+    chat_provider_id: &str,
+    action_facet_configs: &HashMap<String, ActionFacetConfig>,
+) -> Result<PromptManifest, Report> {
+    let mut components = Vec::new();
+    let mut complete = true;
+    let mut has_system_prompt = false;
+    let mut refreshed_file_ids = Vec::new();
+    let mut custom_instruction = None;
+
+    for part in &abstract_seq.parts {
+        match part {
+            AbstractChatSequencePart::SystemPrompt { spec } => {
+                components.push(PromptManifestComponent::new(
+                    PromptComponentKind::SystemPrompt,
+                    Some(chat_provider_id.to_string()),
+                    static_content(spec),
+                ));
+                has_system_prompt = true;
+            }
+            AbstractChatSequencePart::AssistantPrompt { spec } => {
+                components.push(PromptManifestComponent::new(
+                    PromptComponentKind::AssistantPrompt,
+                    chat.assistant_id
+                        .map(|assistant_id| assistant_id.to_string()),
+                    static_content(spec),
+                ));
+                has_system_prompt = true;
+            }
+            AbstractChatSequencePart::ChatCustomInstruction { content } => {
+                custom_instruction = Some(PromptManifestComponent::new(
+                    PromptComponentKind::ChatCustomInstruction,
+                    Some(chat.id.to_string()),
+                    render_chat_custom_instruction(content),
+                ));
+            }
+            AbstractChatSequencePart::FacetPromptTemplate {
+                spec,
+                facet_id,
+                facet_display_name,
+                facet_tools_list,
+            } => {
+                components.push(PromptManifestComponent::new(
+                    PromptComponentKind::FacetPromptTemplate,
+                    Some(facet_id.clone()),
+                    render_facet_template(
+                        &static_content(spec),
+                        facet_display_name,
+                        facet_tools_list,
+                    ),
+                ));
+                has_system_prompt = true;
+            }
+            AbstractChatSequencePart::FacetAdditionalSystemPrompt { spec, facet_id } => {
+                components.push(PromptManifestComponent::new(
+                    PromptComponentKind::FacetSystemPrompt,
+                    Some(facet_id.clone()),
+                    static_content(spec),
+                ));
+                has_system_prompt = true;
+            }
+            AbstractChatSequencePart::ActionFacetPrompt { facet_id, args } => {
+                // Mirrors `resolve_action_facet_markers_in_generation_input`, which drops
+                // directives of unknown facets and empty ones
+                if let Some(config) = action_facet_configs.get(facet_id) {
+                    let rendered = render_action_facet_template(&config.template, args);
+                    if !rendered.is_empty() {
+                        components.push(PromptManifestComponent::new(
+                            PromptComponentKind::ActionFacetPrompt,
+                            Some(facet_id.clone()),
+                            rendered,
+                        ));
+                    }
+                }
+            }
+            AbstractChatSequencePart::HistoricMessagesFromGenerationInputMessages {
+                message_id,
+            } => {
+                if has_system_prompt {
+                    continue;
+                }
+                let message = message_repo.get_message_by_id(message_id).await?;
+                match replayed_components(&message.generation_metadata, message_id) {
+                    Some(replayed) => components.extend(replayed),
+                    None => {
+                        complete &= !has_system_messages(&message.generation_input_messages);
+                    }
+                }
+                has_system_prompt = true;
+            }
+            AbstractChatSequencePart::RefreshedFile { file_id } => {
+                refreshed_file_ids.push(*file_id);
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(component) = custom_instruction {
+        let index = components
+            .iter()
+            .position(|component| component.kind == PromptComponentKind::ActionFacetPrompt)
+            .unwrap_or(components.len());
+        components.insert(index, component);
+    }
+
+    Ok(PromptManifest {
+        refreshed_file_ids,
+        ..PromptManifest::new(components, complete)
+    })
+}
+
+fn static_content(spec: &PromptSpec) -> String {
+    match spec {
+        PromptSpec::Static { content } => content.clone(),
+        // Langfuse prompts are resolved by the `PromptProvider` before they are added
+        PromptSpec::Langfuse { prompt_name } => prompt_name.clone(),
+    }
+}
+
+/// The prompts of the manifest of a message that are replayed with its history.
+///
+/// Action facet directives only apply to their own turn, and the custom instruction of the chat is
+/// added anew for every turn, so they are not replayed.
+fn replayed_components(
+    generation_metadata: &Option<serde_json::Value>,
+    message_id: &Uuid,
+) -> Option<Vec<PromptManifestComponent>> {
+    let generation_metadata =
+        serde_json::from_value::<GenerationMetadata>(generation_metadata.clone()?).ok()?;
+    let manifest = generation_metadata.prompt_manifest?;
+    Some(
+        manifest
+            .components
+            .into_iter()
+            .filter(|component| {
+                !matches!(
+                    component.kind,
+                    PromptComponentKind::ActionFacetPrompt
+                        | PromptComponentKind::ChatCustomInstruction
+                )
+            })
+            .map(|component| PromptManifestComponent {
+                replayed_from_message_id: component
+                    .replayed_from_message_id
+                    .or_else(|| Some(message_id.to_string())),
+                ..component
+            })
+            .collect(),
+    )
+}
+
+fn has_system_messages(generation_input_messages: &Option<serde_json::Value>) -> bool {
+    generation_input_messages
+        .as_ref()
+        .and_then(|messages| {
+            serde_json::from_value::<GenerationInputMessages>(messages.clone()).ok()
+        })
+        .is_some_and(|messages| {
+            messages
+                .messages
+                .iter()
+                .any(|message| message.role == MessageRole::System)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_hash_ignores_text_and_replay_origin() {
+        let component = PromptManifestComponent::new(
+            PromptComponentKind::AssistantPrompt,
+            Some("assistant".to_string()),
+            "Be concise.".to_string(),
+        );
+        let manifest = PromptManifest::new(vec![component.clone()], true);
+        let replayed = PromptManifest::new(
+            vec![PromptManifestComponent {
+                replayed_from_message_id: Some(Uuid::nil().to_string()),
+                ..component.clone()
+            }],
+            true,
+        );
+
+        assert_eq!(manifest.hash, replayed.hash);
+        assert_eq!(manifest.clone().without_text().hash, manifest.hash);
+        assert!(manifest.without_text().components[0].text.is_none());
+        assert_eq!(component.length, 11);
+    }
+
+    #[test]
+    fn manifest_hash_changes_with_prompt_content() {
+        let manifest = |text: &str| {
+            PromptManifest::new(
+                vec![PromptManifestComponent::new(
+                    PromptComponentKind::AssistantPrompt,
+                    Some("assistant".to_string()),
+                    text.to_string(),
+                )],
+                true,
+            )
+        };
+
+        assert_ne!(manifest("Be concise.").hash, manifest("Be verbose.").hash);
+        assert_ne!(
+            manifest("Be concise.").components[0].content_sha256,
+            manifest("Be verbose.").components[0].content_sha256
+        );
+    }
+
+    #[test]
+    fn replayed_components_skip_action_facet_prompts() {
+        let message_id = Uuid::new_v4();
+        let manifest = PromptManifest::new(
+            vec![
+                PromptManifestComponent::new(
+                    PromptComponentKind::SystemPrompt,
+                    Some("provider".to_string()),
+                    "You are helpful.".to_string(),
+                ),
+                PromptManifestComponent::new(
+                    PromptComponentKind::ActionFacetPrompt,
+                    Some("summarize".to_string()),
+                    "Summarize.".to_string(),
+                ),
+            ],
+            true,
+        );
+        let generation_metadata = serde_json::to_value(GenerationMetadata {
+            prompt_manifest: Some(manifest),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let replayed = replayed_components(&Some(generation_metadata), &message_id).unwrap();
+
+        assert_eq!(replayed.len(), 1);
+        assert_eq!(replayed[0].kind, PromptComponentKind::SystemPrompt);
+        assert_eq!(
+            replayed[0].replayed_from_message_id,
+            Some(message_id.to_string())
+        );
+        assert!(replayed_components(&None, &message_id).is_none());
+    }
+}
@@ -13,7 +13,12 @@
 //! use crate::services::prompt_composition::*;
 //!
 //! // Create dependencies
-//! let message_repo = DatabaseMessageRepository { conn, policy, subject };
+//! let message_repo = DatabaseMessageRepository {
+//!     conn,
+//!     policy,
+//!     subject,
+//!     generation_input_archive,
+//! };
 //! let file_resolver = AppStateFileResolver { app_state, access_token };
 //! let prompt_provider = AppStatePromptProvider { app_state, policy, subject };
 //!
@@ -22,31 +27,38 @@
 //!     just_submitted_user_message_id: saved_message_id,
 //!     requested_chat_provider_id: Some("gpt-4".to_string()),
 //!     new_input_file_ids: vec![file_id_1, file_id_2],
+//!     refresh_file_ids: vec![],
 //! };
 //!
 //! // Use the convenience function
-//! let unresolved_messages = compose_prompt_messages(
+//! let (unresolved_messages, prompt_manifest) = compose_prompt_messages(
 //!     &message_repo,
 //!     &file_resolver,
 //!     &prompt_provider,
 //!     &chat,
 //!     &user_input,
 //!     &chat_provider_config,
+//!     "gpt-4",
 //!     &ExperimentalFacetsConfig::default(),
 //!     preferred_language,
 //!     None,
+//!     &FileRecencyConfig::default(),
 //! ).await?;
 //! ```
 
-use crate::config::{ActionFacetConfig, ChatProviderConfig};
+use crate::config::{ActionFacetConfig, ChatProviderConfig, FileRecencyConfig};
 use crate::db::entity::chats;
 use crate::models::message::GenerationInputMessages;
 use eyre::Report;
+use manifest::{PromptManifest, build_prompt_manifest};
 use std::collections::HashMap;
 
 pub mod adapters;
 pub mod allowlist;
+pub mod manifest;
 pub mod model_settings;
+pub mod prompt_caching;
+pub mod token_breakdown;
 pub mod traits;
 pub mod transforms;
 pub mod types;
@@ -55,9 +67,13 @@ pub mod types;
 mod tests;
 
 // Re-export commonly used types
-pub use adapters::{AppStateFileResolver, AppStatePromptProvider, DatabaseMessageRepository};
+pub use adapters::{
+    AppStateFileResolver, AppStatePromptProvider, DatabaseMessageRepository,
+    InMemoryMessageRepository, SyntheticMessageRepository,
+};
 pub use allowlist::build_mcp_tool_allowlist;
 pub use model_settings::build_model_settings_for_facets;
+pub use prompt_caching::mark_stable_prefix_as_cacheable;
 pub use traits::{FileResolver, MessageRepository, PromptProvider};
 pub use transforms::{build_abstract_sequence, resolve_sequence, to_concrete_request};
 pub use types::{
@@ -70,7 +86,8 @@ pub use types::{
 /// This function:
 /// 1. Builds an abstract sequence (determining message structure)
 /// 2. Resolves the sequence (fetching resources)
-/// 3. Returns the unresolved GenerationInputMessages for DB storage
+/// 3. Returns the unresolved GenerationInputMessages for DB storage, together with the manifest
+///    of the prompts they contain
 ///
 /// Note: File pointers are NOT resolved to actual content here. That happens
 /// separately via `resolve_file_pointers_in_generation_input` before sending to LLM.
@@ -82,6 +99,7 @@ pub async fn compose_prompt_messages(
     chat: &chats::Model,
     user_input: &PromptCompositionUserInput,
     chat_provider_config: &ChatProviderConfig,
+    chat_provider_id: &str,
     experimental_facets: &crate::config::ExperimentalFacetsConfig,
     preferred_language: Option<&str>,
     user_preference_nickname: Option<&str>,
@@ -91,7 +109,8 @@ pub async fn compose_prompt_messages(
     facet_tool_expansions: Option<&HashMap<String, Vec<String>>>,
     action_facet_configs: &HashMap<String, ActionFacetConfig>,
     platform: Option<&str>,
-) -> Result<GenerationInputMessages, Report> {
+    file_recency: &FileRecencyConfig,
+) -> Result<(GenerationInputMessages, PromptManifest), Report> {
     // Phase 1: Build abstract sequence
     let abstract_seq = transforms::build_abstract_sequence_with_facet_tool_expansions(
         message_repo,
@@ -111,6 +130,16 @@ pub async fn compose_prompt_messages(
         user_input.action_facet.as_ref(),
         action_facet_configs,
         platform,
+        &user_input.refresh_file_ids,
+        file_recency,
+    )
+    .await?;
+    let prompt_manifest = build_prompt_manifest(
+        &abstract_seq,
+        message_repo,
+        chat,
+        chat_provider_id,
+        action_facet_configs,
     )
     .await?;
 
@@ -120,5 +149,5 @@ pub async fn compose_prompt_messages(
 
     // Return the unresolved version for DB storage
     // File resolution will happen later via resolve_file_pointers_in_generation_input
-    Ok(unresolved_messages)
+    Ok((unresolved_messages, prompt_manifest))
 }
@@ -61,6 +61,9 @@ fn merge_model_settings(base: &ModelSettings, overrides: &ModelSettings) -> Mode
     if let Some(verbosity) = overrides.verbosity {
         merged.verbosity = Some(verbosity);
     }
+    if let Some(output_pacing) = &overrides.output_pacing {
+        merged.output_pacing = Some(output_pacing.clone());
+    }
 
     merged
 }
@@ -95,6 +98,7 @@ mod tests {
             top_p: None,
             reasoning_effort: None,
             verbosity: None,
+            output_pacing: None,
         };
         let config = ExperimentalFacetsConfig::default();
 
@@ -0,0 +1,203 @@
+//! Cache hints for the stable start of the prompt.
+//!
+//! Providers with prompt caching (see `model_capabilities.supports_prompt_caching`) reuse the
+//! processed start of a prompt if it ends in a message that is marked as cacheable, and it is
+//! identical to the start of an earlier request. The system prompt, the hidden facet prompts, the
+//! assistant prompt and the assistant files are replayed unchanged at the start of every turn, so
+//! they are marked. The custom instruction of the chat follows the system prompts, and is part of
+//! the prefix until it is changed. The chat history and the new user message change with every
+//! turn and are left unmarked.
+
+use crate::models::message::{ContentPart, GenerationInputMessages, InputMessage, MessageRole};
+use genai::chat::{CacheControl, ChatRequest};
+
+/// Whether the message may be part of the stable prefix of the generation input.
+fn is_stable_prefix_message(message: &InputMessage) -> bool {
+    matches!(
+        (&message.role, &message.content),
+        (MessageRole::System, ContentPart::Text(_))
+            | (
+                MessageRole::User,
+                ContentPart::TextFilePointer(_) | ContentPart::ImageFilePointer(_)
+            )
+    )
+}
+
+/// Number of messages at the start of the (unresolved) generation input that are replayed
+/// unchanged with every turn, i.e. the leading system prompts and the files after them.
+pub fn stable_prefix_len(messages: &[InputMessage]) -> usize {
+    messages
+        .iter()
+        .take_while(|message| is_stable_prefix_message(message))
+        .count()
+}
+
+/// Number of messages the stable prefix is resolved to by
+/// `resolve_file_pointers_in_generation_input`, i.e. its length in the chat request. Image file
+/// pointers are resolved to a message with the pointer and one with the image.
+pub fn resolved_stable_prefix_len(messages: &[InputMessage]) -> usize {
+    messages[..stable_prefix_len(messages)]
+        .iter()
+        .map(|message| match message.content {
+            ContentPart::ImageFilePointer(_) => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Mark the stable prefix of the chat request as cacheable.
+///
+/// `generation_input_messages` are the unresolved messages the chat request was built from.
+/// Providers cache everything up to a marked message, so only the last message of the prefix is
+/// marked.
+pub fn mark_stable_prefix_as_cacheable(
+    chat_request: &mut ChatRequest,
+    generation_input_messages: &GenerationInputMessages,
+) {
+    let prefix_len = resolved_stable_prefix_len(&generation_input_messages.messages);
+    if let Some(message) = prefix_len
+        .checked_sub(1)
+        .and_then(|index| chat_request.messages.get_mut(index))
+    {
+        message.options = Some(CacheControl::Ephemeral.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::{
+        ContentPartActionFacetMarker, ContentPartImageFilePointer, ContentPartText,
+        ContentPartTextFilePointer,
+    };
+    use sea_orm::prelude::Uuid;
+
+    fn text_message(role: MessageRole, text: &str) -> InputMessage {
+        InputMessage {
+            role,
+            content: ContentPart::Text(ContentPartText {
+                text: text.to_string(),
+            }),
+        }
+    }
+
+    fn text_file_message() -> InputMessage {
+        InputMessage {
+            role: MessageRole::User,
+            content: ContentPart::TextFilePointer(ContentPartTextFilePointer {
+                file_upload_id: Uuid::new_v4(),
+            }),
+        }
+    }
+
+    fn image_file_message() -> InputMessage {
+        InputMessage {
+            role: MessageRole::User,
+            content: ContentPart::ImageFilePointer(ContentPartImageFilePointer {
+                file_upload_id: Uuid::new_v4(),
+                download_url: None,
+                preview_url: None,
+            }),
+        }
+    }
+
+    fn cacheable_indices(chat_request: &ChatRequest) -> Vec<usize> {
+        chat_request
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, message)| {
+                message
+                    .options
+                    .as_ref()
+                    .is_some_and(|options| options.cache_control.is_some())
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    #[test]
+    fn prefix_ends_before_the_history() {
+        let messages = vec![
+            text_message(MessageRole::System, "You are a helpful assistant."),
+            text_message(MessageRole::System, "You answer questions about reports."),
+            text_file_message(),
+            text_message(MessageRole::User, "What is in the report?"),
+            text_message(MessageRole::Assistant, "Q3 numbers."),
+            text_message(MessageRole::System, "File manifest"),
+            text_file_message(),
+        ];
+
+        assert_eq!(stable_prefix_len(&messages), 3);
+        assert_eq!(resolved_stable_prefix_len(&messages), 3);
+    }
+
+    #[test]
+    fn image_files_count_twice_when_resolved() {
+        let messages = vec![
+            text_message(MessageRole::System, "You are a helpful assistant."),
+            image_file_message(),
+            text_file_message(),
+            text_message(MessageRole::User, "Describe the image."),
+        ];
+
+        assert_eq!(stable_prefix_len(&messages), 3);
+        assert_eq!(resolved_stable_prefix_len(&messages), 4);
+    }
+
+    #[test]
+    fn dynamic_messages_end_the_prefix() {
+        let action_facet_marker = InputMessage {
+            role: MessageRole::User,
+            content: ContentPart::ActionFacetMarker(ContentPartActionFacetMarker {
+                facet_id: "summarize".to_string(),
+                args: Default::default(),
+            }),
+        };
+        assert_eq!(
+            stable_prefix_len(&[
+                action_facet_marker,
+                text_message(MessageRole::System, "You are a helpful assistant."),
+            ]),
+            0
+        );
+        assert_eq!(
+            stable_prefix_len(&[
+                text_message(MessageRole::User, "Hi"),
+                text_file_message()
+            ]),
+            0
+        );
+    }
+
+    #[test]
+    fn only_the_last_prefix_message_is_marked() {
+        let messages = GenerationInputMessages {
+            messages: vec![
+                text_message(MessageRole::System, "You are a helpful assistant."),
+                image_file_message(),
+                text_message(MessageRole::User, "Describe the image."),
+            ],
+        };
+        let mut chat_request = ChatRequest {
+            messages: (0..4)
+                .map(|index| genai::chat::ChatMessage::user(format!("Message {index}")))
+                .collect(),
+            ..Default::default()
+        };
+
+        mark_stable_prefix_as_cacheable(&mut chat_request, &messages);
+        assert_eq!(cacheable_indices(&chat_request), vec![2]);
+    }
+
+    #[test]
+    fn nothing_is_marked_without_a_prefix() {
+        let messages = GenerationInputMessages {
+            messages: vec![text_message(MessageRole::User, "Hi")],
+        };
+        let mut chat_request = messages.clone().into_chat_request();
+
+        mark_stable_prefix_as_cacheable(&mut chat_request, &messages);
+        assert!(cacheable_indices(&chat_request).is_empty());
+    }
+}
@@ -1,29 +1,35 @@
 #[cfg(test)]
 mod test_cases {
+    use super::super::manifest::{PromptComponentKind, build_prompt_manifest};
     use super::super::traits::{FileResolver, MessageRepository, PromptProvider};
     use super::super::transforms::{
         build_abstract_sequence, build_abstract_sequence_with_facet_tool_expansions,
-        resolve_sequence,
+        render_chat_custom_instruction, resolve_sequence,
     };
     use super::super::types::{
-        AbstractChatSequencePart, ActionFacetUserInput, PromptSpec, ResolvedChatSequence,
+        AbstractChatSequence, AbstractChatSequencePart, ActionFacetUserInput, PromptSpec,
+        ResolvedChatSequence,
     };
     use crate::config::{
-        ChatProviderConfig, ExperimentalFacetsConfig, FacetConfig, PromptSourceSpecification,
+        ChatProviderConfig, ExperimentalFacetsConfig, FacetConfig, FileRecencyConfig,
+        PromptSourceSpecification,
     };
     use crate::db::entity::{chats, messages};
     use crate::models::assistant::{AssistantWithFiles, FileInfo};
     use crate::models::message::{
-        ContentPart, ContentPartText, GenerationInputMessages, InputMessage, MessageRole,
-        MessageSchema, ToolCallStatus, ToolUse,
+        ContentPart, ContentPartText, ContentPartTextFilePointer, GenerationInputMessages,
+        InputMessage, MessageRole, MessageSchema, ToolCallStatus, ToolUse,
     };
     use crate::server::api::v1beta::message_streaming::{FileContent, FileContentsForGeneration};
+    use crate::services::file_synopsis::{
+        FILE_MANIFEST_PREFIX, build_file_synopsis, format_file_manifest,
+    };
     use crate::services::genai::into_openai_request_parts;
     use async_trait::async_trait;
     use eyre::{OptionExt, Report, eyre};
     use sea_orm::prelude::{DateTimeWithTimeZone, Uuid};
     use serde_json::Value as JsonValue;
-    use std::collections::HashMap;
+    use std::collections::{HashMap, HashSet};
 
     // ============================================================================
     // Mock Implementations
@@ -83,6 +89,9 @@ mod test_cases {
                     is_message_in_active_thread: true,
                     input_file_uploads: None,
                     input_parameters: None,
+                    is_welcome_message: false,
+                    content_draft: None,
+                    edit_of_message_id: None,
                     created_at: chrono::Utc::now().into(),
                     updated_at: chrono::Utc::now().into(),
                 },
@@ -188,6 +197,7 @@ mod test_cases {
                         id: *file_id,
                         filename: filename.clone(),
                         content: FileContent::Text(content.clone()),
+                        synopsis: None,
                     });
                 }
             }
@@ -212,6 +222,27 @@ mod test_cases {
 
             Ok(is_image)
         }
+
+        async fn existing_file_ids(&self, file_ids: &[Uuid]) -> Result<HashSet<Uuid>, Report> {
+            Ok(file_ids
+                .iter()
+                .filter(|file_id| self.files.contains_key(file_id))
+                .copied()
+                .collect())
+        }
+
+        async fn resolve_file_manifest(&self, file_ids: &[Uuid]) -> Result<Option<String>, Report> {
+            let files = file_ids
+                .iter()
+                .map(|file_id| {
+                    let (filename, content) =
+                        self.files.get(file_id).ok_or_eyre("File not found")?;
+                    let synopsis = build_file_synopsis(content, filename, None, 3);
+                    Ok((filename.clone(), *file_id, Some(synopsis)))
+                })
+                .collect::<Result<Vec<_>, Report>>()?;
+            Ok(Some(format_file_manifest(&files)))
+        }
     }
 
     struct MockPromptProvider {
@@ -244,6 +275,12 @@ mod test_cases {
                 facet_ids: None,
                 default_chat_provider: None,
                 enforce_facet_settings: false,
+                welcome_message: None,
+                pinned_facet_ids: None,
+                category: None,
+                tags: vec![],
+                visibility: "private".to_string(),
+                featured: false,
                 archived_at: None,
                 created_at: now,
                 updated_at: now,
@@ -309,6 +346,13 @@ mod test_cases {
             generation_started_at: None,
             generation_heartbeat_at: None,
             generation_ended_at: None,
+            organization_id: None,
+            generation_replica_id: None,
+            generation_message_id: None,
+            assistant_snapshot: None,
+            provisional: false,
+            custom_instruction: None,
+            response_language: None,
         }
     }
 
@@ -332,6 +376,7 @@ mod test_cases {
             model_settings: crate::config::ModelSettings::default(),
             hallucination_suppression: crate::config::HallucinationSuppressionConfig::default(),
             guardrails: None,
+            user_quota: None,
         }
     }
 
@@ -944,6 +989,98 @@ mod test_cases {
         }
     }
 
+    #[tokio::test]
+    async fn test_file_manifest_only_for_multiple_user_files() {
+        let mut message_repo = MockMessageRepository::new();
+        let mut file_resolver = MockFileResolver::new();
+        let prompt_provider = MockPromptProvider::new();
+
+        let report_id = Uuid::new_v4();
+        let notes_id = Uuid::new_v4();
+        file_resolver.add_file(
+            report_id,
+            "report.md",
+            "The report covers the third quarter. Revenue grew.",
+        );
+        file_resolver.add_file(notes_id, "notes.txt", "Remember to follow up with sales.");
+
+        let msg_id = Uuid::new_v4();
+        message_repo.add_message(msg_id, None, MessageRole::User, "Compare these files");
+
+        let chat = create_test_chat();
+        let config = create_test_chat_provider_config();
+
+        // Two files: the manifest is placed before the file pointers
+        let abstract_seq = build_abstract_sequence(
+            &message_repo,
+            &prompt_provider,
+            &chat,
+            &msg_id,
+            vec![report_id, notes_id],
+            &config,
+            &ExperimentalFacetsConfig::default(),
+            &[],
+            None,
+        )
+        .await
+        .expect("Failed to build abstract sequence");
+
+        let (resolved, _) = resolve_sequence(abstract_seq, &message_repo, &file_resolver)
+            .await
+            .expect("Failed to resolve sequence");
+
+        assert_eq!(resolved.messages.len(), 4);
+        assert!(matches!(resolved.messages[0].role, MessageRole::User));
+        assert!(matches!(resolved.messages[1].role, MessageRole::System));
+        match &resolved.messages[1].content {
+            ContentPart::Text(ContentPartText { text }) => {
+                assert!(text.starts_with(FILE_MANIFEST_PREFIX));
+                assert!(text.contains(&format!(
+                    "1. report.md (file_id: erato_file_id:{report_id}): The report covers the third quarter. Revenue grew."
+                )));
+                assert!(
+                    text.contains(&format!("2. notes.txt (file_id: erato_file_id:{notes_id})"))
+                );
+            }
+            _ => panic!("Expected file manifest text"),
+        }
+        assert!(matches!(
+            resolved.messages[2].content,
+            ContentPart::TextFilePointer(_)
+        ));
+        assert!(matches!(
+            resolved.messages[3].content,
+            ContentPart::TextFilePointer(_)
+        ));
+
+        // A single file gets no manifest
+        let abstract_seq = build_abstract_sequence(
+            &message_repo,
+            &prompt_provider,
+            &chat,
+            &msg_id,
+            vec![report_id],
+            &config,
+            &ExperimentalFacetsConfig::default(),
+            &[],
+            None,
+        )
+        .await
+        .expect("Failed to build abstract sequence");
+
+        let (resolved, _) = resolve_sequence(abstract_seq, &message_repo, &file_resolver)
+            .await
+            .expect("Failed to resolve sequence");
+
+        assert_eq!(resolved.messages.len(), 2);
+        assert!(
+            resolved
+                .messages
+                .iter()
+                .all(|message| !matches!(message.role, MessageRole::System))
+        );
+    }
+
     #[tokio::test]
     async fn test_full_chat_request_with_assistant_and_user_files() {
         // Setup: Assistant with files, plus user uploads additional file to chat
@@ -2317,6 +2454,99 @@ mod test_cases {
         );
     }
 
+    #[tokio::test]
+    async fn test_inline_file_contents_in_historic_generation_input_become_pointers() {
+        let mut message_repo = MockMessageRepository::new();
+        let mut file_resolver = MockFileResolver::new();
+        let prompt_provider = MockPromptProvider::new().with_system_prompt("You are helpful.");
+
+        let chat = create_test_chat();
+        let config = create_test_chat_provider_config();
+
+        let existing_file_id = Uuid::new_v4();
+        file_resolver.add_file(existing_file_id, "notes.txt", "Meeting at noon");
+        let deleted_file_id = Uuid::new_v4();
+        let inline_file_content = |file_id: Uuid, filename: &str, content: &str| {
+            format!(
+                "File:\nfile name: {filename}\nfile_id: erato_file_id:{file_id}\nFile contents\n{content}"
+            )
+        };
+
+        let msg1_id = Uuid::new_v4();
+        message_repo.add_message(msg1_id, None, MessageRole::User, "Summarize the files");
+
+        let msg2_id = Uuid::new_v4();
+        message_repo.add_message(msg2_id, Some(msg1_id), MessageRole::Assistant, "Done.");
+        // Simulate a generation input persisted before file pointers existed
+        let deleted_file_content = inline_file_content(deleted_file_id, "old.txt", "Gone");
+        message_repo.update_generation_input_messages(
+            msg2_id,
+            &GenerationInputMessages {
+                messages: vec![
+                    InputMessage {
+                        role: MessageRole::User,
+                        content: ContentPart::Text(ContentPartText {
+                            text: inline_file_content(
+                                existing_file_id,
+                                "notes.txt",
+                                "Meeting at noon",
+                            ),
+                        }),
+                    },
+                    InputMessage {
+                        role: MessageRole::User,
+                        content: ContentPart::Text(ContentPartText {
+                            text: deleted_file_content.clone(),
+                        }),
+                    },
+                    InputMessage {
+                        role: MessageRole::User,
+                        content: ContentPart::Text(ContentPartText {
+                            text: "Summarize the files".to_string(),
+                        }),
+                    },
+                ],
+            },
+        );
+
+        let msg3_id = Uuid::new_v4();
+        message_repo.add_message(msg3_id, Some(msg2_id), MessageRole::User, "Thanks");
+
+        let abstract_seq = build_abstract_sequence(
+            &message_repo,
+            &prompt_provider,
+            &chat,
+            &msg3_id,
+            vec![],
+            &config,
+            &ExperimentalFacetsConfig::default(),
+            &[],
+            None,
+        )
+        .await
+        .expect("Failed to build abstract sequence");
+
+        let (_, unresolved) = resolve_sequence(abstract_seq, &message_repo, &file_resolver)
+            .await
+            .expect("Failed to resolve sequence");
+
+        let contents: Vec<&ContentPart> =
+            unresolved.messages.iter().map(|msg| &msg.content).collect();
+        assert!(
+            contents.contains(&&ContentPart::TextFilePointer(ContentPartTextFilePointer {
+                file_upload_id: existing_file_id
+            }))
+        );
+        // The contents of files that no longer exist can't be resolved, and are kept
+        assert!(contents.contains(&&ContentPart::Text(ContentPartText {
+            text: deleted_file_content
+        })));
+        assert!(!contents.iter().any(|content| matches!(
+            content,
+            ContentPart::Text(ContentPartText { text }) if text.contains("Meeting at noon")
+        )));
+    }
+
     #[tokio::test]
     async fn test_history_replays_single_tool_call_as_valid_openai_sequence() {
         let generation_input = GenerationInputMessages {
@@ -2575,6 +2805,8 @@ mod test_cases {
             Some(&action_facet),
             &action_facet_configs,
             None,
+            &[],
+            &FileRecencyConfig::default(),
         )
         .await
         .expect("Failed to build abstract sequence");
@@ -2687,6 +2919,8 @@ mod test_cases {
             None,
             &HashMap::new(),
             Some("outlook"),
+            &[],
+            &FileRecencyConfig::default(),
         )
         .await
         .expect("Failed to build abstract sequence");
@@ -2808,6 +3042,8 @@ mod test_cases {
             Some(&action_facet),
             &action_facet_configs,
             None,
+            &[],
+            &FileRecencyConfig::default(),
         )
         .await
         .expect("Failed to build second abstract sequence");
@@ -2858,6 +3094,541 @@ mod test_cases {
         );
     }
 
+    // ============================================================================
+    // Chat custom instruction tests
+    // ============================================================================
+
+    /// Compose the turn of the user message `message_id`, returning the abstract and the resolved
+    /// sequence.
+    async fn compose_turn(
+        message_repo: &MockMessageRepository,
+        prompt_provider: &MockPromptProvider,
+        chat: &chats::Model,
+        message_id: &Uuid,
+    ) -> (AbstractChatSequence, ResolvedChatSequence) {
+        let abstract_seq = build_abstract_sequence(
+            message_repo,
+            prompt_provider,
+            chat,
+            message_id,
+            vec![],
+            &create_test_chat_provider_config(),
+            &ExperimentalFacetsConfig::default(),
+            &[],
+            None,
+        )
+        .await
+        .expect("Failed to build abstract sequence");
+        let file_resolver = MockFileResolver::new();
+        let (resolved, _) = resolve_sequence(abstract_seq.clone(), message_repo, &file_resolver)
+            .await
+            .expect("Failed to resolve sequence");
+        (abstract_seq, resolved)
+    }
+
+    fn system_texts(resolved: &ResolvedChatSequence) -> Vec<String> {
+        resolved
+            .messages
+            .iter()
+            .filter(|message| matches!(message.role, MessageRole::System))
+            .filter_map(|message| match &message.content {
+                ContentPart::Text(ContentPartText { text }) => Some(text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_chat_custom_instruction_follows_prompts_and_is_not_replayed() {
+        let mut message_repo = MockMessageRepository::new();
+        let prompt_provider = MockPromptProvider::new()
+            .with_system_prompt("You are helpful.")
+            .with_assistant("Reviewer", "You review documents.", vec![]);
+        let mut chat = create_test_chat();
+        chat.custom_instruction = Some("Answer in bullet points.".to_string());
+
+        // Turn 1: the instruction follows the system and the assistant prompt
+        let msg1_id = Uuid::new_v4();
+        message_repo.add_message(msg1_id, None, MessageRole::User, "Summarize the report");
+        let (abstract_seq1, resolved1) =
+            compose_turn(&message_repo, &prompt_provider, &chat, &msg1_id).await;
+        assert_eq!(
+            system_texts(&resolved1),
+            vec![
+                "You are helpful.".to_string(),
+                "You review documents.".to_string(),
+                render_chat_custom_instruction("Answer in bullet points."),
+            ]
+        );
+        assert!(matches!(resolved1.messages[3].role, MessageRole::User));
+        let manifest = build_prompt_manifest(
+            &abstract_seq1,
+            &message_repo,
+            &chat,
+            "gpt-4",
+            &HashMap::new(),
+        )
+        .await
+        .expect("Failed to build prompt manifest");
+        let kinds: Vec<_> = manifest
+            .components
+            .iter()
+            .map(|component| component.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                PromptComponentKind::SystemPrompt,
+                PromptComponentKind::AssistantPrompt,
+                PromptComponentKind::ChatCustomInstruction,
+            ]
+        );
+        assert_eq!(manifest.components[2].source_id, Some(chat.id.to_string()));
+
+        let msg2_id = Uuid::new_v4();
+        message_repo.add_message(msg2_id, Some(msg1_id), MessageRole::Assistant, "- Q3 grew");
+        message_repo.update_generation_input_messages(
+            msg2_id,
+            &GenerationInputMessages {
+                messages: resolved1.messages.clone(),
+            },
+        );
+
+        // Turn 2: only the changed instruction is sent, after the replayed prompts
+        chat.custom_instruction = Some("Answer in one sentence.".to_string());
+        let msg3_id = Uuid::new_v4();
+        message_repo.add_message(msg3_id, Some(msg2_id), MessageRole::User, "And Q4?");
+        let (abstract_seq2, resolved2) =
+            compose_turn(&message_repo, &prompt_provider, &chat, &msg3_id).await;
+        assert_eq!(
+            system_texts(&resolved2),
+            vec![
+                "You are helpful.".to_string(),
+                "You review documents.".to_string(),
+                render_chat_custom_instruction("Answer in one sentence."),
+            ]
+        );
+        let manifest = build_prompt_manifest(
+            &abstract_seq2,
+            &message_repo,
+            &chat,
+            "gpt-4",
+            &HashMap::new(),
+        )
+        .await
+        .expect("Failed to build prompt manifest");
+        let custom_instructions: Vec<_> = manifest
+            .components
+            .iter()
+            .filter(|component| component.kind == PromptComponentKind::ChatCustomInstruction)
+            .collect();
+        assert_eq!(custom_instructions.len(), 1);
+        assert!(custom_instructions[0].replayed_from_message_id.is_none());
+
+        let msg4_id = Uuid::new_v4();
+        message_repo.add_message(msg4_id, Some(msg3_id), MessageRole::Assistant, "Q4 fell.");
+        message_repo.update_generation_input_messages(
+            msg4_id,
+            &GenerationInputMessages {
+                messages: resolved2.messages.clone(),
+            },
+        );
+
+        // Turn 3: a removed instruction is not sent anymore
+        chat.custom_instruction = None;
+        let msg5_id = Uuid::new_v4();
+        message_repo.add_message(msg5_id, Some(msg4_id), MessageRole::User, "Thanks");
+        let (_, resolved3) = compose_turn(&message_repo, &prompt_provider, &chat, &msg5_id).await;
+        assert_eq!(
+            system_texts(&resolved3),
+            vec![
+                "You are helpful.".to_string(),
+                "You review documents.".to_string(),
+            ]
+        );
+    }
+
+    // ============================================================================
+    // File recency tests
+    // ============================================================================
+
+    /// Seed a chat with `turns` answered user messages, where `file_ids` are attached to the first
+    /// message. Returns the ID of the last answer.
+    async fn seed_chat_with_files_in_first_message(
+        message_repo: &mut MockMessageRepository,
+        file_resolver: &MockFileResolver,
+        chat: &chats::Model,
+        file_ids: &[Uuid],
+        turns: usize,
+    ) -> Uuid {
+        let prompt_provider = MockPromptProvider::new().with_system_prompt("You are helpful.");
+        let config = create_test_chat_provider_config();
+        let mut previous_id = None;
+        for turn in 0..turns {
+            let user_id = Uuid::new_v4();
+            message_repo.add_message(
+                user_id,
+                previous_id,
+                MessageRole::User,
+                &format!("Question {turn}"),
+            );
+            let input_file_ids = if turn == 0 {
+                file_ids.to_vec()
+            } else {
+                vec![]
+            };
+            let abstract_seq = build_abstract_sequence(
+                &*message_repo,
+                &prompt_provider,
+                chat,
+                &user_id,
+                input_file_ids,
+                &config,
+                &ExperimentalFacetsConfig::default(),
+                &[],
+                None,
+            )
+            .await
+            .expect("Failed to build abstract sequence");
+            let (_, unresolved) = resolve_sequence(abstract_seq, &*message_repo, file_resolver)
+                .await
+                .expect("Failed to resolve sequence");
+
+            let assistant_id = Uuid::new_v4();
+            message_repo.add_message(
+                assistant_id,
+                Some(user_id),
+                MessageRole::Assistant,
+                &format!("Answer {turn}"),
+            );
+            message_repo.update_generation_input_messages(assistant_id, &unresolved);
+            previous_id = Some(assistant_id);
+        }
+        previous_id.expect("The chat should have at least one turn")
+    }
+
+    /// Compose the next turn of a chat with the given file recency config.
+    async fn compose_turn_with_file_recency(
+        message_repo: &MockMessageRepository,
+        file_resolver: &MockFileResolver,
+        chat: &chats::Model,
+        user_message_id: Uuid,
+        new_input_file_ids: Vec<Uuid>,
+        refresh_file_ids: &[Uuid],
+        file_recency: &FileRecencyConfig,
+    ) -> (AbstractChatSequence, ResolvedChatSequence) {
+        let prompt_provider = MockPromptProvider::new().with_system_prompt("You are helpful.");
+        let abstract_seq = build_abstract_sequence_with_facet_tool_expansions(
+            message_repo,
+            &prompt_provider,
+            chat,
+            &user_message_id,
+            new_input_file_ids,
+            &create_test_chat_provider_config(),
+            &ExperimentalFacetsConfig::default(),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &HashMap::new(),
+            None,
+            refresh_file_ids,
+            file_recency,
+        )
+        .await
+        .expect("Failed to build abstract sequence");
+        let (resolved, _) = resolve_sequence(abstract_seq.clone(), message_repo, file_resolver)
+            .await
+            .expect("Failed to resolve sequence");
+        (abstract_seq, resolved)
+    }
+
+    /// Positions of the pointers to a file in a resolved sequence.
+    fn file_pointer_positions(resolved: &ResolvedChatSequence, file_id: Uuid) -> Vec<usize> {
+        resolved
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, msg)| {
+                matches!(&msg.content, ContentPart::TextFilePointer(ptr) if ptr.file_upload_id == file_id)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn enabled_file_recency(max_files: usize) -> FileRecencyConfig {
+        FileRecencyConfig {
+            enabled: true,
+            max_files,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_recency_moves_refreshed_file_next_to_newest_message() {
+        let mut message_repo = MockMessageRepository::new();
+        let mut file_resolver = MockFileResolver::new();
+        let file_id = Uuid::new_v4();
+        file_resolver.add_file(file_id, "report.txt", "The revenue grew by 12%.");
+        let chat = create_test_chat();
+
+        let last_answer_id = seed_chat_with_files_in_first_message(
+            &mut message_repo,
+            &file_resolver,
+            &chat,
+            &[file_id],
+            20,
+        )
+        .await;
+        let user_message_id = Uuid::new_v4();
+        message_repo.add_message(
+            user_message_id,
+            Some(last_answer_id),
+            MessageRole::User,
+            "What was the revenue growth again?",
+        );
+
+        let (abstract_seq, resolved) = compose_turn_with_file_recency(
+            &message_repo,
+            &file_resolver,
+            &chat,
+            user_message_id,
+            vec![],
+            &[file_id],
+            &enabled_file_recency(3),
+        )
+        .await;
+
+        // The file directly follows the newest message, and is not replayed at its original
+        // position
+        let last_index = resolved.messages.len() - 1;
+        assert_eq!(file_pointer_positions(&resolved, file_id), vec![last_index]);
+        assert!(matches!(
+            &resolved.messages[last_index - 1].content,
+            ContentPart::Text(ContentPartText { text }) if text == "What was the revenue growth again?"
+        ));
+
+        let manifest = build_prompt_manifest(
+            &abstract_seq,
+            &message_repo,
+            &chat,
+            "gpt-4",
+            &HashMap::new(),
+        )
+        .await
+        .expect("Failed to build prompt manifest");
+        assert_eq!(manifest.refreshed_file_ids, vec![file_id]);
+    }
+
+    #[tokio::test]
+    async fn test_file_recency_disabled_keeps_file_at_original_position() {
+        let mut message_repo = MockMessageRepository::new();
+        let mut file_resolver = MockFileResolver::new();
+        let file_id = Uuid::new_v4();
+        file_resolver.add_file(file_id, "report.txt", "The revenue grew by 12%.");
+        let chat = create_test_chat();
+
+        let last_answer_id = seed_chat_with_files_in_first_message(
+            &mut message_repo,
+            &file_resolver,
+            &chat,
+            &[file_id],
+            20,
+        )
+        .await;
+        let user_message_id = Uuid::new_v4();
+        message_repo.add_message(
+            user_message_id,
+            Some(last_answer_id),
+            MessageRole::User,
+            &format!("What does erato_file_id:{file_id} say?"),
+        );
+
+        let (abstract_seq, resolved) = compose_turn_with_file_recency(
+            &message_repo,
+            &file_resolver,
+            &chat,
+            user_message_id,
+            vec![],
+            &[file_id],
+            &FileRecencyConfig::default(),
+        )
+        .await;
+
+        assert!(
+            !abstract_seq
+                .parts
+                .iter()
+                .any(|part| matches!(part, AbstractChatSequencePart::RefreshedFile { .. }))
+        );
+        // The file is only included after the first message, far from the end
+        let positions = file_pointer_positions(&resolved, file_id);
+        assert_eq!(positions.len(), 1);
+        assert!(
+            positions[0] < 5,
+            "Expected the file at its original position, found it at {} of {}",
+            positions[0],
+            resolved.messages.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_recency_moves_mentioned_and_reattached_files() {
+        let mut message_repo = MockMessageRepository::new();
+        let mut file_resolver = MockFileResolver::new();
+        let mentioned_file_id = Uuid::new_v4();
+        let reattached_file_id = Uuid::new_v4();
+        file_resolver.add_file(mentioned_file_id, "report.txt", "The revenue grew by 12%.");
+        file_resolver.add_file(reattached_file_id, "notes.txt", "Meeting on Monday.");
+        let chat = create_test_chat();
+
+        let last_answer_id = seed_chat_with_files_in_first_message(
+            &mut message_repo,
+            &file_resolver,
+            &chat,
+            &[mentioned_file_id, reattached_file_id],
+            10,
+        )
+        .await;
+        let user_message_id = Uuid::new_v4();
+        message_repo.add_message(
+            user_message_id,
+            Some(last_answer_id),
+            MessageRole::User,
+            &format!("Compare erato_file_id:{mentioned_file_id} with the notes."),
+        );
+
+        let (abstract_seq, resolved) = compose_turn_with_file_recency(
+            &message_repo,
+            &file_resolver,
+            &chat,
+            user_message_id,
+            vec![reattached_file_id],
+            &[],
+            &enabled_file_recency(3),
+        )
+        .await;
+
+        // The re-attached file is moved as well, instead of being added a second time
+        assert!(
+            !abstract_seq
+                .parts
+                .iter()
+                .any(|part| matches!(part, AbstractChatSequencePart::UserFile { .. }))
+        );
+        let len = resolved.messages.len();
+        assert_eq!(
+            file_pointer_positions(&resolved, reattached_file_id),
+            vec![len - 2]
+        );
+        assert_eq!(
+            file_pointer_positions(&resolved, mentioned_file_id),
+            vec![len - 1]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_recency_respects_max_files() {
+        let mut message_repo = MockMessageRepository::new();
+        let mut file_resolver = MockFileResolver::new();
+        let first_file_id = Uuid::new_v4();
+        let second_file_id = Uuid::new_v4();
+        file_resolver.add_file(first_file_id, "report.txt", "The revenue grew by 12%.");
+        file_resolver.add_file(second_file_id, "notes.txt", "Meeting on Monday.");
+        let chat = create_test_chat();
+
+        let last_answer_id = seed_chat_with_files_in_first_message(
+            &mut message_repo,
+            &file_resolver,
+            &chat,
+            &[first_file_id, second_file_id],
+            20,
+        )
+        .await;
+        let user_message_id = Uuid::new_v4();
+        message_repo.add_message(
+            user_message_id,
+            Some(last_answer_id),
+            MessageRole::User,
+            "Summarize both files.",
+        );
+
+        let (_, resolved) = compose_turn_with_file_recency(
+            &message_repo,
+            &file_resolver,
+            &chat,
+            user_message_id,
+            vec![],
+            &[second_file_id, first_file_id],
+            &enabled_file_recency(1),
+        )
+        .await;
+
+        let last_index = resolved.messages.len() - 1;
+        assert_eq!(
+            file_pointer_positions(&resolved, second_file_id),
+            vec![last_index]
+        );
+        let first_file_positions = file_pointer_positions(&resolved, first_file_id);
+        assert_eq!(first_file_positions.len(), 1);
+        assert!(
+            first_file_positions[0] < 5,
+            "Expected the file over the budget at its original position, found it at {} of {}",
+            first_file_positions[0],
+            resolved.messages.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_recency_ignores_files_outside_of_history() {
+        let mut message_repo = MockMessageRepository::new();
+        let mut file_resolver = MockFileResolver::new();
+        let file_id = Uuid::new_v4();
+        let other_file_id = Uuid::new_v4();
+        file_resolver.add_file(file_id, "report.txt", "The revenue grew by 12%.");
+        file_resolver.add_file(other_file_id, "secret.txt", "Not part of this chat.");
+        let chat = create_test_chat();
+
+        let last_answer_id = seed_chat_with_files_in_first_message(
+            &mut message_repo,
+            &file_resolver,
+            &chat,
+            &[file_id],
+            3,
+        )
+        .await;
+        let user_message_id = Uuid::new_v4();
+        message_repo.add_message(
+            user_message_id,
+            Some(last_answer_id),
+            MessageRole::User,
+            &format!("What is in erato_file_id:{other_file_id}?"),
+        );
+
+        let (abstract_seq, resolved) = compose_turn_with_file_recency(
+            &message_repo,
+            &file_resolver,
+            &chat,
+            user_message_id,
+            vec![],
+            &[other_file_id],
+            &enabled_file_recency(3),
+        )
+        .await;
+
+        assert!(
+            !abstract_seq
+                .parts
+                .iter()
+                .any(|part| matches!(part, AbstractChatSequencePart::RefreshedFile { .. }))
+        );
+        assert!(file_pointer_positions(&resolved, other_file_id).is_empty());
+        assert_eq!(file_pointer_positions(&resolved, file_id).len(), 1);
+    }
+
     // ============================================================================
     // render_action_facet_template tests
     // ============================================================================
@@ -0,0 +1,211 @@
+//! Breakdown of the prompt tokens of a generation by part of the prompt.
+
+use crate::models::message::{ContentPart, GenerationInputMessages, MessageRole, TokenBreakdown};
+use crate::services::file_processing_cached::get_token_count_cached;
+use crate::services::file_synopsis::FILE_MANIFEST_PREFIX;
+use crate::state::AppState;
+use eyre::Report;
+
+/// Prefix of resolved file contents (see `format_successful_file_content`).
+pub(crate) const FILE_CONTENT_PREFIX: &str = "File:\nfile name: ";
+/// Prefix of the text part that precedes a resolved image file.
+const IMAGE_FILE_POINTER_PREFIX: &str = "image_file_pointer: ";
+
+/// Part of the prompt a piece of text is counted towards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PromptPart {
+    SystemPrompt,
+    ChatHistory,
+    FileContent,
+    UserMessage,
+}
+
+/// Count the prompt tokens of resolved generation input messages per part of the prompt.
+///
+/// Expects file pointers to already be resolved to their contents.
+pub async fn count_token_breakdown(
+    app_state: &AppState,
+    generation_input_messages: &GenerationInputMessages,
+) -> Result<TokenBreakdown, Report> {
+    let mut breakdown = TokenBreakdown::default();
+
+    for (part, text) in categorize_input_messages(generation_input_messages) {
+        let tokens = get_token_count_cached(app_state, &text).await? as u32;
+        let counter = match part {
+            PromptPart::SystemPrompt => &mut breakdown.system_prompt_tokens,
+            PromptPart::ChatHistory => &mut breakdown.chat_history_tokens,
+            PromptPart::FileContent => &mut breakdown.file_content_tokens,
+            PromptPart::UserMessage => &mut breakdown.user_message_tokens,
+        };
+        *counter = counter.saturating_add(tokens);
+    }
+
+    Ok(breakdown)
+}
+
+/// Assign the countable text of each input message to a part of the prompt.
+///
+/// The trailing user messages form the user message of the current turn; everything
+/// between the system messages and them is chat history. File contents are counted
+/// separately wherever they appear.
+fn categorize_input_messages(
+    generation_input_messages: &GenerationInputMessages,
+) -> Vec<(PromptPart, String)> {
+    let messages = &generation_input_messages.messages;
+    // The file manifest is a system message within the current turn
+    let current_turn_start = messages
+        .iter()
+        .rposition(|message| {
+            message.role != MessageRole::User && !is_file_content(&message.content)
+        })
+        .map_or(0, |index| index + 1);
+
+    messages
+        .iter()
+        .enumerate()
+        .filter_map(|(index, message)| {
+            let text = countable_text(&message.content)?;
+            let part = if is_file_content(&message.content) {
+                PromptPart::FileContent
+            } else if message.role == MessageRole::System {
+                PromptPart::SystemPrompt
+            } else if index >= current_turn_start {
+                PromptPart::UserMessage
+            } else {
+                PromptPart::ChatHistory
+            };
+            Some((part, text))
+        })
+        .collect()
+}
+
+fn countable_text(content: &ContentPart) -> Option<String> {
+    let text = match content {
+        ContentPart::Text(text) => text.text.clone(),
+        ContentPart::ToolUse(tool_use) => [&tool_use.input, &tool_use.output]
+            .into_iter()
+            .flatten()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+fn is_file_content(content: &ContentPart) -> bool {
+    match content {
+        ContentPart::Text(text) => {
+            text.text.starts_with(FILE_CONTENT_PREFIX)
+                || text.text.starts_with(IMAGE_FILE_POINTER_PREFIX)
+                || text.text.starts_with(FILE_MANIFEST_PREFIX)
+        }
+        ContentPart::Image(_) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::{ContentPartText, InputMessage};
+
+    fn text_message(role: MessageRole, text: &str) -> InputMessage {
+        InputMessage {
+            role,
+            content: ContentPart::Text(ContentPartText {
+                text: text.to_string(),
+            }),
+        }
+    }
+
+    #[test]
+    fn categorizes_prompt_parts() {
+        let messages = GenerationInputMessages {
+            messages: vec![
+                text_message(MessageRole::System, "You are a helpful assistant."),
+                text_message(MessageRole::User, "What is the capital of France?"),
+                text_message(MessageRole::Assistant, "Paris."),
+                text_message(
+                    MessageRole::User,
+                    "File:\nfile name: report.txt\nfile_id: erato_file_id:1\nFile contents\n---\nQ3\n---",
+                ),
+                text_message(MessageRole::User, "Summarize the report."),
+            ],
+        };
+
+        let parts: Vec<PromptPart> = categorize_input_messages(&messages)
+            .into_iter()
+            .map(|(part, _)| part)
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                PromptPart::SystemPrompt,
+                PromptPart::ChatHistory,
+                PromptPart::ChatHistory,
+                PromptPart::FileContent,
+                PromptPart::UserMessage,
+            ]
+        );
+    }
+
+    #[test]
+    fn file_manifest_counts_as_file_content() {
+        let messages = GenerationInputMessages {
+            messages: vec![
+                text_message(MessageRole::System, "You are a helpful assistant."),
+                text_message(MessageRole::User, "Compare the reports."),
+                text_message(
+                    MessageRole::System,
+                    "Attached files:\n1. a.txt (file_id: erato_file_id:1)\n2. b.txt (file_id: erato_file_id:2)\nThe contents of each file follow below.",
+                ),
+                text_message(
+                    MessageRole::User,
+                    "File:\nfile name: a.txt\nfile_id: erato_file_id:1\nFile contents\n---\nA\n---",
+                ),
+                text_message(
+                    MessageRole::User,
+                    "File:\nfile name: b.txt\nfile_id: erato_file_id:2\nFile contents\n---\nB\n---",
+                ),
+            ],
+        };
+
+        let parts: Vec<PromptPart> = categorize_input_messages(&messages)
+            .into_iter()
+            .map(|(part, _)| part)
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                PromptPart::SystemPrompt,
+                PromptPart::UserMessage,
+                PromptPart::FileContent,
+                PromptPart::FileContent,
+                PromptPart::FileContent,
+            ]
+        );
+    }
+
+    #[test]
+    fn first_message_has_no_chat_history() {
+        let messages = GenerationInputMessages {
+            messages: vec![
+                text_message(MessageRole::System, "You are a helpful assistant."),
+                text_message(MessageRole::User, "Hello!"),
+                text_message(MessageRole::User, ""),
+            ],
+        };
+
+        assert_eq!(
+            categorize_input_messages(&messages),
+            vec![
+                (
+                    PromptPart::SystemPrompt,
+                    "You are a helpful assistant.".to_string()
+                ),
+                (PromptPart::UserMessage, "Hello!".to_string()),
+            ]
+        );
+    }
+}
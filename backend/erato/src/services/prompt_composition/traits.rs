@@ -7,6 +7,7 @@ use crate::server::api::v1beta::message_streaming::FileContentsForGeneration;
 use async_trait::async_trait;
 use eyre::Report;
 use sea_orm::prelude::Uuid;
+use std::collections::HashSet;
 
 /// Trait for accessing message data from the repository.
 /// This abstraction allows for easy mocking in tests and separation of concerns.
@@ -45,6 +46,13 @@ pub trait FileResolver: Send + Sync {
 
     /// Determine if a file is an image based on its extension
     async fn is_image_file(&self, file_id: Uuid) -> Result<bool, Report>;
+
+    /// Filter the given file IDs down to the files that exist.
+    async fn existing_file_ids(&self, file_ids: &[Uuid]) -> Result<HashSet<Uuid>, Report>;
+
+    /// Build the manifest listing the given files with their synopses.
+    /// Returns `None` if file manifests are disabled.
+    async fn resolve_file_manifest(&self, file_ids: &[Uuid]) -> Result<Option<String>, Report>;
 }
 
 /// Trait for providing system prompts and assistant configurations.
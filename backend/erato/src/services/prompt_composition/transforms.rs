@@ -11,6 +11,7 @@ use super::types::{
 use crate::config::ActionFacetConfig;
 use crate::config::ChatProviderConfig;
 use crate::config::ExperimentalFacetsConfig;
+use crate::config::FileRecencyConfig;
 use crate::db::entity::chats;
 use crate::db::entity::messages;
 use crate::models::message::{
@@ -18,9 +19,19 @@ use crate::models::message::{
     ContentPartTextFilePointer, GenerationInputMessages, GenerationParameters, InputMessage,
     MessageRole, MessageSchema,
 };
+use crate::services::file_pointer_migration::{
+    inline_file_upload_ids, replace_inline_file_contents,
+};
 use eyre::Report;
 use sea_orm::prelude::Uuid;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+/// Prefix of a file mention, as used in the file manifest and the header of file contents.
+const FILE_ID_MENTION_PREFIX: &str = "erato_file_id:";
+
+/// Header of the custom instruction of a chat in the generation input. Identifies the
+/// instruction when the history is replayed, so only the current instruction is sent.
+const CHAT_CUSTOM_INSTRUCTION_HEADER: &str = "Custom instruction of the user for this chat:\n\n";
 
 const DEFAULT_FACET_PROMPT_TEMPLATE: &str = "The user has requested the use of the \"{{facet_display_name}}\" feature.\n\nPrioritize the use of the following tools:\n{{facet_tools_list}}";
 
@@ -62,6 +73,8 @@ pub async fn build_abstract_sequence(
         None,
         &HashMap::new(),
         None,
+        &[],
+        &FileRecencyConfig::default(),
     )
     .await
 }
@@ -88,21 +101,40 @@ pub async fn build_abstract_sequence_with_facet_tool_expansions(
     action_facet_configs: &HashMap<String, ActionFacetConfig>,
     // Request platform (from `X-Erato-Platform`). Used to scope hidden facets.
     platform: Option<&str>,
+    refresh_file_ids: &[Uuid],
+    file_recency: &FileRecencyConfig,
 ) -> Result<AbstractChatSequence, Report> {
     let mut sequence = AbstractChatSequence::new();
 
     // 1. Check if this is the first message
+    // A welcome message or system messages of clients at the root of the chat don't count as
+    // prior messages.
     let previous_message = message_repo.get_message_by_id(previous_message_id).await?;
-    let is_first_message = previous_message.previous_message_id.is_none();
+    let mut is_first_message = true;
+    let mut parent_id = previous_message.previous_message_id;
+    while let Some(id) = parent_id {
+        let parent = message_repo.get_message_by_id(&id).await?;
+        let is_system_message =
+            parent.raw_message.get("role").and_then(|v| v.as_str()) == Some("system");
+        if !parent.is_welcome_message && !is_system_message {
+            is_first_message = false;
+            break;
+        }
+        parent_id = parent.previous_message_id;
+    }
 
     // 2. Get assistant configuration if available
     let assistant_config = prompt_provider.get_assistant_config(chat).await?;
 
     // 3. Get the previous messages for the conversation history
     // We need these early to check if we should add system prompts
-    let previous_messages = message_repo
+    // Welcome messages are only shown to the user and never sent to the LLM.
+    let previous_messages: Vec<_> = message_repo
         .get_generation_input_messages(previous_message_id, 10)
-        .await?;
+        .await?
+        .into_iter()
+        .filter(|msg| !msg.is_welcome_message)
+        .collect();
 
     // 4. Find the most recent message with generation_input_messages
     let most_recent_with_gen_input = previous_messages
@@ -188,6 +220,18 @@ pub async fn build_abstract_sequence_with_facet_tool_expansions(
             },
         });
     }
+    // 8.5 Add the custom instruction of the chat. Unlike the prompts above, it is added for every
+    // turn, and dropped from the replayed history, so changes apply to the next generation.
+    if let Some(instruction) = chat
+        .custom_instruction
+        .as_deref()
+        .map(str::trim)
+        .filter(|instruction| !instruction.is_empty())
+    {
+        sequence.push(AbstractChatSequencePart::ChatCustomInstruction {
+            content: instruction.to_string(),
+        });
+    }
     // 9. Add assistant files if this is the first message
     // This encapsulates the logic that was previously in prepare_chat_request
     if is_first_message
@@ -357,12 +401,20 @@ pub async fn build_abstract_sequence_with_facet_tool_expansions(
                 MessageRole::User => {
                     if prev_msg.id == *previous_message_id {
                         for part in parsed.content {
-                            if let ContentPart::Text(ContentPartText { text }) = part
-                                && !text.is_empty()
-                            {
-                                sequence.push(AbstractChatSequencePart::CurrentUserContent {
-                                    content: text,
-                                });
+                            match part {
+                                ContentPart::Text(ContentPartText { text }) if !text.is_empty() => {
+                                    sequence.push(AbstractChatSequencePart::CurrentUserContent {
+                                        content: text,
+                                    });
+                                }
+                                ContentPart::BlobPointer(pointer) => {
+                                    sequence.push(
+                                        AbstractChatSequencePart::CurrentUserSpilledContent {
+                                            pointer,
+                                        },
+                                    );
+                                }
+                                _ => {}
                             }
                         }
                     }
@@ -372,16 +424,62 @@ pub async fn build_abstract_sequence_with_facet_tool_expansions(
                         message_id: prev_msg.id,
                     });
                 }
-                // System and Tool messages are embedded in the content, not tracked separately here
-                _ => {}
+                // System messages of clients are kept in thread order. Once answered, they are
+                // part of the generation input of the answer.
+                MessageRole::System => {
+                    sequence.push(AbstractChatSequencePart::ClientSystemMessage {
+                        message_id: prev_msg.id,
+                    });
+                }
+                // Tool messages are embedded in the content, not tracked separately here
+                MessageRole::Tool => {}
+            }
+        }
+    }
+
+    // 11. Move files of the history that the current user input refers to next to it, so they
+    // are not only included at the position they were first attached at
+    let refreshed_file_ids = if file_recency.enabled {
+        let files_in_history = most_recent_history_message
+            .map(history_file_ids)
+            .unwrap_or_default();
+        let mentioned_files: Vec<Uuid> = sequence
+            .parts
+            .iter()
+            .filter_map(|part| match part {
+                AbstractChatSequencePart::CurrentUserContent { content } => Some(content),
+                _ => None,
+            })
+            .flat_map(|content| mentioned_file_ids(content))
+            .collect();
+
+        let mut refreshed: Vec<Uuid> = Vec::new();
+        for file_id in refresh_file_ids
+            .iter()
+            .chain(&new_input_file_ids)
+            .chain(&mentioned_files)
+        {
+            if refreshed.len() >= file_recency.max_files {
+                break;
+            }
+            if files_in_history.contains(file_id) && !refreshed.contains(file_id) {
+                refreshed.push(*file_id);
             }
         }
+        refreshed
+    } else {
+        Vec::new()
+    };
+    for file_id in &refreshed_file_ids {
+        sequence.push(AbstractChatSequencePart::RefreshedFile { file_id: *file_id });
     }
 
-    // 11. Add current user input files (as file references)
+    // 12. Add current user input files (as file references)
     let _ = is_first_message;
     for file_id in new_input_file_ids {
-        sequence.push(AbstractChatSequencePart::UserFile { file_id });
+        if !refreshed_file_ids.contains(&file_id) {
+            sequence.push(AbstractChatSequencePart::UserFile { file_id });
+        }
     }
 
     Ok(sequence)
@@ -398,8 +496,46 @@ pub async fn resolve_sequence(
 ) -> Result<(ResolvedChatSequence, GenerationInputMessages), Report> {
     let mut input_messages = Vec::new();
     let mut has_system_message = false;
+    let mut custom_instruction = None;
+
+    // When multiple files are attached in the turn, a manifest listing all of them is placed
+    // before their contents
+    let user_file_ids: Vec<Uuid> = abstract_seq
+        .parts
+        .iter()
+        .filter_map(|part| match part {
+            AbstractChatSequencePart::UserFile { file_id } => Some(*file_id),
+            _ => None,
+        })
+        .collect();
+    let mut file_manifest = if user_file_ids.len() > 1 {
+        file_resolver.resolve_file_manifest(&user_file_ids).await?
+    } else {
+        None
+    };
+    // Files that are moved next to the current user input are not replayed at their original
+    // position as well
+    let refreshed_file_ids: HashSet<Uuid> = abstract_seq
+        .parts
+        .iter()
+        .filter_map(|part| match part {
+            AbstractChatSequencePart::RefreshedFile { file_id } => Some(*file_id),
+            _ => None,
+        })
+        .collect();
 
     for part in abstract_seq.parts {
+        if matches!(part, AbstractChatSequencePart::UserFile { .. })
+            && let Some(manifest) = file_manifest.take()
+        {
+            // Do NOT set has_system_message — the manifest must not suppress
+            // the base system prompt replayed from history.
+            input_messages.push(InputMessage {
+                role: MessageRole::System,
+                content: ContentPart::Text(ContentPartText { text: manifest }),
+            });
+        }
+
         match part {
             AbstractChatSequencePart::SystemPrompt { spec } => {
                 let content = match spec {
@@ -449,6 +585,12 @@ pub async fn resolve_sequence(
                 }
             }
 
+            AbstractChatSequencePart::ChatCustomInstruction { content } => {
+                // Placed after the system prompts once they are all known, as the system prompts
+                // of the history are only replayed further down the sequence
+                custom_instruction = Some(render_chat_custom_instruction(&content));
+            }
+
             AbstractChatSequencePart::FacetPromptTemplate {
                 spec,
                 facet_id: _,
@@ -523,7 +665,16 @@ pub async fn resolve_sequence(
                 if let Some(gen_input_json) = &message.generation_input_messages {
                     match serde_json::from_value::<GenerationInputMessages>(gen_input_json.clone())
                     {
-                        Ok(gen_input) => {
+                        Ok(mut gen_input) => {
+                            // Histories stored before file pointers existed may contain the
+                            // contents of files inline. They are replaced with pointers, so they
+                            // are not copied into the generation input of the new message.
+                            let inline_file_ids = inline_file_upload_ids(&gen_input);
+                            if !inline_file_ids.is_empty() {
+                                let existing_file_ids =
+                                    file_resolver.existing_file_ids(&inline_file_ids).await?;
+                                replace_inline_file_contents(&mut gen_input, &existing_file_ids);
+                            }
                             let include_system = !has_system_message;
                             for input_msg in gen_input.messages {
                                 // Strip prior-turn action-facet directives so
@@ -534,7 +685,10 @@ pub async fn resolve_sequence(
                                 //     in the DB) → System message whose text
                                 //     starts with "FOR THIS MESSAGE ONLY:"
                                 if is_prior_turn_action_facet_message(&input_msg)
+                                    || is_chat_custom_instruction_message(&input_msg)
                                     || is_client_action_tool_use_message(&input_msg)
+                                    || file_pointer_id(&input_msg.content)
+                                        .is_some_and(|id| refreshed_file_ids.contains(&id))
                                 {
                                     continue;
                                 }
@@ -592,6 +746,24 @@ pub async fn resolve_sequence(
                 }
             }
 
+            AbstractChatSequencePart::ClientSystemMessage { message_id } => {
+                // Not counted as a system prompt, so the system prompts of the history are
+                // still replayed before it
+                let message = message_repo.get_message_by_id(&message_id).await?;
+                let parsed = MessageSchema::validate(&message.raw_message)?;
+                for content_part in parsed.content {
+                    if let ContentPart::Text(ContentPartText { text }) = &content_part
+                        && text.is_empty()
+                    {
+                        continue;
+                    }
+                    input_messages.push(InputMessage {
+                        role: MessageRole::System,
+                        content: content_part,
+                    });
+                }
+            }
+
             AbstractChatSequencePart::CurrentUserContent { content } => {
                 if !content.is_empty() {
                     input_messages.push(InputMessage {
@@ -601,7 +773,17 @@ pub async fn resolve_sequence(
                 }
             }
 
+            AbstractChatSequencePart::CurrentUserSpilledContent { pointer } => {
+                // The pointer is stored in the generation input instead of the content, and
+                // resolved in `resolve_file_pointers_in_generation_input`
+                input_messages.push(InputMessage {
+                    role: MessageRole::User,
+                    content: ContentPart::BlobPointer(pointer),
+                });
+            }
+
             AbstractChatSequencePart::UserFile { file_id }
+            | AbstractChatSequencePart::RefreshedFile { file_id }
             | AbstractChatSequencePart::AssistantFile { file_id } => {
                 // Determine if it's an image file
                 let is_image = file_resolver.is_image_file(file_id).await?;
@@ -626,6 +808,23 @@ pub async fn resolve_sequence(
         }
     }
 
+    if let Some(text) = custom_instruction {
+        let index = input_messages
+            .iter()
+            .take_while(|input_msg| {
+                matches!(input_msg.role, MessageRole::System)
+                    && matches!(input_msg.content, ContentPart::Text(_))
+            })
+            .count();
+        input_messages.insert(
+            index,
+            InputMessage {
+                role: MessageRole::System,
+                content: ContentPart::Text(ContentPartText { text }),
+            },
+        );
+    }
+
     // Create the unresolved version (with file pointers) for DB storage
     let unresolved = GenerationInputMessages {
         messages: input_messages.clone(),
@@ -641,6 +840,58 @@ fn normalize_historical_input_message(input_msg: InputMessage) -> InputMessage {
     input_msg
 }
 
+/// The ID of the file a content part points to, if it is a file pointer.
+fn file_pointer_id(content: &ContentPart) -> Option<Uuid> {
+    match content {
+        ContentPart::TextFilePointer(pointer) => Some(pointer.file_upload_id),
+        ContentPart::ImageFilePointer(pointer) => Some(pointer.file_upload_id),
+        _ => None,
+    }
+}
+
+/// IDs of the files pointed to in the generation input of a message, i.e. the files that are
+/// part of the history replayed from it.
+fn history_file_ids(message: &messages::Model) -> HashSet<Uuid> {
+    message
+        .generation_input_messages
+        .clone()
+        .and_then(|messages| serde_json::from_value::<GenerationInputMessages>(messages).ok())
+        .map(|gen_input| {
+            gen_input
+                .messages
+                .iter()
+                .filter_map(|input_msg| file_pointer_id(&input_msg.content))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// IDs of the files mentioned as `erato_file_id:<id>` in a text.
+fn mentioned_file_ids(text: &str) -> Vec<Uuid> {
+    text.match_indices(FILE_ID_MENTION_PREFIX)
+        .filter_map(|(index, prefix)| {
+            let id = text.get(index + prefix.len()..index + prefix.len() + 36)?;
+            Uuid::parse_str(id).ok()
+        })
+        .collect()
+}
+
+/// The custom instruction of a chat, as it is added to the generation input.
+pub(crate) fn render_chat_custom_instruction(instruction: &str) -> String {
+    format!("{CHAT_CUSTOM_INSTRUCTION_HEADER}{instruction}")
+}
+
+/// True when an `InputMessage` is the custom instruction of the chat at the time of an earlier
+/// turn. The current instruction is added anew.
+fn is_chat_custom_instruction_message(input_msg: &InputMessage) -> bool {
+    matches!(input_msg.role, MessageRole::System)
+        && matches!(
+            &input_msg.content,
+            ContentPart::Text(ContentPartText { text })
+                if text.starts_with(CHAT_CUSTOM_INSTRUCTION_HEADER)
+        )
+}
+
 /// True when an `InputMessage` represents an action-facet directive emitted
 /// by a prior turn. Action facets are request-scoped ("FOR THIS MESSAGE
 /// ONLY") — replaying them turns conflicting format directives into a noisy
@@ -796,7 +1047,7 @@ pub(crate) fn render_action_facet_template(
     result
 }
 
-fn render_facet_template(
+pub(crate) fn render_facet_template(
     template: &str,
     facet_display_name: &str,
     facet_tools_list: &[String],
@@ -1,4 +1,4 @@
-use crate::models::message::{GenerationInputMessages, InputMessage};
+use crate::models::message::{ContentPartBlobPointer, GenerationInputMessages, InputMessage};
 use genai::chat::ChatRequest;
 use sea_orm::prelude::Uuid;
 use serde::{Deserialize, Serialize};
@@ -24,11 +24,19 @@ pub struct PromptCompositionUserInput {
     /// via the centralized file resolution in `resolve_file_pointers_in_generation_input`.
     pub new_input_file_ids: Vec<Uuid>,
 
+    /// IDs of files from earlier messages of the chat that the user asked to include again
+    /// next to the newly submitted message (see `chat.file_recency`).
+    pub refresh_file_ids: Vec<Uuid>,
+
     /// IDs of facets selected by the user for this generation.
     pub selected_facet_ids: Vec<String>,
 
     /// Optional action facet requested by the user for this generation.
     pub action_facet: Option<ActionFacetUserInput>,
+
+    /// Language the user explicitly requested the response to be in.
+    /// Takes precedence over the detected message language and profile preference.
+    pub requested_response_language: Option<String>,
 }
 
 /// Action facet input for prompt composition.
@@ -60,6 +68,10 @@ pub enum AbstractChatSequencePart {
     /// Assistant-specific system prompt
     AssistantPrompt { spec: PromptSpec },
 
+    /// Custom instruction of the chat. Composed for every turn from the current instruction, and
+    /// placed after the leading system prompts.
+    ChatCustomInstruction { content: String },
+
     /// Global facet prompt template (rendered with facet metadata)
     FacetPromptTemplate {
         spec: PromptSpec,
@@ -85,12 +97,23 @@ pub enum AbstractChatSequencePart {
     /// File attached to the current user input
     UserFile { file_id: Uuid },
 
+    /// File of the chat history that the current user input refers to. Placed after the current
+    /// user input, and replaces the pointers to the file at its original position.
+    RefreshedFile { file_id: Uuid },
+
     /// The current user input content being submitted
     CurrentUserContent { content: String },
 
+    /// Content of the current user input that was moved to the file storage because it was too
+    /// large. Resolved like a file pointer before sending to the LLM.
+    CurrentUserSpilledContent { pointer: ContentPartBlobPointer },
+
     /// Reference to a previous assistant message in the chat history
     PreviousAssistantMessage { message_id: Uuid },
 
+    /// Reference to a system message submitted by a client since the last generation
+    ClientSystemMessage { message_id: Uuid },
+
     /// Reference point for reconstructing history from generation_input_messages
     HistoricMessagesFromGenerationInputMessages { message_id: Uuid },
 
@@ -0,0 +1,170 @@
+//! Conversation context for the prompt optimizer.
+//!
+//! When a prompt is optimized for an existing chat, a condensed view of the most
+//! recent messages of the chat's active thread is passed to the optimizer, so the
+//! optimized prompt can refer to what was already discussed.
+
+use crate::models::message::{MessageRole, MessageSchema, get_latest_active_thread_message};
+use crate::policy::engine::PolicyEngine;
+use crate::policy::types::Subject;
+use crate::services::file_processing_cached::get_token_count_cached;
+use crate::services::prompt_composition::{DatabaseMessageRepository, MessageRepository};
+use crate::state::AppState;
+use eyre::{Report, eyre};
+use sqlx::types::Uuid;
+
+/// Maximum number of messages of the active thread considered for the context,
+/// before the token limit is applied.
+const MAX_CONTEXT_MESSAGES: usize = 20;
+
+/// Build the condensed conversation context for a prompt optimization.
+///
+/// The context ends at `previous_message_id` if provided, otherwise at the latest
+/// message of the active thread of `chat_id`. If both are provided, the message has
+/// to belong to the chat. Only messages the subject can read are included.
+///
+/// Returns `None` if neither is provided or there is no message text to include.
+pub async fn build_conversation_context(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    chat_id: Option<&Uuid>,
+    previous_message_id: Option<&Uuid>,
+) -> Result<Option<String>, Report> {
+    let message_repo = DatabaseMessageRepository {
+        conn: &app_state.db,
+        policy,
+        subject,
+        generation_input_archive: &app_state.generation_input_archive,
+    };
+
+    let last_message_id = match (chat_id, previous_message_id) {
+        (_, Some(previous_message_id)) => {
+            let message = message_repo.get_message_by_id(previous_message_id).await?;
+            if chat_id.is_some_and(|chat_id| *chat_id != message.chat_id) {
+                return Err(eyre!(
+                    "Message with ID {} not found in chat",
+                    previous_message_id
+                ));
+            }
+            message.id
+        }
+        (Some(chat_id), None) => {
+            match get_latest_active_thread_message(&app_state.db, policy, subject, chat_id).await? {
+                Some(message) => message.id,
+                None => return Ok(None),
+            }
+        }
+        (None, None) => return Ok(None),
+    };
+
+    let messages = message_repo
+        .get_generation_input_messages(&last_message_id, MAX_CONTEXT_MESSAGES)
+        .await?;
+
+    let mut lines = Vec::new();
+    for message in messages
+        .iter()
+        .filter(|message| !message.is_welcome_message)
+    {
+        let Some(line) = condensed_line(&MessageSchema::validate(&message.raw_message)?) else {
+            continue;
+        };
+        let tokens = get_token_count_cached(app_state, &line).await?;
+        lines.push((line, tokens));
+    }
+
+    let lines =
+        take_recent_within_budget(lines, app_state.config.prompt_optimizer.context_max_tokens);
+    if lines.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(lines.join("\n")))
+}
+
+/// Condense a message into a single line prefixed with its author.
+///
+/// System and tool messages as well as messages without text are skipped.
+fn condensed_line(message: &MessageSchema) -> Option<String> {
+    let author = match message.role {
+        MessageRole::User => "User",
+        MessageRole::Assistant => "Assistant",
+        MessageRole::System | MessageRole::Tool => return None,
+    };
+    let text = message.full_text();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then(|| format!("{author}: {text}"))
+}
+
+/// Keep the most recent lines whose combined token count fits into `max_tokens`.
+///
+/// Lines are given in chronological order together with their token count, and are
+/// returned in chronological order.
+fn take_recent_within_budget(lines: Vec<(String, usize)>, max_tokens: usize) -> Vec<String> {
+    let mut used_tokens = 0;
+    let mut kept: Vec<String> = lines
+        .into_iter()
+        .rev()
+        .take_while(|(_, tokens)| {
+            used_tokens += tokens;
+            used_tokens <= max_tokens
+        })
+        .map(|(line, _)| line)
+        .collect();
+    kept.reverse();
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::{ContentPart, ContentPartText};
+
+    fn message(role: MessageRole, texts: &[&str]) -> MessageSchema {
+        MessageSchema {
+            content: texts
+                .iter()
+                .map(|text| {
+                    ContentPart::Text(ContentPartText {
+                        text: text.to_string(),
+                    })
+                })
+                .collect(),
+            role,
+            name: None,
+            additional_fields: Default::default(),
+        }
+    }
+
+    #[test]
+    fn condenses_messages_to_single_lines() {
+        assert_eq!(
+            condensed_line(&message(MessageRole::User, &["Plan a trip\n\nto Lisbon."])),
+            Some("User: Plan a trip to Lisbon.".to_string())
+        );
+        assert_eq!(
+            condensed_line(&message(MessageRole::Assistant, &["Sure,", "when?"])),
+            Some("Assistant: Sure, when?".to_string())
+        );
+        assert_eq!(
+            condensed_line(&message(MessageRole::System, &["You are helpful."])),
+            None
+        );
+        assert_eq!(condensed_line(&message(MessageRole::User, &["  "])), None);
+    }
+
+    #[test]
+    fn keeps_most_recent_lines_within_budget() {
+        let lines = vec![
+            ("User: first".to_string(), 5),
+            ("Assistant: second".to_string(), 5),
+            ("User: third".to_string(), 5),
+        ];
+        assert_eq!(
+            take_recent_within_budget(lines.clone(), 12),
+            vec!["Assistant: second".to_string(), "User: third".to_string()]
+        );
+        assert_eq!(take_recent_within_budget(lines.clone(), 15).len(), 3);
+        assert!(take_recent_within_budget(lines, 4).is_empty());
+    }
+}
@@ -0,0 +1,665 @@
+//! Detection and validation of the blocks in assistant messages that the frontend renders
+//! specially, i.e. mermaid diagrams and math.
+//!
+//! The checks are intentionally lightweight: they catch the mistakes models commonly make
+//! (unknown diagram types, unbalanced brackets, blocks or environments), so the client can
+//! fall back to plain code rendering for them. Passing validation does not guarantee that a
+//! block renders. The text itself is never modified.
+
+use crate::models::message::{ContentPart, RenderableBlock, RenderableBlockType};
+
+/// Diagram types that mermaid accepts as the first statement of a diagram.
+const MERMAID_DIAGRAM_TYPES: &[&str] = &[
+    "architecture-beta",
+    "block-beta",
+    "C4Component",
+    "C4Container",
+    "C4Context",
+    "C4Deployment",
+    "C4Dynamic",
+    "classDiagram",
+    "classDiagram-v2",
+    "erDiagram",
+    "flowchart",
+    "flowchart-elk",
+    "gantt",
+    "gitGraph",
+    "graph",
+    "journey",
+    "kanban",
+    "mindmap",
+    "packet-beta",
+    "pie",
+    "quadrantChart",
+    "radar-beta",
+    "requirementDiagram",
+    "sankey-beta",
+    "sequenceDiagram",
+    "stateDiagram",
+    "stateDiagram-v2",
+    "timeline",
+    "xychart-beta",
+    "zenuml",
+];
+
+/// Statements that open a block closed by `end` in flowcharts.
+const MERMAID_FLOWCHART_BLOCKS: &[&str] = &["subgraph"];
+
+/// Statements that open a block closed by `end` in sequence diagrams.
+const MERMAID_SEQUENCE_BLOCKS: &[&str] = &[
+    "alt", "box", "break", "critical", "loop", "opt", "par", "rect",
+];
+
+/// Find and validate the renderable blocks in the text parts of a message.
+pub fn find_renderable_blocks(content: &[ContentPart]) -> Vec<RenderableBlock> {
+    content
+        .iter()
+        .enumerate()
+        .filter_map(|(content_index, part)| match part {
+            ContentPart::Text(text) => Some((content_index, text.text.as_str())),
+            _ => None,
+        })
+        .flat_map(|(content_index, text)| find_blocks_in_text(content_index, text))
+        .collect()
+}
+
+/// Find and validate the renderable blocks in a single text.
+fn find_blocks_in_text(content_index: usize, text: &str) -> Vec<RenderableBlock> {
+    let lines = line_offsets(text);
+    let mut blocks = Vec::new();
+    let mut line_index = 0;
+
+    while line_index < lines.len() {
+        let (line_start, line) = lines[line_index];
+
+        if let Some(fence) = opening_fence(line) {
+            let closing_line =
+                (line_index + 1..lines.len()).find(|&index| fence.is_closed_by(lines[index].1));
+            let body_start = lines
+                .get(line_index + 1)
+                .map_or(text.len(), |(start, _)| *start);
+            let (body_end, block_end) = match closing_line {
+                Some(index) => {
+                    let (closing_start, closing) = lines[index];
+                    (closing_start, closing_start + closing.len())
+                }
+                None => (text.len(), text.len()),
+            };
+
+            if let Some(block_type) = fence.block_type {
+                let validation = if closing_line.is_some() {
+                    validate_block(block_type, &text[body_start..body_end])
+                } else {
+                    Err("Code block is not closed".to_string())
+                };
+                blocks.push(block(
+                    block_type,
+                    content_index,
+                    line_start,
+                    block_end,
+                    validation,
+                ));
+            }
+            line_index = closing_line.map_or(lines.len(), |index| index + 1);
+            continue;
+        }
+
+        if let Some((opening, closing)) = display_math_delimiters(line) {
+            let start = line_start + (line.len() - line.trim_start().len());
+            let body_start = start + opening.len();
+            let (block_end, validation) = match text[body_start..].find(closing) {
+                Some(offset) => (
+                    body_start + offset + closing.len(),
+                    validate_math(&text[body_start..body_start + offset]),
+                ),
+                None => (
+                    text.len(),
+                    Err(format!("Display math is not closed with `{closing}`")),
+                ),
+            };
+            blocks.push(block(
+                RenderableBlockType::Math,
+                content_index,
+                start,
+                block_end,
+                validation,
+            ));
+            // Continue after the line containing the end of the block.
+            line_index = lines
+                .iter()
+                .position(|(start, line)| start + line.len() >= block_end)
+                .map_or(lines.len(), |index| index + 1);
+            continue;
+        }
+
+        line_index += 1;
+    }
+
+    blocks
+}
+
+fn block(
+    block_type: RenderableBlockType,
+    content_index: usize,
+    start: usize,
+    end: usize,
+    validation: Result<(), String>,
+) -> RenderableBlock {
+    RenderableBlock {
+        block_type,
+        content_index,
+        start,
+        end,
+        valid: validation.is_ok(),
+        error: validation.err(),
+    }
+}
+
+/// Start offsets and contents (without line terminator) of the lines of a text.
+pub(crate) fn line_offsets(text: &str) -> Vec<(usize, &str)> {
+    let mut offset = 0;
+    text.split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            (start, line.trim_end_matches(['\n', '\r']))
+        })
+        .collect()
+}
+
+pub(crate) struct Fence {
+    pub(crate) marker: char,
+    pub(crate) length: usize,
+    block_type: Option<RenderableBlockType>,
+}
+
+impl Fence {
+    pub(crate) fn is_closed_by(&self, line: &str) -> bool {
+        let Some(rest) = strip_fence_indent(line) else {
+            return false;
+        };
+        let length = rest.chars().take_while(|&c| c == self.marker).count();
+        length >= self.length && rest[length..].trim().is_empty()
+    }
+}
+
+/// Strip up to three spaces of indentation, as allowed before a code fence.
+pub(crate) fn strip_fence_indent(line: &str) -> Option<&str> {
+    let rest = line.trim_start_matches(' ');
+    (line.len() - rest.len() <= 3).then_some(rest)
+}
+
+pub(crate) fn opening_fence(line: &str) -> Option<Fence> {
+    let rest = strip_fence_indent(line)?;
+    let marker = rest.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let length = rest.chars().take_while(|&c| c == marker).count();
+    if length < 3 {
+        return None;
+    }
+    let info = rest[length..].trim();
+    if marker == '`' && info.contains('`') {
+        return None;
+    }
+    let language = info.split_whitespace().next().unwrap_or_default();
+    let block_type = match language.to_ascii_lowercase().as_str() {
+        "mermaid" => Some(RenderableBlockType::Mermaid),
+        "math" | "latex" | "tex" | "katex" => Some(RenderableBlockType::Math),
+        _ => None,
+    };
+    Some(Fence {
+        marker,
+        length,
+        block_type,
+    })
+}
+
+/// Delimiters of a display math block starting on this line, if any.
+fn display_math_delimiters(line: &str) -> Option<(&'static str, &'static str)> {
+    let rest = strip_fence_indent(line)?;
+    if rest.starts_with("$$") {
+        Some(("$$", "$$"))
+    } else if rest.starts_with("\\[") {
+        Some(("\\[", "\\]"))
+    } else {
+        None
+    }
+}
+
+fn validate_block(block_type: RenderableBlockType, body: &str) -> Result<(), String> {
+    match block_type {
+        RenderableBlockType::Mermaid => validate_mermaid(body),
+        RenderableBlockType::Math => validate_math(body),
+    }
+}
+
+/// Check that a mermaid diagram starts with a known diagram type, and that brackets and
+/// `end`-terminated blocks are balanced for the diagram types where that can be checked
+/// without a full parser.
+fn validate_mermaid(body: &str) -> Result<(), String> {
+    let mut statements = body
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with("%%"))
+        .peekable();
+
+    // Skip the optional front matter
+    if statements.peek().is_some_and(|(_, line)| *line == "---") {
+        statements.next();
+        if !statements.any(|(_, line)| line == "---") {
+            return Err("Front matter is not closed with `---`".to_string());
+        }
+    }
+
+    let (_, header) = statements
+        .next()
+        .ok_or_else(|| "Diagram is empty".to_string())?;
+    let diagram_type = header
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .next()
+        .unwrap_or_default();
+    if !MERMAID_DIAGRAM_TYPES.contains(&diagram_type) {
+        return Err(format!("Unknown diagram type `{diagram_type}`"));
+    }
+
+    let statements: Vec<(usize, &str)> = statements.collect();
+    match diagram_type {
+        "graph" | "flowchart" | "flowchart-elk" => {
+            check_mermaid_brackets(&statements)?;
+            check_mermaid_end_blocks(&statements, MERMAID_FLOWCHART_BLOCKS)
+        }
+        "sequenceDiagram" => check_mermaid_end_blocks(&statements, MERMAID_SEQUENCE_BLOCKS),
+        // Not for ER diagrams, where cardinalities like `o{` are unbalanced by design
+        "classDiagram" | "classDiagram-v2" | "stateDiagram" | "stateDiagram-v2" => {
+            check_mermaid_brackets(&statements)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check that the brackets of node shapes and bodies are balanced. Quoted text is ignored.
+fn check_mermaid_brackets(statements: &[(usize, &str)]) -> Result<(), String> {
+    let mut open: Vec<(char, usize)> = Vec::new();
+    for &(line_number, line) in statements {
+        let mut in_quotes = false;
+        let mut previous = ' ';
+        for c in line.chars() {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                _ if in_quotes => {}
+                '(' | '[' | '{' => open.push((c, line_number)),
+                // The asymmetric node shape `id>text]`, as opposed to arrows like `-->`
+                '>' if open.is_empty() && (previous.is_alphanumeric() || previous == '_') => {
+                    open.push(('[', line_number))
+                }
+                ')' | ']' | '}' => {
+                    let expected = match c {
+                        ')' => '(',
+                        ']' => '[',
+                        _ => '{',
+                    };
+                    match open.pop() {
+                        Some((opening, _)) if opening == expected => {}
+                        _ => return Err(format!("Unexpected `{c}` on line {line_number}")),
+                    }
+                }
+                _ => {}
+            }
+            previous = c;
+        }
+        if in_quotes {
+            return Err(format!("Unclosed `\"` on line {line_number}"));
+        }
+    }
+    match open.pop() {
+        Some((opening, line_number)) => {
+            Err(format!("Unclosed `{opening}` opened on line {line_number}"))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Check that every block opened by one of `openers` is closed by an `end` statement.
+fn check_mermaid_end_blocks(statements: &[(usize, &str)], openers: &[&str]) -> Result<(), String> {
+    let mut open: Vec<(&str, usize)> = Vec::new();
+    for &(line_number, line) in statements {
+        let keyword = line
+            .split(|c: char| c.is_whitespace() || c == ';')
+            .next()
+            .unwrap_or_default();
+        if keyword == "end" {
+            if open.pop().is_none() {
+                return Err(format!(
+                    "`end` on line {line_number} does not close any block"
+                ));
+            }
+        } else if let Some(opener) = openers.iter().find(|&&opener| opener == keyword) {
+            open.push((opener, line_number));
+        }
+    }
+    match open.pop() {
+        Some((opener, line_number)) => Err(format!(
+            "`{opener}` on line {line_number} is not closed with `end`"
+        )),
+        None => Ok(()),
+    }
+}
+
+enum MathGroup {
+    Brace,
+    Environment(String),
+    Left,
+}
+
+/// Check that braces, environments and `\left`/`\right` pairs of a LaTeX formula are
+/// balanced and properly nested.
+fn validate_math(body: &str) -> Result<(), String> {
+    if body.trim().is_empty() {
+        return Err("Math block is empty".to_string());
+    }
+
+    let mut open: Vec<MathGroup> = Vec::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => {
+                // Comment until the end of the line
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '{' => open.push(MathGroup::Brace),
+            '}' => match open.pop() {
+                Some(MathGroup::Brace) => {}
+                Some(MathGroup::Environment(name)) => {
+                    return Err(format!("`\\begin{{{name}}}` is not closed"));
+                }
+                Some(MathGroup::Left) => {
+                    return Err("`\\left` without matching `\\right`".to_string());
+                }
+                None => return Err("Unexpected `}`".to_string()),
+            },
+            '\\' => {
+                let command: String =
+                    std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_alphabetic())).collect();
+                match command.as_str() {
+                    // Escaped character, e.g. `\{` or `\\`
+                    "" => {
+                        chars.next();
+                    }
+                    "begin" => open.push(MathGroup::Environment(environment_name(&mut chars)?)),
+                    "end" => {
+                        let name = environment_name(&mut chars)?;
+                        match open.pop() {
+                            Some(MathGroup::Environment(opened)) if opened == name => {}
+                            Some(MathGroup::Environment(opened)) => {
+                                return Err(format!(
+                                    "`\\end{{{name}}}` does not match `\\begin{{{opened}}}`"
+                                ));
+                            }
+                            _ => {
+                                return Err(format!(
+                                    "`\\end{{{name}}}` without matching `\\begin{{{name}}}`"
+                                ));
+                            }
+                        }
+                    }
+                    "left" => {
+                        skip_delimiter(&mut chars);
+                        open.push(MathGroup::Left);
+                    }
+                    "right" => {
+                        skip_delimiter(&mut chars);
+                        match open.pop() {
+                            Some(MathGroup::Left) => {}
+                            _ => return Err("`\\right` without matching `\\left`".to_string()),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match open.pop() {
+        Some(MathGroup::Brace) => Err("Unclosed `{`".to_string()),
+        Some(MathGroup::Environment(name)) => Err(format!("`\\begin{{{name}}}` is not closed")),
+        Some(MathGroup::Left) => Err("`\\left` without matching `\\right`".to_string()),
+        None => Ok(()),
+    }
+}
+
+/// Read the `{name}` argument of `\begin` or `\end`.
+fn environment_name(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+) -> Result<String, String> {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    if chars.next() != Some('{') {
+        return Err("Environment name is missing".to_string());
+    }
+    let mut name = String::new();
+    for c in chars.by_ref() {
+        if c == '}' {
+            return Ok(name);
+        }
+        name.push(c);
+    }
+    Err("Environment name is not closed".to_string())
+}
+
+/// Skip the delimiter following `\left` or `\right`, so e.g. `\left\{` or `\right.` is not
+/// mistaken for a group.
+fn skip_delimiter(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    if chars.next() == Some('\\') {
+        let command_length =
+            std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_alphabetic())).count();
+        if command_length == 0 {
+            chars.next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::ContentPartText;
+
+    fn text_part(text: &str) -> ContentPart {
+        ContentPart::Text(ContentPartText {
+            text: text.to_string(),
+        })
+    }
+
+    fn single_block(text: &str) -> RenderableBlock {
+        let blocks = find_renderable_blocks(&[text_part(text)]);
+        assert_eq!(
+            blocks.len(),
+            1,
+            "Expected one block in {text:?}: {blocks:?}"
+        );
+        blocks.into_iter().next().unwrap()
+    }
+
+    const VALID_MERMAID: &[&str] = &[
+        "graph TD\n    A[Start] --> B{Is it?}\n    B -->|Yes| C(OK)\n    B -->|No| D>Flag]\n",
+        "flowchart LR\n    subgraph one\n        a1[\"Label (with bracket\"] --> a2\n    end\n",
+        "sequenceDiagram\n    Alice->>Bob: Hello (Bob\n    loop Every minute\n        Bob-->>Alice: Hi\n    end\n",
+        "sequenceDiagram\n    alt is sick\n        Bob->>Alice: Not so good\n    else is well\n        Bob->>Alice: Fine\n    end\n",
+        "classDiagram\n    class Animal {\n        +String name\n        +eat()\n    }\n",
+        "stateDiagram-v2\n    [*] --> Still\n    state Moving {\n        [*] --> Slow\n    }\n",
+        "erDiagram\n    CUSTOMER ||--o{ ORDER : places\n    ORDER {\n        string id\n    }\n",
+        "pie title Pets\n    \"Dogs\" : 386\n    \"Cats\" : 85\n",
+        "---\ntitle: Example\n---\n%% a comment\ngantt\n    title A Gantt Diagram\n",
+        "graph TD;\n    A-->B;\n",
+    ];
+
+    const INVALID_MERMAID: &[(&str, &str)] = &[
+        ("", "Diagram is empty"),
+        ("%% only a comment\n", "Diagram is empty"),
+        ("graf TD\n    A --> B\n", "Unknown diagram type `graf`"),
+        ("A --> B\n", "Unknown diagram type `A`"),
+        (
+            "graph TD\n    A[Start --> B\n",
+            "Unclosed `[` opened on line 2",
+        ),
+        ("graph TD\n    A(Start] --> B\n", "Unexpected `]` on line 2"),
+        (
+            "graph TD\n    A[\"Start] --> B\n",
+            "Unclosed `\"` on line 2",
+        ),
+        (
+            "flowchart TB\n    subgraph one\n        a1 --> a2\n",
+            "`subgraph` on line 2 is not closed with `end`",
+        ),
+        (
+            "sequenceDiagram\n    Alice->>Bob: Hi\n    end\n",
+            "`end` on line 3 does not close any block",
+        ),
+        (
+            "sequenceDiagram\n    loop Daily\n        Alice->>Bob: Hi\n",
+            "`loop` on line 2 is not closed with `end`",
+        ),
+        (
+            "classDiagram\n    class Animal {\n        +eat()\n",
+            "Unclosed `{` opened on line 2",
+        ),
+        (
+            "---\ntitle: Example\ngraph TD\n    A --> B\n",
+            "Front matter is not closed with `---`",
+        ),
+    ];
+
+    const VALID_MATH: &[&str] = &[
+        "E = mc^2",
+        "\\frac{a}{b} + \\sqrt{x^{2}}",
+        "\\begin{align}\n  a &= b \\\\\n  c &= d\n\\end{align}",
+        "\\begin{pmatrix} 1 & 0 \\\\ 0 & 1 \\end{pmatrix}",
+        "\\left( \\frac{1}{2} \\right)",
+        "\\left\\{ x \\mid x > 0 \\right.",
+        "\\{ a, b \\} \\cup \\{ c \\}",
+        "x % a comment with { an unbalanced brace\n + y",
+        "\\begin{cases} 1 & \\text{if } x > 0 \\\\ 0 & \\text{otherwise} \\end{cases}",
+    ];
+
+    const INVALID_MATH: &[(&str, &str)] = &[
+        ("", "Math block is empty"),
+        ("  \n", "Math block is empty"),
+        ("\\frac{a}{b", "Unclosed `{`"),
+        ("a}", "Unexpected `}`"),
+        ("\\begin{align} a &= b", "`\\begin{align}` is not closed"),
+        (
+            "\\begin{pmatrix} 1 \\end{bmatrix}",
+            "`\\end{bmatrix}` does not match `\\begin{pmatrix}`",
+        ),
+        (
+            "a \\end{align}",
+            "`\\end{align}` without matching `\\begin{align}`",
+        ),
+        ("\\left( x", "`\\left` without matching `\\right`"),
+        ("x \\right)", "`\\right` without matching `\\left`"),
+        ("{\\left( x }", "`\\left` without matching `\\right`"),
+        ("\\begin align", "Environment name is missing"),
+    ];
+
+    #[test]
+    fn accepts_valid_mermaid_diagrams() {
+        for diagram in VALID_MERMAID {
+            let block = single_block(&format!("```mermaid\n{diagram}```"));
+            assert_eq!(block.block_type, RenderableBlockType::Mermaid);
+            assert!(block.valid, "Expected valid diagram {diagram:?}: {block:?}");
+            assert_eq!(block.error, None);
+        }
+    }
+
+    #[test]
+    fn rejects_broken_mermaid_diagrams() {
+        for (diagram, error) in INVALID_MERMAID {
+            let block = single_block(&format!("```mermaid\n{diagram}```"));
+            assert!(!block.valid, "Expected invalid diagram {diagram:?}");
+            assert_eq!(block.error.as_deref(), Some(*error), "For {diagram:?}");
+        }
+    }
+
+    #[test]
+    fn accepts_valid_math() {
+        for formula in VALID_MATH {
+            for text in [
+                format!("```math\n{formula}\n```"),
+                format!("$${formula}$$"),
+                format!("\\[\n{formula}\n\\]"),
+            ] {
+                let block = single_block(&text);
+                assert_eq!(block.block_type, RenderableBlockType::Math);
+                assert!(block.valid, "Expected valid math {text:?}: {block:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_broken_math() {
+        for (formula, error) in INVALID_MATH {
+            for text in [
+                format!("```latex\n{formula}\n```"),
+                format!("$$\n{formula}\n$$"),
+            ] {
+                let block = single_block(&text);
+                assert!(!block.valid, "Expected invalid math {text:?}");
+                assert_eq!(block.error.as_deref(), Some(*error), "For {text:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn reports_spans_and_content_indices() {
+        let first = "Intro\n\n```mermaid\ngraph TD\n  A --> B\n```\n\nThen $$x^2$$ inline.\n";
+        let second = "$$\n\\frac{1}{2\n$$\n";
+        let content = vec![
+            text_part(first),
+            ContentPart::Reasoning(Default::default()),
+            text_part(second),
+        ];
+
+        let blocks = find_renderable_blocks(&content);
+        assert_eq!(blocks.len(), 2);
+
+        assert_eq!(blocks[0].content_index, 0);
+        assert_eq!(
+            &first[blocks[0].start..blocks[0].end],
+            "```mermaid\ngraph TD\n  A --> B\n```"
+        );
+        assert!(blocks[0].valid);
+
+        assert_eq!(blocks[1].content_index, 2);
+        assert_eq!(
+            &second[blocks[1].start..blocks[1].end],
+            "$$\n\\frac{1}{2\n$$"
+        );
+        assert!(!blocks[1].valid);
+        assert_eq!(blocks[1].error.as_deref(), Some("Unclosed `{`"));
+    }
+
+    #[test]
+    fn reports_unclosed_blocks() {
+        let block = single_block("```mermaid\ngraph TD\n  A --> B\n");
+        assert!(!block.valid);
+        assert_eq!(block.error.as_deref(), Some("Code block is not closed"));
+        assert_eq!(block.end, "```mermaid\ngraph TD\n  A --> B\n".len());
+
+        let block = single_block("$$ x + y");
+        assert!(!block.valid);
+        assert_eq!(
+            block.error.as_deref(),
+            Some("Display math is not closed with `$$`")
+        );
+    }
+
+    #[test]
+    fn ignores_other_code_blocks_and_inline_math() {
+        let text = "```rust\nlet x = \"$$\";\n```\n\n~~~\n```mermaid\n~~~\n\nCosts $5 and $10.\n";
+        assert!(find_renderable_blocks(&[text_part(text)]).is_empty());
+    }
+}
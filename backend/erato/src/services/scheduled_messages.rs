@@ -0,0 +1,247 @@
+//! Submission of scheduled messages once they are due.
+//!
+//! The scheduler runs as a maintenance task of the background task manager. It periodically
+//! claims the due messages and submits them through the same pipeline as
+//! `POST /me/messages/submitstream`, on behalf of the user that scheduled them and without a
+//! client listening. Checks like moderation therefore apply at the time the message is submitted.
+//! Failed attempts are retried after `retry_delay_secs`, until `max_attempts` is reached.
+
+use crate::config::ScheduledMessagesConfig;
+use crate::db::entity::scheduled_messages;
+use crate::models::notification::{
+    NotificationPayload, ScheduledMessageFailedPayload, ScheduledMessageSucceededPayload,
+    create_notification,
+};
+use crate::models::scheduled_message::{
+    claim_due_scheduled_messages, record_scheduled_message_failure,
+    record_scheduled_message_success,
+};
+use crate::server::api::v1beta::me_profile_middleware::{
+    MeProfile, user_profile_from_id_token_claims,
+};
+use crate::server::api::v1beta::message_streaming::{
+    MessageSubmitRequest, start_message_submit, validate_message_submit,
+};
+use crate::server::api::v1beta::policy_engine_middleware::POLICY_REBUILD_THRESHOLD;
+use crate::services::background_tasks::StreamingEvent;
+use crate::state::AppState;
+use chrono::{DateTime, TimeDelta, Utc};
+use eyre::{Report, eyre};
+use sea_orm::prelude::Uuid;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Name of the scheduler in the background task manager.
+pub const SCHEDULED_MESSAGES_TASK: &str = "scheduled_messages";
+
+/// Maximum number of scheduled messages that are claimed at once.
+const CLAIM_BATCH_SIZE: u64 = 10;
+
+/// Outcome of an attempt to submit a scheduled message.
+struct SubmissionOutcome {
+    chat_id: Option<Uuid>,
+    assistant_message_id: Option<Uuid>,
+    error: Option<String>,
+}
+
+/// Start the scheduler, unless it is already running.
+pub fn start_scheduled_message_scheduler(app_state: &AppState) -> bool {
+    let scheduler_state = app_state.clone();
+    app_state
+        .background_tasks
+        .start_maintenance_task(SCHEDULED_MESSAGES_TASK, async move {
+            let config = scheduler_state.config.scheduled_messages.clone();
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(config.poll_interval_secs.max(1)));
+            loop {
+                interval.tick().await;
+                if let Err(err) = run_due_scheduled_messages(&scheduler_state, Utc::now()).await {
+                    tracing::warn!(error = %err, "Failed to run due scheduled messages");
+                }
+            }
+        })
+}
+
+/// Submit all scheduled messages that are due at `now`.
+///
+/// Returns the number of scheduled messages that were attempted.
+pub async fn run_due_scheduled_messages(
+    app_state: &AppState,
+    now: DateTime<Utc>,
+) -> Result<usize, Report> {
+    let config = &app_state.config.scheduled_messages;
+    let mut attempted = 0;
+    loop {
+        let due = claim_due_scheduled_messages(&app_state.db, now, CLAIM_BATCH_SIZE).await?;
+        if due.is_empty() {
+            return Ok(attempted);
+        }
+        attempted += due.len();
+        for scheduled_message in due {
+            run_scheduled_message(app_state, config, &scheduled_message, now).await?;
+        }
+    }
+}
+
+async fn run_scheduled_message(
+    app_state: &AppState,
+    config: &ScheduledMessagesConfig,
+    scheduled_message: &scheduled_messages::Model,
+    now: DateTime<Utc>,
+) -> Result<(), Report> {
+    tracing::info!(
+        scheduled_message_id = %scheduled_message.id,
+        attempt = scheduled_message.attempts,
+        "Submitting scheduled message"
+    );
+    let outcome = match submit_scheduled_message(app_state, scheduled_message).await {
+        Ok(outcome) => outcome,
+        Err(err) => SubmissionOutcome {
+            chat_id: None,
+            assistant_message_id: None,
+            error: Some(err.to_string()),
+        },
+    };
+
+    match (outcome.chat_id, outcome.error) {
+        (Some(chat_id), None) => {
+            record_scheduled_message_success(
+                &app_state.db,
+                &scheduled_message.id,
+                chat_id,
+                outcome.assistant_message_id,
+            )
+            .await?;
+            create_notification(
+                &app_state.db,
+                scheduled_message.owner_user_id,
+                &NotificationPayload::ScheduledMessageSucceeded(ScheduledMessageSucceededPayload {
+                    scheduled_message_id: scheduled_message.id.to_string(),
+                    chat_id: chat_id.to_string(),
+                    assistant_message_id: outcome.assistant_message_id.map(|id| id.to_string()),
+                }),
+            )
+            .await?;
+            Ok(())
+        }
+        (chat_id, error) => {
+            let error = error.unwrap_or_else(|| "The message was not submitted".to_string());
+            let retry_at = (scheduled_message.attempts < config.max_attempts as i32)
+                .then(|| now + TimeDelta::seconds(config.retry_delay_secs as i64));
+            tracing::warn!(
+                scheduled_message_id = %scheduled_message.id,
+                attempt = scheduled_message.attempts,
+                retry = retry_at.is_some(),
+                error = %error,
+                "Scheduled message failed"
+            );
+            record_scheduled_message_failure(
+                &app_state.db,
+                &scheduled_message.id,
+                chat_id,
+                error.clone(),
+                retry_at,
+            )
+            .await?;
+            // Only the final failure is worth the attention of the user
+            if retry_at.is_none() {
+                create_notification(
+                    &app_state.db,
+                    scheduled_message.owner_user_id,
+                    &NotificationPayload::ScheduledMessageFailed(ScheduledMessageFailedPayload {
+                        scheduled_message_id: scheduled_message.id.to_string(),
+                        chat_id: chat_id.map(|id| id.to_string()),
+                        error,
+                    }),
+                )
+                .await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Submit a scheduled message on behalf of its owner, and wait for the generation to end.
+async fn submit_scheduled_message(
+    app_state: &AppState,
+    scheduled_message: &scheduled_messages::Model,
+) -> Result<SubmissionOutcome, Report> {
+    let request: MessageSubmitRequest = serde_json::from_value(scheduled_message.request.clone())?;
+    let profile =
+        user_profile_from_id_token_claims(app_state, &scheduled_message.id_token_claims, None)
+            .await
+            .map_err(|status| eyre!("Failed to load the profile of the user: {}", status))?;
+    if profile.id != scheduled_message.owner_user_id.to_string() {
+        return Err(eyre!(
+            "The stored claims don't belong to the owner of the scheduled message"
+        ));
+    }
+    let me_user = MeProfile {
+        profile,
+        // There is no token of the user when the scheduled message is due.
+        oidc_token: None,
+        id_token_claims: scheduled_message.id_token_claims.clone(),
+        access_token: None,
+    };
+    let policy = app_state
+        .global_policy_engine
+        .get_engine_with_rebuild_check(&app_state.db, &app_state.config, POLICY_REBUILD_THRESHOLD)
+        .await?;
+
+    let generation_request_context =
+        validate_message_submit(app_state, &policy, &me_user, &request, &Default::default())
+            .await
+            .map_err(|(status, error)| eyre!("{}: {}", status, error))?;
+    let (chat_id, events, _generation_span) = start_message_submit(
+        app_state,
+        &policy,
+        &me_user,
+        request,
+        generation_request_context,
+    )
+    .await
+    .map_err(|(status, error)| eyre!("{}: {}", status, error))?;
+
+    let mut outcome = wait_for_generation(events).await;
+    outcome.chat_id = Some(chat_id);
+    Ok(outcome)
+}
+
+/// Follow the events of a generation until its stream ends.
+async fn wait_for_generation(mut events: broadcast::Receiver<StreamingEvent>) -> SubmissionOutcome {
+    let mut outcome = SubmissionOutcome {
+        chat_id: None,
+        assistant_message_id: None,
+        error: None,
+    };
+    loop {
+        match events.recv().await {
+            Ok(StreamingEvent::AssistantMessageCompleted { message_id, .. }) => {
+                outcome.assistant_message_id = Some(message_id);
+            }
+            Ok(StreamingEvent::Error { error }) => {
+                outcome
+                    .error
+                    .get_or_insert_with(|| error_description(error));
+            }
+            Ok(StreamingEvent::StreamEnd) | Err(broadcast::error::RecvError::Closed) => {
+                return outcome;
+            }
+            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+        }
+    }
+}
+
+fn error_description(error: Option<Value>) -> String {
+    error
+        .as_ref()
+        .and_then(|error| {
+            error
+                .get("error_description")
+                .or_else(|| error.get("error_type"))
+        })
+        .and_then(Value::as_str)
+        .unwrap_or("The message could not be generated.")
+        .to_string()
+}
@@ -0,0 +1,590 @@
+//! Seeding of demo and development data.
+//!
+//! Creates a deterministic set of users, chats, assistants and share grants, so that a fresh
+//! database can be explored without clicking through the UI first. All records are derived
+//! from a seed value: chats and assistants get UUIDs that are hashed from the seed, and are only
+//! created (together with their messages, files and share grants) if they don't exist yet. This
+//! makes repeated runs with the same seed a no-op.
+//!
+//! Records are created through the functions of the model layer wherever possible, so that the
+//! seeded data looks exactly like data created via the API.
+
+use crate::db::entity::prelude::*;
+use crate::db::entity::{assistants, chats, users};
+use crate::models;
+use crate::models::assistant::VISIBILITY_PRIVATE;
+use crate::models::message::{
+    ContentPart, ContentPartText, GenerationMetadata, StopReason, ToolCallStatus, ToolUse,
+};
+use crate::models::user_preference::UpdateUserPreferencesInput;
+use crate::policy::prelude::*;
+use crate::state::AppState;
+use chrono::Utc;
+use eyre::{Report, WrapErr};
+use sea_orm::prelude::*;
+use sea_orm::{ActiveValue, DatabaseConnection};
+use serde::Serialize;
+use serde_json::{Value as JsonValue, json};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+/// Issuer of all users created by the seeding.
+pub const SEED_USER_ISSUER: &str = "erato-seed";
+
+/// Seed value that is used if none is provided.
+pub const DEFAULT_SEED: u64 = 1;
+
+/// Which set of fixtures to create.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SeedProfile {
+    /// Two users with a single chat and assistant each.
+    #[default]
+    Minimal,
+    /// A handful of users with several chats each.
+    Demo,
+}
+
+impl SeedProfile {
+    pub const ALL: [SeedProfile; 2] = [SeedProfile::Minimal, SeedProfile::Demo];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SeedProfile::Minimal => "minimal",
+            SeedProfile::Demo => "demo",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|profile| profile.name() == name)
+    }
+
+    fn user_count(&self) -> usize {
+        match self {
+            SeedProfile::Minimal => 2,
+            SeedProfile::Demo => 4,
+        }
+    }
+
+    fn chats_per_user(&self) -> usize {
+        match self {
+            SeedProfile::Minimal => 1,
+            SeedProfile::Demo => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SeedOptions {
+    pub profile: SeedProfile,
+    pub seed: u64,
+}
+
+/// Number of records that were created by a seeding run.
+///
+/// Records that already existed from a previous run are not counted.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct SeedSummary {
+    /// Name of the seeded profile
+    pub profile: String,
+    /// The seed value the fixtures were derived from
+    pub seed: u64,
+    pub users: usize,
+    pub chats: usize,
+    pub messages: usize,
+    pub feedbacks: usize,
+    pub assistants: usize,
+    pub files: usize,
+    pub share_grants: usize,
+}
+
+/// Subject claim of the n-th user of a seed.
+pub fn seed_user_subject(seed: u64, user_index: usize) -> String {
+    format!("seed-{seed}-user-{user_index}")
+}
+
+/// Deterministic ID of a seeded record, derived from the seed and a key of the record.
+fn seed_uuid(seed: u64, kind: &str, key: &str) -> Uuid {
+    let hash = Sha256::digest(format!("erato-seed:{seed}:{kind}:{key}").as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    // Mark as a random (v4) UUID, like the ones created by the application
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    Uuid::from_bytes(bytes)
+}
+
+struct SeedUser {
+    nickname: &'static str,
+    job_title: &'static str,
+}
+
+const SEED_USERS: [SeedUser; 4] = [
+    SeedUser {
+        nickname: "Ada",
+        job_title: "Software Engineer",
+    },
+    SeedUser {
+        nickname: "Grace",
+        job_title: "Product Manager",
+    },
+    SeedUser {
+        nickname: "Linus",
+        job_title: "Support Lead",
+    },
+    SeedUser {
+        nickname: "Margaret",
+        job_title: "Data Analyst",
+    },
+];
+
+struct SeedToolCall {
+    tool_name: &'static str,
+    input: fn() -> JsonValue,
+    output: fn() -> JsonValue,
+}
+
+struct SeedConversation {
+    title: &'static str,
+    question: &'static str,
+    tool_call: Option<SeedToolCall>,
+    answer: &'static str,
+    follow_up: &'static str,
+    follow_up_answer: &'static str,
+    /// Edited version of the follow-up, stored as a sibling of the original one.
+    edited_follow_up: &'static str,
+    edited_follow_up_answer: &'static str,
+}
+
+const SEED_CONVERSATIONS: [SeedConversation; 3] = [
+    SeedConversation {
+        title: "Regex Search-and-Replace with Ripgrep",
+        question: "How do I replace all occurrences of `foo_bar` with `fooBar` in a repository?",
+        tool_call: None,
+        answer: "ripgrep itself only searches, but you can combine it with `sed`:\n\n```sh\nrg -l 'foo_bar' | xargs sed -i 's/foo_bar/fooBar/g'\n```",
+        follow_up: "Does that also work on macOS?",
+        follow_up_answer: "On macOS `sed -i` requires a backup suffix, so use `sed -i ''` instead.",
+        edited_follow_up: "Can I preview the changes before applying them?",
+        edited_follow_up_answer: "Yes, ripgrep can show the result without touching any file:\n\n```sh\nrg 'foo_bar' --replace 'fooBar'\n```",
+    },
+    SeedConversation {
+        title: "Weather for the Team Offsite",
+        question: "What will the weather be like in Berlin tomorrow?",
+        tool_call: Some(SeedToolCall {
+            tool_name: "get_weather_forecast",
+            input: || json!({ "location": "Berlin", "days": 1 }),
+            output: || json!({ "condition": "partly cloudy", "high_celsius": 21, "low_celsius": 12 }),
+        }),
+        answer: "Tomorrow will be partly cloudy in Berlin, with temperatures between 12 °C and 21 °C.",
+        follow_up: "Should we plan the offsite outdoors?",
+        follow_up_answer: "The forecast looks good for an outdoor offsite, but a covered fallback area is a good idea.",
+        edited_follow_up: "Should we bring umbrellas?",
+        edited_follow_up_answer: "No rain is expected, so umbrellas should not be necessary.",
+    },
+    SeedConversation {
+        title: "Explain a Customer Support Handoff",
+        question: "Summarize the open support tickets for the handoff to the next shift.",
+        tool_call: Some(SeedToolCall {
+            tool_name: "list_support_tickets",
+            input: || json!({ "status": "open" }),
+            output: || {
+                json!({
+                    "tickets": [
+                        { "id": 4711, "subject": "Login fails with SSO", "priority": "high" },
+                        { "id": 4712, "subject": "Export is missing columns", "priority": "low" },
+                    ]
+                })
+            },
+        }),
+        answer: "There are two open tickets:\n\n- **#4711** Login fails with SSO (high priority)\n- **#4712** Export is missing columns (low priority)",
+        follow_up: "Which one should be handled first?",
+        follow_up_answer: "Ticket #4711 should be handled first, as it blocks users from logging in.",
+        edited_follow_up: "Draft a short handoff note for these tickets.",
+        edited_follow_up_answer: "Handoff: #4711 (SSO login failure) is high priority and still under investigation. #4712 (missing export columns) can wait until the next release.",
+    },
+];
+
+const SEED_ASSISTANT_FILE_NAME: &str = "team-handbook.md";
+const SEED_ASSISTANT_FILE_CONTENT: &str = "# Team Handbook\n\n- Standup is at 9:30 every weekday.\n- Support shifts hand off at 14:00.\n- Releases are published on Thursdays.\n";
+
+fn user_message(text: &str) -> JsonValue {
+    json!({
+        "role": "user",
+        "content": [ContentPart::Text(ContentPartText { text: text.to_string() })],
+    })
+}
+
+fn assistant_message(content: Vec<ContentPart>) -> JsonValue {
+    json!({
+        "role": "assistant",
+        "content": content,
+    })
+}
+
+fn completed_generation_metadata() -> Option<GenerationMetadata> {
+    Some(GenerationMetadata {
+        stop_reason: Some(StopReason::Completed),
+        ..Default::default()
+    })
+}
+
+/// Seed the database with the fixtures of the given profile.
+///
+/// The policy engine of the app state is rebuilt afterward, so the new records are visible to
+/// requests right away.
+pub async fn seed_database(
+    app_state: &AppState,
+    options: &SeedOptions,
+) -> Result<SeedSummary, Report> {
+    let conn = &app_state.db;
+    let seed = options.seed;
+    let profile = options.profile;
+    let mut summary = SeedSummary {
+        profile: profile.name().to_string(),
+        seed,
+        ..Default::default()
+    };
+
+    // Users and their profiles
+    let mut seeded_users = Vec::new();
+    for (user_index, seed_user) in SEED_USERS.iter().take(profile.user_count()).enumerate() {
+        let subject = seed_user_subject(seed, user_index);
+        let existing_user = Users::find()
+            .filter(users::Column::Issuer.eq(SEED_USER_ISSUER))
+            .filter(users::Column::Subject.eq(subject.as_str()))
+            .one(conn)
+            .await?;
+        let user = match existing_user {
+            Some(user) => user,
+            None => {
+                let email = format!(
+                    "{}.seed{seed}@example.com",
+                    seed_user.nickname.to_lowercase()
+                );
+                let user = models::user::get_or_create_user(
+                    conn,
+                    SEED_USER_ISSUER,
+                    &subject,
+                    Some(&email),
+                )
+                .await?;
+                models::user_preference::upsert_user_preferences(
+                    conn,
+                    &user.id,
+                    UpdateUserPreferencesInput {
+                        nickname: Some(Some(seed_user.nickname.to_string())),
+                        job_title: Some(Some(seed_user.job_title.to_string())),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+                summary.users += 1;
+                user
+            }
+        };
+        seeded_users.push(user);
+    }
+
+    // Chats and assistants are created directly, as they need deterministic IDs.
+    // Their contents are only created together with them, so an existing chat or
+    // assistant is left untouched.
+    let mut new_chats = Vec::new();
+    for (user_index, user) in seeded_users.iter().enumerate() {
+        for chat_index in 0..profile.chats_per_user() {
+            let chat_id = seed_uuid(seed, "chat", &format!("{user_index}-{chat_index}"));
+            if Chats::find_by_id(chat_id).one(conn).await?.is_some() {
+                continue;
+            }
+            let conversation_index =
+                (seed as usize + user_index + chat_index) % SEED_CONVERSATIONS.len();
+            let conversation = &SEED_CONVERSATIONS[conversation_index];
+            let new_chat = chats::ActiveModel {
+                id: ActiveValue::Set(chat_id),
+                owner_user_id: ActiveValue::Set(user.id.to_string()),
+                title_by_summary: ActiveValue::Set(Some(conversation.title.to_string())),
+                ..Default::default()
+            };
+            Chats::insert(new_chat).exec(conn).await?;
+            summary.chats += 1;
+            new_chats.push((user, chat_id, conversation));
+        }
+    }
+
+    // The remaining records are authorized against the new chats, assistants and files
+    let policy = PolicyEngine::new();
+    let file_storage_provider_id = app_state.default_file_storage_provider_id();
+    let mut new_assistants = Vec::new();
+    for (user_index, user) in seeded_users.iter().enumerate() {
+        let assistant_id = seed_uuid(seed, "assistant", &user_index.to_string());
+        if Assistants::find_by_id(assistant_id)
+            .one(conn)
+            .await?
+            .is_some()
+        {
+            continue;
+        }
+        let seed_user = &SEED_USERS[user_index];
+        let now = Utc::now();
+        let new_assistant = assistants::ActiveModel {
+            id: ActiveValue::Set(assistant_id),
+            owner_user_id: ActiveValue::Set(user.id),
+            name: ActiveValue::Set(format!("{}'s Team Assistant", seed_user.nickname)),
+            description: ActiveValue::Set(Some(
+                "Answers questions based on the team handbook.".to_string(),
+            )),
+            prompt: ActiveValue::Set(
+                "You are a helpful assistant for the team. Use the team handbook to answer questions."
+                    .to_string(),
+            ),
+            mcp_server_ids: ActiveValue::Set(None),
+            facet_ids: ActiveValue::Set(None),
+            default_chat_provider: ActiveValue::Set(None),
+            enforce_facet_settings: ActiveValue::Set(false),
+            welcome_message: ActiveValue::Set(None),
+            pinned_facet_ids: ActiveValue::Set(None),
+            archived_at: ActiveValue::Set(None),
+            created_at: ActiveValue::Set(now.into()),
+            updated_at: ActiveValue::Set(now.into()),
+            organization_id: ActiveValue::Set(user.organization_id.clone()),
+            category: ActiveValue::Set(None),
+            tags: ActiveValue::Set(Vec::new()),
+            visibility: ActiveValue::Set(VISIBILITY_PRIVATE.to_string()),
+            featured: ActiveValue::Set(false),
+        };
+        Assistants::insert(new_assistant).exec(conn).await?;
+        summary.assistants += 1;
+
+        // Upload the knowledge file of the assistant
+        let subject = Subject::User(user.id.to_string());
+        let file_storage_path = format!(
+            "seed/{}",
+            seed_uuid(seed, "assistant_file", &user_index.to_string())
+        );
+        let mut writer = app_state
+            .default_file_storage_provider()
+            .upload_file_writer(&file_storage_path, Some("text/markdown"))
+            .await
+            .wrap_err("Failed to create writer for seeded assistant file")?;
+        writer
+            .write(SEED_ASSISTANT_FILE_CONTENT.as_bytes().to_vec())
+            .await
+            .wrap_err("Failed to write seeded assistant file")?;
+        writer
+            .close()
+            .await
+            .wrap_err("Failed to close seeded assistant file writer")?;
+        let file_upload = models::assistant::create_standalone_file_upload(
+            conn,
+            &policy,
+            &subject,
+            SEED_ASSISTANT_FILE_NAME.to_string(),
+            file_storage_provider_id.clone(),
+            file_storage_path,
+        )
+        .await?;
+        summary.files += 1;
+        new_assistants.push((user_index, user, assistant_id, file_upload.id));
+    }
+
+    policy.rebuild_data(conn, &app_state.config).await?;
+
+    for (user, chat_id, conversation) in new_chats {
+        let subject = Subject::User(user.id.to_string());
+        seed_conversation(
+            conn,
+            app_state,
+            &policy,
+            &subject,
+            &chat_id,
+            conversation,
+            &mut summary,
+        )
+        .await
+        .wrap_err_with(|| format!("Failed to seed messages of chat {chat_id}"))?;
+    }
+
+    for (user_index, user, assistant_id, file_upload_id) in new_assistants {
+        let subject = Subject::User(user.id.to_string());
+        models::assistant::add_file_to_assistant(
+            conn,
+            &policy,
+            &subject,
+            assistant_id,
+            file_upload_id,
+        )
+        .await?;
+
+        // Share the assistant with the next user
+        if seeded_users.len() > 1 {
+            let shared_with = &seeded_users[(user_index + 1) % seeded_users.len()];
+            models::share_grant::create_share_grant(
+                conn,
+                &policy,
+                &subject,
+                "assistant".to_string(),
+                assistant_id.to_string(),
+                "user".to_string(),
+                "id".to_string(),
+                shared_with.id.to_string(),
+                "viewer".to_string(),
+            )
+            .await?;
+            summary.share_grants += 1;
+        }
+    }
+
+    app_state.global_policy_engine.invalidate_data().await;
+    app_state
+        .global_policy_engine
+        .rebuild_data_if_needed(conn, &app_state.config)
+        .await?;
+
+    Ok(summary)
+}
+
+/// Create the messages of a seeded chat.
+///
+/// The active thread ends with the edited follow-up, while the original follow-up is kept as
+/// its sibling, like after editing a message in the UI.
+async fn seed_conversation(
+    conn: &DatabaseConnection,
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    chat_id: &Uuid,
+    conversation: &SeedConversation,
+    summary: &mut SeedSummary,
+) -> Result<(), Report> {
+    let submit = |raw_message: JsonValue,
+                  previous_message_id: Option<Uuid>,
+                  sibling_message_id: Option<Uuid>,
+                  generation_metadata: Option<GenerationMetadata>| async move {
+        models::message::submit_message(
+            conn,
+            policy,
+            subject,
+            chat_id,
+            raw_message,
+            previous_message_id.as_ref(),
+            sibling_message_id.as_ref(),
+            None,
+            None,
+            &[],
+            None,
+            generation_metadata,
+            None,
+            false,
+        )
+        .await
+    };
+
+    let question = submit(user_message(conversation.question), None, None, None).await?;
+
+    let mut answer_content = Vec::new();
+    if let Some(tool_call) = &conversation.tool_call {
+        answer_content.push(ContentPart::ToolUse(ToolUse {
+            tool_call_id: format!("call_{}", tool_call.tool_name),
+            status: ToolCallStatus::Success,
+            tool_name: tool_call.tool_name.to_string(),
+            progress_message: None,
+            input: Some((tool_call.input)()),
+            output: Some((tool_call.output)()),
+            started_at: None,
+            ended_at: None,
+        }));
+    }
+    answer_content.push(ContentPart::Text(ContentPartText {
+        text: conversation.answer.to_string(),
+    }));
+    let answer = submit(
+        assistant_message(answer_content),
+        Some(question.id),
+        None,
+        completed_generation_metadata(),
+    )
+    .await?;
+
+    let follow_up = submit(
+        user_message(conversation.follow_up),
+        Some(answer.id),
+        None,
+        None,
+    )
+    .await?;
+    let follow_up_answer = submit(
+        assistant_message(vec![ContentPart::Text(ContentPartText {
+            text: conversation.follow_up_answer.to_string(),
+        })]),
+        Some(follow_up.id),
+        None,
+        completed_generation_metadata(),
+    )
+    .await?;
+
+    let edited_follow_up = submit(
+        user_message(conversation.edited_follow_up),
+        Some(answer.id),
+        Some(follow_up.id),
+        None,
+    )
+    .await?;
+    submit(
+        assistant_message(vec![ContentPart::Text(ContentPartText {
+            text: conversation.edited_follow_up_answer.to_string(),
+        })]),
+        Some(edited_follow_up.id),
+        None,
+        completed_generation_metadata(),
+    )
+    .await?;
+    summary.messages += 6;
+
+    for (message_id, sentiment, comment) in [
+        (answer.id, "positive", None),
+        (
+            follow_up_answer.id,
+            "negative",
+            Some("Did not answer the question.".to_string()),
+        ),
+    ] {
+        models::message_feedback::submit_or_update_feedback(
+            conn,
+            policy,
+            subject,
+            &message_id,
+            sentiment.to_string(),
+            comment,
+            &app_state.langfuse_client,
+            // Seeded feedback is never forwarded to Langfuse
+            false,
+            None,
+        )
+        .await?;
+        summary.feedbacks += 1;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_names_round_trip() {
+        for profile in SeedProfile::ALL {
+            assert_eq!(SeedProfile::from_name(profile.name()), Some(profile));
+        }
+        assert_eq!(SeedProfile::from_name("unknown"), None);
+    }
+
+    #[test]
+    fn seed_uuids_are_deterministic() {
+        assert_eq!(seed_uuid(1, "chat", "0-0"), seed_uuid(1, "chat", "0-0"));
+        assert_ne!(seed_uuid(1, "chat", "0-0"), seed_uuid(2, "chat", "0-0"));
+        assert_ne!(
+            seed_uuid(1, "chat", "0-0"),
+            seed_uuid(1, "assistant", "0-0")
+        );
+        assert_eq!(seed_uuid(1, "chat", "0-0").get_version_num(), 4);
+    }
+}
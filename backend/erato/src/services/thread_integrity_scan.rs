@@ -0,0 +1,118 @@
+//! Periodic scan of the threads of a sample of chats for corruptions.
+//!
+//! With `admin.thread_integrity_scan.enabled`, a random sample of chats is checked with
+//! [`check_thread_integrity`] at every interval, and the number of corrupted chats per issue kind
+//! is reported as metrics. Nothing is repaired, corrupted chats can be repaired through the admin
+//! API.
+
+use crate::config::ThreadIntegrityScanConfig;
+use crate::metrics::report_thread_integrity_scan;
+use crate::metrics_constants::POSTGRES_QUERY_SAMPLE_CHAT_IDS;
+use crate::models::message::check_thread_integrity;
+use crate::models::message_thread_integrity::ThreadIntegrityIssueKind;
+use crate::query_metrics::named_statement_from_sql_and_values;
+use crate::state::AppState;
+use eyre::Report;
+use sea_orm::prelude::Uuid;
+use sea_orm::{ConnectionTrait, DatabaseConnection};
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Name of the scan in the background task manager.
+pub const THREAD_INTEGRITY_SCAN_TASK: &str = "thread_integrity_scan";
+
+/// Result of a scan of the threads of a sample of chats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadIntegrityScanSummary {
+    pub scanned_chats: usize,
+    /// Number of scanned chats with at least one issue of each kind, in the order of
+    /// [`ThreadIntegrityIssueKind::ALL`]
+    pub corrupted_chats: Vec<(ThreadIntegrityIssueKind, usize)>,
+}
+
+/// Check the threads of a random sample of chats.
+pub async fn scan_thread_integrity(
+    db: &DatabaseConnection,
+    config: &ThreadIntegrityScanConfig,
+) -> Result<ThreadIntegrityScanSummary, Report> {
+    let statement = named_statement_from_sql_and_values(
+        db.get_database_backend(),
+        POSTGRES_QUERY_SAMPLE_CHAT_IDS,
+        "SELECT id FROM chats ORDER BY random() LIMIT $1",
+        vec![(config.sample_size as i64).into()],
+    );
+    let chat_ids = db
+        .query_all_raw(statement)
+        .await?
+        .iter()
+        .map(|row| row.try_get::<Uuid>("", "id"))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut corrupted_chats: Vec<(ThreadIntegrityIssueKind, usize)> = ThreadIntegrityIssueKind::ALL
+        .iter()
+        .map(|kind| (*kind, 0))
+        .collect();
+    let mut scanned_chats = 0;
+    for chat_id in chat_ids {
+        let report = match check_thread_integrity(db, &chat_id).await {
+            Ok(report) => report,
+            // The chat was deleted since it was sampled
+            Err(err) if err.to_string().contains("not found") => continue,
+            Err(err) => return Err(err),
+        };
+        scanned_chats += 1;
+        if report.is_intact() {
+            continue;
+        }
+        let kinds: HashSet<ThreadIntegrityIssueKind> =
+            report.issues.iter().map(|issue| issue.kind).collect();
+        tracing::warn!(
+            chat_id = %chat_id,
+            issues = report.issues.len(),
+            "Found chat with corrupted thread"
+        );
+        for (kind, count) in &mut corrupted_chats {
+            if kinds.contains(kind) {
+                *count += 1;
+            }
+        }
+    }
+
+    Ok(ThreadIntegrityScanSummary {
+        scanned_chats,
+        corrupted_chats,
+    })
+}
+
+/// Start the periodic scan, if it is enabled and not already running.
+pub fn start_thread_integrity_scan(app_state: &AppState) -> bool {
+    let config = app_state.config.admin.thread_integrity_scan.clone();
+    if !config.enabled {
+        return false;
+    }
+    let db = app_state.db.clone();
+    app_state
+        .background_tasks
+        .start_maintenance_task(THREAD_INTEGRITY_SCAN_TASK, async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(config.interval_secs.max(1)));
+            loop {
+                interval.tick().await;
+                match scan_thread_integrity(&db, &config).await {
+                    Ok(summary) => {
+                        tracing::info!(
+                            scanned_chats = summary.scanned_chats,
+                            "Scanned threads of chats"
+                        );
+                        report_thread_integrity_scan(
+                            summary.scanned_chats,
+                            &summary.corrupted_chats,
+                        );
+                    }
+                    Err(err) => {
+                        tracing::warn!(error = %err, "Failed to scan threads of chats");
+                    }
+                }
+            }
+        })
+}
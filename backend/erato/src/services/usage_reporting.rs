@@ -0,0 +1,527 @@
+//! Anonymous reports of aggregate usage numbers (see `telemetry.usage_reporting` in the config).
+//!
+//! A report is a [`UsageReport`], which by construction only consists of the enabled features,
+//! counts that are bucketed into ranges, and the version of Erato: it has no field that could hold
+//! any text or ID. [`build_usage_report`] also backs `GET /admin/telemetry/preview`, so that the
+//! exact report can be inspected before enabling usage reporting.
+//!
+//! Reports are signed with a random ID of the installation, that is created once and stored in
+//! the database (see [`usage_report_signature`]). The ID is deleted when usage reporting is
+//! disabled, so that the reports of an installation can't be linked across the time it was
+//! disabled.
+
+use crate::db::entity::prelude::*;
+use crate::db::entity::{chats, messages, usage_reporting_installation};
+use crate::metrics_constants::{
+    POSTGRES_QUERY_CLAIM_USAGE_REPORT, POSTGRES_QUERY_CREATE_USAGE_REPORTING_INSTALLATION,
+    POSTGRES_QUERY_USAGE_REPORT_COUNTS,
+};
+use crate::query_metrics::named_statement_from_sql_and_values;
+use crate::services::feature_flags::FeatureFlag;
+use crate::state::AppState;
+use chrono::{TimeDelta, Utc};
+use eyre::{Report, WrapErr, eyre};
+use hmac::{Hmac, KeyInit, Mac};
+use sea_orm::prelude::Uuid;
+use sea_orm::{
+    ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, PaginatorTrait,
+    QueryFilter,
+};
+use serde::Serialize;
+use sha2::Sha256;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Name of the reporting task in the background task manager.
+pub const USAGE_REPORTING_TASK: &str = "usage_reporting";
+/// Header carrying the signature of a report, see [`usage_report_signature`].
+pub const USAGE_REPORT_SIGNATURE_HEADER: &str = "X-Erato-Signature";
+/// Header carrying the Unix timestamp, in seconds, the report was signed at.
+pub const USAGE_REPORT_TIMESTAMP_HEADER: &str = "X-Erato-Timestamp";
+/// Header carrying the random ID of the installation the report was signed with.
+pub const USAGE_REPORT_INSTALLATION_ID_HEADER: &str = "X-Erato-Installation-Id";
+/// Version of the format of [`UsageReport`], increased with every change of its fields.
+pub const USAGE_REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// How often the reporting task checks whether a report is due.
+const REPORT_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Number of attempts to send a report, before it is given up until the next check.
+const SEND_ATTEMPTS: u32 = 3;
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+/// Messages created within this number of days are counted as recent.
+const RECENT_MESSAGES_DAYS: i64 = 30;
+
+/// An anonymous report of aggregate usage numbers.
+///
+/// Only add fields that are enums or bucketed numbers, and increase
+/// [`USAGE_REPORT_SCHEMA_VERSION`] when changing them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct UsageReport {
+    /// Version of the format of the report
+    pub schema_version: u32,
+    /// Version of Erato
+    pub version: SoftwareVersion,
+    /// Features that are enabled
+    pub features: Vec<UsageFeature>,
+    /// Number of users
+    pub users: CountBucket,
+    /// Number of chats
+    pub chats: CountBucket,
+    /// Average number of chats per user
+    pub average_chats_per_user: CountBucket,
+    /// Number of messages of the last 30 days, including answers
+    pub recent_messages: CountBucket,
+    /// Share of the answers of the last 30 days that called a tool
+    pub tool_call_rate: RateBucket,
+    /// Size of the database
+    pub database_size: DatabaseSizeBucket,
+}
+
+/// A version of Erato.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct SoftwareVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl SoftwareVersion {
+    /// The version of this build.
+    pub fn current() -> Self {
+        let mut parts = env!("CARGO_PKG_VERSION")
+            .split(['.', '-', '+'])
+            .map(|part| part.parse().unwrap_or(0));
+        Self {
+            major: parts.next().unwrap_or(0),
+            minor: parts.next().unwrap_or(0),
+            patch: parts.next().unwrap_or(0),
+        }
+    }
+}
+
+/// A feature that can be reported as enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageFeature {
+    Assistants,
+    AssistantHub,
+    AudioDictation,
+    AudioTranscription,
+    Budget,
+    ChatSharing,
+    ClientTools,
+    CompatApi,
+    Facets,
+    McpServers,
+    Moderation,
+    Organizations,
+    OutputTransformers,
+    PromptOptimizer,
+    StarterPrompts,
+    UserPreferences,
+    WebsocketStreaming,
+}
+
+impl UsageFeature {
+    /// All features, in the order they are reported in.
+    pub const ALL: [UsageFeature; 17] = [
+        UsageFeature::Assistants,
+        UsageFeature::AssistantHub,
+        UsageFeature::AudioDictation,
+        UsageFeature::AudioTranscription,
+        UsageFeature::Budget,
+        UsageFeature::ChatSharing,
+        UsageFeature::ClientTools,
+        UsageFeature::CompatApi,
+        UsageFeature::Facets,
+        UsageFeature::McpServers,
+        UsageFeature::Moderation,
+        UsageFeature::Organizations,
+        UsageFeature::OutputTransformers,
+        UsageFeature::PromptOptimizer,
+        UsageFeature::StarterPrompts,
+        UsageFeature::UserPreferences,
+        UsageFeature::WebsocketStreaming,
+    ];
+
+    /// Whether the feature is enabled, taking runtime overrides of feature flags into account.
+    pub fn is_enabled(&self, app_state: &AppState) -> bool {
+        let config = &app_state.config;
+        match self {
+            UsageFeature::Assistants => config.assistants.enabled,
+            UsageFeature::AssistantHub => config.assistant_hub.enabled,
+            UsageFeature::AudioDictation => config.audio_dictation.enabled,
+            UsageFeature::AudioTranscription => config.audio_transcription.enabled,
+            UsageFeature::Budget => config.budget.enabled,
+            UsageFeature::ChatSharing => config.chat_sharing.enabled,
+            UsageFeature::ClientTools => !config.client_tools.tools.is_empty(),
+            UsageFeature::CompatApi => config.compat_api.enabled,
+            UsageFeature::Facets => !config.experimental_facets.facets.is_empty(),
+            UsageFeature::McpServers => !config.mcp_servers.is_empty(),
+            UsageFeature::Moderation => config.moderation.enabled,
+            UsageFeature::Organizations => !config.organizations.is_empty(),
+            UsageFeature::OutputTransformers => !config.chat.output_transformers.is_empty(),
+            UsageFeature::PromptOptimizer => {
+                app_state.feature_enabled(FeatureFlag::PromptOptimizer)
+            }
+            UsageFeature::StarterPrompts => app_state.feature_enabled(FeatureFlag::StarterPrompts),
+            UsageFeature::UserPreferences => config.user_preferences.enabled,
+            UsageFeature::WebsocketStreaming => config.websocket_streaming.enabled,
+        }
+    }
+}
+
+/// A count, bucketed by its order of magnitude.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub enum CountBucket {
+    #[serde(rename = "0")]
+    Zero,
+    #[serde(rename = "1-9")]
+    Ones,
+    #[serde(rename = "10-99")]
+    Tens,
+    #[serde(rename = "100-999")]
+    Hundreds,
+    #[serde(rename = "1000-9999")]
+    Thousands,
+    #[serde(rename = "10000-99999")]
+    TensOfThousands,
+    #[serde(rename = "100000+")]
+    HundredsOfThousandsOrMore,
+}
+
+impl CountBucket {
+    pub fn of(count: u64) -> Self {
+        match count {
+            0 => CountBucket::Zero,
+            1..=9 => CountBucket::Ones,
+            10..=99 => CountBucket::Tens,
+            100..=999 => CountBucket::Hundreds,
+            1_000..=9_999 => CountBucket::Thousands,
+            10_000..=99_999 => CountBucket::TensOfThousands,
+            _ => CountBucket::HundredsOfThousandsOrMore,
+        }
+    }
+}
+
+/// A share of a total, bucketed into ranges of percentages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub enum RateBucket {
+    #[serde(rename = "0%")]
+    None,
+    #[serde(rename = "1-9%")]
+    VeryLow,
+    #[serde(rename = "10-24%")]
+    Low,
+    #[serde(rename = "25-49%")]
+    Medium,
+    #[serde(rename = "50-100%")]
+    High,
+}
+
+impl RateBucket {
+    /// The bucket of `part` out of `total`. Any non-zero part is at least `1-9%`.
+    pub fn of(part: u64, total: u64) -> Self {
+        if part == 0 || total == 0 {
+            return RateBucket::None;
+        }
+        match part.min(total) * 100 / total {
+            0..=9 => RateBucket::VeryLow,
+            10..=24 => RateBucket::Low,
+            25..=49 => RateBucket::Medium,
+            _ => RateBucket::High,
+        }
+    }
+}
+
+/// The size of the database, bucketed into ranges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub enum DatabaseSizeBucket {
+    #[serde(rename = "<100MB")]
+    Tiny,
+    #[serde(rename = "100MB-1GB")]
+    Small,
+    #[serde(rename = "1-10GB")]
+    Medium,
+    #[serde(rename = "10-100GB")]
+    Large,
+    #[serde(rename = ">100GB")]
+    Huge,
+}
+
+impl DatabaseSizeBucket {
+    pub fn of(bytes: u64) -> Self {
+        const MB: u64 = 1_000_000;
+        const GB: u64 = 1_000 * MB;
+        match bytes {
+            bytes if bytes < 100 * MB => DatabaseSizeBucket::Tiny,
+            bytes if bytes < GB => DatabaseSizeBucket::Small,
+            bytes if bytes < 10 * GB => DatabaseSizeBucket::Medium,
+            bytes if bytes < 100 * GB => DatabaseSizeBucket::Large,
+            _ => DatabaseSizeBucket::Huge,
+        }
+    }
+}
+
+/// Assemble the report of the current usage.
+pub async fn build_usage_report(app_state: &AppState) -> Result<UsageReport, Report> {
+    let db = &app_state.db;
+    let users = Users::find().count(db).await?;
+    let chats = Chats::find()
+        .filter(chats::Column::Provisional.eq(false))
+        .count(db)
+        .await?;
+    let recent_since = Utc::now() - TimeDelta::days(RECENT_MESSAGES_DAYS);
+    let recent_messages = Messages::find()
+        .filter(messages::Column::CreatedAt.gte(recent_since))
+        .count(db)
+        .await?;
+
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_USAGE_REPORT_COUNTS,
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE m.raw_message->>'role' = 'assistant') AS answers,
+            COUNT(*) FILTER (
+                WHERE m.raw_message->>'role' = 'assistant'
+                AND m.raw_message->'content' @> '[{"content_type": "tool_use"}]'::jsonb
+            ) AS tool_call_answers,
+            pg_database_size(current_database()) AS database_size_bytes
+        FROM messages m
+        WHERE m.created_at >= $1
+        "#,
+        vec![recent_since.into()],
+    );
+    let row = db
+        .query_one_raw(statement)
+        .await?
+        .ok_or_else(|| eyre!("Usage report counts returned no row"))?;
+    let answers: i64 = row.try_get("", "answers")?;
+    let tool_call_answers: i64 = row.try_get("", "tool_call_answers")?;
+    let database_size_bytes: i64 = row.try_get("", "database_size_bytes")?;
+
+    Ok(UsageReport {
+        schema_version: USAGE_REPORT_SCHEMA_VERSION,
+        version: SoftwareVersion::current(),
+        features: UsageFeature::ALL
+            .into_iter()
+            .filter(|feature| feature.is_enabled(app_state))
+            .collect(),
+        users: CountBucket::of(users),
+        chats: CountBucket::of(chats),
+        average_chats_per_user: CountBucket::of(chats.checked_div(users).unwrap_or(0)),
+        recent_messages: CountBucket::of(recent_messages),
+        tool_call_rate: RateBucket::of(tool_call_answers.max(0) as u64, answers.max(0) as u64),
+        database_size: DatabaseSizeBucket::of(database_size_bytes.max(0) as u64),
+    })
+}
+
+/// The random ID of the installation, which is created if it doesn't exist yet.
+pub async fn get_or_create_installation_id(db: &DatabaseConnection) -> Result<Uuid, Report> {
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_CREATE_USAGE_REPORTING_INSTALLATION,
+        r#"
+        INSERT INTO usage_reporting_installation DEFAULT VALUES
+        ON CONFLICT (singleton) DO UPDATE SET singleton = EXCLUDED.singleton
+        RETURNING id
+        "#,
+        vec![],
+    );
+    let row = db
+        .query_one_raw(statement)
+        .await?
+        .ok_or_else(|| eyre!("Creating the installation ID returned no row"))?;
+    Ok(row.try_get("", "id")?)
+}
+
+/// Delete the random ID of the installation, if there is one.
+///
+/// Returns whether an ID was deleted.
+pub async fn delete_installation_id(db: &DatabaseConnection) -> Result<bool, Report> {
+    let result = UsageReportingInstallation::delete_many().exec(db).await?;
+    Ok(result.rows_affected > 0)
+}
+
+/// Claim the report of the current interval, so that only one backend instance sends it.
+///
+/// Returns the ID of the installation if a report is due, and marks it as sent.
+async fn claim_due_report(
+    db: &DatabaseConnection,
+    interval_hours: u64,
+) -> Result<Option<Uuid>, Report> {
+    let statement = named_statement_from_sql_and_values(
+        sea_orm::DatabaseBackend::Postgres,
+        POSTGRES_QUERY_CLAIM_USAGE_REPORT,
+        r#"
+        UPDATE usage_reporting_installation
+        SET last_reported_at = now()
+        WHERE last_reported_at IS NULL
+           OR last_reported_at <= now() - make_interval(hours => $1)
+        RETURNING id
+        "#,
+        vec![(interval_hours.min(i32::MAX as u64) as i32).into()],
+    );
+    db.query_one_raw(statement)
+        .await?
+        .map(|row| row.try_get::<Uuid>("", "id"))
+        .transpose()
+        .map_err(Into::into)
+}
+
+/// Signature of a report: `sha256=` followed by the hex-encoded HMAC-SHA256 of
+/// `<timestamp>.<body>`, keyed with the random ID of the installation.
+pub fn usage_report_signature(installation_id: &Uuid, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(installation_id.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("{timestamp}.").as_bytes());
+    mac.update(body);
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("sha256={signature}")
+}
+
+/// Send a report to the configured endpoint, if one is due.
+///
+/// Returns whether a report was sent. A report that couldn't be sent is retried at the next
+/// check.
+pub async fn send_usage_report_if_due(app_state: &AppState) -> Result<bool, Report> {
+    let config = &app_state.config.telemetry.usage_reporting;
+    let endpoint = config
+        .endpoint
+        .as_deref()
+        .ok_or_else(|| eyre!("`telemetry.usage_reporting.endpoint` is not set"))?;
+    get_or_create_installation_id(&app_state.db).await?;
+    let Some(installation_id) = claim_due_report(&app_state.db, config.interval_hours).await?
+    else {
+        return Ok(false);
+    };
+
+    let result = match build_usage_report(app_state).await {
+        Ok(report) => send_usage_report(endpoint, &installation_id, &report).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        UsageReportingInstallation::update_many()
+            .set(usage_reporting_installation::ActiveModel {
+                last_reported_at: ActiveValue::Set(None),
+                ..Default::default()
+            })
+            .filter(usage_reporting_installation::Column::Id.eq(installation_id))
+            .exec(&app_state.db)
+            .await?;
+        return Err(err);
+    }
+    Ok(true)
+}
+
+async fn send_usage_report(
+    endpoint: &str,
+    installation_id: &Uuid,
+    report: &UsageReport,
+) -> Result<(), Report> {
+    let body = serde_json::to_vec(report).wrap_err("Failed to serialize usage report")?;
+    let client = reqwest::Client::new();
+    let mut attempt = 1;
+    loop {
+        let timestamp = Utc::now().timestamp();
+        let result = client
+            .post(endpoint)
+            .timeout(SEND_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(USAGE_REPORT_INSTALLATION_ID_HEADER, installation_id.to_string())
+            .header(USAGE_REPORT_TIMESTAMP_HEADER, timestamp.to_string())
+            .header(
+                USAGE_REPORT_SIGNATURE_HEADER,
+                usage_report_signature(installation_id, timestamp, &body),
+            )
+            .body(body.clone())
+            .send()
+            .await
+            .wrap_err("Failed to reach the usage reporting endpoint")
+            .and_then(|response| match response.status() {
+                status if status.is_success() => Ok(()),
+                status => Err(eyre!("Usage reporting endpoint responded with status {status}")),
+            });
+        match result {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt >= SEND_ATTEMPTS => return Err(err),
+            Err(err) => {
+                tracing::debug!(attempt, error = %err, "Failed to send usage report, retrying");
+                tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Start sending reports if usage reporting is enabled, or delete the ID of the installation if
+/// it is disabled.
+///
+/// Returns whether the reporting task was started.
+pub async fn start_usage_reporting(app_state: &AppState) -> bool {
+    if !app_state.config.telemetry.usage_reporting.enabled {
+        match delete_installation_id(&app_state.db).await {
+            Ok(true) => tracing::info!("Usage reporting is disabled, deleted the installation ID"),
+            Ok(false) => {}
+            Err(err) => {
+                tracing::warn!(error = %err, "Failed to delete the installation ID");
+            }
+        }
+        return false;
+    }
+    let task_state = app_state.clone();
+    app_state
+        .background_tasks
+        .start_maintenance_task(USAGE_REPORTING_TASK, async move {
+            let mut interval = tokio::time::interval(REPORT_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                match send_usage_report_if_due(&task_state).await {
+                    Ok(true) => tracing::info!("Sent usage report"),
+                    Ok(false) => {}
+                    Err(err) => tracing::warn!(error = %err, "Failed to send usage report"),
+                }
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_are_bucketed_by_magnitude() {
+        assert_eq!(CountBucket::of(0), CountBucket::Zero);
+        assert_eq!(CountBucket::of(9), CountBucket::Ones);
+        assert_eq!(CountBucket::of(10), CountBucket::Tens);
+        assert_eq!(CountBucket::of(99_999), CountBucket::TensOfThousands);
+        assert_eq!(CountBucket::of(5_000_000), CountBucket::HundredsOfThousandsOrMore);
+    }
+
+    #[test]
+    fn rates_are_bucketed() {
+        assert_eq!(RateBucket::of(0, 10), RateBucket::None);
+        assert_eq!(RateBucket::of(1, 0), RateBucket::None);
+        assert_eq!(RateBucket::of(1, 1000), RateBucket::VeryLow);
+        assert_eq!(RateBucket::of(1, 10), RateBucket::Low);
+        assert_eq!(RateBucket::of(1, 2), RateBucket::High);
+    }
+
+    #[test]
+    fn signature_is_keyed_with_installation_id() {
+        let installation_id = Uuid::new_v4();
+        let signature = usage_report_signature(&installation_id, 1700000000, b"{}");
+
+        assert!(signature.starts_with("sha256="));
+        assert_ne!(
+            signature,
+            usage_report_signature(&Uuid::new_v4(), 1700000000, b"{}")
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Per-user notification channel for events across a user's sessions.
+//!
+//! Lets every open client of a user learn about generations and chat changes that
+//! were triggered elsewhere, e.g. on another device, without polling. Delivery is
+//! best-effort: events are only sent to currently connected clients and are not
+//! persisted.
+
+use serde::Serialize;
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+/// Maximum number of concurrent event connections per user.
+pub const MAX_CONNECTIONS_PER_USER: usize = 10;
+
+/// Number of events buffered per user before slow connections start missing events.
+const EVENT_BUFFER_SIZE: usize = 64;
+
+/// An event on the event stream of a user.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UserEvent {
+    /// A generation started in one of the user's chats.
+    GenerationStarted { chat_id: Uuid, message_id: Uuid },
+    /// A generation finished successfully.
+    GenerationCompleted {
+        chat_id: Uuid,
+        /// The generated assistant message. Not set if the generation ended before
+        /// the message was created.
+        message_id: Option<Uuid>,
+    },
+    /// A generation failed.
+    GenerationFailed {
+        chat_id: Uuid,
+        /// The generated assistant message. Not set if the generation failed before
+        /// the message was created.
+        message_id: Option<Uuid>,
+    },
+    /// A chat was created.
+    ChatCreated { chat_id: Uuid },
+    /// A chat was archived.
+    ChatArchived { chat_id: Uuid },
+}
+
+impl UserEvent {
+    /// Name of the event, as used for the `type` field and the SSE event name.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            Self::GenerationStarted { .. } => "generation_started",
+            Self::GenerationCompleted { .. } => "generation_completed",
+            Self::GenerationFailed { .. } => "generation_failed",
+            Self::ChatCreated { .. } => "chat_created",
+            Self::ChatArchived { .. } => "chat_archived",
+        }
+    }
+}
+
+struct UserChannel {
+    sender: broadcast::Sender<UserEvent>,
+    connections: usize,
+}
+
+/// Registry of the event channels of all currently connected users.
+#[derive(Clone, Default)]
+pub struct UserEventRegistry {
+    channels: Arc<Mutex<HashMap<String, UserChannel>>>,
+}
+
+impl std::fmt::Debug for UserEventRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let connected_users = self
+            .channels
+            .lock()
+            .map(|channels| channels.len())
+            .unwrap_or_default();
+        f.debug_struct("UserEventRegistry")
+            .field("connected_users", &connected_users)
+            .finish()
+    }
+}
+
+impl UserEventRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a new event connection for a user, returning a receiver for the user's
+    /// events.
+    ///
+    /// Returns `None` if the user already has [`MAX_CONNECTIONS_PER_USER`] connections.
+    /// The connection is released when the returned [`UserEventConnection`] is dropped.
+    pub fn subscribe(
+        &self,
+        user_id: &str,
+    ) -> Option<(broadcast::Receiver<UserEvent>, UserEventConnection)> {
+        let mut channels = self.channels.lock().expect("user event registry poisoned");
+        let channel = channels
+            .entry(user_id.to_string())
+            .or_insert_with(|| UserChannel {
+                sender: broadcast::channel(EVENT_BUFFER_SIZE).0,
+                connections: 0,
+            });
+        if channel.connections >= MAX_CONNECTIONS_PER_USER {
+            return None;
+        }
+        channel.connections += 1;
+
+        let connection = UserEventConnection {
+            registry: self.clone(),
+            user_id: user_id.to_string(),
+        };
+        Some((channel.sender.subscribe(), connection))
+    }
+
+    /// Send an event to all open connections of a user. Does nothing if the user
+    /// has none.
+    pub fn publish(&self, user_id: &str, event: UserEvent) {
+        let channels = self.channels.lock().expect("user event registry poisoned");
+        if let Some(channel) = channels.get(user_id) {
+            // Only fails if all receivers were dropped in the meantime.
+            let _ = channel.sender.send(event);
+        }
+    }
+
+    /// Number of open connections of a user.
+    pub fn connection_count(&self, user_id: &str) -> usize {
+        let channels = self.channels.lock().expect("user event registry poisoned");
+        channels
+            .get(user_id)
+            .map_or(0, |channel| channel.connections)
+    }
+
+    fn release(&self, user_id: &str) {
+        let mut channels = self.channels.lock().expect("user event registry poisoned");
+        if let Some(channel) = channels.get_mut(user_id) {
+            channel.connections = channel.connections.saturating_sub(1);
+            if channel.connections == 0 {
+                channels.remove(user_id);
+            }
+        }
+    }
+}
+
+/// Counts towards the open connections of a user while it is alive.
+pub struct UserEventConnection {
+    registry: UserEventRegistry,
+    user_id: String,
+}
+
+impl Drop for UserEventConnection {
+    fn drop(&mut self) {
+        self.registry.release(&self.user_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn delivers_events_only_to_the_users_connections() {
+        let registry = UserEventRegistry::new();
+        let (mut first, _first_connection) = registry.subscribe("user-a").unwrap();
+        let (mut second, _second_connection) = registry.subscribe("user-a").unwrap();
+        let (mut other, _other_connection) = registry.subscribe("user-b").unwrap();
+
+        let event = UserEvent::ChatCreated {
+            chat_id: Uuid::new_v4(),
+        };
+        registry.publish("user-a", event.clone());
+
+        assert_eq!(first.recv().await.unwrap(), event);
+        assert_eq!(second.recv().await.unwrap(), event);
+        assert!(other.try_recv().is_err());
+    }
+
+    #[test]
+    fn caps_and_releases_connections() {
+        let registry = UserEventRegistry::new();
+        let subscriptions: Vec<_> = (0..MAX_CONNECTIONS_PER_USER)
+            .map(|_| registry.subscribe("user-a").unwrap())
+            .collect();
+        assert!(registry.subscribe("user-a").is_none());
+        assert!(registry.subscribe("user-b").is_some());
+
+        drop(subscriptions);
+        assert_eq!(registry.connection_count("user-a"), 0);
+        assert!(registry.subscribe("user-a").is_some());
+    }
+
+    #[test]
+    fn serializes_with_type_tag() {
+        let chat_id = Uuid::new_v4();
+        let event = UserEvent::GenerationFailed {
+            chat_id,
+            message_id: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "type": "generation_failed",
+                "chat_id": chat_id,
+                "message_id": null,
+            })
+        );
+        assert_eq!(event.event_type(), "generation_failed");
+    }
+}
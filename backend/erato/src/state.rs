@@ -1,20 +1,47 @@
 use crate::actors::manager::ActorManager;
-use crate::config::{AppConfig, ChatProviderConfig, PromptSourceSpecification, SummaryConfig};
+use crate::config::{
+    AppConfig, ChatProviderConfig, InputModality, MessageLanguageDetectionConfig,
+    PromptSourceSpecification, SummaryConfig,
+};
 use crate::policy::engine::PolicyEngine;
 use crate::policy::types::Subject;
 use crate::query_metrics::install_postgres_query_metrics;
 use crate::services::background_tasks::BackgroundTaskManager;
+use crate::services::chat_provider_groups::ChatProviderGroupBalancer;
+use crate::services::chat_provider_rate_limits::ChatProviderRateLimits;
+use crate::services::content_spillover::start_content_spillover_migration;
 use crate::services::desktop_sidecar_distribution::DesktopSidecarDistribution;
+use crate::services::ephemeral_chats::EphemeralChatStore;
+use crate::services::feature_flags::{
+    FeatureDecision, FeatureFlag, FeatureFlagStore, start_feature_flags_refresh,
+};
+use crate::services::file_pointer_migration::start_file_pointer_migration;
+use crate::services::file_processor::FileProcessingError;
 use crate::services::file_storage::{FileStorage, SHAREPOINT_PROVIDER_ID};
+use crate::services::file_synopsis::FileSynopsis;
 use crate::services::genai::GenAIClient;
+use crate::services::generation_cache::start_generation_cache_eviction;
+use crate::services::generation_input_archive::{
+    GenerationInputArchive, start_generation_input_archival,
+};
 use crate::services::langfuse::{LangfuseClient, LangfusePrompt};
+use crate::services::langfuse_dead_letters::start_langfuse_dead_letter_retry;
 use crate::services::mcp_manager::McpServers;
+use crate::services::model_selection::{
+    ModelCandidate, ModelHealth, ModelRecommendation, ModelRequirements, rank_models,
+};
+use crate::services::policy_invalidations::start_policy_invalidation_listener;
+use crate::services::provider_capture::ProviderCaptureStore;
+use crate::services::scheduled_messages::start_scheduled_message_scheduler;
 use crate::services::template_rendering::consumers::{
     chat_provider_headers::ChatProviderHeadersRenderer, system_prompt::SystemPromptRenderer,
 };
 use crate::services::template_rendering::contexts::{
     chat_provider_headers::ChatProviderHeadersContext, system_prompt::SystemPromptContext,
 };
+use crate::services::thread_integrity_scan::start_thread_integrity_scan;
+use crate::services::usage_reporting::start_usage_reporting;
+use crate::services::user_events::UserEventRegistry;
 use aes_gcm_siv::aead::{Aead, KeyInit, OsRng, rand_core::RngCore};
 use aes_gcm_siv::{Aes256GcmSiv, Nonce};
 use base64::{Engine as _, engine::general_purpose::STANDARD};
@@ -22,6 +49,7 @@ use eyre::{OptionExt, Report};
 use genai::adapter::AdapterKind;
 use genai::resolver::{AuthData, Endpoint, ServiceTargetResolver};
 use genai::{Client as GenaiClient, ModelIden, ServiceTarget};
+use ipnet::IpNet;
 use moka::future::Cache;
 use reqwest;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
@@ -31,11 +59,14 @@ use std::collections::HashMap;
 use std::hash::Hash;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
 use std::time::{Duration, Instant};
 use tokio::sync::{RwLock, Semaphore};
 use tracing::instrument;
 
 const ENCRYPTED_VALUE_PREFIX: &str = "enc-v1";
+/// Maximum number of files whose processing failure is cached.
+pub const FILE_PROCESSING_ERRORS_CACHE_MAX_ENTRIES: u64 = 10_000;
 const DEFAULT_SUMMARY_SYSTEM_PROMPT: &str = "Generate a summary for the topic of the following chat, based on the first message to the chat. The summary should be a short single sentence description like e.g. `Regex Search-and-Replace with Ripgrep` or `Explain a customer support handoff`. Only return that sentence and nothing else.";
 
 /// Wrapper around PolicyEngine that tracks when it was last rebuilt
@@ -44,6 +75,10 @@ const DEFAULT_SUMMARY_SYSTEM_PROMPT: &str = "Generate a summary for the topic of
 pub struct GlobalPolicyEngine {
     engine: PolicyEngine,
     last_rebuild_time: Arc<RwLock<Option<Instant>>>,
+    /// Highest ID of the `policy_invalidations` outbox seen by this instance
+    invalidation_high_water_mark: Arc<AtomicI64>,
+    /// Whether invalidations from the outbox were seen since the policy data was last invalidated
+    outbox_invalidation_pending: Arc<AtomicBool>,
 }
 
 impl Default for GlobalPolicyEngine {
@@ -57,9 +92,37 @@ impl GlobalPolicyEngine {
         Self {
             engine: PolicyEngine::new(),
             last_rebuild_time: Arc::new(RwLock::new(None)),
+            invalidation_high_water_mark: Arc::new(AtomicI64::new(0)),
+            outbox_invalidation_pending: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Highest ID of the `policy_invalidations` outbox seen by this instance
+    pub fn invalidation_high_water_mark(&self) -> i64 {
+        self.invalidation_high_water_mark.load(Ordering::Acquire)
+    }
+
+    /// Start tracking the outbox at the given ID, without invalidating the policy data for the
+    /// invalidations up to it.
+    pub fn init_invalidation_high_water_mark(&self, id: i64) {
+        self.invalidation_high_water_mark
+            .fetch_max(id, Ordering::AcqRel);
+    }
+
+    /// Record an invalidation from the `policy_invalidations` outbox, which is applied by the
+    /// next rebuild check.
+    ///
+    /// Notified invalidations are applied even if they are below the high-water mark, as
+    /// transactions may commit out of the order of their IDs. Returns whether the invalidation
+    /// raised the high-water mark.
+    pub fn observe_invalidation(&self, id: i64) -> bool {
+        self.outbox_invalidation_pending
+            .store(true, Ordering::Release);
+        self.invalidation_high_water_mark
+            .fetch_max(id, Ordering::AcqRel)
+            < id
+    }
+
     /// Rebuild data if needed based on time threshold or explicit invalidation
     /// Returns a request-scoped PolicyEngine clone for use in the request handler
     #[instrument(skip_all)]
@@ -88,6 +151,7 @@ impl GlobalPolicyEngine {
             tracing::debug!("Policy data due for time-based refresh");
             self.engine.invalidate_data().await;
         }
+        self.apply_outbox_invalidations().await;
 
         self.engine.rebuild_data_if_needed(db, config).await?;
 
@@ -105,6 +169,7 @@ impl GlobalPolicyEngine {
         db: &DatabaseConnection,
         config: &AppConfig,
     ) -> Result<(), Report> {
+        self.apply_outbox_invalidations().await;
         self.engine.rebuild_data_if_needed(db, config).await
     }
 
@@ -112,6 +177,21 @@ impl GlobalPolicyEngine {
     pub async fn invalidate_data(&self) {
         self.engine.invalidate_data().await;
     }
+
+    /// Invalidate the policy data if the outbox has seen invalidations, e.g. of changes made by
+    /// other instances, since the last check.
+    async fn apply_outbox_invalidations(&self) {
+        if self
+            .outbox_invalidation_pending
+            .swap(false, Ordering::AcqRel)
+        {
+            tracing::debug!(
+                high_water_mark = self.invalidation_high_water_mark(),
+                "Policy data invalidated by the outbox"
+            );
+            self.engine.invalidate_data().await;
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -121,10 +201,25 @@ pub struct AppState {
     pub file_storage_providers: HashMap<String, FileStorage>,
     pub mcp_servers: Arc<McpServers>,
     pub config: AppConfig,
+    /// Networks of the proxies that may set the identity headers in the `trusted_headers` auth
+    /// mode, parsed from `auth.trusted_headers.trusted_proxies`.
+    pub trusted_proxy_networks: Arc<[IpNet]>,
     pub actor_manager: ActorManager,
     pub langfuse_client: LangfuseClient,
     pub global_policy_engine: GlobalPolicyEngine,
     pub background_tasks: BackgroundTaskManager,
+    /// Per-user channels for the `/me/events` stream.
+    pub user_events: UserEventRegistry,
+    /// Runtime overrides of feature toggles from the config.
+    pub feature_flags: FeatureFlagStore,
+    /// Selects the members of chat provider groups, based on their recent outcomes.
+    pub chat_provider_groups: ChatProviderGroupBalancer,
+    /// Cooldowns of chat providers that recently responded with a rate limit.
+    pub chat_provider_rate_limits: ChatProviderRateLimits,
+    /// Ephemeral chats of this instance, which are never written to the database.
+    pub ephemeral_chats: EphemeralChatStore,
+    /// Restores the generation inputs of messages that were archived.
+    pub generation_input_archive: GenerationInputArchive,
     pub system_prompt_renderer: SystemPromptRenderer,
     pub desktop_sidecar_distribution: Option<Arc<DesktopSidecarDistribution>>,
     /// Optional inference client used instead of provider-specific clients built from config.
@@ -132,8 +227,10 @@ pub struct AppState {
     pub genai_client_override: Option<Arc<dyn GenAIClient>>,
     /// Cache mapping file identity -> raw file bytes (for both text and images)
     pub file_bytes_cache: Cache<FileCacheKey, Vec<u8>>,
-    /// Cache mapping file identity -> parsed file contents (text files only)
-    pub file_contents_cache: Cache<FileCacheKey, String>,
+    /// Cache mapping file identity -> parsed file contents and synopsis (text files only)
+    pub file_contents_cache: Cache<FileCacheKey, ParsedFileContents>,
+    /// Cache mapping file ID -> deterministic failure to parse the file (text files only)
+    pub file_processing_errors_cache: Cache<Uuid, FailedFileContents>,
     /// Cache mapping file_contents -> token count
     pub token_count_cache: Cache<String, usize>,
     /// Global limiter for file processing work on cache misses.
@@ -150,6 +247,8 @@ pub struct AvailableModel {
     pub model_display_name: String,
     pub model_description: Option<String>,
     pub model_icon: Option<String>,
+    pub max_input_files: Option<usize>,
+    pub supported_input_modalities: Vec<InputModality>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
@@ -158,6 +257,35 @@ pub struct FileCacheKey {
     pub etag: Option<String>,
 }
 
+/// Parsed text of a file, together with the synopsis computed from it.
+#[derive(Clone, Debug)]
+pub struct ParsedFileContents {
+    pub text: String,
+    pub synopsis: FileSynopsis,
+}
+
+impl ParsedFileContents {
+    /// Approximate size in bytes, used to weigh cache entries.
+    pub fn size(&self) -> usize {
+        self.text.len() + self.synopsis.size()
+    }
+}
+
+/// Failure to parse a file that happens again for the same version of the file.
+#[derive(Clone, Debug)]
+pub struct FailedFileContents {
+    /// ETag of the version of the file the failure was seen for.
+    pub etag: Option<String>,
+    pub error: FileProcessingError,
+}
+
+impl FailedFileContents {
+    /// The error, if it was seen for the version of the file of the cache key.
+    pub fn error_for(&self, cache_key: &FileCacheKey) -> Option<&FileProcessingError> {
+        (self.etag == cache_key.etag).then_some(&self.error)
+    }
+}
+
 impl std::fmt::Debug for AppState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AppState")
@@ -172,10 +300,17 @@ impl std::fmt::Debug for AppState {
             )
             .field("mcp_servers", &self.mcp_servers)
             .field("config", &self.config)
+            .field("trusted_proxy_networks", &self.trusted_proxy_networks)
             .field("actor_manager", &self.actor_manager)
             .field("langfuse_client", &self.langfuse_client)
             .field("global_policy_engine", &self.global_policy_engine)
             .field("background_tasks", &self.background_tasks)
+            .field("user_events", &self.user_events)
+            .field("feature_flags", &self.feature_flags)
+            .field("chat_provider_groups", &self.chat_provider_groups)
+            .field("chat_provider_rate_limits", &self.chat_provider_rate_limits)
+            .field("ephemeral_chats", &self.ephemeral_chats)
+            .field("generation_input_archive", &self.generation_input_archive)
             .field("system_prompt_renderer", &self.system_prompt_renderer)
             .field(
                 "desktop_sidecar_distribution",
@@ -187,6 +322,7 @@ impl std::fmt::Debug for AppState {
             )
             .field("file_bytes_cache", &"<Cache>")
             .field("file_contents_cache", &"<Cache>")
+            .field("file_processing_errors_cache", &"<Cache>")
             .field("token_count_cache", &"<Cache>")
             .field("file_processing_semaphore", &"<Semaphore>")
             .field("file_processing_pipeline_semaphore", &"<Semaphore>")
@@ -220,6 +356,9 @@ impl AppState {
             None
         };
 
+        // Parsed once, as every request of the trusted_headers auth mode is checked against them
+        let trusted_proxy_networks = config.auth.trusted_proxy_networks()?.into();
+
         let db_connect_options = ConnectOptions::new(config.database_url.expose_secret());
         // TODO: Change level to Debug, but that also seems to deactivate some other logging (e.g. Errors during request?)
         // db_connect_options.sqlx_logging_level(LevelFilter::Debug);
@@ -234,18 +373,25 @@ impl AppState {
         // Failures are logged but not fatal
         mcp_servers.check_connectivity().await;
 
-        let actor_manager = ActorManager::new(db.clone(), config.clone()).await;
+        let actor_manager = ActorManager::new(&config);
         let langfuse_client = LangfuseClient::from_config(
             &config.integrations.langfuse,
             Some(config.environment.clone()),
-        )?;
+        )?
+        .with_dead_letter_queue(db.clone());
 
         // Initialize the global policy engine
         let global_policy_engine = GlobalPolicyEngine::new();
 
         // Initialize the background task manager
+        let user_events = UserEventRegistry::new();
         let background_tasks =
-            BackgroundTaskManager::new(Some(db.clone()), config.generation_status.clone());
+            BackgroundTaskManager::new(Some(db.clone()), config.generation_status.clone())
+                .with_user_events(user_events.clone())
+                .with_generation_limit(
+                    config.chat.max_concurrent_generations,
+                    config.chat.queue_when_limited,
+                );
 
         // Initialize the system prompt renderer
         let system_prompt_renderer = SystemPromptRenderer::new();
@@ -262,14 +408,20 @@ impl AppState {
 
         // Initialize file contents cache with MB-based weigher
         let file_contents_cache = Cache::builder()
-            .weigher(|_key: &FileCacheKey, value: &String| -> u32 {
+            .weigher(|_key: &FileCacheKey, value: &ParsedFileContents| -> u32 {
                 // Weight by string byte length
-                value.len().try_into().unwrap_or(u32::MAX)
+                value.size().try_into().unwrap_or(u32::MAX)
             })
             .max_capacity(config.caches.file_contents_cache_mb * 1024 * 1024)
             .time_to_idle(Duration::from_hours(12))
             .build();
 
+        // Initialize the cache of files that fail to parse (bounded by entries, as they are small)
+        let file_processing_errors_cache = Cache::builder()
+            .max_capacity(FILE_PROCESSING_ERRORS_CACHE_MAX_ENTRIES)
+            .time_to_idle(Duration::from_hours(12))
+            .build();
+
         // Initialize token count cache with MB-based weigher
         let token_count_cache = Cache::builder()
             .weigher(|key: &String, _value: &usize| -> u32 {
@@ -290,26 +442,132 @@ impl AppState {
             &config.file_processor.processor,
         )?;
 
-        Ok(Self {
+        let generation_input_archive = GenerationInputArchive::new(
+            file_storage_providers.clone(),
+            config.admin.generation_input_archival.cache_entries,
+        );
+
+        let mut app_state = Self {
             db,
             default_file_storage_provider: config.default_file_storage_provider.clone(),
             file_storage_providers,
             mcp_servers,
             config,
+            trusted_proxy_networks,
             actor_manager,
             langfuse_client,
             global_policy_engine,
             background_tasks,
+            user_events,
+            feature_flags: FeatureFlagStore::new(),
+            chat_provider_groups: ChatProviderGroupBalancer::new(),
+            chat_provider_rate_limits: ChatProviderRateLimits::new(),
+            ephemeral_chats: EphemeralChatStore::new(),
+            generation_input_archive,
             system_prompt_renderer,
             desktop_sidecar_distribution,
             genai_client_override: None,
             file_bytes_cache,
             file_contents_cache,
+            file_processing_errors_cache,
             token_count_cache,
             file_processing_semaphore,
             file_processing_pipeline_semaphore,
             file_processor,
-        })
+        };
+        if let Some(store) = ProviderCaptureStore::from_app_state(&app_state) {
+            app_state.background_tasks = app_state.background_tasks.with_provider_capture_cleanup(
+                app_state.db.clone(),
+                store,
+                app_state
+                    .config
+                    .debug
+                    .capture_provider_traffic
+                    .retention_hours,
+            );
+        }
+        if app_state.config.admin.file_pointer_migration.run_on_startup {
+            start_file_pointer_migration(
+                &app_state.background_tasks,
+                app_state.db.clone(),
+                app_state.config.admin.file_pointer_migration.clone(),
+            );
+        }
+        if app_state
+            .config
+            .admin
+            .content_spillover_migration
+            .run_on_startup
+        {
+            start_content_spillover_migration(
+                &app_state.background_tasks,
+                app_state.db.clone(),
+                app_state.default_file_storage_provider().clone(),
+                app_state.config.chat.content_spillover.clone(),
+                app_state.config.admin.content_spillover_migration.clone(),
+            );
+        }
+        if app_state
+            .config
+            .admin
+            .generation_input_archival
+            .run_on_startup
+        {
+            start_generation_input_archival(
+                &app_state.background_tasks,
+                app_state.db.clone(),
+                app_state.generation_input_archive.clone(),
+                app_state.default_file_storage_provider_id(),
+                app_state.config.admin.generation_input_archival.clone(),
+                false,
+            );
+        }
+        if let Err(err) = app_state.feature_flags.refresh(&app_state.db).await {
+            tracing::warn!(error = %err, "Failed to load feature flags");
+        }
+        start_feature_flags_refresh(&app_state);
+        start_langfuse_dead_letter_retry(&app_state);
+        start_scheduled_message_scheduler(&app_state);
+        start_generation_cache_eviction(&app_state);
+        start_thread_integrity_scan(&app_state);
+        start_usage_reporting(&app_state).await;
+        start_policy_invalidation_listener(&app_state);
+        ActorManager::startup(&app_state).await;
+
+        Ok(app_state)
+    }
+
+    /// Whether a feature is enabled, taking runtime overrides into account.
+    ///
+    /// Group overrides and percentage rollouts are ignored, see [`AppState::feature`] for checks
+    /// on behalf of a user.
+    pub fn feature_enabled(&self, flag: FeatureFlag) -> bool {
+        self.feature_flags.is_enabled(flag, &self.config)
+    }
+
+    /// Evaluate a feature flag for a user with the given groups.
+    pub fn feature(&self, key: &str, user_id: &str, groups: &[String]) -> FeatureDecision {
+        self.feature_flags
+            .evaluate(key, &self.config, user_id, groups)
+    }
+
+    /// The message language detection config, with `enabled` taking runtime
+    /// overrides into account.
+    pub fn message_language_detection_config(&self) -> MessageLanguageDetectionConfig {
+        MessageLanguageDetectionConfig {
+            enabled: self.feature_enabled(FeatureFlag::MessageLanguageDetection),
+            ..self.config.i18n.message_language_detection.clone()
+        }
+    }
+
+    /// Finish outstanding background work before the process exits.
+    ///
+    /// Waits for traces that are still being sent to Langfuse, and stores the ones that could
+    /// not be delivered for a retry.
+    pub async fn shutdown(&self) {
+        if let Err(error) = self.langfuse_client.flush().await {
+            tracing::warn!(%error, "Failed to flush Langfuse traces on shutdown");
+        }
     }
 
     pub fn encrypt(&self, value: &str) -> Result<String, Report> {
@@ -410,6 +668,7 @@ impl AppState {
         Ok(ChatProviderConfigWithId {
             chat_provider_id: chat_provider_id.to_string(),
             chat_provider_config: self.config.get_chat_provider(chat_provider_id).clone(),
+            chat_provider_group_id: None,
         })
     }
 
@@ -449,6 +708,7 @@ impl AppState {
         Ok(ChatProviderConfigWithId {
             chat_provider_id: chat_provider_id.to_string(),
             chat_provider_config: self.config.get_chat_provider(chat_provider_id).clone(),
+            chat_provider_group_id: None,
         })
     }
 
@@ -498,12 +758,18 @@ impl AppState {
         )?))
     }
 
+    /// Determines the chat provider for a chat completion of the user.
+    ///
+    /// Chat provider groups are resolved to the member that serves the generation. A requested
+    /// chat provider that is no longer configured falls back to `fallback_chat_provider`, or to
+    /// the available chat provider with the highest priority.
     pub async fn chat_provider_for_chatcompletion(
         &self,
         policy: &PolicyEngine,
         subject: &Subject,
         user_groups: &[String],
         requested_chat_provider: Option<&str>,
+        fallback_chat_provider: Option<&str>,
     ) -> Result<ChatProviderConfigWithId, Report> {
         let chat_provider_allowlist = self
             .determine_chat_provider_allowlist_for_user(policy, subject, user_groups)
@@ -512,16 +778,88 @@ impl AppState {
             .as_ref()
             .map(|list| list.iter().map(|s| s.as_str()).collect());
 
-        let chat_provider_id = self
-            .config
-            .determine_chat_provider(allowlist_refs.as_deref(), requested_chat_provider)?;
+        let mut chat_provider_id = self.config.determine_chat_provider_with_fallback(
+            allowlist_refs.as_deref(),
+            requested_chat_provider,
+            fallback_chat_provider,
+        )?;
+        // Without an explicitly requested model, fall back to the next provider in priority
+        // order while the preferred one is rate limited.
+        if requested_chat_provider.is_none()
+            && self.chat_provider_is_cooling_down(chat_provider_id)
+            && let Some(available_id) = self
+                .config
+                .available_chat_providers(allowlist_refs.as_deref())
+                .into_iter()
+                .find(|provider_id| !self.chat_provider_is_cooling_down(provider_id))
+        {
+            tracing::info!(
+                cooling_down_chat_provider_id = chat_provider_id,
+                chat_provider_id = available_id,
+                "Preferred chat provider is rate limited, using the next available chat provider"
+            );
+            chat_provider_id = available_id;
+        }
+
+        if let Some(member_id) = self.select_chat_provider_group_member(chat_provider_id, &[]) {
+            return Ok(ChatProviderConfigWithId {
+                chat_provider_config: self.config.get_chat_provider(&member_id).clone(),
+                chat_provider_id: member_id,
+                chat_provider_group_id: Some(chat_provider_id.to_string()),
+            });
+        }
 
         Ok(ChatProviderConfigWithId {
             chat_provider_id: chat_provider_id.to_string(),
             chat_provider_config: self.config.get_chat_provider(chat_provider_id).clone(),
+            chat_provider_group_id: None,
         })
     }
 
+    /// Selects the member of a chat provider group that serves the next generation, skipping
+    /// the members in `excluded`.
+    ///
+    /// Members that are cooling down after a rate limit are only selected if all other members
+    /// are cooling down as well.
+    ///
+    /// Returns `None` if `chat_provider_id` is not a group, or if no member is left.
+    pub fn select_chat_provider_group_member(
+        &self,
+        chat_provider_id: &str,
+        excluded: &[String],
+    ) -> Option<String> {
+        let group = self.config.chat_provider_group(chat_provider_id)?;
+        let excluded_or_cooling_down: Vec<String> = group
+            .members
+            .iter()
+            .filter(|member_id| {
+                excluded.contains(member_id)
+                    || self.chat_provider_rate_limits.is_cooling_down(member_id)
+            })
+            .cloned()
+            .collect();
+        self.chat_provider_groups
+            .select_member(chat_provider_id, group, &excluded_or_cooling_down)
+            .or_else(|| {
+                self.chat_provider_groups
+                    .select_member(chat_provider_id, group, excluded)
+            })
+    }
+
+    /// Whether a chat provider is cooling down after a rate limit, which for a chat provider
+    /// group means that all of its members are.
+    pub fn chat_provider_is_cooling_down(&self, chat_provider_id: &str) -> bool {
+        match self.config.chat_provider_group(chat_provider_id) {
+            Some(group) => group
+                .members
+                .iter()
+                .all(|member_id| self.chat_provider_rate_limits.is_cooling_down(member_id)),
+            None => self
+                .chat_provider_rate_limits
+                .is_cooling_down(chat_provider_id),
+        }
+    }
+
     /// Determines chat provider allowlist for a user based on their group memberships.
     /// Uses the model_permissions configuration to filter available chat providers.
     #[instrument(skip_all)]
@@ -532,15 +870,18 @@ impl AppState {
         user_groups: &[String],
     ) -> Result<Option<Vec<String>>, Report> {
         // Get all available chat provider IDs
-        let all_provider_ids: Vec<String> =
-            if let Some(chat_providers) = &self.config.chat_providers {
-                chat_providers.providers.keys().cloned().collect()
-            } else if self.config.chat_provider.is_some() {
-                // Fallback to single provider - use "default" as the provider ID for backward compatibility
-                vec!["default".to_string()]
-            } else {
-                vec![]
-            };
+        let all_provider_ids: Vec<String> = if self.config.chat_providers.is_some() {
+            self.config
+                .chat_provider_and_group_ids()
+                .into_iter()
+                .map(String::from)
+                .collect()
+        } else if self.config.chat_provider.is_some() {
+            // Fallback to single provider - use "default" as the provider ID for backward compatibility
+            vec!["default".to_string()]
+        } else {
+            vec![]
+        };
 
         let allowed_providers = policy
             .filter_authorized_chat_provider_ids(subject, user_groups, &all_provider_ids)
@@ -581,16 +922,85 @@ impl AppState {
             .into_iter()
             .map(|provider_id| {
                 let config = self.config.get_chat_provider(provider_id);
+                let model_display_name = self
+                    .config
+                    .chat_provider_group(provider_id)
+                    .and_then(|group| group.model_display_name.as_deref())
+                    .unwrap_or_else(|| config.model_display_name());
                 AvailableModel {
                     chat_provider_id: provider_id.to_string(),
-                    model_display_name: config.model_display_name().to_string(),
+                    model_display_name: model_display_name.to_string(),
                     model_description: config.model_description.clone(),
                     model_icon: config.model_icon.clone(),
+                    max_input_files: config.model_capabilities.max_input_files,
+                    supported_input_modalities: config.model_capabilities.input_modalities(),
                 }
             })
             .collect())
     }
 
+    /// Rank the models available to the user by how well they fit the requirements of a chat.
+    ///
+    /// `last_chat_provider_id` is the chat provider that generated the last answer of the chat,
+    /// if the recommendation is for an existing chat.
+    pub fn recommend_models(
+        &self,
+        available_models: &[AvailableModel],
+        user_groups: &[String],
+        requirements: &ModelRequirements,
+        last_chat_provider_id: Option<&str>,
+    ) -> Vec<ModelRecommendation> {
+        let group_defaults: Vec<&str> = self
+            .config
+            .chat_providers
+            .iter()
+            .flat_map(|chat_providers| {
+                user_groups
+                    .iter()
+                    .filter_map(|user_group| chat_providers.user_group_defaults.get(user_group))
+            })
+            .map(|provider_id| self.config.resolve_chat_provider_alias(provider_id))
+            .collect();
+
+        let candidates: Vec<ModelCandidate<'_>> = available_models
+            .iter()
+            .map(|model| {
+                let provider_id = model.chat_provider_id.as_str();
+                ModelCandidate {
+                    chat_provider_id: provider_id,
+                    model_capabilities: &self
+                        .config
+                        .get_chat_provider(provider_id)
+                        .model_capabilities,
+                    health: self.chat_provider_health(provider_id),
+                    group_default: group_defaults.contains(&provider_id),
+                    last_used: last_chat_provider_id == Some(provider_id),
+                }
+            })
+            .collect();
+        rank_models(&candidates, requirements)
+    }
+
+    /// The recent health of a chat provider. A chat provider group is as healthy as its
+    /// healthiest member, as generations are routed around the others.
+    fn chat_provider_health(&self, chat_provider_id: &str) -> ModelHealth {
+        let member_ids: Vec<&str> = match self.config.chat_provider_group(chat_provider_id) {
+            Some(group) => group.members.iter().map(String::as_str).collect(),
+            None => vec![chat_provider_id],
+        };
+        ModelHealth {
+            cooling_down: self.chat_provider_is_cooling_down(chat_provider_id),
+            error_rate: member_ids
+                .iter()
+                .map(|member_id| self.chat_provider_groups.error_rate(member_id))
+                .fold(f64::INFINITY, f64::min),
+            average_latency: member_ids
+                .iter()
+                .filter_map(|member_id| self.chat_provider_groups.average_latency(member_id))
+                .min(),
+        }
+    }
+
     pub fn default_file_storage_provider(&self) -> &FileStorage {
         if let Some(provider_id) = &self.default_file_storage_provider {
             self.file_storage_providers.get(provider_id).unwrap()
@@ -957,6 +1367,8 @@ fn extract_system_prompt_from_langfuse_prompt(prompt: &LangfusePrompt) -> Result
 pub struct ChatProviderConfigWithId {
     pub chat_provider_id: String,
     pub chat_provider_config: ChatProviderConfig,
+    /// The chat provider group that `chat_provider_id` was selected from, if any.
+    pub chat_provider_group_id: Option<String>,
 }
 
 pub fn default_endpoint(kind: AdapterKind) -> Endpoint {
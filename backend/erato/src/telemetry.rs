@@ -1,12 +1,14 @@
 use crate::config::{AppConfig, LoggingFormat};
 use eyre::Result;
 use opentelemetry::KeyValue;
-use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::trace::{SpanContext, TraceContextExt as _, TracerProvider as _};
 use opentelemetry_otlp::WithExportConfig;
 use opentelemetry_sdk::{Resource, trace::SdkTracerProvider};
 use opentelemetry_semantic_conventions::resource::SERVICE_NAME;
+use sea_orm::prelude::Uuid;
 use std::collections::HashMap;
 use std::str::FromStr;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
 use tracing_subscriber::filter::Directive;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
@@ -206,3 +208,92 @@ pub fn init_telemetry(config: &AppConfig) -> Result<TelemetryGuard> {
 
     Ok(TelemetryGuard)
 }
+
+/// OpenTelemetry identifiers of a span, used to correlate client-visible events (e.g. SSE events)
+/// and stored messages with the exported backend traces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceCorrelation {
+    /// Hex-encoded trace ID
+    pub trace_id: String,
+    /// Hex-encoded span ID
+    pub span_id: String,
+    sampled: bool,
+}
+
+impl TraceCorrelation {
+    /// Trace correlation of the given span.
+    ///
+    /// Returns `None` if the span is not recorded by an OpenTelemetry layer, e.g. because OTel is
+    /// not enabled.
+    pub fn of_span(span: &tracing::Span) -> Option<Self> {
+        let context = span.context();
+        let otel_span = context.span();
+        Self::from_span_context(otel_span.span_context())
+    }
+
+    /// Trace correlation of the current span.
+    pub fn current() -> Option<Self> {
+        Self::of_span(&tracing::Span::current())
+    }
+
+    fn from_span_context(span_context: &SpanContext) -> Option<Self> {
+        span_context.is_valid().then(|| Self {
+            trace_id: span_context.trace_id().to_string(),
+            span_id: span_context.span_id().to_string(),
+            sampled: span_context.is_sampled(),
+        })
+    }
+
+    /// The correlation as a W3C `traceparent` header value.
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            self.trace_id,
+            self.span_id,
+            u8::from(self.sampled)
+        )
+    }
+}
+
+/// Creates the span covering the generation of a single assistant message.
+///
+/// The span is a child of the current (request) span. As the generation runs in a background
+/// task that outlives the request, the request span is additionally linked explicitly, so the
+/// relation is kept even by backends that drop children of already finished spans.
+pub fn assistant_message_generation_span(chat_id: &Uuid) -> tracing::Span {
+    let request_span = tracing::Span::current();
+    let generation_span = tracing::info_span!("generate_assistant_message", chat_id = %chat_id);
+    let request_context = request_span.context();
+    let request_span_context = request_context.span().span_context().clone();
+    if request_span_context.is_valid() {
+        generation_span.add_link(request_span_context);
+    }
+    generation_span
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{SpanId, TraceFlags, TraceId, TraceState};
+
+    #[test]
+    fn traceparent_formats_w3c_header_value() {
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        );
+        let correlation = TraceCorrelation::from_span_context(&span_context).unwrap();
+        assert_eq!(
+            correlation.traceparent(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+
+        assert_eq!(
+            TraceCorrelation::from_span_context(&SpanContext::empty_context()),
+            None
+        );
+    }
+}
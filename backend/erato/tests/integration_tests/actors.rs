@@ -4,9 +4,13 @@ use crate::test_utils::{TEST_JWT_TOKEN, TestRequestAuthExt, setup_mock_llm_serve
 use crate::{MIGRATOR, test_app_state};
 use axum_test::TestServer;
 use chrono::{Duration, Utc};
-use erato::actors::cleanup_worker::cleanup_archived_chats;
-use erato::db::entity::chats;
+use erato::actors::cleanup_worker::{
+    cleanup_archived_chats, cleanup_expired_share_grants, cleanup_provisional_chats,
+    cleanup_read_notifications,
+};
 use erato::db::entity::{chat_file_uploads, file_uploads};
+use erato::db::entity::{chats, notifications, share_grants};
+use erato::models::notification::{GenerationFailedPayload, NotificationPayload};
 use sea_orm::{ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter};
 use serde_json::{Value, json};
 use sqlx::{Pool, Postgres};
@@ -169,6 +173,8 @@ async fn test_cleanup_logic_with_file_uploads(pool: Pool<Postgres>) {
         audio_transcription: ActiveValue::Set(None),
         created_at: ActiveValue::Set(Utc::now().into()),
         updated_at: ActiveValue::Set(Utc::now().into()),
+        organization_id: ActiveValue::Set(None),
+        storage_status: ActiveValue::Set("ok".to_string()),
     };
     file_upload1.insert(&app_state.db).await.unwrap();
 
@@ -182,6 +188,8 @@ async fn test_cleanup_logic_with_file_uploads(pool: Pool<Postgres>) {
         audio_transcription: ActiveValue::Set(None),
         created_at: ActiveValue::Set(Utc::now().into()),
         updated_at: ActiveValue::Set(Utc::now().into()),
+        organization_id: ActiveValue::Set(None),
+        storage_status: ActiveValue::Set("ok".to_string()),
     };
     file_upload2.insert(&app_state.db).await.unwrap();
 
@@ -264,3 +272,195 @@ async fn test_cleanup_logic_with_file_uploads(pool: Pool<Postgres>) {
         "File uploads should be preserved as orphaned records"
     );
 }
+
+/// Test the cleanup worker logic for expired share grants.
+///
+/// # Test Categories
+/// - `uses-db`
+///
+/// # Test Behavior
+/// Creates share grants that expired long ago, expired recently and don't expire,
+/// and verifies that only the long expired one is deleted by the cleanup worker.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_cleanup_expired_share_grants(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let policy = erato::policy::engine::PolicyEngine::new();
+
+    let owner =
+        erato::models::user::get_or_create_user(&app_state.db, "test-issuer", "owner", None)
+            .await
+            .unwrap();
+    let owner_subject = erato::policy::types::Subject::User(owner.id.to_string());
+    let assistant = erato::models::assistant::create_assistant(
+        &app_state.db,
+        &policy,
+        &owner_subject,
+        "Shared Assistant".to_string(),
+        None,
+        "Prompt".to_string(),
+        None,
+        None,
+        None,
+        false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
+    )
+    .await
+    .unwrap();
+
+    let mut grant_ids = Vec::new();
+    for grantee_subject in ["long-expired", "recently-expired", "not-expiring"] {
+        let grantee = erato::models::user::get_or_create_user(
+            &app_state.db,
+            "test-issuer",
+            grantee_subject,
+            None,
+        )
+        .await
+        .unwrap();
+        let grant = erato::models::share_grant::create_share_grant(
+            &app_state.db,
+            &policy,
+            &owner_subject,
+            "assistant".to_string(),
+            assistant.id.to_string(),
+            "user".to_string(),
+            "id".to_string(),
+            grantee.id.to_string(),
+            "viewer".to_string(),
+        )
+        .await
+        .unwrap();
+        grant_ids.push(grant.id);
+    }
+
+    for (grant_id, expired_days_ago) in [(grant_ids[0], 40), (grant_ids[1], 1)] {
+        share_grants::ActiveModel {
+            id: ActiveValue::Unchanged(grant_id),
+            expires_at: ActiveValue::Set(Some(
+                (Utc::now() - Duration::days(expired_days_ago)).into(),
+            )),
+            ..Default::default()
+        }
+        .update(&app_state.db)
+        .await
+        .unwrap();
+    }
+
+    cleanup_expired_share_grants(&app_state.db, 30)
+        .await
+        .unwrap();
+
+    let remaining_ids: Vec<_> = share_grants::Entity::find()
+        .all(&app_state.db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|grant| grant.id)
+        .collect();
+    assert_eq!(remaining_ids.len(), 2);
+    assert!(!remaining_ids.contains(&grant_ids[0]));
+}
+
+/// Test the cleanup worker logic for read notifications.
+///
+/// # Test Categories
+/// - `uses-db`
+///
+/// # Test Behavior
+/// Creates an old unread notification, and notifications that were read long ago and recently,
+/// and verifies that only the one read long ago is deleted by the cleanup worker.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_cleanup_read_notifications(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+
+    let user = erato::models::user::get_or_create_user(&app_state.db, "test-issuer", "user", None)
+        .await
+        .unwrap();
+
+    let mut notification_ids = Vec::new();
+    for read_days_ago in [None, Some(40), Some(1)] {
+        let notification = erato::models::notification::create_notification(
+            &app_state.db,
+            user.id,
+            &NotificationPayload::GenerationFailed(GenerationFailedPayload {
+                chat_id: "00000000-0000-0000-0000-000000000000".to_string(),
+                message_id: None,
+            }),
+        )
+        .await
+        .unwrap();
+        notifications::ActiveModel {
+            id: ActiveValue::Unchanged(notification.id),
+            created_at: ActiveValue::Set((Utc::now() - Duration::days(60)).into()),
+            read_at: ActiveValue::Set(
+                read_days_ago.map(|days| (Utc::now() - Duration::days(days)).into()),
+            ),
+            ..Default::default()
+        }
+        .update(&app_state.db)
+        .await
+        .unwrap();
+        notification_ids.push(notification.id);
+    }
+
+    cleanup_read_notifications(&app_state.db, 30).await.unwrap();
+
+    let remaining_ids: Vec<_> = notifications::Entity::find()
+        .all(&app_state.db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|notification| notification.id)
+        .collect();
+    assert_eq!(remaining_ids.len(), 2);
+    assert!(!remaining_ids.contains(&notification_ids[1]));
+}
+
+/// Test the cleanup worker logic for provisional chats.
+///
+/// # Test Categories
+/// - `uses-db`
+///
+/// # Test Behavior
+/// Creates a provisional chat that is older than an hour, a recent provisional chat, and an old
+/// chat that is no longer provisional, and verifies that only the old provisional chat is deleted
+/// by the cleanup worker.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_cleanup_provisional_chats(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+
+    let mut chat_ids = Vec::new();
+    for (provisional, created_minutes_ago) in [(true, 90), (true, 10), (false, 90)] {
+        let chat = chats::ActiveModel {
+            owner_user_id: ActiveValue::Set("user".to_string()),
+            created_at: ActiveValue::Set(
+                (Utc::now() - Duration::minutes(created_minutes_ago)).into(),
+            ),
+            provisional: ActiveValue::Set(provisional),
+            ..Default::default()
+        }
+        .insert(&app_state.db)
+        .await
+        .unwrap();
+        chat_ids.push(chat.id);
+    }
+
+    cleanup_provisional_chats(&app_state.db).await.unwrap();
+
+    let remaining_ids: Vec<_> = chats::Entity::find()
+        .all(&app_state.db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|chat| chat.id)
+        .collect();
+    assert_eq!(remaining_ids.len(), 2);
+    assert!(!remaining_ids.contains(&chat_ids[0]));
+}
@@ -0,0 +1,798 @@
+//! Admin API endpoint integration tests.
+
+use axum::http;
+use axum_test::{TestResponse, TestServer};
+use erato::db::entity::{message_redactions, messages};
+use erato::services::seed::{SEED_USER_ISSUER, seed_user_subject};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter, prelude::Uuid,
+};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    JwtTokenBuilder, TEST_JWT_TOKEN, TestRequestAuthExt, admin_app_config, admin_token,
+    completed_assistant_message_id, configure_admin_group, create_test_server, hermetic_app_config,
+    mock_llm_server_base_url, parse_sse_events,
+};
+
+fn feature_flag<'a>(response: &'a Value, name: &str) -> &'a Value {
+    response["flags"]
+        .as_array()
+        .expect("response should contain flags array")
+        .iter()
+        .find(|flag| flag["name"] == name)
+        .unwrap_or_else(|| panic!("flag {name} should be listed"))
+}
+
+/// Verifies that admins can override feature flags at runtime, that the
+/// override takes effect on the gated endpoint, and that it can be reverted.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Overrides the disabled `starter_prompts.enabled` flag, checks the listed
+/// source and the now reachable starter prompts endpoint, and then removes the
+/// override again.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_admin_feature_flag_overrides(pool: Pool<Postgres>) {
+    let mut app_config = admin_app_config();
+    app_config.starter_prompts.enabled = false;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let admin_token = admin_token();
+
+    let list_response = server
+        .get("/api/v1beta/admin/feature-flags")
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(list_response.status_code(), http::StatusCode::OK);
+    let flags: Value = list_response.json();
+    let starter_prompts = feature_flag(&flags, "starter_prompts.enabled");
+    assert_eq!(starter_prompts["enabled"], false);
+    assert_eq!(starter_prompts["source"], "config");
+
+    let starter_prompts_response = server
+        .get("/api/v1beta/me/starter-prompts")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(
+        starter_prompts_response.status_code(),
+        http::StatusCode::NOT_FOUND
+    );
+
+    let set_response = server
+        .post("/api/v1beta/admin/feature-flags/starter_prompts.enabled")
+        .json(&json!({ "enabled": true }))
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(set_response.status_code(), http::StatusCode::OK);
+    let flag: Value = set_response.json();
+    assert_eq!(flag["enabled"], true);
+    assert_eq!(flag["source"], "runtime_override");
+    assert_eq!(flag["config_value"], false);
+
+    // The override applies to all users
+    let starter_prompts_response = server
+        .get("/api/v1beta/me/starter-prompts")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(starter_prompts_response.status_code(), http::StatusCode::OK);
+
+    let flags: Value = server
+        .get("/api/v1beta/admin/feature-flags")
+        .with_bearer_token(&admin_token)
+        .await
+        .json();
+    let starter_prompts = feature_flag(&flags, "starter_prompts.enabled");
+    assert_eq!(starter_prompts["enabled"], true);
+    assert_eq!(starter_prompts["source"], "runtime_override");
+
+    let delete_response = server
+        .delete("/api/v1beta/admin/feature-flags/starter_prompts.enabled")
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(delete_response.status_code(), http::StatusCode::OK);
+    let flag: Value = delete_response.json();
+    assert_eq!(flag["enabled"], false);
+    assert_eq!(flag["source"], "config");
+
+    let starter_prompts_response = server
+        .get("/api/v1beta/me/starter-prompts")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(
+        starter_prompts_response.status_code(),
+        http::StatusCode::NOT_FOUND
+    );
+
+    let unknown_response = server
+        .post("/api/v1beta/admin/feature-flags/unknown.enabled")
+        .json(&json!({ "enabled": true }))
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(unknown_response.status_code(), http::StatusCode::NOT_FOUND);
+}
+
+/// Verifies that the admin API is only available to members of the admin groups.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_admin_feature_flags_require_admin_group(pool: Pool<Postgres>) {
+    let app_config = admin_app_config();
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let other_group_token = JwtTokenBuilder::new()
+        .groups(vec!["other-group".to_string()])
+        .build();
+
+    for token in [TEST_JWT_TOKEN, other_group_token.as_str()] {
+        let list_response = server
+            .get("/api/v1beta/admin/feature-flags")
+            .with_bearer_token(token)
+            .await;
+        assert_eq!(list_response.status_code(), http::StatusCode::FORBIDDEN);
+
+        let set_response = server
+            .post("/api/v1beta/admin/feature-flags/prompt_optimizer.enabled")
+            .json(&json!({ "enabled": true }))
+            .with_bearer_token(token)
+            .await;
+        assert_eq!(set_response.status_code(), http::StatusCode::FORBIDDEN);
+
+        let delete_response = server
+            .delete("/api/v1beta/admin/feature-flags/prompt_optimizer.enabled")
+            .with_bearer_token(token)
+            .await;
+        assert_eq!(delete_response.status_code(), http::StatusCode::FORBIDDEN);
+    }
+
+    assert!(
+        !app_state.feature_enabled(erato::services::feature_flags::FeatureFlag::PromptOptimizer)
+    );
+}
+
+/// Verifies that seeding creates browsable demo data and is idempotent.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Seeds the `minimal` profile twice via the admin API, and then browses the
+/// seeded chat of the first seeded user via `recent_chats` and `messages`.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_admin_seed(pool: Pool<Postgres>) {
+    let app_config = admin_app_config();
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let admin_token = admin_token();
+
+    let seed_response = server
+        .post("/api/v1beta/admin/seed")
+        .json(&json!({ "profile": "minimal", "seed": 7 }))
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(seed_response.status_code(), http::StatusCode::OK);
+    let summary: Value = seed_response.json();
+    assert_eq!(summary["profile"], "minimal");
+    assert_eq!(summary["users"], 2);
+    assert_eq!(summary["chats"], 2);
+    assert_eq!(summary["messages"], 12);
+    assert_eq!(summary["assistants"], 2);
+    assert_eq!(summary["share_grants"], 2);
+
+    // Seeding again with the same seed creates nothing
+    let summary: Value = server
+        .post("/api/v1beta/admin/seed")
+        .json(&json!({ "profile": "minimal", "seed": 7 }))
+        .with_bearer_token(&admin_token)
+        .await
+        .json();
+    for count in [
+        "users",
+        "chats",
+        "messages",
+        "feedbacks",
+        "assistants",
+        "files",
+        "share_grants",
+    ] {
+        assert_eq!(summary[count], 0, "{count} should not be seeded twice");
+    }
+
+    let unknown_profile_response = server
+        .post("/api/v1beta/admin/seed")
+        .json(&json!({ "profile": "unknown" }))
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(
+        unknown_profile_response.status_code(),
+        http::StatusCode::BAD_REQUEST
+    );
+
+    let seeded_user_token = JwtTokenBuilder::new()
+        .issuer(SEED_USER_ISSUER)
+        .subject(seed_user_subject(7, 0))
+        .build();
+
+    let recent_chats: Value = server
+        .get("/api/v1beta/me/recent_chats")
+        .with_bearer_token(&seeded_user_token)
+        .await
+        .json();
+    let chats = recent_chats["chats"]
+        .as_array()
+        .expect("Expected chats array");
+    assert_eq!(chats.len(), 1);
+    assert!(chats[0]["title_resolved"].as_str().is_some());
+    let chat_id = chats[0]["id"].as_str().unwrap();
+
+    let messages_response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(&seeded_user_token)
+        .await;
+    assert_eq!(messages_response.status_code(), http::StatusCode::OK);
+    let messages_json: Value = messages_response.json();
+    let messages = messages_json["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 6);
+
+    // The edited follow-up replaced the original one in the active thread
+    let active_messages: Vec<&Value> = messages
+        .iter()
+        .filter(|message| message["is_message_in_active_thread"] == true)
+        .collect();
+    assert_eq!(active_messages.len(), 4);
+    assert!(
+        messages
+            .iter()
+            .any(|message| message["sibling_message_id"].is_string())
+    );
+    assert_eq!(
+        messages
+            .iter()
+            .filter(|message| message["feedback"].is_object())
+            .count(),
+        2
+    );
+}
+
+/// Verifies that seeding is refused in the `production` environment.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_admin_seed_refused_in_production(pool: Pool<Postgres>) {
+    let mut app_config = admin_app_config();
+    app_config.environment = "production".to_string();
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let admin_token = admin_token();
+
+    let seed_response = server
+        .post("/api/v1beta/admin/seed")
+        .json(&json!({}))
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(seed_response.status_code(), http::StatusCode::FORBIDDEN);
+}
+
+/// Verifies that the message link consistency check reports links across chats.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Creates two chats and stores a message in the second one whose previous message belongs to the
+/// first one, bypassing the API validation. The check reports exactly that link to admins and is
+/// refused for other users.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_admin_message_link_consistency(pool: Pool<Postgres>) {
+    let app_config = admin_app_config();
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let admin_token = admin_token();
+
+    let mut chat_ids = Vec::new();
+    for _ in 0..2 {
+        let response = server
+            .post("/api/v1beta/me/chats")
+            .with_bearer_token(&admin_token)
+            .json(&json!({}))
+            .await;
+        assert_eq!(response.status_code(), http::StatusCode::OK);
+        let chat_id = response.json::<Value>()["chat_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        chat_ids.push(Uuid::parse_str(&chat_id).unwrap());
+    }
+
+    let insert_message = |chat_id: Uuid, previous_message_id: Option<Uuid>| {
+        messages::ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            chat_id: ActiveValue::Set(chat_id),
+            raw_message: ActiveValue::Set(json!({
+                "role": "user",
+                "content": [{ "content_type": "text", "text": "Hello" }]
+            })),
+            previous_message_id: ActiveValue::Set(previous_message_id),
+            is_message_in_active_thread: ActiveValue::Set(true),
+            ..Default::default()
+        }
+        .insert(&app_state.db)
+    };
+    let first_message = insert_message(chat_ids[0], None)
+        .await
+        .expect("Failed to insert message");
+    let linked_message = insert_message(chat_ids[1], Some(first_message.id))
+        .await
+        .expect("Failed to insert message");
+
+    let response = server
+        .get("/api/v1beta/admin/consistency/message-links")
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let report: Value = response.json();
+    assert_eq!(
+        report["cross_chat_links"],
+        json!([{
+            "message_id": linked_message.id.to_string(),
+            "chat_id": chat_ids[1].to_string(),
+            "link_type": "previous",
+            "linked_message_id": first_message.id.to_string(),
+            "linked_chat_id": chat_ids[0].to_string(),
+        }])
+    );
+
+    let response = server
+        .get("/api/v1beta/admin/consistency/message-links")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::FORBIDDEN);
+}
+
+/// Verifies that admins can check and repair the thread of a chat.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Stores a question with two answers that are both part of the active thread, bypassing the API.
+/// The check reports the two ends of the active thread. A dry run of the repair reports the
+/// deactivation of the older answer without modifying it, and the repair applies it, after which
+/// the thread is intact. Unknown chats are reported as not found, and other users are refused.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_admin_thread_integrity_repair(pool: Pool<Postgres>) {
+    let app_config = admin_app_config();
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let admin_token = admin_token();
+    let response = server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(&admin_token)
+        .json(&json!({}))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let chat_id = response.json::<Value>()["chat_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let insert_message = |previous_message_id: Option<Uuid>| {
+        messages::ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            chat_id: ActiveValue::Set(Uuid::parse_str(&chat_id).unwrap()),
+            raw_message: ActiveValue::Set(json!({
+                "role": "user",
+                "content": [{ "content_type": "text", "text": "Hello" }]
+            })),
+            previous_message_id: ActiveValue::Set(previous_message_id),
+            is_message_in_active_thread: ActiveValue::Set(true),
+            ..Default::default()
+        }
+        .insert(&app_state.db)
+    };
+    let question = insert_message(None)
+        .await
+        .expect("Failed to insert message");
+    let older_answer = insert_message(Some(question.id))
+        .await
+        .expect("Failed to insert message");
+    let newer_answer = insert_message(Some(question.id))
+        .await
+        .expect("Failed to insert message");
+
+    let integrity_path = format!("/api/v1beta/admin/chats/{chat_id}/thread-integrity");
+    let repair_path = format!("/api/v1beta/admin/chats/{chat_id}/thread-repair");
+    let response = server
+        .get(&integrity_path)
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let report: Value = response.json();
+    assert_eq!(report["message_count"], 3);
+    assert_eq!(
+        report["issues"],
+        json!([{
+            "kind": "multiple_active_leaves",
+            "message_ids": [older_answer.id.to_string(), newer_answer.id.to_string()],
+        }])
+    );
+
+    let expected_changes = json!([{
+        "message_id": older_answer.id.to_string(),
+        "action": "deactivate",
+    }]);
+    let response = server
+        .post(&repair_path)
+        .with_bearer_token(&admin_token)
+        .json(&json!({ "dry_run": true }))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let repair: Value = response.json();
+    assert_eq!(repair["dry_run"], true);
+    assert_eq!(repair["changes"], expected_changes);
+    assert!(
+        find_message(&app_state.db, older_answer.id)
+            .await
+            .is_message_in_active_thread
+    );
+
+    let response = server
+        .post(&repair_path)
+        .with_bearer_token(&admin_token)
+        .json(&json!({}))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let repair: Value = response.json();
+    assert_eq!(repair["dry_run"], false);
+    assert_eq!(repair["changes"], expected_changes);
+    assert!(
+        !find_message(&app_state.db, older_answer.id)
+            .await
+            .is_message_in_active_thread
+    );
+    let report: Value = server
+        .get(&integrity_path)
+        .with_bearer_token(&admin_token)
+        .await
+        .json();
+    assert_eq!(report["issues"], json!([]));
+
+    let response = server
+        .get(&format!(
+            "/api/v1beta/admin/chats/{}/thread-integrity",
+            Uuid::new_v4()
+        ))
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+
+    let response = server
+        .post(&repair_path)
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({}))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::FORBIDDEN);
+}
+
+/// Verifies that admins can redact the content of a message, including the copies of it in the
+/// history that is sent to the model.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mock-llm-server`
+/// - `sse-streaming`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Submits a message containing a secret. A dry run reports the location of the secret without
+/// modifying the message. The redaction replaces the secret in the stored message and in the
+/// stored generation inputs, and records the locations but not the secret. A follow-up message is
+/// generated from a history that contains the replacement instead of the secret. Other users are
+/// refused.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_admin_redact_message(pool: Pool<Postgres>) {
+    let base_url = mock_llm_server_base_url();
+    let mut app_config = hermetic_app_config(None, Some(format!("{base_url}/base-openai/v1/")));
+    configure_admin_group(&mut app_config);
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let admin_token = admin_token();
+    let secret = "sk-live-4f9a2c";
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(&admin_token)
+        .json(&json!({ "user_message": format!("My API key is {secret}, please keep it safe") }))
+        .await;
+    response.assert_status_ok();
+    let events: Vec<Value> = parse_sse_events(&response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .collect();
+    let event_message_id = |message_type: &str| {
+        events
+            .iter()
+            .find(|event| event["message_type"] == message_type)
+            .and_then(|event| event["message_id"].as_str())
+            .map(|id| Uuid::parse_str(id).unwrap())
+            .unwrap_or_else(|| panic!("Expected {message_type} event"))
+    };
+    let user_message_id = event_message_id("user_message_saved");
+    let assistant_message_id = event_message_id("assistant_message_completed");
+    let redact_path = format!("/api/v1beta/admin/messages/{user_message_id}/redact");
+
+    // Dry run
+    let response = server
+        .post(&redact_path)
+        .with_bearer_token(&admin_token)
+        .json(&json!({ "pattern": r"sk-live-[0-9a-f]+", "dry_run": true }))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let body: Value = response.json();
+    assert_eq!(
+        body["spans"],
+        json!([{ "content_index": 0, "start": 14, "end": 28 }])
+    );
+    let user_message = find_message(&app_state.db, user_message_id).await;
+    assert!(user_message.raw_message.to_string().contains(secret));
+
+    // Other users can't redact messages
+    let response = server
+        .post(&redact_path)
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "pattern": r"sk-live-[0-9a-f]+" }))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::FORBIDDEN);
+
+    let response = server
+        .post(&redact_path)
+        .with_bearer_token(&admin_token)
+        .json(&json!({ "pattern": r"sk-live-[0-9a-f]+" }))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let body: Value = response.json();
+    assert_eq!(body["dry_run"], json!(false));
+    assert_eq!(body["updated_generation_inputs"], json!(1));
+
+    let user_message = find_message(&app_state.db, user_message_id).await;
+    assert!(!user_message.raw_message.to_string().contains(secret));
+    assert!(user_message.updated_at > user_message.created_at);
+    assert_eq!(
+        user_message.raw_message["content"][0]["text"],
+        json!("My API key is [REDACTED], please keep it safe")
+    );
+    let assistant_message = find_message(&app_state.db, assistant_message_id).await;
+    let generation_input = assistant_message
+        .generation_input_messages
+        .expect("Expected generation input messages")
+        .to_string();
+    assert!(!generation_input.contains(secret));
+    assert!(generation_input.contains("[REDACTED]"));
+
+    let redactions = message_redactions::Entity::find()
+        .filter(message_redactions::Column::MessageId.eq(user_message_id))
+        .all(&app_state.db)
+        .await
+        .expect("Failed to load redactions");
+    assert_eq!(redactions.len(), 1);
+    assert_eq!(
+        redactions[0].spans,
+        json!([{ "content_index": 0, "start": 14, "end": 28 }])
+    );
+    assert!(!format!("{:?}", redactions[0]).contains(secret));
+
+    // Subsequent generations don't see the secret
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(&admin_token)
+        .json(&json!({
+            "previous_message_id": assistant_message_id,
+            "user_message": "What was my API key?"
+        }))
+        .await;
+    response.assert_status_ok();
+    let follow_up_id = parse_sse_events(&response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .find(|event| event["message_type"] == "assistant_message_completed")
+        .and_then(|event| event["message_id"].as_str().map(str::to_string))
+        .expect("Expected assistant_message_completed event");
+    let follow_up = find_message(&app_state.db, Uuid::parse_str(&follow_up_id).unwrap()).await;
+    let generation_input = follow_up
+        .generation_input_messages
+        .expect("Expected generation input messages")
+        .to_string();
+    assert!(!generation_input.contains(secret));
+    assert!(generation_input.contains("My API key is [REDACTED]"));
+}
+
+async fn find_message(db: &sea_orm::DatabaseConnection, message_id: Uuid) -> messages::Model {
+    messages::Entity::find_by_id(message_id)
+        .one(db)
+        .await
+        .expect("Failed to load message")
+        .expect("Message should exist")
+}
+
+async fn get_prompt_manifest(server: &TestServer, message_id: &str, token: &str) -> TestResponse {
+    server
+        .get(&format!(
+            "/api/v1beta/admin/messages/{message_id}/prompt-manifest"
+        ))
+        .with_bearer_token(token)
+        .await
+}
+
+/// Verifies that the prompt manifest of assistant messages records a change of the assistant
+/// prompt between two messages of the same chat.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mock-llm-server`
+/// - `sse-streaming`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Submits a message and a follow-up in a chat with an assistant. The follow-up replays the
+/// assistant prompt from the history, so both answers have the same manifest hash. After the
+/// assistant prompt is changed, the first answer is regenerated with a different assistant prompt
+/// hash and manifest hash. The owner of the chat can retrieve the manifests, which contain no
+/// prompt text by default, while other users can't.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_admin_prompt_manifest_records_assistant_prompt_change(pool: Pool<Postgres>) {
+    let base_url = mock_llm_server_base_url();
+    let mut app_config = hermetic_app_config(None, Some(format!("{base_url}/base-openai/v1/")));
+    configure_admin_group(&mut app_config);
+    app_config.admin.prompt_manifest.visible_to_chat_owner = true;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let admin_token = JwtTokenBuilder::new()
+        .subject("prompt-manifest-admin")
+        .admin()
+        .build();
+    let other_token = JwtTokenBuilder::new()
+        .subject("prompt-manifest-other")
+        .build();
+
+    let response = server
+        .post("/api/v1beta/assistants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "name": "Summarizer", "prompt": "Answer in one sentence." }))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::CREATED);
+    let assistant_id = response.json::<Value>()["id"].as_str().unwrap().to_string();
+    let response = server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "assistant_id": assistant_id }))
+        .await;
+    response.assert_status_ok();
+    let chat_id = response.json::<Value>()["chat_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "existing_chat_id": chat_id, "user_message": "What is Rust?" }))
+        .await;
+    response.assert_status_ok();
+    let first_answer_id = completed_assistant_message_id(&response);
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "previous_message_id": first_answer_id,
+            "user_message": "And what is Cargo?"
+        }))
+        .await;
+    response.assert_status_ok();
+    let follow_up_id = completed_assistant_message_id(&response);
+
+    let assistant_prompt = |manifest: &Value| {
+        manifest["components"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|component| component["kind"] == "assistant_prompt")
+            .cloned()
+            .expect("Expected an assistant prompt in the manifest")
+    };
+
+    let response = get_prompt_manifest(&server, &first_answer_id, TEST_JWT_TOKEN).await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let first_manifest: Value = response.json();
+    let first_assistant_prompt = assistant_prompt(&first_manifest);
+    assert_eq!(first_assistant_prompt["source_id"], json!(assistant_id));
+    assert_eq!(first_assistant_prompt["length"], json!(23));
+    assert!(first_assistant_prompt.get("text").is_none());
+    assert!(
+        first_assistant_prompt
+            .get("replayed_from_message_id")
+            .is_none()
+    );
+    assert_eq!(first_manifest["complete"], json!(true));
+
+    let response = get_prompt_manifest(&server, &follow_up_id, TEST_JWT_TOKEN).await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let follow_up_manifest: Value = response.json();
+    assert_eq!(follow_up_manifest["hash"], first_manifest["hash"]);
+    assert_eq!(
+        assistant_prompt(&follow_up_manifest)["replayed_from_message_id"],
+        json!(first_answer_id)
+    );
+
+    // Change the assistant prompt and regenerate the first answer
+    let response = server
+        .put(&format!("/api/v1beta/assistants/{assistant_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "prompt": "Answer in detail, with examples." }))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let response = server
+        .post("/api/v1beta/me/messages/regeneratestream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "current_message_id": first_answer_id }))
+        .await;
+    response.assert_status_ok();
+    let regenerated_id = completed_assistant_message_id(&response);
+
+    let response = get_prompt_manifest(&server, &regenerated_id, &admin_token).await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let regenerated_manifest: Value = response.json();
+    let regenerated_assistant_prompt = assistant_prompt(&regenerated_manifest);
+    assert_eq!(
+        regenerated_assistant_prompt["source_id"],
+        json!(assistant_id)
+    );
+    assert_eq!(regenerated_assistant_prompt["length"], json!(32));
+    assert_ne!(
+        regenerated_assistant_prompt["content_sha256"],
+        first_assistant_prompt["content_sha256"]
+    );
+    assert_ne!(regenerated_manifest["hash"], first_manifest["hash"]);
+
+    // The manifest hash is part of the messages of the chat
+    let messages: Value = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    let manifest_hash = |message_id: &str| {
+        messages["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|message| message["id"] == message_id)
+            .map(|message| message["prompt_manifest_hash"].clone())
+            .unwrap_or_else(|| panic!("Expected message {message_id} in the chat"))
+    };
+    assert_eq!(manifest_hash(&regenerated_id), regenerated_manifest["hash"]);
+
+    // Other users can't retrieve the manifests of the chat
+    let response = get_prompt_manifest(&server, &first_answer_id, &other_token).await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,310 @@
+//! Tests for the commands of `erato admin`.
+
+use axum::http;
+use axum_test::TestServer;
+use erato::db::entity::{chats, messages, policy_invalidations, share_grants};
+use erato::models::message_thread_integrity::ThreadIntegrityIssueKind;
+use erato::models::user::get_or_create_user;
+use erato::services::admin_commands::{
+    AdminCommand, AdminCommandOptions, inspect_chat, run_admin_command,
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter, prelude::Uuid,
+};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    JwtTokenBuilder, TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt,
+    create_chat, create_test_server, hermetic_app_config,
+};
+
+async fn share_chat(server: &TestServer, chat_id: Uuid, user_id: Uuid) -> Uuid {
+    let response = server
+        .post("/api/v1beta/share-grants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "resource_type": "chat",
+            "resource_id": chat_id.to_string(),
+            "subject_type": "user",
+            "subject_id_type": "id",
+            "subject_id": user_id.to_string(),
+            "role": "viewer",
+        }))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::CREATED);
+    Uuid::parse_str(
+        response.json::<Value>()["share_grant"]["id"]
+            .as_str()
+            .unwrap(),
+    )
+    .unwrap()
+}
+
+async fn read_chat_status(server: &TestServer, chat_id: Uuid, token: &str) -> http::StatusCode {
+    server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(token)
+        .await
+        .status_code()
+}
+
+/// Test that `chat-transfer` moves a chat to its new owner.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// The chat is shared with its future owner and with another user. Without `--yes`, the transfer
+/// is refused, and a dry run reports the share grant that would be removed without changing
+/// anything. After the transfer, the previous owner can't read the chat anymore, the new owner
+/// and the other grantee can, the share grant to the new owner is removed, and an invalidation
+/// for the other instances is recorded.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_transfer(pool: Pool<Postgres>) {
+    let app_state = test_app_state(hermetic_app_config(None, None), pool).await;
+    let server = create_test_server(app_state.clone());
+    let previous_owner =
+        get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+            .await
+            .unwrap();
+    let new_owner = get_or_create_user(&app_state.db, TEST_USER_ISSUER, "new-owner", None)
+        .await
+        .unwrap();
+    let grantee = get_or_create_user(&app_state.db, TEST_USER_ISSUER, "grantee", None)
+        .await
+        .unwrap();
+    let new_owner_token = JwtTokenBuilder::new().subject("new-owner").build();
+    let grantee_token = JwtTokenBuilder::new().subject("grantee").build();
+
+    let chat_id = Uuid::parse_str(&create_chat(&server, TEST_JWT_TOKEN).await).unwrap();
+    let new_owner_grant_id = share_chat(&server, chat_id, new_owner.id).await;
+    let grantee_grant_id = share_chat(&server, chat_id, grantee.id).await;
+
+    let command = AdminCommand::ChatTransfer {
+        chat_id,
+        new_owner_user_id: new_owner.id,
+    };
+    let refused = run_admin_command(&app_state, &command, &AdminCommandOptions::default()).await;
+    assert!(refused.unwrap_err().to_string().contains("--yes"));
+
+    let dry_run = run_admin_command(
+        &app_state,
+        &command,
+        &AdminCommandOptions {
+            json: true,
+            dry_run: true,
+            yes: false,
+        },
+    )
+    .await
+    .unwrap();
+    let dry_run: Value = serde_json::from_str(&dry_run).unwrap();
+    assert_eq!(
+        dry_run["removed_share_grant_ids"],
+        json!([new_owner_grant_id.to_string()])
+    );
+    let chat = chats::Entity::find_by_id(chat_id)
+        .one(&app_state.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(chat.owner_user_id, previous_owner.id.to_string());
+
+    let output = run_admin_command(
+        &app_state,
+        &command,
+        &AdminCommandOptions {
+            yes: true,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert!(output.contains(&format!(
+        "owner {} -> {}",
+        previous_owner.id, new_owner.id
+    )));
+
+    let chat = chats::Entity::find_by_id(chat_id)
+        .one(&app_state.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(chat.owner_user_id, new_owner.id.to_string());
+    let remaining_grant_ids: Vec<Uuid> = share_grants::Entity::find()
+        .filter(share_grants::Column::ResourceId.eq(chat_id.to_string()))
+        .all(&app_state.db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|grant| grant.id)
+        .collect();
+    assert_eq!(remaining_grant_ids, vec![grantee_grant_id]);
+    let invalidation = policy_invalidations::Entity::find()
+        .filter(policy_invalidations::Column::Reason.eq("chat_transferred"))
+        .one(&app_state.db)
+        .await
+        .unwrap();
+    assert!(invalidation.is_some());
+
+    assert_eq!(
+        read_chat_status(&server, chat_id, TEST_JWT_TOKEN).await,
+        http::StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        read_chat_status(&server, chat_id, &new_owner_token).await,
+        http::StatusCode::OK
+    );
+    assert_eq!(
+        read_chat_status(&server, chat_id, &grantee_token).await,
+        http::StatusCode::OK
+    );
+
+    // Transferring the chat to its owner again is rejected
+    let repeated = run_admin_command(
+        &app_state,
+        &command,
+        &AdminCommandOptions {
+            yes: true,
+            ..Default::default()
+        },
+    )
+    .await;
+    assert!(repeated.is_err());
+}
+
+/// Test that `chat-inspect` reports the structure and the corruptions of a chat.
+///
+/// # Test Categories
+/// - `uses-db`
+///
+/// # Test Behavior
+/// Stores a question with two answers that are both part of the active thread, one of which
+/// failed with a content filter error, bypassing the API. The inspection lists the messages with
+/// their depth in the thread, their sizes and the generation error, and reports the two ends of
+/// the active thread, both as text and as JSON. Unknown chats are not found, and `--dry-run` is
+/// rejected as the command doesn't modify anything.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_inspect_reports_corrupted_chat(pool: Pool<Postgres>) {
+    let app_state = test_app_state(hermetic_app_config(None, None), pool).await;
+    let server = create_test_server(app_state.clone());
+    let chat_id = Uuid::parse_str(&create_chat(&server, TEST_JWT_TOKEN).await).unwrap();
+
+    let insert_message =
+        |role: &str, previous_message_id: Option<Uuid>, metadata: Option<Value>| {
+            messages::ActiveModel {
+                id: ActiveValue::Set(Uuid::new_v4()),
+                chat_id: ActiveValue::Set(chat_id),
+                raw_message: ActiveValue::Set(json!({
+                    "role": role,
+                    "content": [{ "content_type": "text", "text": "Hello" }]
+                })),
+                previous_message_id: ActiveValue::Set(previous_message_id),
+                is_message_in_active_thread: ActiveValue::Set(true),
+                generation_metadata: ActiveValue::Set(metadata),
+                ..Default::default()
+            }
+            .insert(&app_state.db)
+        };
+    let question = insert_message("user", None, None).await.unwrap();
+    let failed_answer = insert_message(
+        "assistant",
+        Some(question.id),
+        Some(json!({
+            "error": {
+                "error_type": "content_filter",
+                "error_description": "The answer was filtered"
+            }
+        })),
+    )
+    .await
+    .unwrap();
+    let answer = insert_message("assistant", Some(question.id), None)
+        .await
+        .unwrap();
+
+    let inspection = inspect_chat(&app_state, &chat_id).await.unwrap();
+    let message_ids: Vec<Uuid> = inspection
+        .messages
+        .iter()
+        .map(|message| message.id)
+        .collect();
+    assert_eq!(message_ids, vec![question.id, failed_answer.id, answer.id]);
+    let depths: Vec<usize> = inspection
+        .messages
+        .iter()
+        .map(|message| message.depth)
+        .collect();
+    assert_eq!(depths, vec![0, 1, 1]);
+    assert_eq!(
+        inspection.messages[1].generation_error.as_deref(),
+        Some("content_filter")
+    );
+    assert_eq!(inspection.generation_errors, 1);
+    assert!(
+        inspection
+            .messages
+            .iter()
+            .all(|message| message.size_bytes > 0)
+    );
+    assert_eq!(
+        inspection.total_size_bytes,
+        inspection
+            .messages
+            .iter()
+            .map(|message| message.size_bytes)
+            .sum::<usize>()
+    );
+    assert_eq!(inspection.thread_integrity.issues.len(), 1);
+    assert_eq!(
+        inspection.thread_integrity.issues[0].kind,
+        ThreadIntegrityIssueKind::MultipleActiveLeaves
+    );
+    assert_eq!(
+        inspection.thread_integrity.issues[0].message_ids,
+        vec![failed_answer.id, answer.id]
+    );
+
+    let command = AdminCommand::ChatInspect { chat_id };
+    let text = run_admin_command(&app_state, &command, &AdminCommandOptions::default())
+        .await
+        .unwrap();
+    assert!(text.contains("3 messages"));
+    assert!(text.contains("error: content_filter"));
+    assert!(text.contains(&format!(
+        "multiple_active_leaves: {}, {}",
+        failed_answer.id, answer.id
+    )));
+    let json_output = run_admin_command(
+        &app_state,
+        &command,
+        &AdminCommandOptions {
+            json: true,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let report: Value = serde_json::from_str(&json_output).unwrap();
+    assert_eq!(
+        report["thread_integrity"]["issues"][0]["kind"],
+        "multiple_active_leaves"
+    );
+    assert_eq!(report["messages"][1]["generation_error"], "content_filter");
+
+    assert!(inspect_chat(&app_state, &Uuid::new_v4()).await.is_err());
+    let dry_run = run_admin_command(
+        &app_state,
+        &command,
+        &AdminCommandOptions {
+            dry_run: true,
+            ..Default::default()
+        },
+    )
+    .await;
+    assert!(dry_run.is_err());
+}
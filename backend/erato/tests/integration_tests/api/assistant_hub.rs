@@ -93,6 +93,11 @@ async fn test_assistant_hub_rejects_duplicate_version_number(pool: Pool<Postgres
         None,
         Some("mock-llm".to_string()),
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("failed to create source assistant");
@@ -193,6 +198,11 @@ async fn test_assistant_hub_featured_status_carries_across_versions(pool: Pool<P
         None,
         Some("mock-llm".to_string()),
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("failed to create source assistant");
@@ -402,6 +412,11 @@ async fn test_assistant_hub_user_reviews_are_upserted_summarized_and_visibility_
         None,
         Some("mock-llm".to_string()),
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("failed to create source assistant");
@@ -750,6 +765,11 @@ async fn test_published_current_assistant_hub_version_is_listed_for_audience_vie
         None,
         Some("mock-llm".to_string()),
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("failed to create source assistant");
@@ -893,6 +913,11 @@ async fn test_published_current_assistant_hub_version_is_listed_for_group_audien
         None,
         Some("mock-llm".to_string()),
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("failed to create source assistant");
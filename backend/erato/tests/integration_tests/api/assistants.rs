@@ -4,11 +4,15 @@ use axum::Router;
 use axum::http;
 use axum_test::TestServer;
 use erato::config::{
-    ExperimentalFacetsConfig, FacetConfig, McpServerAuthenticationConfig, McpServerConfig,
-    McpServerPermissionRule, ModelSettings, PromptSourceSpecification,
+    AssistantHubCategoryConfig, ExperimentalFacetsConfig, FacetConfig,
+    McpServerAuthenticationConfig, McpServerConfig, McpServerPermissionRule, ModelSettings,
+    PromptSourceSpecification,
 };
+use erato::db::entity::chats;
 use erato::policy::engine::PolicyEngine;
 use erato::server::router::router;
+use sea_orm::prelude::Uuid;
+use sea_orm::{ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
 use serde_json::{Value, json};
 use sqlx::Pool;
 use sqlx::postgres::Postgres;
@@ -17,7 +21,7 @@ use std::collections::HashMap;
 use crate::test_app_state;
 use crate::test_utils::{
     JwtTokenBuilder, TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt,
-    hermetic_app_config, setup_mock_llm_server,
+    admin_app_config, admin_token, hermetic_app_config, setup_mock_llm_server,
 };
 
 /// Test creating an assistant via the model directly (bypassing API).
@@ -58,6 +62,11 @@ async fn test_create_assistant_model_directly(pool: Pool<Postgres>) {
         None,
         Some("openai".to_string()),
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await;
 
@@ -108,6 +117,11 @@ async fn test_list_assistants_endpoint(pool: Pool<Postgres>) {
         None,
         Some("openai".to_string()),
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create assistant 1");
@@ -123,6 +137,11 @@ async fn test_list_assistants_endpoint(pool: Pool<Postgres>) {
         None,
         Some("anthropic".to_string()),
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create assistant 2");
@@ -207,6 +226,11 @@ async fn test_get_assistant_endpoint(pool: Pool<Postgres>) {
         Some(vec!["server1".to_string()]),
         Some("openai".to_string()),
         true,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create assistant");
@@ -259,6 +283,7 @@ async fn test_update_assistant_endpoint(pool: Pool<Postgres>) {
             http_headers: None,
             authentication: McpServerAuthenticationConfig::None,
             max_session_idle_seconds: None,
+            on_schema_violation: Default::default(),
         },
     );
     app_config.experimental_facets = ExperimentalFacetsConfig {
@@ -323,6 +348,11 @@ async fn test_update_assistant_endpoint(pool: Pool<Postgres>) {
         Some(vec!["web_search".to_string()]),
         Some("mock-llm".to_string()),
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create assistant");
@@ -396,6 +426,11 @@ async fn test_archive_assistant_endpoint(pool: Pool<Postgres>) {
         None,
         None,
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create assistant");
@@ -491,6 +526,11 @@ async fn test_assistant_authorization(pool: Pool<Postgres>) {
         None,
         None,
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create assistant");
@@ -651,6 +691,7 @@ async fn test_create_assistant_endpoint(pool: Pool<Postgres>) {
             http_headers: None,
             authentication: McpServerAuthenticationConfig::None,
             max_session_idle_seconds: None,
+            on_schema_violation: Default::default(),
         },
     );
     app_config.mcp_servers.insert(
@@ -661,6 +702,7 @@ async fn test_create_assistant_endpoint(pool: Pool<Postgres>) {
             http_headers: None,
             authentication: McpServerAuthenticationConfig::None,
             max_session_idle_seconds: None,
+            on_schema_violation: Default::default(),
         },
     );
     app_config.experimental_facets = ExperimentalFacetsConfig {
@@ -781,6 +823,7 @@ async fn test_create_assistant_rejects_unauthorized_mcp_server(pool: Pool<Postgr
             http_headers: None,
             authentication: McpServerAuthenticationConfig::None,
             max_session_idle_seconds: None,
+            on_schema_violation: Default::default(),
         },
     );
     app_config.mcp_server_permissions.rules.insert(
@@ -881,6 +924,11 @@ async fn test_list_assistants_sharing_relation_filter(pool: Pool<Postgres>) {
         None,
         None,
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create owned assistant 1");
@@ -896,6 +944,11 @@ async fn test_list_assistants_sharing_relation_filter(pool: Pool<Postgres>) {
         None,
         None,
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create owned assistant 2");
@@ -912,6 +965,11 @@ async fn test_list_assistants_sharing_relation_filter(pool: Pool<Postgres>) {
         None,
         None,
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create shared assistant 1");
@@ -927,6 +985,11 @@ async fn test_list_assistants_sharing_relation_filter(pool: Pool<Postgres>) {
         None,
         None,
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create shared assistant 2");
@@ -1045,3 +1108,448 @@ async fn test_list_assistants_sharing_relation_filter(pool: Pool<Postgres>) {
 
     assert_eq!(response.status_code(), http::StatusCode::BAD_REQUEST);
 }
+
+/// Test that assistants visible to the organization can be read without a share grant.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Creates a private and an organization-visible assistant of another user, and verifies that
+/// only the organization-visible one is listed as shared with the user and can be read.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_organization_visible_assistant_is_readable_without_share_grant(pool: Pool<Postgres>) {
+    let app_state = test_app_state(hermetic_app_config(None, None), pool).await;
+
+    let owner = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        "test-subject-visibility-owner",
+        None,
+    )
+    .await
+    .expect("Failed to create owner");
+    let owner_subject = erato::policy::types::Subject::User(owner.id.to_string());
+
+    let private_assistant = erato::models::assistant::create_assistant(
+        &app_state.db,
+        &PolicyEngine::new(),
+        &owner_subject,
+        "Private Assistant".to_string(),
+        None,
+        "You are private".to_string(),
+        None,
+        None,
+        None,
+        false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
+    )
+    .await
+    .expect("Failed to create private assistant");
+    let organization_assistant = erato::models::assistant::create_assistant(
+        &app_state.db,
+        &PolicyEngine::new(),
+        &owner_subject,
+        "Organization Assistant".to_string(),
+        None,
+        "You are visible to the organization".to_string(),
+        None,
+        None,
+        None,
+        false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_ORGANIZATION,
+    )
+    .await
+    .expect("Failed to create organization assistant");
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let response = server
+        .get("/api/v1beta/assistants?sharing_relation=shared_with_user")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let assistants: Value = response.json();
+    let assistants = assistants.as_array().expect("Response should be an array");
+    assert_eq!(assistants.len(), 1);
+    assert_eq!(assistants[0]["id"], organization_assistant.id.to_string());
+    assert_eq!(assistants[0]["visibility"], "organization");
+    assert_eq!(assistants[0]["can_edit"], false);
+
+    let response = server
+        .get(&format!(
+            "/api/v1beta/assistants/{}",
+            organization_assistant.id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+
+    let response = server
+        .get(&format!("/api/v1beta/assistants/{}", private_assistant.id))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+
+    // Visibility doesn't grant edit permission
+    let response = server
+        .put(&format!(
+            "/api/v1beta/assistants/{}",
+            organization_assistant.id
+        ))
+        .json(&json!({ "name": "Renamed" }))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+}
+
+/// Test filtering and sorting assistants by their marketplace metadata.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Configures `assistants.categories`, creates assistants with a category and tags, and
+/// verifies the category and tag filters, that unknown categories are rejected, that only
+/// admins can feature assistants, and the `featured` and `popular` sort orders.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_list_assistants_marketplace_filters_and_sorts(pool: Pool<Postgres>) {
+    let mut app_config = admin_app_config();
+    app_config.assistants.categories.insert(
+        "legal".to_string(),
+        AssistantHubCategoryConfig {
+            display_name: "Legal".to_string(),
+            icon: "iconoir-scale".to_string(),
+        },
+    );
+    let app_state = test_app_state(app_config, pool).await;
+    let user = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        TEST_USER_SUBJECT,
+        None,
+    )
+    .await
+    .expect("Failed to create user");
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+    let admin_token = admin_token();
+
+    let response = server
+        .get("/api/v1beta/assistants/categories")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    assert_eq!(
+        response.json::<Value>(),
+        json!([{ "id": "legal", "display_name": "Legal", "icon": "iconoir-scale" }])
+    );
+
+    let response = server
+        .post("/api/v1beta/assistants")
+        .json(&json!({
+            "name": "Unknown Category",
+            "prompt": "You are uncategorized",
+            "category": "finance"
+        }))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::BAD_REQUEST);
+
+    let response = server
+        .post("/api/v1beta/assistants")
+        .json(&json!({
+            "name": "Contract Reviewer",
+            "prompt": "You review contracts",
+            "category": "legal",
+            "tags": ["contracts", " contracts ", ""],
+            "visibility": "organization"
+        }))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::CREATED);
+    let contract_reviewer: Value = response.json();
+    assert_eq!(contract_reviewer["category"], "legal");
+    assert_eq!(contract_reviewer["tags"], json!(["contracts"]));
+    assert_eq!(contract_reviewer["visibility"], "organization");
+    assert_eq!(contract_reviewer["featured"], false);
+    let contract_reviewer_id = contract_reviewer["id"].as_str().unwrap().to_string();
+
+    let response = server
+        .post("/api/v1beta/assistants")
+        .json(&json!({
+            "name": "Translator",
+            "prompt": "You translate"
+        }))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::CREATED);
+    let translator: Value = response.json();
+    assert_eq!(translator["visibility"], "private");
+    let translator_id = translator["id"].as_str().unwrap().to_string();
+
+    let list_ids = |response: axum_test::TestResponse| -> Vec<String> {
+        assert_eq!(response.status_code(), http::StatusCode::OK);
+        response
+            .json::<Value>()
+            .as_array()
+            .expect("Response should be an array")
+            .iter()
+            .map(|assistant| assistant["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let response = server
+        .get("/api/v1beta/assistants?category=legal")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(list_ids(response), vec![contract_reviewer_id.clone()]);
+    let response = server
+        .get("/api/v1beta/assistants?tag=contracts&visibility=organization")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(list_ids(response), vec![contract_reviewer_id.clone()]);
+
+    // Only admins can feature assistants
+    let featured_url = format!("/api/v1beta/admin/assistants/{contract_reviewer_id}/featured");
+    let response = server
+        .put(&featured_url)
+        .json(&json!({ "featured": true }))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::FORBIDDEN);
+    let response = server
+        .put(&featured_url)
+        .json(&json!({ "featured": true }))
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    assert_eq!(response.json::<Value>()["featured"], true);
+
+    // The translator was updated last, so it is listed first unless sorted otherwise
+    let response = server
+        .get("/api/v1beta/assistants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(
+        list_ids(response),
+        vec![translator_id.clone(), contract_reviewer_id.clone()]
+    );
+    let response = server
+        .get("/api/v1beta/assistants?sort=featured")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(
+        list_ids(response),
+        vec![contract_reviewer_id.clone(), translator_id.clone()]
+    );
+    let response = server
+        .get("/api/v1beta/assistants?featured=false")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(list_ids(response), vec![translator_id.clone()]);
+
+    // Two chats with the contract reviewer make it the most popular assistant
+    for _ in 0..2 {
+        chats::ActiveModel {
+            owner_user_id: ActiveValue::Set(user.id.to_string()),
+            assistant_configuration: ActiveValue::Set(Some(json!({
+                "assistant_id": contract_reviewer_id
+            }))),
+            ..Default::default()
+        }
+        .insert(&app_state.db)
+        .await
+        .expect("Failed to insert chat");
+    }
+    let response = server
+        .get("/api/v1beta/assistants?sort=popular")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(
+        list_ids(response),
+        vec![contract_reviewer_id, translator_id]
+    );
+}
+
+/// Test that chats with a frozen assistant configuration keep the prompt of their snapshot.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Creates a chat of an assistant and edits the prompt of the assistant afterwards. With
+/// `assistants.freeze_assistant_config`, the chat reports itself as frozen and its generation
+/// still uses the original prompt, until the owner refreshes the snapshot. Without the option,
+/// the same chat picks up the edited prompt right away.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_frozen_assistant_config_keeps_snapshot_prompt(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    app_config.assistants.freeze_assistant_config = true;
+    let app_state = test_app_state(app_config.clone(), pool.clone()).await;
+    let _user = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        TEST_USER_SUBJECT,
+        None,
+    )
+    .await
+    .expect("Failed to create user");
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let assistant_response = server
+        .post("/api/v1beta/assistants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "name": "Reviewer",
+            "prompt": "Original reviewer prompt."
+        }))
+        .await;
+    assistant_response.assert_status(http::StatusCode::CREATED);
+    let assistant_id = assistant_response.json::<Value>()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let chat_response = server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "assistant_id": assistant_id }))
+        .await;
+    chat_response.assert_status_ok();
+    let chat_id = chat_response.json::<Value>()["chat_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    server
+        .put(&format!("/api/v1beta/assistants/{assistant_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "prompt": "Edited reviewer prompt." }))
+        .await
+        .assert_status_ok();
+
+    let detail_response = server
+        .get(&format!("/api/v1beta/me/chats/{chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    detail_response.assert_status_ok();
+    let detail: Value = detail_response.json();
+    assert_eq!(detail["frozen"], true);
+    assert_eq!(detail["assistant_snapshot"]["assistant_id"], assistant_id);
+    assert_eq!(
+        detail["assistant_snapshot"]["prompt"],
+        "Original reviewer prompt."
+    );
+
+    // Submits a message to the chat and returns the generation input of the answer
+    let submit = async |server: &TestServer,
+                        previous_message_id: Option<String>|
+           -> (String, String) {
+        let mut request = json!({ "user_message": "Review this" });
+        match previous_message_id {
+            Some(previous_message_id) => {
+                request["previous_message_id"] = json!(previous_message_id);
+            }
+            None => request["existing_chat_id"] = json!(chat_id),
+        }
+        let response = server
+            .post("/api/v1beta/me/messages/submitstream")
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .json(&request)
+            .await;
+        response.assert_status_ok();
+
+        let assistant_message = erato::db::entity::messages::Entity::find()
+            .filter(
+                erato::db::entity::messages::Column::ChatId.eq(Uuid::parse_str(&chat_id).unwrap()),
+            )
+            .filter(erato::db::entity::messages::Column::GenerationInputMessages.is_not_null())
+            .order_by_desc(erato::db::entity::messages::Column::CreatedAt)
+            .one(&app_state.db)
+            .await
+            .expect("Failed to fetch assistant message")
+            .expect("Expected an assistant message with generation input messages");
+        (
+            assistant_message.id.to_string(),
+            assistant_message
+                .generation_input_messages
+                .unwrap()
+                .to_string(),
+        )
+    };
+
+    let (message_id, generation_input) = submit(&server, None).await;
+    assert!(generation_input.contains("Original reviewer prompt."));
+    assert!(!generation_input.contains("Edited reviewer prompt."));
+
+    let refresh_response = server
+        .post(&format!(
+            "/api/v1beta/me/chats/{chat_id}/refresh-assistant-snapshot"
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    refresh_response.assert_status_ok();
+    let refreshed: Value = refresh_response.json();
+    assert_eq!(refreshed["frozen"], true);
+    assert_eq!(
+        refreshed["assistant_snapshot"]["prompt"],
+        "Edited reviewer prompt."
+    );
+
+    // Without the option, the same chat uses the live assistant
+    app_config.assistants.freeze_assistant_config = false;
+    let live_state = test_app_state(app_config, pool).await;
+    server
+        .put(&format!("/api/v1beta/assistants/{assistant_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "prompt": "Live reviewer prompt." }))
+        .await
+        .assert_status_ok();
+    let live_app: Router = router(live_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(live_state);
+    let live_server =
+        TestServer::new(live_app.into_make_service()).expect("Failed to create test server");
+
+    let detail: Value = live_server
+        .get(&format!("/api/v1beta/me/chats/{chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    assert_eq!(detail["frozen"], false);
+    assert_eq!(
+        detail["assistant_snapshot"]["prompt"],
+        "Edited reviewer prompt."
+    );
+
+    let (_, generation_input) = submit(&live_server, Some(message_id)).await;
+    assert!(generation_input.contains("Live reviewer prompt."));
+}
@@ -0,0 +1,415 @@
+//! Budget reporting API tests.
+
+use axum::http;
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+use erato::config::{BudgetCurrency, BudgetPeriod, CurrencyConversionRate, ScopedBudgetConfig};
+use erato::db::entity::{assistants, chats, messages};
+use erato::models::user::get_or_create_user;
+use erato::state::AppState;
+use sea_orm::{ActiveModelTrait, ActiveValue, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    JwtTokenBuilder, TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt,
+    admin_token, configure_admin_group, create_test_server, hermetic_app_config,
+};
+
+const PROVIDER_ID: &str = "mock-llm";
+
+async fn insert_assistant(app_state: &AppState, owner_user_id: Uuid, name: &str) -> Uuid {
+    let now = Utc::now().into();
+    assistants::ActiveModel {
+        id: ActiveValue::Set(Uuid::new_v4()),
+        owner_user_id: ActiveValue::Set(owner_user_id),
+        name: ActiveValue::Set(name.to_string()),
+        prompt: ActiveValue::Set("You are a helpful assistant.".to_string()),
+        created_at: ActiveValue::Set(now),
+        updated_at: ActiveValue::Set(now),
+        enforce_facet_settings: ActiveValue::Set(false),
+        ..Default::default()
+    }
+    .insert(&app_state.db)
+    .await
+    .expect("Failed to insert assistant")
+    .id
+}
+
+async fn insert_chat(
+    app_state: &AppState,
+    owner_user_id: Uuid,
+    assistant_id: Option<Uuid>,
+) -> Uuid {
+    chats::ActiveModel {
+        owner_user_id: ActiveValue::Set(owner_user_id.to_string()),
+        assistant_id: ActiveValue::Set(assistant_id),
+        ..Default::default()
+    }
+    .insert(&app_state.db)
+    .await
+    .expect("Failed to insert chat")
+    .id
+}
+
+/// Insert an assistant message with the given token usage into the chat.
+async fn insert_generation(
+    app_state: &AppState,
+    chat_id: Uuid,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+) {
+    insert_generation_at(
+        app_state,
+        chat_id,
+        prompt_tokens,
+        completion_tokens,
+        Utc::now(),
+    )
+    .await;
+}
+
+/// Insert an assistant message with the given token usage into the chat, created at the given time.
+async fn insert_generation_at(
+    app_state: &AppState,
+    chat_id: Uuid,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    created_at: DateTime<Utc>,
+) {
+    let now = created_at.into();
+    messages::ActiveModel {
+        id: ActiveValue::Set(Uuid::new_v4()),
+        chat_id: ActiveValue::Set(chat_id),
+        raw_message: ActiveValue::Set(json!({
+            "role": "assistant",
+            "content": [{ "content_type": "text", "text": "Hello" }]
+        })),
+        generation_parameters: ActiveValue::Set(Some(json!({
+            "generation_chat_provider_id": PROVIDER_ID
+        }))),
+        generation_metadata: ActiveValue::Set(Some(json!({
+            "used_prompt_tokens": prompt_tokens,
+            "used_completion_tokens": completion_tokens,
+            "used_total_tokens": prompt_tokens + completion_tokens,
+        }))),
+        created_at: ActiveValue::Set(now),
+        updated_at: ActiveValue::Set(now),
+        is_message_in_active_thread: ActiveValue::Set(true),
+        ..Default::default()
+    }
+    .insert(&app_state.db)
+    .await
+    .expect("Failed to insert message");
+}
+
+/// Verifies that the spending is attributed to the assistant of the chat, both
+/// for the current user and in the admin report across all users.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Seeds generations in chats of two assistants and in one chat without an
+/// assistant, and checks the split in the `breakdown=assistant` budget status
+/// as well as the JSON and CSV variants of the admin report.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_budget_breakdown_by_assistant(pool: Pool<Postgres>) {
+    let mut app_config = hermetic_app_config(None, Some("http://127.0.0.1:1/v1/".to_string()));
+    configure_admin_group(&mut app_config);
+    app_config.budget.enabled = true;
+    let provider = app_config
+        .chat_providers
+        .as_mut()
+        .and_then(|chat_providers| chat_providers.providers.get_mut(PROVIDER_ID))
+        .expect("mock provider should be configured");
+    provider.model_capabilities.cost_input_tokens_per_1m = 1.0;
+    provider.model_capabilities.cost_output_tokens_per_1m = 2.0;
+    let app_state = test_app_state(app_config, pool).await;
+
+    let user = get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let researcher_id = insert_assistant(&app_state, user.id, "Researcher").await;
+    let translator_id = insert_assistant(&app_state, user.id, "Translator | Pro, \"v2\"").await;
+
+    // Researcher: 2 generations costing 2.0 each
+    let researcher_chat_id = insert_chat(&app_state, user.id, Some(researcher_id)).await;
+    insert_generation(&app_state, researcher_chat_id, 1_000_000, 500_000).await;
+    insert_generation(&app_state, researcher_chat_id, 1_000_000, 500_000).await;
+    // Translator: 1 generation costing 2.0 + 1 generation costing 1.0
+    let translator_chat_id = insert_chat(&app_state, user.id, Some(translator_id)).await;
+    insert_generation(&app_state, translator_chat_id, 1_000_000, 500_000).await;
+    insert_generation(&app_state, translator_chat_id, 0, 500_000).await;
+    // No assistant: 1 generation costing 0.5
+    let plain_chat_id = insert_chat(&app_state, user.id, None).await;
+    insert_generation(&app_state, plain_chat_id, 500_000, 0).await;
+
+    let server = create_test_server(app_state);
+
+    // Without the query flag, no breakdown is returned
+    let status: Value = server
+        .get("/api/v1beta/me/budget")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    assert_eq!(status["current_spending"], 7.5);
+    assert!(status.get("assistants").is_none());
+
+    let response = server
+        .get("/api/v1beta/me/budget?breakdown=assistant")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let status: Value = response.json();
+    let assistants = status["assistants"]
+        .as_array()
+        .expect("Expected assistants array");
+    assert_eq!(assistants.len(), 3);
+
+    assert_eq!(assistants[0]["assistant_id"], researcher_id.to_string());
+    assert_eq!(assistants[0]["assistant_name"], "Researcher");
+    assert_eq!(assistants[0]["generation_count"], 2);
+    assert_eq!(assistants[0]["prompt_tokens"], 2_000_000);
+    assert_eq!(assistants[0]["completion_tokens"], 1_000_000);
+    assert_eq!(assistants[0]["total_tokens"], 3_000_000);
+    assert_eq!(assistants[0]["estimated_cost"], 4.0);
+
+    assert_eq!(assistants[1]["assistant_id"], translator_id.to_string());
+    assert_eq!(assistants[1]["generation_count"], 2);
+    assert_eq!(assistants[1]["estimated_cost"], 3.0);
+
+    assert!(assistants[2]["assistant_id"].is_null());
+    assert!(assistants[2]["assistant_name"].is_null());
+    assert_eq!(assistants[2]["generation_count"], 1);
+    assert_eq!(assistants[2]["estimated_cost"], 0.5);
+
+    // Admin report
+    let admin_token = admin_token();
+    let today = Utc::now().date_naive();
+    let report_url = format!("/api/v1beta/admin/budget/assistants?from={today}&to={today}");
+
+    let response = server
+        .get(&report_url)
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let report: Value = response.json();
+    assert_eq!(report["from"], today.to_string());
+    assert_eq!(report["budget_currency"], "USD");
+    let report_assistants = report["assistants"]
+        .as_array()
+        .expect("Expected assistants array");
+    assert_eq!(report_assistants, assistants);
+
+    let response = server
+        .get(&report_url)
+        .add_header(http::header::ACCEPT, "text/csv")
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    assert_eq!(
+        response.header(http::header::CONTENT_TYPE),
+        "text/csv; charset=utf-8"
+    );
+    let csv = response.text();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "assistant_id,assistant_name,generation_count,prompt_tokens,completion_tokens,reasoning_tokens,total_tokens,estimated_cost",
+            &format!("{researcher_id},Researcher,2,2000000,1000000,0,3000000,4"),
+            &format!(
+                "{translator_id},\"Translator | Pro, \"\"v2\"\"\",2,1000000,1000000,0,2000000,3"
+            ),
+            ",,1,500000,0,0,500000,0.5",
+        ]
+    );
+
+    // The period must not end before it starts
+    let response = server
+        .get("/api/v1beta/admin/budget/assistants?from=2026-02-01&to=2026-01-01")
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::BAD_REQUEST);
+
+    // Only admins see the report across all users
+    let response = server
+        .get(&report_url)
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::FORBIDDEN);
+}
+
+fn scoped_budget(
+    scope: &str,
+    limit: f64,
+    currency: BudgetCurrency,
+    period: BudgetPeriod,
+) -> ScopedBudgetConfig {
+    ScopedBudgetConfig {
+        scope: scope.to_string(),
+        limit,
+        currency,
+        period,
+    }
+}
+
+fn parse_timestamp(value: &Value) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value.as_str().expect("Expected a timestamp"))
+        .expect("Failed to parse timestamp")
+        .with_timezone(&Utc)
+}
+
+/// Verifies that all budgets applicable to a user in two groups are reported, with spending
+/// converted to the budget currency and limited to the current calendar period.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Configures a monthly EUR budget and a quarterly USD budget for two groups of the user and a
+/// budget for a group the user isn't a member of, with provider pricing in USD and a USD to EUR
+/// conversion rate. Seeds generations at the start of the current month and before the start of
+/// the current quarter, and checks spending, remaining budget and period end per scope, and that
+/// the budget is reported as exhausted once the EUR budget is used up.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_budget_scopes_for_user_groups(pool: Pool<Postgres>) {
+    let mut app_config = hermetic_app_config(None, Some("http://127.0.0.1:1/v1/".to_string()));
+    app_config.budget.enabled = true;
+    app_config.budget.max_budget = None;
+    app_config.budget.budgets = vec![
+        scoped_budget(
+            "group:engineering",
+            2.0,
+            BudgetCurrency::EUR,
+            BudgetPeriod::Monthly,
+        ),
+        scoped_budget(
+            "group:research",
+            10.0,
+            BudgetCurrency::USD,
+            BudgetPeriod::Quarterly,
+        ),
+        scoped_budget(
+            "group:sales",
+            1.0,
+            BudgetCurrency::USD,
+            BudgetPeriod::Monthly,
+        ),
+    ];
+    app_config.budget.conversion_rates = vec![CurrencyConversionRate {
+        from: BudgetCurrency::USD,
+        to: BudgetCurrency::EUR,
+        rate: 0.5,
+    }];
+    let provider = app_config
+        .chat_providers
+        .as_mut()
+        .and_then(|chat_providers| chat_providers.providers.get_mut(PROVIDER_ID))
+        .expect("mock provider should be configured");
+    provider.model_capabilities.cost_input_tokens_per_1m = 1.0;
+    provider.model_capabilities.cost_output_tokens_per_1m = 2.0;
+    provider.model_capabilities.cost_currency = Some(BudgetCurrency::USD);
+    let app_state = test_app_state(app_config, pool).await;
+
+    let user = get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let chat_id = insert_chat(&app_state, user.id, None).await;
+
+    let today = Utc::now().date_naive();
+    let month_start = NaiveDate::from_ymd_opt(today.year(), today.month(), 1).unwrap();
+    let quarter_start =
+        NaiveDate::from_ymd_opt(today.year(), today.month0() / 3 * 3 + 1, 1).unwrap();
+    let month_end = month_start + Months::new(1);
+    let quarter_end = quarter_start + Months::new(3);
+
+    // 2.0 USD now and 1.0 USD at the start of the month
+    insert_generation(&app_state, chat_id, 1_000_000, 500_000).await;
+    insert_generation_at(
+        &app_state,
+        chat_id,
+        1_000_000,
+        0,
+        month_start.and_hms_opt(0, 0, 0).unwrap().and_utc(),
+    )
+    .await;
+    // 4.0 USD in the previous quarter, which counts against none of the budgets
+    insert_generation_at(
+        &app_state,
+        chat_id,
+        2_000_000,
+        1_000_000,
+        quarter_start.and_hms_opt(0, 0, 0).unwrap().and_utc() - chrono::Duration::seconds(1),
+    )
+    .await;
+
+    let server = create_test_server(app_state.clone());
+    let token = JwtTokenBuilder::new()
+        .groups(vec!["engineering".to_string(), "research".to_string()])
+        .build();
+
+    let response = server
+        .get("/api/v1beta/me/budget")
+        .with_bearer_token(&token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let status: Value = response.json();
+    assert_eq!(status["exhausted"], false);
+    let scopes = status["scopes"].as_array().expect("Expected scopes array");
+    assert_eq!(scopes.len(), 2);
+
+    let engineering = &scopes[0];
+    assert_eq!(engineering["scope"], "group:engineering");
+    assert_eq!(engineering["budget_currency"], "EUR");
+    assert_eq!(engineering["budget_limit"], 2.0);
+    assert_eq!(engineering["current_spending"], 1.5);
+    assert_eq!(engineering["remaining"], 0.5);
+    assert_eq!(
+        parse_timestamp(&engineering["period_start"]).date_naive(),
+        month_start
+    );
+    assert_eq!(
+        parse_timestamp(&engineering["period_end"]).date_naive(),
+        month_end
+    );
+
+    let research = &scopes[1];
+    assert_eq!(research["scope"], "group:research");
+    assert_eq!(research["budget_currency"], "USD");
+    assert_eq!(research["current_spending"], 3.0);
+    assert_eq!(research["remaining"], 7.0);
+    assert_eq!(
+        parse_timestamp(&research["period_start"]).date_naive(),
+        quarter_start
+    );
+    assert_eq!(
+        parse_timestamp(&research["period_end"]).date_naive(),
+        quarter_end
+    );
+
+    // Another 1.0 USD uses up the EUR budget
+    insert_generation(&app_state, chat_id, 1_000_000, 0).await;
+    let status: Value = server
+        .get("/api/v1beta/me/budget")
+        .with_bearer_token(&token)
+        .await
+        .json();
+    assert_eq!(status["exhausted"], true);
+    assert_eq!(status["scopes"][0]["current_spending"], 2.0);
+    assert_eq!(status["scopes"][0]["remaining"], 0.0);
+    assert_eq!(status["scopes"][1]["remaining"], 6.0);
+
+    // Users outside of the groups have no applicable budgets
+    let status: Value = server
+        .get("/api/v1beta/me/budget")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    assert_eq!(status["scopes"], json!([]));
+    assert_eq!(status["exhausted"], false);
+}
@@ -0,0 +1,143 @@
+//! Integration tests for the custom instructions of chats.
+
+use axum::http;
+use axum_test::TestServer;
+use erato::config::PromptSourceSpecification;
+use erato::models::share_grant::create_share_grant;
+use erato::policy::engine::PolicyEngine;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::fixtures::{ChatHandle, FixtureChat, FixtureUser, UserHandle};
+use crate::test_utils::{TestRequestAuthExt, create_test_server, setup_mock_llm_server};
+
+const SYSTEM_PROMPT: &str = "You are the research assistant of ACME Corp.";
+const CUSTOM_INSTRUCTION: &str = "Answer only in bullet points.";
+
+/// The texts of the system messages of the prompt composed for a new message of the owner.
+async fn dry_run_system_texts(
+    server: &TestServer,
+    owner: &UserHandle,
+    chat: &ChatHandle,
+) -> Vec<String> {
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(&owner.token())
+        .json(&json!({
+            "existing_chat_id": chat.id,
+            "previous_message_id": chat.last_message_id(),
+            "user_message": "What did we ship last quarter?",
+            "dry_run": true,
+        }))
+        .await;
+    response.assert_status_ok();
+    let dry_run: Value = response.json();
+    dry_run["messages"]
+        .as_array()
+        .expect("Expected the messages of the dry run")
+        .iter()
+        .filter(|message| message["role"] == "system")
+        .filter_map(|message| message["content"].as_str().map(str::to_string))
+        .collect()
+}
+
+/// Verifies that the custom instruction of a chat is added to its prompt, until it is removed.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// The owner sets the custom instruction of a chat, which is returned with the chat, and is added
+/// to the composed prompt of a dry run right after the system prompt. Instructions longer than
+/// 2000 characters are rejected. A user the chat is shared with for reading can't set the
+/// instruction. Once the owner removes the instruction, it's no longer part of the prompt.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_custom_instruction_reaches_the_prompt(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    app_config.debug.allow_dry_run = true;
+    for provider in app_config
+        .chat_providers
+        .as_mut()
+        .expect("Expected chat providers in test config")
+        .providers
+        .values_mut()
+    {
+        provider.system_prompt = Some(PromptSourceSpecification::Static {
+            content: SYSTEM_PROMPT.to_string(),
+        });
+    }
+    let app_state = test_app_state(app_config, pool).await;
+
+    let owner = FixtureUser::new("instruction-owner").create(&app_state).await;
+    let viewer = FixtureUser::new("instruction-viewer")
+        .create(&app_state)
+        .await;
+    let chat = FixtureChat::new(&owner)
+        .with_messages(2)
+        .create(&app_state)
+        .await;
+    create_share_grant(
+        &app_state.db,
+        &PolicyEngine::new(),
+        &owner.subject(),
+        "chat".to_string(),
+        chat.id.to_string(),
+        "user".to_string(),
+        "id".to_string(),
+        viewer.id.to_string(),
+        "viewer".to_string(),
+    )
+    .await
+    .expect("Failed to share the chat");
+
+    let server = create_test_server(app_state);
+    let instruction_path = format!("/api/v1beta/me/chats/{}/custom-instruction", chat.id);
+
+    let set_response = server
+        .put(&instruction_path)
+        .with_bearer_token(&owner.token())
+        .json(&json!({ "custom_instruction": format!("  {CUSTOM_INSTRUCTION}\n") }))
+        .await;
+    set_response.assert_status_ok();
+    let set: Value = set_response.json();
+    assert_eq!(set["custom_instruction"], CUSTOM_INSTRUCTION);
+    let chat_detail: Value = server
+        .get(&format!("/api/v1beta/me/chats/{}", chat.id))
+        .with_bearer_token(&owner.token())
+        .await
+        .json();
+    assert_eq!(chat_detail["custom_instruction"], CUSTOM_INSTRUCTION);
+
+    let system_texts = dry_run_system_texts(&server, &owner, &chat).await;
+    assert_eq!(system_texts.len(), 2);
+    assert_eq!(system_texts[0], SYSTEM_PROMPT);
+    assert!(system_texts[1].ends_with(CUSTOM_INSTRUCTION));
+
+    server
+        .put(&instruction_path)
+        .with_bearer_token(&owner.token())
+        .json(&json!({ "custom_instruction": "a".repeat(2001) }))
+        .await
+        .assert_status(http::StatusCode::UNPROCESSABLE_ENTITY);
+    server
+        .put(&instruction_path)
+        .with_bearer_token(&viewer.token())
+        .json(&json!({ "custom_instruction": "Answer in French." }))
+        .await
+        .assert_status(http::StatusCode::FORBIDDEN);
+
+    let clear_response = server
+        .put(&instruction_path)
+        .with_bearer_token(&owner.token())
+        .json(&json!({ "custom_instruction": null }))
+        .await;
+    clear_response.assert_status_ok();
+    let cleared: Value = clear_response.json();
+    assert!(cleared["custom_instruction"].is_null());
+
+    let system_texts = dry_run_system_texts(&server, &owner, &chat).await;
+    assert_eq!(system_texts, vec![SYSTEM_PROMPT.to_string()]);
+}
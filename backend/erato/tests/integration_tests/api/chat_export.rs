@@ -0,0 +1,244 @@
+//! Chat export API tests.
+
+use axum::http;
+use axum_test::multipart::{MultipartForm, Part};
+use erato::state::AppState;
+use lopdf::Document;
+use sea_orm::prelude::Uuid;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::fixtures::{ChatHandle, FixtureChat, FixtureMessage, FixtureUser};
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TestRequestAuthExt, create_test_server, hermetic_app_config,
+    read_integration_test_file_bytes,
+};
+
+/// Seed a conversation of `count` messages with long texts, code blocks and a tool call.
+async fn seed_conversation(app_state: &AppState, chat: &ChatHandle, count: usize) -> Vec<Uuid> {
+    let mut message_ids: Vec<Uuid> = Vec::new();
+    for index in 0..count {
+        let raw_message = if index % 2 == 0 {
+            json!({
+                "role": "user",
+                "content": [{
+                    "content_type": "text",
+                    "text": format!("Question {index}: {}", "How does this work? ".repeat(30)),
+                }]
+            })
+        } else {
+            json!({
+                "role": "assistant",
+                "content": [
+                    {
+                        "content_type": "tool_use",
+                        "tool_call_id": format!("call_{index}"),
+                        "status": "success",
+                        "tool_name": "search_documents",
+                        "input": { "query": "how it works" },
+                        "output": { "results": [] },
+                    },
+                    {
+                        "content_type": "text",
+                        "text": format!(
+                            "## Answer {index}\n\n{}\n\n```rust\nfn main() {{\n    println!(\"hello\");\n}}\n```",
+                            "It works like this. ".repeat(40)
+                        ),
+                    }
+                ]
+            })
+        };
+        let mut message = FixtureMessage::new(chat, raw_message);
+        if let Some(previous_message_id) = message_ids.last() {
+            message = message.after(*previous_message_id);
+        }
+        message_ids.push(message.create(app_state).await.id);
+    }
+    message_ids
+}
+
+fn embedded_image_count(document: &Document) -> usize {
+    document
+        .objects
+        .values()
+        .filter_map(|object| object.as_stream().ok())
+        .filter(|stream| {
+            stream
+                .dict
+                .get(b"Subtype")
+                .and_then(|subtype| subtype.as_name())
+                .is_ok_and(|subtype| subtype == b"Image")
+        })
+        .count()
+}
+
+/// Verifies that the active thread of a chat is exported as a multi-page PDF.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Seeds a long conversation and checks the response headers, the PDF header and the page count
+/// of the export. Unsupported formats are rejected.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_export_pdf(pool: Pool<Postgres>) {
+    let app_config = hermetic_app_config(None, Some("http://127.0.0.1:1/v1/".to_string()));
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let user = FixtureUser::test_user().create(&app_state).await;
+    let chat = FixtureChat::new(&user).create(&app_state).await;
+    let chat_id = chat.id;
+    seed_conversation(&app_state, &chat, 30).await;
+
+    let response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/export?format=pdf"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    assert_eq!(
+        response.header(http::header::CONTENT_TYPE),
+        "application/pdf"
+    );
+    assert!(
+        response
+            .header(http::header::CONTENT_DISPOSITION)
+            .to_str()
+            .unwrap()
+            .starts_with("attachment")
+    );
+
+    let pdf = response.as_bytes().to_vec();
+    assert!(pdf.starts_with(b"%PDF-"));
+    let document = Document::load_mem(&pdf).expect("Export should be a valid PDF");
+    let page_count = document.get_pages().len();
+    assert!(
+        page_count >= 5,
+        "Expected the conversation to span several pages, got {page_count}"
+    );
+
+    let response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/export?format=docx"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::BAD_REQUEST);
+}
+
+/// Verifies that images uploaded to a chat are embedded in the PDF export.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// Uploads a PNG, attaches it to a user message and checks that the export contains one image.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_export_pdf_embeds_images(pool: Pool<Postgres>) {
+    let app_config = hermetic_app_config(None, Some("http://127.0.0.1:1/v1/".to_string()));
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let user = FixtureUser::test_user().create(&app_state).await;
+    let chat = FixtureChat::new(&user).create(&app_state).await;
+    let chat_id = chat.id;
+    let upload_response = server
+        .post(&format!("/api/v1beta/me/files?chat_id={chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .multipart(
+            MultipartForm::new().add_part(
+                "file",
+                Part::bytes(read_integration_test_file_bytes("image_1.png"))
+                    .file_name("image_1.png")
+                    .mime_type("image/png"),
+            ),
+        )
+        .await;
+    upload_response.assert_status_ok();
+    let upload_json: Value = upload_response.json();
+    let file_id = Uuid::parse_str(
+        upload_json["files"][0]["id"]
+            .as_str()
+            .expect("Expected file id in upload response"),
+    )
+    .unwrap();
+
+    let question = FixtureMessage::text(&chat, "user", "What is in this image?")
+        .with_files(&[file_id])
+        .create(&app_state)
+        .await;
+    FixtureMessage::text(&chat, "assistant", "A chart.")
+        .after(question.id)
+        .create(&app_state)
+        .await;
+
+    let response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/export?format=pdf"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let document = Document::load_mem(response.as_bytes()).expect("Export should be a valid PDF");
+    assert_eq!(embedded_image_count(&document), 1);
+}
+
+/// Verifies the page limit of PDF exports and narrowing down the exported messages.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// With `chat_export.max_pdf_pages = 2`, exporting a long conversation is rejected with 413,
+/// while exporting a range of two messages succeeds. Ranges that end before they start or that
+/// reference unknown messages are rejected.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_export_pdf_page_limit_and_range(pool: Pool<Postgres>) {
+    let mut app_config = hermetic_app_config(None, Some("http://127.0.0.1:1/v1/".to_string()));
+    app_config.chat_export.max_pdf_pages = 2;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let user = FixtureUser::test_user().create(&app_state).await;
+    let chat = FixtureChat::new(&user).create(&app_state).await;
+    let chat_id = chat.id;
+    let message_ids = seed_conversation(&app_state, &chat, 30).await;
+
+    let response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/export?format=pdf"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(response.text().contains("from_message_id"));
+
+    let response = server
+        .get(&format!(
+            "/api/v1beta/chats/{chat_id}/export?format=pdf&from_message_id={}&to_message_id={}",
+            message_ids[10], message_ids[11]
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let document = Document::load_mem(response.as_bytes()).expect("Export should be a valid PDF");
+    assert!(document.get_pages().len() <= 2);
+
+    let response = server
+        .get(&format!(
+            "/api/v1beta/chats/{chat_id}/export?format=pdf&from_message_id={}&to_message_id={}",
+            message_ids[11], message_ids[10]
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::BAD_REQUEST);
+
+    let response = server
+        .get(&format!(
+            "/api/v1beta/chats/{chat_id}/export?format=pdf&from_message_id={}",
+            Uuid::new_v4()
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,164 @@
+//! Integration tests for the fallback of chat providers that were removed from the configuration.
+
+use axum::http;
+use erato::config::{AppConfig, ModelPermissionRule};
+use erato::db::entity::messages;
+use mocktail::MockSet;
+use sea_orm::{EntityTrait, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TestRequestAuthExt, build_openai_text_streaming_response, create_test_server,
+    mock_llm_sse_response, parse_sse_events, setup_mock_llm_server_with_mocks,
+};
+
+const REMOVED_PROVIDER_ID: &str = "mock-llm";
+const REPLACEMENT_PROVIDER_ID: &str = "mock-llm-v2";
+
+/// The configuration after `mock-llm` was replaced by `mock-llm-v2`, which is granted by `rule`.
+fn config_without_removed_provider(app_config: &AppConfig, rule: ModelPermissionRule) -> AppConfig {
+    let mut app_config = app_config.clone();
+    let chat_providers = app_config.chat_providers.as_mut().unwrap();
+    let provider = chat_providers
+        .providers
+        .remove(REMOVED_PROVIDER_ID)
+        .expect("The mock provider should be configured");
+    chat_providers
+        .providers
+        .insert(REPLACEMENT_PROVIDER_ID.to_string(), provider);
+    chat_providers.priority_order = vec![REPLACEMENT_PROVIDER_ID.to_string()];
+    app_config.model_permissions.rules.clear();
+    app_config
+        .model_permissions
+        .rules
+        .insert(format!("allow-{}", REPLACEMENT_PROVIDER_ID), rule);
+    app_config
+}
+
+/// Verifies that chats that were last generated with a chat provider that was removed from the
+/// configuration can still be regenerated.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `sse-streaming`
+///
+/// # Test Behavior
+/// Submits a message with `mock-llm`, and then replaces it by `mock-llm-v2` in the configuration.
+/// The recent chats still report `mock-llm` as the last model, marked as unavailable.
+/// Regenerating the answer falls back to `mock-llm-v2`, announces it with a `provider_fallback`
+/// event, and records both chat providers in the generation parameters. When no chat provider is
+/// available to the user, the regeneration is rejected with 422 before it starts.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_regenerate_falls_back_from_removed_chat_provider(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        mock_llm_sse_response(then, build_openai_text_streaming_response(&["Hello!"]));
+    });
+    let (app_config, _llm_server) = setup_mock_llm_server_with_mocks(mocks).await;
+
+    let server = create_test_server(test_app_state(app_config.clone(), pool.clone()).await);
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Hi" }))
+        .await;
+    response.assert_status_ok();
+    let assistant_message_id = parse_sse_events(&response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .find(|event| event["message_type"] == "assistant_message_completed")
+        .and_then(|event| event["message_id"].as_str().map(String::from))
+        .expect("Expected assistant_message_completed event");
+
+    let app_state = test_app_state(
+        config_without_removed_provider(
+            &app_config,
+            ModelPermissionRule::AllowAll {
+                chat_provider_ids: vec![REPLACEMENT_PROVIDER_ID.to_string()],
+            },
+        ),
+        pool.clone(),
+    )
+    .await;
+    let server = create_test_server(app_state.clone());
+
+    let recent_chats: Value = server
+        .get("/api/v1beta/me/recent_chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    let last_model = &recent_chats["chats"][0]["last_model"];
+    assert_eq!(last_model["chat_provider_id"], REMOVED_PROVIDER_ID);
+    assert_eq!(last_model["available"], false);
+
+    let response = server
+        .post("/api/v1beta/me/messages/regeneratestream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "current_message_id": assistant_message_id }))
+        .await;
+    response.assert_status_ok();
+    let events: Vec<Value> = parse_sse_events(&response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .collect();
+    let fallback = events
+        .iter()
+        .find(|event| event["message_type"] == "provider_fallback")
+        .expect("Expected provider_fallback event");
+    assert_eq!(fallback["requested_chat_provider_id"], REMOVED_PROVIDER_ID);
+    assert_eq!(fallback["chat_provider_id"], REPLACEMENT_PROVIDER_ID);
+    let regenerated_message_id = events
+        .iter()
+        .find(|event| event["message_type"] == "assistant_message_completed")
+        .and_then(|event| event["message_id"].as_str().map(String::from))
+        .expect("Expected assistant_message_completed event");
+
+    let generation_parameters =
+        messages::Entity::find_by_id(Uuid::parse_str(&regenerated_message_id).unwrap())
+            .one(&app_state.db)
+            .await
+            .expect("Failed to load the regenerated message")
+            .expect("The regenerated message should exist")
+            .generation_parameters
+            .expect("The regenerated message should have generation parameters");
+    assert_eq!(
+        generation_parameters["generation_chat_provider_id"],
+        REPLACEMENT_PROVIDER_ID
+    );
+    assert_eq!(
+        generation_parameters["fallback_from_chat_provider_id"],
+        REMOVED_PROVIDER_ID
+    );
+
+    let server = create_test_server(
+        test_app_state(
+            config_without_removed_provider(
+                &app_config,
+                ModelPermissionRule::AllowForGroupMembers {
+                    chat_provider_ids: vec![REPLACEMENT_PROVIDER_ID.to_string()],
+                    groups: vec!["not-a-member".to_string()],
+                },
+            ),
+            pool,
+        )
+        .await,
+    );
+    let rejected = server
+        .post("/api/v1beta/me/messages/regeneratestream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "current_message_id": regenerated_message_id }))
+        .await;
+    assert_eq!(
+        rejected.status_code(),
+        http::StatusCode::UNPROCESSABLE_ENTITY
+    );
+    let body: Value = rejected.json();
+    assert_eq!(body["requested_chat_provider_id"], REPLACEMENT_PROVIDER_ID);
+    assert_eq!(body["available_chat_provider_ids"], json!([]));
+}
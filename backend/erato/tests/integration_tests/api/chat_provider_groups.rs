@@ -0,0 +1,145 @@
+//! Integration tests for chat provider groups.
+
+use axum::http;
+use erato::config::{ChatProviderGroupConfig, ChatProviderGroupStrategy, ModelPermissionRule};
+use erato::db::entity::messages;
+use mocktail::MockSet;
+use sea_orm::{DatabaseConnection, EntityTrait, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TestRequestAuthExt, build_openai_text_streaming_response, create_test_server,
+    mock_llm_sse_response, parse_sse_events, setup_mock_llm_server_with_mocks,
+};
+
+const GROUP_ID: &str = "mock-llm-pool";
+const HEALTHY_MEMBER_ID: &str = "mock-llm";
+const DEAD_MEMBER_ID: &str = "mock-llm-dead";
+
+/// The ID of the assistant message of a generation, and whether the generation failed.
+fn assistant_message_outcome(response: &axum_test::TestResponse) -> (String, bool) {
+    let events: Vec<Value> = parse_sse_events(response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .collect();
+    let message_id = events
+        .iter()
+        .filter(|event| {
+            event["message_type"] == "assistant_message_completed"
+                || event["message_type"] == "error"
+        })
+        .find_map(|event| event["message_id"].as_str().map(String::from))
+        .expect("Expected the ID of the assistant message");
+    let failed = events.iter().any(|event| event["message_type"] == "error");
+    (message_id, failed)
+}
+
+async fn stored_generation_parameters(db: &DatabaseConnection, message_id: &str) -> Value {
+    messages::Entity::find_by_id(Uuid::parse_str(message_id).unwrap())
+        .one(db)
+        .await
+        .expect("Failed to load the assistant message")
+        .expect("The assistant message should exist")
+        .generation_parameters
+        .expect("The assistant message should have generation parameters")
+}
+
+/// Verifies that a `least_errors` chat provider group routes around a member that is down.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `sse-streaming`
+///
+/// # Test Behavior
+/// Configures a group of two members, of which the first one points at a port nobody listens
+/// on, and submits a message in three new chats. The first generation is served by the dead
+/// member and fails. The following generations avoid it and are served by the healthy member.
+/// The group is offered as a model and reported as the chat provider of the chats, while the
+/// generation parameters record the member that served each generation.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_least_errors_chat_provider_group_avoids_dead_member(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        mock_llm_sse_response(
+            then,
+            build_openai_text_streaming_response(&["Served by the healthy member."]),
+        );
+    });
+    let (mut app_config, _server) = setup_mock_llm_server_with_mocks(mocks).await;
+    let chat_providers = app_config.chat_providers.as_mut().unwrap();
+    let mut dead_member = chat_providers.providers[HEALTHY_MEMBER_ID].clone();
+    dead_member.base_url = Some("http://127.0.0.1:1/v1/".to_string());
+    chat_providers
+        .providers
+        .insert(DEAD_MEMBER_ID.to_string(), dead_member);
+    chat_providers.priority_order = vec![GROUP_ID.to_string()];
+    app_config.chat_provider_groups.insert(
+        GROUP_ID.to_string(),
+        ChatProviderGroupConfig {
+            members: vec![DEAD_MEMBER_ID.to_string(), HEALTHY_MEMBER_ID.to_string()],
+            strategy: ChatProviderGroupStrategy::LeastErrors,
+            model_display_name: Some("Pooled model".to_string()),
+            ..Default::default()
+        },
+    );
+    app_config.model_permissions.rules.insert(
+        "allow-pool".to_string(),
+        ModelPermissionRule::AllowAll {
+            chat_provider_ids: vec![GROUP_ID.to_string()],
+        },
+    );
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let models: Value = server
+        .get("/api/v1beta/me/models")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    assert_eq!(models[0]["chat_provider_id"], GROUP_ID);
+    assert_eq!(models[0]["model_display_name"], "Pooled model");
+
+    let mut outcomes = vec![];
+    for user_message in ["First question", "Second question", "Third question"] {
+        let response = server
+            .post("/api/v1beta/me/messages/submitstream")
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .json(&json!({
+                "user_message": user_message,
+                "chat_provider_id": GROUP_ID,
+            }))
+            .await;
+        response.assert_status_ok();
+        let (message_id, failed) = assistant_message_outcome(&response);
+        let generation_parameters = stored_generation_parameters(&app_state.db, &message_id).await;
+        assert_eq!(generation_parameters["chat_provider_group_id"], GROUP_ID);
+        outcomes.push((
+            generation_parameters["generation_chat_provider_id"].clone(),
+            failed,
+        ));
+    }
+
+    assert_eq!(
+        outcomes,
+        vec![
+            (json!(DEAD_MEMBER_ID), true),
+            (json!(HEALTHY_MEMBER_ID), false),
+            (json!(HEALTHY_MEMBER_ID), false),
+        ]
+    );
+
+    let recent_chats: Value = server
+        .get("/api/v1beta/me/recent_chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    let chat = &recent_chats["chats"][0];
+    assert_eq!(chat["last_chat_provider_id"], GROUP_ID);
+    assert_eq!(chat["last_model"]["model_display_name"], "Pooled model");
+}
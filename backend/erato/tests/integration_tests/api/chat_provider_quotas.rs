@@ -0,0 +1,127 @@
+//! Integration tests for the per-user quotas of chat providers.
+
+use axum::http;
+use chrono::{DateTime, Days, NaiveTime, Utc};
+use erato::config::ChatProviderUserQuotaConfig;
+use mocktail::MockSet;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    JwtTokenBuilder, TEST_JWT_TOKEN, TestRequestAuthExt, build_openai_text_streaming_response,
+    completed_assistant_message_id, create_test_server, mock_llm_sse_response,
+    setup_mock_llm_server_with_mocks, submit_message,
+};
+
+const PROVIDER_ID: &str = "mock-llm";
+const EXEMPT_GROUP_ID: &str = "quota-exempt";
+
+/// Verifies that messages are rejected once the daily message quota of the chat provider is
+/// reached, and that exempt users are not limited.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// The chat provider allows one message per user and day. After the first message, `/me/models`
+/// reports the quota as reached, and submitting another message or regenerating the answer is
+/// rejected with 429. The body reports the usage of the limit and that it resets at the next
+/// midnight UTC. A user in an exempt group can send several messages, and gets no quota reported.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_message_quota_rejects_messages_until_reset(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        mock_llm_sse_response(then, build_openai_text_streaming_response(&["Hello!"]));
+    });
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config
+        .chat_providers
+        .as_mut()
+        .unwrap()
+        .providers
+        .get_mut(PROVIDER_ID)
+        .unwrap()
+        .user_quota = Some(ChatProviderUserQuotaConfig {
+        messages_per_day: Some(1),
+        exempt_groups: vec![EXEMPT_GROUP_ID.to_string()],
+        ..Default::default()
+    });
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+    let next_midnight = (Utc::now().date_naive() + Days::new(1))
+        .and_time(NaiveTime::MIN)
+        .and_utc();
+
+    let response = submit_message(&server, TEST_JWT_TOKEN, &json!({ "user_message": "Hi" })).await;
+    let assistant_message_id = completed_assistant_message_id(&response);
+
+    let models_response = server
+        .get("/api/v1beta/me/models")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    models_response.assert_status_ok();
+    let models: Value = models_response.json();
+    let quota = &models[0]["quota"];
+    assert_eq!(quota["chat_provider_id"], PROVIDER_ID);
+    assert_eq!(quota["exceeded"], true);
+    assert_eq!(quota["limits"][0]["used"], 1);
+
+    let rejected = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Hi again" }))
+        .await;
+    assert_eq!(rejected.status_code(), http::StatusCode::TOO_MANY_REQUESTS);
+    let body: Value = rejected.json();
+    assert_eq!(body["chat_provider_id"], PROVIDER_ID);
+    assert_eq!(body["exceeded"], true);
+    assert_eq!(
+        body["limits"],
+        json!([{
+            "window": "day",
+            "metric": "messages",
+            "used": 1,
+            "limit": 1,
+            "resets_at": body["resets_at"],
+        }])
+    );
+    let resets_at: DateTime<Utc> = body["resets_at"]
+        .as_str()
+        .expect("Expected resets_at in the error")
+        .parse()
+        .unwrap();
+    assert_eq!(resets_at, next_midnight);
+    assert!(
+        body["error"]
+            .as_str()
+            .is_some_and(|error| error.contains("quota"))
+    );
+
+    let rejected = server
+        .post("/api/v1beta/me/messages/regeneratestream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "current_message_id": assistant_message_id }))
+        .await;
+    assert_eq!(rejected.status_code(), http::StatusCode::TOO_MANY_REQUESTS);
+
+    let exempt_token = JwtTokenBuilder::new()
+        .subject("quota-exempt-user")
+        .groups(vec![EXEMPT_GROUP_ID.to_string()])
+        .build();
+    for user_message in ["Hi", "Hi again"] {
+        let request = json!({ "user_message": user_message });
+        let response = submit_message(&server, &exempt_token, &request).await;
+        completed_assistant_message_id(&response);
+    }
+    let models: Value = server
+        .get("/api/v1beta/me/models")
+        .with_bearer_token(&exempt_token)
+        .await
+        .json();
+    assert!(models[0].get("quota").is_none());
+}
@@ -0,0 +1,167 @@
+//! Integration tests for the handling of rate limit responses of chat providers.
+
+use axum::http;
+use erato::config::ModelPermissionRule;
+use erato::db::entity::messages;
+use mocktail::MockSet;
+use sea_orm::{EntityTrait, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TestRequestAuthExt, admin_token, build_openai_text_streaming_response,
+    configure_admin_group, create_test_server, mock_llm_sse_response, parse_sse_events,
+    setup_mock_llm_server_with_mocks,
+};
+
+const RATE_LIMITED_PROVIDER_ID: &str = "mock-llm";
+const BACKUP_PROVIDER_ID: &str = "mock-llm-backup";
+
+fn sse_event(response: &axum_test::TestResponse, message_type: &str) -> Option<Value> {
+    parse_sse_events(response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .find(|event| event["message_type"] == message_type)
+}
+
+/// Verifies that a rate limit response of a chat provider is reported with retry hints, and that
+/// the provider is avoided while it is cooling down.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `sse-streaming`
+///
+/// # Test Behavior
+/// Configures two chat providers, of which the preferred one responds with a 429 that carries a
+/// `Retry-After` header and an OpenAI rate limit body. The first generation fails with a
+/// `rate_limit` error that contains the retry hint and the exceeded limit, both in the SSE error
+/// event and in the stored generation metadata. The following generation is served by the other
+/// provider, and the admin status endpoint reports the cooldown of the rate limited provider.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_rate_limited_chat_provider_reports_retry_hint_and_cools_down(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        then.status(http::StatusCode::TOO_MANY_REQUESTS)
+            .headers([("Content-Type", "application/json"), ("Retry-After", "20")])
+            .json(json!({
+                "error": {
+                    "message": "Rate limit reached for gpt-4o-mini on tokens per min (TPM): Limit 30000, Used 30000, Requested 1200. Please try again in 20s.",
+                    "type": "tokens",
+                    "param": null,
+                    "code": "rate_limit_exceeded"
+                }
+            }));
+    });
+    mocks.mock(|when, then| {
+        when.post().path("/backup/v1/chat/completions");
+        mock_llm_sse_response(
+            then,
+            build_openai_text_streaming_response(&["Served by the backup provider."]),
+        );
+    });
+    let (mut app_config, llm_server) = setup_mock_llm_server_with_mocks(mocks).await;
+    configure_admin_group(&mut app_config);
+    let chat_providers = app_config.chat_providers.as_mut().unwrap();
+    let mut backup_provider = chat_providers.providers[RATE_LIMITED_PROVIDER_ID].clone();
+    backup_provider.base_url = Some(llm_server.url("/backup/v1/").to_string());
+    chat_providers
+        .providers
+        .insert(BACKUP_PROVIDER_ID.to_string(), backup_provider);
+    chat_providers.priority_order = vec![
+        RATE_LIMITED_PROVIDER_ID.to_string(),
+        BACKUP_PROVIDER_ID.to_string(),
+    ];
+    app_config.model_permissions.rules.insert(
+        "allow-backup".to_string(),
+        ModelPermissionRule::AllowAll {
+            chat_provider_ids: vec![BACKUP_PROVIDER_ID.to_string()],
+        },
+    );
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let rate_limited_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "First question" }))
+        .await;
+    rate_limited_response.assert_status_ok();
+
+    let error_event = sse_event(&rate_limited_response, "error").expect("Expected an error event");
+    assert_eq!(error_event["error_type"], "rate_limit");
+    assert_eq!(error_event["retry_after_seconds"], 20);
+    assert_eq!(error_event["scope"], "tokens");
+
+    let completed_event = sse_event(&rate_limited_response, "assistant_message_completed")
+        .expect("Expected assistant_message_completed event");
+    assert_eq!(
+        completed_event["message"]["error"]["retry_after_seconds"],
+        20
+    );
+    let message_id = Uuid::parse_str(completed_event["message_id"].as_str().unwrap()).unwrap();
+    let stored_error = messages::Entity::find_by_id(message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the assistant message")
+        .expect("The assistant message should exist")
+        .generation_metadata
+        .expect("The assistant message should have generation metadata")["error"]
+        .clone();
+    assert_eq!(stored_error["error_type"], "rate_limit");
+    assert_eq!(stored_error["retry_after_seconds"], 20);
+    assert_eq!(stored_error["scope"], "tokens");
+
+    let backup_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Second question" }))
+        .await;
+    backup_response.assert_status_ok();
+    assert!(sse_event(&backup_response, "error").is_none());
+    let completed_event = sse_event(&backup_response, "assistant_message_completed")
+        .expect("Expected assistant_message_completed event");
+    let message_id = Uuid::parse_str(completed_event["message_id"].as_str().unwrap()).unwrap();
+    let generation_parameters = messages::Entity::find_by_id(message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the assistant message")
+        .expect("The assistant message should exist")
+        .generation_parameters
+        .expect("The assistant message should have generation parameters");
+    assert_eq!(
+        generation_parameters["generation_chat_provider_id"],
+        BACKUP_PROVIDER_ID
+    );
+
+    let admin_token = admin_token();
+    let status: Value = server
+        .get("/api/v1beta/admin/chat-providers/status")
+        .with_bearer_token(&admin_token)
+        .await
+        .json();
+    let chat_providers = status["chat_providers"].as_array().unwrap();
+    let provider_status = |chat_provider_id: &str| {
+        chat_providers
+            .iter()
+            .find(|provider| provider["chat_provider_id"] == chat_provider_id)
+            .unwrap_or_else(|| panic!("Expected the status of {chat_provider_id}"))
+    };
+    let rate_limited_status = provider_status(RATE_LIMITED_PROVIDER_ID);
+    assert_eq!(rate_limited_status["cooling_down"], true);
+    assert!(rate_limited_status["cooldown_until"].is_string());
+    assert_eq!(rate_limited_status["rate_limit_scope"], "tokens");
+    let backup_status = provider_status(BACKUP_PROVIDER_ID);
+    assert_eq!(backup_status["cooling_down"], false);
+    assert!(backup_status["cooldown_until"].is_null());
+
+    server
+        .get("/api/v1beta/admin/chat-providers/status")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,219 @@
+//! Chat read state and unread indicator tests.
+
+use axum::http;
+use axum_test::TestServer;
+use chrono::Duration;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::fixtures::{FixtureChat, FixtureUser};
+use crate::test_utils::{
+    JwtTokenBuilder, TEST_JWT_TOKEN, TEST_USER_ISSUER, TestRequestAuthExt, create_test_server,
+    hermetic_app_config,
+};
+
+async fn recent_chat(server: &TestServer, token: &str, chat_id: &str) -> Value {
+    let response = server
+        .get("/api/v1beta/me/recent_chats")
+        .with_bearer_token(token)
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    body["chats"]
+        .as_array()
+        .expect("Expected chats array")
+        .iter()
+        .find(|chat| chat["id"] == chat_id)
+        .cloned()
+        .expect("Chat should be listed in recent chats")
+}
+
+async fn put_read_state(
+    server: &TestServer,
+    token: &str,
+    chat_id: &str,
+    last_read_message_id: &str,
+) -> axum_test::TestResponse {
+    server
+        .put(&format!("/api/v1beta/me/chats/{chat_id}/read-state"))
+        .with_bearer_token(token)
+        .json(&json!({ "last_read_message_id": last_read_message_id }))
+        .await
+}
+
+/// Test that the unread count of a chat decreases after updating its read state.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Seeds a chat with two user and two assistant messages. Without a read state, both assistant
+/// messages are unread. Marking the first assistant message as read leaves one unread message,
+/// marking the last one as read leaves none. Messages of other chats are rejected.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_read_state_updates_unread_count(pool: Pool<Postgres>) {
+    let app_config = hermetic_app_config(None, Some("http://127.0.0.1:1/v1/".to_string()));
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let user = FixtureUser::test_user().create(&app_state).await;
+    let chat = FixtureChat::new(&user)
+        .with_messages(4)
+        .with_message_spacing(Duration::seconds(1))
+        .create(&app_state)
+        .await;
+    let chat_id = chat.id.to_string();
+    let first_answer = chat.message_ids[1];
+    let second_answer = chat.message_ids[3];
+
+    let chat = recent_chat(&server, TEST_JWT_TOKEN, &chat_id).await;
+    assert_eq!(chat["unread_count"], 2);
+    assert!(chat.get("last_read_message_id").is_none());
+
+    let response =
+        put_read_state(&server, TEST_JWT_TOKEN, &chat_id, &first_answer.to_string()).await;
+    response.assert_status_ok();
+    let read_state: Value = response.json();
+    assert_eq!(read_state["chat_id"], chat_id.as_str());
+    assert_eq!(read_state["last_read_message_id"], first_answer.to_string());
+    assert_eq!(read_state["unread_count"], 1);
+
+    let chat = recent_chat(&server, TEST_JWT_TOKEN, &chat_id).await;
+    assert_eq!(chat["unread_count"], 1);
+    assert_eq!(chat["last_read_message_id"], first_answer.to_string());
+
+    put_read_state(
+        &server,
+        TEST_JWT_TOKEN,
+        &chat_id,
+        &second_answer.to_string(),
+    )
+    .await
+    .assert_status_ok();
+    let chat = recent_chat(&server, TEST_JWT_TOKEN, &chat_id).await;
+    assert_eq!(chat["unread_count"], 0);
+    assert_eq!(chat["last_read_message_id"], second_answer.to_string());
+
+    // Messages of other chats can't be used as the read position
+    let other_chat = FixtureChat::new(&user)
+        .with_messages(2)
+        .create(&app_state)
+        .await;
+    let other_message = other_chat.message_ids[1];
+    let response = put_read_state(
+        &server,
+        TEST_JWT_TOKEN,
+        &chat_id,
+        &other_message.to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+
+    let response = put_read_state(&server, TEST_JWT_TOKEN, &chat_id, "not-a-uuid").await;
+    assert_eq!(response.status_code(), http::StatusCode::BAD_REQUEST);
+}
+
+/// Test that users of a shared chat have independent read states.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// The owner shares a chat with a second user, who marks the latest assistant message as read.
+/// The owner's unread count is unaffected, and the owner's own read state doesn't change the
+/// second user's. Users without access to the chat can't set a read state for it.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_read_state_is_independent_per_user(pool: Pool<Postgres>) {
+    let app_config = hermetic_app_config(None, Some("http://127.0.0.1:1/v1/".to_string()));
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let grantee_subject = "chat-read-state-grantee";
+    let grantee = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        grantee_subject,
+        None,
+    )
+    .await
+    .expect("Failed to create grantee user");
+    let grantee_token = JwtTokenBuilder::new()
+        .subject(grantee_subject)
+        .email("grantee@example.com")
+        .name("grantee")
+        .build();
+    let outsider_token = JwtTokenBuilder::new()
+        .subject("chat-read-state-outsider")
+        .email("outsider@example.com")
+        .name("outsider")
+        .build();
+
+    let user = FixtureUser::test_user().create(&app_state).await;
+    let chat = FixtureChat::new(&user)
+        .with_messages(4)
+        .with_message_spacing(Duration::seconds(1))
+        .create(&app_state)
+        .await;
+    let chat_id = chat.id.to_string();
+    let first_answer = chat.message_ids[1];
+    let second_answer = chat.message_ids[3];
+
+    let response = server
+        .post("/api/v1beta/share-grants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "resource_type": "chat",
+            "resource_id": chat_id,
+            "subject_type": "user",
+            "subject_id_type": "id",
+            "subject_id": grantee.id.to_string(),
+            "role": "viewer",
+        }))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::CREATED);
+
+    // The grantee reads the whole chat
+    let response = put_read_state(
+        &server,
+        &grantee_token,
+        &chat_id,
+        &second_answer.to_string(),
+    )
+    .await;
+    response.assert_status_ok();
+    assert_eq!(response.json::<Value>()["unread_count"], 0);
+
+    // The owner still sees both answers as unread
+    let chat = recent_chat(&server, TEST_JWT_TOKEN, &chat_id).await;
+    assert_eq!(chat["unread_count"], 2);
+    assert!(chat.get("last_read_message_id").is_none());
+
+    // The owner's read state doesn't move the grantee's
+    let response =
+        put_read_state(&server, TEST_JWT_TOKEN, &chat_id, &first_answer.to_string()).await;
+    response.assert_status_ok();
+    assert_eq!(response.json::<Value>()["unread_count"], 1);
+    let response = put_read_state(
+        &server,
+        &grantee_token,
+        &chat_id,
+        &second_answer.to_string(),
+    )
+    .await;
+    response.assert_status_ok();
+    assert_eq!(response.json::<Value>()["unread_count"], 0);
+
+    // Users without access to the chat can't track a read state for it
+    let response = put_read_state(
+        &server,
+        &outsider_token,
+        &chat_id,
+        &second_answer.to_string(),
+    )
+    .await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,143 @@
+//! Integration tests for the response language of chats.
+
+use axum::http;
+use axum_test::TestServer;
+use erato::db::entity::messages;
+use sea_orm::{DatabaseConnection, EntityTrait, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TestRequestAuthExt, completed_assistant_message_id, create_chat,
+    create_test_server, setup_mock_llm_server, submit_message,
+};
+
+/// Long enough for the language detection, which skips messages shorter than 20 characters.
+const GERMAN_MESSAGE: &str = "Kannst du mir bitte den angehängten Bericht zusammenfassen und die \
+    wichtigsten Risiken für das nächste Quartal hervorheben?";
+
+/// Submits `request` as the next message of the chat, and returns the ID of the assistant message
+/// and the response language recorded in its generation parameters.
+async fn submit_and_get_response_language(
+    server: &TestServer,
+    db: &DatabaseConnection,
+    request: Value,
+) -> (String, Value) {
+    let response = submit_message(server, TEST_JWT_TOKEN, &request).await;
+    let message_id = completed_assistant_message_id(&response);
+    let message_uuid = Uuid::parse_str(&message_id).expect("Invalid assistant message ID");
+    let generation_parameters = messages::Entity::find_by_id(message_uuid)
+        .one(db)
+        .await
+        .expect("Failed to load the assistant message")
+        .expect("The assistant message should exist")
+        .generation_parameters
+        .expect("The assistant message should have generation parameters");
+    (message_id, generation_parameters["response_language"].clone())
+}
+
+/// Verifies the precedence of the response language of a chat over the other sources.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `sse-streaming`
+///
+/// # Test Behavior
+/// The user sets the response language of a chat, which is returned with the chat, and is used
+/// for a message too short to detect its language. The detected language of a longer message and
+/// an explicit `response_language` of the request take precedence over it. Values that aren't
+/// language codes are rejected, and once the language is removed, the preferred language of the
+/// user is used again.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_response_language_precedence(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let chat_id = create_chat(&server, TEST_JWT_TOKEN).await;
+    let language_path = format!("/api/v1beta/me/chats/{chat_id}/response-language");
+
+    let set_response = server
+        .put(&language_path)
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "response_language": "fr-CA" }))
+        .await;
+    set_response.assert_status_ok();
+    let set: Value = set_response.json();
+    assert_eq!(set["response_language"], "fr");
+    let chat_detail: Value = server
+        .get(&format!("/api/v1beta/me/chats/{chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    assert_eq!(chat_detail["response_language"], "fr");
+
+    let (message_id, response_language) = submit_and_get_response_language(
+        &server,
+        &app_state.db,
+        json!({ "existing_chat_id": chat_id, "user_message": "Hi" }),
+    )
+    .await;
+    assert_eq!(response_language["code"], "fr");
+    assert_eq!(response_language["source"], "chat_setting");
+
+    let (message_id, response_language) = submit_and_get_response_language(
+        &server,
+        &app_state.db,
+        json!({
+            "existing_chat_id": chat_id,
+            "previous_message_id": message_id,
+            "user_message": GERMAN_MESSAGE,
+        }),
+    )
+    .await;
+    assert_eq!(response_language["code"], "de");
+    assert_eq!(response_language["source"], "detected_message_language");
+
+    let (message_id, response_language) = submit_and_get_response_language(
+        &server,
+        &app_state.db,
+        json!({
+            "existing_chat_id": chat_id,
+            "previous_message_id": message_id,
+            "user_message": GERMAN_MESSAGE,
+            "response_language": "es",
+        }),
+    )
+    .await;
+    assert_eq!(response_language["code"], "es");
+    assert_eq!(response_language["source"], "request_override");
+
+    server
+        .put(&language_path)
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "response_language": "french" }))
+        .await
+        .assert_status(http::StatusCode::UNPROCESSABLE_ENTITY);
+
+    let clear_response = server
+        .put(&language_path)
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "response_language": null }))
+        .await;
+    clear_response.assert_status_ok();
+    let cleared: Value = clear_response.json();
+    assert!(cleared["response_language"].is_null());
+
+    let (_, response_language) = submit_and_get_response_language(
+        &server,
+        &app_state.db,
+        json!({
+            "existing_chat_id": chat_id,
+            "previous_message_id": message_id,
+            "user_message": "Hi",
+        }),
+    )
+    .await;
+    assert_eq!(response_language["code"], "en");
+    assert_eq!(response_language["source"], "profile_preference");
+}
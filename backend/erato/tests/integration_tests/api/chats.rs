@@ -18,8 +18,10 @@ use sqlx::postgres::Postgres;
 use std::collections::HashMap;
 
 use crate::test_app_state;
+use crate::test_utils::fixtures::{FixtureAssistant, FixtureChat, FixtureUser};
 use crate::test_utils::{
-    TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt, setup_mock_llm_server,
+    JwtTokenBuilder, TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt,
+    create_test_server, extract_chat_id, parse_sse_events, setup_mock_llm_server,
 };
 
 /// Test retrieving recent chats for the authenticated user.
@@ -382,40 +384,16 @@ async fn test_archive_all_chats_endpoint_archives_only_unarchived(pool: Pool<Pos
     let (app_config, _server) = setup_mock_llm_server(None).await;
     let app_state = test_app_state(app_config, pool).await;
 
-    let user = erato::models::user::get_or_create_user(
-        &app_state.db,
-        TEST_USER_ISSUER,
-        TEST_USER_SUBJECT,
-        None,
-    )
-    .await
-    .expect("Failed to create user");
-
-    let owner_user_id = user.id.to_string();
+    let user = FixtureUser::test_user().create(&app_state).await;
 
     let archived_at_before: sqlx::types::chrono::DateTime<sqlx::types::chrono::FixedOffset> =
         (Utc::now() - chrono::Duration::days(2)).into();
 
-    let active_chat_1 = chats::ActiveModel {
-        owner_user_id: ActiveValue::Set(owner_user_id.clone()),
-        archived_at: ActiveValue::Set(None),
-        ..Default::default()
-    }
-    .insert(&app_state.db)
-    .await
-    .expect("Failed to create active chat 1");
-
-    let active_chat_2 = chats::ActiveModel {
-        owner_user_id: ActiveValue::Set(owner_user_id.clone()),
-        archived_at: ActiveValue::Set(None),
-        ..Default::default()
-    }
-    .insert(&app_state.db)
-    .await
-    .expect("Failed to create active chat 2");
+    let active_chat_1 = FixtureChat::new(&user).create(&app_state).await;
+    let active_chat_2 = FixtureChat::new(&user).create(&app_state).await;
 
     let already_archived_chat = chats::ActiveModel {
-        owner_user_id: ActiveValue::Set(owner_user_id),
+        owner_user_id: ActiveValue::Set(user.id.to_string()),
         archived_at: ActiveValue::Set(Some(archived_at_before)),
         ..Default::default()
     }
@@ -1751,6 +1729,128 @@ async fn test_create_chat_with_assistant(pool: Pool<Postgres>) {
     );
 }
 
+/// Test that creating a chat with an assistant that has a welcome message adds it to the chat.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Verifies that:
+/// - Creating a chat with an assistant that has a `welcome_message` returns a `welcome_message_id`
+/// - The welcome message is listed as the first assistant message of the chat
+/// - The welcome message is not sent to the LLM, while the assistant prompt still is
+/// - Creating a chat with an assistant without a welcome message returns no `welcome_message_id`
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_create_chat_with_assistant_welcome_message(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let welcome_text = "Hi! I can help you draft release notes.";
+    let assistant_response = server
+        .post("/api/v1beta/assistants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "name": "Release Notes Assistant",
+            "prompt": "You are a release notes assistant.",
+            "welcome_message": welcome_text
+        }))
+        .await;
+    assistant_response.assert_status(http::StatusCode::CREATED);
+    let assistant_json: Value = assistant_response.json();
+    assert_eq!(assistant_json["welcome_message"], welcome_text);
+    let assistant_id = assistant_json["id"].as_str().unwrap();
+
+    // Creating the chat adds the welcome message
+    let create_chat_response = server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "assistant_id": assistant_id }))
+        .await;
+    create_chat_response.assert_status_ok();
+    let create_chat_json: Value = create_chat_response.json();
+    let chat_id = create_chat_json["chat_id"].as_str().unwrap();
+    let welcome_message_id = create_chat_json["welcome_message_id"]
+        .as_str()
+        .expect("Expected welcome_message_id in response");
+
+    let messages_response = server
+        .get(&format!("/api/v1beta/chats/{}/messages", chat_id))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    messages_response.assert_status_ok();
+    let messages_json: Value = messages_response.json();
+    let messages = messages_json["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 1, "Expected only the welcome message");
+    assert_eq!(messages[0]["id"], welcome_message_id);
+    assert_eq!(messages[0]["role"], "assistant");
+    assert_eq!(messages[0]["content"][0]["text"], welcome_text);
+
+    // Reply to the welcome message
+    let message_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "previous_message_id": welcome_message_id,
+            "user_message": "Please draft notes for version 2.0"
+        }))
+        .await;
+    message_response.assert_status_ok();
+
+    let chat_uuid = Uuid::parse_str(chat_id).unwrap();
+    let generated_message = messages::Entity::find()
+        .filter(messages::Column::ChatId.eq(chat_uuid))
+        .filter(messages::Column::GenerationInputMessages.is_not_null())
+        .order_by_desc(messages::Column::CreatedAt)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to fetch message with generation inputs")
+        .expect("Message not found");
+    let gen_inputs_str =
+        serde_json::to_string(&generated_message.generation_input_messages.unwrap()).unwrap();
+    assert!(
+        gen_inputs_str.contains("You are a release notes assistant."),
+        "Assistant prompt should be in generation inputs"
+    );
+    assert!(
+        gen_inputs_str.contains("Please draft notes for version 2.0"),
+        "User message should be in generation inputs"
+    );
+    assert!(
+        !gen_inputs_str.contains(welcome_text),
+        "Welcome message must not be sent to the LLM"
+    );
+
+    // An assistant without a welcome message doesn't add one
+    let plain_assistant_response = server
+        .post("/api/v1beta/assistants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "name": "Plain Assistant",
+            "prompt": "You are a plain assistant."
+        }))
+        .await;
+    plain_assistant_response.assert_status(http::StatusCode::CREATED);
+    let plain_assistant_json: Value = plain_assistant_response.json();
+    assert!(plain_assistant_json.get("welcome_message").is_none());
+
+    let plain_chat_response = server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "assistant_id": plain_assistant_json["id"] }))
+        .await;
+    plain_chat_response.assert_status_ok();
+    let plain_chat_json: Value = plain_chat_response.json();
+    assert!(plain_chat_json.get("welcome_message_id").is_none());
+}
+
 /// Test the frequent_assistants endpoint that returns assistants ordered by usage frequency.
 ///
 /// # Test Categories
@@ -1967,3 +2067,813 @@ async fn test_frequent_assistants_endpoint(pool: Pool<Postgres>) {
         "First should still be the most frequent"
     );
 }
+
+/// Create a chat owned by the test user with `count` messages, one minute apart.
+/// Returns the chat ID and the message IDs, oldest first.
+async fn insert_chat_with_timed_messages(
+    app_state: &erato::state::AppState,
+    count: usize,
+) -> (Uuid, Vec<String>) {
+    let user = FixtureUser::test_user().create(app_state).await;
+    let chat = FixtureChat::new(&user)
+        .with_messages(count)
+        .with_message_spacing(Duration::minutes(1))
+        .create(app_state)
+        .await;
+    let message_ids = chat.message_ids.iter().map(Uuid::to_string).collect();
+    (chat.id, message_ids)
+}
+
+fn message_ids_of(body: &Value) -> Vec<String> {
+    body["messages"]
+        .as_array()
+        .expect("Expected messages array")
+        .iter()
+        .map(|message| message["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+/// Test the `order` query parameter of the chat messages endpoint.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Verifies that messages are listed newest first by default and with `order=desc`,
+/// oldest first with `order=asc`, and that an unknown order is rejected.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_messages_order(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let (chat_id, ids) = insert_chat_with_timed_messages(&app_state, 4).await;
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let mut newest_first = ids.clone();
+    newest_first.reverse();
+
+    let default_response = server
+        .get(&format!("/api/v1beta/chats/{}/messages", chat_id))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    default_response.assert_status_ok();
+    let default_body: Value = default_response.json();
+    assert_eq!(message_ids_of(&default_body), newest_first);
+    assert!(default_body.get("next_cursor").is_none());
+    assert!(default_body.get("prev_cursor").is_none());
+
+    let desc_response = server
+        .get(&format!(
+            "/api/v1beta/chats/{}/messages?order=desc",
+            chat_id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    desc_response.assert_status_ok();
+    assert_eq!(message_ids_of(&desc_response.json()), newest_first);
+
+    let asc_response = server
+        .get(&format!("/api/v1beta/chats/{}/messages?order=asc", chat_id))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    asc_response.assert_status_ok();
+    assert_eq!(message_ids_of(&asc_response.json()), ids);
+
+    let invalid_response = server
+        .get(&format!(
+            "/api/v1beta/chats/{}/messages?order=sideways",
+            chat_id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    invalid_response.assert_status(http::StatusCode::BAD_REQUEST);
+}
+
+/// Test cursor pagination of the chat messages endpoint.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Walks a chat of five messages two at a time via `next_cursor`, then back via
+/// `prev_cursor`, checking page contents and cursor presence at both ends.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_messages_cursor_pagination(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let (chat_id, ids) = insert_chat_with_timed_messages(&app_state, 5).await;
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let fetch = |query: String| {
+        let server = &server;
+        async move {
+            let response = server
+                .get(&format!("/api/v1beta/chats/{}/messages?{}", chat_id, query))
+                .with_bearer_token(TEST_JWT_TOKEN)
+                .await;
+            response.assert_status_ok();
+            response.json::<Value>()
+        }
+    };
+
+    // Newest first: [4, 3], [2, 1], [0]
+    let page_1 = fetch("limit=2".to_string()).await;
+    assert_eq!(
+        message_ids_of(&page_1),
+        vec![ids[4].clone(), ids[3].clone()]
+    );
+    assert!(page_1.get("prev_cursor").is_none());
+    let next = page_1["next_cursor"]
+        .as_str()
+        .expect("Expected next_cursor");
+
+    let page_2 = fetch(format!("limit=2&cursor={}", next)).await;
+    assert_eq!(
+        message_ids_of(&page_2),
+        vec![ids[2].clone(), ids[1].clone()]
+    );
+    assert_eq!(page_2["stats"]["total_count"].as_i64(), Some(5));
+    assert_eq!(page_2["stats"]["has_more"].as_bool(), Some(true));
+    let next = page_2["next_cursor"]
+        .as_str()
+        .expect("Expected next_cursor");
+
+    let page_3 = fetch(format!("limit=2&cursor={}", next)).await;
+    assert_eq!(message_ids_of(&page_3), vec![ids[0].clone()]);
+    assert_eq!(page_3["stats"]["has_more"].as_bool(), Some(false));
+    assert!(page_3.get("next_cursor").is_none());
+    let prev = page_3["prev_cursor"]
+        .as_str()
+        .expect("Expected prev_cursor");
+
+    // Walking back returns the same pages, still newest first
+    let back_2 = fetch(format!("limit=2&cursor={}", prev)).await;
+    assert_eq!(message_ids_of(&back_2), message_ids_of(&page_2));
+    let prev = back_2["prev_cursor"]
+        .as_str()
+        .expect("Expected prev_cursor");
+
+    let back_1 = fetch(format!("limit=2&cursor={}", prev)).await;
+    assert_eq!(message_ids_of(&back_1), message_ids_of(&page_1));
+    assert!(back_1.get("prev_cursor").is_none());
+    assert!(back_1["next_cursor"].is_string());
+
+    // Ascending order walks the other way
+    let asc_page_1 = fetch("limit=2&order=asc".to_string()).await;
+    assert_eq!(
+        message_ids_of(&asc_page_1),
+        vec![ids[0].clone(), ids[1].clone()]
+    );
+    let next = asc_page_1["next_cursor"]
+        .as_str()
+        .expect("Expected next_cursor");
+    let asc_page_2 = fetch(format!("limit=2&order=asc&cursor={}", next)).await;
+    assert_eq!(
+        message_ids_of(&asc_page_2),
+        vec![ids[2].clone(), ids[3].clone()]
+    );
+
+    // Garbage cursors are rejected
+    let invalid_response = server
+        .get(&format!(
+            "/api/v1beta/chats/{}/messages?cursor=not-a-cursor",
+            chat_id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    invalid_response.assert_status(http::StatusCode::BAD_REQUEST);
+}
+
+/// Test that chat messages are ordered by instant regardless of the offset they were written with.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Inserts messages whose creation times were written with different UTC offsets, in an order
+/// that differs from both their instants and their local wall-clock times. The listing is ordered
+/// by instant, with the message ID breaking ties between messages of the same instant, and all
+/// timestamps are returned as RFC3339 with a `+00:00` offset.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_messages_order_with_skewed_offsets(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let user = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        TEST_USER_SUBJECT,
+        None,
+    )
+    .await
+    .expect("Failed to create user");
+    let chat = chats::ActiveModel {
+        owner_user_id: ActiveValue::Set(user.id.to_string()),
+        ..Default::default()
+    }
+    .insert(&app_state.db)
+    .await
+    .expect("Failed to insert chat");
+
+    // The same hour written by instances in different timezones, in insertion order
+    let created_ats = [
+        "2025-03-01T18:20:00+09:00", // 09:20 UTC
+        "2025-03-01T02:05:00-07:00", // 09:05 UTC
+        "2025-03-01T14:40:00+05:30", // 09:10 UTC
+        "2025-03-01T09:10:00+00:00", // 09:10 UTC
+        "2025-03-01T09:00:00Z",      // 09:00 UTC
+    ];
+    let mut inserted = Vec::new();
+    for (i, created_at) in created_ats.iter().enumerate() {
+        let created_at = chrono::DateTime::parse_from_rfc3339(created_at).unwrap();
+        let message = messages::ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            chat_id: ActiveValue::Set(chat.id),
+            raw_message: ActiveValue::Set(json!({
+                "role": "user",
+                "content": [{ "content_type": "text", "text": format!("Message {}", i) }]
+            })),
+            created_at: ActiveValue::Set(created_at),
+            updated_at: ActiveValue::Set(created_at),
+            is_message_in_active_thread: ActiveValue::Set(true),
+            ..Default::default()
+        }
+        .insert(&app_state.db)
+        .await
+        .expect("Failed to insert message");
+        inserted.push((created_at.with_timezone(&Utc), message.id));
+    }
+    inserted.sort();
+    let expected_ids: Vec<String> = inserted.iter().map(|(_, id)| id.to_string()).collect();
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let response = server
+        .get(&format!("/api/v1beta/chats/{}/messages?order=asc", chat.id))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    assert_eq!(message_ids_of(&body), expected_ids);
+    let created_ats: Vec<&str> = body["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| message["created_at"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        created_ats,
+        vec![
+            "2025-03-01T09:00:00+00:00",
+            "2025-03-01T09:05:00+00:00",
+            "2025-03-01T09:10:00+00:00",
+            "2025-03-01T09:10:00+00:00",
+            "2025-03-01T09:20:00+00:00",
+        ]
+    );
+
+    // Walking the same listing one message at a time yields the same order
+    let mut walked_ids = Vec::new();
+    let mut url = format!("/api/v1beta/chats/{}/messages?order=asc&limit=1", chat.id);
+    loop {
+        let page: Value = server
+            .get(&url)
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .await
+            .json();
+        walked_ids.extend(message_ids_of(&page));
+        let Some(next) = page["next_cursor"].as_str() else {
+            break;
+        };
+        url = format!(
+            "/api/v1beta/chats/{}/messages?order=asc&limit=1&cursor={}",
+            chat.id, next
+        );
+    }
+    assert_eq!(walked_ids, expected_ids);
+}
+
+/// Test paging backward through a long chat while new messages are appended.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Seeds a chat with 300 messages and loads it like a chat view does: the latest page first
+/// with `order=desc&page_order=asc`, then older pages via `stats.next_cursor`, appending a new
+/// message before every request. Verifies that each page is returned oldest first, that the
+/// pages cover the seeded messages without gaps or duplicates, that walking back via
+/// `stats.prev_cursor` returns the latest seeded page and then exactly the appended messages,
+/// and that combining `cursor` and `offset` is rejected.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_messages_backward_paging_while_appending(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let (chat_id, ids) = insert_chat_with_timed_messages(&app_state, 300).await;
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let fetch = |query: String| {
+        let server = &server;
+        async move {
+            let response = server
+                .get(&format!("/api/v1beta/chats/{}/messages?{}", chat_id, query))
+                .with_bearer_token(TEST_JWT_TOKEN)
+                .await;
+            response.assert_status_ok();
+            response.json::<Value>()
+        }
+    };
+
+    let mut appended_ids = Vec::new();
+    let mut append_message = async || {
+        let created_at: chrono::DateTime<chrono::FixedOffset> = Utc::now().into();
+        let message = messages::ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            chat_id: ActiveValue::Set(chat_id),
+            raw_message: ActiveValue::Set(json!({
+                "role": "user",
+                "content": [{ "content_type": "text", "text": "Appended message" }]
+            })),
+            created_at: ActiveValue::Set(created_at),
+            updated_at: ActiveValue::Set(created_at),
+            is_message_in_active_thread: ActiveValue::Set(true),
+            ..Default::default()
+        }
+        .insert(&app_state.db)
+        .await
+        .expect("Failed to append message");
+        appended_ids.push(message.id.to_string());
+    };
+
+    let first_page = fetch("limit=40&order=desc&page_order=asc".to_string()).await;
+    let mut loaded = message_ids_of(&first_page);
+    assert_eq!(loaded, ids[260..].to_vec());
+    assert!(first_page["stats"]["prev_cursor"].is_null());
+    let mut next_cursor = first_page["stats"]["next_cursor"]
+        .as_str()
+        .map(String::from);
+    let mut pages = 1;
+
+    while let Some(cursor) = next_cursor {
+        append_message().await;
+        let page = fetch(format!(
+            "limit=40&order=desc&page_order=asc&cursor={}",
+            cursor
+        ))
+        .await;
+        let mut page_ids = message_ids_of(&page);
+        assert!(!page_ids.is_empty());
+        // Each page is oldest first and strictly older than everything loaded so far
+        let first_loaded = ids.iter().position(|id| *id == loaded[0]).unwrap();
+        let page_start = first_loaded - page_ids.len();
+        assert_eq!(page_ids, ids[page_start..first_loaded].to_vec());
+        page_ids.append(&mut loaded);
+        loaded = page_ids;
+        next_cursor = page["stats"]["next_cursor"].as_str().map(String::from);
+        pages += 1;
+    }
+
+    assert_eq!(pages, 8);
+    assert_eq!(loaded, ids);
+    let unique: std::collections::HashSet<&String> = loaded.iter().collect();
+    assert_eq!(unique.len(), 300);
+
+    // Walking back towards the newest messages returns the first page again, and then exactly
+    // the messages appended in the meantime
+    append_message().await;
+    let second_page = fetch(format!(
+        "limit=40&order=desc&page_order=asc&cursor={}",
+        first_page["stats"]["next_cursor"].as_str().unwrap()
+    ))
+    .await;
+    let prev_cursor = second_page["stats"]["prev_cursor"]
+        .as_str()
+        .expect("Expected prev_cursor");
+    let back_page = fetch(format!(
+        "limit=40&order=desc&page_order=asc&cursor={}",
+        prev_cursor
+    ))
+    .await;
+    assert_eq!(message_ids_of(&back_page), ids[260..].to_vec());
+    let prev_cursor = back_page["stats"]["prev_cursor"]
+        .as_str()
+        .expect("Expected prev_cursor towards the appended messages");
+    let newest_page = fetch(format!(
+        "limit=40&order=desc&page_order=asc&cursor={}",
+        prev_cursor
+    ))
+    .await;
+    assert_eq!(message_ids_of(&newest_page), appended_ids);
+    assert!(newest_page["stats"]["prev_cursor"].is_null());
+
+    // A cursor and an offset can't be combined
+    let mixed_response = server
+        .get(&format!(
+            "/api/v1beta/chats/{}/messages?offset=10&cursor={}",
+            chat_id, prev_cursor
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    mixed_response.assert_status(http::StatusCode::BAD_REQUEST);
+}
+
+/// Test listing the recent chats of a specific assistant.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Creates chats with two different assistants and without an assistant, and verifies
+/// that `/me/chats/by-assistant/{assistant_id}` only returns the chats of the requested
+/// assistant, respects pagination and `include_archived`, and returns 404 for unknown or
+/// inaccessible assistants.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_recent_chats_by_assistant(pool: Pool<Postgres>) {
+    let app_state = test_app_state(crate::test_utils::hermetic_app_config(None, None), pool).await;
+    let user = FixtureUser::test_user().create(&app_state).await;
+    let other_user = FixtureUser::new("other-subject-for-assistant-chats")
+        .create(&app_state)
+        .await;
+
+    let first_assistant = FixtureAssistant::new(&user, "First Assistant")
+        .create(&app_state)
+        .await;
+    let second_assistant = FixtureAssistant::new(&user, "Second Assistant")
+        .create(&app_state)
+        .await;
+    let foreign_assistant = FixtureAssistant::new(&other_user, "Foreign Assistant")
+        .create(&app_state)
+        .await;
+
+    // Chats need a message to show up in the recent chats listing
+    let create_chat = async |assistant_id: Option<Uuid>, minutes_ago: i64, archived: bool| {
+        let timestamp = (Utc::now() - Duration::minutes(minutes_ago)).into();
+        let chat = chats::ActiveModel {
+            owner_user_id: ActiveValue::Set(user.id.to_string()),
+            assistant_id: ActiveValue::Set(assistant_id),
+            archived_at: ActiveValue::Set(archived.then_some(timestamp)),
+            ..Default::default()
+        }
+        .insert(&app_state.db)
+        .await
+        .expect("Failed to insert chat");
+        messages::ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            chat_id: ActiveValue::Set(chat.id),
+            raw_message: ActiveValue::Set(json!({
+                "role": "user",
+                "content": [{ "content_type": "text", "text": "Hello" }]
+            })),
+            created_at: ActiveValue::Set(timestamp),
+            updated_at: ActiveValue::Set(timestamp),
+            is_message_in_active_thread: ActiveValue::Set(true),
+            ..Default::default()
+        }
+        .insert(&app_state.db)
+        .await
+        .expect("Failed to insert message");
+        chat.id
+    };
+    let older_first_chat = create_chat(Some(first_assistant.id), 30, false).await;
+    let newer_first_chat = create_chat(Some(first_assistant.id), 10, false).await;
+    let archived_first_chat = create_chat(Some(first_assistant.id), 5, true).await;
+    let second_chat = create_chat(Some(second_assistant.id), 20, false).await;
+    create_chat(None, 1, false).await;
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let list_chat_ids = |body: &Value| -> Vec<Uuid> {
+        body["chats"]
+            .as_array()
+            .expect("Expected chats array")
+            .iter()
+            .map(|chat| Uuid::parse_str(chat["id"].as_str().unwrap()).unwrap())
+            .collect()
+    };
+
+    let response = server
+        .get(&format!(
+            "/api/v1beta/me/chats/by-assistant/{}",
+            first_assistant.id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    assert_eq!(
+        list_chat_ids(&body),
+        vec![newer_first_chat, older_first_chat]
+    );
+    assert_eq!(body["stats"]["total_count"], 2);
+    assert!(
+        body["chats"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|chat| chat["assistant_id"] == first_assistant.id.to_string())
+    );
+
+    let response = server
+        .get(&format!(
+            "/api/v1beta/me/chats/by-assistant/{}?include_archived=true&limit=2",
+            first_assistant.id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    assert_eq!(
+        list_chat_ids(&body),
+        vec![archived_first_chat, newer_first_chat]
+    );
+    assert_eq!(body["stats"]["total_count"], 3);
+    assert_eq!(body["stats"]["has_more"], true);
+
+    let response = server
+        .get(&format!(
+            "/api/v1beta/me/chats/by-assistant/{}?include_archived=true&limit=2&offset=2",
+            first_assistant.id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    assert_eq!(list_chat_ids(&response.json()), vec![older_first_chat]);
+
+    let response = server
+        .get(&format!(
+            "/api/v1beta/me/chats/by-assistant/{}",
+            second_assistant.id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    assert_eq!(list_chat_ids(&response.json()), vec![second_chat]);
+
+    // Unknown and inaccessible assistants are reported as not found
+    for assistant_id in [Uuid::new_v4(), foreign_assistant.id] {
+        server
+            .get(&format!("/api/v1beta/me/chats/by-assistant/{assistant_id}"))
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .await
+            .assert_status(http::StatusCode::NOT_FOUND);
+    }
+    server
+        .get("/api/v1beta/me/chats/by-assistant/not-a-uuid")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::BAD_REQUEST);
+}
+
+/// Test retrieving a single chat.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Creates a chat and archives it. Verifies that the owner can still retrieve it with
+/// `can_edit` set, that a user the chat is shared with as viewer can retrieve it without
+/// `can_edit`, and that other users and unknown IDs get a not found response.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_detail_endpoint(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let viewer_token = JwtTokenBuilder::new()
+        .subject("chat-detail-viewer")
+        .email("viewer@example.com")
+        .build();
+    let stranger_token = JwtTokenBuilder::new()
+        .subject("chat-detail-stranger")
+        .email("stranger@example.com")
+        .build();
+    let mut user_ids = Vec::new();
+    for token in [viewer_token.as_str(), stranger_token.as_str()] {
+        let response = server
+            .get("/api/v1beta/me/profile")
+            .with_bearer_token(token)
+            .await;
+        response.assert_status_ok();
+        let profile: Value = response.json();
+        user_ids.push(profile["id"].as_str().unwrap().to_string());
+    }
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Please respond with a short hello" }))
+        .await;
+    response.assert_status_ok();
+    let chat_id =
+        extract_chat_id(&parse_sse_events(&response)).expect("Expected chat_created event");
+    server
+        .post(&format!("/api/v1beta/chats/{chat_id}/archive"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({}))
+        .await
+        .assert_status_ok();
+
+    // Archived chats are returned to the owner
+    let response = server
+        .get(&format!("/api/v1beta/me/chats/{chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let chat: Value = response.json();
+    assert_eq!(chat["id"], chat_id);
+    assert!(chat["archived_at"].is_string());
+    assert!(chat["last_message_at"].is_string());
+    assert!(chat["title_resolved"].is_string());
+    assert_eq!(chat["can_edit"], true);
+    assert_eq!(chat["file_uploads"], json!([]));
+    assert_eq!(chat["file_upload_details"], json!([]));
+
+    // Viewers of a shared chat can retrieve it, but not edit it
+    server
+        .post("/api/v1beta/share-grants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "resource_type": "chat",
+            "resource_id": chat_id,
+            "subject_type": "user",
+            "subject_id_type": "id",
+            "subject_id": user_ids[0],
+            "role": "viewer",
+        }))
+        .await
+        .assert_status(http::StatusCode::CREATED);
+    let response = server
+        .get(&format!("/api/v1beta/me/chats/{chat_id}"))
+        .with_bearer_token(&viewer_token)
+        .await;
+    response.assert_status_ok();
+    let chat: Value = response.json();
+    assert_eq!(chat["id"], chat_id);
+    assert_eq!(chat["can_edit"], false);
+
+    // Other users can't tell the chat apart from one that doesn't exist
+    for (token, chat_id) in [
+        (stranger_token.as_str(), chat_id.clone()),
+        (TEST_JWT_TOKEN, Uuid::new_v4().to_string()),
+    ] {
+        server
+            .get(&format!("/api/v1beta/me/chats/{chat_id}"))
+            .with_bearer_token(token)
+            .await
+            .assert_status(http::StatusCode::NOT_FOUND);
+    }
+    server
+        .get("/api/v1beta/me/chats/not-a-uuid")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::BAD_REQUEST);
+}
+
+/// Test that a chat created for a message is only announced once the message is saved.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Submits a message without a chat. The stream starts with `chat_created`, followed by
+/// `user_message_saved`, and the created chat is no longer provisional.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_new_chat_is_announced_with_its_first_message(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Hello" }))
+        .await;
+    response.assert_status_ok();
+    let events = parse_sse_events(&response);
+    let message_types: Vec<String> = events
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .map(|json| json["message_type"].as_str().unwrap_or_default().to_string())
+        .collect();
+    assert_eq!(message_types[..2], ["chat_created", "user_message_saved"]);
+
+    let chat_id = Uuid::parse_str(&extract_chat_id(&events).unwrap()).unwrap();
+    let chat = chats::Entity::find_by_id(chat_id)
+        .one(&app_state.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!chat.provisional);
+}
+
+/// Test that a chat whose first message was never saved is not listed.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Creates a chat the way a message submission does, and stops before the message is saved, as
+/// a submission that fails in between does. The chat is provisional, and not part of the recent
+/// chats. Once a message is submitted to it, it's no longer provisional and is listed.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_provisional_chat_is_not_listed(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let user = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        TEST_USER_SUBJECT,
+        None,
+    )
+    .await
+    .unwrap();
+    let (chat, _) = erato::models::chat::get_or_create_chat_by_previous_message_id(
+        &app_state.db,
+        &erato::policy::engine::PolicyEngine::new(),
+        &erato::policy::types::Subject::User(user.id.to_string()),
+        None,
+        &user.id.to_string(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    assert!(chat.provisional);
+
+    let server = create_test_server(app_state.clone());
+    let recent_chat_ids = |body: &Value| -> Vec<String> {
+        body["chats"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|chat| chat["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let response = server
+        .get("/api/v1beta/me/recent_chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    assert!(recent_chat_ids(&body).is_empty());
+    assert_eq!(body["stats"]["total_count"], 0);
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "existing_chat_id": chat.id, "user_message": "Hello" }))
+        .await;
+    response.assert_status_ok();
+    assert!(extract_chat_id(&parse_sse_events(&response)).is_none());
+
+    let chat = chats::Entity::find_by_id(chat.id)
+        .one(&app_state.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!chat.provisional);
+    let response = server
+        .get("/api/v1beta/me/recent_chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    assert_eq!(
+        recent_chat_ids(&response.json::<Value>()),
+        vec![chat.id.to_string()]
+    );
+}
@@ -0,0 +1,265 @@
+//! Tests for the OpenAI-compatible API under `/compat/v1`.
+
+use axum::http;
+use erato::config::{AppConfig, CompatApiUsageRecording};
+use erato::db::entity::{chats, messages};
+use erato::models::user::get_or_create_user;
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    RequestBodyRecorder, TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt,
+    create_test_server, parse_sse_events, recording_llm_mocks, setup_mock_llm_server_with_mocks,
+};
+
+fn enable_compat_api(app_config: &mut AppConfig) {
+    app_config.compat_api.enabled = true;
+    app_config
+        .compat_api
+        .models
+        .insert("gpt-4o".to_string(), "mock-llm".to_string());
+}
+
+/// Test a non-streamed chat completion through the OpenAI-compatible API.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Lists the models, which include the mapped model name and the chat provider, and requests a
+/// completion for the mapped model. Verifies the OpenAI response shape, that the messages were
+/// passed to the LLM without additional prompts, and that no chat was stored in the default
+/// ephemeral mode.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_compat_chat_completion(pool: Pool<Postgres>) {
+    let recorder = RequestBodyRecorder::new();
+    let (mut app_config, _server) =
+        setup_mock_llm_server_with_mocks(recording_llm_mocks(&recorder, &["Hello", " there!"]))
+            .await;
+    enable_compat_api(&mut app_config);
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let server = create_test_server(app_state.clone());
+
+    let models = server
+        .get("/compat/v1/models")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    models.assert_status_ok();
+    let models: Value = models.json();
+    assert_eq!(models["object"], "list");
+    let model_ids: Vec<&str> = models["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|model| model["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(model_ids, vec!["gpt-4o", "mock-llm"]);
+
+    let response = server
+        .post("/compat/v1/chat/completions")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "model": "gpt-4o",
+            "messages": [
+                { "role": "system", "content": "You answer briefly." },
+                { "role": "user", "content": "Say hello" },
+            ],
+            "temperature": 0.2,
+        }))
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    assert_eq!(body["object"], "chat.completion");
+    assert_eq!(body["model"], "gpt-4o");
+    assert!(body["id"].as_str().unwrap().starts_with("chatcmpl-"));
+    assert_eq!(body["choices"][0]["message"]["role"], "assistant");
+    assert_eq!(body["choices"][0]["message"]["content"], "Hello there!");
+    assert_eq!(body["choices"][0]["finish_reason"], "stop");
+    assert!(body["usage"]["total_tokens"].is_u64());
+
+    let bodies = recorder.bodies();
+    assert_eq!(bodies.len(), 1);
+    let llm_request: Value = serde_json::from_str(&bodies[0]).unwrap();
+    let llm_messages = llm_request["messages"].as_array().unwrap();
+    assert_eq!(llm_messages.len(), 2, "No prompts should be added");
+    assert_eq!(llm_messages[0]["content"], "You answer briefly.");
+    assert_eq!(llm_messages[1]["content"], "Say hello");
+
+    let chat_count = chats::Entity::find().count(&app_state.db).await.unwrap();
+    assert_eq!(chat_count, 0, "Ephemeral requests should not be stored");
+}
+
+/// Test a streamed chat completion that is recorded in the API chat of the user.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `sse-streaming`
+///
+/// # Test Behavior
+/// Streams two completions with `usage_recording = "chat"`. Verifies the `chat.completion.chunk`
+/// events, the usage chunk and the `[DONE]` terminator, and that both exchanges were stored in
+/// the same chat titled "API".
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_compat_streamed_chat_completion_recorded_in_chat(pool: Pool<Postgres>) {
+    let recorder = RequestBodyRecorder::new();
+    let (mut app_config, _server) =
+        setup_mock_llm_server_with_mocks(recording_llm_mocks(&recorder, &["Streamed", " answer"]))
+            .await;
+    enable_compat_api(&mut app_config);
+    app_config.compat_api.usage_recording = CompatApiUsageRecording::Chat;
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let server = create_test_server(app_state.clone());
+
+    for prompt in ["First question", "Second question"] {
+        let response = server
+            .post("/compat/v1/chat/completions")
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .json(&json!({
+                "model": "mock-llm",
+                "messages": [{ "role": "user", "content": prompt }],
+                "stream": true,
+                "stream_options": { "include_usage": true },
+            }))
+            .await;
+        response.assert_status_ok();
+
+        let events = parse_sse_events(&response);
+        assert_eq!(events.last().unwrap().data, "[DONE]");
+        let chunks: Vec<Value> = events[..events.len() - 1]
+            .iter()
+            .map(|event| serde_json::from_str(&event.data).unwrap())
+            .collect();
+        assert!(
+            chunks
+                .iter()
+                .all(|chunk| chunk["object"] == "chat.completion.chunk")
+        );
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+        let text: String = chunks
+            .iter()
+            .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+            .collect();
+        assert_eq!(text, "Streamed answer");
+
+        let finish_chunk = &chunks[chunks.len() - 2];
+        assert_eq!(finish_chunk["choices"][0]["finish_reason"], "stop");
+        let usage_chunk = &chunks[chunks.len() - 1];
+        assert_eq!(usage_chunk["choices"], json!([]));
+        assert!(usage_chunk["usage"]["total_tokens"].is_u64());
+    }
+
+    let api_chats = chats::Entity::find()
+        .filter(chats::Column::TitleByUserProvided.eq("API"))
+        .all(&app_state.db)
+        .await
+        .unwrap();
+    assert_eq!(api_chats.len(), 1, "Exchanges should share one chat");
+    let stored_messages = messages::Entity::find()
+        .filter(messages::Column::ChatId.eq(api_chats[0].id))
+        .order_by_asc(messages::Column::CreatedAt)
+        .all(&app_state.db)
+        .await
+        .unwrap();
+    let stored: Vec<(String, String)> = stored_messages
+        .iter()
+        .map(|message| {
+            (
+                message.raw_message["role"].as_str().unwrap().to_string(),
+                message.raw_message["content"][0]["text"]
+                    .as_str()
+                    .unwrap()
+                    .to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        stored,
+        vec![
+            ("user".to_string(), "First question".to_string()),
+            ("assistant".to_string(), "Streamed answer".to_string()),
+            ("user".to_string(), "Second question".to_string()),
+            ("assistant".to_string(), "Streamed answer".to_string()),
+        ]
+    );
+    assert_eq!(
+        stored_messages[2].previous_message_id,
+        Some(stored_messages[1].id)
+    );
+}
+
+/// Test that invalid requests are rejected with OpenAI-shaped errors.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Requests with tools or an unknown model are rejected before the LLM is called, with the
+/// error in the `error` object of the OpenAI API. With the API disabled, it responds with 404.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_compat_rejects_invalid_requests(pool: Pool<Postgres>) {
+    let recorder = RequestBodyRecorder::new();
+    let (mut app_config, _server) =
+        setup_mock_llm_server_with_mocks(recording_llm_mocks(&recorder, &["Unused"])).await;
+    enable_compat_api(&mut app_config);
+    let mut disabled_config = app_config.clone();
+    disabled_config.compat_api.enabled = false;
+    let app_state = test_app_state(app_config, pool.clone()).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let server = create_test_server(app_state);
+
+    let response = server
+        .post("/compat/v1/chat/completions")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "What's the weather?" }],
+            "tools": [{ "type": "function", "function": { "name": "get_weather" } }],
+        }))
+        .await;
+    response.assert_status(http::StatusCode::BAD_REQUEST);
+    let body: Value = response.json();
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    assert_eq!(body["error"]["code"], "unsupported_parameter");
+    assert_eq!(body["error"]["param"], "tools");
+
+    let response = server
+        .post("/compat/v1/chat/completions")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "model": "unknown-model",
+            "messages": [{ "role": "user", "content": "Hi" }],
+        }))
+        .await;
+    response.assert_status(http::StatusCode::NOT_FOUND);
+    let body: Value = response.json();
+    assert_eq!(body["error"]["code"], "model_not_found");
+    assert_eq!(body["error"]["param"], "model");
+
+    assert!(recorder.bodies().is_empty(), "The LLM should not be called");
+
+    let disabled_state = test_app_state(disabled_config, pool).await;
+    let response = create_test_server(disabled_state)
+        .get("/compat/v1/models")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status(http::StatusCode::NOT_FOUND);
+    let body: Value = response.json();
+    assert!(body["error"]["message"].is_string());
+}
@@ -0,0 +1,199 @@
+//! Tests for the content indices of the streamed events of a generation.
+
+use axum::http;
+use erato::config::{McpServerAuthenticationConfig, McpServerConfig, McpServerPermissionRule};
+use erato::models::user::get_or_create_user;
+use mocktail::MockSet;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::collections::BTreeMap;
+use std::env;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    BodyContainsMatcher, TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt,
+    build_openai_text_and_tool_calls_streaming_response, build_openai_text_streaming_response,
+    create_test_server, parse_sse_events, setup_mock_llm_server_with_mocks,
+};
+
+fn mock_mcp_base_url() -> String {
+    env::var("TEST_MOCK_MCP_SERVER_BASE_URL")
+        .unwrap_or_else(|_| "http://127.0.0.1:44321".to_string())
+}
+
+fn mcp_server_config(path: &str) -> McpServerConfig {
+    McpServerConfig {
+        transport_type: "streamable_http".to_string(),
+        url: format!("{}{path}", mock_mcp_base_url()),
+        http_headers: None,
+        authentication: McpServerAuthenticationConfig::None,
+        max_session_idle_seconds: None,
+        on_schema_violation: Default::default(),
+    }
+}
+
+/// Assembles the content parts from the events the way clients do, by content index.
+fn assemble_content_from_events(events: &[Value]) -> BTreeMap<usize, Value> {
+    let mut content: BTreeMap<usize, Value> = BTreeMap::new();
+    for event in events {
+        let Some(content_index) = event["content_index"].as_u64() else {
+            continue;
+        };
+        let content_index = content_index as usize;
+        match event["message_type"].as_str().unwrap() {
+            "text_delta" => {
+                let part = content
+                    .entry(content_index)
+                    .or_insert_with(|| json!({ "content_type": "text", "text": "" }));
+                assert_eq!(part["content_type"], "text", "index {content_index} reused");
+                let text = format!(
+                    "{}{}",
+                    part["text"].as_str().unwrap(),
+                    event["new_text"].as_str().unwrap()
+                );
+                part["text"] = json!(text);
+            }
+            "tool_call_proposed" | "tool_call_update" => {
+                let part = content.entry(content_index).or_insert_with(|| {
+                    json!({
+                        "content_type": "tool_use",
+                        "tool_call_id": event["tool_call_id"],
+                    })
+                });
+                assert_eq!(
+                    part["tool_call_id"], event["tool_call_id"],
+                    "index {content_index} reused"
+                );
+            }
+            _ => {}
+        }
+    }
+    content
+}
+
+/// Test that the content indices of the events match the positions in the completed message.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// The model streams text and calls `list_files` and `generate_image` in the first turn, whose
+/// image is stored after the tool call. The second turn streams text and calls `list_files`
+/// again, and the third turn only streams text. The content assembled from the `text_delta`,
+/// `tool_call_proposed` and `tool_call_update` events by content index equals the text and tool
+/// call parts of the completed message at exactly the same positions, and every text and tool
+/// call part of the completed message was announced by an event.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_event_content_indices_match_completed_message(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post()
+            .path("/v1/chat/completions")
+            .matcher(BodyContainsMatcher::new(&[], &["call_image"]));
+        then.status(http::StatusCode::OK)
+            .headers([("Content-Type", "text/event-stream")])
+            .bytes_stream_with_delays(build_openai_text_and_tool_calls_streaming_response(
+                &["Let me ", "look."],
+                &[
+                    ("call_files", "list_files", json!({})),
+                    ("call_image", "generate_image", json!({ "prompt": "A cat" })),
+                ],
+            ));
+    });
+    mocks.mock(|when, then| {
+        when.post()
+            .path("/v1/chat/completions")
+            .matcher(BodyContainsMatcher::new(
+                &["call_image"],
+                &["call_files_again"],
+            ));
+        then.status(http::StatusCode::OK)
+            .headers([("Content-Type", "text/event-stream")])
+            .bytes_stream_with_delays(build_openai_text_and_tool_calls_streaming_response(
+                &["Here is ", "a cat."],
+                &[("call_files_again", "list_files", json!({}))],
+            ));
+    });
+    mocks.mock(|when, then| {
+        when.post()
+            .path("/v1/chat/completions")
+            .matcher(BodyContainsMatcher::new(&["call_files_again"], &[]));
+        then.status(http::StatusCode::OK)
+            .headers([("Content-Type", "text/event-stream")])
+            .bytes_stream_with_delays(build_openai_text_streaming_response(&["Done", "."]));
+    });
+
+    let (mut app_config, _server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config
+        .mcp_servers
+        .insert("files".to_string(), mcp_server_config("/mcp/file"));
+    app_config.mcp_servers.insert(
+        "image-generation".to_string(),
+        mcp_server_config("/mcp/image-generation"),
+    );
+    app_config.mcp_server_permissions.rules.insert(
+        "allow-all".to_string(),
+        McpServerPermissionRule::AllowAll {
+            mcp_server_ids: vec!["files".to_string(), "image-generation".to_string()],
+        },
+    );
+
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .unwrap();
+    let server = create_test_server(app_state);
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Look at my files and draw a cat" }))
+        .await;
+    response.assert_status_ok();
+    let events: Vec<Value> = parse_sse_events(&response)
+        .iter()
+        .filter_map(|event| serde_json::from_str(&event.data).ok())
+        .collect();
+    let completed = events
+        .iter()
+        .find(|event| event["message_type"] == "assistant_message_completed")
+        .expect("Expected a completed message");
+    let content = completed["content"].as_array().unwrap();
+
+    let content_types: Vec<&str> = content
+        .iter()
+        .map(|part| part["content_type"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        content_types,
+        vec![
+            "text",
+            "tool_use",
+            "tool_use",
+            "image_file_pointer",
+            "text",
+            "tool_use",
+            "text",
+        ]
+    );
+
+    let assembled = assemble_content_from_events(&events);
+    for (content_index, part) in &assembled {
+        let stored = &content[*content_index];
+        assert_eq!(stored["content_type"], part["content_type"]);
+        match part["content_type"].as_str().unwrap() {
+            "text" => assert_eq!(stored["text"], part["text"]),
+            _ => assert_eq!(stored["tool_call_id"], part["tool_call_id"]),
+        }
+    }
+    let announced: Vec<usize> = assembled.keys().copied().collect();
+    assert_eq!(announced, vec![0, 1, 2, 4, 5, 6]);
+    assert_eq!(assembled[&0]["text"], "Let me look.");
+    assert_eq!(assembled[&2]["tool_call_id"], "call_image");
+    assert_eq!(assembled[&6]["text"], "Done.");
+}
@@ -0,0 +1,266 @@
+//! Integration tests for the spillover of oversized message content to the file storage.
+
+use axum::http;
+use erato::config::ContentSpilloverMigrationConfig;
+use erato::db::entity::messages;
+use erato::services::content_spillover::run_content_spillover_migration;
+use mocktail::MockSet;
+use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    RequestBodyRecorder, TEST_JWT_TOKEN, TestRequestAuthExt, build_openai_text_streaming_response,
+    create_test_server, mock_llm_sse_response, parse_sse_events, setup_mock_llm_server_with_mocks,
+};
+
+const THRESHOLD_BYTES: u64 = 4096;
+
+fn saved_message_id(response: &axum_test::TestResponse, message_type: &str) -> String {
+    parse_sse_events(response)
+        .iter()
+        .find_map(|event| {
+            if let Ok(json) = serde_json::from_str::<Value>(&event.data)
+                && json["message_type"] == message_type
+            {
+                return json["message_id"].as_str().map(|s| s.to_string());
+            }
+            None
+        })
+        .unwrap_or_else(|| panic!("Expected {message_type} event with message_id"))
+}
+
+/// Verifies that oversized user messages are spilled to the file storage when they are saved,
+/// while the LLM and the content endpoint still see their full content.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// Sends a user message that is larger than the spillover threshold. The stored message only
+/// keeps a `blob_pointer` part with a preview, which is what the messages endpoint returns, while
+/// the content endpoint returns the original text. A follow-up message replays the full text to
+/// the LLM.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_oversized_user_message_is_spilled_on_write(pool: Pool<Postgres>) {
+    let llm_request_recorder = RequestBodyRecorder::new();
+    let mut mocks = MockSet::new();
+    {
+        let recorder = llm_request_recorder.clone();
+        mocks.mock(move |when, then| {
+            when.post().path("/v1/chat/completions").matcher(recorder);
+            mock_llm_sse_response(then, build_openai_text_streaming_response(&["Noted."]));
+        });
+    }
+    let (mut app_config, _server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config.chat.content_spillover.threshold_bytes = THRESHOLD_BYTES;
+    app_config.chat.content_spillover.preview_chars = 100;
+    app_config.security.wrap_untrusted_content = false;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let large_text = format!(
+        "Please review this log:\n{}",
+        "line of log output\n".repeat(1000)
+    );
+    let first_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": large_text }))
+        .await;
+    first_response.assert_status_ok();
+    let user_message_id = saved_message_id(&first_response, "user_message_saved");
+    let assistant_message_id = saved_message_id(&first_response, "assistant_message_completed");
+    let chat_id = parse_sse_events(&first_response)
+        .iter()
+        .find_map(|event| {
+            let json = serde_json::from_str::<Value>(&event.data).ok()?;
+            (json["message_type"] == "chat_created")
+                .then(|| json["chat_id"].as_str().map(|s| s.to_string()))
+                .flatten()
+        })
+        .expect("Expected chat_created event with chat_id");
+
+    let stored_message = messages::Entity::find_by_id(Uuid::parse_str(&user_message_id).unwrap())
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the user message")
+        .expect("The user message should exist");
+    let stored_json = stored_message.raw_message.to_string();
+    assert!(stored_json.contains("blob_pointer"));
+    assert!((stored_json.len() as u64) < THRESHOLD_BYTES);
+
+    let messages_response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    messages_response.assert_status_ok();
+    let listed_messages: Value = messages_response.json();
+    let listed_user_message = listed_messages["messages"]
+        .as_array()
+        .expect("Expected messages in response")
+        .iter()
+        .find(|message| message["id"] == user_message_id.as_str())
+        .expect("The user message should be listed");
+    let pointer = &listed_user_message["content"][0];
+    assert_eq!(pointer["content_type"], "blob_pointer");
+    assert_eq!(
+        pointer["preview"]
+            .as_str()
+            .map(|preview| preview.chars().count()),
+        Some(100)
+    );
+
+    let part_response = server
+        .get(&format!("/api/v1beta/messages/{user_message_id}/content/0"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    part_response.assert_status_ok();
+    let part: Value = part_response.json();
+    assert_eq!(part["content_type"], "text");
+    assert_eq!(part["text"], large_text.as_str());
+
+    let missing_part_response = server
+        .get(&format!("/api/v1beta/messages/{user_message_id}/content/5"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(
+        missing_part_response.status_code(),
+        http::StatusCode::NOT_FOUND
+    );
+
+    let request_count = llm_request_recorder.bodies().len();
+    let second_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "previous_message_id": assistant_message_id,
+            "user_message": "Anything unusual?"
+        }))
+        .await;
+    second_response.assert_status_ok();
+    assert!(
+        llm_request_recorder.bodies()[request_count..]
+            .iter()
+            .any(|body| body.contains("line of log output\\nline of log output")),
+        "The follow-up request should contain the full spilled message"
+    );
+}
+
+/// Verifies that the migration spills the content of messages that were stored inline.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mocked-llm`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// Rewrites a stored answer to contain a large tool call output inline and runs the migration.
+/// The answer is reduced below the threshold, the savings are reported, and the content endpoint
+/// still returns the original tool call.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_content_spillover_migration_spills_stored_messages(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        mock_llm_sse_response(then, build_openai_text_streaming_response(&["Done."]));
+    });
+    let (mut app_config, _server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config.chat.content_spillover.threshold_bytes = THRESHOLD_BYTES;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Fetch the report" }))
+        .await;
+    response.assert_status_ok();
+    let assistant_message_id =
+        Uuid::parse_str(&saved_message_id(&response, "assistant_message_completed")).unwrap();
+
+    let assistant_message = messages::Entity::find_by_id(assistant_message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the answer")
+        .expect("The answer should exist");
+    let report = "quarterly figures ".repeat(2000);
+    let mut raw_message = assistant_message.raw_message.clone();
+    raw_message["content"] = json!([
+        {
+            "content_type": "tool_use",
+            "tool_call_id": "call_1",
+            "status": "success",
+            "tool_name": "fetch_report",
+            "input": { "report": "q3" },
+            "output": { "text": report }
+        },
+        { "content_type": "text", "text": "Done." }
+    ]);
+    messages::ActiveModel {
+        id: ActiveValue::Unchanged(assistant_message_id),
+        raw_message: ActiveValue::Set(raw_message),
+        ..Default::default()
+    }
+    .update(&app_state.db)
+    .await
+    .expect("Failed to store the inline tool call output");
+
+    let progress = run_content_spillover_migration(
+        &app_state.db,
+        app_state.default_file_storage_provider(),
+        &app_state.config.chat.content_spillover,
+        &ContentSpilloverMigrationConfig {
+            batch_interval_ms: 0,
+            ..Default::default()
+        },
+    )
+    .await
+    .expect("The migration should succeed");
+    assert!(progress.completed_at.is_some());
+    assert_eq!(progress.migrated_messages, 1);
+    assert!(progress.bytes_saved > 0);
+
+    let migrated_message = messages::Entity::find_by_id(assistant_message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the answer")
+        .expect("The answer should exist");
+    assert_eq!(
+        migrated_message.raw_message["content"][0]["content_type"],
+        "blob_pointer"
+    );
+    assert_eq!(migrated_message.raw_message["content"][1]["text"], "Done.");
+    assert!((migrated_message.raw_message.to_string().len() as u64) < THRESHOLD_BYTES);
+
+    let part_response = server
+        .get(&format!(
+            "/api/v1beta/messages/{assistant_message_id}/content/0"
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    part_response.assert_status_ok();
+    let part: Value = part_response.json();
+    assert_eq!(part["content_type"], "tool_use");
+    assert_eq!(part["output"]["text"], report.as_str());
+
+    // Running the migration again leaves the spilled message alone.
+    let progress = run_content_spillover_migration(
+        &app_state.db,
+        app_state.default_file_storage_provider(),
+        &app_state.config.chat.content_spillover,
+        &ContentSpilloverMigrationConfig {
+            batch_interval_ms: 0,
+            ..Default::default()
+        },
+    )
+    .await
+    .expect("The migration should succeed again");
+    assert_eq!(progress.migrated_messages, 0);
+}
@@ -5,33 +5,22 @@ use axum::http;
 use axum_test::TestServer;
 use erato::config::ActionFacetConfig;
 use erato::models::message::GenerationParameters;
-use erato::models::user::get_or_create_user;
 use erato::server::router::router;
 use mocktail::MockSet;
-use mocktail::body::BodyAction;
-use mocktail::mock_builder::Then;
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
 use serde_json::{Value, json};
 use sqlx::Pool;
 use sqlx::postgres::Postgres;
 
 use crate::test_app_state;
+use crate::test_utils::fixtures::FixtureUser;
 use crate::test_utils::{
-    RequestBodyRecorder, TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt,
-    build_openai_text_streaming_response, hermetic_app_config, parse_sse_events,
-    setup_mock_llm_server, setup_mock_llm_server_with_mocks,
+    JwtTokenBuilder, RequestBodyRecorder, TEST_JWT_TOKEN, TestRequestAuthExt,
+    build_openai_text_streaming_response, create_test_server, hermetic_app_config,
+    mock_llm_sse_response, parse_sse_events, setup_mock_llm_server,
+    setup_mock_llm_server_with_mocks,
 };
 
-fn mock_llm_sse_response(then: Then, actions: Vec<BodyAction>) {
-    then.status(http::StatusCode::OK)
-        .headers([
-            ("Content-Type", "text/event-stream"),
-            ("Cache-Control", "no-cache"),
-            ("Connection", "keep-alive"),
-        ])
-        .bytes_stream_with_delays(actions);
-}
-
 fn add_action_facets(app_config: &mut erato::config::AppConfig) {
     app_config.action_facets.facets.insert(
         "rewrite".to_string(),
@@ -610,9 +599,7 @@ async fn test_edit_falls_back_to_stored_action_facet(pool: Pool<Postgres>) {
     let app_state = test_app_state(app_config, pool).await;
     let db = app_state.db.clone();
 
-    let _user = get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
-        .await
-        .expect("Failed to create user");
+    FixtureUser::test_user().create(&app_state).await;
 
     let app: Router = router(app_state.clone())
         .split_for_parts()
@@ -789,9 +776,7 @@ async fn test_edit_chained_fallback_persists_action_facet(pool: Pool<Postgres>)
     let app_state = test_app_state(app_config, pool).await;
     let db = app_state.db.clone();
 
-    let _user = get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
-        .await
-        .expect("Failed to create user");
+    FixtureUser::test_user().create(&app_state).await;
 
     let app: Router = router(app_state.clone())
         .split_for_parts()
@@ -926,9 +911,7 @@ async fn test_edit_explicit_action_facet_overrides_stored_one(pool: Pool<Postgre
     let app_state = test_app_state(app_config, pool).await;
     let db = app_state.db.clone();
 
-    let _user = get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
-        .await
-        .expect("Failed to create user");
+    FixtureUser::test_user().create(&app_state).await;
 
     let app: Router = router(app_state.clone())
         .split_for_parts()
@@ -1059,9 +1042,7 @@ async fn test_edit_drops_stored_action_facet_that_no_longer_validates(pool: Pool
     add_action_facets(&mut app_config);
     let app_state = test_app_state(app_config, pool.clone()).await;
 
-    let _user = get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
-        .await
-        .expect("Failed to create user");
+    FixtureUser::test_user().create(&app_state).await;
 
     let app: Router = router(app_state.clone())
         .split_for_parts()
@@ -1170,8 +1151,153 @@ async fn test_edit_drops_stored_action_facet_that_no_longer_validates(pool: Pool
         .await
         .expect("Failed to fetch edited user message")
         .expect("Edited user message should exist");
+    // The edited message may still carry input parameters for its detected
+    // language, but none for the dropped facet.
+    let edited_input_params: Option<erato::models::message::InputParameters> = edited_user_message
+        .input_parameters
+        .clone()
+        .map(serde_json::from_value)
+        .transpose()
+        .expect("Failed to deserialize edited input parameters");
     assert_eq!(
-        edited_user_message.input_parameters, None,
+        edited_input_params
+            .as_ref()
+            .and_then(|params| params.action_facet_id.as_deref()),
+        None,
         "A dropped facet must not be persisted onto the edited user message",
     );
+    assert_eq!(
+        edited_input_params.and_then(|params| params.action_facet_args),
+        None,
+    );
+}
+
+/// Find the event with the given `message_type` in an SSE response.
+fn find_event(response: &axum_test::TestResponse, message_type: &str) -> Value {
+    parse_sse_events(response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .find(|event| event["message_type"] == message_type)
+        .unwrap_or_else(|| panic!("Expected {message_type} event"))
+}
+
+async fn edit_message(server: &TestServer, message_id: &str, text: &str) -> Value {
+    let response = server
+        .post("/api/v1beta/me/messages/editstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "message_id": message_id,
+            "replace_user_message": text
+        }))
+        .await;
+    response.assert_status_ok();
+    find_event(&response, "user_message_saved")["message"].clone()
+}
+
+async fn message_history(server: &TestServer, message_id: &str) -> Vec<Value> {
+    let response = server
+        .get(&format!("/api/v1beta/messages/{message_id}/history"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    response.json()
+}
+
+/// Test that edits and regenerations of messages can be told apart.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// In one chat, an answer is regenerated and the question is edited twice. The regenerated
+/// answer has a sibling, but is neither `edited` nor has previous versions. The edited questions
+/// are `edited`, point to the version they were edited from, and their history lists the
+/// previous versions starting with the original question. Messages of other users' chats are
+/// not found.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_edits_are_distinguishable_from_regenerations(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "What is the capital of France?" }))
+        .await;
+    response.assert_status_ok();
+    let original_question = find_event(&response, "user_message_saved")["message"].clone();
+    let original_question_id = original_question["id"].as_str().unwrap().to_string();
+    let chat_id = original_question["chat_id"].as_str().unwrap().to_string();
+    let original_answer_id = find_event(&response, "assistant_message_completed")["message_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert_eq!(original_question["edited"], false);
+
+    let response = server
+        .post("/api/v1beta/me/messages/regeneratestream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "current_message_id": original_answer_id }))
+        .await;
+    response.assert_status_ok();
+    let regenerated_answer =
+        find_event(&response, "assistant_message_completed")["message"].clone();
+    assert_eq!(regenerated_answer["sibling_message_id"], original_answer_id);
+    assert_eq!(regenerated_answer["edited"], false);
+    assert!(regenerated_answer.get("edit_of_message_id").is_none());
+    assert!(
+        message_history(&server, regenerated_answer["id"].as_str().unwrap())
+            .await
+            .is_empty()
+    );
+
+    let first_edit =
+        edit_message(&server, &original_question_id, "What is the capital of Spain?").await;
+    assert_eq!(first_edit["edited"], true);
+    assert_eq!(first_edit["edit_of_message_id"], original_question_id);
+    let first_edit_id = first_edit["id"].as_str().unwrap().to_string();
+    let second_edit = edit_message(&server, &first_edit_id, "What is the capital of Italy?").await;
+    assert_eq!(second_edit["edit_of_message_id"], first_edit_id);
+    let second_edit_id = second_edit["id"].as_str().unwrap().to_string();
+
+    let history = message_history(&server, &second_edit_id).await;
+    let history_ids: Vec<&str> = history
+        .iter()
+        .map(|version| version["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        history_ids,
+        vec![original_question_id.as_str(), first_edit_id.as_str()]
+    );
+    assert_eq!(history[0]["text_preview"], "What is the capital of France?");
+    assert_eq!(history[1]["text_preview"], "What is the capital of Spain?");
+    assert!(history[0]["editor"].is_string());
+
+    // The edit is also exposed on the messages of the chat
+    let response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let messages: Value = response.json();
+    let listed_edit = messages["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|message| message["id"] == second_edit_id)
+        .expect("Expected the edited message in the messages of the chat");
+    assert_eq!(listed_edit["edited"], true);
+
+    let other_user_token = JwtTokenBuilder::new()
+        .subject("other-user")
+        .build();
+    let response = server
+        .get(&format!("/api/v1beta/messages/{second_edit_id}/history"))
+        .with_bearer_token(&other_user_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
 }
@@ -0,0 +1,221 @@
+//! Integration tests for messages and files without content.
+
+use axum::http::StatusCode;
+use axum_test::multipart::{MultipartForm, Part};
+use erato::db::entity::{file_uploads, messages};
+use mocktail::MockSet;
+use sea_orm::{EntityTrait, PaginatorTrait};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::fixtures::{FixtureChat, FixtureUser};
+use crate::test_utils::{
+    RequestBodyRecorder, TEST_JWT_TOKEN, TestRequestAuthExt, build_openai_text_streaming_response,
+    create_chat, create_test_server, mock_llm_sse_response, setup_mock_llm_server_with_mocks,
+    submit_events, submit_message,
+};
+
+fn text_file(content: &str, filename: &str) -> Part {
+    Part::bytes(content.as_bytes().to_vec())
+        .file_name(filename)
+        .mime_type("text/plain")
+}
+
+/// Verifies that messages without text or files are rejected before anything is stored.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// A whitespace-only message is rejected with 422 by the submit endpoint, and an edit that
+/// replaces a message with whitespace-only text is rejected with 422 by the edit endpoint. No
+/// message is stored, and the chat provider is never called.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_whitespace_only_message_is_rejected(pool: Pool<Postgres>) {
+    let llm_request_recorder = RequestBodyRecorder::new();
+    let mut mocks = MockSet::new();
+    {
+        let recorder = llm_request_recorder.clone();
+        mocks.mock(move |when, then| {
+            when.post().path("/v1/chat/completions").matcher(recorder);
+            mock_llm_sse_response(then, build_openai_text_streaming_response(&["Hm?"]));
+        });
+    }
+    let (app_config, _llm_server) = setup_mock_llm_server_with_mocks(mocks).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let submit_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "  \n\t " }))
+        .await;
+    submit_response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        messages::Entity::find().count(&app_state.db).await.unwrap(),
+        0
+    );
+
+    let user = FixtureUser::test_user().create(&app_state).await;
+    let chat = FixtureChat::new(&user)
+        .with_messages(2)
+        .create(&app_state)
+        .await;
+    let edit_response = server
+        .post("/api/v1beta/me/messages/editstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "message_id": chat.message_ids[0],
+            "replace_user_message": "",
+        }))
+        .await;
+    edit_response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        messages::Entity::find().count(&app_state.db).await.unwrap(),
+        2
+    );
+
+    assert!(llm_request_recorder.bodies().is_empty());
+}
+
+/// Verifies that a message with files, but without text, is submitted with a placeholder.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `uses-file-storage`
+/// - `sse-streaming`
+///
+/// # Test Behavior
+/// A message with an empty text and an attached file is answered. The message is returned with
+/// the `chat.file_only_message_placeholder` as its `placeholder_text`, while the request to the
+/// chat provider only contains the file and not the placeholder.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_empty_message_with_file_uses_placeholder(pool: Pool<Postgres>) {
+    let llm_request_recorder = RequestBodyRecorder::new();
+    let mut mocks = MockSet::new();
+    {
+        let recorder = llm_request_recorder.clone();
+        mocks.mock(move |when, then| {
+            when.post().path("/v1/chat/completions").matcher(recorder);
+            mock_llm_sse_response(then, build_openai_text_streaming_response(&["Got it."]));
+        });
+    }
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config.chat.file_only_message_placeholder = "[see attachment]".to_string();
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let chat_id = create_chat(&server, TEST_JWT_TOKEN).await;
+    let multipart_form = MultipartForm::new().add_part(
+        "file",
+        text_file("The launch is on March 3rd.", "notes.txt"),
+    );
+    let upload_response = server
+        .post(&format!("/api/v1beta/me/files?chat_id={chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .multipart(multipart_form)
+        .await;
+    upload_response.assert_status_ok();
+    let file_id = upload_response.json::<Value>()["files"][0]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let message_response = submit_message(
+        &server,
+        TEST_JWT_TOKEN,
+        &json!({
+            "existing_chat_id": chat_id,
+            "user_message": " ",
+            "input_files_ids": [file_id],
+        }),
+    )
+    .await;
+    let user_message_saved = submit_events(&message_response)
+        .into_iter()
+        .find(|event| event["message_type"] == "user_message_saved")
+        .expect("Expected a user_message_saved event");
+    assert_eq!(
+        user_message_saved["message"]["placeholder_text"],
+        "[see attachment]"
+    );
+    assert_eq!(user_message_saved["message"]["content"][0]["text"], "");
+
+    let llm_requests = llm_request_recorder.bodies().join("\n");
+    assert!(llm_requests.contains("March 3rd"));
+    assert!(!llm_requests.contains("[see attachment]"));
+
+    let messages_response: Value = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    let user_message = messages_response["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|message| message["role"] == "user")
+        .expect("Expected the user message");
+    assert_eq!(user_message["placeholder_text"], "[see attachment]");
+}
+
+/// Verifies that empty files and whitespace-only text files are rejected on upload.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// An upload of a zero-byte file, a text file that only contains whitespace and a regular text
+/// file succeeds for the regular file only. The other two files are listed in `errors` of the
+/// response, and no file upload is recorded for them.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_empty_file_uploads_are_rejected(pool: Pool<Postgres>) {
+    let (app_config, _llm_server) = setup_mock_llm_server_with_mocks(MockSet::new()).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let chat_id = create_chat(&server, TEST_JWT_TOKEN).await;
+    let upload_response = server
+        .post(&format!("/api/v1beta/me/files?chat_id={chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .multipart(
+            MultipartForm::new()
+                .add_part(
+                    "empty",
+                    Part::bytes(Vec::new())
+                        .file_name("empty.pdf")
+                        .mime_type("application/pdf"),
+                )
+                .add_part("blank", text_file(" \n\n\t", "blank.txt"))
+                .add_part("notes", text_file("Some notes", "notes.txt")),
+        )
+        .await;
+    upload_response.assert_status_ok();
+    let upload_json: Value = upload_response.json();
+
+    let files = upload_json["files"].as_array().unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0]["filename"], "notes.txt");
+
+    let errors = upload_json["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0]["filename"], "empty.pdf");
+    assert_eq!(errors[0]["reason"], "The file is empty");
+    assert_eq!(errors[1]["filename"], "blank.txt");
+    assert_eq!(errors[1]["reason"], "The file only contains whitespace");
+
+    assert_eq!(
+        file_uploads::Entity::find()
+            .count(&app_state.db)
+            .await
+            .unwrap(),
+        1
+    );
+}
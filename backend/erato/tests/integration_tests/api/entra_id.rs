@@ -1,6 +1,6 @@
 //! Entra ID integration tests.
 //!
-//! These tests are disabled by default as they require a valid MS Graph access token.
+//! The tests against the real MS Graph API are skipped unless a valid access token is set.
 //! To run them:
 //! 1. Set the `ENTRA_ID_TEST_ACCESS_TOKEN` environment variable to a valid MS Graph
 //!    access token with User.Read.All and Group.Read.All permissions
@@ -9,14 +9,20 @@
 //! Note: The tests use the standard test JWT for backend authentication, and the
 //! MS Graph access token is passed via the X-Forwarded-Access-Token header (as would
 //! happen in production with oauth2-proxy).
+//!
+//! The search, pagination and throttling tests run against a mocked MS Graph API.
 
 use axum::Router;
 use axum::http::StatusCode;
 use axum_test::TestServer;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL_SAFE};
 use erato::server::router::router;
-use serde_json::Value;
+use mocktail::prelude::*;
+use mocktail::server::MockServerConfig;
+use serde_json::{Value, json};
 use sqlx::Pool;
 use sqlx::postgres::Postgres;
+use std::net::{IpAddr, Ipv4Addr};
 
 use crate::test_utils::{TEST_JWT_TOKEN, TestRequestAuthExt, setup_mock_llm_server};
 
@@ -196,7 +202,7 @@ async fn test_entra_id_list_groups(pool: Pool<Postgres>) {
     }
 }
 
-/// Test that endpoints return empty lists when Entra ID is disabled.
+/// Test that endpoints return 404 with a structured body when Entra ID is disabled.
 ///
 /// # Test Categories
 /// - `uses-db`
@@ -205,9 +211,9 @@ async fn test_entra_id_list_groups(pool: Pool<Postgres>) {
 ///
 /// # Test Behavior
 /// This test verifies that when the Entra ID integration is disabled,
-/// the endpoints return empty lists instead of 404 errors.
+/// the endpoints return 404 with the `entra_id_disabled` error code.
 #[sqlx::test(migrator = "crate::MIGRATOR")]
-async fn test_entra_id_disabled_returns_empty_lists(pool: Pool<Postgres>) {
+async fn test_entra_id_disabled_returns_404(pool: Pool<Postgres>) {
     // Set up the test environment with Entra ID DISABLED
     let (app_config, _server) = setup_mock_llm_server(None).await;
     // Note: Don't enable Entra ID in the config
@@ -220,37 +226,17 @@ async fn test_entra_id_disabled_returns_empty_lists(pool: Pool<Postgres>) {
 
     let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
 
-    // Test users endpoint
-    let users_response = server
-        .get("/api/v1beta/me/organization/users")
-        .with_bearer_token(TEST_JWT_TOKEN)
-        .await;
-
-    users_response.assert_status(StatusCode::OK);
-    let users_json: Value = users_response.json();
-    let users = users_json["users"]
-        .as_array()
-        .expect("Expected users array in response");
-    assert!(
-        users.is_empty(),
-        "Expected empty users list when Entra ID is disabled"
-    );
-
-    // Test groups endpoint
-    let groups_response = server
-        .get("/api/v1beta/me/organization/groups")
-        .with_bearer_token(TEST_JWT_TOKEN)
-        .await;
+    for path in [
+        "/api/v1beta/me/organization/users",
+        "/api/v1beta/me/organization/groups",
+    ] {
+        let response = server.get(path).with_bearer_token(TEST_JWT_TOKEN).await;
 
-    groups_response.assert_status(StatusCode::OK);
-    let groups_json: Value = groups_response.json();
-    let groups = groups_json["groups"]
-        .as_array()
-        .expect("Expected groups array in response");
-    assert!(
-        groups.is_empty(),
-        "Expected empty groups list when Entra ID is disabled"
-    );
+        response.assert_status(StatusCode::NOT_FOUND);
+        let body: Value = response.json();
+        assert_eq!(body["error"], "entra_id_disabled");
+        assert!(body["message"].is_string());
+    }
 }
 
 /// Test that endpoints return 401 when no access token is available.
@@ -451,3 +437,207 @@ async fn test_entra_id_list_groups_with_is_involved_filter(pool: Pool<Postgres>)
         );
     }
 }
+
+/// Start a mock MS Graph API, and point the Entra ID integration of the config at it.
+///
+/// Mocks are added to the returned server after it has started, so that they can reference its
+/// URL in `@odata.nextLink`s.
+async fn setup_mock_graph_server(app_config: &mut erato::config::AppConfig) -> MockServer {
+    let mockserver_config = MockServerConfig {
+        listen_addr: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+        ..Default::default()
+    };
+    let server = MockServer::new_http("graph-mock").with_config(mockserver_config);
+    server.start().await.expect("Failed to start mock server");
+
+    app_config.integrations.experimental_entra_id.graph_api_base_url =
+        server.url("/v1.0").to_string();
+
+    server
+}
+
+/// Test searching organization users with pagination against a mocked MS Graph API.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `entra-id-integration`
+///
+/// # Test Behavior
+/// The search query is forwarded as a `$search` expression, the `@odata.nextLink` of the first
+/// page is returned as an opaque `next_cursor`, and passing that cursor fetches the next page.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_entra_id_list_users_search_and_pagination(pool: Pool<Postgres>) {
+    let (mut app_config, _llm_server) = setup_mock_llm_server(None).await;
+    let graph_server = setup_mock_graph_server(&mut app_config).await;
+    let next_link = format!("{}/users?$skiptoken=page2", graph_server.url("/v1.0"));
+
+    graph_server.mock(|when, then| {
+        when.get().path("/v1.0/users").query_param(
+            "$search",
+            "\"displayName:ali\" OR \"mail:ali\" OR \"userPrincipalName:ali\"",
+        );
+        then.status(StatusCode::OK)
+            .headers([("Content-Type", "application/json")])
+            .json(json!({
+                "value": [{
+                    "id": "user-alice",
+                    "displayName": "Alice Example",
+                    "mail": "alice@example.com",
+                    "userPrincipalName": "alice@example.com",
+                    "jobTitle": "Engineer"
+                }],
+                "@odata.nextLink": next_link
+            }));
+    });
+    graph_server.mock(|when, then| {
+        when.get()
+            .path("/v1.0/users")
+            .query_param("$skiptoken", "page2");
+        then.status(StatusCode::OK)
+            .headers([("Content-Type", "application/json")])
+            .json(json!({
+                "value": [{
+                    "id": "user-alicia",
+                    "displayName": "Alicia Example",
+                    "mail": null,
+                    "userPrincipalName": "alicia@example.onmicrosoft.com",
+                    "jobTitle": null
+                }]
+            }));
+    });
+
+    let app_state = test_app_state_with_entra_id(app_config, pool).await;
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let first_response = server
+        .get("/api/v1beta/me/organization/users?q=ali&limit=1")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .with_ms_graph_token("mock-graph-token")
+        .await;
+    first_response.assert_status_ok();
+    let first_json: Value = first_response.json();
+    let users = first_json["users"].as_array().expect("Expected users array");
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0]["id"], "user-alice");
+    assert_eq!(users[0]["display_name"], "Alice Example");
+    assert_eq!(users[0]["mail"], "alice@example.com");
+    assert_eq!(users[0]["user_principal_name"], "alice@example.com");
+    assert_eq!(users[0]["subject_type_id"], "organization_user_id");
+    let cursor = first_json["next_cursor"]
+        .as_str()
+        .expect("Expected a next_cursor for the first page");
+
+    let second_response = server
+        .get("/api/v1beta/me/organization/users")
+        .add_query_param("cursor", cursor)
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .with_ms_graph_token("mock-graph-token")
+        .await;
+    second_response.assert_status_ok();
+    let second_json: Value = second_response.json();
+    let users = second_json["users"].as_array().expect("Expected users array");
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0]["id"], "user-alicia");
+    assert!(users[0]["mail"].is_null());
+    assert_eq!(
+        users[0]["user_principal_name"],
+        "alicia@example.onmicrosoft.com"
+    );
+    assert!(
+        second_json.get("next_cursor").is_none(),
+        "Expected no next_cursor for the last page"
+    );
+}
+
+/// Test that cursors pointing outside of the configured MS Graph API are rejected.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `entra-id-integration`
+///
+/// # Test Behavior
+/// Invalid cursors, and cursors whose next link points at another host, return 400 without
+/// forwarding the access token anywhere.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_entra_id_rejects_invalid_cursors(pool: Pool<Postgres>) {
+    let (mut app_config, _llm_server) = setup_mock_llm_server(None).await;
+    let _graph_server = setup_mock_graph_server(&mut app_config).await;
+
+    let app_state = test_app_state_with_entra_id(app_config, pool).await;
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let foreign_cursor = BASE64_URL_SAFE.encode(
+        json!({
+            "kind": "next_link",
+            "url": "https://attacker.example/v1.0/groups?$skiptoken=page2",
+            "search": false
+        })
+        .to_string(),
+    );
+    for cursor in ["not-a-cursor", foreign_cursor.as_str()] {
+        let response = server
+            .get("/api/v1beta/me/organization/groups")
+            .add_query_param("cursor", cursor)
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .with_ms_graph_token("mock-graph-token")
+            .await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+}
+
+/// Test that MS Graph throttling is surfaced as 503 with a retry hint.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `entra-id-integration`
+///
+/// # Test Behavior
+/// When MS Graph answers with 429 and a `Retry-After` that is too long to wait for within the
+/// request, the endpoint returns 503 with the same `Retry-After` and the `graph_throttled` code.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_entra_id_graph_throttling_returns_503(pool: Pool<Postgres>) {
+    let (mut app_config, _llm_server) = setup_mock_llm_server(None).await;
+    let graph_server = setup_mock_graph_server(&mut app_config).await;
+
+    graph_server.mock(|when, then| {
+        when.get().path("/v1.0/groups");
+        then.status(StatusCode::TOO_MANY_REQUESTS)
+            .headers([("Content-Type", "application/json"), ("Retry-After", "30")])
+            .json(json!({
+                "error": {
+                    "code": "TooManyRequests",
+                    "message": "Too many requests"
+                }
+            }));
+    });
+
+    let app_state = test_app_state_with_entra_id(app_config, pool).await;
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let response = server
+        .get("/api/v1beta/me/organization/groups")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .with_ms_graph_token("mock-graph-token")
+        .await;
+
+    response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.header("retry-after"), "30");
+    let body: Value = response.json();
+    assert_eq!(body["error"], "graph_throttled");
+    assert_eq!(body["retry_after_seconds"], 30);
+}
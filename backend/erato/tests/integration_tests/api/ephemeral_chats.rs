@@ -0,0 +1,156 @@
+//! Integration tests for ephemeral chats.
+
+use axum::http;
+use axum_test::{TestResponse, TestServer};
+use erato::db::entity::{chats, messages};
+use mocktail::MockSet;
+use sea_orm::{EntityTrait, PaginatorTrait};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TestRequestAuthExt, build_openai_text_streaming_response, create_test_server,
+    mock_llm_sse_response, parse_sse_events, setup_mock_llm_server_with_mocks,
+};
+
+async fn create_ephemeral_chat(server: &TestServer) -> TestResponse {
+    server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "ephemeral": true }))
+        .await
+}
+
+/// Find the event with the given message type in a streamed submission.
+fn find_event(response: &TestResponse, message_type: &str) -> Option<Value> {
+    parse_sse_events(response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .find(|event| event["message_type"] == message_type)
+}
+
+/// Verifies that the messages of an ephemeral chat are never written to the database.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `sse-streaming`
+///
+/// # Test Behavior
+/// With ephemeral chats enabled, an ephemeral chat is created and two consecutive messages are
+/// submitted to it. Both are answered by the chat provider, the second one continuing the thread
+/// of the first, while no chat or message is stored in the database and the chat is not listed in
+/// the recent chats.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_ephemeral_chat_is_not_persisted(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        mock_llm_sse_response(then, build_openai_text_streaming_response(&["Hello", " there!"]));
+    });
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config.chat.ephemeral_chats.enabled = true;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let create_response = create_ephemeral_chat(&server).await;
+    create_response.assert_status_ok();
+    let create_json: Value = create_response.json();
+    let chat_id = create_json["chat_id"].as_str().unwrap().to_string();
+    assert!(create_json.get("welcome_message_id").is_none());
+
+    let first_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "user_message": "Hi!",
+        }))
+        .await;
+    first_response.assert_status_ok();
+    let first_completed = find_event(&first_response, "assistant_message_completed")
+        .expect("Expected an assistant_message_completed event");
+    assert_eq!(first_completed["message"]["chat_id"], chat_id);
+
+    let second_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "previous_message_id": first_completed["message_id"],
+            "user_message": "How are you?",
+        }))
+        .await;
+    second_response.assert_status_ok();
+    let second_saved = find_event(&second_response, "user_message_saved")
+        .expect("Expected a user_message_saved event");
+    assert_eq!(
+        second_saved["message"]["previous_message_id"],
+        first_completed["message_id"]
+    );
+    assert!(find_event(&second_response, "assistant_message_completed").is_some());
+
+    assert_eq!(chats::Entity::find().count(&app_state.db).await.unwrap(), 0);
+    assert_eq!(
+        messages::Entity::find().count(&app_state.db).await.unwrap(),
+        0
+    );
+
+    let recent_chats: Value = server
+        .get("/api/v1beta/me/recent_chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    assert!(
+        recent_chats["chats"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|chat| chat["id"] != chat_id),
+        "Ephemeral chats should not be listed in the recent chats"
+    );
+}
+
+/// Verifies that ephemeral chats can only be created while the feature is enabled.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Without `chat.ephemeral_chats.enabled`, creating an ephemeral chat is rejected with 404.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_ephemeral_chat_requires_feature(pool: Pool<Postgres>) {
+    let (app_config, _llm_server) = setup_mock_llm_server_with_mocks(MockSet::new()).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    create_ephemeral_chat(&server)
+        .await
+        .assert_status(http::StatusCode::NOT_FOUND);
+}
+
+/// Verifies that a user can only hold a limited number of ephemeral chats.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// With `max_chats_per_user = 1`, the second ephemeral chat of the user is rejected with 429.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_ephemeral_chats_are_capped_per_user(pool: Pool<Postgres>) {
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(MockSet::new()).await;
+    app_config.chat.ephemeral_chats.enabled = true;
+    app_config.chat.ephemeral_chats.max_chats_per_user = 1;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    create_ephemeral_chat(&server).await.assert_status_ok();
+    create_ephemeral_chat(&server)
+        .await
+        .assert_status(http::StatusCode::TOO_MANY_REQUESTS);
+}
@@ -0,0 +1,110 @@
+//! User event stream API tests.
+
+use erato::models::user::get_or_create_user;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::time::Duration;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, setup_mock_llm_server,
+};
+
+/// Test that generation and chat events are delivered on the user event stream.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Opens the `/me/events` stream, submits a message in a separate request, and
+/// verifies that the chat creation as well as the start and completion of the
+/// generation arrive on the event stream.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_events_stream_reports_generation(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+
+    // Start a real server, as the event stream stays open while the message is submitted
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    let app: axum::Router = erato::server::router::router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server_handle = tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let client = reqwest::Client::new();
+    let base_url = format!("http://{}", server_addr);
+
+    let mut events_response = client
+        .get(format!("{}/api/v1beta/me/events", base_url))
+        .header("Authorization", format!("Bearer {}", TEST_JWT_TOKEN))
+        .send()
+        .await
+        .expect("Failed to open event stream");
+    assert!(events_response.status().is_success());
+
+    let submit_body = client
+        .post(format!("{}/api/v1beta/me/messages/submitstream", base_url))
+        .header("Authorization", format!("Bearer {}", TEST_JWT_TOKEN))
+        .json(&json!({ "user_message": "Hello from another device" }))
+        .send()
+        .await
+        .expect("Failed to send submit request")
+        .text()
+        .await
+        .expect("Failed to read submit response");
+    assert!(submit_body.contains("assistant_message_completed"));
+
+    // Read events until the generation has completed
+    let mut buffer = String::new();
+    let mut events: Vec<Value> = Vec::new();
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while !events
+            .iter()
+            .any(|event| event["type"] == "generation_completed")
+        {
+            let chunk = events_response
+                .chunk()
+                .await
+                .expect("Failed to read event stream")
+                .expect("Event stream ended unexpectedly");
+            buffer.push_str(&String::from_utf8_lossy(&chunk));
+            while let Some(end) = buffer.find("\n\n") {
+                let raw_event: String = buffer.drain(..end + 2).collect();
+                if let Some(data) = raw_event
+                    .lines()
+                    .find_map(|line| line.strip_prefix("data: "))
+                {
+                    events.push(serde_json::from_str(data).unwrap());
+                }
+            }
+        }
+    })
+    .await
+    .expect("Timed out waiting for the generation_completed event");
+
+    let event_types: Vec<&str> = events
+        .iter()
+        .map(|event| event["type"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        event_types,
+        vec!["chat_created", "generation_started", "generation_completed"]
+    );
+    let chat_id = &events[0]["chat_id"];
+    assert!(events.iter().all(|event| &event["chat_id"] == chat_id));
+    assert!(events[1]["message_id"].is_string());
+    assert_eq!(events[1]["message_id"], events[2]["message_id"]);
+
+    server_handle.abort();
+}
@@ -7,7 +7,7 @@ use erato::config::{
     PromptSourceSpecification,
 };
 use erato::server::router::router;
-use serde_json::Value;
+use serde_json::{Value, json};
 use sqlx::Pool;
 use sqlx::postgres::Postgres;
 use std::collections::HashMap;
@@ -352,6 +352,180 @@ async fn test_facets_endpoint_filters_by_policy(pool: Pool<Postgres>) {
     assert_eq!(premium_facets.len(), 2);
 }
 
+fn facet_flags(body: &Value, facet_id: &str) -> (bool, bool) {
+    let facet = body["facets"]
+        .as_array()
+        .expect("Missing facets list")
+        .iter()
+        .find(|facet| facet["id"] == facet_id)
+        .unwrap_or_else(|| panic!("Missing facet {facet_id}"));
+    (
+        facet["default_enabled"].as_bool().unwrap(),
+        facet["pinned"].as_bool().unwrap_or(false),
+    )
+}
+
+/// Test the precedence of the defaults reported by `/me/facets`.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Without user preferences the configured `default_selected_facets` apply. A user's
+/// `preference_default_selected_facets` replaces them, and facets pinned by the assistant given
+/// via `assistant_id` are enabled by default and flagged as `pinned` on top of that. Unknown
+/// facet ids are rejected both as user defaults and as assistant pins.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_facets_endpoint_merges_user_defaults_and_assistant_pins(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    app_config.experimental_facets = ExperimentalFacetsConfig {
+        facets: HashMap::from([
+            (
+                "extended_thinking".to_string(),
+                FacetConfig {
+                    display_name: "Extended thinking".to_string(),
+                    ..Default::default()
+                },
+            ),
+            (
+                "web_search".to_string(),
+                FacetConfig {
+                    display_name: "Web search".to_string(),
+                    ..Default::default()
+                },
+            ),
+            (
+                "code_review".to_string(),
+                FacetConfig {
+                    display_name: "Code review".to_string(),
+                    ..Default::default()
+                },
+            ),
+        ]),
+        priority_order: vec!["extended_thinking".to_string(), "web_search".to_string()],
+        default_selected_facets: vec!["web_search".to_string()],
+        ..Default::default()
+    };
+
+    let app_state = test_app_state(app_config, pool).await;
+    let _user = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        TEST_USER_SUBJECT,
+        None,
+    )
+    .await
+    .expect("Failed to create user");
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    // Configured defaults
+    let response = server
+        .get("/api/v1beta/me/facets")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    assert_eq!(facet_flags(&body, "web_search"), (true, false));
+    assert_eq!(facet_flags(&body, "extended_thinking"), (false, false));
+    assert_eq!(facet_flags(&body, "code_review"), (false, false));
+
+    // User defaults replace the configured defaults
+    let response = server
+        .put("/api/v1beta/me/profile/preferences")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "preference_default_selected_facets": ["extended_thinking"] }))
+        .await;
+    response.assert_status_ok();
+    let profile: Value = response.json();
+    assert_eq!(
+        profile["preference_default_selected_facets"],
+        json!(["extended_thinking"])
+    );
+
+    let response = server
+        .put("/api/v1beta/me/profile/preferences")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "preference_default_selected_facets": ["unknown_facet"] }))
+        .await;
+    response.assert_status(axum::http::StatusCode::BAD_REQUEST);
+
+    let response = server
+        .get("/api/v1beta/me/facets")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    assert_eq!(facet_flags(&body, "web_search"), (false, false));
+    assert_eq!(facet_flags(&body, "extended_thinking"), (true, false));
+    assert_eq!(facet_flags(&body, "code_review"), (false, false));
+
+    // Assistant pins are enabled on top of the user defaults
+    let response = server
+        .post("/api/v1beta/assistants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "name": "Reviewer",
+            "prompt": "You review code.",
+            "pinned_facet_ids": ["unknown_facet"]
+        }))
+        .await;
+    response.assert_status(axum::http::StatusCode::BAD_REQUEST);
+
+    let response = server
+        .post("/api/v1beta/assistants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "name": "Reviewer",
+            "prompt": "You review code.",
+            "pinned_facet_ids": ["code_review"]
+        }))
+        .await;
+    response.assert_status(axum::http::StatusCode::CREATED);
+    let assistant: Value = response.json();
+    assert_eq!(assistant["pinned_facet_ids"], json!(["code_review"]));
+    let assistant_id = assistant["id"].as_str().unwrap();
+
+    let response = server
+        .get(&format!(
+            "/api/v1beta/me/facets?assistant_id={assistant_id}"
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    assert_eq!(facet_flags(&body, "web_search"), (false, false));
+    assert_eq!(facet_flags(&body, "extended_thinking"), (true, false));
+    assert_eq!(facet_flags(&body, "code_review"), (true, true));
+
+    // Resetting the user defaults falls back to the configured defaults
+    let response = server
+        .put("/api/v1beta/me/profile/preferences")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "preference_default_selected_facets": null }))
+        .await;
+    response.assert_status_ok();
+    let profile: Value = response.json();
+    assert!(profile.get("preference_default_selected_facets").is_none());
+
+    let response = server
+        .get(&format!(
+            "/api/v1beta/me/facets?assistant_id={assistant_id}"
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    assert_eq!(facet_flags(&body, "web_search"), (true, false));
+    assert_eq!(facet_flags(&body, "extended_thinking"), (false, false));
+    assert_eq!(facet_flags(&body, "code_review"), (true, true));
+}
+
 #[sqlx::test(migrator = "crate::MIGRATOR")]
 async fn test_models_endpoint_filters_by_policy(pool: Pool<Postgres>) {
     let (mut app_config, _server) = setup_mock_llm_server(None).await;
@@ -0,0 +1,177 @@
+//! Tests for the feature flags stored in the database.
+
+use axum::http;
+use axum_test::TestServer;
+use erato::db::entity::feature_flags;
+use sea_orm::{ActiveModelTrait, ActiveValue};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    JwtTokenBuilder, TEST_JWT_TOKEN, TestRequestAuthExt, admin_app_config, admin_token,
+    create_test_server,
+};
+
+fn token_with_groups(groups: &[&str]) -> String {
+    JwtTokenBuilder::new()
+        .groups(groups.iter().map(|group| group.to_string()).collect())
+        .build()
+}
+
+async fn profile_feature_flags(server: &TestServer, token: &str) -> Value {
+    let response = server
+        .get("/api/v1beta/me/profile")
+        .with_bearer_token(token)
+        .await;
+    response.assert_status_ok();
+    response.json::<Value>()["feature_flags"].clone()
+}
+
+/// Test that group overrides take precedence over the default of a flag.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Stores `starter_prompts.enabled` as disabled by default, with an override enabling it for the
+/// `beta-testers` group and one disabling it for the `legal` group. Members of `beta-testers`
+/// can fetch the starter prompts, also when they are in `legal` as well, while members of only
+/// `legal` and users without groups get 404. `/me/profile` lists the evaluated flags, including
+/// a flag without a config toggle that is rolled out to all users.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_group_overrides_take_precedence(pool: Pool<Postgres>) {
+    let mut app_config = admin_app_config();
+    app_config.starter_prompts.enabled = true;
+    let server = create_test_server(test_app_state(app_config, pool).await);
+    let admin_token = admin_token();
+
+    let response = server
+        .put("/api/v1beta/admin/feature-flags/starter_prompts.enabled")
+        .json(&json!({
+            "description": "Starter prompts on the welcome screen",
+            "default_on": false,
+            "group_overrides": { "beta-testers": true, "legal": false },
+        }))
+        .with_bearer_token(&admin_token)
+        .await;
+    response.assert_status_ok();
+    let flag: Value = response.json();
+    assert_eq!(flag["enabled"], false);
+    assert_eq!(flag["source"], "runtime_override");
+    assert_eq!(flag["config_value"], true);
+    assert_eq!(flag["group_overrides"]["beta-testers"], true);
+
+    let response = server
+        .put("/api/v1beta/admin/feature-flags/new_sidebar")
+        .json(&json!({ "percentage_rollout": 100 }))
+        .with_bearer_token(&admin_token)
+        .await;
+    response.assert_status_ok();
+    assert_eq!(response.json::<Value>()["config_value"], Value::Null);
+
+    for (groups, enabled) in [
+        (vec!["beta-testers"], true),
+        (vec!["legal", "beta-testers"], true),
+        (vec!["legal"], false),
+        (vec![], false),
+    ] {
+        let token = token_with_groups(&groups);
+        let response = server
+            .get("/api/v1beta/me/starter-prompts")
+            .with_bearer_token(&token)
+            .await;
+        let expected_status = if enabled {
+            http::StatusCode::OK
+        } else {
+            http::StatusCode::NOT_FOUND
+        };
+        assert_eq!(response.status_code(), expected_status, "groups {groups:?}");
+
+        let flags = profile_feature_flags(&server, &token).await;
+        assert_eq!(flags["starter_prompts.enabled"], enabled);
+        assert_eq!(flags["new_sidebar"], true);
+    }
+
+    let flags: Value = server
+        .get("/api/v1beta/admin/feature-flags")
+        .with_bearer_token(&admin_token)
+        .await
+        .json();
+    assert!(
+        flags["flags"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|flag| flag["name"] == "new_sidebar")
+    );
+}
+
+/// Test that the cached flags are refreshed after changes.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// A flag saved through the admin API applies right away. A change written directly to the
+/// database, as done by another instance, applies once the cache is refreshed. Deleting the flag
+/// removes it from `/me/profile`, and invalid percentages and unknown flags are rejected.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_feature_flag_cache_is_refreshed(pool: Pool<Postgres>) {
+    let app_config = admin_app_config();
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+    let admin_token = admin_token();
+
+    let response = server
+        .put("/api/v1beta/admin/feature-flags/new_sidebar")
+        .json(&json!({ "default_on": true }))
+        .with_bearer_token(&admin_token)
+        .await;
+    response.assert_status_ok();
+    let flags = profile_feature_flags(&server, TEST_JWT_TOKEN).await;
+    assert_eq!(flags["new_sidebar"], true);
+
+    feature_flags::ActiveModel {
+        key: ActiveValue::Unchanged("new_sidebar".to_string()),
+        default_on: ActiveValue::Set(false),
+        ..Default::default()
+    }
+    .update(&app_state.db)
+    .await
+    .unwrap();
+    let flags = profile_feature_flags(&server, TEST_JWT_TOKEN).await;
+    assert_eq!(flags["new_sidebar"], true);
+
+    app_state
+        .feature_flags
+        .refresh(&app_state.db)
+        .await
+        .unwrap();
+    let flags = profile_feature_flags(&server, TEST_JWT_TOKEN).await;
+    assert_eq!(flags["new_sidebar"], false);
+
+    let response = server
+        .delete("/api/v1beta/admin/feature-flags/new_sidebar")
+        .with_bearer_token(&admin_token)
+        .await;
+    response.assert_status_ok();
+    let flags = profile_feature_flags(&server, TEST_JWT_TOKEN).await;
+    assert!(flags.get("new_sidebar").is_none());
+
+    let response = server
+        .delete("/api/v1beta/admin/feature-flags/new_sidebar")
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+
+    let response = server
+        .put("/api/v1beta/admin/feature-flags/new_sidebar")
+        .json(&json!({ "percentage_rollout": 101 }))
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,247 @@
+//! Integration tests for deleting files via `DELETE /me/files/{file_id}`.
+
+use axum::http;
+use axum_test::TestServer;
+use axum_test::multipart::{MultipartForm, Part};
+use erato::db::entity::prelude::{FileDeletions, FileUploads};
+use erato::db::entity::{file_deletions, file_uploads};
+use erato::services::file_storage::is_not_found_error;
+use mocktail::MockSet;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    RequestBodyRecorder, TEST_JWT_TOKEN, TestRequestAuthExt, build_openai_text_streaming_response,
+    create_chat, create_test_server, mock_llm_sse_response, setup_mock_llm_server_with_mocks,
+    submit_events, submit_message,
+};
+
+/// Create a chat and upload a text file to it. Returns the chat ID and the file ID.
+async fn upload_text_file(server: &TestServer) -> (String, String) {
+    let chat_id = create_chat(server, TEST_JWT_TOKEN).await;
+
+    let upload_response = server
+        .post(&format!("/api/v1beta/me/files?chat_id={chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .multipart(
+            MultipartForm::new().add_part(
+                "file",
+                Part::bytes(b"The launch is scheduled for March 3rd.".to_vec())
+                    .file_name("notes.txt")
+                    .mime_type("text/plain"),
+            ),
+        )
+        .await;
+    upload_response.assert_status_ok();
+    let file_id = upload_response.json::<Value>()["files"][0]["id"]
+        .as_str()
+        .expect("Expected file id in upload response")
+        .to_string();
+
+    (chat_id, file_id)
+}
+
+/// Verifies that a referenced file is only deleted with `force=true`, and that its contents are
+/// unavailable afterwards.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// Uploads a text file and submits a message with it. Deleting the file is rejected with 409,
+/// listing the user message that has the file attached. With `force=true`, the file is deleted:
+/// the row is kept and marked as deleted, retrieving the file returns 410, and the deletion is
+/// recorded in the audit log. A subsequent message in the chat completes, reports the file as
+/// unavailable, and sends the model a placeholder instead of the file contents.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_delete_referenced_file_requires_force(pool: Pool<Postgres>) {
+    let llm_request_recorder = RequestBodyRecorder::new();
+    let mut mocks = MockSet::new();
+    {
+        let recorder = llm_request_recorder.clone();
+        mocks.mock(move |when, then| {
+            when.post().path("/v1/chat/completions").matcher(recorder);
+            mock_llm_sse_response(then, build_openai_text_streaming_response(&["Noted."]));
+        });
+    }
+    let (mut app_config, _server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config.security.wrap_untrusted_content = false;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let (chat_id, file_id) = upload_text_file(&server).await;
+    let response = submit_message(
+        &server,
+        TEST_JWT_TOKEN,
+        &json!({
+            "existing_chat_id": chat_id,
+            "user_message": "When is the launch?",
+            "input_files_ids": [file_id]
+        }),
+    )
+    .await;
+    let events = submit_events(&response);
+    let user_message_id = events
+        .iter()
+        .find(|event| event["message_type"] == "user_message_saved")
+        .and_then(|event| event["message_id"].as_str())
+        .expect("Expected user_message_saved event")
+        .to_string();
+    assert!(
+        llm_request_recorder
+            .bodies()
+            .join("\n")
+            .contains("March 3rd")
+    );
+
+    let conflict_response = server
+        .delete(&format!("/api/v1beta/me/files/{file_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(conflict_response.status_code(), http::StatusCode::CONFLICT);
+    let conflict: Value = conflict_response.json();
+    let references = conflict["references"]
+        .as_array()
+        .expect("Expected references array");
+    assert!(
+        references
+            .iter()
+            .any(|reference| reference["kind"] == "message"
+                && reference["id"] == user_message_id.as_str()),
+        "The user message should be listed as a reference: {references:?}"
+    );
+
+    server
+        .delete(&format!("/api/v1beta/me/files/{file_id}?force=true"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::NO_CONTENT);
+
+    let file_upload_id = Uuid::parse_str(&file_id).unwrap();
+    let file_upload = FileUploads::find_by_id(file_upload_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the file upload")
+        .expect("The referenced file upload should be kept");
+    assert_eq!(file_upload.storage_status, "deleted");
+
+    server
+        .get(&format!("/api/v1beta/files/{file_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::GONE);
+
+    let deletion = FileDeletions::find()
+        .filter(file_deletions::Column::FileUploadId.eq(file_upload_id))
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the file deletion")
+        .expect("The deletion should be recorded");
+    assert!(deletion.forced);
+    assert_eq!(deletion.filename, "notes.txt");
+
+    let response = submit_message(
+        &server,
+        TEST_JWT_TOKEN,
+        &json!({
+            "existing_chat_id": chat_id,
+            "user_message": "Remind me, when was the launch again?"
+        }),
+    )
+    .await;
+    let events = submit_events(&response);
+    let files_unavailable = events
+        .iter()
+        .find(|event| event["message_type"] == "files_unavailable")
+        .expect("Expected a files_unavailable event");
+    assert_eq!(files_unavailable["files"][0]["id"], file_id.as_str());
+    assert!(
+        events
+            .iter()
+            .any(|event| event["message_type"] == "assistant_message_completed"),
+        "The generation should complete despite the deleted file"
+    );
+
+    let bodies = llm_request_recorder.bodies();
+    let last_request = bodies.last().expect("Expected a second LLM request");
+    assert!(
+        last_request.contains("no longer exists in the storage"),
+        "The model should be told that the file is unavailable"
+    );
+    assert!(!last_request.contains("March 3rd"));
+}
+
+/// Verifies that an unreferenced file is removed together with its object in the storage.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// Uploads a text file to a chat without sending it, and deletes it. The file upload row is
+/// removed, its object no longer exists in the storage, retrieving the file returns 404, and the
+/// deletion is recorded as not forced.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_delete_unreferenced_file_removes_object(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server_with_mocks(MockSet::new()).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let (_chat_id, file_id) = upload_text_file(&server).await;
+    let file_upload_id = Uuid::parse_str(&file_id).unwrap();
+    let file_upload: file_uploads::Model = FileUploads::find_by_id(file_upload_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the file upload")
+        .expect("The file upload should exist");
+    let file_storage = app_state
+        .file_storage_providers
+        .get(&file_upload.file_storage_provider_id)
+        .expect("The file storage provider should exist");
+    file_storage
+        .read_file_to_bytes(&file_upload.file_storage_path)
+        .await
+        .expect("The object should exist before the deletion");
+
+    server
+        .delete(&format!("/api/v1beta/me/files/{file_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::NO_CONTENT);
+
+    assert!(
+        FileUploads::find_by_id(file_upload_id)
+            .one(&app_state.db)
+            .await
+            .expect("Failed to load the file upload")
+            .is_none(),
+        "The file upload row should be removed"
+    );
+    let read_error = file_storage
+        .read_file_to_bytes(&file_upload.file_storage_path)
+        .await
+        .expect_err("The object should be removed from the storage");
+    assert!(is_not_found_error(&read_error));
+
+    server
+        .get(&format!("/api/v1beta/files/{file_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::NOT_FOUND);
+
+    let deletion = FileDeletions::find()
+        .filter(file_deletions::Column::FileUploadId.eq(file_upload_id))
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the file deletion")
+        .expect("The deletion should be recorded");
+    assert!(!deletion.forced);
+    assert_eq!(deletion.referencing_resources, json!([]));
+}
@@ -0,0 +1,287 @@
+//! Integration tests for the migration of inline file contents to file pointers.
+
+use axum::http;
+use axum_test::multipart::{MultipartForm, Part};
+use erato::config::FilePointerMigrationConfig;
+use erato::db::entity::messages;
+use erato::services::file_pointer_migration::{
+    FILE_POINTER_MIGRATION_TASK, run_file_pointer_migration,
+};
+use erato::services::maintenance_tasks::get_maintenance_task_progress;
+use mocktail::MockSet;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, prelude::Uuid,
+};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    RequestBodyRecorder, TEST_JWT_TOKEN, TestRequestAuthExt, admin_token,
+    build_openai_text_streaming_response, completed_assistant_message_id, configure_admin_group,
+    create_test_server, mock_llm_sse_response, setup_mock_llm_server_with_mocks,
+};
+
+/// Find the first string in `value` that starts with `prefix`.
+fn find_string_with_prefix(value: &Value, prefix: &str) -> Option<String> {
+    match value {
+        Value::String(text) if text.starts_with(prefix) => Some(text.clone()),
+        Value::Array(items) => items
+            .iter()
+            .find_map(|item| find_string_with_prefix(item, prefix)),
+        Value::Object(fields) => fields
+            .values()
+            .find_map(|field| find_string_with_prefix(field, prefix)),
+        _ => None,
+    }
+}
+
+/// Replace all text file pointers in a stored generation input with the given file contents.
+fn inline_text_file_pointers(value: &mut Value, file_contents: &str) {
+    match value {
+        Value::Object(fields)
+            if fields.get("content_type") == Some(&json!("text_file_pointer")) =>
+        {
+            *value = json!({ "content_type": "text", "text": file_contents });
+        }
+        Value::Array(items) => {
+            for item in items {
+                inline_text_file_pointers(item, file_contents);
+            }
+        }
+        Value::Object(fields) => {
+            for field in fields.values_mut() {
+                inline_text_file_pointers(field, file_contents);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Verifies that the migration converts the inline file contents of a legacy generation input
+/// into a file pointer, and that the converted history resolves to identical contents.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// Sends two messages, the first one with an attached text file, and then rewrites the stored
+/// generation input of the first answer into the legacy format with inline file contents. After
+/// running the migration, the generation input contains a file pointer again, the savings are
+/// recorded and reported by the admin status endpoint, and regenerating the second answer sends
+/// the same file contents to the LLM as the original request.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_file_pointer_migration_converts_legacy_generation_inputs(pool: Pool<Postgres>) {
+    let llm_request_recorder = RequestBodyRecorder::new();
+    let mut mocks = MockSet::new();
+    {
+        let recorder = llm_request_recorder.clone();
+        mocks.mock(move |when, then| {
+            when.post().path("/v1/chat/completions").matcher(recorder);
+            mock_llm_sse_response(then, build_openai_text_streaming_response(&["Noted."]));
+        });
+    }
+    let (mut app_config, _server) = setup_mock_llm_server_with_mocks(mocks).await;
+    configure_admin_group(&mut app_config);
+    app_config.security.wrap_untrusted_content = false;
+    let app_state = test_app_state(app_config, pool.clone()).await;
+    let server = create_test_server(app_state.clone());
+
+    let chat_response = server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({}))
+        .await;
+    chat_response.assert_status_ok();
+    let chat_id = chat_response.json::<Value>()["chat_id"]
+        .as_str()
+        .expect("Expected chat_id in response")
+        .to_string();
+
+    let upload_response = server
+        .post(&format!("/api/v1beta/me/files?chat_id={chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .multipart(
+            MultipartForm::new().add_part(
+                "file",
+                Part::bytes(b"The launch is scheduled for March 3rd.".to_vec())
+                    .file_name("notes.txt")
+                    .mime_type("text/plain"),
+            ),
+        )
+        .await;
+    upload_response.assert_status_ok();
+    let file_id = upload_response.json::<Value>()["files"][0]["id"]
+        .as_str()
+        .expect("Expected file id in upload response")
+        .to_string();
+
+    let first_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "user_message": "When is the launch?",
+            "input_files_ids": [file_id]
+        }))
+        .await;
+    first_response.assert_status_ok();
+    let first_assistant_message_id = completed_assistant_message_id(&first_response);
+
+    let file_contents = llm_request_recorder
+        .bodies()
+        .iter()
+        .filter_map(|body| serde_json::from_str::<Value>(body).ok())
+        .find_map(|body| find_string_with_prefix(&body, "File:\nfile name: notes.txt"))
+        .expect("The file contents should have been sent to the LLM");
+    assert!(file_contents.contains("March 3rd"));
+
+    let second_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "previous_message_id": first_assistant_message_id,
+            "user_message": "Thanks!"
+        }))
+        .await;
+    second_response.assert_status_ok();
+    let second_assistant_message_id = completed_assistant_message_id(&second_response);
+
+    // Rewrite the generation input of the first answer into the legacy format.
+    let first_assistant_message_id = Uuid::parse_str(&first_assistant_message_id).unwrap();
+    let first_assistant_message = messages::Entity::find_by_id(first_assistant_message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the first answer")
+        .expect("The first answer should exist");
+    let mut legacy_generation_input = first_assistant_message
+        .generation_input_messages
+        .clone()
+        .expect("The first answer should have a generation input");
+    inline_text_file_pointers(&mut legacy_generation_input, &file_contents);
+    assert!(
+        !legacy_generation_input
+            .to_string()
+            .contains("text_file_pointer")
+    );
+    messages::ActiveModel {
+        id: ActiveValue::Unchanged(first_assistant_message_id),
+        generation_input_messages: ActiveValue::Set(Some(legacy_generation_input)),
+        ..Default::default()
+    }
+    .update(&app_state.db)
+    .await
+    .expect("Failed to store the legacy generation input");
+
+    let progress = run_file_pointer_migration(
+        &app_state.db,
+        &FilePointerMigrationConfig {
+            batch_interval_ms: 0,
+            ..Default::default()
+        },
+    )
+    .await
+    .expect("The migration should succeed");
+    assert!(progress.completed_at.is_some());
+    assert_eq!(progress.migrated_messages, 1);
+    assert!(progress.bytes_saved > 0);
+
+    let migrated_generation_input = messages::Entity::find_by_id(first_assistant_message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the first answer")
+        .expect("The first answer should exist")
+        .generation_input_messages
+        .expect("The first answer should have a generation input");
+    assert!(
+        migrated_generation_input
+            .to_string()
+            .contains("text_file_pointer")
+    );
+    assert!(!migrated_generation_input.to_string().contains("March 3rd"));
+
+    let (replaced_parts, bytes_before, bytes_after) = sqlx::query_as::<_, (i32, i64, i64)>(
+        "SELECT replaced_parts, bytes_before, bytes_after FROM message_file_pointer_migrations WHERE message_id = $1",
+    )
+    .bind(first_assistant_message_id)
+    .fetch_one(&pool)
+    .await
+    .expect("The savings of the migrated message should be recorded");
+    assert_eq!(replaced_parts, 1);
+    assert!(bytes_after < bytes_before);
+
+    // Running the migration is idempotent.
+    let progress = run_file_pointer_migration(
+        &app_state.db,
+        &FilePointerMigrationConfig {
+            batch_interval_ms: 0,
+            ..Default::default()
+        },
+    )
+    .await
+    .expect("The migration should succeed again");
+    assert_eq!(progress.migrated_messages, 0);
+    let progress = get_maintenance_task_progress(&app_state.db, FILE_POINTER_MIGRATION_TASK)
+        .await
+        .expect("Failed to load the progress")
+        .expect("The progress should be recorded");
+    assert!(progress.completed_at.is_some());
+
+    let admin_token = admin_token();
+    let status_response = server
+        .get("/api/v1beta/admin/maintenance/file-pointer-migration")
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(status_response.status_code(), http::StatusCode::OK);
+    let status: Value = status_response.json();
+    assert_eq!(status["running"], false);
+    assert!(status["completed_at"].is_string());
+    assert_eq!(status["processed_messages"], progress.processed_messages);
+
+    let forbidden_response = server
+        .get("/api/v1beta/admin/maintenance/file-pointer-migration")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(
+        forbidden_response.status_code(),
+        http::StatusCode::FORBIDDEN
+    );
+
+    // Regenerating the second answer replays the migrated history of the first one.
+    let request_count = llm_request_recorder.bodies().len();
+    let regenerate_response = server
+        .post("/api/v1beta/me/messages/regeneratestream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "current_message_id": second_assistant_message_id }))
+        .await;
+    regenerate_response.assert_status_ok();
+
+    let regenerated_file_contents = llm_request_recorder.bodies()[request_count..]
+        .iter()
+        .filter_map(|body| serde_json::from_str::<Value>(body).ok())
+        .find_map(|body| find_string_with_prefix(&body, "File:\nfile name: notes.txt"))
+        .expect("The regenerated request should contain the file contents");
+    assert_eq!(regenerated_file_contents, file_contents);
+
+    let regenerated_message = messages::Entity::find()
+        .filter(messages::Column::ChatId.eq(Uuid::parse_str(&chat_id).unwrap()))
+        .filter(messages::Column::GenerationInputMessages.is_not_null())
+        .order_by_desc(messages::Column::CreatedAt)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the regenerated answer")
+        .expect("The regenerated answer should exist");
+    assert!(
+        !regenerated_message
+            .generation_input_messages
+            .expect("The regenerated answer should have a generation input")
+            .to_string()
+            .contains("March 3rd"),
+        "The regenerated answer should store file pointers instead of file contents"
+    );
+}
@@ -0,0 +1,262 @@
+//! Tests for the configuration of the sources users can add files from.
+
+use axum::http;
+use axum_test::multipart::{MultipartForm, Part};
+use erato::config::{AppConfig, McpServerAuthenticationConfig, McpServerConfig};
+use erato::db::entity::file_uploads;
+use erato::models::user::get_or_create_user;
+use mocktail::MockSet;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::env;
+
+use crate::test_utils::{
+    BodyContainsMatcher, TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt,
+    build_openai_text_streaming_response, build_openai_tool_calls_streaming_response, create_chat,
+    create_test_server, extract_full_text, hermetic_app_config, parse_sse_events,
+    setup_mock_llm_server_with_mocks,
+};
+use crate::{test_app_state, test_app_state_with_sharepoint};
+
+fn mock_mcp_base_url() -> String {
+    env::var("TEST_MOCK_MCP_SERVER_BASE_URL")
+        .unwrap_or_else(|_| "http://127.0.0.1:44321".to_string())
+}
+
+fn uploads_disabled_config() -> AppConfig {
+    let mut app_config = hermetic_app_config(None, None);
+    app_config.file_uploads.local_upload_enabled = false;
+    app_config
+}
+
+fn text_file_form() -> MultipartForm {
+    MultipartForm::new().add_part(
+        "file",
+        Part::bytes(b"Hello world".to_vec())
+            .file_name("notes.txt")
+            .mime_type("text/plain"),
+    )
+}
+
+/// Test that uploads are rejected with a structured reason when they are disabled.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// With `file_uploads.local_upload_enabled = false`, uploading a file to a chat and uploading a
+/// standalone file both respond with 403 and the `local_upload_disabled` reason, and the file
+/// capabilities don't list `local` as a source. With the `assistant_files` carve-out, standalone
+/// uploads for assistants are accepted again, while uploads to chats stay rejected.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_upload_is_rejected_when_local_uploads_are_disabled(pool: Pool<Postgres>) {
+    let app_state = test_app_state(uploads_disabled_config(), pool.clone()).await;
+    let server = create_test_server(app_state);
+    let chat_id = create_chat(&server, TEST_JWT_TOKEN).await;
+
+    for path in [
+        format!("/api/v1beta/me/files?chat_id={chat_id}"),
+        "/api/v1beta/me/files".to_string(),
+    ] {
+        let response = server
+            .post(&path)
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .multipart(text_file_form())
+            .await;
+        assert_eq!(response.status_code(), http::StatusCode::FORBIDDEN);
+        assert_eq!(
+            response.json::<Value>()["reason"],
+            json!("local_upload_disabled")
+        );
+    }
+
+    let response = server
+        .get("/api/v1beta/me/file-capabilities")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let capabilities: Value = response.json();
+    assert!(
+        capabilities
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|capability| capability.get("sources").is_none())
+    );
+
+    let mut app_config = uploads_disabled_config();
+    app_config.file_uploads.storage_carve_outs.assistant_files = true;
+    let server = create_test_server(test_app_state(app_config, pool).await);
+
+    let response = server
+        .post("/api/v1beta/me/files")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .multipart(text_file_form())
+        .await;
+    response.assert_status_ok();
+    let response = server
+        .post(&format!("/api/v1beta/me/files?chat_id={chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .multipart(text_file_form())
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::FORBIDDEN);
+}
+
+/// Test that files can only be linked from the enabled sources.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sharepoint-integration`
+///
+/// # Test Behavior
+/// With uploads disabled and the SharePoint integration enabled, a link from SharePoint passes
+/// the source check, and fails with 401 as no MS Graph access token is forwarded. The file
+/// capabilities list `sharepoint` as the only source. Once `sharepoint` is removed from
+/// `file_uploads.link_enabled_sources`, the same link responds with 403 and the
+/// `link_source_disabled` reason, and unknown sources are rejected with 400.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_link_is_only_accepted_from_enabled_sources(pool: Pool<Postgres>) {
+    let link_request = json!({
+        "source": "sharepoint",
+        "provider_metadata": { "drive_id": "drive", "item_id": "item" }
+    });
+
+    let app_state = test_app_state_with_sharepoint(uploads_disabled_config(), pool.clone()).await;
+    let server = create_test_server(app_state);
+
+    let response = server
+        .post("/api/v1beta/me/files/link")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&link_request)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::UNAUTHORIZED);
+
+    let response = server
+        .get("/api/v1beta/me/file-capabilities")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let capabilities: Value = response.json();
+    assert!(
+        capabilities
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|capability| capability["sources"] == json!(["sharepoint"]))
+    );
+
+    let mut app_config = uploads_disabled_config();
+    app_config.file_uploads.link_enabled_sources = Vec::new();
+    let server = create_test_server(test_app_state_with_sharepoint(app_config, pool).await);
+
+    let response = server
+        .post("/api/v1beta/me/files/link")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&link_request)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.json::<Value>()["reason"],
+        json!("link_source_disabled")
+    );
+
+    let response = server
+        .post("/api/v1beta/me/files/link")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "source": "google_drive", "provider_metadata": {} }))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::BAD_REQUEST);
+}
+
+/// Test that images generated by MCP tools are stored with uploads disabled and the carve-out.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// With `file_uploads.local_upload_enabled = false` and the `generated_images` carve-out, the
+/// model calls the `generate_image` tool of the mock MCP server. The generation completes, and
+/// the returned image is stored as a file of the chat under `generated_images/`.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_generated_images_are_stored_with_carve_out(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post()
+            .path("/v1/chat/completions")
+            .matcher(BodyContainsMatcher::new(&[], &["call_image"]));
+        then.status(http::StatusCode::OK)
+            .headers([("Content-Type", "text/event-stream")])
+            .bytes_stream_with_delays(build_openai_tool_calls_streaming_response(&[(
+                "call_image",
+                "generate_image",
+                json!({ "prompt": "A cat" }),
+            )]));
+    });
+    mocks.mock(|when, then| {
+        when.post()
+            .path("/v1/chat/completions")
+            .matcher(BodyContainsMatcher::new(&["call_image"], &[]));
+        then.status(http::StatusCode::OK)
+            .headers([("Content-Type", "text/event-stream")])
+            .bytes_stream_with_delays(build_openai_text_streaming_response(&["Here is a cat."]));
+    });
+
+    let (mut app_config, _server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config.file_uploads.local_upload_enabled = false;
+    app_config.file_uploads.storage_carve_outs.generated_images = true;
+    app_config.mcp_servers.insert(
+        "image-generation".to_string(),
+        McpServerConfig {
+            transport_type: "streamable_http".to_string(),
+            url: format!("{}/mcp/image-generation", mock_mcp_base_url()),
+            http_headers: None,
+            authentication: McpServerAuthenticationConfig::None,
+            max_session_idle_seconds: None,
+            on_schema_violation: Default::default(),
+        },
+    );
+    app_config.mcp_server_permissions.rules.insert(
+        "allow-image-generation".to_string(),
+        erato::config::McpServerPermissionRule::AllowAll {
+            mcp_server_ids: vec!["image-generation".to_string()],
+        },
+    );
+
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .unwrap();
+    let server = create_test_server(app_state.clone());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Draw a cat" }))
+        .await;
+    response.assert_status_ok();
+    assert_eq!(
+        extract_full_text(&parse_sse_events(&response)),
+        "Here is a cat."
+    );
+
+    let generated_images = file_uploads::Entity::find()
+        .filter(file_uploads::Column::Filename.eq("generated-cat.png"))
+        .all(&app_state.db)
+        .await
+        .unwrap();
+    assert_eq!(generated_images.len(), 1);
+    assert!(
+        generated_images[0]
+            .file_storage_path
+            .starts_with("generated_images/")
+    );
+}
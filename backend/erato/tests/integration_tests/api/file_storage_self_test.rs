@@ -0,0 +1,212 @@
+//! Tests for the self-test of the file storage providers.
+
+use axum::http;
+use erato::config::AppConfig;
+use erato::services::file_storage::{FileStorage, SHAREPOINT_PROVIDER_ID};
+use erato::services::file_storage_self_test::run_file_storage_self_tests;
+use erato::state::AppState;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TestRequestAuthExt, admin_token, configure_admin_group, create_test_server,
+    setup_mock_llm_server,
+};
+use crate::{test_app_state, test_app_state_with_sharepoint};
+
+const BROKEN_PROVIDER_ID: &str = "broken";
+
+fn self_test_path(provider_id: &str) -> String {
+    format!("/api/v1beta/admin/file-storage/{provider_id}/self-test")
+}
+
+/// Add a copy of the `seaweedfs` provider with an endpoint nothing listens on to the config, and
+/// return the app state with it registered.
+async fn app_state_with_broken_provider(
+    mut app_config: AppConfig,
+    pool: Pool<Postgres>,
+    required: bool,
+) -> AppState {
+    let mut broken_config = app_config.file_storage_providers["seaweedfs"].clone();
+    broken_config.config.endpoint = Some("http://127.0.0.1:1".to_string());
+    broken_config.required = required;
+    app_config
+        .file_storage_providers
+        .insert(BROKEN_PROVIDER_ID.to_string(), broken_config.clone());
+
+    let mut app_state = test_app_state(app_config, pool).await;
+    app_state.default_file_storage_provider = Some("seaweedfs".to_string());
+    app_state.file_storage_providers.insert(
+        BROKEN_PROVIDER_ID.to_string(),
+        FileStorage::from_config(&broken_config).expect("Failed to create the broken provider"),
+    );
+    app_state
+}
+
+/// Test the self-test of the SeaweedFS test instance.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// Running the self-test as an admin writes, presigns, fetches and deletes a probe object, and
+/// reports the provider as healthy with the latency of each step.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_file_storage_self_test_succeeds(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    configure_admin_group(&mut app_config);
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let response = server
+        .post(&self_test_path("seaweedfs"))
+        .with_bearer_token(&admin_token())
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+
+    assert_eq!(body["provider_id"], "seaweedfs");
+    assert_eq!(body["status"], "healthy");
+    assert!(body["tested_at"].is_string());
+    let steps = body["steps"].as_array().expect("Expected steps array");
+    let step_kinds: Vec<&Value> = steps.iter().map(|step| &step["step"]).collect();
+    assert_eq!(
+        step_kinds,
+        vec![
+            &json!("write"),
+            &json!("presign"),
+            &json!("fetch"),
+            &json!("delete")
+        ]
+    );
+    for step in steps {
+        assert_eq!(step["succeeded"], true, "Unexpected failed step {step}");
+        assert!(step["latency_ms"].is_u64());
+        assert!(step["error"].is_null());
+    }
+}
+
+/// Test the self-test of a provider with a wrong endpoint.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// The self-test reports the provider as unhealthy with status 200. The write of the probe object
+/// fails with an error, and the following steps are skipped.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_file_storage_self_test_reports_wrong_endpoint(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    configure_admin_group(&mut app_config);
+    let app_state = app_state_with_broken_provider(app_config, pool, false).await;
+    let server = create_test_server(app_state);
+
+    let response = server
+        .post(&self_test_path(BROKEN_PROVIDER_ID))
+        .with_bearer_token(&admin_token())
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+
+    assert_eq!(body["provider_id"], BROKEN_PROVIDER_ID);
+    assert_eq!(body["status"], "unhealthy");
+    let steps = body["steps"].as_array().expect("Expected steps array");
+    assert_eq!(steps.len(), 1);
+    assert_eq!(steps[0]["step"], "write");
+    assert_eq!(steps[0]["succeeded"], false);
+    assert!(
+        !steps[0]["error"].as_str().unwrap_or_default().is_empty(),
+        "Expected an error for the failed write"
+    );
+}
+
+/// Test the self-tests that run at startup.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// A failing provider only fails the startup if it is configured as `required`.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_startup_self_tests_fail_for_required_providers(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+
+    let app_state = app_state_with_broken_provider(app_config.clone(), pool.clone(), false).await;
+    let results = run_file_storage_self_tests(&app_state)
+        .await
+        .expect("A failing optional provider should not fail the startup");
+    let provider_ids: Vec<&str> = results
+        .iter()
+        .map(|result| result.provider_id.as_str())
+        .collect();
+    assert_eq!(provider_ids, vec![BROKEN_PROVIDER_ID, "seaweedfs"]);
+    assert!(!results[0].is_healthy());
+    assert!(results[1].is_healthy());
+
+    let app_state = app_state_with_broken_provider(app_config, pool, true).await;
+    let error = run_file_storage_self_tests(&app_state)
+        .await
+        .expect_err("A failing required provider should fail the startup");
+    assert!(error.to_string().contains(BROKEN_PROVIDER_ID));
+}
+
+/// Test that Sharepoint is reported as unverifiable.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Sharepoint only works with the access token of a user, so no steps are run.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_file_storage_self_test_sharepoint_is_unverifiable(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    configure_admin_group(&mut app_config);
+    let app_state = test_app_state_with_sharepoint(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let response = server
+        .post(&self_test_path(SHAREPOINT_PROVIDER_ID))
+        .with_bearer_token(&admin_token())
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+
+    assert_eq!(body["status"], "unverifiable");
+    assert_eq!(body["steps"], json!([]));
+    assert_eq!(body["detail"], "user-token-only, unverifiable");
+}
+
+/// Test the access checks of the self-test endpoint.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Users that aren't admins get 403, and unknown providers 404.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_file_storage_self_test_access(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    configure_admin_group(&mut app_config);
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let response = server
+        .post(&self_test_path("seaweedfs"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::FORBIDDEN);
+
+    let response = server
+        .post(&self_test_path("unknown"))
+        .with_bearer_token(&admin_token())
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,164 @@
+//! Tests for the download URLs of files, and how clients can use them.
+
+use axum::http;
+use axum_test::TestServer;
+use axum_test::multipart::{MultipartForm, Part};
+use erato::models::file_upload::create_sharepoint_file_upload;
+use erato::models::user::get_or_create_user;
+use erato::policy::engine::PolicyEngine;
+use erato::policy::types::Subject;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state_with_sharepoint;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt, create_test_server,
+    hermetic_app_config,
+};
+
+const FILE_CONTENTS: &[u8] = b"Quarterly numbers";
+
+fn file_by_id<'a>(files: &'a Value, file_id: &str) -> &'a Value {
+    files
+        .as_array()
+        .expect("Expected files array")
+        .iter()
+        .find(|file| file["id"] == json!(file_id))
+        .unwrap_or_else(|| panic!("Expected file {file_id} in the response"))
+}
+
+/// Asserts that the MinIO file has a direct URL, which can be fetched without credentials.
+async fn assert_direct_url_resolves(file: &Value) {
+    assert_eq!(file["url_kind"], json!("direct"));
+    let download_url = file["download_url"].as_str().unwrap();
+    let response = reqwest::get(download_url)
+        .await
+        .expect("Failed to fetch the direct download URL");
+    assert!(response.status().is_success());
+    assert_eq!(response.bytes().await.unwrap().as_ref(), FILE_CONTENTS);
+}
+
+/// Asserts that the SharePoint file falls back to the API route of the file, which resolves
+/// with the credentials of the user.
+async fn assert_auth_proxy_url_resolves(server: &TestServer, file: &Value, file_id: &str) {
+    assert_eq!(file["url_kind"], json!("requires_auth_proxy"));
+    assert_eq!(
+        file["download_url"],
+        json!(format!("/api/v1beta/files/{file_id}"))
+    );
+    assert_eq!(
+        file["file_contents_unavailable_missing_permissions"],
+        json!(false)
+    );
+
+    let response = server
+        .get(file["download_url"].as_str().unwrap())
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let resolved: Value = response.json();
+    assert_eq!(resolved["id"], json!(file_id));
+    assert_eq!(resolved["url_kind"], json!("requires_auth_proxy"));
+}
+
+/// Test the download URLs of assistant files stored in MinIO and linked from SharePoint.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-file-storage`
+/// - `sharepoint-integration`
+///
+/// # Test Behavior
+/// Creates an assistant with an uploaded file and a file linked from SharePoint. Without an MS
+/// Graph access token, the SharePoint file gets the API route of the file with the
+/// `requires_auth_proxy` kind in both the assistant and the frequent assistants responses, and
+/// the route resolves the file. The uploaded file gets a `direct` pre-signed URL, which returns
+/// the contents of the file.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_assistant_file_urls_for_storage_and_sharepoint_files(pool: Pool<Postgres>) {
+    let app_state = test_app_state_with_sharepoint(hermetic_app_config(None, None), pool).await;
+    let user = get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .unwrap();
+    let sharepoint_file = create_sharepoint_file_upload(
+        &app_state.db,
+        &PolicyEngine::new(),
+        &Subject::User(user.id.to_string()),
+        None,
+        "plan.docx".to_string(),
+        "drive-id".to_string(),
+        "item-id".to_string(),
+    )
+    .await
+    .unwrap();
+    let sharepoint_file_id = sharepoint_file.id.to_string();
+    let server = create_test_server(app_state);
+
+    let response = server
+        .post("/api/v1beta/me/files")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .multipart(MultipartForm::new().add_part(
+            "file",
+            Part::bytes(FILE_CONTENTS.to_vec())
+                .file_name("numbers.txt")
+                .mime_type("text/plain"),
+        ))
+        .await;
+    response.assert_status_ok();
+    let storage_file_id = response.json::<Value>()["files"][0]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let response = server
+        .post("/api/v1beta/assistants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "name": "Planning assistant",
+            "prompt": "You help with planning.",
+            "file_ids": [storage_file_id, sharepoint_file_id],
+        }))
+        .await;
+    response.assert_status(http::StatusCode::CREATED);
+    let assistant_id = response.json::<Value>()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let response = server
+        .get(&format!("/api/v1beta/assistants/{assistant_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let files = response.json::<Value>()["files"].clone();
+    assert_direct_url_resolves(file_by_id(&files, &storage_file_id)).await;
+    assert_auth_proxy_url_resolves(
+        &server,
+        file_by_id(&files, &sharepoint_file_id),
+        &sharepoint_file_id,
+    )
+    .await;
+
+    let response = server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "assistant_id": assistant_id }))
+        .await;
+    response.assert_status_ok();
+
+    let response = server
+        .get("/api/v1beta/me/frequent_assistants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let files = response.json::<Value>()["assistants"][0]["files"].clone();
+    assert_direct_url_resolves(file_by_id(&files, &storage_file_id)).await;
+    assert_auth_proxy_url_resolves(
+        &server,
+        file_by_id(&files, &sharepoint_file_id),
+        &sharepoint_file_id,
+    )
+    .await;
+}
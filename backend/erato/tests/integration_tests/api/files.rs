@@ -15,8 +15,9 @@ use sqlx::postgres::Postgres;
 
 use crate::test_app_state;
 use crate::test_utils::{
-    MockLlmConfig, RequestHeadersRecorder, TEST_JWT_TOKEN, TestRequestAuthExt, hermetic_app_config,
-    read_integration_test_file_bytes, setup_mock_llm_server, setup_mock_llm_server_with_mocks,
+    MockLlmConfig, RequestHeadersRecorder, TEST_JWT_TOKEN, TestRequestAuthExt, create_chat,
+    hermetic_app_config, read_integration_test_file_bytes, setup_mock_llm_server,
+    setup_mock_llm_server_with_mocks,
 };
 
 const CANONICAL_AUDIO_SAMPLE_RATE_HZ: usize = 16_000;
@@ -35,23 +36,6 @@ fn assert_download_url_contains_filename(download_url: &str, filename: &str) {
     );
 }
 
-async fn create_chat(server: &TestServer) -> String {
-    let create_chat_response = server
-        .post("/api/v1beta/me/chats")
-        .with_bearer_token(TEST_JWT_TOKEN)
-        .add_header(http::header::CONTENT_TYPE, "application/json")
-        .json(&json!({}))
-        .await;
-
-    create_chat_response.assert_status_ok();
-
-    let create_chat_json: Value = create_chat_response.json();
-    create_chat_json["chat_id"]
-        .as_str()
-        .expect("Expected chat_id in response")
-        .to_string()
-}
-
 async fn upload_file_to_chat(
     server: &TestServer,
     chat_id: &str,
@@ -397,7 +381,7 @@ async fn test_eml_upload_supports_rfc822_and_octet_stream_content_types(pool: Po
         .with_state(app_state);
     let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
 
-    let chat_id = create_chat(&server).await;
+    let chat_id = create_chat(&server, TEST_JWT_TOKEN).await;
     let test_cases = [
         ("message/rfc822", "email_rfc822.eml"),
         ("application/octet-stream", "email_octet_stream.eml"),
@@ -591,7 +575,7 @@ async fn test_audio_upload_initializes_transcription_metadata_with_mock_llm(pool
         .0
         .with_state(app_state);
     let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
-    let chat_id = create_chat(&server).await;
+    let chat_id = create_chat(&server, TEST_JWT_TOKEN).await;
 
     let upload_json = upload_file_to_chat(
         &server,
@@ -689,7 +673,7 @@ async fn test_audio_transcription_sends_rendered_chat_provider_headers(pool: Poo
         },
     )
     .expect("Failed to create test server");
-    let chat_id = create_chat(&server).await;
+    let chat_id = create_chat(&server, TEST_JWT_TOKEN).await;
 
     let mut websocket = server
         .get_websocket("/api/v1beta/me/files/audio-transcriptions/socket")
@@ -778,7 +762,7 @@ async fn test_get_file_by_id(pool: Pool<Postgres>) {
     let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
 
     // First, create a chat to attach the file to
-    let chat_id = create_chat(&server).await;
+    let chat_id = create_chat(&server, TEST_JWT_TOKEN).await;
 
     // Create a file to upload
     let file_content = json!({"test": "content"}).to_string();
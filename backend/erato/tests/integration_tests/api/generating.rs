@@ -1,14 +1,16 @@
 //! Generation status API tests (chats-row persistence and GET /me/generating).
 
 use chrono::Utc;
-use erato::db::entity::chats;
-use erato::db::entity::prelude::Chats;
+use erato::db::entity::prelude::{Chats, Messages};
+use erato::db::entity::{chats, messages};
+use erato::models::message::finalize_stale_message_content_drafts;
 use erato::models::user::get_or_create_user;
 use erato::services::background_tasks::TaskOutcome;
 use mocktail::MockSet;
 use sea_orm::prelude::Uuid;
 use sea_orm::{
-    ActiveModelTrait, ActiveValue, ConnectionTrait, DatabaseConnection, EntityTrait, Statement,
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+    QueryFilter, Statement,
 };
 use serde_json::{Value, json};
 use sqlx::Pool;
@@ -220,6 +222,103 @@ async fn test_generating_chats_running_then_completed(pool: Pool<Postgres>) {
     server_handle.abort();
 }
 
+/// Test that a generation keeps running when its client disconnects.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// The client of a submit stream goes away right after the first text delta. The generation
+/// still runs to the end, and the full assistant message is stored with the chat marked as
+/// 'completed'.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_generation_completes_after_client_disconnect(pool: Pool<Postgres>) {
+    let chunks: Vec<String> = (1..=10).map(|i| format!("Message {:02} ", i)).collect();
+    let expected_text: String = chunks.concat();
+    let mock_config = MockLlmConfig {
+        chunks,
+        delay_ms: 100,
+        ..Default::default()
+    };
+    let (app_config, _server) = setup_mock_llm_server(Some(mock_config)).await;
+    let app_state = test_app_state(app_config, pool).await;
+
+    let _user = get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+
+    // Real TCP server so the client can close the connection mid-stream
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    let app: axum::Router = erato::server::router::router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server_handle = tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let mut response = client
+        .post(format!(
+            "http://{}/api/v1beta/me/messages/submitstream",
+            server_addr
+        ))
+        .header("Authorization", format!("Bearer {}", TEST_JWT_TOKEN))
+        .header("Content-Type", "application/json")
+        .json(&json!({
+            "user_message": "Generate numbered messages"
+        }))
+        .send()
+        .await
+        .expect("Failed to send submit request");
+    assert!(response.status().is_success());
+
+    let mut received = String::new();
+    while !received.contains("\"text_delta\"") {
+        let chunk = response
+            .chunk()
+            .await
+            .expect("Failed to read submit body")
+            .expect("Stream ended before the first text delta");
+        received.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    let chat_id = {
+        let tasks = app_state.background_tasks.tasks.read().await;
+        tasks.keys().next().copied()
+    }
+    .expect("Expected an active background task");
+
+    // Disconnect the client
+    drop(response);
+    drop(client);
+
+    let mut chat = None;
+    for _ in 0..100 {
+        let current = Chats::find_by_id(chat_id)
+            .one(&app_state.db)
+            .await
+            .unwrap()
+            .expect("Chat row should exist");
+        if current.generation_state.as_deref() != Some("running") {
+            chat = Some(current);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let chat = chat.expect("Generation should finish after the client disconnected");
+    assert_eq!(chat.generation_state.as_deref(), Some("completed"));
+
+    let message = find_assistant_message(&app_state.db, chat_id).await;
+    assert_eq!(message.raw_message["content"][0]["text"], expected_text);
+
+    server_handle.abort();
+}
+
 /// Test that terminal entries expire from /me/generating after the retention window.
 ///
 /// # Test Categories
@@ -641,11 +740,13 @@ async fn test_generating_lease_identity_gates_terminal_writes(pool: Pool<Postgre
     let (_rx1, task1) = app_state
         .background_tasks
         .start_task(chat.id, Uuid::new_v4())
-        .await;
+        .await
+        .expect("Failed to start task");
     let (_rx2, task2) = app_state
         .background_tasks
         .start_task(chat.id, Uuid::new_v4())
-        .await;
+        .await
+        .expect("Failed to start task");
 
     // A stale wrapper finishing late must not touch the replacement generation
     app_state
@@ -761,3 +862,313 @@ async fn test_generating_chats_excludes_other_users_and_archived(pool: Pool<Post
     assert_eq!(entries[0]["state"], "running");
     assert_eq!(entries[0]["title"], "My running chat");
 }
+
+/// Find the assistant message of a chat.
+async fn find_assistant_message(db: &DatabaseConnection, chat_id: Uuid) -> messages::Model {
+    Messages::find()
+        .filter(messages::Column::ChatId.eq(chat_id))
+        .all(db)
+        .await
+        .expect("Failed to load messages")
+        .into_iter()
+        .find(|message| message.raw_message["role"] == "assistant")
+        .expect("Expected an assistant message")
+}
+
+async fn insert_assistant_message_with_draft(
+    db: &DatabaseConnection,
+    chat_id: Uuid,
+    draft_text: &str,
+) -> messages::Model {
+    messages::ActiveModel {
+        id: ActiveValue::Set(Uuid::new_v4()),
+        chat_id: ActiveValue::Set(chat_id),
+        raw_message: ActiveValue::Set(json!({ "role": "assistant", "content": [] })),
+        updated_at: ActiveValue::Set((Utc::now() - chrono::Duration::seconds(60)).into()),
+        is_message_in_active_thread: ActiveValue::Set(true),
+        content_draft: ActiveValue::Set(Some(json!([
+            { "content_type": "text", "text": draft_text }
+        ]))),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("Failed to insert message")
+}
+
+/// Test that the streamed content of an assistant message is saved as a draft.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// With a draft flush after every delta, the content streamed so far is
+/// present in `content_draft` of the assistant message while the turn is in
+/// flight. Once the turn finishes, the final content is stored in the message
+/// and the draft is cleared.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_assistant_message_content_draft_is_saved_while_streaming(pool: Pool<Postgres>) {
+    let chunks: Vec<String> = (1..=20).map(|i| format!("Message {:02}", i)).collect();
+    let mock_config = MockLlmConfig {
+        chunks,
+        delay_ms: 200,
+        ..Default::default()
+    };
+    let (mut app_config, _server) = setup_mock_llm_server(Some(mock_config)).await;
+    app_config.generation_status.content_draft_flush_deltas = 1;
+    app_config
+        .generation_status
+        .content_draft_min_flush_interval_ms = 0;
+    let app_state = test_app_state(app_config, pool).await;
+
+    let _user = get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    let app: axum::Router = erato::server::router::router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server_handle = tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let base_url = format!("http://{}", server_addr);
+    let submit_handle = tokio::spawn(async move {
+        let response = reqwest::Client::new()
+            .post(format!("{}/api/v1beta/me/messages/submitstream", base_url))
+            .header("Authorization", format!("Bearer {}", TEST_JWT_TOKEN))
+            .header("Content-Type", "application/json")
+            .json(&json!({
+                "user_message": "Generate numbered messages"
+            }))
+            .send()
+            .await
+            .expect("Failed to send submit request");
+        assert!(response.status().is_success());
+        response.text().await.expect("Failed to read submit body")
+    });
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let chat_id = {
+        let tasks = app_state.background_tasks.tasks.read().await;
+        tasks.keys().next().copied()
+    }
+    .expect("Expected an active background task");
+
+    let message = find_assistant_message(&app_state.db, chat_id).await;
+    let draft = message
+        .content_draft
+        .expect("Expected a content draft while streaming")
+        .to_string();
+    assert!(draft.contains("Message 01"));
+    assert!(!draft.contains("Message 20"));
+    assert!(
+        !message.raw_message["content"]
+            .to_string()
+            .contains("Message 01")
+    );
+
+    let _ = submit_handle.await.expect("Submit task panicked");
+
+    let message = find_assistant_message(&app_state.db, chat_id).await;
+    assert_eq!(message.content_draft, None);
+    let content = message.raw_message["content"].to_string();
+    assert!(content.contains("Message 01"));
+    assert!(content.contains("Message 20"));
+
+    server_handle.abort();
+}
+
+/// Test that stale drafts of interrupted generations are finalized.
+///
+/// # Test Categories
+/// - `uses-db`
+///
+/// # Test Behavior
+/// A draft whose generation is no longer running is merged into the message
+/// content and cleared once it is older than the staleness threshold, while
+/// the draft of a chat that is still generating is left untouched.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_stale_content_drafts_are_finalized(pool: Pool<Postgres>) {
+    let db = sea_orm::SqlxPostgresConnector::from_sqlx_postgres_pool(pool);
+    let user = get_or_create_user(&db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+
+    let interrupted_chat = insert_chat(&db, &user.id.to_string()).await;
+    let interrupted_message =
+        insert_assistant_message_with_draft(&db, interrupted_chat.id, "Interrupted answer").await;
+
+    let running_chat = insert_chat(&db, &user.id.to_string()).await;
+    mark_running(&db, running_chat.id, 0).await;
+    let running_message =
+        insert_assistant_message_with_draft(&db, running_chat.id, "Running answer").await;
+
+    let finalized = finalize_stale_message_content_drafts(&db, 30)
+        .await
+        .expect("Failed to finalize drafts");
+    assert_eq!(finalized, 1);
+
+    let message = Messages::find_by_id(interrupted_message.id)
+        .one(&db)
+        .await
+        .unwrap()
+        .expect("Message should exist");
+    assert_eq!(message.content_draft, None);
+    assert_eq!(
+        message.raw_message["content"][0]["text"],
+        "Interrupted answer"
+    );
+
+    let message = Messages::find_by_id(running_message.id)
+        .one(&db)
+        .await
+        .unwrap()
+        .expect("Message should exist");
+    assert!(message.content_draft.is_some());
+    assert_eq!(message.raw_message["content"], json!([]));
+}
+
+/// Test that only one backend replica generates in a chat at a time.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Two app states share one database with different replica IDs. While replica A
+/// holds the generation lease of a chat, replica B can't start a task for it, and
+/// its submit and resume endpoints return 409 with the message being generated and
+/// the ID of replica A, without storing a message. Once A finishes, B can start.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_generation_lease_is_exclusive_across_replicas(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    app_config.generation_status.replica_id = Some("replica-a".to_string());
+    let replica_a = test_app_state(app_config.clone(), pool.clone()).await;
+    app_config.generation_status.replica_id = Some("replica-b".to_string());
+    let replica_b = test_app_state(app_config, pool).await;
+
+    let user = get_or_create_user(&replica_a.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let chat = insert_chat(&replica_a.db, &user.id.to_string()).await;
+    replica_b.global_policy_engine.invalidate_data().await;
+
+    let (_rx, task) = replica_a
+        .background_tasks
+        .start_task(chat.id, Uuid::new_v4())
+        .await
+        .expect("Replica A should claim the chat");
+    let error = replica_b
+        .background_tasks
+        .start_task(chat.id, Uuid::new_v4())
+        .await
+        .expect_err("Replica B must not start a second generation");
+    assert_eq!(error.replica_id, "replica-a");
+    assert_eq!(error.message_id, None);
+
+    let message_id = Uuid::new_v4();
+    replica_a
+        .background_tasks
+        .record_generation_message_id(&chat.id, task.generation_id, message_id)
+        .await;
+
+    let server = create_test_server(replica_b.clone());
+    let submit = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat.id,
+            "user_message": "hello",
+        }))
+        .await;
+    submit.assert_status(axum::http::StatusCode::CONFLICT);
+    let body: Value = serde_json::from_str(&submit.text()).expect("JSON conflict body");
+    assert_eq!(body["replica_id"], "replica-a");
+    assert_eq!(body["message_id"], message_id.to_string());
+    let stored_messages = Messages::find()
+        .filter(messages::Column::ChatId.eq(chat.id))
+        .all(&replica_b.db)
+        .await
+        .expect("Failed to load messages");
+    assert!(stored_messages.is_empty(), "No message should be stored");
+
+    let resume = server
+        .post("/api/v1beta/me/messages/resumestream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "chat_id": chat.id }))
+        .await;
+    resume.assert_status(axum::http::StatusCode::CONFLICT);
+
+    replica_a
+        .background_tasks
+        .remove_task(&chat.id, task.generation_id, TaskOutcome::Completed)
+        .await;
+    replica_b
+        .background_tasks
+        .start_task(chat.id, Uuid::new_v4())
+        .await
+        .expect("Replica B should claim the chat once A finished");
+}
+
+/// Test that the lease of a replica that stopped heartbeating can be taken over.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// A running lease of another replica with a fresh heartbeat blocks a new task,
+/// while the same lease with a heartbeat older than `stale_after_secs` is claimed,
+/// and the chats row then names the new replica.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_generation_lease_of_dead_replica_is_taken_over(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    app_config.generation_status.replica_id = Some("replica-b".to_string());
+    let app_state = test_app_state(app_config, pool).await;
+    let chat = insert_chat(&app_state.db, "dead-replica-owner").await;
+
+    let set_lease_owner = |chat_id: Uuid| {
+        Statement::from_sql_and_values(
+            sea_orm::DatabaseBackend::Postgres,
+            "UPDATE chats SET generation_replica_id = 'dead-replica' WHERE id = $1",
+            [chat_id.into()],
+        )
+    };
+
+    mark_running(&app_state.db, chat.id, 0).await;
+    app_state
+        .db
+        .execute_raw(set_lease_owner(chat.id))
+        .await
+        .expect("Failed to set lease owner");
+    let error = app_state
+        .background_tasks
+        .start_task(chat.id, Uuid::new_v4())
+        .await
+        .expect_err("A live lease of another replica must block");
+    assert_eq!(error.replica_id, "dead-replica");
+
+    mark_running(&app_state.db, chat.id, 600).await;
+    app_state
+        .background_tasks
+        .start_task(chat.id, Uuid::new_v4())
+        .await
+        .expect("An expired lease should be taken over");
+    let row = Chats::find_by_id(chat.id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load chat")
+        .expect("Chat missing");
+    assert_eq!(row.generation_replica_id.as_deref(), Some("replica-b"));
+    assert_eq!(row.generation_state.as_deref(), Some("running"));
+}
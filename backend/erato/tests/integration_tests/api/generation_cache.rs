@@ -0,0 +1,91 @@
+//! Integration tests for the generation cache.
+
+use axum_test::TestResponse;
+use erato::db::entity::messages;
+use mocktail::MockSet;
+use sea_orm::{DatabaseConnection, EntityTrait, prelude::Uuid};
+use serde_json::json;
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    RequestBodyRecorder, TEST_JWT_TOKEN, build_openai_text_streaming_response,
+    completed_assistant_message_id, create_test_server, mock_llm_sse_response,
+    setup_mock_llm_server_with_mocks, submit_message,
+};
+
+/// Load the stored assistant message of a submission.
+async fn assistant_message(db: &DatabaseConnection, response: &TestResponse) -> messages::Model {
+    let message_id = completed_assistant_message_id(response);
+    messages::Entity::find_by_id(Uuid::parse_str(&message_id).unwrap())
+        .one(db)
+        .await
+        .expect("Failed to load the assistant message")
+        .expect("The assistant message should exist")
+}
+
+/// Verifies that identical generation requests are answered from the generation cache.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `sse-streaming`
+///
+/// # Test Behavior
+/// With the generation cache enabled, the same message is submitted in two new chats. Only the
+/// first submission reaches the chat provider; the second one is replayed from the cache with the
+/// same text, is marked as a cache hit and reports no token usage. A different message misses the
+/// cache and is sent to the chat provider again.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_generation_cache_replays_identical_requests(pool: Pool<Postgres>) {
+    let llm_request_recorder = RequestBodyRecorder::new();
+    let mut mocks = MockSet::new();
+    {
+        let recorder = llm_request_recorder.clone();
+        mocks.mock(move |when, then| {
+            when.post().path("/v1/chat/completions").matcher(recorder);
+            mock_llm_sse_response(
+                then,
+                build_openai_text_streaming_response(&["The capital", " of France is Paris."]),
+            );
+        });
+    }
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config.chat.generation_cache.enabled = true;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let question = json!({ "user_message": "What is the capital of France?" });
+    let first_response = submit_message(&server, TEST_JWT_TOKEN, &question).await;
+    let first_message = assistant_message(&app_state.db, &first_response).await;
+    assert_eq!(llm_request_recorder.bodies().len(), 1);
+    let first_metadata = first_message.generation_metadata.unwrap_or_default();
+    assert!(first_metadata["cache_hit"].is_null());
+
+    let cached_response = submit_message(&server, TEST_JWT_TOKEN, &question).await;
+    let cached_message = assistant_message(&app_state.db, &cached_response).await;
+    assert_eq!(
+        llm_request_recorder.bodies().len(),
+        1,
+        "The second submission should be answered from the cache"
+    );
+    assert_ne!(cached_message.chat_id, first_message.chat_id);
+    assert_eq!(
+        cached_message.raw_message["content"][0]["text"],
+        "The capital of France is Paris."
+    );
+    let cached_metadata = cached_message
+        .generation_metadata
+        .expect("The cached answer should have generation metadata");
+    assert_eq!(cached_metadata["cache_hit"], json!(true));
+    assert_eq!(
+        cached_metadata["used_total_tokens"].as_u64().unwrap_or(0),
+        0
+    );
+
+    let other_question = json!({ "user_message": "What is the capital of Italy?" });
+    submit_message(&server, TEST_JWT_TOKEN, &other_question).await;
+    assert_eq!(llm_request_recorder.bodies().len(), 2);
+}
@@ -0,0 +1,181 @@
+//! Integration tests for the tools and chat options recorded per generation.
+
+use axum::http;
+use erato::config::{
+    ExperimentalFacetsConfig, FacetConfig, McpServerAuthenticationConfig, McpServerConfig,
+    McpServerPermissionRule,
+};
+use erato::models::user::get_or_create_user;
+use mocktail::MockSet;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::collections::HashMap;
+use std::env;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt, admin_token,
+    build_openai_text_streaming_response, configure_admin_group, create_test_server,
+    parse_sse_events, setup_mock_llm_server_with_mocks,
+};
+
+fn mock_mcp_base_url() -> String {
+    env::var("TEST_MOCK_MCP_SERVER_BASE_URL")
+        .unwrap_or_else(|_| "http://127.0.0.1:44321".to_string())
+}
+
+fn mcp_server_config(path: &str) -> McpServerConfig {
+    McpServerConfig {
+        transport_type: "streamable_http".to_string(),
+        url: format!("{}{path}", mock_mcp_base_url()),
+        http_headers: None,
+        authentication: McpServerAuthenticationConfig::None,
+        max_session_idle_seconds: None,
+        on_schema_violation: Default::default(),
+    }
+}
+
+/// The `(server_id, name)` pairs of recorded offered tools, sorted.
+fn offered_tool_pairs(offered_tools: &Value) -> Vec<(String, String)> {
+    let mut pairs: Vec<(String, String)> = offered_tools["tools"]
+        .as_array()
+        .expect("Expected the offered tools")
+        .iter()
+        .map(|tool| {
+            (
+                tool["server_id"].as_str().unwrap_or_default().to_string(),
+                tool["name"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    pairs.sort();
+    pairs
+}
+
+/// Verifies that the tools offered to the model are recorded with the generated message.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// The `files` and `image-generation` MCP servers of the mock MCP server are configured, and a
+/// selected facet only allows the tools of `files`. The dry run and the generated message both
+/// list `list_files` and `read_file` of `files` as offered tools, without `generate_image`. The
+/// usage of the message reports that two tools were offered, and the generation detail of the
+/// admin API returns the offered tools, the chat options and the chat provider. The generation
+/// detail is not available to users without the admin group.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_offered_tools_are_recorded(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        then.status(http::StatusCode::OK)
+            .headers([("Content-Type", "text/event-stream")])
+            .bytes_stream_with_delays(build_openai_text_streaming_response(&["No tools needed."]));
+    });
+    let (mut app_config, _server) = setup_mock_llm_server_with_mocks(mocks).await;
+    configure_admin_group(&mut app_config);
+    app_config.debug.allow_dry_run = true;
+    app_config
+        .mcp_servers
+        .insert("files".to_string(), mcp_server_config("/mcp/file"));
+    app_config.mcp_servers.insert(
+        "image-generation".to_string(),
+        mcp_server_config("/mcp/image-generation"),
+    );
+    app_config.mcp_server_permissions.rules.insert(
+        "allow-all".to_string(),
+        McpServerPermissionRule::AllowAll {
+            mcp_server_ids: vec!["files".to_string(), "image-generation".to_string()],
+        },
+    );
+    app_config.experimental_facets = ExperimentalFacetsConfig {
+        facets: HashMap::from([(
+            "files_only".to_string(),
+            FacetConfig {
+                display_name: "Files only".to_string(),
+                icon: None,
+                additional_system_prompt: None,
+                tool_call_allowlist: vec!["files/*".to_string()],
+                model_settings: Default::default(),
+                disable_facet_prompt_template: true,
+                hidden: false,
+                hidden_always_active_for_platform: None,
+            },
+        )]),
+        priority_order: vec!["files_only".to_string()],
+        tool_call_allowlist: vec![],
+        facet_prompt_template: None,
+        only_single_facet: false,
+        show_facet_indicator_with_display_name: false,
+        default_selected_facets: vec![],
+    };
+
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .unwrap();
+    let server = create_test_server(app_state);
+    let expected_tools = vec![
+        ("files".to_string(), "list_files".to_string()),
+        ("files".to_string(), "read_file".to_string()),
+    ];
+
+    let dry_run_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "user_message": "What is in my files?",
+            "selected_facet_ids": ["files_only"],
+            "dry_run": true,
+        }))
+        .await;
+    dry_run_response.assert_status_ok();
+    let dry_run: Value = dry_run_response.json();
+    assert_eq!(offered_tool_pairs(&dry_run["offered_tools"]), expected_tools);
+    assert_eq!(dry_run["offered_tools"]["count"], 2);
+    assert_eq!(dry_run["offered_tools"]["truncated"], false);
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "user_message": "What is in my files?",
+            "selected_facet_ids": ["files_only"],
+        }))
+        .await;
+    response.assert_status_ok();
+    let completed = parse_sse_events(&response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .find(|event| event["message_type"] == "assistant_message_completed")
+        .expect("Expected a completed message");
+    assert_eq!(completed["message"]["usage"]["tools_offered"], true);
+    assert_eq!(completed["message"]["usage"]["offered_tool_count"], 2);
+    let message_id = completed["message_id"].as_str().unwrap();
+
+    let admin_token = admin_token();
+    let detail_response = server
+        .get(&format!("/api/v1beta/admin/messages/{message_id}/generation-detail"))
+        .with_bearer_token(&admin_token)
+        .await;
+    detail_response.assert_status_ok();
+    let detail: Value = detail_response.json();
+    assert_eq!(detail["message_id"], message_id);
+    assert_eq!(detail["chat_provider_id"], dry_run["chat_provider_id"]);
+    assert!(detail["chat_options"].is_object());
+    assert_eq!(offered_tool_pairs(&detail["offered_tools"]), expected_tools);
+    assert_eq!(detail["offered_tools"]["count"], 2);
+    assert_eq!(detail["offered_tools"]["truncated"], false);
+    assert_eq!(detail["mcp_servers_unavailable"], json!([]));
+
+    server
+        .get(&format!("/api/v1beta/admin/messages/{message_id}/generation-detail"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::FORBIDDEN);
+}
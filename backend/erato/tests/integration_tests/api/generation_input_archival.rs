@@ -0,0 +1,351 @@
+//! Integration tests for the archival of the generation inputs of old archived chats.
+
+use axum::http;
+use erato::config::{GenerationInputArchivalConfig, GenerationInputArchiveStorage};
+use erato::db::entity::messages;
+use erato::services::generation_input_archive::{
+    ArchivedGenerationInput, ArchivedGenerationInputLocation, run_generation_input_archival,
+};
+use mocktail::MockSet;
+use sea_orm::{EntityTrait, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    RequestBodyRecorder, TEST_JWT_TOKEN, TestRequestAuthExt, admin_token,
+    build_openai_text_streaming_response, completed_assistant_message_id, configure_admin_group,
+    create_test_server, mock_llm_sse_response, setup_mock_llm_server_with_mocks,
+};
+
+/// The `messages` of the last request that was sent to the LLM.
+fn last_request_messages(recorder: &RequestBodyRecorder) -> Value {
+    let bodies = recorder.bodies();
+    let body: Value = serde_json::from_str(bodies.last().expect("Expected a request to the LLM"))
+        .expect("The request to the LLM should be JSON");
+    body["messages"].clone()
+}
+
+/// A config that archives all generation inputs of archived chats without waiting.
+fn archival_config(storage: GenerationInputArchiveStorage) -> GenerationInputArchivalConfig {
+    GenerationInputArchivalConfig {
+        min_age_days: 0,
+        storage,
+        batch_interval_ms: 0,
+        ..Default::default()
+    }
+}
+
+/// Verifies that the archival compresses the generation inputs of an archived chat, and that the
+/// archived history resolves to an identical prompt.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Sends two long messages, regenerates the second answer, and archives the chat. A dry run
+/// reports the savings without changing the generation inputs. The archival then replaces both
+/// generation inputs with pointers to compressed copies in the database, and the admin status
+/// endpoint reports the savings. After restoring the chat, regenerating the second answer sends
+/// exactly the same messages to the LLM as before the archival.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_generation_input_archival_preserves_prompts(pool: Pool<Postgres>) {
+    let llm_request_recorder = RequestBodyRecorder::new();
+    let mut mocks = MockSet::new();
+    {
+        let recorder = llm_request_recorder.clone();
+        mocks.mock(move |when, then| {
+            when.post().path("/v1/chat/completions").matcher(recorder);
+            mock_llm_sse_response(then, build_openai_text_streaming_response(&["Noted."]));
+        });
+    }
+    let (mut app_config, _server) = setup_mock_llm_server_with_mocks(mocks).await;
+    configure_admin_group(&mut app_config);
+    let app_state = test_app_state(app_config, pool.clone()).await;
+    let server = create_test_server(app_state.clone());
+
+    let chat_response = server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({}))
+        .await;
+    chat_response.assert_status_ok();
+    let chat_id = chat_response.json::<Value>()["chat_id"]
+        .as_str()
+        .expect("Expected chat_id in response")
+        .to_string();
+
+    let first_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "user_message": "Please remember this checklist item. ".repeat(50)
+        }))
+        .await;
+    first_response.assert_status_ok();
+    let first_assistant_message_id = completed_assistant_message_id(&first_response);
+
+    let second_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "previous_message_id": first_assistant_message_id,
+            "user_message": "And remember this one as well. ".repeat(50)
+        }))
+        .await;
+    second_response.assert_status_ok();
+    let second_assistant_message_id = completed_assistant_message_id(&second_response);
+
+    let regenerate = || {
+        server
+            .post("/api/v1beta/me/messages/regeneratestream")
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .json(&json!({ "current_message_id": second_assistant_message_id }))
+    };
+    regenerate().await.assert_status_ok();
+    let original_messages = last_request_messages(&llm_request_recorder);
+
+    server
+        .post(&format!("/api/v1beta/chats/{chat_id}/archive"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({}))
+        .await
+        .assert_status_ok();
+
+    let first_assistant_message_id = Uuid::parse_str(&first_assistant_message_id).unwrap();
+    let original_generation_input = messages::Entity::find_by_id(first_assistant_message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the first answer")
+        .expect("The first answer should exist")
+        .generation_input_messages
+        .expect("The first answer should have a generation input");
+
+    let progress = run_generation_input_archival(
+        &app_state.db,
+        &app_state.generation_input_archive,
+        &app_state.default_file_storage_provider_id(),
+        &archival_config(GenerationInputArchiveStorage::Database),
+        true,
+    )
+    .await
+    .expect("The dry run should succeed");
+    assert!(progress.completed_at.is_some());
+    assert!(progress.migrated_messages >= 2);
+    assert!(progress.bytes_saved > 0);
+    let dry_run_generation_input = messages::Entity::find_by_id(first_assistant_message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the first answer")
+        .expect("The first answer should exist")
+        .generation_input_messages;
+    assert_eq!(
+        dry_run_generation_input.as_ref(),
+        Some(&original_generation_input)
+    );
+
+    let progress = run_generation_input_archival(
+        &app_state.db,
+        &app_state.generation_input_archive,
+        &app_state.default_file_storage_provider_id(),
+        &archival_config(GenerationInputArchiveStorage::Database),
+        false,
+    )
+    .await
+    .expect("The archival should succeed");
+    assert!(progress.migrated_messages >= 2);
+    assert!(progress.bytes_saved > 0);
+
+    let archived_generation_input = messages::Entity::find_by_id(first_assistant_message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the first answer")
+        .expect("The first answer should exist")
+        .generation_input_messages
+        .expect("The archived answer should still have a generation input");
+    let archived = ArchivedGenerationInput::from_generation_input(&archived_generation_input)
+        .expect("The generation input should be replaced with a pointer");
+    assert_eq!(archived.location, ArchivedGenerationInputLocation::Database);
+    assert!(archived.compressed_bytes < archived.uncompressed_bytes);
+    let (compressed_bytes,) = sqlx::query_as::<_, (i32,)>(
+        "SELECT octet_length(compressed_input) FROM archived_generation_inputs WHERE message_id = $1",
+    )
+    .bind(first_assistant_message_id)
+    .fetch_one(&pool)
+    .await
+    .expect("The compressed generation input should be stored");
+    assert_eq!(compressed_bytes as u64, archived.compressed_bytes);
+
+    // Running the archival again doesn't archive the pointers.
+    let progress = run_generation_input_archival(
+        &app_state.db,
+        &app_state.generation_input_archive,
+        &app_state.default_file_storage_provider_id(),
+        &archival_config(GenerationInputArchiveStorage::Database),
+        false,
+    )
+    .await
+    .expect("The archival should succeed again");
+    assert_eq!(progress.migrated_messages, 0);
+
+    let admin_token = admin_token();
+    let status_response = server
+        .get("/api/v1beta/admin/maintenance/generation-input-archival")
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(status_response.status_code(), http::StatusCode::OK);
+    let status: Value = status_response.json();
+    assert_eq!(status["dry_run"], false);
+    assert_eq!(status["running"], false);
+    assert!(status["completed_at"].is_string());
+
+    let dry_run_status_response = server
+        .get("/api/v1beta/admin/maintenance/generation-input-archival?dry_run=true")
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(dry_run_status_response.status_code(), http::StatusCode::OK);
+    let dry_run_status: Value = dry_run_status_response.json();
+    assert_eq!(dry_run_status["dry_run"], true);
+    assert!(dry_run_status["bytes_saved"].as_i64().unwrap() > 0);
+
+    let forbidden_response = server
+        .get("/api/v1beta/admin/maintenance/generation-input-archival")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(
+        forbidden_response.status_code(),
+        http::StatusCode::FORBIDDEN
+    );
+
+    // Restore the chat, as archived chats can't be continued.
+    sqlx::query("UPDATE chats SET archived_at = NULL WHERE id = $1")
+        .bind(Uuid::parse_str(&chat_id).unwrap())
+        .execute(&pool)
+        .await
+        .expect("Failed to restore the chat");
+
+    regenerate().await.assert_status_ok();
+    assert_eq!(
+        last_request_messages(&llm_request_recorder),
+        original_messages
+    );
+}
+
+/// Verifies that generation inputs archived to the file storage are restored unchanged.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// Sends a long message, archives the chat, and archives its generation input to the file
+/// storage. The pointer references the compressed file, and restoring the answer yields the
+/// original generation input. Deleting the archived copy removes the file.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_generation_input_archival_to_file_storage(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        mock_llm_sse_response(then, build_openai_text_streaming_response(&["Noted."]));
+    });
+    let (app_config, _server) = setup_mock_llm_server_with_mocks(mocks).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let chat_response = server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({}))
+        .await;
+    chat_response.assert_status_ok();
+    let chat_id = chat_response.json::<Value>()["chat_id"]
+        .as_str()
+        .expect("Expected chat_id in response")
+        .to_string();
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "user_message": "Please remember this checklist item. ".repeat(50)
+        }))
+        .await;
+    response.assert_status_ok();
+    let assistant_message_id = Uuid::parse_str(&completed_assistant_message_id(&response)).unwrap();
+
+    server
+        .post(&format!("/api/v1beta/chats/{chat_id}/archive"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({}))
+        .await
+        .assert_status_ok();
+
+    let original_message = messages::Entity::find_by_id(assistant_message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the answer")
+        .expect("The answer should exist");
+
+    let file_storage_provider_id = app_state.default_file_storage_provider_id();
+    run_generation_input_archival(
+        &app_state.db,
+        &app_state.generation_input_archive,
+        &file_storage_provider_id,
+        &archival_config(GenerationInputArchiveStorage::FileStorage),
+        false,
+    )
+    .await
+    .expect("The archival should succeed");
+
+    let archived_message = messages::Entity::find_by_id(assistant_message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the answer")
+        .expect("The answer should exist");
+    let archived = archived_message
+        .generation_input_messages
+        .as_ref()
+        .and_then(ArchivedGenerationInput::from_generation_input)
+        .expect("The generation input should be replaced with a pointer");
+    let ArchivedGenerationInputLocation::FileStorage {
+        file_storage_provider_id: archived_provider_id,
+        path,
+    } = &archived.location
+    else {
+        panic!("The generation input should be archived to the file storage");
+    };
+    assert_eq!(archived_provider_id, &file_storage_provider_id);
+    assert_eq!(
+        path,
+        &format!("archived-generation-inputs/{assistant_message_id}.json.zst")
+    );
+
+    let restored_message = app_state
+        .generation_input_archive
+        .rehydrate(&app_state.db, archived_message)
+        .await
+        .expect("The generation input should be restored");
+    assert_eq!(
+        restored_message.generation_input_messages,
+        original_message.generation_input_messages
+    );
+
+    app_state
+        .generation_input_archive
+        .delete(&app_state.db, assistant_message_id, &archived)
+        .await
+        .expect("The archived copy should be deleted");
+    let file_storage = app_state
+        .file_storage_providers
+        .get(&file_storage_provider_id)
+        .expect("The default file storage provider should exist");
+    assert!(file_storage.read_file_to_bytes(path).await.is_err());
+}
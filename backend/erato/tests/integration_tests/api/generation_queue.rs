@@ -0,0 +1,191 @@
+//! Tests for the concurrency limit of generations and the queue of generations over it.
+
+use erato::models::user::get_or_create_user;
+use erato::state::AppState;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    MockLlmConfig, TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, setup_mock_llm_server,
+};
+
+/// Mock LLM config whose generations take about a second.
+fn slow_llm_config() -> MockLlmConfig {
+    MockLlmConfig {
+        chunks: (1..=5).map(|i| format!("Part {i}. ")).collect(),
+        delay_ms: 200,
+        ..Default::default()
+    }
+}
+
+/// Serve the app on a real TCP listener, so that requests can run concurrently.
+async fn serve(app_state: AppState) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    let app: axum::Router = erato::server::router::router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    format!("http://{}", server_addr)
+}
+
+/// Submit a message in a new chat, and return the status, the events of the stream and the time
+/// the stream ended.
+fn spawn_submit(
+    base_url: &str,
+    user_message: &str,
+) -> JoinHandle<(reqwest::StatusCode, Vec<Value>, Instant)> {
+    let url = format!("{}/api/v1beta/me/messages/submitstream", base_url);
+    let body = json!({ "user_message": user_message });
+    tokio::spawn(async move {
+        let response = reqwest::Client::new()
+            .post(url)
+            .header("Authorization", format!("Bearer {}", TEST_JWT_TOKEN))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to send submit request");
+        let status = response.status();
+        let text = response.text().await.expect("Failed to read submit body");
+        let events = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str::<Value>(data).ok())
+            .collect();
+        (status, events, Instant::now())
+    })
+}
+
+fn queue_positions(events: &[Value]) -> Vec<u64> {
+    events
+        .iter()
+        .filter(|event| event["message_type"] == "queued")
+        .map(|event| event["position"].as_u64().expect("Expected a position"))
+        .collect()
+}
+
+fn is_completed(events: &[Value]) -> bool {
+    events
+        .iter()
+        .any(|event| event["message_type"] == "assistant_message_completed")
+}
+
+/// Test that generations over the concurrency limit wait in the queue.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// With a limit of one concurrent generation, three messages are submitted one after the other
+/// while the first one is generated. The user messages of the later ones are saved right away,
+/// and their streams report their position in the queue until they start, with decreasing
+/// positions. All three are answered, in the order they were submitted.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_generations_over_the_limit_are_queued(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(Some(slow_llm_config())).await;
+    app_config.chat.max_concurrent_generations = Some(1);
+    app_config.chat.queue_when_limited = true;
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let base_url = serve(app_state).await;
+
+    let first = spawn_submit(&base_url, "First question");
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let second = spawn_submit(&base_url, "Second question");
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let third = spawn_submit(&base_url, "Third question");
+
+    let (first_status, first_events, first_completed_at) = first.await.unwrap();
+    let (second_status, second_events, second_completed_at) = second.await.unwrap();
+    let (third_status, third_events, third_completed_at) = third.await.unwrap();
+    for status in [first_status, second_status, third_status] {
+        assert!(status.is_success());
+    }
+
+    for events in [&second_events, &third_events] {
+        let saved = events
+            .iter()
+            .position(|event| event["message_type"] == "user_message_saved")
+            .expect("Expected the user message to be saved");
+        let queued = events
+            .iter()
+            .position(|event| event["message_type"] == "queued")
+            .expect("Expected queued events");
+        assert!(saved < queued, "The user message should be saved first");
+    }
+
+    assert!(queue_positions(&first_events).is_empty());
+    let second_positions = queue_positions(&second_events);
+    assert!(second_positions.iter().all(|&position| position == 1));
+    let third_positions = queue_positions(&third_events);
+    assert_eq!(third_positions.first(), Some(&2));
+    assert_eq!(third_positions.last(), Some(&1));
+    assert!(
+        third_positions.windows(2).all(|pair| pair[0] >= pair[1]),
+        "Positions should only decrease: {third_positions:?}"
+    );
+    // The first generation completed before the third one left the queue
+    assert!(
+        third_events
+            .iter()
+            .any(|event| event["message_type"] == "queued"
+                && event["estimated_wait_seconds"].is_u64()),
+        "Expected a wait estimate once a generation completed"
+    );
+
+    assert!(is_completed(&first_events));
+    assert!(is_completed(&second_events));
+    assert!(is_completed(&third_events));
+    assert!(first_completed_at < second_completed_at);
+    assert!(second_completed_at < third_completed_at);
+}
+
+/// Test that generations over the concurrency limit are rejected without queueing.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// With a limit of one concurrent generation and queueing disabled, a message submitted while
+/// another one is generated is rejected with 429, and is accepted once the generation completed.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_generations_over_the_limit_are_rejected(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(Some(slow_llm_config())).await;
+    app_config.chat.max_concurrent_generations = Some(1);
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let base_url = serve(app_state).await;
+
+    let first = spawn_submit(&base_url, "First question");
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let (status, events, _) = spawn_submit(&base_url, "Second question").await.unwrap();
+    assert_eq!(status, reqwest::StatusCode::TOO_MANY_REQUESTS);
+    assert!(events.is_empty());
+
+    let (status, events, _) = first.await.unwrap();
+    assert!(status.is_success());
+    assert!(is_completed(&events));
+
+    let (status, events, _) = spawn_submit(&base_url, "Third question").await.unwrap();
+    assert!(status.is_success());
+    assert!(is_completed(&events));
+}
@@ -0,0 +1,423 @@
+//! Integration tests for the per-model limits on attachments.
+
+use axum::http;
+use axum_test::TestServer;
+use axum_test::multipart::{MultipartForm, Part};
+use erato::config::{InputModality, ModelPermissionRule};
+use erato::db::entity::prelude::{FileUploads, Messages};
+use mocktail::MockSet;
+use sea_orm::EntityTrait;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    JwtTokenBuilder, TEST_JWT_TOKEN, TestRequestAuthExt, build_openai_text_streaming_response,
+    create_chat, create_test_server, mock_llm_sse_response, read_integration_test_file_bytes,
+    setup_mock_llm_server_with_mocks,
+};
+
+const TEXT_PROVIDER_ID: &str = "mock-llm";
+const VISION_PROVIDER_ID: &str = "mock-llm-vision";
+const VISION_GROUP_ID: &str = "vision-users";
+
+async fn upload_file(server: &TestServer, token: &str, chat_id: &str, part: Part) -> String {
+    let response = server
+        .post(&format!("/api/v1beta/me/files?chat_id={chat_id}"))
+        .with_bearer_token(token)
+        .multipart(MultipartForm::new().add_part("file", part))
+        .await;
+    response.assert_status_ok();
+    response.json::<Value>()["files"][0]["id"]
+        .as_str()
+        .expect("Expected file id in upload response")
+        .to_string()
+}
+
+fn image_part() -> Part {
+    Part::bytes(read_integration_test_file_bytes("image_1.png"))
+        .file_name("image_1.png")
+        .mime_type("image/png")
+}
+
+fn text_part(filename: &str) -> Part {
+    Part::bytes(b"Quarterly numbers are up.".to_vec())
+        .file_name(filename)
+        .mime_type("text/plain")
+}
+
+/// Verifies that attachments a text-only model can't process are rejected before anything is
+/// stored, and that the model constraints are reported to clients.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// The only chat provider accepts text attachments and at most two files per message.
+/// `/me/models` reports both constraints, and the file capabilities of the model offer no
+/// operations for images. Submitting a message with an image, and with three text files, is
+/// rejected with 422 listing the offending files and without a suggested chat provider, and no
+/// message is stored.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_text_only_provider_rejects_image_attachments(pool: Pool<Postgres>) {
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(MockSet::new()).await;
+    let model_capabilities = &mut app_config
+        .chat_providers
+        .as_mut()
+        .unwrap()
+        .providers
+        .get_mut(TEXT_PROVIDER_ID)
+        .unwrap()
+        .model_capabilities;
+    model_capabilities.supported_input_modalities = Some(vec![InputModality::Text]);
+    model_capabilities.max_input_files = Some(2);
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let models_response = server
+        .get("/api/v1beta/me/models")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    models_response.assert_status_ok();
+    let models: Value = models_response.json();
+    assert_eq!(models[0]["chat_provider_id"], TEXT_PROVIDER_ID);
+    assert_eq!(models[0]["max_input_files"], 2);
+    assert_eq!(models[0]["supported_input_modalities"], json!(["text"]));
+
+    let capabilities_response = server
+        .get(&format!(
+            "/api/v1beta/me/file-capabilities?model_id={TEXT_PROVIDER_ID}"
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    capabilities_response.assert_status_ok();
+    let capabilities: Vec<Value> = capabilities_response.json();
+    let image_capability = capabilities
+        .iter()
+        .find(|capability| capability["id"] == "image")
+        .expect("Expected an image capability");
+    assert_eq!(image_capability["operations"], json!([]));
+
+    let chat_id = create_chat(&server, TEST_JWT_TOKEN).await;
+    let image_id = upload_file(&server, TEST_JWT_TOKEN, &chat_id, image_part()).await;
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "user_message": "What is in this picture?",
+            "input_files_ids": [image_id]
+        }))
+        .await;
+    response.assert_status(http::StatusCode::UNPROCESSABLE_ENTITY);
+    let error: Value = response.json();
+    assert_eq!(error["chat_provider_id"], TEXT_PROVIDER_ID);
+    assert_eq!(error["unsupported_files"][0]["file_id"], image_id.as_str());
+    assert_eq!(error["unsupported_files"][0]["filename"], "image_1.png");
+    assert_eq!(error["unsupported_files"][0]["modality"], "image");
+    assert_eq!(
+        error["unsupported_files"][0]["reason"],
+        "unsupported_modality"
+    );
+    assert!(error.get("suggested_chat_provider_id").is_none());
+
+    let mut text_file_ids = vec![];
+    for filename in ["a.txt", "b.txt", "c.txt"] {
+        text_file_ids
+            .push(upload_file(&server, TEST_JWT_TOKEN, &chat_id, text_part(filename)).await);
+    }
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "user_message": "Compare these files",
+            "input_files_ids": text_file_ids
+        }))
+        .await;
+    response.assert_status(http::StatusCode::UNPROCESSABLE_ENTITY);
+    let error: Value = response.json();
+    let unsupported_files = error["unsupported_files"].as_array().unwrap();
+    assert_eq!(unsupported_files.len(), 1);
+    assert_eq!(unsupported_files[0]["filename"], "c.txt");
+    assert_eq!(unsupported_files[0]["reason"], "too_many_files");
+
+    let stored_messages = Messages::find().all(&app_state.db).await.unwrap();
+    assert!(stored_messages.is_empty());
+}
+
+/// Verifies that a vision model available to the groups of the user is suggested for image
+/// attachments.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// Configures a text-only chat provider for everyone and a vision chat provider for members of
+/// a group. An image submitted to the text-only provider by a member of the group is rejected
+/// with the vision provider as suggestion, and the same message succeeds when the suggested
+/// provider is selected. Users outside of the group get no suggestion.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_image_attachment_suggests_available_vision_provider(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        mock_llm_sse_response(
+            then,
+            build_openai_text_streaming_response(&["A red square."]),
+        );
+    });
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(mocks).await;
+    let chat_providers = app_config.chat_providers.as_mut().unwrap();
+    let text_provider = chat_providers.providers.get_mut(TEXT_PROVIDER_ID).unwrap();
+    text_provider
+        .model_capabilities
+        .supports_image_understanding = false;
+    let mut vision_provider = text_provider.clone();
+    vision_provider
+        .model_capabilities
+        .supports_image_understanding = true;
+    chat_providers
+        .providers
+        .insert(VISION_PROVIDER_ID.to_string(), vision_provider);
+    chat_providers.priority_order =
+        vec![TEXT_PROVIDER_ID.to_string(), VISION_PROVIDER_ID.to_string()];
+    app_config.model_permissions.rules.insert(
+        "allow-vision-for-group".to_string(),
+        ModelPermissionRule::AllowForGroupMembers {
+            chat_provider_ids: vec![VISION_PROVIDER_ID.to_string()],
+            groups: vec![VISION_GROUP_ID.to_string()],
+        },
+    );
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+    let group_member_token = JwtTokenBuilder::new()
+        .groups(vec![VISION_GROUP_ID.to_string()])
+        .build();
+
+    let chat_id = create_chat(&server, &group_member_token).await;
+    let image_id = upload_file(&server, &group_member_token, &chat_id, image_part()).await;
+    let submit_image = |token: String, chat_provider_id: Option<&str>| {
+        let mut body = json!({
+            "existing_chat_id": chat_id,
+            "user_message": "What is in this picture?",
+            "input_files_ids": [image_id]
+        });
+        if let Some(chat_provider_id) = chat_provider_id {
+            body["chat_provider_id"] = json!(chat_provider_id);
+        }
+        server
+            .post("/api/v1beta/me/messages/submitstream")
+            .with_bearer_token(&token)
+            .json(&body)
+    };
+
+    let response = submit_image(group_member_token.clone(), None).await;
+    response.assert_status(http::StatusCode::UNPROCESSABLE_ENTITY);
+    let error: Value = response.json();
+    assert_eq!(error["chat_provider_id"], TEXT_PROVIDER_ID);
+    assert_eq!(error["unsupported_files"][0]["file_id"], image_id.as_str());
+    assert_eq!(error["suggested_chat_provider_id"], VISION_PROVIDER_ID);
+
+    let response = submit_image(TEST_JWT_TOKEN.to_string(), None).await;
+    response.assert_status(http::StatusCode::UNPROCESSABLE_ENTITY);
+    let error: Value = response.json();
+    assert!(error.get("suggested_chat_provider_id").is_none());
+
+    submit_image(group_member_token, Some(VISION_PROVIDER_ID))
+        .await
+        .assert_status_ok();
+}
+
+async fn file_capabilities(server: &TestServer, path: &str) -> Vec<Value> {
+    let response = server.get(path).with_bearer_token(TEST_JWT_TOKEN).await;
+    response.assert_status_ok();
+    response.json()
+}
+
+fn zip_part() -> Part {
+    Part::bytes(b"PK\x05\x06".to_vec())
+        .file_name("archive.zip")
+        .mime_type("application/zip")
+}
+
+/// Verifies that files none of the available models can use are accepted with a warning, or
+/// rejected before anything is stored if `file_uploads.reject_unsupported` is set.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// By default, uploading a ZIP archive succeeds with a warning naming the file and the reason,
+/// while a text file is uploaded without warnings. With `file_uploads.reject_unsupported`, the
+/// ZIP archive is rejected with 422, the evaluated `other` capability and the reason, and no
+/// file is stored.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_unsupported_uploads_are_rejected_or_accepted_with_warning(pool: Pool<Postgres>) {
+    let (app_config, _llm_server) = setup_mock_llm_server_with_mocks(MockSet::new()).await;
+    let server = create_test_server(test_app_state(app_config.clone(), pool.clone()).await);
+    let chat_id = create_chat(&server, TEST_JWT_TOKEN).await;
+
+    let response = server
+        .post(&format!("/api/v1beta/me/files?chat_id={chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .multipart(MultipartForm::new().add_part("file", zip_part()))
+        .await;
+    response.assert_status_ok();
+    let upload: Value = response.json();
+    assert_eq!(upload["files"][0]["file_capability"]["id"], "other");
+    assert_eq!(
+        upload["warnings"],
+        json!([{
+            "file_id": upload["files"][0]["id"],
+            "filename": "archive.zip",
+            "reason": "Files of this type can't be processed"
+        }])
+    );
+
+    let response = server
+        .post(&format!("/api/v1beta/me/files?chat_id={chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .multipart(MultipartForm::new().add_part("file", text_part("notes.txt")))
+        .await;
+    response.assert_status_ok();
+    assert_eq!(response.json::<Value>()["warnings"], json!([]));
+
+    let mut app_config = app_config;
+    app_config.file_uploads.reject_unsupported = true;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+    let stored_files = FileUploads::find().all(&app_state.db).await.unwrap();
+
+    let response = server
+        .post(&format!("/api/v1beta/me/files?chat_id={chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .multipart(MultipartForm::new().add_part("file", zip_part()))
+        .await;
+    response.assert_status(http::StatusCode::UNPROCESSABLE_ENTITY);
+    let error: Value = response.json();
+    assert_eq!(error["filename"], "archive.zip");
+    assert_eq!(error["error"], "Files of this type can't be processed");
+    assert_eq!(error["file_capability"]["id"], "other");
+    assert_eq!(error["file_capability"]["operations"], json!([]));
+
+    let files_after_rejection = FileUploads::find().all(&app_state.db).await.unwrap();
+    assert_eq!(files_after_rejection.len(), stored_files.len());
+}
+
+/// Verifies that the capability of a file is the same wherever it is reported.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// The only chat provider accepts text attachments. The image and text capabilities of
+/// `/me/file-capabilities`, with and without the `model_id` of that provider, match the ones of
+/// the upload responses of an image and a text file, and the image is uploaded with a warning.
+/// After sending a message with the text file, the file item of the message in
+/// `/chats/{chat_id}/messages` has the same capability as the upload response.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_file_capabilities_are_consistent(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        mock_llm_sse_response(then, build_openai_text_streaming_response(&["Noted."]));
+    });
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config
+        .chat_providers
+        .as_mut()
+        .unwrap()
+        .providers
+        .get_mut(TEXT_PROVIDER_ID)
+        .unwrap()
+        .model_capabilities
+        .supported_input_modalities = Some(vec![InputModality::Text]);
+    let server = create_test_server(test_app_state(app_config, pool).await);
+
+    let capabilities = file_capabilities(&server, "/api/v1beta/me/file-capabilities").await;
+    let model_capabilities = file_capabilities(
+        &server,
+        &format!("/api/v1beta/me/file-capabilities?model_id={TEXT_PROVIDER_ID}"),
+    )
+    .await;
+    assert_eq!(capabilities, model_capabilities);
+    let capability = |id: &str| {
+        let mut capability = capabilities
+            .iter()
+            .find(|capability| capability["id"] == id)
+            .cloned()
+            .expect("Expected the capability");
+        // Only the file capabilities endpoint sets the sources
+        capability.as_object_mut().unwrap().remove("sources");
+        capability
+    };
+
+    let chat_id = create_chat(&server, TEST_JWT_TOKEN).await;
+    let upload = |part: Part| {
+        server
+            .post(&format!("/api/v1beta/me/files?chat_id={chat_id}"))
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .multipart(MultipartForm::new().add_part("file", part))
+    };
+
+    let response = upload(image_part()).await;
+    response.assert_status_ok();
+    let image_upload: Value = response.json();
+    assert_eq!(
+        image_upload["files"][0]["file_capability"],
+        capability("image")
+    );
+    assert_eq!(
+        image_upload["warnings"][0]["reason"],
+        "None of the available models can analyze images"
+    );
+
+    let response = upload(text_part("notes.txt")).await;
+    response.assert_status_ok();
+    let text_upload: Value = response.json();
+    let text_file = &text_upload["files"][0];
+    assert_eq!(text_file["file_capability"], capability("text"));
+    assert_eq!(text_upload["warnings"], json!([]));
+
+    server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "user_message": "Summarize the notes",
+            "input_files_ids": [text_file["id"]]
+        }))
+        .await
+        .assert_status_ok();
+
+    let response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let messages: Value = response.json();
+    let message_file = messages["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|message| message["files"].as_array().unwrap())
+        .find(|file| file["id"] == text_file["id"])
+        .expect("Expected the file in the messages of the chat");
+    assert_eq!(
+        message_file["file_capability"],
+        text_file["file_capability"]
+    );
+}
@@ -0,0 +1,136 @@
+//! Tests for serving the internal routes on a separate listener.
+
+use erato::state::AppState;
+use reqwest::StatusCode;
+use serde_json::json;
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use utoipa_axum::router::OpenApiRouter;
+
+use crate::test_app_state;
+use crate::test_utils::{TEST_JWT_TOKEN, admin_app_config, admin_token};
+
+/// Serves the router on an ephemeral port and returns its base URL.
+async fn serve(router: OpenApiRouter<AppState>, app_state: AppState) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    let app: axum::Router = router.split_for_parts().0.with_state(app_state);
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{}", server_addr)
+}
+
+/// Verifies that the internal routes are only served on the internal listener if one is configured.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Boots the public and internal listener on ephemeral ports, and checks that the health check
+/// and admin API are reachable on the internal listener and respond with 404 on the public one,
+/// while the regular API is only reachable on the public listener.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_internal_listener_serves_internal_routes(pool: Pool<Postgres>) {
+    let mut app_config = admin_app_config();
+    app_config.internal_http_port = Some(3132);
+    let app_state = test_app_state(app_config, pool).await;
+
+    let (public_router, internal_router) =
+        erato::server::router::listener_routers(app_state.clone());
+    let internal_router = internal_router.expect("internal router should be configured");
+    let public_url = serve(public_router, app_state.clone()).await;
+    let internal_url = serve(internal_router, app_state).await;
+
+    let client = reqwest::Client::new();
+    let admin_token = admin_token();
+
+    let health_response = client
+        .get(format!("{}/health", internal_url))
+        .send()
+        .await
+        .expect("Failed to request health check");
+    assert_eq!(health_response.status(), StatusCode::OK);
+    let health_response = client
+        .get(format!("{}/health", public_url))
+        .send()
+        .await
+        .expect("Failed to request health check");
+    assert_eq!(health_response.status(), StatusCode::NOT_FOUND);
+
+    let admin_response = client
+        .get(format!("{}/api/v1beta/admin/feature-flags", internal_url))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .expect("Failed to request admin API");
+    assert_eq!(admin_response.status(), StatusCode::OK);
+    let admin_response = client
+        .get(format!("{}/api/v1beta/admin/feature-flags", public_url))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .expect("Failed to request admin API");
+    assert_eq!(admin_response.status(), StatusCode::NOT_FOUND);
+    let admin_response = client
+        .post(format!(
+            "{}/api/v1beta/admin/feature-flags/prompt_optimizer.enabled",
+            public_url
+        ))
+        .bearer_auth(&admin_token)
+        .json(&json!({ "enabled": true }))
+        .send()
+        .await
+        .expect("Failed to request admin API");
+    assert_eq!(admin_response.status(), StatusCode::NOT_FOUND);
+
+    let profile_response = client
+        .get(format!("{}/api/v1beta/me/profile", public_url))
+        .bearer_auth(TEST_JWT_TOKEN)
+        .send()
+        .await
+        .expect("Failed to request profile");
+    assert_eq!(profile_response.status(), StatusCode::OK);
+    let profile_response = client
+        .get(format!("{}/api/v1beta/me/profile", internal_url))
+        .bearer_auth(TEST_JWT_TOKEN)
+        .send()
+        .await
+        .expect("Failed to request profile");
+    assert_eq!(profile_response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Verifies that all routes are served on a single listener if no internal listener is configured.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_single_listener_serves_internal_routes(pool: Pool<Postgres>) {
+    let app_config = admin_app_config();
+    let app_state = test_app_state(app_config, pool).await;
+
+    let (public_router, internal_router) =
+        erato::server::router::listener_routers(app_state.clone());
+    assert!(internal_router.is_none());
+    let public_url = serve(public_router, app_state).await;
+
+    let client = reqwest::Client::new();
+    let admin_token = admin_token();
+
+    let health_response = client
+        .get(format!("{}/health", public_url))
+        .send()
+        .await
+        .expect("Failed to request health check");
+    assert_eq!(health_response.status(), StatusCode::OK);
+
+    let admin_response = client
+        .get(format!("{}/api/v1beta/admin/feature-flags", public_url))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .expect("Failed to request admin API");
+    assert_eq!(admin_response.status(), StatusCode::OK);
+}
@@ -0,0 +1,477 @@
+//! Chat label API tests.
+
+use axum::Router;
+use axum::http;
+use axum_test::TestServer;
+use chrono::Utc;
+use erato::db::entity::{chat_labels, chats, labels, messages};
+use erato::server::router::router;
+use futures::future::join_all;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    prelude::Uuid,
+};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt, setup_mock_llm_server,
+};
+
+/// Insert a chat owned by `owner_user_id` with a single user message, so it
+/// shows up in the recent chats listing.
+async fn insert_chat_with_message(app_state: &erato::state::AppState, owner_user_id: &str) -> Uuid {
+    let chat = chats::ActiveModel {
+        owner_user_id: ActiveValue::Set(owner_user_id.to_string()),
+        ..Default::default()
+    }
+    .insert(&app_state.db)
+    .await
+    .expect("Failed to insert chat");
+
+    let now = Utc::now().into();
+    messages::ActiveModel {
+        id: ActiveValue::Set(Uuid::new_v4()),
+        chat_id: ActiveValue::Set(chat.id),
+        raw_message: ActiveValue::Set(json!({
+            "role": "user",
+            "content": [{ "content_type": "text", "text": "Hello" }]
+        })),
+        created_at: ActiveValue::Set(now),
+        updated_at: ActiveValue::Set(now),
+        is_message_in_active_thread: ActiveValue::Set(true),
+        ..Default::default()
+    }
+    .insert(&app_state.db)
+    .await
+    .expect("Failed to insert message");
+
+    chat.id
+}
+
+async fn create_label(server: &TestServer, name: &str) -> String {
+    let response = server
+        .post("/api/v1beta/me/labels")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .add_header(http::header::CONTENT_TYPE, "application/json")
+        .json(&json!({ "name": name }))
+        .await;
+    response.assert_status(http::StatusCode::CREATED);
+    let body: Value = response.json();
+    body["id"].as_str().expect("Expected label id").to_string()
+}
+
+fn chat_ids_of(body: &Value) -> Vec<String> {
+    body["chats"]
+        .as_array()
+        .expect("Expected chats array")
+        .iter()
+        .map(|chat| chat["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+/// Test label CRUD and assigning labels to chats.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Creates, renames and lists labels, assigns them to chats, and verifies the
+/// `labels` array on recent chats as well as the `label_id` filter, including its
+/// combination with `include_archived`.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_labels_assignment_and_filtering(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let user = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        TEST_USER_SUBJECT,
+        None,
+    )
+    .await
+    .expect("Failed to create user");
+
+    let work_chat_id = insert_chat_with_message(&app_state, &user.id.to_string()).await;
+    let other_chat_id = insert_chat_with_message(&app_state, &user.id.to_string()).await;
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let work_label_id = create_label(&server, "Work").await;
+    let personal_label_id = create_label(&server, "Personal").await;
+
+    // Duplicate names are rejected
+    let duplicate_response = server
+        .post("/api/v1beta/me/labels")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .add_header(http::header::CONTENT_TYPE, "application/json")
+        .json(&json!({ "name": "Work" }))
+        .await;
+    duplicate_response.assert_status(http::StatusCode::CONFLICT);
+
+    // Renaming and coloring a label
+    let update_response = server
+        .put(&format!("/api/v1beta/me/labels/{}", personal_label_id))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .add_header(http::header::CONTENT_TYPE, "application/json")
+        .json(&json!({ "name": "Home", "color": "#FF8800" }))
+        .await;
+    update_response.assert_status_ok();
+    let updated: Value = update_response.json();
+    assert_eq!(updated["name"], "Home");
+    assert_eq!(updated["color"], "#ff8800");
+
+    let list_response = server
+        .get("/api/v1beta/me/labels")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    list_response.assert_status_ok();
+    let list_body: Value = list_response.json();
+    let names: Vec<&str> = list_body["labels"]
+        .as_array()
+        .expect("Expected labels array")
+        .iter()
+        .map(|label| label["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["Home", "Work"]);
+
+    // Assign labels; assigning twice is a no-op
+    for _ in 0..2 {
+        server
+            .post(&format!(
+                "/api/v1beta/chats/{}/labels/{}",
+                work_chat_id, work_label_id
+            ))
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .await
+            .assert_status(http::StatusCode::NO_CONTENT);
+    }
+    server
+        .post(&format!(
+            "/api/v1beta/chats/{}/labels/{}",
+            work_chat_id, personal_label_id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::NO_CONTENT);
+
+    let recent_response = server
+        .get("/api/v1beta/me/recent_chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    recent_response.assert_status_ok();
+    let recent_body: Value = recent_response.json();
+    let work_chat = recent_body["chats"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|chat| chat["id"] == work_chat_id.to_string())
+        .expect("Labelled chat should be listed");
+    let chat_label_names: Vec<&str> = work_chat["labels"]
+        .as_array()
+        .expect("Expected labels array on chat")
+        .iter()
+        .map(|label| label["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(chat_label_names, vec!["Home", "Work"]);
+    let other_chat = recent_body["chats"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|chat| chat["id"] == other_chat_id.to_string())
+        .expect("Unlabelled chat should be listed");
+    assert_eq!(other_chat["labels"], json!([]));
+
+    // Filtering by label
+    let filtered_response = server
+        .get(&format!(
+            "/api/v1beta/me/recent_chats?label_id={}",
+            work_label_id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    filtered_response.assert_status_ok();
+    let filtered_body: Value = filtered_response.json();
+    assert_eq!(chat_ids_of(&filtered_body), vec![work_chat_id.to_string()]);
+    assert_eq!(filtered_body["stats"]["total_count"].as_i64(), Some(1));
+
+    // Archived chats are only included in the filtered list when requested
+    server
+        .post(&format!("/api/v1beta/chats/{}/archive", work_chat_id))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .add_header(http::header::CONTENT_TYPE, "application/json")
+        .json(&json!({}))
+        .await
+        .assert_status_ok();
+    let archived_hidden: Value = server
+        .get(&format!(
+            "/api/v1beta/me/recent_chats?label_id={}",
+            work_label_id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    assert!(chat_ids_of(&archived_hidden).is_empty());
+    let archived_included: Value = server
+        .get(&format!(
+            "/api/v1beta/me/recent_chats?label_id={}&include_archived=true",
+            work_label_id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    assert_eq!(
+        chat_ids_of(&archived_included),
+        vec![work_chat_id.to_string()]
+    );
+
+    // Removing a label
+    server
+        .delete(&format!(
+            "/api/v1beta/chats/{}/labels/{}",
+            work_chat_id, work_label_id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::NO_CONTENT);
+    let after_removal: Value = server
+        .get(&format!(
+            "/api/v1beta/me/recent_chats?label_id={}&include_archived=true",
+            work_label_id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    assert!(chat_ids_of(&after_removal).is_empty());
+
+    // Invalid label IDs are rejected
+    server
+        .get("/api/v1beta/me/recent_chats?label_id=not-a-uuid")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::BAD_REQUEST);
+}
+
+/// Test that deleting a label removes its chat assignments.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Assigns a label to a chat, deletes the label and verifies the join rows are
+/// gone while the chat itself is still listed.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_label_delete_cascades_to_chats(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let user = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        TEST_USER_SUBJECT,
+        None,
+    )
+    .await
+    .expect("Failed to create user");
+    let chat_id = insert_chat_with_message(&app_state, &user.id.to_string()).await;
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let label_id = create_label(&server, "Temporary").await;
+    server
+        .post(&format!(
+            "/api/v1beta/chats/{}/labels/{}",
+            chat_id, label_id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::NO_CONTENT);
+
+    server
+        .delete(&format!("/api/v1beta/me/labels/{}", label_id))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::NO_CONTENT);
+
+    let remaining_assignments = chat_labels::Entity::find()
+        .filter(chat_labels::Column::ChatId.eq(chat_id))
+        .count(&app_state.db)
+        .await
+        .expect("Failed to count chat labels");
+    assert_eq!(remaining_assignments, 0);
+
+    let recent_body: Value = server
+        .get("/api/v1beta/me/recent_chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    assert_eq!(chat_ids_of(&recent_body), vec![chat_id.to_string()]);
+    assert_eq!(recent_body["chats"][0]["labels"], json!([]));
+
+    // The label is gone
+    server
+        .delete(&format!("/api/v1beta/me/labels/{}", label_id))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::NOT_FOUND);
+}
+
+/// Test the per-user and per-chat label limits.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Verifies that a user cannot create more than the maximum number of labels
+/// and that a chat cannot have more than the maximum number of labels assigned.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_label_limits(pool: Pool<Postgres>) {
+    use erato::models::label::{MAX_LABELS_PER_CHAT, MAX_LABELS_PER_USER};
+
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let user = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        TEST_USER_SUBJECT,
+        None,
+    )
+    .await
+    .expect("Failed to create user");
+    let chat_id = insert_chat_with_message(&app_state, &user.id.to_string()).await;
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let mut label_ids = Vec::new();
+    for i in 0..MAX_LABELS_PER_USER {
+        label_ids.push(create_label(&server, &format!("Label {:02}", i)).await);
+    }
+    server
+        .post("/api/v1beta/me/labels")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .add_header(http::header::CONTENT_TYPE, "application/json")
+        .json(&json!({ "name": "One too many" }))
+        .await
+        .assert_status(http::StatusCode::CONFLICT);
+
+    for label_id in label_ids.iter().take(MAX_LABELS_PER_CHAT as usize) {
+        server
+            .post(&format!(
+                "/api/v1beta/chats/{}/labels/{}",
+                chat_id, label_id
+            ))
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .await
+            .assert_status(http::StatusCode::NO_CONTENT);
+    }
+    server
+        .post(&format!(
+            "/api/v1beta/chats/{}/labels/{}",
+            chat_id, label_ids[MAX_LABELS_PER_CHAT as usize]
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::CONFLICT);
+}
+
+/// Test that the label limits hold for concurrent requests.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Sends more label creations than the per-user limit at once, and then more label assignments
+/// to a chat than the per-chat limit at once. Only as many requests as the limits allow succeed,
+/// the others are rejected, and the stored labels and assignments stay within the limits.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_label_limits_hold_for_concurrent_requests(pool: Pool<Postgres>) {
+    use erato::models::label::{MAX_LABELS_PER_CHAT, MAX_LABELS_PER_USER};
+
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let user = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        TEST_USER_SUBJECT,
+        None,
+    )
+    .await
+    .expect("Failed to create user");
+    let chat_id = insert_chat_with_message(&app_state, &user.id.to_string()).await;
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let creations = (0..MAX_LABELS_PER_USER + 5).map(|i| {
+        server
+            .post("/api/v1beta/me/labels")
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .add_header(http::header::CONTENT_TYPE, "application/json")
+            .json(&json!({ "name": format!("Label {:02}", i) }))
+            .into_future()
+    });
+    let creations = join_all(creations).await;
+    let label_ids: Vec<String> = creations
+        .iter()
+        .filter(|response| response.status_code() == http::StatusCode::CREATED)
+        .map(|response| {
+            let body: Value = response.json();
+            body["id"].as_str().expect("Expected label id").to_string()
+        })
+        .collect();
+    assert_eq!(label_ids.len() as u64, MAX_LABELS_PER_USER);
+    assert!(
+        creations
+            .iter()
+            .filter(|response| response.status_code() != http::StatusCode::CREATED)
+            .all(|response| response.status_code() == http::StatusCode::CONFLICT)
+    );
+    let stored_labels = labels::Entity::find()
+        .filter(labels::Column::OwnerUserId.eq(user.id))
+        .count(&app_state.db)
+        .await
+        .expect("Failed to count labels");
+    assert_eq!(stored_labels, MAX_LABELS_PER_USER);
+
+    let assignments = label_ids
+        .iter()
+        .take(MAX_LABELS_PER_CHAT as usize + 5)
+        .map(|label_id| {
+            server
+                .post(&format!("/api/v1beta/chats/{chat_id}/labels/{label_id}"))
+                .with_bearer_token(TEST_JWT_TOKEN)
+                .into_future()
+        });
+    let assignments = join_all(assignments).await;
+    let assigned = assignments
+        .iter()
+        .filter(|response| response.status_code() == http::StatusCode::NO_CONTENT)
+        .count();
+    assert_eq!(assigned as u64, MAX_LABELS_PER_CHAT);
+    let stored_assignments = chat_labels::Entity::find()
+        .filter(chat_labels::Column::ChatId.eq(chat_id))
+        .count(&app_state.db)
+        .await
+        .expect("Failed to count label assignments");
+    assert_eq!(stored_assignments, MAX_LABELS_PER_CHAT);
+}
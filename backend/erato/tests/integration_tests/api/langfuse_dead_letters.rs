@@ -0,0 +1,188 @@
+//! Tests for the dead-letter queue of Langfuse ingestion batches.
+
+use axum::Router;
+use axum::body::Bytes;
+use axum::routing::post;
+use erato::config::{LangfuseConfig, LangfuseDeadLetterQueueConfig};
+use erato::services::langfuse::{CreateScoreRequest, LangfuseClient};
+use sea_orm::DatabaseConnection;
+use serde_json::Value;
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::net::SocketAddr;
+use tokio::sync::mpsc;
+
+use crate::test_app_state;
+use crate::test_utils::{TestRequestAuthExt, admin_app_config, admin_token, create_test_server};
+
+/// A local address that nothing listens on, until [`serve_langfuse`] is called with it.
+async fn unused_address() -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    listener.local_addr().unwrap()
+}
+
+/// Serve a mock Langfuse ingestion endpoint at `addr`, which forwards the received batches.
+async fn serve_langfuse(addr: SocketAddr) -> mpsc::UnboundedReceiver<Value> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/api/public/ingestion",
+        post(move |body: Bytes| {
+            let sender = sender.clone();
+            async move {
+                sender.send(serde_json::from_slice(&body).unwrap()).unwrap();
+                "{}"
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    receiver
+}
+
+fn langfuse_client(addr: SocketAddr, db: DatabaseConnection, max_batches: u64) -> LangfuseClient {
+    let config = LangfuseConfig {
+        enabled: true,
+        base_url: Some(format!("http://{addr}")),
+        public_key: Some("pk-lf-test".to_string()),
+        secret_key: Some("sk-lf-test".into()),
+        dead_letter_queue: LangfuseDeadLetterQueueConfig {
+            max_batches,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    LangfuseClient::from_config(&config, None)
+        .unwrap()
+        .with_dead_letter_queue(db)
+}
+
+fn score_request(id: &str) -> CreateScoreRequest {
+    CreateScoreRequest {
+        id: id.to_string(),
+        trace_id: "trace_58406520a006649127e371903a2de979".to_string(),
+        name: "user_feedback".to_string(),
+        value: 1.0,
+        comment: None,
+        data_type: "NUMERIC".to_string(),
+        environment: None,
+    }
+}
+
+/// Receive `count` batches and return the IDs of their scores.
+async fn received_score_ids(
+    receiver: &mut mpsc::UnboundedReceiver<Value>,
+    count: usize,
+) -> Vec<String> {
+    let mut ids = Vec::new();
+    for _ in 0..count {
+        let batch = receiver.recv().await.unwrap();
+        ids.push(batch["batch"][0]["body"]["id"].as_str().unwrap().to_string());
+    }
+    ids
+}
+
+/// Test that batches queued while Langfuse is unreachable are delivered in order once it is
+/// reachable again.
+///
+/// # Test Categories
+/// - `uses-db`
+///
+/// # Test Behavior
+/// Sends three scores while nothing listens on the Langfuse address. The first one fails and is
+/// queued, and the later ones are queued right away, without waiting for Langfuse. A retry while
+/// Langfuse is still unreachable keeps them queued and reports the failures. After the mock
+/// endpoint is started, a retry delivers the batches in the order they were sent, and new scores
+/// are sent right away again.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_queued_batches_are_delivered_in_order(pool: Pool<Postgres>) {
+    let db = sea_orm::SqlxPostgresConnector::from_sqlx_postgres_pool(pool);
+    let addr = unused_address().await;
+    let client = langfuse_client(addr, db, 100);
+
+    for i in 0..3 {
+        client
+            .create_score(score_request(&format!("score-{i}")))
+            .await
+            .unwrap();
+    }
+    assert_eq!(client.dead_letters().unwrap().depth(), 3);
+
+    assert!(client.retry_dead_letters().await.is_err());
+    let status = client.status().await.unwrap();
+    assert_eq!(status.queued_batches, 3);
+    assert_eq!(status.connected, Some(false));
+    assert_eq!(status.consecutive_failures, 2);
+    assert!(status.last_error.is_some());
+    assert!(status.last_successful_flush_at.is_none());
+    assert!(status.next_retry_at.is_some());
+
+    let mut received = serve_langfuse(addr).await;
+    client.retry_dead_letters().await.unwrap();
+    assert_eq!(
+        received_score_ids(&mut received, 3).await,
+        vec!["score-0", "score-1", "score-2"]
+    );
+
+    let status = client.status().await.unwrap();
+    assert_eq!(status.queued_batches, 0);
+    assert_eq!(status.connected, Some(true));
+    assert_eq!(status.consecutive_failures, 0);
+    assert!(status.last_successful_flush_at.is_some());
+    assert!(status.next_retry_at.is_none());
+
+    client.create_score(score_request("score-3")).await.unwrap();
+    assert_eq!(received_score_ids(&mut received, 1).await, vec!["score-3"]);
+    assert_eq!(client.dead_letters().unwrap().depth(), 0);
+}
+
+/// Test that a full queue drops the oldest batches, and that admins can see its state.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// With a queue of at most two batches, queues two scores while Langfuse is unreachable and
+/// stores them, then queues a third one. Storing the third one drops the oldest batch.
+/// `/admin/langfuse/status` reports the disconnected client, two queued batches and one evicted
+/// batch. Once Langfuse is reachable, the two remaining batches are delivered.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_full_queue_drops_oldest_batches(pool: Pool<Postgres>) {
+    let addr = unused_address().await;
+    let app_config = admin_app_config();
+    let mut app_state = test_app_state(app_config, pool).await;
+    app_state.langfuse_client = langfuse_client(addr, app_state.db.clone(), 2);
+    let client = app_state.langfuse_client.clone();
+
+    for i in 0..2 {
+        client
+            .create_score(score_request(&format!("score-{i}")))
+            .await
+            .unwrap();
+    }
+    assert!(client.retry_dead_letters().await.is_err());
+    client.create_score(score_request("score-2")).await.unwrap();
+    assert!(client.retry_dead_letters().await.is_err());
+
+    let server = create_test_server(app_state);
+    let admin_token = admin_token();
+    let response = server
+        .get("/api/v1beta/admin/langfuse/status")
+        .with_bearer_token(&admin_token)
+        .await;
+    response.assert_status_ok();
+    let status: Value = response.json();
+    assert_eq!(status["enabled"], true);
+    assert_eq!(status["connected"], false);
+    assert_eq!(status["queued_batches"], 2);
+    assert_eq!(status["evicted_batches"], 1);
+
+    let mut received = serve_langfuse(addr).await;
+    client.retry_dead_letters().await.unwrap();
+    assert_eq!(
+        received_score_ids(&mut received, 2).await,
+        vec!["score-1", "score-2"]
+    );
+}
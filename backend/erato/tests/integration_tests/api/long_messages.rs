@@ -0,0 +1,103 @@
+//! Integration tests for the handling of long messages.
+
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    Event, TEST_JWT_TOKEN, TestRequestAuthExt, create_test_server, parse_sse_events,
+    setup_mock_llm_server,
+};
+
+const LOG_MARKER: &str = "BUILD-LOG-7F3A";
+
+fn event_data(events: &[Event], message_type: &str) -> Value {
+    events
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .find(|data| data["message_type"] == message_type)
+        .unwrap_or_else(|| panic!("Expected a {message_type} event"))
+}
+
+/// Verifies that a long message is attached as a file, which is replayed to the model.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// Submits a message of 300 KB. The `user_message_saved` event names the file the text was
+/// attached as, and the message only keeps a preview of `chat.long_messages.preview_chars`
+/// characters, so that no event of the stream exceeds 100 KB. The prompt of a follow-up message
+/// contains the contents of the attached file.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_long_message_is_attached_as_file(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    app_config.debug.allow_dry_run = true;
+    let max_inline_bytes = app_config.chat.long_messages.max_inline_bytes as usize;
+    let preview_chars = app_config.chat.long_messages.preview_chars;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let long_message = format!(
+        "{LOG_MARKER}\n{}",
+        "2026-10-17T08:00:00Z INFO worker: processed batch\n".repeat(6500)
+    );
+    assert!(long_message.len() > 300 * 1024);
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": long_message }))
+        .await;
+    response.assert_status_ok();
+    let events = parse_sse_events(&response);
+    for event in &events {
+        assert!(
+            event.data.len() < max_inline_bytes,
+            "The {} event has {} bytes",
+            event.event_type,
+            event.data.len()
+        );
+    }
+
+    let saved = event_data(&events, "user_message_saved");
+    let file_id = saved["auto_attached_file_id"]
+        .as_str()
+        .expect("Expected the ID of the attached file");
+    assert_eq!(saved["message"]["input_files_ids"], json!([file_id]));
+    let preview = saved["message"]["content"][0]["text"]
+        .as_str()
+        .expect("Expected the preview of the message");
+    assert!(preview.starts_with(LOG_MARKER));
+    assert_eq!(preview.chars().count(), preview_chars + 1);
+
+    let chat_id = event_data(&events, "chat_created")["chat_id"].clone();
+    let assistant_message_id =
+        event_data(&events, "assistant_message_completed")["message_id"].clone();
+    let dry_run_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "previous_message_id": assistant_message_id,
+            "user_message": "Which batches failed?",
+            "dry_run": true,
+        }))
+        .await;
+    dry_run_response.assert_status_ok();
+    let dry_run: Value = dry_run_response.json();
+    assert!(
+        dry_run["messages"]
+            .as_array()
+            .expect("Expected the messages of the dry run")
+            .iter()
+            .map(Value::to_string)
+            .any(|message| {
+                message.contains("file name: pasted-message.txt") && message.contains(LOG_MARKER)
+            }),
+        "The follow-up prompt should contain the attached message"
+    );
+}
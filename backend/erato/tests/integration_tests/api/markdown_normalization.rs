@@ -0,0 +1,143 @@
+//! Tests for the normalization of the markdown of generated answers.
+
+use axum::http;
+use erato::db::entity::messages;
+use erato::state::AppState;
+use mocktail::MockSet;
+use sea_orm::{EntityTrait, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TestRequestAuthExt, build_openai_interrupted_streaming_response,
+    create_test_server, setup_mock_llm_server_with_mocks, streamed_text, submit_events,
+};
+
+/// Chunks of an answer that is cut off inside a code block.
+const INTERRUPTED_CHUNKS: &[&str] = &[
+    "Here is the code:\n\n```rust\n",
+    "fn main() {\n",
+    "    println!(\"Hello\");\n",
+];
+
+/// Mocks a chat provider whose stream fails after the chunks of [`INTERRUPTED_CHUNKS`].
+fn interrupted_llm_mocks() -> MockSet {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        then.status(http::StatusCode::OK)
+            .headers([
+                ("Content-Type", "text/event-stream"),
+                ("Cache-Control", "no-cache"),
+            ])
+            .bytes_stream_with_delays(build_openai_interrupted_streaming_response(
+                INTERRUPTED_CHUNKS,
+            ));
+    });
+    mocks
+}
+
+fn event<'a>(events: &'a [Value], message_type: &str) -> Option<&'a Value> {
+    events
+        .iter()
+        .find(|event| event["message_type"] == message_type)
+}
+
+/// Load the saved assistant message of the `assistant_message_completed` event.
+async fn saved_message(app_state: &AppState, completed: &Value) -> messages::Model {
+    let message_id = Uuid::parse_str(completed["message_id"].as_str().unwrap()).unwrap();
+    messages::Entity::find_by_id(message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the assistant message")
+        .expect("The assistant message should exist")
+}
+
+/// Test that an answer cut off inside a code block is saved with the code block closed.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// The stream of the mock LLM fails in the middle of a code block. The streamed deltas carry the
+/// text as generated, while the `assistant_message_completed` event and the saved message have
+/// the code fence closed, and the change is recorded in the generation metadata.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_interrupted_code_block_is_closed(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server_with_mocks(interrupted_llm_mocks()).await;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Write a hello world program" }))
+        .await;
+    response.assert_status_ok();
+    let events = submit_events(&response);
+
+    assert!(
+        event(&events, "error").is_some(),
+        "Expected the interrupted stream to end with an error event"
+    );
+    let generated_text = INTERRUPTED_CHUNKS.concat();
+    assert_eq!(streamed_text(&events), generated_text);
+
+    let expected_text = format!("{generated_text}```");
+    let completed = event(&events, "assistant_message_completed")
+        .expect("Expected assistant_message_completed event");
+    assert_eq!(completed["message"]["content"][0]["text"], expected_text);
+
+    let message = saved_message(&app_state, completed).await;
+    assert_eq!(message.raw_message["content"][0]["text"], expected_text);
+    let generation_metadata = message
+        .generation_metadata
+        .expect("The assistant message should have generation metadata");
+    assert_eq!(generation_metadata["markdown_normalized"], true);
+}
+
+/// Test that the answer is saved as generated with `chat.normalize_markdown` disabled.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// The stream of the mock LLM fails in the middle of a code block. The saved message keeps the
+/// unterminated code block, and nothing is recorded in the generation metadata.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_markdown_normalization_can_be_disabled(pool: Pool<Postgres>) {
+    let (mut app_config, _server) =
+        setup_mock_llm_server_with_mocks(interrupted_llm_mocks()).await;
+    app_config.chat.normalize_markdown = false;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Write a hello world program" }))
+        .await;
+    response.assert_status_ok();
+    let events = submit_events(&response);
+
+    let generated_text = INTERRUPTED_CHUNKS.concat();
+    let completed = event(&events, "assistant_message_completed")
+        .expect("Expected assistant_message_completed event");
+    assert_eq!(completed["message"]["content"][0]["text"], generated_text);
+
+    let message = saved_message(&app_state, completed).await;
+    assert_eq!(message.raw_message["content"][0]["text"], generated_text);
+    assert!(
+        message
+            .generation_metadata
+            .is_none_or(|metadata| metadata.get("markdown_normalized").is_none())
+    );
+}
@@ -0,0 +1,268 @@
+//! Message reactions API tests.
+
+use axum::Router;
+use axum::http;
+use axum_test::TestServer;
+use erato::models::user::get_or_create_user;
+use erato::server::router::router;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    JwtTokenBuilder, TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt,
+    extract_chat_id, parse_sse_events, setup_mock_llm_server,
+};
+
+/// Path of the reaction endpoints for a message and emoji, with the emoji percent-encoded.
+fn reaction_path(message_id: &str, emoji: &str) -> String {
+    let encoded_emoji: String = emoji.bytes().map(|b| format!("%{b:02X}")).collect();
+    format!("/api/v1beta/messages/{message_id}/reactions/{encoded_emoji}")
+}
+
+/// Submits a first message as the given user and returns the chat ID and the ID
+/// of the generated assistant message.
+async fn create_chat_with_message(server: &TestServer, token: &str) -> (String, String) {
+    let submit_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(token)
+        .json(&json!({
+            "user_message": "Test message for reactions",
+        }))
+        .await;
+    submit_response.assert_status_ok();
+
+    let events = parse_sse_events(&submit_response);
+    let chat_id = extract_chat_id(&events).expect("Expected chat_created event");
+    let assistant_message_id = events
+        .iter()
+        .find_map(|event| {
+            let json: Value = serde_json::from_str(&event.data).ok()?;
+            if json["message_type"] == "assistant_message_completed" {
+                return json["message_id"].as_str().map(|s| s.to_string());
+            }
+            None
+        })
+        .expect("Expected assistant_message_completed event");
+    (chat_id, assistant_message_id)
+}
+
+/// Returns the reactions of a message from the messages listing of its chat.
+async fn listed_reactions(
+    server: &TestServer,
+    token: &str,
+    chat_id: &str,
+    message_id: &str,
+) -> Value {
+    let response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(token)
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    body["messages"]
+        .as_array()
+        .expect("Expected messages array")
+        .iter()
+        .find(|message| message["id"] == message_id)
+        .expect("Expected the message in the listing")["reactions"]
+        .clone()
+}
+
+/// Test adding and removing a reaction.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Adding a reaction twice counts it once, the reaction is listed with the messages of the chat,
+/// and removing it (also repeatedly) leaves no reactions.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_toggle_message_reaction(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+
+    let _user = get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let (chat_id, message_id) = create_chat_with_message(&server, TEST_JWT_TOKEN).await;
+    let expected = json!([{ "emoji": "👍", "count": 1, "reacted_by_me": true }]);
+
+    for _ in 0..2 {
+        let response = server
+            .put(&reaction_path(&message_id, "👍"))
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(body["reactions"], expected);
+    }
+
+    assert_eq!(
+        listed_reactions(&server, TEST_JWT_TOKEN, &chat_id, &message_id).await,
+        expected
+    );
+
+    for _ in 0..2 {
+        let response = server
+            .delete(&reaction_path(&message_id, "👍"))
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .await;
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(body["reactions"], json!([]));
+    }
+
+    assert_eq!(
+        listed_reactions(&server, TEST_JWT_TOKEN, &chat_id, &message_id).await,
+        json!([])
+    );
+}
+
+/// Test that reactions of all users that can read a shared chat are aggregated.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// The owner and a user the chat is shared with read-only both react. Each of them sees the
+/// counts of both, with `reacted_by_me` set for their own reactions. Users without access to the
+/// chat can't react.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_message_reactions_aggregate_across_shared_users(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+
+    let owner_subject = "reactions-owner";
+    let grantee_subject = "reactions-grantee";
+    let outsider_subject = "reactions-outsider";
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, owner_subject, None)
+        .await
+        .expect("Failed to create owner user");
+    let grantee = get_or_create_user(&app_state.db, TEST_USER_ISSUER, grantee_subject, None)
+        .await
+        .expect("Failed to create grantee user");
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, outsider_subject, None)
+        .await
+        .expect("Failed to create outsider user");
+
+    let token_for = |subject: &str| {
+        JwtTokenBuilder::new()
+            .subject(subject)
+            .email(format!("{subject}@example.com"))
+            .name(subject)
+            .build()
+    };
+    let owner_token = token_for(owner_subject);
+    let grantee_token = token_for(grantee_subject);
+    let outsider_token = token_for(outsider_subject);
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let (chat_id, message_id) = create_chat_with_message(&server, &owner_token).await;
+
+    let share_response = server
+        .post("/api/v1beta/share-grants")
+        .with_bearer_token(&owner_token)
+        .json(&json!({
+            "resource_type": "chat",
+            "resource_id": chat_id,
+            "subject_type": "user",
+            "subject_id_type": "id",
+            "subject_id": grantee.id.to_string(),
+            "role": "viewer",
+        }))
+        .await;
+    assert_eq!(share_response.status_code(), http::StatusCode::CREATED);
+
+    for (token, emoji) in [
+        (&owner_token, "👍"),
+        (&grantee_token, "👍"),
+        (&grantee_token, "🎉"),
+    ] {
+        server
+            .put(&reaction_path(&message_id, emoji))
+            .with_bearer_token(token)
+            .await
+            .assert_status_ok();
+    }
+
+    assert_eq!(
+        listed_reactions(&server, &owner_token, &chat_id, &message_id).await,
+        json!([
+            { "emoji": "👍", "count": 2, "reacted_by_me": true },
+            { "emoji": "🎉", "count": 1, "reacted_by_me": false },
+        ])
+    );
+    assert_eq!(
+        listed_reactions(&server, &grantee_token, &chat_id, &message_id).await,
+        json!([
+            { "emoji": "👍", "count": 2, "reacted_by_me": true },
+            { "emoji": "🎉", "count": 1, "reacted_by_me": true },
+        ])
+    );
+
+    let outsider_response = server
+        .put(&reaction_path(&message_id, "👍"))
+        .with_bearer_token(&outsider_token)
+        .await;
+    assert_eq!(outsider_response.status_code(), http::StatusCode::NOT_FOUND);
+}
+
+/// Test that reactions with emojis outside of the allowlist are rejected.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// With `frontend.message_reaction_emojis` restricted to a single emoji, reacting with another
+/// emoji returns 400 and stores nothing.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_message_reaction_rejects_emoji_outside_allowlist(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    app_config.frontend.message_reaction_emojis = vec!["👍".to_string()];
+    let app_state = test_app_state(app_config, pool).await;
+
+    let _user = get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let (chat_id, message_id) = create_chat_with_message(&server, TEST_JWT_TOKEN).await;
+
+    for emoji in ["🎉", "not-an-emoji"] {
+        let response = server
+            .put(&reaction_path(&message_id, emoji))
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .await;
+        assert_eq!(response.status_code(), http::StatusCode::BAD_REQUEST);
+    }
+
+    assert_eq!(
+        listed_reactions(&server, TEST_JWT_TOKEN, &chat_id, &message_id).await,
+        json!([])
+    );
+}
@@ -0,0 +1,224 @@
+//! Tests for searching within the messages of a chat.
+
+use axum::http;
+use axum_test::TestServer;
+use sea_orm::prelude::Uuid;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::fixtures::{FixtureChat, FixtureMessage, FixtureUser};
+use crate::test_utils::{
+    JwtTokenBuilder, TEST_JWT_TOKEN, TestRequestAuthExt, create_test_server, hermetic_app_config,
+};
+
+/// The raw content of an assistant message with the given text content parts.
+fn assistant_message(texts: &[&str]) -> Value {
+    let content: Vec<Value> = texts
+        .iter()
+        .map(|text| json!({ "content_type": "text", "text": text }))
+        .collect();
+    json!({ "role": "assistant", "content": content })
+}
+
+async fn search(server: &TestServer, token: &str, chat_id: Uuid, query: &str) -> Value {
+    let response = server
+        .get(&format!(
+            "/api/v1beta/chats/{chat_id}/messages/search?{query}"
+        ))
+        .with_bearer_token(token)
+        .await;
+    response.assert_status_ok();
+    response.json()
+}
+
+/// Test that all matches of the messages of a chat are found, in order.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Seeds a chat with a message that matches in two content parts, one of them twice, and a
+/// message without a match. Verifies that both content parts are returned in order with the
+/// offsets of all matches, that the results are paginated, and that short queries, other users
+/// and unknown chats are rejected.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_search_chat_messages_finds_multiple_matches(pool: Pool<Postgres>) {
+    let app_config = hermetic_app_config(None, Some("http://127.0.0.1:1/v1/".to_string()));
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let user = FixtureUser::test_user().create(&app_state).await;
+    let chat = FixtureChat::new(&user).create(&app_state).await;
+    let chat_id = chat.id;
+    let message_id = FixtureMessage::new(
+        &chat,
+        assistant_message(&["Tea or coffee?", "Coffee first, then more COFFEE"]),
+    )
+    .create(&app_state)
+    .await
+    .id;
+    let water_message_id = FixtureMessage::text(&chat, "assistant", "Just water")
+        .after(message_id)
+        .create(&app_state)
+        .await
+        .id;
+    let later_message_id = FixtureMessage::text(&chat, "assistant", "Decaf coffee later")
+        .after(water_message_id)
+        .create(&app_state)
+        .await
+        .id;
+
+    let body = search(&server, TEST_JWT_TOKEN, chat_id, "q=coffee").await;
+    assert_eq!(body["total_count"], 3);
+    assert_eq!(body["has_more"], false);
+    let matches = body["matches"].as_array().unwrap();
+    assert_eq!(matches.len(), 3);
+    assert_eq!(matches[0]["message_id"], message_id.to_string());
+    assert_eq!(matches[0]["content_index"], 0);
+    assert_eq!(matches[0]["snippet"], "Tea or coffee?");
+    assert_eq!(
+        matches[0]["match_offsets"],
+        json!([{ "start": 7, "end": 13 }])
+    );
+    assert_eq!(matches[1]["message_id"], message_id.to_string());
+    assert_eq!(matches[1]["content_index"], 1);
+    assert_eq!(
+        matches[1]["match_offsets"],
+        json!([{ "start": 0, "end": 6 }, { "start": 24, "end": 30 }])
+    );
+    assert_eq!(matches[2]["message_id"], later_message_id.to_string());
+
+    // Pagination over the matches
+    let body = search(
+        &server,
+        TEST_JWT_TOKEN,
+        chat_id,
+        "q=coffee&limit=1&offset=1",
+    )
+    .await;
+    assert_eq!(body["total_count"], 3);
+    assert_eq!(body["has_more"], true);
+    let matches = body["matches"].as_array().unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0]["content_index"], 1);
+
+    // LIKE wildcards in the query are matched literally
+    let body = search(&server, TEST_JWT_TOKEN, chat_id, "q=%25ea").await;
+    assert_eq!(body["total_count"], 0);
+
+    server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages/search?q=c"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::BAD_REQUEST);
+    let other_token = JwtTokenBuilder::new()
+        .subject("message-search-other-user")
+        .email("other@example.com")
+        .build();
+    for (token, chat_id) in [
+        (other_token.as_str(), chat_id),
+        (TEST_JWT_TOKEN, Uuid::new_v4()),
+    ] {
+        server
+            .get(&format!(
+                "/api/v1beta/chats/{chat_id}/messages/search?q=coffee"
+            ))
+            .with_bearer_token(token)
+            .await
+            .assert_status(http::StatusCode::NOT_FOUND);
+    }
+}
+
+/// Test that messages outside of the active thread are only searched on request.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Seeds a chat with a matching message in the active thread and a matching message that was
+/// regenerated away. Verifies that only the active message is found by default, and both with
+/// `include_inactive=true`.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_search_chat_messages_excludes_inactive_thread(pool: Pool<Postgres>) {
+    let app_config = hermetic_app_config(None, Some("http://127.0.0.1:1/v1/".to_string()));
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let user = FixtureUser::test_user().create(&app_state).await;
+    let chat = FixtureChat::new(&user).create(&app_state).await;
+    let chat_id = chat.id;
+    let inactive_message_id = FixtureMessage::text(&chat, "assistant", "An old answer")
+        .create(&app_state)
+        .await
+        .id;
+    // A new thread moves the first message out of the active thread
+    let active_message_id = FixtureMessage::text(&chat, "assistant", "A new answer")
+        .create(&app_state)
+        .await
+        .id;
+
+    let body = search(&server, TEST_JWT_TOKEN, chat_id, "q=answer").await;
+    let matches = body["matches"].as_array().unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0]["message_id"], active_message_id.to_string());
+
+    let body = search(
+        &server,
+        TEST_JWT_TOKEN,
+        chat_id,
+        "q=answer&include_inactive=true",
+    )
+    .await;
+    let message_ids: Vec<&str> = body["matches"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["message_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        message_ids,
+        vec![
+            inactive_message_id.to_string(),
+            active_message_id.to_string()
+        ]
+    );
+}
+
+/// Test that match offsets count characters rather than bytes.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Seeds a message with multi-byte characters and an emoji before the matches and searches for a
+/// word containing a non-ASCII character in a different case. Verifies that the offsets point at the match when
+/// counted in characters.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_search_chat_messages_unicode_offsets(pool: Pool<Postgres>) {
+    let app_config = hermetic_app_config(None, Some("http://127.0.0.1:1/v1/".to_string()));
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let user = FixtureUser::test_user().create(&app_state).await;
+    let chat = FixtureChat::new(&user).create(&app_state).await;
+    let chat_id = chat.id;
+    FixtureMessage::text(&chat, "assistant", "Grüße 👋 aus Köln, köln!")
+        .create(&app_state)
+        .await;
+
+    let body = search(&server, TEST_JWT_TOKEN, chat_id, "q=K%C3%B6LN").await;
+    let matches = body["matches"].as_array().unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(
+        matches[0]["match_offsets"],
+        json!([{ "start": 12, "end": 16 }, { "start": 18, "end": 22 }])
+    );
+    let snippet: Vec<char> = matches[0]["snippet"].as_str().unwrap().chars().collect();
+    assert_eq!(snippet[12..16].iter().collect::<String>(), "Köln");
+    assert_eq!(snippet[18..22].iter().collect::<String>(), "köln");
+}
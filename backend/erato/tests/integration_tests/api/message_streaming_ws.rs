@@ -0,0 +1,141 @@
+//! Integration tests for streaming generations over a WebSocket.
+
+use axum::Router;
+use axum::http;
+use axum_test::{TestServer, TestServerConfig, Transport};
+use erato::config::AppConfig;
+use erato::server::router::router;
+use mocktail::MockSet;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TestRequestAuthExt, build_openai_text_streaming_response, mock_llm_sse_response,
+    setup_mock_llm_server_with_mocks,
+};
+
+async fn create_websocket_server(app_config: AppConfig, pool: Pool<Postgres>) -> TestServer {
+    let app_state = test_app_state(app_config, pool).await;
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    TestServer::new_with_config(
+        app,
+        TestServerConfig {
+            transport: Some(Transport::HttpRandomPort),
+            ..Default::default()
+        },
+    )
+    .expect("Failed to create test server")
+}
+
+/// Verifies that a message can be submitted and its generation streamed over the WebSocket.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Opens a WebSocket, sends a frame with an invalid operation and a `submit` operation, and
+/// receives frames until the stream of the generation ends. The invalid frame is answered with
+/// an operation error, and the frames of the submission carry its correlation ID and follow the
+/// events of the SSE endpoint, from the created chat over the text deltas to the completed
+/// assistant message.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_message_submit_over_websocket(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        mock_llm_sse_response(
+            then,
+            build_openai_text_streaming_response(&["Hello", " over", " WebSocket!"]),
+        );
+    });
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config.websocket_streaming.enabled = true;
+    let server = create_websocket_server(app_config, pool).await;
+
+    let mut websocket = server
+        .get_websocket("/api/v1beta/me/ws")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .into_websocket()
+        .await;
+
+    websocket
+        .send_json(&json!({ "op": "transmogrify", "correlation_id": "bogus" }))
+        .await;
+    let error_frame: Value = websocket.receive_json().await;
+    assert_eq!(error_frame["message_type"], "operation_error");
+    assert_eq!(error_frame["status_code"], 400);
+
+    websocket
+        .send_json(&json!({
+            "op": "submit",
+            "correlation_id": "first",
+            "payload": { "user_message": "Hello?" }
+        }))
+        .await;
+
+    let mut frames = vec![];
+    loop {
+        let frame: Value = websocket.receive_json().await;
+        assert_eq!(frame["correlation_id"], "first");
+        let message_type = frame["message_type"].clone();
+        frames.push(frame);
+        if message_type == "stream_end" {
+            break;
+        }
+    }
+    websocket.close().await;
+
+    let message_types: Vec<&str> = frames
+        .iter()
+        .map(|frame| frame["message_type"].as_str().unwrap())
+        .collect();
+    assert_eq!(message_types.first(), Some(&"chat_created"));
+    assert!(message_types.contains(&"user_message_saved"));
+    assert!(message_types.contains(&"assistant_message_started"));
+    assert!(!message_types.contains(&"error"));
+
+    let streamed_text: String = frames
+        .iter()
+        .filter(|frame| frame["message_type"] == "text_delta")
+        .map(|frame| frame["new_text"].as_str().unwrap())
+        .collect();
+    assert_eq!(streamed_text, "Hello over WebSocket!");
+
+    let completed_frame = frames
+        .iter()
+        .find(|frame| frame["message_type"] == "assistant_message_completed")
+        .expect("Expected assistant_message_completed frame");
+    assert_eq!(
+        completed_frame["message"]["content"][0]["text"],
+        "Hello over WebSocket!"
+    );
+}
+
+/// Verifies that the WebSocket endpoint is not available unless it is enabled.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Requests the WebSocket endpoint with the default configuration, which responds with
+/// `404 Not Found`.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_websocket_streaming_is_disabled_by_default(pool: Pool<Postgres>) {
+    let (app_config, _llm_server) = setup_mock_llm_server_with_mocks(MockSet::new()).await;
+    let server = create_websocket_server(app_config, pool).await;
+
+    server
+        .get_websocket("/api/v1beta/me/ws")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,166 @@
+//! Integration tests for files whose object is missing in the storage.
+
+use axum::http;
+use axum_test::multipart::{MultipartForm, Part};
+use erato::db::entity::file_uploads;
+use mocktail::MockSet;
+use sea_orm::{EntityTrait, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    RequestBodyRecorder, TEST_JWT_TOKEN, TestRequestAuthExt, admin_token,
+    build_openai_text_streaming_response, configure_admin_group, create_test_server,
+    mock_llm_sse_response, parse_sse_events, setup_mock_llm_server_with_mocks,
+};
+
+/// Verifies that a file whose object was deleted from the storage doesn't fail the generation,
+/// but is reported to the user, the model and the admins.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// Uploads a text file to a chat and deletes its object directly in the storage. Submitting a
+/// message with the file still completes, emits a `files_unavailable` event, and sends a
+/// placeholder telling the model that the file is unavailable instead of its contents. The file
+/// is then marked as missing in the file details and listed in the admin report, which is
+/// forbidden for non-admins.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_missing_storage_object_is_reported(pool: Pool<Postgres>) {
+    let llm_request_recorder = RequestBodyRecorder::new();
+    let mut mocks = MockSet::new();
+    {
+        let recorder = llm_request_recorder.clone();
+        mocks.mock(move |when, then| {
+            when.post().path("/v1/chat/completions").matcher(recorder);
+            mock_llm_sse_response(
+                then,
+                build_openai_text_streaming_response(&["The file is unavailable."]),
+            );
+        });
+    }
+    let (mut app_config, _server) = setup_mock_llm_server_with_mocks(mocks).await;
+    configure_admin_group(&mut app_config);
+    app_config.security.wrap_untrusted_content = false;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let chat_response = server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({}))
+        .await;
+    chat_response.assert_status_ok();
+    let chat_id = chat_response.json::<Value>()["chat_id"]
+        .as_str()
+        .expect("Expected chat_id in response")
+        .to_string();
+
+    let upload_response = server
+        .post(&format!("/api/v1beta/me/files?chat_id={chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .multipart(
+            MultipartForm::new().add_part(
+                "file",
+                Part::bytes(b"The launch is scheduled for March 3rd.".to_vec())
+                    .file_name("notes.txt")
+                    .mime_type("text/plain"),
+            ),
+        )
+        .await;
+    upload_response.assert_status_ok();
+    let upload_json: Value = upload_response.json();
+    assert_eq!(upload_json["files"][0]["storage_status"], "ok");
+    let file_id = upload_json["files"][0]["id"]
+        .as_str()
+        .expect("Expected file id in upload response")
+        .to_string();
+
+    // Delete the object behind the file upload, e.g. like a bucket lifecycle rule would.
+    let file_upload = file_uploads::Entity::find_by_id(Uuid::parse_str(&file_id).unwrap())
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the file upload")
+        .expect("The file upload should exist");
+    app_state
+        .file_storage_providers
+        .get(&file_upload.file_storage_provider_id)
+        .expect("The file storage provider should exist")
+        .delete_file(&file_upload.file_storage_path)
+        .await
+        .expect("Failed to delete the object from the storage");
+
+    let message_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "user_message": "When is the launch?",
+            "input_files_ids": [file_id]
+        }))
+        .await;
+    message_response.assert_status_ok();
+
+    let events: Vec<Value> = parse_sse_events(&message_response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .collect();
+    let files_unavailable = events
+        .iter()
+        .find(|event| event["message_type"] == "files_unavailable")
+        .expect("Expected a files_unavailable event");
+    assert_eq!(files_unavailable["files"][0]["id"], file_id.as_str());
+    assert_eq!(files_unavailable["files"][0]["filename"], "notes.txt");
+    assert!(
+        events
+            .iter()
+            .any(|event| event["message_type"] == "assistant_message_completed"),
+        "The generation should complete despite the missing file"
+    );
+
+    let llm_requests = llm_request_recorder.bodies().join("\n");
+    assert!(
+        llm_requests.contains("no longer exists in the storage"),
+        "The model should be told that the file is unavailable"
+    );
+    assert!(!llm_requests.contains("March 3rd"));
+
+    let file_response = server
+        .get(&format!("/api/v1beta/files/{file_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    file_response.assert_status_ok();
+    assert_eq!(file_response.json::<Value>()["storage_status"], "missing");
+
+    let admin_token = admin_token();
+    let report_response = server
+        .get("/api/v1beta/admin/files/missing")
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(report_response.status_code(), http::StatusCode::OK);
+    let report: Value = report_response.json();
+    let missing_files = report["files"]
+        .as_array()
+        .expect("Expected files array in report");
+    assert_eq!(missing_files.len(), 1);
+    assert_eq!(missing_files[0]["id"], file_id.as_str());
+    assert_eq!(
+        missing_files[0]["file_storage_path"],
+        file_upload.file_storage_path.as_str()
+    );
+
+    let forbidden_response = server
+        .get("/api/v1beta/admin/files/missing")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(
+        forbidden_response.status_code(),
+        http::StatusCode::FORBIDDEN
+    );
+}
@@ -1,16 +1,68 @@
 //! API endpoint integration tests.
 
+pub mod admin;
+pub mod admin_commands;
 pub mod assistant_hub;
 pub mod assistants;
 pub mod auth;
+pub mod budget;
+pub mod chat_custom_instructions;
+pub mod chat_export;
+pub mod chat_provider_fallback;
+pub mod chat_provider_groups;
+pub mod chat_provider_quotas;
+pub mod chat_provider_rate_limits;
+pub mod chat_read_states;
+pub mod chat_response_language;
 pub mod chats;
+pub mod compat_v1;
+pub mod content_indices;
+pub mod content_spillover;
 pub mod edit;
+pub mod empty_messages;
+pub mod ephemeral_chats;
 pub mod entra_id;
+pub mod events;
 pub mod facets;
+pub mod feature_flags;
+pub mod file_deletion;
+pub mod file_pointer_migration;
+pub mod file_sources;
+pub mod file_storage_self_test;
+pub mod file_urls;
 pub mod files;
 pub mod generating;
+pub mod generation_cache;
+pub mod generation_detail;
+pub mod generation_input_archival;
+pub mod generation_queue;
+pub mod input_file_capabilities;
+pub mod internal_listener;
+pub mod labels;
+pub mod langfuse_dead_letters;
+pub mod long_messages;
+pub mod markdown_normalization;
 pub mod message_feedback;
+pub mod message_reactions;
+pub mod message_search;
+pub mod message_streaming_ws;
 pub mod messages;
+pub mod missing_files;
+pub mod model_recommendations;
+pub mod moderation;
+pub mod notifications;
+pub mod organizations;
+pub mod output_compliance;
+pub mod output_pacing;
+pub mod output_transformers;
+pub mod ownership_transfers;
+pub mod policy_invalidations;
+pub mod prompt_caching;
+pub mod prompt_optimizer;
+pub mod scheduled_messages;
 pub mod sharepoint;
 pub mod sharing;
 pub mod starter_prompts;
+pub mod system_messages;
+pub mod trusted_headers;
+pub mod usage_reporting;
@@ -0,0 +1,238 @@
+//! Tests for the recommendation of chat models.
+
+use axum::http;
+use axum_test::TestServer;
+use axum_test::multipart::{MultipartForm, Part};
+use erato::config::{AppConfig, ModelPermissionRule};
+use mocktail::MockSet;
+use sea_orm::prelude::Uuid;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    JwtTokenBuilder, TEST_JWT_TOKEN, TestRequestAuthExt, create_test_server,
+    read_integration_test_file_bytes, setup_mock_llm_server_with_mocks,
+};
+
+const TEXT_PROVIDER_ID: &str = "mock-llm";
+const VISION_PROVIDER_ID: &str = "mock-llm-vision";
+const BACKUP_PROVIDER_ID: &str = "mock-llm-backup";
+const BACKUP_GROUP_ID: &str = "backup-users";
+
+/// Configure a text-only, a vision and a backup text-only chat provider, in this priority order,
+/// all available to everyone.
+fn configure_providers(app_config: &mut AppConfig) {
+    let chat_providers = app_config.chat_providers.as_mut().unwrap();
+    let text_provider = chat_providers.providers.get_mut(TEXT_PROVIDER_ID).unwrap();
+    text_provider
+        .model_capabilities
+        .supports_image_understanding = false;
+    let backup_provider = text_provider.clone();
+    let mut vision_provider = text_provider.clone();
+    vision_provider
+        .model_capabilities
+        .supports_image_understanding = true;
+    chat_providers
+        .providers
+        .insert(VISION_PROVIDER_ID.to_string(), vision_provider);
+    chat_providers
+        .providers
+        .insert(BACKUP_PROVIDER_ID.to_string(), backup_provider);
+    chat_providers.priority_order = vec![
+        TEXT_PROVIDER_ID.to_string(),
+        VISION_PROVIDER_ID.to_string(),
+        BACKUP_PROVIDER_ID.to_string(),
+    ];
+    app_config.model_permissions.rules.insert(
+        "allow-all-mock-providers".to_string(),
+        ModelPermissionRule::AllowAll {
+            chat_provider_ids: vec![
+                VISION_PROVIDER_ID.to_string(),
+                BACKUP_PROVIDER_ID.to_string(),
+            ],
+        },
+    );
+}
+
+async fn recommend(server: &TestServer, token: &str, body: Value) -> Vec<Value> {
+    let response = server
+        .post("/api/v1beta/me/models/recommend")
+        .with_bearer_token(token)
+        .json(&body)
+        .await;
+    response.assert_status_ok();
+    response.json()
+}
+
+fn ids(recommendations: &[Value]) -> Vec<&str> {
+    recommendations
+        .iter()
+        .map(|recommendation| recommendation["chat_provider_id"].as_str().unwrap())
+        .collect()
+}
+
+fn has_reason(recommendation: &Value, reason: &str) -> bool {
+    recommendation["reasons"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|value| value == reason)
+}
+
+async fn recommended_model(server: &TestServer, token: &str) -> String {
+    let response = server
+        .get("/api/v1beta/me/models")
+        .with_bearer_token(token)
+        .await;
+    response.assert_status_ok();
+    let models: Vec<Value> = response.json();
+    let recommended: Vec<&Value> = models
+        .iter()
+        .filter(|model| model["recommended"] == true)
+        .collect();
+    assert_eq!(recommended.len(), 1, "Expected one recommended model");
+    recommended[0]["chat_provider_id"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Test that models are recommended in priority order without any context.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// All models have the same score and keep the configured priority order, and the first one is
+/// marked as `recommended` by `/me/models`. For members of a group with a group default, the
+/// group default is recommended first, also by `/me/models`.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_recommendations_follow_priority_and_group_defaults(pool: Pool<Postgres>) {
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(MockSet::new()).await;
+    configure_providers(&mut app_config);
+    app_config
+        .chat_providers
+        .as_mut()
+        .unwrap()
+        .user_group_defaults
+        .insert(BACKUP_GROUP_ID.to_string(), BACKUP_PROVIDER_ID.to_string());
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let recommendations = recommend(&server, TEST_JWT_TOKEN, json!({})).await;
+    assert_eq!(
+        ids(&recommendations),
+        vec![TEXT_PROVIDER_ID, VISION_PROVIDER_ID, BACKUP_PROVIDER_ID]
+    );
+    assert!(
+        recommendations
+            .iter()
+            .all(|recommendation| recommendation["score"] == recommendations[0]["score"])
+    );
+    assert!(has_reason(&recommendations[0], "highest configured priority"));
+    assert_eq!(recommended_model(&server, TEST_JWT_TOKEN).await, TEXT_PROVIDER_ID);
+
+    let group_member_token = JwtTokenBuilder::new()
+        .groups(vec![BACKUP_GROUP_ID.to_string()])
+        .build();
+    let recommendations = recommend(&server, &group_member_token, json!({})).await;
+    assert_eq!(recommendations[0]["chat_provider_id"], BACKUP_PROVIDER_ID);
+    assert!(has_reason(&recommendations[0], "your group default"));
+    assert_eq!(recommended_model(&server, &group_member_token).await, BACKUP_PROVIDER_ID);
+}
+
+/// Test that only models with image input are recommended for chats with images.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-file-storage`
+///
+/// # Test Behavior
+/// With `needs_vision`, and for a chat with an uploaded image, only the vision model is
+/// recommended, with "supports image input" as reason. Unknown chats are rejected with 404.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_vision_is_required_for_images(pool: Pool<Postgres>) {
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(MockSet::new()).await;
+    configure_providers(&mut app_config);
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let recommendations =
+        recommend(&server, TEST_JWT_TOKEN, json!({ "needs_vision": true })).await;
+    assert_eq!(ids(&recommendations), vec![VISION_PROVIDER_ID]);
+    assert!(has_reason(&recommendations[0], "supports image input"));
+
+    let response = server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({}))
+        .await;
+    response.assert_status_ok();
+    let chat_id = response.json::<Value>()["chat_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let response = server
+        .post(&format!("/api/v1beta/me/files?chat_id={chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .multipart(
+            MultipartForm::new().add_part(
+                "file",
+                Part::bytes(read_integration_test_file_bytes("image_1.png"))
+                    .file_name("image_1.png")
+                    .mime_type("image/png"),
+            ),
+        )
+        .await;
+    response.assert_status_ok();
+
+    let recommendations =
+        recommend(&server, TEST_JWT_TOKEN, json!({ "chat_id": chat_id })).await;
+    assert_eq!(ids(&recommendations), vec![VISION_PROVIDER_ID]);
+
+    let response = server
+        .post("/api/v1beta/me/models/recommend")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "chat_id": Uuid::new_v4() }))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+}
+
+/// Test that degraded and rate limited models are demoted.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// After recent generations of the text model failed, and the vision model was rate limited,
+/// the backup model is recommended first, followed by the degraded and the rate limited model.
+/// `/me/models` marks the backup model as `recommended`.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_degraded_models_are_demoted(pool: Pool<Postgres>) {
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(MockSet::new()).await;
+    configure_providers(&mut app_config);
+    let app_state = test_app_state(app_config, pool).await;
+    for _ in 0..3 {
+        app_state
+            .chat_provider_groups
+            .record_error(TEXT_PROVIDER_ID);
+    }
+    app_state
+        .chat_provider_rate_limits
+        .record_rate_limit(VISION_PROVIDER_ID, Some(60), None);
+    let server = create_test_server(app_state);
+
+    let recommendations = recommend(&server, TEST_JWT_TOKEN, json!({})).await;
+    assert_eq!(
+        ids(&recommendations),
+        vec![BACKUP_PROVIDER_ID, TEXT_PROVIDER_ID, VISION_PROVIDER_ID]
+    );
+    assert!(has_reason(&recommendations[1], "currently degraded"));
+    assert!(has_reason(&recommendations[2], "currently rate limited"));
+    assert_eq!(recommended_model(&server, TEST_JWT_TOKEN).await, BACKUP_PROVIDER_ID);
+}
@@ -0,0 +1,304 @@
+//! Tests for the moderation of user messages before generation.
+
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt, create_test_server,
+    extract_chat_id, hermetic_app_config, mock_llm_server_base_url, parse_sse_events,
+};
+use crate::{MIGRATOR, test_app_state};
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use erato::config::{AppConfig, ModerationAction, ModerationConfig, ModerationProviderKind};
+use erato::models::user::get_or_create_user;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Start a fake moderation service, returning its base URL and a counter of received requests.
+///
+/// - `POST /v1/moderations` answers in the shape of the OpenAI moderation API, flagging inputs
+///   that contain "forbidden" as `violence`.
+/// - `POST /webhook` flags inputs that contain "suspicious" as `spam`.
+/// - `POST /unavailable` always responds with `503 Service Unavailable`.
+async fn start_moderation_service() -> (String, Arc<AtomicUsize>) {
+    async fn openai_moderation(
+        State(requests): State<Arc<AtomicUsize>>,
+        Json(body): Json<Value>,
+    ) -> Json<Value> {
+        requests.fetch_add(1, Ordering::SeqCst);
+        let flagged = body["input"]
+            .as_str()
+            .unwrap_or_default()
+            .contains("forbidden");
+        Json(json!({
+            "id": "modr-test",
+            "model": body["model"],
+            "results": [{
+                "flagged": flagged,
+                "categories": { "violence": flagged, "harassment": false }
+            }]
+        }))
+    }
+
+    async fn webhook(
+        State(requests): State<Arc<AtomicUsize>>,
+        Json(body): Json<Value>,
+    ) -> Json<Value> {
+        requests.fetch_add(1, Ordering::SeqCst);
+        let flagged = body["input"]
+            .as_str()
+            .unwrap_or_default()
+            .contains("suspicious");
+        Json(json!({
+            "flagged": flagged,
+            "categories": if flagged { vec!["spam"] } else { vec![] }
+        }))
+    }
+
+    async fn unavailable(State(requests): State<Arc<AtomicUsize>>) -> StatusCode {
+        requests.fetch_add(1, Ordering::SeqCst);
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let app = axum::Router::new()
+        .route("/v1/moderations", post(openai_moderation))
+        .route("/webhook", post(webhook))
+        .route("/unavailable", post(unavailable))
+        .with_state(requests.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    (format!("http://{addr}"), requests)
+}
+
+fn app_config_with_moderation(moderation: ModerationConfig) -> AppConfig {
+    let base_url = mock_llm_server_base_url();
+    let mut app_config = hermetic_app_config(None, Some(format!("{base_url}/base-openai/v1/")));
+    app_config.moderation = moderation;
+    app_config
+}
+
+fn message_events(response: &axum_test::TestResponse) -> Vec<Value> {
+    parse_sse_events(response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .collect()
+}
+
+fn find_event<'a>(events: &'a [Value], message_type: &str) -> Option<&'a Value> {
+    events
+        .iter()
+        .find(|event| event["message_type"] == message_type)
+}
+
+/// Test that a flagged user message is blocked before generation.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mock-llm-server`
+/// - `sse-streaming`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Configures the OpenAI moderation provider with the `block` action and submits a message that
+/// the fake moderation service flags. Verifies that no text is generated, that the stream contains
+/// a `moderation_blocked` error with the flagged categories, and that regenerating the answer is
+/// blocked as well, reusing the stored moderation result instead of calling the service again.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_flagged_message_is_blocked(pool: Pool<Postgres>) {
+    let (moderation_url, moderation_requests) = start_moderation_service().await;
+    let app_config = app_config_with_moderation(ModerationConfig {
+        enabled: true,
+        provider: ModerationProviderKind::OpenaiModeration,
+        endpoint: Some(format!("{moderation_url}/v1/moderations")),
+        model: Some("omni-moderation-latest".to_string()),
+        action: ModerationAction::Block,
+        ..Default::default()
+    });
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let server = create_test_server(app_state.clone());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Tell me something forbidden" }))
+        .await;
+    response.assert_status_ok();
+    let events = message_events(&response);
+
+    assert!(find_event(&events, "text_delta").is_none());
+    let error = find_event(&events, "error").expect("Expected an error event");
+    assert_eq!(error["error_type"], "moderation_blocked");
+    assert_eq!(error["categories"], json!(["violence"]));
+    let completed = find_event(&events, "assistant_message_completed")
+        .expect("Expected assistant_message_completed event");
+    assert_eq!(completed["content"], json!([]));
+    assert_eq!(
+        completed["message"]["error"]["error_type"],
+        "moderation_blocked"
+    );
+    assert_eq!(moderation_requests.load(Ordering::SeqCst), 1);
+
+    // Regenerating the answer is blocked without moderating the message again
+    let assistant_message_id = completed["message_id"].as_str().unwrap().to_string();
+    let response = server
+        .post("/api/v1beta/me/messages/regeneratestream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "current_message_id": assistant_message_id }))
+        .await;
+    response.assert_status_ok();
+    let events = message_events(&response);
+
+    assert!(find_event(&events, "text_delta").is_none());
+    let error = find_event(&events, "error").expect("Expected an error event");
+    assert_eq!(error["error_type"], "moderation_blocked");
+    assert_eq!(moderation_requests.load(Ordering::SeqCst), 1);
+}
+
+/// Test that the `flag` action annotates the user message without blocking it.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mock-llm-server`
+/// - `sse-streaming`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Configures the webhook moderation provider with the `flag` action and submits a message that
+/// the fake moderation service flags. Verifies that the answer is generated, and that the user
+/// message in the chat messages carries the moderation result.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_flag_action_annotates_user_message(pool: Pool<Postgres>) {
+    let (moderation_url, moderation_requests) = start_moderation_service().await;
+    let app_config = app_config_with_moderation(ModerationConfig {
+        enabled: true,
+        provider: ModerationProviderKind::Webhook,
+        endpoint: Some(format!("{moderation_url}/webhook")),
+        action: ModerationAction::Flag,
+        ..Default::default()
+    });
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let server = create_test_server(app_state.clone());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "A suspicious offer" }))
+        .await;
+    response.assert_status_ok();
+    let events = message_events(&response);
+
+    assert!(find_event(&events, "error").is_none());
+    assert!(find_event(&events, "text_delta").is_some());
+    assert_eq!(moderation_requests.load(Ordering::SeqCst), 1);
+
+    let chat_id = extract_chat_id(&parse_sse_events(&response)).expect("Expected a chat ID");
+    let user_message_id = find_event(&events, "user_message_saved")
+        .expect("Expected user_message_saved event")["message_id"]
+        .clone();
+    let response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    let user_message = body["messages"]
+        .as_array()
+        .and_then(|messages| {
+            messages
+                .iter()
+                .find(|message| message["id"] == user_message_id)
+        })
+        .expect("Expected user message in chat messages response");
+    assert_eq!(
+        user_message["moderation"],
+        json!({ "flagged": true, "categories": ["spam"], "blocked": false })
+    );
+}
+
+/// Test the handling of an unavailable moderation service.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mock-llm-server`
+/// - `sse-streaming`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Points the moderation at an endpoint that always fails. Verifies that with `fail_open`
+/// disabled the message is blocked without categories, and that with `fail_open` enabled the
+/// answer is generated.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_unavailable_moderation_service(pool: Pool<Postgres>) {
+    let (moderation_url, moderation_requests) = start_moderation_service().await;
+    let moderation = ModerationConfig {
+        enabled: true,
+        provider: ModerationProviderKind::Webhook,
+        endpoint: Some(format!("{moderation_url}/unavailable")),
+        ..Default::default()
+    };
+
+    // Fail closed
+    let app_state = test_app_state(
+        app_config_with_moderation(ModerationConfig {
+            fail_open: false,
+            ..moderation.clone()
+        }),
+        pool.clone(),
+    )
+    .await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let server = create_test_server(app_state);
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Hello" }))
+        .await;
+    response.assert_status_ok();
+    let events = message_events(&response);
+
+    assert!(find_event(&events, "text_delta").is_none());
+    let error = find_event(&events, "error").expect("Expected an error event");
+    assert_eq!(error["error_type"], "moderation_blocked");
+    assert_eq!(error["categories"], json!([]));
+    assert_eq!(moderation_requests.load(Ordering::SeqCst), 1);
+
+    // Fail open
+    let app_state = test_app_state(
+        app_config_with_moderation(ModerationConfig {
+            fail_open: true,
+            ..moderation
+        }),
+        pool,
+    )
+    .await;
+    let server = create_test_server(app_state);
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Hello" }))
+        .await;
+    response.assert_status_ok();
+    let events = message_events(&response);
+
+    assert!(find_event(&events, "error").is_none());
+    assert!(find_event(&events, "text_delta").is_some());
+    assert_eq!(moderation_requests.load(Ordering::SeqCst), 2);
+}
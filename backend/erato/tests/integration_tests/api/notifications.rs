@@ -0,0 +1,167 @@
+//! Integration tests for the in-app notification inbox.
+
+use axum::http;
+use axum_test::TestServer;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    JwtTokenBuilder, TEST_USER_ISSUER, TestRequestAuthExt, create_test_server, hermetic_app_config,
+};
+
+/// Create an empty chat and share it with the user with the ID `grantee_id`.
+async fn create_and_share_chat(server: &TestServer, owner_token: &str, grantee_id: &str) -> String {
+    let response = server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(owner_token)
+        .json(&json!({}))
+        .await;
+    response.assert_status_ok();
+    let chat_id = response.json::<Value>()["chat_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let response = server
+        .post("/api/v1beta/share-grants")
+        .with_bearer_token(owner_token)
+        .json(&json!({
+            "resource_type": "chat",
+            "resource_id": chat_id,
+            "subject_type": "user",
+            "subject_id_type": "id",
+            "subject_id": grantee_id,
+            "role": "viewer",
+        }))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::CREATED);
+    chat_id
+}
+
+async fn unread_notifications(server: &TestServer, token: &str) -> Value {
+    let profile: Value = server
+        .get("/api/v1beta/me/profile")
+        .with_bearer_token(token)
+        .await
+        .json();
+    profile["unread_notifications"].clone()
+}
+
+/// Verifies that sharing a chat with a user notifies the user, and that notifications can be
+/// marked as read.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// The owner shares a chat with the grantee by user ID. The grantee gets an unread
+/// `share_grant_created` notification, which is counted in `/me/profile`, and the owner gets none.
+/// Marking it as read sets `read_at` and is idempotent, and other users get 404 for it. After a
+/// second share, the new unread notification is listed before the read one, and marking all as
+/// read marks only that one.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_share_grant_notifications_read_state(pool: Pool<Postgres>) {
+    let mut app_config = hermetic_app_config(None, None);
+    app_config.chat_sharing.enabled = true;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let owner = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        "notification-owner",
+        None,
+    )
+    .await
+    .expect("Failed to create owner user");
+    let grantee = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        "notification-grantee",
+        None,
+    )
+    .await
+    .expect("Failed to create grantee user");
+    let owner_token = JwtTokenBuilder::new().subject("notification-owner").build();
+    let grantee_token = JwtTokenBuilder::new()
+        .subject("notification-grantee")
+        .build();
+
+    let chat_id = create_and_share_chat(&server, &owner_token, &grantee.id.to_string()).await;
+
+    let list: Value = server
+        .get("/api/v1beta/me/notifications")
+        .with_bearer_token(&grantee_token)
+        .await
+        .json();
+    assert_eq!(list["stats"]["total_count"], 1);
+    let notification = &list["notifications"][0];
+    assert_eq!(notification["kind"], "share_grant_created");
+    assert_eq!(notification["payload"]["resource_type"], "chat");
+    assert_eq!(notification["payload"]["resource_id"], chat_id);
+    assert_eq!(
+        notification["payload"]["shared_by_user_id"],
+        owner.id.to_string()
+    );
+    assert_eq!(notification["payload"]["permission"], "read");
+    assert_eq!(notification["read_at"], Value::Null);
+    let notification_id = notification["id"].as_str().unwrap().to_string();
+    assert_eq!(unread_notifications(&server, &grantee_token).await, 1);
+    assert_eq!(unread_notifications(&server, &owner_token).await, 0);
+
+    // Other users can't mark the notification as read
+    let response = server
+        .post(&format!(
+            "/api/v1beta/me/notifications/{notification_id}/read"
+        ))
+        .with_bearer_token(&owner_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+
+    let response = server
+        .post(&format!(
+            "/api/v1beta/me/notifications/{notification_id}/read"
+        ))
+        .with_bearer_token(&grantee_token)
+        .await;
+    response.assert_status_ok();
+    let read_at = response.json::<Value>()["read_at"].clone();
+    assert!(read_at.is_string());
+    assert_eq!(unread_notifications(&server, &grantee_token).await, 0);
+
+    let response = server
+        .post(&format!(
+            "/api/v1beta/me/notifications/{notification_id}/read"
+        ))
+        .with_bearer_token(&grantee_token)
+        .await;
+    response.assert_status_ok();
+    assert_eq!(response.json::<Value>()["read_at"], read_at);
+
+    let second_chat_id =
+        create_and_share_chat(&server, &owner_token, &grantee.id.to_string()).await;
+    let list: Value = server
+        .get("/api/v1beta/me/notifications")
+        .with_bearer_token(&grantee_token)
+        .await
+        .json();
+    assert_eq!(list["stats"]["total_count"], 2);
+    assert_eq!(
+        list["notifications"][0]["payload"]["resource_id"],
+        second_chat_id
+    );
+    assert_eq!(list["notifications"][0]["read_at"], Value::Null);
+    assert_eq!(list["notifications"][1]["id"], notification_id);
+    assert_eq!(unread_notifications(&server, &grantee_token).await, 1);
+
+    let response = server
+        .post("/api/v1beta/me/notifications/read-all")
+        .with_bearer_token(&grantee_token)
+        .await;
+    response.assert_status_ok();
+    assert_eq!(response.json::<Value>()["marked_count"], 1);
+    assert_eq!(unread_notifications(&server, &grantee_token).await, 0);
+}
@@ -0,0 +1,223 @@
+//! Tests for the scoping of data to organizations.
+
+use axum::http;
+use axum_test::TestServer;
+use erato::policy::engine::PolicyEngine;
+use erato::policy::types::Subject;
+use sea_orm::prelude::Uuid;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    JwtTokenBuilder, TestRequestAuthExt, create_test_server, extract_chat_id, parse_sse_events,
+    setup_mock_llm_server,
+};
+
+/// Fetch the profile of the user, creating the user on the first request.
+async fn fetch_profile(server: &TestServer, token: &str) -> Value {
+    let response = server
+        .get("/api/v1beta/me/profile")
+        .with_bearer_token(token)
+        .await;
+    response.assert_status_ok();
+    response.json()
+}
+
+async fn create_chat(server: &TestServer, token: &str) -> String {
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(token)
+        .add_header(http::header::CONTENT_TYPE, "application/json")
+        .json(&json!({ "user_message": "Please respond with a short hello" }))
+        .await;
+    response.assert_status_ok();
+    extract_chat_id(&parse_sse_events(&response)).expect("Expected chat_created event")
+}
+
+async fn share_chat_with_user(
+    server: &TestServer,
+    token: &str,
+    chat_id: &str,
+    user_id: &str,
+) -> http::StatusCode {
+    server
+        .post("/api/v1beta/share-grants")
+        .with_bearer_token(token)
+        .json(&json!({
+            "resource_type": "chat",
+            "resource_id": chat_id,
+            "subject_type": "user",
+            "subject_id_type": "id",
+            "subject_id": user_id,
+            "role": "viewer",
+        }))
+        .await
+        .status_code()
+}
+
+/// Test that users can neither access nor probe the data of other organizations.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Configures `organization_claim` and logs in users of two organizations. Verifies that a chat,
+/// an assistant and a file of the user of organization B respond exactly like non-existent IDs
+/// to the user of organization A, that the user of organization A can share with users of their
+/// own organization but not with the user of organization B, and that sharing the chat of the
+/// user of organization B is reported as not found.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_data_is_scoped_to_organizations(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    app_config.organization_claim = Some("tid".to_string());
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let user_a_token = JwtTokenBuilder::new()
+        .subject("org-a-user")
+        .email("a@org-a.example.com")
+        .tenant_id("org-a")
+        .build();
+    let colleague_a_token = JwtTokenBuilder::new()
+        .subject("org-a-colleague")
+        .email("colleague@org-a.example.com")
+        .tenant_id("org-a")
+        .build();
+    let user_b_token = JwtTokenBuilder::new()
+        .subject("org-b-user")
+        .email("b@org-b.example.com")
+        .tenant_id("org-b")
+        .build();
+
+    let user_a = fetch_profile(&server, &user_a_token).await;
+    let colleague_a = fetch_profile(&server, &colleague_a_token).await;
+    let user_b = fetch_profile(&server, &user_b_token).await;
+    assert_eq!(user_a["organization_id"], "org-a");
+    assert_eq!(user_b["organization_id"], "org-b");
+    let user_b_id = user_b["id"].as_str().unwrap().to_string();
+
+    // Resources of user B
+    let chat_b = create_chat(&server, &user_b_token).await;
+    let subject_b = Subject::UserWithOrganizationInfo {
+        id: user_b_id.clone(),
+        organization_id: Some("org-b".to_string()),
+        organization_user_id: None,
+        organization_group_ids: vec![],
+    };
+    let assistant_b = erato::models::assistant::create_assistant(
+        &app_state.db,
+        &PolicyEngine::new(),
+        &subject_b,
+        "Org B Assistant".to_string(),
+        None,
+        "You are the assistant of organization B".to_string(),
+        None,
+        None,
+        None,
+        false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
+    )
+    .await
+    .expect("Failed to create assistant");
+    assert_eq!(assistant_b.organization_id.as_deref(), Some("org-b"));
+    let file_b = erato::models::assistant::create_standalone_file_upload(
+        &app_state.db,
+        &PolicyEngine::new(),
+        &subject_b,
+        "org-b.txt".to_string(),
+        "seaweedfs".to_string(),
+        "org-b.txt".to_string(),
+    )
+    .await
+    .expect("Failed to create file");
+    app_state.global_policy_engine.invalidate_data().await;
+
+    // User B can access their own resources
+    server
+        .get(&format!("/api/v1beta/chats/{chat_b}/messages"))
+        .with_bearer_token(&user_b_token)
+        .await
+        .assert_status_ok();
+    server
+        .get(&format!("/api/v1beta/assistants/{}", assistant_b.id))
+        .with_bearer_token(&user_b_token)
+        .await
+        .assert_status_ok();
+
+    // For user A, they are indistinguishable from resources that don't exist
+    let unknown_id = Uuid::new_v4();
+    for path in [
+        format!("/api/v1beta/chats/{chat_b}/messages"),
+        format!("/api/v1beta/assistants/{}", assistant_b.id),
+        format!("/api/v1beta/files/{}", file_b.id),
+    ] {
+        let response = server.get(&path).with_bearer_token(&user_a_token).await;
+        assert_eq!(
+            response.status_code(),
+            http::StatusCode::NOT_FOUND,
+            "Expected {path} to be not found for user A"
+        );
+    }
+    for path in [
+        format!("/api/v1beta/chats/{unknown_id}/messages"),
+        format!("/api/v1beta/assistants/{unknown_id}"),
+        format!("/api/v1beta/files/{unknown_id}"),
+    ] {
+        let response = server.get(&path).with_bearer_token(&user_a_token).await;
+        assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+    }
+
+    // User A can't share the chat of user B
+    assert_eq!(
+        share_chat_with_user(
+            &server,
+            &user_a_token,
+            &chat_b,
+            user_a["id"].as_str().unwrap()
+        )
+        .await,
+        http::StatusCode::NOT_FOUND
+    );
+
+    // User A can share with users of their own organization, but not with user B, who is
+    // rejected the same way as a user that doesn't exist
+    let chat_a = create_chat(&server, &user_a_token).await;
+    assert_eq!(
+        share_chat_with_user(
+            &server,
+            &user_a_token,
+            &chat_a,
+            colleague_a["id"].as_str().unwrap()
+        )
+        .await,
+        http::StatusCode::CREATED
+    );
+    assert_eq!(
+        share_chat_with_user(&server, &user_a_token, &chat_a, &user_b_id).await,
+        http::StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        share_chat_with_user(&server, &user_a_token, &chat_a, &unknown_id.to_string()).await,
+        http::StatusCode::BAD_REQUEST
+    );
+
+    // The colleague can read the shared chat, user B can't
+    server
+        .get(&format!("/api/v1beta/chats/{chat_a}/messages"))
+        .with_bearer_token(&colleague_a_token)
+        .await
+        .assert_status_ok();
+    let response = server
+        .get(&format!("/api/v1beta/chats/{chat_a}/messages"))
+        .with_bearer_token(&user_b_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,135 @@
+//! Tests for the output compliance rules applied to generated answers.
+
+use erato::config::{OutputComplianceAction, OutputComplianceRuleConfig, PromptPatternType};
+use erato::db::entity::messages;
+use sea_orm::{EntityTrait, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    MockLlmConfig, TEST_JWT_TOKEN, TestRequestAuthExt, create_test_server, setup_mock_llm_server,
+    streamed_text, submit_events,
+};
+
+/// Mock LLM config whose answer mentions a competitor, split across two chunks.
+fn competitor_llm_config() -> MockLlmConfig {
+    MockLlmConfig {
+        chunks: vec![
+            "Try the products of Acme".to_string(),
+            " Corp instead.".to_string(),
+        ],
+        ..Default::default()
+    }
+}
+
+fn competitor_rule(action: OutputComplianceAction) -> OutputComplianceRuleConfig {
+    OutputComplianceRuleConfig {
+        r#type: PromptPatternType::Fixed,
+        pattern: "acme corp".to_string(),
+        case_insensitive: true,
+        action,
+    }
+}
+
+fn event<'a>(events: &'a [Value], message_type: &str) -> Option<&'a Value> {
+    events
+        .iter()
+        .find(|event| event["message_type"] == message_type)
+}
+
+/// Test that a blocked phrase split across chunks is masked.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// The mock LLM splits a phrase of a `mask` rule across two chunks. Neither the streamed text nor
+/// the saved message contain the phrase, both contain the mask instead, and the match is recorded
+/// on the message without the matched text.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_blocked_phrase_split_across_chunks_is_masked(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(Some(competitor_llm_config())).await;
+    app_config.guardrails.output_compliance.rules.insert(
+        "competitor".to_string(),
+        competitor_rule(OutputComplianceAction::Mask),
+    );
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Which products should I buy?" }))
+        .await;
+    response.assert_status_ok();
+    let events = submit_events(&response);
+
+    assert!(event(&events, "error").is_none());
+    let expected_text = "Try the products of [redacted] instead.";
+    assert_eq!(streamed_text(&events), expected_text);
+    let completed = event(&events, "assistant_message_completed")
+        .expect("Expected assistant_message_completed event");
+    assert_eq!(completed["message"]["content"][0]["text"], expected_text);
+
+    let message_id = Uuid::parse_str(completed["message_id"].as_str().unwrap()).unwrap();
+    let generation_metadata = messages::Entity::find_by_id(message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the assistant message")
+        .expect("The assistant message should exist")
+        .generation_metadata
+        .expect("The assistant message should have generation metadata");
+    assert_eq!(
+        generation_metadata["output_compliance_matches"],
+        json!([{ "rule_id": "competitor", "action": "mask" }])
+    );
+    assert!(!generation_metadata.to_string().contains("Acme"));
+}
+
+/// Test that a blocked phrase split across chunks aborts the generation before it is streamed.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// The mock LLM splits a phrase of an `abort` rule across two chunks. The generation ends with a
+/// `compliance_policy` error that names the rule, and no part of the phrase was streamed or saved.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_blocked_phrase_split_across_chunks_aborts(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(Some(competitor_llm_config())).await;
+    app_config.guardrails.output_compliance.rules.insert(
+        "competitor".to_string(),
+        competitor_rule(OutputComplianceAction::Abort),
+    );
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Which products should I buy?" }))
+        .await;
+    response.assert_status_ok();
+    let events = submit_events(&response);
+
+    let error = event(&events, "error").expect("Expected an error event");
+    assert_eq!(error["error_type"], "compliance_policy");
+    assert_eq!(error["matched_rule_id"], "competitor");
+    assert!(!streamed_text(&events).contains("Acme"));
+
+    let completed = event(&events, "assistant_message_completed")
+        .expect("Expected assistant_message_completed event");
+    assert_eq!(
+        completed["message"]["error"]["error_type"],
+        "compliance_policy"
+    );
+    assert!(!completed["message"]["content"].to_string().contains("Acme"));
+}
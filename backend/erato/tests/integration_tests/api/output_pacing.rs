@@ -0,0 +1,145 @@
+//! Tests for the pacing of the text streamed to clients.
+
+use std::time::{Duration, Instant};
+
+use erato::config::{AppConfig, OutputPacingConfig};
+use mocktail::prelude::MockServer;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    Event, MockLlmConfig, TEST_JWT_TOKEN, TestRequestAuthExt, create_test_server,
+    extract_text_deltas, parse_sse_events, setup_mock_llm_server,
+};
+
+/// The answer of the "fast" mock LLM, returned in two large chunks without delay.
+const FAST_CHUNKS: &[&str] = &[
+    "Output pacing splits the large chunks of very fast providers into smaller deltas, ",
+    "so that the answer appears gradually instead of as one blob of text in the client.",
+];
+
+/// Set up the mock LLM that returns [`FAST_CHUNKS`], with the given pacing of its output.
+async fn fast_llm_config(output_pacing: OutputPacingConfig) -> (AppConfig, MockServer) {
+    let (mut app_config, server) = setup_mock_llm_server(Some(MockLlmConfig {
+        chunks: FAST_CHUNKS.iter().map(|chunk| chunk.to_string()).collect(),
+        delay_ms: 0,
+        ..Default::default()
+    }))
+    .await;
+    let provider = app_config
+        .chat_providers
+        .as_mut()
+        .and_then(|chat_providers| chat_providers.providers.get_mut("mock-llm"))
+        .expect("mock provider should be configured");
+    provider.model_settings.output_pacing = Some(output_pacing);
+    (app_config, server)
+}
+
+fn completed_text(events: &[Event]) -> String {
+    events
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .find(|event| event["message_type"] == "assistant_message_completed")
+        .expect("Expected assistant_message_completed event")["message"]["content"][0]["text"]
+        .as_str()
+        .expect("Expected the text of the completed message")
+        .to_string()
+}
+
+/// Test that the large chunks of a fast provider are streamed as smaller deltas.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// The mock LLM returns its answer in two chunks at once. With pacing at 400 characters per
+/// second, the client receives deltas of at most 20 characters, and the stream takes about as
+/// long as the text takes at that rate. The completed message contains the full text.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_fast_provider_output_is_paced(pool: Pool<Postgres>) {
+    let (app_config, _server) = fast_llm_config(OutputPacingConfig {
+        enabled: true,
+        target_chars_per_second: 400,
+        max_chunk_chars: 20,
+        max_delay_ms: 10_000,
+    })
+    .await;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let started_at = Instant::now();
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Explain output pacing" }))
+        .await;
+    let elapsed = started_at.elapsed();
+    response.assert_status_ok();
+    let events = parse_sse_events(&response);
+
+    let full_text = FAST_CHUNKS.concat();
+    let deltas = extract_text_deltas(&events);
+    assert!(
+        deltas.len() > FAST_CHUNKS.len(),
+        "Expected the chunks to be split, got {deltas:?}"
+    );
+    assert!(
+        deltas.iter().all(|delta| delta.chars().count() <= 20),
+        "Expected deltas of at most 20 characters, got {deltas:?}"
+    );
+    assert_eq!(deltas.concat(), full_text);
+    assert_eq!(completed_text(&events), full_text);
+
+    // Roughly the duration of the text at the target rate, with some tolerance for the last chunk
+    let paced_duration = Duration::from_secs_f64(full_text.chars().count() as f64 / 400.0);
+    assert!(
+        elapsed >= paced_duration.mul_f64(0.8),
+        "Expected the stream to take at least {paced_duration:?}, took {elapsed:?}"
+    );
+}
+
+/// Test that pacing doesn't delay the completion of a message by more than `max_delay_ms`.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// At 10 characters per second, the text would take more than 15 seconds to stream. As the
+/// generation completes right away, the remaining text is sent after `max_delay_ms`, and the
+/// client still receives the full text.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_output_pacing_delay_is_capped(pool: Pool<Postgres>) {
+    let (app_config, _server) = fast_llm_config(OutputPacingConfig {
+        enabled: true,
+        target_chars_per_second: 10,
+        max_chunk_chars: 20,
+        max_delay_ms: 200,
+    })
+    .await;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+
+    let started_at = Instant::now();
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Explain output pacing" }))
+        .await;
+    let elapsed = started_at.elapsed();
+    response.assert_status_ok();
+    let events = parse_sse_events(&response);
+
+    assert_eq!(extract_text_deltas(&events).concat(), FAST_CHUNKS.concat());
+    assert!(
+        elapsed < Duration::from_secs(10),
+        "Expected pacing to be capped, took {elapsed:?}"
+    );
+}
@@ -0,0 +1,318 @@
+//! Tests for the output transformers post-processing generated answers.
+
+use axum::Json;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use erato::config::{
+    AppConfig, OutputTransformerConfig, OutputTransformerFailMode, OutputTransformerMode,
+};
+use erato::db::entity::messages;
+use erato::services::output_transformers::{
+    OUTPUT_TRANSFORMER_SIGNATURE_HEADER, OUTPUT_TRANSFORMER_TIMESTAMP_HEADER,
+    output_transformer_signature,
+};
+use sea_orm::{EntityTrait, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::sync::{Arc, Mutex};
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TestRequestAuthExt, create_test_server, setup_mock_llm_server, streamed_text,
+    submit_events,
+};
+
+const TRANSFORMER_SECRET: &str = "transformer-secret";
+const DISCLAIMER: &str = "\n\nGenerated by AI.";
+
+/// Start a fake transformer service, returning its base URL and the texts received by the
+/// `streaming_buffered` transformer.
+///
+/// Requests without a valid signature are rejected with `401 Unauthorized`.
+/// - `POST /disclaimer` appends [`DISCLAIMER`] to the last text part of a completed answer.
+/// - `POST /uppercase` converts streamed text to upper case.
+/// - `POST /failing` always responds with `500 Internal Server Error`.
+async fn start_transformer_service() -> (String, Arc<Mutex<Vec<String>>>) {
+    fn verified_request(headers: &HeaderMap, body: &[u8]) -> Result<Value, StatusCode> {
+        let timestamp = headers
+            .get(OUTPUT_TRANSFORMER_TIMESTAMP_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        let signature = headers
+            .get(OUTPUT_TRANSFORMER_SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        if signature != output_transformer_signature(TRANSFORMER_SECRET, timestamp, body) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        serde_json::from_slice(body).map_err(|_| StatusCode::BAD_REQUEST)
+    }
+
+    async fn disclaimer(headers: HeaderMap, body: Bytes) -> Result<Json<Value>, StatusCode> {
+        let request = verified_request(&headers, &body)?;
+        let mut content = request["content"].as_array().cloned().unwrap_or_default();
+        if let Some(text_part) = content
+            .iter_mut()
+            .rev()
+            .find(|part| part["content_type"] == "text")
+        {
+            text_part["text"] = json!(format!(
+                "{}{DISCLAIMER}",
+                text_part["text"].as_str().unwrap_or_default()
+            ));
+        }
+        Ok(Json(json!({ "content": content })))
+    }
+
+    async fn uppercase(
+        State(received_texts): State<Arc<Mutex<Vec<String>>>>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json<Value>, StatusCode> {
+        let request = verified_request(&headers, &body)?;
+        let text = request["text"].as_str().unwrap_or_default().to_string();
+        let transformed = text.to_uppercase();
+        received_texts.lock().unwrap().push(text);
+        Ok(Json(json!({ "text": transformed })))
+    }
+
+    async fn failing() -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+
+    let received_texts = Arc::new(Mutex::new(Vec::new()));
+    let app = axum::Router::new()
+        .route("/disclaimer", post(disclaimer))
+        .route("/uppercase", post(uppercase))
+        .route("/failing", post(failing))
+        .with_state(received_texts.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    (format!("http://{addr}"), received_texts)
+}
+
+fn transformer(
+    name: &str,
+    url: String,
+    mode: OutputTransformerMode,
+    fail_mode: OutputTransformerFailMode,
+) -> OutputTransformerConfig {
+    OutputTransformerConfig {
+        name: name.to_string(),
+        url,
+        secret: TRANSFORMER_SECRET.into(),
+        mode,
+        buffer_chars: 10,
+        timeout_ms: 5000,
+        fail_mode,
+    }
+}
+
+/// Submit a message to the mock LLM, which answers "Hello from the mocked LLM!" in five chunks,
+/// and return the events of the stream.
+async fn submit(app_config: AppConfig, pool: Pool<Postgres>) -> (Vec<Value>, Option<Value>) {
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Say hello" }))
+        .await;
+    response.assert_status_ok();
+    let events = submit_events(&response);
+
+    let completed = event(&events, "assistant_message_completed")
+        .expect("Expected assistant_message_completed event");
+    let message_id = Uuid::parse_str(completed["message_id"].as_str().unwrap()).unwrap();
+    let generation_metadata = messages::Entity::find_by_id(message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load the assistant message")
+        .expect("The assistant message should exist")
+        .generation_metadata;
+    (events, generation_metadata)
+}
+
+fn event<'a>(events: &'a [Value], message_type: &str) -> Option<&'a Value> {
+    events
+        .iter()
+        .find(|event| event["message_type"] == message_type)
+}
+
+fn completed_text(events: &[Value]) -> String {
+    event(events, "assistant_message_completed").unwrap()["message"]["content"][0]["text"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Test that a `final` transformer changes the completed answer.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// The fake transformer verifies the signature of the request and appends a disclaimer to the
+/// answer. The streamed text is unchanged, while the completed message contains the disclaimer,
+/// and the transformation is recorded in the generation metadata with the number of added bytes.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_final_transformer_changes_completed_answer(pool: Pool<Postgres>) {
+    let (transformer_url, _) = start_transformer_service().await;
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    app_config.chat.output_transformers = vec![transformer(
+        "disclaimer",
+        format!("{transformer_url}/disclaimer"),
+        OutputTransformerMode::Final,
+        OutputTransformerFailMode::Closed,
+    )];
+
+    let (events, generation_metadata) = submit(app_config, pool).await;
+
+    assert!(event(&events, "error").is_none());
+    assert_eq!(streamed_text(&events), "Hello from the mocked LLM!");
+    assert_eq!(
+        completed_text(&events),
+        format!("Hello from the mocked LLM!{DISCLAIMER}")
+    );
+    assert_eq!(
+        generation_metadata.unwrap()["output_transformations"],
+        json!([{ "transformer": "disclaimer", "changed_bytes": DISCLAIMER.len() }])
+    );
+}
+
+/// Test that a `streaming_buffered` transformer changes the streamed text in chunks.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// The transformer collects at least 10 characters before they are sent to the fake transformer,
+/// which converts them to upper case, and the rest of the text once the answer is complete. Both
+/// the streamed text and the completed message are transformed, and the changed bytes of all
+/// chunks are recorded.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_streaming_transformer_changes_streamed_text(pool: Pool<Postgres>) {
+    let (transformer_url, received_texts) = start_transformer_service().await;
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    app_config.chat.output_transformers = vec![transformer(
+        "uppercase",
+        format!("{transformer_url}/uppercase"),
+        OutputTransformerMode::StreamingBuffered,
+        OutputTransformerFailMode::Closed,
+    )];
+
+    let (events, generation_metadata) = submit(app_config, pool).await;
+
+    assert!(event(&events, "error").is_none());
+    assert_eq!(
+        *received_texts.lock().unwrap(),
+        vec!["Hello from", " the mocked", " LLM!"]
+    );
+    assert_eq!(streamed_text(&events), "HELLO FROM THE MOCKED LLM!");
+    assert_eq!(completed_text(&events), "HELLO FROM THE MOCKED LLM!");
+    assert_eq!(
+        generation_metadata.unwrap()["output_transformations"],
+        json!([{ "transformer": "uppercase", "changed_bytes": 19 }])
+    );
+}
+
+/// Test that failing transformers with `fail_mode = "open"` pass the answer through.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// A `streaming_buffered` and a `final` transformer both fail. The generation succeeds with the
+/// untransformed answer, and no transformation is recorded.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_failing_transformers_fail_open(pool: Pool<Postgres>) {
+    let (transformer_url, _) = start_transformer_service().await;
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    app_config.chat.output_transformers = vec![
+        transformer(
+            "streaming",
+            format!("{transformer_url}/failing"),
+            OutputTransformerMode::StreamingBuffered,
+            OutputTransformerFailMode::Open,
+        ),
+        transformer(
+            "final",
+            format!("{transformer_url}/failing"),
+            OutputTransformerMode::Final,
+            OutputTransformerFailMode::Open,
+        ),
+    ];
+
+    let (events, generation_metadata) = submit(app_config, pool).await;
+
+    assert!(event(&events, "error").is_none());
+    assert_eq!(streamed_text(&events), "Hello from the mocked LLM!");
+    assert_eq!(completed_text(&events), "Hello from the mocked LLM!");
+    assert!(generation_metadata.unwrap()["output_transformations"].is_null());
+}
+
+/// Test that failing transformers with `fail_mode = "closed"` end the generation.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// For a failing `streaming_buffered` transformer, and then for a failing `final` transformer,
+/// the generation ends with a `transformer_failed` error that names the transformer, and the
+/// untransformed answer is neither streamed by the `streaming_buffered` transformer nor stored.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_failing_transformers_fail_closed(pool: Pool<Postgres>) {
+    let (transformer_url, _) = start_transformer_service().await;
+    for mode in [
+        OutputTransformerMode::StreamingBuffered,
+        OutputTransformerMode::Final,
+    ] {
+        let (mut app_config, _server) = setup_mock_llm_server(None).await;
+        app_config.chat.output_transformers = vec![transformer(
+            "failing",
+            format!("{transformer_url}/failing"),
+            mode,
+            OutputTransformerFailMode::Closed,
+        )];
+
+        let (events, generation_metadata) = submit(app_config, pool.clone()).await;
+
+        let error = event(&events, "error").expect("Expected an error event");
+        assert_eq!(error["error_type"], "transformer_failed");
+        assert_eq!(error["transformer"], "failing");
+        if mode == OutputTransformerMode::StreamingBuffered {
+            assert_eq!(streamed_text(&events), "");
+        }
+        let completed = event(&events, "assistant_message_completed").unwrap();
+        assert_eq!(
+            completed["message"]["error"]["error_type"],
+            "transformer_failed"
+        );
+        assert!(!completed["message"]["content"].to_string().contains("Hello"));
+        assert_eq!(
+            generation_metadata.unwrap()["error"]["error_type"],
+            "transformer_failed"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Integration tests for the transfer of chats and assistants to another owner.
+
+use axum::http;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::fixtures::{FixtureAssistant, FixtureChat, FixtureUser};
+use crate::test_utils::{TEST_JWT_TOKEN, TestRequestAuthExt, admin_app_config, create_test_server};
+
+/// Verifies that owners and admins can transfer chats and assistants to another user.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// A user that doesn't own the chat can't transfer it. A dry run of the owner records nothing.
+/// The owner then transfers the chat, keeping read access and rewriting the attribution of their
+/// messages: the new owner can edit the chat, the previous owner can only read it, and the user
+/// messages are attributed to the new owner. An admin transfers all chats of the new owner to
+/// another user, without keeping access, and an assistant of the first owner. Every applied
+/// transfer is recorded in `ownership_transfers`.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_and_assistant_ownership_transfer(pool: Pool<Postgres>) {
+    let app_config = admin_app_config();
+    let app_state = test_app_state(app_config, pool.clone()).await;
+
+    let owner = FixtureUser::new("transfer-owner").create(&app_state).await;
+    let new_owner = FixtureUser::new("transfer-new-owner")
+        .create(&app_state)
+        .await;
+    let admin = FixtureUser::new("transfer-admin").create(&app_state).await;
+    let admin_token = admin.admin_token();
+    let chat = FixtureChat::new(&owner)
+        .with_messages(2)
+        .create(&app_state)
+        .await;
+    let assistant = FixtureAssistant::new(&owner, "Team assistant")
+        .create(&app_state)
+        .await;
+    // Messages submitted through the API carry their author in `name`
+    sqlx::query(
+        "UPDATE messages SET raw_message = jsonb_set(raw_message, '{name}', to_jsonb($1::text)) \
+         WHERE chat_id = $2 AND raw_message->>'role' = 'user'",
+    )
+    .bind(owner.id.to_string())
+    .bind(chat.id)
+    .execute(&pool)
+    .await
+    .expect("Failed to attribute the messages");
+
+    let server = create_test_server(app_state);
+    let transfer_path = format!("/api/v1beta/chats/{}/transfer-ownership", chat.id);
+
+    server
+        .post(&transfer_path)
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "new_owner_user_id": admin.id.to_string() }))
+        .await
+        .assert_status(http::StatusCode::FORBIDDEN);
+
+    let dry_run_response = server
+        .post(&transfer_path)
+        .with_bearer_token(&owner.token())
+        .json(&json!({ "new_owner_user_id": new_owner.id.to_string(), "dry_run": true }))
+        .await;
+    dry_run_response.assert_status_ok();
+    let dry_run: Value = dry_run_response.json();
+    assert_eq!(dry_run["new_owner_user_id"], new_owner.id.to_string());
+    server
+        .get(&format!("/api/v1beta/me/chats/{}", chat.id))
+        .with_bearer_token(&new_owner.token())
+        .await
+        .assert_status(http::StatusCode::NOT_FOUND);
+
+    let transfer_response = server
+        .post(&transfer_path)
+        .with_bearer_token(&owner.token())
+        .json(&json!({
+            "new_owner_user_id": new_owner.id.to_string(),
+            "keep_previous_owner_access": true,
+            "rewrite_attribution": true,
+        }))
+        .await;
+    transfer_response.assert_status_ok();
+    let transfer: Value = transfer_response.json();
+    assert_eq!(transfer["previous_owner_user_id"], owner.id.to_string());
+    assert_eq!(transfer["previous_owner_access_kept"], true);
+    assert_eq!(transfer["rewritten_attribution_count"], 1);
+
+    let new_owner_chat: Value = server
+        .get(&format!("/api/v1beta/me/chats/{}", chat.id))
+        .with_bearer_token(&new_owner.token())
+        .await
+        .json();
+    assert_eq!(new_owner_chat["can_edit"], true);
+    let previous_owner_chat_response = server
+        .get(&format!("/api/v1beta/me/chats/{}", chat.id))
+        .with_bearer_token(&owner.token())
+        .await;
+    previous_owner_chat_response.assert_status_ok();
+    let previous_owner_chat: Value = previous_owner_chat_response.json();
+    assert_eq!(previous_owner_chat["can_edit"], false);
+
+    let authors: Vec<(Option<String>,)> = sqlx::query_as(
+        "SELECT raw_message->>'name' FROM messages \
+         WHERE chat_id = $1 AND raw_message->>'role' = 'user'",
+    )
+    .bind(chat.id)
+    .fetch_all(&pool)
+    .await
+    .expect("Failed to load the message authors");
+    assert_eq!(authors, vec![(Some(new_owner.id.to_string()),)]);
+
+    // Admins can transfer all chats of a user
+    let bulk_path = format!("/api/v1beta/admin/users/{}/transfer-chats", new_owner.id);
+    server
+        .post(&bulk_path)
+        .with_bearer_token(&new_owner.token())
+        .json(&json!({ "new_owner_user_id": admin.id.to_string() }))
+        .await
+        .assert_status(http::StatusCode::FORBIDDEN);
+    let bulk_response = server
+        .post(&bulk_path)
+        .with_bearer_token(&admin_token)
+        .json(&json!({ "new_owner_user_id": admin.id.to_string() }))
+        .await;
+    bulk_response.assert_status_ok();
+    let bulk: Value = bulk_response.json();
+    assert_eq!(bulk["dry_run"], false);
+    assert_eq!(bulk["transfers"].as_array().unwrap().len(), 1);
+    assert_eq!(bulk["transfers"][0]["chat_id"], chat.id.to_string());
+    server
+        .get(&format!("/api/v1beta/me/chats/{}", chat.id))
+        .with_bearer_token(&new_owner.token())
+        .await
+        .assert_status(http::StatusCode::NOT_FOUND);
+
+    let assistant_response = server
+        .post(&format!(
+            "/api/v1beta/admin/assistants/{}/transfer-ownership",
+            assistant.id
+        ))
+        .with_bearer_token(&admin_token)
+        .json(&json!({ "new_owner_user_id": new_owner.id.to_string() }))
+        .await;
+    assistant_response.assert_status_ok();
+    let assistant_transfer: Value = assistant_response.json();
+    assert_eq!(assistant_transfer["new_owner_user_id"], new_owner.id.to_string());
+    server
+        .get(&format!("/api/v1beta/assistants/{}", assistant.id))
+        .with_bearer_token(&new_owner.token())
+        .await
+        .assert_status_ok();
+
+    let (recorded_transfers,) =
+        sqlx::query_as::<_, (i64,)>("SELECT count(*) FROM ownership_transfers")
+            .fetch_one(&pool)
+            .await
+            .expect("Failed to count the recorded transfers");
+    assert_eq!(recorded_transfers, 3);
+}
@@ -0,0 +1,121 @@
+//! Tests for the propagation of policy invalidations between backend instances.
+
+use std::time::{Duration, Instant};
+
+use axum::http;
+use axum_test::TestServer;
+use erato::services::policy_invalidations::{
+    poll_policy_invalidations, start_policy_invalidation_listener,
+};
+use mocktail::MockSet;
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TestRequestAuthExt, create_chat, create_test_server,
+    setup_mock_llm_server_with_mocks,
+};
+
+/// Upper bound of the propagation delay with a connected listener
+const PROPAGATION_WINDOW: Duration = Duration::from_secs(10);
+
+async fn read_chat_status(server: &TestServer, chat_id: &str) -> http::StatusCode {
+    server
+        .get(&format!("/api/v1beta/me/chats/{chat_id}"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .status_code()
+}
+
+/// Build the policy data of the instance behind the server, as done by any earlier request.
+async fn warm_up(server: &TestServer) {
+    server
+        .get("/api/v1beta/me/recent_chats")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status_ok();
+}
+
+/// Test that a chat created on one instance can be read on another one shortly after.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Two app states share the database, and the second one runs the invalidation listener. After
+/// both built their policy data, a chat is created via the first one. Reading the chat via the
+/// second one succeeds within the propagation window, without a manual rebuild and long before
+/// the periodic rebuild.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_created_chat_is_readable_on_other_instance(pool: Pool<Postgres>) {
+    let (app_config, _llm_server) = setup_mock_llm_server_with_mocks(MockSet::new()).await;
+    let app_state_a = test_app_state(app_config.clone(), pool.clone()).await;
+    let app_state_b = test_app_state(app_config, pool).await;
+    assert!(start_policy_invalidation_listener(&app_state_b));
+    let server_a = create_test_server(app_state_a);
+    let server_b = create_test_server(app_state_b.clone());
+    warm_up(&server_a).await;
+    warm_up(&server_b).await;
+    let high_water_mark = app_state_b
+        .global_policy_engine
+        .invalidation_high_water_mark();
+
+    let chat_id = create_chat(&server_a, TEST_JWT_TOKEN).await;
+
+    let started_at = Instant::now();
+    loop {
+        let status = read_chat_status(&server_b, &chat_id).await;
+        if status == http::StatusCode::OK {
+            break;
+        }
+        assert!(
+            started_at.elapsed() < PROPAGATION_WINDOW,
+            "Expected the chat to be readable on the other instance, got {status}"
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(
+        app_state_b
+            .global_policy_engine
+            .invalidation_high_water_mark()
+            > high_water_mark
+    );
+}
+
+/// Test that invalidations whose notification was missed are picked up by polling the outbox.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Without a listener on the second instance, a chat created via the first one is not readable
+/// via the second one. After a poll of the outbox, it is.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_missed_invalidations_are_polled(pool: Pool<Postgres>) {
+    let (app_config, _llm_server) = setup_mock_llm_server_with_mocks(MockSet::new()).await;
+    let app_state_a = test_app_state(app_config.clone(), pool.clone()).await;
+    let app_state_b = test_app_state(app_config, pool).await;
+    let server_a = create_test_server(app_state_a);
+    let server_b = create_test_server(app_state_b.clone());
+    warm_up(&server_a).await;
+    warm_up(&server_b).await;
+
+    let chat_id = create_chat(&server_a, TEST_JWT_TOKEN).await;
+    assert_ne!(
+        read_chat_status(&server_b, &chat_id).await,
+        http::StatusCode::OK
+    );
+
+    assert!(
+        poll_policy_invalidations(&app_state_b.db, &app_state_b.global_policy_engine)
+            .await
+            .unwrap()
+    );
+    assert_eq!(
+        read_chat_status(&server_b, &chat_id).await,
+        http::StatusCode::OK
+    );
+}
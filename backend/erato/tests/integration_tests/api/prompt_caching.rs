@@ -0,0 +1,141 @@
+//! Integration tests for the prompt caching hints and the cached-token usage.
+
+use axum::http;
+use erato::config::PromptSourceSpecification;
+use erato::models::user::get_or_create_user;
+use mocktail::MockSet;
+use mocktail::body::BodyAction;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt,
+    build_openai_text_streaming_response, create_test_server, parse_sse_events,
+    setup_mock_llm_server_with_mocks,
+};
+
+const SYSTEM_PROMPT: &str = "You are the research assistant of ACME Corp.";
+
+/// A streamed answer, followed by a usage chunk that reports cached prompt tokens.
+fn streaming_response_with_cached_usage(
+    prompt_tokens: u64,
+    cached_tokens: u64,
+    completion_tokens: u64,
+) -> Vec<BodyAction> {
+    let mut actions = build_openai_text_streaming_response(&["From the cache."]);
+    let usage_chunk = json!({
+        "id": "chatcmpl-mock-123",
+        "object": "chat.completion.chunk",
+        "created": 1234567890,
+        "model": "gpt-3.5-turbo",
+        "choices": [],
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens,
+            "prompt_tokens_details": { "cached_tokens": cached_tokens }
+        }
+    });
+    // The usage chunk is sent right before `[DONE]`
+    actions.insert(
+        actions.len() - 1,
+        BodyAction::Bytes(format!("data: {usage_chunk}\n\n").into()),
+    );
+    actions
+}
+
+/// Verifies that the stable start of the prompt is marked as cacheable, and that cached prompt
+/// tokens are reported and priced with the cache price.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sse-streaming`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// The chat provider supports prompt caching and has a system prompt. The dry run marks the
+/// system prompt as cacheable, but not the user message. The chat provider reports 2M of the 3M
+/// prompt tokens as read from its cache, which are reported as `cache_read_tokens` in the usage
+/// of the message, and priced with `cost_cache_read_tokens_per_1m` in the budget.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_stable_prompt_prefix_is_cached(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        then.status(http::StatusCode::OK)
+            .headers([("Content-Type", "text/event-stream")])
+            .bytes_stream_with_delays(streaming_response_with_cached_usage(
+                3_000_000, 2_000_000, 0,
+            ));
+    });
+    let (mut app_config, _server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config.debug.allow_dry_run = true;
+    app_config.budget.enabled = true;
+    for provider in app_config
+        .chat_providers
+        .as_mut()
+        .expect("Expected chat providers in test config")
+        .providers
+        .values_mut()
+    {
+        provider.system_prompt = Some(PromptSourceSpecification::Static {
+            content: SYSTEM_PROMPT.to_string(),
+        });
+        provider.model_capabilities.supports_prompt_caching = true;
+        provider.model_capabilities.cost_input_tokens_per_1m = 1.0;
+        provider.model_capabilities.cost_cache_read_tokens_per_1m = Some(0.25);
+    }
+
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .unwrap();
+    let server = create_test_server(app_state);
+
+    let dry_run: Value = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "user_message": "What did we ship last quarter?",
+            "dry_run": true,
+        }))
+        .await
+        .json();
+    let messages = dry_run["messages"].as_array().unwrap();
+    let system_message = messages
+        .iter()
+        .find(|message| message["role"] == "system")
+        .expect("Expected the system prompt");
+    assert_eq!(system_message["content"], SYSTEM_PROMPT);
+    assert_eq!(system_message["cache_control"], json!({ "type": "ephemeral" }));
+    let user_message = messages.last().unwrap();
+    assert_eq!(user_message["role"], "user");
+    assert!(user_message.get("cache_control").is_none());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "What did we ship last quarter?" }))
+        .await;
+    response.assert_status_ok();
+    let completed = parse_sse_events(&response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .find(|event| event["message_type"] == "assistant_message_completed")
+        .expect("Expected a completed message");
+    let usage = &completed["message"]["usage"];
+    assert_eq!(usage["prompt_tokens"], 3_000_000);
+    assert_eq!(usage["cache_read_tokens"], 2_000_000);
+    assert!(usage.get("cache_write_tokens").is_none());
+
+    // 1M uncached prompt tokens at 1.0 and 2M cached prompt tokens at 0.25
+    let budget: Value = server
+        .get("/api/v1beta/me/budget")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .json();
+    assert_eq!(budget["current_spending"], 1.5);
+}
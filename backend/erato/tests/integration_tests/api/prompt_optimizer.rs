@@ -0,0 +1,217 @@
+//! Prompt optimizer API tests.
+
+use axum::Router;
+use axum::http;
+use axum_test::TestServer;
+use chrono::Utc;
+use erato::config::AppConfig;
+use erato::db::entity::{chats, messages};
+use erato::models::user::get_or_create_user;
+use erato::server::router::router;
+use sea_orm::prelude::Uuid;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter,
+};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    RequestBodyRecorder, TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt,
+    extract_chat_id, extract_full_text, parse_sse_events, recording_llm_mocks,
+    setup_mock_llm_server_with_mocks,
+};
+
+const CONTEXT_MARKER: &str = "Recent messages of that conversation";
+
+fn enable_prompt_optimizer(app_config: &mut AppConfig) {
+    app_config.prompt_optimizer.enabled = true;
+    app_config.prompt_optimizer.chat_provider_id = Some("mock-llm".to_string());
+}
+
+/// Request bodies sent to the LLM for the prompt optimizer, identified by the prompt.
+fn optimizer_request_bodies(recorder: &RequestBodyRecorder, prompt: &str) -> Vec<String> {
+    recorder
+        .bodies()
+        .into_iter()
+        .filter(|body| body.contains(prompt))
+        .collect()
+}
+
+/// Test streaming prompt optimization with the context of an existing chat.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `sse-streaming`
+///
+/// # Test Behavior
+/// Submits a message to create a chat, then streams an optimization of a follow-up
+/// prompt for that chat. Verifies the event sequence, that recent messages of the
+/// chat were passed to the LLM, and that no messages were persisted.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_prompt_optimizer_stream_with_chat_context(pool: Pool<Postgres>) {
+    let recorder = RequestBodyRecorder::new();
+    let (mut app_config, _server) =
+        setup_mock_llm_server_with_mocks(recording_llm_mocks(&recorder, &["Plan a", " trip."]))
+            .await;
+    enable_prompt_optimizer(&mut app_config);
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let submit_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "I want to visit Lisbon in May" }))
+        .await;
+    submit_response.assert_status_ok();
+    let chat_id = extract_chat_id(&parse_sse_events(&submit_response))
+        .expect("Expected a chat_created event");
+    let chat_uuid = Uuid::parse_str(&chat_id).unwrap();
+    let message_count_before = messages::Entity::find()
+        .filter(messages::Column::ChatId.eq(chat_uuid))
+        .count(&app_state.db)
+        .await
+        .unwrap();
+
+    let prompt = "make it a three day itinerary";
+    let response = server
+        .post("/api/v1beta/prompt-optimizer/stream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "prompt": prompt, "chat_id": chat_id }))
+        .await;
+    response.assert_status_ok();
+
+    let events = parse_sse_events(&response);
+    let event_types: Vec<&str> = events
+        .iter()
+        .map(|e| e.event_type.as_str())
+        .filter(|event_type| !event_type.is_empty())
+        .collect();
+    let (last_event_type, delta_event_types) = event_types.split_last().unwrap();
+    assert_eq!(*last_event_type, "completed");
+    assert!(!delta_event_types.is_empty());
+    assert!(delta_event_types.iter().all(|t| *t == "text_delta"));
+    assert_eq!(extract_full_text(&events), "Plan a trip.");
+    let completed: Value = serde_json::from_str(&events.last().unwrap().data).unwrap();
+    assert_eq!(completed["message_type"], "completed");
+    assert_eq!(completed["optimized_prompt"], "Plan a trip.");
+
+    let bodies = optimizer_request_bodies(&recorder, prompt);
+    assert_eq!(
+        bodies.len(),
+        1,
+        "Expected exactly one optimizer LLM request"
+    );
+    assert!(bodies[0].contains(CONTEXT_MARKER));
+    assert!(bodies[0].contains("User: I want to visit Lisbon in May"));
+    assert!(bodies[0].contains("Assistant: Plan a trip."));
+
+    let message_count_after = messages::Entity::find()
+        .filter(messages::Column::ChatId.eq(chat_uuid))
+        .count(&app_state.db)
+        .await
+        .unwrap();
+    assert_eq!(message_count_after, message_count_before);
+}
+
+/// Test prompt optimizer context access checks and optimization without context.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `sse-streaming`
+///
+/// # Test Behavior
+/// Verifies that a chat of another user cannot be used as context, that an unknown
+/// message is rejected, and that without a chat only the prompt is sent to the LLM.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_prompt_optimizer_stream_context_access(pool: Pool<Postgres>) {
+    let recorder = RequestBodyRecorder::new();
+    let (mut app_config, _server) =
+        setup_mock_llm_server_with_mocks(recording_llm_mocks(&recorder, &["Better prompt."])).await;
+    enable_prompt_optimizer(&mut app_config);
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+
+    // A chat owned by someone else
+    let other_chat = chats::ActiveModel {
+        owner_user_id: ActiveValue::Set(Uuid::new_v4().to_string()),
+        ..Default::default()
+    }
+    .insert(&app_state.db)
+    .await
+    .expect("Failed to insert chat");
+    let now = Utc::now().into();
+    messages::ActiveModel {
+        id: ActiveValue::Set(Uuid::new_v4()),
+        chat_id: ActiveValue::Set(other_chat.id),
+        raw_message: ActiveValue::Set(json!({
+            "role": "user",
+            "content": [{ "content_type": "text", "text": "Secret plans" }]
+        })),
+        created_at: ActiveValue::Set(now),
+        updated_at: ActiveValue::Set(now),
+        is_message_in_active_thread: ActiveValue::Set(true),
+        ..Default::default()
+    }
+    .insert(&app_state.db)
+    .await
+    .expect("Failed to insert message");
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    server
+        .post("/api/v1beta/prompt-optimizer/stream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "prompt": "summarize this", "chat_id": other_chat.id }))
+        .await
+        .assert_status(http::StatusCode::NOT_FOUND);
+    server
+        .post("/api/v1beta/prompt-optimizer/stream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "prompt": "summarize this", "previous_message_id": Uuid::new_v4() }))
+        .await
+        .assert_status(http::StatusCode::NOT_FOUND);
+    assert!(
+        recorder
+            .bodies()
+            .iter()
+            .all(|body| !body.contains("Secret plans"))
+    );
+
+    let prompt = "write a haiku about the sea";
+    let response = server
+        .post("/api/v1beta/prompt-optimizer/stream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "prompt": prompt }))
+        .await;
+    response.assert_status_ok();
+    let events = parse_sse_events(&response);
+    assert_eq!(
+        events.last().map(|e| e.event_type.as_str()),
+        Some("completed")
+    );
+    assert_eq!(extract_full_text(&events), "Better prompt.");
+
+    let bodies = optimizer_request_bodies(&recorder, prompt);
+    assert_eq!(bodies.len(), 1);
+    assert!(!bodies[0].contains(CONTEXT_MARKER));
+}
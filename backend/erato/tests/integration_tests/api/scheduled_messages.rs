@@ -0,0 +1,203 @@
+//! Scheduled message API tests.
+
+use axum::http;
+use axum_test::TestServer;
+use chrono::{TimeDelta, Utc};
+use erato::db::entity::prelude::Messages;
+use erato::services::scheduled_messages::run_due_scheduled_messages;
+use mocktail::MockSet;
+use sea_orm::EntityTrait;
+use sea_orm::prelude::Uuid;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TestRequestAuthExt, build_openai_text_streaming_response, create_test_server,
+    mock_llm_sse_response, setup_mock_llm_server_with_mocks,
+};
+
+async fn schedule_message(server: &TestServer, user_message: &str, run_in: TimeDelta) -> Value {
+    let response = server
+        .post("/api/v1beta/me/messages/schedule")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "user_message": user_message,
+            "run_at": Utc::now() + run_in,
+        }))
+        .await;
+    response.assert_status_ok();
+    response.json()
+}
+
+async fn list_scheduled_messages(server: &TestServer) -> Vec<Value> {
+    let response = server
+        .get("/api/v1beta/me/scheduled-messages")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    body["scheduled_messages"]
+        .as_array()
+        .expect("Expected scheduled_messages array")
+        .clone()
+}
+
+/// Test scheduling, listing, cancelling and submitting scheduled messages.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// Rejects `run_at` values in the past or beyond the configured horizon, schedules two
+/// messages and cancels one of them. Running the scheduler with a clock one hour ahead submits
+/// the remaining message, which is then listed as succeeded with its chat and answer, and can no
+/// longer be cancelled.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_scheduled_message_lifecycle(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        mock_llm_sse_response(
+            then,
+            build_openai_text_streaming_response(&["Good", " morning!"]),
+        );
+    });
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config.scheduled_messages.max_horizon_secs = 24 * 60 * 60;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    for run_in in [TimeDelta::minutes(-1), TimeDelta::days(2)] {
+        server
+            .post("/api/v1beta/me/messages/schedule")
+            .with_bearer_token(TEST_JWT_TOKEN)
+            .json(&json!({ "user_message": "Too late", "run_at": Utc::now() + run_in }))
+            .await
+            .assert_status(http::StatusCode::BAD_REQUEST);
+    }
+
+    let scheduled = schedule_message(&server, "Good morning?", TimeDelta::minutes(30)).await;
+    assert_eq!(scheduled["status"], "pending");
+    assert_eq!(scheduled["user_message"], "Good morning?");
+    assert_eq!(scheduled["attempts"], 0);
+    let cancelled = schedule_message(&server, "Never mind", TimeDelta::minutes(45)).await;
+    assert_eq!(list_scheduled_messages(&server).await.len(), 2);
+
+    server
+        .delete(&format!(
+            "/api/v1beta/me/scheduled-messages/{}",
+            cancelled["id"].as_str().unwrap()
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::NO_CONTENT);
+
+    // Nothing is due yet
+    let attempted = run_due_scheduled_messages(&app_state, Utc::now())
+        .await
+        .expect("Failed to run due scheduled messages");
+    assert_eq!(attempted, 0);
+
+    let attempted = run_due_scheduled_messages(&app_state, Utc::now() + TimeDelta::hours(1))
+        .await
+        .expect("Failed to run due scheduled messages");
+    assert_eq!(attempted, 1);
+
+    let scheduled_messages = list_scheduled_messages(&server).await;
+    assert_eq!(scheduled_messages.len(), 1);
+    let submitted = &scheduled_messages[0];
+    assert_eq!(submitted["id"], scheduled["id"]);
+    assert_eq!(submitted["status"], "succeeded");
+    assert_eq!(submitted["attempts"], 1);
+    assert!(submitted["last_error"].is_null());
+    assert!(submitted["chat_id"].is_string());
+
+    let assistant_message_id =
+        Uuid::parse_str(submitted["assistant_message_id"].as_str().unwrap()).unwrap();
+    let assistant_message = Messages::find_by_id(assistant_message_id)
+        .one(&app_state.db)
+        .await
+        .unwrap()
+        .expect("Expected the assistant message to exist");
+    assert_eq!(
+        assistant_message.chat_id.to_string(),
+        submitted["chat_id"].as_str().unwrap()
+    );
+    assert_eq!(
+        assistant_message.raw_message["content"][0]["text"],
+        "Good morning!"
+    );
+
+    server
+        .delete(&format!(
+            "/api/v1beta/me/scheduled-messages/{}",
+            submitted["id"].as_str().unwrap()
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await
+        .assert_status(http::StatusCode::CONFLICT);
+}
+
+/// Test that failed attempts are retried until `max_attempts` is reached.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// A mock LLM that rejects every completion request makes the first attempt fail, after which
+/// the message is pending again with the error and a later `run_at`. The second attempt fails as
+/// well, which marks the message as failed.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_scheduled_message_retries_failed_attempts(pool: Pool<Postgres>) {
+    let mut mocks = MockSet::new();
+    mocks.mock(|when, then| {
+        when.post().path("/v1/chat/completions");
+        then.status(http::StatusCode::INTERNAL_SERVER_ERROR)
+            .json(json!({
+                "error": {
+                    "message": "mock provider failure",
+                    "type": "server_error"
+                }
+            }));
+    });
+    let (mut app_config, _llm_server) = setup_mock_llm_server_with_mocks(mocks).await;
+    app_config.scheduled_messages.max_attempts = 2;
+    app_config.scheduled_messages.retry_delay_secs = 600;
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let scheduled = schedule_message(&server, "Will this work?", TimeDelta::minutes(5)).await;
+
+    let first_run = Utc::now() + TimeDelta::minutes(10);
+    run_due_scheduled_messages(&app_state, first_run)
+        .await
+        .expect("Failed to run due scheduled messages");
+    let retried = &list_scheduled_messages(&server).await[0];
+    assert_eq!(retried["id"], scheduled["id"]);
+    assert_eq!(retried["status"], "pending");
+    assert_eq!(retried["attempts"], 1);
+    assert!(retried["last_error"].is_string());
+    let retry_at: chrono::DateTime<Utc> =
+        serde_json::from_value(retried["run_at"].clone()).unwrap();
+    assert!(retry_at > first_run);
+
+    // Not due again before the retry delay has passed
+    let attempted = run_due_scheduled_messages(&app_state, first_run)
+        .await
+        .expect("Failed to run due scheduled messages");
+    assert_eq!(attempted, 0);
+
+    run_due_scheduled_messages(&app_state, retry_at)
+        .await
+        .expect("Failed to run due scheduled messages");
+    let failed = &list_scheduled_messages(&server).await[0];
+    assert_eq!(failed["status"], "failed");
+    assert_eq!(failed["attempts"], 2);
+    assert!(failed["last_error"].is_string());
+}
@@ -406,3 +406,134 @@ async fn test_sharepoint_full_flow(pool: Pool<Postgres>) {
 
     println!("Full Sharepoint flow completed successfully!");
 }
+
+/// Test the download URL of an assistant file linked from Sharepoint.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sharepoint-integration`
+/// - `requires-external-token`
+///
+/// # Test Behavior
+/// Links the first file at the root of the first drive, and creates an assistant with it. With
+/// the MS Graph access token, the assistant file gets a `direct` short-lived download URL that
+/// can be fetched without further authentication. Without the token, it gets the API route of
+/// the file with the `requires_auth_proxy` kind.
+// #[ignore = "Requires SHAREPOINT_TEST_ACCESS_TOKEN environment variable with valid MS Graph token"]
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_sharepoint_assistant_file_url(pool: Pool<Postgres>) {
+    let ms_graph_token = match get_ms_graph_access_token() {
+        Some(token) => token,
+        None => {
+            println!("Skipping test: SHAREPOINT_TEST_ACCESS_TOKEN not set");
+            return;
+        }
+    };
+
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state_with_sharepoint(app_config, pool).await;
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let drives_response = server
+        .get("/api/v1beta/integrations/sharepoint/all-drives")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .with_ms_graph_token(&ms_graph_token)
+        .await;
+    drives_response.assert_status_ok();
+    let drives_json: Value = drives_response.json();
+    let drive_id = drives_json["drives"][0]["id"]
+        .as_str()
+        .expect("Expected at least one drive to be accessible")
+        .to_string();
+
+    let root_response = server
+        .get(&format!(
+            "/api/v1beta/integrations/sharepoint/drives/{}",
+            drive_id
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .with_ms_graph_token(&ms_graph_token)
+        .await;
+    root_response.assert_status_ok();
+    let root_json: Value = root_response.json();
+    let Some(item_id) = root_json["items"].as_array().and_then(|items| {
+        items
+            .iter()
+            .find(|item| !item["is_folder"].as_bool().unwrap_or(true))
+            .and_then(|item| item["id"].as_str())
+            .map(str::to_string)
+    }) else {
+        println!("Skipping test: no file at the root of drive {}", drive_id);
+        return;
+    };
+
+    let link_response = server
+        .post("/api/v1beta/me/files/link")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .with_ms_graph_token(&ms_graph_token)
+        .json(&json!({
+            "source": "sharepoint",
+            "provider_metadata": {
+                "drive_id": drive_id,
+                "item_id": item_id
+            }
+        }))
+        .await;
+    link_response.assert_status_ok();
+    let link_json: Value = link_response.json();
+    let file_id = link_json["files"][0]["id"]
+        .as_str()
+        .expect("Expected id in file response")
+        .to_string();
+
+    let assistant_response = server
+        .post("/api/v1beta/assistants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .with_ms_graph_token(&ms_graph_token)
+        .json(&json!({
+            "name": "Sharepoint assistant",
+            "prompt": "You are a helpful test assistant.",
+            "file_ids": [file_id]
+        }))
+        .await;
+    assistant_response.assert_status(StatusCode::CREATED);
+    let assistant_json: Value = assistant_response.json();
+    let assistant_path = format!(
+        "/api/v1beta/assistants/{}",
+        assistant_json["id"].as_str().unwrap()
+    );
+
+    // With the access token, the file gets a short-lived MS Graph download URL
+    let assistant_response = server
+        .get(&assistant_path)
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .with_ms_graph_token(&ms_graph_token)
+        .await;
+    assistant_response.assert_status_ok();
+    let file = assistant_response.json::<Value>()["files"][0].clone();
+    assert_eq!(file["url_kind"], json!("direct"));
+    let download_response = reqwest::get(file["download_url"].as_str().unwrap())
+        .await
+        .expect("Failed to fetch the MS Graph download URL");
+    assert!(download_response.status().is_success());
+
+    // Without the access token, the file falls back to its API route
+    let assistant_response = server
+        .get(&assistant_path)
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assistant_response.assert_status_ok();
+    let file = assistant_response.json::<Value>()["files"][0].clone();
+    assert_eq!(file["url_kind"], json!("requires_auth_proxy"));
+    assert_eq!(
+        file["download_url"],
+        json!(format!("/api/v1beta/files/{}", file_id))
+    );
+}
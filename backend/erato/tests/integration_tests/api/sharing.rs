@@ -3,13 +3,17 @@
 use axum::Router;
 use axum::http;
 use axum_test::TestServer;
+use erato::db::entity::share_grants;
 use erato::policy::engine::PolicyEngine;
 use erato::server::router::router;
+use sea_orm::prelude::Uuid;
+use sea_orm::{ActiveModelTrait, ActiveValue};
 use serde_json::{Value, json};
 use sqlx::Pool;
 use sqlx::postgres::Postgres;
 
 use crate::test_app_state;
+use crate::test_utils::fixtures::{FixtureAssistant, FixtureUser};
 use crate::test_utils::{
     JwtTokenBuilder, TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt,
     extract_chat_id, has_event_type, hermetic_app_config, parse_sse_events, setup_mock_llm_server,
@@ -188,49 +192,22 @@ async fn test_full_assistant_sharing_flow(pool: Pool<Postgres>) {
     // Create app state with the database connection
     let app_state = test_app_state(hermetic_app_config(None, None), pool).await;
 
-    // Create User A (owner)
-    let user_a_subject = "user-a-subject";
-    let user_a = erato::models::user::get_or_create_user(
-        &app_state.db,
-        TEST_USER_ISSUER,
-        user_a_subject,
-        None,
-    )
-    .await
-    .expect("Failed to create user A");
-
-    // Create User B (viewer)
-    let user_b_subject = TEST_USER_SUBJECT; // Will use default JWT token
-    let user_b = erato::models::user::get_or_create_user(
-        &app_state.db,
-        TEST_USER_ISSUER,
-        user_b_subject,
-        None,
-    )
-    .await
-    .expect("Failed to create user B");
+    // Create User A (owner) and User B (viewer), who uses the default JWT token
+    let user_a = FixtureUser::new("user-a-subject").create(&app_state).await;
+    let user_b = FixtureUser::test_user().create(&app_state).await;
 
     // Step 1: User A creates an assistant with a file
-    let assistant = erato::models::assistant::create_assistant(
-        &app_state.db,
-        &PolicyEngine::new(),
-        &erato::policy::types::Subject::User(user_a.id.to_string()),
-        "Shared Assistant".to_string(),
-        Some("This will be shared".to_string()),
-        "You are a shared assistant".to_string(),
-        None,
-        None,
-        None,
-        false,
-    )
-    .await
-    .expect("Failed to create assistant");
+    let assistant = FixtureAssistant::new(&user_a, "Shared Assistant")
+        .with_description("This will be shared")
+        .with_prompt("You are a shared assistant")
+        .create(&app_state)
+        .await;
 
     // Create a file and attach it to the assistant
     let file = erato::models::assistant::create_standalone_file_upload(
         &app_state.db,
         &PolicyEngine::new(),
-        &erato::policy::types::Subject::User(user_a.id.to_string()),
+        &user_a.subject(),
         "test_file.txt".to_string(),
         "seaweedfs".to_string(),
         "test_path.txt".to_string(),
@@ -241,7 +218,7 @@ async fn test_full_assistant_sharing_flow(pool: Pool<Postgres>) {
     erato::models::assistant::add_file_to_assistant(
         &app_state.db,
         &PolicyEngine::new(),
-        &erato::policy::types::Subject::User(user_a.id.to_string()),
+        &user_a.subject(),
         assistant.id,
         file.id,
     )
@@ -261,7 +238,7 @@ async fn test_full_assistant_sharing_flow(pool: Pool<Postgres>) {
     let share_grant = erato::models::share_grant::create_share_grant(
         &app_state.db,
         &PolicyEngine::new(),
-        &erato::policy::types::Subject::User(user_a.id.to_string()),
+        &user_a.subject(),
         "assistant".to_string(),
         assistant.id.to_string(),
         "user".to_string(),
@@ -382,6 +359,11 @@ async fn test_archived_shared_assistant_hidden_but_existing_chat_accessible(pool
         None,
         None,
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create assistant");
@@ -523,6 +505,11 @@ async fn test_viewer_cannot_update_assistant(pool: Pool<Postgres>) {
         None,
         None,
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create assistant");
@@ -607,6 +594,11 @@ async fn test_list_share_grants(pool: Pool<Postgres>) {
         None,
         None,
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create assistant");
@@ -697,6 +689,11 @@ async fn test_delete_share_grant(pool: Pool<Postgres>) {
         None,
         None,
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create assistant");
@@ -813,6 +810,11 @@ async fn test_share_assistant_with_organization_user_id(pool: Pool<Postgres>) {
         None,
         None,
         false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
     )
     .await
     .expect("Failed to create assistant");
@@ -911,6 +913,7 @@ async fn test_share_assistant_with_organization_user_id(pool: Pool<Postgres>) {
         &PolicyEngine::new(),
         &erato::policy::types::Subject::UserWithOrganizationInfo {
             id: user_b.id.to_string(),
+            organization_id: None,
             organization_user_id: Some(user_b_org_id.to_string()),
             organization_group_ids: vec![],
         },
@@ -994,3 +997,709 @@ async fn test_share_assistant_with_organization_user_id(pool: Pool<Postgres>) {
         "Should receive chat events from the LLM"
     );
 }
+
+/// Submits a first message as the given user and returns the chat ID and the ID
+/// of the generated assistant message.
+async fn create_chat_with_message(server: &TestServer, token: &str) -> (String, String) {
+    let submit_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(token)
+        .add_header(http::header::CONTENT_TYPE, "application/json")
+        .json(&json!({
+            "previous_message_id": null,
+            "user_message": "Please respond with a short hello",
+        }))
+        .await;
+    submit_response.assert_status_ok();
+
+    let events = parse_sse_events(&submit_response);
+    let chat_id = extract_chat_id(&events).expect("Expected chat_created event");
+    let assistant_message_id = events
+        .iter()
+        .find_map(|event| {
+            let json: Value = serde_json::from_str(&event.data).ok()?;
+            if json["message_type"] == "assistant_message_completed" {
+                return json["message_id"].as_str().map(|s| s.to_string());
+            }
+            None
+        })
+        .expect("Expected assistant_message_completed event");
+    (chat_id, assistant_message_id)
+}
+
+/// Test that a user with an `edit` share grant on a chat can read it, continue
+/// the conversation and archive it.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_chat_edit_share_grant_allows_grantee_to_edit(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+
+    let owner_subject = "chat-edit-grant-owner";
+    let grantee_subject = "chat-edit-grant-grantee";
+    erato::models::user::get_or_create_user(&app_state.db, TEST_USER_ISSUER, owner_subject, None)
+        .await
+        .expect("Failed to create owner user");
+    let grantee = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        grantee_subject,
+        None,
+    )
+    .await
+    .expect("Failed to create grantee user");
+
+    let owner_token = JwtTokenBuilder::new()
+        .subject(owner_subject)
+        .email("owner@example.com")
+        .name("owner")
+        .build();
+    let grantee_token = JwtTokenBuilder::new()
+        .subject(grantee_subject)
+        .email("grantee@example.com")
+        .name("grantee")
+        .build();
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let (chat_id, assistant_message_id) = create_chat_with_message(&server, &owner_token).await;
+
+    let create_response = server
+        .post("/api/v1beta/share-grants")
+        .with_bearer_token(&owner_token)
+        .json(&json!({
+            "resource_type": "chat",
+            "resource_id": chat_id,
+            "subject_type": "user",
+            "subject_id_type": "id",
+            "subject_id": grantee.id.to_string(),
+            "role": "viewer",
+            "permission": "edit",
+        }))
+        .await;
+    assert_eq!(create_response.status_code(), http::StatusCode::CREATED);
+    let create_json: Value = create_response.json();
+    assert_eq!(create_json["share_grant"]["permission"], "edit");
+    assert!(create_json["share_grant"].get("expires_at").is_none());
+
+    // The grantee can read the chat
+    let messages_response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(&grantee_token)
+        .await;
+    messages_response.assert_status_ok();
+
+    // The grantee can continue the conversation
+    let submit_response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(&grantee_token)
+        .add_header(http::header::CONTENT_TYPE, "application/json")
+        .json(&json!({
+            "previous_message_id": assistant_message_id,
+            "user_message": "Please respond with another short hello",
+        }))
+        .await;
+    submit_response.assert_status_ok();
+    let events = parse_sse_events(&submit_response);
+    assert!(has_event_type(&events, "assistant_message_completed"));
+
+    // The grantee can archive the chat
+    let archive_response = server
+        .post(&format!("/api/v1beta/chats/{chat_id}/archive"))
+        .with_bearer_token(&grantee_token)
+        .json(&json!({}))
+        .await;
+    archive_response.assert_status_ok();
+}
+
+/// Test that creating a share grant validates the permission and the expiry.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_create_share_grant_validates_permission_and_expiry(pool: Pool<Postgres>) {
+    let app_state = test_app_state(hermetic_app_config(None, None), pool).await;
+
+    let owner = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        TEST_USER_SUBJECT,
+        None,
+    )
+    .await
+    .expect("Failed to create owner user");
+    let grantee = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        "share-grant-validation-grantee",
+        None,
+    )
+    .await
+    .expect("Failed to create grantee user");
+
+    let assistant = erato::models::assistant::create_assistant(
+        &app_state.db,
+        &PolicyEngine::new(),
+        &erato::policy::types::Subject::User(owner.id.to_string()),
+        "Validated Assistant".to_string(),
+        None,
+        "Prompt".to_string(),
+        None,
+        None,
+        None,
+        false,
+        None,
+        None,
+        None,
+        Vec::new(),
+        erato::models::assistant::VISIBILITY_PRIVATE,
+    )
+    .await
+    .expect("Failed to create assistant");
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let request = |permission: &str, expires_at: chrono::DateTime<chrono::Utc>| {
+        json!({
+            "resource_type": "assistant",
+            "resource_id": assistant.id.to_string(),
+            "subject_type": "user",
+            "subject_id_type": "id",
+            "subject_id": grantee.id.to_string(),
+            "role": "viewer",
+            "permission": permission,
+            "expires_at": expires_at.to_rfc3339(),
+        })
+    };
+
+    let past_expiry_response = server
+        .post("/api/v1beta/share-grants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&request(
+            "edit",
+            chrono::Utc::now() - chrono::Duration::hours(1),
+        ))
+        .await;
+    assert_eq!(
+        past_expiry_response.status_code(),
+        http::StatusCode::BAD_REQUEST
+    );
+
+    let invalid_permission_response = server
+        .post("/api/v1beta/share-grants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&request(
+            "admin",
+            chrono::Utc::now() + chrono::Duration::days(7),
+        ))
+        .await;
+    assert_eq!(
+        invalid_permission_response.status_code(),
+        http::StatusCode::UNPROCESSABLE_ENTITY
+    );
+
+    let expires_at = chrono::Utc::now() + chrono::Duration::days(7);
+    let valid_response = server
+        .post("/api/v1beta/share-grants")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&request("edit", expires_at))
+        .await;
+    assert_eq!(valid_response.status_code(), http::StatusCode::CREATED);
+    let valid_json: Value = valid_response.json();
+    assert_eq!(valid_json["share_grant"]["permission"], "edit");
+    let returned_expiry = chrono::DateTime::parse_from_rfc3339(
+        valid_json["share_grant"]["expires_at"]
+            .as_str()
+            .expect("expires_at should be set"),
+    )
+    .expect("expires_at should be a valid timestamp");
+    assert_eq!(returned_expiry.timestamp(), expires_at.timestamp());
+}
+
+/// Test that an expired share grant no longer gives access to the shared chat.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// The grantee can read the chat while the grant is valid. Once the expiry of the grant is in the
+/// past, reading the chat is rejected with 404, while the owner still sees the grant. That the
+/// policy checks the expiry of grants that were still valid when the policy data was loaded is
+/// covered by the policy tests.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_expired_share_grant_revokes_access(pool: Pool<Postgres>) {
+    let (app_config, _server) = setup_mock_llm_server(None).await;
+    let app_state = test_app_state(app_config, pool).await;
+
+    let owner_subject = "chat-expiry-owner";
+    let grantee_subject = "chat-expiry-grantee";
+    erato::models::user::get_or_create_user(&app_state.db, TEST_USER_ISSUER, owner_subject, None)
+        .await
+        .expect("Failed to create owner user");
+    let grantee = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        grantee_subject,
+        None,
+    )
+    .await
+    .expect("Failed to create grantee user");
+
+    let owner_token = JwtTokenBuilder::new()
+        .subject(owner_subject)
+        .email("owner@example.com")
+        .name("owner")
+        .build();
+    let grantee_token = JwtTokenBuilder::new()
+        .subject(grantee_subject)
+        .email("grantee@example.com")
+        .name("grantee")
+        .build();
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let (chat_id, _) = create_chat_with_message(&server, &owner_token).await;
+
+    let expires_at = chrono::Utc::now() + chrono::Duration::days(1);
+    let create_response = server
+        .post("/api/v1beta/share-grants")
+        .with_bearer_token(&owner_token)
+        .json(&json!({
+            "resource_type": "chat",
+            "resource_id": chat_id,
+            "subject_type": "user",
+            "subject_id_type": "id",
+            "subject_id": grantee.id.to_string(),
+            "role": "viewer",
+            "expires_at": expires_at.to_rfc3339(),
+        }))
+        .await;
+    assert_eq!(create_response.status_code(), http::StatusCode::CREATED);
+    let create_json: Value = create_response.json();
+    assert_eq!(create_json["share_grant"]["permission"], "read");
+    let grant_id = Uuid::parse_str(create_json["share_grant"]["id"].as_str().unwrap()).unwrap();
+
+    let before_expiry_response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(&grantee_token)
+        .await;
+    before_expiry_response.assert_status_ok();
+
+    // Move the expiry of the grant into the past instead of waiting for it
+    let expired_at = chrono::Utc::now() - chrono::Duration::minutes(1);
+    share_grants::ActiveModel {
+        id: ActiveValue::Unchanged(grant_id),
+        expires_at: ActiveValue::Set(Some(expired_at.into())),
+        ..Default::default()
+    }
+    .update(&app_state.db)
+    .await
+    .expect("Failed to expire the share grant");
+    app_state.global_policy_engine.invalidate_data().await;
+
+    let after_expiry_response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(&grantee_token)
+        .await;
+    assert_eq!(
+        after_expiry_response.status_code(),
+        http::StatusCode::NOT_FOUND
+    );
+
+    // Expired grants are still listed for the owner, so they can be cleaned up
+    let list_response = server
+        .get(&format!(
+            "/api/v1beta/share-grants?resource_type=chat&resource_id={chat_id}"
+        ))
+        .with_bearer_token(&owner_token)
+        .await;
+    list_response.assert_status_ok();
+    let list_json: Value = list_response.json();
+    assert_eq!(list_json["grants"].as_array().unwrap().len(), 1);
+}
+
+/// Test that deleting and re-sharing a chat stays restricted to its owner, even
+/// for users with an `edit` share grant.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_edit_share_grant_keeps_owner_only_operations_restricted(pool: Pool<Postgres>) {
+    let (mut app_config, _server) = setup_mock_llm_server(None).await;
+    app_config.chat_sharing.enabled = true;
+    let app_state = test_app_state(app_config, pool).await;
+
+    let owner_subject = "chat-restricted-owner";
+    let grantee_subject = "chat-restricted-grantee";
+    erato::models::user::get_or_create_user(&app_state.db, TEST_USER_ISSUER, owner_subject, None)
+        .await
+        .expect("Failed to create owner user");
+    let grantee = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        grantee_subject,
+        None,
+    )
+    .await
+    .expect("Failed to create grantee user");
+    let third_user = erato::models::user::get_or_create_user(
+        &app_state.db,
+        TEST_USER_ISSUER,
+        "chat-restricted-third-user",
+        None,
+    )
+    .await
+    .expect("Failed to create third user");
+
+    let owner_token = JwtTokenBuilder::new()
+        .subject(owner_subject)
+        .email("owner@example.com")
+        .name("owner")
+        .build();
+    let grantee_token = JwtTokenBuilder::new()
+        .subject(grantee_subject)
+        .email("grantee@example.com")
+        .name("grantee")
+        .build();
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let (chat_id, _) = create_chat_with_message(&server, &owner_token).await;
+
+    let create_response = server
+        .post("/api/v1beta/share-grants")
+        .with_bearer_token(&owner_token)
+        .json(&json!({
+            "resource_type": "chat",
+            "resource_id": chat_id,
+            "subject_type": "user",
+            "subject_id_type": "id",
+            "subject_id": grantee.id.to_string(),
+            "role": "viewer",
+            "permission": "edit",
+        }))
+        .await;
+    assert_eq!(create_response.status_code(), http::StatusCode::CREATED);
+    let create_json: Value = create_response.json();
+    let grant_id = create_json["share_grant"]["id"]
+        .as_str()
+        .expect("Missing share grant id")
+        .to_string();
+
+    // The grantee can't share the chat further
+    let reshare_response = server
+        .post("/api/v1beta/share-grants")
+        .with_bearer_token(&grantee_token)
+        .json(&json!({
+            "resource_type": "chat",
+            "resource_id": chat_id,
+            "subject_type": "user",
+            "subject_id_type": "id",
+            "subject_id": third_user.id.to_string(),
+            "role": "viewer",
+        }))
+        .await;
+    assert_eq!(reshare_response.status_code(), http::StatusCode::FORBIDDEN);
+
+    let share_link_response = server
+        .put("/api/v1beta/share-links")
+        .with_bearer_token(&grantee_token)
+        .json(&json!({
+            "resource_type": "chat",
+            "resource_id": chat_id,
+            "enabled": true,
+        }))
+        .await;
+    assert_eq!(
+        share_link_response.status_code(),
+        http::StatusCode::FORBIDDEN
+    );
+
+    // The grantee can't revoke share grants
+    let delete_response = server
+        .delete(&format!("/api/v1beta/share-grants/{grant_id}"))
+        .with_bearer_token(&grantee_token)
+        .await;
+    assert_eq!(delete_response.status_code(), http::StatusCode::FORBIDDEN);
+
+    // The grant is still in place for the owner
+    let list_response = server
+        .get(&format!(
+            "/api/v1beta/share-grants?resource_type=chat&resource_id={chat_id}"
+        ))
+        .with_bearer_token(&owner_token)
+        .await;
+    list_response.assert_status_ok();
+    let list_json: Value = list_response.json();
+    assert_eq!(list_json["grants"][0]["id"], grant_id);
+}
+
+/// Creates an assistant owned by the test user, together with the users with the given subjects.
+/// Returns the assistant ID and the IDs of the created users.
+async fn create_assistant_and_users(
+    app_state: &erato::state::AppState,
+    user_subjects: &[&str],
+) -> (String, Vec<String>) {
+    let owner = FixtureUser::test_user().create(app_state).await;
+
+    let mut user_ids = Vec::new();
+    for user_subject in user_subjects {
+        let user = FixtureUser::new(*user_subject).create(app_state).await;
+        user_ids.push(user.id.to_string());
+    }
+
+    let assistant = FixtureAssistant::new(&owner, "Team Assistant")
+        .with_prompt("Test prompt")
+        .create(app_state)
+        .await;
+
+    (assistant.id.to_string(), user_ids)
+}
+
+fn user_grantee(user_id: &str) -> Value {
+    json!({ "subject_type": "user", "subject_id_type": "id", "subject_id": user_id })
+}
+
+fn result_statuses(response: &Value) -> Vec<(String, String)> {
+    response["results"]
+        .as_array()
+        .expect("Should have results array")
+        .iter()
+        .map(|result| {
+            (
+                result["subject_id"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                result["status"].as_str().unwrap_or_default().to_string(),
+            )
+        })
+        .collect()
+}
+
+async fn list_grant_subject_ids(server: &TestServer, assistant_id: &str) -> Vec<String> {
+    let response = server
+        .get(&format!(
+            "/api/v1beta/share-grants?resource_type=assistant&resource_id={assistant_id}"
+        ))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let mut subject_ids: Vec<String> = response.json::<Value>()["grants"]
+        .as_array()
+        .expect("Should have grants array")
+        .iter()
+        .map(|grant| grant["subject_id"].as_str().unwrap().to_string())
+        .collect();
+    subject_ids.sort();
+    subject_ids
+}
+
+/// Test sharing an assistant with multiple users at once
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Shares an assistant with two users, one of them listed twice, and an invalid grantee in one
+/// request. The users get a share grant each, the duplicate is reported as already existing and
+/// the invalid grantee is rejected without failing the request. Running the same request again
+/// creates no further share grants.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_bulk_create_share_grants(pool: Pool<Postgres>) {
+    let app_state = test_app_state(hermetic_app_config(None, None), pool).await;
+    let (assistant_id, user_ids) =
+        create_assistant_and_users(&app_state, &["user-b-bulk-test", "user-c-bulk-test"]).await;
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let request = json!({
+        "resource_type": "assistant",
+        "resource_id": assistant_id,
+        "grantees": [
+            user_grantee(&user_ids[0]),
+            user_grantee(&user_ids[1]),
+            user_grantee(&user_ids[1]),
+            { "subject_type": "team", "subject_id_type": "id", "subject_id": "team-1" }
+        ],
+        "role": "viewer"
+    });
+    let response = server
+        .post("/api/v1beta/share-grants/bulk")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&request)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let body: Value = response.json();
+    assert_eq!(
+        result_statuses(&body),
+        vec![
+            (user_ids[0].clone(), "created".to_string()),
+            (user_ids[1].clone(), "created".to_string()),
+            (user_ids[1].clone(), "already_exists".to_string()),
+            ("team-1".to_string(), "invalid".to_string()),
+        ]
+    );
+    assert!(body["results"][3]["error"].is_string());
+    assert!(body["results"][3].get("share_grant").is_none());
+    assert_eq!(
+        body["results"][2]["share_grant"]["id"],
+        body["results"][1]["share_grant"]["id"]
+    );
+
+    let mut expected_subject_ids = user_ids.clone();
+    expected_subject_ids.sort();
+    assert_eq!(
+        list_grant_subject_ids(&server, &assistant_id).await,
+        expected_subject_ids
+    );
+
+    // Running the same request again doesn't create any share grants
+    let rerun_response = server
+        .post("/api/v1beta/share-grants/bulk")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&request)
+        .await;
+    assert_eq!(rerun_response.status_code(), http::StatusCode::OK);
+    let rerun_statuses = result_statuses(&rerun_response.json::<Value>());
+    assert_eq!(rerun_statuses[0].1, "already_exists");
+    assert_eq!(rerun_statuses[1].1, "already_exists");
+    assert_eq!(rerun_statuses[2].1, "already_exists");
+    assert_eq!(
+        list_grant_subject_ids(&server, &assistant_id).await,
+        expected_subject_ids
+    );
+
+    // Too many grantees are rejected as a whole
+    let grantees: Vec<Value> = (0..201)
+        .map(|index| user_grantee(&format!("user-{index}")))
+        .collect();
+    let too_many_response = server
+        .post("/api/v1beta/share-grants/bulk")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "resource_type": "assistant",
+            "resource_id": assistant_id,
+            "grantees": grantees,
+            "role": "viewer"
+        }))
+        .await;
+    assert_eq!(
+        too_many_response.status_code(),
+        http::StatusCode::BAD_REQUEST
+    );
+}
+
+/// Test setting the full set of grantees of an assistant
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Shares an assistant with users B and C, then syncs its grantees to B and D. B's share grant
+/// is kept, D gets a new one and C's share grant is removed. Only the owner can sync the
+/// grantees.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_sync_share_grants_removes_member(pool: Pool<Postgres>) {
+    let app_state = test_app_state(hermetic_app_config(None, None), pool).await;
+    let (assistant_id, user_ids) = create_assistant_and_users(
+        &app_state,
+        &["user-b-sync-test", "user-c-sync-test", "user-d-sync-test"],
+    )
+    .await;
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    server
+        .post("/api/v1beta/share-grants/bulk")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "resource_type": "assistant",
+            "resource_id": assistant_id,
+            "grantees": [user_grantee(&user_ids[0]), user_grantee(&user_ids[1])],
+            "role": "viewer"
+        }))
+        .await
+        .assert_status_ok();
+
+    let response = server
+        .put("/api/v1beta/share-grants/sync")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "resource_type": "assistant",
+            "resource_id": assistant_id,
+            "grantees": [user_grantee(&user_ids[0]), user_grantee(&user_ids[2])],
+            "role": "viewer"
+        }))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    assert_eq!(
+        result_statuses(&response.json::<Value>()),
+        vec![
+            (user_ids[0].clone(), "already_exists".to_string()),
+            (user_ids[2].clone(), "created".to_string()),
+            (user_ids[1].clone(), "removed".to_string()),
+        ]
+    );
+
+    let mut expected_subject_ids = vec![user_ids[0].clone(), user_ids[2].clone()];
+    expected_subject_ids.sort();
+    assert_eq!(
+        list_grant_subject_ids(&server, &assistant_id).await,
+        expected_subject_ids
+    );
+
+    // Other users can't sync the grantees of the assistant
+    let other_user_token = JwtTokenBuilder::new().subject("user-b-sync-test").build();
+    let forbidden_response = server
+        .put("/api/v1beta/share-grants/sync")
+        .with_bearer_token(&other_user_token)
+        .json(&json!({
+            "resource_type": "assistant",
+            "resource_id": assistant_id,
+            "grantees": [],
+            "role": "viewer"
+        }))
+        .await;
+    assert_eq!(
+        forbidden_response.status_code(),
+        http::StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        list_grant_subject_ids(&server, &assistant_id).await,
+        expected_subject_ids
+    );
+}
@@ -0,0 +1,174 @@
+//! Tests for system messages submitted by privileged clients.
+
+use axum::http;
+use sea_orm::prelude::Uuid;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+use crate::test_app_state;
+use crate::test_utils::{
+    JwtTokenBuilder, RequestBodyRecorder, TEST_JWT_TOKEN, TestRequestAuthExt, create_test_server,
+    recording_llm_mocks, setup_mock_llm_server_with_mocks, submit_events,
+};
+
+const AUTOMATION_GROUP: &str = "automation";
+
+fn event_field(events: &[Value], message_type: &str, field: &str) -> Option<String> {
+    events
+        .iter()
+        .find(|event| event["message_type"] == message_type)
+        .and_then(|event| event[field].as_str())
+        .map(String::from)
+}
+
+/// Test that system messages are only accepted from members of the configured groups.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+///
+/// # Test Behavior
+/// A user without an allowed group can't submit a system message. Members of an allowed group
+/// can't attach files to a system message, and user messages can't be submitted without
+/// generating an answer. None of the rejected requests reach the LLM.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_submit_system_message_is_restricted(pool: Pool<Postgres>) {
+    let recorder = RequestBodyRecorder::new();
+    let (mut app_config, _server) =
+        setup_mock_llm_server_with_mocks(recording_llm_mocks(&recorder, &["Noted", "."])).await;
+    app_config.chat.allow_client_system_messages_groups = vec![AUTOMATION_GROUP.to_string()];
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+    let automation_token = JwtTokenBuilder::new()
+        .groups(vec![AUTOMATION_GROUP.to_string()])
+        .build();
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Answer in French", "role": "system" }))
+        .await;
+    response.assert_status(http::StatusCode::FORBIDDEN);
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(&automation_token)
+        .json(&json!({
+            "user_message": "Answer in French",
+            "role": "system",
+            "input_files_ids": [Uuid::new_v4()],
+        }))
+        .await;
+    response.assert_status(http::StatusCode::BAD_REQUEST);
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(&automation_token)
+        .json(&json!({ "user_message": "Hello", "generate": false }))
+        .await;
+    response.assert_status(http::StatusCode::BAD_REQUEST);
+
+    assert!(recorder.bodies().is_empty(), "The LLM should not be called");
+}
+
+/// Test that a system message is stored without generating and steers the next answer.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `uses-mocked-llm`
+/// - `sse-streaming`
+///
+/// # Test Behavior
+/// Submits a user message, then a system message after the answer, which is saved without
+/// calling the LLM and listed with the `system` role. A follow-up user message is generated from
+/// a prompt that contains the system message between the previous answer and the new message.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_submit_system_message_without_generation(pool: Pool<Postgres>) {
+    let recorder = RequestBodyRecorder::new();
+    let (mut app_config, _server) =
+        setup_mock_llm_server_with_mocks(recording_llm_mocks(&recorder, &["Noted", "."])).await;
+    app_config.chat.allow_client_system_messages_groups = vec![AUTOMATION_GROUP.to_string()];
+    let app_state = test_app_state(app_config, pool).await;
+    let server = create_test_server(app_state);
+    let automation_token = JwtTokenBuilder::new()
+        .groups(vec![AUTOMATION_GROUP.to_string()])
+        .build();
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(&automation_token)
+        .json(&json!({ "user_message": "Hello" }))
+        .await;
+    response.assert_status_ok();
+    let events = submit_events(&response);
+    let chat_id = event_field(&events, "chat_created", "chat_id").expect("Expected a new chat");
+    let assistant_message_id = event_field(&events, "assistant_message_completed", "message_id")
+        .expect("Expected an answer");
+    assert_eq!(recorder.bodies().len(), 1);
+
+    let steering = "The user switched to the billing page.";
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(&automation_token)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "previous_message_id": assistant_message_id,
+            "user_message": steering,
+            "role": "system",
+        }))
+        .await;
+    response.assert_status_ok();
+    let events = submit_events(&response);
+    let system_message_id =
+        event_field(&events, "user_message_saved", "message_id").expect("Expected a saved message");
+    assert!(
+        event_field(&events, "assistant_message_completed", "message_id").is_none(),
+        "No answer should be generated for the system message"
+    );
+    assert_eq!(recorder.bodies().len(), 1, "The LLM should not be called");
+
+    let response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(&automation_token)
+        .await;
+    response.assert_status_ok();
+    let messages: Value = response.json();
+    let system_message = messages["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|message| message["id"] == system_message_id.as_str())
+        .expect("System message should be listed");
+    assert_eq!(system_message["role"], "system");
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(&automation_token)
+        .json(&json!({
+            "existing_chat_id": chat_id,
+            "previous_message_id": system_message_id,
+            "user_message": "What's my balance?",
+        }))
+        .await;
+    response.assert_status_ok();
+    let events = submit_events(&response);
+    assert!(event_field(&events, "assistant_message_completed", "message_id").is_some());
+
+    let bodies = recorder.bodies();
+    assert_eq!(bodies.len(), 2);
+    let llm_request: Value = serde_json::from_str(&bodies[1]).unwrap();
+    let llm_messages = llm_request["messages"].as_array().unwrap();
+    let position = |content: &str| {
+        llm_messages
+            .iter()
+            .position(|message| message.to_string().contains(content))
+            .unwrap_or_else(|| panic!("Expected {content:?} in the prompt"))
+    };
+    let steering_position = position(steering);
+    assert_eq!(llm_messages[steering_position]["role"], "system");
+    assert!(position("Noted.") < steering_position);
+    assert!(steering_position < position("What's my balance?"));
+}
@@ -0,0 +1,175 @@
+//! Tests for the `trusted_headers` auth mode.
+
+use axum::Router;
+use axum::extract::connect_info::MockConnectInfo;
+use axum::http;
+use axum_test::TestServer;
+use erato::config::{AppConfig, AuthMode};
+use erato::server::router::router;
+use erato::state::AppState;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::net::SocketAddr;
+
+use crate::test_utils::{
+    ADMIN_GROUP_ID, TEST_JWT_TOKEN, TestRequestAuthExt, configure_admin_group, hermetic_app_config,
+};
+use crate::{test_app_state, test_app_state_with_sharepoint};
+
+const TRUSTED_PROXY_ADDR: &str = "10.1.2.3:41000";
+
+fn trusted_headers_config() -> AppConfig {
+    let mut app_config = hermetic_app_config(None, None);
+    app_config.auth.mode = AuthMode::TrustedHeaders;
+    app_config.auth.trusted_headers.trusted_proxies = vec!["10.0.0.0/8".to_string()];
+    configure_admin_group(&mut app_config);
+    app_config
+}
+
+/// A test server whose requests come from `peer_addr`.
+fn server_with_peer(app_state: AppState, peer_addr: &str) -> TestServer {
+    let peer_addr: SocketAddr = peer_addr.parse().unwrap();
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state)
+        .layer(MockConnectInfo(peer_addr));
+    TestServer::new(app.into_make_service()).expect("Failed to create test server")
+}
+
+/// Test that the profile is built from the identity headers of a trusted proxy.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Requests from the trusted proxy network with the oauth2-proxy identity headers, and without
+/// any token, get the profile from the headers, with the groups split at the separator and the
+/// language from the preferred language header. Subsequent requests resolve to the same user,
+/// and the groups grant access to the admin API like the `groups` claim of an ID token.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_profile_from_trusted_headers(pool: Pool<Postgres>) {
+    let app_state = test_app_state(trusted_headers_config(), pool).await;
+    let server = server_with_peer(app_state, TRUSTED_PROXY_ADDR);
+
+    let request = |path: &str| {
+        server
+            .get(path)
+            .add_header("X-Forwarded-User", "user-1")
+            .add_header("X-Forwarded-Email", "user-1@example.com")
+            .add_header("X-Forwarded-Preferred-Username", "User One")
+            .add_header("X-Forwarded-Groups", format!("{ADMIN_GROUP_ID}, users"))
+            .add_header("X-Forwarded-Preferred-Language", "de-DE")
+    };
+
+    let response = request("/api/v1beta/me/profile").await;
+    response.assert_status_ok();
+    let profile: Value = response.json();
+    assert_eq!(profile["email"], "user-1@example.com");
+    assert_eq!(profile["name"], "User One");
+    assert_eq!(profile["groups"], json!([ADMIN_GROUP_ID, "users"]));
+    assert_eq!(
+        profile["organization_group_ids"],
+        json!([ADMIN_GROUP_ID, "users"])
+    );
+    assert_eq!(profile["preferred_language"], "de");
+
+    let response = request("/api/v1beta/me/profile").await;
+    response.assert_status_ok();
+    assert_eq!(response.json::<Value>()["id"], profile["id"]);
+
+    request("/api/v1beta/admin/langfuse/status")
+        .await
+        .assert_status_ok();
+}
+
+/// Test that identity headers are only accepted from trusted proxies.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Requests with valid identity headers are rejected with 401 when they come from an address
+/// outside of the trusted proxy networks, or when the address of the peer is unknown. Requests
+/// from a trusted proxy without the user ID header are rejected as well, even with a valid JWT,
+/// which is ignored in this mode.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_trusted_headers_require_a_trusted_proxy(pool: Pool<Postgres>) {
+    let app_state = test_app_state(trusted_headers_config(), pool).await;
+
+    let untrusted_server = server_with_peer(app_state.clone(), "192.168.1.10:41000");
+    let response = untrusted_server
+        .get("/api/v1beta/me/profile")
+        .add_header("X-Forwarded-User", "user-1")
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::UNAUTHORIZED);
+
+    let app: Router = router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state.clone());
+    let server_without_peer =
+        TestServer::new(app.into_make_service()).expect("Failed to create test server");
+    let response = server_without_peer
+        .get("/api/v1beta/me/profile")
+        .add_header("X-Forwarded-User", "user-1")
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::UNAUTHORIZED);
+
+    let trusted_server = server_with_peer(app_state, TRUSTED_PROXY_ADDR);
+    let response = trusted_server
+        .get("/api/v1beta/me/profile")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::UNAUTHORIZED);
+}
+
+/// Test that the SharePoint integration is unavailable in the `trusted_headers` mode.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+/// - `sharepoint-integration`
+///
+/// # Test Behavior
+/// With the SharePoint integration enabled, its endpoints and linking a file from SharePoint
+/// respond with 409, as there is no access token of the user for MS Graph. The file
+/// capabilities don't offer SharePoint as a source.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_sharepoint_is_unavailable_with_trusted_headers(pool: Pool<Postgres>) {
+    let app_state = test_app_state_with_sharepoint(trusted_headers_config(), pool).await;
+    let server = server_with_peer(app_state, TRUSTED_PROXY_ADDR);
+
+    let response = server
+        .get("/api/v1beta/integrations/sharepoint/all-drives")
+        .add_header("X-Forwarded-User", "user-1")
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::CONFLICT);
+
+    let response = server
+        .post("/api/v1beta/me/files/link")
+        .add_header("X-Forwarded-User", "user-1")
+        .json(&json!({
+            "source": "sharepoint",
+            "provider_metadata": { "drive_id": "drive", "item_id": "item" }
+        }))
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::CONFLICT);
+
+    let response = server
+        .get("/api/v1beta/me/file-capabilities")
+        .add_header("X-Forwarded-User", "user-1")
+        .await;
+    response.assert_status_ok();
+    let capabilities: Value = response.json();
+    assert!(
+        capabilities
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|capability| capability["sources"] == json!(["local"]))
+    );
+}
@@ -0,0 +1,201 @@
+//! Tests for the anonymous usage reports.
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{self, HeaderMap};
+use axum::routing::post;
+use erato::config::AppConfig;
+use erato::db::entity::usage_reporting_installation;
+use erato::services::usage_reporting::{
+    USAGE_REPORT_INSTALLATION_ID_HEADER, USAGE_REPORT_SIGNATURE_HEADER,
+    USAGE_REPORT_TIMESTAMP_HEADER, build_usage_report, get_or_create_installation_id,
+    send_usage_report_if_due, start_usage_reporting, usage_report_signature,
+};
+use sea_orm::EntityTrait;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::sync::{Arc, Mutex};
+
+use crate::test_app_state;
+use crate::test_utils::fixtures::{FixtureChat, FixtureMessage, FixtureUser};
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TestRequestAuthExt, admin_app_config, admin_token, create_test_server,
+    read_integration_test_file_bytes,
+};
+
+const PREVIEW_PATH: &str = "/api/v1beta/admin/telemetry/preview";
+
+/// A report received by the fake reporting service.
+#[derive(Debug, Clone)]
+struct ReceivedReport {
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// Start a fake reporting service, returning the URL of its endpoint and the reports it
+/// received.
+async fn start_reporting_service() -> (String, Arc<Mutex<Vec<ReceivedReport>>>) {
+    async fn receive(
+        State(received): State<Arc<Mutex<Vec<ReceivedReport>>>>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> http::StatusCode {
+        received
+            .lock()
+            .unwrap()
+            .push(ReceivedReport { headers, body });
+        http::StatusCode::NO_CONTENT
+    }
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let app = axum::Router::new()
+        .route("/usage", post(receive))
+        .with_state(received.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    (format!("http://{addr}/usage"), received)
+}
+
+fn usage_reporting_config(enabled: bool, endpoint: String) -> AppConfig {
+    let mut app_config = admin_app_config();
+    app_config.assistants.enabled = true;
+    app_config.chat_sharing.enabled = true;
+    app_config.telemetry.usage_reporting.enabled = enabled;
+    app_config.telemetry.usage_reporting.endpoint = Some(endpoint);
+    app_config
+}
+
+/// Test that the preview of the usage report matches the golden file.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// With two chats of one user, and a question with two answers of which one called a tool, the
+/// preview only contains the enabled features and bucketed counts, exactly as in
+/// `usage_report_golden.json`, plus the version of this build. The preview is available while
+/// usage reporting is disabled, and only to admins.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_usage_report_preview_matches_golden_file(pool: Pool<Postgres>) {
+    let (endpoint, received) = start_reporting_service().await;
+    let app_state = test_app_state(usage_reporting_config(false, endpoint), pool).await;
+    let server = create_test_server(app_state.clone());
+
+    let user = FixtureUser::test_user().create(&app_state).await;
+    let chat = FixtureChat::new(&user).create(&app_state).await;
+    FixtureChat::new(&user).create(&app_state).await;
+    let question = FixtureMessage::text(&chat, "user", "Which files mention the budget?")
+        .create(&app_state)
+        .await;
+    let tool_use = json!({
+        "role": "assistant",
+        "content": [{
+            "content_type": "tool_use",
+            "tool_call_id": "call_1",
+            "status": "success",
+            "tool_name": "search_files",
+            "input": { "query": "budget" },
+            "output": null
+        }]
+    });
+    FixtureMessage::new(&chat, tool_use)
+        .after(question.id)
+        .create(&app_state)
+        .await;
+    FixtureMessage::text(&chat, "assistant", "The budget is mentioned in the plan.")
+        .after(question.id)
+        .create(&app_state)
+        .await;
+
+    let response = server
+        .get(PREVIEW_PATH)
+        .with_bearer_token(&admin_token())
+        .await;
+    response.assert_status_ok();
+    let mut report: Value = response.json();
+
+    let version = report.as_object_mut().unwrap().remove("version").unwrap();
+    let version = format!(
+        "{}.{}.{}",
+        version["major"], version["minor"], version["patch"]
+    );
+    assert!(env!("CARGO_PKG_VERSION").starts_with(&version));
+    let golden: Value =
+        serde_json::from_slice(&read_integration_test_file_bytes("usage_report_golden.json"))
+            .unwrap();
+    assert_eq!(report, golden);
+
+    let response = server
+        .get(PREVIEW_PATH)
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::FORBIDDEN);
+    assert!(received.lock().unwrap().is_empty());
+}
+
+/// Test that nothing is sent, and the installation ID is deleted, when usage reporting is
+/// disabled.
+///
+/// # Test Categories
+/// - `uses-db`
+///
+/// # Test Behavior
+/// An installation ID left over from when usage reporting was enabled is deleted at startup, no
+/// reporting task is started, and the reporting service receives nothing.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_nothing_is_sent_when_disabled(pool: Pool<Postgres>) {
+    let (endpoint, received) = start_reporting_service().await;
+    let app_state = test_app_state(usage_reporting_config(false, endpoint), pool).await;
+    get_or_create_installation_id(&app_state.db).await.unwrap();
+
+    assert!(!start_usage_reporting(&app_state).await);
+
+    let installations = usage_reporting_installation::Entity::find()
+        .all(&app_state.db)
+        .await
+        .unwrap();
+    assert!(installations.is_empty());
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert!(received.lock().unwrap().is_empty());
+}
+
+/// Test that a signed report is sent once per interval when usage reporting is enabled.
+///
+/// # Test Categories
+/// - `uses-db`
+///
+/// # Test Behavior
+/// The first check sends the report to the endpoint, signed with the installation ID that is
+/// stored in the database, with the same content as the preview. A second check within the
+/// interval sends nothing. The installation ID stays the same across checks.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_signed_report_is_sent_when_enabled(pool: Pool<Postgres>) {
+    let (endpoint, received) = start_reporting_service().await;
+    let app_state = test_app_state(usage_reporting_config(true, endpoint), pool).await;
+
+    assert!(send_usage_report_if_due(&app_state).await.unwrap());
+    assert!(!send_usage_report_if_due(&app_state).await.unwrap());
+
+    let installation_id = get_or_create_installation_id(&app_state.db).await.unwrap();
+    let received = received.lock().unwrap().clone();
+    assert_eq!(received.len(), 1);
+    let report = &received[0];
+    let header = |name: &str| report.headers[name].to_str().unwrap().to_string();
+    assert_eq!(
+        header(USAGE_REPORT_INSTALLATION_ID_HEADER),
+        installation_id.to_string()
+    );
+    let timestamp: i64 = header(USAGE_REPORT_TIMESTAMP_HEADER).parse().unwrap();
+    assert_eq!(
+        header(USAGE_REPORT_SIGNATURE_HEADER),
+        usage_report_signature(&installation_id, timestamp, &report.body)
+    );
+    let body: Value = serde_json::from_slice(&report.body).unwrap();
+    assert_eq!(body, json!(build_usage_report(&app_state).await.unwrap()));
+}
@@ -0,0 +1,170 @@
+//! Tests of the fixture builders of the integration tests.
+
+use erato::db::entity::{chat_file_uploads, users};
+use erato::models::message::{check_thread_integrity, get_all_chat_messages};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::Value;
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::collections::HashSet;
+
+use crate::test_app_state;
+use crate::test_utils::fixtures::{FixtureAssistant, FixtureChat, FixtureUser};
+use crate::test_utils::{QueryCounter, TestRequestAuthExt, create_test_server, hermetic_app_config};
+
+/// Test that the threads built by `FixtureChat` are intact.
+///
+/// # Test Categories
+/// - `uses-db`
+///
+/// # Test Behavior
+/// Builds a chat with a main thread of four messages and two edits of its second user message.
+/// Verifies that the thread passes the integrity check, that the last edit is in the active
+/// thread, that roles alternate along the active thread, and that the edits are linked to the
+/// message they replace.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_fixture_chat_thread_is_intact(pool: Pool<Postgres>) {
+    let app_state = test_app_state(hermetic_app_config(None, None), pool).await;
+    let user = FixtureUser::test_user().create(&app_state).await;
+
+    let chat = FixtureChat::new(&user)
+        .with_messages(4)
+        .with_thread_branch(1, 2)
+        .with_thread_branch(1, 1)
+        .create(&app_state)
+        .await;
+
+    let report = check_thread_integrity(&app_state.db, &chat.id)
+        .await
+        .expect("Failed to check thread integrity");
+    assert!(report.is_intact(), "Fixture thread is corrupted: {report:?}");
+    assert_eq!(report.message_count, 7);
+
+    let messages = get_all_chat_messages(&app_state.db, &chat.id)
+        .await
+        .expect("Failed to load messages");
+    let active_ids: HashSet<_> = messages
+        .iter()
+        .filter(|message| message.is_message_in_active_thread)
+        .map(|message| message.id)
+        .collect();
+    let active_thread = chat.active_thread();
+    assert_eq!(active_ids, active_thread.iter().copied().collect());
+    assert_eq!(chat.last_message_id(), active_thread.last().copied());
+
+    for (index, message_id) in active_thread.iter().enumerate() {
+        let message = messages
+            .iter()
+            .find(|message| message.id == *message_id)
+            .unwrap();
+        let expected_role = if index % 2 == 0 { "user" } else { "assistant" };
+        assert_eq!(message.raw_message["role"], Value::from(expected_role));
+    }
+
+    for branch in &chat.branches {
+        let first = messages
+            .iter()
+            .find(|message| message.id == branch.message_ids[0])
+            .unwrap();
+        assert_eq!(first.previous_message_id, Some(branch.from_message_id));
+        assert_eq!(first.sibling_message_id, Some(chat.message_ids[2]));
+        assert_eq!(first.edit_of_message_id, Some(chat.message_ids[2]));
+    }
+}
+
+/// Test that `FixtureChat` creates chats with an assistant and files.
+///
+/// # Test Categories
+/// - `uses-db`
+///
+/// # Test Behavior
+/// Verifies that the chat is created with the assistant and title, and that the files are linked
+/// to the chat and attached to its first message.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_fixture_chat_with_assistant_and_files(pool: Pool<Postgres>) {
+    let app_state = test_app_state(hermetic_app_config(None, None), pool).await;
+    let user = FixtureUser::test_user().create(&app_state).await;
+    let assistant = FixtureAssistant::new(&user, "Fixture Assistant")
+        .create(&app_state)
+        .await;
+
+    let chat = FixtureChat::new(&user)
+        .with_assistant(&assistant)
+        .with_title("Fixture chat")
+        .with_files(&["notes.txt", "report.pdf"])
+        .with_messages(2)
+        .create(&app_state)
+        .await;
+
+    assert_eq!(chat.model.assistant_id, Some(assistant.id));
+    assert_eq!(
+        chat.model.title_by_user_provided.as_deref(),
+        Some("Fixture chat")
+    );
+    assert_eq!(chat.file_upload_ids.len(), 2);
+
+    let linked_ids: HashSet<_> = chat_file_uploads::Entity::find()
+        .filter(chat_file_uploads::Column::ChatId.eq(chat.id))
+        .all(&app_state.db)
+        .await
+        .expect("Failed to load chat file uploads")
+        .into_iter()
+        .map(|link| link.file_upload_id)
+        .collect();
+    assert_eq!(linked_ids, chat.file_upload_ids.iter().copied().collect());
+
+    let messages = get_all_chat_messages(&app_state.db, &chat.id)
+        .await
+        .expect("Failed to load messages");
+    let first_message = messages
+        .iter()
+        .find(|message| message.id == chat.message_ids[0])
+        .unwrap();
+    assert_eq!(
+        first_message.input_file_uploads,
+        Some(chat.file_upload_ids.clone())
+    );
+}
+
+/// Test that the token of a fixture user authenticates that user.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Verifies that the profile endpoint returns the fixture user for its token, and that a
+/// `QueryCounter` counts the queries of the request, and exactly the queries of a block on a
+/// connection only that block uses.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_fixture_user_token_and_query_counter(pool: Pool<Postgres>) {
+    let mut app_state = test_app_state(hermetic_app_config(None, None), pool).await;
+    let query_counter = QueryCounter::install(&mut app_state.db);
+    let user = FixtureUser::new("fixture-user")
+        .with_email("fixture-user@example.com")
+        .create(&app_state)
+        .await;
+    let server = create_test_server(app_state.clone());
+
+    let (response, queries) = query_counter
+        .count(server.get("/api/v1beta/me/profile").with_bearer_token(&user.token()))
+        .await;
+    response.assert_status_ok();
+    let profile: Value = response.json();
+    assert_eq!(profile["id"], user.id.to_string());
+    assert!(queries > 0, "The profile request should query the database");
+
+    let mut db = app_state.db.clone();
+    let db_query_counter = QueryCounter::install(&mut db);
+    let (_, queries) = db_query_counter
+        .count(async {
+            users::Entity::find_by_id(user.id)
+                .one(&db)
+                .await
+                .expect("Failed to load user")
+        })
+        .await;
+    assert_eq!(queries, 1);
+    let (_, queries) = db_query_counter.count(async {}).await;
+    assert_eq!(queries, 0);
+}
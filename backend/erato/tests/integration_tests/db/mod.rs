@@ -1,4 +1,6 @@
 //! Database-related integration tests.
 
+pub mod fixtures;
 pub mod migrations;
+pub mod policy_data_tiering;
 pub mod users;
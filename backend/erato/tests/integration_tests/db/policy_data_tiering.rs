@@ -0,0 +1,250 @@
+//! Tests for the tiering of the policy data.
+
+use crate::MIGRATOR;
+use chrono::{Duration, Utc};
+use erato::config::AppConfig;
+use erato::db::entity::{chat_file_uploads, chats, file_uploads, share_grants, share_links};
+use erato::policy::engine::{AuthorizeShort, PolicyEngine};
+use erato::policy::types::{Action, Resource, Subject};
+use sea_orm::prelude::Uuid;
+use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+async fn insert_chat(
+    db: &DatabaseConnection,
+    owner: &str,
+    organization_id: Option<&str>,
+    updated_days_ago: i64,
+    archived_days_ago: Option<i64>,
+) -> Uuid {
+    let updated_at = Utc::now() - Duration::days(updated_days_ago);
+    chats::ActiveModel {
+        id: ActiveValue::Set(Uuid::new_v4()),
+        owner_user_id: ActiveValue::Set(owner.to_string()),
+        created_at: ActiveValue::Set(updated_at.into()),
+        updated_at: ActiveValue::Set(updated_at.into()),
+        archived_at: ActiveValue::Set(
+            archived_days_ago.map(|days| (Utc::now() - Duration::days(days)).into()),
+        ),
+        organization_id: ActiveValue::Set(organization_id.map(String::from)),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("Failed to insert chat")
+    .id
+}
+
+async fn insert_file(
+    db: &DatabaseConnection,
+    owner: &str,
+    organization_id: Option<&str>,
+    chat_ids: &[Uuid],
+) -> Uuid {
+    let file_id = Uuid::new_v4();
+    file_uploads::ActiveModel {
+        id: ActiveValue::Set(file_id),
+        owner_user_id: ActiveValue::Set(owner.to_string()),
+        filename: ActiveValue::Set("notes.txt".to_string()),
+        file_storage_provider_id: ActiveValue::Set("local".to_string()),
+        file_storage_path: ActiveValue::Set("notes.txt".to_string()),
+        audio_transcription: ActiveValue::Set(None),
+        created_at: ActiveValue::Set(Utc::now().into()),
+        updated_at: ActiveValue::Set(Utc::now().into()),
+        organization_id: ActiveValue::Set(organization_id.map(String::from)),
+        storage_status: ActiveValue::Set("ok".to_string()),
+    }
+    .insert(db)
+    .await
+    .expect("Failed to insert file");
+    for chat_id in chat_ids {
+        chat_file_uploads::ActiveModel {
+            chat_id: ActiveValue::Set(*chat_id),
+            file_upload_id: ActiveValue::Set(file_id),
+            created_at: ActiveValue::Set(Utc::now().into()),
+            updated_at: ActiveValue::Set(Utc::now().into()),
+        }
+        .insert(db)
+        .await
+        .expect("Failed to link file to chat");
+    }
+    file_id
+}
+
+fn subject(id: &str, organization_id: Option<&str>) -> Subject {
+    Subject::UserWithOrganizationInfo {
+        id: id.to_string(),
+        organization_id: organization_id.map(String::from),
+        organization_user_id: None,
+        organization_group_ids: vec![],
+    }
+}
+
+/// Test that authorizing with tiered policy data gives the same results as with all data in
+/// memory.
+///
+/// # Test Categories
+/// - `uses-db`
+///
+/// # Test Behavior
+/// Seeds recent, recently archived, long archived and inactive chats across two organizations,
+/// with files linked to them, a share grant and share links. Builds one engine with tiering
+/// disabled and one with tiering enabled, and verifies that both give the same result for every
+/// combination of subject, resource and action, including repeated lookups of cold resources.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_tiered_policy_data_matches_full_policy_data(pool: Pool<Postgres>) {
+    let db = sea_orm::SqlxPostgresConnector::from_sqlx_postgres_pool(pool);
+
+    let recent_chat = insert_chat(&db, "user-a", None, 0, None).await;
+    let recently_archived_chat = insert_chat(&db, "user-a", None, 2, Some(1)).await;
+    let archived_chat = insert_chat(&db, "user-a", None, 90, Some(60)).await;
+    let archived_shared_chat = insert_chat(&db, "user-b", None, 90, Some(60)).await;
+    let archived_linked_chat = insert_chat(&db, "user-b", None, 90, Some(60)).await;
+    let inactive_linked_chat = insert_chat(&db, "user-b", None, 400, None).await;
+    let archived_org_chat = insert_chat(&db, "user-a", Some("org-b"), 90, Some(60)).await;
+    let chat_ids = [
+        recent_chat,
+        recently_archived_chat,
+        archived_chat,
+        archived_shared_chat,
+        archived_linked_chat,
+        inactive_linked_chat,
+        archived_org_chat,
+    ];
+
+    let file_ids = [
+        insert_file(&db, "user-a", None, &[archived_chat]).await,
+        insert_file(&db, "user-b", None, &[archived_shared_chat]).await,
+        insert_file(&db, "user-b", None, &[recent_chat, inactive_linked_chat]).await,
+        insert_file(&db, "user-a", None, &[]).await,
+        insert_file(&db, "user-a", Some("org-b"), &[archived_org_chat]).await,
+    ];
+
+    share_grants::ActiveModel {
+        id: ActiveValue::Set(Uuid::new_v4()),
+        resource_type: ActiveValue::Set("chat".to_string()),
+        resource_id: ActiveValue::Set(archived_shared_chat.to_string()),
+        subject_type: ActiveValue::Set("user".to_string()),
+        subject_id_type: ActiveValue::Set("id".to_string()),
+        subject_id: ActiveValue::Set("user-a".to_string()),
+        role: ActiveValue::Set("viewer".to_string()),
+        permission: ActiveValue::Set("read".to_string()),
+        created_at: ActiveValue::Set(Utc::now().into()),
+        updated_at: ActiveValue::Set(Utc::now().into()),
+        expires_at: ActiveValue::Set(None),
+        organization_id: ActiveValue::Set(None),
+    }
+    .insert(&db)
+    .await
+    .expect("Failed to insert share grant");
+    for chat_id in [archived_linked_chat, inactive_linked_chat] {
+        share_links::ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            resource_type: ActiveValue::Set("chat".to_string()),
+            resource_id: ActiveValue::Set(chat_id.to_string()),
+            enabled: ActiveValue::Set(true),
+            created_at: ActiveValue::Set(Utc::now().into()),
+            updated_at: ActiveValue::Set(Utc::now().into()),
+        }
+        .insert(&db)
+        .await
+        .expect("Failed to insert share link");
+    }
+
+    let mut full_config = AppConfig::default();
+    full_config.chat_sharing.enabled = true;
+    full_config.policy_engine.tiering.enabled = false;
+    let mut tiered_config = full_config.clone();
+    tiered_config.policy_engine.tiering.enabled = true;
+    tiered_config.policy_engine.tiering.archived_after_days = 30;
+    tiered_config.policy_engine.tiering.inactive_after_days = Some(365);
+
+    let full_engine = PolicyEngine::new();
+    full_engine
+        .rebuild_data(&db, &full_config)
+        .await
+        .expect("Failed to build full policy data");
+    let tiered_engine = PolicyEngine::new();
+    tiered_engine
+        .rebuild_data(&db, &tiered_config)
+        .await
+        .expect("Failed to build tiered policy data");
+
+    let subjects = [
+        subject("user-a", None),
+        subject("user-b", None),
+        subject("user-c", None),
+        subject("user-a", Some("org-b")),
+        subject("user-b", Some("org-b")),
+    ];
+    let mut resources = Vec::new();
+    for chat_id in chat_ids {
+        for action in [
+            Action::Read,
+            Action::Update,
+            Action::SubmitMessage,
+            Action::Share,
+        ] {
+            resources.push((Resource::Chat(chat_id.to_string()), action));
+        }
+    }
+    for file_id in file_ids {
+        for action in [Action::Read, Action::Update] {
+            resources.push((Resource::FileUpload(file_id.to_string()), action));
+        }
+    }
+
+    let mut allowed_count = 0;
+    // The second round is served from the promoted cold resources
+    for _ in 0..2 {
+        for subject in &subjects {
+            let request_engine = tiered_engine.clone_for_request();
+            for (resource, action) in &resources {
+                let expected = full_engine
+                    .authorize(subject, resource.clone(), *action)
+                    .await
+                    .is_ok();
+                let tiered = request_engine
+                    .authorize(subject, resource.clone(), *action)
+                    .await
+                    .is_ok();
+                assert_eq!(
+                    tiered, expected,
+                    "Tiered result differs for {subject:?} on {resource:?} with {action:?}"
+                );
+                allowed_count += usize::from(expected);
+            }
+        }
+    }
+    assert!(allowed_count > 0, "Some of the actions should be allowed");
+
+    // Cold resources are still authorized through the database
+    tiered_engine
+        .clone_for_request()
+        .authorize(
+            subject("user-a", None),
+            Resource::Chat(archived_shared_chat.to_string()),
+            Action::Read,
+        )
+        .await
+        .expect("Shared cold chat should be readable");
+    tiered_engine
+        .clone_for_request()
+        .authorize(
+            subject("user-c", None),
+            Resource::FileUpload(file_ids[2].to_string()),
+            Action::Read,
+        )
+        .await
+        .expect("File of a shared inactive chat should be readable");
+    tiered_engine
+        .clone_for_request()
+        .authorize(
+            subject("user-a", None),
+            Resource::Chat(archived_org_chat.to_string()),
+            Action::Read,
+        )
+        .await
+        .expect_err("Cold chats of other organizations should not be readable");
+}
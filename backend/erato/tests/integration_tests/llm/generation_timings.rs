@@ -0,0 +1,94 @@
+//! Tests for the generation timings recorded for assistant messages.
+
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt, create_test_server,
+    hermetic_app_config, mock_llm_server_base_url, parse_sse_events,
+};
+use crate::{MIGRATOR, test_app_state};
+use erato::models::user::get_or_create_user;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+
+/// Initial delay of the `Delay` mock of the mock LLM server before the first content chunk.
+const MOCK_INITIAL_DELAY_MS: u64 = 5000;
+
+/// Test that the time to first token and the generation duration are recorded for a message.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mock-llm-server`
+/// - `sse-streaming`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Submits a message triggering the `Delay` mock of the running mock LLM server, which waits 5
+/// seconds before streaming its first content chunk. Verifies that the usage block of the
+/// completed assistant message reports a time to first token in the expected range and a
+/// generation duration that is not shorter than it, and that the same timings are returned when
+/// listing the chat's messages.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_message_submit_records_generation_timings(pool: Pool<Postgres>) {
+    let base_url = mock_llm_server_base_url();
+    let app_config = hermetic_app_config(None, Some(format!("{base_url}/base-openai/v1/")));
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let server = create_test_server(app_state.clone());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "delay" }))
+        .await;
+    response.assert_status_ok();
+
+    let completed = parse_sse_events(&response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .find(|event| event["message_type"] == "assistant_message_completed")
+        .expect("Could not find assistant_message_completed event");
+    let usage = &completed["message"]["usage"];
+    let time_to_first_token_ms = usage["time_to_first_token_ms"]
+        .as_u64()
+        .expect("Expected time_to_first_token_ms in usage");
+    let generation_duration_ms = usage["generation_duration_ms"]
+        .as_u64()
+        .expect("Expected generation_duration_ms in usage");
+
+    assert!(
+        (MOCK_INITIAL_DELAY_MS..MOCK_INITIAL_DELAY_MS + 3000).contains(&time_to_first_token_ms),
+        "Time to first token should reflect the initial delay of the mock, got {time_to_first_token_ms}ms"
+    );
+    assert!(
+        generation_duration_ms >= time_to_first_token_ms,
+        "Generation duration ({generation_duration_ms}ms) should include the time to first token ({time_to_first_token_ms}ms)"
+    );
+
+    // The timings are persisted with the message
+    let chat_id = completed["message"]["chat_id"]
+        .as_str()
+        .expect("Expected chat_id on the completed message");
+    let message_id = completed["message_id"].as_str().unwrap();
+    let response = server
+        .get(&format!("/api/v1beta/chats/{chat_id}/messages"))
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    let body: Value = response.json();
+    let message = body["messages"]
+        .as_array()
+        .expect("Expected messages array")
+        .iter()
+        .find(|message| message["id"] == message_id)
+        .expect("Completed message should be listed");
+    assert_eq!(
+        message["usage"]["time_to_first_token_ms"].as_u64(),
+        Some(time_to_first_token_ms)
+    );
+    assert_eq!(
+        message["usage"]["generation_duration_ms"].as_u64(),
+        Some(generation_duration_ms)
+    );
+}
@@ -6,7 +6,9 @@ use erato::config::{
 };
 use erato::services::mcp_manager::McpRequestAuthContext;
 use erato::services::mcp_manager::McpServers;
+use erato::services::mcp_session_manager::McpServerConnectionStatus;
 use genai::chat::ToolCall as GenaiToolCall;
+use rmcp::model::RawContent;
 use sea_orm::prelude::Uuid;
 use serde_json::json;
 use sqlx::Pool;
@@ -30,6 +32,7 @@ fn mcp_server_config(
         http_headers: None,
         authentication,
         max_session_idle_seconds: None,
+        on_schema_violation: Default::default(),
     }
 }
 
@@ -277,6 +280,166 @@ async fn test_forwarded_mcp_auth_filters_tools_by_identity(pool: Pool<Postgres>)
     );
 }
 
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_forwarded_mcp_auth_without_oidc_token_is_unavailable(pool: Pool<Postgres>) {
+    let mock_mcp_base_url = mock_mcp_base_url();
+    let (mut app_config, _llm_server) = setup_mock_llm_server(None).await;
+
+    app_config.mcp_servers.insert(
+        "auth-forwarded-oidc".to_string(),
+        mcp_server_config(
+            &mock_mcp_base_url,
+            "/mcp/auth-forwarded-oidc",
+            McpServerAuthenticationConfig::Forwarded {
+                forwarded: McpServerForwardedAuthenticationConfig {
+                    credential: McpServerForwardedCredential::OidcIdToken,
+                    ..Default::default()
+                },
+            },
+        ),
+    );
+
+    let _app_state = test_app_state(app_config.clone(), pool).await;
+    let mcp_servers = McpServers::new(&app_config);
+    // Like a request in the `trusted_headers` auth mode, which carries no token of the user
+    let auth_context = McpRequestAuthContext {
+        app_state: None,
+        user_id: None,
+        oidc_token: None,
+        access_token: None,
+    };
+
+    let tools = mcp_servers
+        .list_tools_for_server_ids(Uuid::new_v4(), None, &auth_context)
+        .await
+        .expect("Failed to discover MCP tools without an OIDC token");
+    assert!(tools.is_empty());
+    assert_eq!(
+        mcp_servers
+            .probe_connection("auth-forwarded-oidc", &auth_context)
+            .await,
+        McpServerConnectionStatus::NeedsAuthentication
+    );
+}
+
+/// Calls the `auth_echo` tool of the mock MCP server, which returns the bearer token the
+/// call was sent with.
+async fn call_auth_echo(
+    mcp_servers: &McpServers,
+    chat_id: Uuid,
+    auth_context: &McpRequestAuthContext<'_>,
+) -> String {
+    let managed_tool_call = mcp_servers
+        .convert_tool_call_to_managed_tool_call(
+            chat_id,
+            GenaiToolCall {
+                call_id: "call_auth_echo".to_string(),
+                fn_name: "auth_echo".to_string(),
+                fn_arguments: json!({}),
+                thought_signatures: None,
+            },
+            auth_context,
+        )
+        .await
+        .expect("Failed to resolve managed tool call");
+    let result = mcp_servers
+        .call_tool(chat_id, managed_tool_call, auth_context)
+        .await
+        .expect("Expected MCP tool call to succeed");
+
+    result
+        .content
+        .iter()
+        .find_map(|content| match &content.raw {
+            RawContent::Text(text_content) => Some(text_content.text.to_string()),
+            _ => None,
+        })
+        .expect("Expected text content in auth echo result")
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_forwarded_mcp_auth_isolates_sessions_per_user(pool: Pool<Postgres>) {
+    let mock_mcp_base_url = mock_mcp_base_url();
+    let (mut app_config, _llm_server) = setup_mock_llm_server(None).await;
+
+    app_config.mcp_servers.insert(
+        "auth-echo".to_string(),
+        mcp_server_config(
+            &mock_mcp_base_url,
+            "/mcp/auth-echo",
+            McpServerAuthenticationConfig::Forwarded {
+                forwarded: McpServerForwardedAuthenticationConfig {
+                    credential: McpServerForwardedCredential::AccessToken,
+                    ..Default::default()
+                },
+            },
+        ),
+    );
+
+    let _app_state = test_app_state(app_config.clone(), pool).await;
+    let mcp_servers = McpServers::new(&app_config);
+    let chat_id = Uuid::new_v4();
+    let first_user_id = Uuid::new_v4();
+    let second_user_id = Uuid::new_v4();
+    let oidc_token = JwtTokenBuilder::new().build();
+    let oidc_token = oidc_token.as_str();
+    let auth_context = move |user_id: Uuid, access_token: &'static str| McpRequestAuthContext {
+        app_state: None,
+        user_id: Some(user_id),
+        oidc_token: Some(oidc_token),
+        access_token: Some(access_token),
+    };
+
+    // Both users use the same chat, but each of them gets their own session
+    assert_eq!(
+        call_auth_echo(
+            &mcp_servers,
+            chat_id,
+            &auth_context(first_user_id, "access:first-user")
+        )
+        .await,
+        "access:first-user"
+    );
+    assert_eq!(
+        call_auth_echo(
+            &mcp_servers,
+            chat_id,
+            &auth_context(second_user_id, "access:second-user")
+        )
+        .await,
+        "access:second-user"
+    );
+    assert_eq!(
+        call_auth_echo(
+            &mcp_servers,
+            chat_id,
+            &auth_context(first_user_id, "access:first-user")
+        )
+        .await,
+        "access:first-user"
+    );
+
+    // A refreshed token transparently reconnects the session of the user
+    assert_eq!(
+        call_auth_echo(
+            &mcp_servers,
+            chat_id,
+            &auth_context(first_user_id, "access:first-user-refreshed")
+        )
+        .await,
+        "access:first-user-refreshed"
+    );
+    assert_eq!(
+        call_auth_echo(
+            &mcp_servers,
+            chat_id,
+            &auth_context(second_user_id, "access:second-user")
+        )
+        .await,
+        "access:second-user"
+    );
+}
+
 #[sqlx::test(migrator = "MIGRATOR")]
 async fn test_mcp_connection_errors_are_propagated_during_tool_discovery(pool: Pool<Postgres>) {
     let (mut app_config, _llm_server) = setup_mock_llm_server(None).await;
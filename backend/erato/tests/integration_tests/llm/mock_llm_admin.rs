@@ -0,0 +1,140 @@
+//! Tests installing scenario-specific mocks on the mock LLM server via its admin API.
+
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt, create_test_server,
+    hermetic_app_config,
+};
+use crate::{MIGRATOR, test_app_state};
+use erato::models::user::get_or_create_user;
+use sea_orm::prelude::Uuid;
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::env;
+
+fn mock_llm_base_url() -> String {
+    env::var("TEST_MOCK_LLM_SERVER_BASE_URL")
+        .unwrap_or_else(|_| "http://127.0.0.1:44320".to_string())
+}
+
+async fn get_active_mocks(client: &reqwest::Client, base_url: &str) -> Vec<Value> {
+    let response = client
+        .get(format!("{base_url}/admin/mocks"))
+        .send()
+        .await
+        .expect("Failed to reach the mock LLM server");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: Value = serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+    body["mocks"].as_array().cloned().unwrap_or_default()
+}
+
+async fn put_active_mocks(client: &reqwest::Client, base_url: &str, mocks: Vec<Value>) {
+    let response = client
+        .put(format!("{base_url}/admin/mocks"))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(json!({ "mocks": mocks }).to_string())
+        .send()
+        .await
+        .expect("Failed to reach the mock LLM server");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+/// Test installing a custom mock on the mock LLM server at runtime.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mock-llm-server`
+/// - `sse-streaming`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Installs a mock with a unique trigger phrase via `PUT /admin/mocks` of the running mock LLM
+/// server (`TEST_MOCK_LLM_SERVER_BASE_URL`), submits a message containing that phrase and
+/// verifies that the streamed text deltas and the completed assistant message contain exactly the
+/// chunks of the installed mock. The previously active mocks are restored afterwards.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_message_submit_with_runtime_installed_mock(pool: Pool<Postgres>) {
+    let base_url = mock_llm_base_url();
+    let client = reqwest::Client::new();
+
+    let trigger = format!("scenario-{}", Uuid::new_v4());
+    let chunks = [
+        "Scenario",
+        " specific",
+        " answer",
+        " from",
+        " the",
+        " mock.",
+    ];
+    let custom_mock = json!({
+        "name": trigger,
+        "description": "Installed by the backend integration tests",
+        "match_rules": [{ "UserMessagePattern": { "pattern": trigger } }],
+        "response": {
+            "Static": {
+                "chunks": chunks,
+                "delay_ms": 10,
+                "usage": { "prompt_tokens": 42, "completion_tokens": 6 }
+            }
+        }
+    });
+
+    // Install the custom mock in front of the currently active ones
+    let previous_mocks = get_active_mocks(&client, &base_url).await;
+    let mut mocks = vec![custom_mock];
+    mocks.extend(previous_mocks.iter().cloned());
+    put_active_mocks(&client, &base_url, mocks).await;
+    assert!(
+        get_active_mocks(&client, &base_url)
+            .await
+            .iter()
+            .any(|mock| mock["name"] == trigger.as_str()),
+        "Custom mock should be active after PUT /admin/mocks"
+    );
+
+    let app_config = hermetic_app_config(None, Some(format!("{base_url}/base-openai/v1/")));
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let server = create_test_server(app_state.clone());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({
+            "user_message": format!("Please answer {trigger}")
+        }))
+        .await;
+
+    // Restore the previous mocks before asserting, so that a failure doesn't leak the custom mock
+    put_active_mocks(&client, &base_url, previous_mocks).await;
+
+    response.assert_status_ok();
+    let events: Vec<Value> = response
+        .text()
+        .split("\n\n")
+        .filter_map(|event| event.split("data:").nth(1))
+        .filter_map(|data| serde_json::from_str::<Value>(data.trim()).ok())
+        .collect();
+
+    let streamed_text: String = events
+        .iter()
+        .filter(|event| event["message_type"] == "text_delta")
+        .filter_map(|event| event["new_text"].as_str())
+        .collect();
+    assert_eq!(streamed_text, chunks.concat());
+
+    let completed = events
+        .iter()
+        .find(|event| event["message_type"] == "assistant_message_completed")
+        .expect("Could not find assistant_message_completed event");
+    let completed_text: String = completed["content"]
+        .as_array()
+        .expect("Content should be an array")
+        .iter()
+        .filter(|part| part["content_type"] == "text")
+        .filter_map(|part| part["text"].as_str())
+        .collect();
+    assert_eq!(completed_text, chunks.concat());
+}
@@ -1,4 +1,8 @@
 //! LLM (Large Language Model) integration tests.
 
+pub mod generation_timings;
 pub mod mcp_auth;
+pub mod mock_llm_admin;
 pub mod mocked;
+pub mod otel_trace_context;
+pub mod provider_capture;
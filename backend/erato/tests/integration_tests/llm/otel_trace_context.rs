@@ -0,0 +1,171 @@
+//! Tests for the OpenTelemetry trace context exposed for generated messages.
+
+use crate::test_utils::{
+    TEST_JWT_TOKEN, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt, create_test_server,
+    hermetic_app_config, mock_llm_server_base_url, parse_sse_events,
+};
+use crate::{MIGRATOR, test_app_state};
+use erato::db::entity::messages;
+use erato::models::message::GenerationMetadata;
+use erato::models::user::get_or_create_user;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+use sea_orm::{EntityTrait, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::time::Duration;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Test that the trace context of a generation is exposed in the SSE stream and the message.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mock-llm-server`
+/// - `sse-streaming`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Records spans with an in-memory OpenTelemetry exporter and submits a message. Verifies that
+/// the `assistant_message_started` and `assistant_message_completed` events carry the same
+/// trace and span ID, that the `traceparent` header of the response points at that span, that
+/// the exported `generate_assistant_message` span has these IDs and that the trace ID is stored
+/// in the generation metadata of the message.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_message_submit_exposes_otel_trace_context(pool: Pool<Postgres>) {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("erato-tests")));
+    // The test runtime is single-threaded, so the background generation task uses this subscriber too
+    let _subscriber_guard = tracing::subscriber::set_default(subscriber);
+
+    let base_url = mock_llm_server_base_url();
+    let app_config = hermetic_app_config(None, Some(format!("{base_url}/base-openai/v1/")));
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let server = create_test_server(app_state.clone());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Hello" }))
+        .await;
+    response.assert_status_ok();
+
+    let events: Vec<Value> = parse_sse_events(&response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .collect();
+    let started = events
+        .iter()
+        .find(|event| event["message_type"] == "assistant_message_started")
+        .expect("Could not find assistant_message_started event");
+    let completed = events
+        .iter()
+        .find(|event| event["message_type"] == "assistant_message_completed")
+        .expect("Could not find assistant_message_completed event");
+
+    let trace_id = started["trace_id"]
+        .as_str()
+        .expect("Expected trace_id on assistant_message_started")
+        .to_string();
+    let span_id = started["span_id"]
+        .as_str()
+        .expect("Expected span_id on assistant_message_started")
+        .to_string();
+    assert_eq!(trace_id.len(), 32);
+    assert_eq!(span_id.len(), 16);
+    assert_eq!(completed["trace_id"].as_str(), Some(trace_id.as_str()));
+    assert_eq!(completed["span_id"].as_str(), Some(span_id.as_str()));
+
+    let traceparent = response.header("traceparent");
+    let traceparent = traceparent.to_str().unwrap();
+    assert!(
+        traceparent.starts_with(&format!("00-{trace_id}-{span_id}-")),
+        "Unexpected traceparent header: {traceparent}"
+    );
+
+    // The generation span is exported once the background task has finished
+    let mut generation_span = None;
+    for _ in 0..50 {
+        generation_span = exporter
+            .get_finished_spans()
+            .expect("Failed to read exported spans")
+            .into_iter()
+            .find(|span| span.span_context.span_id().to_string() == span_id);
+        if generation_span.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let generation_span = generation_span.expect("Generation span should have been exported");
+    assert_eq!(generation_span.name, "generate_assistant_message");
+    assert_eq!(
+        generation_span.span_context.trace_id().to_string(),
+        trace_id
+    );
+
+    // The trace ID is persisted with the message
+    let message_id = Uuid::parse_str(completed["message_id"].as_str().unwrap()).unwrap();
+    let message = messages::Entity::find_by_id(message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load message")
+        .expect("Completed message should exist");
+    let generation_metadata: GenerationMetadata = serde_json::from_value(
+        message
+            .generation_metadata
+            .expect("Expected generation metadata on the message"),
+    )
+    .expect("Failed to parse generation metadata");
+    assert_eq!(generation_metadata.otel_trace_id, Some(trace_id));
+}
+
+/// Test that no trace context is exposed when OpenTelemetry is not enabled.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mock-llm-server`
+/// - `sse-streaming`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Submits a message without an OpenTelemetry layer and verifies that neither the response nor
+/// the message lifecycle events contain trace context.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_message_submit_without_otel_omits_trace_context(pool: Pool<Postgres>) {
+    let base_url = mock_llm_server_base_url();
+    let app_config = hermetic_app_config(None, Some(format!("{base_url}/base-openai/v1/")));
+    let app_state = test_app_state(app_config, pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let server = create_test_server(app_state.clone());
+
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .json(&json!({ "user_message": "Hello" }))
+        .await;
+    response.assert_status_ok();
+    assert!(response.maybe_header("traceparent").is_none());
+
+    let lifecycle_events: Vec<Value> = parse_sse_events(&response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .filter(|event| {
+            event["message_type"] == "assistant_message_started"
+                || event["message_type"] == "assistant_message_completed"
+        })
+        .collect();
+    assert_eq!(lifecycle_events.len(), 2);
+    for event in lifecycle_events {
+        assert!(event.get("trace_id").is_none());
+        assert!(event.get("span_id").is_none());
+    }
+}
@@ -0,0 +1,223 @@
+//! Tests for the capture of the traffic to the chat providers.
+
+use crate::test_utils::{
+    JwtTokenBuilder, TEST_USER_ISSUER, TEST_USER_SUBJECT, TestRequestAuthExt, admin_token,
+    completed_assistant_message_id, configure_admin_group, create_test_server, hermetic_app_config,
+    mock_llm_server_base_url, submit_message,
+};
+use crate::{MIGRATOR, test_app_state};
+use axum::http;
+use erato::config::{AppConfig, ProviderTrafficCaptureConfig, ProviderTrafficCaptureTarget};
+use erato::db::entity::messages;
+use erato::models::message::GenerationMetadata;
+use erato::models::user::get_or_create_user;
+use erato::services::provider_capture::{ProviderCaptureStore, cleanup_expired_provider_captures};
+use erato::state::AppState;
+use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait, prelude::Uuid};
+use serde_json::{Value, json};
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::path::Path;
+
+fn app_config_with_capture(directory: &Path, sample_rate: f64) -> AppConfig {
+    let base_url = mock_llm_server_base_url();
+    let mut app_config = hermetic_app_config(None, Some(format!("{base_url}/base-openai/v1/")));
+    configure_admin_group(&mut app_config);
+    app_config.debug.capture_provider_traffic = ProviderTrafficCaptureConfig {
+        enabled: true,
+        sample_rate,
+        target: ProviderTrafficCaptureTarget::File,
+        directory: Some(directory.to_string_lossy().into_owned()),
+        include_responses: true,
+        ..Default::default()
+    };
+    app_config
+}
+
+/// Submit a message and return the ID and the generation metadata of the answer.
+async fn answer_metadata(
+    app_state: &AppState,
+    token: &str,
+    user_message: &str,
+) -> (Uuid, GenerationMetadata) {
+    let server = create_test_server(app_state.clone());
+    let response = submit_message(&server, token, &json!({ "user_message": user_message })).await;
+
+    let message_id = Uuid::parse_str(&completed_assistant_message_id(&response)).unwrap();
+    let message = messages::Entity::find_by_id(message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load message")
+        .expect("Completed message should exist");
+    let generation_metadata = serde_json::from_value(
+        message
+            .generation_metadata
+            .expect("Expected generation metadata on the message"),
+    )
+    .expect("Failed to parse generation metadata");
+    (message_id, generation_metadata)
+}
+
+/// Test that a sampled generation is captured with redacted contents.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mock-llm-server`
+/// - `sse-streaming`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Enables the capture into a temporary directory and submits a message containing an email
+/// address. Verifies that the capture ID is recorded in the generation metadata, that the capture
+/// contains the request and the response but not the email address, and that admins can
+/// retrieve it while other users can't.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_provider_traffic_is_captured_redacted(pool: Pool<Postgres>) {
+    let directory = tempfile::tempdir().unwrap();
+    let app_state = test_app_state(app_config_with_capture(directory.path(), 1.0), pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let admin_token = admin_token();
+
+    let (_, generation_metadata) = answer_metadata(
+        &app_state,
+        &admin_token,
+        "Please reply to jane.doe@example.com",
+    )
+    .await;
+    let capture_id = generation_metadata
+        .provider_capture_id
+        .expect("Expected a provider capture ID");
+
+    let path = directory
+        .path()
+        .join("debug-captures")
+        .join(format!("{capture_id}.json"));
+    let contents = std::fs::read_to_string(&path).expect("Capture should have been written");
+    assert!(!contents.contains("jane.doe@example.com"));
+    let capture: Value = serde_json::from_str(&contents).unwrap();
+    assert_eq!(capture["trace_id"], json!(capture_id));
+    let turn = &capture["turns"][0];
+    let user_message = turn["request"]["messages"]
+        .as_array()
+        .expect("Expected the captured request messages")
+        .iter()
+        .rfind(|message| message["role"] == "user")
+        .expect("Expected a user message in the captured request");
+    assert!(user_message["content"].to_string().contains("[REDACTED]"));
+    assert!(turn["response"].is_array());
+
+    let server = create_test_server(app_state.clone());
+    let response = server
+        .get(&format!("/api/v1beta/admin/captures/{capture_id}"))
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::OK);
+    let body: Value = response.json();
+    assert_eq!(body["capture"], capture);
+    assert_eq!(body["download_url"], Value::Null);
+
+    let response = server
+        .get(&format!("/api/v1beta/admin/captures/{capture_id}"))
+        .with_bearer_token(&JwtTokenBuilder::new().build())
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::FORBIDDEN);
+
+    let response = server
+        .get(&format!(
+            "/api/v1beta/admin/captures/{}",
+            Uuid::new_v4().simple()
+        ))
+        .with_bearer_token(&admin_token)
+        .await;
+    assert_eq!(response.status_code(), http::StatusCode::NOT_FOUND);
+}
+
+/// Test that generations that are not sampled are not captured.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mock-llm-server`
+/// - `sse-streaming`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Enables the capture with a sample rate of 0 and submits a message. Verifies that no capture ID
+/// is recorded and no capture is written.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_provider_traffic_capture_sampling(pool: Pool<Postgres>) {
+    let directory = tempfile::tempdir().unwrap();
+    let app_state = test_app_state(app_config_with_capture(directory.path(), 0.0), pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+
+    let (_, generation_metadata) =
+        answer_metadata(&app_state, &JwtTokenBuilder::new().build(), "Hello").await;
+
+    assert_eq!(generation_metadata.provider_capture_id, None);
+    assert!(!directory.path().join("debug-captures").exists());
+}
+
+/// Test that expired captures are deleted.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `uses-mock-llm-server`
+/// - `sse-streaming`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Captures a generation, and runs the cleanup once while the capture is within the retention
+/// period and once after moving the message past it. Verifies that only the second run deletes
+/// the capture and removes its ID from the generation metadata.
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn test_expired_provider_captures_are_deleted(pool: Pool<Postgres>) {
+    let directory = tempfile::tempdir().unwrap();
+    let app_state = test_app_state(app_config_with_capture(directory.path(), 1.0), pool).await;
+    get_or_create_user(&app_state.db, TEST_USER_ISSUER, TEST_USER_SUBJECT, None)
+        .await
+        .expect("Failed to create user");
+    let store = ProviderCaptureStore::Directory(directory.path().to_path_buf());
+
+    let (message_id, generation_metadata) =
+        answer_metadata(&app_state, &JwtTokenBuilder::new().build(), "Hello").await;
+    let capture_id = generation_metadata
+        .provider_capture_id
+        .expect("Expected a provider capture ID");
+    let path = directory
+        .path()
+        .join("debug-captures")
+        .join(format!("{capture_id}.json"));
+    assert!(path.exists());
+
+    let deleted = cleanup_expired_provider_captures(&app_state.db, &store, 24)
+        .await
+        .expect("Failed to clean up captures");
+    assert_eq!(deleted, 0);
+    assert!(path.exists());
+
+    messages::ActiveModel {
+        id: ActiveValue::Unchanged(message_id),
+        created_at: ActiveValue::Set((chrono::Utc::now() - chrono::Duration::hours(25)).into()),
+        ..Default::default()
+    }
+    .update(&app_state.db)
+    .await
+    .expect("Failed to update message");
+
+    let deleted = cleanup_expired_provider_captures(&app_state.db, &store, 24)
+        .await
+        .expect("Failed to clean up captures");
+    assert_eq!(deleted, 1);
+    assert!(!path.exists());
+    let message = messages::Entity::find_by_id(message_id)
+        .one(&app_state.db)
+        .await
+        .expect("Failed to load message")
+        .expect("Message should exist");
+    let generation_metadata: GenerationMetadata =
+        serde_json::from_value(message.generation_metadata.unwrap()).unwrap();
+    assert_eq!(generation_metadata.provider_capture_id, None);
+}
@@ -8,10 +8,19 @@
 use ctor::ctor;
 use erato::config::{AppConfig, LangfuseConfig};
 use erato::services::background_tasks::BackgroundTaskManager;
+use erato::services::chat_provider_groups::ChatProviderGroupBalancer;
+use erato::services::chat_provider_rate_limits::ChatProviderRateLimits;
+use erato::services::ephemeral_chats::EphemeralChatStore;
+use erato::services::feature_flags::FeatureFlagStore;
 use erato::services::file_storage::{FileStorage, SHAREPOINT_PROVIDER_ID};
+use erato::services::generation_input_archive::GenerationInputArchive;
 use erato::services::langfuse::LangfuseClient;
 use erato::services::mcp_manager::McpServers;
-use erato::state::{AppState, FileCacheKey, GlobalPolicyEngine};
+use erato::services::user_events::UserEventRegistry;
+use erato::state::{
+    AppState, FILE_PROCESSING_ERRORS_CACHE_MAX_ENTRIES, FileCacheKey, GlobalPolicyEngine,
+    ParsedFileContents,
+};
 use sqlx::Pool;
 use sqlx::postgres::Postgres;
 use std::collections::HashMap;
@@ -25,6 +34,7 @@ mod api;
 mod config;
 mod db;
 mod llm;
+mod openapi;
 mod test_utils;
 
 // Using a (possibly brittle?) life-before-main method to set the DATABASE_URL before any tests run.
@@ -106,9 +116,7 @@ async fn test_app_state_internal(
         );
     }
 
-    let actor_manager =
-        erato::actors::manager::ActorManager::new_with_name(db.clone(), app_config.clone(), None)
-            .await;
+    let actor_manager = erato::actors::manager::ActorManager::new_with_name(&app_config, None);
 
     // Create a disabled Langfuse client for testing
     let langfuse_config = LangfuseConfig {
@@ -122,13 +130,17 @@ async fn test_app_state_internal(
 
     // Initialize file contents cache with MB-based weigher
     let file_contents_cache = moka::future::Cache::builder()
-        .weigher(|_key: &FileCacheKey, value: &String| -> u32 {
+        .weigher(|_key: &FileCacheKey, value: &ParsedFileContents| -> u32 {
             // Weight by string byte length
-            value.len().try_into().unwrap_or(u32::MAX)
+            value.size().try_into().unwrap_or(u32::MAX)
         })
         .max_capacity(app_config.caches.file_contents_cache_mb * 1024 * 1024)
         .build();
 
+    let file_processing_errors_cache = moka::future::Cache::builder()
+        .max_capacity(FILE_PROCESSING_ERRORS_CACHE_MAX_ENTRIES)
+        .build();
+
     // Initialize file bytes cache with MB-based weigher
     let file_bytes_cache = moka::future::Cache::builder()
         .weigher(|_key: &FileCacheKey, value: &Vec<u8>| -> u32 {
@@ -160,8 +172,25 @@ async fn test_app_state_internal(
     )
     .unwrap();
 
+    let user_events = UserEventRegistry::new();
     let background_tasks =
-        BackgroundTaskManager::new(Some(db.clone()), app_config.generation_status.clone());
+        BackgroundTaskManager::new(Some(db.clone()), app_config.generation_status.clone())
+            .with_user_events(user_events.clone())
+            .with_generation_limit(
+                app_config.chat.max_concurrent_generations,
+                app_config.chat.queue_when_limited,
+            );
+
+    let generation_input_archive = GenerationInputArchive::new(
+        file_storage_providers.clone(),
+        app_config.admin.generation_input_archival.cache_entries,
+    );
+
+    let trusted_proxy_networks = app_config
+        .auth
+        .trusted_proxy_networks()
+        .expect("Invalid trusted proxy networks")
+        .into();
 
     let app_state = AppState {
         db: db.clone(),
@@ -169,10 +198,17 @@ async fn test_app_state_internal(
         file_storage_providers,
         mcp_servers: Arc::new(McpServers::new(&app_config)),
         config: app_config,
+        trusted_proxy_networks,
         actor_manager,
         langfuse_client,
         global_policy_engine,
         background_tasks,
+        user_events,
+        feature_flags: FeatureFlagStore::new(),
+        chat_provider_groups: ChatProviderGroupBalancer::new(),
+        chat_provider_rate_limits: ChatProviderRateLimits::new(),
+        ephemeral_chats: EphemeralChatStore::new(),
+        generation_input_archive,
         system_prompt_renderer:
             erato::services::template_rendering::consumers::system_prompt::SystemPromptRenderer::new(
             ),
@@ -180,11 +216,13 @@ async fn test_app_state_internal(
         genai_client_override: None,
         file_bytes_cache,
         file_contents_cache,
+        file_processing_errors_cache,
         token_count_cache,
         file_processing_semaphore,
         file_processing_pipeline_semaphore,
         file_processor,
     };
+    erato::actors::manager::ActorManager::startup(&app_state).await;
 
     // For tests: Initialize policy engine and work around the middleware rebuild issue
     // The problem is that policy invalidation during API calls doesn't trigger proper rebuilds
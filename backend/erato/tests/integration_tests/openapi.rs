@@ -0,0 +1,223 @@
+//! OpenAPI spec snapshot tests.
+
+use axum::Extension;
+use axum_test::TestServer;
+use erato::ApiDoc;
+use erato::config::PublicBaseUrlConfig;
+use erato::frontend_environment::DeploymentVersion;
+use erato::server::router::{ERATO_VERSION_HEADER, api_docs_routes};
+use serde_json::Value;
+use similar::TextDiff;
+use sqlx::Pool;
+use sqlx::postgres::Postgres;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use crate::test_app_state;
+use crate::test_utils::{TEST_JWT_TOKEN, TestRequestAuthExt, hermetic_app_config};
+
+/// Streaming endpoints, which have to be documented with an SSE response.
+const SSE_PATHS: &[&str] = &[
+    "/api/v1beta/me/events",
+    "/api/v1beta/me/messages/submitstream",
+    "/api/v1beta/me/messages/editstream",
+    "/api/v1beta/me/messages/regeneratestream",
+    "/api/v1beta/me/messages/continuestream",
+    "/api/v1beta/me/messages/resumestream",
+    "/api/v1beta/prompt-optimizer/stream",
+];
+
+fn committed_spec_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../generated/openapi.json")
+}
+
+/// Test that the committed OpenAPI spec matches the one generated from the source.
+///
+/// # Test Behavior
+/// Regenerates the spec without any app state and compares it with
+/// `generated/openapi.json`. On a mismatch, fails with a diff of the two, so changes
+/// to the API only pass once the committed spec (which the frontend client is
+/// generated from) has been updated via `just generate_open_api`.
+#[test]
+fn test_openapi_spec_matches_committed_snapshot() {
+    let path = committed_spec_path();
+    let committed = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read committed OpenAPI spec {:?}: {e}", path));
+    let generated = ApiDoc::build_openapi_json();
+
+    if committed != generated {
+        let diff = TextDiff::from_lines(&committed, &generated)
+            .unified_diff()
+            .context_radius(3)
+            .header("generated/openapi.json", "regenerated")
+            .to_string();
+        panic!(
+            "The OpenAPI spec changed without updating generated/openapi.json. \
+             Run `just generate_open_api` and review the changes:\n\n{diff}"
+        );
+    }
+}
+
+/// Test that streaming endpoints and their event schemas are part of the OpenAPI spec.
+///
+/// # Test Behavior
+/// Verifies that every SSE endpoint is documented with a `text/event-stream` response,
+/// and that the schemas referenced by the event streams are registered as components.
+#[test]
+fn test_openapi_spec_includes_sse_endpoints() {
+    let spec: Value = serde_json::from_str(&ApiDoc::build_openapi_json()).unwrap();
+
+    for path in SSE_PATHS {
+        let operations = spec["paths"][path]
+            .as_object()
+            .unwrap_or_else(|| panic!("Missing path {path} in OpenAPI spec"));
+        assert!(
+            operations
+                .values()
+                .any(|operation| operation["responses"]["200"]["content"]
+                    .get("text/event-stream")
+                    .is_some()),
+            "Path {path} is not documented as an event stream"
+        );
+    }
+
+    let schemas = &spec["components"]["schemas"];
+    for schema in [
+        "MessageSubmitStreamingResponseMessage",
+        "MessageSubmitStreamingResponseError",
+        "UserEvent",
+    ] {
+        assert!(
+            schemas.get(schema).is_some(),
+            "Missing schema {schema} in OpenAPI spec"
+        );
+    }
+}
+
+fn deployment_version(version: &str) -> DeploymentVersion {
+    DeploymentVersion(Some(version.to_string()), "test".to_string())
+}
+
+fn server_urls(spec: &utoipa::openapi::OpenApi) -> Vec<String> {
+    spec.servers
+        .iter()
+        .flatten()
+        .map(|server| server.url.clone())
+        .collect()
+}
+
+/// Test that the configured public base URLs are advertised as servers.
+///
+/// # Test Behavior
+/// Builds the spec for a deployment with two `http.public_base_url` entries, and verifies that
+/// both are listed as servers in order and with their description, and that the deployment
+/// version is included as `x-erato-version` in the info block.
+#[test]
+fn test_openapi_spec_uses_configured_public_base_urls() {
+    let mut config = hermetic_app_config(None, None);
+    config.environment = "production".to_string();
+    config.http.public_base_url = vec![
+        PublicBaseUrlConfig {
+            url: "https://chat.example.com".to_string(),
+            description: Some("Production".to_string()),
+        },
+        PublicBaseUrlConfig {
+            url: "https://chat-eu.example.com".to_string(),
+            description: None,
+        },
+    ];
+    let local_addr: SocketAddr = "127.0.0.1:3130".parse().unwrap();
+
+    let spec = ApiDoc::build_openapi_for_deployment(
+        &config,
+        Some(local_addr),
+        &deployment_version("2026.10.1"),
+    );
+
+    assert_eq!(
+        server_urls(&spec),
+        vec!["https://chat.example.com", "https://chat-eu.example.com"]
+    );
+    let servers = spec.servers.as_ref().unwrap();
+    assert_eq!(servers[0].description.as_deref(), Some("Production"));
+    assert_eq!(servers[1].description, None);
+
+    let spec: Value = serde_json::to_value(&spec).unwrap();
+    assert_eq!(spec["info"]["x-erato-version"], "2026.10.1");
+}
+
+/// Test the server list if no public base URL is configured.
+///
+/// # Test Behavior
+/// In the `development` environment, the address of the listener is advertised as server. In
+/// any other environment, the spec doesn't list any servers, so clients use the origin the spec
+/// was fetched from.
+#[test]
+fn test_openapi_spec_server_fallback_without_public_base_url() {
+    let mut config = hermetic_app_config(None, None);
+    let local_addr: SocketAddr = "127.0.0.1:3130".parse().unwrap();
+    let version = deployment_version("2026.10.1");
+
+    config.environment = "development".to_string();
+    let spec = ApiDoc::build_openapi_for_deployment(&config, Some(local_addr), &version);
+    assert_eq!(server_urls(&spec), vec!["http://127.0.0.1:3130"]);
+
+    config.environment = "production".to_string();
+    let spec = ApiDoc::build_openapi_for_deployment(&config, Some(local_addr), &version);
+    assert!(spec.servers.is_none());
+}
+
+/// Test that the served spec and API docs reflect the configured servers.
+///
+/// # Test Behavior
+/// Serves the docs routes for a spec with a configured public base URL, and verifies that both
+/// `/openapi.json` and the Scalar page at `/scalar` contain it.
+#[tokio::test]
+async fn test_api_docs_routes_serve_configured_servers() {
+    let mut config = hermetic_app_config(None, None);
+    config.http.public_base_url = vec![PublicBaseUrlConfig {
+        url: "https://chat.example.com".to_string(),
+        description: None,
+    }];
+    let spec =
+        ApiDoc::build_openapi_for_deployment(&config, None, &deployment_version("2026.10.1"));
+    let app: axum::Router = api_docs_routes(spec);
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let response = server.get("/openapi.json").await;
+    response.assert_status_ok();
+    let served: Value = response.json();
+    assert_eq!(served["servers"][0]["url"], "https://chat.example.com");
+    assert_eq!(served["info"]["x-erato-version"], "2026.10.1");
+
+    let response = server.get("/scalar").await;
+    response.assert_status_ok();
+    assert!(response.text().contains("https://chat.example.com"));
+}
+
+/// Test that API responses include the deployment version header.
+///
+/// # Test Categories
+/// - `uses-db`
+/// - `auth-required`
+///
+/// # Test Behavior
+/// Requests the profile of the user through the API router with a deployment version set, and
+/// verifies that the response carries it in the `X-Erato-Version` header.
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_api_responses_include_version_header(pool: Pool<Postgres>) {
+    let app_state = test_app_state(hermetic_app_config(None, None), pool).await;
+    let app: axum::Router = erato::server::router::router(app_state.clone())
+        .split_for_parts()
+        .0
+        .with_state(app_state)
+        .layer(Extension(deployment_version("build-42")));
+    let server = TestServer::new(app.into_make_service()).expect("Failed to create test server");
+
+    let response = server
+        .get("/api/v1beta/me/profile")
+        .with_bearer_token(TEST_JWT_TOKEN)
+        .await;
+    response.assert_status_ok();
+    assert_eq!(response.header(ERATO_VERSION_HEADER), "build-42");
+}
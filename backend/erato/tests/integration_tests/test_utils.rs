@@ -5,17 +5,22 @@
 
 #![allow(dead_code)]
 
+pub mod fixtures;
+
 use axum::Router;
 use axum_test::{TestResponse, TestServer};
 use erato::config::AppConfig;
 use erato::state::AppState;
+use mocktail::mock_builder::Then;
 use mocktail::prelude::*;
 use mocktail::server::MockServerConfig;
+use sea_orm::DatabaseConnection;
 use serde_json::{Value, json};
 use std::fs;
 use std::io::Write;
 use std::net::{IpAddr, Ipv4Addr};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tempfile::{Builder, NamedTempFile};
@@ -151,6 +156,9 @@ pub const TEST_USER_ISSUER: &str = "http://0.0.0.0:5556";
 /// Standard test user subject (matches the TEST_JWT_TOKEN).
 pub const TEST_USER_SUBJECT: &str = "CiQwOGE4Njg0Yi1kYjg4LTRiNzMtOTBhOS0zY2QxNjYxZjU0NjYSBWxvY2Fs";
 
+/// Group whose members are admins in configs prepared with [`configure_admin_group`].
+pub const ADMIN_GROUP_ID: &str = "erato-admins";
+
 /// Secret used for test JWT tokens. Since the backend relies on oauth2-proxy for JWT validation
 /// and doesn't validate signatures itself, this is just a placeholder.
 const TEST_JWT_SECRET: &[u8] = b"test-secret-key-for-integration-tests";
@@ -166,6 +174,7 @@ pub struct JwtTokenBuilder {
     preferred_language: Option<String>,
     tenant_preferred_language: Option<String>,
     organization_user_id: Option<String>,
+    tenant_id: Option<String>,
     groups: Vec<String>,
 }
 
@@ -180,6 +189,7 @@ impl Default for JwtTokenBuilder {
             preferred_language: None,
             tenant_preferred_language: None,
             organization_user_id: None,
+            tenant_id: None,
             groups: Vec::new(),
         }
     }
@@ -242,12 +252,24 @@ impl JwtTokenBuilder {
         self
     }
 
+    /// Set the tenant ID claim (Azure AD's "tid")
+    pub fn tenant_id(mut self, tid: impl Into<String>) -> Self {
+        self.tenant_id = Some(tid.into());
+        self
+    }
+
     /// Set the groups claim
     pub fn groups(mut self, groups: Vec<String>) -> Self {
         self.groups = groups;
         self
     }
 
+    /// Add the [`ADMIN_GROUP_ID`] to the groups claim
+    pub fn admin(mut self) -> Self {
+        self.groups.push(ADMIN_GROUP_ID.to_string());
+        self
+    }
+
     /// Build and encode the JWT token
     pub fn build(self) -> String {
         let mut claims = json!({
@@ -284,6 +306,10 @@ impl JwtTokenBuilder {
             claims["oid"] = Value::String(oid);
         }
 
+        if let Some(tid) = self.tenant_id {
+            claims["tid"] = Value::String(tid);
+        }
+
         if !self.groups.is_empty() {
             claims["groups"] = Value::Array(self.groups.into_iter().map(Value::String).collect());
         }
@@ -294,6 +320,11 @@ impl JwtTokenBuilder {
     }
 }
 
+/// A JWT of the standard test user as a member of the [`ADMIN_GROUP_ID`].
+pub fn admin_token() -> String {
+    JwtTokenBuilder::new().admin().build()
+}
+
 // ============================================================================
 // Test Server Helpers
 // ============================================================================
@@ -349,6 +380,41 @@ impl TestRequestAuthExt for axum_test::TestRequest {
     }
 }
 
+/// Creates an empty chat for the user of `token` through the API.
+///
+/// # Returns
+/// The ID of the chat
+pub async fn create_chat(server: &TestServer, token: &str) -> String {
+    let response = server
+        .post("/api/v1beta/me/chats")
+        .with_bearer_token(token)
+        .json(&json!({}))
+        .await;
+    response.assert_status_ok();
+    response.json::<Value>()["chat_id"]
+        .as_str()
+        .expect("Expected chat_id in response")
+        .to_string()
+}
+
+/// Submits a message through `/me/messages/submitstream` as the user of `token`, and asserts that
+/// the stream was accepted.
+///
+/// # Arguments
+/// * `request` - The body of the request, e.g. `json!({ "user_message": "Hello" })`
+///
+/// # Returns
+/// The response with the SSE events of the stream
+pub async fn submit_message(server: &TestServer, token: &str, request: &Value) -> TestResponse {
+    let response = server
+        .post("/api/v1beta/me/messages/submitstream")
+        .with_bearer_token(token)
+        .json(request)
+        .await;
+    response.assert_status_ok();
+    response
+}
+
 // ============================================================================
 // SSE (Server-Sent Events) Helpers
 // ============================================================================
@@ -447,6 +513,41 @@ pub fn extract_chat_id(events: &[Event]) -> Option<String> {
     })
 }
 
+/// Extracts the ID of the generated assistant message from the SSE events of a response.
+///
+/// # Returns
+/// The message ID of the `assistant_message_completed` event, panicking if there is none
+pub fn completed_assistant_message_id(response: &TestResponse) -> String {
+    parse_sse_events(response)
+        .iter()
+        .find_map(|event| {
+            if let Ok(json) = serde_json::from_str::<Value>(&event.data)
+                && json["message_type"] == "assistant_message_completed"
+            {
+                return json["message_id"].as_str().map(|s| s.to_string());
+            }
+            None
+        })
+        .expect("Expected assistant_message_completed event with message_id")
+}
+
+/// Parses the data of all SSE events of a response as JSON, skipping events without JSON data.
+pub fn submit_events(response: &TestResponse) -> Vec<Value> {
+    parse_sse_events(response)
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .collect()
+}
+
+/// Concatenates the text of the `text_delta` events parsed by [`submit_events`].
+pub fn streamed_text(events: &[Value]) -> String {
+    events
+        .iter()
+        .filter(|event| event["message_type"] == "text_delta")
+        .map(|event| event["new_text"].as_str().unwrap())
+        .collect()
+}
+
 /// Collects all text deltas from SSE events.
 ///
 /// # Arguments
@@ -482,10 +583,139 @@ pub fn extract_full_text(events: &[Event]) -> String {
     extract_text_deltas(events).join("")
 }
 
+/// Lists the message types of SSE events, in the order they were received.
+///
+/// Consecutive events of the same type (e.g. the `text_delta` events of a generation) are
+/// listed once.
+///
+/// # Arguments
+/// * `events` - Slice of parsed events
+///
+/// # Returns
+/// The message types of the events
+pub fn event_type_sequence(events: &[Event]) -> Vec<String> {
+    let mut message_types: Vec<String> = Vec::new();
+    for event in events {
+        let Ok(json) = serde_json::from_str::<Value>(&event.data) else {
+            continue;
+        };
+        let Some(message_type) = json["message_type"].as_str() else {
+            continue;
+        };
+        if message_types.last().map(String::as_str) != Some(message_type) {
+            message_types.push(message_type.to_string());
+        }
+    }
+    message_types
+}
+
+/// Asserts that SSE events contain the given message types in the given order.
+///
+/// Events of other types may occur in between, so that the assertion holds as further events
+/// are added to a stream.
+///
+/// # Arguments
+/// * `events` - Slice of parsed events
+/// * `expected` - The message types that must occur, in order
+///
+/// # Example
+/// ```no_run
+/// assert_event_sequence(
+///     &parse_sse_events(&response),
+///     &["user_message_saved", "assistant_message_started", "text_delta"],
+/// );
+/// ```
+pub fn assert_event_sequence(events: &[Event], expected: &[&str]) {
+    let received = event_type_sequence(events);
+    let mut remaining = received.iter();
+    for message_type in expected {
+        assert!(
+            remaining.any(|received_type| received_type == message_type),
+            "Expected the events {expected:?} in this order, but received {received:?}"
+        );
+    }
+}
+
+// ============================================================================
+// Database Helpers
+// ============================================================================
+
+/// Counts the SQL queries that are run on a database connection.
+///
+/// The counter is installed on a connection before it is handed to the code under test, e.g. the
+/// connection of an `AppState` before the test server is created from it. Queries of background
+/// tasks that use the same connection are counted as well.
+///
+/// # Example
+/// ```no_run
+/// let mut app_state = test_app_state(app_config, pool).await;
+/// let query_counter = QueryCounter::install(&mut app_state.db);
+/// let server = create_test_server(app_state);
+/// let (_, queries) = query_counter
+///     .count(server.get("/api/v1beta/me/recent_chats").with_bearer_token(TEST_JWT_TOKEN))
+///     .await;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct QueryCounter {
+    queries: Arc<AtomicUsize>,
+}
+
+impl QueryCounter {
+    /// Install a counter on the connection, replacing its query metrics callback.
+    pub fn install(db: &mut DatabaseConnection) -> Self {
+        let counter = Self::default();
+        let queries = counter.queries.clone();
+        db.set_metric_callback(move |_| {
+            queries.fetch_add(1, Ordering::SeqCst);
+        });
+        counter
+    }
+
+    /// Number of queries run since the counter was installed.
+    pub fn total(&self) -> usize {
+        self.queries.load(Ordering::SeqCst)
+    }
+
+    /// Run a block, and return its output with the number of queries run while it ran.
+    pub async fn count<T>(&self, block: impl IntoFuture<Output = T>) -> (T, usize) {
+        let before = self.total();
+        let output = block.await;
+        (output, self.total() - before)
+    }
+}
+
 // ============================================================================
 // Mock LLM Server Helpers
 // ============================================================================
 
+/// Base URL of the standalone mock LLM server (see `run_mock_llm_server.sh`).
+///
+/// Tests using it need the mock LLM server to be running, unlike the per-test mock servers set up
+/// by [`setup_mock_llm_server`].
+pub fn mock_llm_server_base_url() -> String {
+    std::env::var("TEST_MOCK_LLM_SERVER_BASE_URL")
+        .unwrap_or_else(|_| "http://127.0.0.1:44320".to_string())
+}
+
+/// Responds to a mocked chat completion request with a successful SSE stream of `actions`.
+///
+/// # Example
+/// ```no_run
+/// mocks.mock(|when, then| {
+///     when.post().path("/v1/chat/completions");
+///     mock_llm_sse_response(then, build_openai_text_streaming_response(&["Hello"]));
+/// });
+/// ```
+pub fn mock_llm_sse_response(then: Then, actions: Vec<BodyAction>) {
+    then.status(axum::http::StatusCode::OK)
+        .headers([
+            ("Content-Type", "text/event-stream"),
+            ("Cache-Control", "no-cache"),
+            ("Connection", "keep-alive"),
+        ])
+        .bytes_stream_with_delays(actions);
+}
+
 /// Helper function to build an OpenAI-compatible SSE streaming chunk.
 fn build_openai_chat_chunk(content: &str, finish_reason: Option<&str>) -> String {
     let delta = if content.is_empty() {
@@ -516,6 +746,15 @@ fn build_openai_chat_chunk(content: &str, finish_reason: Option<&str>) -> String
 
 /// Helper function to build a delayed streaming response with multiple chunks
 fn build_delayed_streaming_response(chunks: Vec<&str>, delay_ms: u64) -> Vec<BodyAction> {
+    build_delayed_streaming_response_with_finish_reason(chunks, delay_ms, "stop")
+}
+
+/// Like [`build_delayed_streaming_response`], but ends the stream with the given finish reason.
+fn build_delayed_streaming_response_with_finish_reason(
+    chunks: Vec<&str>,
+    delay_ms: u64,
+    finish_reason: &str,
+) -> Vec<BodyAction> {
     let mut actions = Vec::new();
 
     // First chunk is typically an empty role message
@@ -536,7 +775,7 @@ fn build_delayed_streaming_response(chunks: Vec<&str>, delay_ms: u64) -> Vec<Bod
 
     // Final chunk with finish_reason
     actions.push(BodyAction::Bytes(
-        build_openai_chat_chunk("", Some("stop")).into(),
+        build_openai_chat_chunk("", Some(finish_reason)).into(),
     ));
 
     // OpenAI sends a final [DONE] message
@@ -613,13 +852,7 @@ pub async fn setup_mock_llm_server(config: Option<MockLlmConfig>) -> (AppConfig,
         // Create a streaming response with the configured chunks
         let streaming_actions = build_delayed_streaming_response(chunk_refs, config.delay_ms);
 
-        then.status(axum::http::StatusCode::OK)
-            .headers([
-                ("Content-Type", "text/event-stream"),
-                ("Cache-Control", "no-cache"),
-                ("Connection", "keep-alive"),
-            ])
-            .bytes_stream_with_delays(streaming_actions);
+        mock_llm_sse_response(then, streaming_actions);
     });
     if config.audio_transcription_enabled {
         let transcription_text = config.audio_transcription_text.clone();
@@ -764,6 +997,19 @@ pub fn hermetic_app_config(
     app_config.build().unwrap().try_deserialize().unwrap()
 }
 
+/// Make the members of the [`ADMIN_GROUP_ID`] admins.
+pub fn configure_admin_group(app_config: &mut AppConfig) {
+    app_config.admin.groups = vec![ADMIN_GROUP_ID.to_string()];
+}
+
+/// A [`hermetic_app_config`] without a chat provider, in which the members of the
+/// [`ADMIN_GROUP_ID`] are admins.
+pub fn admin_app_config() -> AppConfig {
+    let mut app_config = hermetic_app_config(None, None);
+    configure_admin_group(&mut app_config);
+    app_config
+}
+
 // ============================================================================
 // Multi-turn Mock LLM Helpers
 // ============================================================================
@@ -859,11 +1105,112 @@ pub fn build_openai_tool_calls_streaming_response(
     actions
 }
 
+/// Builds an OpenAI-compatible SSE stream for an assistant turn that streams the
+/// given text chunks before calling the given tools.
+pub fn build_openai_text_and_tool_calls_streaming_response(
+    chunks: &[&str],
+    tool_calls: &[(&str, &str, Value)],
+) -> Vec<BodyAction> {
+    let mut actions = build_openai_tool_calls_streaming_response(tool_calls);
+    let text_actions = chunks.iter().map(|chunk| {
+        let text_chunk = json!({
+            "id": "chatcmpl-mock-123",
+            "object": "chat.completion.chunk",
+            "created": 1234567890,
+            "model": "gpt-3.5-turbo",
+            "choices": [{
+                "index": 0,
+                "delta": { "content": chunk },
+                "finish_reason": null
+            }]
+        });
+        BodyAction::Bytes(format!("data: {}\n\n", text_chunk).into())
+    });
+    // The text follows the role chunk
+    actions.splice(1..1, text_actions);
+    actions
+}
+
+/// Builds an OpenAI-compatible SSE stream for an assistant turn consisting of a
+/// single tool call, whose arguments are streamed in the given fragments with
+/// `delay_ms` between them.
+pub fn build_openai_fragmented_tool_call_streaming_response(
+    call_id: &str,
+    tool_name: &str,
+    argument_fragments: &[&str],
+    delay_ms: u64,
+) -> Vec<BodyAction> {
+    let chunk = |delta: Value, finish_reason: Option<&str>| {
+        let chunk = json!({
+            "id": "chatcmpl-mock-123",
+            "object": "chat.completion.chunk",
+            "created": 1234567890,
+            "model": "gpt-3.5-turbo",
+            "choices": [{
+                "index": 0,
+                "delta": delta,
+                "finish_reason": finish_reason
+            }]
+        });
+        BodyAction::Bytes(format!("data: {}\n\n", chunk).into())
+    };
+
+    let mut actions = vec![
+        chunk(json!({ "role": "assistant", "content": null }), None),
+        chunk(
+            json!({
+                "tool_calls": [{
+                    "index": 0,
+                    "id": call_id,
+                    "type": "function",
+                    "function": { "name": tool_name, "arguments": "" }
+                }]
+            }),
+            None,
+        ),
+    ];
+    for fragment in argument_fragments {
+        actions.push(BodyAction::Delay(Duration::from_millis(delay_ms)));
+        actions.push(chunk(
+            json!({
+                "tool_calls": [{
+                    "index": 0,
+                    "function": { "arguments": fragment }
+                }]
+            }),
+            None,
+        ));
+    }
+    actions.push(chunk(json!({}), Some("tool_calls")));
+    actions.push(BodyAction::Bytes("data: [DONE]\n\n".into()));
+
+    actions
+}
+
 /// Builds an OpenAI-compatible SSE stream for a plain text assistant turn.
 pub fn build_openai_text_streaming_response(chunks: &[&str]) -> Vec<BodyAction> {
     build_delayed_streaming_response(chunks.to_vec(), 0)
 }
 
+/// Builds an OpenAI-compatible SSE stream for a plain text assistant turn that was cut off
+/// at the max-token limit (`finish_reason: "length"`).
+pub fn build_openai_length_limited_streaming_response(chunks: &[&str]) -> Vec<BodyAction> {
+    build_delayed_streaming_response_with_finish_reason(chunks.to_vec(), 0, "length")
+}
+
+/// Builds an OpenAI-compatible SSE stream for a plain text assistant turn that is interrupted by
+/// a truncated chunk after the given chunks, which makes the stream fail mid-generation.
+pub fn build_openai_interrupted_streaming_response(chunks: &[&str]) -> Vec<BodyAction> {
+    let mut actions = vec![BodyAction::Bytes(build_openai_chat_chunk("", None).into())];
+    for chunk in chunks {
+        actions.push(BodyAction::Bytes(build_openai_chat_chunk(chunk, None).into()));
+    }
+    actions.push(BodyAction::Bytes(
+        "data: {\"id\": \"chatcmpl-mock-123\", \"choices\": [\n\n".into(),
+    ));
+    actions
+}
+
 fn request_body_string(req: &Request) -> String {
     String::from_utf8_lossy(&req.body().clone().as_bytes()).into_owned()
 }
@@ -939,6 +1286,20 @@ impl Matcher for RequestBodyRecorder {
     }
 }
 
+/// Mock LLM that records every request body with `recorder` and streams `chunks` as the response.
+pub fn recording_llm_mocks(
+    recorder: &RequestBodyRecorder,
+    chunks: &'static [&'static str],
+) -> MockSet {
+    let mut mocks = MockSet::new();
+    let recorder = recorder.clone();
+    mocks.mock(move |when, then| {
+        when.post().path("/v1/chat/completions").matcher(recorder);
+        mock_llm_sse_response(then, build_openai_text_streaming_response(chunks));
+    });
+    mocks
+}
+
 /// Always-matching matcher that records request headers it is evaluated
 /// against. Attach it last in a `when` chain so it only sees requests that
 /// passed the preceding matchers.
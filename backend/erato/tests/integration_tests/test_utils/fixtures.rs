@@ -0,0 +1,577 @@
+//! Builders for the users, assistants, chats and messages that integration tests start from.
+//!
+//! The builders go through the model layer like the API does, so the created records pass the
+//! same policy checks and schema validation as real ones, and they return handles with the IDs of
+//! what they created. Their contents are deterministic: the messages of a chat alternate between
+//! user and assistant messages, starting with a user message, and the `n`-th message of the main
+//! thread is always `"Message n"`.
+//!
+//! # Example
+//! ```no_run
+//! let user = FixtureUser::test_user().create(&app_state).await;
+//! let chat = FixtureChat::new(&user)
+//!     .with_messages(4)
+//!     .with_thread_branch(1, 2)
+//!     .create(&app_state)
+//!     .await;
+//! ```
+
+use chrono::{Duration, Utc};
+use erato::db::entity::{assistants, chats, messages, users};
+use erato::models::assistant::{VISIBILITY_PRIVATE, create_assistant};
+use erato::models::chat::get_or_create_chat;
+use erato::models::file_upload::create_file_upload;
+use erato::models::message::submit_message;
+use erato::models::user::get_or_create_user;
+use erato::policy::engine::PolicyEngine;
+use erato::policy::types::Subject;
+use erato::state::AppState;
+use sea_orm::prelude::Uuid;
+use sea_orm::{ActiveModelTrait, ActiveValue};
+use serde_json::{Value, json};
+
+use crate::test_utils::{JwtTokenBuilder, TEST_USER_ISSUER, TEST_USER_SUBJECT};
+
+/// A policy engine with the current data of the database, for the model functions that authorize
+/// against existing records.
+async fn policy_engine(app_state: &AppState) -> PolicyEngine {
+    let policy = PolicyEngine::new();
+    policy
+        .rebuild_data(&app_state.db, &app_state.config)
+        .await
+        .expect("Failed to build the policy data of the fixtures");
+    policy
+}
+
+// ============================================================================
+// Users
+// ============================================================================
+
+/// Builder for a user, created like on their first login.
+#[derive(Debug, Clone)]
+pub struct FixtureUser {
+    subject: String,
+    email: Option<String>,
+}
+
+impl FixtureUser {
+    /// A user with the given subject, issued by [`TEST_USER_ISSUER`].
+    pub fn new(subject: impl Into<String>) -> Self {
+        Self {
+            subject: subject.into(),
+            email: None,
+        }
+    }
+
+    /// The user that [`TEST_JWT_TOKEN`](crate::test_utils::TEST_JWT_TOKEN) authenticates.
+    pub fn test_user() -> Self {
+        Self::new(TEST_USER_SUBJECT).with_email("admin@example.com")
+    }
+
+    /// Set the email of the user.
+    pub fn with_email(mut self, email: impl Into<String>) -> Self {
+        self.email = Some(email.into());
+        self
+    }
+
+    /// Create the user, or return it if it already exists.
+    pub async fn create(self, app_state: &AppState) -> UserHandle {
+        let model = get_or_create_user(
+            &app_state.db,
+            TEST_USER_ISSUER,
+            &self.subject,
+            self.email.as_deref(),
+        )
+        .await
+        .expect("Failed to create fixture user");
+        UserHandle { id: model.id, model }
+    }
+}
+
+/// A user created by [`FixtureUser`].
+#[derive(Debug, Clone)]
+pub struct UserHandle {
+    pub id: Uuid,
+    pub model: users::Model,
+}
+
+impl UserHandle {
+    /// The policy subject of the user.
+    pub fn subject(&self) -> Subject {
+        Subject::User(self.id.to_string())
+    }
+
+    /// A JWT that authenticates the user.
+    pub fn token(&self) -> String {
+        self.token_builder().build()
+    }
+
+    /// A JWT that authenticates the user as a member of the
+    /// [`ADMIN_GROUP_ID`](crate::test_utils::ADMIN_GROUP_ID).
+    pub fn admin_token(&self) -> String {
+        self.token_builder().admin().build()
+    }
+
+    fn token_builder(&self) -> JwtTokenBuilder {
+        let builder = JwtTokenBuilder::new()
+            .issuer(&self.model.issuer)
+            .subject(&self.model.subject);
+        match &self.model.email {
+            Some(email) => builder.email(email),
+            None => builder,
+        }
+    }
+}
+
+// ============================================================================
+// Assistants
+// ============================================================================
+
+/// Builder for an assistant.
+#[derive(Debug, Clone)]
+pub struct FixtureAssistant {
+    owner: UserHandle,
+    name: String,
+    description: Option<String>,
+    prompt: String,
+    welcome_message: Option<String>,
+    visibility: String,
+}
+
+impl FixtureAssistant {
+    /// A private assistant of `owner`, with the given name.
+    pub fn new(owner: &UserHandle, name: impl Into<String>) -> Self {
+        Self {
+            owner: owner.clone(),
+            name: name.into(),
+            description: None,
+            prompt: "You are a helpful assistant.".to_string(),
+            welcome_message: None,
+            visibility: VISIBILITY_PRIVATE.to_string(),
+        }
+    }
+
+    /// Set the description of the assistant.
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the system prompt of the assistant.
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Set the welcome message of the assistant.
+    pub fn with_welcome_message(mut self, welcome_message: impl Into<String>) -> Self {
+        self.welcome_message = Some(welcome_message.into());
+        self
+    }
+
+    /// Set the visibility of the assistant, e.g. `VISIBILITY_ORGANIZATION`.
+    pub fn with_visibility(mut self, visibility: impl Into<String>) -> Self {
+        self.visibility = visibility.into();
+        self
+    }
+
+    /// Create the assistant.
+    pub async fn create(self, app_state: &AppState) -> AssistantHandle {
+        let model = create_assistant(
+            &app_state.db,
+            &PolicyEngine::new(),
+            &self.owner.subject(),
+            self.name,
+            self.description,
+            self.prompt,
+            None,
+            None,
+            None,
+            false,
+            self.welcome_message,
+            None,
+            None,
+            Vec::new(),
+            &self.visibility,
+        )
+        .await
+        .expect("Failed to create fixture assistant");
+        app_state.global_policy_engine.invalidate_data().await;
+        AssistantHandle { id: model.id, model }
+    }
+}
+
+/// An assistant created by [`FixtureAssistant`].
+#[derive(Debug, Clone)]
+pub struct AssistantHandle {
+    pub id: Uuid,
+    pub model: assistants::Model,
+}
+
+// ============================================================================
+// Chats
+// ============================================================================
+
+/// A thread that branches off the main thread of a chat, see [`FixtureChat::with_thread_branch`].
+#[derive(Debug, Clone, Copy)]
+struct ThreadBranch {
+    from_index: usize,
+    message_count: usize,
+}
+
+/// Builder for a chat with a thread of messages.
+#[derive(Debug, Clone)]
+pub struct FixtureChat {
+    owner: UserHandle,
+    assistant_id: Option<Uuid>,
+    title: Option<String>,
+    message_count: usize,
+    branches: Vec<ThreadBranch>,
+    file_names: Vec<String>,
+    message_spacing: Option<Duration>,
+}
+
+impl FixtureChat {
+    /// An empty chat of `owner`.
+    pub fn new(owner: &UserHandle) -> Self {
+        Self {
+            owner: owner.clone(),
+            assistant_id: None,
+            title: None,
+            message_count: 0,
+            branches: Vec::new(),
+            file_names: Vec::new(),
+            message_spacing: None,
+        }
+    }
+
+    /// Create the chat with an assistant.
+    pub fn with_assistant(mut self, assistant: &AssistantHandle) -> Self {
+        self.assistant_id = Some(assistant.id);
+        self
+    }
+
+    /// Set the title the user gave the chat.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Add a main thread of `count` messages.
+    pub fn with_messages(mut self, count: usize) -> Self {
+        self.message_count = count;
+        self
+    }
+
+    /// Add a thread of `count` messages that continues from the `from_index`-th message of the
+    /// main thread, replacing the message after it like an edit or regeneration does.
+    ///
+    /// Branches are added after the main thread in the order they are given, so the last one
+    /// becomes the active thread of the chat.
+    pub fn with_thread_branch(mut self, from_index: usize, count: usize) -> Self {
+        self.branches.push(ThreadBranch {
+            from_index,
+            message_count: count,
+        });
+        self
+    }
+
+    /// Upload files with the given names to the chat, and attach them to its first message.
+    ///
+    /// Only the file uploads are recorded, their contents are not written to the file storage.
+    pub fn with_files(mut self, file_names: &[&str]) -> Self {
+        self.file_names = file_names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    /// Backdate the messages so that they were created `spacing` apart, in the order they were
+    /// added, with the last one created now.
+    pub fn with_message_spacing(mut self, spacing: Duration) -> Self {
+        self.message_spacing = Some(spacing);
+        self
+    }
+
+    /// Create the chat with its messages and files.
+    pub async fn create(self, app_state: &AppState) -> ChatHandle {
+        let subject = self.owner.subject();
+        let (model, _) = get_or_create_chat(
+            &app_state.db,
+            &policy_engine(app_state).await,
+            &subject,
+            None,
+            &self.owner.id.to_string(),
+            self.assistant_id.as_ref(),
+            self.title,
+        )
+        .await
+        .expect("Failed to create fixture chat");
+
+        // The chat is authorized against the policy data from here on
+        let policy = policy_engine(app_state).await;
+        let mut file_upload_ids = Vec::new();
+        for file_name in &self.file_names {
+            let file_upload = create_file_upload(
+                &app_state.db,
+                &policy,
+                &subject,
+                &model.id,
+                file_name.clone(),
+                "seaweedfs".to_string(),
+                format!("fixtures/{}/{file_name}", model.id),
+            )
+            .await
+            .expect("Failed to create fixture file upload");
+            file_upload_ids.push(file_upload.id);
+        }
+
+        let mut created_ids: Vec<Uuid> = Vec::new();
+        let mut message_ids = Vec::new();
+        for index in 0..self.message_count {
+            let previous_message_id = message_ids.last();
+            let input_files_ids: &[Uuid] = if index == 0 {
+                file_upload_ids.as_slice()
+            } else {
+                &[]
+            };
+            let message = add_message(
+                app_state,
+                &policy,
+                &subject,
+                &model.id,
+                index,
+                format!("Message {index}"),
+                previous_message_id,
+                None,
+                input_files_ids,
+            )
+            .await;
+            message_ids.push(message.id);
+        }
+        created_ids.extend(&message_ids);
+
+        let mut branches = Vec::new();
+        for (branch_index, branch) in self.branches.iter().enumerate() {
+            assert!(
+                branch.from_index + 1 < message_ids.len(),
+                "A thread branch must replace a message of the main thread"
+            );
+            let mut branch_message_ids: Vec<Uuid> = Vec::new();
+            for offset in 0..branch.message_count {
+                let index = branch.from_index + 1 + offset;
+                let previous_message_id = branch_message_ids
+                    .last()
+                    .unwrap_or(&message_ids[branch.from_index]);
+                let replaced_message_id = (offset == 0).then(|| message_ids[index]);
+                let message = add_message(
+                    app_state,
+                    &policy,
+                    &subject,
+                    &model.id,
+                    index,
+                    format!("Branch {branch_index} message {index}"),
+                    Some(previous_message_id),
+                    replaced_message_id.as_ref(),
+                    &[],
+                )
+                .await;
+                branch_message_ids.push(message.id);
+            }
+            created_ids.extend(&branch_message_ids);
+            branches.push(ChatBranch {
+                from_message_id: message_ids[branch.from_index],
+                message_ids: branch_message_ids,
+            });
+        }
+
+        if let Some(spacing) = self.message_spacing {
+            backdate_messages(app_state, &created_ids, spacing).await;
+        }
+        app_state.global_policy_engine.invalidate_data().await;
+
+        ChatHandle {
+            id: model.id,
+            model,
+            message_ids,
+            branches,
+            file_upload_ids,
+        }
+    }
+}
+
+/// Submit the message at `index` of a thread, whose role follows from its position.
+///
+/// A message that replaces another one is its sibling, and for user messages also its edit.
+#[allow(clippy::too_many_arguments)]
+async fn add_message(
+    app_state: &AppState,
+    policy: &PolicyEngine,
+    subject: &Subject,
+    chat_id: &Uuid,
+    index: usize,
+    text: String,
+    previous_message_id: Option<&Uuid>,
+    replaced_message_id: Option<&Uuid>,
+    input_files_ids: &[Uuid],
+) -> messages::Model {
+    let role = if index % 2 == 0 { "user" } else { "assistant" };
+    let edit_of_message_id = replaced_message_id.filter(|_| role == "user");
+    submit_message(
+        &app_state.db,
+        policy,
+        subject,
+        chat_id,
+        json!({
+            "role": role,
+            "content": [{ "content_type": "text", "text": text }]
+        }),
+        previous_message_id,
+        replaced_message_id,
+        edit_of_message_id,
+        None,
+        input_files_ids,
+        None,
+        None,
+        None,
+        false,
+    )
+    .await
+    .expect("Failed to submit fixture message")
+}
+
+/// Set the creation time of the messages to `spacing` apart, with the last one created now.
+async fn backdate_messages(app_state: &AppState, message_ids: &[Uuid], spacing: Duration) {
+    let now = Utc::now();
+    for (index, message_id) in message_ids.iter().enumerate() {
+        let created_at = now - spacing * (message_ids.len() - 1 - index) as i32;
+        messages::ActiveModel {
+            id: ActiveValue::Unchanged(*message_id),
+            created_at: ActiveValue::Set(created_at.into()),
+            updated_at: ActiveValue::Set(created_at.into()),
+            ..Default::default()
+        }
+        .update(&app_state.db)
+        .await
+        .expect("Failed to backdate fixture message");
+    }
+}
+
+/// A branch of the thread of a chat created by [`FixtureChat`].
+#[derive(Debug, Clone)]
+pub struct ChatBranch {
+    /// The message of the main thread the branch continues from
+    pub from_message_id: Uuid,
+    /// The messages of the branch, oldest first
+    pub message_ids: Vec<Uuid>,
+}
+
+/// A chat created by [`FixtureChat`].
+#[derive(Debug, Clone)]
+pub struct ChatHandle {
+    pub id: Uuid,
+    pub model: chats::Model,
+    /// The messages of the main thread, oldest first
+    pub message_ids: Vec<Uuid>,
+    /// The branches of the thread, in the order they were added
+    pub branches: Vec<ChatBranch>,
+    /// The files uploaded to the chat
+    pub file_upload_ids: Vec<Uuid>,
+}
+
+impl ChatHandle {
+    /// The messages of the active thread, oldest first.
+    pub fn active_thread(&self) -> Vec<Uuid> {
+        let Some(branch) = self.branches.last() else {
+            return self.message_ids.clone();
+        };
+        let from_position = self
+            .message_ids
+            .iter()
+            .position(|id| *id == branch.from_message_id)
+            .expect("A branch continues from a message of the main thread");
+        let mut thread = self.message_ids[..=from_position].to_vec();
+        thread.extend(&branch.message_ids);
+        thread
+    }
+
+    /// The last message of the active thread, which new messages continue from.
+    pub fn last_message_id(&self) -> Option<Uuid> {
+        self.active_thread().last().copied()
+    }
+}
+
+// ============================================================================
+// Messages
+// ============================================================================
+
+/// Builder for a single message with arbitrary content, for tests that need other messages than
+/// the `"Message n"` threads of [`FixtureChat`].
+///
+/// Like every submitted message, the new message becomes the end of the active thread of its chat.
+#[derive(Debug, Clone)]
+pub struct FixtureMessage {
+    chat_id: Uuid,
+    owner_user_id: String,
+    raw_message: Value,
+    previous_message_id: Option<Uuid>,
+    input_files_ids: Vec<Uuid>,
+}
+
+impl FixtureMessage {
+    /// A message of `chat` with the given raw content, that starts a new thread.
+    pub fn new(chat: &ChatHandle, raw_message: Value) -> Self {
+        Self {
+            chat_id: chat.id,
+            owner_user_id: chat.model.owner_user_id.clone(),
+            raw_message,
+            previous_message_id: None,
+            input_files_ids: Vec::new(),
+        }
+    }
+
+    /// A message of `chat` with the given role and a single text part.
+    pub fn text(chat: &ChatHandle, role: &str, text: impl Into<String>) -> Self {
+        Self::new(
+            chat,
+            json!({
+                "role": role,
+                "content": [{ "content_type": "text", "text": text.into() }]
+            }),
+        )
+    }
+
+    /// Continue the thread from `previous_message_id`.
+    pub fn after(mut self, previous_message_id: Uuid) -> Self {
+        self.previous_message_id = Some(previous_message_id);
+        self
+    }
+
+    /// Attach already uploaded files to the message.
+    pub fn with_files(mut self, file_upload_ids: &[Uuid]) -> Self {
+        self.input_files_ids = file_upload_ids.to_vec();
+        self
+    }
+
+    /// Submit the message.
+    pub async fn create(self, app_state: &AppState) -> messages::Model {
+        let message = submit_message(
+            &app_state.db,
+            &policy_engine(app_state).await,
+            &Subject::User(self.owner_user_id),
+            &self.chat_id,
+            self.raw_message,
+            self.previous_message_id.as_ref(),
+            None,
+            None,
+            None,
+            &self.input_files_ids,
+            None,
+            None,
+            None,
+            false,
+        )
+        .await
+        .expect("Failed to submit fixture message");
+        app_state.global_policy_engine.invalidate_data().await;
+        message
+    }
+}
@@ -0,0 +1,77 @@
+use axum::{
+    extract::{Extension, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{
+    log,
+    matcher::{Matcher, Mock},
+    request_id::RequestId,
+};
+
+/// Body of the admin mocks endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockList {
+    /// Mocks in the order they are matched
+    pub mocks: Vec<Mock>,
+}
+
+/// List the currently active chat mocks
+pub async fn get_mocks(
+    State(matcher): State<Arc<Matcher>>,
+    Extension(request_id): Extension<RequestId>,
+) -> Json<MockList> {
+    let mocks = matcher.mocks();
+    log::log_request(
+        request_id.as_str(),
+        "GET",
+        "/admin/mocks",
+        &format!("Listing {} active mocks", mocks.len()),
+    );
+    Json(MockList { mocks })
+}
+
+/// Replace the active chat mocks
+///
+/// The new mocks are used for all subsequent requests, until they are replaced again or reset.
+pub async fn put_mocks(
+    State(matcher): State<Arc<Matcher>>,
+    Extension(request_id): Extension<RequestId>,
+    Json(body): Json<MockList>,
+) -> Json<MockList> {
+    log::log_request(
+        request_id.as_str(),
+        "PUT",
+        "/admin/mocks",
+        &format!(
+            "Replacing active mocks with: {}",
+            body.mocks
+                .iter()
+                .map(|mock| mock.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    );
+    matcher.replace_mocks(body.mocks);
+    Json(MockList {
+        mocks: matcher.mocks(),
+    })
+}
+
+/// Reset the active chat mocks to the ones the server was started with
+pub async fn reset_mocks(
+    State(matcher): State<Arc<Matcher>>,
+    Extension(request_id): Extension<RequestId>,
+) -> Json<MockList> {
+    matcher.reset_mocks();
+    let mocks = matcher.mocks();
+    log::log_request(
+        request_id.as_str(),
+        "POST",
+        "/admin/mocks/reset",
+        &format!("Reset to {} startup mocks", mocks.len()),
+    );
+    Json(MockList { mocks })
+}
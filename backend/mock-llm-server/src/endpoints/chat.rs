@@ -95,6 +95,8 @@ pub async fn chat_completions(
                 static_config.chunks,
                 static_config.delay_ms,
                 static_config.initial_delay_ms,
+                static_config.finish_reason.as_deref(),
+                static_config.usage.as_ref(),
             )
         }
         crate::matcher::ResponseConfig::ToolCall(tool_config) => {
@@ -109,6 +111,7 @@ pub async fn chat_completions(
                 tool_config.tool_name,
                 tool_config.arguments,
                 tool_config.delay_ms,
+                tool_config.usage.as_ref(),
             )
         }
         crate::matcher::ResponseConfig::ToolCalls(tool_calls_config) => {
@@ -123,8 +126,12 @@ pub async fn chat_completions(
             build_multiple_tool_calls_streaming_response(
                 tool_calls_config.tool_calls,
                 tool_calls_config.delay_ms,
+                tool_calls_config.usage.as_ref(),
             )
         }
+        crate::matcher::ResponseConfig::ProviderError(_) => {
+            unreachable!("ProviderError responses should be resolved into Error in matcher")
+        }
         crate::matcher::ResponseConfig::CiteFiles(_) => {
             unreachable!("CiteFiles responses should be resolved into Static in matcher")
         }
@@ -210,7 +217,11 @@ async fn non_streaming_chat_completion(
                 + static_config.delay_ms * static_config.chunks.len() as u64;
             tokio::time::sleep(std::time::Duration::from_millis(total_delay_ms)).await;
             log::log_response_complete(request_id.as_str());
-            Ok(Json(build_openai_chat_completion(&static_config.chunks.concat())).into_response())
+            Ok(Json(build_openai_chat_completion(
+                &static_config.chunks.concat(),
+                static_config.usage.as_ref(),
+            ))
+            .into_response())
         }
         ResponseConfig::ToolCall(tool_config) => {
             log::log_with_id(
@@ -222,10 +233,13 @@ async fn non_streaming_chat_completion(
             );
             tokio::time::sleep(std::time::Duration::from_millis(tool_config.delay_ms)).await;
             log::log_response_complete(request_id.as_str());
-            Ok(Json(build_openai_tool_calls_completion(&[ToolCallDef {
-                tool_name: tool_config.tool_name,
-                arguments: tool_config.arguments,
-            }]))
+            Ok(Json(build_openai_tool_calls_completion(
+                &[ToolCallDef {
+                    tool_name: tool_config.tool_name,
+                    arguments: tool_config.arguments,
+                }],
+                tool_config.usage.as_ref(),
+            ))
             .into_response())
         }
         ResponseConfig::ToolCalls(tool_calls_config) => {
@@ -240,9 +254,13 @@ async fn non_streaming_chat_completion(
             log::log_response_complete(request_id.as_str());
             Ok(Json(build_openai_tool_calls_completion(
                 &tool_calls_config.tool_calls,
+                tool_calls_config.usage.as_ref(),
             ))
             .into_response())
         }
+        ResponseConfig::ProviderError(_) => {
+            unreachable!("ProviderError responses should be resolved into Error in matcher")
+        }
         ResponseConfig::CiteFiles(_) => {
             unreachable!("CiteFiles responses should be resolved into Static in matcher")
         }
@@ -1,3 +1,4 @@
+pub mod admin;
 pub mod audio;
 pub mod chat;
 pub mod embeddings;
@@ -77,5 +77,15 @@ pub fn log_startup(addr: &str) {
         "POST".bright_cyan(),
         "/base-vertex/v1/publishers/google/{*path}".bright_yellow()
     );
+    println!(
+        "  {} {}",
+        "GET/PUT".bright_cyan(),
+        "/admin/mocks".bright_yellow()
+    );
+    println!(
+        "  {} {}",
+        "POST".bright_cyan(),
+        "/admin/mocks/reset".bright_yellow()
+    );
     println!();
 }
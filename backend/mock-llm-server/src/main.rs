@@ -59,14 +59,38 @@ async fn main() {
         )
         .init();
 
-    // Load configured mocks
-    let mocks = mocks::get_default_mocks();
+    // Load configured mocks, optionally combined with the mocks of a mocks file
+    let mut mocks = mocks::get_default_mocks();
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(path) = mocks::config::mocks_file_path(&args) {
+        let mocks_file = mocks::config::MocksFile::load(&path).unwrap_or_else(|e| panic!("{}", e));
+        println!(
+            "{}",
+            format!(
+                "Loaded {} mocks from {}{}",
+                mocks_file.mocks.len(),
+                path.display(),
+                if mocks_file.replace_defaults {
+                    " (replacing default mocks)"
+                } else {
+                    ""
+                }
+            )
+            .bright_white()
+        );
+        mocks = mocks_file.apply_to(mocks);
+    }
 
     let matcher = Arc::new(Matcher::new(mocks.clone()));
 
     // Build the router with all endpoints nested under /base-openai
     let app = Router::new()
         .route("/health", get(health))
+        .route(
+            "/admin/mocks",
+            get(endpoints::admin::get_mocks).put(endpoints::admin::put_mocks),
+        )
+        .route("/admin/mocks/reset", post(endpoints::admin::reset_mocks))
         .nest(
             "/base-openai",
             Router::new()
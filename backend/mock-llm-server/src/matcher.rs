@@ -3,6 +3,7 @@ use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashSet;
+use std::sync::RwLock;
 
 /// Static response configuration with chunks and delay
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -15,6 +16,38 @@ pub struct StaticResponseConfig {
     /// If None, uses delay_ms for all chunks
     #[serde(default)]
     pub initial_delay_ms: Option<u64>,
+    /// Optional finish reason of the final chunk (e.g. `length` for a response cut off at the
+    /// max-token limit). If None, `stop` is used
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+    /// Optional token usage to report. If None, a minimal usage block is reported for
+    /// non-streaming responses and none for streaming responses
+    #[serde(default)]
+    pub usage: Option<UsageConfig>,
+}
+
+/// Token usage block reported with a response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageConfig {
+    /// Number of prompt tokens
+    pub prompt_tokens: u32,
+    /// Number of completion tokens
+    pub completion_tokens: u32,
+    /// Total number of tokens. If None, the sum of prompt and completion tokens is used
+    #[serde(default)]
+    pub total_tokens: Option<u32>,
+    /// Number of the prompt tokens that are reported as read from the prompt cache
+    /// (`prompt_tokens_details.cached_tokens`)
+    #[serde(default)]
+    pub cached_tokens: Option<u32>,
+}
+
+impl UsageConfig {
+    /// Total number of tokens to report
+    pub fn total_tokens(&self) -> u32 {
+        self.total_tokens
+            .unwrap_or(self.prompt_tokens + self.completion_tokens)
+    }
 }
 
 /// Tool call response configuration
@@ -27,6 +60,9 @@ pub struct ToolCallResponseConfig {
     /// Delay before sending the tool call (in milliseconds)
     #[serde(default)]
     pub delay_ms: u64,
+    /// Optional token usage to report
+    #[serde(default)]
+    pub usage: Option<UsageConfig>,
 }
 
 /// Single tool call definition for multiple tool calls
@@ -46,6 +82,9 @@ pub struct ToolCallsResponseConfig {
     /// Delay before sending the tool calls (in milliseconds)
     #[serde(default)]
     pub delay_ms: u64,
+    /// Optional token usage to report
+    #[serde(default)]
+    pub usage: Option<UsageConfig>,
 }
 
 /// Error response configuration (non-streaming)
@@ -60,6 +99,52 @@ pub struct ErrorResponseConfig {
     pub initial_delay_ms: Option<u64>,
 }
 
+/// Provider error response configuration (non-streaming)
+///
+/// Builds an OpenAI-style error body (`{"error": {"message", "type", "param", "code"}}`),
+/// e.g. to simulate rate limits or context length errors of a provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderErrorResponseConfig {
+    /// HTTP status code to return
+    pub status_code: u16,
+    /// Error message of the provider
+    pub message: String,
+    /// Error type of the provider (e.g. `rate_limit_error`)
+    #[serde(default = "default_provider_error_type")]
+    pub error_type: String,
+    /// Optional error code of the provider (e.g. `context_length_exceeded`)
+    #[serde(default)]
+    pub code: Option<String>,
+    /// Optional parameter the error refers to
+    #[serde(default)]
+    pub param: Option<String>,
+    /// Optional delay before returning the error response (in milliseconds)
+    #[serde(default)]
+    pub initial_delay_ms: Option<u64>,
+}
+
+fn default_provider_error_type() -> String {
+    "invalid_request_error".to_string()
+}
+
+impl ProviderErrorResponseConfig {
+    /// Convert into a plain error response with the provider error body
+    pub fn to_error_response(&self) -> ErrorResponseConfig {
+        ErrorResponseConfig {
+            status_code: self.status_code,
+            body: serde_json::json!({
+                "error": {
+                    "message": self.message,
+                    "type": self.error_type,
+                    "param": self.param,
+                    "code": self.code,
+                }
+            }),
+            initial_delay_ms: self.initial_delay_ms,
+        }
+    }
+}
+
 /// Cite files response configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CiteFilesResponseConfig {
@@ -119,6 +204,8 @@ pub enum ResponseConfig {
     ToolCalls(ToolCallsResponseConfig),
     /// Error response with status code and JSON body
     Error(ErrorResponseConfig),
+    /// Error response with an OpenAI-style provider error body
+    ProviderError(ProviderErrorResponseConfig),
     /// Dynamic response listing erato-file links in request messages
     CiteFiles(CiteFilesResponseConfig),
     /// Dynamic long-running response configurable via `long running <seconds>`
@@ -181,11 +268,12 @@ pub enum MatchRule {
 }
 
 /// A mock with metadata, match rules, and response
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Mock {
     /// Name of the mock for identification
     pub name: String,
     /// Description of what this mock does
+    #[serde(default)]
     pub description: String,
     /// Match rules to determine if this mock should be used
     pub match_rules: Vec<MatchRule>,
@@ -328,6 +416,14 @@ impl Mock {
                     );
                 }
             }
+            ResponseConfig::ProviderError(config) => {
+                println!(
+                    "    {}: provider error \"{}\" with status {}",
+                    "Response".bold(),
+                    config.error_type,
+                    config.status_code
+                );
+            }
             ResponseConfig::CiteFiles(config) => {
                 println!(
                     "    {}: list erato-file links from request messages with {}ms delay",
@@ -358,8 +454,12 @@ impl Mock {
 }
 
 /// Matcher that finds the appropriate response for a chat request
+///
+/// The active mocks can be swapped at runtime (see the admin endpoints), while the mocks the
+/// matcher was created with are kept to be able to reset to them.
 pub struct Matcher {
-    mocks: Vec<Mock>,
+    mocks: RwLock<Vec<Mock>>,
+    initial_mocks: Vec<Mock>,
     default_response: ResponseConfig,
 }
 
@@ -383,11 +483,27 @@ impl Matcher {
         });
 
         Self {
-            mocks,
+            mocks: RwLock::new(mocks.clone()),
+            initial_mocks: mocks,
             default_response,
         }
     }
 
+    /// Get a snapshot of the currently active mocks
+    pub fn mocks(&self) -> Vec<Mock> {
+        self.mocks.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replace the active mocks
+    pub fn replace_mocks(&self, mocks: Vec<Mock>) {
+        *self.mocks.write().unwrap_or_else(|e| e.into_inner()) = mocks;
+    }
+
+    /// Restore the mocks the matcher was created with
+    pub fn reset_mocks(&self) {
+        self.replace_mocks(self.initial_mocks.clone());
+    }
+
     /// Match a chat request and return the appropriate response config
     pub fn match_request(
         &self,
@@ -395,11 +511,16 @@ impl Matcher {
         request_id: &str,
     ) -> ResponseConfig {
         // Try to find a matching mock
-        for mock in &self.mocks {
-            if self.matches_any_rule(&mock.match_rules, request) {
-                crate::log::log_with_id(request_id, &format!("Matched mock: {}", mock.name));
-                return self.resolve_response(&mock.response, request);
-            }
+        let matched = self
+            .mocks
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|mock| self.matches_any_rule(&mock.match_rules, request))
+            .cloned();
+        if let Some(mock) = matched {
+            crate::log::log_with_id(request_id, &format!("Matched mock: {}", mock.name));
+            return self.resolve_response(&mock.response, request);
         }
 
         crate::log::log_with_id(request_id, "No match found, using default response");
@@ -456,6 +577,9 @@ impl Matcher {
                     ..Default::default()
                 })
             }
+            ResponseConfig::ProviderError(config) => {
+                ResponseConfig::Error(config.to_error_response())
+            }
             _ => response.clone(),
         }
     }
@@ -1732,4 +1856,78 @@ mod tests {
             _ => panic!("Expected Static response"),
         }
     }
+
+    #[test]
+    fn test_matcher_replace_and_reset_mocks() {
+        let static_mock = |name: &str, pattern: &str, chunk: &str| Mock {
+            name: name.to_string(),
+            description: String::new(),
+            match_rules: vec![MatchRule::UserMessagePattern(MatchRuleUserMessagePattern {
+                pattern: pattern.to_string(),
+            })],
+            response: ResponseConfig::Static(StaticResponseConfig {
+                chunks: vec![chunk.to_string()],
+                ..Default::default()
+            }),
+        };
+        let matcher = Matcher::new(vec![static_mock("Initial", "hello", "Initial")]);
+        let request: ChatCompletionRequest = serde_json::from_value(json!({
+            "messages": [
+                {"role": "user", "content": "hello"}
+            ]
+        }))
+        .unwrap();
+        let first_chunk = |response: ResponseConfig| match response {
+            ResponseConfig::Static(config) => config.chunks[0].clone(),
+            _ => panic!("Expected Static response"),
+        };
+
+        matcher.replace_mocks(vec![static_mock("Replaced", "hello", "Replaced")]);
+        assert_eq!(matcher.mocks()[0].name, "Replaced");
+        assert_eq!(
+            first_chunk(matcher.match_request(&request, "test0003")),
+            "Replaced"
+        );
+
+        matcher.reset_mocks();
+        assert_eq!(matcher.mocks().len(), 1);
+        assert_eq!(
+            first_chunk(matcher.match_request(&request, "test0004")),
+            "Initial"
+        );
+    }
+
+    #[test]
+    fn test_provider_error_resolves_into_error_response() {
+        let matcher = Matcher::new(vec![Mock {
+            name: "RateLimited".to_string(),
+            description: String::new(),
+            match_rules: vec![MatchRule::UserMessagePattern(MatchRuleUserMessagePattern {
+                pattern: "rate limit".to_string(),
+            })],
+            response: ResponseConfig::ProviderError(ProviderErrorResponseConfig {
+                status_code: 429,
+                message: "Rate limit reached".to_string(),
+                error_type: "rate_limit_error".to_string(),
+                code: Some("rate_limit_exceeded".to_string()),
+                param: None,
+                initial_delay_ms: None,
+            }),
+        }]);
+        let request: ChatCompletionRequest = serde_json::from_value(json!({
+            "messages": [
+                {"role": "user", "content": "trigger a rate limit"}
+            ]
+        }))
+        .unwrap();
+
+        match matcher.match_request(&request, "test0005") {
+            ResponseConfig::Error(config) => {
+                assert_eq!(config.status_code, 429);
+                assert_eq!(config.body["error"]["type"], "rate_limit_error");
+                assert_eq!(config.body["error"]["code"], "rate_limit_exceeded");
+            }
+            _ => panic!("Expected Error response"),
+        }
+    }
 }
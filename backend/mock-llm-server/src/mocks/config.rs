@@ -0,0 +1,252 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::matcher::Mock;
+
+/// Environment variable pointing to a mocks file
+pub const MOCKS_FILE_ENV: &str = "MOCKS_FILE";
+/// CLI flag pointing to a mocks file (takes precedence over the environment variable)
+pub const MOCKS_FILE_FLAG: &str = "--mocks-file";
+
+/// Contents of a mocks file (JSON or YAML)
+///
+/// ```yaml
+/// replace_defaults: false
+/// mocks:
+///   - name: RateLimited
+///     match_rules:
+///       - UserMessagePattern:
+///           pattern: "rate limit"
+///     response:
+///       ProviderError:
+///         status_code: 429
+///         error_type: rate_limit_error
+///         message: Rate limit reached
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MocksFile {
+    /// Whether the mocks of the file replace the default mocks.
+    /// If false, they are matched before the default mocks.
+    #[serde(default)]
+    pub replace_defaults: bool,
+    /// Mocks defined in the file
+    #[serde(default)]
+    pub mocks: Vec<Mock>,
+}
+
+impl MocksFile {
+    /// Parse a mocks file; `.yaml`/`.yml` files are parsed as YAML, everything else as JSON
+    pub fn parse(path: &Path, contents: &str) -> Result<Self, String> {
+        let is_yaml = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                extension.eq_ignore_ascii_case("yaml") || extension.eq_ignore_ascii_case("yml")
+            });
+        if is_yaml {
+            serde_yaml::from_str(contents).map_err(|e| e.to_string())
+        } else {
+            serde_json::from_str(contents).map_err(|e| e.to_string())
+        }
+    }
+
+    /// Read and parse a mocks file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read mocks file {}: {}", path.display(), e))?;
+        Self::parse(path, &contents)
+            .map_err(|e| format!("Failed to parse mocks file {}: {}", path.display(), e))
+    }
+
+    /// Combine the mocks of this file with the default mocks
+    pub fn apply_to(self, default_mocks: Vec<Mock>) -> Vec<Mock> {
+        if self.replace_defaults {
+            return self.mocks;
+        }
+        let mut mocks = self.mocks;
+        mocks.extend(default_mocks);
+        mocks
+    }
+}
+
+/// Resolve the mocks file path from the CLI arguments (`--mocks-file <path>` or
+/// `--mocks-file=<path>`), falling back to the `MOCKS_FILE` environment variable
+pub fn mocks_file_path(args: &[String]) -> Option<PathBuf> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == MOCKS_FILE_FLAG {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix(&format!("{}=", MOCKS_FILE_FLAG)) {
+            return Some(PathBuf::from(path));
+        }
+    }
+
+    std::env::var(MOCKS_FILE_ENV)
+        .ok()
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::{MatchRule, ResponseConfig};
+    use crate::mocks::get_default_mocks;
+
+    #[test]
+    fn test_default_mocks_round_trip_json() {
+        let file = MocksFile {
+            replace_defaults: true,
+            mocks: get_default_mocks(),
+        };
+        let json = serde_json::to_string(&file).unwrap();
+        let parsed = MocksFile::parse(Path::new("mocks.json"), &json).unwrap();
+
+        assert!(parsed.replace_defaults);
+        assert_eq!(parsed.mocks.len(), file.mocks.len());
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+    }
+
+    #[test]
+    fn test_default_mocks_round_trip_yaml() {
+        let file = MocksFile {
+            replace_defaults: false,
+            mocks: get_default_mocks(),
+        };
+        let yaml = serde_yaml::to_string(&file).unwrap();
+        let parsed = MocksFile::parse(Path::new("mocks.yaml"), &yaml).unwrap();
+
+        assert_eq!(parsed.mocks.len(), file.mocks.len());
+        assert_eq!(serde_yaml::to_string(&parsed).unwrap(), yaml);
+    }
+
+    #[test]
+    fn test_parse_yaml_with_all_response_types() {
+        let yaml = r#"
+mocks:
+  - name: Static
+    description: Static chunks with usage
+    match_rules:
+      - UserMessagePattern:
+          pattern: "static"
+    response:
+      Static:
+        chunks: ["Hello", " world"]
+        delay_ms: 10
+        initial_delay_ms: 100
+        usage:
+          prompt_tokens: 12
+          completion_tokens: 2
+  - name: ToolCall
+    match_rules:
+      - LastMessageIsUserWithPattern:
+          pattern: "tool"
+    response:
+      ToolCall:
+        tool_name: list_files
+        arguments: "{}"
+  - name: ToolCalls
+    match_rules:
+      - AnyUserMessageInCurrentTurnWithPattern:
+          pattern: "tools"
+    response:
+      ToolCalls:
+        tool_calls:
+          - tool_name: read_file
+            arguments: '{"path":"a.txt"}'
+          - tool_name: read_file
+            arguments: '{"path":"b.txt"}'
+        delay_ms: 50
+  - name: Error
+    match_rules:
+      - AnySystemMessageWithPattern:
+          pattern: "error"
+    response:
+      Error:
+        status_code: 500
+        body: { "error": "boom" }
+  - name: ProviderError
+    match_rules:
+      - LastMessageIsToolResult
+      - AnyMessageContainsAudioContent: {}
+    response:
+      ProviderError:
+        status_code: 400
+        message: Too many tokens
+        code: context_length_exceeded
+"#;
+        let file = MocksFile::parse(Path::new("mocks.yml"), yaml).unwrap();
+
+        assert!(!file.replace_defaults);
+        assert_eq!(file.mocks.len(), 5);
+        match &file.mocks[0].response {
+            ResponseConfig::Static(config) => {
+                assert_eq!(config.chunks, vec!["Hello", " world"]);
+                assert_eq!(config.initial_delay_ms, Some(100));
+                assert_eq!(config.usage.unwrap().total_tokens(), 14);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        assert!(matches!(
+            file.mocks[1].response,
+            ResponseConfig::ToolCall(ref config) if config.delay_ms == 0
+        ));
+        assert!(matches!(
+            file.mocks[2].response,
+            ResponseConfig::ToolCalls(ref config) if config.tool_calls.len() == 2
+        ));
+        assert!(matches!(
+            file.mocks[3].response,
+            ResponseConfig::Error(ref config) if config.status_code == 500
+        ));
+        assert!(matches!(
+            file.mocks[4].match_rules[0],
+            MatchRule::LastMessageIsToolResult
+        ));
+        match &file.mocks[4].response {
+            ResponseConfig::ProviderError(config) => {
+                let error = config.to_error_response();
+                assert_eq!(error.status_code, 400);
+                assert_eq!(error.body["error"]["type"], "invalid_request_error");
+                assert_eq!(error.body["error"]["code"], "context_length_exceeded");
+                assert_eq!(error.body["error"]["message"], "Too many tokens");
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_apply_to_defaults() {
+        let defaults = get_default_mocks();
+        let file: MocksFile = serde_json::from_str(
+            r#"{"mocks": [{"name": "Custom", "match_rules": [], "response": {"Static": {"chunks": ["x"], "delay_ms": 0}}}]}"#,
+        )
+        .unwrap();
+
+        let combined = file.clone().apply_to(defaults.clone());
+        assert_eq!(combined.len(), defaults.len() + 1);
+        assert_eq!(combined[0].name, "Custom");
+
+        let replaced = MocksFile {
+            replace_defaults: true,
+            ..file
+        }
+        .apply_to(defaults);
+        assert_eq!(replaced.len(), 1);
+    }
+
+    #[test]
+    fn test_mocks_file_path_from_args() {
+        let args = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            mocks_file_path(&args(&["mock-llm-server", "--mocks-file", "a.yaml"])),
+            Some(PathBuf::from("a.yaml"))
+        );
+        assert_eq!(
+            mocks_file_path(&args(&["mock-llm-server", "--mocks-file=b.json"])),
+            Some(PathBuf::from("b.json"))
+        );
+    }
+}
@@ -1,3 +1,5 @@
+pub mod config;
+
 use crate::image_data;
 use crate::matcher::{
     CiteFilesResponseConfig, ErrorResponseConfig, ImageMock, LongRunningResponseConfig, MatchRule,
@@ -646,6 +648,34 @@ pub fn get_default_mocks() -> Vec<Mock> {
                 ],
                 delay_ms: 20,
                 initial_delay_ms: Some(5000),
+                finish_reason: None,
+                usage: None,
+            }),
+        },
+        Mock {
+            name: "MaxTokens".to_string(),
+            description:
+                "Returns a response cut off at the max-token limit (finish_reason: length), which can be continued"
+                    .to_string(),
+            match_rules: vec![MatchRule::LastMessageIsUserWithPattern(
+                MatchRuleLastMessageIsUserWithPattern {
+                    pattern: "max_tokens".to_string(),
+                },
+            )],
+            response: ResponseConfig::Static(StaticResponseConfig {
+                chunks: vec![
+                    "This".to_string(),
+                    " response".to_string(),
+                    " stops".to_string(),
+                    " in".to_string(),
+                    " the".to_string(),
+                    " middle".to_string(),
+                    " of".to_string(),
+                    " a".to_string(),
+                ],
+                delay_ms: 50,
+                finish_reason: Some("length".to_string()),
+                ..Default::default()
             }),
         },
         Mock {
@@ -801,6 +831,7 @@ pub fn get_default_mocks() -> Vec<Mock> {
                 tool_name: "read_text_file".to_string(),
                 arguments: r#"{"path":"./secret.txt"}"#.to_string(),
                 delay_ms: 100,
+                usage: None,
             }),
         },
         Mock {
@@ -817,6 +848,7 @@ pub fn get_default_mocks() -> Vec<Mock> {
                 tool_name: "read_file".to_string(),
                 arguments: r#"{"path":"docs/readme.txt"}"#.to_string(),
                 delay_ms: 100,
+                usage: None,
             }),
         },
         Mock {
@@ -834,6 +866,7 @@ pub fn get_default_mocks() -> Vec<Mock> {
                 arguments: r#"{"prompt":"A cute cat, studio lighting","num_images":1,"width":1024,"height":1024}"#
                     .to_string(),
                 delay_ms: 100,
+                usage: None,
             }),
         },
         Mock {
@@ -850,6 +883,7 @@ pub fn get_default_mocks() -> Vec<Mock> {
                 tool_name: "trigger_content_filter".to_string(),
                 arguments: "{}".to_string(),
                 delay_ms: 100,
+                usage: None,
             }),
         },
         Mock {
@@ -865,6 +899,7 @@ pub fn get_default_mocks() -> Vec<Mock> {
                 tool_name: "auth_none_probe".to_string(),
                 arguments: "{}".to_string(),
                 delay_ms: 100,
+                usage: None,
             }),
         },
         Mock {
@@ -881,6 +916,7 @@ pub fn get_default_mocks() -> Vec<Mock> {
                 tool_name: "auth_fixed_api_key_probe".to_string(),
                 arguments: "{}".to_string(),
                 delay_ms: 100,
+                usage: None,
             }),
         },
         Mock {
@@ -897,6 +933,7 @@ pub fn get_default_mocks() -> Vec<Mock> {
                 tool_name: "auth_forwarded_access_probe".to_string(),
                 arguments: "{}".to_string(),
                 delay_ms: 100,
+                usage: None,
             }),
         },
         Mock {
@@ -913,6 +950,7 @@ pub fn get_default_mocks() -> Vec<Mock> {
                 tool_name: "auth_forwarded_oidc_probe".to_string(),
                 arguments: "{}".to_string(),
                 delay_ms: 100,
+                usage: None,
             }),
         },
         Mock {
@@ -959,6 +997,7 @@ pub fn get_default_mocks() -> Vec<Mock> {
                     },
                 ],
                 delay_ms: 100,
+                usage: None,
             }),
         },
     ]
@@ -6,7 +6,7 @@ use std::time::Duration;
 
 use crate::matcher::{
     ResponseConfig, StaticResponseConfig, ToolCallDef, ToolCallResponseConfig,
-    ToolCallsResponseConfig,
+    ToolCallsResponseConfig, UsageConfig,
 };
 
 /// Build an OpenAI-compatible SSE streaming chunk
@@ -39,8 +39,44 @@ pub fn build_openai_chat_chunk(content: &str, finish_reason: Option<&str>) -> St
     chunk.to_string()
 }
 
+/// Build an OpenAI-compatible usage block, falling back to a minimal one
+pub fn build_openai_usage(usage: Option<&UsageConfig>) -> serde_json::Value {
+    let usage = usage.copied().unwrap_or(UsageConfig {
+        prompt_tokens: 1,
+        completion_tokens: 1,
+        total_tokens: None,
+        cached_tokens: None,
+    });
+    let mut usage_json = json!({
+        "prompt_tokens": usage.prompt_tokens,
+        "completion_tokens": usage.completion_tokens,
+        "total_tokens": usage.total_tokens()
+    });
+    if let Some(cached_tokens) = usage.cached_tokens {
+        usage_json["prompt_tokens_details"] = json!({ "cached_tokens": cached_tokens });
+    }
+    usage_json
+}
+
+/// Build the trailing usage chunk of a streaming response
+/// (as sent by OpenAI with `stream_options.include_usage`)
+pub fn build_openai_usage_chunk(usage: &UsageConfig) -> String {
+    json!({
+        "id": "chatcmpl-mock-123",
+        "object": "chat.completion.chunk",
+        "created": 1234567890,
+        "model": "gpt-3.5-turbo",
+        "choices": [],
+        "usage": build_openai_usage(Some(usage))
+    })
+    .to_string()
+}
+
 /// Build a non-streaming OpenAI-compatible chat completion body
-pub fn build_openai_chat_completion(content: &str) -> serde_json::Value {
+pub fn build_openai_chat_completion(
+    content: &str,
+    usage: Option<&UsageConfig>,
+) -> serde_json::Value {
     json!({
         "id": "chatcmpl-mock-123",
         "object": "chat.completion",
@@ -54,16 +90,15 @@ pub fn build_openai_chat_completion(content: &str) -> serde_json::Value {
             },
             "finish_reason": "stop"
         }],
-        "usage": {
-            "prompt_tokens": 1,
-            "completion_tokens": 1,
-            "total_tokens": 2
-        }
+        "usage": build_openai_usage(usage)
     })
 }
 
 /// Build a non-streaming OpenAI-compatible chat completion body with tool calls
-pub fn build_openai_tool_calls_completion(tool_calls: &[ToolCallDef]) -> serde_json::Value {
+pub fn build_openai_tool_calls_completion(
+    tool_calls: &[ToolCallDef],
+    usage: Option<&UsageConfig>,
+) -> serde_json::Value {
     let mut rng = rand::thread_rng();
     let calls: Vec<serde_json::Value> = tool_calls
         .iter()
@@ -99,11 +134,7 @@ pub fn build_openai_tool_calls_completion(tool_calls: &[ToolCallDef]) -> serde_j
             },
             "finish_reason": "tool_calls"
         }],
-        "usage": {
-            "prompt_tokens": 1,
-            "completion_tokens": 1,
-            "total_tokens": 2
-        }
+        "usage": build_openai_usage(usage)
     })
 }
 
@@ -277,6 +308,8 @@ pub fn build_delayed_streaming_response(
     chunks: Vec<String>,
     delay_ms: u64,
     initial_delay_ms: Option<u64>,
+    finish_reason: Option<&str>,
+    usage: Option<&UsageConfig>,
 ) -> Vec<StreamAction> {
     let mut actions = Vec::new();
 
@@ -300,9 +333,13 @@ pub fn build_delayed_streaming_response(
     // Final chunk with finish_reason
     actions.push(StreamAction::Bytes(build_openai_chat_chunk(
         "",
-        Some("stop"),
+        Some(finish_reason.unwrap_or("stop")),
     )));
 
+    if let Some(usage) = usage {
+        actions.push(StreamAction::Bytes(build_openai_usage_chunk(usage)));
+    }
+
     // OpenAI sends a final [DONE] message
     actions.push(StreamAction::Bytes("[DONE]".to_string()));
 
@@ -314,6 +351,7 @@ pub fn build_tool_call_streaming_response(
     tool_name: String,
     arguments: String,
     delay_ms: u64,
+    usage: Option<&UsageConfig>,
 ) -> Vec<StreamAction> {
     let mut actions = Vec::new();
 
@@ -343,6 +381,10 @@ pub fn build_tool_call_streaming_response(
         actions.push(StreamAction::Bytes(chunk.clone()));
     }
 
+    if let Some(usage) = usage {
+        actions.push(StreamAction::Bytes(build_openai_usage_chunk(usage)));
+    }
+
     // OpenAI sends a final [DONE] message
     actions.push(StreamAction::Bytes("[DONE]".to_string()));
 
@@ -353,6 +395,7 @@ pub fn build_tool_call_streaming_response(
 pub fn build_multiple_tool_calls_streaming_response(
     tool_calls: Vec<ToolCallDef>,
     delay_ms: u64,
+    usage: Option<&UsageConfig>,
 ) -> Vec<StreamAction> {
     let mut actions = Vec::new();
 
@@ -372,6 +415,10 @@ pub fn build_multiple_tool_calls_streaming_response(
         actions.push(StreamAction::Bytes(chunk.clone()));
     }
 
+    if let Some(usage) = usage {
+        actions.push(StreamAction::Bytes(build_openai_usage_chunk(usage)));
+    }
+
     // OpenAI sends a final [DONE] message
     actions.push(StreamAction::Bytes("[DONE]".to_string()));
 
@@ -387,19 +434,26 @@ pub async fn stream_response(
             static_config.chunks,
             static_config.delay_ms,
             static_config.initial_delay_ms,
+            static_config.finish_reason.as_deref(),
+            static_config.usage.as_ref(),
         ),
         ResponseConfig::Error(_) => Vec::new(),
         ResponseConfig::ToolCall(tool_config) => build_tool_call_streaming_response(
             tool_config.tool_name,
             tool_config.arguments,
             tool_config.delay_ms,
+            tool_config.usage.as_ref(),
         ),
         ResponseConfig::ToolCalls(tool_calls_config) => {
             build_multiple_tool_calls_streaming_response(
                 tool_calls_config.tool_calls,
                 tool_calls_config.delay_ms,
+                tool_calls_config.usage.as_ref(),
             )
         }
+        ResponseConfig::ProviderError(_) => {
+            unreachable!("ProviderError responses should be resolved into Error in matcher")
+        }
         ResponseConfig::CiteFiles(_) => {
             unreachable!("CiteFiles responses should be resolved into Static in matcher")
         }
@@ -498,7 +552,7 @@ mod tests {
 
     #[test]
     fn test_build_openai_chat_completion() {
-        let body = build_openai_chat_completion("Mock Summary Title");
+        let body = build_openai_chat_completion("Mock Summary Title", None);
         assert_eq!(body["object"], "chat.completion");
         assert_eq!(
             body["choices"][0]["message"]["content"],
@@ -509,10 +563,13 @@ mod tests {
 
     #[test]
     fn test_build_openai_tool_calls_completion() {
-        let body = build_openai_tool_calls_completion(&[ToolCallDef {
-            tool_name: "list_files".to_string(),
-            arguments: "{}".to_string(),
-        }]);
+        let body = build_openai_tool_calls_completion(
+            &[ToolCallDef {
+                tool_name: "list_files".to_string(),
+                arguments: "{}".to_string(),
+            }],
+            None,
+        );
         assert_eq!(body["object"], "chat.completion");
         assert_eq!(body["choices"][0]["finish_reason"], "tool_calls");
         assert_eq!(
@@ -531,7 +588,7 @@ mod tests {
     #[test]
     fn test_build_delayed_streaming_response() {
         let chunks = vec!["Hello".to_string(), " world".to_string()];
-        let actions = build_delayed_streaming_response(chunks, 50, None);
+        let actions = build_delayed_streaming_response(chunks, 50, None, None, None);
 
         // Should have: initial empty, 2 content chunks, delay before final, final chunk, [DONE]
         assert!(actions.len() >= 5);
@@ -544,6 +601,40 @@ mod tests {
         assert!(delay_count >= 1);
     }
 
+    #[test]
+    fn test_build_delayed_streaming_response_with_usage() {
+        let usage = UsageConfig {
+            prompt_tokens: 120,
+            completion_tokens: 30,
+            total_tokens: None,
+            cached_tokens: Some(100),
+        };
+        let actions =
+            build_delayed_streaming_response(vec!["Hi".to_string()], 0, None, None, Some(&usage));
+
+        let payloads: Vec<&String> = actions
+            .iter()
+            .filter_map(|a| match a {
+                StreamAction::Bytes(data) => Some(data),
+                StreamAction::Delay(_) => None,
+            })
+            .collect();
+        assert_eq!(payloads.last().unwrap().as_str(), "[DONE]");
+        let usage_chunk: serde_json::Value =
+            serde_json::from_str(payloads[payloads.len() - 2]).unwrap();
+        assert_eq!(usage_chunk["choices"].as_array().unwrap().len(), 0);
+        assert_eq!(usage_chunk["usage"]["prompt_tokens"], 120);
+        assert_eq!(usage_chunk["usage"]["completion_tokens"], 30);
+        assert_eq!(usage_chunk["usage"]["total_tokens"], 150);
+        assert_eq!(
+            usage_chunk["usage"]["prompt_tokens_details"]["cached_tokens"],
+            100
+        );
+
+        let body = build_openai_chat_completion("Hi", Some(&usage));
+        assert_eq!(body["usage"]["total_tokens"], 150);
+    }
+
     #[test]
     fn test_generate_mock_embedding() {
         let embedding = generate_mock_embedding(1536);
@@ -0,0 +1,613 @@
+//! Admin API of the mock MCP server: capture of tool calls and fault injection.
+//!
+//! Every `tools/call` request that reaches one of the `/mcp/*` endpoints is recorded in an
+//! in-memory ring buffer, which tests can read via `GET /admin/calls` and clear via
+//! `POST /admin/calls/reset`. The JSON-RPC method of every message, including `initialize` and
+//! `tools/list`, is recorded as well and can be read via `GET /admin/requests`. Clients can tag
+//! their messages with the `x-mock-mcp-client` header to tell them apart from the messages of
+//! other clients of the same server. Faults are configured per tool, either at startup via the
+//! `MOCK_MCP_FAULTS` environment variable (JSON) or at runtime via `PUT /admin/faults`, and are
+//! applied on the streamable HTTP transport before the call reaches the MCP server.
+
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header::CONTENT_LENGTH, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::time::{sleep, Duration};
+
+/// Environment variable with the fault configuration the server starts with, as JSON
+pub const FAULTS_ENV: &str = "MOCK_MCP_FAULTS";
+/// Number of calls kept in the call log; the oldest calls are dropped first
+const CALL_LOG_CAPACITY: usize = 1000;
+/// Maximum size of a request body that is inspected for tool calls
+const MAX_INSPECTED_BODY_BYTES: usize = 16 * 1024 * 1024;
+/// Header with which clients tag the messages recorded in the request log
+pub const CLIENT_HEADER: &str = "x-mock-mcp-client";
+
+/// A `tools/call` request received by one of the MCP endpoints
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapturedCall {
+    /// Path of the MCP endpoint that received the call, e.g. `/mcp/file`
+    pub endpoint: String,
+    /// Name of the called tool
+    pub tool: String,
+    /// Arguments of the call, as sent by the client
+    pub arguments: Value,
+    /// `_meta` of the call, as sent by the client
+    pub meta: Option<Value>,
+    /// When the call was received, in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+}
+
+/// Body of the admin calls endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallList {
+    /// Captured calls, the oldest first
+    pub calls: Vec<CapturedCall>,
+}
+
+/// A JSON-RPC request or notification received by one of the MCP endpoints
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapturedRequest {
+    /// Path of the MCP endpoint that received the message, e.g. `/mcp/file`
+    pub endpoint: String,
+    /// JSON-RPC method of the message, e.g. `initialize` or `tools/list`
+    pub method: String,
+    /// Value of the `x-mock-mcp-client` header of the HTTP request, if set
+    pub client: Option<String>,
+    /// When the message was received, in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+}
+
+/// Body of the admin requests endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestList {
+    /// Captured messages, the oldest first
+    pub requests: Vec<CapturedRequest>,
+}
+
+/// Faults injected into the calls of a tool
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolFault {
+    /// Delay in milliseconds before the call is handled
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// Probability (`0.0` to `1.0`) that the call fails with HTTP 500 instead of reaching the
+    /// MCP server
+    #[serde(default)]
+    pub error_rate: f64,
+    /// Close the connection after this many bytes of the response were sent
+    #[serde(default)]
+    pub disconnect_after_bytes: Option<usize>,
+    /// Never respond to the call
+    #[serde(default)]
+    pub hang: bool,
+}
+
+/// Fault configuration of the server
+///
+/// ```json
+/// { "tools": { "read_file": { "latency_ms": 2000, "error_rate": 0.5 } } }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FaultConfig {
+    /// Faults by tool name. Tools without an entry are not affected.
+    #[serde(default)]
+    pub tools: BTreeMap<String, ToolFault>,
+}
+
+impl FaultConfig {
+    /// Read the fault configuration from `MOCK_MCP_FAULTS`, if set
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var(FAULTS_ENV) {
+            Ok(faults) if !faults.trim().is_empty() => {
+                let config: Self = serde_json::from_str(&faults)
+                    .map_err(|e| format!("Failed to parse {FAULTS_ENV}: {e}"))?;
+                config
+                    .validate()
+                    .map_err(|e| format!("Invalid {FAULTS_ENV}: {e}"))?;
+                Ok(config)
+            }
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (tool, fault) in &self.tools {
+            if !(0.0..=1.0).contains(&fault.error_rate) {
+                return Err(format!(
+                    "error_rate of tool '{tool}' must be between 0.0 and 1.0, got {}",
+                    fault.error_rate
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Shared state of the admin API
+#[derive(Debug, Clone, Default)]
+pub struct AdminState {
+    calls: Arc<Mutex<VecDeque<CapturedCall>>>,
+    requests: Arc<Mutex<VecDeque<CapturedRequest>>>,
+    faults: Arc<Mutex<FaultConfig>>,
+}
+
+impl AdminState {
+    pub fn new(faults: FaultConfig) -> Self {
+        Self {
+            calls: Arc::default(),
+            requests: Arc::default(),
+            faults: Arc::new(Mutex::new(faults)),
+        }
+    }
+
+    fn record(&self, call: CapturedCall) {
+        let mut calls = self.calls.lock().unwrap();
+        if calls.len() == CALL_LOG_CAPACITY {
+            calls.pop_front();
+        }
+        calls.push_back(call);
+    }
+
+    fn calls(&self) -> Vec<CapturedCall> {
+        self.calls.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn record_request(&self, request: CapturedRequest) {
+        let mut requests = self.requests.lock().unwrap();
+        if requests.len() == CALL_LOG_CAPACITY {
+            requests.pop_front();
+        }
+        requests.push_back(request);
+    }
+
+    fn requests(&self) -> Vec<CapturedRequest> {
+        self.requests.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn faults(&self) -> FaultConfig {
+        self.faults.lock().unwrap().clone()
+    }
+
+    fn fault_for(&self, tool: &str) -> Option<ToolFault> {
+        self.faults.lock().unwrap().tools.get(tool).cloned()
+    }
+}
+
+/// List the captured tool calls
+pub async fn get_calls(State(admin): State<AdminState>) -> Json<CallList> {
+    Json(CallList {
+        calls: admin.calls(),
+    })
+}
+
+/// Clear the captured tool calls
+pub async fn reset_calls(State(admin): State<AdminState>) -> Json<CallList> {
+    admin.calls.lock().unwrap().clear();
+    Json(CallList { calls: Vec::new() })
+}
+
+/// List the captured JSON-RPC messages
+pub async fn get_requests(State(admin): State<AdminState>) -> Json<RequestList> {
+    Json(RequestList {
+        requests: admin.requests(),
+    })
+}
+
+/// Clear the captured JSON-RPC messages
+pub async fn reset_requests(State(admin): State<AdminState>) -> Json<RequestList> {
+    admin.requests.lock().unwrap().clear();
+    Json(RequestList {
+        requests: Vec::new(),
+    })
+}
+
+/// Get the active fault configuration
+pub async fn get_faults(State(admin): State<AdminState>) -> Json<FaultConfig> {
+    Json(admin.faults())
+}
+
+/// Replace the active fault configuration
+///
+/// The new faults apply to all subsequent calls. `PUT` an empty object to remove all faults.
+pub async fn put_faults(
+    State(admin): State<AdminState>,
+    Json(faults): Json<FaultConfig>,
+) -> Response {
+    if let Err(error) = faults.validate() {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": error }))).into_response();
+    }
+    *admin.faults.lock().unwrap() = faults.clone();
+    Json(faults).into_response()
+}
+
+/// Capture the tool calls sent to the MCP endpoints, and apply the faults of the called tools.
+pub async fn capture_and_inject_faults(
+    State(admin): State<AdminState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let endpoint = request.uri().path().to_string();
+    if request.method() != Method::POST || !endpoint.starts_with("/mcp/") {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_INSPECTED_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => {
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(json!({ "error": "request body too large" })),
+            )
+                .into_response()
+        }
+    };
+    let client = parts
+        .headers
+        .get(CLIENT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    for request in jsonrpc_requests(&endpoint, client, &body) {
+        admin.record_request(request);
+    }
+    let calls = tool_calls(&endpoint, &body);
+    let fault = calls.iter().find_map(|call| admin.fault_for(&call.tool));
+    for call in calls {
+        admin.record(call);
+    }
+    let request = Request::from_parts(parts, Body::from(body));
+
+    let Some(fault) = fault else {
+        return next.run(request).await;
+    };
+    if fault.latency_ms > 0 {
+        sleep(Duration::from_millis(fault.latency_ms)).await;
+    }
+    if fault.hang {
+        return std::future::pending().await;
+    }
+    if happens(fault.error_rate) {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": "simulated MCP transport failure" })),
+        )
+            .into_response();
+    }
+
+    let response = next.run(request).await;
+    match fault.disconnect_after_bytes {
+        Some(limit) => {
+            let (mut parts, body) = response.into_parts();
+            parts.headers.remove(CONTENT_LENGTH);
+            Response::from_parts(parts, disconnect_after(body, limit))
+        }
+        None => response,
+    }
+}
+
+/// The messages of a JSON-RPC message or batch.
+fn jsonrpc_messages(body: &[u8]) -> Vec<Value> {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(Value::Array(messages)) => messages,
+        Ok(message) => vec![message],
+        Err(_) => Vec::new(),
+    }
+}
+
+fn timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Extract the requests and notifications of a JSON-RPC message or batch.
+fn jsonrpc_requests(endpoint: &str, client: Option<String>, body: &[u8]) -> Vec<CapturedRequest> {
+    let timestamp_ms = timestamp_ms();
+    jsonrpc_messages(body)
+        .iter()
+        .filter_map(|message| message["method"].as_str())
+        .map(|method| CapturedRequest {
+            endpoint: endpoint.to_string(),
+            method: method.to_string(),
+            client: client.clone(),
+            timestamp_ms,
+        })
+        .collect()
+}
+
+/// Extract the `tools/call` requests of a JSON-RPC message or batch.
+fn tool_calls(endpoint: &str, body: &[u8]) -> Vec<CapturedCall> {
+    let timestamp_ms = timestamp_ms();
+    jsonrpc_messages(body)
+        .into_iter()
+        .filter(|message| message["method"] == "tools/call")
+        .map(|mut message| {
+            let mut params = message["params"].take();
+            CapturedCall {
+                endpoint: endpoint.to_string(),
+                tool: params["name"].as_str().unwrap_or_default().to_string(),
+                arguments: params
+                    .get_mut("arguments")
+                    .map(Value::take)
+                    .unwrap_or_default(),
+                meta: params.get("_meta").cloned(),
+                timestamp_ms,
+            }
+        })
+        .collect()
+}
+
+/// Whether an event with the given probability happens.
+///
+/// Uses the random keys of the hasher of the standard library, which is random enough for
+/// fault injection.
+fn happens(probability: f64) -> bool {
+    if probability <= 0.0 {
+        return false;
+    }
+    if probability >= 1.0 {
+        return true;
+    }
+    let sample = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+    sample < probability
+}
+
+/// Pass through the first `limit` bytes of a body, then fail it, which drops the connection.
+fn disconnect_after(body: Body, limit: usize) -> Body {
+    let stream = futures::stream::unfold(
+        (body.into_data_stream(), Some(limit), false),
+        |(mut stream, remaining, cut)| async move {
+            if cut {
+                return Some((Err(simulated_disconnect()), (stream, None, false)));
+            }
+            let remaining = remaining?;
+            let mut chunk = match stream.next().await? {
+                Ok(chunk) => chunk,
+                Err(err) => return Some((Err(io::Error::other(err)), (stream, None, false))),
+            };
+            if chunk.len() <= remaining {
+                let remaining = remaining - chunk.len();
+                return Some((Ok(chunk), (stream, Some(remaining), false)));
+            }
+            chunk.truncate(remaining);
+            Some((Ok(chunk), (stream, None, true)))
+        },
+    );
+    Body::from_stream(stream)
+}
+
+fn simulated_disconnect() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "simulated disconnect")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header::{ACCEPT, CONTENT_TYPE};
+    use axum::Router;
+    use tower::util::ServiceExt;
+
+    async fn send(app: &Router, method: Method, uri: &str, body: Option<Value>) -> Response {
+        let request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json, text/event-stream")
+            .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    async fn json_body(response: Response) -> Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn tool_call(tool: &str, arguments: Value) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": tool, "arguments": arguments, "_meta": { "progressToken": 7 } }
+        })
+    }
+
+    #[tokio::test]
+    async fn tool_calls_are_captured_until_reset() {
+        let app = crate::app_with_faults(FaultConfig::default());
+
+        send(
+            &app,
+            Method::POST,
+            "/mcp/file",
+            Some(json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {} })),
+        )
+        .await;
+        send(
+            &app,
+            Method::POST,
+            "/mcp/file",
+            Some(tool_call("read_file", json!({ "path": "docs/readme.txt" }))),
+        )
+        .await;
+
+        let calls = json_body(send(&app, Method::GET, "/admin/calls", None).await).await;
+        let calls = calls["calls"].as_array().unwrap();
+        assert_eq!(calls.len(), 1, "Got: {calls:?}");
+        assert_eq!(calls[0]["endpoint"], "/mcp/file");
+        assert_eq!(calls[0]["tool"], "read_file");
+        assert_eq!(calls[0]["arguments"], json!({ "path": "docs/readme.txt" }));
+        assert_eq!(calls[0]["meta"], json!({ "progressToken": 7 }));
+        assert!(calls[0]["timestamp_ms"].as_u64().unwrap() > 0);
+
+        let reset = send(&app, Method::POST, "/admin/calls/reset", None).await;
+        assert_eq!(reset.status(), StatusCode::OK);
+        let calls = json_body(send(&app, Method::GET, "/admin/calls", None).await).await;
+        assert_eq!(calls["calls"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn requests_are_captured_with_their_client() {
+        let app = crate::app_with_faults(FaultConfig::default());
+        let initialize = json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {} });
+        let request = axum::http::Request::builder()
+            .method(Method::POST)
+            .uri("/mcp/file")
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json, text/event-stream")
+            .header(CLIENT_HEADER, "test-client")
+            .body(Body::from(initialize.to_string()))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap();
+        send(
+            &app,
+            Method::POST,
+            "/mcp/file",
+            Some(tool_call("read_file", json!({ "path": "docs/readme.txt" }))),
+        )
+        .await;
+
+        let requests = json_body(send(&app, Method::GET, "/admin/requests", None).await).await;
+        let requests = requests["requests"].as_array().unwrap();
+        assert_eq!(requests.len(), 2, "Got: {requests:?}");
+        assert_eq!(requests[0]["endpoint"], "/mcp/file");
+        assert_eq!(requests[0]["method"], "initialize");
+        assert_eq!(requests[0]["client"], "test-client");
+        assert_eq!(requests[1]["method"], "tools/call");
+        assert_eq!(requests[1]["client"], Value::Null);
+
+        let reset = send(&app, Method::POST, "/admin/requests/reset", None).await;
+        assert_eq!(reset.status(), StatusCode::OK);
+        let requests = json_body(send(&app, Method::GET, "/admin/requests", None).await).await;
+        assert_eq!(requests["requests"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn faults_can_be_replaced_and_are_validated() {
+        let app = crate::app_with_faults(FaultConfig::default());
+        let faults = json!({ "tools": { "read_file": { "latency_ms": 50, "error_rate": 0.5 } } });
+
+        let response = send(&app, Method::PUT, "/admin/faults", Some(faults)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let active = json_body(send(&app, Method::GET, "/admin/faults", None).await).await;
+        assert_eq!(active["tools"]["read_file"]["latency_ms"], 50);
+        assert_eq!(active["tools"]["read_file"]["error_rate"], 0.5);
+        assert_eq!(active["tools"]["read_file"]["hang"], false);
+
+        let invalid = json!({ "tools": { "read_file": { "error_rate": 1.5 } } });
+        let response = send(&app, Method::PUT, "/admin/faults", Some(invalid)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let unchanged = json_body(send(&app, Method::GET, "/admin/faults", None).await).await;
+        assert_eq!(unchanged, active);
+    }
+
+    #[tokio::test]
+    async fn latency_and_error_faults_only_apply_to_their_tool() {
+        let mut faults = FaultConfig::default();
+        faults.tools.insert(
+            "read_file".to_string(),
+            ToolFault {
+                latency_ms: 100,
+                error_rate: 1.0,
+                ..Default::default()
+            },
+        );
+        let app = crate::app_with_faults(faults);
+
+        let started = std::time::Instant::now();
+        let response = send(
+            &app,
+            Method::POST,
+            "/mcp/file",
+            Some(tool_call("read_file", json!({ "path": "docs/readme.txt" }))),
+        )
+        .await;
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            json_body(response).await["error"],
+            "simulated MCP transport failure"
+        );
+
+        let response = send(
+            &app,
+            Method::POST,
+            "/mcp/file",
+            Some(tool_call("list_files", json!({}))),
+        )
+        .await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("simulated"));
+
+        // Calls that failed because of a fault are still captured
+        let calls = json_body(send(&app, Method::GET, "/admin/calls", None).await).await;
+        assert_eq!(calls["calls"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn hang_fault_never_responds() {
+        let mut faults = FaultConfig::default();
+        faults.tools.insert(
+            "read_file".to_string(),
+            ToolFault {
+                hang: true,
+                ..Default::default()
+            },
+        );
+        let app = crate::app_with_faults(faults);
+
+        let response = tokio::time::timeout(
+            Duration::from_millis(200),
+            send(
+                &app,
+                Method::POST,
+                "/mcp/file",
+                Some(tool_call("read_file", json!({ "path": "docs/readme.txt" }))),
+            ),
+        )
+        .await;
+        assert!(response.is_err(), "The call should not get a response");
+    }
+
+    #[tokio::test]
+    async fn disconnect_after_fails_the_body_after_the_limit() {
+        let mut stream = disconnect_after(Body::from("hello world"), 5).into_data_stream();
+        assert_eq!(stream.next().await.unwrap().unwrap(), "hello");
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+
+        let body = axum::body::to_bytes(disconnect_after(Body::from("hello"), 5), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "hello");
+    }
+
+    #[test]
+    fn happens_respects_the_bounds() {
+        assert!(!happens(0.0));
+        assert!(happens(1.0));
+    }
+}
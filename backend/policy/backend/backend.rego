@@ -14,17 +14,22 @@ package backend
 
 # `data` structure
 #
+# The data is partitioned by organization: a subject is only ever evaluated against the data of
+# their own organization, so the policy never sees resources of other organizations.
+#
 # resource_attributes := {
 #   "chat": {
 #     "some-chat-id": {
 #       "id": "some-chat-id",
-#       "owner_id": "some-user-id"
+#       "owner_id": "some-user-id",
+#       "organization_id": "some-organization-id" # or null
 #     }
 #   },
 #   "assistant": {
 #     "some-assistant-id": {
 #       "id": "some-assistant-id",
-#       "owner_id": "some-user-id"
+#       "owner_id": "some-user-id",
+#       "visibility": "private" # or "organization"
 #     }
 #   },
 #   "file_upload": {
@@ -45,7 +50,9 @@ package backend
 #     "subject_type": "user", # or "organization_group"
 #     "subject_id_type": "id", # or "organization_group_id"
 #     "subject_id": "some-user-id",
-#     "role": "viewer"
+#     "role": "viewer",
+#     "permission": "read", # or "edit"
+#     "expires_at": "2026-01-01T00:00:00+00:00" # or null
 #   }
 # ]
 #
@@ -75,7 +82,8 @@ package backend
 #   "subject_id": "some-user-id",
 #   "resource_kind": "chat",
 #   "resource_id": "some-chat-id",
-#   "action": "read"
+#   "action": "read",
+#   "organization_group_ids": ["some-group-id"]
 # }
 
 # Constants
@@ -161,6 +169,13 @@ can_read_assistant(assistant_id) if {
 	data.resource_attributes[resource_kind_assistant][assistant_id].owner_id == input.subject_id
 }
 
+# The data only contains the assistants of the organization of the subject, so an assistant that
+# is visible to the organization is readable by every user in the data.
+can_read_assistant(assistant_id) if {
+	data.resource_attributes[resource_kind_assistant][assistant_id].visibility == "organization"
+	assistant_share_grant_active(assistant_id)
+}
+
 assistant_hub_version_for_assistant(assistant_id) := version if {
 	some version in data.assistant_hub_versions
 	version.assistant_id == assistant_id
@@ -179,6 +194,7 @@ assistant_share_grant_active(assistant_id) if {
 
 can_read_assistant(assistant_id) if {
 	some grant in data.share_grants
+	share_grant_in_effect(grant)
 	grant.resource_type == "assistant"
 	grant.resource_id == assistant_id
 	grant.subject_type == "user"
@@ -187,6 +203,45 @@ can_read_assistant(assistant_id) if {
 	assistant_share_grant_active(assistant_id)
 }
 
+# A share grant is in effect until it expires. This is checked here, as the policy data is only
+# rebuilt when it is invalidated, not when a grant expires.
+share_grant_in_effect(grant) if {
+	object.get(grant, "expires_at", null) == null
+}
+
+share_grant_in_effect(grant) if {
+	time.parse_rfc3339_ns(grant.expires_at) > time.now_ns()
+}
+
+# A share grant applies to the subject if it was granted to the user or to one of their organization groups.
+share_grant_applies_to_subject(grant) if {
+	grant.subject_type == "user"
+	grant.subject_id == input.subject_id
+}
+
+share_grant_applies_to_subject(grant) if {
+	grant.subject_type == "organization_group"
+	some group_id in input.organization_group_ids
+	group_id == grant.subject_id
+}
+
+has_share_grant(resource_type, resource_id) if {
+	some grant in data.share_grants
+	share_grant_in_effect(grant)
+	grant.resource_type == resource_type
+	grant.resource_id == resource_id
+	share_grant_applies_to_subject(grant)
+}
+
+has_edit_share_grant(resource_type, resource_id) if {
+	some grant in data.share_grants
+	share_grant_in_effect(grant)
+	grant.resource_type == resource_type
+	grant.resource_id == resource_id
+	grant.permission == "edit"
+	share_grant_applies_to_subject(grant)
+}
+
 can_read_shared_chat(chat_id) if {
 	chat_sharing_enabled
 	has_enabled_share_link(resource_kind_chat, chat_id)
@@ -195,6 +250,7 @@ can_read_shared_chat(chat_id) if {
 
 can_read_assistant(assistant_id) if {
 	some grant in data.share_grants
+	share_grant_in_effect(grant)
 	grant.resource_type == "assistant"
 	grant.resource_id == assistant_id
 	grant.subject_type == "organization_group"
@@ -237,6 +293,25 @@ allow if {
 	can_read_shared_chat(input.resource_id)
 }
 
+# A user can read chats shared with them via a share grant.
+allow if {
+	input.subject_kind == subject_kind_user
+	input.subject_id != not_logged_in
+	input.resource_kind == resource_kind_chat
+	input.action == action_read
+	has_share_grant(resource_kind_chat, input.resource_id)
+}
+
+# A user can update and submit messages to chats shared with them with edit permission.
+# Sharing the chat further stays restricted to the owner.
+allow if {
+	input.subject_kind == subject_kind_user
+	input.subject_id != not_logged_in
+	input.resource_kind == resource_kind_chat
+	input.action in [action_update, action_submit_message]
+	has_edit_share_grant(resource_kind_chat, input.resource_id)
+}
+
 # A user can submit messages to chats they own.
 allow if {
 	# Ensure subject is a user and is logged in.
@@ -349,6 +424,17 @@ allow if {
 	can_read_shared_chat(chat_id)
 }
 
+# A user can read file uploads if one of the linked chats was shared with them via a share grant.
+allow if {
+	input.subject_kind == subject_kind_user
+	input.subject_id != not_logged_in
+	input.resource_kind == resource_kind_file_upload
+	input.action == action_read
+
+	some chat_id in data.resource_attributes[resource_kind_file_upload][input.resource_id].linked_chat_ids
+	has_share_grant(resource_kind_chat, chat_id)
+}
+
 # A user can read file uploads if they can access one of the linked assistants.
 allow if {
 	# Ensure subject is a user and is logged in.
@@ -376,6 +462,7 @@ allow if {
 
 	# Check if there's a share grant for this user and resource
 	some grant in data.share_grants
+	share_grant_in_effect(grant)
 	grant.resource_type == "assistant"
 	grant.resource_id == input.resource_id
 	grant.subject_type == "user"
@@ -396,6 +483,7 @@ allow if {
 
 	# Check if there's a share grant for an organization group
 	some grant in data.share_grants
+	share_grant_in_effect(grant)
 	grant.resource_type == "assistant"
 	grant.resource_id == input.resource_id
 	grant.subject_type == "organization_group"
@@ -407,6 +495,27 @@ allow if {
 	group_id == grant.subject_id
 }
 
+# A user can read assistants that are visible to their organization.
+allow if {
+	input.subject_kind == subject_kind_user
+	input.subject_id != not_logged_in
+	input.resource_kind == resource_kind_assistant
+	input.action == action_read
+	data.resource_attributes[resource_kind_assistant][input.resource_id].visibility == "organization"
+	assistant_share_grant_active(input.resource_id)
+}
+
+# A user can update assistants shared with them with edit permission.
+# Sharing the assistant further stays restricted to the owner.
+allow if {
+	input.subject_kind == subject_kind_user
+	input.subject_id != not_logged_in
+	input.resource_kind == resource_kind_assistant
+	input.action == action_update
+	has_edit_share_grant(resource_kind_assistant, input.resource_id)
+	assistant_share_grant_active(input.resource_id)
+}
+
 # A logged-in user can create an assistant.
 allow if {
 	# Ensure subject is a user and is logged in.
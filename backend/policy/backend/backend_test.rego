@@ -9,6 +9,7 @@ user_3_id := "user-3"
 chat_1_id := "chat-1"
 assistant_1_id := "assistant-1"
 assistant_2_id := "assistant-2"
+assistant_3_id := "assistant-3"
 assistant_hub_version_1_id := "assistant-hub-version-1"
 file_upload_1_id := "file-upload-1"
 file_upload_2_id := "file-upload-2"
@@ -39,6 +40,11 @@ resource_attributes := {
 			"id": assistant_2_id,
 			"owner_id": user_2_id,
 		},
+		assistant_3_id: {
+			"id": assistant_3_id,
+			"owner_id": user_1_id,
+			"visibility": "organization",
+		},
 		assistant_hub_version_1_id: {
 			"id": assistant_hub_version_1_id,
 			"owner_id": user_1_id,
@@ -234,6 +240,41 @@ share_grants_with_org_group := [
 	},
 ]
 
+# Share grants data - chat_1 is shared with user_2 for reading and with org-group-1 for editing,
+# assistant_1 is shared with user_3 for editing
+share_grants_with_permissions := [
+	{
+		"id": "grant-3",
+		"resource_type": "chat",
+		"resource_id": chat_1_id,
+		"subject_type": "user",
+		"subject_id_type": "id",
+		"subject_id": user_2_id,
+		"role": "viewer",
+		"permission": "read",
+	},
+	{
+		"id": "grant-4",
+		"resource_type": "chat",
+		"resource_id": chat_1_id,
+		"subject_type": "organization_group",
+		"subject_id_type": "organization_group_id",
+		"subject_id": org_group_1_id,
+		"role": "viewer",
+		"permission": "edit",
+	},
+	{
+		"id": "grant-5",
+		"resource_type": "assistant",
+		"resource_id": assistant_1_id,
+		"subject_type": "user",
+		"subject_id_type": "id",
+		"subject_id": user_3_id,
+		"role": "viewer",
+		"permission": "edit",
+	},
+]
+
 # A user can read their own chat.
 test_user_can_read_own_chat if {
 	backend.allow with input as {
@@ -471,6 +512,32 @@ test_viewer_can_read_shared_assistant if {
 		with data.share_grants as share_grants
 }
 
+# A share grant that expired no longer grants access, even before the policy data is rebuilt.
+test_viewer_cannot_read_assistant_with_expired_share_grant if {
+	expired_grant := object.union(share_grants[0], {"expires_at": "2020-01-01T00:00:00+00:00"})
+	not backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": user_2_id,
+		"resource_kind": "assistant",
+		"resource_id": assistant_1_id,
+		"action": "read",
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as [expired_grant]
+}
+
+# A share grant that expires in the future still grants access.
+test_viewer_can_read_assistant_with_unexpired_share_grant if {
+	grant := object.union(share_grants[0], {"expires_at": "2999-01-01T00:00:00+00:00"})
+	backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": user_2_id,
+		"resource_kind": "assistant",
+		"resource_id": assistant_1_id,
+		"action": "read",
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as [grant]
+}
+
 # A viewer cannot update the shared assistant.
 test_viewer_cannot_update_shared_assistant if {
 	not backend.allow with input as {
@@ -718,6 +785,44 @@ test_user_without_grant_cannot_read_assistant if {
 		with data.share_grants as share_grants
 }
 
+# --- Assistant Visibility Tests ---
+
+# Any user can read an assistant that is visible to their organization.
+test_user_can_read_organization_visible_assistant if {
+	backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": user_3_id,
+		"resource_kind": "assistant",
+		"resource_id": assistant_3_id,
+		"action": "read",
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as []
+}
+
+# Organization visibility doesn't allow other users to update the assistant.
+test_user_cannot_update_organization_visible_assistant if {
+	not backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": user_3_id,
+		"resource_kind": "assistant",
+		"resource_id": assistant_3_id,
+		"action": "update",
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as []
+}
+
+# Anonymous users cannot read an assistant that is visible to the organization.
+test_anonymous_user_cannot_read_organization_visible_assistant if {
+	not backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": "__not_logged_in__",
+		"resource_kind": "assistant",
+		"resource_id": assistant_3_id,
+		"action": "read",
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as []
+}
+
 # --- Assistant Creation Tests ---
 
 # A logged-in user can create an assistant.
@@ -842,6 +947,120 @@ test_user_in_org_group_cannot_update_shared_assistant if {
 		with data.share_grants as share_grants_with_org_group
 }
 
+# --- Share Grant Permission Tests ---
+
+# A read grantee can read the shared chat.
+test_read_grantee_can_read_shared_chat if {
+	backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": user_2_id,
+		"resource_kind": "chat",
+		"resource_id": chat_1_id,
+		"action": "read",
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as share_grants_with_permissions
+}
+
+# A read grantee cannot submit messages to the shared chat.
+test_read_grantee_cannot_submit_message_to_shared_chat if {
+	not backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": user_2_id,
+		"resource_kind": "chat",
+		"resource_id": chat_1_id,
+		"action": "submit_message",
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as share_grants_with_permissions
+}
+
+# A read grantee can read files linked to the shared chat.
+test_read_grantee_can_read_linked_file_via_shared_chat if {
+	backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": user_2_id,
+		"resource_kind": "file_upload",
+		"resource_id": file_upload_2_id,
+		"action": "read",
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as share_grants_with_permissions
+}
+
+# An edit grantee can update the shared chat.
+test_edit_grantee_can_update_shared_chat if {
+	backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": user_3_id,
+		"resource_kind": "chat",
+		"resource_id": chat_1_id,
+		"action": "update",
+		"organization_group_ids": [org_group_1_id],
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as share_grants_with_permissions
+}
+
+# An edit grantee can submit messages to the shared chat.
+test_edit_grantee_can_submit_message_to_shared_chat if {
+	backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": user_3_id,
+		"resource_kind": "chat",
+		"resource_id": chat_1_id,
+		"action": "submit_message",
+		"organization_group_ids": [org_group_1_id],
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as share_grants_with_permissions
+}
+
+# An edit grantee cannot share the shared chat further.
+test_edit_grantee_cannot_share_shared_chat if {
+	not backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": user_3_id,
+		"resource_kind": "chat",
+		"resource_id": chat_1_id,
+		"action": "share",
+		"organization_group_ids": [org_group_1_id],
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as share_grants_with_permissions
+}
+
+# A user outside of the grantee group cannot update the shared chat.
+test_user_not_in_edit_grantee_group_cannot_update_shared_chat if {
+	not backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": user_3_id,
+		"resource_kind": "chat",
+		"resource_id": chat_1_id,
+		"action": "update",
+		"organization_group_ids": [org_group_2_id],
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as share_grants_with_permissions
+}
+
+# An edit grantee can update the shared assistant.
+test_edit_grantee_can_update_shared_assistant if {
+	backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": user_3_id,
+		"resource_kind": "assistant",
+		"resource_id": assistant_1_id,
+		"action": "update",
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as share_grants_with_permissions
+}
+
+# An edit grantee cannot share the shared assistant further.
+test_edit_grantee_cannot_share_shared_assistant if {
+	not backend.allow with input as {
+		"subject_kind": "user",
+		"subject_id": user_3_id,
+		"resource_kind": "assistant",
+		"resource_id": assistant_1_id,
+		"action": "share",
+	} with data.resource_attributes as resource_attributes
+		with data.share_grants as share_grants_with_permissions
+}
+
 # --- Config Resource Permission Tests ---
 
 test_chat_provider_read_allowed_without_rules if {
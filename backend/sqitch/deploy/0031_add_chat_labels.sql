@@ -0,0 +1,43 @@
+-- Deploy erato:0031_add_chat_labels to pg
+
+BEGIN;
+
+-- Per-user labels for organizing chats in the sidebar.
+CREATE TABLE public.labels (
+    id uuid PRIMARY KEY DEFAULT public.uuidv7(),
+    owner_user_id uuid NOT NULL,
+    name text NOT NULL,
+    color text DEFAULT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT labels_owner_user_id_fkey
+        FOREIGN KEY (owner_user_id)
+        REFERENCES public.users (id)
+        ON DELETE CASCADE,
+    CONSTRAINT labels_owner_user_id_name_key UNIQUE (owner_user_id, name)
+);
+
+CREATE TRIGGER set_updated_at_column
+    BEFORE UPDATE ON public.labels
+    FOR EACH ROW
+    EXECUTE FUNCTION public.set_updated_at_column();
+
+CREATE TABLE public.chat_labels (
+    chat_id uuid NOT NULL,
+    label_id uuid NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    PRIMARY KEY (chat_id, label_id),
+    CONSTRAINT chat_labels_chat_id_fkey
+        FOREIGN KEY (chat_id)
+        REFERENCES public.chats (id)
+        ON DELETE CASCADE,
+    CONSTRAINT chat_labels_label_id_fkey
+        FOREIGN KEY (label_id)
+        REFERENCES public.labels (id)
+        ON DELETE CASCADE
+);
+
+-- Supports filtering recent chats by label.
+CREATE INDEX chat_labels_label_id_idx ON public.chat_labels (label_id);
+
+COMMIT;
@@ -0,0 +1,13 @@
+-- Deploy erato:0032_add_welcome_messages to pg
+
+BEGIN;
+
+-- Optional greeting shown as the first assistant message of new chats.
+ALTER TABLE public.assistants
+    ADD COLUMN welcome_message text DEFAULT NULL;
+
+-- Welcome messages are displayed in the chat but never sent to the LLM.
+ALTER TABLE public.messages
+    ADD COLUMN is_welcome_message boolean DEFAULT false NOT NULL;
+
+COMMIT;
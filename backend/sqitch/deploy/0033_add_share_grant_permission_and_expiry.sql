@@ -0,0 +1,18 @@
+-- Deploy erato:0033_add_share_grant_permission_and_expiry to pg
+
+BEGIN;
+
+-- Permission level of a share grant. 'read' keeps the previous behavior of
+-- read-only access, 'edit' additionally allows modifying the shared resource.
+ALTER TABLE public.share_grants ADD COLUMN permission text NOT NULL DEFAULT 'read';
+ALTER TABLE public.share_grants ADD CONSTRAINT share_grants_permission_check
+    CHECK (permission IN ('read', 'edit'));
+
+-- Optional point in time after which the share grant no longer grants access.
+ALTER TABLE public.share_grants ADD COLUMN expires_at timestamptz DEFAULT NULL;
+
+-- Index for the periodic cleanup of expired share grants
+CREATE INDEX idx_share_grants_expires_at ON public.share_grants (expires_at)
+    WHERE expires_at IS NOT NULL;
+
+COMMIT;
@@ -0,0 +1,12 @@
+-- Deploy erato:0034_add_messages_generation_created_at_index to pg
+
+BEGIN;
+
+-- Supports aggregating the token usage of generations over a date range
+-- (e.g. the budget report per assistant). Only messages with generation usage
+-- are indexed, and chat_id is included to join the chats without a heap lookup.
+CREATE INDEX idx_messages_generation_created_at ON public.messages (created_at)
+    INCLUDE (chat_id)
+    WHERE generation_metadata ? 'used_total_tokens';
+
+COMMIT;
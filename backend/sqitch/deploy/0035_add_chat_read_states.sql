@@ -0,0 +1,37 @@
+-- Deploy erato:0035_add_chat_read_states to pg
+
+BEGIN;
+
+-- Per-user read position in a chat, used to compute unread indicators across devices.
+-- Shared chats have one row per user that has read them.
+CREATE TABLE public.chat_read_states (
+    user_id uuid NOT NULL,
+    chat_id uuid NOT NULL,
+    last_read_message_id uuid NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL,
+    PRIMARY KEY (user_id, chat_id),
+    CONSTRAINT chat_read_states_user_id_fkey
+        FOREIGN KEY (user_id)
+        REFERENCES public.users (id)
+        ON DELETE CASCADE,
+    CONSTRAINT chat_read_states_chat_id_fkey
+        FOREIGN KEY (chat_id)
+        REFERENCES public.chats (id)
+        ON DELETE CASCADE,
+    CONSTRAINT chat_read_states_last_read_message_id_fkey
+        FOREIGN KEY (last_read_message_id)
+        REFERENCES public.messages (id)
+        ON DELETE CASCADE
+);
+
+CREATE TRIGGER set_updated_at_column
+    BEFORE UPDATE ON public.chat_read_states
+    FOR EACH ROW
+    EXECUTE FUNCTION public.set_updated_at_column();
+
+-- Supports counting unread assistant messages per chat in the recent chats listing.
+CREATE INDEX idx_messages_chat_id_created_at_active_thread ON public.messages (chat_id, created_at)
+    WHERE is_message_in_active_thread = true;
+
+COMMIT;
@@ -0,0 +1,11 @@
+-- Deploy erato:0036_add_facet_preferences_and_pinning to pg
+
+BEGIN;
+
+ALTER TABLE public.user_preferences
+    ADD COLUMN default_selected_facets text[];
+
+ALTER TABLE public.assistants
+    ADD COLUMN pinned_facet_ids text[];
+
+COMMIT;
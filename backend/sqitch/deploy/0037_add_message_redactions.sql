@@ -0,0 +1,34 @@
+-- Deploy erato:0037_add_message_redactions to pg
+
+BEGIN;
+
+-- Audit log of the redactions of message contents by admins.
+-- Only the locations of the redacted text are recorded, never the redacted text itself.
+CREATE TABLE public.message_redactions (
+    id uuid DEFAULT public.uuidv7() NOT NULL PRIMARY KEY,
+    message_id uuid NOT NULL,
+    -- Set if the redaction targeted a file attached to the message instead of its text.
+    file_upload_id uuid,
+    -- The admin that redacted the message.
+    actor_user_id uuid,
+    -- The redacted spans, as `[{content_index, start, end}]` character offsets.
+    spans jsonb NOT NULL,
+    replacement text NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT message_redactions_message_id_fkey
+        FOREIGN KEY (message_id)
+        REFERENCES public.messages (id)
+        ON DELETE CASCADE,
+    CONSTRAINT message_redactions_file_upload_id_fkey
+        FOREIGN KEY (file_upload_id)
+        REFERENCES public.file_uploads (id)
+        ON DELETE SET NULL,
+    CONSTRAINT message_redactions_actor_user_id_fkey
+        FOREIGN KEY (actor_user_id)
+        REFERENCES public.users (id)
+        ON DELETE SET NULL
+);
+
+CREATE INDEX idx_message_redactions_message_id ON public.message_redactions (message_id);
+
+COMMIT;
@@ -0,0 +1,20 @@
+-- Deploy erato:0038_add_organization_scoping to pg
+
+BEGIN;
+
+-- ID of the organization (tenant) a record belongs to, taken from the claim configured in
+-- `organization_claim`. NULL if no organization claim is configured, or the user has none.
+-- Existing records are backfilled with the organization of their owner on the owner's next login.
+ALTER TABLE public.users ADD COLUMN organization_id text DEFAULT NULL;
+ALTER TABLE public.chats ADD COLUMN organization_id text DEFAULT NULL;
+ALTER TABLE public.assistants ADD COLUMN organization_id text DEFAULT NULL;
+ALTER TABLE public.file_uploads ADD COLUMN organization_id text DEFAULT NULL;
+ALTER TABLE public.share_grants ADD COLUMN organization_id text DEFAULT NULL;
+
+CREATE INDEX idx_users_organization_id ON public.users (organization_id);
+CREATE INDEX idx_chats_organization_id ON public.chats (organization_id);
+CREATE INDEX idx_assistants_organization_id ON public.assistants (organization_id);
+CREATE INDEX idx_file_uploads_organization_id ON public.file_uploads (organization_id);
+CREATE INDEX idx_share_grants_organization_id ON public.share_grants (organization_id);
+
+COMMIT;
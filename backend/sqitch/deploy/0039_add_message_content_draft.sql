@@ -0,0 +1,13 @@
+-- Deploy erato:0039_add_message_content_draft to pg
+
+BEGIN;
+
+-- Content of an assistant message that is still being generated, periodically flushed while the
+-- answer is streamed. Cleared when the generation completes, and merged into `raw_message` if the
+-- generation ended without completing (e.g. because the process died).
+ALTER TABLE public.messages ADD COLUMN content_draft jsonb DEFAULT NULL;
+
+CREATE INDEX idx_messages_content_draft_updated_at ON public.messages (updated_at)
+    WHERE content_draft IS NOT NULL;
+
+COMMIT;
@@ -0,0 +1,34 @@
+-- Deploy erato:0040_add_file_pointer_migration to pg
+
+BEGIN;
+
+-- Progress of resumable maintenance tasks that walk over the messages table, one row per task.
+CREATE TABLE public.maintenance_task_progress (
+    task_name text NOT NULL PRIMARY KEY,
+    -- Position of the last processed message, in the order of (created_at, id).
+    cursor_created_at timestamp with time zone,
+    cursor_id uuid,
+    processed_messages bigint DEFAULT 0 NOT NULL,
+    migrated_messages bigint DEFAULT 0 NOT NULL,
+    bytes_saved bigint DEFAULT 0 NOT NULL,
+    last_error text,
+    started_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL,
+    completed_at timestamp with time zone
+);
+
+-- Messages whose stored generation input had inline file contents replaced with file pointers,
+-- with the size of the generation input before and after.
+CREATE TABLE public.message_file_pointer_migrations (
+    message_id uuid NOT NULL PRIMARY KEY,
+    replaced_parts integer NOT NULL,
+    bytes_before bigint NOT NULL,
+    bytes_after bigint NOT NULL,
+    migrated_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT message_file_pointer_migrations_message_id_fkey
+        FOREIGN KEY (message_id)
+        REFERENCES public.messages (id)
+        ON DELETE CASCADE
+);
+
+COMMIT;
@@ -0,0 +1,16 @@
+-- Deploy erato:0041_add_file_upload_storage_status to pg
+
+BEGIN;
+
+-- Whether the object of a file upload is available in the storage. Set to 'missing' when reading
+-- the object failed because it doesn't exist (e.g. after a manual cleanup or a bucket lifecycle
+-- rule), and back to 'ok' when it could be read again.
+ALTER TABLE public.file_uploads ADD COLUMN storage_status text NOT NULL DEFAULT 'ok';
+ALTER TABLE public.file_uploads ADD CONSTRAINT file_uploads_storage_status_check
+    CHECK (storage_status IN ('ok', 'missing'));
+
+-- Index for the admin report of missing files
+CREATE INDEX idx_file_uploads_storage_status_missing ON public.file_uploads (created_at)
+    WHERE storage_status = 'missing';
+
+COMMIT;
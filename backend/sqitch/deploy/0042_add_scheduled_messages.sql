@@ -0,0 +1,45 @@
+-- Deploy erato:0042_add_scheduled_messages to pg
+
+BEGIN;
+
+-- Messages that users scheduled to be submitted at a later time.
+CREATE TABLE public.scheduled_messages (
+    id uuid DEFAULT public.uuidv7() NOT NULL PRIMARY KEY,
+    owner_user_id uuid NOT NULL,
+    -- The submission, in the format of the body of `POST /me/messages/submitstream`.
+    request jsonb NOT NULL,
+    -- The ID token claims of the user at the time of scheduling, used to submit the message on
+    -- behalf of the user.
+    id_token_claims jsonb NOT NULL,
+    -- When the message is due. Moved forward when a failed attempt is retried.
+    run_at timestamp with time zone NOT NULL,
+    status text DEFAULT 'pending' NOT NULL,
+    attempts integer DEFAULT 0 NOT NULL,
+    last_error text,
+    -- The chat the message was submitted to, and the generated answer.
+    chat_id uuid,
+    assistant_message_id uuid,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT scheduled_messages_status_check
+        CHECK (status IN ('pending', 'running', 'succeeded', 'failed')),
+    CONSTRAINT scheduled_messages_owner_user_id_fkey
+        FOREIGN KEY (owner_user_id)
+        REFERENCES public.users (id)
+        ON DELETE CASCADE,
+    CONSTRAINT scheduled_messages_chat_id_fkey
+        FOREIGN KEY (chat_id)
+        REFERENCES public.chats (id)
+        ON DELETE SET NULL,
+    CONSTRAINT scheduled_messages_assistant_message_id_fkey
+        FOREIGN KEY (assistant_message_id)
+        REFERENCES public.messages (id)
+        ON DELETE SET NULL
+);
+
+CREATE INDEX idx_scheduled_messages_owner_user_id ON public.scheduled_messages (owner_user_id, run_at);
+-- Only covers the rows the scheduler still has to pick up.
+CREATE INDEX idx_scheduled_messages_due ON public.scheduled_messages (run_at)
+    WHERE status IN ('pending', 'running');
+
+COMMIT;
@@ -0,0 +1,22 @@
+-- Deploy erato:0043_add_generation_cache to pg
+
+BEGIN;
+
+-- Generations that are replayed for identical requests (see `chat.generation_cache`).
+CREATE TABLE public.generation_cache_entries (
+    -- SHA-256 of the request sent to the chat provider, together with the chat provider ID and
+    -- the generation options.
+    cache_key text NOT NULL PRIMARY KEY,
+    chat_provider_id text NOT NULL,
+    -- The generated content parts of the assistant message.
+    content jsonb NOT NULL,
+    -- The token usage of the original generation, as reported by the chat provider.
+    usage jsonb,
+    hit_count integer DEFAULT 0 NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    last_hit_at timestamp with time zone
+);
+
+CREATE INDEX idx_generation_cache_entries_created_at ON public.generation_cache_entries (created_at);
+
+COMMIT;
@@ -0,0 +1,32 @@
+-- Deploy erato:0044_add_file_deletions to pg
+
+BEGIN;
+
+-- 'deleted': the owner deleted the contents of a file that is still referenced by messages or
+-- assistants. The row is kept for the references, but the object was removed from the storage.
+ALTER TABLE public.file_uploads DROP CONSTRAINT file_uploads_storage_status_check;
+ALTER TABLE public.file_uploads ADD CONSTRAINT file_uploads_storage_status_check
+    CHECK (storage_status IN ('ok', 'missing', 'deleted'));
+
+-- Audit log of the deletions of files by their owners.
+CREATE TABLE public.file_deletions (
+    id uuid DEFAULT public.uuidv7() NOT NULL PRIMARY KEY,
+    -- Not a foreign key, as the file upload is removed unless it is still referenced.
+    file_upload_id uuid NOT NULL,
+    filename text NOT NULL,
+    -- The user that deleted the file.
+    actor_user_id uuid,
+    -- Whether the file was still referenced, and only its contents were deleted.
+    forced boolean NOT NULL,
+    -- The resources referencing the file at the time of the deletion, as `[{kind, id}]`.
+    referencing_resources jsonb NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT file_deletions_actor_user_id_fkey
+        FOREIGN KEY (actor_user_id)
+        REFERENCES public.users (id)
+        ON DELETE SET NULL
+);
+
+CREATE INDEX idx_file_deletions_file_upload_id ON public.file_deletions (file_upload_id);
+
+COMMIT;
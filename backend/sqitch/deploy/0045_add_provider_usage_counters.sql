@@ -0,0 +1,28 @@
+-- Deploy erato:0045_add_provider_usage_counters to pg
+
+BEGIN;
+
+-- Usage of a chat provider by a user per day (UTC), counted against the quotas configured in
+-- `chat_providers.providers.<provider-id>.user_quota`. Weekly usage is the sum of the days of the
+-- week, so windows reset without any cleanup.
+CREATE TABLE public.provider_usage_counters (
+    user_id uuid NOT NULL,
+    chat_provider_id text NOT NULL,
+    window_start date NOT NULL,
+    messages bigint DEFAULT 0 NOT NULL,
+    tokens bigint DEFAULT 0 NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL,
+    PRIMARY KEY (user_id, chat_provider_id, window_start),
+    CONSTRAINT provider_usage_counters_user_id_fkey
+        FOREIGN KEY (user_id)
+        REFERENCES public.users (id)
+        ON DELETE CASCADE
+);
+
+CREATE TRIGGER set_updated_at_column
+    BEFORE UPDATE ON public.provider_usage_counters
+    FOR EACH ROW
+    EXECUTE FUNCTION public.set_updated_at_column();
+
+COMMIT;
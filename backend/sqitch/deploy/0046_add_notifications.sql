@@ -0,0 +1,27 @@
+-- Deploy erato:0046_add_notifications to pg
+
+BEGIN;
+
+-- Notifications of the in-app inbox of users, e.g. about resources shared with them.
+CREATE TABLE public.notifications (
+    id uuid DEFAULT public.uuidv7() NOT NULL PRIMARY KEY,
+    user_id uuid NOT NULL,
+    -- The kind of the notification, e.g. 'share_grant_created'. Determines the shape of `payload`.
+    kind text NOT NULL,
+    payload jsonb NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    read_at timestamp with time zone,
+    CONSTRAINT notifications_user_id_fkey
+        FOREIGN KEY (user_id)
+        REFERENCES public.users (id)
+        ON DELETE CASCADE
+);
+
+-- Listing of the inbox, unread notifications first.
+CREATE INDEX idx_notifications_user_id_unread_created_at
+    ON public.notifications (user_id, (read_at IS NULL) DESC, created_at DESC);
+-- Deletion of old read notifications.
+CREATE INDEX idx_notifications_read_at ON public.notifications (read_at)
+    WHERE read_at IS NOT NULL;
+
+COMMIT;
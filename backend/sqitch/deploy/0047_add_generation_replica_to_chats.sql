@@ -0,0 +1,11 @@
+-- Deploy erato:0047_add_generation_replica_to_chats to pg
+
+BEGIN;
+
+-- The backend replica that holds the lease of the in-flight generation, so
+-- that other replicas don't start a second generation in the same chat, and
+-- the assistant message the generation produces once it was created.
+ALTER TABLE public.chats ADD COLUMN generation_replica_id text DEFAULT NULL;
+ALTER TABLE public.chats ADD COLUMN generation_message_id uuid DEFAULT NULL;
+
+COMMIT;
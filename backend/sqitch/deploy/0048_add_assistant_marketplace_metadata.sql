@@ -0,0 +1,19 @@
+-- Deploy erato:0048_add_assistant_marketplace_metadata to pg
+
+BEGIN;
+
+-- Metadata for browsing the assistants of an organization. The category refers to one of the
+-- categories configured in `assistants.categories`. Assistants with the 'organization'
+-- visibility are readable by all users of their organization without a share grant, and
+-- featured assistants are curated by admins.
+ALTER TABLE public.assistants ADD COLUMN category text DEFAULT NULL;
+ALTER TABLE public.assistants ADD COLUMN tags text[] NOT NULL DEFAULT '{}';
+ALTER TABLE public.assistants ADD COLUMN visibility text NOT NULL DEFAULT 'private';
+ALTER TABLE public.assistants ADD CONSTRAINT assistants_visibility_check
+    CHECK (visibility IN ('private', 'organization'));
+ALTER TABLE public.assistants ADD COLUMN featured boolean NOT NULL DEFAULT false;
+
+CREATE INDEX idx_assistants_visibility_organization ON public.assistants (organization_id)
+    WHERE visibility = 'organization' AND archived_at IS NULL;
+
+COMMIT;
@@ -0,0 +1,29 @@
+-- Deploy erato:0049_normalize_message_ordering to pg
+
+BEGIN;
+
+-- The timestamps of messages are already stored as timestamptz, i.e. as absolute instants, so
+-- no data has to be rewritten. Messages created within the same instant are ordered by their
+-- ID, which is a UUIDv7 and therefore increases with the time of creation.
+
+-- Supports listing and keyset pagination of the messages of a chat.
+CREATE INDEX idx_messages_chat_id_created_at_id ON public.messages (chat_id, created_at, id);
+
+CREATE OR REPLACE VIEW chats_latest_message AS
+SELECT
+    chat_id,
+    id AS latest_message_id,
+    created_at AS latest_message_at
+FROM (
+    SELECT
+        m.chat_id,
+        m.id,
+        m.created_at,
+        ROW_NUMBER() OVER (PARTITION BY m.chat_id ORDER BY m.created_at DESC, m.id DESC) AS rn
+    FROM
+        messages m
+) ranked_messages
+WHERE
+    rn = 1;
+
+COMMIT;
@@ -0,0 +1,10 @@
+-- Deploy erato:0050_add_assistant_snapshot_to_chats to pg
+
+BEGIN;
+
+-- Copy of the configuration of the assistant of a chat as it was when the chat
+-- was created, so the chat can be reproduced after the assistant was edited or
+-- deleted.
+ALTER TABLE public.chats ADD COLUMN assistant_snapshot jsonb DEFAULT NULL;
+
+COMMIT;
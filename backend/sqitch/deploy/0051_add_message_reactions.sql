@@ -0,0 +1,25 @@
+-- Deploy erato:0051_add_message_reactions to pg
+
+BEGIN;
+
+-- Emoji reactions of users on messages, visible to everyone that can read the chat.
+-- A user can react with several emojis, but with each emoji only once per message.
+CREATE TABLE public.message_reactions (
+    id uuid DEFAULT public.uuidv7() NOT NULL PRIMARY KEY,
+    message_id uuid NOT NULL,
+    user_id uuid NOT NULL,
+    emoji text NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT message_reactions_message_id_user_id_emoji_key
+        UNIQUE (message_id, user_id, emoji),
+    CONSTRAINT message_reactions_message_id_fkey
+        FOREIGN KEY (message_id)
+        REFERENCES public.messages (id)
+        ON DELETE CASCADE,
+    CONSTRAINT message_reactions_user_id_fkey
+        FOREIGN KEY (user_id)
+        REFERENCES public.users (id)
+        ON DELETE CASCADE
+);
+
+COMMIT;
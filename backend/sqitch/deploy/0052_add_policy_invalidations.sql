@@ -0,0 +1,33 @@
+-- Deploy erato:0052_add_policy_invalidations to pg
+
+BEGIN;
+
+-- Outbox of changes that invalidate the policy data of all backend instances, e.g. a created
+-- chat or a new share grant. Rows are written in the same transaction as the change itself, so
+-- an invalidation is visible exactly when the change is. Instances are notified on the
+-- `policy_invalidations` channel, and poll the table for rows they may have missed.
+CREATE TABLE public.policy_invalidations (
+    id bigserial NOT NULL PRIMARY KEY,
+    reason text NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+CREATE INDEX policy_invalidations_created_at_idx
+    ON public.policy_invalidations (created_at);
+
+CREATE FUNCTION public.notify_policy_invalidation() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify(
+        'policy_invalidations',
+        json_build_object('id', NEW.id, 'created_at', NEW.created_at)::text
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER notify_policy_invalidation
+    AFTER INSERT ON public.policy_invalidations
+    FOR EACH ROW
+    EXECUTE FUNCTION public.notify_policy_invalidation();
+
+COMMIT;
@@ -0,0 +1,16 @@
+-- Deploy erato:0053_add_edit_of_message_id_to_messages to pg
+
+BEGIN;
+
+-- The message a user message is an edited version of. Unlike `sibling_message_id`, which is
+-- set for edits and regenerations alike, it is only set for edits, so the previous versions of
+-- an edited message can be followed back to the original. Edits made before this column existed
+-- are not recorded.
+ALTER TABLE public.messages
+    ADD COLUMN edit_of_message_id uuid DEFAULT NULL REFERENCES public.messages(id);
+
+CREATE INDEX idx_messages_edit_of_message_id
+    ON public.messages (edit_of_message_id)
+    WHERE edit_of_message_id IS NOT NULL;
+
+COMMIT;
@@ -0,0 +1,15 @@
+-- Deploy erato:0054_add_provisional_to_chats to pg
+
+BEGIN;
+
+-- Chats that are created together with their first message are provisional until that message
+-- is saved, so that a failed submission doesn't leave an empty chat behind. Provisional chats
+-- are not listed, and are deleted by the cleanup worker when they are older than an hour.
+ALTER TABLE public.chats
+    ADD COLUMN provisional boolean NOT NULL DEFAULT false;
+
+CREATE INDEX idx_chats_provisional_created_at
+    ON public.chats (created_at)
+    WHERE provisional;
+
+COMMIT;
@@ -0,0 +1,16 @@
+-- Deploy erato:0055_add_usage_reporting_installation to pg
+
+BEGIN;
+
+-- The random ID of the installation that anonymous usage reports are signed with (see
+-- `telemetry.usage_reporting`). It holds at most one row, which is created when usage reporting
+-- is enabled, and deleted when it is disabled again. `last_reported_at` ensures that only one
+-- backend instance sends a report per interval.
+CREATE TABLE public.usage_reporting_installation (
+    id uuid NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+    singleton boolean NOT NULL DEFAULT true UNIQUE CHECK (singleton),
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    last_reported_at timestamp with time zone
+);
+
+COMMIT;
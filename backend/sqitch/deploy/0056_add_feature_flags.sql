@@ -0,0 +1,20 @@
+-- Deploy erato:0056_add_feature_flags to pg
+
+BEGIN;
+
+-- Feature flags that admins can change at runtime and roll out to a subset of users. Members of
+-- a group of `group_overrides` (an object of group IDs to booleans) get the value of the group.
+-- Otherwise, the users in the `percentage_rollout` bucket have the flag enabled, and all other
+-- users get `default_on`. Flags named after a config toggle use the config value while they have
+-- no row.
+CREATE TABLE public.feature_flags (
+    key text NOT NULL PRIMARY KEY,
+    description text,
+    default_on boolean NOT NULL DEFAULT false,
+    group_overrides jsonb NOT NULL DEFAULT '{}'::jsonb,
+    percentage_rollout smallint CHECK (percentage_rollout BETWEEN 0 AND 100),
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+COMMIT;
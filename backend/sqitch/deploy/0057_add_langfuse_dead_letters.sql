@@ -0,0 +1,16 @@
+-- Deploy erato:0057_add_langfuse_dead_letters to pg
+
+BEGIN;
+
+-- Ingestion batches that could not be delivered to Langfuse, retried in the order of `id`. The
+-- backend caps the number of rows and drops the oldest ones first.
+CREATE TABLE public.langfuse_dead_letters (
+    id bigserial PRIMARY KEY,
+    batch jsonb NOT NULL,
+    attempts integer NOT NULL DEFAULT 0,
+    last_error text,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+COMMIT;
@@ -0,0 +1,14 @@
+-- Deploy erato:0058_add_archived_generation_inputs to pg
+
+BEGIN;
+
+-- Generation inputs of old messages in archived chats, compressed with zstd. The
+-- `generation_input_messages` of the message is replaced with a small pointer to the row, and
+-- restored from it when the message is used for a generation again.
+CREATE TABLE public.archived_generation_inputs (
+    message_id uuid PRIMARY KEY REFERENCES public.messages(id) ON DELETE CASCADE,
+    compressed_input bytea NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+COMMIT;
@@ -0,0 +1,29 @@
+-- Deploy erato:0060_add_ownership_transfers to pg
+
+BEGIN;
+
+-- Audit log of the transfers of chats and assistants to another owner.
+CREATE TABLE public.ownership_transfers (
+    id uuid DEFAULT public.uuidv7() NOT NULL PRIMARY KEY,
+    -- `chat` or `assistant`
+    resource_type text NOT NULL,
+    resource_id uuid NOT NULL,
+    -- Owners are recorded as they were stored on the resource, as the previous owner may have
+    -- been removed since.
+    previous_owner_user_id text NOT NULL,
+    new_owner_user_id text NOT NULL,
+    -- The user that transferred the resource, or NULL for transfers through the CLI.
+    actor_user_id uuid,
+    keep_previous_owner_access boolean NOT NULL,
+    rewrite_attribution boolean NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT ownership_transfers_actor_user_id_fkey
+        FOREIGN KEY (actor_user_id)
+        REFERENCES public.users (id)
+        ON DELETE SET NULL
+);
+
+CREATE INDEX idx_ownership_transfers_resource
+    ON public.ownership_transfers (resource_type, resource_id);
+
+COMMIT;
@@ -0,0 +1,11 @@
+-- Deploy erato:0061_add_custom_instruction_to_chats to pg
+
+BEGIN;
+
+-- Instruction of the user that is added to the prompt of every generation of the chat, after
+-- the assistant prompt. Limited to 2000 characters, like in the API.
+ALTER TABLE public.chats
+    ADD COLUMN custom_instruction text DEFAULT NULL
+    CONSTRAINT chats_custom_instruction_length CHECK (char_length(custom_instruction) <= 2000);
+
+COMMIT;
@@ -0,0 +1,11 @@
+-- Deploy erato:0062_add_response_language_to_chats to pg
+
+BEGIN;
+
+-- ISO 639-1 code of the language the assistant should respond in, unless the generation request
+-- asks for another language or the latest user message was detected to be in another language.
+ALTER TABLE public.chats
+    ADD COLUMN response_language text DEFAULT NULL
+    CONSTRAINT chats_response_language_format CHECK (response_language ~ '^[a-z]{2,3}$');
+
+COMMIT;
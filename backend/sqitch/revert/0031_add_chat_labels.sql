@@ -0,0 +1,8 @@
+-- Revert erato:0031_add_chat_labels from pg
+
+BEGIN;
+
+DROP TABLE public.chat_labels;
+DROP TABLE public.labels;
+
+COMMIT;
@@ -0,0 +1,11 @@
+-- Revert erato:0032_add_welcome_messages from pg
+
+BEGIN;
+
+ALTER TABLE public.messages
+    DROP COLUMN is_welcome_message;
+
+ALTER TABLE public.assistants
+    DROP COLUMN welcome_message;
+
+COMMIT;
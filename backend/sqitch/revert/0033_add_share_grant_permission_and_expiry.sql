@@ -0,0 +1,10 @@
+-- Revert erato:0033_add_share_grant_permission_and_expiry from pg
+
+BEGIN;
+
+DROP INDEX public.idx_share_grants_expires_at;
+
+ALTER TABLE public.share_grants DROP COLUMN expires_at;
+ALTER TABLE public.share_grants DROP COLUMN permission;
+
+COMMIT;
@@ -0,0 +1,7 @@
+-- Revert erato:0034_add_messages_generation_created_at_index from pg
+
+BEGIN;
+
+DROP INDEX public.idx_messages_generation_created_at;
+
+COMMIT;
@@ -0,0 +1,8 @@
+-- Revert erato:0035_add_chat_read_states from pg
+
+BEGIN;
+
+DROP INDEX public.idx_messages_chat_id_created_at_active_thread;
+DROP TABLE public.chat_read_states;
+
+COMMIT;
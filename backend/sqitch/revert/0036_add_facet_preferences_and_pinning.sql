@@ -0,0 +1,11 @@
+-- Revert erato:0036_add_facet_preferences_and_pinning from pg
+
+BEGIN;
+
+ALTER TABLE public.assistants
+    DROP COLUMN pinned_facet_ids;
+
+ALTER TABLE public.user_preferences
+    DROP COLUMN default_selected_facets;
+
+COMMIT;
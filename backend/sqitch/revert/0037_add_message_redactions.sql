@@ -0,0 +1,7 @@
+-- Revert erato:0037_add_message_redactions from pg
+
+BEGIN;
+
+DROP TABLE public.message_redactions;
+
+COMMIT;
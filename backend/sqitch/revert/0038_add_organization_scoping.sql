@@ -0,0 +1,11 @@
+-- Revert erato:0038_add_organization_scoping from pg
+
+BEGIN;
+
+ALTER TABLE public.share_grants DROP COLUMN organization_id;
+ALTER TABLE public.file_uploads DROP COLUMN organization_id;
+ALTER TABLE public.assistants DROP COLUMN organization_id;
+ALTER TABLE public.chats DROP COLUMN organization_id;
+ALTER TABLE public.users DROP COLUMN organization_id;
+
+COMMIT;
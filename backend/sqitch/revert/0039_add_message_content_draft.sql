@@ -0,0 +1,8 @@
+-- Revert erato:0039_add_message_content_draft from pg
+
+BEGIN;
+
+DROP INDEX public.idx_messages_content_draft_updated_at;
+ALTER TABLE public.messages DROP COLUMN content_draft;
+
+COMMIT;
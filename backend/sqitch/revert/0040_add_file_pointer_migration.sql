@@ -0,0 +1,8 @@
+-- Revert erato:0040_add_file_pointer_migration from pg
+
+BEGIN;
+
+DROP TABLE public.message_file_pointer_migrations;
+DROP TABLE public.maintenance_task_progress;
+
+COMMIT;
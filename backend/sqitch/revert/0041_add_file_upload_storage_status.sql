@@ -0,0 +1,8 @@
+-- Revert erato:0041_add_file_upload_storage_status from pg
+
+BEGIN;
+
+DROP INDEX public.idx_file_uploads_storage_status_missing;
+ALTER TABLE public.file_uploads DROP COLUMN storage_status;
+
+COMMIT;
@@ -0,0 +1,7 @@
+-- Revert erato:0042_add_scheduled_messages from pg
+
+BEGIN;
+
+DROP TABLE public.scheduled_messages;
+
+COMMIT;
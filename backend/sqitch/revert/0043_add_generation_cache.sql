@@ -0,0 +1,7 @@
+-- Revert erato:0043_add_generation_cache from pg
+
+BEGIN;
+
+DROP TABLE public.generation_cache_entries;
+
+COMMIT;
@@ -0,0 +1,13 @@
+-- Revert erato:0044_add_file_deletions from pg
+
+BEGIN;
+
+DROP TABLE public.file_deletions;
+
+-- The objects of deleted files are gone, which is closest to 'missing'.
+UPDATE public.file_uploads SET storage_status = 'missing' WHERE storage_status = 'deleted';
+ALTER TABLE public.file_uploads DROP CONSTRAINT file_uploads_storage_status_check;
+ALTER TABLE public.file_uploads ADD CONSTRAINT file_uploads_storage_status_check
+    CHECK (storage_status IN ('ok', 'missing'));
+
+COMMIT;
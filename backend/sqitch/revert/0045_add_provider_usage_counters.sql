@@ -0,0 +1,7 @@
+-- Revert erato:0045_add_provider_usage_counters from pg
+
+BEGIN;
+
+DROP TABLE public.provider_usage_counters;
+
+COMMIT;
@@ -0,0 +1,7 @@
+-- Revert erato:0046_add_notifications from pg
+
+BEGIN;
+
+DROP TABLE public.notifications;
+
+COMMIT;
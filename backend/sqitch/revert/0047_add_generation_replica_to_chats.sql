@@ -0,0 +1,8 @@
+-- Revert erato:0047_add_generation_replica_to_chats from pg
+
+BEGIN;
+
+ALTER TABLE public.chats DROP COLUMN generation_message_id;
+ALTER TABLE public.chats DROP COLUMN generation_replica_id;
+
+COMMIT;
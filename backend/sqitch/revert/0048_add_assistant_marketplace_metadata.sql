@@ -0,0 +1,12 @@
+-- Revert erato:0048_add_assistant_marketplace_metadata from pg
+
+BEGIN;
+
+DROP INDEX public.idx_assistants_visibility_organization;
+ALTER TABLE public.assistants DROP COLUMN featured;
+ALTER TABLE public.assistants DROP CONSTRAINT assistants_visibility_check;
+ALTER TABLE public.assistants DROP COLUMN visibility;
+ALTER TABLE public.assistants DROP COLUMN tags;
+ALTER TABLE public.assistants DROP COLUMN category;
+
+COMMIT;
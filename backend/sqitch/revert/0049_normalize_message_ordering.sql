@@ -0,0 +1,24 @@
+-- Revert erato:0049_normalize_message_ordering from pg
+
+BEGIN;
+
+CREATE OR REPLACE VIEW chats_latest_message AS
+SELECT
+    chat_id,
+    id AS latest_message_id,
+    created_at AS latest_message_at
+FROM (
+    SELECT
+        m.chat_id,
+        m.id,
+        m.created_at,
+        ROW_NUMBER() OVER (PARTITION BY m.chat_id ORDER BY m.created_at DESC) AS rn
+    FROM
+        messages m
+) ranked_messages
+WHERE
+    rn = 1;
+
+DROP INDEX public.idx_messages_chat_id_created_at_id;
+
+COMMIT;
@@ -0,0 +1,7 @@
+-- Revert erato:0050_add_assistant_snapshot_to_chats from pg
+
+BEGIN;
+
+ALTER TABLE public.chats DROP COLUMN assistant_snapshot;
+
+COMMIT;
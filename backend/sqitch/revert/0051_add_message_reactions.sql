@@ -0,0 +1,7 @@
+-- Revert erato:0051_add_message_reactions from pg
+
+BEGIN;
+
+DROP TABLE public.message_reactions;
+
+COMMIT;
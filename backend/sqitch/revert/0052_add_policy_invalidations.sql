@@ -0,0 +1,8 @@
+-- Revert erato:0052_add_policy_invalidations from pg
+
+BEGIN;
+
+DROP TABLE public.policy_invalidations;
+DROP FUNCTION public.notify_policy_invalidation();
+
+COMMIT;
@@ -0,0 +1,7 @@
+-- Revert erato:0053_add_edit_of_message_id_to_messages from pg
+
+BEGIN;
+
+ALTER TABLE public.messages DROP COLUMN edit_of_message_id;
+
+COMMIT;
@@ -0,0 +1,7 @@
+-- Revert erato:0054_add_provisional_to_chats from pg
+
+BEGIN;
+
+ALTER TABLE public.chats DROP COLUMN provisional;
+
+COMMIT;
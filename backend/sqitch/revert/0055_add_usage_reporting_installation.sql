@@ -0,0 +1,7 @@
+-- Revert erato:0055_add_usage_reporting_installation from pg
+
+BEGIN;
+
+DROP TABLE public.usage_reporting_installation;
+
+COMMIT;
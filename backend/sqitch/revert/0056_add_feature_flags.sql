@@ -0,0 +1,7 @@
+-- Revert erato:0056_add_feature_flags from pg
+
+BEGIN;
+
+DROP TABLE public.feature_flags;
+
+COMMIT;
@@ -0,0 +1,7 @@
+-- Revert erato:0057_add_langfuse_dead_letters from pg
+
+BEGIN;
+
+DROP TABLE public.langfuse_dead_letters;
+
+COMMIT;
@@ -0,0 +1,7 @@
+-- Revert erato:0058_add_archived_generation_inputs from pg
+
+BEGIN;
+
+DROP TABLE public.archived_generation_inputs;
+
+COMMIT;
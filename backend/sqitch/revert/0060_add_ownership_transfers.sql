@@ -0,0 +1,7 @@
+-- Revert erato:0060_add_ownership_transfers from pg
+
+BEGIN;
+
+DROP TABLE public.ownership_transfers;
+
+COMMIT;
@@ -0,0 +1,7 @@
+-- Revert erato:0061_add_custom_instruction_to_chats from pg
+
+BEGIN;
+
+ALTER TABLE public.chats DROP COLUMN custom_instruction;
+
+COMMIT;
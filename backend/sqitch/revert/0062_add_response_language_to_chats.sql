@@ -0,0 +1,7 @@
+-- Revert erato:0062_add_response_language_to_chats from pg
+
+BEGIN;
+
+ALTER TABLE public.chats DROP COLUMN response_language;
+
+COMMIT;
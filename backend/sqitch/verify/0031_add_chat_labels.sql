@@ -0,0 +1,22 @@
+-- Verify erato:0031_add_chat_labels on pg
+
+BEGIN;
+
+SELECT
+    id,
+    owner_user_id,
+    name,
+    color,
+    created_at,
+    updated_at
+FROM public.labels
+WHERE FALSE;
+
+SELECT
+    chat_id,
+    label_id,
+    created_at
+FROM public.chat_labels
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,13 @@
+-- Verify erato:0032_add_welcome_messages on pg
+
+BEGIN;
+
+SELECT welcome_message
+FROM public.assistants
+WHERE FALSE;
+
+SELECT is_welcome_message
+FROM public.messages
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,10 @@
+-- Verify erato:0033_add_share_grant_permission_and_expiry on pg
+
+BEGIN;
+
+SELECT permission,
+       expires_at
+FROM public.share_grants
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,10 @@
+-- Verify erato:0034_add_messages_generation_created_at_index on pg
+
+BEGIN;
+
+SELECT 1/COUNT(*)
+FROM pg_indexes
+WHERE schemaname = 'public'
+  AND indexname = 'idx_messages_generation_created_at';
+
+ROLLBACK;
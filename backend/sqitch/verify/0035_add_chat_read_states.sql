@@ -0,0 +1,14 @@
+-- Verify erato:0035_add_chat_read_states on pg
+
+BEGIN;
+
+SELECT
+    user_id,
+    chat_id,
+    last_read_message_id,
+    created_at,
+    updated_at
+FROM public.chat_read_states
+WHERE FALSE;
+
+ROLLBACK;
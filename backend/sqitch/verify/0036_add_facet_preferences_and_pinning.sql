@@ -0,0 +1,13 @@
+-- Verify erato:0036_add_facet_preferences_and_pinning on pg
+
+BEGIN;
+
+SELECT default_selected_facets
+FROM public.user_preferences
+WHERE FALSE;
+
+SELECT pinned_facet_ids
+FROM public.assistants
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,16 @@
+-- Verify erato:0037_add_message_redactions on pg
+
+BEGIN;
+
+SELECT
+    id,
+    message_id,
+    file_upload_id,
+    actor_user_id,
+    spans,
+    replacement,
+    created_at
+FROM public.message_redactions
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,11 @@
+-- Verify erato:0038_add_organization_scoping on pg
+
+BEGIN;
+
+SELECT organization_id FROM public.users WHERE FALSE;
+SELECT organization_id FROM public.chats WHERE FALSE;
+SELECT organization_id FROM public.assistants WHERE FALSE;
+SELECT organization_id FROM public.file_uploads WHERE FALSE;
+SELECT organization_id FROM public.share_grants WHERE FALSE;
+
+ROLLBACK;
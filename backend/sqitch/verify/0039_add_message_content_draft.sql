@@ -0,0 +1,7 @@
+-- Verify erato:0039_add_message_content_draft on pg
+
+BEGIN;
+
+SELECT content_draft FROM public.messages WHERE FALSE;
+
+ROLLBACK;
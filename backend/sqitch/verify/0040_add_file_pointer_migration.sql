@@ -0,0 +1,10 @@
+-- Verify erato:0040_add_file_pointer_migration on pg
+
+BEGIN;
+
+SELECT task_name, cursor_created_at, cursor_id, processed_messages, migrated_messages, bytes_saved
+FROM public.maintenance_task_progress WHERE FALSE;
+SELECT message_id, replaced_parts, bytes_before, bytes_after
+FROM public.message_file_pointer_migrations WHERE FALSE;
+
+ROLLBACK;
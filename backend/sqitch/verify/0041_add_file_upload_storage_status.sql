@@ -0,0 +1,7 @@
+-- Verify erato:0041_add_file_upload_storage_status on pg
+
+BEGIN;
+
+SELECT storage_status FROM public.file_uploads WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,21 @@
+-- Verify erato:0042_add_scheduled_messages on pg
+
+BEGIN;
+
+SELECT
+    id,
+    owner_user_id,
+    request,
+    id_token_claims,
+    run_at,
+    status,
+    attempts,
+    last_error,
+    chat_id,
+    assistant_message_id,
+    created_at,
+    updated_at
+FROM public.scheduled_messages
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,16 @@
+-- Verify erato:0043_add_generation_cache on pg
+
+BEGIN;
+
+SELECT
+    cache_key,
+    chat_provider_id,
+    content,
+    usage,
+    hit_count,
+    created_at,
+    last_hit_at
+FROM public.generation_cache_entries
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,16 @@
+-- Verify erato:0044_add_file_deletions on pg
+
+BEGIN;
+
+SELECT
+    id,
+    file_upload_id,
+    filename,
+    actor_user_id,
+    forced,
+    referencing_resources,
+    created_at
+FROM public.file_deletions
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,16 @@
+-- Verify erato:0045_add_provider_usage_counters on pg
+
+BEGIN;
+
+SELECT
+    user_id,
+    chat_provider_id,
+    window_start,
+    messages,
+    tokens,
+    created_at,
+    updated_at
+FROM public.provider_usage_counters
+WHERE FALSE;
+
+ROLLBACK;
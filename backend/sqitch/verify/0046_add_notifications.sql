@@ -0,0 +1,15 @@
+-- Verify erato:0046_add_notifications on pg
+
+BEGIN;
+
+SELECT
+    id,
+    user_id,
+    kind,
+    payload,
+    created_at,
+    read_at
+FROM public.notifications
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,11 @@
+-- Verify erato:0047_add_generation_replica_to_chats on pg
+
+BEGIN;
+
+SELECT id,
+       generation_replica_id,
+       generation_message_id
+FROM public.chats
+WHERE FALSE;
+
+ROLLBACK;
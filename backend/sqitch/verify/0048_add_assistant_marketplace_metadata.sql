@@ -0,0 +1,13 @@
+-- Verify erato:0048_add_assistant_marketplace_metadata on pg
+
+BEGIN;
+
+SELECT id,
+       category,
+       tags,
+       visibility,
+       featured
+FROM public.assistants
+WHERE FALSE;
+
+ROLLBACK;
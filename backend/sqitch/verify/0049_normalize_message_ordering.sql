@@ -0,0 +1,14 @@
+-- Verify erato:0049_normalize_message_ordering on pg
+
+BEGIN;
+
+SELECT 1/COUNT(*)
+FROM pg_indexes
+WHERE schemaname = 'public'
+  AND indexname = 'idx_messages_chat_id_created_at_id';
+
+SELECT chat_id, latest_message_id, latest_message_at
+FROM chats_latest_message
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,10 @@
+-- Verify erato:0050_add_assistant_snapshot_to_chats on pg
+
+BEGIN;
+
+SELECT id,
+       assistant_snapshot
+FROM public.chats
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,14 @@
+-- Verify erato:0051_add_message_reactions on pg
+
+BEGIN;
+
+SELECT
+    id,
+    message_id,
+    user_id,
+    emoji,
+    created_at
+FROM public.message_reactions
+WHERE FALSE;
+
+ROLLBACK;
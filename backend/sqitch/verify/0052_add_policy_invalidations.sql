@@ -0,0 +1,14 @@
+-- Verify erato:0052_add_policy_invalidations on pg
+
+BEGIN;
+
+SELECT
+    id,
+    reason,
+    created_at
+FROM public.policy_invalidations
+WHERE FALSE;
+
+SELECT pg_get_functiondef('public.notify_policy_invalidation()'::regprocedure);
+
+ROLLBACK;
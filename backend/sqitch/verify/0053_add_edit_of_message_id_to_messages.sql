@@ -0,0 +1,10 @@
+-- Verify erato:0053_add_edit_of_message_id_to_messages on pg
+
+BEGIN;
+
+SELECT id,
+       edit_of_message_id
+FROM public.messages
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,10 @@
+-- Verify erato:0054_add_provisional_to_chats on pg
+
+BEGIN;
+
+SELECT id,
+       provisional
+FROM public.chats
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,12 @@
+-- Verify erato:0055_add_usage_reporting_installation on pg
+
+BEGIN;
+
+SELECT id,
+       singleton,
+       created_at,
+       last_reported_at
+FROM public.usage_reporting_installation
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,15 @@
+-- Verify erato:0056_add_feature_flags on pg
+
+BEGIN;
+
+SELECT key,
+       description,
+       default_on,
+       group_overrides,
+       percentage_rollout,
+       created_at,
+       updated_at
+FROM public.feature_flags
+WHERE FALSE;
+
+ROLLBACK;
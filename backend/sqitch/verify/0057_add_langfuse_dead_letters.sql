@@ -0,0 +1,14 @@
+-- Verify erato:0057_add_langfuse_dead_letters on pg
+
+BEGIN;
+
+SELECT id,
+       batch,
+       attempts,
+       last_error,
+       created_at,
+       updated_at
+FROM public.langfuse_dead_letters
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,11 @@
+-- Verify erato:0058_add_archived_generation_inputs on pg
+
+BEGIN;
+
+SELECT message_id,
+       compressed_input,
+       created_at
+FROM public.archived_generation_inputs
+WHERE FALSE;
+
+ROLLBACK;
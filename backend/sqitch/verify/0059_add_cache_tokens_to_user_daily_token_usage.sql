@@ -0,0 +1,10 @@
+-- Verify erato:0059_add_cache_tokens_to_user_daily_token_usage on pg
+
+BEGIN;
+
+SELECT total_cache_read_tokens,
+       total_cache_write_tokens
+FROM user_daily_token_usage
+WHERE FALSE;
+
+ROLLBACK;
@@ -0,0 +1,18 @@
+-- Verify erato:0060_add_ownership_transfers on pg
+
+BEGIN;
+
+SELECT
+    id,
+    resource_type,
+    resource_id,
+    previous_owner_user_id,
+    new_owner_user_id,
+    actor_user_id,
+    keep_previous_owner_access,
+    rewrite_attribution,
+    created_at
+FROM public.ownership_transfers
+WHERE FALSE;
+
+ROLLBACK;
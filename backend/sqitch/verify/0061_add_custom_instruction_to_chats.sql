@@ -0,0 +1,10 @@
+-- Verify erato:0061_add_custom_instruction_to_chats on pg
+
+BEGIN;
+
+SELECT id,
+       custom_instruction
+FROM public.chats
+WHERE FALSE;
+
+ROLLBACK;
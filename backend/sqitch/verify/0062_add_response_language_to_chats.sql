@@ -0,0 +1,10 @@
+-- Verify erato:0062_add_response_language_to_chats on pg
+
+BEGIN;
+
+SELECT id,
+       response_language
+FROM public.chats
+WHERE FALSE;
+
+ROLLBACK;
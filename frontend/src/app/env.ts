@@ -15,6 +15,7 @@ export type Env = {
   themeLogoDarkPath: string | null;
   themeAssistantAvatarPath: string | null;
   disableUpload: boolean;
+  localUploadEnabled: boolean;
   disableChatInputAutofocus: boolean;
   chatInputEmptyStateLayout: "bottom" | "centered";
   disableLogout: boolean;
@@ -32,6 +33,7 @@ export type Env = {
   sharepointEnabled: boolean;
   sharepointShowDisclaimer: boolean;
   chatSharingEnabled: boolean;
+  ephemeralChatsEnabled: boolean;
   messageFeedbackEnabled: boolean;
   messageFeedbackCommentsEnabled: boolean;
   messageFeedbackEditTimeLimitSeconds: number | null;
@@ -69,6 +71,7 @@ declare global {
     THEME_LOGO_DARK_PATH?: string;
     THEME_ASSISTANT_AVATAR_PATH?: string;
     DISABLE_UPLOAD?: boolean;
+    LOCAL_UPLOAD_ENABLED?: boolean;
     DISABLE_CHAT_INPUT_AUTOFOCUS?: boolean;
     CHAT_INPUT_EMPTY_STATE_LAYOUT?: string;
     DISABLE_LOGOUT?: boolean;
@@ -86,6 +89,7 @@ declare global {
     SHAREPOINT_ENABLED?: boolean;
     SHAREPOINT_SHOW_DISCLAIMER?: boolean;
     CHAT_SHARING_ENABLED?: boolean;
+    EPHEMERAL_CHATS_ENABLED?: boolean;
     MESSAGE_FEEDBACK_ENABLED?: boolean;
     MESSAGE_FEEDBACK_COMMENTS_ENABLED?: boolean;
     MESSAGE_FEEDBACK_EDIT_TIME_LIMIT_SECONDS?: number;
@@ -186,6 +190,10 @@ export const env = (): Env => {
     import.meta.env.VITE_DISABLE_UPLOAD === "true"
       ? true
       : (window.DISABLE_UPLOAD ?? false);
+  const localUploadEnabled =
+    import.meta.env.VITE_LOCAL_UPLOAD_ENABLED === "false"
+      ? false
+      : (window.LOCAL_UPLOAD_ENABLED ?? true);
   const disableChatInputAutofocus =
     import.meta.env.VITE_DISABLE_CHAT_INPUT_AUTOFOCUS === "true"
       ? true
@@ -255,6 +263,10 @@ export const env = (): Env => {
     import.meta.env.VITE_CHAT_SHARING_ENABLED === "true"
       ? true
       : (window.CHAT_SHARING_ENABLED ?? false);
+  const ephemeralChatsEnabled =
+    import.meta.env.VITE_EPHEMERAL_CHATS_ENABLED === "true"
+      ? true
+      : (window.EPHEMERAL_CHATS_ENABLED ?? false);
   const messageFeedbackEnabled =
     import.meta.env.VITE_MESSAGE_FEEDBACK_ENABLED === "true"
       ? true
@@ -362,6 +374,7 @@ export const env = (): Env => {
     themeLogoDarkPath,
     themeAssistantAvatarPath,
     disableUpload,
+    localUploadEnabled,
     disableChatInputAutofocus,
     chatInputEmptyStateLayout,
     disableLogout,
@@ -379,6 +392,7 @@ export const env = (): Env => {
     sharepointEnabled,
     sharepointShowDisclaimer,
     chatSharingEnabled,
+    ephemeralChatsEnabled,
     messageFeedbackEnabled,
     messageFeedbackCommentsEnabled,
     messageFeedbackEditTimeLimitSeconds,
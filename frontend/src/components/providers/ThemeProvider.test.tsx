@@ -42,6 +42,7 @@ const createMockEnv = (overrides: Partial<Env> = {}): Env => ({
   sharepointEnabled: false,
   sharepointShowDisclaimer: false,
   chatSharingEnabled: false,
+  ephemeralChatsEnabled: false,
   messageFeedbackEnabled: false,
   messageFeedbackCommentsEnabled: false,
   showVerboseAssistantErrors: false,
@@ -12,6 +12,8 @@ import type { CloudProvider } from "@/providers/FeatureConfigProvider";
 export interface FileSourceSelectorProps {
   /** List of available cloud providers */
   availableProviders: CloudProvider[];
+  /** Whether files can be uploaded from the computer */
+  localUploadEnabled?: boolean;
   /** Callback when "Upload from Computer" is selected */
   onSelectDisk: () => void;
   /** Callback when a cloud provider is selected */
@@ -30,7 +32,7 @@ export interface FileSourceSelectorProps {
  * FileSourceSelector Component
  *
  * Displays a dropdown menu allowing users to choose between uploading from:
- * - Computer (local disk)
+ * - Computer (local disk, unless local uploads are disabled)
  * - OneDrive/Sharepoint (when available)
  * - Google Drive (future, when available)
  *
@@ -40,6 +42,7 @@ export interface FileSourceSelectorProps {
 export const FileSourceSelector = memo<FileSourceSelectorProps>(
   ({
     availableProviders,
+    localUploadEnabled = true,
     onSelectDisk,
     onSelectCloud,
     disabled = false,
@@ -51,9 +54,9 @@ export const FileSourceSelector = memo<FileSourceSelectorProps>(
     }
 
     // Build menu items based on available providers
-    const menuItems: DropdownMenuItem[] = [
-      // Always show "Upload from Computer" option
-      {
+    const menuItems: DropdownMenuItem[] = [];
+    if (localUploadEnabled) {
+      menuItems.push({
         label: t({
           id: "fileSourceSelector.uploadFromComputer",
           message: "Upload from Computer",
@@ -61,8 +64,8 @@ export const FileSourceSelector = memo<FileSourceSelectorProps>(
         icon: <Computer className="size-4" />,
         onClick: onSelectDisk,
         disabled,
-      },
-    ];
+      });
+    }
 
     // Add cloud provider options
     if (availableProviders.includes("sharepoint")) {
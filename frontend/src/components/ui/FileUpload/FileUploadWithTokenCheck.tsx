@@ -8,6 +8,7 @@ import { t } from "@lingui/core/macro";
 
 import { componentRegistry } from "@/config/componentRegistry";
 import { useChatFileSources } from "@/hooks/files/useChatFileSources";
+import { useUploadFeature } from "@/providers/FeatureConfigProvider";
 
 import { CloudFilePickerModal } from "./CloudFilePickerModal";
 import { FileSourceSelector } from "./FileSourceSelector";
@@ -77,6 +78,7 @@ export function FileUploadWithTokenCheck({
   onProcessingChange,
 }: FileUploadWithTokenCheckProps) {
   const hasCustomSelector = componentRegistry.ChatFileSourceSelector != null;
+  const { localEnabled: localUploadEnabled } = useUploadFeature();
 
   const {
     availableProviders,
@@ -131,6 +133,7 @@ export function FileUploadWithTokenCheck({
           {componentRegistry.ChatFileSourceSelector ? (
             <componentRegistry.ChatFileSourceSelector
               availableProviders={availableProviders}
+              localUploadEnabled={localUploadEnabled}
               onSelectDisk={onSelectDisk}
               onSelectCloud={onSelectCloud}
               onSelectFiles={onSelectFiles}
@@ -141,6 +144,7 @@ export function FileUploadWithTokenCheck({
           ) : (
             <FileSourceSelector
               availableProviders={availableProviders}
+              localUploadEnabled={localUploadEnabled}
               onSelectDisk={onSelectDisk}
               onSelectCloud={onSelectCloud}
               onSelectFiles={onSelectFiles}
@@ -49,6 +49,7 @@ describe("themeConfig", () => {
     sharepointEnabled: false,
     sharepointShowDisclaimer: false,
     chatSharingEnabled: false,
+    ephemeralChatsEnabled: false,
     messageFeedbackEnabled: false,
     messageFeedbackCommentsEnabled: false,
     showVerboseAssistantErrors: false,
@@ -24,7 +24,7 @@ vi.mock("@/lib/generated/v1betaApi/v1betaApiComponents", () => ({
 
 // Mock FeatureConfigProvider
 vi.mock("@/providers/FeatureConfigProvider", () => ({
-  useUploadFeature: vi.fn(() => ({ enabled: true })),
+  useUploadFeature: vi.fn(() => ({ enabled: true, localEnabled: true })),
 }));
 
 // Mock FileCapabilitiesProvider
@@ -147,6 +147,7 @@ export function useFileDropzone({
   // Check if upload feature is enabled
   const {
     enabled: uploadEnabled,
+    localEnabled: localUploadEnabled,
     maxSizeBytes,
     maxSizeFormatted,
   } = useUploadFeature();
@@ -393,7 +394,7 @@ export function useFileDropzone({
         ? FileTypeUtil.getAcceptObject(acceptedFileTypes)
         : undefined,
     multiple,
-    disabled: disabled || isUploading || !uploadEnabled,
+    disabled: disabled || isUploading || !uploadEnabled || !localUploadEnabled,
     maxSize: getMaxFileSize(),
   });
 
@@ -54,6 +54,7 @@ beforeEach(() => {
     promptOptimizerEnabled: false,
     mcpServersTabEnabled: false,
     chatSharingEnabled: false,
+    ephemeralChatsEnabled: false,
     sharepointEnabled: false,
     sharepointShowDisclaimer: false,
     messageFeedbackEnabled: false,
@@ -16,6 +16,8 @@ import type { ReactNode } from "react";
 interface UploadFeatureConfig {
   /** Whether file upload functionality is enabled */
   enabled: boolean;
+  /** Whether files can be uploaded from the device, not only linked */
+  localEnabled: boolean;
   /** Maximum upload size in bytes */
   maxSizeBytes: number;
   /** Human-readable max upload size (e.g., "10 MB", "2 GB") */
@@ -219,6 +221,7 @@ const FeatureConfigContext = createContext<FeatureConfig | null>(null);
 export const defaultStaticFeatureConfig: FeatureConfig = {
   upload: {
     enabled: true,
+    localEnabled: true,
     maxSizeBytes: 20 * 1024 * 1024,
     maxSizeFormatted: "20 MB",
   },
@@ -315,6 +318,7 @@ function createFeatureConfig(
   return {
     upload: {
       enabled: !environment.disableUpload,
+      localEnabled: environment.localUploadEnabled,
       maxSizeBytes: environment.maxUploadSizeBytes,
       maxSizeFormatted: formatBytes(environment.maxUploadSizeBytes),
     },
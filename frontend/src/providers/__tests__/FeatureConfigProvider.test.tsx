@@ -57,6 +57,7 @@ describe("FeatureConfigProvider", () => {
       themeLogoDarkPath: null,
       themeAssistantAvatarPath: null,
       disableUpload: false,
+      localUploadEnabled: true,
       disableChatInputAutofocus: false,
       chatInputEmptyStateLayout: "bottom",
       disableLogout: false,
@@ -92,6 +93,7 @@ describe("FeatureConfigProvider", () => {
       sidebarLogoDarkPath: null,
       sidebarChatHistoryShowMetadata: true,
       chatSharingEnabled: false,
+      ephemeralChatsEnabled: false,
       msalClientId: null,
       msalAuthority: null,
       maskReasoningTraceText: false,
@@ -126,6 +128,7 @@ describe("FeatureConfigProvider", () => {
       expect(result.current).toEqual({
         upload: {
           enabled: true,
+          localEnabled: true,
           maxSizeBytes: 20971520,
           maxSizeFormatted: "20 MB",
         },
@@ -414,6 +417,7 @@ describe("FeatureConfigProvider", () => {
         sidebarLogoDarkPath: null,
         sidebarChatHistoryShowMetadata: true,
         chatSharingEnabled: false,
+        ephemeralChatsEnabled: false,
         msalClientId: null,
         msalAuthority: null,
         maskReasoningTraceText: false,
@@ -474,6 +478,7 @@ describe("FeatureConfigProvider", () => {
         sidebarLogoDarkPath: null,
         sidebarChatHistoryShowMetadata: true,
         chatSharingEnabled: false,
+        ephemeralChatsEnabled: false,
         msalClientId: null,
         msalAuthority: null,
         maskReasoningTraceText: false,
@@ -513,6 +518,7 @@ describe("FeatureConfigProvider", () => {
 
       expect(result.current).toEqual({
         enabled: true,
+        localEnabled: true,
         maxSizeBytes: 20971520,
         maxSizeFormatted: "20 MB",
       });
@@ -707,6 +713,7 @@ describe("FeatureConfigProvider", () => {
         sharepointEnabled: false,
         sharepointShowDisclaimer: false,
         chatSharingEnabled: false,
+        ephemeralChatsEnabled: false,
         messageFeedbackEnabled: false,
         messageFeedbackCommentsEnabled: false,
         userPreferencesEnabled: true,
@@ -923,6 +930,7 @@ describe("FeatureConfigProvider", () => {
         sidebarLogoDarkPath: null,
         sidebarChatHistoryShowMetadata: true,
         chatSharingEnabled: false,
+        ephemeralChatsEnabled: false,
         msalClientId: null,
         msalAuthority: null,
       });
@@ -1243,6 +1251,7 @@ describe("FeatureConfigProvider", () => {
         sidebarLogoDarkPath: null,
         sidebarChatHistoryShowMetadata: true,
         chatSharingEnabled: false,
+        ephemeralChatsEnabled: false,
         msalClientId: null,
         msalAuthority: null,
         maskReasoningTraceText: true,